@@ -364,7 +364,7 @@ impl Controller {
                 PipelineState::Terminated => {
                     circuit
                         .kill()
-                        .map_err(|_| AnyError::msg("dbsp thead panicked"))?;
+                        .map_err(|error| AnyError::msg(format!("failed to kill dbsp: {error}")))?;
                     return Ok(());
                 }
             }
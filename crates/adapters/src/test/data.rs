@@ -1,4 +1,5 @@
 use bincode::{Decode, Encode};
+use dbsp::NumEntries;
 use proptest::{collection, prelude::*};
 use proptest_derive::Arbitrary;
 use serde::{Deserialize, Serialize};
@@ -26,6 +27,18 @@ pub struct TestStruct {
     pub s: String,
 }
 
+impl NumEntries for TestStruct {
+    const CONST_NUM_ENTRIES: Option<usize> = Some(1);
+
+    fn num_entries_shallow(&self) -> usize {
+        1
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        1
+    }
+}
+
 /// Generate a batch of records no larger that `size`.
 ///
 /// Makes sure all elements in the vector are unique and ordered.
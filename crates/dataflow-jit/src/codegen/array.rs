@@ -0,0 +1,149 @@
+//! The buffers backing array columns
+//!
+//! Array columns are stored as a single pointer to a heap allocated buffer
+//! containing the array's length (as a `usize`) followed by each of its
+//! elements, every element being a row of the array's element layout. Empty
+//! arrays are represented by a null pointer so that they never allocate
+
+use crate::codegen::NativeLayout;
+use std::{
+    alloc::{self, Layout},
+    mem::{align_of, size_of},
+    ptr,
+};
+
+/// The layout of the elements within an array column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ArrayLayout {
+    element_size: usize,
+    element_align: usize,
+}
+
+impl ArrayLayout {
+    pub(crate) const fn new(element_size: usize, element_align: usize) -> Self {
+        debug_assert!(element_align.is_power_of_two());
+
+        Self {
+            element_size,
+            element_align,
+        }
+    }
+
+    /// Creates the array layout for elements of the given layout
+    pub(crate) fn of(element_layout: &NativeLayout) -> Self {
+        Self::new(
+            element_layout.size() as usize,
+            element_layout.align() as usize,
+        )
+    }
+
+    pub(crate) const fn element_size(self) -> usize {
+        self.element_size
+    }
+
+    /// Returns the offset of the first element from the start of an array's
+    /// buffer
+    const fn header_size(self) -> usize {
+        // Both values are powers of two so the larger is always a multiple of the
+        // smaller
+        if self.element_align > size_of::<usize>() {
+            self.element_align
+        } else {
+            size_of::<usize>()
+        }
+    }
+
+    fn buffer_layout(self, length: usize) -> Layout {
+        let size = self
+            .element_size
+            .checked_mul(length)
+            .and_then(|elements| elements.checked_add(self.header_size()))
+            .expect("array buffer size overflowed");
+        let align = self.element_align.max(align_of::<usize>());
+
+        Layout::from_size_align(size, align).expect("invalid array buffer layout")
+    }
+
+    /// Allocates the buffer for an array of `length` elements and writes its
+    /// length, returning a null pointer if `length` is zero
+    ///
+    /// The returned array's elements are uninitialized
+    pub(crate) fn alloc(self, length: usize) -> *mut u8 {
+        if length == 0 {
+            return ptr::null_mut();
+        }
+
+        let layout = self.buffer_layout(length);
+        let array = unsafe { alloc::alloc(layout) };
+        if array.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+
+        unsafe { array.cast::<usize>().write(length) };
+        array
+    }
+
+    /// Deallocates the given array's buffer without dropping its elements
+    ///
+    /// # Safety
+    ///
+    /// `array` must be null or have been allocated by [`ArrayLayout::alloc()`]
+    /// with the current layout
+    pub(crate) unsafe fn dealloc(self, array: *mut u8) {
+        if !array.is_null() {
+            let layout = self.buffer_layout(unsafe { Self::len(array) });
+            unsafe { alloc::dealloc(array, layout) }
+        }
+    }
+
+    /// Returns the number of elements within the given array
+    ///
+    /// # Safety
+    ///
+    /// `array` must be null or have been allocated by [`ArrayLayout::alloc()`]
+    pub(crate) unsafe fn len(array: *const u8) -> usize {
+        if array.is_null() {
+            0
+        } else {
+            unsafe { array.cast::<usize>().read() }
+        }
+    }
+
+    /// Returns a pointer to the given array's first element
+    ///
+    /// # Safety
+    ///
+    /// `array` must be non-null and have been allocated by
+    /// [`ArrayLayout::alloc()`] with the current layout
+    pub(crate) unsafe fn elements(self, array: *const u8) -> *mut u8 {
+        debug_assert!(!array.is_null());
+        unsafe { array.add(self.header_size()) as *mut u8 }
+    }
+
+    /// Returns a pointer to the element at `index` within the given array
+    ///
+    /// # Safety
+    ///
+    /// `array` must be non-null and have been allocated by
+    /// [`ArrayLayout::alloc()`] with the current layout and `index` must be
+    /// less than the array's length
+    pub(crate) unsafe fn element(self, array: *const u8, index: usize) -> *mut u8 {
+        debug_assert!(index < unsafe { Self::len(array) });
+        unsafe { self.elements(array).add(index * self.element_size) }
+    }
+
+    /// Returns the number of bytes allocated for the given array, zero for
+    /// empty arrays
+    ///
+    /// # Safety
+    ///
+    /// `array` must be null or have been allocated by [`ArrayLayout::alloc()`]
+    /// with the current layout
+    pub(crate) unsafe fn allocated_bytes(self, array: *const u8) -> usize {
+        if array.is_null() {
+            0
+        } else {
+            self.buffer_layout(unsafe { Self::len(array) }).size()
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Faults that tests can seed into generated code in order to check that
+//! [`verify()`](crate::verify) catches miscompilations
+
+#[cfg(test)]
+use std::cell::Cell;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CodegenFault {
+    /// Integer additions are compiled as subtractions
+    IntAddAsSub,
+}
+
+#[cfg(test)]
+thread_local! {
+    static SEEDED_FAULT: Cell<Option<CodegenFault>> = Cell::new(None);
+}
+
+impl CodegenFault {
+    /// Returns `true` if the current fault should be emitted by codegen,
+    /// always returns `false` outside of tests
+    pub(crate) fn is_seeded(self) -> bool {
+        #[cfg(test)]
+        {
+            SEEDED_FAULT.with(|seeded| seeded.get() == Some(self))
+        }
+
+        #[cfg(not(test))]
+        {
+            false
+        }
+    }
+
+    /// Seeds the current fault into all code generated on the current thread
+    /// until the returned guard is dropped
+    #[cfg(test)]
+    pub(crate) fn seed(self) -> SeededFault {
+        SEEDED_FAULT.with(|seeded| seeded.set(Some(self)));
+        SeededFault { _private: () }
+    }
+}
+
+#[cfg(test)]
+pub(crate) struct SeededFault {
+    _private: (),
+}
+
+#[cfg(test)]
+impl Drop for SeededFault {
+    fn drop(&mut self) {
+        SEEDED_FAULT.with(|seeded| seeded.set(None));
+    }
+}
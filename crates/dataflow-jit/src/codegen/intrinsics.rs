@@ -1,5 +1,5 @@
 use crate::{
-    codegen::{pretty_clif::CommentWriter, VTable},
+    codegen::{array::ArrayLayout, pretty_clif::CommentWriter, VTable},
     row::{Row, UninitRow},
     thin_str::ThinStrRef,
     ThinStr,
@@ -14,7 +14,7 @@ use cranelift_module::{FuncId, Linkage, Module};
 use std::{
     cell::RefCell,
     cmp::Ordering,
-    fmt::{self, Debug},
+    fmt::{self, Debug, Write},
     hash::{Hash, Hasher},
     mem::ManuallyDrop,
     rc::Rc,
@@ -165,6 +165,15 @@ intrinsics! {
     string_hash = fn(ptr, ptr),
     row_vec_push = fn(ptr, ptr, ptr),
 
+    // Array functions
+    array_clone = fn(ptr, ptr, ptr, ptr) -> ptr,
+    array_drop_in_place = fn(ptr, ptr, ptr, ptr),
+    array_eq = fn(ptr, ptr, ptr, ptr, ptr) -> bool,
+    array_cmp = fn(ptr, ptr, ptr, ptr, ptr) -> i8,
+    array_hash = fn(ptr, ptr, ptr, ptr, ptr),
+    array_debug = fn(ptr, ptr, ptr, ptr, ptr) -> bool,
+    array_size_of_children = fn(ptr, ptr, ptr, ptr, ptr),
+
     // String functions
     string_with_capacity = fn(ptr) -> ptr,
     string_push_str = fn(ptr, ptr, ptr),
@@ -326,6 +335,154 @@ unsafe extern "C" fn row_vec_push(vec: &mut Vec<Row>, vtable: &'static VTable, r
     vec.push(row);
 }
 
+/// Clones the given array, cloning its elements with the element layout's
+/// `clone_into_slice` function
+// FIXME: Technically this can unwind
+unsafe extern "C" fn array_clone(
+    array: *const u8,
+    element_size: usize,
+    element_align: usize,
+    clone_into_slice: unsafe extern "C" fn(*const u8, *mut u8, usize),
+) -> *mut u8 {
+    let layout = ArrayLayout::new(element_size, element_align);
+    let length = unsafe { ArrayLayout::len(array) };
+
+    let cloned = layout.alloc(length);
+    if length != 0 {
+        unsafe { clone_into_slice(layout.elements(array), layout.elements(cloned), length) };
+    }
+
+    cloned
+}
+
+/// Drops the given array's elements with the element layout's
+/// `drop_slice_in_place` function and then deallocates the array
+// FIXME: Technically this can unwind
+unsafe extern "C" fn array_drop_in_place(
+    array: *mut u8,
+    element_size: usize,
+    element_align: usize,
+    drop_slice_in_place: unsafe extern "C" fn(*mut u8, usize),
+) {
+    let layout = ArrayLayout::new(element_size, element_align);
+    let length = unsafe { ArrayLayout::len(array) };
+
+    if length != 0 {
+        unsafe {
+            drop_slice_in_place(layout.elements(array), length);
+            layout.dealloc(array);
+        }
+    }
+}
+
+/// Returns `true` if both arrays have the same length and all of their
+/// elements are equal
+unsafe extern "C" fn array_eq(
+    lhs: *const u8,
+    rhs: *const u8,
+    element_size: usize,
+    element_align: usize,
+    eq: unsafe extern "C" fn(*const u8, *const u8) -> bool,
+) -> bool {
+    let layout = ArrayLayout::new(element_size, element_align);
+    let length = unsafe { ArrayLayout::len(lhs) };
+
+    length == unsafe { ArrayLayout::len(rhs) }
+        && (0..length).all(|idx| unsafe { eq(layout.element(lhs, idx), layout.element(rhs, idx)) })
+}
+
+/// Compares the given arrays lexicographically by their elements, the same
+/// way as slices are compared
+unsafe extern "C" fn array_cmp(
+    lhs: *const u8,
+    rhs: *const u8,
+    element_size: usize,
+    element_align: usize,
+    cmp: unsafe extern "C" fn(*const u8, *const u8) -> Ordering,
+) -> Ordering {
+    let layout = ArrayLayout::new(element_size, element_align);
+    let (lhs_length, rhs_length) = unsafe { (ArrayLayout::len(lhs), ArrayLayout::len(rhs)) };
+
+    for idx in 0..lhs_length.min(rhs_length) {
+        match unsafe { cmp(layout.element(lhs, idx), layout.element(rhs, idx)) } {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+    }
+
+    lhs_length.cmp(&rhs_length)
+}
+
+/// Hashes the given array's length followed by each of its elements
+unsafe extern "C" fn array_hash(
+    hasher: &mut &mut dyn Hasher,
+    array: *const u8,
+    element_size: usize,
+    element_align: usize,
+    hash: unsafe extern "C" fn(&mut &mut dyn Hasher, *const u8),
+) {
+    let layout = ArrayLayout::new(element_size, element_align);
+    let length = unsafe { ArrayLayout::len(array) };
+
+    length.hash(hasher);
+    for idx in 0..length {
+        unsafe { hash(hasher, layout.element(array, idx)) };
+    }
+}
+
+/// Debugs the given array as a list of its elements
+unsafe extern "C" fn array_debug(
+    array: *const u8,
+    fmt: *mut fmt::Formatter<'_>,
+    element_size: usize,
+    element_align: usize,
+    debug: unsafe extern "C" fn(*const u8, *mut fmt::Formatter<'_>) -> bool,
+) -> bool {
+    debug_assert!(!fmt.is_null());
+
+    let layout = ArrayLayout::new(element_size, element_align);
+    let length = unsafe { ArrayLayout::len(array) };
+
+    if unsafe { (*fmt).write_char('[') }.is_err() {
+        return false;
+    }
+
+    for idx in 0..length {
+        if idx != 0 && unsafe { (*fmt).write_str(", ") }.is_err() {
+            return false;
+        }
+
+        if !unsafe { debug(layout.element(array, idx), fmt) } {
+            return false;
+        }
+    }
+
+    unsafe { (*fmt).write_char(']') }.is_ok()
+}
+
+/// Records the array's buffer as a distinct allocation along with the children
+/// of each of its elements
+unsafe extern "C" fn array_size_of_children(
+    array: *const u8,
+    context: &mut size_of::Context,
+    element_size: usize,
+    element_align: usize,
+    size_of_children: unsafe extern "C" fn(*const u8, &mut size_of::Context),
+) {
+    let layout = ArrayLayout::new(element_size, element_align);
+    let length = unsafe { ArrayLayout::len(array) };
+
+    if length != 0 {
+        context
+            .add_distinct_allocation()
+            .add(unsafe { layout.allocated_bytes(array) });
+
+        for idx in 0..length {
+            unsafe { size_of_children(layout.element(array, idx), context) };
+        }
+    }
+}
+
 unsafe extern "C" fn string_with_capacity(capacity: usize) -> ThinStr {
     ThinStr::with_capacity(capacity)
 }
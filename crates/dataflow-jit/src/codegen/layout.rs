@@ -1,4 +1,4 @@
-use crate::ir::{ColumnType, RowLayout};
+use crate::ir::{ColumnType, LayoutId, RowLayout};
 use cranelift::prelude::{
     isa::{CallConv, TargetFrontendConfig},
     types, FunctionBuilder, InstBuilder, Type as ClifType, Value,
@@ -69,7 +69,7 @@ impl NativeType {
     }
 
     /// Creates a type from the given [`ColumnType`], returning `None`
-    /// if it's a [`ColumnType::Unit`]
+    /// if it's a [`ColumnType::Unit`] or a [`ColumnType::Struct`]
     #[must_use]
     pub const fn from_column_type(column_type: ColumnType) -> Option<Self> {
         column_type.native_type()
@@ -312,7 +312,7 @@ pub struct NativeLayout {
     /// The alignment of the layout
     align: u32,
     /// The native type of each column's data
-    /// For zsts this is meaningless
+    /// For zsts and struct columns this is meaningless
     types: Vec<NativeType>,
     /// The offset of each column's data
     /// For zsts this is meaningless
@@ -321,6 +321,9 @@ pub struct NativeLayout {
     /// column doesn't have a bitset. If the row has no nullable columns,
     /// this will be empty
     bitsets: Vec<Option<(BitSetType, u32, u8)>>,
+    /// The size of each inline struct column, will be `None` for all other
+    /// columns. If the row has no struct columns, this will be empty
+    structs: Vec<Option<u32>>,
     /// Each field of the layout (columns and bitsets) in the order they appear
     /// within the concrete layout
     memory_order: Vec<MemoryEntry>,
@@ -362,6 +365,20 @@ impl NativeLayout {
         self.types[column]
     }
 
+    /// Returns `true` if the given column is an inline struct
+    pub fn is_struct(&self, column: usize) -> bool {
+        !self.structs.is_empty() && self.structs[column].is_some()
+    }
+
+    /// Returns the size of the given inline struct column
+    ///
+    /// # Panics
+    ///
+    /// Panics if `column` isn't a struct column
+    pub fn struct_size_of(&self, column: usize) -> u32 {
+        self.structs[column].unwrap()
+    }
+
     /// Returns `true` if the given column is nullable
     pub fn is_nullable(&self, column: usize) -> bool {
         !self.bitsets.is_empty() && self.bitsets[column].is_some()
@@ -411,8 +428,28 @@ impl NativeLayout {
 
     // TODO: Strings can use zero as their null value, this requires actual
     //       null-checking abstractions for writing code with though
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layout` contains any struct columns, their layouts must be
+    /// resolved using [`NativeLayout::from_row_with_structs()`]
     pub fn from_row(layout: &RowLayout, config: &LayoutConfig) -> Self {
-        algorithm::compute_native_layout(layout, config)
+        Self::from_row_with_structs(layout, config, |nested| {
+            panic!("cannot compute the layout of a struct column of {nested} without its native layout")
+        })
+    }
+
+    /// Computes the native layout of `layout`, calling `struct_layout` to get
+    /// the size and alignment of the inline layout of each struct column
+    pub fn from_row_with_structs<F>(
+        layout: &RowLayout,
+        config: &LayoutConfig,
+        struct_layout: F,
+    ) -> Self
+    where
+        F: FnMut(LayoutId) -> (u32, u32),
+    {
+        algorithm::compute_native_layout(layout, config, struct_layout)
     }
 
     pub fn size(&self) -> u32 {
@@ -512,6 +549,7 @@ impl Display for NativeLayout {
             ty: NativeType,
             offset: u32,
             bitset: Option<(BitSetType, u32, u8)>,
+            struct_size: Option<u32>,
         }
 
         impl Debug for LayoutField {
@@ -529,30 +567,33 @@ impl Display for NativeLayout {
                     },
                 };
 
+                let (ty, size) = match self.struct_size {
+                    Some(size) => ("struct", size),
+                    None => (self.ty.to_str(), self.ty.size(&frontend)),
+                };
+
                 if let Some((bitset, bitset_offset, bitset_bit)) = self.bitset {
                     write!(
                         f,
-                        "{} @ {}..{}, null @ bit {bitset_bit} of {bitset_offset}..{}",
-                        self.ty.to_str(),
+                        "{ty} @ {}..{}, null @ bit {bitset_bit} of {bitset_offset}..{}",
                         self.offset,
-                        self.offset + self.ty.size(&frontend),
+                        self.offset + size,
                         bitset_offset + bitset.size(),
                     )
                 } else {
-                    write!(
-                        f,
-                        "{} @ {}..{}",
-                        self.ty.to_str(),
-                        self.offset,
-                        self.offset + self.ty.size(&frontend),
-                    )
+                    write!(f, "{ty} @ {}..{}", self.offset, self.offset + size)
                 }
             }
         }
 
         let mut debug = f.debug_tuple("NativeLayout");
-        for ((offset, ty), &bitset) in self.columns().zip(&self.bitsets) {
-            debug.field(&LayoutField { ty, offset, bitset });
+        for (column, (offset, ty)) in self.columns().enumerate() {
+            debug.field(&LayoutField {
+                ty,
+                offset,
+                bitset: self.bitsets.get(column).copied().flatten(),
+                struct_size: self.structs.get(column).copied().flatten(),
+            });
         }
 
         // let mut bitsets: Vec<_> = self.bitsets.iter().copied().flatten().collect();
@@ -575,6 +616,16 @@ pub enum MemoryEntry {
         column: u32,
     },
 
+    /// The data associated with an inline struct column
+    Struct {
+        /// The offset this struct resides at
+        offset: u32,
+        /// The size of the struct's layout
+        size: u32,
+        /// The column associated with this entry
+        column: u32,
+    },
+
     /// The data associated with a bitset
     BitSet {
         /// The offset this bitset resides at
@@ -599,6 +650,10 @@ impl MemoryEntry {
         matches!(self, Self::Column { .. })
     }
 
+    pub const fn is_struct(&self) -> bool {
+        matches!(self, Self::Struct { .. })
+    }
+
     pub const fn is_bitset(&self) -> bool {
         matches!(self, Self::BitSet { .. })
     }
@@ -611,6 +666,7 @@ impl MemoryEntry {
     pub const fn offset(&self) -> u32 {
         match *self {
             Self::Column { offset, .. }
+            | Self::Struct { offset, .. }
             | Self::BitSet { offset, .. }
             | Self::Padding { offset, .. } => offset,
         }
@@ -620,6 +676,7 @@ impl MemoryEntry {
     pub fn size(&self, target: &TargetFrontendConfig) -> u32 {
         match *self {
             Self::Column { ty, .. } => ty.size(target),
+            Self::Struct { size, .. } => size,
             Self::BitSet { ty, .. } => ty.size(),
             Self::Padding { bytes, .. } => bytes as u32,
         }
@@ -641,7 +698,7 @@ mod algorithm {
             layout::{next_multiple_of, LayoutConfig, MemoryEntry},
             BitSetType, NativeLayout, NativeType,
         },
-        ir::RowLayout,
+        ir::{LayoutId, RowLayout},
     };
     use std::cmp::Reverse;
     use tinyvec::TinyVec;
@@ -651,6 +708,11 @@ mod algorithm {
             column: u32,
             ty: Option<NativeType>,
         },
+        Struct {
+            column: u32,
+            size: u32,
+            align: u32,
+        },
         BitSet {
             // TODO: Could use delta encoding on these
             columns: TinyVec<[u32; 8]>,
@@ -668,7 +730,14 @@ mod algorithm {
     //       the most-accessed null flags and then prioritize putting them in their
     //       own unique bytes to maximize the happy path of just loading a byte
     // instead       of dealing with bitset munging
-    pub(super) fn compute_native_layout(layout: &RowLayout, config: &LayoutConfig) -> NativeLayout {
+    pub(super) fn compute_native_layout<F>(
+        layout: &RowLayout,
+        config: &LayoutConfig,
+        mut struct_layout: F,
+    ) -> NativeLayout
+    where
+        F: FnMut(LayoutId) -> (u32, u32),
+    {
         // Ensure that the given layout has less than u32::MAX fields
         debug_assert!(
             layout.len() <= u32::MAX as usize,
@@ -678,16 +747,28 @@ mod algorithm {
 
         let null_columns = layout.total_null_columns();
         let mut fields = Vec::with_capacity(layout.len());
-        fields.extend(
-            layout
-                .columns()
-                .iter()
-                .enumerate()
-                .map(|(column, &ty)| Field::Column {
+        let mut has_structs = false;
+        fields.extend(layout.columns().iter().enumerate().map(|(column, &ty)| {
+            if ty.is_struct() {
+                has_structs = true;
+
+                let nested = layout
+                    .nested_layout(column)
+                    .expect("struct columns must have a layout");
+                let (size, align) = struct_layout(nested);
+
+                Field::Struct {
+                    column: column as u32,
+                    size,
+                    align,
+                }
+            } else {
+                Field::Column {
                     column: column as u32,
                     ty: NativeType::from_column_type(ty),
-                }),
-        );
+                }
+            }
+        }));
         bitsets(
             null_columns,
             layout
@@ -709,6 +790,10 @@ mod algorithm {
                         (false, ty.effective_align(&config.target), ty.is_ptr())
                     }
 
+                    // Structs are placed by their alignment, their size isn't
+                    // necessarily a power of two
+                    &Field::Struct { align, .. } => (false, align.trailing_zeros(), false),
+
                     Field::BitSet { ty, .. } => (false, ty.effective_align(), false),
                 };
 
@@ -732,13 +817,50 @@ mod algorithm {
         } else {
             vec![None; layout.len()]
         };
+        // If the row doesn't have any struct columns we use an empty structs vec as
+        // an optimization
+        let mut structs = if has_structs {
+            vec![None; layout.len()]
+        } else {
+            Vec::new()
+        };
         let mut memory_order = Vec::with_capacity(fields.len());
         let mut padding_bytes = Vec::new();
 
         for field in &fields {
+            // Structs are placed using their own size and alignment
+            if let Field::Struct {
+                column,
+                size,
+                align: field_align,
+            } = *field
+            {
+                let padding = padding_needed_for(offset, field_align);
+                padding_bytes.extend((0..padding).map(|i| offset + i));
+
+                offset = offset
+                    .checked_add(padding)
+                    .expect("layout overflowed u32::MAX");
+                align = align.max(field_align);
+
+                offsets[column as usize] = offset;
+                structs[column as usize] = Some(size);
+                memory_order.push(MemoryEntry::Struct {
+                    offset,
+                    size,
+                    column,
+                });
+
+                offset = offset
+                    .checked_add(size)
+                    .expect("layout overflowed u32::MAX");
+                continue;
+            }
+
             let field_ty = match *field {
                 Field::Column { ty, .. } => ty,
                 Field::BitSet { ty, .. } => Some(ty.into()),
+                Field::Struct { .. } => unreachable!(),
             };
 
             let field_size = if let Some(field_ty) = field_ty {
@@ -762,6 +884,8 @@ mod algorithm {
                             bitsets[column as usize] = Some((ty, offset, bit_idx as u8));
                         }
                     }
+
+                    Field::Struct { .. } => unreachable!(),
                 }
 
                 field_ty.size(&config.target)
@@ -787,6 +911,8 @@ mod algorithm {
                         columns: columns.clone(),
                     });
                 }
+
+                Field::Struct { .. } => unreachable!(),
             }
 
             offset = offset
@@ -814,6 +940,7 @@ mod algorithm {
             types,
             offsets,
             bitsets,
+            structs,
             memory_order,
             padding_bytes,
         }
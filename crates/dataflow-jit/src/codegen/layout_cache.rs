@@ -53,10 +53,25 @@ impl NativeLayoutCache {
                 return native_layout;
             }
 
-            self.inner.borrow_mut().layouts.insert(
-                layout_id,
-                NativeLayout::from_row(&row_layout, &self.layout_config),
-            );
+            // Struct columns are stored inline, so we need the native layouts of all
+            // nested structs before we can compute the current one. We also compute the
+            // element layouts of arrays so that all layouts reachable from the current one
+            // are already cached, letting callers hold onto the current layout while
+            // looking up nested ones
+            for nested in row_layout.nested_layouts() {
+                drop(self.layout_of(nested));
+            }
+
+            let native_layout =
+                NativeLayout::from_row_with_structs(&row_layout, &self.layout_config, |nested| {
+                    let nested = self.layout_of(nested);
+                    (nested.size(), nested.align())
+                });
+
+            self.inner
+                .borrow_mut()
+                .layouts
+                .insert(layout_id, native_layout);
             Ref::map(self.inner.borrow(), |inner| &inner.layouts[&layout_id])
         })();
 
@@ -81,3 +96,127 @@ impl Debug for NativeLayoutCacheInner {
         f.debug_map().entries(&self.layouts).finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codegen::{LayoutConfig, NativeLayoutCache, NativeType},
+        ir::{ColumnType, LayoutId, RowLayoutBuilder, RowLayoutCache},
+    };
+    use cranelift::prelude::isa::{CallConv, TargetFrontendConfig};
+    use target_lexicon::PointerWidth;
+
+    fn nested_layouts(layout_cache: &RowLayoutCache) -> [LayoutId; 3] {
+        let inner = layout_cache.add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, false)
+                .with_column(ColumnType::String, true)
+                .build(),
+        );
+        let middle = layout_cache.add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U8, false)
+                .with_struct_column(inner, false)
+                .with_array_column(inner, true)
+                .build(),
+        );
+        let outer = layout_cache.add(
+            RowLayoutBuilder::new()
+                .with_struct_column(middle, true)
+                .with_column(ColumnType::Bool, false)
+                .with_array_column(middle, false)
+                .build(),
+        );
+
+        [inner, middle, outer]
+    }
+
+    #[test]
+    fn nested_struct_layouts() {
+        for optimize_layouts in [false, true] {
+            let layout_cache = RowLayoutCache::new();
+            let [inner, middle, outer] = nested_layouts(&layout_cache);
+
+            let config = LayoutConfig::new(
+                TargetFrontendConfig {
+                    default_call_conv: CallConv::Fast,
+                    pointer_width: PointerWidth::U64,
+                },
+                optimize_layouts,
+            );
+            let native_cache = NativeLayoutCache::new(layout_cache.clone(), config);
+
+            // Computing the outermost layout first must resolve every layout it
+            // contains
+            let outer_layout = native_cache.layout_of(outer).clone();
+            let middle_layout = native_cache.layout_of(middle).clone();
+            let inner_layout = native_cache.layout_of(inner).clone();
+
+            for (layout, column, nested) in [
+                (&middle_layout, 1, &inner_layout),
+                (&outer_layout, 0, &middle_layout),
+            ] {
+                assert!(layout.is_struct(column));
+                assert_eq!(layout.struct_size_of(column), nested.size());
+                assert_eq!(layout.offset_of(column) % nested.align(), 0);
+                assert!(layout.align() >= nested.align());
+                assert!(layout.offset_of(column) + nested.size() <= layout.size());
+            }
+
+            // Arrays are stored as a single pointer
+            assert!(!middle_layout.is_struct(2));
+            assert_eq!(middle_layout.type_of(2), NativeType::Ptr);
+            assert_eq!(outer_layout.type_of(2), NativeType::Ptr);
+            assert!(outer_layout.is_nullable(0));
+
+            // No other column may overlap with an inline struct
+            for (layout, struct_column) in [(&middle_layout, 1), (&outer_layout, 0)] {
+                let start = layout.offset_of(struct_column);
+                let end = start + layout.struct_size_of(struct_column);
+
+                for (column, (offset, _)) in layout.columns().enumerate() {
+                    if column != struct_column && !layout.is_struct(column) {
+                        assert!(offset < start || offset >= end);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nested_row_layouts() {
+        let layout_cache = RowLayoutCache::new();
+        let [inner, middle, outer] = nested_layouts(&layout_cache);
+
+        // Adding an identical nested layout reuses the cached one
+        let [inner2, middle2, outer2] = nested_layouts(&layout_cache);
+        assert_eq!((inner, middle, outer), (inner2, middle2, outer2));
+
+        let outer_layout = layout_cache.get(outer).clone();
+        assert_eq!(outer_layout.nested_layout(0), Some(middle));
+        assert_eq!(outer_layout.nested_layout(1), None);
+        assert_eq!(outer_layout.nested_layout(2), Some(middle));
+        assert!(outer_layout.needs_drop());
+
+        let middle_layout = layout_cache.get(middle).clone();
+        assert_eq!(
+            middle_layout.nested_layouts().collect::<Vec<_>>(),
+            [inner, inner],
+        );
+
+        // Nested layouts survive a serialization round trip
+        let json = serde_json::to_string(&outer_layout).unwrap();
+        let deserialized = serde_json::from_str(&json).unwrap();
+        assert_eq!(outer_layout, deserialized);
+
+        // Layouts with the same columns but different nested layouts are distinct
+        let other_outer = layout_cache.add(
+            RowLayoutBuilder::new()
+                .with_struct_column(inner, true)
+                .with_column(ColumnType::Bool, false)
+                .with_array_column(middle, false)
+                .build(),
+        );
+        assert_ne!(outer, other_outer);
+    }
+}
@@ -1,31 +1,41 @@
+mod array;
 mod call;
+mod fault;
 mod intrinsics;
 mod layout;
 mod layout_cache;
 mod math;
 mod pretty_clif;
+mod target;
 mod tests;
 mod timestamp;
+mod udf;
 mod utils;
 mod vtable;
 
 pub use layout::{BitSetType, InvalidBitsetType, NativeLayout, NativeType};
 pub use layout_cache::NativeLayoutCache;
+pub use target::{CodegenTarget, TargetError};
 pub use vtable::{LayoutVTable, VTable};
 
+pub(crate) use array::ArrayLayout;
+pub(crate) use fault::CodegenFault;
 pub(crate) use layout::LayoutConfig;
+pub(crate) use target::isa_string;
+pub(crate) use udf::UdfTrampolines;
 
 use crate::{
     codegen::{
         intrinsics::{ImportIntrinsics, Intrinsics},
         layout::MemoryEntry,
         pretty_clif::CommentWriter,
+        udf::{udf_symbol, RegisteredUdf},
         utils::FunctionBuilderExt,
     },
     ir::{
-        block::ParamType, BinaryOp, BinaryOpKind, BlockId, Branch, Cast, ColumnType, Constant,
-        Expr, ExprId, Function, InputFlags, IsNull, LayoutId, Load, NullRow, RValue,
-        RowLayoutCache, Select, SetNull, Signature, Terminator, UnaryOp, UnaryOpKind,
+        block::ParamType, udf::UdfSignature, BinaryOp, BinaryOpKind, BlockId, Branch, Cast,
+        ColumnType, Constant, Expr, ExprId, Function, InputFlags, IsNull, LayoutId, Load, NullRow,
+        RValue, RowLayoutCache, Select, SetNull, Signature, Terminator, UnaryOp, UnaryOpKind,
     },
     ThinStr,
 };
@@ -35,8 +45,7 @@ use cranelift::{
         Context,
     },
     prelude::{
-        isa::{self, TargetFrontendConfig, TargetIsa},
-        settings, types, AbiParam, Block as ClifBlock, Configurable, FloatCC, FunctionBuilder,
+        isa::TargetFrontendConfig, types, AbiParam, Block as ClifBlock, FloatCC, FunctionBuilder,
         FunctionBuilderContext, InstBuilder, IntCC, MemFlags, Signature as ClifSignature,
         StackSlotData, StackSlotKind, TrapCode, Type, Value,
     },
@@ -48,7 +57,6 @@ use std::{
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet, HashMap},
     rc::Rc,
-    sync::Arc,
 };
 use target_lexicon::Triple;
 
@@ -63,7 +71,7 @@ const TRAP_ABORT: TrapCode = TrapCode::User(6);
 // TODO: Pretty function debugging https://github.com/bjorn3/rustc_codegen_cranelift/blob/master/src/pretty_clif.rs
 
 // TODO: Config option for packed null flags or 1 byte booleans
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct CodegenConfig {
     /// Whether or not to add invariant assertions into generated code
     pub debug_assertions: bool,
@@ -81,6 +89,14 @@ pub struct CodegenConfig {
     /// trap when the float is NaN and if this option is enabled then float
     /// to int casts will yield zero when the float is NaN
     pub saturating_float_to_int_casts: bool,
+    /// Whether or not [`CollectStatistics`](crate::ir::nodes::CollectStatistics)
+    /// nodes should collect statistics, if this option is disabled then they
+    /// add no operators to the compiled dataflow
+    pub collect_statistics: bool,
+    /// The instruction set to generate code for
+    pub target: CodegenTarget,
+    /// User defined functions that generated code can call
+    udfs: BTreeMap<String, RegisteredUdf>,
 }
 
 impl CodegenConfig {
@@ -97,6 +113,9 @@ impl CodegenConfig {
             optimize_layouts,
             clif_comments,
             saturating_float_to_int_casts,
+            collect_statistics: true,
+            target: CodegenTarget::Native,
+            udfs: BTreeMap::new(),
         }
     }
 
@@ -128,6 +147,83 @@ impl CodegenConfig {
         self
     }
 
+    pub const fn with_collect_statistics(mut self, collect_statistics: bool) -> Self {
+        self.collect_statistics = collect_statistics;
+        self
+    }
+
+    pub fn with_target(mut self, target: CodegenTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Generates code for the host machine using every feature it supports
+    pub fn native(self) -> Self {
+        self.with_target(CodegenTarget::Native)
+    }
+
+    /// Generates code for a conservative baseline of the host's architecture,
+    /// see [`CodegenTarget::Baseline`]
+    pub fn baseline(self) -> Self {
+        self.with_target(CodegenTarget::Baseline)
+    }
+
+    /// Generates code for the given target with the given isa features enabled
+    pub fn for_target<I>(self, triple: Triple, features: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.with_target(CodegenTarget::Custom {
+            triple,
+            features: features.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Registers a user defined function that can be called with
+    /// [`CallUdf`](crate::ir::exprs::CallUdf) expressions
+    ///
+    /// # Safety
+    ///
+    /// `func` must be a pointer to an `extern "C"` function whose signature
+    /// matches `signature` under the ABI described in [`crate::ir::udf`] and
+    /// it must stay valid for as long as any code generated with this config
+    /// is alive
+    ///
+    /// # Panics
+    ///
+    /// Panics if a udf named `name` was already registered
+    pub unsafe fn register_udf<N>(
+        &mut self,
+        name: N,
+        signature: UdfSignature,
+        func: *const u8,
+    ) -> &mut Self
+    where
+        N: Into<String>,
+    {
+        let name = name.into();
+        assert!(
+            !func.is_null(),
+            "registered a null pointer for the udf `{name}`"
+        );
+        assert!(
+            !self.udfs.contains_key(&name),
+            "the udf `{name}` was registered twice",
+        );
+
+        self.udfs.insert(name, RegisteredUdf { signature, func });
+        self
+    }
+
+    /// Returns the signatures of all registered udfs
+    pub fn udf_signatures(&self) -> BTreeMap<String, UdfSignature> {
+        self.udfs
+            .iter()
+            .map(|(name, udf)| (name.clone(), udf.signature.clone()))
+            .collect()
+    }
+
     pub const fn debug() -> Self {
         Self {
             debug_assertions: true,
@@ -135,6 +231,9 @@ impl CodegenConfig {
             optimize_layouts: true,
             clif_comments: true,
             saturating_float_to_int_casts: true,
+            collect_statistics: true,
+            target: CodegenTarget::Native,
+            udfs: BTreeMap::new(),
         }
     }
 
@@ -145,6 +244,9 @@ impl CodegenConfig {
             optimize_layouts: true,
             clif_comments: false,
             saturating_float_to_int_casts: true,
+            collect_statistics: true,
+            target: CodegenTarget::Native,
+            udfs: BTreeMap::new(),
         }
     }
 }
@@ -166,11 +268,17 @@ pub struct Codegen {
     vtables: BTreeMap<LayoutId, LayoutVTable>,
     data: HashMap<Box<[u8]>, DataId>,
     comment_writer: Option<Rc<RefCell<CommentWriter>>>,
+    /// The symbols of all functions generated by [`Codegen::codegen_func()`] in
+    /// the order they were generated in
+    function_symbols: Vec<String>,
 }
 
 impl Codegen {
     pub fn new(layout_cache: RowLayoutCache, config: CodegenConfig) -> Self {
-        let target = Self::target_isa();
+        let target = config
+            .target
+            .target_isa()
+            .unwrap_or_else(|error| panic!("invalid codegen target {:?}: {error}", config.target));
         tracing::info!(
             config = ?config,
             flags = %target.flags(),
@@ -190,6 +298,9 @@ impl Codegen {
             cranelift_module::default_libcall_names(),
         );
         Intrinsics::register(&mut builder);
+        for (name, udf) in &config.udfs {
+            builder.symbol(udf_symbol(name), udf.func);
+        }
 
         let mut module = JITModule::new(builder);
         let intrinsics = Intrinsics::new(&mut module);
@@ -206,38 +317,27 @@ impl Codegen {
             vtables: BTreeMap::new(),
             data: HashMap::new(),
             comment_writer: None,
+            function_symbols: Vec::new(),
         }
     }
 
-    fn target_isa() -> Arc<dyn TargetIsa> {
-        let mut settings = settings::builder();
-
-        let options = &[
-            ("opt_level", "speed"),
-            ("enable_simd", "true"),
-            ("use_egraphs", "true"),
-            ("unwind_info", "true"),
-            ("enable_verifier", "true"),
-            ("enable_jump_tables", "true"),
-            ("enable_alias_analysis", "true"),
-            ("use_colocated_libcalls", "false"),
-            // FIXME: Set back to true once the x64 backend supports it.
-            ("is_pic", "false"),
-        ];
-        for (name, value) in options {
-            settings.set(name, value).unwrap();
-        }
-
-        isa::lookup(Triple::host())
-            .unwrap()
-            .finish(settings::Flags::new(settings))
-            .unwrap()
+    /// Returns a string identifying the instruction set that code is generated
+    /// for, code generated with one isa string can only be loaded by code
+    /// generators with the same isa string
+    pub fn isa_string(&self) -> String {
+        isa_string(self.module.isa())
     }
 
     pub fn native_layout_cache(&self) -> &NativeLayoutCache {
         &self.layout_cache
     }
 
+    /// Returns the symbols of all functions generated so far in the order they
+    /// were generated in
+    pub fn function_symbols(&self) -> &[String] {
+        &self.function_symbols
+    }
+
     pub fn finalize_definitions(mut self) -> (JITModule, NativeLayoutCache) {
         self.module.finalize_definitions().unwrap();
         (self.module, self.layout_cache)
@@ -251,6 +351,8 @@ impl Codegen {
     }
 
     pub fn codegen_func(&mut self, symbol: &str, function: &Function) -> FuncId {
+        self.function_symbols.push(symbol.to_owned());
+
         let abi = function
             .signature()
             .display(self.layout_cache.row_layout_cache())
@@ -268,7 +370,7 @@ impl Codegen {
         {
             let layout_cache = self.layout_cache.clone();
             let mut ctx = CodegenCtx::new(
-                self.config,
+                self.config.clone(),
                 &mut self.module,
                 &mut self.data_ctx,
                 &mut self.data,
@@ -334,6 +436,7 @@ impl Codegen {
                 for &(expr_id, ref expr) in block_contents.body() {
                     match expr {
                         Expr::Call(call) => ctx.call(expr_id, call, &mut builder),
+                        Expr::CallUdf(call) => ctx.call_udf(expr_id, call, &mut builder),
                         Expr::Cast(cast) => ctx.cast(expr_id, cast, &mut builder),
                         Expr::BinOp(binop) => ctx.binary_op(expr_id, binop, &mut builder),
                         Expr::UnaryOp(unary) => ctx.unary_op(expr_id, unary, &mut builder),
@@ -789,9 +892,12 @@ impl<'a> CodegenCtx<'a> {
             Constant::Isize(int) => int as i64,
             Constant::Bool(bool) => bool as i64,
 
-            Constant::Unit | Constant::F32(_) | Constant::F64(_) | Constant::String(_) => {
-                unreachable!()
-            }
+            Constant::Unit
+            | Constant::F32(_)
+            | Constant::F64(_)
+            | Constant::String(_)
+            | Constant::Struct(_)
+            | Constant::Array(_) => unreachable!(),
         };
 
         builder.ins().iconst(ty, val)
@@ -1145,6 +1251,24 @@ impl<'a> CodegenCtx<'a> {
         let layout_id = self.layout_id(load.source());
         let (layout, row_layout) = layout_cache.get_layouts(layout_id);
         let offset = layout.offset_of(load.column());
+
+        // Structs are stored inline, so loading one gives us a pointer to the nested
+        // row which can then be used like any other row
+        if row_layout.column_type(load.column()).is_struct() {
+            let nested = row_layout.nested_layout(load.column()).unwrap();
+            let addr = if let Some(&slot) = self.stack_slots.get(&load.source()) {
+                builder
+                    .ins()
+                    .stack_addr(self.pointer_type(), slot, offset as i32)
+            } else {
+                let addr = self.exprs[&load.source()];
+                builder.ins().iadd_imm(addr, offset as i64)
+            };
+
+            self.add_expr(expr_id, addr, None, nested);
+            return;
+        }
+
         let ty = layout
             .type_of(load.column())
             .native_type(&self.module.isa().frontend_config());
@@ -1460,7 +1584,11 @@ impl<'a> CodegenCtx<'a> {
                 if lhs_ty.is_float() {
                     builder.ins().fadd(lhs, rhs)
                 } else if lhs_ty.is_int() {
-                    builder.ins().iadd(lhs, rhs)
+                    if CodegenFault::IntAddAsSub.is_seeded() {
+                        builder.ins().isub(lhs, rhs)
+                    } else {
+                        builder.ins().iadd(lhs, rhs)
+                    }
                 } else {
                     todo!("unknown binop type: {lhs_ty} ({binop:?})")
                 }
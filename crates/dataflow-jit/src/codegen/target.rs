@@ -0,0 +1,136 @@
+//! Selection of the instruction set that code is generated for
+
+use cranelift::{
+    codegen::CodegenError,
+    prelude::{
+        isa::{self, LookupError, TargetIsa},
+        settings::{self, SetError},
+        Configurable,
+    },
+};
+use derive_more::Display;
+use std::{error::Error, sync::Arc};
+use target_lexicon::{Architecture, Triple};
+
+/// The instruction set that code is generated for
+///
+/// Code compiled for one target is only guaranteed to run on machines that
+/// support every feature of that target, so code that's compiled on one
+/// machine and run on another should use [`CodegenTarget::Baseline`] or an
+/// explicit [`CodegenTarget::Custom`] target
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CodegenTarget {
+    /// The host machine, using every instruction set extension it supports
+    #[default]
+    Native,
+    /// The host's architecture restricted to a conservative set of extensions,
+    /// x86-64-v2 on x86-64 and the architecture's defaults elsewhere
+    Baseline,
+    /// The given target with the given cranelift isa features or cpu presets
+    /// (e.g. `has_avx2` or `x86-64-v3`) enabled
+    ///
+    /// Code can be generated for targets other than the host but it can't be
+    /// run on it
+    Custom {
+        triple: Triple,
+        features: Vec<String>,
+    },
+}
+
+impl CodegenTarget {
+    /// The cpu preset used for [`CodegenTarget::Baseline`] on x86-64
+    const X86_64_BASELINE: &'static str = "x86-64-v2";
+
+    /// Creates the isa for the current target with the flags used by all
+    /// generated code
+    pub fn target_isa(&self) -> Result<Arc<dyn TargetIsa>, TargetError> {
+        let mut settings = settings::builder();
+
+        let options = &[
+            ("opt_level", "speed"),
+            ("enable_simd", "true"),
+            ("use_egraphs", "true"),
+            ("unwind_info", "true"),
+            ("enable_verifier", "true"),
+            ("enable_jump_tables", "true"),
+            ("enable_alias_analysis", "true"),
+            ("use_colocated_libcalls", "false"),
+            // FIXME: Set back to true once the x64 backend supports it.
+            ("is_pic", "false"),
+        ];
+        for (name, value) in options {
+            settings.set(name, value).unwrap();
+        }
+
+        self.isa_builder()?
+            .finish(settings::Flags::new(settings))
+            .map_err(TargetError::Finish)
+    }
+
+    fn isa_builder(&self) -> Result<isa::Builder, TargetError> {
+        match self {
+            Self::Native => cranelift_native::builder().map_err(TargetError::UnsupportedHost),
+
+            Self::Baseline => {
+                let triple = Triple::host();
+                let is_x86_64 = triple.architecture == Architecture::X86_64;
+
+                let mut builder = lookup(triple)?;
+                if is_x86_64 {
+                    enable(&mut builder, Self::X86_64_BASELINE)?;
+                }
+
+                Ok(builder)
+            }
+
+            Self::Custom { triple, features } => {
+                let mut builder = lookup(triple.clone())?;
+                for feature in features {
+                    enable(&mut builder, feature)?;
+                }
+
+                Ok(builder)
+            }
+        }
+    }
+}
+
+fn lookup(triple: Triple) -> Result<isa::Builder, TargetError> {
+    isa::lookup(triple.clone()).map_err(|error| TargetError::UnsupportedTriple { triple, error })
+}
+
+fn enable(builder: &mut isa::Builder, feature: &str) -> Result<(), TargetError> {
+    builder
+        .enable(feature)
+        .map_err(|error| TargetError::InvalidFeature {
+            feature: feature.to_owned(),
+            error,
+        })
+}
+
+/// Returns a string identifying the given isa's triple and every isa-specific
+/// flag, two isas with the same string generate interchangeable code
+pub(crate) fn isa_string(isa: &dyn TargetIsa) -> String {
+    let mut flags: Vec<_> = isa
+        .isa_flags()
+        .iter()
+        .map(|flag| flag.to_string())
+        .collect();
+    flags.sort_unstable();
+
+    format!("{} {}", isa.triple(), flags.join(","))
+}
+
+#[derive(Debug, Display)]
+pub enum TargetError {
+    #[display(fmt = "the host machine isn't supported: {_0}")]
+    UnsupportedHost(&'static str),
+    #[display(fmt = "the target {triple} isn't supported: {error}")]
+    UnsupportedTriple { triple: Triple, error: LookupError },
+    #[display(fmt = "invalid isa feature {feature:?}: {error}")]
+    InvalidFeature { feature: String, error: SetError },
+    #[display(fmt = "failed to create the target isa: {_0}")]
+    Finish(CodegenError),
+}
+
+impl Error for TargetError {}
@@ -12,6 +12,7 @@ use crate::{
 };
 use chrono::{Datelike, Utc};
 use std::mem::transmute;
+use target_lexicon::Triple;
 
 #[test]
 fn block_param_phi() {
@@ -500,6 +501,85 @@ fn unwrap_optional_bool() {
     unsafe { jit.free_memory() };
 }
 
+#[test]
+fn baseline_target() {
+    utils::test_logger();
+
+    let layout_cache = RowLayoutCache::new();
+    let i64 = layout_cache.add(
+        RowLayoutBuilder::new()
+            .with_column(ColumnType::I64, false)
+            .build(),
+    );
+
+    let function = {
+        let mut builder = FunctionBuilder::new(layout_cache.clone());
+        let input = builder.add_input(i64);
+        let output = builder.add_output(i64);
+
+        let x = builder.load(input, 0);
+        let three = builder.constant(Constant::I64(3));
+        let x_times_three = builder.mul(x, three);
+        builder.store(output, 0, x_times_three);
+        builder.ret_unit();
+
+        builder.build()
+    };
+
+    let mut codegen = Codegen::new(layout_cache, CodegenConfig::debug().baseline());
+    let function = codegen.codegen_func("times_three", &function);
+
+    let (jit, layout_cache) = codegen.finalize_definitions();
+    {
+        let offset = layout_cache.layout_of(i64).offset_of(0) as usize;
+        let times_three = unsafe {
+            transmute::<*const u8, extern "C" fn(*const u8, *mut u8)>(
+                jit.get_finalized_function(function),
+            )
+        };
+
+        // The row is a single i64, so an i64 buffer is large and aligned enough
+        let (mut input, mut output) = ([0i64; 2], [0i64; 2]);
+        unsafe {
+            input
+                .as_mut_ptr()
+                .cast::<u8>()
+                .add(offset)
+                .cast::<i64>()
+                .write(14);
+            times_three(input.as_ptr().cast(), output.as_mut_ptr().cast());
+
+            let result = output
+                .as_ptr()
+                .cast::<u8>()
+                .add(offset)
+                .cast::<i64>()
+                .read();
+            assert_eq!(result, 42);
+        }
+    }
+
+    unsafe { jit.free_memory() };
+}
+
+#[test]
+fn isa_strings_identify_targets() {
+    utils::test_logger();
+
+    let isa_string = |config| Codegen::new(RowLayoutCache::new(), config).isa_string();
+
+    let baseline = isa_string(CodegenConfig::release().baseline());
+    assert_eq!(baseline, isa_string(CodegenConfig::debug().baseline()));
+    assert!(baseline.starts_with(&Triple::host().to_string()));
+
+    // Code compiled with more isa features than the baseline mustn't be mistaken
+    // for baseline code
+    if cfg!(target_arch = "x86_64") {
+        let v3 = isa_string(CodegenConfig::release().for_target(Triple::host(), ["x86-64-v3"]));
+        assert_ne!(baseline, v3);
+    }
+}
+
 // TODO: Min/max with and without normalization
 // TODO: More binops
 // TODO: Test different codegen options
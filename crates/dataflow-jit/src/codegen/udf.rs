@@ -0,0 +1,314 @@
+//! Calls to user defined functions (udfs), see [`crate::ir::udf`] for the ABI
+//! they're called with
+
+use crate::{
+    codegen::{CodegenConfig, CodegenCtx},
+    ir::{exprs::CallUdf, udf::UdfSignature, ColumnType, ExprId},
+};
+use cranelift::prelude::{
+    types, AbiParam, FunctionBuilder, FunctionBuilderContext, InstBuilder, MemFlags, Signature,
+};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{Linkage, Module};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    mem::{self, ManuallyDrop},
+};
+
+/// A udf registered with the code generator
+#[derive(Debug, Clone)]
+pub(crate) struct RegisteredUdf {
+    pub(crate) signature: UdfSignature,
+    pub(crate) func: *const u8,
+}
+
+// Safety: Udfs are plain function pointers
+unsafe impl Send for RegisteredUdf {}
+unsafe impl Sync for RegisteredUdf {}
+
+/// Returns the symbol a udf is imported under, udfs are namespaced so that
+/// they can't collide with intrinsics
+pub(crate) fn udf_symbol(name: &str) -> String {
+    format!("dbsp.udf.{name}")
+}
+
+/// Builds the signature udfs with the given `signature` are called with,
+/// nullable arguments are followed by their null flag and nullable returns are
+/// written to a trailing out pointer
+fn clif_signature(signature: &UdfSignature, module: &JITModule) -> Signature {
+    let frontend_config = module.isa().frontend_config();
+    let clif_ty = |ty: ColumnType| {
+        ty.native_type()
+            .expect("udfs can't take or return unit values")
+            .native_type(&frontend_config)
+    };
+
+    let mut clif_signature = module.make_signature();
+    for arg in signature.args() {
+        clif_signature.params.push(AbiParam::new(clif_ty(arg.ty())));
+
+        if arg.is_nullable() {
+            clif_signature.params.push(AbiParam::new(types::I8));
+        }
+    }
+
+    let ret = signature.ret();
+    if ret.is_nullable() {
+        clif_signature
+            .params
+            .push(AbiParam::new(frontend_config.pointer_type()));
+        clif_signature.returns.push(AbiParam::new(types::I8));
+    } else if !ret.ty().is_unit() {
+        clif_signature
+            .returns
+            .push(AbiParam::new(clif_ty(ret.ty())));
+    }
+
+    clif_signature
+}
+
+impl CodegenCtx<'_> {
+    pub(super) fn call_udf(
+        &mut self,
+        expr_id: ExprId,
+        call: &CallUdf,
+        builder: &mut FunctionBuilder<'_>,
+    ) {
+        let signature = self
+            .config
+            .udfs
+            .get(call.name())
+            .unwrap_or_else(|| panic!("called unregistered udf `{}` in {expr_id}", call.name()))
+            .signature
+            .clone();
+        let ptr_ty = self.pointer_type();
+        let clif_signature = clif_signature(&signature, self.module);
+        let ret = signature.ret();
+
+        let udf = self
+            .module
+            .declare_function(&udf_symbol(call.name()), Linkage::Import, &clif_signature)
+            .unwrap();
+        let udf = self.module.declare_func_in_func(udf, builder.func);
+
+        let mut args: Vec<_> = call.args().iter().map(|&arg| self.value(arg)).collect();
+
+        if ret.is_nullable() {
+            let ret_layout = call.ret_layout().unwrap();
+            let slot = self.stack_slot_for_layout(expr_id, ret_layout, builder);
+
+            let (offset, bitset_ty, bitset_offset) = {
+                let layout = self.layout_cache.layout_of(ret_layout);
+                debug_assert_eq!(layout.bitset_occupants(0), 1);
+
+                let (bitset_ty, bitset_offset, bit_idx) = layout.nullability_of(0);
+                debug_assert_eq!(bit_idx, 0);
+
+                (layout.offset_of(0), bitset_ty.native_type(), bitset_offset)
+            };
+
+            let out = builder.ins().stack_addr(ptr_ty, slot, offset as i32);
+            args.push(out);
+
+            let call_inst = builder.ins().call(udf, &args);
+            let is_null = builder.inst_results(call_inst)[0];
+            self.debug_assert_bool_is_valid(is_null, builder);
+
+            // The result is the only occupant of its bitset so we can set it directly
+            let is_null = match bitset_ty.bytes().cmp(&types::I8.bytes()) {
+                Ordering::Less => builder.ins().ireduce(bitset_ty, is_null),
+                Ordering::Equal => is_null,
+                Ordering::Greater => builder.ins().uextend(bitset_ty, is_null),
+            };
+            builder
+                .ins()
+                .stack_store(is_null, slot, bitset_offset as i32);
+
+            self.comment(call_inst, || {
+                format!("call udf `{}` with nullable return", call.name())
+            });
+        } else {
+            let call_inst = builder.ins().call(udf, &args);
+            self.comment(call_inst, || format!("call udf `{}`", call.name()));
+
+            if !ret.ty().is_unit() {
+                let result = builder.inst_results(call_inst)[0];
+                self.add_expr(expr_id, result, ret.ty(), None);
+            }
+        }
+    }
+}
+
+/// The signature of the trampolines generated by [`UdfTrampolines`], they
+/// return a non-zero value when the udf's result is null
+type Trampoline = unsafe extern "C" fn(*const u64, *mut u64) -> u8;
+
+/// A trampoline that calls a registered udf, see [`UdfTrampolines`]
+#[derive(Debug, Clone)]
+pub(crate) struct UdfTrampoline {
+    signature: UdfSignature,
+    trampoline: Trampoline,
+}
+
+impl UdfTrampoline {
+    pub(crate) fn signature(&self) -> &UdfSignature {
+        &self.signature
+    }
+
+    /// Calls the udf with `args`, each argument expression of the call takes
+    /// up one slot of `args` and is stored at the start of it in its native
+    /// representation. Returns the slot the udf's result was written to and
+    /// `true` if the result is null
+    ///
+    /// # Safety
+    ///
+    /// `args` must contain valid values of the types the udf expects, strings
+    /// are passed as pointers to [`ThinStr`](crate::ThinStr)s which must stay
+    /// alive for the duration of the call. Returned strings are owned
+    /// [`ThinStr`](crate::ThinStr)s that the caller must free
+    pub(crate) unsafe fn call(&self, args: &[u64]) -> (u64, bool) {
+        assert_eq!(args.len(), self.signature.arg_exprs());
+
+        let mut out = 0;
+        let is_null = unsafe { (self.trampoline)(args.as_ptr(), &mut out) };
+        (out, is_null != 0)
+    }
+}
+
+/// Trampolines for calling registered udfs from outside of generated code,
+/// used by the interpreter to call udfs through the same function pointers
+/// that generated code calls them through
+///
+/// The trampoline of each udf loads the udf's arguments from an array of
+/// 8 byte slots, calls the udf under the ABI described in [`crate::ir::udf`]
+/// and writes its result to an out slot
+pub(crate) struct UdfTrampolines {
+    module: ManuallyDrop<JITModule>,
+    trampolines: BTreeMap<String, UdfTrampoline>,
+}
+
+impl UdfTrampolines {
+    /// Generates trampolines for all udfs registered with `config`
+    pub(crate) fn new(config: &CodegenConfig) -> Self {
+        let target = config
+            .target
+            .target_isa()
+            .unwrap_or_else(|error| panic!("invalid codegen target {:?}: {error}", config.target));
+
+        let mut builder = JITBuilder::with_isa(target, cranelift_module::default_libcall_names());
+        for (name, udf) in &config.udfs {
+            builder.symbol(udf_symbol(name), udf.func);
+        }
+
+        let mut module = JITModule::new(builder);
+        let mut ctx = module.make_context();
+        let mut function_ctx = FunctionBuilderContext::new();
+        let ptr_ty = module.isa().pointer_type();
+
+        let mut trampoline_ids = Vec::with_capacity(config.udfs.len());
+        for (name, udf) in &config.udfs {
+            let udf_signature = clif_signature(&udf.signature, &module);
+            let udf_id = module
+                .declare_function(&udf_symbol(name), Linkage::Import, &udf_signature)
+                .unwrap();
+
+            ctx.func.signature = module.make_signature();
+            ctx.func.signature.params.push(AbiParam::new(ptr_ty));
+            ctx.func.signature.params.push(AbiParam::new(ptr_ty));
+            ctx.func.signature.returns.push(AbiParam::new(types::I8));
+            let trampoline_id = module
+                .declare_anonymous_function(&ctx.func.signature)
+                .unwrap();
+
+            {
+                let mut builder = FunctionBuilder::new(&mut ctx.func, &mut function_ctx);
+                let entry = builder.create_block();
+                builder.append_block_params_for_function_params(entry);
+                builder.switch_to_block(entry);
+
+                let (args_ptr, out) = (
+                    builder.block_params(entry)[0],
+                    builder.block_params(entry)[1],
+                );
+                let udf_ref = module.declare_func_in_func(udf_id, builder.func);
+
+                // Every argument expression is loaded from its own slot
+                let flags = MemFlags::trusted();
+                let ret = udf.signature.ret();
+                let arg_exprs = udf.signature.arg_exprs();
+                let mut args: Vec<_> = udf_signature.params[..arg_exprs]
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, param)| {
+                        builder
+                            .ins()
+                            .load(param.value_type, flags, args_ptr, slot as i32 * 8)
+                    })
+                    .collect();
+
+                let is_null = if ret.is_nullable() {
+                    // Nullable udfs write their result to the out pointer themselves
+                    args.push(out);
+                    let call = builder.ins().call(udf_ref, &args);
+                    builder.inst_results(call)[0]
+                } else {
+                    let call = builder.ins().call(udf_ref, &args);
+                    if !ret.ty().is_unit() {
+                        let result = builder.inst_results(call)[0];
+                        builder.ins().store(flags, result, out, 0);
+                    }
+                    builder.ins().iconst(types::I8, 0)
+                };
+                builder.ins().return_(&[is_null]);
+
+                builder.seal_all_blocks();
+                builder.finalize();
+            }
+
+            module.define_function(trampoline_id, &mut ctx).unwrap();
+            module.clear_context(&mut ctx);
+            trampoline_ids.push((name.clone(), udf.signature.clone(), trampoline_id));
+        }
+
+        module.finalize_definitions().unwrap();
+
+        let trampolines = trampoline_ids
+            .into_iter()
+            .map(|(name, signature, trampoline_id)| {
+                // Safety: The trampoline was generated with the signature of `Trampoline`
+                let trampoline = unsafe {
+                    mem::transmute::<*const u8, Trampoline>(
+                        module.get_finalized_function(trampoline_id),
+                    )
+                };
+
+                (
+                    name,
+                    UdfTrampoline {
+                        signature,
+                        trampoline,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            module: ManuallyDrop::new(module),
+            trampolines,
+        }
+    }
+
+    /// Returns the trampoline of the udf named `name`
+    pub(crate) fn get(&self, name: &str) -> Option<&UdfTrampoline> {
+        self.trampolines.get(name)
+    }
+}
+
+impl Drop for UdfTrampolines {
+    fn drop(&mut self) {
+        // Safety: The trampolines are only reachable through `self` so nothing can
+        // still be using them
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() }
+    }
+}
@@ -1,9 +1,11 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::BTreeMap};
 
 use crate::{
     codegen::{
-        intrinsics::ImportIntrinsics, utils::FunctionBuilderExt, vtable::column_non_null, Codegen,
-        NativeLayout, TRAP_NULL_PTR,
+        intrinsics::ImportIntrinsics,
+        utils::FunctionBuilderExt,
+        vtable::{array_args, column_non_null, nested_vtable, LayoutVTable},
+        Codegen, NativeLayout, TRAP_NULL_PTR,
     },
     ir::{ColumnType, LayoutId, RowLayout},
 };
@@ -79,6 +81,7 @@ impl Codegen {
                         dest,
                         &layout,
                         &row_layout,
+                        &self.vtables,
                         &mut builder,
                         &mut imports,
                         &mut self.module,
@@ -230,6 +233,7 @@ impl Codegen {
                         dest,
                         &layout,
                         &row_layout,
+                        &self.vtables,
                         &mut builder,
                         &mut imports,
                         &mut self.module,
@@ -277,11 +281,13 @@ impl Codegen {
 
 // TODO: We can copy over the bitflag bytes wholesale without doing the whole
 // "check bit, set bit, write bit" thing
+#[allow(clippy::too_many_arguments)]
 fn clone_layout(
     src: Value,
     dest: Value,
     layout: &NativeLayout,
     row_layout: &RowLayout,
+    vtables: &BTreeMap<LayoutId, LayoutVTable>,
     builder: &mut FunctionBuilder,
     imports: &mut ImportIntrinsics,
     module: &mut JITModule,
@@ -367,44 +373,66 @@ fn clone_layout(
         debug_assert!(!ty.is_unit());
 
         let offset = layout.offset_of(idx) as i32;
-        let native_ty = layout
-            .type_of(idx)
-            .native_type(&module.isa().frontend_config());
-
-        // Load the source value
-        let src_value = builder.ins().load(native_ty, src_flags, src, offset);
-
-        // Clone the source value
-        let cloned = match ty {
-            // For scalar types we just copy the value directly
-            ColumnType::Bool
-            | ColumnType::U8
-            | ColumnType::I8
-            | ColumnType::U16
-            | ColumnType::U32
-            | ColumnType::U64
-            | ColumnType::Usize
-            | ColumnType::I16
-            | ColumnType::I32
-            | ColumnType::I64
-            | ColumnType::Isize
-            | ColumnType::F32
-            | ColumnType::F64
-            | ColumnType::Date
-            | ColumnType::Timestamp => src_value,
-
-            // Strings need their clone function called
-            ColumnType::String => {
-                let clone_string = imports.string_clone(module, builder.func);
-                builder.call_fn(clone_string, &[src_value])
-            }
 
-            // Unit types have been handled
-            ColumnType::Ptr | ColumnType::Unit => unreachable!(),
-        };
+        // Structs are stored inline, so they're cloned in place by their own clone
+        // function
+        if ty.is_struct() {
+            let vtable = nested_vtable(idx, row_layout, vtables);
+            let clone_struct = module.declare_func_in_func(vtable.clone, builder.func);
+
+            let src = builder.ins().iadd_imm(src, offset as i64);
+            let dest = builder.ins().iadd_imm(dest, offset as i64);
+            builder.ins().call(clone_struct, &[src, dest]);
+        } else {
+            let native_ty = layout
+                .type_of(idx)
+                .native_type(&module.isa().frontend_config());
+
+            // Load the source value
+            let src_value = builder.ins().load(native_ty, src_flags, src, offset);
+
+            // Clone the source value
+            let cloned = match ty {
+                // For scalar types we just copy the value directly
+                ColumnType::Bool
+                | ColumnType::U8
+                | ColumnType::I8
+                | ColumnType::U16
+                | ColumnType::U32
+                | ColumnType::U64
+                | ColumnType::Usize
+                | ColumnType::I16
+                | ColumnType::I32
+                | ColumnType::I64
+                | ColumnType::Isize
+                | ColumnType::F32
+                | ColumnType::F64
+                | ColumnType::Date
+                | ColumnType::Timestamp => src_value,
+
+                // Strings need their clone function called
+                ColumnType::String => {
+                    let clone_string = imports.string_clone(module, builder.func);
+                    builder.call_fn(clone_string, &[src_value])
+                }
+
+                // Arrays clone each of their elements into a new buffer
+                ColumnType::Array => {
+                    let vtable = nested_vtable(idx, row_layout, vtables);
+                    let [size, align, clone_into_slice] =
+                        array_args(&vtable, vtable.clone_into_slice, builder, module);
 
-        // Store the cloned value
-        builder.ins().store(dest_flags, cloned, dest, offset);
+                    let clone_array = imports.array_clone(module, builder.func);
+                    builder.call_fn(clone_array, &[src_value, size, align, clone_into_slice])
+                }
+
+                // Unit types and structs have been handled
+                ColumnType::Ptr | ColumnType::Unit | ColumnType::Struct => unreachable!(),
+            };
+
+            // Store the cloned value
+            builder.ins().store(dest_flags, cloned, dest, offset);
+        }
 
         if let Some(next_clone) = next_clone {
             builder.ins().jump(next_clone, &[]);
@@ -1,7 +1,7 @@
 use crate::{
     codegen::{
         utils::{normalize_float, FunctionBuilderExt},
-        vtable::column_non_null,
+        vtable::{column_non_null, nested_cmp, nested_eq, nested_vtable},
         Codegen, TRAP_NULL_PTR,
     },
     ir::{ColumnType, LayoutId},
@@ -57,13 +57,18 @@ impl Codegen {
             let are_equal = if layout.is_zero_sized() || row_layout.is_empty() {
                 builder.true_byte()
 
-            // If there's any strings then comparisons are non-trivial
-            } else if row_layout.columns().iter().any(ColumnType::is_string) {
+            // If there's any strings, structs or arrays then comparisons are non-trivial
+            } else if row_layout
+                .columns()
+                .iter()
+                .any(|ty| ty.is_string() || ty.is_nested())
+            {
                 let return_block = builder.create_block();
                 builder.append_block_params_for_function_returns(return_block);
 
                 // We compare the fields of the struct in an order determined by three criteria:
-                // - Whether or not it has a non-trivial comparison function (strings)
+                // - Whether or not it has a non-trivial comparison function (strings, structs
+                //   and arrays)
                 // - Whether or not it's nullable
                 // - Where it lies within the struct
                 // This allows us to do the trivial work (like comparing integers) before we
@@ -73,7 +78,8 @@ impl Codegen {
                 let mut fields: Vec<_> = (0..row_layout.len()).collect();
                 fields.sort_by_key(|&idx| {
                     (
-                        row_layout.columns()[idx].is_string(),
+                        row_layout.columns()[idx].is_string()
+                            || row_layout.columns()[idx].is_nested(),
                         row_layout.column_nullable(idx),
                         layout.offset_of(idx),
                     )
@@ -131,7 +137,14 @@ impl Codegen {
                     debug_assert!(!row_ty.is_unit());
 
                     // Load both values
-                    let (lhs, rhs) = {
+                    let (lhs, rhs) = if row_ty.is_struct() {
+                        // Structs are stored inline so we compare them by address
+                        let offset = layout.offset_of(idx) as i64;
+                        let lhs = builder.ins().iadd_imm(lhs, offset);
+                        let rhs = builder.ins().iadd_imm(rhs, offset);
+
+                        (lhs, rhs)
+                    } else {
                         let offset = layout.offset_of(idx) as i32;
                         let native_ty = layout
                             .type_of(idx)
@@ -184,6 +197,20 @@ impl Codegen {
                             builder.call_fn(string_eq, &[lhs, rhs])
                         }
 
+                        // Compare structs and arrays using their layout's vtable
+                        ColumnType::Struct | ColumnType::Array => {
+                            let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                            nested_eq(
+                                row_ty,
+                                &vtable,
+                                lhs,
+                                rhs,
+                                &mut builder,
+                                &mut imports,
+                                &mut self.module,
+                            )
+                        }
+
                         // Unit values have already been handled
                         ColumnType::Ptr | ColumnType::Unit => unreachable!(),
                     };
@@ -233,6 +260,67 @@ impl Codegen {
         func_id
     }
 
+    /// Generates a function returning `true` if any of the given layout's
+    /// columns are null
+    ///
+    /// Used to implement SQL join semantics where keys containing nulls never
+    /// match anything, including themselves
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn codegen_layout_has_nulls(&mut self, layout_id: LayoutId) -> FuncId {
+        tracing::info!("creating has_nulls function for {layout_id}");
+
+        // fn(*const u8) -> bool
+        let func_id = self.new_vtable_fn([self.module.isa().pointer_type()], Some(types::I8));
+
+        self.set_comment_writer(
+            &format!("{layout_id}_has_nulls"),
+            &format!(
+                "fn(*const {:?}) -> bool",
+                self.layout_cache.row_layout(layout_id),
+            ),
+        );
+
+        {
+            let mut builder =
+                FunctionBuilder::new(&mut self.module_ctx.func, &mut self.function_ctx);
+
+            // Create the entry block
+            let entry_block = builder.create_block();
+            builder.switch_to_block(entry_block);
+
+            // Add the function params as block params
+            builder.append_block_params_for_function_params(entry_block);
+
+            let (layout, row_layout) = self.layout_cache.get_layouts(layout_id);
+            let row = builder.block_params(entry_block)[0];
+
+            if self.config.debug_assertions {
+                builder.ins().trapz(row, TRAP_NULL_PTR);
+            }
+
+            // Or together the null flags of every nullable column
+            let mut has_nulls = builder.false_byte();
+            for column in 0..row_layout.len() {
+                if row_layout.column_nullable(column) {
+                    // Zero = value isn't null, non-zero = value is null
+                    let non_null = column_non_null(column, row, &layout, &mut builder, true);
+                    let is_null = builder.ins().icmp_imm(IntCC::NotEqual, non_null, 0);
+                    has_nulls = builder.ins().bor(has_nulls, is_null);
+                }
+            }
+
+            builder.ins().return_(&[has_nulls]);
+
+            // Finish building the function
+            builder.seal_all_blocks();
+            builder.finalize();
+        }
+
+        self.finalize_function(func_id);
+
+        func_id
+    }
+
     #[tracing::instrument(skip(self))]
     pub(super) fn codegen_layout_lt(&mut self, layout_id: LayoutId) -> FuncId {
         tracing::info!("creating lt vtable function for {layout_id}");
@@ -350,7 +438,14 @@ impl Codegen {
                     debug_assert!(!row_type.is_unit());
 
                     // Load each row's value
-                    let (lhs, rhs) = {
+                    let (lhs, rhs) = if row_type.is_struct() {
+                        // Structs are stored inline so we compare them by address
+                        let offset = layout.offset_of(idx) as i64;
+                        let lhs = builder.ins().iadd_imm(lhs, offset);
+                        let rhs = builder.ins().iadd_imm(rhs, offset);
+
+                        (lhs, rhs)
+                    } else {
                         let offset = layout.offset_of(idx) as i32;
                         let native_ty = layout
                             .type_of(idx)
@@ -400,6 +495,41 @@ impl Codegen {
                             let string_lt = imports.string_lt(&mut self.module, builder.func);
                             builder.call_fn(string_lt, &[lhs, rhs])
                         }
+
+                        // Structs and arrays are ordered by their three-way comparison, if
+                        // the lhs is greater than the rhs we return `false` immediately
+                        // instead of moving on to the next column
+                        ColumnType::Struct | ColumnType::Array => {
+                            let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                            let ordering = nested_cmp(
+                                row_type,
+                                &vtable,
+                                lhs,
+                                rhs,
+                                &mut builder,
+                                &mut imports,
+                                &mut self.module,
+                            );
+
+                            let not_greater = builder.create_block();
+                            let is_greater =
+                                builder
+                                    .ins()
+                                    .icmp_imm(IntCC::SignedGreaterThan, ordering, 0);
+                            let false_val = builder.false_byte();
+                            builder.ins().brif(
+                                is_greater,
+                                return_block,
+                                &[false_val],
+                                not_greater,
+                                &[],
+                            );
+
+                            builder.seal_current();
+                            builder.switch_to_block(not_greater);
+
+                            builder.ins().icmp_imm(IntCC::SignedLessThan, ordering, 0)
+                        }
                     };
 
                     let next = builder.create_block();
@@ -554,12 +684,18 @@ impl Codegen {
                         next_compare = after_compare;
                     }
 
-                    let native_ty = layout
-                        .type_of(idx)
-                        .native_type(&self.module.isa().frontend_config());
-
                     // Load the column's values
-                    let (lhs, rhs) = {
+                    let (lhs, rhs) = if row_type.is_struct() {
+                        // Structs are stored inline so we compare them by address
+                        let offset = layout.offset_of(idx) as i64;
+                        let lhs = builder.ins().iadd_imm(lhs, offset);
+                        let rhs = builder.ins().iadd_imm(rhs, offset);
+
+                        (lhs, rhs)
+                    } else {
+                        let native_ty = layout
+                            .type_of(idx)
+                            .native_type(&self.module.isa().frontend_config());
                         let offset = layout.offset_of(idx) as i32;
                         let flags = MemFlags::trusted().with_readonly();
 
@@ -677,6 +813,25 @@ impl Codegen {
                                 .brif(cmp, return_block, &[cmp], next_compare, &[]);
                         }
 
+                        ColumnType::Struct | ColumnType::Array => {
+                            let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+
+                            // -1 for less, 0 for equal, 1 for greater
+                            let cmp = nested_cmp(
+                                row_type,
+                                &vtable,
+                                lhs,
+                                rhs,
+                                &mut builder,
+                                &mut imports,
+                                &mut self.module,
+                            );
+
+                            builder
+                                .ins()
+                                .brif(cmp, return_block, &[cmp], next_compare, &[]);
+                        }
+
                         ColumnType::Ptr => unreachable!(),
                     }
 
@@ -1,5 +1,9 @@
 use crate::{
-    codegen::{utils::FunctionBuilderExt, vtable::column_non_null, Codegen, CodegenCtx},
+    codegen::{
+        utils::FunctionBuilderExt,
+        vtable::{array_args, column_non_null, nested_vtable},
+        Codegen, CodegenCtx,
+    },
     ir::{ColumnType, LayoutId},
 };
 use cranelift::prelude::{types, FunctionBuilder, InstBuilder, MemFlags};
@@ -26,7 +30,7 @@ impl Codegen {
         {
             let layout_cache = self.layout_cache.clone();
             let mut ctx = CodegenCtx::new(
-                self.config,
+                self.config.clone(),
                 &mut self.module,
                 &mut self.data_ctx,
                 &mut self.data,
@@ -139,13 +143,18 @@ impl Codegen {
                         // Load the value
                         let layout = ctx.layout_cache.layout_of(layout_id);
                         let offset = layout.offset_of(idx) as i32;
-                        let native_ty = layout
-                            .type_of(idx)
-                            .native_type(&ctx.module.isa().frontend_config());
-                        let flags = MemFlags::trusted().with_readonly();
-                        // TODO: We could take advantage of uload16/uload32/sload16/sload32 here
-                        // instead of uext/sext later on
-                        let mut value = builder.ins().load(native_ty, flags, ptr, offset);
+                        let mut value = if ty.is_struct() {
+                            // Structs are stored inline, so we debug them by address
+                            builder.ins().iadd_imm(ptr, offset as i64)
+                        } else {
+                            let native_ty = layout
+                                .type_of(idx)
+                                .native_type(&ctx.module.isa().frontend_config());
+                            let flags = MemFlags::trusted().with_readonly();
+                            // TODO: We could take advantage of uload16/uload32/sload16/sload32
+                            // here instead of uext/sext later on
+                            builder.ins().load(native_ty, flags, ptr, offset)
+                        };
 
                         if let Some(writer) = ctx.comment_writer.as_deref() {
                             let layout = ctx.layout_cache.row_layout(layout_id);
@@ -203,10 +212,25 @@ impl Codegen {
                                 ctx.imports.string_debug(ctx.module, builder.func)
                             }
 
+                            ColumnType::Struct => {
+                                let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                                ctx.module.declare_func_in_func(vtable.debug, builder.func)
+                            }
+                            ColumnType::Array => ctx.imports.array_debug(ctx.module, builder.func),
+
                             ColumnType::Ptr | ColumnType::Unit => unreachable!(),
                         };
 
-                        builder.call_fn(debug_fn, &[value, fmt])
+                        if ty.is_array() {
+                            // Arrays debug each of their elements with the element layout's
+                            // debug function
+                            let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                            let [size, align, debug] =
+                                array_args(&vtable, vtable.debug, &mut builder, ctx.module);
+                            builder.call_fn(debug_fn, &[value, fmt, size, align, debug])
+                        } else {
+                            builder.call_fn(debug_fn, &[value, fmt])
+                        }
                     };
 
                     // If writing the value failed, return an error
@@ -21,7 +21,7 @@ impl Codegen {
 
         {
             let ctx = CodegenCtx::new(
-                self.config,
+                self.config.clone(),
                 &mut self.module,
                 &mut self.data_ctx,
                 &mut self.data,
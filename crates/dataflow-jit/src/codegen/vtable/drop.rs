@@ -1,13 +1,16 @@
 use crate::{
     codegen::{
-        intrinsics::ImportIntrinsics, utils::FunctionBuilderExt, vtable::column_non_null, Codegen,
-        NativeLayout, TRAP_NULL_PTR,
+        intrinsics::ImportIntrinsics,
+        utils::FunctionBuilderExt,
+        vtable::{array_args, column_non_null, nested_vtable, LayoutVTable},
+        Codegen, NativeLayout, TRAP_NULL_PTR,
     },
     ir::{ColumnType, LayoutId, RowLayout},
 };
 use cranelift::prelude::{FunctionBuilder, InstBuilder, IntCC, MemFlags, Value};
 use cranelift_jit::JITModule;
 use cranelift_module::{FuncId, Module};
+use std::collections::BTreeMap;
 
 impl Codegen {
     #[tracing::instrument(skip(self))]
@@ -36,6 +39,7 @@ impl Codegen {
                     ptr,
                     &layout,
                     &row_layout,
+                    &self.vtables,
                     &mut builder,
                     &mut imports,
                     &mut self.module,
@@ -128,6 +132,7 @@ impl Codegen {
                     ptr,
                     &layout,
                     &row_layout,
+                    &self.vtables,
                     &mut builder,
                     &mut imports,
                     &mut self.module,
@@ -158,6 +163,7 @@ fn drop_layout(
     ptr: Value,
     layout: &NativeLayout,
     row_layout: &RowLayout,
+    vtables: &BTreeMap<LayoutId, LayoutVTable>,
     builder: &mut FunctionBuilder,
     imports: &mut ImportIntrinsics,
     module: &mut JITModule,
@@ -167,40 +173,64 @@ fn drop_layout(
         .enumerate()
         .filter(|(_, (ty, _))| ty.needs_drop())
     {
-        // Strings are the only thing that need dropping right now
-        debug_assert_eq!(ty, ColumnType::String);
-
         let next_drop = if nullable {
-            // Zero = string isn't null, non-zero = string is null
-            let string_null = column_non_null(idx, ptr, layout, builder, false);
+            // Zero = value isn't null, non-zero = value is null
+            let value_null = column_non_null(idx, ptr, layout, builder, false);
 
-            // If the string is null, jump to the `next_drop` block and don't drop
-            // the current string. Otherwise (if the string isn't null) drop it and
+            // If the value is null, jump to the `next_drop` block and don't drop
+            // the current value. Otherwise (if the value isn't null) drop it and
             // then continue dropping any other fields
-            let drop_string = builder.create_block();
+            let drop_value = builder.create_block();
             let next_drop = builder.create_block();
             builder
                 .ins()
-                .brif(string_null, next_drop, &[], drop_string, &[]);
+                .brif(value_null, next_drop, &[], drop_value, &[]);
 
-            builder.switch_to_block(drop_string);
+            builder.switch_to_block(drop_value);
 
             Some(next_drop)
         } else {
             None
         };
 
-        // Load the string
         let offset = layout.offset_of(idx) as i32;
-        let native_ty = layout
-            .type_of(idx)
-            .native_type(&module.isa().frontend_config());
-        let flags = MemFlags::trusted();
-        let string = builder.ins().load(native_ty, flags, ptr, offset);
-
-        // Drop the string
-        let string_drop_in_place = imports.string_drop_in_place(module, builder.func);
-        builder.ins().call(string_drop_in_place, &[string]);
+        match ty {
+            // Structs are stored inline, so they're dropped in place by their own
+            // drop function
+            ColumnType::Struct => {
+                let vtable = nested_vtable(idx, row_layout, vtables);
+                let drop_in_place = module.declare_func_in_func(vtable.drop_in_place, builder.func);
+
+                let value = builder.ins().iadd_imm(ptr, offset as i64);
+                builder.ins().call(drop_in_place, &[value]);
+            }
+
+            ColumnType::String | ColumnType::Array => {
+                // Load the string or array
+                let ptr_ty = module.isa().pointer_type();
+                let value = builder.ins().load(ptr_ty, MemFlags::trusted(), ptr, offset);
+
+                if ty.is_string() {
+                    // Drop the string
+                    let string_drop_in_place = imports.string_drop_in_place(module, builder.func);
+                    builder.ins().call(string_drop_in_place, &[value]);
+                } else {
+                    // Drop the array's elements and then deallocate it
+                    let vtable = nested_vtable(idx, row_layout, vtables);
+                    let [size, align, drop_slice_in_place] =
+                        array_args(&vtable, vtable.drop_slice_in_place, builder, module);
+
+                    let array_drop_in_place = imports.array_drop_in_place(module, builder.func);
+                    builder.ins().call(
+                        array_drop_in_place,
+                        &[value, size, align, drop_slice_in_place],
+                    );
+                }
+            }
+
+            // Strings, structs and arrays are the only things that need dropping right now
+            _ => unreachable!(),
+        }
 
         if let Some(next_drop) = next_drop {
             builder.ins().jump(next_drop, &[]);
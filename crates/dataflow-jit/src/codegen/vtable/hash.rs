@@ -1,5 +1,9 @@
 use crate::{
-    codegen::{utils::FunctionBuilderExt, vtable::column_non_null, Codegen, CodegenCtx},
+    codegen::{
+        utils::FunctionBuilderExt,
+        vtable::{array_args, column_non_null, nested_vtable},
+        Codegen, CodegenCtx,
+    },
     ir::{ColumnType, LayoutId},
 };
 use cranelift::prelude::{types, FunctionBuilder, InstBuilder, IntCC, MemFlags};
@@ -24,7 +28,7 @@ impl Codegen {
 
         {
             let ctx = CodegenCtx::new(
-                self.config,
+                self.config.clone(),
                 &mut self.module,
                 &mut self.data_ctx,
                 &mut self.data,
@@ -89,7 +93,10 @@ impl Codegen {
 
                     // Load the source value
                     let flags = MemFlags::trusted().with_readonly();
-                    let value = if !native_ty.is_float() {
+                    let value = if ty.is_struct() {
+                        // Structs are stored inline, so we hash them by address
+                        builder.ins().iadd_imm(ptr, offset as i64)
+                    } else if !native_ty.is_float() {
                         builder.ins().load(native_ty, flags, ptr, offset)
 
                     // If total float comparisons are enabled, we normalize the
@@ -146,9 +153,26 @@ impl Codegen {
                         ColumnType::F32 => imports.u32_hash(ctx.module, builder.func),
                         ColumnType::F64 => imports.u64_hash(ctx.module, builder.func),
                         ColumnType::String => imports.string_hash(ctx.module, builder.func),
+                        ColumnType::Struct => {
+                            let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                            ctx.module.declare_func_in_func(vtable.hash, builder.func)
+                        }
+                        ColumnType::Array => imports.array_hash(ctx.module, builder.func),
                         ColumnType::Ptr | ColumnType::Unit => unreachable!(),
                     };
-                    builder.ins().call(hash_function, &[hasher, value]);
+
+                    if ty.is_array() {
+                        // Arrays hash each of their elements with the element layout's hash
+                        // function
+                        let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                        let [size, align, hash] =
+                            array_args(&vtable, vtable.hash, &mut builder, ctx.module);
+                        builder
+                            .ins()
+                            .call(hash_function, &[hasher, value, size, align, hash]);
+                    } else {
+                        builder.ins().call(hash_function, &[hasher, value]);
+                    }
 
                     if let Some(next_clone) = next_hash {
                         builder.ins().jump(next_clone, &[]);
@@ -7,8 +7,11 @@ mod hash;
 mod tests;
 
 use crate::{
-    codegen::{Codegen, CodegenCtx, NativeLayout, NativeType},
-    ir::{ColumnType, LayoutId},
+    codegen::{
+        intrinsics::ImportIntrinsics, utils::FunctionBuilderExt, Codegen, CodegenCtx, NativeLayout,
+        NativeType,
+    },
+    ir::{ColumnType, LayoutId, RowLayout},
 };
 use cranelift::{
     codegen::ir::UserFuncName,
@@ -22,6 +25,7 @@ use dbsp::trace::layers::erased::ErasedVTable;
 use std::{
     any::TypeId,
     cmp::Ordering,
+    collections::BTreeMap,
     fmt::{self, Debug},
     hash::Hasher,
     mem::align_of,
@@ -100,6 +104,17 @@ macro_rules! vtable {
                 }
 
                 fn make_vtable_for(&mut self, layout_id: LayoutId) -> LayoutVTable {
+                    // Struct and array columns call into the vtables of the layouts
+                    // they contain, so those have to be generated first
+                    let nested: Vec<_> = self
+                        .layout_cache
+                        .row_layout(layout_id)
+                        .nested_layouts()
+                        .collect();
+                    for nested in nested {
+                        self.vtable_for(nested);
+                    }
+
                     let (size_of, align_of) = {
                         let layout = self.layout_cache.layout_of(layout_id);
                         (
@@ -219,7 +234,7 @@ impl Codegen {
 
         {
             let mut ctx = CodegenCtx::new(
-                self.config,
+                self.config.clone(),
                 &mut self.module,
                 &mut self.data_ctx,
                 &mut self.data,
@@ -281,7 +296,7 @@ impl Codegen {
 
         {
             let mut ctx = CodegenCtx::new(
-                self.config,
+                self.config.clone(),
                 &mut self.module,
                 &mut self.data_ctx,
                 &mut self.data,
@@ -310,49 +325,86 @@ impl Codegen {
                 &mut builder,
             );
 
-            if row_layout.columns().iter().any(ColumnType::is_string) {
+            if row_layout
+                .columns()
+                .iter()
+                .any(|ty| ty.is_string() || ty.is_nested())
+            {
                 for (idx, (ty, nullable)) in row_layout
                     .iter()
                     .enumerate()
-                    // Strings are the only thing that have children sizes right now
-                    .filter(|(_, (ty, _))| ty.is_string())
+                    // Strings, structs and arrays are the only things that have children sizes
+                    // right now
+                    .filter(|(_, (ty, _))| ty.is_string() || ty.is_nested())
                 {
-                    debug_assert_eq!(ty, ColumnType::String);
-
                     let next_size_of = if nullable {
-                        // Zero = string isn't null, non-zero = string is null
-                        let string_null = column_non_null(idx, ptr, &layout, &mut builder, true);
+                        // Zero = value isn't null, non-zero = value is null
+                        let value_null = column_non_null(idx, ptr, &layout, &mut builder, true);
 
-                        // If the string is null, jump to the `next_size_of` block and don't
-                        // get the size of the current string (since it's null). Otherwise
-                        // (if the string isn't null) get its size and then continue recording
+                        // If the value is null, jump to the `next_size_of` block and don't
+                        // get the size of the current value (since it's null). Otherwise
+                        // (if the value isn't null) get its size and then continue recording
                         // any other fields
-                        let size_of_string = builder.create_block();
+                        let size_of_value = builder.create_block();
                         let next_size_of = builder.create_block();
                         builder
                             .ins()
-                            .brif(string_null, next_size_of, &[], size_of_string, &[]);
+                            .brif(value_null, next_size_of, &[], size_of_value, &[]);
 
-                        builder.switch_to_block(size_of_string);
+                        builder.switch_to_block(size_of_value);
 
                         Some(next_size_of)
                     } else {
                         None
                     };
 
-                    // Load the string
                     let offset = layout.offset_of(idx) as i32;
-                    let native_ty = layout.type_of(idx).native_type(&ctx.frontend_config());
-                    let flags = MemFlags::trusted().with_readonly();
-                    let string = builder.ins().load(native_ty, flags, ptr, offset);
-
-                    // Get the size of the string's children
-                    let string_size_of_children = ctx
-                        .imports
-                        .string_size_of_children(ctx.module, builder.func);
-                    builder
-                        .ins()
-                        .call(string_size_of_children, &[string, context]);
+                    match ty {
+                        // Structs are stored inline, so we pass their address to their own
+                        // size_of_children function
+                        ColumnType::Struct => {
+                            let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                            let size_of_children = ctx
+                                .module
+                                .declare_func_in_func(vtable.size_of_children, builder.func);
+                            let value = builder.ins().iadd_imm(ptr, offset as i64);
+                            builder.ins().call(size_of_children, &[value, context]);
+                        }
+
+                        ColumnType::String | ColumnType::Array => {
+                            // Load the string or array
+                            let ptr_ty = ctx.pointer_type();
+                            let flags = MemFlags::trusted().with_readonly();
+                            let value = builder.ins().load(ptr_ty, flags, ptr, offset);
+
+                            if ty.is_string() {
+                                // Get the size of the string's children
+                                let string_size_of_children = ctx
+                                    .imports
+                                    .string_size_of_children(ctx.module, builder.func);
+                                builder
+                                    .ins()
+                                    .call(string_size_of_children, &[value, context]);
+                            } else {
+                                // Get the size of the array's buffer and its elements' children
+                                let vtable = nested_vtable(idx, &row_layout, &self.vtables);
+                                let [size, align, size_of_children] = array_args(
+                                    &vtable,
+                                    vtable.size_of_children,
+                                    &mut builder,
+                                    ctx.module,
+                                );
+                                let array_size_of_children =
+                                    ctx.imports.array_size_of_children(ctx.module, builder.func);
+                                builder.ins().call(
+                                    array_size_of_children,
+                                    &[value, context, size, align, size_of_children],
+                                );
+                            }
+                        }
+
+                        _ => unreachable!(),
+                    }
 
                     if let Some(next_drop) = next_size_of {
                         builder.ins().jump(next_drop, &[]);
@@ -417,3 +469,82 @@ fn column_non_null(
         builder.ins().band_imm(bitset, 1i64 << bit_idx)
     }
 }
+
+/// Returns the vtable of the layout contained within the given struct or array
+/// column
+fn nested_vtable(
+    column: usize,
+    row_layout: &RowLayout,
+    vtables: &BTreeMap<LayoutId, LayoutVTable>,
+) -> LayoutVTable {
+    let nested = row_layout
+        .nested_layout(column)
+        .expect("struct and array columns always have a nested layout");
+    vtables[&nested]
+}
+
+/// Creates the trailing arguments taken by the array intrinsics, the size and
+/// alignment of the array's elements followed by the address of `func`, the
+/// element layout's vtable function that gets called on each element
+fn array_args(
+    vtable: &LayoutVTable,
+    func: FuncId,
+    builder: &mut FunctionBuilder,
+    module: &mut JITModule,
+) -> [ClifValue; 3] {
+    let ptr_ty = module.isa().pointer_type();
+    let size = builder.ins().iconst(ptr_ty, vtable.size_of as i64);
+    let align = builder.ins().iconst(ptr_ty, vtable.align_of.get() as i64);
+
+    let func = module.declare_func_in_func(func, builder.func);
+    let func = builder.ins().func_addr(ptr_ty, func);
+
+    [size, align, func]
+}
+
+/// Compares two structs or arrays for equality, `lhs` and `rhs` are the
+/// addresses of the structs or the pointers held by the array columns
+fn nested_eq(
+    ty: ColumnType,
+    vtable: &LayoutVTable,
+    lhs: ClifValue,
+    rhs: ClifValue,
+    builder: &mut FunctionBuilder,
+    imports: &mut ImportIntrinsics,
+    module: &mut JITModule,
+) -> ClifValue {
+    if ty.is_struct() {
+        let eq = module.declare_func_in_func(vtable.eq, builder.func);
+        builder.call_fn(eq, &[lhs, rhs])
+    } else {
+        debug_assert!(ty.is_array());
+
+        let [size, align, eq] = array_args(vtable, vtable.eq, builder, module);
+        let array_eq = imports.array_eq(module, builder.func);
+        builder.call_fn(array_eq, &[lhs, rhs, size, align, eq])
+    }
+}
+
+/// Compares two structs or arrays, returning -1 for less, 0 for equal and 1 for
+/// greater. `lhs` and `rhs` are the addresses of the structs or the pointers
+/// held by the array columns
+fn nested_cmp(
+    ty: ColumnType,
+    vtable: &LayoutVTable,
+    lhs: ClifValue,
+    rhs: ClifValue,
+    builder: &mut FunctionBuilder,
+    imports: &mut ImportIntrinsics,
+    module: &mut JITModule,
+) -> ClifValue {
+    if ty.is_struct() {
+        let cmp = module.declare_func_in_func(vtable.cmp, builder.func);
+        builder.call_fn(cmp, &[lhs, rhs])
+    } else {
+        debug_assert!(ty.is_array());
+
+        let [size, align, cmp] = array_args(vtable, vtable.cmp, builder, module);
+        let array_cmp = imports.array_cmp(module, builder.func);
+        builder.call_fn(array_cmp, &[lhs, rhs, size, align, cmp])
+    }
+}
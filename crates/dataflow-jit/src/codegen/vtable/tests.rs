@@ -840,3 +840,181 @@ mod proptests {
         ],
     }
 }
+
+mod nested {
+    use crate::{
+        codegen::{Codegen, CodegenConfig},
+        dataflow::{row_from_literal, row_to_literal},
+        ir::{
+            literal::{NullableConstant, RowLiteral},
+            ColumnType, Constant, LayoutId, RowLayoutBuilder, RowLayoutCache,
+        },
+        row::Row,
+    };
+    use proptest::{
+        collection::vec, option, prelude::any, prop_assert_eq, proptest, strategy::Strategy,
+        test_runner::TestCaseResult,
+    };
+    use size_of::SizeOf;
+    use std::{
+        cmp::Ordering,
+        collections::hash_map::DefaultHasher,
+        hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
+    };
+
+    /// Creates the layouts `{ i32, str? }`, `{ u8, struct inner, array? inner }`
+    /// and `{ struct? middle, bool, array middle }`, returning the outermost one
+    fn nested_layout(cache: &RowLayoutCache) -> LayoutId {
+        let inner = cache.add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, false)
+                .with_column(ColumnType::String, true)
+                .build(),
+        );
+        let middle = cache.add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U8, false)
+                .with_struct_column(inner, false)
+                .with_array_column(inner, true)
+                .build(),
+        );
+
+        cache.add(
+            RowLayoutBuilder::new()
+                .with_struct_column(middle, true)
+                .with_column(ColumnType::Bool, false)
+                .with_array_column(middle, false)
+                .build(),
+        )
+    }
+
+    // Values are drawn from small ranges so that rows often share prefixes,
+    // giving comparisons a chance to reach the nested columns
+    fn inner() -> impl Strategy<Value = RowLiteral> {
+        (0..3i32, option::of("[ab]{0,2}")).prop_map(|(int, string)| {
+            RowLiteral::new(vec![
+                NullableConstant::NonNull(Constant::I32(int)),
+                NullableConstant::Nullable(string.map(Constant::String)),
+            ])
+        })
+    }
+
+    fn middle() -> impl Strategy<Value = RowLiteral> {
+        (0..3u8, inner(), option::of(vec(inner(), 0..4))).prop_map(|(int, inner, array)| {
+            RowLiteral::new(vec![
+                NullableConstant::NonNull(Constant::U8(int)),
+                NullableConstant::NonNull(Constant::Struct(inner)),
+                NullableConstant::Nullable(array.map(Constant::Array)),
+            ])
+        })
+    }
+
+    fn outer() -> impl Strategy<Value = RowLiteral> {
+        (option::of(middle()), any::<bool>(), vec(middle(), 0..4)).prop_map(
+            |(middle, bool, array)| {
+                RowLiteral::new(vec![
+                    NullableConstant::Nullable(middle.map(Constant::Struct)),
+                    NullableConstant::NonNull(Constant::Bool(bool)),
+                    NullableConstant::NonNull(Constant::Array(array)),
+                ])
+            },
+        )
+    }
+
+    /// The expected ordering of two rows, nulls are greater than all non-null
+    /// values and arrays are ordered lexicographically by their elements
+    fn cmp_literals(lhs: &RowLiteral, rhs: &RowLiteral) -> Ordering {
+        lhs.rows()
+            .iter()
+            .zip(rhs.rows())
+            .map(|(lhs, rhs)| match (lhs, rhs) {
+                (NullableConstant::NonNull(lhs), NullableConstant::NonNull(rhs))
+                | (NullableConstant::Nullable(Some(lhs)), NullableConstant::Nullable(Some(rhs))) => {
+                    cmp_constants(lhs, rhs)
+                }
+                (NullableConstant::Nullable(lhs), NullableConstant::Nullable(rhs)) => {
+                    lhs.is_none().cmp(&rhs.is_none())
+                }
+                _ => unreachable!(),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    fn cmp_constants(lhs: &Constant, rhs: &Constant) -> Ordering {
+        match (lhs, rhs) {
+            (Constant::Struct(lhs), Constant::Struct(rhs)) => cmp_literals(lhs, rhs),
+            (Constant::Array(lhs), Constant::Array(rhs)) => lhs
+                .iter()
+                .zip(rhs)
+                .map(|(lhs, rhs)| cmp_literals(lhs, rhs))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| lhs.len().cmp(&rhs.len())),
+            (lhs, rhs) => lhs.cmp(rhs),
+        }
+    }
+
+    fn test_nested(lhs: RowLiteral, rhs: RowLiteral, debug: bool) -> TestCaseResult {
+        let cache = RowLayoutCache::new();
+        let layout_id = nested_layout(&cache);
+
+        let config = if debug {
+            CodegenConfig::debug()
+        } else {
+            CodegenConfig::release()
+        };
+        let mut codegen = Codegen::new(cache, config);
+        let vtable = codegen.vtable_for(layout_id);
+
+        let (jit, cache) = codegen.finalize_definitions();
+        let vtable = Box::into_raw(Box::new(vtable.marshalled(&jit)));
+
+        let lhs_row = unsafe { row_from_literal(&lhs, &*vtable, &cache) };
+        let rhs_row = unsafe { row_from_literal(&rhs, &*vtable, &cache) };
+        prop_assert_eq!(&row_to_literal(&lhs_row, &cache), &lhs);
+        prop_assert_eq!(&row_to_literal(&rhs_row, &cache), &rhs);
+
+        // Clones should be equal to the original row and own all of their data
+        let clone = lhs_row.clone();
+        prop_assert_eq!(&lhs_row, &clone);
+        prop_assert_eq!(lhs_row.cmp(&clone), Ordering::Equal);
+        prop_assert_eq!(&row_to_literal(&clone, &cache), &lhs);
+
+        let builder = BuildHasherDefault::<DefaultHasher>::default();
+        let hash = |row: &Row| {
+            let mut hasher = builder.build_hasher();
+            row.hash(&mut hasher);
+            hasher.finish()
+        };
+        prop_assert_eq!(hash(&lhs_row), hash(&clone));
+
+        let expected = cmp_literals(&lhs, &rhs);
+        prop_assert_eq!(lhs_row.cmp(&rhs_row), expected);
+        prop_assert_eq!(rhs_row.cmp(&lhs_row), expected.reverse());
+        prop_assert_eq!(lhs_row == rhs_row, expected.is_eq());
+
+        // Dropping the original shouldn't affect the clone
+        drop(lhs_row);
+        prop_assert_eq!(&row_to_literal(&clone, &cache), &lhs);
+
+        // TODO: Assert that these are correct
+        let _debug = format!("{clone:?}");
+        let _size_of = clone.size_of();
+
+        unsafe {
+            drop(rhs_row);
+            drop(clone);
+            drop(Box::from_raw(vtable));
+            jit.free_memory();
+        }
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn nested_vtables(lhs in outer(), rhs in outer(), debug in any::<bool>()) {
+            test_nested(lhs, rhs, debug)?;
+        }
+    }
+}
@@ -1,9 +1,14 @@
 mod nodes;
 mod operators;
+mod statistics;
 mod tests;
 
+pub use statistics::{ColumnStatistics, DataflowStatistics, HyperLogLog, StreamStatistics};
+
 use crate::{
-    codegen::{Codegen, CodegenConfig, LayoutVTable, NativeLayout, NativeLayoutCache, VTable},
+    codegen::{
+        isa_string, ArrayLayout, Codegen, CodegenConfig, LayoutVTable, NativeLayoutCache, VTable,
+    },
     dataflow::nodes::{
         Antijoin, DataflowSubgraph, DelayedFeedback, Delta0, Differentiate, Distinct, Export,
         FilterFn, FilterMap, FilterMapIndex, FlatMap, FlatMapFn, Fold, Integrate, JoinCore, MapFn,
@@ -11,15 +16,18 @@ use crate::{
     },
     ir::{
         graph,
-        literal::{NullableConstant, RowLiteral, StreamCollection},
-        nodes::{DataflowNode as _, Node, StreamKind, StreamLayout, Subgraph as SubgraphNode},
-        Constant, Graph, GraphExt, LayoutId, NodeId,
+        literal::{NullableConstant, RowLiteral, StreamCollection, StreamLiteral},
+        nodes::{
+            DataflowNode as _, Node, SinkOutputMode, StreamKind, StreamLayout,
+            Subgraph as SubgraphNode,
+        },
+        ColumnType, Constant, Graph, GraphExt, LayoutId, NodeId,
     },
-    row::{Row, UninitRow},
+    row::{self, Row, UninitRow},
     ThinStr,
 };
 use cranelift_jit::JITModule;
-use cranelift_module::FuncId;
+use cranelift_module::{FuncId, Module};
 use dbsp::{
     algebra::UnimplementedSemigroup,
     operator::{FilterMap as _, Generator},
@@ -29,10 +37,20 @@ use dbsp::{
 };
 use derive_more::{IsVariant, Unwrap};
 use nodes::{
-    DataflowNode, Filter, IndexWith, Map, MonotonicJoin, Neg, Sink, Source, SourceMap, Sum,
+    CollectStatistics, DataflowNode, Filter, IndexWith, Map, MonotonicJoin, Neg, Sink, Source,
+    SourceMap, Sum,
+};
+use petgraph::prelude::DiGraphMap;
+use statistics::StatisticsLayout;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    iter,
+    mem::{self, transmute, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::Arc,
 };
-use petgraph::{algo, prelude::DiGraphMap};
-use std::{collections::BTreeMap, iter, mem::transmute, ptr::NonNull};
 
 // TODO: Keep layout ids in dataflow nodes so we can do assertions that types
 // are correct
@@ -45,12 +63,12 @@ type Outputs = BTreeMap<NodeId, RowOutput>;
 
 #[derive(Clone, IsVariant, Unwrap)]
 pub enum RowInput {
-    Set(CollectionHandle<Row, i32>),
-    Map(CollectionHandle<Row, (Row, i32)>),
+    Set(JitOwned<CollectionHandle<Row, i32>>),
+    Map(JitOwned<CollectionHandle<Row, (Row, i32)>>),
 }
 
 impl RowInput {
-    pub fn as_set_mut(&mut self) -> Option<&mut CollectionHandle<Row, i32>> {
+    pub fn as_set_mut(&mut self) -> Option<&mut JitOwned<CollectionHandle<Row, i32>>> {
         if let Self::Set(handle) = self {
             Some(handle)
         } else {
@@ -58,7 +76,7 @@ impl RowInput {
         }
     }
 
-    pub fn as_map_mut(&mut self) -> Option<&mut CollectionHandle<Row, (Row, i32)>> {
+    pub fn as_map_mut(&mut self) -> Option<&mut JitOwned<CollectionHandle<Row, (Row, i32)>>> {
         if let Self::Map(handle) = self {
             Some(handle)
         } else {
@@ -69,12 +87,12 @@ impl RowInput {
 
 #[derive(Clone)]
 pub enum RowOutput {
-    Set(OutputHandle<RowSet>),
-    Map(OutputHandle<RowMap>),
+    Set(JitOwned<OutputHandle<RowSet>>),
+    Map(JitOwned<OutputHandle<RowMap>>),
 }
 
 impl RowOutput {
-    pub const fn as_set(&self) -> Option<&OutputHandle<RowSet>> {
+    pub const fn as_set(&self) -> Option<&JitOwned<OutputHandle<RowSet>>> {
         if let Self::Set(handle) = self {
             Some(handle)
         } else {
@@ -82,7 +100,7 @@ impl RowOutput {
         }
     }
 
-    pub fn as_set_mut(&mut self) -> Option<&mut OutputHandle<RowSet>> {
+    pub fn as_set_mut(&mut self) -> Option<&mut JitOwned<OutputHandle<RowSet>>> {
         if let Self::Set(handle) = self {
             Some(handle)
         } else {
@@ -90,7 +108,7 @@ impl RowOutput {
         }
     }
 
-    pub const fn as_map(&self) -> Option<&OutputHandle<RowMap>> {
+    pub const fn as_map(&self) -> Option<&JitOwned<OutputHandle<RowMap>>> {
         if let Self::Map(handle) = self {
             Some(handle)
         } else {
@@ -98,7 +116,7 @@ impl RowOutput {
         }
     }
 
-    pub fn as_map_mut(&mut self) -> Option<&mut OutputHandle<RowMap>> {
+    pub fn as_map_mut(&mut self) -> Option<&mut JitOwned<OutputHandle<RowMap>>> {
         if let Self::Map(handle) = self {
             Some(handle)
         } else {
@@ -144,30 +162,178 @@ pub enum RowZSet {
     Map(RowMap),
 }
 
-pub struct JitHandle {
-    jit: JITModule,
+/// The memory backing jit compiled functions and vtables, it's freed once the
+/// last reference to it is dropped
+struct JitMemory {
+    jit: ManuallyDrop<JITModule>,
     vtables: BTreeMap<LayoutId, *mut VTable>,
+    function_symbols: Vec<String>,
+}
+
+// Safety: The module and vtables are never mutated while shared and are only
+// freed once no references to them remain
+unsafe impl Send for JitMemory {}
+unsafe impl Sync for JitMemory {}
+
+impl Debug for JitMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JitMemory")
+            .field("vtables", &self.vtables)
+            .field("function_symbols", &self.function_symbols)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for JitMemory {
+    fn drop(&mut self) {
+        // Safety: This is the last reference to the memory so nothing can still
+        // be using the vtables or functions
+        unsafe {
+            for &vtable in self.vtables.values() {
+                drop(Box::from_raw(vtable));
+            }
+
+            ManuallyDrop::take(&mut self.jit).free_memory();
+        }
+    }
+}
+
+/// A value that refers to a dataflow's jit compiled code, along with a
+/// reference that keeps the code alive for as long as the value is
+///
+/// The input and output handles of a circuit constructed from a
+/// [`CompiledDataflow`] are wrapped in a `JitOwned`, since they can hold rows
+/// using the compiled vtables after the circuit has been dropped, as are the
+/// batches read out of the output handles. The wrapped value can be accessed
+/// through [`Deref`] and [`DerefMut`]
+pub struct JitOwned<T> {
+    value: T,
+    // Dropped after `value`, so the compiled code outlives the rows within it
+    jit: Arc<JitMemory>,
+}
+
+impl<T> JitOwned<T> {
+    fn new(value: T, jit: Arc<JitMemory>) -> Self {
+        Self { value, jit }
+    }
+}
+
+impl<T> Deref for JitOwned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for JitOwned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Clone for JitOwned<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone(), self.jit.clone())
+    }
+}
+
+impl<T> Debug for JitOwned<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.value, f)
+    }
+}
+
+impl<T> PartialEq<T> for JitOwned<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &T) -> bool {
+        self.value == *other
+    }
+}
+
+impl<T> JitOwned<OutputHandle<T>>
+where
+    T: Clone + Send + 'static,
+{
+    /// Reads the value produced by `worker` during the last clock cycle, see
+    /// [`OutputHandle::take_from_worker()`]
+    pub fn take_from_worker(&self, worker: usize) -> Option<JitOwned<T>> {
+        self.value
+            .take_from_worker(worker)
+            .map(|value| JitOwned::new(value, self.jit.clone()))
+    }
+
+    /// Reads the values produced by all workers during the last clock cycle,
+    /// see [`OutputHandle::take_from_all()`]
+    pub fn take_from_all(&self) -> Vec<JitOwned<T>> {
+        self.value
+            .take_from_all()
+            .into_iter()
+            .map(|value| JitOwned::new(value, self.jit.clone()))
+            .collect()
+    }
+}
+
+impl<T> JitOwned<OutputHandle<T>>
+where
+    T: Batch<Time = ()> + Send,
+{
+    /// Reads the batches produced by all workers during the last clock cycle
+    /// and consolidates them, see [`OutputHandle::consolidate()`]
+    pub fn consolidate(&self) -> JitOwned<T> {
+        JitOwned::new(self.value.consolidate(), self.jit.clone())
+    }
+}
+
+/// A handle to the jit compiled code of a dataflow
+///
+/// The compiled code is reference counted, the handle, every
+/// [`CompiledDataflow`], every circuit constructed from one and each of the
+/// circuit's input and output handles (see [`JitOwned`]) hold a reference and
+/// the code is only freed once all of them have been dropped. Rows created by
+/// the caller using [`JitHandle::vtables()`] must be dropped or passed to an
+/// input handle before the last of them is dropped
+pub struct JitHandle {
+    memory: Arc<JitMemory>,
 }
 
 impl JitHandle {
     pub fn vtables(&self) -> &BTreeMap<LayoutId, *mut VTable> {
-        &self.vtables
+        &self.memory.vtables
     }
 
-    /// Free all memory associated with the JIT compiled code, including vtables
-    /// and the functions themselves
-    ///
-    /// # Safety
+    /// Returns the symbols of the dataflow's compiled functions in the order
+    /// they were generated in
+    pub fn function_symbols(&self) -> &[String] {
+        &self.memory.function_symbols
+    }
+
+    /// Returns a string identifying the instruction set the dataflow was
+    /// compiled for, see [`Codegen::isa_string()`]
+    pub fn isa_string(&self) -> String {
+        isa_string(self.memory.jit.isa())
+    }
+
+    /// Releases the handle's reference to the jit compiled code, the code is
+    /// freed once every dataflow and circuit using it has also been dropped
     ///
-    /// Cannot call this while any vtable or function pointers are still live or
-    /// in use, any attempt to use them after calling this function is UB
-    pub unsafe fn free_memory(mut self) {
-        for &vtable in self.vtables.values() {
-            drop(Box::from_raw(vtable));
-        }
+    /// This is equivalent to dropping the handle
+    pub fn free_memory(self) {
+        drop(self);
+    }
 
-        self.vtables.clear();
-        self.jit.free_memory();
+    /// Leaks the jit compiled code so that it's never freed, even after every
+    /// dataflow and circuit using it has been dropped
+    pub fn leak(self) {
+        mem::forget(self.memory);
     }
 }
 
@@ -175,6 +341,9 @@ impl JitHandle {
 pub struct CompiledDataflow {
     nodes: BTreeMap<NodeId, DataflowNode>,
     edges: DiGraphMap<NodeId, ()>,
+    /// Keeps the compiled functions used by `nodes` alive
+    jit: Arc<JitMemory>,
+    statistics: DataflowStatistics,
 }
 
 impl CompiledDataflow {
@@ -183,7 +352,7 @@ impl CompiledDataflow {
         let mut node_streams: BTreeMap<NodeId, Option<_>> = BTreeMap::new();
 
         let (mut inputs, mut input_nodes) = (Vec::with_capacity(16), Vec::with_capacity(16));
-        let order = algo::toposort(graph.edges(), None).unwrap();
+        let order = graph::toposort(graph.edges()).unwrap();
         for node_id in order {
             if !graph.nodes().contains_key(&node_id) {
                 continue;
@@ -219,7 +388,7 @@ impl CompiledDataflow {
             node_kinds: &mut BTreeMap<NodeId, Option<StreamKind>>,
             node_streams: &mut BTreeMap<NodeId, Option<StreamLayout>>,
         ) {
-            let order = algo::toposort(graph.edges(), None).unwrap();
+            let order = graph::toposort(graph.edges()).unwrap();
             for node_id in order {
                 if graph.input_nodes().contains_key(&node_id) {
                     continue;
@@ -372,7 +541,20 @@ impl CompiledDataflow {
                     Node::JoinCore(join) => {
                         let join_fn =
                             codegen.codegen_func(&format!("join_fn_{node_id}"), join.join_fn());
-                        functions.insert(node_id, vec![join_fn]);
+
+                        // Keys containing nulls only need to be filtered out when the join uses
+                        // SQL semantics and the key actually has nullable columns
+                        let join_key = join.join_key_layout();
+                        let key_nullable = codegen
+                            .native_layout_cache()
+                            .row_layout(join_key)
+                            .has_nullable_columns();
+                        if !join.null_safe() && key_nullable {
+                            let key_has_nulls = codegen.codegen_layout_has_nulls(join_key);
+                            functions.insert(node_id, vec![join_fn, key_has_nulls]);
+                        } else {
+                            functions.insert(node_id, vec![join_fn]);
+                        }
 
                         vtables
                             .entry(join.key_layout())
@@ -396,22 +578,21 @@ impl CompiledDataflow {
                         collect_functions(codegen, functions, vtables, subgraph.subgraph());
                     }
 
-                    Node::Constant(constant) => match constant.layout() {
-                        StreamLayout::Set(key) => {
+                    Node::Constant(constant) => {
+                        constant.layout().map_layouts(&mut |layout| {
                             vtables
-                                .entry(key)
-                                .or_insert_with(|| codegen.vtable_for(key));
-                        }
+                                .entry(layout)
+                                .or_insert_with(|| codegen.vtable_for(layout));
+                        });
+                    }
 
-                        StreamLayout::Map(key, value) => {
+                    Node::ConstantSource(constant) => {
+                        constant.layout().map_layouts(&mut |layout| {
                             vtables
-                                .entry(key)
-                                .or_insert_with(|| codegen.vtable_for(key));
-                            vtables
-                                .entry(value)
-                                .or_insert_with(|| codegen.vtable_for(value));
-                        }
-                    },
+                                .entry(layout)
+                                .or_insert_with(|| codegen.vtable_for(layout));
+                        });
+                    }
 
                     Node::Min(_)
                     | Node::Max(_)
@@ -423,6 +604,7 @@ impl CompiledDataflow {
                     | Node::Differentiate(_)
                     | Node::Integrate(_)
                     | Node::Sink(_)
+                    | Node::CollectStatistics(_)
                     | Node::Export(_)
                     | Node::ExportedNode(_)
                     | Node::Minus(_)
@@ -432,6 +614,7 @@ impl CompiledDataflow {
         }
 
         // Run codegen over all nodes
+        let collect_statistics = config.collect_statistics;
         let mut codegen = Codegen::new(graph.layout_cache().clone(), config);
         // TODO: SmallVec
         let mut node_functions = BTreeMap::new();
@@ -450,6 +633,7 @@ impl CompiledDataflow {
             node_streams: &BTreeMap<NodeId, Option<StreamLayout>>,
             node_functions: &BTreeMap<NodeId, Vec<FuncId>>,
             layout_cache: &NativeLayoutCache,
+            collect_statistics: bool,
         ) -> BTreeMap<NodeId, DataflowNode> {
             let mut nodes = BTreeMap::new();
             for (node_id, node) in graph.nodes() {
@@ -631,10 +815,8 @@ impl CompiledDataflow {
                                 &*vtables[&fold.output_layout()],
                             )
                         };
-                        let acc_layout = layout_cache.layout_of(fold.acc_layout());
-
                         let init =
-                            unsafe { row_from_literal(fold.init(), acc_vtable, &acc_layout) };
+                            unsafe { row_from_literal(fold.init(), acc_vtable, layout_cache) };
 
                         let (step_fn, finish_fn) = (
                             jit.get_finalized_function(node_functions[node_id][0]),
@@ -661,10 +843,8 @@ impl CompiledDataflow {
                                 &*vtables[&fold.output_layout()],
                             )
                         };
-                        let acc_layout = layout_cache.layout_of(fold.acc_layout());
-
                         let init =
-                            unsafe { row_from_literal(fold.init(), acc_vtable, &acc_layout) };
+                            unsafe { row_from_literal(fold.init(), acc_vtable, layout_cache) };
 
                         let (step_fn, finish_fn) = (
                             jit.get_finalized_function(node_functions[node_id][0]),
@@ -689,10 +869,38 @@ impl CompiledDataflow {
                             *node_id,
                             DataflowNode::Sink(Sink {
                                 input: sink.input(),
+                                output_mode: sink.output_mode(),
                             }),
                         );
                     }
 
+                    // Statistics nodes are skipped entirely when statistics collection is
+                    // disabled so that they add no operators to the circuit
+                    Node::CollectStatistics(collect) if collect_statistics => {
+                        let layout = |layout_id| {
+                            let (native, row) = layout_cache.get_layouts(layout_id);
+                            StatisticsLayout::new(native.clone(), row.clone())
+                        };
+
+                        let (key_layout, value_layout) = match node_streams[&collect.input()] {
+                            Some(StreamLayout::Set(key)) => (layout(key), None),
+                            Some(StreamLayout::Map(key, value)) => {
+                                (layout(key), Some(layout(value)))
+                            }
+                            None => unreachable!("statistics node {node_id} has no input stream"),
+                        };
+
+                        nodes.insert(
+                            *node_id,
+                            DataflowNode::CollectStatistics(CollectStatistics {
+                                input: collect.input(),
+                                key_layout,
+                                value_layout,
+                            }),
+                        );
+                    }
+                    Node::CollectStatistics(_) => {}
+
                     Node::Source(source) => {
                         let output_vtable = unsafe { &*vtables[&source.layout()] };
                         nodes.insert(*node_id, DataflowNode::Source(Source { output_vtable }));
@@ -785,6 +993,12 @@ impl CompiledDataflow {
                     Node::JoinCore(join) => {
                         let (lhs, rhs) = (join.lhs(), join.rhs());
                         let join_fn = jit.get_finalized_function(node_functions[node_id][0]);
+                        let key_has_nulls =
+                            node_functions[node_id].get(1).map(|&has_nulls| unsafe {
+                                transmute::<_, unsafe extern "C" fn(*const u8) -> bool>(
+                                    jit.get_finalized_function(has_nulls),
+                                )
+                            });
                         let key_vtable = unsafe { &*vtables[&join.key_layout()] };
                         let value_vtable = unsafe { &*vtables[&join.value_layout()] };
 
@@ -809,6 +1023,7 @@ impl CompiledDataflow {
                                     key_vtable,
                                     value_vtable,
                                     output_kind: join.result_kind(),
+                                    key_has_nulls,
                                 })
                             }
 
@@ -856,6 +1071,7 @@ impl CompiledDataflow {
                                 node_streams,
                                 node_functions,
                                 layout_cache,
+                                collect_statistics,
                             ),
                             feedback_connections: subgraph.feedback_connections().clone(),
                         });
@@ -877,64 +1093,25 @@ impl CompiledDataflow {
                     }
 
                     Node::Constant(constant) => {
-                        let value = match constant.value().value() {
-                            StreamCollection::Set(set) => {
-                                let key_layout = constant.layout().unwrap_set();
-                                let key_vtable = unsafe { &*vtables[&key_layout] };
-                                let key_layout = layout_cache.layout_of(key_layout);
-
-                                let mut batch = Vec::with_capacity(set.len());
-                                for (literal, diff) in set {
-                                    let key = unsafe {
-                                        row_from_literal(literal, key_vtable, &key_layout)
-                                    };
-
-                                    batch.push((key, *diff));
-                                }
-
-                                // Build a batch from the set's values
-                                let mut batcher = <RowSet as Batch>::Batcher::new_batcher(());
-                                batcher.push_batch(&mut batch);
-                                RowZSet::Set(batcher.seal())
-                            }
-
-                            StreamCollection::Map(map) => {
-                                let (key_layout, value_layout) = constant.layout().unwrap_map();
-                                let (key_vtable, value_vtable) =
-                                    unsafe { (&*vtables[&key_layout], &*vtables[&value_layout]) };
-                                let (key_layout, value_layout) = (
-                                    layout_cache.layout_of(key_layout),
-                                    layout_cache.layout_of(value_layout),
-                                );
-
-                                let mut batch = Vec::with_capacity(map.len());
-                                for (key_literal, value_literal, diff) in map {
-                                    let key = unsafe {
-                                        row_from_literal(key_literal, key_vtable, &key_layout)
-                                    };
-                                    let value = unsafe {
-                                        row_from_literal(value_literal, value_vtable, &value_layout)
-                                    };
-
-                                    batch.push(((key, value), *diff));
-                                }
-
-                                // Build a batch from the set's values
-                                let mut batcher = <RowMap as Batch>::Batcher::new_batcher(());
-                                batcher.push_batch(&mut batch);
-                                RowZSet::Map(batcher.seal())
-                            }
-                        };
-
+                        let value =
+                            unsafe { batch_from_literal(constant.value(), vtables, layout_cache) };
                         let node = DataflowNode::Constant(nodes::Constant { value });
                         nodes.insert(*node_id, node);
                     }
+
+                    Node::ConstantSource(constant) => {
+                        let value =
+                            unsafe { batch_from_literal(constant.value(), vtables, layout_cache) };
+                        let node = DataflowNode::ConstantSource(nodes::Constant { value });
+                        nodes.insert(*node_id, node);
+                    }
                 }
             }
 
             nodes
         }
 
+        let function_symbols = codegen.function_symbols().to_vec();
         let (jit, native_layout_cache) = codegen.finalize_definitions();
         let vtables = vtables
             .into_iter()
@@ -947,25 +1124,49 @@ impl CompiledDataflow {
             &node_streams,
             &node_functions,
             &native_layout_cache,
+            collect_statistics,
         );
 
+        let memory = Arc::new(JitMemory {
+            jit: ManuallyDrop::new(jit),
+            vtables,
+            function_symbols,
+        });
+
         (
             Self {
                 nodes,
                 edges: graph.edges().clone(),
+                jit: memory.clone(),
+                statistics: DataflowStatistics::default(),
             },
-            JitHandle { jit, vtables },
+            JitHandle { memory },
             native_layout_cache,
         )
     }
 
+    /// Returns the statistics collected by the dataflow's statistics nodes,
+    /// they're updated as the circuit the dataflow is constructed within runs
+    pub fn statistics(&self) -> DataflowStatistics {
+        self.statistics.clone()
+    }
+
     pub fn construct(mut self, circuit: &mut RootCircuit) -> (Inputs, Outputs) {
+        // Keep the compiled code alive for as long as the circuit is. The
+        // circuit's event handlers are dropped after all of its operators, so
+        // operators (and the rows within their traces) can safely use the
+        // compiled functions and vtables until they're dropped
+        let jit = self.jit.clone();
+        circuit.register_circuit_event_handler("dataflow-jit", move |_| {
+            let _ = &jit;
+        });
+
         let mut streams = BTreeMap::<NodeId, RowStream<RootCircuit>>::new();
 
         let mut inputs = BTreeMap::new();
         let mut outputs = BTreeMap::new();
 
-        let order = algo::toposort(&self.edges, None).unwrap();
+        let order = graph::toposort(&self.edges).unwrap();
         for node_id in order {
             let node = match self.nodes.remove(&node_id) {
                 Some(node) => node,
@@ -1122,15 +1323,14 @@ impl CompiledDataflow {
                 }
 
                 DataflowNode::Sink(sink) => {
-                    let input = &streams[&sink.input];
-                    let output = match input {
-                        RowStream::Set(input) => RowOutput::Set(input.output()),
-                        RowStream::Map(input) => RowOutput::Map(input.output()),
-                    };
-
+                    let output = self.sink(node_id, sink, &streams);
                     outputs.insert(node_id, output);
                 }
 
+                DataflowNode::CollectStatistics(collect) => {
+                    self.collect_statistics(node_id, collect, &streams)
+                }
+
                 DataflowNode::Source(source) => {
                     let (stream, handle) = circuit.add_input_zset::<Row, i32>();
 
@@ -1146,13 +1346,13 @@ impl CompiledDataflow {
                     }
 
                     streams.insert(node_id, RowStream::Set(stream));
-                    inputs.insert(node_id, RowInput::Set(handle));
+                    inputs.insert(node_id, RowInput::Set(self.jit_owned(handle)));
                 }
 
                 DataflowNode::SourceMap(_source) => {
                     let (stream, handle) = circuit.add_input_indexed_zset::<Row, Row, i32>();
                     streams.insert(node_id, RowStream::Map(stream));
-                    inputs.insert(node_id, RowInput::Map(handle));
+                    inputs.insert(node_id, RowInput::Map(self.jit_owned(handle)));
                 }
 
                 DataflowNode::Delta0(_) => todo!(),
@@ -1266,15 +1466,20 @@ impl CompiledDataflow {
                 DataflowNode::Distinct(distinct) => self.distinct(node_id, distinct, &mut streams),
 
                 DataflowNode::JoinCore(join) => {
-                    let lhs = streams[&join.lhs].clone();
-                    let rhs = streams[&join.rhs].clone();
+                    let mut lhs = streams[&join.lhs].clone().unwrap_map();
+                    let mut rhs = streams[&join.rhs].clone().unwrap_map();
                     let (join_fn, key_vtable, _value_vtable) =
                         (join.join_fn, join.key_vtable, join.value_vtable);
 
+                    // Keys containing nulls never match under SQL semantics
+                    if let Some(key_has_nulls) = join.key_has_nulls {
+                        lhs = lhs.filter(move |(key, _)| unsafe { !key_has_nulls(key.as_ptr()) });
+                        rhs = rhs.filter(move |(key, _)| unsafe { !key_has_nulls(key.as_ptr()) });
+                    }
+
                     let joined = match join.output_kind {
-                        StreamKind::Set => RowStream::Set(lhs.unwrap_map().join_generic(
-                            &rhs.unwrap_map(),
-                            move |key, lhs_val, rhs_val| {
+                        StreamKind::Set => {
+                            RowStream::Set(lhs.join_generic(&rhs, move |key, lhs_val, rhs_val| {
                                 let mut output = UninitRow::new(key_vtable);
                                 unsafe {
                                     join_fn(
@@ -1287,8 +1492,8 @@ impl CompiledDataflow {
                                 }
 
                                 iter::once((unsafe { output.assume_init() }, ()))
-                            },
-                        )),
+                            }))
+                        }
 
                         StreamKind::Map => todo!(),
                     };
@@ -1350,6 +1555,10 @@ impl CompiledDataflow {
                     self.constant(node_id, constant, circuit, &mut streams);
                 }
 
+                DataflowNode::ConstantSource(constant) => {
+                    self.constant_source(node_id, constant, circuit, &mut streams);
+                }
+
                 DataflowNode::Subgraph(subgraph) => self.subgraph(subgraph, circuit, &mut streams),
 
                 DataflowNode::Noop(_) => {}
@@ -1372,7 +1581,7 @@ impl CompiledDataflow {
                 let mut substreams = BTreeMap::new();
                 let mut feedbacks = BTreeMap::new();
 
-                let nodes = algo::toposort(&subgraph.edges, None).unwrap();
+                let nodes = graph::toposort(&subgraph.edges).unwrap();
                 for node_id in nodes {
                     if subgraph.inputs.contains_key(&node_id) {
                         continue;
@@ -1386,6 +1595,9 @@ impl CompiledDataflow {
                         DataflowNode::Constant(constant) => {
                             self.constant(node_id, constant, subcircuit, &mut substreams);
                         }
+                        DataflowNode::ConstantSource(constant) => {
+                            self.constant_source(node_id, constant, subcircuit, &mut substreams);
+                        }
                         DataflowNode::Map(map) => self.map(node_id, map, &mut substreams),
                         DataflowNode::Filter(filter) => {
                             self.filter(node_id, filter, &mut substreams);
@@ -1526,6 +1738,7 @@ impl CompiledDataflow {
                         DataflowNode::Neg(neg) => self.neg(node_id, neg, &mut substreams),
 
                         DataflowNode::Sink(_)
+                        | DataflowNode::CollectStatistics(_)
                         | DataflowNode::Source(_)
                         | DataflowNode::SourceMap(_) => todo!(),
 
@@ -1665,14 +1878,24 @@ impl CompiledDataflow {
                         }
 
                         DataflowNode::JoinCore(join) => {
-                            let lhs = substreams[&join.lhs].clone();
-                            let rhs = substreams[&join.rhs].clone();
+                            let mut lhs = substreams[&join.lhs].clone().unwrap_map();
+                            let mut rhs = substreams[&join.rhs].clone().unwrap_map();
                             let (join_fn, key_vtable, _value_vtable) =
                                 (join.join_fn, join.key_vtable, join.value_vtable);
 
+                            // Keys containing nulls never match under SQL semantics
+                            if let Some(key_has_nulls) = join.key_has_nulls {
+                                lhs = lhs.filter(move |(key, _)| unsafe {
+                                    !key_has_nulls(key.as_ptr())
+                                });
+                                rhs = rhs.filter(move |(key, _)| unsafe {
+                                    !key_has_nulls(key.as_ptr())
+                                });
+                            }
+
                             let joined = match join.output_kind {
-                                StreamKind::Set => RowStream::Set(lhs.unwrap_map().join_generic(
-                                    &rhs.unwrap_map(),
+                                StreamKind::Set => RowStream::Set(lhs.join_generic(
+                                    &rhs,
                                     move |key, lhs_val, rhs_val| {
                                         let mut output = UninitRow::new(key_vtable);
                                         unsafe {
@@ -2006,6 +2229,160 @@ impl CompiledDataflow {
         streams.insert(node_id, constant);
     }
 
+    /// Wraps a value referring to the compiled code, keeping the code alive for
+    /// as long as the value is
+    fn jit_owned<T>(&self, value: T) -> JitOwned<T> {
+        JitOwned::new(value, self.jit.clone())
+    }
+
+    /// Outputs the sink's input stream according to its output mode
+    fn sink(
+        &self,
+        node_id: NodeId,
+        sink: Sink,
+        streams: &BTreeMap<NodeId, RowStream<RootCircuit>>,
+    ) -> RowOutput {
+        match (&streams[&sink.input], sink.output_mode) {
+            (RowStream::Set(input), SinkOutputMode::Deltas) => {
+                RowOutput::Set(self.jit_owned(input.output()))
+            }
+            (RowStream::Map(input), SinkOutputMode::Deltas) => {
+                RowOutput::Map(self.jit_owned(input.output()))
+            }
+
+            // Gathering every worker's deltas onto a single worker consolidates them
+            (RowStream::Set(input), SinkOutputMode::NetDeltas) => {
+                RowOutput::Set(self.jit_owned(input.gather(0).output()))
+            }
+            (RowStream::Map(input), SinkOutputMode::NetDeltas) => {
+                RowOutput::Map(self.jit_owned(input.gather(0).output()))
+            }
+
+            // Retractions panic the worker, which the runtime reports as an error
+            // from the step that produced them
+            (RowStream::Set(input), SinkOutputMode::AppendOnly) => {
+                let input = input.gather(0);
+                input.inspect(move |batch| {
+                    let mut cursor = batch.cursor();
+                    while cursor.key_valid() {
+                        let weight = cursor.weight();
+                        if weight < 0 {
+                            panic!(
+                                "append-only sink {node_id} received a retraction of {:?} with weight {weight}",
+                                cursor.key(),
+                            );
+                        }
+                        cursor.step_key();
+                    }
+                });
+
+                RowOutput::Set(self.jit_owned(input.output()))
+            }
+            (RowStream::Map(input), SinkOutputMode::AppendOnly) => {
+                let input = input.gather(0);
+                input.inspect(move |batch| {
+                    let mut cursor = batch.cursor();
+                    while cursor.key_valid() {
+                        while cursor.val_valid() {
+                            let weight = cursor.weight();
+                            if weight < 0 {
+                                panic!(
+                                    "append-only sink {node_id} received a retraction of {:?} => {:?} with weight {weight}",
+                                    cursor.key(),
+                                    cursor.val(),
+                                );
+                            }
+                            cursor.step_val();
+                        }
+                        cursor.step_key();
+                    }
+                });
+
+                RowOutput::Map(self.jit_owned(input.output()))
+            }
+        }
+    }
+
+    fn collect_statistics(
+        &self,
+        node_id: NodeId,
+        collect: CollectStatistics,
+        streams: &BTreeMap<NodeId, RowStream<RootCircuit>>,
+    ) {
+        let key_columns = collect.key_layout.len();
+        let value_columns = collect
+            .value_layout
+            .as_ref()
+            .map_or(0, StatisticsLayout::len);
+        self.statistics
+            .register(node_id, key_columns, value_columns);
+
+        let statistics = self.statistics.clone();
+        match &streams[&collect.input] {
+            RowStream::Set(input) => {
+                let key_layout = collect.key_layout;
+
+                input.inspect(move |batch| {
+                    statistics.update(node_id, |stats| {
+                        let mut cursor = batch.cursor();
+                        while cursor.key_valid() {
+                            stats.record_set(cursor.key(), &key_layout, cursor.weight());
+                            cursor.step_key();
+                        }
+                    });
+                });
+            }
+
+            RowStream::Map(input) => {
+                let (key_layout, value_layout) = (
+                    collect.key_layout,
+                    collect
+                        .value_layout
+                        .expect("map streams have a value layout"),
+                );
+
+                input.inspect(move |batch| {
+                    statistics.update(node_id, |stats| {
+                        let mut cursor = batch.cursor();
+                        while cursor.key_valid() {
+                            while cursor.val_valid() {
+                                stats.record_map(
+                                    (cursor.key(), &key_layout),
+                                    (cursor.val(), &value_layout),
+                                    cursor.weight(),
+                                );
+                                cursor.step_val();
+                            }
+                            cursor.step_key();
+                        }
+                    });
+                });
+            }
+        }
+    }
+
+    /// Constant sources yield their value on the first step after each clock
+    /// start and empty batches on every following step
+    fn constant_source<C>(
+        &self,
+        node_id: NodeId,
+        constant: nodes::Constant,
+        circuit: &mut C,
+        streams: &mut BTreeMap<NodeId, RowStream<C>>,
+    ) where
+        C: Circuit,
+    {
+        let source = match constant.value {
+            RowZSet::Set(set) => {
+                RowStream::Set(circuit.add_source(operators::ConstantSource::new(set)))
+            }
+            RowZSet::Map(map) => {
+                RowStream::Map(circuit.add_source(operators::ConstantSource::new(map)))
+            }
+        };
+        streams.insert(node_id, source);
+    }
+
     fn map<C>(&self, node_id: NodeId, map: Map, streams: &mut BTreeMap<NodeId, RowStream<C>>)
     where
         C: Circuit,
@@ -2073,38 +2450,101 @@ impl CompiledDataflow {
     }
 }
 
-unsafe fn row_from_literal(
+/// Builds a batch from the given literal, the vtables of the literal's layouts
+/// must be within `vtables`
+unsafe fn batch_from_literal(
+    literal: &StreamLiteral,
+    vtables: &BTreeMap<LayoutId, *mut VTable>,
+    layout_cache: &NativeLayoutCache,
+) -> RowZSet {
+    match literal.value() {
+        StreamCollection::Set(set) => {
+            let key_layout = literal.layout().unwrap_set();
+            let key_vtable = unsafe { &*vtables[&key_layout] };
+
+            let mut batch = Vec::with_capacity(set.len());
+            for (literal, diff) in set {
+                let key = unsafe { row_from_literal(literal, key_vtable, layout_cache) };
+                batch.push((key, *diff));
+            }
+
+            // Build a batch from the set's values
+            let mut batcher = <RowSet as Batch>::Batcher::new_batcher(());
+            batcher.push_batch(&mut batch);
+            RowZSet::Set(batcher.seal())
+        }
+
+        StreamCollection::Map(map) => {
+            let (key_layout, value_layout) = literal.layout().unwrap_map();
+            let (key_vtable, value_vtable) =
+                unsafe { (&*vtables[&key_layout], &*vtables[&value_layout]) };
+
+            let mut batch = Vec::with_capacity(map.len());
+            for (key_literal, value_literal, diff) in map {
+                let key = unsafe { row_from_literal(key_literal, key_vtable, layout_cache) };
+                let value = unsafe { row_from_literal(value_literal, value_vtable, layout_cache) };
+                batch.push(((key, value), *diff));
+            }
+
+            // Build a batch from the map's values
+            let mut batcher = <RowMap as Batch>::Batcher::new_batcher(());
+            batcher.push_batch(&mut batch);
+            RowZSet::Map(batcher.seal())
+        }
+    }
+}
+
+pub(crate) unsafe fn row_from_literal(
     literal: &RowLiteral,
     vtable: &'static VTable,
-    layout: &NativeLayout,
+    layout_cache: &NativeLayoutCache,
 ) -> Row {
     let mut row = UninitRow::new(vtable);
+    unsafe { write_literal_to(literal, vtable.layout_id, layout_cache, row.as_mut_ptr()) };
+    unsafe { row.assume_init() }
+}
+
+/// Writes the given literal to `ptr` as a row of the given layout
+unsafe fn write_literal_to(
+    literal: &RowLiteral,
+    layout_id: LayoutId,
+    layout_cache: &NativeLayoutCache,
+    ptr: *mut u8,
+) {
+    let (layout, row_layout) = layout_cache.get_layouts(layout_id);
 
     for (idx, column) in literal.rows().iter().enumerate() {
-        match column {
-            NullableConstant::NonNull(constant) => unsafe {
-                let column_ptr = row.as_mut_ptr().add(layout.offset_of(idx) as usize);
-                write_constant_to(constant, column_ptr);
-            },
+        let constant = match column {
+            NullableConstant::NonNull(constant) => Some(constant),
 
             NullableConstant::Nullable(constant) => {
-                row.set_column_null(idx, layout, constant.is_none());
-
-                if let Some(constant) = constant {
-                    unsafe {
-                        let column_ptr = row.as_mut_ptr().add(layout.offset_of(idx) as usize);
+                unsafe { row::set_column_null(ptr, idx, &layout, constant.is_none()) };
+                constant.as_ref()
+            }
+        };
 
-                        write_constant_to(constant, column_ptr)
-                    }
-                }
+        if let Some(constant) = constant {
+            unsafe {
+                let column_ptr = ptr.add(layout.offset_of(idx) as usize);
+                write_constant_to(
+                    constant,
+                    row_layout.nested_layout(idx),
+                    layout_cache,
+                    column_ptr,
+                );
             }
         }
     }
-
-    unsafe { row.assume_init() }
 }
 
-unsafe fn write_constant_to(constant: &Constant, ptr: *mut u8) {
+/// Writes the given constant to `ptr`, `nested` is the nested layout of struct
+/// and array columns
+unsafe fn write_constant_to(
+    constant: &Constant,
+    nested: Option<LayoutId>,
+    layout_cache: &NativeLayoutCache,
+    ptr: *mut u8,
+) {
     match *constant {
         Constant::Unit => ptr.cast::<()>().write(()),
 
@@ -2131,5 +2571,135 @@ unsafe fn write_constant_to(constant: &Constant, ptr: *mut u8) {
         Constant::String(ref value) => ptr.cast::<ThinStr>().write(ThinStr::from(&**value)),
         // Constant::Date(date) => ptr.cast::<i32>().write(date),
         // Constant::Timestamp(timestamp) => ptr.cast::<i64>().write(timestamp),
+
+        // Structs are written in place
+        Constant::Struct(ref literal) => {
+            let nested = nested.expect("struct constants must be written to struct columns");
+            write_literal_to(literal, nested, layout_cache, ptr);
+        }
+
+        Constant::Array(ref elements) => {
+            let nested = nested.expect("array constants must be written to array columns");
+            let array_layout = ArrayLayout::of(&layout_cache.layout_of(nested));
+
+            let array = array_layout.alloc(elements.len());
+            for (idx, element) in elements.iter().enumerate() {
+                write_literal_to(
+                    element,
+                    nested,
+                    layout_cache,
+                    array_layout.element(array, idx),
+                );
+            }
+
+            ptr.cast::<*mut u8>().write(array);
+        }
     }
 }
+
+/// Reads the given row back into a [`RowLiteral`], the inverse of
+/// [`row_from_literal()`]
+pub fn row_to_literal(row: &Row, layout_cache: &NativeLayoutCache) -> RowLiteral {
+    // Safety: The row is initialized and of the layout its vtable refers to
+    unsafe { read_literal_from(row.as_ptr(), row.vtable().layout_id, layout_cache) }
+}
+
+/// Reads the row of the given layout at `ptr` into a [`RowLiteral`]
+unsafe fn read_literal_from(
+    ptr: *const u8,
+    layout_id: LayoutId,
+    layout_cache: &NativeLayoutCache,
+) -> RowLiteral {
+    let (layout, row_layout) = layout_cache.get_layouts(layout_id);
+
+    let columns = (0..row_layout.len())
+        .map(|idx| {
+            let column_ptr = unsafe { ptr.add(layout.offset_of(idx) as usize) };
+            let read_column = || unsafe {
+                read_constant_from(
+                    row_layout.column_type(idx),
+                    row_layout.nested_layout(idx),
+                    layout_cache,
+                    column_ptr,
+                )
+            };
+
+            if row_layout.column_nullable(idx) {
+                let is_null = unsafe { row::column_is_null(ptr, idx, &layout) };
+                NullableConstant::Nullable((!is_null).then(read_column))
+            } else {
+                NullableConstant::NonNull(read_column())
+            }
+        })
+        .collect();
+
+    RowLiteral::new(columns)
+}
+
+/// Reads a constant of the given type from `ptr`, `nested` is the nested layout
+/// of struct and array columns
+unsafe fn read_constant_from(
+    ty: ColumnType,
+    nested: Option<LayoutId>,
+    layout_cache: &NativeLayoutCache,
+    ptr: *const u8,
+) -> Constant {
+    match ty {
+        ColumnType::Struct => {
+            let nested = nested.expect("struct columns always have a nested layout");
+            Constant::Struct(read_literal_from(ptr, nested, layout_cache))
+        }
+
+        ColumnType::Array => {
+            let nested = nested.expect("array columns always have a nested layout");
+            let array_layout = ArrayLayout::of(&layout_cache.layout_of(nested));
+
+            let array = *ptr.cast::<*const u8>();
+            let elements = (0..ArrayLayout::len(array))
+                .map(|idx| {
+                    read_literal_from(array_layout.element(array, idx), nested, layout_cache)
+                })
+                .collect();
+
+            Constant::Array(elements)
+        }
+
+        scalar => read_scalar_from(scalar, ptr).unwrap(),
+    }
+}
+
+/// Reads a scalar of the given type from `ptr`, returns `None` for struct and
+/// array columns
+unsafe fn read_scalar_from(ty: ColumnType, ptr: *const u8) -> Option<Constant> {
+    Some(match ty {
+        ColumnType::Unit => Constant::Unit,
+
+        ColumnType::Bool => Constant::Bool(*ptr.cast::<bool>()),
+
+        ColumnType::U8 => Constant::U8(*ptr.cast::<u8>()),
+        ColumnType::I8 => Constant::I8(*ptr.cast::<i8>()),
+
+        ColumnType::U16 => Constant::U16(*ptr.cast::<u16>()),
+        ColumnType::I16 => Constant::I16(*ptr.cast::<i16>()),
+
+        ColumnType::U32 => Constant::U32(*ptr.cast::<u32>()),
+        // TODO: Date constants
+        ColumnType::I32 | ColumnType::Date => Constant::I32(*ptr.cast::<i32>()),
+
+        ColumnType::U64 => Constant::U64(*ptr.cast::<u64>()),
+        // TODO: Timestamp constants
+        ColumnType::I64 | ColumnType::Timestamp => Constant::I64(*ptr.cast::<i64>()),
+
+        ColumnType::Usize => Constant::Usize(*ptr.cast::<usize>()),
+        ColumnType::Isize => Constant::Isize(*ptr.cast::<isize>()),
+
+        ColumnType::F32 => Constant::F32(*ptr.cast::<f32>()),
+        ColumnType::F64 => Constant::F64(*ptr.cast::<f64>()),
+
+        ColumnType::String => Constant::String((*ptr.cast::<ThinStr>()).as_str().to_owned()),
+
+        ColumnType::Struct | ColumnType::Array => return None,
+
+        ColumnType::Ptr => unreachable!("pointer columns cannot be read"),
+    })
+}
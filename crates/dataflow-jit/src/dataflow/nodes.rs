@@ -1,8 +1,8 @@
 use crate::{
     codegen::VTable,
-    dataflow::RowZSet,
+    dataflow::{statistics::StatisticsLayout, RowZSet},
     ir::{
-        nodes::{StreamKind, StreamLayout},
+        nodes::{SinkOutputMode, StreamKind, StreamLayout},
         NodeId,
     },
     row::Row,
@@ -41,10 +41,12 @@ pub enum DataflowNode {
     Differentiate(Differentiate),
     Integrate(Integrate),
     Constant(Constant),
+    ConstantSource(Constant),
     Fold(Fold),
     PartitionedRollingFold(PartitionedRollingFold),
     FlatMap(FlatMap),
     Antijoin(Antijoin),
+    CollectStatistics(CollectStatistics),
 }
 
 #[derive(Debug, Clone)]
@@ -175,6 +177,10 @@ pub struct JoinCore {
     pub key_vtable: &'static VTable,
     pub value_vtable: &'static VTable,
     pub output_kind: StreamKind,
+    /// Returns `true` if the given input key contains nulls, keys for which it
+    /// returns `true` are removed from both inputs before joining. `None` for
+    /// null-safe joins and for keys without nullable columns
+    pub key_has_nulls: Option<unsafe extern "C" fn(*const u8) -> bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -217,6 +223,14 @@ pub struct SourceMap {
 #[derive(Debug, Clone)]
 pub struct Sink {
     pub input: NodeId,
+    pub output_mode: SinkOutputMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct CollectStatistics {
+    pub input: NodeId,
+    pub key_layout: StatisticsLayout,
+    pub value_layout: Option<StatisticsLayout>,
 }
 
 #[derive(Debug, Clone)]
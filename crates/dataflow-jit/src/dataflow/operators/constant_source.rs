@@ -0,0 +1,65 @@
+//! A source operator that produces a batch once per clock epoch
+
+use dbsp::{
+    circuit::{
+        operator_traits::{Operator, SourceOperator},
+        Scope,
+    },
+    trace::{Batch, BatchReader},
+};
+use std::borrow::Cow;
+
+/// Yields its value on the first step after every clock start and empty
+/// batches on all following steps
+pub struct ConstantSource<B> {
+    value: B,
+    emitted: bool,
+}
+
+impl<B> ConstantSource<B> {
+    pub fn new(value: B) -> Self {
+        Self {
+            value,
+            emitted: false,
+        }
+    }
+}
+
+impl<B> Operator for ConstantSource<B>
+where
+    B: BatchReader + 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("ConstantSource")
+    }
+
+    fn clock_start(&mut self, scope: Scope) {
+        if scope == 0 {
+            self.emitted = false;
+        }
+    }
+
+    fn fixedpoint(&self, scope: Scope) -> bool {
+        // Once the value has been emitted the source only produces empty batches
+        // until the next clock start
+        if scope == 0 {
+            self.emitted || self.value.is_empty()
+        } else {
+            true
+        }
+    }
+}
+
+impl<B> SourceOperator<B> for ConstantSource<B>
+where
+    B: Batch<Time = ()> + Clone + 'static,
+{
+    fn eval(&mut self) -> B {
+        if self.emitted {
+            B::empty(())
+        } else {
+            self.emitted = true;
+            self.value.clone()
+        }
+    }
+}
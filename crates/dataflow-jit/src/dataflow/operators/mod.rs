@@ -1,5 +1,7 @@
 //! Custom dataflow operators for the jit
 
+mod constant_source;
 mod flat_map;
 
+pub use constant_source::ConstantSource;
 pub use flat_map::FlatMap;
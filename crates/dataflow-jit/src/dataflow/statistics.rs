@@ -0,0 +1,320 @@
+//! Per-column statistics collected by
+//! [`CollectStatistics`](crate::ir::nodes::CollectStatistics) nodes
+//!
+//! Row and null counts are exact and account for retractions, while the
+//! minimum, maximum and number of distinct values are taken over every value
+//! that was ever inserted into the stream. Since retracted values are never
+//! removed from them they're bounds on the stream's current contents rather
+//! than exact values
+
+use crate::{
+    codegen::NativeLayout,
+    dataflow::read_scalar_from,
+    ir::{Constant, NodeId, RowLayout},
+    row::Row,
+};
+use serde::{Serialize, Serializer};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+use xxhash_rust::xxh3::Xxh3;
+
+/// The statistics collected by every statistics node of a dataflow
+///
+/// Statistics are shared between all clones of a `DataflowStatistics` and are
+/// updated as the circuit the dataflow was constructed within runs
+#[derive(Debug, Clone, Default)]
+pub struct DataflowStatistics {
+    streams: Arc<Mutex<BTreeMap<NodeId, StreamStatistics>>>,
+}
+
+impl DataflowStatistics {
+    /// Returns the current statistics for the stream collected by the given
+    /// statistics node
+    pub fn get(&self, node: NodeId) -> Option<StreamStatistics> {
+        self.streams.lock().unwrap().get(&node).cloned()
+    }
+
+    /// Returns the current statistics of every statistics node
+    pub fn snapshot(&self) -> BTreeMap<NodeId, StreamStatistics> {
+        self.streams.lock().unwrap().clone()
+    }
+
+    pub(super) fn register(&self, node: NodeId, keys: usize, values: usize) {
+        self.streams
+            .lock()
+            .unwrap()
+            .insert(node, StreamStatistics::new(keys, values));
+    }
+
+    pub(super) fn update<F>(&self, node: NodeId, update: F)
+    where
+        F: FnOnce(&mut StreamStatistics),
+    {
+        update(self.streams.lock().unwrap().get_mut(&node).unwrap());
+    }
+}
+
+/// Statistics over the rows of a stream
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamStatistics {
+    rows: i64,
+    keys: Vec<ColumnStatistics>,
+    values: Vec<ColumnStatistics>,
+}
+
+impl StreamStatistics {
+    fn new(keys: usize, values: usize) -> Self {
+        Self {
+            rows: 0,
+            keys: vec![ColumnStatistics::new(); keys],
+            values: vec![ColumnStatistics::new(); values],
+        }
+    }
+
+    /// Returns the weighted number of rows within the stream
+    pub const fn rows(&self) -> i64 {
+        self.rows
+    }
+
+    /// Returns the statistics of each column of the stream's keys
+    pub fn keys(&self) -> &[ColumnStatistics] {
+        &self.keys
+    }
+
+    /// Returns the statistics of each column of the stream's values, empty for
+    /// sets
+    pub fn values(&self) -> &[ColumnStatistics] {
+        &self.values
+    }
+
+    pub(super) fn record_set(&mut self, key: &Row, key_layout: &StatisticsLayout, weight: i32) {
+        self.rows += weight as i64;
+        key_layout.record(&mut self.keys, key, weight);
+    }
+
+    pub(super) fn record_map(
+        &mut self,
+        (key, key_layout): (&Row, &StatisticsLayout),
+        (value, value_layout): (&Row, &StatisticsLayout),
+        weight: i32,
+    ) {
+        self.rows += weight as i64;
+        key_layout.record(&mut self.keys, key, weight);
+        value_layout.record(&mut self.values, value, weight);
+    }
+}
+
+/// Statistics over a single column
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnStatistics {
+    min: Option<Constant>,
+    max: Option<Constant>,
+    null_count: i64,
+    #[serde(serialize_with = "serialize_estimate")]
+    ndv: HyperLogLog,
+}
+
+impl ColumnStatistics {
+    fn new() -> Self {
+        Self {
+            min: None,
+            max: None,
+            null_count: 0,
+            ndv: HyperLogLog::new(),
+        }
+    }
+
+    /// Returns the smallest value ever inserted into the column, `None` if no
+    /// non-null values have been inserted or if the column is a struct, array
+    /// or unit column
+    pub const fn min(&self) -> Option<&Constant> {
+        self.min.as_ref()
+    }
+
+    /// Returns the largest value ever inserted into the column, `None` if no
+    /// non-null values have been inserted or if the column is a struct, array
+    /// or unit column
+    pub const fn max(&self) -> Option<&Constant> {
+        self.max.as_ref()
+    }
+
+    /// Returns the weighted number of nulls within the column
+    pub const fn null_count(&self) -> i64 {
+        self.null_count
+    }
+
+    /// Returns an estimate of the number of distinct non-null values ever
+    /// inserted into the column
+    pub fn ndv(&self) -> u64 {
+        self.ndv.estimate()
+    }
+
+    fn record(&mut self, value: Constant, weight: i32) {
+        // Retractions can't shrink the minimum, maximum or distinct values
+        if weight <= 0 {
+            return;
+        }
+
+        self.ndv.insert(&value);
+
+        if self.min.as_ref().map_or(true, |min| value < *min) {
+            self.min = Some(value.clone());
+        }
+        if self.max.as_ref().map_or(true, |max| value > *max) {
+            self.max = Some(value);
+        }
+    }
+}
+
+fn serialize_estimate<S>(ndv: &HyperLogLog, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u64(ndv.estimate())
+}
+
+/// The layouts needed to read the columns of a stream's rows
+#[derive(Debug, Clone)]
+pub struct StatisticsLayout {
+    native: NativeLayout,
+    row: RowLayout,
+}
+
+impl StatisticsLayout {
+    pub(super) fn new(native: NativeLayout, row: RowLayout) -> Self {
+        Self { native, row }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.row.len()
+    }
+
+    fn record(&self, columns: &mut [ColumnStatistics], row: &Row, weight: i32) {
+        debug_assert_eq!(columns.len(), self.row.len());
+
+        for (idx, column) in columns.iter_mut().enumerate() {
+            if self.row.column_nullable(idx) && row.column_is_null(idx, &self.native) {
+                column.null_count += weight as i64;
+                continue;
+            }
+
+            // Unit columns have no meaningful values to collect
+            let ty = self.row.column_type(idx);
+            if ty.is_unit() {
+                continue;
+            }
+
+            // Safety: The row is initialized and of the current layout
+            let value = unsafe {
+                read_scalar_from(ty, row.as_ptr().add(self.native.offset_of(idx) as usize))
+            };
+            if let Some(value) = value {
+                column.record(value, weight);
+            }
+        }
+    }
+}
+
+/// The number of bits of each hash used to select a register
+const PRECISION: u32 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// A [HyperLogLog](https://en.wikipedia.org/wiki/HyperLogLog) sketch for
+/// estimating the number of distinct values within a column, it has a
+/// standard error of roughly 1.6%
+#[derive(Clone)]
+pub struct HyperLogLog {
+    registers: Box<[u8; REGISTERS]>,
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        Self {
+            registers: Box::new([0; REGISTERS]),
+        }
+    }
+
+    pub fn insert<T>(&mut self, value: &T)
+    where
+        T: Hash + ?Sized,
+    {
+        let mut hasher = Xxh3::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // The top bits select the register and the position of the first set bit
+        // within the remaining ones is its rank, the low sentinel bit bounds the
+        // rank for hashes whose remaining bits are all zero
+        let register = (hash >> (u64::BITS - PRECISION)) as usize;
+        let remaining = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = remaining.leading_zeros() as u8 + 1;
+
+        let current = &mut self.registers[register];
+        *current = (*current).max(rank);
+    }
+
+    /// Returns the estimated number of distinct values inserted into the sketch
+    pub fn estimate(&self) -> u64 {
+        let registers = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / registers);
+
+        let (mut sum, mut zeros) = (0.0, 0);
+        for &register in self.registers.iter() {
+            sum += 1.0 / (1u64 << register) as f64;
+            zeros += (register == 0) as usize;
+        }
+        let estimate = alpha * registers * registers / sum;
+
+        // Use linear counting for small cardinalities where the raw estimate is
+        // heavily biased
+        if estimate <= 2.5 * registers && zeros != 0 {
+            (registers * (registers / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for HyperLogLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HyperLogLog")
+            .field("estimate", &self.estimate())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dataflow::HyperLogLog;
+
+    #[test]
+    fn hyperloglog_estimates() {
+        for cardinality in [0u64, 1, 10, 1000, 100_000] {
+            let mut sketch = HyperLogLog::new();
+
+            // Duplicates don't affect the estimate
+            for _ in 0..2 {
+                for value in 0..cardinality {
+                    sketch.insert(&value);
+                }
+            }
+
+            let estimate = sketch.estimate() as f64;
+            let error = (estimate - cardinality as f64).abs() / (cardinality.max(1) as f64);
+            assert!(
+                error < 0.05,
+                "estimated {estimate} distinct values for {cardinality} distinct values",
+            );
+        }
+    }
+}
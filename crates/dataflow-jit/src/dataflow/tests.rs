@@ -2,19 +2,24 @@
 
 use crate::{
     codegen::CodegenConfig,
-    dataflow::{CompiledDataflow, RowOutput},
+    dataflow::{row_from_literal, CompiledDataflow, RowOutput},
+    interpreter::{Interpreter, InterpreterError},
     ir::{
         graph::GraphExt,
-        nodes::{Min, Minus, MonotonicJoin, StreamKind, StreamLayout, Sum},
-        ColumnType, Constant, FunctionBuilder, Graph, RowLayoutBuilder,
+        literal::{NullableConstant, RowLiteral, StreamCollection, StreamLiteral},
+        nodes::{Min, Minus, MonotonicJoin, Sink, SinkOutputMode, StreamKind, StreamLayout, Sum},
+        ColumnType, Constant, FunctionBuilder, Graph, LayoutId, NodeId, RowLayoutBuilder,
     },
     row::UninitRow,
+    sql_graph::SqlGraph,
     utils,
 };
 use dbsp::{
+    monitor::TraceMonitor,
     trace::{BatchReader, Cursor},
-    Runtime,
+    Circuit, RootCircuit, Runtime,
 };
+use std::collections::BTreeMap;
 
 #[test]
 fn compiled_dataflow() {
@@ -109,7 +114,7 @@ fn compiled_dataflow() {
     let layout = layout_cache.layout_of(xy_layout);
     for (x, y) in [(1, 2), (0, 0), (1000, 2000), (12, 12)] {
         unsafe {
-            let mut row = UninitRow::new(&*jit_handle.vtables[&xy_layout]);
+            let mut row = UninitRow::new(&*jit_handle.vtables()[&xy_layout]);
             row.as_mut_ptr()
                 .add(layout.offset_of(0) as usize)
                 .cast::<u32>()
@@ -167,7 +172,7 @@ fn compiled_dataflow() {
 
     runtime.kill().unwrap();
 
-    unsafe { jit_handle.free_memory() };
+    jit_handle.free_memory();
 }
 
 #[test]
@@ -467,5 +472,643 @@ fn bfs() {
         assert_eq!(produced, expected);
     }
 
-    unsafe { jit_handle.free_memory() };
+    jit_handle.free_memory();
+}
+
+#[test]
+fn dropping_jit_handle_while_running() {
+    utils::test_logger();
+
+    let mut graph = Graph::new();
+
+    let x_layout = graph.layout_cache().add(
+        RowLayoutBuilder::new()
+            .with_column(ColumnType::U32, false)
+            .build(),
+    );
+
+    let source = graph.source(x_layout);
+    let doubled = graph.map(
+        source,
+        StreamLayout::Set(x_layout),
+        StreamLayout::Set(x_layout),
+        {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let input = func.add_input(x_layout);
+            let output = func.add_output(x_layout);
+
+            let x = func.load(input, 0);
+            let doubled = func.add(x, x);
+            func.store(output, 0, doubled);
+
+            func.ret_unit();
+            func.build()
+        },
+    );
+    // Distinct holds onto rows within its traces until the circuit is dropped
+    let distinct = graph.distinct(doubled);
+    let sink = graph.sink(distinct);
+
+    graph.optimize();
+
+    let (dataflow, jit_handle, layout_cache) =
+        CompiledDataflow::new(&graph, CodegenConfig::debug());
+    let vtable = unsafe { &*jit_handle.vtables()[&x_layout] };
+    let offset = layout_cache.layout_of(x_layout).offset_of(0) as usize;
+
+    let (mut runtime, (mut inputs, outputs)) =
+        Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)).unwrap();
+
+    // The circuit keeps the compiled code alive after the handle is dropped
+    jit_handle.free_memory();
+
+    for step in 0..5u32 {
+        let mut values: Vec<_> = (0..10)
+            .map(|x| unsafe {
+                let mut row = UninitRow::new(vtable);
+                row.as_mut_ptr()
+                    .add(offset)
+                    .cast::<u32>()
+                    .write(step * 10 + x);
+                (row.assume_init(), 1i32)
+            })
+            .collect();
+        inputs
+            .get_mut(&source)
+            .unwrap()
+            .as_set_mut()
+            .unwrap()
+            .append(&mut values);
+
+        runtime.step().unwrap();
+
+        let output = outputs[&sink].as_set().unwrap().consolidate();
+        let mut produced = Vec::new();
+        let mut cursor = output.cursor();
+        while cursor.key_valid() {
+            let x = unsafe { *cursor.key().as_ptr().add(offset).cast::<u32>() };
+            produced.push((x, cursor.weight()));
+
+            cursor.step_key();
+        }
+
+        let expected: Vec<_> = (0..10).map(|x| ((step * 10 + x) * 2, 1)).collect();
+        assert_eq!(produced, expected);
+    }
+
+    runtime.kill().unwrap();
+}
+
+#[test]
+fn dropping_handles_after_circuit() {
+    utils::test_logger();
+
+    let mut graph = Graph::new();
+
+    let x_layout = graph.layout_cache().add(
+        RowLayoutBuilder::new()
+            .with_column(ColumnType::U32, false)
+            .build(),
+    );
+
+    let source = graph.source(x_layout);
+    let incremented = graph.map(
+        source,
+        StreamLayout::Set(x_layout),
+        StreamLayout::Set(x_layout),
+        {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let input = func.add_input(x_layout);
+            let output = func.add_output(x_layout);
+
+            let x = func.load(input, 0);
+            let one = func.constant(Constant::U32(1));
+            let incremented = func.add(x, one);
+            func.store(output, 0, incremented);
+
+            func.ret_unit();
+            func.build()
+        },
+    );
+    let sink = graph.sink(incremented);
+
+    graph.optimize();
+
+    let (dataflow, jit_handle, layout_cache) =
+        CompiledDataflow::new(&graph, CodegenConfig::debug());
+    let vtable = unsafe { &*jit_handle.vtables()[&x_layout] };
+    let offset = layout_cache.layout_of(x_layout).offset_of(0) as usize;
+
+    let (mut runtime, (mut inputs, outputs)) =
+        Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)).unwrap();
+
+    let mut push_rows = |values: &[u32]| {
+        let mut rows: Vec<_> = values
+            .iter()
+            .map(|&x| unsafe {
+                let mut row = UninitRow::new(vtable);
+                row.as_mut_ptr().add(offset).cast::<u32>().write(x);
+                (row.assume_init(), 1i32)
+            })
+            .collect();
+        inputs
+            .get_mut(&source)
+            .unwrap()
+            .as_set_mut()
+            .unwrap()
+            .append(&mut rows);
+    };
+
+    push_rows(&[1, 2, 3]);
+    runtime.step().unwrap();
+    let output = outputs[&sink].as_set().unwrap().consolidate();
+
+    // Leave rows buffered within the input handle and the output handle's mailbox
+    push_rows(&[4, 5]);
+    runtime.step().unwrap();
+    push_rows(&[6]);
+    drop(push_rows);
+
+    runtime.kill().unwrap();
+    jit_handle.free_memory();
+
+    // The rows read out of the circuit are still usable
+    let mut produced = Vec::new();
+    let mut cursor = output.cursor();
+    while cursor.key_valid() {
+        let x = unsafe { *cursor.key().as_ptr().add(offset).cast::<u32>() };
+        produced.push((x, cursor.weight()));
+        cursor.step_key();
+    }
+    assert_eq!(produced, vec![(2, 1), (3, 1), (4, 1)]);
+
+    // The handles drop their rows before releasing the compiled code
+    drop(inputs);
+    drop(outputs);
+    drop(output);
+}
+
+#[test]
+fn deterministic_construction() {
+    utils::test_logger();
+
+    fn branching_graph() -> Graph {
+        let mut graph = Graph::new();
+
+        let x_layout = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .build(),
+        );
+
+        let map_fn = |graph: &Graph, factor: u32| {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let input = func.add_input(x_layout);
+            let output = func.add_output(x_layout);
+
+            let x = func.load(input, 0);
+            let factor = func.constant(Constant::U32(factor));
+            let scaled = func.mul(x, factor);
+            func.store(output, 0, scaled);
+
+            func.ret_unit();
+            func.build()
+        };
+        let filter_fn = |graph: &Graph, bound: u32| {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let input = func.add_input(x_layout);
+
+            let x = func.load(input, 0);
+            let bound = func.constant(Constant::U32(bound));
+            let less = func.lt(x, bound);
+
+            func.ret(less);
+            func.build()
+        };
+
+        let lhs = graph.source(x_layout);
+        let rhs = graph.source(x_layout);
+
+        for (source, factor) in [(lhs, 2), (rhs, 3), (lhs, 5)] {
+            let func = map_fn(&graph, factor);
+            let mapped = graph.map(
+                source,
+                StreamLayout::Set(x_layout),
+                StreamLayout::Set(x_layout),
+                func,
+            );
+
+            let func = filter_fn(&graph, factor * 100);
+            let filtered = graph.filter(mapped, func);
+            let distinct = graph.distinct(filtered);
+            graph.sink(distinct);
+        }
+
+        let sum = graph.add_node(Sum::new(vec![rhs, lhs]));
+        graph.sink(sum);
+
+        graph
+    }
+
+    let construct = |mut graph: Graph| {
+        graph.optimize();
+
+        let (dataflow, jit_handle, _) = CompiledDataflow::new(&graph, CodegenConfig::debug());
+        let symbols = jit_handle.function_symbols().to_vec();
+
+        let monitor = TraceMonitor::new_panic_on_error();
+        let (circuit, _) = RootCircuit::build(|circuit| {
+            monitor.attach_circuit_events(circuit, "monitor");
+            dataflow.construct(circuit)
+        })
+        .unwrap();
+        let dot = monitor.visualize_circuit().to_dot();
+
+        drop(circuit);
+        jit_handle.free_memory();
+
+        (dot, symbols)
+    };
+
+    let (expected_dot, expected_symbols) = construct(branching_graph());
+    assert!(!expected_symbols.is_empty());
+
+    // Rebuilding the graph directly produces an identical circuit
+    let (dot, symbols) = construct(branching_graph());
+    assert_eq!(dot, expected_dot);
+    assert_eq!(symbols, expected_symbols);
+
+    // And so does rebuilding it from its serialized form
+    let json = serde_json::to_string(&SqlGraph::from(branching_graph())).unwrap();
+    let graph = serde_json::from_str::<SqlGraph>(&json)
+        .unwrap()
+        .rematerialize();
+    let (dot, symbols) = construct(graph);
+    assert_eq!(dot, expected_dot);
+    assert_eq!(symbols, expected_symbols);
+}
+
+#[test]
+fn constant_sources() {
+    utils::test_logger();
+
+    let mut graph = Graph::new();
+
+    let x_layout = graph.layout_cache().add(
+        RowLayoutBuilder::new()
+            .with_column(ColumnType::U32, false)
+            .build(),
+    );
+    let literal = |values: &[u32]| {
+        StreamLiteral::new(
+            StreamLayout::Set(x_layout),
+            StreamCollection::Set(
+                values
+                    .iter()
+                    .map(|&x| {
+                        let row = vec![NullableConstant::NonNull(Constant::U32(x))];
+                        (RowLiteral::new(row), 1)
+                    })
+                    .collect(),
+            ),
+        )
+    };
+
+    // `SELECT x * 2 FROM (VALUES (1), (2), (3)) UNION ALL VALUES (3), (4)`
+    let values = graph.constant_source(literal(&[1, 2, 3]));
+    let doubled = graph.map(
+        values,
+        StreamLayout::Set(x_layout),
+        StreamLayout::Set(x_layout),
+        {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let input = func.add_input(x_layout);
+            let output = func.add_output(x_layout);
+
+            let x = func.load(input, 0);
+            let doubled = func.add(x, x);
+            func.store(output, 0, doubled);
+
+            func.ret_unit();
+            func.build()
+        },
+    );
+    let more_values = graph.constant_source(literal(&[3, 4]));
+    let union = graph.add_node(Sum::new(vec![doubled, more_values]));
+    let sink = graph.sink(union);
+
+    graph.optimize();
+
+    let (dataflow, jit_handle, layout_cache) =
+        CompiledDataflow::new(&graph, CodegenConfig::debug());
+    let offset = layout_cache.layout_of(x_layout).offset_of(0) as usize;
+
+    let (mut runtime, (inputs, outputs)) =
+        Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)).unwrap();
+    assert!(inputs.is_empty());
+
+    for step in 0..3 {
+        runtime.step().unwrap();
+
+        let output = outputs[&sink].as_set().unwrap().consolidate();
+        let mut produced = Vec::new();
+        let mut cursor = output.cursor();
+        while cursor.key_valid() {
+            let x = unsafe { *cursor.key().as_ptr().add(offset).cast::<u32>() };
+            produced.push((x, cursor.weight()));
+
+            cursor.step_key();
+        }
+
+        // The constant sources only produce their values on the first step
+        if step == 0 {
+            assert_eq!(produced, vec![(2, 1), (3, 1), (4, 2), (6, 1)]);
+        } else {
+            assert_eq!(produced, Vec::new());
+        }
+    }
+
+    runtime.kill().unwrap();
+    jit_handle.free_memory();
+}
+
+#[test]
+fn collect_statistics() {
+    utils::test_logger();
+
+    let mut graph = Graph::new();
+
+    let layout = graph.layout_cache().add(
+        RowLayoutBuilder::new()
+            .with_column(ColumnType::I32, false)
+            .with_column(ColumnType::String, true)
+            .build(),
+    );
+
+    let source = graph.source(layout);
+    let statistics_node = graph.collect_statistics(source);
+    graph.sink(source);
+
+    graph.optimize();
+
+    let (dataflow, jit_handle, layout_cache) =
+        CompiledDataflow::new(&graph, CodegenConfig::debug());
+    let vtable = unsafe { &*jit_handle.vtables()[&layout] };
+    let statistics = dataflow.statistics();
+
+    let (mut runtime, (mut inputs, _outputs)) =
+        Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)).unwrap();
+
+    let mut step = |rows: &[(i32, Option<&str>, i32)]| {
+        let mut values: Vec<_> = rows
+            .iter()
+            .map(|&(x, y, weight)| {
+                let literal = RowLiteral::new(vec![
+                    NullableConstant::NonNull(Constant::I32(x)),
+                    NullableConstant::Nullable(y.map(|y| Constant::String(y.to_owned()))),
+                ]);
+                let row = unsafe { row_from_literal(&literal, vtable, &layout_cache) };
+                (row, weight)
+            })
+            .collect();
+        inputs
+            .get_mut(&source)
+            .unwrap()
+            .as_set_mut()
+            .unwrap()
+            .append(&mut values);
+
+        runtime.step().unwrap();
+    };
+
+    step(&[(1, Some("a")), (5, None), (-3, Some("b")), (5, Some("a"))].map(|(x, y)| (x, y, 1)));
+    step(&[(-3, Some("b"), -1), (7, None, 1)]);
+
+    let stats = statistics.get(statistics_node).unwrap();
+    assert_eq!(stats.rows(), 4);
+    assert!(stats.values().is_empty());
+
+    // Retracting -3 doesn't shrink the minimum
+    let x = &stats.keys()[0];
+    assert_eq!(x.min(), Some(&Constant::I32(-3)));
+    assert_eq!(x.max(), Some(&Constant::I32(7)));
+    assert_eq!(x.null_count(), 0);
+    assert!((3..=5).contains(&x.ndv()), "{}", x.ndv());
+
+    let y = &stats.keys()[1];
+    assert_eq!(y.min(), Some(&Constant::String("a".to_owned())));
+    assert_eq!(y.max(), Some(&Constant::String("b".to_owned())));
+    assert_eq!(y.null_count(), 2);
+    assert!((1..=3).contains(&y.ndv()), "{}", y.ndv());
+
+    runtime.kill().unwrap();
+    jit_handle.free_memory();
+}
+
+#[test]
+fn disabled_statistics_add_no_operators() {
+    utils::test_logger();
+
+    let graph = |collect_statistics: bool| {
+        let mut graph = Graph::new();
+
+        let layout = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .build(),
+        );
+
+        let source = graph.source(layout);
+        if collect_statistics {
+            graph.collect_statistics(source);
+        }
+        graph.sink(source);
+
+        graph.optimize();
+        graph
+    };
+
+    let operators = |graph: &Graph, config: CodegenConfig| {
+        let (dataflow, jit_handle, _) = CompiledDataflow::new(graph, config);
+
+        let (circuit, nodes) = RootCircuit::build(|circuit| {
+            dataflow.construct(circuit);
+            circuit.num_nodes()
+        })
+        .unwrap();
+
+        drop(circuit);
+        jit_handle.free_memory();
+
+        nodes
+    };
+
+    let without_statistics = operators(&graph(false), CodegenConfig::debug());
+    let disabled = operators(
+        &graph(true),
+        CodegenConfig::debug().with_collect_statistics(false),
+    );
+    let enabled = operators(&graph(true), CodegenConfig::debug());
+
+    assert_eq!(disabled, without_statistics);
+    assert!(enabled > without_statistics);
+}
+
+/// Builds a graph with a source of `u32`s feeding a sink of each output mode
+fn sink_modes_graph() -> (Graph, LayoutId, NodeId, [NodeId; 3]) {
+    let mut graph = Graph::new();
+
+    let layout = graph.layout_cache().add(
+        RowLayoutBuilder::new()
+            .with_column(ColumnType::U32, false)
+            .build(),
+    );
+
+    let source = graph.source(layout);
+    let sinks = [
+        SinkOutputMode::Deltas,
+        SinkOutputMode::NetDeltas,
+        SinkOutputMode::AppendOnly,
+    ]
+    .map(|mode| graph.add_node(Sink::new(source).with_output_mode(mode)));
+
+    graph.optimize();
+    (graph, layout, source, sinks)
+}
+
+fn u32_literal(value: u32) -> RowLiteral {
+    RowLiteral::new(vec![NullableConstant::NonNull(Constant::U32(value))])
+}
+
+#[test]
+fn sink_output_modes() {
+    utils::test_logger();
+
+    let (graph, layout, source, [deltas, net_deltas, append_only]) = sink_modes_graph();
+
+    let (dataflow, jit_handle, layout_cache) =
+        CompiledDataflow::new(&graph, CodegenConfig::debug());
+    let vtable = unsafe { &*jit_handle.vtables()[&layout] };
+
+    let (mut runtime, (mut inputs, outputs)) =
+        Runtime::init_circuit(2, move |circuit| dataflow.construct(circuit)).unwrap();
+
+    let mut step = |values: &[(u32, i32)]| {
+        // Pushing rows one at a time spreads them across both workers
+        let input = inputs.get_mut(&source).unwrap().as_set_mut().unwrap();
+        for &(value, weight) in values {
+            let row = unsafe { row_from_literal(&u32_literal(value), vtable, &layout_cache) };
+            input.push(row, weight);
+        }
+
+        runtime.step().unwrap();
+    };
+
+    // The weights of the batch each worker produced for the sink
+    let worker_weights = |sink| {
+        (0..2)
+            .map(|worker| {
+                let batch = outputs[&sink]
+                    .as_set()
+                    .unwrap()
+                    .take_from_worker(worker)
+                    .unwrap();
+
+                let mut weights = Vec::new();
+                let mut cursor = batch.cursor();
+                while cursor.key_valid() {
+                    weights.push(cursor.weight());
+                    cursor.step_key();
+                }
+                weights
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Each worker sees one insertion of the same row
+    step(&[(1, 1), (1, 1)]);
+    assert_eq!(worker_weights(deltas), [vec![1], vec![1]]);
+    assert_eq!(worker_weights(net_deltas), [vec![2], vec![]]);
+    assert_eq!(worker_weights(append_only), [vec![2], vec![]]);
+
+    // An insertion and a retraction of the same row on different workers cancel
+    // out, so the append-only sink accepts them
+    step(&[(2, 1), (2, -1)]);
+    assert_eq!(worker_weights(deltas), [vec![1], vec![-1]]);
+    assert_eq!(worker_weights(net_deltas), [vec![], vec![]]);
+    assert_eq!(worker_weights(append_only), [vec![], vec![]]);
+
+    runtime.kill().unwrap();
+    jit_handle.free_memory();
+}
+
+#[test]
+fn append_only_sink_rejects_retractions() {
+    utils::test_logger();
+
+    let (graph, layout, source, [.., append_only]) = sink_modes_graph();
+    let steps: [&[(u32, i32)]; 2] = [&[(1, 1), (2, 1)], &[(1, -1), (3, 1)]];
+
+    let mut interpreter = Interpreter::new(&graph, CodegenConfig::debug()).unwrap();
+    let results = steps.map(|values| {
+        let rows = values
+            .iter()
+            .map(|&(value, weight)| (u32_literal(value), weight))
+            .collect();
+        interpreter.step(&BTreeMap::from([(source, StreamCollection::Set(rows))]))
+    });
+
+    assert!(results[0].is_ok());
+    match &results[1] {
+        Err(InterpreterError::Retraction { node, row, weight }) => {
+            assert_eq!(*node, append_only);
+            assert_eq!(row, "{ 1u32 }");
+            assert_eq!(*weight, -1);
+        }
+
+        result => panic!("expected a retraction error, got {result:?}"),
+    }
+
+    let (dataflow, jit_handle, layout_cache) =
+        CompiledDataflow::new(&graph, CodegenConfig::debug());
+    let vtable = unsafe { &*jit_handle.vtables()[&layout] };
+
+    let (mut runtime, (mut inputs, outputs)) =
+        Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)).unwrap();
+
+    let results = steps.map(|values| {
+        let mut rows = values
+            .iter()
+            .map(|&(value, weight)| {
+                let row = unsafe { row_from_literal(&u32_literal(value), vtable, &layout_cache) };
+                (row, weight)
+            })
+            .collect();
+        inputs
+            .get_mut(&source)
+            .unwrap()
+            .as_set_mut()
+            .unwrap()
+            .append(&mut rows);
+
+        runtime.step()
+    });
+
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err(), "the retraction should fail the step");
+
+    // The runtime can't be cleanly killed once a worker has panicked
+    let _ = runtime.kill();
+    drop((inputs, outputs));
+    jit_handle.free_memory();
+}
+
+#[test]
+fn sink_output_mode_defaults_to_deltas() {
+    let sink: Sink = serde_json::from_str(r#"{ "input": 1 }"#).unwrap();
+    assert_eq!(sink.output_mode(), SinkOutputMode::Deltas);
+
+    let sink: Sink =
+        serde_json::from_str(r#"{ "input": 1, "output_mode": "append_only" }"#).unwrap();
+    assert_eq!(sink.output_mode(), SinkOutputMode::AppendOnly);
 }
@@ -24,7 +24,8 @@ impl DbspCircuit {
         let mut graph = build();
 
         {
-            let mut validator = Validator::new(graph.layout_cache().clone());
+            let mut validator =
+                Validator::new(graph.layout_cache().clone()).with_udfs(config.udf_signatures());
             validator
                 .validate_graph(&graph)
                 .expect("failed to validate graph before optimization");
@@ -0,0 +1,1199 @@
+//! Evaluates IR functions over dynamically typed rows
+
+use crate::{
+    codegen::{CodegenConfig, UdfTrampolines},
+    interpreter::{
+        value::{DynRow, Value},
+        InterpreterError,
+    },
+    ir::{
+        exprs::{Call, CallUdf},
+        BinaryOp, BinaryOpKind, BlockId, Cast, ColumnType, Constant, Expr, ExprId, Function,
+        LayoutId, RValue, RowLayoutCache, Terminator, UnaryOp, UnaryOpKind,
+    },
+    ThinStr,
+};
+use chrono::{DateTime, Datelike, LocalResult, TimeZone, Timelike, Utc};
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+type Result<T> = std::result::Result<T, InterpreterError>;
+
+/// A scalar value, strings are shared so that loads, stores and in-place
+/// string mutations alias the same way they do within generated code
+#[derive(Debug, Clone)]
+enum Scalar {
+    Value(Value),
+    String(Rc<RefCell<String>>),
+}
+
+impl Scalar {
+    fn string(string: String) -> Self {
+        Self::String(Rc::new(RefCell::new(string)))
+    }
+
+    fn from_value(value: Value) -> Self {
+        match value {
+            Value::String(string) => Self::string(string),
+            value => Self::Value(value),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Self::Value(value) => value.clone(),
+            Self::String(string) => Value::String(string.borrow().clone()),
+        }
+    }
+
+    fn value(&self, expr: ExprId) -> Result<&Value> {
+        match self {
+            Self::Value(value) => Ok(value),
+            Self::String(_) => Err(InterpreterError::Unsupported(format!(
+                "{expr} is a string where a non-string scalar was expected",
+            ))),
+        }
+    }
+
+    fn str(&self, expr: ExprId) -> Result<&Rc<RefCell<String>>> {
+        match self {
+            Self::String(string) => Ok(string),
+            Self::Value(value) => Err(InterpreterError::Unsupported(format!(
+                "{expr} is a {} where a string was expected",
+                value.column_type(),
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RowData {
+    layout: LayoutId,
+    columns: Vec<Scalar>,
+    nulls: Vec<bool>,
+}
+
+type RowRef = Rc<RefCell<RowData>>;
+type RowVec = Rc<RefCell<Vec<DynRow>>>;
+
+#[derive(Debug, Clone)]
+enum Binding {
+    Scalar(Scalar),
+    Row(RowRef),
+    RowVec(RowVec),
+}
+
+/// An output produced by a function call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Output {
+    /// An output row
+    Row(DynRow),
+    /// The rows pushed to an output row vector
+    Rows(Vec<DynRow>),
+}
+
+impl Output {
+    pub(crate) fn into_row(self) -> DynRow {
+        match self {
+            Self::Row(row) => row,
+            Self::Rows(_) => unreachable!("expected an output row"),
+        }
+    }
+
+    pub(crate) fn into_rows(self) -> Vec<DynRow> {
+        match self {
+            Self::Rows(rows) => rows,
+            Self::Row(_) => unreachable!("expected an output row vector"),
+        }
+    }
+}
+
+/// The result of calling a function with [`FunctionInterpreter::call()`]
+#[derive(Debug)]
+pub(crate) struct CallResult {
+    /// The function's return value
+    pub(crate) ret: Value,
+    /// The function's output and inout arguments in the order they were
+    /// declared in
+    pub(crate) outputs: Vec<Output>,
+}
+
+pub(crate) struct FunctionInterpreter<'a> {
+    layout_cache: &'a RowLayoutCache,
+    config: CodegenConfig,
+    /// Trampolines for calling the udfs registered with `config`, `None` if
+    /// there are no udfs
+    udfs: Option<UdfTrampolines>,
+}
+
+impl<'a> FunctionInterpreter<'a> {
+    pub(crate) fn new(layout_cache: &'a RowLayoutCache, config: CodegenConfig) -> Self {
+        let udfs = (!config.udf_signatures().is_empty()).then(|| UdfTrampolines::new(&config));
+
+        Self {
+            layout_cache,
+            config,
+            udfs,
+        }
+    }
+
+    /// Calls `function`, input (and inout) arguments are taken from `inputs`
+    /// in order and fresh rows are created for all output arguments
+    pub(crate) fn call(&self, function: &Function, inputs: &[&DynRow]) -> Result<CallResult> {
+        let row_vector = self.layout_cache.row_vector();
+
+        let (mut args, mut outputs, mut inputs) = (Vec::new(), Vec::new(), inputs.iter());
+        for arg in function.args() {
+            let binding = if arg.flags.is_input() {
+                let input = inputs.next().ok_or_else(|| {
+                    InterpreterError::Unsupported(format!(
+                        "missing input for argument {} of function",
+                        arg.id,
+                    ))
+                })?;
+                Binding::Row(Rc::new(RefCell::new(self.row_from_dyn(input, arg.layout))))
+            } else if arg.layout == row_vector {
+                Binding::RowVec(Rc::new(RefCell::new(Vec::new())))
+            } else {
+                Binding::Row(Rc::new(RefCell::new(self.zeroed_row(arg.layout, false))))
+            };
+
+            if arg.flags.is_output() {
+                outputs.push(binding.clone());
+            }
+            args.push((arg.id, binding));
+        }
+
+        let ret = Frame::new(self, args).run(function)?;
+
+        let outputs = outputs
+            .into_iter()
+            .map(|output| match output {
+                Binding::Row(row) => Output::Row(self.row_to_dyn(&row.borrow())),
+                Binding::RowVec(rows) => Output::Rows(rows.take()),
+                Binding::Scalar(_) => unreachable!(),
+            })
+            .collect();
+
+        Ok(CallResult { ret, outputs })
+    }
+
+    fn zeroed_row(&self, layout: LayoutId, null: bool) -> RowData {
+        let row_layout = self.layout_cache.get(layout);
+        let (columns, nulls) = row_layout
+            .iter()
+            .map(|(ty, nullable)| (Scalar::from_value(Value::zeroed(ty)), null && nullable))
+            .unzip();
+
+        RowData {
+            layout,
+            columns,
+            nulls,
+        }
+    }
+
+    fn row_from_dyn(&self, row: &DynRow, layout: LayoutId) -> RowData {
+        let row_layout = self.layout_cache.get(layout);
+        let (columns, nulls) = row_layout
+            .iter()
+            .enumerate()
+            .map(|(idx, (ty, _))| match row.columns().get(idx) {
+                Some(Some(value)) => (Scalar::from_value(value.clone()), false),
+                Some(None) => (Scalar::from_value(Value::zeroed(ty)), true),
+                // Unit values of sets are represented by empty rows
+                None => (Scalar::from_value(Value::zeroed(ty)), false),
+            })
+            .unzip();
+
+        RowData {
+            layout,
+            columns,
+            nulls,
+        }
+    }
+
+    fn row_to_dyn(&self, row: &RowData) -> DynRow {
+        let row_layout = self.layout_cache.get(row.layout);
+        let columns = row_layout
+            .iter()
+            .zip(row.columns.iter().zip(&row.nulls))
+            .map(|((ty, nullable), (value, &null))| {
+                if nullable && null {
+                    None
+                } else {
+                    Some(value.to_value().retype(ty))
+                }
+            })
+            .collect();
+
+        DynRow::new(columns)
+    }
+}
+
+/// The state of a single function invocation
+struct Frame<'a, 'b> {
+    interpreter: &'b FunctionInterpreter<'a>,
+    bindings: BTreeMap<ExprId, Binding>,
+}
+
+impl<'a, 'b> Frame<'a, 'b> {
+    fn new(interpreter: &'b FunctionInterpreter<'a>, args: Vec<(ExprId, Binding)>) -> Self {
+        Self {
+            interpreter,
+            bindings: args.into_iter().collect(),
+        }
+    }
+
+    fn run(mut self, function: &Function) -> Result<Value> {
+        let mut block_id = function.entry_block();
+
+        loop {
+            let block = &function.blocks()[&block_id];
+            for (expr_id, expr) in block.body() {
+                self.expr(*expr_id, expr)?;
+            }
+
+            let (target, params) = match block.terminator() {
+                Terminator::Return(ret) => {
+                    return self
+                        .rvalue(ret.value())
+                        .map(|scalar| scalar.to_value().retype(function.return_type()));
+                }
+
+                Terminator::Jump(jump) => (jump.target(), jump.params()),
+
+                Terminator::Branch(branch) => {
+                    if self.bool_rvalue(branch.cond())? {
+                        (branch.truthy(), branch.true_params())
+                    } else {
+                        (branch.falsy(), branch.false_params())
+                    }
+                }
+
+                Terminator::Unreachable => {
+                    return Err(InterpreterError::Trap(format!(
+                        "reached an unreachable terminator in {block_id}",
+                    )))
+                }
+            };
+
+            self.jump(function, target, params)?;
+            block_id = target;
+        }
+    }
+
+    fn jump(&mut self, function: &Function, target: BlockId, params: &[ExprId]) -> Result<()> {
+        let args = params
+            .iter()
+            .map(|&param| self.binding(param).cloned())
+            .collect::<Result<Vec<_>>>()?;
+
+        for (&(param, _), arg) in function.blocks()[&target].params().iter().zip(args) {
+            self.bindings.insert(param, arg);
+        }
+
+        Ok(())
+    }
+
+    fn binding(&self, expr: ExprId) -> Result<&Binding> {
+        self.bindings.get(&expr).ok_or_else(|| {
+            InterpreterError::Unsupported(format!("use of undefined expression {expr}"))
+        })
+    }
+
+    fn scalar(&self, expr: ExprId) -> Result<&Scalar> {
+        match self.binding(expr)? {
+            Binding::Scalar(scalar) => Ok(scalar),
+            Binding::Row(_) | Binding::RowVec(_) => Err(InterpreterError::Unsupported(format!(
+                "{expr} is a row where a scalar was expected",
+            ))),
+        }
+    }
+
+    fn value(&self, expr: ExprId) -> Result<&Value> {
+        self.scalar(expr)?.value(expr)
+    }
+
+    fn row(&self, expr: ExprId) -> Result<&RowRef> {
+        match self.binding(expr)? {
+            Binding::Row(row) => Ok(row),
+            Binding::Scalar(_) | Binding::RowVec(_) => Err(InterpreterError::Unsupported(format!(
+                "{expr} is not a row"
+            ))),
+        }
+    }
+
+    fn rvalue(&self, rvalue: &RValue) -> Result<Scalar> {
+        match rvalue {
+            &RValue::Expr(expr) => self.scalar(expr).cloned(),
+            RValue::Imm(constant) => Ok(constant_scalar(constant)),
+        }
+    }
+
+    fn bool_rvalue(&self, rvalue: &RValue) -> Result<bool> {
+        self.rvalue(rvalue)?.to_value().as_bool().ok_or_else(|| {
+            InterpreterError::Unsupported(format!("non-boolean condition {rvalue:?}"))
+        })
+    }
+
+    fn bool(&self, expr: ExprId) -> Result<bool> {
+        self.value(expr)?
+            .as_bool()
+            .ok_or_else(|| InterpreterError::Unsupported(format!("{expr} is not a boolean value")))
+    }
+
+    fn set(&mut self, expr: ExprId, scalar: Scalar) {
+        self.bindings.insert(expr, Binding::Scalar(scalar));
+    }
+
+    fn set_row(&mut self, expr: ExprId, row: RowData) {
+        self.bindings
+            .insert(expr, Binding::Row(Rc::new(RefCell::new(row))));
+    }
+
+    fn expr(&mut self, expr_id: ExprId, expr: &Expr) -> Result<()> {
+        match expr {
+            Expr::Call(call) => self.call(expr_id, call),
+
+            Expr::CallUdf(call) => self.call_udf(expr_id, call),
+
+            Expr::Cast(cast) => {
+                let value = self.cast(cast)?;
+                self.set(expr_id, Scalar::Value(value));
+                Ok(())
+            }
+
+            Expr::Load(load) => {
+                let value = self.row(load.source())?.borrow().columns[load.column()].clone();
+                self.set(expr_id, value);
+                Ok(())
+            }
+
+            Expr::Store(store) => {
+                let value = self.rvalue(store.value())?;
+                self.row(store.target())?.borrow_mut().columns[store.column()] = value;
+                Ok(())
+            }
+
+            Expr::Select(select) => {
+                let selected = if self.bool(select.cond())? {
+                    select.if_true()
+                } else {
+                    select.if_false()
+                };
+
+                let binding = self.binding(selected)?.clone();
+                self.bindings.insert(expr_id, binding);
+                Ok(())
+            }
+
+            Expr::IsNull(is_null) => {
+                let null = self.row(is_null.target())?.borrow().nulls[is_null.column()];
+                self.set(expr_id, Scalar::Value(Value::Bool(null)));
+                Ok(())
+            }
+
+            Expr::SetNull(set_null) => {
+                let null = self.bool_rvalue(set_null.is_null())?;
+                self.row(set_null.target())?.borrow_mut().nulls[set_null.column()] = null;
+                Ok(())
+            }
+
+            Expr::BinOp(binop) => {
+                let value = self.binary_op(binop)?;
+                self.set(expr_id, Scalar::Value(value));
+                Ok(())
+            }
+
+            Expr::UnaryOp(unary) => {
+                let value = self.unary_op(unary)?;
+                self.set(expr_id, value);
+                Ok(())
+            }
+
+            Expr::Copy(copy) => {
+                let value = match self.scalar(copy.value())? {
+                    Scalar::String(string) => Scalar::string(string.borrow().clone()),
+                    value => value.clone(),
+                };
+                self.set(expr_id, value);
+                Ok(())
+            }
+
+            Expr::Constant(constant) => {
+                self.set(expr_id, constant_scalar(constant));
+                Ok(())
+            }
+
+            Expr::NullRow(null_row) => {
+                let row = self.interpreter.zeroed_row(null_row.layout(), true);
+                self.set_row(expr_id, row);
+                Ok(())
+            }
+
+            Expr::UninitRow(uninit) => {
+                let row = self.interpreter.zeroed_row(uninit.layout(), false);
+                self.set_row(expr_id, row);
+                Ok(())
+            }
+
+            Expr::CopyRowTo(copy) => {
+                if copy.src() != copy.dest() {
+                    let (columns, nulls) = {
+                        let src = self.row(copy.src())?.borrow();
+                        (src.columns.clone(), src.nulls.clone())
+                    };
+
+                    let mut dest = self.row(copy.dest())?.borrow_mut();
+                    dest.columns = columns;
+                    dest.nulls = nulls;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn cast(&self, cast: &Cast) -> Result<Value> {
+        let value = self.value(cast.value())?;
+        let (from, to) = (cast.from(), cast.to());
+
+        if from == to {
+            return Ok(value.clone());
+        }
+
+        let float = match *value {
+            Value::F32(float) => Some(float as f64),
+            Value::F64(float) => Some(float),
+            _ => None,
+        };
+
+        match float {
+            Some(float) if to.is_float() => Ok(Value::from_float(to, float)),
+
+            Some(float) => {
+                if !self.interpreter.config.saturating_float_to_int_casts
+                    && !float_fits_int(float, to)
+                {
+                    Err(InterpreterError::Trap(format!(
+                        "cast of {value} to {to} is out of range",
+                    )))
+                } else {
+                    Ok(Value::from_float(to, float))
+                }
+            }
+
+            None => {
+                let int = value.as_int().ok_or_else(|| {
+                    InterpreterError::Unsupported(format!("cast from {from} to {to}"))
+                })?;
+                Ok(Value::from_int(to, int))
+            }
+        }
+    }
+
+    fn binary_op(&self, binop: &BinaryOp) -> Result<Value> {
+        let (lhs, rhs) = (self.value(binop.lhs())?, self.value(binop.rhs())?);
+        let ty = binop.operand_ty();
+
+        match (lhs, rhs) {
+            (&Value::F32(lhs), &Value::F32(rhs)) => {
+                self.float_binary_op(binop.kind(), lhs as f64, rhs as f64, ty)
+            }
+            (&Value::F64(lhs), &Value::F64(rhs)) => {
+                self.float_binary_op(binop.kind(), lhs, rhs, ty)
+            }
+
+            (lhs, rhs) => match (lhs.as_int(), rhs.as_int()) {
+                (Some(lhs), Some(rhs)) => int_binary_op(binop.kind(), lhs, rhs, ty),
+                _ => Err(InterpreterError::Unsupported(format!(
+                    "{:?} between {} and {}",
+                    binop.kind(),
+                    lhs.column_type(),
+                    rhs.column_type(),
+                ))),
+            },
+        }
+    }
+
+    fn float_binary_op(
+        &self,
+        kind: BinaryOpKind,
+        lhs: f64,
+        rhs: f64,
+        ty: ColumnType,
+    ) -> Result<Value> {
+        let is_f32 = ty.is_f32();
+        // Performs the operation with the precision of the operand type
+        let arith = |op: fn(f64, f64) -> f64, op32: fn(f32, f32) -> f32| {
+            if is_f32 {
+                Value::F32(op32(lhs as f32, rhs as f32))
+            } else {
+                Value::F64(op(lhs, rhs))
+            }
+        };
+        let total = self.interpreter.config.total_float_comparisons;
+
+        Ok(match kind {
+            BinaryOpKind::Add => arith(|a, b| a + b, |a, b| a + b),
+            BinaryOpKind::Sub => arith(|a, b| a - b, |a, b| a - b),
+            BinaryOpKind::Mul => arith(|a, b| a * b, |a, b| a * b),
+            BinaryOpKind::Div => arith(|a, b| a / b, |a, b| a / b),
+            BinaryOpKind::Rem => arith(libm::fmod, libm::fmodf),
+            BinaryOpKind::Mod => arith(
+                |a, b| {
+                    let rem = libm::fmod(a, b);
+                    if rem < 0.0 {
+                        rem + b.abs()
+                    } else {
+                        rem
+                    }
+                },
+                |a, b| {
+                    let rem = libm::fmodf(a, b);
+                    if rem < 0.0 {
+                        rem + b.abs()
+                    } else {
+                        rem
+                    }
+                },
+            ),
+
+            BinaryOpKind::Eq if total => Value::Bool(lhs.total_cmp(&rhs).is_eq()),
+            BinaryOpKind::Neq if total => Value::Bool(lhs.total_cmp(&rhs).is_ne()),
+            BinaryOpKind::LessThan if total => Value::Bool(lhs.total_cmp(&rhs).is_lt()),
+            BinaryOpKind::GreaterThan if total => Value::Bool(lhs.total_cmp(&rhs).is_gt()),
+            BinaryOpKind::LessThanOrEqual if total => Value::Bool(lhs.total_cmp(&rhs).is_le()),
+            BinaryOpKind::GreaterThanOrEqual if total => Value::Bool(lhs.total_cmp(&rhs).is_ge()),
+            BinaryOpKind::Min if total => arith(
+                |a, b| if b.total_cmp(&a).is_lt() { b } else { a },
+                |a, b| if b.total_cmp(&a).is_lt() { b } else { a },
+            ),
+            BinaryOpKind::Max if total => arith(
+                |a, b| if b.total_cmp(&a).is_gt() { b } else { a },
+                |a, b| if b.total_cmp(&a).is_gt() { b } else { a },
+            ),
+
+            BinaryOpKind::Eq => Value::Bool(lhs == rhs),
+            BinaryOpKind::Neq => Value::Bool(lhs != rhs),
+            BinaryOpKind::LessThan => Value::Bool(lhs < rhs),
+            BinaryOpKind::GreaterThan => Value::Bool(lhs > rhs),
+            BinaryOpKind::LessThanOrEqual => Value::Bool(lhs <= rhs),
+            BinaryOpKind::GreaterThanOrEqual => Value::Bool(lhs >= rhs),
+            BinaryOpKind::Min => arith(ieee_min, |a, b| ieee_min(a as f64, b as f64) as f32),
+            BinaryOpKind::Max => arith(ieee_max, |a, b| ieee_max(a as f64, b as f64) as f32),
+
+            BinaryOpKind::DivFloor
+            | BinaryOpKind::ModFloor
+            | BinaryOpKind::And
+            | BinaryOpKind::Or
+            | BinaryOpKind::Xor => {
+                return Err(InterpreterError::Unsupported(format!(
+                    "{kind:?} between {ty} values",
+                )))
+            }
+        })
+    }
+
+    fn unary_op(&self, unary: &UnaryOp) -> Result<Scalar> {
+        let kind = unary.kind();
+
+        if kind == UnaryOpKind::StringLen {
+            let string = self.scalar(unary.value())?.str(unary.value())?;
+            return Ok(Scalar::Value(Value::U64(string.borrow().len() as u64)));
+        }
+
+        let value = self.value(unary.value())?;
+        let unsupported =
+            || InterpreterError::Unsupported(format!("{kind:?} on a {}", value.column_type()));
+
+        let result = match *value {
+            Value::Bool(value) if kind == UnaryOpKind::Not => Value::Bool(!value),
+
+            Value::F32(float) => {
+                Value::F32(float_unary_op(kind, float as f64).ok_or_else(unsupported)? as f32)
+            }
+            Value::F64(float) => Value::F64(float_unary_op(kind, float).ok_or_else(unsupported)?),
+
+            ref value => {
+                let ty = value.column_type();
+                if !ty.is_int() {
+                    return Err(unsupported());
+                }
+
+                let int = value.as_int().unwrap();
+                let bits = int_bits(ty);
+                let unsigned = (int as u128) & (u128::MAX >> (128 - bits));
+
+                let result = match kind {
+                    UnaryOpKind::Abs => {
+                        if ty.is_signed_int() && int < 0 {
+                            -int
+                        } else {
+                            int
+                        }
+                    }
+                    UnaryOpKind::Neg => int.wrapping_neg(),
+                    UnaryOpKind::Not => !int,
+
+                    UnaryOpKind::CountOnes => unsigned.count_ones() as i128,
+                    UnaryOpKind::CountZeroes => (bits - unsigned.count_ones()) as i128,
+                    UnaryOpKind::LeadingOnes => {
+                        (unsigned << (128 - bits)).leading_ones().min(bits) as i128
+                    }
+                    UnaryOpKind::LeadingZeroes => (unsigned.leading_zeros() - (128 - bits)) as i128,
+                    UnaryOpKind::TrailingOnes => unsigned.trailing_ones() as i128,
+                    UnaryOpKind::TrailingZeroes => unsigned.trailing_zeros().min(bits) as i128,
+                    UnaryOpKind::BitReverse => (unsigned.reverse_bits() >> (128 - bits)) as i128,
+                    UnaryOpKind::ByteReverse => (unsigned.swap_bytes() >> (128 - bits)) as i128,
+
+                    UnaryOpKind::Ceil
+                    | UnaryOpKind::Floor
+                    | UnaryOpKind::Trunc
+                    | UnaryOpKind::Sqrt
+                    | UnaryOpKind::StringLen => return Err(unsupported()),
+                };
+
+                Value::from_int(ty, result)
+            }
+        };
+
+        Ok(Scalar::Value(result))
+    }
+
+    /// Calls a udf through the same function pointer compiled code calls it
+    /// through, see [`crate::ir::udf`] for the ABI
+    fn call_udf(&mut self, expr_id: ExprId, call: &CallUdf) -> Result<()> {
+        let interpreter = self.interpreter;
+        let udf = interpreter
+            .udfs
+            .as_ref()
+            .and_then(|udfs| udfs.get(call.name()))
+            .ok_or_else(|| {
+                InterpreterError::Unsupported(format!(
+                    "call to the unregistered udf `{}` in {expr_id}",
+                    call.name(),
+                ))
+            })?;
+
+        if call.args().len() != udf.signature().arg_exprs() {
+            return Err(InterpreterError::Unsupported(format!(
+                "call to the udf `{}` in {expr_id} has {} arguments but the udf takes {}",
+                call.name(),
+                call.args().len(),
+                udf.signature().arg_exprs(),
+            )));
+        }
+
+        // Strings are passed as borrowed `ThinStr`s which are freed after the call
+        let mut strings = Vec::new();
+        let mut args = Vec::with_capacity(call.args().len());
+        for &arg in call.args() {
+            let slot = match self.scalar(arg)? {
+                Scalar::String(string) => {
+                    let string = ThinStr::from(&**string.borrow()).into_raw();
+                    strings.push(string);
+                    udf_slot((string as usize).to_ne_bytes())
+                }
+                Scalar::Value(value) => value_to_udf_slot(value, arg)?,
+            };
+            args.push(slot);
+        }
+
+        // Safety: The arguments were checked against the udf's signature by the
+        // validator and strings are alive until the end of the call
+        let (result, is_null) = unsafe { udf.call(&args) };
+        for string in strings {
+            drop(unsafe { ThinStr::from_raw(string) });
+        }
+
+        let ret = udf.signature().ret();
+        let value = if is_null || ret.ty().is_unit() {
+            None
+        } else if ret.ty().is_string() {
+            // Safety: Udfs return owned strings
+            let string = unsafe { ThinStr::from_raw(udf_slot_to_usize(result) as *mut ()) };
+            Some(Scalar::string(string.as_str().to_owned()))
+        } else {
+            Some(Scalar::Value(value_from_udf_slot(ret.ty(), result)))
+        };
+
+        if ret.is_nullable() {
+            let layout = call.ret_layout().ok_or_else(|| {
+                InterpreterError::Unsupported(format!(
+                    "call to the nullable udf `{}` in {expr_id} has no return layout",
+                    call.name(),
+                ))
+            })?;
+
+            // Nullable results are written to a row with a single column
+            let mut row = interpreter.zeroed_row(layout, false);
+            row.nulls[0] = is_null;
+            if let Some(value) = value {
+                row.columns[0] = value;
+            }
+            self.set_row(expr_id, row);
+        } else if let Some(value) = value {
+            self.set(expr_id, value);
+        }
+
+        Ok(())
+    }
+
+    fn call(&mut self, expr_id: ExprId, call: &Call) -> Result<()> {
+        let args = call.args();
+        let ret_ty = call.ret_ty();
+
+        let result = match call.function() {
+            "dbsp.error.abort" => {
+                return Err(InterpreterError::Trap(
+                    "called @dbsp.error.abort()".to_owned(),
+                ))
+            }
+
+            "dbsp.row.vec.push" => {
+                let layout = call.arg_types()[1].as_row().ok_or_else(|| {
+                    InterpreterError::Unsupported("pushed a scalar to a row vector".to_owned())
+                })?;
+
+                let row = {
+                    let row = self.row(args[1])?.borrow();
+                    debug_assert_eq!(row.layout, layout);
+                    self.interpreter.row_to_dyn(&row)
+                };
+
+                match self.binding(args[0])? {
+                    Binding::RowVec(rows) => rows.borrow_mut().push(row),
+                    _ => {
+                        return Err(InterpreterError::Unsupported(format!(
+                            "{} is not a row vector",
+                            args[0],
+                        )))
+                    }
+                }
+
+                Value::Unit
+            }
+
+            "dbsp.str.truncate" => {
+                let length = self.usize(args[1])?;
+                let string = self.scalar(args[0])?.str(args[0])?;
+                truncate(&mut string.borrow_mut(), length);
+                Value::Unit
+            }
+
+            "dbsp.str.truncate_clone" => {
+                let length = self.usize(args[1])?;
+                let mut string = self.string(args[0])?;
+                truncate(&mut string, length);
+                self.set(expr_id, Scalar::string(string));
+                return Ok(());
+            }
+
+            "dbsp.str.concat" => {
+                let suffix = self.string(args[1])?;
+                self.scalar(args[0])?
+                    .str(args[0])?
+                    .borrow_mut()
+                    .push_str(&suffix);
+                Value::Unit
+            }
+
+            "dbsp.str.concat_clone" => {
+                let mut string = self.string(args[0])?;
+                string.push_str(&self.string(args[1])?);
+                self.set(expr_id, Scalar::string(string));
+                return Ok(());
+            }
+
+            "dbsp.str.clear" => {
+                self.scalar(args[0])?.str(args[0])?.borrow_mut().clear();
+                Value::Unit
+            }
+
+            "dbsp.str.bit_length" => Value::Usize(self.string(args[0])?.len() * 8),
+            "dbsp.str.byte_length" => Value::Usize(self.string(args[0])?.len()),
+            "dbsp.str.char_length" => Value::Usize(self.string(args[0])?.chars().count()),
+
+            "dbsp.str.is_nfc" => Value::Bool(unicode_normalization::is_nfc(&self.string(args[0])?)),
+            "dbsp.str.is_nfd" => Value::Bool(unicode_normalization::is_nfd(&self.string(args[0])?)),
+            "dbsp.str.is_nfkc" => {
+                Value::Bool(unicode_normalization::is_nfkc(&self.string(args[0])?))
+            }
+            "dbsp.str.is_nfkd" => {
+                Value::Bool(unicode_normalization::is_nfkd(&self.string(args[0])?))
+            }
+            "dbsp.str.is_lowercase" => {
+                Value::Bool(self.string(args[0])?.chars().all(char::is_lowercase))
+            }
+            "dbsp.str.is_uppercase" => {
+                Value::Bool(self.string(args[0])?.chars().all(char::is_uppercase))
+            }
+
+            "dbsp.timestamp.to_date" => {
+                Value::from_int(ret_ty, (self.int(args[0])? as i64 / 86_400_000) as i128)
+            }
+            "dbsp.timestamp.epoch" => Value::from_int(ret_ty, self.int(args[0])? / 1000),
+            "dbsp.timestamp.year" => {
+                let year = Utc
+                    .timestamp_millis_opt(self.int(args[0])? as i64)
+                    .single()
+                    .map_or(i64::MIN, |time| time.year() as i64);
+                Value::from_int(ret_ty, year as i128)
+            }
+            function if function.starts_with("dbsp.timestamp.") => {
+                let field = &function["dbsp.timestamp.".len()..];
+                let millis = self.int(args[0])? as i64;
+                let value = match Utc.timestamp_millis_opt(millis) {
+                    LocalResult::Single(time) => {
+                        timestamp_field(field, time).ok_or_else(|| unknown_function(function))?
+                    }
+                    _ => 0,
+                };
+                Value::from_int(ret_ty, value as i128)
+            }
+
+            "dbsp.date.to_timestamp" => {
+                let days = self.int(args[0])? as i32 as u32;
+                Value::from_int(ret_ty, (days as i64).wrapping_mul(86_400_000) as i128)
+            }
+            "dbsp.date.epoch" => {
+                let days = self.int(args[0])? as i32;
+                Value::from_int(ret_ty, days.wrapping_mul(86_400) as i128)
+            }
+            "dbsp.date.hour"
+            | "dbsp.date.minute"
+            | "dbsp.date.second"
+            | "dbsp.date.millisecond"
+            | "dbsp.date.microsecond" => Value::from_int(ret_ty, 0),
+            function if function.starts_with("dbsp.date.") => {
+                let field = &function["dbsp.date.".len()..];
+                let days = self.int(args[0])? as i32;
+                let value = match Utc.timestamp_opt(days as i64 * 86_400, 0) {
+                    LocalResult::Single(date) => {
+                        date_field(field, date).ok_or_else(|| unknown_function(function))?
+                    }
+                    _ => 0,
+                };
+                Value::from_int(ret_ty, value as i128)
+            }
+
+            function => return Err(unknown_function(function)),
+        };
+
+        self.set(expr_id, Scalar::Value(result));
+        Ok(())
+    }
+
+    fn int(&self, expr: ExprId) -> Result<i128> {
+        let value = self.value(expr)?;
+        value.as_int().ok_or_else(|| {
+            InterpreterError::Unsupported(format!(
+                "{expr} is a {} where an integer was expected",
+                value.column_type(),
+            ))
+        })
+    }
+
+    fn usize(&self, expr: ExprId) -> Result<usize> {
+        self.int(expr).map(|int| int as usize)
+    }
+
+    fn string(&self, expr: ExprId) -> Result<String> {
+        Ok(self.scalar(expr)?.str(expr)?.borrow().clone())
+    }
+}
+
+fn unknown_function(function: &str) -> InterpreterError {
+    InterpreterError::Unsupported(format!("unknown function @{function}"))
+}
+
+/// Stores `bytes` at the start of an argument slot of a udf trampoline
+fn udf_slot<const N: usize>(bytes: [u8; N]) -> u64 {
+    let mut slot = [0; 8];
+    slot[..N].copy_from_slice(&bytes);
+    u64::from_ne_bytes(slot)
+}
+
+/// Reads the `N` bytes at the start of a udf trampoline's slot
+fn udf_slot_bytes<const N: usize>(slot: u64) -> [u8; N] {
+    slot.to_ne_bytes()[..N].try_into().unwrap()
+}
+
+fn udf_slot_to_usize(slot: u64) -> usize {
+    usize::from_ne_bytes(udf_slot_bytes(slot))
+}
+
+/// Converts a non-string value passed to a udf into its native representation
+fn value_to_udf_slot(value: &Value, expr: ExprId) -> Result<u64> {
+    Ok(match *value {
+        Value::Bool(value) => udf_slot([value as u8]),
+        Value::U8(value) => udf_slot(value.to_ne_bytes()),
+        Value::I8(value) => udf_slot(value.to_ne_bytes()),
+        Value::U16(value) => udf_slot(value.to_ne_bytes()),
+        Value::I16(value) => udf_slot(value.to_ne_bytes()),
+        Value::U32(value) => udf_slot(value.to_ne_bytes()),
+        Value::I32(value) | Value::Date(value) => udf_slot(value.to_ne_bytes()),
+        Value::U64(value) => udf_slot(value.to_ne_bytes()),
+        Value::I64(value) | Value::Timestamp(value) => udf_slot(value.to_ne_bytes()),
+        Value::Usize(value) => udf_slot(value.to_ne_bytes()),
+        Value::Isize(value) => udf_slot(value.to_ne_bytes()),
+        Value::F32(value) => udf_slot(value.to_ne_bytes()),
+        Value::F64(value) => udf_slot(value.to_ne_bytes()),
+        Value::Unit | Value::String(_) => {
+            return Err(InterpreterError::Unsupported(format!(
+                "passed {expr} of type {} to a udf",
+                value.column_type(),
+            )))
+        }
+    })
+}
+
+/// Converts the native representation of a non-string value of type `ty`
+/// returned by a udf into a value
+fn value_from_udf_slot(ty: ColumnType, slot: u64) -> Value {
+    match ty {
+        ColumnType::Bool => Value::Bool(udf_slot_bytes::<1>(slot)[0] != 0),
+        ColumnType::U8 => Value::U8(u8::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::I8 => Value::I8(i8::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::U16 => Value::U16(u16::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::I16 => Value::I16(i16::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::U32 => Value::U32(u32::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::I32 => Value::I32(i32::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::U64 => Value::U64(u64::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::I64 => Value::I64(i64::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::Usize => Value::Usize(udf_slot_to_usize(slot)),
+        ColumnType::Isize => Value::Isize(isize::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::F32 => Value::F32(f32::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::F64 => Value::F64(f64::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::Date => Value::Date(i32::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::Timestamp => Value::Timestamp(i64::from_ne_bytes(udf_slot_bytes(slot))),
+        ColumnType::Unit
+        | ColumnType::String
+        | ColumnType::Struct
+        | ColumnType::Array
+        | ColumnType::Ptr => unreachable!("udfs can't return {ty} values through a slot"),
+    }
+}
+
+fn constant_scalar(constant: &Constant) -> Scalar {
+    Scalar::from_value(Value::from_constant(constant, constant.column_type()))
+}
+
+fn truncate(string: &mut String, length: usize) {
+    string.truncate(length.min(string.len()));
+}
+
+fn timestamp_field(field: &str, time: DateTime<Utc>) -> Option<i64> {
+    Some(match field {
+        "millennium" => ((time.year() + 999) / 1000) as i64,
+        "century" => ((time.year() + 99) / 100) as i64,
+        "decade" => (time.year() / 10) as i64,
+        "iso_year" => time.iso_week().year() as i64,
+        "quarter" => (time.month0() / 3 + 1) as i64,
+        "month" => time.month() as i64,
+        "week" => time.iso_week().week() as i64,
+        "day" => time.day() as i64,
+        "hour" => time.hour() as i64,
+        "minute" => time.minute() as i64,
+        "second" => time.second() as i64,
+        "millisecond" => (time.second() * 1000 + time.timestamp_subsec_millis()) as i64,
+        "microsecond" => (time.second() * 1_000_000 + time.timestamp_subsec_micros()) as i64,
+        "day_of_week" => time.weekday().num_days_from_sunday() as i64 + 1,
+        "iso_day_of_week" => time.weekday().num_days_from_monday() as i64 + 1,
+        "day_of_year" => time.ordinal() as i64,
+        "floor_week" => {
+            let midnight = time.date_naive().and_hms_opt(0, 0, 0)?;
+            let weekday = time.weekday().num_days_from_sunday() as i64;
+            midnight.timestamp_millis() - weekday * 86_400 * 1000
+        }
+        _ => return None,
+    })
+}
+
+fn date_field(field: &str, date: DateTime<Utc>) -> Option<i32> {
+    Some(match field {
+        "year" => date.year(),
+        "month" => date.month() as i32,
+        "day" => date.day() as i32,
+        "quarter" => date.month0() as i32 / 3 + 1,
+        "decade" => date.year() / 10,
+        "century" => (date.year() + 99) / 100,
+        "millennium" => (date.year() + 999) / 1000,
+        "iso_year" => date.iso_week().year(),
+        "week" => date.iso_week().week() as i32,
+        "day_of_week" => date.weekday().num_days_from_sunday() as i32 + 1,
+        "iso_day_of_week" => date.weekday().num_days_from_monday() as i32 + 1,
+        "day_of_year" => date.ordinal() as i32,
+        _ => return None,
+    })
+}
+
+fn int_binary_op(kind: BinaryOpKind, lhs: i128, rhs: i128, ty: ColumnType) -> Result<Value> {
+    let is_comparison = matches!(
+        kind,
+        BinaryOpKind::Eq
+            | BinaryOpKind::Neq
+            | BinaryOpKind::LessThan
+            | BinaryOpKind::GreaterThan
+            | BinaryOpKind::LessThanOrEqual
+            | BinaryOpKind::GreaterThanOrEqual
+            | BinaryOpKind::Min
+            | BinaryOpKind::Max,
+    );
+    let is_bitwise = matches!(
+        kind,
+        BinaryOpKind::And | BinaryOpKind::Or | BinaryOpKind::Xor,
+    );
+
+    let is_supported = ty.is_int()
+        || (ty.is_bool() && (is_bitwise || kind == BinaryOpKind::Eq || kind == BinaryOpKind::Neq))
+        || ((ty.is_date() || ty.is_timestamp()) && is_comparison);
+    if !is_supported {
+        return Err(InterpreterError::Unsupported(format!(
+            "{kind:?} between {ty} values",
+        )));
+    }
+
+    let checked = |result: Option<i128>| -> Result<Value> {
+        let result = result
+            .ok_or_else(|| InterpreterError::Trap(format!("{kind:?} of {lhs}{ty} by {rhs}{ty}")))?;
+
+        // Signed division overflows when dividing `MIN` by -1
+        if Value::from_int(ty, result).as_int() != Some(result) {
+            Err(InterpreterError::Trap(format!(
+                "{kind:?} of {lhs}{ty} by {rhs}{ty} overflowed",
+            )))
+        } else {
+            Ok(Value::from_int(ty, result))
+        }
+    };
+
+    Ok(match kind {
+        BinaryOpKind::Add => Value::from_int(ty, lhs.wrapping_add(rhs)),
+        BinaryOpKind::Sub => Value::from_int(ty, lhs.wrapping_sub(rhs)),
+        BinaryOpKind::Mul => Value::from_int(ty, lhs.wrapping_mul(rhs)),
+
+        BinaryOpKind::Div => return checked(lhs.checked_div(rhs)),
+        BinaryOpKind::Rem => return checked(lhs.checked_rem(rhs)),
+        BinaryOpKind::Mod => {
+            return checked(lhs.checked_rem(rhs).map(
+                |rem| {
+                    if rem < 0 {
+                        rem + rhs.abs()
+                    } else {
+                        rem
+                    }
+                },
+            ))
+        }
+        BinaryOpKind::DivFloor => {
+            return checked(lhs.checked_div(rhs).map(|div| {
+                let rem = lhs % rhs;
+                if (rem > 0 && rhs < 0) || (rem < 0 && rhs > 0) {
+                    div - 1
+                } else {
+                    div
+                }
+            }))
+        }
+        BinaryOpKind::ModFloor => {
+            return checked(lhs.checked_rem(rhs).map(|rem| {
+                if (rem > 0 && rhs < 0) || (rem < 0 && rhs > 0) {
+                    rem + rhs
+                } else {
+                    rem
+                }
+            }))
+        }
+
+        BinaryOpKind::Eq => Value::Bool(lhs == rhs),
+        BinaryOpKind::Neq => Value::Bool(lhs != rhs),
+        BinaryOpKind::LessThan => Value::Bool(lhs < rhs),
+        BinaryOpKind::GreaterThan => Value::Bool(lhs > rhs),
+        BinaryOpKind::LessThanOrEqual => Value::Bool(lhs <= rhs),
+        BinaryOpKind::GreaterThanOrEqual => Value::Bool(lhs >= rhs),
+        BinaryOpKind::Min => Value::from_int(ty, lhs.min(rhs)),
+        BinaryOpKind::Max => Value::from_int(ty, lhs.max(rhs)),
+
+        BinaryOpKind::And => Value::from_int(ty, lhs & rhs),
+        BinaryOpKind::Or => Value::from_int(ty, lhs | rhs),
+        BinaryOpKind::Xor => Value::from_int(ty, lhs ^ rhs),
+    })
+}
+
+fn float_unary_op(kind: UnaryOpKind, float: f64) -> Option<f64> {
+    Some(match kind {
+        UnaryOpKind::Abs => float.abs(),
+        UnaryOpKind::Neg => -float,
+        UnaryOpKind::Ceil => float.ceil(),
+        UnaryOpKind::Floor => float.floor(),
+        UnaryOpKind::Trunc => float.trunc(),
+        UnaryOpKind::Sqrt => float.sqrt(),
+        _ => return None,
+    })
+}
+
+/// Returns the minimum of two floats, propagating NaNs and treating -0.0 as
+/// less than +0.0
+fn ieee_min(lhs: f64, rhs: f64) -> f64 {
+    if lhs.is_nan() || rhs.is_nan() {
+        f64::NAN
+    } else if lhs == rhs {
+        if lhs.is_sign_negative() {
+            lhs
+        } else {
+            rhs
+        }
+    } else {
+        lhs.min(rhs)
+    }
+}
+
+/// Returns the maximum of two floats, propagating NaNs and treating +0.0 as
+/// greater than -0.0
+fn ieee_max(lhs: f64, rhs: f64) -> f64 {
+    if lhs.is_nan() || rhs.is_nan() {
+        f64::NAN
+    } else if lhs == rhs {
+        if lhs.is_sign_positive() {
+            lhs
+        } else {
+            rhs
+        }
+    } else {
+        lhs.max(rhs)
+    }
+}
+
+/// Returns the width of the given integer type in bits
+fn int_bits(ty: ColumnType) -> u32 {
+    match ty {
+        ColumnType::U8 | ColumnType::I8 => 8,
+        ColumnType::U16 | ColumnType::I16 => 16,
+        ColumnType::U32 | ColumnType::I32 | ColumnType::Date => 32,
+        ColumnType::U64 | ColumnType::I64 | ColumnType::Timestamp => 64,
+        ColumnType::Usize | ColumnType::Isize => usize::BITS,
+        _ => unreachable!("{ty} is not an integer"),
+    }
+}
+
+/// Returns `true` if `float` can be converted into an integer of type `ty`
+/// without being truncated or saturated
+fn float_fits_int(float: f64, ty: ColumnType) -> bool {
+    let (min, max) = match ty {
+        ColumnType::U8 => (u8::MIN as i128, u8::MAX as i128),
+        ColumnType::I8 => (i8::MIN as i128, i8::MAX as i128),
+        ColumnType::U16 => (u16::MIN as i128, u16::MAX as i128),
+        ColumnType::I16 => (i16::MIN as i128, i16::MAX as i128),
+        ColumnType::U32 => (u32::MIN as i128, u32::MAX as i128),
+        ColumnType::I32 => (i32::MIN as i128, i32::MAX as i128),
+        ColumnType::U64 => (u64::MIN as i128, u64::MAX as i128),
+        ColumnType::I64 => (i64::MIN as i128, i64::MAX as i128),
+        ColumnType::Usize => (usize::MIN as i128, usize::MAX as i128),
+        ColumnType::Isize => (isize::MIN as i128, isize::MAX as i128),
+        _ => return false,
+    };
+
+    let float = float.trunc();
+    !float.is_nan() && float >= min as f64 && float < max as f64 + 1.0
+}
@@ -0,0 +1,669 @@
+//! A reference interpreter for dataflow graphs
+//!
+//! The interpreter evaluates the JIT IR directly over dynamically typed
+//! [`DynRow`]s instead of compiling it, which makes it slow but simple enough
+//! to serve as an oracle for the compiled dataflow. See [`verify()`] for
+//! checking a compiled graph against it
+//!
+//! [`verify()`]: crate::verify
+
+mod function;
+mod value;
+
+pub use value::{Batch, DynRow, Value};
+
+use crate::{
+    codegen::CodegenConfig,
+    interpreter::function::FunctionInterpreter,
+    ir::{
+        graph::{self, GraphExt},
+        literal::{NullableConstant, RowLiteral, StreamCollection},
+        nodes::{DataflowNode as _, Node, SinkOutputMode, StreamKind, StreamLayout},
+        Function, Graph, NodeId, RowLayout,
+    },
+};
+use derive_more::Display;
+use std::{collections::BTreeMap, error::Error};
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum InterpreterError {
+    /// The generated code would have trapped, e.g. by dividing by zero
+    #[display(fmt = "trap: {_0}")]
+    Trap(String),
+
+    /// The graph contains something the interpreter can't evaluate
+    #[display(fmt = "unsupported: {_0}")]
+    Unsupported(String),
+
+    /// An input was given to a node that isn't a source or to a source of a
+    /// different kind
+    #[display(fmt = "invalid input given to {node}: {reason}")]
+    InvalidInput { node: NodeId, reason: String },
+
+    /// An append-only sink received a row with a negative weight
+    #[display(fmt = "append-only sink {node} received a retraction of {row} with weight {weight}")]
+    Retraction {
+        node: NodeId,
+        row: String,
+        weight: i32,
+    },
+}
+
+impl Error for InterpreterError {}
+
+/// State kept by stateful operators between steps
+#[derive(Debug, Default)]
+struct NodeState {
+    /// The integrals of the node's inputs
+    inputs: Vec<Batch>,
+    /// The node's previous (integrated) output
+    output: Option<Batch>,
+}
+
+/// Evaluates a dataflow graph one step at a time
+pub struct Interpreter<'a> {
+    graph: &'a Graph,
+    functions: FunctionInterpreter<'a>,
+    order: Vec<NodeId>,
+    streams: BTreeMap<NodeId, StreamLayout>,
+    state: BTreeMap<NodeId, NodeState>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Creates an interpreter for `graph`, the arithmetic semantics of the
+    /// interpreter follow those selected by `config`
+    pub fn new(graph: &'a Graph, config: CodegenConfig) -> Result<Self, InterpreterError> {
+        let order: Vec<_> = graph::toposort(graph.edges())
+            .map_err(|node| {
+                InterpreterError::Unsupported(format!("cycle within the dataflow graph at {node}",))
+            })?
+            .into_iter()
+            .filter(|node_id| graph.nodes().contains_key(node_id))
+            .collect();
+
+        let (mut streams, mut inputs, mut input_nodes) = (BTreeMap::new(), Vec::new(), Vec::new());
+        for &node_id in &order {
+            let node = &graph.nodes()[&node_id];
+            match node {
+                Node::Subgraph(_)
+                | Node::Export(_)
+                | Node::ExportedNode(_)
+                | Node::Delta0(_)
+                | Node::DelayedFeedback(_)
+                | Node::PartitionedRollingFold(_) => {
+                    return Err(InterpreterError::Unsupported(format!(
+                        "cannot interpret node {node_id}: {node:?}",
+                    )))
+                }
+
+                _ => {}
+            }
+
+            node.inputs(&mut input_nodes);
+            inputs.extend(
+                input_nodes
+                    .iter()
+                    .filter_map(|input| streams.get(input).copied()),
+            );
+            if let Some(stream) = node.output_stream(&inputs) {
+                let mut has_nested_columns = false;
+                stream.map_layouts(&mut |layout| {
+                    has_nested_columns |= graph.layout_cache().get(layout).has_nested_columns();
+                });
+
+                if has_nested_columns {
+                    return Err(InterpreterError::Unsupported(format!(
+                        "cannot interpret node {node_id}: struct and array columns are unsupported",
+                    )));
+                }
+
+                streams.insert(node_id, stream);
+            }
+
+            inputs.clear();
+            input_nodes.clear();
+        }
+
+        Ok(Self {
+            graph,
+            functions: FunctionInterpreter::new(graph.layout_cache(), config),
+            order,
+            streams,
+            state: BTreeMap::new(),
+        })
+    }
+
+    /// Evaluates a single step of the dataflow with the given inputs, returns
+    /// the batch received by each sink
+    pub fn step(
+        &mut self,
+        inputs: &BTreeMap<NodeId, StreamCollection>,
+    ) -> Result<BTreeMap<NodeId, Batch>, InterpreterError> {
+        for &node in inputs.keys() {
+            if !matches!(
+                self.graph.nodes().get(&node),
+                Some(Node::Source(_) | Node::SourceMap(_)),
+            ) {
+                return Err(InterpreterError::InvalidInput {
+                    node,
+                    reason: "the node isn't a source".to_owned(),
+                });
+            }
+        }
+
+        let mut batches: BTreeMap<NodeId, Batch> = BTreeMap::new();
+        let mut sinks = BTreeMap::new();
+
+        let graph = self.graph;
+        for node_id in self.order.clone() {
+            let output = match &graph.nodes()[&node_id] {
+                Node::Source(_) | Node::SourceMap(_) => {
+                    self.source(node_id, inputs.get(&node_id))?
+                }
+
+                // Batches are always consolidated so net deltas are the same as deltas
+                Node::Sink(sink) => {
+                    let batch = &batches[&sink.input()];
+                    if sink.output_mode() == SinkOutputMode::AppendOnly {
+                        if let Some((key, value, weight)) =
+                            batch.iter().find(|&(.., weight)| weight < 0)
+                        {
+                            let row = match batch.kind() {
+                                StreamKind::Set => key.to_string(),
+                                StreamKind::Map => format!("{key} => {value}"),
+                            };
+                            return Err(InterpreterError::Retraction {
+                                node: node_id,
+                                row,
+                                weight,
+                            });
+                        }
+                    }
+
+                    sinks.insert(node_id, batch.clone());
+                    continue;
+                }
+
+                // Statistics have no effect on the dataflow's output
+                Node::CollectStatistics(_) => continue,
+
+                Node::Constant(constant) => {
+                    self.collection(constant.value().value(), constant.layout(), node_id)?
+                }
+
+                // Constant sources only produce their rows on the first step
+                Node::ConstantSource(constant) => {
+                    if self.state.insert(node_id, NodeState::default()).is_none() {
+                        self.collection(constant.value().value(), constant.layout(), node_id)?
+                    } else {
+                        Batch::new(self.kind_of(node_id))
+                    }
+                }
+
+                Node::Map(map) => {
+                    let input = &batches[&map.input()];
+                    let mut output = Batch::new(self.kind_of(node_id));
+
+                    for (key, value, weight) in input.iter() {
+                        let outputs = self
+                            .functions
+                            .call(map.map_fn(), &tuple_args(input.kind(), key, value))?
+                            .outputs;
+
+                        let (key, value) = output_tuple(output.kind(), outputs);
+                        output.insert(key, value, weight);
+                    }
+
+                    output
+                }
+
+                Node::Filter(filter) => {
+                    let input = &batches[&filter.input()];
+                    let mut output = Batch::new(input.kind());
+
+                    for (key, value, weight) in input.iter() {
+                        let keep = self
+                            .functions
+                            .call(filter.filter_fn(), &tuple_args(input.kind(), key, value))?
+                            .ret;
+
+                        if keep.as_bool() == Some(true) {
+                            output.insert(key.clone(), value.clone(), weight);
+                        }
+                    }
+
+                    output
+                }
+
+                Node::FilterMap(filter_map) => {
+                    let input = &batches[&filter_map.input()];
+                    let mut output = Batch::new(StreamKind::Set);
+
+                    for (key, value, weight) in input.iter() {
+                        let result = self.functions.call(
+                            filter_map.filter_map(),
+                            &tuple_args(input.kind(), key, value),
+                        )?;
+
+                        if result.ret.as_bool() == Some(true) {
+                            let (key, value) = output_tuple(StreamKind::Set, result.outputs);
+                            output.insert(key, value, weight);
+                        }
+                    }
+
+                    output
+                }
+
+                Node::FlatMap(flat_map) => {
+                    let input = &batches[&flat_map.input()];
+                    let mut output = Batch::new(flat_map.output_layout().kind());
+
+                    for (key, value, weight) in input.iter() {
+                        let mut outputs = self
+                            .functions
+                            .call(flat_map.flat_map(), &tuple_args(input.kind(), key, value))?
+                            .outputs
+                            .into_iter()
+                            .map(|output| output.into_rows());
+
+                        let keys = outputs.next().unwrap_or_default();
+                        match output.kind() {
+                            StreamKind::Set => {
+                                for key in keys {
+                                    output.insert(key, DynRow::default(), weight);
+                                }
+                            }
+
+                            StreamKind::Map => {
+                                let values = outputs.next().unwrap_or_default();
+                                for (key, value) in keys.into_iter().zip(values) {
+                                    output.insert(key, value, weight);
+                                }
+                            }
+                        }
+                    }
+
+                    output
+                }
+
+                Node::IndexWith(index_with) => {
+                    let input = &batches[&index_with.input()];
+                    let mut output = Batch::new(StreamKind::Map);
+
+                    for (key, _, weight) in input.iter() {
+                        let outputs = self.functions.call(index_with.index_fn(), &[key])?.outputs;
+
+                        let (key, value) = output_tuple(StreamKind::Map, outputs);
+                        output.insert(key, value, weight);
+                    }
+
+                    output
+                }
+
+                Node::Neg(neg) => {
+                    let input = &batches[&neg.input()];
+                    let mut output = Batch::new(input.kind());
+                    output.minus(input);
+                    output
+                }
+
+                Node::Sum(sum) => {
+                    let mut output = Batch::new(self.kind_of(node_id));
+                    for input in sum.inputs() {
+                        output.plus(&batches[input]);
+                    }
+                    output
+                }
+
+                Node::Minus(minus) => {
+                    let mut output = batches[&minus.lhs()].clone();
+                    output.minus(&batches[&minus.rhs()]);
+                    output
+                }
+
+                Node::MonotonicJoin(join) => self.join(
+                    join.join_fn(),
+                    &batches[&join.lhs()],
+                    &batches[&join.rhs()],
+                    StreamKind::Set,
+                )?,
+
+                Node::Integrate(integrate) => {
+                    let state = self.state.entry(node_id).or_default();
+                    let integral = state
+                        .output
+                        .get_or_insert_with(|| Batch::new(batches[&integrate.input()].kind()));
+                    integral.plus(&batches[&integrate.input()]);
+                    integral.clone()
+                }
+
+                Node::Differentiate(differentiate) => {
+                    let input = &batches[&differentiate.input()];
+                    let state = self.state.entry(node_id).or_default();
+
+                    let mut output = input.clone();
+                    if let Some(previous) = state.output.replace(input.clone()) {
+                        output.minus(&previous);
+                    }
+                    output
+                }
+
+                // Incremental operators are evaluated by integrating their
+                // inputs, evaluating the operator over the integrals and then
+                // differentiating the result
+                Node::Distinct(distinct) => {
+                    self.incremental(node_id, &[distinct.input()], &batches, |_, inputs| {
+                        let mut output = Batch::new(inputs[0].kind());
+                        for (key, value, weight) in inputs[0].iter() {
+                            if weight > 0 {
+                                output.insert(key.clone(), value.clone(), 1);
+                            }
+                        }
+                        Ok(output)
+                    })?
+                }
+
+                Node::JoinCore(join) => {
+                    let kind = self.kind_of(node_id);
+                    self.incremental(
+                        node_id,
+                        &[join.lhs(), join.rhs()],
+                        &batches,
+                        |this, inputs| {
+                            if join.null_safe() {
+                                this.join(join.join_fn(), &inputs[0], &inputs[1], kind)
+                            } else {
+                                // Keys containing nulls never match under SQL semantics
+                                let (lhs, rhs) =
+                                    (without_null_keys(&inputs[0]), without_null_keys(&inputs[1]));
+                                this.join(join.join_fn(), &lhs, &rhs, kind)
+                            }
+                        },
+                    )?
+                }
+
+                Node::Antijoin(antijoin) => self.incremental(
+                    node_id,
+                    &[antijoin.lhs(), antijoin.rhs()],
+                    &batches,
+                    |_, inputs| {
+                        let (lhs, rhs) = (&inputs[0], &inputs[1]);
+
+                        let mut output = lhs.clone();
+                        for (key, value, weight) in lhs.iter() {
+                            let matches =
+                                rhs.values_of(key).filter(|&(_, weight)| weight > 0).count() as i32;
+                            output.insert(
+                                key.clone(),
+                                value.clone(),
+                                weight.wrapping_mul(matches).wrapping_neg(),
+                            );
+                        }
+
+                        Ok(output)
+                    },
+                )?,
+
+                Node::Min(min) => {
+                    self.incremental(node_id, &[min.input()], &batches, |_, inputs| {
+                        Ok(aggregate(&inputs[0], |values| {
+                            values.first().map(|&(value, _)| value.clone())
+                        }))
+                    })?
+                }
+
+                Node::Max(max) => {
+                    self.incremental(node_id, &[max.input()], &batches, |_, inputs| {
+                        Ok(aggregate(&inputs[0], |values| {
+                            values.last().map(|&(value, _)| value.clone())
+                        }))
+                    })?
+                }
+
+                Node::Fold(fold) => {
+                    let init = {
+                        let layout_cache = self.graph.layout_cache();
+                        let acc_layout = layout_cache.get(fold.acc_layout());
+                        row_from_literal(fold.init(), &acc_layout)
+                    };
+
+                    self.incremental(node_id, &[fold.input()], &batches, |this, inputs| {
+                        let mut error = None;
+                        let output = aggregate(&inputs[0], |values| {
+                            let fold_values = || -> Result<DynRow, InterpreterError> {
+                                let mut acc = init.clone();
+                                for &(value, weight) in values {
+                                    let weight = DynRow::new(vec![Some(Value::I32(weight))]);
+                                    acc = this
+                                        .functions
+                                        .call(fold.step_fn(), &[&acc, value, &weight])?
+                                        .outputs
+                                        .swap_remove(0)
+                                        .into_row();
+                                }
+
+                                let mut outputs =
+                                    this.functions.call(fold.finish_fn(), &[&acc])?.outputs;
+                                Ok(outputs.pop().unwrap().into_row())
+                            };
+
+                            match fold_values() {
+                                Ok(output) => Some(output),
+                                Err(fold_error) => {
+                                    error.get_or_insert(fold_error);
+                                    None
+                                }
+                            }
+                        });
+
+                        error.map_or(Ok(output), Err)
+                    })?
+                }
+
+                Node::Subgraph(_)
+                | Node::Export(_)
+                | Node::ExportedNode(_)
+                | Node::Delta0(_)
+                | Node::DelayedFeedback(_)
+                | Node::PartitionedRollingFold(_) => unreachable!(),
+            };
+
+            batches.insert(node_id, output);
+        }
+
+        Ok(sinks)
+    }
+
+    fn kind_of(&self, node: NodeId) -> StreamKind {
+        self.streams[&node].kind()
+    }
+
+    fn source(
+        &self,
+        node: NodeId,
+        input: Option<&StreamCollection>,
+    ) -> Result<Batch, InterpreterError> {
+        let stream = self.streams[&node];
+        match input {
+            Some(input) => self.collection(input, stream, node),
+            None => Ok(Batch::new(stream.kind())),
+        }
+    }
+
+    fn collection(
+        &self,
+        collection: &StreamCollection,
+        stream: StreamLayout,
+        node: NodeId,
+    ) -> Result<Batch, InterpreterError> {
+        let layout_cache = self.graph.layout_cache();
+        let mut batch = Batch::new(stream.kind());
+
+        match (collection, stream) {
+            (StreamCollection::Set(set), StreamLayout::Set(key_layout)) => {
+                let key_layout = layout_cache.get(key_layout);
+                for (key, weight) in set {
+                    batch.insert(
+                        row_from_literal(key, &key_layout),
+                        DynRow::default(),
+                        *weight,
+                    );
+                }
+            }
+
+            (StreamCollection::Map(map), StreamLayout::Map(key_layout, value_layout)) => {
+                let (key_layout, value_layout) =
+                    (layout_cache.get(key_layout), layout_cache.get(value_layout));
+                for (key, value, weight) in map {
+                    batch.insert(
+                        row_from_literal(key, &key_layout),
+                        row_from_literal(value, &value_layout),
+                        *weight,
+                    );
+                }
+            }
+
+            (_, stream) => {
+                return Err(InterpreterError::InvalidInput {
+                    node,
+                    reason: format!("expected a {:?} collection", stream.kind()),
+                })
+            }
+        }
+
+        Ok(batch)
+    }
+
+    /// Joins all tuples of `lhs` and `rhs` with matching keys
+    fn join(
+        &self,
+        join_fn: &Function,
+        lhs: &Batch,
+        rhs: &Batch,
+        kind: StreamKind,
+    ) -> Result<Batch, InterpreterError> {
+        let mut output = Batch::new(kind);
+
+        for (key, lhs_value, lhs_weight) in lhs.iter() {
+            for (rhs_value, rhs_weight) in rhs.values_of(key) {
+                let outputs = self
+                    .functions
+                    .call(join_fn, &[key, lhs_value, rhs_value])?
+                    .outputs;
+
+                let (key, value) = output_tuple(kind, outputs);
+                output.insert(key, value, lhs_weight.wrapping_mul(rhs_weight));
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Evaluates an incremental operator by integrating its inputs, applying
+    /// `evaluate` to the integrals and returning the change in its output
+    fn incremental<F>(
+        &mut self,
+        node: NodeId,
+        inputs: &[NodeId],
+        batches: &BTreeMap<NodeId, Batch>,
+        evaluate: F,
+    ) -> Result<Batch, InterpreterError>
+    where
+        F: FnOnce(&Self, &[Batch]) -> Result<Batch, InterpreterError>,
+    {
+        let mut state = self.state.remove(&node).unwrap_or_default();
+        if state.inputs.is_empty() {
+            state.inputs = inputs
+                .iter()
+                .map(|input| Batch::new(batches[input].kind()))
+                .collect();
+        }
+
+        for (integral, input) in state.inputs.iter_mut().zip(inputs) {
+            integral.plus(&batches[input]);
+        }
+
+        let result = evaluate(self, &state.inputs);
+        let output = result.map(|output| {
+            let mut delta = output.clone();
+            if let Some(previous) = state.output.replace(output) {
+                delta.minus(&previous);
+            }
+            delta
+        });
+
+        self.state.insert(node, state);
+        output
+    }
+}
+
+/// Returns a copy of `batch` without any of the tuples whose keys contain nulls
+fn without_null_keys(batch: &Batch) -> Batch {
+    let mut output = Batch::new(batch.kind());
+    for (key, value, weight) in batch.iter() {
+        if key.columns().iter().all(Option::is_some) {
+            output.insert(key.clone(), value.clone(), weight);
+        }
+    }
+
+    output
+}
+
+/// Returns the arguments a function receives for a tuple of a stream of
+/// the given kind
+fn tuple_args<'a>(kind: StreamKind, key: &'a DynRow, value: &'a DynRow) -> Vec<&'a DynRow> {
+    match kind {
+        StreamKind::Set => vec![key],
+        StreamKind::Map => vec![key, value],
+    }
+}
+
+/// Turns the outputs of a function into a tuple of a stream of the given kind
+fn output_tuple(kind: StreamKind, outputs: Vec<function::Output>) -> (DynRow, DynRow) {
+    let mut outputs = outputs.into_iter().map(function::Output::into_row);
+    let key = outputs.next().unwrap_or_default();
+
+    match kind {
+        StreamKind::Set => (key, DynRow::default()),
+        StreamKind::Map => (key, outputs.next().unwrap_or_default()),
+    }
+}
+
+/// Aggregates the values of each key within `input` into at most one value
+fn aggregate<F>(input: &Batch, mut aggregate: F) -> Batch
+where
+    F: FnMut(&[(&DynRow, i32)]) -> Option<DynRow>,
+{
+    let mut output = Batch::new(StreamKind::Map);
+
+    let mut values = Vec::new();
+    let mut tuples = input.iter().peekable();
+    while let Some((key, value, weight)) = tuples.next() {
+        values.push((value, weight));
+
+        if tuples.peek().map_or(true, |&(next, _, _)| next != key) {
+            if let Some(value) = aggregate(&values) {
+                output.insert(key.clone(), value, 1);
+            }
+            values.clear();
+        }
+    }
+
+    output
+}
+
+fn row_from_literal(literal: &RowLiteral, layout: &RowLayout) -> DynRow {
+    let columns = literal
+        .rows()
+        .iter()
+        .zip(layout.columns())
+        .map(|(column, &ty)| match column {
+            NullableConstant::NonNull(constant) | NullableConstant::Nullable(Some(constant)) => {
+                Some(Value::from_constant(constant, ty))
+            }
+            NullableConstant::Nullable(None) => None,
+        })
+        .collect();
+
+    DynRow::new(columns)
+}
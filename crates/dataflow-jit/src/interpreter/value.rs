@@ -0,0 +1,412 @@
+use crate::ir::{nodes::StreamKind, ColumnType, Constant};
+use std::{
+    cmp::Ordering,
+    collections::{btree_map::Entry, BTreeMap},
+    fmt::{self, Debug, Display},
+};
+
+/// A dynamically typed scalar value
+#[derive(Debug, Clone)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    Usize(usize),
+    Isize(isize),
+    F32(f32),
+    F64(f64),
+    /// Days since Jan 1 1970
+    Date(i32),
+    /// Milliseconds since Jan 1 1970
+    Timestamp(i64),
+    String(String),
+}
+
+impl Value {
+    /// Returns the column type of the current value
+    pub const fn column_type(&self) -> ColumnType {
+        match self {
+            Self::Unit => ColumnType::Unit,
+            Self::Bool(_) => ColumnType::Bool,
+            Self::U8(_) => ColumnType::U8,
+            Self::I8(_) => ColumnType::I8,
+            Self::U16(_) => ColumnType::U16,
+            Self::I16(_) => ColumnType::I16,
+            Self::U32(_) => ColumnType::U32,
+            Self::I32(_) => ColumnType::I32,
+            Self::U64(_) => ColumnType::U64,
+            Self::I64(_) => ColumnType::I64,
+            Self::Usize(_) => ColumnType::Usize,
+            Self::Isize(_) => ColumnType::Isize,
+            Self::F32(_) => ColumnType::F32,
+            Self::F64(_) => ColumnType::F64,
+            Self::Date(_) => ColumnType::Date,
+            Self::Timestamp(_) => ColumnType::Timestamp,
+            Self::String(_) => ColumnType::String,
+        }
+    }
+
+    /// Returns the value that an uninitialized column of type `ty` is given
+    /// by the interpreter
+    pub(crate) fn zeroed(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Bool => Self::Bool(false),
+            ColumnType::String => Self::String(String::new()),
+            // Pointers only appear within row vectors which are handled
+            // separately by the interpreter
+            ColumnType::Unit | ColumnType::Ptr => Self::Unit,
+            ty => Self::from_int(ty, 0),
+        }
+    }
+
+    /// Converts `constant` into a value of type `ty`, `ty` is used to
+    /// distinguish dates and timestamps from their integer representations
+    pub(crate) fn from_constant(constant: &Constant, ty: ColumnType) -> Self {
+        match *constant {
+            Constant::Unit => Self::Unit,
+            Constant::Bool(value) => Self::Bool(value),
+            Constant::I32(days) if ty.is_date() => Self::Date(days),
+            Constant::I64(millis) if ty.is_timestamp() => Self::Timestamp(millis),
+            Constant::U8(value) => Self::U8(value),
+            Constant::I8(value) => Self::I8(value),
+            Constant::U16(value) => Self::U16(value),
+            Constant::I16(value) => Self::I16(value),
+            Constant::U32(value) => Self::U32(value),
+            Constant::I32(value) => Self::I32(value),
+            Constant::U64(value) => Self::U64(value),
+            Constant::I64(value) => Self::I64(value),
+            Constant::Usize(value) => Self::Usize(value),
+            Constant::Isize(value) => Self::Isize(value),
+            Constant::F32(value) => Self::F32(value),
+            Constant::F64(value) => Self::F64(value),
+            Constant::String(ref value) => Self::String(value.clone()),
+            Constant::Struct(_) | Constant::Array(_) => {
+                unreachable!("the interpreter doesn't support nested constants")
+            }
+        }
+    }
+
+    /// Creates an integer-like value of type `ty` from `value` with the
+    /// semantics of an `as` cast
+    pub(crate) fn from_int(ty: ColumnType, value: i128) -> Self {
+        match ty {
+            ColumnType::Bool => Self::Bool(value as u8 != 0),
+            ColumnType::U8 => Self::U8(value as u8),
+            ColumnType::I8 => Self::I8(value as i8),
+            ColumnType::U16 => Self::U16(value as u16),
+            ColumnType::I16 => Self::I16(value as i16),
+            ColumnType::U32 => Self::U32(value as u32),
+            ColumnType::I32 => Self::I32(value as i32),
+            ColumnType::U64 => Self::U64(value as u64),
+            ColumnType::I64 => Self::I64(value as i64),
+            ColumnType::Usize => Self::Usize(value as usize),
+            ColumnType::Isize => Self::Isize(value as isize),
+            ColumnType::F32 => Self::F32(value as f32),
+            ColumnType::F64 => Self::F64(value as f64),
+            ColumnType::Date => Self::Date(value as i32),
+            ColumnType::Timestamp => Self::Timestamp(value as i64),
+            ColumnType::Unit
+            | ColumnType::String
+            | ColumnType::Struct
+            | ColumnType::Array
+            | ColumnType::Ptr => unreachable!("cannot create a {ty} from an integer"),
+        }
+    }
+
+    /// Creates a float or integer value of type `ty` from `value` with the
+    /// semantics of an `as` cast
+    pub(crate) fn from_float(ty: ColumnType, value: f64) -> Self {
+        match ty {
+            ColumnType::U8 => Self::U8(value as u8),
+            ColumnType::I8 => Self::I8(value as i8),
+            ColumnType::U16 => Self::U16(value as u16),
+            ColumnType::I16 => Self::I16(value as i16),
+            ColumnType::U32 => Self::U32(value as u32),
+            ColumnType::I32 => Self::I32(value as i32),
+            ColumnType::U64 => Self::U64(value as u64),
+            ColumnType::I64 => Self::I64(value as i64),
+            ColumnType::Usize => Self::Usize(value as usize),
+            ColumnType::Isize => Self::Isize(value as isize),
+            ColumnType::F32 => Self::F32(value as f32),
+            ColumnType::F64 => Self::F64(value),
+            ColumnType::Bool
+            | ColumnType::Date
+            | ColumnType::Timestamp
+            | ColumnType::Unit
+            | ColumnType::String
+            | ColumnType::Struct
+            | ColumnType::Array
+            | ColumnType::Ptr => unreachable!("cannot create a {ty} from a float"),
+        }
+    }
+
+    /// Reinterprets an integer-like value as a value of type `ty`, used to
+    /// turn the integers produced by functions into the dates and timestamps
+    /// of the columns they're stored into
+    pub(crate) fn retype(self, ty: ColumnType) -> Self {
+        match self.as_int() {
+            Some(int)
+                if self.column_type() != ty
+                    && (ty.is_int() || ty.is_date() || ty.is_timestamp()) =>
+            {
+                Self::from_int(ty, int)
+            }
+            _ => self,
+        }
+    }
+
+    /// Returns the value of a boolean, integer, date or timestamp
+    pub(crate) const fn as_int(&self) -> Option<i128> {
+        Some(match *self {
+            Self::Bool(value) => value as i128,
+            Self::U8(value) => value as i128,
+            Self::I8(value) => value as i128,
+            Self::U16(value) => value as i128,
+            Self::I16(value) => value as i128,
+            Self::U32(value) => value as i128,
+            Self::I32(value) => value as i128,
+            Self::U64(value) => value as i128,
+            Self::I64(value) => value as i128,
+            Self::Usize(value) => value as i128,
+            Self::Isize(value) => value as i128,
+            Self::Date(value) => value as i128,
+            Self::Timestamp(value) => value as i128,
+            Self::Unit | Self::F32(_) | Self::F64(_) | Self::String(_) => return None,
+        })
+    }
+
+    pub const fn as_bool(&self) -> Option<bool> {
+        if let Self::Bool(value) = *self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let Self::String(value) = self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    const fn discriminant(&self) -> u8 {
+        match self {
+            Self::Unit => 0,
+            Self::Bool(_) => 1,
+            Self::U8(_) => 2,
+            Self::I8(_) => 3,
+            Self::U16(_) => 4,
+            Self::I16(_) => 5,
+            Self::U32(_) => 6,
+            Self::I32(_) => 7,
+            Self::U64(_) => 8,
+            Self::I64(_) => 9,
+            Self::Usize(_) => 10,
+            Self::Isize(_) => 11,
+            Self::F32(_) => 12,
+            Self::F64(_) => 13,
+            Self::Date(_) => 14,
+            Self::Timestamp(_) => 15,
+            Self::String(_) => 16,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Values are ordered the same way compiled rows order their columns, floats
+/// use `totalOrder` comparisons
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::F32(lhs), Self::F32(rhs)) => lhs.total_cmp(rhs),
+            (Self::F64(lhs), Self::F64(rhs)) => lhs.total_cmp(rhs),
+            (Self::String(lhs), Self::String(rhs)) => lhs.cmp(rhs),
+
+            (lhs, rhs) if lhs.discriminant() == rhs.discriminant() => {
+                lhs.as_int().cmp(&rhs.as_int())
+            }
+            (lhs, rhs) => lhs.discriminant().cmp(&rhs.discriminant()),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => f.write_str("unit"),
+            Self::Bool(value) => Display::fmt(value, f),
+            Self::F32(value) => Debug::fmt(value, f),
+            Self::F64(value) => Debug::fmt(value, f),
+            Self::Date(days) => write!(f, "date({days})"),
+            Self::Timestamp(millis) => write!(f, "timestamp({millis})"),
+            Self::String(value) => Debug::fmt(value, f),
+            int => write!(f, "{}{}", int.as_int().unwrap(), int.column_type()),
+        }
+    }
+}
+
+/// A dynamically typed row, null columns are represented by `None`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DynRow(pub Vec<Option<Value>>);
+
+impl DynRow {
+    pub const fn new(columns: Vec<Option<Value>>) -> Self {
+        Self(columns)
+    }
+
+    pub fn columns(&self) -> &[Option<Value>] {
+        &self.0
+    }
+}
+
+impl PartialOrd for DynRow {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Rows are compared column by column with null columns sorting after all
+/// non-null values, the same as compiled rows
+impl Ord for DynRow {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for (lhs, rhs) in self.0.iter().zip(&other.0) {
+            let ordering = match (lhs, rhs) {
+                (Some(lhs), Some(rhs)) => lhs.cmp(rhs),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        self.0.len().cmp(&other.0.len())
+    }
+}
+
+impl Display for DynRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{ ")?;
+        for (idx, column) in self.0.iter().enumerate() {
+            if idx != 0 {
+                f.write_str(", ")?;
+            }
+
+            match column {
+                Some(value) => Display::fmt(value, f)?,
+                None => f.write_str("null")?,
+            }
+        }
+        f.write_str(" }")
+    }
+}
+
+/// A consolidated batch of weighted rows flowing through a stream, the values
+/// of sets are empty rows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Batch {
+    kind: StreamKind,
+    tuples: BTreeMap<(DynRow, DynRow), i32>,
+}
+
+impl Batch {
+    pub fn new(kind: StreamKind) -> Self {
+        Self {
+            kind,
+            tuples: BTreeMap::new(),
+        }
+    }
+
+    pub const fn kind(&self) -> StreamKind {
+        self.kind
+    }
+
+    pub fn len(&self) -> usize {
+        self.tuples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tuples.is_empty()
+    }
+
+    /// Adds `weight` to the weight of `(key, value)`
+    pub fn insert(&mut self, key: DynRow, value: DynRow, weight: i32) {
+        if weight == 0 {
+            return;
+        }
+
+        match self.tuples.entry((key, value)) {
+            Entry::Vacant(entry) => {
+                entry.insert(weight);
+            }
+            Entry::Occupied(mut entry) => {
+                *entry.get_mut() = entry.get().wrapping_add(weight);
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+    }
+
+    /// Returns the weight of `(key, value)`
+    pub fn weight(&self, key: &DynRow, value: &DynRow) -> i32 {
+        // TODO: Avoid cloning here
+        self.tuples
+            .get(&(key.clone(), value.clone()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&DynRow, &DynRow, i32)> + '_ {
+        self.tuples
+            .iter()
+            .map(|((key, value), &weight)| (key, value, weight))
+    }
+
+    /// Returns all values associated with `key` along with their weights
+    pub(crate) fn values_of<'a>(
+        &'a self,
+        key: &'a DynRow,
+    ) -> impl Iterator<Item = (&'a DynRow, i32)> + 'a {
+        self.tuples
+            .range((key.clone(), DynRow::default())..)
+            .take_while(move |((tuple_key, _), _)| tuple_key == key)
+            .map(|((_, value), &weight)| (value, weight))
+    }
+
+    pub(crate) fn plus(&mut self, other: &Self) {
+        for (key, value, weight) in other.iter() {
+            self.insert(key.clone(), value.clone(), weight);
+        }
+    }
+
+    pub(crate) fn minus(&mut self, other: &Self) {
+        for (key, value, weight) in other.iter() {
+            self.insert(key.clone(), value.clone(), weight.wrapping_neg());
+        }
+    }
+}
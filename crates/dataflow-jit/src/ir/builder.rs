@@ -0,0 +1,750 @@
+//! A typed builder for constructing dataflow graphs from rust
+//!
+//! Streams are referred to by [`StreamHandle`]s which carry their layout, so
+//! connecting streams of different layouts is caught as soon as the nodes are
+//! added instead of when the graph is validated. Row functions are built with
+//! an [`ExprBuilder`] which checks the types of the expressions it's given
+//!
+//! ```rust,ignore
+//! let mut builder = graph.builder();
+//! let source = builder.source(layout);
+//! let filtered = builder.filter(source, |expr| {
+//!     let x = expr.column(expr.key(), 0);
+//!     let ten = expr.constant(Constant::U32(10));
+//!     expr.lt(x, ten)
+//! });
+//! builder.sink(filtered);
+//! ```
+//!
+//! The builder adds the same nodes as the plain [`GraphExt`] methods do, so
+//! graphs built with it are identical to those built by hand or deserialized
+//! from json
+
+use crate::ir::{
+    nodes::{
+        CollectStatistics, Differentiate, Distinct, Filter, Integrate, Map, Minus, Neg, Node, Sink,
+        Source, SourceMap, StreamLayout, Sum,
+    },
+    ColumnType, Constant, ExprId, Function, FunctionBuilder, GraphExt, InputFlags, LayoutId,
+    NodeId, RowLayoutCache,
+};
+use derive_more::Display;
+use std::error::Error;
+
+/// A stream within a graph along with its layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamHandle {
+    node: NodeId,
+    layout: StreamLayout,
+}
+
+impl StreamHandle {
+    /// Returns the node that produces the stream
+    pub const fn node(&self) -> NodeId {
+        self.node
+    }
+
+    pub const fn layout(&self) -> StreamLayout {
+        self.layout
+    }
+}
+
+/// Builds a dataflow graph out of [`StreamHandle`]s, see
+/// [`GraphExt::builder()`]
+pub struct GraphBuilder<'a, G: ?Sized> {
+    graph: &'a mut G,
+}
+
+impl<'a, G> GraphBuilder<'a, G>
+where
+    G: GraphExt + ?Sized,
+{
+    pub(crate) fn new(graph: &'a mut G) -> Self {
+        Self { graph }
+    }
+
+    fn stream<N>(&mut self, node: N, layout: StreamLayout) -> StreamHandle
+    where
+        N: Into<Node>,
+    {
+        let node = self.graph.add_node(node);
+        StreamHandle { node, layout }
+    }
+
+    pub fn source(&mut self, key_layout: LayoutId) -> StreamHandle {
+        self.stream(Source::new(key_layout), StreamLayout::Set(key_layout))
+    }
+
+    pub fn source_map(&mut self, key_layout: LayoutId, value_layout: LayoutId) -> StreamHandle {
+        self.stream(
+            SourceMap::new(key_layout, value_layout),
+            StreamLayout::Map(key_layout, value_layout),
+        )
+    }
+
+    pub fn sink(&mut self, input: StreamHandle) -> NodeId {
+        self.graph.add_node(Sink::new(input.node))
+    }
+
+    pub fn collect_statistics(&mut self, input: StreamHandle) -> NodeId {
+        self.graph.add_node(CollectStatistics::new(input.node))
+    }
+
+    /// Filters `input` with the predicate built by `build`, which must produce
+    /// a bool
+    ///
+    /// # Panics
+    ///
+    /// Panics if the predicate isn't a bool
+    pub fn filter<F>(&mut self, input: StreamHandle, build: F) -> StreamHandle
+    where
+        F: FnOnce(&mut ExprBuilder<'_>) -> Value,
+    {
+        let mut func = self
+            .graph
+            .function_builder()
+            .with_return_type(ColumnType::Bool);
+        let layout_cache = self.graph.layout_cache().clone();
+        let mut exprs = ExprBuilder::new(&mut func, layout_cache, input.layout);
+
+        let predicate = build(&mut exprs);
+        assert_eq!(
+            predicate.ty,
+            ColumnType::Bool,
+            "filter predicates must be bools, got a {}",
+            predicate.ty,
+        );
+        func.ret(predicate.expr);
+
+        self.stream(Filter::new(input.node, func.build()), input.layout)
+    }
+
+    /// Maps `input` to a stream of the given layout, `build` produces the value
+    /// of each output column, the key's columns followed by the value's
+    ///
+    /// Nullable output columns are always set to non-null values
+    pub fn map<F>(
+        &mut self,
+        input: StreamHandle,
+        output_layout: StreamLayout,
+        build: F,
+    ) -> Result<StreamHandle, BuildError>
+    where
+        F: FnOnce(&mut ExprBuilder<'_>) -> Vec<Value>,
+    {
+        let mut func = self.graph.function_builder();
+        let layout_cache = self.graph.layout_cache().clone();
+        let mut exprs = ExprBuilder::new(&mut func, layout_cache, input.layout);
+
+        let mut outputs = Vec::with_capacity(2);
+        output_layout.map_layouts(&mut |layout| {
+            outputs.push((exprs.func.add_output(layout), layout));
+        });
+        let values = build(&mut exprs);
+
+        // Make sure the produced values match the output layout's columns
+        let layout_cache = self.graph.layout_cache();
+        let columns: Vec<_> = outputs
+            .iter()
+            .flat_map(|&(output, layout)| {
+                let layout = layout_cache.get(layout);
+                (0..layout.len())
+                    .map(|column| {
+                        (
+                            output,
+                            column,
+                            layout.column_type(column),
+                            layout.column_nullable(column),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        if columns.len() != values.len()
+            || columns
+                .iter()
+                .zip(&values)
+                .any(|(&(.., ty, _), value)| ty != value.ty)
+        {
+            return Err(BuildError::MismatchedMapOutput {
+                expected: describe_layout(layout_cache, output_layout),
+                found: format!(
+                    "{{ {} }}",
+                    values
+                        .iter()
+                        .map(|value| value.ty.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            });
+        }
+
+        for ((output, column, _, nullable), value) in columns.into_iter().zip(values) {
+            func.store(output, column, value.expr);
+            if nullable {
+                func.set_null(output, column, false);
+            }
+        }
+        func.ret_unit();
+
+        Ok(self.stream(
+            Map::new(input.node, func.build(), input.layout, output_layout),
+            output_layout,
+        ))
+    }
+
+    /// Maps `input` to a stream of the given layout with an already built map
+    /// function
+    pub fn map_with(
+        &mut self,
+        input: StreamHandle,
+        output_layout: StreamLayout,
+        map_fn: Function,
+    ) -> Result<StreamHandle, BuildError> {
+        let mut expected = Vec::with_capacity(4);
+        input
+            .layout
+            .map_layouts(&mut |layout| expected.push((layout, InputFlags::INPUT)));
+        output_layout.map_layouts(&mut |layout| expected.push((layout, InputFlags::OUTPUT)));
+
+        let matches = map_fn.args().len() == expected.len()
+            && map_fn
+                .args()
+                .iter()
+                .zip(&expected)
+                .all(|(arg, &(layout, flags))| arg.layout == layout && arg.flags == flags);
+        if !matches {
+            let layout_cache = self.graph.layout_cache();
+            return Err(BuildError::MismatchedFunctionArgs {
+                node: "map",
+                expected: describe_args(layout_cache, expected.iter().copied()),
+                found: describe_args(
+                    layout_cache,
+                    map_fn.args().iter().map(|arg| (arg.layout, arg.flags)),
+                ),
+            });
+        }
+
+        Ok(self.stream(
+            Map::new(input.node, map_fn, input.layout, output_layout),
+            output_layout,
+        ))
+    }
+
+    pub fn distinct(&mut self, input: StreamHandle) -> StreamHandle {
+        self.stream(Distinct::new(input.node), input.layout)
+    }
+
+    pub fn neg(&mut self, input: StreamHandle) -> StreamHandle {
+        self.stream(Neg::new(input.node, input.layout), input.layout)
+    }
+
+    pub fn differentiate(&mut self, input: StreamHandle) -> StreamHandle {
+        self.stream(Differentiate::new(input.node), input.layout)
+    }
+
+    pub fn integrate(&mut self, input: StreamHandle) -> StreamHandle {
+        self.stream(Integrate::new(input.node), input.layout)
+    }
+
+    /// Sums the given streams, all of which must have the same layout
+    ///
+    /// # Panics
+    ///
+    /// Panics if `inputs` is empty
+    pub fn sum(&mut self, inputs: &[StreamHandle]) -> Result<StreamHandle, BuildError> {
+        let (first, rest) = inputs.split_first().expect("summed zero streams");
+        for input in rest {
+            self.expect_layout("sum", first.layout, input.layout)?;
+        }
+
+        Ok(self.stream(
+            Sum::new(inputs.iter().map(|input| input.node).collect()),
+            first.layout,
+        ))
+    }
+
+    /// Subtracts `rhs` from `lhs`, both of which must have the same layout
+    pub fn minus(
+        &mut self,
+        lhs: StreamHandle,
+        rhs: StreamHandle,
+    ) -> Result<StreamHandle, BuildError> {
+        self.expect_layout("minus", lhs.layout, rhs.layout)?;
+        Ok(self.stream(Minus::new(lhs.node, rhs.node), lhs.layout))
+    }
+
+    fn expect_layout(
+        &self,
+        node: &'static str,
+        expected: StreamLayout,
+        found: StreamLayout,
+    ) -> Result<(), BuildError> {
+        if expected == found {
+            Ok(())
+        } else {
+            let layout_cache = self.graph.layout_cache();
+            Err(BuildError::MismatchedLayouts {
+                node,
+                expected: describe_layout(layout_cache, expected),
+                found: describe_layout(layout_cache, found),
+            })
+        }
+    }
+}
+
+fn describe_layout(layout_cache: &RowLayoutCache, layout: StreamLayout) -> String {
+    match layout {
+        StreamLayout::Set(key) => format!("set of {key} {}", layout_cache.get(key)),
+        StreamLayout::Map(key, value) => format!(
+            "map of {key} {} to {value} {}",
+            layout_cache.get(key),
+            layout_cache.get(value),
+        ),
+    }
+}
+
+fn describe_args<I>(layout_cache: &RowLayoutCache, args: I) -> String
+where
+    I: IntoIterator<Item = (LayoutId, InputFlags)>,
+{
+    let args: Vec<_> = args
+        .into_iter()
+        .map(|(layout, flags)| {
+            let kind = if flags.contains(InputFlags::OUTPUT) {
+                "output"
+            } else {
+                "input"
+            };
+            format!("{kind} {layout} {}", layout_cache.get(layout))
+        })
+        .collect();
+
+    format!("({})", args.join(", "))
+}
+
+#[derive(Debug, Display)]
+pub enum BuildError {
+    #[display(
+        fmt = "mismatched stream layouts in {node}, expected a {expected} but got a {found}"
+    )]
+    MismatchedLayouts {
+        node: &'static str,
+        expected: String,
+        found: String,
+    },
+
+    #[display(
+        fmt = "mismatched function arguments in {node}, expected {expected} but got {found}"
+    )]
+    MismatchedFunctionArgs {
+        node: &'static str,
+        expected: String,
+        found: String,
+    },
+
+    #[display(fmt = "mismatched map output, expected a {expected} but got the values {found}")]
+    MismatchedMapOutput { expected: String, found: String },
+}
+
+impl Error for BuildError {}
+
+/// A row within an [`ExprBuilder`]'s function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Row {
+    expr: ExprId,
+    layout: LayoutId,
+}
+
+impl Row {
+    pub const fn layout(&self) -> LayoutId {
+        self.layout
+    }
+}
+
+/// A scalar value within an [`ExprBuilder`]'s function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Value {
+    expr: ExprId,
+    ty: ColumnType,
+}
+
+impl Value {
+    pub const fn expr(&self) -> ExprId {
+        self.expr
+    }
+
+    pub const fn ty(&self) -> ColumnType {
+        self.ty
+    }
+}
+
+/// Builds the body of a row function over the rows of a stream, panics as soon
+/// as it's given values of mismatched types
+pub struct ExprBuilder<'a> {
+    func: &'a mut FunctionBuilder,
+    layout_cache: RowLayoutCache,
+    key: Row,
+    value: Option<Row>,
+}
+
+impl<'a> ExprBuilder<'a> {
+    fn new(
+        func: &'a mut FunctionBuilder,
+        layout_cache: RowLayoutCache,
+        input: StreamLayout,
+    ) -> Self {
+        let key_layout = input.key_layout();
+        let key = Row {
+            expr: func.add_input(key_layout),
+            layout: key_layout,
+        };
+        let value = input.value_layout().map(|layout| Row {
+            expr: func.add_input(layout),
+            layout,
+        });
+
+        Self {
+            func,
+            layout_cache,
+            key,
+            value,
+        }
+    }
+
+    /// Returns the current key row
+    pub const fn key(&self) -> Row {
+        self.key
+    }
+
+    /// Returns the current value row
+    ///
+    /// # Panics
+    ///
+    /// Panics if the input stream is a set
+    pub fn value(&self) -> Row {
+        self.value.expect("sets have no value rows")
+    }
+
+    /// Loads the given column of `row`, the value of null columns is
+    /// unspecified
+    pub fn column(&mut self, row: Row, column: usize) -> Value {
+        let ty = self.layout_cache.get(row.layout).column_type(column);
+        assert!(
+            !matches!(ty, ColumnType::Struct | ColumnType::Array),
+            "cannot load {ty} column {column} of {}",
+            row.layout,
+        );
+
+        Value {
+            expr: self.func.load(row.expr, column),
+            ty,
+        }
+    }
+
+    /// Returns `true` if the given column of `row` is null
+    pub fn is_null(&mut self, row: Row, column: usize) -> Value {
+        Value {
+            expr: self.func.is_null(row.expr, column),
+            ty: ColumnType::Bool,
+        }
+    }
+
+    pub fn constant(&mut self, constant: Constant) -> Value {
+        let ty = constant.column_type();
+        Value {
+            expr: self.func.constant(constant),
+            ty,
+        }
+    }
+
+    pub fn cast(&mut self, value: Value, ty: ColumnType) -> Value {
+        Value {
+            expr: self.func.cast(value.expr, ty),
+            ty,
+        }
+    }
+
+    pub fn add(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.arithmetic("add", lhs, rhs, FunctionBuilder::add)
+    }
+
+    pub fn sub(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.arithmetic("sub", lhs, rhs, FunctionBuilder::sub)
+    }
+
+    pub fn mul(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.arithmetic("mul", lhs, rhs, FunctionBuilder::mul)
+    }
+
+    pub fn div(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.arithmetic("div", lhs, rhs, FunctionBuilder::div)
+    }
+
+    pub fn eq(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.comparison("eq", lhs, rhs, FunctionBuilder::eq)
+    }
+
+    pub fn neq(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.comparison("neq", lhs, rhs, FunctionBuilder::neq)
+    }
+
+    pub fn lt(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.comparison("lt", lhs, rhs, FunctionBuilder::lt)
+    }
+
+    pub fn gt(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.comparison("gt", lhs, rhs, FunctionBuilder::gt)
+    }
+
+    pub fn le(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.comparison("le", lhs, rhs, FunctionBuilder::le)
+    }
+
+    pub fn ge(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.comparison("ge", lhs, rhs, FunctionBuilder::ge)
+    }
+
+    pub fn and(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.expect_bools("and", lhs, rhs);
+        self.arithmetic("and", lhs, rhs, FunctionBuilder::and)
+    }
+
+    pub fn or(&mut self, lhs: Value, rhs: Value) -> Value {
+        self.expect_bools("or", lhs, rhs);
+        self.arithmetic("or", lhs, rhs, FunctionBuilder::or)
+    }
+
+    /// Selects `if_true` when `cond` is true and `if_false` otherwise
+    pub fn select(&mut self, cond: Value, if_true: Value, if_false: Value) -> Value {
+        assert_eq!(
+            cond.ty,
+            ColumnType::Bool,
+            "select conditions must be bools, got a {}",
+            cond.ty,
+        );
+        self.expect_same_types("select", if_true, if_false);
+
+        Value {
+            expr: self.func.select(cond.expr, if_true.expr, if_false.expr),
+            ty: if_true.ty,
+        }
+    }
+
+    fn arithmetic(
+        &mut self,
+        op: &str,
+        lhs: Value,
+        rhs: Value,
+        build: fn(&mut FunctionBuilder, ExprId, ExprId) -> ExprId,
+    ) -> Value {
+        self.expect_same_types(op, lhs, rhs);
+        Value {
+            expr: build(self.func, lhs.expr, rhs.expr),
+            ty: lhs.ty,
+        }
+    }
+
+    fn comparison(
+        &mut self,
+        op: &str,
+        lhs: Value,
+        rhs: Value,
+        build: fn(&mut FunctionBuilder, ExprId, ExprId) -> ExprId,
+    ) -> Value {
+        self.expect_same_types(op, lhs, rhs);
+        Value {
+            expr: build(self.func, lhs.expr, rhs.expr),
+            ty: ColumnType::Bool,
+        }
+    }
+
+    #[track_caller]
+    fn expect_same_types(&self, op: &str, lhs: Value, rhs: Value) {
+        assert_eq!(
+            lhs.ty, rhs.ty,
+            "mismatched types in {op}, {} and {}",
+            lhs.ty, rhs.ty,
+        );
+    }
+
+    #[track_caller]
+    fn expect_bools(&self, op: &str, lhs: Value, rhs: Value) {
+        assert!(
+            lhs.ty.is_bool() && rhs.ty.is_bool(),
+            "{op} takes bools, got {} and {}",
+            lhs.ty,
+            rhs.ty,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ir::{
+            builder::BuildError,
+            nodes::{Filter, StreamLayout},
+            ColumnType, Constant, Graph, GraphExt, RowLayoutBuilder,
+        },
+        sql_graph::SqlGraph,
+    };
+
+    #[test]
+    fn builder_matches_hand_built_graph() {
+        let layouts = |graph: &Graph| {
+            graph.layout_cache().add(
+                RowLayoutBuilder::new()
+                    .with_column(ColumnType::U32, false)
+                    .build(),
+            )
+        };
+
+        let built = {
+            let mut graph = Graph::new();
+            let x = layouts(&graph);
+
+            let mut builder = graph.builder();
+            let source = builder.source(x);
+            let filtered = builder.filter(source, |exprs| {
+                let x = exprs.column(exprs.key(), 0);
+                let ten = exprs.constant(Constant::U32(10));
+                exprs.lt(x, ten)
+            });
+            let doubled = builder
+                .map(filtered, StreamLayout::Set(x), |exprs| {
+                    let x = exprs.column(exprs.key(), 0);
+                    vec![exprs.add(x, x)]
+                })
+                .unwrap();
+            let distinct = builder.distinct(doubled);
+            builder.sink(distinct);
+
+            graph
+        };
+
+        let by_hand = {
+            let mut graph = Graph::new();
+            let x = layouts(&graph);
+
+            let source = graph.source(x);
+            let filtered = graph.add_node(Filter::new(source, {
+                let mut func = graph.function_builder().with_return_type(ColumnType::Bool);
+                let input = func.add_input(x);
+                let x = func.load(input, 0);
+                let ten = func.constant(Constant::U32(10));
+                let less = func.lt(x, ten);
+                func.ret(less);
+                func.build()
+            }));
+            let doubled = graph.map(filtered, StreamLayout::Set(x), StreamLayout::Set(x), {
+                let mut func = graph.function_builder();
+                let input = func.add_input(x);
+                let output = func.add_output(x);
+                let x = func.load(input, 0);
+                let doubled = func.add(x, x);
+                func.store(output, 0, doubled);
+                func.ret_unit();
+                func.build()
+            });
+            let distinct = graph.distinct(doubled);
+            graph.sink(distinct);
+
+            graph
+        };
+
+        let built = serde_json::to_value(SqlGraph::from(built)).unwrap();
+        let by_hand = serde_json::to_value(SqlGraph::from(by_hand)).unwrap();
+        assert_eq!(built, by_hand);
+
+        // Deserializing the built graph produces the same graph
+        let deserialized = serde_json::from_value::<SqlGraph>(built.clone())
+            .unwrap()
+            .rematerialize();
+        assert_eq!(
+            serde_json::to_value(SqlGraph::from(deserialized)).unwrap(),
+            built,
+        );
+    }
+
+    #[test]
+    fn mismatched_stream_layouts() {
+        let mut graph = Graph::new();
+        let u32 = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .build(),
+        );
+        let string = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::String, true)
+                .build(),
+        );
+        let u32_fn = {
+            let mut func = graph.function_builder();
+            func.add_input(u32);
+            func.add_output(u32);
+            func.ret_unit();
+            func.build()
+        };
+
+        let mut builder = graph.builder();
+        let numbers = builder.source(u32);
+        let strings = builder.source(string);
+
+        let error = builder.sum(&[numbers, strings]).unwrap_err();
+        assert!(matches!(
+            error,
+            BuildError::MismatchedLayouts { node: "sum", .. }
+        ));
+        let message = error.to_string();
+        assert!(message.contains(&format!("{u32}")), "{message}");
+        assert!(message.contains(&format!("{string}")), "{message}");
+
+        let error = builder.minus(strings, numbers).unwrap_err();
+        assert!(matches!(
+            error,
+            BuildError::MismatchedLayouts { node: "minus", .. }
+        ));
+
+        let error = builder
+            .map_with(strings, StreamLayout::Set(u32), u32_fn)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            BuildError::MismatchedFunctionArgs { node: "map", .. }
+        ));
+
+        let error = builder
+            .map(numbers, StreamLayout::Set(string), |exprs| {
+                vec![exprs.column(exprs.key(), 0)]
+            })
+            .unwrap_err();
+        assert!(matches!(error, BuildError::MismatchedMapOutput { .. }));
+
+        // Streams of the same layouts can still be connected
+        let other_numbers = builder.source(u32);
+        assert!(builder.sum(&[numbers, other_numbers]).is_ok());
+    }
+
+    #[test]
+    #[should_panic = "mismatched types in add, u32 and str"]
+    fn mismatched_expr_types() {
+        let mut graph = Graph::new();
+        let layout = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .with_column(ColumnType::String, false)
+                .build(),
+        );
+
+        let mut builder = graph.builder();
+        let source = builder.source(layout);
+        builder.filter(source, |exprs| {
+            let x = exprs.column(exprs.key(), 0);
+            let y = exprs.column(exprs.key(), 1);
+            exprs.add(x, y)
+        });
+    }
+}
@@ -117,3 +117,52 @@ impl Call {
         self.ret_ty
     }
 }
+
+/// A call to a user defined function (udf) registered with the code generator,
+/// see [`crate::ir::udf`] for the ABI udfs are called with
+///
+/// Nullable arguments take two expressions, their value followed by a bool
+/// that's `true` when the argument is null. Calls to udfs returning non-null
+/// values produce a scalar of the udf's return type while calls to udfs with
+/// nullable returns produce a row of `ret_layout`, a layout with a single
+/// nullable column of the udf's return type which the result is written to
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct CallUdf {
+    /// The name of the udf being called
+    name: String,
+    /// The arguments passed to the udf
+    args: Vec<ExprId>,
+    /// The layout of the row the result of udfs with nullable returns is
+    /// written to
+    ret_layout: Option<LayoutId>,
+}
+
+impl CallUdf {
+    pub fn new(name: String, args: Vec<ExprId>, ret_layout: Option<LayoutId>) -> Self {
+        Self {
+            name,
+            args,
+            ret_layout,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn args(&self) -> &[ExprId] {
+        &self.args
+    }
+
+    pub fn args_mut(&mut self) -> &mut Vec<ExprId> {
+        &mut self.args
+    }
+
+    pub const fn ret_layout(&self) -> Option<LayoutId> {
+        self.ret_layout
+    }
+
+    pub fn ret_layout_mut(&mut self) -> Option<&mut LayoutId> {
+        self.ret_layout.as_mut()
+    }
+}
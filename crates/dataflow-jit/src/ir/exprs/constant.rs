@@ -1,7 +1,11 @@
-use crate::ir::ColumnType;
+use crate::ir::{literal::RowLiteral, ColumnType};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, mem};
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    mem,
+};
 
 /// A constant value
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
@@ -21,6 +25,10 @@ pub enum Constant {
     F64(f64),
     Bool(bool),
     String(String),
+    /// A row of a nested layout, only valid as the value of a struct column
+    Struct(RowLiteral),
+    /// The elements of an array, only valid as the value of an array column
+    Array(Vec<RowLiteral>),
     // TODO: Date, Timestamp
 }
 
@@ -96,6 +104,8 @@ impl Constant {
             Self::F64(_) => ColumnType::F64,
             Self::Bool(_) => ColumnType::Bool,
             Self::String(_) => ColumnType::String,
+            Self::Struct(_) => ColumnType::Struct,
+            Self::Array(_) => ColumnType::Array,
         }
     }
 }
@@ -117,6 +127,8 @@ impl PartialEq for Constant {
             (Self::F64(lhs), Self::F64(rhs)) => lhs.total_cmp(rhs).is_eq(),
             (Self::Bool(lhs), Self::Bool(rhs)) => lhs == rhs,
             (Self::String(lhs), Self::String(rhs)) => lhs == rhs,
+            (Self::Struct(lhs), Self::Struct(rhs)) => lhs == rhs,
+            (Self::Array(lhs), Self::Array(rhs)) => lhs == rhs,
 
             _ => {
                 debug_assert_ne!(mem::discriminant(self), mem::discriminant(other));
@@ -128,6 +140,34 @@ impl PartialEq for Constant {
 
 impl Eq for Constant {}
 
+// Floats are hashed by their bits to stay consistent with `PartialEq`, which
+// compares them with `total_cmp()`
+impl Hash for Constant {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match self {
+            Self::Unit => {}
+            Self::U8(value) => value.hash(state),
+            Self::I8(value) => value.hash(state),
+            Self::U16(value) => value.hash(state),
+            Self::I16(value) => value.hash(state),
+            Self::U32(value) => value.hash(state),
+            Self::I32(value) => value.hash(state),
+            Self::U64(value) => value.hash(state),
+            Self::I64(value) => value.hash(state),
+            Self::Usize(value) => value.hash(state),
+            Self::Isize(value) => value.hash(state),
+            Self::F32(value) => value.to_bits().hash(state),
+            Self::F64(value) => value.to_bits().hash(state),
+            Self::Bool(value) => value.hash(state),
+            Self::String(value) => value.hash(state),
+            Self::Struct(value) => value.hash(state),
+            Self::Array(value) => value.hash(state),
+        }
+    }
+}
+
 impl PartialOrd for Constant {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(match (self, other) {
@@ -145,6 +185,8 @@ impl PartialOrd for Constant {
             (Self::F64(lhs), Self::F64(rhs)) => lhs.total_cmp(rhs),
             (Self::Bool(lhs), Self::Bool(rhs)) => lhs.cmp(rhs),
             (Self::String(lhs), Self::String(rhs)) => lhs.cmp(rhs),
+            (Self::Struct(lhs), Self::Struct(rhs)) => lhs.cmp(rhs),
+            (Self::Array(lhs), Self::Array(rhs)) => lhs.cmp(rhs),
 
             _ => {
                 debug_assert_ne!(mem::discriminant(self), mem::discriminant(other));
@@ -171,6 +213,8 @@ impl Ord for Constant {
             (Self::F64(lhs), Self::F64(rhs)) => lhs.total_cmp(rhs),
             (Self::Bool(lhs), Self::Bool(rhs)) => lhs.cmp(rhs),
             (Self::String(lhs), Self::String(rhs)) => lhs.cmp(rhs),
+            (Self::Struct(lhs), Self::Struct(rhs)) => lhs.cmp(rhs),
+            (Self::Array(lhs), Self::Array(rhs)) => lhs.cmp(rhs),
 
             _ => {
                 debug_assert_ne!(mem::discriminant(self), mem::discriminant(other));
@@ -7,7 +7,7 @@ mod select;
 mod unary;
 
 pub use binary::{BinaryOp, BinaryOpKind};
-pub use call::{ArgType, Call};
+pub use call::{ArgType, Call, CallUdf};
 pub use constant::Constant;
 pub use select::Select;
 pub use unary::{UnaryOp, UnaryOpKind};
@@ -24,6 +24,7 @@ use std::collections::BTreeMap;
 #[derive(Debug, Clone, From, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub enum Expr {
     Call(Call),
+    CallUdf(CallUdf),
     Cast(Cast),
     Load(Load),
     Store(Store),
@@ -56,6 +57,11 @@ impl Expr {
                     }
                 }
             }
+            Self::CallUdf(call) => {
+                if let Some(layout) = call.ret_layout_mut() {
+                    *layout = mappings[layout];
+                }
+            }
 
             // These expressions don't contain `LayoutId`s
             Self::Cast(_)
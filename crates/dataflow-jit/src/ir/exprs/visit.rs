@@ -1,13 +1,14 @@
 use crate::ir::{
     exprs::{
-        ArgType, BinaryOp, Call, Cast, Constant, Copy, CopyRowTo, Expr, IsNull, Load, NullRow,
-        Select, SetNull, Store, UnaryOp, UninitRow,
+        ArgType, BinaryOp, Call, CallUdf, Cast, Constant, Copy, CopyRowTo, Expr, IsNull, Load,
+        NullRow, Select, SetNull, Store, UnaryOp, UninitRow,
     },
     LayoutId,
 };
 
 pub trait ExprVisitor {
     fn visit_call(&mut self, _call: &Call) {}
+    fn visit_call_udf(&mut self, _call: &CallUdf) {}
     fn visit_cast(&mut self, _cast: &Cast) {}
     fn visit_load(&mut self, _load: &Load) {}
     fn visit_store(&mut self, _store: &Store) {}
@@ -25,6 +26,7 @@ pub trait ExprVisitor {
 
 pub trait MutExprVisitor {
     fn visit_call(&mut self, _call: &mut Call) {}
+    fn visit_call_udf(&mut self, _call: &mut CallUdf) {}
     fn visit_cast(&mut self, _cast: &mut Cast) {}
     fn visit_load(&mut self, _load: &mut Load) {}
     fn visit_store(&mut self, _store: &mut Store) {}
@@ -47,6 +49,7 @@ impl Expr {
     {
         match self {
             Self::Call(call) => visitor.visit_call(call),
+            Self::CallUdf(call) => visitor.visit_call_udf(call),
             Self::Cast(cast) => visitor.visit_cast(cast),
             Self::Load(load) => visitor.visit_load(load),
             Self::Store(store) => visitor.visit_store(store),
@@ -69,6 +72,7 @@ impl Expr {
     {
         match self {
             Self::Call(call) => visitor.visit_call(call),
+            Self::CallUdf(call) => visitor.visit_call_udf(call),
             Self::Cast(cast) => visitor.visit_cast(cast),
             Self::Load(load) => visitor.visit_load(load),
             Self::Store(store) => visitor.visit_store(store),
@@ -135,4 +139,10 @@ where
             }
         }
     }
+
+    fn visit_call_udf(&mut self, call: &CallUdf) {
+        if let Some(layout) = call.ret_layout() {
+            (self.map_layout)(layout);
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use crate::ir::{
     block::Block,
     block::{ParamType, UnsealedBlock},
+    exprs::CallUdf,
     function::FuncArg,
     layout_cache::RowLayoutCache,
     BinaryOp, BinaryOpKind, BlockId, BlockIdGen, Branch, Cast, ColumnType, Constant, Copy,
@@ -371,6 +372,33 @@ impl FunctionBuilder {
         self.add_expr(CopyRowTo::new(src, dest, src_layout));
     }
 
+    /// Calls the udf `name` which returns a non-null `ret_ty`
+    pub fn call_udf<N>(&mut self, name: N, args: Vec<ExprId>, ret_ty: ColumnType) -> ExprId
+    where
+        N: Into<String>,
+    {
+        let expr = self.add_expr(CallUdf::new(name.into(), args, None));
+        self.set_expr_type(expr, ret_ty);
+        expr
+    }
+
+    /// Calls the udf `name` which returns a nullable value, writing the result
+    /// to a row of `ret_layout` which must have a single nullable column of
+    /// the udf's return type
+    pub fn call_nullable_udf<N>(
+        &mut self,
+        name: N,
+        args: Vec<ExprId>,
+        ret_layout: LayoutId,
+    ) -> ExprId
+    where
+        N: Into<String>,
+    {
+        let expr = self.add_expr(CallUdf::new(name.into(), args, Some(ret_layout)));
+        self.set_expr_type(expr, ret_layout);
+        expr
+    }
+
     // pub fn call<F>(&mut self, func: F) -> ExprId  where F: Into<String>{
     //     self.add_expr(Call::new(
     //         func.into(),
@@ -212,6 +212,12 @@ impl Function {
                             used.extend(call.args());
                         }
 
+                        Expr::CallUdf(call) => {
+                            // Udfs are opaque so they're always regarded as effectful
+                            used.insert(expr_id);
+                            used.extend(call.args());
+                        }
+
                         // These contain no expressions
                         Expr::NullRow(_) | Expr::Constant(_) | Expr::UninitRow(_) => {}
                     }
@@ -324,6 +330,12 @@ impl Function {
                         }
                     }
 
+                    Expr::CallUdf(call) => {
+                        for arg in call.args_mut() {
+                            remap(arg);
+                        }
+                    }
+
                     // Constants contain no expressions
                     Expr::Constant(_) => {}
 
@@ -463,9 +475,16 @@ impl Function {
 
         if !substitutions.is_empty() {
             for block in self.blocks.values_mut() {
-                for (_, expr) in block.body() {
+                for (_, expr) in block.body_mut() {
                     match expr {
                         Expr::Call(_) => todo!(),
+                        Expr::CallUdf(call) => {
+                            for arg in call.args_mut() {
+                                if let Some(&subst) = substitutions.get(arg) {
+                                    *arg = subst;
+                                }
+                            }
+                        }
                         Expr::Load(_) => todo!(),
                         Expr::Store(_) => todo!(),
                         Expr::BinOp(_) => todo!(),
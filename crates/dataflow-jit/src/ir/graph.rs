@@ -4,8 +4,13 @@
 // simplify rerouting edges and removing nodes
 
 use crate::ir::{
+    builder::GraphBuilder,
     layout_cache::RowLayoutCache,
-    nodes::{ConstantStream, Distinct, Integrate, Node, StreamLayout, Subgraph as SubgraphNode},
+    literal::StreamLiteral,
+    nodes::{
+        CollectStatistics, ConstantSource, ConstantStream, Distinct, Integrate, Node, StreamLayout,
+        Subgraph as SubgraphNode,
+    },
     nodes::{
         DataflowNode, Differentiate, ExportedNode, Filter, IndexWith, JoinCore, Map, Sink, Source,
         SourceMap, StreamKind,
@@ -14,10 +19,13 @@ use crate::ir::{
     visit::{MutNodeVisitor, NodeVisitor},
     Function, FunctionBuilder, LayoutId, NodeId, NodeIdGen,
 };
-use petgraph::prelude::DiGraphMap;
+use petgraph::{prelude::DiGraphMap, Direction};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, rc::Rc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
 
 pub trait GraphExt {
     fn layout_cache(&self) -> &RowLayoutCache;
@@ -120,6 +128,12 @@ pub trait GraphExt {
         FunctionBuilder::new(self.layout_cache().clone())
     }
 
+    /// Returns a builder that adds nodes to the graph through typed stream
+    /// handles, see [`GraphBuilder`]
+    fn builder(&mut self) -> GraphBuilder<'_, Self> {
+        GraphBuilder::new(self)
+    }
+
     fn source(&mut self, key_layout: LayoutId) -> NodeId {
         self.add_node(Source::new(key_layout))
     }
@@ -132,6 +146,10 @@ pub trait GraphExt {
         self.add_node(Sink::new(input))
     }
 
+    fn collect_statistics(&mut self, input: NodeId) -> NodeId {
+        self.add_node(CollectStatistics::new(input))
+    }
+
     fn filter(&mut self, input: NodeId, filter_fn: Function) -> NodeId {
         self.add_node(Filter::new(input, filter_fn))
     }
@@ -199,6 +217,12 @@ pub trait GraphExt {
     fn empty_stream(&mut self, layout: StreamLayout) -> NodeId {
         self.add_node(ConstantStream::empty(layout))
     }
+
+    /// Creates a source that produces `value` once at the start of the
+    /// circuit's clock
+    fn constant_source(&mut self, value: StreamLiteral) -> NodeId {
+        self.add_node(ConstantSource::new(value))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -426,6 +450,53 @@ impl Default for GraphContext {
     }
 }
 
+/// Topologically sorts a dataflow graph, picking the node with the lowest id
+/// whenever more than one node could come next
+///
+/// Unlike [`petgraph::algo::toposort()`] the produced order only depends on
+/// the graph's nodes and edges and not on the order they were added in, so
+/// equivalent graphs always produce identical orders. Returns a node that
+/// couldn't be ordered if the graph contains a cycle
+pub(crate) fn toposort(edges: &DiGraphMap<NodeId, ()>) -> Result<Vec<NodeId>, NodeId> {
+    let mut in_degrees: BTreeMap<NodeId, usize> = edges
+        .nodes()
+        .map(|node| {
+            let in_degree = edges.neighbors_directed(node, Direction::Incoming).count();
+            (node, in_degree)
+        })
+        .collect();
+
+    let mut ready: BTreeSet<NodeId> = in_degrees
+        .iter()
+        .filter(|&(_, &in_degree)| in_degree == 0)
+        .map(|(&node, _)| node)
+        .collect();
+
+    let mut order = Vec::with_capacity(in_degrees.len());
+    while let Some(node) = ready.pop_first() {
+        order.push(node);
+
+        for target in edges.neighbors_directed(node, Direction::Outgoing) {
+            let in_degree = in_degrees.get_mut(&target).unwrap();
+            *in_degree -= 1;
+            if *in_degree == 0 {
+                ready.insert(target);
+            }
+        }
+    }
+
+    if order.len() == in_degrees.len() {
+        Ok(order)
+    } else {
+        let unordered = in_degrees
+            .into_iter()
+            .find(|&(_, in_degree)| in_degree != 0)
+            .map(|(node, _)| node)
+            .unwrap();
+        Err(unordered)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -436,7 +507,7 @@ mod tests {
             function::FunctionBuilder,
             graph::{Graph, GraphExt},
             literal::{NullableConstant, RowLiteral},
-            nodes::{Differentiate, Fold, IndexWith, Neg, Sink, Source, StreamLayout, Sum},
+            nodes::{Differentiate, Fold, IndexWith, Neg, Source, StreamLayout, Sum},
             types::{ColumnType, RowLayout, RowLayoutBuilder},
             validate::Validator,
         },
@@ -799,33 +870,23 @@ mod tests {
                 .with_column(ColumnType::U32, false)
                 .build(),
         );
-        let source = graph.add_node(Source::new(xy_layout));
-
         let x_layout = graph.layout_cache().add(
             RowLayoutBuilder::new()
                 .with_column(ColumnType::U32, false)
                 .build(),
         );
-        let map = graph.map(
-            source,
-            StreamLayout::Set(xy_layout),
-            StreamLayout::Set(x_layout),
-            {
-                let mut func = FunctionBuilder::new(graph.layout_cache().clone());
-                let input = func.add_input(xy_layout);
-                let output = func.add_output(x_layout);
-
-                let x = func.load(input, 0);
-                let y = func.load(input, 1);
-                let xy = func.mul(x, y);
-                func.store(output, 0, xy);
-
-                func.ret_unit();
-                func.build()
-            },
-        );
 
-        let sink = graph.add_node(Sink::new(map));
+        let mut builder = graph.builder();
+        let source = builder.source(xy_layout);
+        let map = builder
+            .map(source, StreamLayout::Set(x_layout), |exprs| {
+                let x = exprs.column(exprs.key(), 0);
+                let y = exprs.column(exprs.key(), 1);
+                vec![exprs.mul(x, y)]
+            })
+            .unwrap();
+        let sink = builder.sink(map);
+        let source = source.node();
 
         let mut validator = Validator::new(graph.layout_cache().clone());
         validator.validate_graph(&graph).unwrap();
@@ -894,6 +955,6 @@ mod tests {
             runtime.kill().unwrap();
         }
 
-        unsafe { jit_handle.free_memory() }
+        jit_handle.free_memory();
     }
 }
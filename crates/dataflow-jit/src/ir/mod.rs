@@ -1,13 +1,16 @@
 pub mod block;
+pub mod builder;
 pub mod exprs;
 pub mod graph;
 pub mod literal;
 pub mod nodes;
+pub mod udf;
 pub mod visit;
 
 mod function;
 mod ids;
 mod layout_cache;
+mod monotonicity;
 mod optimize;
 mod terminator;
 mod types;
@@ -24,6 +27,6 @@ pub use ids::{BlockId, ExprId, LayoutId, NodeId};
 pub use layout_cache::RowLayoutCache;
 pub use terminator::{Branch, Jump, Return, Terminator};
 pub use types::{ColumnType, RowLayout, RowLayoutBuilder, Signature};
-pub use validate::Validator;
+pub use validate::{ValidationError, Validator};
 
 pub(crate) use ids::{BlockIdGen, ExprIdGen, NodeIdGen};
@@ -0,0 +1,601 @@
+//! Monotonicity analysis for the streams that watermarks are derived from
+//!
+//! A column of a stream is monotone when its values never decrease from one
+//! step to the next. Sources declare which of their columns are monotone and
+//! the analysis works backwards from the column a watermark is taken from,
+//! following it through the nodes and expressions that are known to preserve
+//! monotonicity until it reaches a source that declares it. Anything else, a
+//! non-monotone node, an expression like `x * y` or a source without the
+//! declaration, is reported as the point where monotonicity is lost
+//!
+//! The analysis is deliberately conservative, a column that can't be proven
+//! monotone is treated as non-monotone even if it is
+
+use crate::ir::{
+    nodes::{Node, StreamColumn, StreamLayout},
+    BinaryOpKind, ColumnType, Constant, Expr, ExprId, Function, Graph, GraphExt, NodeId, RValue,
+    UnaryOpKind,
+};
+use std::collections::BTreeMap;
+
+/// The point at which a column stops being monotone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct NonMonotone {
+    /// The node that breaks monotonicity
+    pub node: NodeId,
+    /// The expression within the node's function that breaks monotonicity, if
+    /// there is one
+    pub expr: Option<ExprId>,
+    pub reason: String,
+}
+
+impl NonMonotone {
+    fn node(node: NodeId, reason: String) -> Self {
+        Self {
+            node,
+            expr: None,
+            reason,
+        }
+    }
+
+    fn expr(node: NodeId, expr: ExprId, reason: String) -> Self {
+        Self {
+            node,
+            expr: Some(expr),
+            reason,
+        }
+    }
+}
+
+/// Checks that `column` of the stream produced by `node` is monotone
+pub(super) fn check_monotone(
+    graph: &Graph,
+    mut node: NodeId,
+    mut column: StreamColumn,
+) -> Result<(), NonMonotone> {
+    loop {
+        let (input, input_column) = match &graph.nodes()[&node] {
+            Node::Source(source) => {
+                return match column {
+                    StreamColumn::Key(key) if source.monotone_columns().contains(&key) => Ok(()),
+                    _ => Err(NonMonotone::node(
+                        node,
+                        format!("source {node} doesn't declare its {column} as monotone"),
+                    )),
+                };
+            }
+
+            Node::SourceMap(source) => {
+                return if source.monotone_columns().contains(&column) {
+                    Ok(())
+                } else {
+                    Err(NonMonotone::node(
+                        node,
+                        format!("source {node} doesn't declare its {column} as monotone"),
+                    ))
+                };
+            }
+
+            // Filtering and deduplicating rows never changes their values
+            Node::Filter(filter) => (filter.input(), column),
+            Node::Distinct(distinct) => (distinct.input(), column),
+
+            Node::Map(map) => {
+                let inputs = match map.input_layout() {
+                    StreamLayout::Set(_) => 1,
+                    StreamLayout::Map(..) => 2,
+                };
+                let origin = FunctionOrigins::new(node, map.map_fn(), inputs).column(column)?;
+
+                match origin {
+                    Origin::Constant => return Ok(()),
+                    Origin::Input(input) => (map.input(), input),
+                }
+            }
+
+            Node::IndexWith(index_with) => {
+                match FunctionOrigins::new(node, index_with.index_fn(), 1).column(column)? {
+                    Origin::Constant => return Ok(()),
+                    Origin::Input(input) => (index_with.input(), input),
+                }
+            }
+
+            _ => {
+                return Err(NonMonotone::node(
+                    node,
+                    format!(
+                    "the output of {node} isn't known to preserve the monotonicity of its inputs"
+                ),
+                ))
+            }
+        };
+
+        node = input;
+        column = input_column;
+    }
+}
+
+/// Where the value of a monotone column comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    /// The column is a constant, which is trivially monotone
+    Constant,
+    /// The column is a monotone function of a column of the node's input
+    Input(StreamColumn),
+}
+
+/// Traces the output columns of a node's function back to its inputs
+struct FunctionOrigins<'a> {
+    node: NodeId,
+    func: &'a Function,
+    /// The number of input arguments the function takes, the remaining
+    /// arguments are outputs
+    inputs: usize,
+    exprs: BTreeMap<ExprId, &'a Expr>,
+}
+
+impl<'a> FunctionOrigins<'a> {
+    fn new(node: NodeId, func: &'a Function, inputs: usize) -> Self {
+        let exprs = func
+            .blocks()
+            .values()
+            .flat_map(|block| block.body())
+            .map(|(expr_id, expr)| (*expr_id, expr))
+            .collect();
+
+        Self {
+            node,
+            func,
+            inputs,
+            exprs,
+        }
+    }
+
+    /// Finds the origin of the given output column, the function's first
+    /// output row is the key and the second is the value
+    fn column(&self, column: StreamColumn) -> Result<Origin, NonMonotone> {
+        let (output, output_column) = match column {
+            StreamColumn::Key(column) => (self.inputs, column),
+            StreamColumn::Value(column) => (self.inputs + 1, column),
+        };
+        let output = match self.func.args().get(output) {
+            Some(arg) => arg.id,
+            None => {
+                return Err(NonMonotone::node(
+                    self.node,
+                    format!("{} doesn't produce a {column}", self.node),
+                ))
+            }
+        };
+
+        let mut value = None;
+        for (&expr_id, expr) in &self.exprs {
+            let written = match expr {
+                Expr::Store(store) => store.target() == output && store.column() == output_column,
+                Expr::SetNull(set_null) => {
+                    set_null.target() == output && set_null.column() == output_column
+                }
+                Expr::CopyRowTo(copy) => copy.dest() == output,
+                _ => false,
+            };
+            if !written {
+                continue;
+            }
+
+            match (expr, value) {
+                (Expr::Store(store), None) => value = Some(store.value()),
+
+                (Expr::Store(_), Some(_)) => {
+                    return Err(self.error(
+                        expr_id,
+                        format!("{expr_id} is one of multiple stores to output {column}"),
+                    ))
+                }
+
+                _ => {
+                    return Err(self.error(
+                        expr_id,
+                        format!(
+                            "{expr_id} writes to output {column} by something other than a store"
+                        ),
+                    ))
+                }
+            }
+        }
+
+        match value {
+            Some(RValue::Expr(value)) => self.origin(*value),
+            Some(RValue::Imm(_)) => Ok(Origin::Constant),
+            None => Err(NonMonotone::node(
+                self.node,
+                format!(
+                    "the function of {} never writes to output {column}",
+                    self.node
+                ),
+            )),
+        }
+    }
+
+    fn origin(&self, expr_id: ExprId) -> Result<Origin, NonMonotone> {
+        let expr = match self.exprs.get(&expr_id) {
+            Some(expr) => *expr,
+            None => return Err(self.error(expr_id, format!("{expr_id} isn't a value of a column"))),
+        };
+
+        match expr {
+            Expr::Constant(_) => Ok(Origin::Constant),
+
+            Expr::Load(load) => {
+                let arg = self.func.args()[..self.inputs]
+                    .iter()
+                    .position(|arg| arg.id == load.source());
+
+                match arg {
+                    Some(0) => Ok(Origin::Input(StreamColumn::Key(load.column()))),
+                    Some(_) => Ok(Origin::Input(StreamColumn::Value(load.column()))),
+                    None => Err(self.error(
+                        expr_id,
+                        format!(
+                            "{expr_id} loads from {} which isn't one of the function's inputs",
+                            load.source()
+                        ),
+                    )),
+                }
+            }
+
+            Expr::Copy(copy) => self.origin(copy.value()),
+
+            Expr::Cast(cast) if is_monotone_cast(cast.from(), cast.to()) => {
+                self.origin(cast.value())
+            }
+            Expr::Cast(cast) => Err(self.error(
+                expr_id,
+                format!(
+                    "{expr_id} casts from {} to {} which doesn't preserve monotonicity",
+                    cast.from(),
+                    cast.to()
+                ),
+            )),
+
+            Expr::UnaryOp(unary)
+                if matches!(
+                    unary.kind(),
+                    UnaryOpKind::Floor | UnaryOpKind::Ceil | UnaryOpKind::Trunc,
+                ) =>
+            {
+                self.origin(unary.value())
+            }
+
+            Expr::BinOp(binop) => {
+                let (lhs, rhs) = (binop.lhs(), binop.rhs());
+                let (lhs_const, rhs_const) = (self.constant(lhs), self.constant(rhs));
+
+                let preserved = match binop.kind() {
+                    // `x + c`, `c + x`, `min(x, c)` and `max(x, c)`
+                    BinaryOpKind::Add | BinaryOpKind::Min | BinaryOpKind::Max => {
+                        match (lhs_const, rhs_const) {
+                            (_, Some(_)) => Some(lhs),
+                            (Some(_), None) => Some(rhs),
+                            (None, None) => None,
+                        }
+                    }
+
+                    // `x - c`
+                    BinaryOpKind::Sub => rhs_const.map(|_| lhs),
+
+                    // `x * c` and `c * x` where `c >= 0`
+                    BinaryOpKind::Mul => match (lhs_const, rhs_const) {
+                        (_, Some(rhs)) if is_non_negative(rhs) => Some(lhs),
+                        (Some(lhs), _) if is_non_negative(lhs) => Some(rhs),
+                        _ => None,
+                    },
+
+                    // `x / c` where `c > 0`
+                    BinaryOpKind::Div | BinaryOpKind::DivFloor => rhs_const
+                        .filter(|&rhs| is_non_negative(rhs) && !is_zero(rhs))
+                        .map(|_| lhs),
+
+                    _ => None,
+                };
+
+                match preserved {
+                    Some(value) => self.origin(value),
+                    None => Err(self.error(
+                        expr_id,
+                        format!("{expr_id} is a `{:?}` operation which isn't known to preserve monotonicity", binop.kind()),
+                    )),
+                }
+            }
+
+            _ => Err(self.error(
+                expr_id,
+                format!("{expr_id} isn't known to preserve monotonicity"),
+            )),
+        }
+    }
+
+    /// Returns the value of `expr_id` if it's a constant
+    fn constant(&self, expr_id: ExprId) -> Option<&'a Constant> {
+        match self.exprs.get(&expr_id) {
+            Some(Expr::Constant(constant)) => Some(constant),
+            _ => None,
+        }
+    }
+
+    fn error(&self, expr_id: ExprId, reason: String) -> NonMonotone {
+        NonMonotone::expr(self.node, expr_id, reason)
+    }
+}
+
+/// Returns `true` if casting from `from` to `to` never reorders values
+fn is_monotone_cast(from: ColumnType, to: ColumnType) -> bool {
+    // The width of each integer type, pointer-width integers are treated as
+    // being 64 bits since they can never be wider
+    const fn int_width(ty: ColumnType) -> Option<u32> {
+        Some(match ty {
+            ColumnType::U8 | ColumnType::I8 => 8,
+            ColumnType::U16 | ColumnType::I16 => 16,
+            ColumnType::U32 | ColumnType::I32 | ColumnType::Date => 32,
+            ColumnType::U64
+            | ColumnType::I64
+            | ColumnType::Usize
+            | ColumnType::Isize
+            | ColumnType::Timestamp => 64,
+            _ => return None,
+        })
+    }
+
+    if from == to {
+        return true;
+    }
+
+    match (int_width(from), int_width(to)) {
+        // Widening an integer preserves its value as long as the sign bit isn't
+        // reinterpreted, dates and timestamps are signed
+        (Some(from_width), Some(to_width)) => {
+            let from_signed = from.is_signed_int() || from.is_date() || from.is_timestamp();
+            let to_signed = to.is_signed_int() || to.is_date() || to.is_timestamp();
+
+            (from_signed == to_signed && from_width <= to_width)
+                || (!from_signed && to_signed && from_width < to_width)
+        }
+
+        // Rounding to a float never reorders values
+        (Some(_), None) => to.is_float(),
+        (None, None) => from.is_float() && to.is_float(),
+
+        // Float to int casts saturate or wrap depending on the codegen config
+        (None, Some(_)) => false,
+    }
+}
+
+fn is_non_negative(constant: &Constant) -> bool {
+    match *constant {
+        Constant::I8(value) => value >= 0,
+        Constant::I16(value) => value >= 0,
+        Constant::I32(value) => value >= 0,
+        Constant::I64(value) => value >= 0,
+        Constant::Isize(value) => value >= 0,
+        Constant::F32(value) => value >= 0.0,
+        Constant::F64(value) => value >= 0.0,
+        Constant::U8(_)
+        | Constant::U16(_)
+        | Constant::U32(_)
+        | Constant::U64(_)
+        | Constant::Usize(_) => true,
+        _ => false,
+    }
+}
+
+fn is_zero(constant: &Constant) -> bool {
+    match *constant {
+        Constant::U8(value) => value == 0,
+        Constant::I8(value) => value == 0,
+        Constant::U16(value) => value == 0,
+        Constant::I16(value) => value == 0,
+        Constant::U32(value) => value == 0,
+        Constant::I32(value) => value == 0,
+        Constant::U64(value) => value == 0,
+        Constant::I64(value) => value == 0,
+        Constant::Usize(value) => value == 0,
+        Constant::Isize(value) => value == 0,
+        Constant::F32(value) => value == 0.0,
+        Constant::F64(value) => value == 0.0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ir::{
+            literal::{NullableConstant, RowLiteral},
+            nodes::{Map, PartitionedRollingFold, SourceMap, StreamColumn, StreamLayout},
+            ColumnType, Constant, ExprId, FunctionBuilder, Graph, GraphExt, NodeId,
+            RowLayoutBuilder, ValidationError, Validator,
+        },
+        utils,
+    };
+    use dbsp::operator::time_series::{RelOffset, RelRange};
+
+    /// Builds a graph computing a rolling sum over a map source of timestamped
+    /// values, the timestamps the fold sees are produced by `timestamps` from
+    /// the source's timestamps
+    fn rolling_sum<F>(
+        declared: bool,
+        assume_monotone: bool,
+        timestamps: F,
+    ) -> (Graph, NodeId, NodeId)
+    where
+        F: FnOnce(&mut FunctionBuilder, ExprId) -> ExprId,
+    {
+        let mut graph = Graph::new();
+
+        let key = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .build(),
+        );
+        let value = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I64, false)
+                .with_column(ColumnType::I32, false)
+                .build(),
+        );
+        let i32 = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, false)
+                .build(),
+        );
+
+        let monotone_columns = if declared {
+            vec![StreamColumn::Value(0)]
+        } else {
+            Vec::new()
+        };
+        let source =
+            graph.add_node(SourceMap::new(key, value).with_monotone_columns(monotone_columns));
+
+        let map = {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let input_key = func.add_input(key);
+            let input_value = func.add_input(value);
+            let output_key = func.add_output(key);
+            let output_value = func.add_output(value);
+
+            let partition = func.load(input_key, 0);
+            func.store(output_key, 0, partition);
+
+            let timestamp = func.load(input_value, 0);
+            let timestamp = timestamps(&mut func, timestamp);
+            func.store(output_value, 0, timestamp);
+
+            let x = func.load(input_value, 1);
+            func.store(output_value, 1, x);
+
+            func.ret_unit();
+            func.build()
+        };
+        let map = graph.add_node(Map::new(
+            source,
+            map,
+            StreamLayout::Map(key, value),
+            StreamLayout::Map(key, value),
+        ));
+
+        let step_fn = {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let acc = func.add_input_output(i32);
+            let step = func.add_input(value);
+            let weight = func.add_input(i32);
+
+            let sum = func.load(acc, 0);
+            let x = func.load(step, 1);
+            let weight = func.load(weight, 0);
+            let diff = func.mul(x, weight);
+            let sum = func.add(sum, diff);
+            func.store(acc, 0, sum);
+
+            func.ret_unit();
+            func.build()
+        };
+        let finish_fn = {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let acc = func.add_input(i32);
+            let output = func.add_output(i32);
+
+            let sum = func.load(acc, 0);
+            func.store(output, 0, sum);
+
+            func.ret_unit();
+            func.build()
+        };
+        let fold = graph.add_node(
+            PartitionedRollingFold::new(
+                map,
+                RelRange::new(RelOffset::Before(1000), RelOffset::Before(0)),
+                RowLiteral::new(vec![NullableConstant::NonNull(Constant::I32(0))]),
+                step_fn,
+                finish_fn,
+                i32,
+                value,
+                i32,
+            )
+            .with_unsafe_assume_monotone(assume_monotone),
+        );
+        graph.sink(fold);
+
+        (graph, source, map)
+    }
+
+    fn validate(graph: &Graph) -> Result<(), ValidationError> {
+        Validator::new(graph.layout_cache().clone()).validate_graph(graph)
+    }
+
+    #[test]
+    fn offset_timestamps_are_monotone() {
+        utils::test_logger();
+
+        let (graph, ..) = rolling_sum(true, false, |func, timestamp| {
+            let offset = func.constant(Constant::I64(1000));
+            func.add(timestamp, offset)
+        });
+        validate(&graph).unwrap();
+    }
+
+    #[test]
+    fn undeclared_timestamps_are_rejected() {
+        utils::test_logger();
+
+        let (graph, source, _) = rolling_sum(false, false, |func, timestamp| {
+            let offset = func.constant(Constant::I64(1000));
+            func.add(timestamp, offset)
+        });
+
+        match validate(&graph) {
+            Err(ValidationError::NonMonotoneWatermark {
+                breaking_node,
+                breaking_expr,
+                ..
+            }) => {
+                assert_eq!(breaking_node, source);
+                assert_eq!(breaking_expr, None);
+            }
+
+            result => panic!("expected a non-monotone watermark, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn squared_timestamps_are_rejected() {
+        utils::test_logger();
+
+        let mut squared = None;
+        let (graph, _, map) = rolling_sum(true, false, |func, timestamp| {
+            let square = func.mul(timestamp, timestamp);
+            squared = Some(square);
+            square
+        });
+
+        match validate(&graph) {
+            Err(ValidationError::NonMonotoneWatermark {
+                breaking_node,
+                breaking_expr,
+                ..
+            }) => {
+                assert_eq!(breaking_node, map);
+                assert_eq!(breaking_expr, squared);
+            }
+
+            result => panic!("expected a non-monotone watermark, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn assumed_monotone_timestamps_are_accepted() {
+        utils::test_logger();
+
+        let (graph, ..) = rolling_sum(true, true, |func, timestamp| func.mul(timestamp, timestamp));
+        validate(&graph).unwrap();
+    }
+}
@@ -304,6 +304,16 @@ pub struct PartitionedRollingFold {
     step_layout: LayoutId,
     /// The layout of the output stream
     output_layout: LayoutId,
+    /// Skips checking that the timestamps of the input stream are monotone
+    ///
+    /// The first column of the input stream's values are the timestamps the
+    /// fold's range is relative to and the fold uses them as its watermark, so
+    /// they must never decrease. The validator rejects inputs whose timestamps
+    /// it can't prove are monotone unless this is set, in which case feeding
+    /// the fold non-monotone timestamps silently produces incorrect results
+    // Graphs serialized before the flag existed deserialize with the check enabled
+    #[serde(default)]
+    unsafe_assume_monotone: bool,
 }
 
 impl PartitionedRollingFold {
@@ -327,9 +337,17 @@ impl PartitionedRollingFold {
             acc_layout,
             step_layout,
             output_layout,
+            unsafe_assume_monotone: false,
         }
     }
 
+    /// Sets whether the input's timestamps are assumed to be monotone instead
+    /// of being checked by the validator
+    pub fn with_unsafe_assume_monotone(mut self, unsafe_assume_monotone: bool) -> Self {
+        self.unsafe_assume_monotone = unsafe_assume_monotone;
+        self
+    }
+
     pub const fn input(&self) -> NodeId {
         self.input
     }
@@ -361,6 +379,10 @@ impl PartitionedRollingFold {
     pub const fn range(&self) -> RelRange<i64> {
         self.range
     }
+
+    pub const fn unsafe_assume_monotone(&self) -> bool {
+        self.unsafe_assume_monotone
+    }
 }
 
 impl DataflowNode for PartitionedRollingFold {
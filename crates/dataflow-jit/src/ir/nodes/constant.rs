@@ -48,64 +48,7 @@ impl ConstantStream {
 
     pub fn consolidate(&mut self) {
         if !self.consolidated {
-            let start_len = self.value.len();
-            match self.value.value_mut() {
-                StreamCollection::Set(set) => {
-                    // FIXME: We really should be sorting by the criteria that the
-                    // runtime rows will be sorted by so we have less work to do at
-                    // runtime, but technically any sorting criteria works as long
-                    // as it's consistent and allows us to deduplicate the stream
-                    set.sort_by(|(a, _), (b, _)| a.cmp(b));
-
-                    // Deduplicate rows and combine their weights
-                    set.dedup_by(|(a, weight_a), (b, weight_b)| {
-                        if a == b {
-                            *weight_b = weight_b
-                                .checked_add(*weight_a)
-                                .expect("weight overflow in constant stream");
-
-                            true
-                        } else {
-                            false
-                        }
-                    });
-
-                    // Remove all zero weights
-                    set.retain(|&(_, weight)| weight != 0);
-                }
-
-                StreamCollection::Map(map) => {
-                    // FIXME: We really should be sorting by the criteria that the
-                    // runtime rows will be sorted by so we have less work to do at
-                    // runtime, but technically any sorting criteria works as long
-                    // as it's consistent and allows us to deduplicate the stream
-                    map.sort_by(|(key_a, value_a, _), (key_b, value_b, _)| {
-                        key_a.cmp(key_b).then_with(|| value_a.cmp(value_b))
-                    });
-
-                    // Deduplicate rows and combine their weights
-                    map.dedup_by(|(key_a, value_a, weight_a), (key_b, value_b, weight_b)| {
-                        if key_a == key_b && value_a == value_b {
-                            *weight_b = weight_b
-                                .checked_add(*weight_a)
-                                .expect("weight overflow in constant stream");
-
-                            true
-                        } else {
-                            false
-                        }
-                    });
-
-                    // Remove all zero weights
-                    map.retain(|&(_, _, weight)| weight != 0);
-                }
-            }
-
-            let removed = start_len - self.value.len();
-            if removed != 0 {
-                tracing::trace!("removed {removed} items from constant stream");
-            }
-
+            consolidate_literal(&mut self.value);
             self.consolidated = true;
         }
     }
@@ -152,3 +95,146 @@ impl DataflowNode for ConstantStream {
         self.value.layout_mut().remap_layouts(mappings);
     }
 }
+
+/// A source that produces a constant collection of rows once, at the start
+/// of its circuit's clock, and empty collections on every following step
+///
+/// This is the dataflow equivalent of a `VALUES (...)` clause, a relation that
+/// doesn't depend on any inputs. Unlike [`ConstantStream`], which produces the
+/// same collection on every step, the rows of a constant source are only
+/// inserted once
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, JsonSchema)]
+pub struct ConstantSource {
+    value: StreamLiteral,
+    #[serde(skip_deserializing)]
+    consolidated: bool,
+}
+
+impl ConstantSource {
+    pub const fn new(value: StreamLiteral) -> Self {
+        Self {
+            value,
+            consolidated: false,
+        }
+    }
+
+    pub const fn value(&self) -> &StreamLiteral {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut StreamLiteral {
+        &mut self.value
+    }
+
+    pub const fn layout(&self) -> StreamLayout {
+        self.value.layout()
+    }
+
+    pub const fn consolidated(&self) -> bool {
+        self.consolidated
+    }
+
+    pub fn consolidate(&mut self) {
+        if !self.consolidated {
+            consolidate_literal(&mut self.value);
+            self.consolidated = true;
+        }
+    }
+}
+
+impl DataflowNode for ConstantSource {
+    fn map_inputs<F>(&self, _map: &mut F)
+    where
+        F: FnMut(NodeId),
+    {
+    }
+
+    fn map_inputs_mut<F>(&mut self, _map: &mut F)
+    where
+        F: FnMut(&mut NodeId),
+    {
+    }
+
+    fn output_stream(&self, _inputs: &[StreamLayout]) -> Option<StreamLayout> {
+        Some(self.layout())
+    }
+
+    fn validate(&self, _inputs: &[StreamLayout], _layout_cache: &RowLayoutCache) {}
+
+    fn optimize(&mut self, _layout_cache: &RowLayoutCache) {
+        self.consolidate();
+    }
+
+    fn map_layouts<F>(&self, map: &mut F)
+    where
+        F: FnMut(LayoutId),
+    {
+        self.value.layout().map_layouts(map);
+    }
+
+    fn remap_layouts(&mut self, mappings: &BTreeMap<LayoutId, LayoutId>) {
+        self.value.layout_mut().remap_layouts(mappings);
+    }
+}
+
+/// Consolidates a stream literal by sorting it, combining the weights of
+/// duplicate rows and removing rows with a weight of zero
+fn consolidate_literal(value: &mut StreamLiteral) {
+    let start_len = value.len();
+    match value.value_mut() {
+        StreamCollection::Set(set) => {
+            // FIXME: We really should be sorting by the criteria that the
+            // runtime rows will be sorted by so we have less work to do at
+            // runtime, but technically any sorting criteria works as long
+            // as it's consistent and allows us to deduplicate the stream
+            set.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            // Deduplicate rows and combine their weights
+            set.dedup_by(|(a, weight_a), (b, weight_b)| {
+                if a == b {
+                    *weight_b = weight_b
+                        .checked_add(*weight_a)
+                        .expect("weight overflow in constant stream");
+
+                    true
+                } else {
+                    false
+                }
+            });
+
+            // Remove all zero weights
+            set.retain(|&(_, weight)| weight != 0);
+        }
+
+        StreamCollection::Map(map) => {
+            // FIXME: We really should be sorting by the criteria that the
+            // runtime rows will be sorted by so we have less work to do at
+            // runtime, but technically any sorting criteria works as long
+            // as it's consistent and allows us to deduplicate the stream
+            map.sort_by(|(key_a, value_a, _), (key_b, value_b, _)| {
+                key_a.cmp(key_b).then_with(|| value_a.cmp(value_b))
+            });
+
+            // Deduplicate rows and combine their weights
+            map.dedup_by(|(key_a, value_a, weight_a), (key_b, value_b, weight_b)| {
+                if key_a == key_b && value_a == value_b {
+                    *weight_b = weight_b
+                        .checked_add(*weight_a)
+                        .expect("weight overflow in constant stream");
+
+                    true
+                } else {
+                    false
+                }
+            });
+
+            // Remove all zero weights
+            map.retain(|&(_, _, weight)| weight != 0);
+        }
+    }
+
+    let removed = start_len - value.len();
+    if removed != 0 {
+        tracing::trace!("removed {removed} items from constant stream");
+    }
+}
@@ -1,6 +1,6 @@
 use crate::ir::{
     layout_cache::RowLayoutCache,
-    nodes::{DataflowNode, StreamLayout},
+    nodes::{DataflowNode, StreamColumn, StreamLayout},
     LayoutId, NodeId,
 };
 use schemars::JsonSchema;
@@ -132,17 +132,37 @@ impl DataflowNode for ExportedNode {
 pub struct Source {
     /// The type of the source's produced stream
     layout: LayoutId,
+    /// The columns whose values never decrease from one step to the next
+    // Graphs serialized before monotonicity was tracked deserialize without any
+    // monotone columns
+    #[serde(default)]
+    monotone_columns: Vec<usize>,
 }
 
 impl Source {
     pub const fn new(layout: LayoutId) -> Self {
-        Self { layout }
+        Self {
+            layout,
+            monotone_columns: Vec::new(),
+        }
+    }
+
+    /// Declares that the given columns never decrease from one step to the
+    /// next, which is what allows watermarks to be derived from them
+    pub fn with_monotone_columns(mut self, monotone_columns: Vec<usize>) -> Self {
+        self.monotone_columns = monotone_columns;
+        self
     }
 
     /// The type of the source's produced stream
     pub const fn layout(&self) -> LayoutId {
         self.layout
     }
+
+    /// The columns whose values never decrease from one step to the next
+    pub fn monotone_columns(&self) -> &[usize] {
+        &self.monotone_columns
+    }
 }
 
 impl DataflowNode for Source {
@@ -182,6 +202,12 @@ impl DataflowNode for Source {
 pub struct SourceMap {
     key_layout: LayoutId,
     value_layout: LayoutId,
+    /// The key and value columns whose values never decrease from one step to
+    /// the next
+    // Graphs serialized before monotonicity was tracked deserialize without any
+    // monotone columns
+    #[serde(default)]
+    monotone_columns: Vec<StreamColumn>,
 }
 
 impl SourceMap {
@@ -189,9 +215,18 @@ impl SourceMap {
         Self {
             key_layout: key,
             value_layout: value,
+            monotone_columns: Vec::new(),
         }
     }
 
+    /// Declares that the given key and value columns never decrease from one
+    /// step to the next, which is what allows watermarks to be derived from
+    /// them
+    pub fn with_monotone_columns(mut self, monotone_columns: Vec<StreamColumn>) -> Self {
+        self.monotone_columns = monotone_columns;
+        self
+    }
+
     /// The key type of the source's produced stream
     pub const fn key(&self) -> LayoutId {
         self.key_layout
@@ -201,6 +236,12 @@ impl SourceMap {
     pub const fn value(&self) -> LayoutId {
         self.value_layout
     }
+
+    /// The key and value columns whose values never decrease from one step to
+    /// the next
+    pub fn monotone_columns(&self) -> &[StreamColumn] {
+        &self.monotone_columns
+    }
 }
 
 impl DataflowNode for SourceMap {
@@ -241,16 +282,49 @@ impl DataflowNode for SourceMap {
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct Sink {
     input: NodeId,
+    /// What the sink emits on each step, see [`SinkOutputMode`]
+    // Graphs serialized before the mode existed deserialize as emitting deltas
+    #[serde(default)]
+    output_mode: SinkOutputMode,
 }
 
 impl Sink {
     pub fn new(input: NodeId) -> Self {
-        Self { input }
+        Self {
+            input,
+            output_mode: SinkOutputMode::Deltas,
+        }
+    }
+
+    /// Sets what the sink emits on each step
+    pub fn with_output_mode(mut self, output_mode: SinkOutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
     }
 
     pub const fn input(&self) -> NodeId {
         self.input
     }
+
+    pub const fn output_mode(&self) -> SinkOutputMode {
+        self.output_mode
+    }
+}
+
+/// What a [`Sink`] emits on each step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkOutputMode {
+    /// The raw weighted deltas produced by the sink's input, with multiple
+    /// workers each worker's deltas are emitted separately
+    #[default]
+    Deltas,
+    /// The net effect of each step, deltas are consolidated across all workers
+    /// so that each row appears at most once with its total weight
+    NetDeltas,
+    /// The net effect of each step which may only insert rows, the circuit
+    /// fails with the offending row if any row has a negative net weight
+    AppendOnly,
 }
 
 impl DataflowNode for Sink {
@@ -284,3 +358,56 @@ impl DataflowNode for Sink {
 
     fn remap_layouts(&mut self, _mappings: &BTreeMap<LayoutId, LayoutId>) {}
 }
+
+/// Collects per-column statistics over its input stream for adaptive
+/// optimization, produces no output
+///
+/// Statistics are only collected when
+/// [`CodegenConfig::collect_statistics`](crate::codegen::CodegenConfig::collect_statistics)
+/// is enabled, otherwise the node is compiled to nothing
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct CollectStatistics {
+    input: NodeId,
+}
+
+impl CollectStatistics {
+    pub fn new(input: NodeId) -> Self {
+        Self { input }
+    }
+
+    pub const fn input(&self) -> NodeId {
+        self.input
+    }
+}
+
+impl DataflowNode for CollectStatistics {
+    fn map_inputs<F>(&self, map: &mut F)
+    where
+        F: FnMut(NodeId),
+    {
+        map(self.input);
+    }
+
+    fn map_inputs_mut<F>(&mut self, map: &mut F)
+    where
+        F: FnMut(&mut NodeId),
+    {
+        map(&mut self.input);
+    }
+
+    fn output_stream(&self, _inputs: &[StreamLayout]) -> Option<StreamLayout> {
+        None
+    }
+
+    fn validate(&self, _inputs: &[StreamLayout], _layout_cache: &RowLayoutCache) {}
+
+    fn optimize(&mut self, _layout_cache: &RowLayoutCache) {}
+
+    fn map_layouts<F>(&self, _map: &mut F)
+    where
+        F: FnMut(LayoutId),
+    {
+    }
+
+    fn remap_layouts(&mut self, _mappings: &BTreeMap<LayoutId, LayoutId>) {}
+}
@@ -17,6 +17,12 @@ pub struct JoinCore {
     key_layout: LayoutId,
     value_layout: LayoutId,
     output_kind: StreamKind,
+    /// Whether keys containing nulls are able to match each other, e.g. for
+    /// joins on `IS NOT DISTINCT FROM`. When false (the default) SQL semantics
+    /// are used and keys with any null columns never match anything
+    // Graphs serialized before the flag existed deserialize with SQL semantics
+    #[serde(default)]
+    null_safe: bool,
 }
 
 impl JoinCore {
@@ -35,9 +41,16 @@ impl JoinCore {
             key_layout,
             value_layout,
             output_kind,
+            null_safe: false,
         }
     }
 
+    /// Sets whether keys containing nulls are able to match each other
+    pub fn with_null_safe(mut self, null_safe: bool) -> Self {
+        self.null_safe = null_safe;
+        self
+    }
+
     pub const fn lhs(&self) -> NodeId {
         self.lhs
     }
@@ -50,6 +63,11 @@ impl JoinCore {
         &self.join_fn
     }
 
+    /// Returns the layout of the keys of both input streams
+    pub fn join_key_layout(&self) -> LayoutId {
+        self.join_fn.args()[0].layout
+    }
+
     pub const fn key_layout(&self) -> LayoutId {
         self.key_layout
     }
@@ -58,6 +76,10 @@ impl JoinCore {
         self.value_layout
     }
 
+    pub const fn null_safe(&self) -> bool {
+        self.null_safe
+    }
+
     pub(crate) fn result_kind(&self) -> StreamKind {
         self.output_kind
     }
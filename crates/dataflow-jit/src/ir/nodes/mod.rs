@@ -10,12 +10,12 @@ mod subgraph;
 mod sum;
 
 pub use aggregate::{Fold, Max, Min, PartitionedRollingFold};
-pub use constant::ConstantStream;
+pub use constant::{ConstantSource, ConstantStream};
 pub use differentiate::{Differentiate, Integrate};
 pub use filter_map::{Filter, FilterMap, Map};
 pub use flat_map::FlatMap;
 pub use index::IndexWith;
-pub use io::{Export, ExportedNode, Sink, Source, SourceMap};
+pub use io::{CollectStatistics, Export, ExportedNode, Sink, SinkOutputMode, Source, SourceMap};
 pub use join::{Antijoin, JoinCore, MonotonicJoin};
 pub use subgraph::Subgraph;
 pub use sum::{Minus, Sum};
@@ -25,7 +25,10 @@ use derive_more::{IsVariant, Unwrap};
 use enum_dispatch::enum_dispatch;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
 
 #[enum_dispatch(DataflowNode)]
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema, IsVariant, Unwrap)]
@@ -54,9 +57,11 @@ pub enum Node {
     ExportedNode(ExportedNode),
     MonotonicJoin(MonotonicJoin),
     Constant(ConstantStream),
+    ConstantSource(ConstantSource),
     PartitionedRollingFold(PartitionedRollingFold),
     FlatMap(FlatMap),
     Antijoin(Antijoin),
+    CollectStatistics(CollectStatistics),
     // TODO: OrderBy, Windows
 }
 
@@ -69,6 +74,14 @@ impl Node {
         }
     }
 
+    pub const fn as_constant_source(&self) -> Option<&ConstantSource> {
+        if let Self::ConstantSource(constant) = self {
+            Some(constant)
+        } else {
+            None
+        }
+    }
+
     pub const fn as_antijoin(&self) -> Option<&Antijoin> {
         if let Self::Antijoin(antijoin) = self {
             Some(antijoin)
@@ -198,6 +211,36 @@ pub enum StreamKind {
     Map,
 }
 
+/// A column of either the keys or the values of a stream
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Deserialize,
+    Serialize,
+    JsonSchema,
+    IsVariant,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamColumn {
+    Key(usize),
+    Value(usize),
+}
+
+impl Display for StreamColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(column) => write!(f, "key column {column}"),
+            Self::Value(column) => write!(f, "value column {column}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, JsonSchema)]
 pub struct Distinct {
     input: NodeId,
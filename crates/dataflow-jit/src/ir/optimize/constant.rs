@@ -0,0 +1,208 @@
+//! Fold operations over constant sources into constant sources
+//!
+//! Constant sources produce their rows once at the start of the clock and empty
+//! collections afterwards, so operations that act on each step's collection
+//! independently (`neg`, `sum`, `minus`, `distinct`) can be evaluated ahead of
+//! time. Integrating a constant source produces its rows on every step, which
+//! is exactly a constant stream
+
+use crate::ir::{
+    graph::{toposort, Subgraph},
+    literal::{StreamCollection, StreamLiteral},
+    nodes::{ConstantSource, ConstantStream, Node},
+    GraphExt, NodeId,
+};
+use petgraph::Direction;
+
+// TODO: Fold maps, filters and joins over constant sources by interpreting
+// their functions
+impl Subgraph {
+    pub(super) fn fold_constant_sources(&mut self) {
+        let order = toposort(self.edges()).expect("cyclic dataflow graph");
+
+        let mut inputs = Vec::new();
+        for node_id in order {
+            let folded = match self.nodes().get(&node_id) {
+                Some(node) => self.fold_node(node),
+                None => continue,
+            };
+
+            if let Some(folded) = folded {
+                tracing::trace!("folded node {node_id} into a constant");
+
+                // The folded node no longer depends on any of its inputs, if they're
+                // otherwise unused they'll be removed by later passes
+                inputs.extend(
+                    self.edges()
+                        .neighbors_directed(node_id, Direction::Incoming),
+                );
+                for input in inputs.drain(..) {
+                    self.edges_mut().remove_edge(input, node_id);
+                }
+
+                self.nodes_mut().insert(node_id, folded);
+            }
+        }
+
+        for node in self.nodes_mut().values_mut() {
+            if let Node::Subgraph(subgraph) = node {
+                subgraph.subgraph_mut().fold_constant_sources();
+            }
+        }
+    }
+
+    fn fold_node(&self, node: &Node) -> Option<Node> {
+        let constant = |node_id: NodeId| {
+            self.nodes()
+                .get(&node_id)
+                .and_then(Node::as_constant_source)
+                .map(ConstantSource::value)
+        };
+
+        let value = match node {
+            // `-constant`
+            Node::Neg(neg) => {
+                let mut value = constant(neg.input())?.clone();
+                negate_weights(&mut value);
+                value
+            }
+
+            // `constant₁ + constant₂ + ...`
+            Node::Sum(sum) => {
+                let (&first, rest) = sum.inputs().split_first()?;
+
+                let mut value = constant(first)?.clone();
+                for &input in rest {
+                    append(&mut value, constant(input)?);
+                }
+                value
+            }
+
+            // `constant₁ - constant₂`
+            Node::Minus(minus) => {
+                let (lhs, rhs) = (constant(minus.lhs())?, constant(minus.rhs())?);
+
+                let mut value = lhs.clone();
+                let mut negated = rhs.clone();
+                negate_weights(&mut negated);
+                append(&mut value, &negated);
+                value
+            }
+
+            // `distinct(constant)`
+            Node::Distinct(distinct) => {
+                let mut source = ConstantSource::new(constant(distinct.input())?.clone());
+                source.consolidate();
+
+                let mut value = source.value().clone();
+                match value.value_mut() {
+                    StreamCollection::Set(set) => {
+                        set.retain(|&(_, weight)| weight > 0);
+                        set.iter_mut().for_each(|(_, weight)| *weight = 1);
+                    }
+                    StreamCollection::Map(map) => {
+                        map.retain(|&(.., weight)| weight > 0);
+                        map.iter_mut().for_each(|(.., weight)| *weight = 1);
+                    }
+                }
+                value
+            }
+
+            // `integrate(constant)` is a constant stream of the source's rows
+            Node::Integrate(integrate) => {
+                let value = constant(integrate.input())?.clone();
+                let mut stream = ConstantStream::new(value.clone(), value.layout());
+                stream.consolidate();
+
+                return Some(Node::Constant(stream));
+            }
+
+            _ => return None,
+        };
+
+        let mut source = ConstantSource::new(value);
+        source.consolidate();
+        Some(Node::ConstantSource(source))
+    }
+}
+
+fn negate_weights(literal: &mut StreamLiteral) {
+    let negate = |weight: &mut i32| {
+        *weight = weight
+            .checked_neg()
+            .expect("weight overflow in constant stream");
+    };
+
+    match literal.value_mut() {
+        StreamCollection::Set(set) => set.iter_mut().for_each(|(_, weight)| negate(weight)),
+        StreamCollection::Map(map) => map.iter_mut().for_each(|(.., weight)| negate(weight)),
+    }
+}
+
+fn append(literal: &mut StreamLiteral, other: &StreamLiteral) {
+    match (literal.value_mut(), other.value()) {
+        (StreamCollection::Set(set), StreamCollection::Set(other)) => {
+            set.extend(other.iter().cloned());
+        }
+        (StreamCollection::Map(map), StreamCollection::Map(other)) => {
+            map.extend(other.iter().cloned());
+        }
+        _ => unreachable!("summed constants of different kinds"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ir::{
+            literal::{NullableConstant, RowLiteral, StreamCollection, StreamLiteral},
+            nodes::{Neg, StreamLayout, Sum},
+            ColumnType, Constant, Graph, GraphExt, RowLayoutBuilder,
+        },
+        utils,
+    };
+
+    #[test]
+    fn fold_constant_sources() {
+        utils::test_logger();
+
+        let mut graph = Graph::new();
+
+        let u32 = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .build(),
+        );
+        let literal = |values: &[(u32, i32)]| {
+            StreamLiteral::new(
+                StreamLayout::Set(u32),
+                StreamCollection::Set(
+                    values
+                        .iter()
+                        .map(|&(value, weight)| {
+                            let row = vec![NullableConstant::NonNull(Constant::U32(value))];
+                            (RowLiteral::new(row), weight)
+                        })
+                        .collect(),
+                ),
+            )
+        };
+
+        let lhs = graph.constant_source(literal(&[(1, 1), (2, 1)]));
+        let rhs = graph.constant_source(literal(&[(2, 1), (3, -1)]));
+        let negated = graph.add_node(Neg::new(rhs, StreamLayout::Set(u32)));
+        let sum = graph.add_node(Sum::new(vec![lhs, negated, rhs]));
+        let distinct = graph.distinct(sum);
+        let sink = graph.sink(distinct);
+
+        graph.optimize();
+
+        // Everything upstream of the sink should've been folded into a single
+        // constant source
+        assert_eq!(graph.nodes().len(), 2);
+
+        let input = graph.nodes()[&sink].clone().unwrap_sink().input();
+        let folded = graph.nodes()[&input].as_constant_source().unwrap();
+        assert_eq!(folded.value(), &literal(&[(1, 1), (2, 1)]));
+    }
+}
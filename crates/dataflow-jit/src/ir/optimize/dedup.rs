@@ -2,7 +2,7 @@
 
 use crate::ir::{
     graph::Subgraph,
-    nodes::{ConstantStream, Distinct, Subgraph as SubgraphNode},
+    nodes::{ConstantSource, ConstantStream, Distinct, Subgraph as SubgraphNode},
     visit::MutNodeVisitor,
     GraphExt, NodeId,
 };
@@ -45,6 +45,7 @@ impl Subgraph {
 #[derive(Debug, Default)]
 struct NodeCollector {
     constant: BTreeMap<ConstantStream, NodeId>,
+    constant_source: BTreeMap<ConstantSource, NodeId>,
     distinct: BTreeMap<Distinct, NodeId>,
     replacements: BTreeMap<NodeId, NodeId>,
 }
@@ -59,6 +60,16 @@ impl MutNodeVisitor for NodeCollector {
         }
     }
 
+    fn visit_constant_source(&mut self, node_id: NodeId, constant_source: &mut ConstantSource) {
+        if let Some(&canon) = self.constant_source.get(constant_source) {
+            tracing::trace!("deduplicating constant source nodes {node_id} and {canon}");
+            self.replacements.insert(node_id, canon);
+        } else {
+            self.constant_source
+                .insert(constant_source.clone(), node_id);
+        }
+    }
+
     fn visit_distinct(&mut self, node_id: NodeId, distinct: &mut Distinct) {
         match self.distinct.entry(distinct.clone()) {
             Entry::Vacant(vacant) => {
@@ -1,11 +1,13 @@
 //! Remove distinct over distinct streams
 
-use crate::ir::{graph::Subgraph, literal::StreamCollection, nodes::Node, GraphExt, NodeId};
-use petgraph::{
-    algo::{toposort, DfsSpace},
-    Direction,
+use crate::ir::{
+    graph::{toposort, Subgraph},
+    literal::{StreamCollection, StreamLiteral},
+    nodes::Node,
+    GraphExt, NodeId,
 };
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use petgraph::Direction;
+use std::collections::{BTreeMap, BTreeSet};
 
 impl Subgraph {
     pub(super) fn remove_redundant_distinct(&mut self) {
@@ -13,12 +15,11 @@ impl Subgraph {
 
         {
             let mut is_distinct = BTreeSet::new();
-            let mut buffer = DfsSpace::default();
 
             // Iteratively collect distinct nodes and propagate their distinct-ness
             // as much as possible (in and out of subgraphs, backwards through feedbacks,
             // etc.)
-            self.collect_distinct_nodes(&mut is_distinct, &mut redirects, &mut buffer);
+            self.collect_distinct_nodes(&mut is_distinct, &mut redirects);
         }
 
         if !redirects.is_empty() {
@@ -50,14 +51,13 @@ impl Subgraph {
         &self,
         is_distinct: &mut BTreeSet<NodeId>,
         redirects: &mut BTreeMap<NodeId, NodeId>,
-        buffer: &mut DfsSpace<NodeId, HashSet<NodeId>>,
     ) -> bool {
         // TODO: Could reuse toposort vectors
-        let order = toposort(self.edges(), Some(buffer)).expect("cyclic dataflow graph");
+        let order = toposort(self.edges()).expect("cyclic dataflow graph");
 
         let mut changed = false;
         for _ in 0..100 {
-            if !self.collect_distinct_nodes_inner(&order, is_distinct, redirects, buffer) {
+            if !self.collect_distinct_nodes_inner(&order, is_distinct, redirects) {
                 break;
             } else {
                 changed = true;
@@ -72,7 +72,6 @@ impl Subgraph {
         order: &[NodeId],
         is_distinct: &mut BTreeSet<NodeId>,
         redirects: &mut BTreeMap<NodeId, NodeId>,
-        buffer: &mut DfsSpace<NodeId, HashSet<NodeId>>,
     ) -> bool {
         let mut changed = false;
 
@@ -165,10 +164,9 @@ impl Subgraph {
                     }
 
                     // Run distinct propagation within subgraphs
-                    changed |=
-                        subgraph
-                            .subgraph()
-                            .collect_distinct_nodes(is_distinct, redirects, buffer);
+                    changed |= subgraph
+                        .subgraph()
+                        .collect_distinct_nodes(is_distinct, redirects);
 
                     // FIXME: This is unsound since the stream given to a feedback node
                     // could depend on the distinct-ness of the feedback itself, in the
@@ -199,25 +197,30 @@ impl Subgraph {
                 }
 
                 Node::Constant(constant) => {
-                    if constant.consolidated() && !is_distinct.contains(&node_id) {
-                        // If all tuples/rows within the stream have a weight of 1, the stream is
-                        // distinct
-                        let constant_is_distinct = match constant.value().value() {
-                            StreamCollection::Set(set) => {
-                                set.iter().all(|&(_, weight)| weight == 1)
-                            }
-                            StreamCollection::Map(map) => {
-                                map.iter().all(|&(.., weight)| weight == 1)
-                            }
-                        };
-
-                        if constant_is_distinct {
-                            let assert = is_distinct.insert(node_id);
-                            debug_assert!(assert);
-
-                            tracing::trace!("marking constant node {node_id} as distinct");
-                            changed = true;
-                        }
+                    if constant.consolidated()
+                        && !is_distinct.contains(&node_id)
+                        && literal_is_distinct(constant.value())
+                    {
+                        let assert = is_distinct.insert(node_id);
+                        debug_assert!(assert);
+
+                        tracing::trace!("marking constant node {node_id} as distinct");
+                        changed = true;
+                    }
+                }
+
+                // Constant sources only produce their rows once so they're distinct under the
+                // same conditions as constant streams
+                Node::ConstantSource(constant) => {
+                    if constant.consolidated()
+                        && !is_distinct.contains(&node_id)
+                        && literal_is_distinct(constant.value())
+                    {
+                        let assert = is_distinct.insert(node_id);
+                        debug_assert!(assert);
+
+                        tracing::trace!("marking constant source node {node_id} as distinct");
+                        changed = true;
                     }
                 }
 
@@ -229,6 +232,15 @@ impl Subgraph {
     }
 }
 
+/// If all tuples/rows within a consolidated literal have a weight of 1, the
+/// literal is distinct
+fn literal_is_distinct(literal: &StreamLiteral) -> bool {
+    match literal.value() {
+        StreamCollection::Set(set) => set.iter().all(|&(_, weight)| weight == 1),
+        StreamCollection::Map(map) => map.iter().all(|&(.., weight)| weight == 1),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -1,4 +1,5 @@
 mod antijoin_self;
+mod constant;
 mod dedup;
 mod distinct;
 mod shake;
@@ -17,6 +18,7 @@ pub(super) fn optimize_graph(graph: &mut Graph) {
     let graph = graph.graph_mut();
 
     graph.optimize();
+    graph.fold_constant_sources();
     graph.remove_redundant_distinct();
     graph.remove_self_antijoins();
     graph.dedup_nodes();
@@ -9,27 +9,24 @@
 //! So really this isn't a reachability check so much as it's an "produces
 //! outputs" check which has slightly different semantics
 
-use crate::ir::{graph::Subgraph, nodes::Node, GraphExt, NodeId};
-use petgraph::{
-    algo::{toposort, DfsSpace},
-    Direction,
+use crate::ir::{
+    graph::{toposort, Subgraph},
+    nodes::Node,
+    GraphExt, NodeId,
 };
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use petgraph::Direction;
+use std::collections::{BTreeMap, BTreeSet};
 
 impl Subgraph {
     pub(super) fn shake_dead_nodes(&mut self) {
-        let (mut scratch, mut unreachable) = (DfsSpace::default(), Vec::new());
-        self.shake_dead_nodes_inner(&mut scratch, &mut unreachable);
+        let mut unreachable = Vec::new();
+        self.shake_dead_nodes_inner(&mut unreachable);
     }
 
-    fn shake_dead_nodes_inner(
-        &mut self,
-        scratch: &mut DfsSpace<NodeId, HashSet<NodeId>>,
-        unreachable: &mut Vec<NodeId>,
-    ) {
+    fn shake_dead_nodes_inner(&mut self, unreachable: &mut Vec<NodeId>) {
         debug_assert!(unreachable.is_empty());
 
-        let order = toposort(self.edges(), Some(scratch)).unwrap();
+        let order = toposort(self.edges()).unwrap();
 
         // TODO: This should be done iteratively, removing dead nodes within subgraphs
         // as well as unused subgraph inputs/outputs
@@ -38,9 +35,7 @@ impl Subgraph {
 
         for node in self.nodes_mut().values_mut() {
             if let Node::Subgraph(subgraph) = node {
-                subgraph
-                    .subgraph_mut()
-                    .shake_dead_nodes_inner(scratch, unreachable);
+                subgraph.subgraph_mut().shake_dead_nodes_inner(unreachable);
             }
         }
     }
@@ -70,10 +65,14 @@ impl Subgraph {
                 }
             }
 
-            if node
+            // Don't mark empty streams as reachable
+            let is_empty = node
                 .as_constant()
-                // Don't mark empty streams as reachable
-                .map_or(true, |constant| !constant.value().is_empty())
+                .map(|constant| constant.value())
+                .or_else(|| node.as_constant_source().map(|constant| constant.value()))
+                .map_or(false, |value| value.is_empty());
+
+            if !is_empty
                 && (node.is_source()
                     || node.is_source_map()
                     || node.is_delayed_feedback()
@@ -122,6 +121,7 @@ impl Subgraph {
         for &node_id in order.iter().rev() {
             if let Some(node) = self.nodes().get(&node_id) {
                 if node.is_sink()
+                    || node.is_collect_statistics()
                     || self
                         .edges()
                         .edges_directed(node_id, Direction::Outgoing)
@@ -30,8 +30,10 @@ macro_rules! column_type {
             /// Returns the [`NativeType`] that corresponds with the current `ColumnType`,
             /// returning `None` if there's no equivalent `NativeType`.
             ///
-            /// Currently [`Unit`][ColumnType::Unit] is the only type that will return
-            /// `None`, as zero sized types have no runtime representation
+            /// Currently [`Unit`][ColumnType::Unit] and [`Struct`][ColumnType::Struct]
+            /// are the only types that will return `None`, zero sized types have no
+            /// runtime representation and structs are stored inline using their own
+            /// layout
             #[must_use]
             pub const fn native_type(self) -> Option<NativeType> {
                 use NativeType::*;
@@ -95,6 +97,12 @@ column_type! {
     /// A unit value
     Unit = ("unit", return None),
 
+    /// A row of another layout stored inline within the current row
+    Struct = ("struct", return None),
+    /// A variable-length array of rows of another layout, stored as a pointer
+    /// to a length-prefixed buffer of elements
+    Array = ("array", Ptr),
+
     /// A raw pointer value
     Ptr = ("ptr", Ptr),
 }
@@ -144,17 +152,31 @@ impl ColumnType {
     }
 
     /// Returns `true` if the column type requires a non-trivial drop
-    /// operation (currently just [`String`][ColumnType::String])
+    /// operation (strings, arrays and structs)
+    ///
+    /// Structs are conservatively assumed to require dropping since the layout
+    /// they contain isn't known from the column type alone
     #[must_use]
     pub const fn needs_drop(&self) -> bool {
-        matches!(self, Self::String)
+        matches!(self, Self::String | Self::Array | Self::Struct)
     }
 
     /// Returns `true` if the column type requires a non-trivial clone
-    /// operation (currently just [`String`][ColumnType::String])
+    /// operation (strings, arrays and structs)
+    ///
+    /// Structs are conservatively assumed to require non-trivial clones since
+    /// the layout they contain isn't known from the column type alone
     #[must_use]
     pub const fn requires_nontrivial_clone(&self) -> bool {
-        matches!(self, Self::String)
+        matches!(self, Self::String | Self::Array | Self::Struct)
+    }
+
+    /// Returns `true` if the column type refers to another layout
+    /// (either a [`Struct`][ColumnType::Struct] or an
+    /// [`Array`][ColumnType::Array])
+    #[must_use]
+    pub const fn is_nested(&self) -> bool {
+        matches!(self, Self::Struct | Self::Array)
     }
 
     /// Returns `true` if the column type is a zero-sized type
@@ -174,6 +196,7 @@ impl Display for ColumnType {
 pub struct RowLayoutBuilder {
     columns: Vec<ColumnType>,
     nullability: BitVec,
+    nested: Vec<Option<LayoutId>>,
 }
 
 impl RowLayoutBuilder {
@@ -181,6 +204,7 @@ impl RowLayoutBuilder {
         Self {
             columns: Vec::new(),
             nullability: BitVec::EMPTY,
+            nested: Vec::new(),
         }
     }
 
@@ -190,17 +214,53 @@ impl RowLayoutBuilder {
     }
 
     pub fn add_column(&mut self, column_type: ColumnType, nullable: bool) -> &mut Self {
+        debug_assert!(
+            !column_type.is_nested(),
+            "{column_type} columns must be added with their layout",
+        );
+
         self.columns.push(column_type);
         self.nullability.push(nullable);
+        self.nested.push(None);
+        self
+    }
+
+    /// Adds a struct column containing an inline row of the given layout
+    pub fn with_struct_column(mut self, layout: LayoutId, nullable: bool) -> Self {
+        self.add_struct_column(layout, nullable);
+        self
+    }
+
+    /// Adds a struct column containing an inline row of the given layout
+    pub fn add_struct_column(&mut self, layout: LayoutId, nullable: bool) -> &mut Self {
+        self.columns.push(ColumnType::Struct);
+        self.nullability.push(nullable);
+        self.nested.push(Some(layout));
+        self
+    }
+
+    /// Adds an array column with elements of the given layout
+    pub fn with_array_column(mut self, element_layout: LayoutId, nullable: bool) -> Self {
+        self.add_array_column(element_layout, nullable);
+        self
+    }
+
+    /// Adds an array column with elements of the given layout
+    pub fn add_array_column(&mut self, element_layout: LayoutId, nullable: bool) -> &mut Self {
+        self.columns.push(ColumnType::Array);
+        self.nullability.push(nullable);
+        self.nested.push(Some(element_layout));
         self
     }
 
     pub fn build(self) -> RowLayout {
         debug_assert_eq!(self.columns.len(), self.nullability.len());
+        debug_assert_eq!(self.columns.len(), self.nested.len());
 
         RowLayout {
             columns: self.columns,
             nullability: self.nullability,
+            nested: self.nested,
         }
     }
 }
@@ -214,6 +274,10 @@ pub struct RowLayout {
     /// The nullability of each column within the current row, a `true` at index
     /// `n` means that `columns[n]` is nullable
     nullability: BitVec,
+    /// The layout referenced by each column, `Some` for struct columns (the
+    /// layout of the inline row) and array columns (the layout of each
+    /// element) and `None` for all other columns
+    nested: Vec<Option<LayoutId>>,
 }
 
 impl RowLayout {
@@ -252,6 +316,24 @@ impl RowLayout {
         &self.nullability
     }
 
+    /// Returns the layout referenced by the given column, the layout of the
+    /// inline row for struct columns and the layout of each element for array
+    /// columns. Returns `None` for all other columns
+    pub fn nested_layout(&self, column: usize) -> Option<LayoutId> {
+        self.nested[column]
+    }
+
+    /// Returns all layouts referenced by struct and array columns within the
+    /// current row
+    pub fn nested_layouts(&self) -> impl Iterator<Item = LayoutId> + '_ {
+        self.nested.iter().flatten().copied()
+    }
+
+    /// Returns `true` if the current row contains any struct or array columns
+    pub fn has_nested_columns(&self) -> bool {
+        self.nested.iter().any(Option::is_some)
+    }
+
     pub fn is_unit(&self) -> bool {
         self.columns == [ColumnType::Unit] && self.nullability.not_any()
     }
@@ -269,6 +351,7 @@ impl RowLayout {
         Self {
             columns: vec![ColumnType::Unit],
             nullability,
+            nested: vec![None],
         }
     }
 
@@ -281,6 +364,7 @@ impl RowLayout {
         Self {
             columns: vec![ColumnType::I32],
             nullability,
+            nested: vec![None],
         }
     }
 
@@ -291,6 +375,7 @@ impl RowLayout {
         Self {
             columns: vec![ColumnType::Ptr, ColumnType::Ptr],
             nullability,
+            nested: vec![None; 2],
         }
     }
 
@@ -330,16 +415,21 @@ impl RowLayout {
 
 impl Debug for RowLayout {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        struct DebugColumnLayout<'a>(&'a ColumnType, bool);
+        struct DebugColumnLayout<'a>(&'a ColumnType, bool, Option<LayoutId>);
 
         impl Debug for DebugColumnLayout<'_> {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                let Self(row, nullable) = *self;
+                let Self(row, nullable, nested) = *self;
                 if nullable {
                     f.write_char('?')?;
                 }
 
-                f.write_str(row.to_str())
+                f.write_str(row.to_str())?;
+                if let Some(nested) = nested {
+                    write!(f, "<{nested}>")?;
+                }
+
+                Ok(())
             }
         }
 
@@ -348,7 +438,10 @@ impl Debug for RowLayout {
                 self.columns
                     .iter()
                     .zip(self.nullability.iter().by_vals())
-                    .map(|(column, nullable)| DebugColumnLayout(column, nullable)),
+                    .zip(&self.nested)
+                    .map(|((column, nullable), &nested)| {
+                        DebugColumnLayout(column, nullable, nested)
+                    }),
             )
             .finish()
     }
@@ -380,7 +473,12 @@ impl From<RowLayout> for SerRowLayout {
         Self {
             columns: layout
                 .iter()
-                .map(|(ty, nullable)| SerColumnLayout { ty, nullable })
+                .zip(&layout.nested)
+                .map(|((ty, nullable), &layout)| SerColumnLayout {
+                    ty,
+                    nullable,
+                    layout,
+                })
                 .collect(),
         }
     }
@@ -390,15 +488,23 @@ impl From<SerRowLayout> for RowLayout {
     fn from(layout: SerRowLayout) -> Self {
         let mut columns = Vec::with_capacity(layout.columns.len());
         let mut nullability = BitVec::with_capacity(layout.columns.len());
-
-        for SerColumnLayout { ty, nullable } in layout.columns {
+        let mut nested = Vec::with_capacity(layout.columns.len());
+
+        for SerColumnLayout {
+            ty,
+            nullable,
+            layout,
+        } in layout.columns
+        {
             columns.push(ty);
             nullability.push(nullable);
+            nested.push(layout);
         }
 
         Self {
             columns,
             nullability,
+            nested,
         }
     }
 }
@@ -407,6 +513,10 @@ impl From<SerRowLayout> for RowLayout {
 struct SerColumnLayout {
     ty: ColumnType,
     nullable: bool,
+    /// The layout of the inline row for struct columns or the layout of
+    /// each element for array columns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    layout: Option<LayoutId>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,139 @@
+//! Signatures of user defined functions (udfs)
+//!
+//! Udfs are plain `extern "C"` functions registered with the code generator
+//! and called by [`CallUdf`] expressions. Arguments and returns use the
+//! following C ABI:
+//!
+//! - Scalars are passed and returned as their native types, bools are passed
+//!   as a `u8` that's either zero or one
+//! - Strings are passed as borrowed [`ThinStrRef`]s which the udf must not free
+//!   and are returned as owned [`ThinStr`]s
+//! - Nullable arguments are passed as their value followed by a `bool` that's
+//!   `true` when the argument is null, the value of a null argument is
+//!   unspecified
+//! - Udfs with nullable returns take a trailing `*mut T` that they write their
+//!   result to and return a `bool` that's `true` when the result is null,
+//!   nothing must be written to the pointer when returning null
+//!
+//! So a udf taking a nullable `i32` and a string and returning a nullable
+//! string has the signature
+//! `extern "C" fn(i32, bool, ThinStrRef, *mut ThinStr) -> bool`
+//!
+//! [`CallUdf`]: crate::ir::exprs::CallUdf
+//! [`ThinStrRef`]: crate::ThinStrRef
+//! [`ThinStr`]: crate::ThinStr
+
+use crate::ir::ColumnType;
+use std::fmt::{self, Display};
+
+/// The type of a udf's argument or return value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UdfType {
+    ty: ColumnType,
+    nullable: bool,
+}
+
+impl UdfType {
+    pub const fn new(ty: ColumnType, nullable: bool) -> Self {
+        Self { ty, nullable }
+    }
+
+    /// Creates a non-nullable type
+    pub const fn non_null(ty: ColumnType) -> Self {
+        Self::new(ty, false)
+    }
+
+    /// Creates a nullable type
+    pub const fn nullable(ty: ColumnType) -> Self {
+        Self::new(ty, true)
+    }
+
+    pub const fn ty(&self) -> ColumnType {
+        self.ty
+    }
+
+    pub const fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+}
+
+impl Display for UdfType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.nullable {
+            write!(f, "{}?", self.ty)
+        } else {
+            Display::fmt(&self.ty, f)
+        }
+    }
+}
+
+/// The signature of a udf
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UdfSignature {
+    args: Vec<UdfType>,
+    ret: UdfType,
+}
+
+impl UdfSignature {
+    /// Creates a new udf signature
+    ///
+    /// # Panics
+    ///
+    /// Panics if any argument is a unit, pointer, struct or array type or if
+    /// the return type is a pointer, struct or array type or a nullable unit
+    pub fn new(args: Vec<UdfType>, ret: UdfType) -> Self {
+        for arg in &args {
+            assert!(
+                is_udf_type(arg.ty()) && !arg.ty().is_unit(),
+                "udfs can't take {} arguments",
+                arg.ty(),
+            );
+        }
+        assert!(
+            is_udf_type(ret.ty()) && !(ret.ty().is_unit() && ret.is_nullable()),
+            "udfs can't return {ret}",
+        );
+
+        Self { args, ret }
+    }
+
+    pub fn args(&self) -> &[UdfType] {
+        &self.args
+    }
+
+    pub const fn ret(&self) -> UdfType {
+        self.ret
+    }
+
+    /// Returns the number of argument expressions a call to this udf takes,
+    /// nullable arguments take two expressions
+    pub fn arg_exprs(&self) -> usize {
+        self.args
+            .iter()
+            .map(|arg| 1 + arg.is_nullable() as usize)
+            .sum()
+    }
+}
+
+impl Display for UdfSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("fn(")?;
+        for (idx, arg) in self.args.iter().enumerate() {
+            Display::fmt(arg, f)?;
+            if idx + 1 != self.args.len() {
+                f.write_str(", ")?;
+            }
+        }
+        f.write_str(")")?;
+
+        if !self.ret.ty().is_unit() {
+            write!(f, " -> {}", self.ret)?;
+        }
+
+        Ok(())
+    }
+}
+
+const fn is_udf_type(ty: ColumnType) -> bool {
+    !matches!(ty, ColumnType::Ptr | ColumnType::Struct | ColumnType::Array,)
+}
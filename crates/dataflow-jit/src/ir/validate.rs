@@ -1,8 +1,11 @@
 use crate::ir::{
     exprs::ArgType,
-    exprs::{Call, Select},
+    exprs::{Call, CallUdf, Select},
     graph::GraphExt,
-    nodes::{DataflowNode, Node, StreamKind, StreamLayout},
+    literal::{NullableConstant, RowLiteral, StreamCollection, StreamLiteral},
+    monotonicity,
+    nodes::{DataflowNode, Node, PartitionedRollingFold, StreamColumn, StreamKind, StreamLayout},
+    udf::UdfSignature,
     BinaryOp, BinaryOpKind, BlockId, Cast, ColumnType, Constant, Expr, ExprId, Function, Graph,
     InputFlags, IsNull, LayoutId, Load, NodeId, NullRow, RValue, RowLayoutCache, SetNull, Store,
     UnaryOpKind, UninitRow,
@@ -38,6 +41,12 @@ impl Validator {
         }
     }
 
+    /// Sets the udfs that functions are allowed to call
+    pub fn with_udfs(mut self, udfs: BTreeMap<String, UdfSignature>) -> Self {
+        self.function_validator.udfs = udfs;
+        self
+    }
+
     pub fn clear(&mut self) {
         self.nodes.clear();
         self.node_inputs.clear();
@@ -87,6 +96,10 @@ impl Validator {
                     self.node_inputs.insert(node_id, vec![sink.input()]);
                 }
 
+                Node::CollectStatistics(collect) => {
+                    self.node_inputs.insert(node_id, vec![collect.input()]);
+                }
+
                 Node::Source(source) => {
                     self.node_outputs
                         .insert(node_id, StreamLayout::Set(source.layout()));
@@ -133,7 +146,10 @@ impl Validator {
                 }
 
                 Node::Constant(constant) => {
-                    // TODO: Verify that the layout of the row matches its literal value
+                    self.node_outputs.insert(node_id, constant.layout());
+                }
+
+                Node::ConstantSource(constant) => {
                     self.node_outputs.insert(node_id, constant.layout());
                 }
 
@@ -141,6 +157,16 @@ impl Validator {
                     self.node_inputs.insert(node_id, vec![distinct.input()]);
                 }
 
+                Node::PartitionedRollingFold(fold) => {
+                    self.node_inputs.insert(node_id, vec![fold.input()]);
+
+                    let input_layout = self.get_expected_input(node_id, fold.input());
+                    self.node_outputs.insert(
+                        node_id,
+                        StreamLayout::Map(input_layout.key_layout(), fold.output_layout()),
+                    );
+                }
+
                 _ => todo!(),
             }
         }
@@ -190,6 +216,16 @@ impl Validator {
                     self.function_validator.validate_function(join.join_fn())?;
                 }
 
+                Node::Constant(constant) => self.validate_literal(node_id, constant.value())?,
+
+                Node::ConstantSource(constant) => {
+                    self.validate_literal(node_id, constant.value())?;
+                }
+
+                Node::PartitionedRollingFold(fold) => {
+                    self.validate_watermark(graph, node_id, fold)?
+                }
+
                 _ => {}
             }
         }
@@ -197,6 +233,125 @@ impl Validator {
         Ok(())
     }
 
+    /// Checks that the timestamps a rolling fold uses as its watermark are
+    /// monotone, non-monotone timestamps make the fold silently produce
+    /// incorrect results
+    fn validate_watermark(
+        &self,
+        graph: &Graph,
+        node: NodeId,
+        fold: &PartitionedRollingFold,
+    ) -> ValidationResult {
+        if fold.unsafe_assume_monotone() {
+            return Ok(());
+        }
+
+        // The fold's timestamps are the first column of its input's values
+        monotonicity::check_monotone(graph, fold.input(), StreamColumn::Value(0)).map_err(
+            |non_monotone| ValidationError::NonMonotoneWatermark {
+                node,
+                input: fold.input(),
+                breaking_node: non_monotone.node,
+                breaking_expr: non_monotone.expr,
+                reason: non_monotone.reason,
+            },
+        )
+    }
+
+    /// Checks that every row of `literal` matches the literal's layout
+    fn validate_literal(&self, node: NodeId, literal: &StreamLiteral) -> ValidationResult {
+        let invalid = |reason| ValidationError::InvalidLiteral { node, reason };
+
+        match (literal.value(), literal.layout()) {
+            (StreamCollection::Set(set), StreamLayout::Set(key_layout)) => {
+                for (row, (key, _)) in set.iter().enumerate() {
+                    self.validate_row_literal(row, key, key_layout)
+                        .map_err(invalid)?;
+                }
+            }
+
+            (StreamCollection::Map(map), StreamLayout::Map(key_layout, value_layout)) => {
+                for (row, (key, value, _)) in map.iter().enumerate() {
+                    self.validate_row_literal(row, key, key_layout)
+                        .map_err(invalid)?;
+                    self.validate_row_literal(row, value, value_layout)
+                        .map_err(invalid)?;
+                }
+            }
+
+            (_, layout) => {
+                return Err(invalid(format!(
+                    "expected a {:?} collection to match the literal's layout",
+                    layout.kind(),
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_row_literal(
+        &self,
+        row: usize,
+        literal: &RowLiteral,
+        layout_id: LayoutId,
+    ) -> Result<(), String> {
+        let layout = self.function_validator.layout_cache.get(layout_id);
+        if literal.len() != layout.len() {
+            return Err(format!(
+                "row {row} has {} columns but its layout {layout} has {}",
+                literal.len(),
+                layout.len(),
+            ));
+        }
+
+        for (column, value) in literal.rows().iter().enumerate() {
+            let (constant, nullable) = match value {
+                NullableConstant::NonNull(constant) => (Some(constant), false),
+                NullableConstant::Nullable(constant) => (constant.as_ref(), true),
+            };
+
+            if nullable != layout.column_nullable(column) {
+                return Err(format!(
+                    "column {column} of row {row} is {} but it's {} within the layout {layout}",
+                    if nullable { "nullable" } else { "non-null" },
+                    if nullable { "non-null" } else { "nullable" },
+                ));
+            }
+
+            if let Some(constant) = constant {
+                let expected = layout.column_type(column);
+                if constant.column_type() != expected {
+                    return Err(format!(
+                        "column {column} of row {row} is a {} but the layout {layout} expects a {expected}",
+                        constant.column_type(),
+                    ));
+                }
+
+                match constant {
+                    Constant::Struct(nested) => {
+                        self.validate_row_literal(
+                            row,
+                            nested,
+                            layout.nested_layout(column).unwrap(),
+                        )?;
+                    }
+
+                    Constant::Array(elements) => {
+                        let element_layout = layout.nested_layout(column).unwrap();
+                        for element in elements {
+                            self.validate_row_literal(row, element, element_layout)?;
+                        }
+                    }
+
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     #[track_caller]
     fn get_expected_input(&self, node: NodeId, input: NodeId) -> StreamLayout {
         if let Some(&input_layout) = self.node_outputs.get(&input) {
@@ -218,6 +373,8 @@ pub struct FunctionValidator {
     // TODO: Block parameters once those are implemented
     // TODO: Control flow validation
     layout_cache: RowLayoutCache,
+    /// The signatures of all udfs that can be called
+    udfs: BTreeMap<String, UdfSignature>,
 }
 
 impl FunctionValidator {
@@ -228,6 +385,7 @@ impl FunctionValidator {
             expr_row_mutability: BTreeMap::new(),
             blocks: BTreeSet::new(),
             layout_cache,
+            udfs: BTreeMap::new(),
         }
     }
 
@@ -287,6 +445,7 @@ impl FunctionValidator {
             for &(expr_id, ref expr) in block.body() {
                 match expr {
                     Expr::Call(call) => self.call(expr_id, call)?,
+                    Expr::CallUdf(call) => self.call_udf(expr_id, call)?,
                     Expr::Cast(cast) => self.cast(expr_id, cast)?,
                     Expr::Constant(constant) => self.constant(expr_id, constant)?,
                     Expr::Select(select) => self.select(expr_id, select)?,
@@ -407,6 +566,10 @@ impl FunctionValidator {
     }
 
     fn constant(&mut self, expr_id: ExprId, constant: &Constant) -> ValidationResult {
+        if constant.column_type().is_nested() {
+            return Err(ValidationError::NestedConstant { expr: expr_id });
+        }
+
         self.add_column_expr(expr_id, constant.column_type());
         Ok(())
     }
@@ -452,7 +615,24 @@ impl FunctionValidator {
             }
         }
 
-        self.add_column_expr(expr_id, load.column_type());
+        // Loading a struct column produces a pointer to the nested row, which is
+        // immutable since it's borrowed from the source row
+        if load.column_type().is_struct() {
+            let nested = self
+                .layout_cache
+                .get(source_layout)
+                .nested_layout(load.column())
+                .unwrap();
+
+            let prev = self.expr_types.insert(expr_id, Err(nested));
+            debug_assert!(
+                prev.is_none(),
+                "all duplicate expressions should be caught earlier on in validation",
+            );
+            self.expr_row_mutability.insert(expr_id, false);
+        } else {
+            self.add_column_expr(expr_id, load.column_type());
+        }
 
         Ok(())
     }
@@ -497,6 +677,13 @@ impl FunctionValidator {
                         target: store.target(),
                         target_type,
                     });
+                } else if target_type.is_nested() {
+                    return Err(ValidationError::StoreToNestedColumn {
+                        store: expr_id,
+                        column: store.column(),
+                        target: store.target(),
+                        target_type,
+                    });
                 } else if target_type != value_type {
                     todo!("invalid store value type in store {expr_id}, tried to store value of type {value_type} to column of type {target_type}")
                 }
@@ -979,6 +1166,100 @@ impl FunctionValidator {
         Ok(())
     }
 
+    fn call_udf(&mut self, expr_id: ExprId, call: &CallUdf) -> ValidationResult {
+        let signature =
+            self.udfs
+                .get(call.name())
+                .cloned()
+                .ok_or_else(|| ValidationError::UnknownUdf {
+                    expr_id,
+                    udf: call.name().to_owned(),
+                    known_udfs: if self.udfs.is_empty() {
+                        "no udfs are registered".to_owned()
+                    } else {
+                        format!(
+                            "registered udfs are {}",
+                            self.udfs
+                                .keys()
+                                .map(|name| format!("`{name}`"))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        )
+                    },
+                })?;
+
+        if call.args().len() != signature.arg_exprs() {
+            return Err(ValidationError::IncorrectUdfArgLen {
+                expr_id,
+                udf: call.name().to_owned(),
+                signature: signature.to_string(),
+                expected_args: signature.arg_exprs(),
+                args: call.args().len(),
+            });
+        }
+
+        // Nullable arguments are followed by their null flag
+        let mut args = call.args().iter();
+        for (idx, arg) in signature.args().iter().enumerate() {
+            let value = *args.next().unwrap();
+            let value_ty = self.expr_type(value)?;
+            if value_ty != Ok(arg.ty()) {
+                return Err(ValidationError::MismatchedUdfArg {
+                    expr_id,
+                    udf: call.name().to_owned(),
+                    arg: idx,
+                    expected: arg.ty(),
+                    expr: value,
+                });
+            }
+
+            if arg.is_nullable() {
+                let is_null = *args.next().unwrap();
+                if self.expr_type(is_null)? != Ok(ColumnType::Bool) {
+                    return Err(ValidationError::MismatchedUdfArg {
+                        expr_id,
+                        udf: call.name().to_owned(),
+                        arg: idx,
+                        expected: ColumnType::Bool,
+                        expr: is_null,
+                    });
+                }
+            }
+        }
+
+        let ret = signature.ret();
+        if ret.is_nullable() {
+            let is_valid_layout = call.ret_layout().map_or(false, |ret_layout| {
+                let layout = self.layout_cache.get(ret_layout);
+                layout.len() == 1 && layout.column_type(0) == ret.ty() && layout.column_nullable(0)
+            });
+            if !is_valid_layout {
+                return Err(ValidationError::InvalidUdfReturnLayout {
+                    expr_id,
+                    udf: call.name().to_owned(),
+                    ret: ret.ty(),
+                });
+            }
+
+            let prev = self
+                .expr_types
+                .insert(expr_id, Err(call.ret_layout().unwrap()));
+            debug_assert!(prev.is_none());
+            self.expr_row_mutability.insert(expr_id, true);
+        } else {
+            if call.ret_layout().is_some() {
+                return Err(ValidationError::UnexpectedUdfReturnLayout {
+                    expr_id,
+                    udf: call.name().to_owned(),
+                });
+            }
+
+            self.add_column_expr(expr_id, ret.ty());
+        }
+
+        Ok(())
+    }
+
     fn binop(&mut self, expr_id: ExprId, binop: &BinaryOp) -> ValidationResult {
         let lhs_ty = self.expr_types.get(&binop.lhs()).unwrap().unwrap();
         let rhs_ty = self.expr_types.get(&binop.rhs()).unwrap().unwrap();
@@ -1130,6 +1411,21 @@ pub enum ValidationError {
     )]
     StoreWithRow { store: ExprId, value: ExprId },
 
+    #[display(
+        fmt = "store {store} attempted to store to column {column} of {target} which is a {target_type} column, struct and array columns can't be stored to"
+    )]
+    StoreToNestedColumn {
+        store: ExprId,
+        column: usize,
+        target: ExprId,
+        target_type: ColumnType,
+    },
+
+    #[display(
+        fmt = "the constant {expr} is a struct or array, which can only be used within row literals"
+    )]
+    NestedConstant { expr: ExprId },
+
     #[display(
         fmt = "join {join} produces a set but its value layout {value_layout} was {layout}, not {{ unit }}"
     )]
@@ -1153,6 +1449,67 @@ pub enum ValidationError {
         expected_args: usize,
         args: usize,
     },
+
+    #[display(
+        fmt = "unknown udf call in expression {expr_id}: `{udf}` does not exist, {known_udfs}"
+    )]
+    UnknownUdf {
+        expr_id: ExprId,
+        udf: String,
+        known_udfs: String,
+    },
+
+    #[display(
+        fmt = "incorrect number of arguments to the udf `{udf}` with signature `{signature}` in {expr_id}, expected {expected_args} arguments but got {args}"
+    )]
+    IncorrectUdfArgLen {
+        expr_id: ExprId,
+        udf: String,
+        signature: String,
+        expected_args: usize,
+        args: usize,
+    },
+
+    #[display(
+        fmt = "mismatched argument {arg} to the udf `{udf}` in {expr_id}: expected a {expected} but {expr} isn't one"
+    )]
+    MismatchedUdfArg {
+        expr_id: ExprId,
+        udf: String,
+        arg: usize,
+        expected: ColumnType,
+        expr: ExprId,
+    },
+
+    #[display(
+        fmt = "the udf `{udf}` called in {expr_id} returns a nullable {ret} and must be given a return layout with a single nullable {ret} column"
+    )]
+    InvalidUdfReturnLayout {
+        expr_id: ExprId,
+        udf: String,
+        ret: ColumnType,
+    },
+
+    #[display(
+        fmt = "the udf `{udf}` called in {expr_id} returns a non-null value and can't be given a return layout"
+    )]
+    UnexpectedUdfReturnLayout { expr_id: ExprId, udf: String },
+
+    #[display(fmt = "invalid literal in {node}: {reason}")]
+    InvalidLiteral { node: NodeId, reason: String },
+
+    #[display(
+        fmt = "the timestamps of {node}'s input {input} can't be proven to be monotone, monotonicity is lost at {breaking_node}: {reason} (set `unsafe_assume_monotone` on {node} to skip this check)"
+    )]
+    NonMonotoneWatermark {
+        node: NodeId,
+        input: NodeId,
+        breaking_node: NodeId,
+        /// The expression within `breaking_node`'s function that loses
+        /// monotonicity, if there is one
+        breaking_expr: Option<ExprId>,
+        reason: String,
+    },
 }
 
 impl Error for ValidationError {}
@@ -1,9 +1,9 @@
 use crate::ir::{
     nodes::{
-        Antijoin, ConstantStream, DelayedFeedback, Delta0, Differentiate, Distinct, Export,
-        ExportedNode, Filter, FilterMap, FlatMap, Fold, IndexWith, Integrate, JoinCore, Map, Max,
-        Min, Minus, MonotonicJoin, Neg, Node, PartitionedRollingFold, Sink, Source, SourceMap,
-        Subgraph, Sum,
+        Antijoin, CollectStatistics, ConstantSource, ConstantStream, DelayedFeedback, Delta0,
+        Differentiate, Distinct, Export, ExportedNode, Filter, FilterMap, FlatMap, Fold, IndexWith,
+        Integrate, JoinCore, Map, Max, Min, Minus, MonotonicJoin, Neg, Node,
+        PartitionedRollingFold, Sink, Source, SourceMap, Subgraph, Sum,
     },
     GraphExt, NodeId,
 };
@@ -34,6 +34,7 @@ pub trait NodeVisitor {
     fn visit_exported_node(&mut self, _node_id: NodeId, _exported_node: &ExportedNode) {}
     fn visit_monotonic_join(&mut self, _node_id: NodeId, _monotonic_join: &MonotonicJoin) {}
     fn visit_constant(&mut self, _node_id: NodeId, _constant: &ConstantStream) {}
+    fn visit_constant_source(&mut self, _node_id: NodeId, _constant_source: &ConstantSource) {}
     fn visit_partitioned_rolling_fold(
         &mut self,
         _node_id: NodeId,
@@ -42,6 +43,12 @@ pub trait NodeVisitor {
     }
     fn visit_flat_map(&mut self, _node_id: NodeId, _flat_map: &FlatMap) {}
     fn visit_antijoin(&mut self, _node_id: NodeId, _antijoin: &Antijoin) {}
+    fn visit_collect_statistics(
+        &mut self,
+        _node_id: NodeId,
+        _collect_statistics: &CollectStatistics,
+    ) {
+    }
 
     fn visit_subgraph(&mut self, node_id: NodeId, subgraph: &Subgraph) {
         self.enter_subgraph(node_id, subgraph);
@@ -83,6 +90,7 @@ pub trait MutNodeVisitor {
     fn visit_exported_node(&mut self, _node_id: NodeId, _exported_node: &mut ExportedNode) {}
     fn visit_monotonic_join(&mut self, _node_id: NodeId, _monotonic_join: &mut MonotonicJoin) {}
     fn visit_constant(&mut self, _node_id: NodeId, _constant: &mut ConstantStream) {}
+    fn visit_constant_source(&mut self, _node_id: NodeId, _constant_source: &mut ConstantSource) {}
     fn visit_partitioned_rolling_fold(
         &mut self,
         _node_id: NodeId,
@@ -91,6 +99,12 @@ pub trait MutNodeVisitor {
     }
     fn visit_flat_map(&mut self, _node_id: NodeId, _flat_map: &mut FlatMap) {}
     fn visit_antijoin(&mut self, _node_id: NodeId, _antijoin: &mut Antijoin) {}
+    fn visit_collect_statistics(
+        &mut self,
+        _node_id: NodeId,
+        _collect_statistics: &mut CollectStatistics,
+    ) {
+    }
 
     fn visit_subgraph(&mut self, node_id: NodeId, subgraph: &mut Subgraph) {
         self.enter_subgraph(node_id, subgraph);
@@ -141,11 +155,17 @@ impl Node {
                 visitor.visit_monotonic_join(node_id, monotonic_join);
             }
             Self::Constant(constant) => visitor.visit_constant(node_id, constant),
+            Self::ConstantSource(constant_source) => {
+                visitor.visit_constant_source(node_id, constant_source);
+            }
             Self::PartitionedRollingFold(partitioned_rolling_fold) => {
                 visitor.visit_partitioned_rolling_fold(node_id, partitioned_rolling_fold);
             }
             Self::FlatMap(flat_map) => visitor.visit_flat_map(node_id, flat_map),
             Self::Antijoin(antijoin) => visitor.visit_antijoin(node_id, antijoin),
+            Self::CollectStatistics(collect_statistics) => {
+                visitor.visit_collect_statistics(node_id, collect_statistics);
+            }
         }
     }
 
@@ -186,11 +206,17 @@ impl Node {
                 visitor.visit_monotonic_join(node_id, monotonic_join);
             }
             Self::Constant(constant) => visitor.visit_constant(node_id, constant),
+            Self::ConstantSource(constant_source) => {
+                visitor.visit_constant_source(node_id, constant_source);
+            }
             Self::PartitionedRollingFold(partitioned_rolling_fold) => {
                 visitor.visit_partitioned_rolling_fold(node_id, partitioned_rolling_fold);
             }
             Self::FlatMap(flat_map) => visitor.visit_flat_map(node_id, flat_map),
             Self::Antijoin(antijoin) => visitor.visit_antijoin(node_id, antijoin),
+            Self::CollectStatistics(collect_statistics) => {
+                visitor.visit_collect_statistics(node_id, collect_statistics);
+            }
         }
     }
 }
@@ -1,5 +1,6 @@
 pub mod codegen;
 pub mod dataflow;
+pub mod interpreter;
 pub mod ir;
 pub mod row;
 pub mod sql_graph;
@@ -7,5 +8,7 @@ pub mod sql_graph;
 mod facade;
 mod thin_str;
 mod utils;
+mod verify;
 
-pub use thin_str::ThinStr;
+pub use thin_str::{ThinStr, ThinStrRef};
+pub use verify::{verify, verify_with_config, Mismatch, RowDiff, VerifyError};
@@ -1,19 +1,22 @@
 use clap::Parser;
 use dataflow_jit::{
-    codegen::CodegenConfig,
-    dataflow::CompiledDataflow,
-    ir::{GraphExt, Validator},
+    codegen::{CodegenConfig, CodegenTarget},
+    dataflow::{CompiledDataflow, DataflowStatistics},
+    ir::{literal::StreamCollection, Graph, GraphExt, NodeId, Validator},
     sql_graph::SqlGraph,
+    VerifyError,
 };
-use dbsp::Runtime;
+use dbsp::{DBSPHandle, Runtime};
 use jsonschema::paths::PathChunk;
 use serde_json::Value;
 use std::{
-    fs::File,
+    collections::BTreeMap,
+    fs::{self, File},
     io::{self, Read},
     path::{Path, PathBuf},
     process::ExitCode,
 };
+use target_lexicon::Triple;
 
 fn main() -> ExitCode {
     {
@@ -126,27 +129,149 @@ fn main() -> ExitCode {
 
     // TODO: Validate the given graph once validation works
 
+    let config = CodegenConfig::release().with_target(target_cpu(&args.target_cpu));
+    if let Err(error) = config.target.target_isa() {
+        eprintln!("invalid target cpu {:?}: {error}", args.target_cpu);
+        return ExitCode::FAILURE;
+    }
+
     println!("Unoptimized: {graph:#?}");
-    if let Err(error) = Validator::new(graph.layout_cache().clone()).validate_graph(&graph) {
+    if let Err(error) = Validator::new(graph.layout_cache().clone())
+        .with_udfs(config.udf_signatures())
+        .validate_graph(&graph)
+    {
         eprintln!("validation error: {error}");
         return ExitCode::FAILURE;
     }
     graph.optimize();
 
-    let (dataflow, jit_handle, _layout_cache) =
-        CompiledDataflow::new(&graph, CodegenConfig::release());
+    if let Some(inputs_path) = &args.verify {
+        return verify(&graph, inputs_path, config);
+    }
+
+    let (dataflow, jit_handle, _layout_cache) = CompiledDataflow::new(&graph, config);
+    let statistics = dataflow.statistics();
+
+    let mut runtime = match Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)) {
+        Ok((runtime, _)) => runtime,
+        Err(error) => {
+            eprintln!("failed to start the runtime: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    let (runtime, _) =
-        Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)).unwrap();
-    if let Err(_error) = runtime.kill() {
-        eprintln!("failed to kill runtime");
+    if let Some(profile_path) = &args.profile_cpu {
+        if let Err(error) = profile_cpu(&mut runtime, profile_path) {
+            eprintln!(
+                "failed to write cpu profile to {}: {error}",
+                profile_path.display(),
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(statistics_path) = &args.statistics {
+        if let Err(error) = write_statistics(&mut runtime, &statistics, statistics_path) {
+            eprintln!(
+                "failed to write statistics to {}: {error}",
+                statistics_path.display(),
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Err(error) = runtime.kill() {
+        eprintln!("failed to kill runtime: {error}");
         return ExitCode::FAILURE;
     }
-    unsafe { jit_handle.free_memory() }
+    jit_handle.free_memory();
 
     ExitCode::SUCCESS
 }
 
+/// Evaluates the circuit for a single step with the cpu profiler enabled and
+/// writes the resulting profile to `path` in the folded stacks format.
+fn profile_cpu(runtime: &mut DBSPHandle, path: &Path) -> Result<(), dbsp::Error> {
+    runtime.enable_cpu_profiler()?;
+    runtime.step()?;
+
+    let profile = runtime.cpu_profile()?;
+    fs::write(path, profile.to_folded())?;
+
+    Ok(())
+}
+
+/// Evaluates the circuit for a single step and writes the statistics collected
+/// by the graph's statistics nodes to `path` as json
+fn write_statistics(
+    runtime: &mut DBSPHandle,
+    statistics: &DataflowStatistics,
+    path: &Path,
+) -> Result<(), dbsp::Error> {
+    runtime.step()?;
+
+    let statistics =
+        serde_json::to_string_pretty(&statistics.snapshot()).map_err(io::Error::from)?;
+    fs::write(path, statistics)?;
+
+    Ok(())
+}
+
+/// Returns the codegen target for the given `--target-cpu`, any cpu other than
+/// `native` or `baseline` is treated as a cranelift cpu preset or isa feature
+/// for the host's architecture
+fn target_cpu(cpu: &str) -> CodegenTarget {
+    match cpu {
+        "native" => CodegenTarget::Native,
+        "baseline" => CodegenTarget::Baseline,
+        cpu => CodegenTarget::Custom {
+            triple: Triple::host(),
+            features: vec![cpu.to_owned()],
+        },
+    }
+}
+
+/// Checks the compiled graph against the interpreter using the per-step
+/// inputs within `path`, reporting the first sink output that differs
+fn verify(graph: &Graph, path: &Path, config: CodegenConfig) -> ExitCode {
+    let inputs = match fs::read_to_string(path) {
+        Ok(inputs) => inputs,
+        Err(error) => {
+            eprintln!("failed to read {}: {error}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let inputs: Vec<BTreeMap<NodeId, StreamCollection>> = match serde_json::from_str(&inputs) {
+        Ok(inputs) => inputs,
+        Err(error) => {
+            eprintln!("failed to parse inputs from {}: {error}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match dataflow_jit::verify_with_config(graph, &inputs, config) {
+        Ok(()) => {
+            println!(
+                "compiled dataflow matched the interpreter over {} step{}",
+                inputs.len(),
+                if inputs.len() == 1 { "" } else { "s" },
+            );
+            ExitCode::SUCCESS
+        }
+
+        Err(VerifyError::Mismatch(mismatch)) => {
+            eprint!("compiled dataflow differed from the interpreter: {mismatch}");
+            ExitCode::FAILURE
+        }
+
+        Err(error) => {
+            eprintln!("failed to verify dataflow: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
 #[derive(Parser)]
 struct Args {
     /// The file to parse json from, if `-` is passed then stdin will be read
@@ -155,4 +280,23 @@ struct Args {
     /// Print the json schema of the dataflow graph
     #[clap(long)]
     pub print_schema: bool,
+    /// Run the dataflow for a single step and write its cpu profile to the
+    /// given file in the folded stacks format used by flamegraph tools
+    #[clap(long, value_name = "OUT.folded")]
+    pub profile_cpu: Option<PathBuf>,
+    /// Check the compiled dataflow against the interpreter, the given json
+    /// file should contain a list of steps each mapping source nodes to the
+    /// collection they're given on that step
+    #[clap(long, value_name = "INPUTS.json")]
+    pub verify: Option<PathBuf>,
+    /// Run the dataflow for a single step and write the statistics collected
+    /// by its statistics nodes to the given json file
+    #[clap(long, value_name = "OUT.json")]
+    pub statistics: Option<PathBuf>,
+    /// The cpu to generate code for, either `native` for the current machine,
+    /// `baseline` for a conservative subset of the current architecture that
+    /// runs on any reasonably modern machine or a cranelift cpu preset such as
+    /// `x86-64-v3` or `haswell`
+    #[clap(long, value_name = "CPU", default_value = "native")]
+    pub target_cpu: String,
 }
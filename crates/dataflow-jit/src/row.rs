@@ -81,47 +81,8 @@ impl UninitRow {
     // `layout` argument TODO: Make sure that `layout` corresponds to the
     // current row's layout
     pub fn set_column_null(&mut self, column: usize, layout: &NativeLayout, null: bool) {
-        let (ty, bit_offset, bit) = layout.nullability_of(column);
-
-        let bitset = unsafe { self.as_mut_ptr().add(bit_offset as usize) };
-        debug_assert_eq!(bitset as usize % ty.align() as usize, 0);
-
-        let value = if layout.bitset_occupants(column) == 1 {
-            // If there's only one occupant in the bitset we can set it directly
-            null as u64
-
-        // If there's more than one occupant in the bitset we need to load,
-        // set/unset the bit and then store it
-        } else {
-            // Load the bitset's current value
-            let mut mask = unsafe {
-                match ty {
-                    BitSetType::U8 => bitset.read() as u64,
-                    BitSetType::U16 => bitset.cast::<u16>().read() as u64,
-                    BitSetType::U32 => bitset.cast::<u32>().read() as u64,
-                    BitSetType::U64 => bitset.cast::<u64>().read(),
-                }
-            };
-
-            // Set or unset the bit
-            if null {
-                mask |= 1 << bit;
-            } else {
-                mask &= !(1 << bit);
-            }
-
-            mask
-        };
-
-        // Store the modified bitset
-        unsafe {
-            match ty {
-                BitSetType::U8 => bitset.write(value as u8),
-                BitSetType::U16 => bitset.cast::<u16>().write(value as u16),
-                BitSetType::U32 => bitset.cast::<u32>().write(value as u32),
-                BitSetType::U64 => bitset.cast::<u64>().write(value),
-            }
-        }
+        // Safety: The row was allocated with the current layout
+        unsafe { set_column_null(self.as_mut_ptr(), column, layout, null) }
     }
 
     /// Returns `true` if the given column is null
@@ -137,25 +98,7 @@ impl UninitRow {
     // `layout` argument TODO: Make sure that `layout` corresponds to the
     // current row's layout
     pub unsafe fn column_is_null(&self, column: usize, layout: &NativeLayout) -> bool {
-        let (ty, bit_offset, bit) = layout.nullability_of(column);
-
-        let bitset = unsafe { self.as_ptr().add(bit_offset as usize) };
-        debug_assert_eq!(bitset as usize % ty.align() as usize, 0);
-
-        let value = unsafe {
-            match ty {
-                BitSetType::U8 => bitset.read() as u64,
-                BitSetType::U16 => bitset.cast::<u16>().read() as u64,
-                BitSetType::U32 => bitset.cast::<u32>().read() as u64,
-                BitSetType::U64 => bitset.cast::<u64>().read(),
-            }
-        };
-
-        if layout.bitset_occupants(column) == 1 {
-            value != 0
-        } else {
-            value & (1 << bit) != 0
-        }
+        unsafe { column_is_null(self.as_ptr(), column, layout) }
     }
 }
 
@@ -368,3 +311,85 @@ impl Debug for Row {
         }
     }
 }
+
+/// Sets the null flag of the given column within the row pointed to by `row`
+///
+/// # Safety
+///
+/// `row` must point to a row of the given layout
+pub(crate) unsafe fn set_column_null(
+    row: *mut u8,
+    column: usize,
+    layout: &NativeLayout,
+    null: bool,
+) {
+    let (ty, bit_offset, bit) = layout.nullability_of(column);
+
+    let bitset = unsafe { row.add(bit_offset as usize) };
+    debug_assert_eq!(bitset as usize % ty.align() as usize, 0);
+
+    let value = if layout.bitset_occupants(column) == 1 {
+        // If there's only one occupant in the bitset we can set it directly
+        null as u64
+
+    // If there's more than one occupant in the bitset we need to load,
+    // set/unset the bit and then store it
+    } else {
+        // Load the bitset's current value
+        let mut mask = unsafe {
+            match ty {
+                BitSetType::U8 => bitset.read() as u64,
+                BitSetType::U16 => bitset.cast::<u16>().read() as u64,
+                BitSetType::U32 => bitset.cast::<u32>().read() as u64,
+                BitSetType::U64 => bitset.cast::<u64>().read(),
+            }
+        };
+
+        // Set or unset the bit
+        if null {
+            mask |= 1 << bit;
+        } else {
+            mask &= !(1 << bit);
+        }
+
+        mask
+    };
+
+    // Store the modified bitset
+    unsafe {
+        match ty {
+            BitSetType::U8 => bitset.write(value as u8),
+            BitSetType::U16 => bitset.cast::<u16>().write(value as u16),
+            BitSetType::U32 => bitset.cast::<u32>().write(value as u32),
+            BitSetType::U64 => bitset.cast::<u64>().write(value),
+        }
+    }
+}
+
+/// Returns `true` if the given column of the row pointed to by `row` is null
+///
+/// # Safety
+///
+/// `row` must point to a row of the given layout and the null flag for the
+/// given column must have been initialized
+pub(crate) unsafe fn column_is_null(row: *const u8, column: usize, layout: &NativeLayout) -> bool {
+    let (ty, bit_offset, bit) = layout.nullability_of(column);
+
+    let bitset = unsafe { row.add(bit_offset as usize) };
+    debug_assert_eq!(bitset as usize % ty.align() as usize, 0);
+
+    let value = unsafe {
+        match ty {
+            BitSetType::U8 => bitset.read() as u64,
+            BitSetType::U16 => bitset.cast::<u16>().read() as u64,
+            BitSetType::U32 => bitset.cast::<u32>().read() as u64,
+            BitSetType::U64 => bitset.cast::<u64>().read(),
+        }
+    };
+
+    if layout.bitset_occupants(column) == 1 {
+        value != 0
+    } else {
+        value & (1 << bit) != 0
+    }
+}
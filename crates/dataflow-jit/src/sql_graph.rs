@@ -208,19 +208,25 @@ impl From<Graph> for SqlGraph {
 #[cfg(test)]
 mod tests {
     use crate::{
-        dataflow::CompiledDataflow,
+        codegen::CodegenConfig,
+        dataflow::{row_from_literal, row_to_literal, CompiledDataflow},
         ir::{
             exprs::{ArgType, Call},
+            literal::{NullableConstant, RowLiteral, StreamCollection, StreamLiteral},
             nodes::{FilterMap, FlatMap, Node, StreamLayout},
-            ColumnType, Constant, Graph, GraphExt, RowLayoutBuilder,
+            udf::{UdfSignature, UdfType},
+            ColumnType, Constant, Graph, GraphExt, LayoutId, NodeId, RowLayoutBuilder,
+            ValidationError, Validator,
         },
         row::{Row, UninitRow},
         sql_graph::SqlGraph,
+        ThinStr, ThinStrRef,
     };
     use dbsp::{
-        trace::{Batch, Batcher},
+        trace::{Batch, BatchReader, Batcher, Cursor},
         OrdZSet, Runtime,
     };
+    use std::collections::BTreeMap;
 
     #[test]
     fn flat_map_set_set() {
@@ -329,7 +335,7 @@ mod tests {
             runtime.kill().unwrap();
         }
 
-        unsafe { jit_handle.free_memory() };
+        jit_handle.free_memory();
     }
 
     #[test]
@@ -370,4 +376,204 @@ mod tests {
         let json_graph = serde_json::to_string_pretty(&graph).unwrap();
         println!("{json_graph}");
     }
+
+    extern "C" fn reverse(string: ThinStrRef<'_>) -> ThinStr {
+        ThinStr::from(&*string.as_str().chars().rev().collect::<String>())
+    }
+
+    fn reverse_signature() -> UdfSignature {
+        UdfSignature::new(
+            vec![UdfType::non_null(ColumnType::String)],
+            UdfType::non_null(ColumnType::String),
+        )
+    }
+
+    /// Creates a graph that calls the udf `udf` on every string it receives,
+    /// round tripping it through json like the cli does
+    fn udf_graph(udf: &str) -> (Graph, NodeId, NodeId, LayoutId) {
+        let mut graph = Graph::new();
+
+        let string = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::String, false)
+                .build(),
+        );
+
+        let source = graph.source(string);
+        let map = graph.map(
+            source,
+            StreamLayout::Set(string),
+            StreamLayout::Set(string),
+            {
+                let mut builder = graph.function_builder();
+                let input = builder.add_input(string);
+                let output = builder.add_output(string);
+
+                let value = builder.load(input, 0);
+                let reversed = builder.call_udf(udf, vec![value], ColumnType::String);
+                builder.store(output, 0, reversed);
+
+                builder.ret_unit();
+                builder.build()
+            },
+        );
+        let sink = graph.sink(map);
+
+        let graph = SqlGraph::from(graph);
+        let json_graph = serde_json::to_string_pretty(&graph).unwrap();
+        let graph = serde_json::from_str::<SqlGraph>(&json_graph)
+            .unwrap()
+            .rematerialize();
+
+        (graph, source, sink, string)
+    }
+
+    #[test]
+    fn call_udf() {
+        crate::utils::test_logger();
+
+        let mut config = CodegenConfig::debug();
+        unsafe { config.register_udf("reverse", reverse_signature(), reverse as *const u8) };
+
+        let (mut graph, source, sink, string) = udf_graph("reverse");
+        Validator::new(graph.layout_cache().clone())
+            .with_udfs(config.udf_signatures())
+            .validate_graph(&graph)
+            .unwrap();
+        graph.optimize();
+
+        let (dataflow, jit_handle, layout_cache) = CompiledDataflow::new(&graph, config);
+        let vtable = unsafe { &*jit_handle.vtables()[&string] };
+
+        {
+            let (mut runtime, (mut inputs, outputs)) =
+                Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit)).unwrap();
+
+            let strings = ["", "a", "foobar", "hello world", "ünïcödé"];
+            let mut values: Vec<_> = strings
+                .iter()
+                .map(|&string| {
+                    let literal = RowLiteral::new(vec![NullableConstant::NonNull(
+                        Constant::String(string.to_owned()),
+                    )]);
+                    (
+                        unsafe { row_from_literal(&literal, vtable, &layout_cache) },
+                        1,
+                    )
+                })
+                .collect();
+            inputs
+                .get_mut(&source)
+                .unwrap()
+                .as_set_mut()
+                .unwrap()
+                .append(&mut values);
+
+            runtime.step().unwrap();
+
+            let output = outputs[&sink].as_set().unwrap().consolidate();
+            let mut reversed = BTreeMap::new();
+            let mut cursor = output.cursor();
+            while cursor.key_valid() {
+                let literal = row_to_literal(cursor.key(), &layout_cache);
+                match literal.rows() {
+                    [NullableConstant::NonNull(Constant::String(string))] => {
+                        reversed.insert(string.clone(), cursor.weight());
+                    }
+                    unexpected => panic!("unexpected output row {unexpected:?}"),
+                }
+
+                cursor.step_key();
+            }
+
+            let expected: BTreeMap<_, _> = strings
+                .iter()
+                .map(|string| (string.chars().rev().collect::<String>(), 1))
+                .collect();
+            assert_eq!(reversed, expected);
+
+            runtime.kill().unwrap();
+        }
+
+        jit_handle.free_memory();
+    }
+
+    #[test]
+    fn unknown_udf() {
+        let mut config = CodegenConfig::debug();
+        unsafe { config.register_udf("reverse", reverse_signature(), reverse as *const u8) };
+
+        let (graph, ..) = udf_graph("reverse_words");
+        let error = Validator::new(graph.layout_cache().clone())
+            .with_udfs(config.udf_signatures())
+            .validate_graph(&graph)
+            .unwrap_err();
+
+        assert!(matches!(error, ValidationError::UnknownUdf { .. }));
+        let message = error.to_string();
+        assert!(message.contains("`reverse_words`"), "{message}");
+        assert!(
+            message.contains("registered udfs are `reverse`"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn constant_source_literals_are_validated() {
+        let mut graph = Graph::new();
+        let layout = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .with_column(ColumnType::String, true)
+                .build(),
+        );
+
+        let row = |x: Constant, y: Option<Constant>| {
+            RowLiteral::new(vec![
+                NullableConstant::NonNull(x),
+                NullableConstant::Nullable(y),
+            ])
+        };
+        let valid = graph.constant_source(StreamLiteral::new(
+            StreamLayout::Set(layout),
+            StreamCollection::Set(vec![
+                (
+                    row(Constant::U32(1), Some(Constant::String("a".to_owned()))),
+                    1,
+                ),
+                (row(Constant::U32(2), None), 1),
+            ]),
+        ));
+        graph.sink(valid);
+
+        // Constant sources survive a round trip through json
+        let json = serde_json::to_string(&SqlGraph::from(graph)).unwrap();
+        let mut graph = serde_json::from_str::<SqlGraph>(&json)
+            .unwrap()
+            .rematerialize();
+        Validator::new(graph.layout_cache().clone())
+            .validate_graph(&graph)
+            .unwrap();
+
+        // A string where the layout expects an unsigned integer
+        let layout = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::U32, false)
+                .with_column(ColumnType::String, true)
+                .build(),
+        );
+        let invalid = graph.constant_source(StreamLiteral::new(
+            StreamLayout::Set(layout),
+            StreamCollection::Set(vec![(row(Constant::String("1".to_owned()), None), 1)]),
+        ));
+        graph.sink(invalid);
+
+        let error = Validator::new(graph.layout_cache().clone())
+            .validate_graph(&graph)
+            .unwrap_err();
+        assert!(
+            matches!(error, ValidationError::InvalidLiteral { node, .. } if node == invalid),
+            "{error}",
+        );
+    }
 }
@@ -0,0 +1,633 @@
+//! Checks compiled dataflows against the [interpreter](crate::interpreter)
+
+use crate::{
+    codegen::{CodegenConfig, NativeLayout, NativeLayoutCache, VTable},
+    dataflow::{row_from_literal, CompiledDataflow, RowInput, RowOutput},
+    interpreter::{Batch, DynRow, Interpreter, InterpreterError, Value},
+    ir::{
+        graph::GraphExt,
+        literal::{RowLiteral, StreamCollection},
+        nodes::{Node, StreamKind},
+        ColumnType, Graph, LayoutId, NodeId, RowLayout,
+    },
+    row::Row,
+    ThinStr,
+};
+use dbsp::{
+    trace::{BatchReader, Cursor},
+    Error as DBSPError, Runtime,
+};
+use derive_more::{Display, From};
+use std::{collections::BTreeMap, error::Error, fmt};
+
+#[derive(Debug, Display, From)]
+pub enum VerifyError {
+    #[display(fmt = "failed to interpret dataflow: {_0}")]
+    Interpreter(InterpreterError),
+
+    #[display(fmt = "failed to run compiled dataflow: {_0}")]
+    Runtime(DBSPError),
+
+    #[display(fmt = "{_0}")]
+    Mismatch(Mismatch),
+}
+
+impl Error for VerifyError {}
+
+/// The first sink batch where the compiled dataflow and the interpreter
+/// disagreed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The step the batches were produced in
+    pub step: usize,
+    /// The sink the batches were received by
+    pub sink: NodeId,
+    /// Every tuple whose weight differs between the two batches
+    pub diffs: Vec<RowDiff>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "the output of sink {} differed at step {}:",
+            self.sink, self.step,
+        )?;
+
+        for diff in &self.diffs {
+            writeln!(f, "  {diff}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A tuple with different weights in the compiled and interpreted outputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowDiff {
+    pub key: DynRow,
+    /// The tuple's value, `None` for sets
+    pub value: Option<DynRow>,
+    pub compiled: i32,
+    pub interpreted: i32,
+}
+
+impl fmt::Display for RowDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key)?;
+        if let Some(value) = &self.value {
+            write!(f, " => {value}")?;
+        }
+
+        write!(
+            f,
+            ": compiled weight {}, interpreted weight {}",
+            self.compiled, self.interpreted,
+        )
+    }
+}
+
+/// Runs `graph` both compiled and through the interpreter, feeding it
+/// `inputs[n]` on the `n`th step and comparing the batches received by every
+/// sink at each step
+///
+/// Returns the first sink batch that differs between the two, which
+/// generally indicates a miscompilation
+pub fn verify(
+    graph: &Graph,
+    inputs: &[BTreeMap<NodeId, StreamCollection>],
+) -> Result<(), VerifyError> {
+    verify_with_config(graph, inputs, CodegenConfig::release())
+}
+
+/// Same as [`verify()`] but compiles the dataflow with the given `config`
+pub fn verify_with_config(
+    graph: &Graph,
+    inputs: &[BTreeMap<NodeId, StreamCollection>],
+    config: CodegenConfig,
+) -> Result<(), VerifyError> {
+    // The interpreter also validates the inputs, so run it before compiling
+    let mut interpreter = Interpreter::new(graph, config.clone())?;
+    let expected = inputs
+        .iter()
+        .map(|inputs| interpreter.step(inputs))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (dataflow, jit_handle, layout_cache) = CompiledDataflow::new(graph, config);
+    let (mut runtime, (mut handles, outputs)) =
+        Runtime::init_circuit(1, move |circuit| dataflow.construct(circuit))?;
+
+    let vtables = jit_handle.vtables();
+    let result = (|| -> Result<(), VerifyError> {
+        for (step, (inputs, expected)) in inputs.iter().zip(expected).enumerate() {
+            for (node, collection) in inputs {
+                let handle = handles.get_mut(node).unwrap();
+                append_input(graph, *node, collection, handle, vtables, &layout_cache);
+            }
+
+            runtime.step()?;
+
+            for (sink, expected) in expected {
+                let compiled = read_output(&outputs[&sink], &layout_cache);
+
+                let diffs = diff_batches(&compiled, &expected);
+                if !diffs.is_empty() {
+                    return Err(VerifyError::Mismatch(Mismatch { step, sink, diffs }));
+                }
+            }
+        }
+
+        Ok(())
+    })();
+
+    // All rows must be dropped before we free their vtables
+    let killed = runtime.kill();
+    drop((handles, outputs));
+    jit_handle.free_memory();
+
+    result?;
+    killed?;
+
+    Ok(())
+}
+
+fn append_input(
+    graph: &Graph,
+    node: NodeId,
+    collection: &StreamCollection,
+    handle: &mut RowInput,
+    vtables: &BTreeMap<LayoutId, *mut VTable>,
+    layout_cache: &NativeLayoutCache,
+) {
+    let literal_row = |literal: &RowLiteral, layout: LayoutId| unsafe {
+        row_from_literal(literal, &*vtables[&layout], layout_cache)
+    };
+
+    match (collection, &graph.nodes()[&node]) {
+        (StreamCollection::Set(set), Node::Source(source)) => {
+            let mut rows = set
+                .iter()
+                .map(|(key, weight)| (literal_row(key, source.layout()), *weight))
+                .collect();
+
+            handle.as_set_mut().unwrap().append(&mut rows);
+        }
+
+        (StreamCollection::Map(map), Node::SourceMap(source)) => {
+            let mut rows = map
+                .iter()
+                .map(|(key, value, weight)| {
+                    (
+                        literal_row(key, source.key()),
+                        (literal_row(value, source.value()), *weight),
+                    )
+                })
+                .collect();
+
+            handle.as_map_mut().unwrap().append(&mut rows);
+        }
+
+        // Inputs have already been checked by the interpreter
+        _ => unreachable!(),
+    }
+}
+
+fn read_output(output: &RowOutput, layout_cache: &NativeLayoutCache) -> Batch {
+    match output {
+        RowOutput::Set(output) => {
+            let output = output.consolidate();
+            let mut batch = Batch::new(StreamKind::Set);
+
+            let mut cursor = output.cursor();
+            while cursor.key_valid() {
+                let key = read_row(cursor.key(), layout_cache);
+                batch.insert(key, DynRow::default(), cursor.weight());
+                cursor.step_key();
+            }
+
+            batch
+        }
+
+        RowOutput::Map(output) => {
+            let output = output.consolidate();
+            let mut batch = Batch::new(StreamKind::Map);
+
+            let mut cursor = output.cursor();
+            while cursor.key_valid() {
+                let key = read_row(cursor.key(), layout_cache);
+                while cursor.val_valid() {
+                    let value = read_row(cursor.val(), layout_cache);
+                    batch.insert(key.clone(), value, cursor.weight());
+                    cursor.step_val();
+                }
+
+                cursor.step_key();
+            }
+
+            batch
+        }
+    }
+}
+
+fn read_row(row: &Row, layout_cache: &NativeLayoutCache) -> DynRow {
+    let (native_layout, row_layout) = layout_cache.get_layouts(row.vtable().layout_id);
+    let columns = (0..row_layout.len())
+        .map(|column| unsafe { read_column(row, column, &row_layout, &native_layout) })
+        .collect();
+
+    DynRow::new(columns)
+}
+
+unsafe fn read_column(
+    row: &Row,
+    column: usize,
+    row_layout: &RowLayout,
+    native_layout: &NativeLayout,
+) -> Option<Value> {
+    if row_layout.column_nullable(column) && row.column_is_null(column, native_layout) {
+        return None;
+    }
+
+    let ty = row_layout.column_type(column);
+    if ty.is_unit() {
+        return Some(Value::Unit);
+    }
+
+    let ptr = row.as_ptr().add(native_layout.offset_of(column) as usize);
+    Some(match ty {
+        ColumnType::Bool => Value::Bool(*ptr.cast::<bool>()),
+        ColumnType::U8 => Value::U8(*ptr.cast::<u8>()),
+        ColumnType::I8 => Value::I8(*ptr.cast::<i8>()),
+        ColumnType::U16 => Value::U16(*ptr.cast::<u16>()),
+        ColumnType::I16 => Value::I16(*ptr.cast::<i16>()),
+        ColumnType::U32 => Value::U32(*ptr.cast::<u32>()),
+        ColumnType::I32 => Value::I32(*ptr.cast::<i32>()),
+        ColumnType::U64 => Value::U64(*ptr.cast::<u64>()),
+        ColumnType::I64 => Value::I64(*ptr.cast::<i64>()),
+        ColumnType::Usize => Value::Usize(*ptr.cast::<usize>()),
+        ColumnType::Isize => Value::Isize(*ptr.cast::<isize>()),
+        ColumnType::F32 => Value::F32(*ptr.cast::<f32>()),
+        ColumnType::F64 => Value::F64(*ptr.cast::<f64>()),
+        ColumnType::Date => Value::Date(*ptr.cast::<i32>()),
+        ColumnType::Timestamp => Value::Timestamp(*ptr.cast::<i64>()),
+        ColumnType::String => Value::String((*ptr.cast::<ThinStr>()).as_str().to_owned()),
+        // The interpreter doesn't support nested columns
+        ColumnType::Unit | ColumnType::Ptr | ColumnType::Struct | ColumnType::Array => {
+            unreachable!("{ty} columns cannot be read")
+        }
+    })
+}
+
+/// Returns every tuple whose weight differs between `compiled` and
+/// `interpreted`
+fn diff_batches(compiled: &Batch, interpreted: &Batch) -> Vec<RowDiff> {
+    let mut difference = compiled.clone();
+    difference.minus(interpreted);
+
+    difference
+        .iter()
+        .map(|(key, value, _)| RowDiff {
+            key: key.clone(),
+            value: (interpreted.kind() == StreamKind::Map).then(|| value.clone()),
+            compiled: compiled.weight(key, value),
+            interpreted: interpreted.weight(key, value),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codegen::{CodegenConfig, CodegenFault},
+        interpreter::{Batch, DynRow, Interpreter, Value},
+        ir::{
+            graph::GraphExt,
+            literal::{NullableConstant, RowLiteral, StreamCollection},
+            nodes::{JoinCore, StreamKind, StreamLayout},
+            udf::{UdfSignature, UdfType},
+            ColumnType, Constant, FunctionBuilder, Graph, NodeId, RowLayoutBuilder,
+        },
+        utils, verify, verify_with_config, ThinStr, ThinStrRef, VerifyError,
+    };
+    use std::collections::BTreeMap;
+
+    /// Builds a graph that adds one to each row of its input
+    fn add_one() -> (Graph, NodeId, NodeId) {
+        let mut graph = Graph::new();
+
+        let i32x1 = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, false)
+                .build(),
+        );
+
+        let source = graph.source(i32x1);
+        let plus_one = graph.map(
+            source,
+            StreamLayout::Set(i32x1),
+            StreamLayout::Set(i32x1),
+            {
+                let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+                let input = func.add_input(i32x1);
+                let output = func.add_output(i32x1);
+
+                let x = func.load(input, 0);
+                let one = func.constant(Constant::I32(1));
+                let x_plus_one = func.add(x, one);
+                func.store(output, 0, x_plus_one);
+
+                func.ret_unit();
+                func.build()
+            },
+        );
+        let sink = graph.sink(plus_one);
+
+        graph.optimize();
+        (graph, source, sink)
+    }
+
+    fn inputs(source: NodeId) -> Vec<BTreeMap<NodeId, StreamCollection>> {
+        let set = |values: &[(i32, i32)]| {
+            let rows = values
+                .iter()
+                .map(|&(x, weight)| {
+                    let row = RowLiteral::new(vec![NullableConstant::NonNull(Constant::I32(x))]);
+                    (row, weight)
+                })
+                .collect();
+
+            BTreeMap::from([(source, StreamCollection::Set(rows))])
+        };
+
+        vec![
+            set(&[(1, 1), (2, 1), (-5, 2)]),
+            set(&[(1, -1), (i32::MAX, 1)]),
+        ]
+    }
+
+    #[test]
+    fn compiled_matches_interpreter() {
+        utils::test_logger();
+
+        let (graph, source, _sink) = add_one();
+        verify(&graph, &inputs(source)).unwrap();
+    }
+
+    #[test]
+    fn seeded_fault_is_caught() {
+        utils::test_logger();
+
+        let (graph, source, sink) = add_one();
+
+        let _fault = CodegenFault::IntAddAsSub.seed();
+        match verify(&graph, &inputs(source)) {
+            Err(VerifyError::Mismatch(mismatch)) => {
+                assert_eq!(mismatch.step, 0);
+                assert_eq!(mismatch.sink, sink);
+                assert!(!mismatch.diffs.is_empty());
+            }
+
+            result => panic!("expected a mismatch, got {result:?}"),
+        }
+    }
+
+    /// Builds a graph joining two maps with nullable keys, producing the pair of
+    /// values for each match
+    fn nullable_key_join(null_safe: bool) -> (Graph, NodeId, NodeId, NodeId) {
+        let mut graph = Graph::new();
+
+        let unit = graph.layout_cache().unit();
+        let key_layout = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, true)
+                .build(),
+        );
+        let i32x1 = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, false)
+                .build(),
+        );
+        let i32x2 = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, false)
+                .with_column(ColumnType::I32, false)
+                .build(),
+        );
+
+        let lhs = graph.source_map(key_layout, i32x1);
+        let rhs = graph.source_map(key_layout, i32x1);
+
+        let join_fn = {
+            let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+            let _key = func.add_input(key_layout);
+            let lhs_value = func.add_input(i32x1);
+            let rhs_value = func.add_input(i32x1);
+            let output_key = func.add_output(i32x2);
+            let _output_value = func.add_output(unit);
+
+            let lhs_value = func.load(lhs_value, 0);
+            let rhs_value = func.load(rhs_value, 0);
+            func.store(output_key, 0, lhs_value);
+            func.store(output_key, 1, rhs_value);
+
+            func.ret_unit();
+            func.build()
+        };
+        let join = graph.add_node(
+            JoinCore::new(lhs, rhs, join_fn, i32x2, unit, StreamKind::Set)
+                .with_null_safe(null_safe),
+        );
+        let sink = graph.sink(join);
+
+        graph.optimize();
+        (graph, lhs, rhs, sink)
+    }
+
+    fn nullable_key_inputs(lhs: NodeId, rhs: NodeId) -> Vec<BTreeMap<NodeId, StreamCollection>> {
+        let map = |values: &[(Option<i32>, i32)]| {
+            let rows = values
+                .iter()
+                .map(|&(key, value)| {
+                    let key =
+                        RowLiteral::new(vec![NullableConstant::Nullable(key.map(Constant::I32))]);
+                    let value =
+                        RowLiteral::new(vec![NullableConstant::NonNull(Constant::I32(value))]);
+                    (key, value, 1)
+                })
+                .collect();
+
+            StreamCollection::Map(rows)
+        };
+
+        vec![BTreeMap::from([
+            (lhs, map(&[(Some(1), 10), (None, 20), (Some(3), 30)])),
+            (rhs, map(&[(Some(1), 100), (None, 200), (Some(4), 400)])),
+        ])]
+    }
+
+    /// Joins maps with null keys, checking the interpreter against the expected
+    /// SQL results and the compiled dataflow against the interpreter
+    fn check_nullable_key_join(null_safe: bool, expected: &[(i32, i32)]) {
+        let (graph, lhs, rhs, sink) = nullable_key_join(null_safe);
+        let inputs = nullable_key_inputs(lhs, rhs);
+
+        let mut interpreter = Interpreter::new(&graph, CodegenConfig::debug()).unwrap();
+        let outputs = interpreter.step(&inputs[0]).unwrap();
+
+        let mut expected_batch = Batch::new(StreamKind::Set);
+        for &(lhs, rhs) in expected {
+            let row = DynRow::new(vec![Some(Value::I32(lhs)), Some(Value::I32(rhs))]);
+            expected_batch.insert(row, DynRow::default(), 1);
+        }
+        assert_eq!(outputs[&sink], expected_batch);
+
+        verify(&graph, &inputs).unwrap();
+    }
+
+    #[test]
+    fn null_keys_never_match() {
+        utils::test_logger();
+        check_nullable_key_join(false, &[(10, 100)]);
+    }
+
+    #[test]
+    fn null_safe_null_keys_match() {
+        utils::test_logger();
+        check_nullable_key_join(true, &[(10, 100), (20, 200)]);
+    }
+
+    extern "C" fn checked_double(x: i32, x_is_null: bool, out: *mut i32) -> bool {
+        match x.checked_mul(2) {
+            Some(doubled) if !x_is_null => {
+                unsafe { out.write(doubled) };
+                false
+            }
+            _ => true,
+        }
+    }
+
+    extern "C" fn reverse(string: ThinStrRef<'_>) -> ThinStr {
+        ThinStr::from(&*string.as_str().chars().rev().collect::<String>())
+    }
+
+    fn udf_config() -> CodegenConfig {
+        let mut config = CodegenConfig::debug();
+        unsafe {
+            config.register_udf(
+                "checked_double",
+                UdfSignature::new(
+                    vec![UdfType::nullable(ColumnType::I32)],
+                    UdfType::nullable(ColumnType::I32),
+                ),
+                checked_double as *const u8,
+            );
+            config.register_udf(
+                "reverse",
+                UdfSignature::new(
+                    vec![UdfType::non_null(ColumnType::String)],
+                    UdfType::non_null(ColumnType::String),
+                ),
+                reverse as *const u8,
+            );
+        }
+
+        config
+    }
+
+    /// Builds a graph that doubles the first column of each row and reverses
+    /// the second one using udfs
+    fn udf_graph() -> (Graph, NodeId, NodeId) {
+        let mut graph = Graph::new();
+
+        let nullable_i32 = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, true)
+                .build(),
+        );
+        let row_layout = graph.layout_cache().add(
+            RowLayoutBuilder::new()
+                .with_column(ColumnType::I32, true)
+                .with_column(ColumnType::String, false)
+                .build(),
+        );
+
+        let source = graph.source(row_layout);
+        let map = graph.map(
+            source,
+            StreamLayout::Set(row_layout),
+            StreamLayout::Set(row_layout),
+            {
+                let mut func = FunctionBuilder::new(graph.layout_cache().clone());
+                let input = func.add_input(row_layout);
+                let output = func.add_output(row_layout);
+
+                let x = func.load(input, 0);
+                let x_is_null = func.is_null(input, 0);
+                let doubled =
+                    func.call_nullable_udf("checked_double", vec![x, x_is_null], nullable_i32);
+                let doubled_is_null = func.is_null(doubled, 0);
+                let doubled_value = func.load(doubled, 0);
+                func.store(output, 0, doubled_value);
+                func.set_null(output, 0, doubled_is_null);
+
+                let string = func.load(input, 1);
+                let reversed = func.call_udf("reverse", vec![string], ColumnType::String);
+                func.store(output, 1, reversed);
+
+                func.ret_unit();
+                func.build()
+            },
+        );
+        let sink = graph.sink(map);
+
+        graph.optimize();
+        (graph, source, sink)
+    }
+
+    #[test]
+    fn interpreter_calls_udfs() {
+        utils::test_logger();
+
+        let (graph, source, sink) = udf_graph();
+        let row = |x: Option<i32>, string: &str| {
+            RowLiteral::new(vec![
+                NullableConstant::Nullable(x.map(Constant::I32)),
+                NullableConstant::NonNull(Constant::String(string.to_owned())),
+            ])
+        };
+        let inputs = vec![
+            BTreeMap::from([(
+                source,
+                StreamCollection::Set(vec![
+                    (row(Some(1), "foobar"), 1),
+                    (row(None, "ünïcödé"), 1),
+                    (row(Some(i32::MAX), ""), 2),
+                ]),
+            )]),
+            BTreeMap::from([(
+                source,
+                StreamCollection::Set(vec![(row(Some(1), "foobar"), -1), (row(Some(-21), "a"), 1)]),
+            )]),
+        ];
+
+        let mut interpreter = Interpreter::new(&graph, udf_config()).unwrap();
+        let outputs = interpreter.step(&inputs[0]).unwrap();
+
+        let mut expected = Batch::new(StreamKind::Set);
+        for (x, string, weight) in [
+            (Some(2), "raboof", 1),
+            (None, "édöcïnü", 1),
+            // Doubling overflows
+            (None, "", 2),
+        ] {
+            let row = DynRow::new(vec![
+                x.map(Value::I32),
+                Some(Value::String(string.to_owned())),
+            ]);
+            expected.insert(row, DynRow::default(), weight);
+        }
+        assert_eq!(outputs[&sink], expected);
+
+        verify_with_config(&graph, &inputs, udf_config()).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dbsp::trace::layers::{advance_with_hint, SearchHint};
+
+const LENGTH: usize = 1 << 20;
+
+/// Seeks through a sorted slice in increments of `distance` elements, the way
+/// a merge join walks one input while following the keys of the other.
+fn seek_all(keys: &[u64], distance: u64, hint: SearchHint) -> usize {
+    let mut position = 0;
+    let mut needle = 0;
+    let mut seeks = 0;
+
+    while position < keys.len() {
+        needle += distance;
+        position += advance_with_hint(&keys[position..], hint, |&key| key < needle);
+        seeks += 1;
+    }
+
+    seeks
+}
+
+fn adjacent_seeks(c: &mut Criterion) {
+    let keys: Vec<u64> = (0..LENGTH as u64).collect();
+
+    let mut group = c.benchmark_group("advance-seek");
+    for distance in [1, 4, 16, 1024] {
+        for (name, hint) in [
+            ("near", SearchHint::Near),
+            ("far", SearchHint::Far),
+            ("unknown", SearchHint::Unknown),
+        ] {
+            group.bench_with_input(
+                BenchmarkId::new(name, distance),
+                &distance,
+                |b, &distance| b.iter(|| seek_all(black_box(&keys), distance, hint)),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, adjacent_seeks);
+criterion_main!(benches);
@@ -28,6 +28,19 @@ where
     data
 }
 
+// Sorted keys with runs of duplicates and diffs, some of which cancel out
+fn sorted_pod_data(length: usize) -> (Vec<u64>, Vec<i64>) {
+    let mut rng = Xoshiro256StarStar::from_seed(SEED);
+
+    let mut keys: Vec<u64> = (0..length)
+        .map(|_| rng.gen_range(0..(length as u64 / 2).max(1)))
+        .collect();
+    keys.sort_unstable();
+    let diffs: Vec<i64> = (0..length).map(|_| rng.gen_range(-1..=2)).collect();
+
+    (keys, diffs)
+}
+
 // Consolidation using stable sorting
 fn consolidate_slice_stable<T, D>(slice: &mut [(T, D)]) -> usize
 where
@@ -187,6 +200,38 @@ macro_rules! consolidation_benches {
                 });
             )*
             group.finish();
+
+            let mut group = c.benchmark_group("compact-sorted-scalar");
+            $(
+                group.bench_function($name, |b| {
+                    let (keys, diffs) = sorted_pod_data($size);
+
+                    b.iter_batched(
+                        || (keys.clone(), diffs.clone()),
+                        |(mut keys, mut diffs)| {
+                            consolidation::compact_paired_slices_scalar(black_box(&mut keys), black_box(&mut diffs))
+                        },
+                        BatchSize::PerIteration,
+                    );
+                });
+            )*
+            group.finish();
+
+            let mut group = c.benchmark_group("compact-sorted-pod");
+            $(
+                group.bench_function($name, |b| {
+                    let (keys, diffs) = sorted_pod_data($size);
+
+                    b.iter_batched(
+                        || (keys.clone(), diffs.clone()),
+                        |(mut keys, mut diffs)| {
+                            consolidation::pod::compact_pod(black_box(&mut keys), black_box(&mut diffs))
+                        },
+                        BatchSize::PerIteration,
+                    );
+                });
+            )*
+            group.finish();
         }
     };
 }
@@ -409,6 +409,7 @@ fn main() {
             }
         }
     })
+    .unwrap()
     .join()
     .unwrap();
 
@@ -124,7 +124,8 @@ fn main() {
         //fs::write("path.dot", graph.to_dot()).unwrap();
 
         circuit.step().unwrap();
-    });
+    })
+    .unwrap();
 
     hruntime.join().unwrap();
 }
@@ -10,24 +10,41 @@ use std::{
 };
 
 #[cfg(feature = "with-serde")]
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 macro_rules! float {
     ($($outer:ident($inner:ident)),* $(,)?) => {
         $(
-            #[doc = concat!("A wrapper around [`", stringify!($inner), "`] that allows using it as a DBSP weight")]
+            #[doc = concat!("A wrapper around [`", stringify!($inner), "`] that allows using it as DBSP data and weight")]
+            ///
+            /// The wrapper stores floats in canonical form: all NaNs are
+            /// replaced with the positive quiet NaN, and `-0.0` is replaced
+            /// with `0.0`.  Values are canonicalized on construction and after
+            /// each arithmetic operation, so equal values always have the same
+            /// bit pattern and hence the same hash and serialized form.  For
+            /// canonical values, the ordering of the wrapper coincides with
+            #[doc = concat!("[`", stringify!($inner), "::total_cmp`], i.e., NaN is greater than all other values.")]
             #[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, SizeOf)]
             #[repr(transparent)]
             #[size_of(skip_all)]
-            #[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+            #[cfg_attr(feature = "with-serde", derive(Serialize))]
             #[cfg_attr(feature = "with-serde", serde(transparent))]
             pub struct $outer(OrderedFloat<$inner>);
 
             impl $outer {
-                pub const EPSILON: Self = Self::new($inner::EPSILON);
+                pub const EPSILON: Self = Self(OrderedFloat($inner::EPSILON));
 
                 #[inline]
-                pub const fn new(float: $inner) -> Self {
+                pub fn new(float: $inner) -> Self {
+                    let float = if float.is_nan() {
+                        $inner::NAN
+                    } else if float == 0.0 {
+                        // Replaces `-0.0` with `0.0`.
+                        0.0
+                    } else {
+                        float
+                    };
+
                     Self(OrderedFloat(float))
                 }
 
@@ -72,7 +89,7 @@ macro_rules! float {
 
                 #[inline]
                 fn add(self, rhs: Self) -> Self::Output {
-                    Self(self.0 + rhs.0)
+                    Self::new(self.into_inner() + rhs.into_inner())
                 }
             }
 
@@ -113,7 +130,7 @@ macro_rules! float {
 
                 #[inline]
                 fn sub(self, rhs: Self) -> Self::Output {
-                    Self(self.0 - rhs.0)
+                    Self::new(self.into_inner() - rhs.into_inner())
                 }
             }
 
@@ -163,7 +180,7 @@ macro_rules! float {
 
                 #[inline]
                 fn mul(self, rhs: Self) -> Self::Output {
-                    Self(self.0 * rhs.0)
+                    Self::new(self.into_inner() * rhs.into_inner())
                 }
             }
 
@@ -231,7 +248,7 @@ macro_rules! float {
 
                 #[inline]
                 fn div(self, rhs: Self) -> Self::Output {
-                    Self(self.0 / rhs.0)
+                    Self::new(self.into_inner() / rhs.into_inner())
                 }
             }
 
@@ -290,7 +307,7 @@ macro_rules! float {
 
                 #[inline]
                 fn neg(self) -> Self::Output {
-                    Self(-self.0)
+                    Self::new(-self.into_inner())
                 }
             }
 
@@ -299,7 +316,7 @@ macro_rules! float {
 
                 #[inline]
                 fn neg(self) -> Self::Output {
-                    $outer(-self.0)
+                    -*self
                 }
             }
 
@@ -364,6 +381,17 @@ macro_rules! float {
                 }
             }
 
+            #[cfg(feature = "with-serde")]
+            impl<'de> Deserialize<'de> for $outer {
+                #[inline]
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    $inner::deserialize(deserializer).map(Self::new)
+                }
+            }
+
             impl FromStr for $outer {
                 type Err = ParseFloatError;
 
@@ -455,7 +483,8 @@ impl<'de> bincode::BorrowDecode<'de> for F32 {
 #[cfg(test)]
 mod tests {
     use super::{F32, F64};
-    use std::str::FromStr;
+    use crate::default_hash;
+    use std::{cmp::Ordering, str::FromStr};
 
     #[test]
     fn fromstr() {
@@ -512,4 +541,129 @@ mod tests {
             assert_eq!(decoded, input);
         }
     }
+
+    #[test]
+    fn canonical_nan() {
+        let nans = [
+            f64::NAN,
+            -f64::NAN,
+            f64::from_bits(f64::NAN.to_bits() | 1),
+            f64::INFINITY - f64::INFINITY,
+        ];
+
+        for nan in nans {
+            assert_eq!(F64::new(nan).into_inner().to_bits(), f64::NAN.to_bits());
+            assert_eq!(F64::new(nan), F64::new(f64::NAN));
+            assert_eq!(
+                default_hash(&F64::new(nan)),
+                default_hash(&F64::new(f64::NAN))
+            );
+        }
+
+        assert_eq!(
+            F32::new(-f32::NAN).into_inner().to_bits(),
+            f32::NAN.to_bits()
+        );
+    }
+
+    #[test]
+    fn canonical_zero() {
+        assert_eq!(F64::new(-0.0).into_inner().to_bits(), 0.0f64.to_bits());
+        assert_eq!(F32::new(-0.0).into_inner().to_bits(), 0.0f32.to_bits());
+        assert_eq!(default_hash(&F64::new(-0.0)), default_hash(&F64::new(0.0)));
+
+        // Arithmetic operations produce canonical values.
+        let zero = F64::new(0.0);
+        assert_eq!((-zero).into_inner().to_bits(), 0.0f64.to_bits());
+        assert_eq!((zero * -1.0).into_inner().to_bits(), 0.0f64.to_bits());
+        assert_eq!(
+            (F64::new(-1.0) + F64::new(1.0)).into_inner().to_bits(),
+            0.0f64.to_bits()
+        );
+        assert_eq!(
+            (F64::new(f64::INFINITY) - F64::new(f64::INFINITY))
+                .into_inner()
+                .to_bits(),
+            f64::NAN.to_bits()
+        );
+        assert_eq!(
+            (F64::new(0.0) / F64::new(0.0)).into_inner().to_bits(),
+            f64::NAN.to_bits()
+        );
+    }
+
+    #[test]
+    fn total_order() {
+        let floats = [
+            f64::NAN,
+            f64::INFINITY,
+            1.5,
+            f64::MIN_POSITIVE,
+            0.0,
+            -0.0,
+            -f64::MIN_POSITIVE,
+            -1.5,
+            f64::NEG_INFINITY,
+        ];
+
+        for x in floats {
+            for y in floats {
+                let (x, y) = (F64::new(x), F64::new(y));
+                assert_eq!(x.cmp(&y), x.into_inner().total_cmp(&y.into_inner()));
+                assert_eq!(x == y, x.cmp(&y) == Ordering::Equal);
+            }
+        }
+
+        let mut sorted: Vec<F64> = floats.into_iter().map(F64::new).collect();
+        sorted.sort();
+        assert_eq!(
+            sorted,
+            vec![
+                F64::new(f64::NEG_INFINITY),
+                F64::new(-1.5),
+                F64::new(-f64::MIN_POSITIVE),
+                F64::new(0.0),
+                F64::new(0.0),
+                F64::new(f64::MIN_POSITIVE),
+                F64::new(1.5),
+                F64::new(f64::INFINITY),
+                F64::new(f64::NAN),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_non_canonical() {
+        let mut slice = [0u8; 12];
+
+        for (input, expected) in [(-f64::NAN, f64::NAN), (-0.0, 0.0)] {
+            let length =
+                bincode::encode_into_slice(input, &mut slice, bincode::config::standard()).unwrap();
+            let decoded: F64 =
+                bincode::decode_from_slice(&slice[..length], bincode::config::standard())
+                    .unwrap()
+                    .0;
+            assert_eq!(decoded.into_inner().to_bits(), expected.to_bits());
+        }
+    }
+
+    #[cfg(feature = "with-serde")]
+    #[test]
+    fn serde_roundtrip() {
+        for input in [-1.25, 0.0, 1.0, f64::MAX, f64::MIN, f64::MIN_POSITIVE] {
+            let input = F64::new(input);
+            let json = serde_json::to_string(&input).unwrap();
+            assert_eq!(serde_json::from_str::<F64>(&json).unwrap(), input);
+        }
+
+        for input in [-1.25f32, 0.0, 1.0, f32::MAX, f32::MIN] {
+            let input = F32::new(input);
+            let json = serde_json::to_string(&input).unwrap();
+            assert_eq!(serde_json::from_str::<F32>(&json).unwrap(), input);
+        }
+
+        // Deserialization canonicalizes negative zero.
+        let zero: F64 = serde_json::from_str("-0.0").unwrap();
+        assert_eq!(zero.into_inner().to_bits(), 0.0f64.to_bits());
+    }
 }
@@ -0,0 +1,801 @@
+//! Conversions between batches and Arrow [`RecordBatch`]es.
+//!
+//! A batch of `(key, value, weight)` tuples is represented as a record batch
+//! with one row per tuple.  Keys and values are mapped to Arrow columns via
+//! the [`ArrowSchema`] trait, which is implemented for primitive types,
+//! strings, `Option`s (as nullable columns), and tuples, and can be
+//! implemented for structs using the [`arrow_schema`](`crate::arrow_schema`)
+//! macro.  Weights are stored in a separate column named `weight`.
+//!
+//! Conversions are columnar: each column is built from, or decoded into, a
+//! vector of values of the corresponding Rust type.
+
+use crate::{
+    algebra::{HasOne, F32, F64},
+    operator::OutputHandle,
+    trace::{Batch, BatchReader, Cursor, DBData, DBWeight},
+    OrdIndexedZSet,
+};
+use arrow::{
+    array::{BooleanArray, PrimitiveArray, StringArray},
+    datatypes::{
+        ArrowPrimitiveType, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+        UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+    },
+};
+use std::sync::Arc;
+
+pub use arrow::{
+    array::{Array, ArrayRef},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+
+/// Name of the weight column.
+pub const WEIGHT_COLUMN: &str = "weight";
+
+/// A type that is stored in a single Arrow column.
+pub trait ArrowColumn: Sized {
+    /// Arrow data type of the column.
+    fn data_type() -> DataType;
+
+    /// `true` if the column can contain nulls.
+    fn nullable() -> bool {
+        false
+    }
+
+    /// Build an Arrow array from `values`.
+    fn to_array(values: &[&Self]) -> ArrayRef;
+
+    /// Build an Arrow array from `values`, representing `None` as null.
+    fn to_nullable_array(values: &[Option<&Self>]) -> ArrayRef;
+
+    /// Decode all elements of `array`, which must not contain nulls.
+    fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError>;
+
+    /// Decode all elements of `array`, representing nulls as `None`.
+    fn from_nullable_array(array: &dyn Array) -> Result<Vec<Option<Self>>, ArrowError>;
+}
+
+/// A type that is stored in zero or more Arrow columns.
+pub trait ArrowSchema: Sized {
+    /// The number of columns used to store values of this type.
+    fn num_columns() -> usize;
+
+    /// Arrow fields that describe the columns.
+    ///
+    /// `name` is used to name columns that don't have a name of their own:
+    /// a primitive value is stored in a column called `name`, and the
+    /// components of a tuple are stored in columns called `name.0`,
+    /// `name.1`, etc.
+    fn fields(name: &str) -> Vec<Field>;
+
+    /// Build columns from `values` and append them to `columns`.
+    fn to_columns(values: &[&Self], columns: &mut Vec<ArrayRef>);
+
+    /// Decode `num_rows` values from `columns`, which must contain exactly
+    /// [`num_columns`](`Self::num_columns`) arrays of length `num_rows`.
+    fn from_columns(columns: &[ArrayRef], num_rows: usize) -> Result<Vec<Self>, ArrowError>;
+}
+
+fn downcast<A, T>(array: &dyn Array) -> Result<&A, ArrowError>
+where
+    A: Array + 'static,
+    T: ArrowColumn,
+{
+    array.as_any().downcast_ref::<A>().ok_or_else(|| {
+        ArrowError::CastError(format!(
+            "expected column of type {}, found {}",
+            T::data_type(),
+            array.data_type()
+        ))
+    })
+}
+
+fn check_no_nulls(array: &dyn Array) -> Result<(), ArrowError> {
+    if array.null_count() > 0 {
+        Err(ArrowError::InvalidArgumentError(
+            "unexpected null in a non-nullable column".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+macro_rules! primitive_column {
+    ($type:ty, $arrow_type:ty, $data_type:expr, $to_native:expr, $from_native:expr) => {
+        impl ArrowColumn for $type {
+            fn data_type() -> DataType {
+                $data_type
+            }
+
+            fn to_array(values: &[&Self]) -> ArrayRef {
+                let to_native: fn($type) -> <$arrow_type as ArrowPrimitiveType>::Native =
+                    $to_native;
+
+                Arc::new(PrimitiveArray::<$arrow_type>::from_iter_values(
+                    values.iter().map(|value| to_native(**value)),
+                ))
+            }
+
+            fn to_nullable_array(values: &[Option<&Self>]) -> ArrayRef {
+                let to_native: fn($type) -> <$arrow_type as ArrowPrimitiveType>::Native =
+                    $to_native;
+
+                Arc::new(
+                    values
+                        .iter()
+                        .map(|value| value.map(|value| to_native(*value)))
+                        .collect::<PrimitiveArray<$arrow_type>>(),
+                )
+            }
+
+            fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError> {
+                let from_native: fn(<$arrow_type as ArrowPrimitiveType>::Native) -> $type =
+                    $from_native;
+
+                check_no_nulls(array)?;
+                let array = downcast::<PrimitiveArray<$arrow_type>, Self>(array)?;
+                Ok(array
+                    .values()
+                    .iter()
+                    .map(|value| from_native(*value))
+                    .collect())
+            }
+
+            fn from_nullable_array(array: &dyn Array) -> Result<Vec<Option<Self>>, ArrowError> {
+                let from_native: fn(<$arrow_type as ArrowPrimitiveType>::Native) -> $type =
+                    $from_native;
+
+                let array = downcast::<PrimitiveArray<$arrow_type>, Self>(array)?;
+                Ok(array.iter().map(|value| value.map(from_native)).collect())
+            }
+        }
+    };
+}
+
+primitive_column!(i8, Int8Type, DataType::Int8, |v| v, |v| v);
+primitive_column!(i16, Int16Type, DataType::Int16, |v| v, |v| v);
+primitive_column!(i32, Int32Type, DataType::Int32, |v| v, |v| v);
+primitive_column!(i64, Int64Type, DataType::Int64, |v| v, |v| v);
+primitive_column!(isize, Int64Type, DataType::Int64, |v| v as _, |v| v as _);
+primitive_column!(u8, UInt8Type, DataType::UInt8, |v| v, |v| v);
+primitive_column!(u16, UInt16Type, DataType::UInt16, |v| v, |v| v);
+primitive_column!(u32, UInt32Type, DataType::UInt32, |v| v, |v| v);
+primitive_column!(u64, UInt64Type, DataType::UInt64, |v| v, |v| v);
+primitive_column!(usize, UInt64Type, DataType::UInt64, |v| v as _, |v| v as _);
+primitive_column!(
+    F32,
+    Float32Type,
+    DataType::Float32,
+    F32::into_inner,
+    F32::new
+);
+primitive_column!(
+    F64,
+    Float64Type,
+    DataType::Float64,
+    F64::into_inner,
+    F64::new
+);
+
+impl ArrowColumn for bool {
+    fn data_type() -> DataType {
+        DataType::Boolean
+    }
+
+    fn to_array(values: &[&Self]) -> ArrayRef {
+        Arc::new(
+            values
+                .iter()
+                .map(|value| Some(**value))
+                .collect::<BooleanArray>(),
+        )
+    }
+
+    fn to_nullable_array(values: &[Option<&Self>]) -> ArrayRef {
+        Arc::new(
+            values
+                .iter()
+                .map(|value| value.copied())
+                .collect::<BooleanArray>(),
+        )
+    }
+
+    fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError> {
+        check_no_nulls(array)?;
+        let array = downcast::<BooleanArray, Self>(array)?;
+        Ok((0..array.len()).map(|i| array.value(i)).collect())
+    }
+
+    fn from_nullable_array(array: &dyn Array) -> Result<Vec<Option<Self>>, ArrowError> {
+        Ok(downcast::<BooleanArray, Self>(array)?.iter().collect())
+    }
+}
+
+impl ArrowColumn for String {
+    fn data_type() -> DataType {
+        DataType::Utf8
+    }
+
+    fn to_array(values: &[&Self]) -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(values))
+    }
+
+    fn to_nullable_array(values: &[Option<&Self>]) -> ArrayRef {
+        Arc::new(values.iter().copied().collect::<StringArray>())
+    }
+
+    fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError> {
+        check_no_nulls(array)?;
+        let array = downcast::<StringArray, Self>(array)?;
+        Ok((0..array.len())
+            .map(|i| array.value(i).to_string())
+            .collect())
+    }
+
+    fn from_nullable_array(array: &dyn Array) -> Result<Vec<Option<Self>>, ArrowError> {
+        Ok(downcast::<StringArray, Self>(array)?
+            .iter()
+            .map(|value| value.map(str::to_string))
+            .collect())
+    }
+}
+
+impl<T> ArrowColumn for Option<T>
+where
+    T: ArrowColumn,
+{
+    fn data_type() -> DataType {
+        T::data_type()
+    }
+
+    fn nullable() -> bool {
+        true
+    }
+
+    fn to_array(values: &[&Self]) -> ArrayRef {
+        T::to_nullable_array(
+            &values
+                .iter()
+                .map(|value| value.as_ref())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn to_nullable_array(values: &[Option<&Self>]) -> ArrayRef {
+        T::to_nullable_array(
+            &values
+                .iter()
+                .map(|value| value.and_then(Option::as_ref))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError> {
+        T::from_nullable_array(array)
+    }
+
+    fn from_nullable_array(array: &dyn Array) -> Result<Vec<Option<Self>>, ArrowError> {
+        Ok(T::from_nullable_array(array)?
+            .into_iter()
+            .map(Some)
+            .collect())
+    }
+}
+
+macro_rules! column_schema {
+    ($($type:ty),*) => {
+        $(
+            impl ArrowSchema for $type {
+                fn num_columns() -> usize {
+                    1
+                }
+
+                fn fields(name: &str) -> Vec<Field> {
+                    vec![Field::new(name, Self::data_type(), Self::nullable())]
+                }
+
+                fn to_columns(values: &[&Self], columns: &mut Vec<ArrayRef>) {
+                    columns.push(Self::to_array(values));
+                }
+
+                fn from_columns(
+                    columns: &[ArrayRef],
+                    _num_rows: usize,
+                ) -> Result<Vec<Self>, ArrowError> {
+                    Self::from_array(columns[0].as_ref())
+                }
+            }
+        )*
+    };
+}
+
+column_schema!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, F32, F64, bool, String);
+
+impl<T> ArrowSchema for Option<T>
+where
+    T: ArrowColumn,
+{
+    fn num_columns() -> usize {
+        1
+    }
+
+    fn fields(name: &str) -> Vec<Field> {
+        vec![Field::new(name, Self::data_type(), Self::nullable())]
+    }
+
+    fn to_columns(values: &[&Self], columns: &mut Vec<ArrayRef>) {
+        columns.push(Self::to_array(values));
+    }
+
+    fn from_columns(columns: &[ArrayRef], _num_rows: usize) -> Result<Vec<Self>, ArrowError> {
+        Self::from_array(columns[0].as_ref())
+    }
+}
+
+impl ArrowSchema for () {
+    fn num_columns() -> usize {
+        0
+    }
+
+    fn fields(_name: &str) -> Vec<Field> {
+        Vec::new()
+    }
+
+    fn to_columns(_values: &[&Self], _columns: &mut Vec<ArrayRef>) {}
+
+    fn from_columns(_columns: &[ArrayRef], num_rows: usize) -> Result<Vec<Self>, ArrowError> {
+        Ok(vec![(); num_rows])
+    }
+}
+
+macro_rules! tuple_schema {
+    ($($type:ident $var:ident $index:tt),+) => {
+        impl<$($type),+> ArrowSchema for ($($type,)+)
+        where
+            $($type: ArrowSchema,)+
+        {
+            fn num_columns() -> usize {
+                [$($type::num_columns()),+].iter().sum()
+            }
+
+            fn fields(name: &str) -> Vec<Field> {
+                let mut fields = Vec::with_capacity(Self::num_columns());
+                $(fields.extend($type::fields(&format!("{name}.{}", $index)));)+
+                fields
+            }
+
+            fn to_columns(values: &[&Self], columns: &mut Vec<ArrayRef>) {
+                $(
+                    $type::to_columns(
+                        &values.iter().map(|value| &value.$index).collect::<Vec<_>>(),
+                        columns,
+                    );
+                )+
+            }
+
+            #[allow(unused_assignments)]
+            fn from_columns(
+                columns: &[ArrayRef],
+                num_rows: usize,
+            ) -> Result<Vec<Self>, ArrowError> {
+                let mut offset = 0;
+                $(
+                    let mut $var = $type::from_columns(
+                        &columns[offset..offset + $type::num_columns()],
+                        num_rows,
+                    )?
+                    .into_iter();
+                    offset += $type::num_columns();
+                )+
+
+                Ok((0..num_rows).map(|_| ($($var.next().unwrap(),)+)).collect())
+            }
+        }
+    };
+}
+
+tuple_schema!(A a 0, B b 1);
+tuple_schema!(A a 0, B b 1, C c 2);
+tuple_schema!(A a 0, B b 1, C c 2, D d 3);
+
+/// Implement [`ArrowSchema`](`crate::arrow::ArrowSchema`) for a struct.
+///
+/// Each field of the struct is stored in a column named after the field.
+/// Field types must implement [`ArrowColumn`](`crate::arrow::ArrowColumn`).
+///
+/// # Example
+///
+/// ```
+/// use dbsp::arrow_schema;
+///
+/// struct Person {
+///     name: String,
+///     age: Option<u32>,
+/// }
+///
+/// arrow_schema!(Person { name: String, age: Option<u32> });
+/// ```
+#[macro_export]
+macro_rules! arrow_schema {
+    ($type:ident { $($field:ident: $field_type:ty),+ $(,)? }) => {
+        impl $crate::arrow::ArrowSchema for $type {
+            fn num_columns() -> usize {
+                [$(stringify!($field)),+].len()
+            }
+
+            fn fields(_name: &str) -> Vec<$crate::arrow::Field> {
+                vec![$(
+                    $crate::arrow::Field::new(
+                        stringify!($field),
+                        <$field_type as $crate::arrow::ArrowColumn>::data_type(),
+                        <$field_type as $crate::arrow::ArrowColumn>::nullable(),
+                    )
+                ),+]
+            }
+
+            fn to_columns(values: &[&Self], columns: &mut Vec<$crate::arrow::ArrayRef>) {
+                $(
+                    columns.push(<$field_type as $crate::arrow::ArrowColumn>::to_array(
+                        &values.iter().map(|value| &value.$field).collect::<Vec<_>>(),
+                    ));
+                )+
+            }
+
+            fn from_columns(
+                columns: &[$crate::arrow::ArrayRef],
+                num_rows: usize,
+            ) -> Result<Vec<Self>, $crate::arrow::ArrowError> {
+                let mut columns = columns.iter();
+                $(
+                    let mut $field = <$field_type as $crate::arrow::ArrowColumn>::from_array(
+                        columns.next().unwrap().as_ref(),
+                    )?
+                    .into_iter();
+                )+
+
+                Ok((0..num_rows)
+                    .map(|_| $type { $($field: $field.next().unwrap()),+ })
+                    .collect())
+            }
+        }
+    };
+}
+
+/// Arrow schema of record batches that store batches with keys of type `K`,
+/// values of type `V`, and weights of type `R`.
+///
+/// Key and value columns are named according to
+/// [`ArrowSchema::fields`], using `key` and `val` as default names.  The
+/// weight is stored in the last column, named [`WEIGHT_COLUMN`].
+pub fn batch_schema<K, V, R>() -> Schema
+where
+    K: ArrowSchema,
+    V: ArrowSchema,
+    R: ArrowColumn,
+{
+    let mut fields = K::fields("key");
+    fields.extend(V::fields("val"));
+    fields.push(Field::new(WEIGHT_COLUMN, R::data_type(), false));
+
+    Schema::new(fields)
+}
+
+/// Convert a record batch into an indexed Z-set.
+///
+/// Looks up key and value columns in `batch` by the names in
+/// [`batch_schema`].  Reads weights from `weight_column`, or assigns weight
+/// 1 to each row if `weight_column` is `None`.  Other columns of `batch` are
+/// ignored.
+pub fn from_arrow<K, V, R>(
+    batch: &RecordBatch,
+    weight_column: Option<&str>,
+) -> Result<OrdIndexedZSet<K, V, R>, ArrowError>
+where
+    K: DBData + ArrowSchema,
+    V: DBData + ArrowSchema,
+    R: DBWeight + ArrowColumn + HasOne,
+{
+    let schema = batch.schema();
+    let num_rows = batch.num_rows();
+    let columns = |fields: Vec<Field>| {
+        fields
+            .iter()
+            .map(|field| Ok(batch.column(schema.index_of(field.name())?).clone()))
+            .collect::<Result<Vec<_>, ArrowError>>()
+    };
+
+    let keys = K::from_columns(&columns(K::fields("key"))?, num_rows)?;
+    let vals = V::from_columns(&columns(V::fields("val"))?, num_rows)?;
+    let weights = match weight_column {
+        Some(weight_column) => {
+            R::from_array(batch.column(schema.index_of(weight_column)?).as_ref())?
+        }
+        None => vec![R::one(); num_rows],
+    };
+
+    let tuples = keys.into_iter().zip(vals).zip(weights).collect::<Vec<_>>();
+
+    Ok(OrdIndexedZSet::from_tuples((), tuples))
+}
+
+/// Convert a batch into a record batch with [`batch_schema`].
+pub fn to_arrow<B>(batch: &B) -> RecordBatch
+where
+    B: BatchReader<Time = ()>,
+    B::Key: ArrowSchema,
+    B::Val: ArrowSchema,
+    B::R: ArrowColumn,
+{
+    to_arrow_with_schema(batch, Arc::new(batch_schema::<B::Key, B::Val, B::R>()))
+}
+
+fn to_arrow_with_schema<B>(batch: &B, schema: SchemaRef) -> RecordBatch
+where
+    B: BatchReader<Time = ()>,
+    B::Key: ArrowSchema,
+    B::Val: ArrowSchema,
+    B::R: ArrowColumn,
+{
+    let mut keys = Vec::with_capacity(batch.len());
+    let mut vals = Vec::with_capacity(batch.len());
+    let mut weights = Vec::with_capacity(batch.len());
+
+    let mut cursor = batch.cursor();
+    while cursor.key_valid() {
+        while cursor.val_valid() {
+            keys.push(cursor.key().clone());
+            vals.push(cursor.val().clone());
+            weights.push(cursor.weight());
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    B::Key::to_columns(&keys.iter().collect::<Vec<_>>(), &mut columns);
+    B::Val::to_columns(&vals.iter().collect::<Vec<_>>(), &mut columns);
+    columns.push(B::R::to_array(&weights.iter().collect::<Vec<_>>()));
+
+    // The columns are built from the same schema, so this cannot fail.
+    RecordBatch::try_new(schema, columns).unwrap()
+}
+
+/// Adapter that reads the output of a circuit as Arrow record batches.
+///
+/// Wraps an [`OutputHandle`] attached to a stream of batches and converts
+/// the batches produced at each clock cycle into record batches with
+/// [`batch_schema`].
+#[derive(Clone)]
+pub struct ArrowOutputHandle<B> {
+    handle: OutputHandle<B>,
+    schema: SchemaRef,
+}
+
+impl<B> ArrowOutputHandle<B>
+where
+    B: Batch<Time = ()> + Send,
+    B::Key: ArrowSchema,
+    B::Val: ArrowSchema,
+    B::R: ArrowColumn,
+{
+    /// Create an adapter that reads from `handle`.
+    pub fn new(handle: OutputHandle<B>) -> Self {
+        Self {
+            handle,
+            schema: Arc::new(batch_schema::<B::Key, B::Val, B::R>()),
+        }
+    }
+
+    /// Arrow schema of the record batches produced by this handle.
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Read batches produced by all worker threads during the last clock
+    /// cycle and convert them into a single record batch.
+    ///
+    /// See [`OutputHandle::consolidate`].
+    pub fn consolidate(&self) -> RecordBatch {
+        to_arrow_with_schema(&self.handle.consolidate(), self.schema.clone())
+    }
+
+    /// Read batches produced by all worker threads during the last clock
+    /// cycle and convert each of them into a record batch.
+    ///
+    /// Unlike [`consolidate`](`Self::consolidate`), doesn't merge the
+    /// batches.  See [`OutputHandle::take_from_all`].
+    pub fn take_from_all(&self) -> Vec<RecordBatch> {
+        self.handle
+            .take_from_all()
+            .iter()
+            .map(|batch| to_arrow_with_schema(batch, self.schema.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        batch_schema, from_arrow, to_arrow, ArrayRef, ArrowOutputHandle, DataType, Field,
+        RecordBatch, Schema,
+    };
+    use crate::{
+        algebra::F64,
+        arrow_schema, indexed_zset,
+        operator::time_series::{RelOffset, RelRange},
+        trace::Batch,
+        OrdIndexedZSet, Runtime,
+    };
+    use arrow::array::{Int64Array, StringArray, UInt32Array};
+    use bincode::{Decode, Encode};
+    use size_of::SizeOf;
+    use std::sync::Arc;
+
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, SizeOf, Encode, Decode)]
+    struct Person {
+        name: String,
+        nickname: Option<String>,
+        age: Option<u32>,
+        active: bool,
+    }
+
+    arrow_schema!(Person {
+        name: String,
+        nickname: Option<String>,
+        age: Option<u32>,
+        active: bool,
+    });
+
+    fn person(name: &str, nickname: Option<&str>, age: Option<u32>, active: bool) -> Person {
+        Person {
+            name: name.to_string(),
+            nickname: nickname.map(str::to_string),
+            age,
+            active,
+        }
+    }
+
+    #[test]
+    fn arrow_round_trip_struct() {
+        let batch: OrdIndexedZSet<u64, Person, isize> = indexed_zset! {
+            1 => { person("Alice", None, Some(30), true) => 1 },
+            2 => {
+                person("Bob", Some("Bobby"), None, false) => -1,
+                person("Bob", Some("Rob"), Some(41), true) => 2,
+            },
+            3 => { person("", Some(""), Some(0), false) => 1 },
+        };
+
+        let record_batch = to_arrow(&batch);
+        assert_eq!(
+            record_batch.schema().as_ref(),
+            &Schema::new(vec![
+                Field::new("key", DataType::UInt64, false),
+                Field::new("name", DataType::Utf8, false),
+                Field::new("nickname", DataType::Utf8, true),
+                Field::new("age", DataType::UInt32, true),
+                Field::new("active", DataType::Boolean, false),
+                Field::new("weight", DataType::Int64, false),
+            ])
+        );
+        assert_eq!(record_batch.num_rows(), 4);
+        assert_eq!(record_batch.column(2).null_count(), 1);
+        assert_eq!(record_batch.column(3).null_count(), 1);
+
+        assert_eq!(from_arrow(&record_batch, Some("weight")).unwrap(), batch);
+    }
+
+    type TupleBatch = OrdIndexedZSet<(String, Option<i64>), (Option<String>, F64), isize>;
+
+    #[test]
+    fn arrow_round_trip_tuples() {
+        let batch: TupleBatch = indexed_zset! {
+            ("a".to_string(), None) => {
+                (None, F64::new(1.5)) => 1,
+                (Some("x".to_string()), F64::new(-2.0)) => 3,
+            },
+            ("b".to_string(), Some(-5)) => { (Some("y".to_string()), F64::new(0.0)) => -2 },
+        };
+
+        let record_batch = to_arrow(&batch);
+        assert_eq!(
+            record_batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect::<Vec<_>>(),
+            vec!["key.0", "key.1", "val.0", "val.1", "weight"]
+        );
+        assert_eq!(from_arrow(&record_batch, Some("weight")).unwrap(), batch);
+
+        let empty = TupleBatch::empty(());
+        let record_batch = to_arrow(&empty);
+        assert_eq!(record_batch.num_rows(), 0);
+        assert_eq!(from_arrow(&record_batch, Some("weight")).unwrap(), empty);
+    }
+
+    #[test]
+    fn arrow_ingress() {
+        // Record batch produced by another system, with an extra column and
+        // without weights.
+        let schema = Schema::new(vec![
+            Field::new("key", DataType::Int64, false),
+            Field::new("comment", DataType::Utf8, true),
+            Field::new("val", DataType::UInt32, true),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(vec![1, 2, 1])),
+            Arc::new(StringArray::from(vec![Some("foo"), None, Some("bar")])),
+            Arc::new(UInt32Array::from(vec![Some(10), None, Some(10)])),
+        ];
+        let record_batch = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+
+        let batch: OrdIndexedZSet<i64, Option<u32>, isize> =
+            from_arrow(&record_batch, None).unwrap();
+        assert_eq!(
+            batch,
+            indexed_zset! { 1 => { Some(10) => 2 }, 2 => { None => 1 } }
+        );
+
+        // Nulls in non-nullable columns and type mismatches are errors.
+        assert!(from_arrow::<i64, u32, isize>(&record_batch, None).is_err());
+        assert!(from_arrow::<i64, Option<u64>, isize>(&record_batch, None).is_err());
+        assert!(from_arrow::<i64, Option<u32>, isize>(&record_batch, Some("weight")).is_err());
+    }
+
+    #[test]
+    fn arrow_rolling_aggregate_output() {
+        let (mut dbsp, (mut input, output)) = Runtime::init_circuit(4, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let range = RelRange::new(RelOffset::Before(2), RelOffset::Before(0));
+            let sum = input.partitioned_rolling_aggregate_linear(|v| *v, |sum| sum, range);
+
+            (input_handle, ArrowOutputHandle::new(sum.output()))
+        })
+        .unwrap();
+
+        input.append(&mut vec![
+            (0, ((1, 10), 1)),
+            (0, ((2, 20), 1)),
+            (0, ((4, 40), 1)),
+            (1, ((1, -1), 1)),
+        ]);
+        dbsp.step().unwrap();
+
+        let record_batch = output.consolidate();
+        assert_eq!(
+            record_batch.schema().as_ref(),
+            &batch_schema::<u64, (u64, Option<i64>), isize>()
+        );
+        assert_eq!(
+            from_arrow(&record_batch, Some("weight")).unwrap(),
+            indexed_zset! {
+                0 => { (1, Some(10)) => 1, (2, Some(30)) => 1, (4, Some(60)) => 1 },
+                1 => { (1, Some(-1)) => 1 },
+            }
+        );
+
+        input.append(&mut vec![(0, ((3, 30), 1))]);
+        dbsp.step().unwrap();
+
+        let record_batches = output.take_from_all();
+        assert_eq!(record_batches.len(), 4);
+        let changes = record_batches
+            .iter()
+            .map(|record_batch| {
+                from_arrow::<u64, (u64, Option<i64>), isize>(record_batch, Some("weight")).unwrap()
+            })
+            .fold(OrdIndexedZSet::empty(()), |acc, batch| acc.merge(&batch));
+        assert_eq!(
+            changes,
+            indexed_zset! { 0 => { (3, Some(60)) => 1, (4, Some(60)) => -1, (4, Some(90)) => 1 } }
+        );
+
+        dbsp.kill().unwrap();
+    }
+}
@@ -0,0 +1,645 @@
+//! Change data capture (CDC) connectors.
+//!
+//! This module converts change events produced by CDC tools into weighted
+//! tuples that can be fed to a circuit via a [`CollectionHandle`].  Currently
+//! supports the JSON encoding of [Debezium](https://debezium.io) change
+//! events.
+
+use crate::{CollectionHandle, DBData};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use serde::de::DeserializeOwned;
+use serde_json::{Error as JsonError, Map, Value};
+use std::{
+    error::Error as StdError,
+    fmt::{Display, Error as FmtError, Formatter},
+    io::{BufRead, Error as IOError, Lines},
+    marker::PhantomData,
+};
+
+/// Describes which columns of a table row make up a typed key or value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowSchema {
+    // `None` selects the entire row.
+    columns: Option<Vec<String>>,
+}
+
+impl RowSchema {
+    /// Select all columns of the row.
+    ///
+    /// The row is deserialized from a JSON object, e.g., into a struct whose
+    /// fields are named after the columns of the table.  Columns that don't
+    /// correspond to a field of the struct are ignored, so columns added to
+    /// the table by a schema change don't break decoding.
+    pub fn all() -> Self {
+        Self { columns: None }
+    }
+
+    /// Select the specified columns of the row.
+    ///
+    /// A single column is deserialized directly, so a one-column primary key
+    /// can be decoded into, e.g., `i64`.  Multiple columns are deserialized
+    /// from a sequence in the order listed here, e.g., into a tuple or a
+    /// struct that declares its fields in the same order.
+    pub fn columns<I, S>(columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            columns: Some(columns.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    fn extract<T>(&self, row: &Map<String, Value>) -> Result<T, CdcError>
+    where
+        T: DeserializeOwned,
+    {
+        let column = |name: &String| {
+            row.get(name)
+                .cloned()
+                .ok_or_else(|| CdcError::MissingColumn(name.clone()))
+        };
+
+        let value = match &self.columns {
+            None => Value::Object(row.clone()),
+            Some(columns) if columns.len() == 1 => column(&columns[0])?,
+            Some(columns) => Value::Array(columns.iter().map(column).collect::<Result<_, _>>()?),
+        };
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// The kind of a successfully decoded Debezium message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebeziumEvent {
+    /// Row inserted into the table (`"op": "c"`).
+    Create,
+    /// Row read during the initial snapshot of the table (`"op": "r"`).
+    Read,
+    /// Row updated (`"op": "u"`).
+    Update,
+    /// Row deleted (`"op": "d"`).
+    Delete,
+    /// Schema change message.  Produces no updates.
+    SchemaChange,
+    /// Tombstone that follows a delete event in Kafka topics with log
+    /// compaction.  Produces no updates.
+    Tombstone,
+}
+
+/// Error decoding a CDC message.
+#[derive(Debug)]
+pub enum CdcError {
+    /// Error reading the message.
+    IO(IOError),
+    /// The message is not valid JSON or a row doesn't match the expected
+    /// type.
+    Json(JsonError),
+    /// The message is valid JSON, but not a valid change event.
+    InvalidEnvelope(String),
+    /// Unsupported operation type, e.g., `"t"` (truncate).
+    UnsupportedOp(String),
+    /// A column selected by a [`RowSchema`] is missing from the row.
+    MissingColumn(String),
+}
+
+impl Display for CdcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self {
+            Self::IO(error) => write!(f, "IO error: '{error}'"),
+            Self::Json(error) => write!(f, "JSON error: '{error}'"),
+            Self::InvalidEnvelope(error) => write!(f, "invalid change event: {error}"),
+            Self::UnsupportedOp(op) => write!(f, "unsupported operation '{op}'"),
+            Self::MissingColumn(column) => write!(f, "missing column '{column}'"),
+        }
+    }
+}
+
+impl StdError for CdcError {}
+
+impl From<IOError> for CdcError {
+    fn from(error: IOError) -> Self {
+        Self::IO(error)
+    }
+}
+
+impl From<JsonError> for CdcError {
+    fn from(error: JsonError) -> Self {
+        Self::Json(error)
+    }
+}
+
+/// A message that could not be decoded.
+#[derive(Debug)]
+pub struct MessageError {
+    /// Position of the message in the sequence of messages processed by the
+    /// decoder, starting from 0.
+    pub position: usize,
+    /// The message.
+    pub message: String,
+    /// The error.
+    pub error: CdcError,
+}
+
+/// Decoder of Debezium change events in JSON format.
+///
+/// Converts change events into weighted `(key, (value, weight))` tuples that
+/// can be appended to the input handle of an indexed Z-set (see
+/// [`add_input_indexed_zset`](`crate::RootCircuit::add_input_indexed_zset`)).
+/// Keys and values are extracted from table rows according to the key and
+/// value [`RowSchema`]s and deserialized using `serde`.
+///
+/// Inserts (`"op": "c"`) and snapshot reads (`"op": "r"`) insert the new row,
+/// deletes (`"op": "d"`) retract the old row, and updates (`"op": "u"`)
+/// retract the old row and insert the new one.  Messages with and without
+/// the `schema` section are accepted.  Schema change messages and tombstones
+/// are skipped.
+///
+/// [`decode_batch`](`Self::decode_batch`) reports messages that fail to
+/// decode to the error channel returned by [`errors`](`Self::errors`)
+/// instead of failing the entire batch.
+pub struct DebeziumDecoder<K, V> {
+    key_schema: RowSchema,
+    value_schema: RowSchema,
+    // Number of messages processed by `decode_batch`.
+    position: usize,
+    error_sender: Sender<MessageError>,
+    error_receiver: Receiver<MessageError>,
+    _phantom: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> DebeziumDecoder<K, V>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+{
+    /// Create a decoder that extracts keys and values from table rows
+    /// according to `key_schema` and `value_schema` respectively.
+    pub fn new(key_schema: RowSchema, value_schema: RowSchema) -> Self {
+        let (error_sender, error_receiver) = unbounded();
+
+        Self {
+            key_schema,
+            value_schema,
+            position: 0,
+            error_sender,
+            error_receiver,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the receiving end of the channel where
+    /// [`decode_batch`](`Self::decode_batch`) reports messages it failed to
+    /// decode.
+    pub fn errors(&self) -> Receiver<MessageError> {
+        self.error_receiver.clone()
+    }
+
+    /// Decode a single message, appending the resulting updates to `tuples`.
+    ///
+    /// Doesn't modify `tuples` if the message cannot be decoded.
+    pub fn decode(
+        &self,
+        message: &str,
+        tuples: &mut Vec<(K, (V, isize))>,
+    ) -> Result<DebeziumEvent, CdcError> {
+        let payload = match serde_json::from_str::<Value>(message)? {
+            // Messages with schemas enabled carry the change event in the
+            // `payload` field.
+            Value::Object(mut envelope)
+                if envelope.contains_key("schema") && envelope.contains_key("payload") =>
+            {
+                envelope.remove("payload").unwrap()
+            }
+            envelope => envelope,
+        };
+
+        let payload = match payload {
+            Value::Null => return Ok(DebeziumEvent::Tombstone),
+            Value::Object(payload) => payload,
+            _ => {
+                return Err(CdcError::InvalidEnvelope(
+                    "change event is not a JSON object".to_string(),
+                ))
+            }
+        };
+
+        let op = match payload.get("op") {
+            Some(Value::String(op)) => op.as_str(),
+            Some(_) => {
+                return Err(CdcError::InvalidEnvelope(
+                    "'op' field is not a string".to_string(),
+                ))
+            }
+            None if payload.contains_key("ddl") || payload.contains_key("tableChanges") => {
+                return Ok(DebeziumEvent::SchemaChange)
+            }
+            None => return Err(CdcError::InvalidEnvelope("missing 'op' field".to_string())),
+        };
+
+        match op {
+            "c" | "r" => {
+                let (key, val) = self.decode_row(&payload, "after")?;
+                tuples.push((key, (val, 1)));
+
+                Ok(if op == "c" {
+                    DebeziumEvent::Create
+                } else {
+                    DebeziumEvent::Read
+                })
+            }
+            "u" => {
+                let (old_key, old_val) = self.decode_row(&payload, "before")?;
+                let (new_key, new_val) = self.decode_row(&payload, "after")?;
+                tuples.push((old_key, (old_val, -1)));
+                tuples.push((new_key, (new_val, 1)));
+
+                Ok(DebeziumEvent::Update)
+            }
+            "d" => {
+                let (key, val) = self.decode_row(&payload, "before")?;
+                tuples.push((key, (val, -1)));
+
+                Ok(DebeziumEvent::Delete)
+            }
+            op => Err(CdcError::UnsupportedOp(op.to_string())),
+        }
+    }
+
+    /// Decode a batch of messages, appending the resulting updates to
+    /// `tuples`.
+    ///
+    /// Messages that cannot be decoded are sent to the error channel.
+    /// Returns the number of messages decoded successfully.
+    pub fn decode_batch<I, S>(&mut self, messages: I, tuples: &mut Vec<(K, (V, isize))>) -> usize
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut decoded = 0;
+
+        for message in messages {
+            let message = message.as_ref();
+            match self.decode(message, tuples) {
+                Ok(_) => decoded += 1,
+                Err(error) => self.report_error(message.to_string(), error),
+            }
+            self.position += 1;
+        }
+
+        decoded
+    }
+
+    fn decode_row(&self, payload: &Map<String, Value>, field: &str) -> Result<(K, V), CdcError> {
+        match payload.get(field) {
+            Some(Value::Object(row)) => Ok((
+                self.key_schema.extract(row)?,
+                self.value_schema.extract(row)?,
+            )),
+            _ => Err(CdcError::InvalidEnvelope(format!("missing '{field}' row"))),
+        }
+    }
+
+    fn report_error(&self, message: String, error: CdcError) {
+        // The decoder owns a receiver, so the channel cannot be disconnected.
+        let _ = self.error_sender.send(MessageError {
+            position: self.position,
+            message,
+            error,
+        });
+    }
+}
+
+/// Feeds Debezium change events from a stream of messages to an input
+/// handle.
+///
+/// Each call to [`step`](`Self::step`) decodes the next batch of messages
+/// and appends the resulting updates to the handle, to be consumed by the
+/// next step of the circuit.
+pub struct DebeziumInput<K, V, I>
+where
+    K: DBData,
+    V: DBData,
+{
+    decoder: DebeziumDecoder<K, V>,
+    handle: CollectionHandle<K, (V, isize)>,
+    messages: I,
+    messages_per_step: usize,
+    exhausted: bool,
+}
+
+impl<K, V, I> DebeziumInput<K, V, I>
+where
+    K: DBData + DeserializeOwned,
+    V: DBData + DeserializeOwned,
+    I: Iterator<Item = Result<String, IOError>>,
+{
+    /// Create a driver that reads up to `messages_per_step` messages from
+    /// `messages` per step, decodes them using `decoder` and appends the
+    /// updates to `handle`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `messages_per_step` is 0.
+    pub fn new(
+        decoder: DebeziumDecoder<K, V>,
+        handle: CollectionHandle<K, (V, isize)>,
+        messages: I,
+        messages_per_step: usize,
+    ) -> Self {
+        assert_ne!(messages_per_step, 0);
+
+        Self {
+            decoder,
+            handle,
+            messages,
+            messages_per_step,
+            exhausted: false,
+        }
+    }
+
+    /// Returns the decoder used by this driver.
+    pub fn decoder(&self) -> &DebeziumDecoder<K, V> {
+        &self.decoder
+    }
+
+    /// Feed the next batch of messages to the input handle.
+    ///
+    /// Empty messages are ignored.  Messages that cannot be decoded are
+    /// reported to the decoder's [error channel](`DebeziumDecoder::errors`).
+    /// An error reading the next message is reported to the error channel
+    /// and ends the input.
+    ///
+    /// Returns the number of messages consumed.  Returns 0 once the input is
+    /// exhausted.
+    pub fn step(&mut self) -> usize {
+        let mut messages = Vec::with_capacity(self.messages_per_step);
+        let mut consumed = 0;
+
+        while !self.exhausted && consumed < self.messages_per_step {
+            match self.messages.next() {
+                None => self.exhausted = true,
+                Some(Err(error)) => {
+                    self.exhausted = true;
+                    self.decoder.report_error(String::new(), error.into());
+                }
+                Some(Ok(message)) => {
+                    consumed += 1;
+                    if !message.trim().is_empty() {
+                        messages.push(message);
+                    }
+                }
+            }
+        }
+
+        let mut tuples = Vec::new();
+        self.decoder.decode_batch(messages, &mut tuples);
+        self.handle.append(&mut tuples);
+
+        consumed
+    }
+}
+
+impl<K, V, R> DebeziumInput<K, V, Lines<R>>
+where
+    K: DBData + DeserializeOwned,
+    V: DBData + DeserializeOwned,
+    R: BufRead,
+{
+    /// Create a driver that reads newline-delimited messages from `reader`.
+    ///
+    /// See [`new`](`Self::new`).
+    pub fn from_reader(
+        decoder: DebeziumDecoder<K, V>,
+        handle: CollectionHandle<K, (V, isize)>,
+        reader: R,
+        messages_per_step: usize,
+    ) -> Self {
+        Self::new(decoder, handle, reader.lines(), messages_per_step)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CdcError, DebeziumDecoder, DebeziumEvent, DebeziumInput, RowSchema};
+    use crate::{indexed_zset, RootCircuit};
+    use serde::Deserialize;
+    use std::io::Cursor;
+
+    // Events captured from the Debezium PostgreSQL connector, with and
+    // without schemas enabled.
+    const SNAPSHOT: &str = r#"{"schema":{"type":"struct","fields":[{"type":"struct","fields":[{"type":"int32","optional":false,"field":"id"},{"type":"string","optional":false,"field":"first_name"},{"type":"string","optional":false,"field":"email"}],"optional":true,"name":"dbserver1.inventory.customers.Value","field":"before"},{"type":"struct","fields":[{"type":"int32","optional":false,"field":"id"},{"type":"string","optional":false,"field":"first_name"},{"type":"string","optional":false,"field":"email"}],"optional":true,"name":"dbserver1.inventory.customers.Value","field":"after"},{"type":"string","optional":false,"field":"op"},{"type":"int64","optional":true,"field":"ts_ms"}],"optional":false,"name":"dbserver1.inventory.customers.Envelope"},"payload":{"before":null,"after":{"id":1001,"first_name":"Sally","email":"sally.thomas@acme.com"},"source":{"version":"2.1.2.Final","connector":"postgresql","name":"dbserver1","ts_ms":1675898410136,"snapshot":"true","db":"postgres","schema":"inventory","table":"customers","txId":767,"lsn":34280352},"op":"r","ts_ms":1675898410139,"transaction":null}}"#;
+    const CREATE: &str = r#"{"before":null,"after":{"id":1005,"first_name":"Sarah","email":"sarah@example.com"},"source":{"version":"2.1.2.Final","connector":"postgresql","name":"dbserver1","ts_ms":1675898520317,"snapshot":"false","db":"postgres","schema":"inventory","table":"customers","txId":768,"lsn":34281024},"op":"c","ts_ms":1675898520752,"transaction":null}"#;
+    const UPDATE: &str = r#"{"before":{"id":1001,"first_name":"Sally","email":"sally.thomas@acme.com"},"after":{"id":1001,"first_name":"Sally","email":"sally@example.com"},"source":{"version":"2.1.2.Final","connector":"postgresql","name":"dbserver1","ts_ms":1675898560110,"snapshot":"false","db":"postgres","schema":"inventory","table":"customers","txId":769,"lsn":34281480},"op":"u","ts_ms":1675898560406,"transaction":null}"#;
+    const DELETE: &str = r#"{"before":{"id":1005,"first_name":"Sarah","email":"sarah@example.com"},"after":null,"source":{"version":"2.1.2.Final","connector":"postgresql","name":"dbserver1","ts_ms":1675898601450,"snapshot":"false","db":"postgres","schema":"inventory","table":"customers","txId":770,"lsn":34281872},"op":"d","ts_ms":1675898601871,"transaction":null}"#;
+    const TOMBSTONE: &str = "null";
+    const SCHEMA_CHANGE: &str = r#"{"source":{"version":"2.1.2.Final","connector":"mysql","name":"dbserver1","ts_ms":1675898650000,"snapshot":"false","db":"inventory","table":"customers"},"databaseName":"inventory","schemaName":null,"ddl":"ALTER TABLE customers ADD COLUMN phone VARCHAR(255)","tableChanges":[]}"#;
+    // Insert after the schema change that added the `phone` column.
+    const CREATE_WITH_NEW_COLUMN: &str = r#"{"before":null,"after":{"id":1006,"first_name":"Bob","email":"bob@example.com","phone":"555-0100"},"source":{"version":"2.1.2.Final","connector":"postgresql","name":"dbserver1","ts_ms":1675898700000,"snapshot":"false","db":"postgres","schema":"inventory","table":"customers","txId":771,"lsn":34282264},"op":"c","ts_ms":1675898700112,"transaction":null}"#;
+
+    const MALFORMED_JSON: &str = r#"{"before":null,"after":{"id":1007"#;
+    const MISSING_OP: &str =
+        r#"{"before":null,"after":{"id":1007,"first_name":"Ann","email":"ann@example.com"}}"#;
+    const TRUNCATE: &str = r#"{"before":null,"after":null,"op":"t","ts_ms":1675898800000}"#;
+    const WRONG_TYPE: &str = r#"{"before":null,"after":{"id":"1008","first_name":"Joe","email":"joe@example.com"},"op":"c"}"#;
+    const MISSING_COLUMN: &str =
+        r#"{"before":null,"after":{"id":1009,"first_name":"Eve"},"op":"c"}"#;
+    const UPDATE_WITHOUT_BEFORE: &str = r#"{"before":null,"after":{"id":1001,"first_name":"Sally","email":"sally@acme.com"},"op":"u"}"#;
+
+    type Customers = DebeziumDecoder<i64, (String, String)>;
+
+    fn customers() -> Customers {
+        DebeziumDecoder::new(
+            RowSchema::columns(["id"]),
+            RowSchema::columns(["first_name", "email"]),
+        )
+    }
+
+    fn customer(first_name: &str, email: &str) -> (String, String) {
+        (first_name.to_string(), email.to_string())
+    }
+
+    #[test]
+    fn debezium_ops() {
+        let decoder = customers();
+        let mut tuples = Vec::new();
+
+        assert_eq!(
+            decoder.decode(SNAPSHOT, &mut tuples).unwrap(),
+            DebeziumEvent::Read
+        );
+        assert_eq!(
+            decoder.decode(CREATE, &mut tuples).unwrap(),
+            DebeziumEvent::Create
+        );
+        assert_eq!(
+            decoder.decode(UPDATE, &mut tuples).unwrap(),
+            DebeziumEvent::Update
+        );
+        assert_eq!(
+            decoder.decode(DELETE, &mut tuples).unwrap(),
+            DebeziumEvent::Delete
+        );
+        assert_eq!(
+            decoder.decode(TOMBSTONE, &mut tuples).unwrap(),
+            DebeziumEvent::Tombstone
+        );
+        assert_eq!(
+            decoder.decode(SCHEMA_CHANGE, &mut tuples).unwrap(),
+            DebeziumEvent::SchemaChange
+        );
+        assert_eq!(
+            decoder.decode(CREATE_WITH_NEW_COLUMN, &mut tuples).unwrap(),
+            DebeziumEvent::Create
+        );
+
+        assert_eq!(
+            tuples,
+            vec![
+                (1001, (customer("Sally", "sally.thomas@acme.com"), 1)),
+                (1005, (customer("Sarah", "sarah@example.com"), 1)),
+                (1001, (customer("Sally", "sally.thomas@acme.com"), -1)),
+                (1001, (customer("Sally", "sally@example.com"), 1)),
+                (1005, (customer("Sarah", "sarah@example.com"), -1)),
+                (1006, (customer("Bob", "bob@example.com"), 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn debezium_struct() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct Customer {
+            id: i64,
+            first_name: String,
+            email: String,
+        }
+
+        let decoder =
+            DebeziumDecoder::<i64, Customer>::new(RowSchema::columns(["id"]), RowSchema::all());
+        let mut tuples = Vec::new();
+
+        decoder.decode(UPDATE, &mut tuples).unwrap();
+        decoder.decode(CREATE_WITH_NEW_COLUMN, &mut tuples).unwrap();
+
+        let customer = |id, first_name: &str, email: &str| Customer {
+            id,
+            first_name: first_name.to_string(),
+            email: email.to_string(),
+        };
+        assert_eq!(
+            tuples,
+            vec![
+                (1001, (customer(1001, "Sally", "sally.thomas@acme.com"), -1)),
+                (1001, (customer(1001, "Sally", "sally@example.com"), 1)),
+                (1006, (customer(1006, "Bob", "bob@example.com"), 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn debezium_malformed() {
+        let mut decoder = customers();
+        let errors = decoder.errors();
+        let mut tuples = Vec::new();
+
+        let decoded = decoder.decode_batch(
+            [
+                CREATE,
+                MALFORMED_JSON,
+                MISSING_OP,
+                TRUNCATE,
+                WRONG_TYPE,
+                MISSING_COLUMN,
+                UPDATE_WITHOUT_BEFORE,
+                DELETE,
+            ],
+            &mut tuples,
+        );
+
+        // Malformed messages don't affect the rest of the batch.
+        assert_eq!(decoded, 2);
+        assert_eq!(
+            tuples,
+            vec![
+                (1005, (customer("Sarah", "sarah@example.com"), 1)),
+                (1005, (customer("Sarah", "sarah@example.com"), -1)),
+            ]
+        );
+
+        let errors: Vec<_> = errors.try_iter().collect();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.position)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(errors[0].message, MALFORMED_JSON);
+        assert!(matches!(errors[0].error, CdcError::Json(_)));
+        assert!(matches!(errors[1].error, CdcError::InvalidEnvelope(_)));
+        assert!(matches!(&errors[2].error, CdcError::UnsupportedOp(op) if op == "t"));
+        assert!(matches!(errors[3].error, CdcError::Json(_)));
+        assert!(matches!(&errors[4].error, CdcError::MissingColumn(column) if column == "email"));
+        assert!(matches!(errors[5].error, CdcError::InvalidEnvelope(_)));
+    }
+
+    #[test]
+    fn debezium_input() {
+        let (circuit, (input_handle, output)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) =
+                circuit.add_input_indexed_zset::<i64, (String, String), isize>();
+            (input_handle, input.integrate().output())
+        })
+        .unwrap();
+
+        let messages = [
+            SNAPSHOT,
+            "",
+            CREATE,
+            MALFORMED_JSON,
+            UPDATE,
+            SCHEMA_CHANGE,
+            DELETE,
+            TOMBSTONE,
+            CREATE_WITH_NEW_COLUMN,
+        ]
+        .join("\n");
+
+        let mut input =
+            DebeziumInput::from_reader(customers(), input_handle, Cursor::new(messages), 4);
+        let errors = input.decoder().errors();
+
+        assert_eq!(input.step(), 4);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1001 => { customer("Sally", "sally.thomas@acme.com") => 1 },
+                1005 => { customer("Sarah", "sarah@example.com") => 1 },
+            }
+        );
+        assert_eq!(errors.try_iter().count(), 1);
+
+        assert_eq!(input.step(), 4);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 1001 => { customer("Sally", "sally@example.com") => 1 } }
+        );
+
+        assert_eq!(input.step(), 1);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1001 => { customer("Sally", "sally@example.com") => 1 },
+                1006 => { customer("Bob", "bob@example.com") => 1 },
+            }
+        );
+
+        assert_eq!(input.step(), 0);
+        assert_eq!(errors.try_iter().count(), 0);
+    }
+}
@@ -29,11 +29,13 @@
 use crate::{
     circuit::{
         cache::{CircuitCache, CircuitStoreMarker},
-        metadata::OperatorMeta,
+        metadata::{MetaItem, OperatorMeta},
+        metrics::{MetricLabels, STEPS},
         operator_traits::{
             BinaryOperator, Data, ImportOperator, NaryOperator, QuaternaryOperator, SinkOperator,
             SourceOperator, StrictUnaryOperator, TernaryOperator, UnaryOperator,
         },
+        runtime::BootstrapGuard,
         schedule::{
             DynamicScheduler, Error as SchedulerError, Executor, IterativeExecutor, OnceExecutor,
             Scheduler,
@@ -45,6 +47,7 @@ use crate::{
     time::{Timestamp, UnitTimestamp},
     Runtime,
 };
+use itertools::Itertools;
 use std::{
     borrow::Cow,
     cell::{Ref, RefCell, RefMut, UnsafeCell},
@@ -59,6 +62,9 @@ use std::{
 };
 use typedmap::{TypedMap, TypedMapKey};
 
+#[cfg(feature = "tracing-spans")]
+use crate::circuit::spans::EnteredSpans;
+
 /// Value stored in the stream.
 struct StreamValue<D> {
     /// Value written to the stream at the current clock cycle;
@@ -334,6 +340,21 @@ pub trait Node {
     fn fixedpoint(&self, scope: Scope) -> bool;
 
     fn map_nodes_recursive(&self, _f: &mut dyn FnMut(&dyn Node)) {}
+
+    /// `true` if the node can be fused with its neighbors in a linear chain
+    /// of operators (see [`CircuitConfig::fuse_linear_chains`]).
+    ///
+    /// Only synchronous nodes with a single input stream and a single output
+    /// stream can be fused.
+    fn is_fusible(&self) -> bool {
+        false
+    }
+
+    /// `true` if the node has been absorbed by a fused operator and is no
+    /// longer evaluated by the scheduler.
+    fn is_absorbed(&self) -> bool {
+        false
+    }
 }
 
 /// Id of an operator, guaranteed to be unique within a circuit.
@@ -722,6 +743,12 @@ pub trait Circuit: WithClock + Clone + 'static {
 
     fn is_async_node(&self, id: NodeId) -> bool;
 
+    /// `true` if the node with the given id has been absorbed by a fused
+    /// operator (see [`CircuitConfig::fuse_linear_chains`]).  Absorbed nodes
+    /// are not connected to any other nodes and must not be evaluated by the
+    /// scheduler.
+    fn is_absorbed_node(&self, id: NodeId) -> bool;
+
     /// Evaluate operator with the given id.
     ///
     /// This method should only be used by schedulers.
@@ -1239,6 +1266,13 @@ where
     circuit_event_handlers: CircuitEventHandlers,
     scheduler_event_handlers: SchedulerEventHandlers,
     store: CircuitCache,
+    // Names of the regions that contain the nodes currently being added to
+    // the circuit, outermost first.
+    #[cfg(feature = "tracing-spans")]
+    region_stack: Vec<String>,
+    // Regions that contain each node in `nodes`.
+    #[cfg(feature = "tracing-spans")]
+    node_regions: Vec<Vec<String>>,
 }
 
 impl<P> CircuitInner<P>
@@ -1263,6 +1297,10 @@ where
             circuit_event_handlers,
             scheduler_event_handlers,
             store: TypedMap::new(),
+            #[cfg(feature = "tracing-spans")]
+            region_stack: Vec::new(),
+            #[cfg(feature = "tracing-spans")]
+            node_regions: Vec::new(),
         }
     }
 
@@ -1275,10 +1313,33 @@ where
         N: Node + 'static,
     {
         self.nodes.push(Box::new(node) as Box<dyn Node>);
+        #[cfg(feature = "tracing-spans")]
+        self.node_regions.push(self.region_stack.clone());
+    }
+
+    /// Enter `region` spans for the regions that contain node `id` followed
+    /// by an `operator` span for the node.
+    #[cfg(feature = "tracing-spans")]
+    fn enter_node_spans(&self, id: NodeId) -> EnteredSpans {
+        let node = self.nodes[id.0].as_ref();
+        let mut spans = EnteredSpans::default();
+
+        for region in self.node_regions[id.0].iter() {
+            spans.push(tracing::trace_span!("region", name = %region));
+        }
+        spans.push(tracing::trace_span!(
+            "operator",
+            name = %node.name(),
+            node_id = %node.global_id()
+        ));
+
+        spans
     }
 
     fn clear(&mut self) {
         self.nodes.clear();
+        #[cfg(feature = "tracing-spans")]
+        self.node_regions.clear();
         self.edges.clear();
         self.store.clear();
     }
@@ -1329,6 +1390,85 @@ where
         }
     }
 
+    /// Fuse linear chains of operators into [`FusedNode`]s.
+    ///
+    /// Two nodes are chained if the output of the first node is only
+    /// consumed by the second node, and the second node has no other inputs
+    /// or dependencies.  Each maximal chain is replaced by a fused node that
+    /// takes over the id of its last node.  The other nodes in the chain are
+    /// replaced with [`AbsorbedNode`] placeholders and edges between them are
+    /// removed, while edges to the first node in the chain are redirected
+    /// to the fused node.
+    ///
+    /// Returns the ids of the nodes in each chain, in evaluation order.
+    fn fuse_linear_chains(&mut self) -> Vec<Vec<NodeId>> {
+        let num_nodes = self.nodes.len();
+        let mut num_inputs = vec![0; num_nodes];
+        let mut num_outputs = vec![0; num_nodes];
+
+        for edge in self.edges.iter() {
+            num_outputs[edge.from.0] += 1;
+            num_inputs[edge.to.0] += 1;
+        }
+
+        // `next[i]` is the node that node `i` is chained to.
+        let mut next = vec![None; num_nodes];
+        let mut has_prev = vec![false; num_nodes];
+
+        for edge in self.edges.iter() {
+            let (from, to) = (edge.from.0, edge.to.0);
+            if edge.is_stream()
+                && from != to
+                && edge.origin == self.global_node_id.child(edge.from)
+                && num_outputs[from] == 1
+                && num_inputs[to] == 1
+                && self.nodes[from].is_fusible()
+                && self.nodes[to].is_fusible()
+            {
+                next[from] = Some(to);
+                has_prev[to] = true;
+            }
+        }
+
+        let mut chains = Vec::new();
+
+        for head in 0..num_nodes {
+            if has_prev[head] || next[head].is_none() {
+                continue;
+            }
+
+            let mut chain = vec![head];
+            while let Some(node) = next[*chain.last().unwrap()] {
+                chain.push(node);
+            }
+
+            let tail = *chain.last().unwrap();
+            let nodes = chain
+                .iter()
+                .map(|&i| {
+                    let id = self.nodes[i].global_id().clone();
+                    std::mem::replace(&mut self.nodes[i], Box::new(AbsorbedNode::new(id)))
+                })
+                .collect();
+            self.nodes[tail] = Box::new(FusedNode::new(nodes));
+
+            chains.push(chain.into_iter().map(NodeId).collect::<Vec<_>>());
+        }
+
+        self.edges
+            .retain(|edge| next[edge.from.0] != Some(edge.to.0));
+        for chain in chains.iter() {
+            let (head, tail) = (chain[0], *chain.last().unwrap());
+            for edge in self.edges.iter_mut() {
+                if edge.to == head {
+                    edge.to = tail;
+                }
+            }
+        }
+
+        chains
+    }
+
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.nodes.iter().all(|node| {
             node.fixedpoint(scope)
@@ -1382,6 +1522,64 @@ where
     }
 }
 
+/// Options that control how [`RootCircuit::build_with_config`] prepares a
+/// circuit for execution.
+///
+/// # Examples
+///
+/// ```
+/// use dbsp::{CircuitConfig, RootCircuit};
+///
+/// let config = CircuitConfig::new().fuse_linear_chains(true);
+///
+/// let (circuit, ()) = RootCircuit::build_with_config(config, |_circuit| ()).unwrap();
+/// circuit.step().unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitConfig {
+    fuse_linear_chains: bool,
+}
+
+impl CircuitConfig {
+    /// Creates the default configuration, with all optimizations disabled.
+    pub fn new() -> Self {
+        Self {
+            fuse_linear_chains: false,
+        }
+    }
+
+    /// Fuses linear chains of operators into single operators once the
+    /// circuit has been constructed.  Disabled by default.
+    ///
+    /// A chain is a sequence of synchronous unary operators, such as
+    /// [`map`](`crate::operator::FilterMap::map`),
+    /// [`filter`](`crate::operator::FilterMap::filter`), or
+    /// [`map_index`](`crate::operator::FilterMap::map_index`), where each operator is the
+    /// only consumer of the previous operator's output.  The scheduler
+    /// evaluates a fused chain as a single node, invoking the operators
+    /// back-to-back and passing each intermediate batch to the next operator
+    /// by value.  This saves the scheduling overhead of the individual
+    /// operators and allows them to reuse the allocations of their inputs.
+    ///
+    /// The fused operator is named after its constituents, e.g.,
+    /// `Fused(Map, FilterKeys)`, in circuit visualizations and profiles, and
+    /// reports its constituents' metadata.  Since a fused chain is profiled
+    /// as a whole, fusion is best left disabled when looking for the most
+    /// expensive operators in a circuit.
+    ///
+    /// Only operators in the root circuit are fused.
+    pub fn fuse_linear_chains(mut self, fuse_linear_chains: bool) -> Self {
+        self.fuse_linear_chains = fuse_linear_chains;
+        self
+    }
+}
+
+impl Default for CircuitConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RootCircuit {
     /// Create a circuit and prepare it for execution.
     ///
@@ -1414,12 +1612,44 @@ impl RootCircuit {
     pub fn build_with_scheduler<F, T, S>(
         constructor: F,
     ) -> Result<(CircuitHandle, T), SchedulerError>
+    where
+        F: FnOnce(&mut RootCircuit) -> T,
+        S: Scheduler + 'static,
+    {
+        Self::build_with_config_and_scheduler::<F, T, S>(CircuitConfig::new(), constructor)
+    }
+
+    /// Create a circuit and prepare it for execution.
+    ///
+    /// Similar to [`build`](`Self::build`), but applies the optimizations
+    /// enabled in `config` to the circuit after `constructor` returns.
+    pub fn build_with_config<F, T>(
+        config: CircuitConfig,
+        constructor: F,
+    ) -> Result<(CircuitHandle, T), SchedulerError>
+    where
+        F: FnOnce(&mut RootCircuit) -> T,
+    {
+        Self::build_with_config_and_scheduler::<F, T, DynamicScheduler>(config, constructor)
+    }
+
+    /// Create a circuit and prepare it for execution.
+    ///
+    /// Similar to [`build_with_config`](`Self::build_with_config`), but with
+    /// a user-specified [`Scheduler`] implementation.
+    pub fn build_with_config_and_scheduler<F, T, S>(
+        config: CircuitConfig,
+        constructor: F,
+    ) -> Result<(CircuitHandle, T), SchedulerError>
     where
         F: FnOnce(&mut RootCircuit) -> T,
         S: Scheduler + 'static,
     {
         let mut circuit = RootCircuit::new();
         let res = constructor(&mut circuit);
+        if config.fuse_linear_chains {
+            circuit.fuse_linear_chains();
+        }
         let executor =
             Box::new(<OnceExecutor<S>>::new(&circuit)?) as Box<dyn Executor<RootCircuit>>;
 
@@ -1429,11 +1659,39 @@ impl RootCircuit {
         // scratch.
         circuit.log_scheduler_event(&SchedulerEvent::clock_start());
         circuit.clock_start(0);
-        Ok((CircuitHandle { circuit, executor }, res))
+        Ok((
+            CircuitHandle {
+                circuit,
+                executor,
+                #[cfg(feature = "tracing-spans")]
+                steps: std::cell::Cell::new(0),
+            },
+            res,
+        ))
     }
 }
 
 impl RootCircuit {
+    // Fuse linear chains of operators (see
+    // [`CircuitConfig::fuse_linear_chains`]) and notify circuit event handlers
+    // about the fused operators.
+    fn fuse_linear_chains(&self) {
+        let chains = self.inner_mut().fuse_linear_chains();
+
+        for chain in chains.into_iter() {
+            let (tail, absorbed) = chain.split_last().unwrap();
+            let name = self.inner().nodes[tail.0].name();
+            self.log_circuit_event(&CircuitEvent::fused_operators(
+                GlobalNodeId::child_of(self, *tail),
+                name,
+                absorbed
+                    .iter()
+                    .map(|id| GlobalNodeId::child_of(self, *id))
+                    .collect(),
+            ));
+        }
+    }
+
     // Create new top-level circuit.  Clients invoke this via the
     // [`RootCircuit::build`] API.
     fn new() -> Self {
@@ -1795,6 +2053,10 @@ where
         self.inner().nodes[id.0].is_async()
     }
 
+    fn is_absorbed_node(&self, id: NodeId) -> bool {
+        self.inner().nodes[id.0].is_absorbed()
+    }
+
     fn eval_node(&self, id: NodeId) -> Result<(), SchedulerError> {
         let mut circuit = self.inner_mut();
         debug_assert!(id.0 < circuit.nodes.len());
@@ -1805,6 +2067,9 @@ where
         // optimization.
         circuit.log_scheduler_event(&SchedulerEvent::eval_start(circuit.nodes[id.0].as_ref()));
 
+        #[cfg(feature = "tracing-spans")]
+        let spans = circuit.enter_node_spans(id);
+
         // Safety: `eval` cannot invoke the
         // `eval` method of another node.  To circumvent
         // this invariant the user would have to extract a
@@ -1813,6 +2078,9 @@ where
         // streams.
         unsafe { circuit.nodes[id.0].eval()? };
 
+        #[cfg(feature = "tracing-spans")]
+        drop(spans);
+
         circuit.log_scheduler_event(&SchedulerEvent::eval_end(circuit.nodes[id.0].as_ref()));
 
         Ok(())
@@ -1824,7 +2092,11 @@ where
         F: FnOnce() -> T,
     {
         self.log_circuit_event(&CircuitEvent::push_region(name, Some(Location::caller())));
+        #[cfg(feature = "tracing-spans")]
+        self.inner_mut().region_stack.push(name.to_string());
         let res = f();
+        #[cfg(feature = "tracing-spans")]
+        self.inner_mut().region_stack.pop();
         self.log_circuit_event(&CircuitEvent::pop_region());
         res
     }
@@ -2737,6 +3009,154 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_fusible(&self) -> bool {
+        !self.operator.is_async()
+    }
+}
+
+/// A chain of nodes evaluated back-to-back as a single node.
+///
+/// Created by [`CircuitConfig::fuse_linear_chains`].  Each node in the chain
+/// is the only consumer of the previous node's output, so evaluating the
+/// chain in one step hands every intermediate value to the next operator by
+/// value and bypasses the scheduler.  The fused node takes over the id of the
+/// last node in the chain.
+struct FusedNode {
+    id: GlobalNodeId,
+    name: Cow<'static, str>,
+    nodes: Vec<Box<dyn Node>>,
+}
+
+impl FusedNode {
+    fn new(nodes: Vec<Box<dyn Node>>) -> Self {
+        debug_assert!(nodes.len() > 1);
+
+        let name = format!(
+            "Fused({})",
+            nodes.iter().map(|node| node.name()).format(", ")
+        );
+
+        Self {
+            id: nodes.last().unwrap().global_id().clone(),
+            name: Cow::Owned(name),
+            nodes,
+        }
+    }
+}
+
+impl Node for FusedNode {
+    fn name(&self) -> Cow<'static, str> {
+        self.name.clone()
+    }
+
+    fn local_id(&self) -> NodeId {
+        self.id.local_node_id().unwrap()
+    }
+
+    fn global_id(&self) -> &GlobalNodeId {
+        &self.id
+    }
+
+    fn is_async(&self) -> bool {
+        false
+    }
+
+    fn ready(&self) -> bool {
+        true
+    }
+
+    unsafe fn eval(&mut self) -> Result<(), SchedulerError> {
+        for node in self.nodes.iter_mut() {
+            node.eval()?;
+        }
+        Ok(())
+    }
+
+    fn clock_start(&mut self, scope: Scope) {
+        for node in self.nodes.iter_mut() {
+            node.clock_start(scope);
+        }
+    }
+
+    unsafe fn clock_end(&mut self, scope: Scope) {
+        for node in self.nodes.iter_mut() {
+            node.clock_end(scope);
+        }
+    }
+
+    fn metadata(&self, output: &mut OperatorMeta) {
+        for node in self.nodes.iter() {
+            let mut meta = OperatorMeta::new();
+            node.metadata(&mut meta);
+            if !meta.is_empty() {
+                output.push((
+                    Cow::Owned(format!("{} ({})", node.name(), node.global_id())),
+                    MetaItem::Map(meta),
+                ));
+            }
+        }
+    }
+
+    fn fixedpoint(&self, scope: Scope) -> bool {
+        self.nodes.iter().all(|node| node.fixedpoint(scope))
+    }
+
+    fn is_fusible(&self) -> bool {
+        true
+    }
+}
+
+/// Placeholder left behind by a node absorbed by a [`FusedNode`], so that
+/// the ids of the remaining nodes in the circuit stay unchanged.
+struct AbsorbedNode {
+    id: GlobalNodeId,
+}
+
+impl AbsorbedNode {
+    fn new(id: GlobalNodeId) -> Self {
+        Self { id }
+    }
+}
+
+impl Node for AbsorbedNode {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Absorbed")
+    }
+
+    fn local_id(&self) -> NodeId {
+        self.id.local_node_id().unwrap()
+    }
+
+    fn global_id(&self) -> &GlobalNodeId {
+        &self.id
+    }
+
+    fn is_async(&self) -> bool {
+        false
+    }
+
+    fn ready(&self) -> bool {
+        true
+    }
+
+    unsafe fn eval(&mut self) -> Result<(), SchedulerError> {
+        Ok(())
+    }
+
+    fn clock_start(&mut self, _scope: Scope) {}
+
+    unsafe fn clock_end(&mut self, _scope: Scope) {}
+
+    fn metadata(&self, _output: &mut OperatorMeta) {}
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn is_absorbed(&self) -> bool {
+        true
+    }
 }
 
 struct SinkNode<C, I, Op> {
@@ -3604,6 +4024,29 @@ where
         self.executor.run(&self.circuit)
     }
 
+    /// Evaluate the circuit for one clock cycle in bootstrap mode.
+    ///
+    /// Used to initialize the circuit from an existing snapshot of its
+    /// inputs, e.g., pushed to input handles using
+    /// [`CollectionHandle::preload`](`crate::CollectionHandle::preload`),
+    /// without flooding the consumers of the circuit with the entire contents
+    /// of every view derived from the snapshot.
+    ///
+    /// The bootstrap step is an ordinary clock cycle: all operators are
+    /// evaluated as usual, so integrals, traces, delayed values and feedback
+    /// loops capture the snapshot and subsequent steps produce only the
+    /// changes caused by new inputs.  The only difference is that
+    /// [output handles](`crate::OutputHandle`) don't receive any values
+    /// during the bootstrap step (see
+    /// [`Runtime::bootstrap_in_progress`](`crate::Runtime::bootstrap_in_progress`)).
+    ///
+    /// The bootstrap step is normally the first step of the circuit, but
+    /// this is not required.
+    pub fn bootstrap(&self) -> Result<(), SchedulerError> {
+        let _guard = BootstrapGuard::new();
+        self.step()
+    }
+
     fn clock_start(&mut self, scope: Scope) {
         self.circuit.clock_start(scope + 1);
     }
@@ -3627,6 +4070,9 @@ where
 pub struct CircuitHandle {
     circuit: RootCircuit,
     executor: Box<dyn Executor<RootCircuit>>,
+    // Number of steps evaluated so far, reported in `step` spans.
+    #[cfg(feature = "tracing-spans")]
+    steps: std::cell::Cell<u64>,
 }
 
 impl Drop for CircuitHandle {
@@ -3659,6 +4105,14 @@ impl CircuitHandle {
         // TODO: Add a runtime check to prevent re-entering this method from an
         // operator.
 
+        metric_counter!(STEPS, 1, MetricLabels::worker());
+
+        #[cfg(feature = "tracing-spans")]
+        let _span = {
+            let step = self.steps.replace(self.steps.get() + 1);
+            tracing::debug_span!("step", worker = Runtime::worker_index(), step).entered()
+        };
+
         self.executor.run(&self.circuit)
     }
 
@@ -3691,10 +4145,14 @@ impl CircuitHandle {
 #[cfg(test)]
 mod tests {
     use crate::{
-        circuit::schedule::{DynamicScheduler, Scheduler, StaticScheduler},
+        circuit::{
+            schedule::{DynamicScheduler, Scheduler, StaticScheduler},
+            trace::SchedulerEvent,
+        },
+        indexed_zset,
         monitor::TraceMonitor,
-        operator::{Generator, Z1},
-        Circuit, RootCircuit,
+        operator::{FilterMap, Generator, Z1},
+        Circuit, CircuitConfig, OrdIndexedZSet, RootCircuit,
     };
     use std::{cell::RefCell, ops::Deref, rc::Rc, vec::Vec};
 
@@ -3744,6 +4202,58 @@ mod tests {
         assert_eq!(&expected_output, actual_output.borrow().deref());
     }
 
+    // Operators with higher priorities are evaluated first, as long as their
+    // dependencies allow it.
+    #[test]
+    fn priority_circuit_static() {
+        priority_circuit::<StaticScheduler>();
+    }
+
+    #[test]
+    fn priority_circuit_dynamic() {
+        priority_circuit::<DynamicScheduler>();
+    }
+
+    fn priority_circuit<S>()
+    where
+        S: Scheduler + 'static,
+    {
+        let log: Rc<RefCell<Vec<&'static str>>> = Rc::new(RefCell::new(Vec::new()));
+        let log_clone = log.clone();
+
+        let circuit = RootCircuit::build_with_scheduler::<_, _, S>(move |circuit| {
+            let record = |name: &'static str| {
+                let log = log_clone.clone();
+                move |_: &usize| log.borrow_mut().push(name)
+            };
+
+            let source = circuit.add_source(Generator::new(|| 0usize));
+
+            // `b1` inherits the priority of its consumer `b2`.
+            let b1 = source.inspect(record("b1")).set_priority(1);
+            b1.inspect(record("b2")).set_priority(5);
+
+            source.inspect(record("c")).set_priority(10);
+
+            // `d` has the highest priority, but can only be evaluated after
+            // `a1` and `a2`, which inherit its priority.
+            let a = source.inspect(record("a1")).inspect(record("a2"));
+            let d = a.inspect(record("d")).set_priority(20);
+            assert_eq!(d.priority(), 20);
+            assert_eq!(a.priority(), 0);
+        })
+        .unwrap()
+        .0;
+
+        for _ in 0..3 {
+            circuit.step().unwrap();
+            assert_eq!(
+                log.borrow_mut().drain(..).collect::<Vec<_>>(),
+                vec!["a1", "a2", "d", "c", "b1", "b2"]
+            );
+        }
+    }
+
     // Recursive circuit
     #[test]
     fn recursive_sum_circuit_static() {
@@ -3863,4 +4373,117 @@ mod tests {
             n * my_factorial(n - 1)
         }
     }
+
+    // Fusing a linear chain of operators doesn't affect the output of the
+    // circuit, but shrinks the number of nodes evaluated at each step.
+    #[test]
+    fn fuse_linear_chains_static() {
+        fuse_linear_chains::<StaticScheduler>();
+    }
+
+    #[test]
+    fn fuse_linear_chains_dynamic() {
+        fuse_linear_chains::<DynamicScheduler>();
+    }
+
+    fn fuse_linear_chains<S>()
+    where
+        S: Scheduler + 'static,
+    {
+        let (unfused, unfused_evals, unfused_dot) = fused_chain_circuit::<S>(false);
+        let (fused, fused_evals, fused_dot) = fused_chain_circuit::<S>(true);
+
+        assert_eq!(unfused, fused);
+        assert_eq!(
+            fused[1],
+            indexed_zset! { 0 => {30 => 1}, 1 => {36 => 1}, 2 => {42 => 1}, 3 => {48 => 1}, 4 => {54 => 1} }
+        );
+
+        // Two out of three operators in the chain are absorbed by the fused
+        // operator.
+        assert_eq!(fused_evals + 2 * 3, unfused_evals);
+
+        assert!(!unfused_dot.contains("Fused"));
+        assert!(fused_dot.contains("Fused(MapKeys, FilterKeys, Map)"));
+    }
+
+    // Evaluate a circuit with a `map -> filter -> map_index` chain for three
+    // steps.  Returns the outputs of the chain, the number of operators
+    // evaluated, and the circuit in dot format.
+    fn fused_chain_circuit<S>(
+        fuse_linear_chains: bool,
+    ) -> (Vec<OrdIndexedZSet<u64, u64, isize>>, usize, String)
+    where
+        S: Scheduler + 'static,
+    {
+        let monitor = TraceMonitor::new_panic_on_error();
+        let evals = Rc::new(RefCell::new(0));
+        let evals_clone = evals.clone();
+
+        let (circuit, (input_handle, output_handle)) =
+            RootCircuit::build_with_config_and_scheduler::<_, _, S>(
+                CircuitConfig::new().fuse_linear_chains(fuse_linear_chains),
+                |circuit| {
+                    monitor.attach(circuit, "monitor");
+                    circuit.register_scheduler_event_handler("evals", move |event| {
+                        if let SchedulerEvent::EvalStart { .. } = event {
+                            *evals_clone.borrow_mut() += 1;
+                        }
+                    });
+
+                    let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+                    let output_handle = input
+                        .map(|x| x * 3)
+                        .filter(|x| x % 2 == 0)
+                        .map_index(|x| (x % 5, *x))
+                        .output();
+
+                    (input_handle, output_handle)
+                },
+            )
+            .unwrap();
+
+        let mut outputs = Vec::new();
+        for step in 0..3 {
+            for x in step * 10..step * 10 + 10 {
+                input_handle.push(x, 1);
+            }
+            circuit.step().unwrap();
+            outputs.push(output_handle.consolidate());
+        }
+
+        (outputs, evals.take(), monitor.visualize_circuit().to_dot())
+    }
+
+    // Operators whose output is consumed by multiple operators are not fused.
+    #[test]
+    fn fuse_linear_chains_fan_out() {
+        let evals = |fuse_linear_chains| {
+            let evals = Rc::new(RefCell::new(0));
+            let evals_clone = evals.clone();
+
+            let (circuit, ()) = RootCircuit::build_with_config(
+                CircuitConfig::new().fuse_linear_chains(fuse_linear_chains),
+                |circuit| {
+                    TraceMonitor::new_panic_on_error().attach(circuit, "monitor");
+                    circuit.register_scheduler_event_handler("evals", move |event| {
+                        if let SchedulerEvent::EvalStart { .. } = event {
+                            *evals_clone.borrow_mut() += 1;
+                        }
+                    });
+
+                    let (input, _input_handle) = circuit.add_input_zset::<u64, isize>();
+                    let mapped = input.map(|x| x + 1);
+                    mapped.filter(|x| x % 2 == 0).output();
+                    mapped.filter(|x| x % 3 == 0).output();
+                },
+            )
+            .unwrap();
+
+            circuit.step().unwrap();
+            evals.take()
+        };
+
+        assert_eq!(evals(false), evals(true));
+    }
 }
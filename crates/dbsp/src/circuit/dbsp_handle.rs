@@ -1,13 +1,15 @@
+#[cfg(feature = "with-serde")]
+use crate::operator::{export::export_snapshot, ExportTarget, SnapshotExport};
 use crate::{
-    circuit::runtime::RuntimeHandle, profile::Profiler, Error as DBSPError, RootCircuit, Runtime,
-    RuntimeError, SchedulerError,
+    circuit::runtime::{RuntimeConfig, RuntimeHandle},
+    profile::{FoldedCPUProfile, Profiler},
+    Error as DBSPError, RootCircuit, Runtime, RuntimeError, SchedulerError,
 };
 use crossbeam::channel::{bounded, Receiver, Sender, TryRecvError};
 use std::{
     fs,
     fs::create_dir_all,
     path::{Path, PathBuf},
-    thread::Result as ThreadResult,
     time::Instant,
 };
 
@@ -38,6 +40,30 @@ impl Runtime {
         F: FnOnce(&mut RootCircuit) -> T + Clone + Send + 'static,
         T: Clone + Send + 'static,
     {
+        Self::init(RuntimeConfig::new().workers(nworkers), constructor)
+    }
+
+    /// Instantiate a circuit in a multithreaded runtime described by `config`.
+    ///
+    /// Behaves like [`Runtime::init_circuit`], except that the number of
+    /// workers and the other properties of the runtime are taken from
+    /// `config`.  Creates the configured storage directory if it doesn't
+    /// exist yet.
+    ///
+    /// Returns [`RuntimeError::ConstructorPanic`] if `constructor` panics in
+    /// any of the workers, and [`RuntimeError::WorkerSpawn`] if a worker
+    /// thread could not be spawned.
+    pub fn init<F, T>(config: RuntimeConfig, constructor: F) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut RootCircuit) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+    {
+        if let Some(storage_dir) = config.storage_dir_path() {
+            create_dir_all(storage_dir)?;
+        }
+        let nworkers = config.num_workers();
+        let circuit_config = config.circuit_options().clone();
+
         // When a worker finishes building the circuit, it sends completion status back
         // to us via this channel.  The function returns after receiving a
         // notification from each worker.
@@ -52,7 +78,7 @@ impl Runtime {
         let (status_senders, status_receivers): (Vec<_>, Vec<_>) =
             (0..nworkers).map(|_| bounded(1)).unzip();
 
-        let runtime = Self::run(nworkers, move || {
+        let runtime = Self::run_with_config(config, move || {
             let worker_index = Runtime::worker_index();
 
             // Drop all but one channels.  This makes sure that if one of the worker panics
@@ -61,11 +87,12 @@ impl Runtime {
             let status_sender = status_senders.into_iter().nth(worker_index).unwrap();
             let command_receiver = command_receivers.into_iter().nth(worker_index).unwrap();
 
-            let (circuit, profiler) = match RootCircuit::build(|circuit| {
+            let build_result = RootCircuit::build_with_config(circuit_config, |circuit| {
                 let profiler = Profiler::new(circuit);
                 let res = constructor(circuit);
                 (res, profiler)
-            }) {
+            });
+            let (circuit, profiler) = match build_result {
                 Ok((circuit, (res, profiler))) => {
                     if init_sender.send(Ok(res)).is_err() {
                         return;
@@ -92,6 +119,12 @@ impl Runtime {
                             return;
                         }
                     }
+                    Ok(Command::Bootstrap) => {
+                        let status = circuit.bootstrap().map(|_| Response::Unit);
+                        if status_sender.send(status).is_err() {
+                            return;
+                        }
+                    }
                     Ok(Command::EnableProfiler) => {
                         profiler.enable_cpu_profiler();
                         // Send response.
@@ -99,6 +132,22 @@ impl Runtime {
                             return;
                         }
                     }
+                    Ok(Command::CPUProfile) => {
+                        if status_sender
+                            .send(Ok(Response::CPUProfile(profiler.cpu_profile())))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(Command::UsedBytes) => {
+                        if status_sender
+                            .send(Ok(Response::UsedBytes(profiler.used_bytes())))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
                     Ok(Command::DumpProfile) => {
                         if status_sender
                             .send(Ok(Response::Profile(profiler.dump_profile())))
@@ -122,7 +171,7 @@ impl Runtime {
                     }
                 }
             }
-        });
+        })?;
 
         // Receive initialization status from all workers.
 
@@ -134,9 +183,9 @@ impl Runtime {
                     init_status.push(Err(DBSPError::Scheduler(scheduler_error)))
                 }
                 Ok(Ok(ret)) => init_status.push(Ok(ret)),
-                Err(_) => {
-                    init_status.push(Err(DBSPError::Runtime(RuntimeError::WorkerPanic(worker))))
-                }
+                Err(_) => init_status.push(Err(DBSPError::Runtime(
+                    RuntimeError::ConstructorPanic(worker),
+                ))),
             }
         }
 
@@ -161,13 +210,18 @@ impl Runtime {
 #[derive(Clone)]
 enum Command {
     Step,
+    Bootstrap,
     EnableProfiler,
     DumpProfile,
+    CPUProfile,
+    UsedBytes,
 }
 
 enum Response {
     Unit,
     Profile(String),
+    CPUProfile(FoldedCPUProfile),
+    UsedBytes(usize),
 }
 
 /// A handle to control the execution of a circuit in a multithreaded runtime.
@@ -181,6 +235,8 @@ pub struct DBSPHandle {
     // Channels used to receive command completion status from
     // workers.
     status_receivers: Vec<Receiver<Result<Response, SchedulerError>>>,
+    // Number of clock cycles evaluated so far.
+    steps: u64,
 }
 
 impl DBSPHandle {
@@ -194,13 +250,26 @@ impl DBSPHandle {
             runtime: Some(runtime),
             command_senders,
             status_receivers,
+            steps: 0,
         }
     }
 
-    fn kill_inner(&mut self) -> ThreadResult<()> {
+    fn kill_inner(&mut self) -> Result<(), DBSPError> {
         self.command_senders.clear();
         self.status_receivers.clear();
-        self.runtime.take().unwrap().kill()
+        self.runtime.take().unwrap().kill()?;
+        Ok(())
+    }
+
+    /// Kills the runtime after losing the connection to `worker`.
+    ///
+    /// Returns the first worker that panicked, if any, or reports `worker` as
+    /// disconnected otherwise.
+    fn worker_failed(&mut self, worker: usize) -> DBSPError {
+        match self.kill_inner() {
+            Ok(()) => DBSPError::Runtime(RuntimeError::WorkerDisconnected(worker)),
+            Err(error) => error,
+        }
     }
 
     fn broadcast_command<F>(&mut self, command: Command, mut handler: F) -> Result<(), DBSPError>
@@ -214,8 +283,7 @@ impl DBSPHandle {
         // Send command.
         for (worker, sender) in self.command_senders.iter().enumerate() {
             if matches!(sender.send(command.clone()), Err(_)) {
-                let _ = self.kill_inner();
-                return Err(DBSPError::Runtime(RuntimeError::WorkerPanic(worker)));
+                return Err(self.worker_failed(worker));
             }
             self.runtime.as_ref().unwrap().unpark_worker(worker);
         }
@@ -223,10 +291,7 @@ impl DBSPHandle {
         // Receive responses.
         for (worker, receiver) in self.status_receivers.iter().enumerate() {
             match receiver.recv() {
-                Err(_) => {
-                    let _ = self.kill_inner();
-                    return Err(DBSPError::Runtime(RuntimeError::WorkerPanic(worker)));
-                }
+                Err(_) => return Err(self.worker_failed(worker)),
                 Ok(Err(e)) => {
                     let _ = self.kill_inner();
                     return Err(DBSPError::Scheduler(e));
@@ -243,8 +308,52 @@ impl DBSPHandle {
     }
 
     /// Evaluate the circuit for one clock cycle.
+    ///
+    /// Returns [`RuntimeError::WorkerPanic`] if a worker panics, after which
+    /// the circuit is killed and all further operations on the handle fail
+    /// with [`RuntimeError::Killed`].
     pub fn step(&mut self) -> Result<(), DBSPError> {
-        self.broadcast_command(Command::Step, |_| {})
+        self.broadcast_command(Command::Step, |_| {})?;
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// Evaluate the circuit for one clock cycle in bootstrap mode.
+    ///
+    /// Output handles don't receive the outputs of the bootstrap step.  See
+    /// [`CircuitHandle::bootstrap`](`crate::CircuitHandle::bootstrap`).
+    pub fn bootstrap(&mut self) -> Result<(), DBSPError> {
+        self.broadcast_command(Command::Bootstrap, |_| {})?;
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// Export a consistent snapshot of several collections to `dir`.
+    ///
+    /// Must be invoked between clock cycles.  Takes a snapshot of the
+    /// contents of each collection in `targets` (see
+    /// [`Stream::export_target`](`crate::Stream::export_target`)) as of the
+    /// last clock cycle, and writes them to `dir` in a background thread, so
+    /// that the circuit can keep running while the export is in progress.
+    /// Taking the snapshot only requires cloning references to immutable
+    /// batches.
+    ///
+    /// Creates `dir` if it doesn't exist.  Each collection is written to a
+    /// file named after the target, consolidated across workers.  Once all
+    /// files have been written, the export writes an
+    /// [`ExportManifest`](`crate::operator::ExportManifest`) recording the
+    /// step number, and the number of tuples and checksum of each file.
+    #[cfg(feature = "with-serde")]
+    pub fn export_snapshot<P: AsRef<Path>>(
+        &self,
+        targets: &[ExportTarget],
+        dir: P,
+    ) -> Result<SnapshotExport, DBSPError> {
+        if self.runtime.is_none() {
+            return Err(DBSPError::Runtime(RuntimeError::Killed));
+        }
+
+        export_snapshot(targets, dir.as_ref(), self.steps)
     }
 
     /// Enable CPU profiler.
@@ -285,15 +394,51 @@ impl DBSPHandle {
         Ok(dir_path)
     }
 
+    /// CPU profile of the circuit aggregated across all worker threads.
+    ///
+    /// The profile attributes the time spent evaluating each operator to the
+    /// hierarchy of subcircuits and regions that contain it and can be
+    /// written out in the folded stacks format for flamegraph tools using
+    /// [`FoldedCPUProfile::to_folded`].  It is empty unless CPU profiling was
+    /// enabled using [`Self::enable_cpu_profiler`].
+    pub fn cpu_profile(&mut self) -> Result<FoldedCPUProfile, DBSPError> {
+        let mut profile = FoldedCPUProfile::new();
+
+        self.broadcast_command(Command::CPUProfile, |resp| {
+            if let Response::CPUProfile(worker_profile) = resp {
+                profile.merge(&worker_profile);
+            }
+        })?;
+
+        Ok(profile)
+    }
+
+    /// Total number of bytes used by the state of all operators (e.g.,
+    /// traces) across all worker threads.
+    ///
+    /// This method computes the size of all operator state on each call and
+    /// can be expensive for circuits with large traces.
+    pub fn used_bytes(&mut self) -> Result<usize, DBSPError> {
+        let mut used_bytes = 0;
+
+        self.broadcast_command(Command::UsedBytes, |resp| {
+            if let Response::UsedBytes(bytes) = resp {
+                used_bytes += bytes;
+            }
+        })?;
+
+        Ok(used_bytes)
+    }
+
     /// Terminate the execution of the circuit, exiting all worker threads.
     ///
-    /// If one or more of the worker threads panics, returns the argument the
-    /// `panic!` macro was called with (see `std::thread::Result`).
+    /// If one or more of the worker threads panicked, returns
+    /// [`RuntimeError::WorkerPanic`] for the first of them.
     ///
     /// This is the preferred way of killing a circuit.  Simply dropping the
     /// handle will have the same effect, but without reporting the error
     /// status.
-    pub fn kill(mut self) -> ThreadResult<()> {
+    pub fn kill(mut self) -> Result<(), DBSPError> {
         if self.runtime.is_none() {
             return Ok(());
         }
@@ -312,7 +457,51 @@ impl Drop for DBSPHandle {
 
 #[cfg(test)]
 mod tests {
-    use crate::{operator::Generator, Circuit, Error as DBSPError, Runtime, RuntimeError};
+    use crate::{
+        indexed_zset,
+        operator::Generator,
+        profile::FoldedCPUProfile,
+        trace::{Batch, BatchReader},
+        Circuit, Error as DBSPError, OrdZSet, Runtime, RuntimeConfig, RuntimeError,
+    };
+    use std::{
+        hint::spin_loop,
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    // Workers are named after the configured prefix.
+    #[test]
+    fn test_init_with_config() {
+        let names = Arc::new(Mutex::new(Vec::new()));
+
+        let config = RuntimeConfig::new()
+            .workers(4)
+            .thread_name_prefix("test-worker");
+        let (mut handle, _) = Runtime::init(config, {
+            let names = names.clone();
+            move |_circuit| {
+                let name = thread::current().name().unwrap().to_string();
+                names.lock().unwrap().push(name);
+            }
+        })
+        .unwrap();
+        handle.step().unwrap();
+        handle.kill().unwrap();
+
+        let mut names = names.lock().unwrap().clone();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "test-worker-0",
+                "test-worker-1",
+                "test-worker-2",
+                "test-worker-3"
+            ]
+        );
+    }
 
     // Panic during initialization in worker thread.
     #[test]
@@ -334,8 +523,13 @@ mod tests {
             circuit.add_source(Generator::new(|| 5usize));
         });
 
-        if let DBSPError::Runtime(err) = res.unwrap_err() {
-            assert_eq!(err, RuntimeError::WorkerPanic(0));
+        let error = res.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "runtime error: 'worker thread '0' panicked while constructing the circuit'"
+        );
+        if let DBSPError::Runtime(err) = error {
+            assert_eq!(err, RuntimeError::ConstructorPanic(0));
         } else {
             panic!();
         }
@@ -373,6 +567,18 @@ mod tests {
         } else {
             panic!();
         }
+
+        // The runtime has been killed after the panic.
+        if let DBSPError::Runtime(err) = handle.step().unwrap_err() {
+            assert_eq!(err, RuntimeError::Killed);
+        } else {
+            panic!();
+        }
+        assert!(matches!(
+            handle.used_bytes(),
+            Err(DBSPError::Runtime(RuntimeError::Killed))
+        ));
+        handle.kill().unwrap();
     }
 
     // Kill the runtime.
@@ -400,6 +606,127 @@ mod tests {
         handle.kill().unwrap();
     }
 
+    #[test]
+    fn test_used_bytes1() {
+        test_used_bytes(1);
+    }
+
+    #[test]
+    fn test_used_bytes4() {
+        test_used_bytes(4);
+    }
+
+    fn test_used_bytes(nworkers: usize) {
+        let (mut handle, mut input_handle) = Runtime::init_circuit(nworkers, |circuit| {
+            let (stream, input_handle) = circuit.add_input_zset::<u64, isize>();
+            stream.integrate_trace();
+            input_handle
+        })
+        .unwrap();
+
+        handle.step().unwrap();
+        let empty_bytes = handle.used_bytes().unwrap();
+
+        input_handle.append(&mut (0..1000).map(|x| (x, 1)).collect());
+        handle.step().unwrap();
+        assert!(handle.used_bytes().unwrap() > empty_bytes);
+
+        handle.kill().unwrap();
+    }
+
+    #[test]
+    fn test_bootstrap1() {
+        test_bootstrap(1);
+    }
+
+    #[test]
+    fn test_bootstrap4() {
+        test_bootstrap(4);
+    }
+
+    fn test_bootstrap(nworkers: usize) {
+        let (mut handle, (mut input_handle, counts, contents)) =
+            Runtime::init_circuit(nworkers, |circuit| {
+                let (stream, input_handle) = circuit.add_input_zset::<u64, isize>();
+                let counts = stream
+                    .index_with(|x| (x % 3, ()))
+                    .aggregate_linear(|_key, _val| 1isize);
+
+                (input_handle, counts.output(), stream.integrate().output())
+            })
+            .unwrap();
+
+        // The outputs of the bootstrap step are not delivered to output
+        // handles.
+        input_handle.preload(&mut (0..1000).map(|x| (x, 1)).collect());
+        handle.bootstrap().unwrap();
+        assert!(counts.consolidate().is_empty());
+        assert!(contents.consolidate().is_empty());
+
+        // Subsequent steps only output changes caused by new inputs, while
+        // integrals contain the snapshot.
+        input_handle.append(&mut vec![(1000, 1), (0, -1)]);
+        handle.step().unwrap();
+        assert_eq!(
+            counts.consolidate(),
+            indexed_zset! { 0 => { 334 => -1, 333 => 1 }, 1 => { 333 => -1, 334 => 1 } }
+        );
+        assert_eq!(
+            contents.consolidate(),
+            OrdZSet::from_keys((), (1..=1000).map(|x| (x, 1)).collect())
+        );
+
+        handle.kill().unwrap();
+    }
+
+    #[test]
+    fn test_cpu_profile1() {
+        test_cpu_profile(1);
+    }
+
+    #[test]
+    fn test_cpu_profile4() {
+        test_cpu_profile(4);
+    }
+
+    // A busy-looping operator dominates the CPU profile.
+    fn test_cpu_profile(nworkers: usize) {
+        let (mut handle, _) = Runtime::init_circuit(nworkers, |circuit| {
+            let stream = circuit.add_source(Generator::new(|| 5usize));
+            stream.inspect(|_| {});
+            circuit.region("slow", || {
+                stream.inspect(|_| {
+                    let start = Instant::now();
+                    while start.elapsed() < Duration::from_millis(10) {
+                        spin_loop();
+                    }
+                });
+            });
+        })
+        .unwrap();
+
+        // The profile is empty until the profiler is enabled.
+        handle.step().unwrap();
+        assert_eq!(handle.cpu_profile().unwrap(), FoldedCPUProfile::new());
+
+        handle.enable_cpu_profiler().unwrap();
+        for _ in 0..3 {
+            handle.step().unwrap();
+        }
+
+        let profile = handle.cpu_profile().unwrap();
+        let (stack, time) = profile.stacks().max_by_key(|(_, time)| *time).unwrap();
+        assert_eq!(stack, "slow;Inspect");
+        assert!(time * 2 > profile.total_time());
+        assert!(profile.stacks().any(|(stack, _)| stack == "Inspect"));
+        assert!(profile
+            .to_folded()
+            .lines()
+            .any(|line| line.starts_with("slow;Inspect ")));
+
+        handle.kill().unwrap();
+    }
+
     // Drop the runtime.
     #[test]
     fn test_drop1() {
@@ -0,0 +1,241 @@
+//! Runtime metrics published via the [`metrics`](https://docs.rs/metrics)
+//! crate facade.
+//!
+//! When the `metrics` feature is enabled, DBSP reports the metrics listed in
+//! this module to the recorder installed by the application, e.g., a
+//! Prometheus exporter.  When the feature is disabled, the instrumentation
+//! compiles to nothing.
+//!
+//! All metrics are labeled with the index of the worker thread (`worker`).
+//! Metrics that describe an individual stream are additionally labeled with
+//! the name of the operator that reports them (`operator`) and the global id
+//! of the node that produces the stream (`stream`, e.g., `[0.3]`).
+
+use crate::{circuit::GlobalNodeId, Runtime};
+
+/// Number of clock cycles evaluated by the worker (counter).
+pub const STEPS: &str = "dbsp_steps_total";
+
+/// Number of tuples pushed to an input stream via
+/// [`CollectionHandle`](`crate::CollectionHandle`) or
+/// [`UpsertHandle`](`crate::UpsertHandle`) (counter).  The `worker` label
+/// identifies the worker the tuples were sent to.
+pub const INPUT_TUPLES: &str = "dbsp_input_tuples_total";
+
+/// Number of tuples written to an [`OutputHandle`](`crate::OutputHandle`)
+/// (counter), whichever method they are later read with.  The `worker` label
+/// identifies the worker that produced the tuples.
+pub const OUTPUT_TUPLES: &str = "dbsp_output_tuples_total";
+
+/// Number of entries in a trace maintained by a
+/// [`Z1Trace`](`crate::operator::Z1Trace`) operator (gauge).
+pub const TRACE_ENTRIES: &str = "dbsp_trace_entries";
+
+/// Number of batch merges started by traces (counter).
+pub const TRACE_MERGES: &str = "dbsp_trace_merges_total";
+
+/// Number of bytes sent by the worker to other workers while sharding a
+/// stream (counter).
+pub const EXCHANGE_BYTES: &str = "dbsp_exchange_bytes_total";
+
+/// Increment counter `$name` by `$value`.
+///
+/// Arguments are only evaluated when the `metrics` feature is enabled, but
+/// are type-checked either way.
+macro_rules! metric_counter {
+    ($name:expr, $value:expr, $labels:expr) => {{
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!($name, $value as u64, $labels);
+        #[cfg(not(feature = "metrics"))]
+        if false {
+            let _ = ($name, $value, $labels);
+        }
+    }};
+}
+
+/// Set gauge `$name` to `$value`.
+///
+/// Arguments are only evaluated when the `metrics` feature is enabled, but
+/// are type-checked either way.
+macro_rules! metric_gauge {
+    ($name:expr, $value:expr, $labels:expr) => {{
+        #[cfg(feature = "metrics")]
+        ::metrics::gauge!($name, $value as f64, $labels);
+        #[cfg(not(feature = "metrics"))]
+        if false {
+            let _ = ($name, $value, $labels);
+        }
+    }};
+}
+
+/// Labels attached to a metric.
+///
+/// This type is zero-sized when the `metrics` feature is disabled, so
+/// operators and handles can store their labels unconditionally.
+#[derive(Clone, Default)]
+pub(crate) struct MetricLabels {
+    #[cfg(feature = "metrics")]
+    labels: Vec<::metrics::Label>,
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+impl MetricLabels {
+    /// Labels of a metric that describes the current worker.
+    pub(crate) fn worker() -> Self {
+        Self::default().for_worker(Runtime::worker_index())
+    }
+
+    /// Labels of a metric reported by `operator` running in the current
+    /// worker for stream `stream`.
+    pub(crate) fn stream(operator: &str, stream: &GlobalNodeId) -> Self {
+        let labels = Self {
+            #[cfg(feature = "metrics")]
+            labels: vec![
+                ::metrics::Label::new("operator", operator.to_string()),
+                ::metrics::Label::new("stream", stream.to_string()),
+            ],
+        };
+
+        labels.for_worker(Runtime::worker_index())
+    }
+
+    /// Returns a copy of `self` with the `worker` label set to `worker`.
+    pub(crate) fn for_worker(&self, worker: usize) -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            labels: std::iter::once(::metrics::Label::new("worker", worker.to_string()))
+                .chain(
+                    self.labels
+                        .iter()
+                        .filter(|label| label.key() != "worker")
+                        .cloned(),
+                )
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl ::metrics::IntoLabels for MetricLabels {
+    fn into_labels(self) -> Vec<::metrics::Label> {
+        self.labels
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl ::metrics::IntoLabels for &MetricLabels {
+    fn into_labels(self) -> Vec<::metrics::Label> {
+        self.labels.clone()
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod test {
+    use super::{EXCHANGE_BYTES, INPUT_TUPLES, OUTPUT_TUPLES, STEPS, TRACE_ENTRIES, TRACE_MERGES};
+    use crate::{trace::BatchReader, Runtime};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    // The recorder is global, so other tests running concurrently can report
+    // metrics too.  We only check label sets and lower bounds on counters.
+    #[test]
+    fn metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        recorder.install().unwrap();
+
+        let (mut dbsp, (mut input, output, input_stream, sharded_stream)) =
+            Runtime::init_circuit(2, |circuit| {
+                let (stream, handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+                let sharded = stream.shard();
+                sharded.integrate_trace();
+
+                (
+                    handle,
+                    sharded.output(),
+                    stream.origin_node_id().to_string(),
+                    sharded.origin_node_id().to_string(),
+                )
+            })
+            .unwrap();
+
+        for step in 0..5 {
+            let mut tuples = (0..100).map(|i| (i, (step, 1))).collect();
+            input.append(&mut tuples);
+            dbsp.step().unwrap();
+            let batches = output.take_from_all();
+            assert_eq!(batches.iter().map(|batch| batch.len()).sum::<usize>(), 100);
+        }
+
+        dbsp.kill().unwrap();
+
+        // Label names of each metric and values of each (metric, labels) pair.
+        let mut label_names: BTreeMap<String, BTreeSet<Vec<String>>> = BTreeMap::new();
+        let mut values: BTreeMap<(String, Vec<(String, String)>), DebugValue> = BTreeMap::new();
+
+        for (key, _unit, _description, value) in snapshotter.snapshot().into_vec() {
+            let key = key.key();
+            let mut labels: Vec<(String, String)> = key
+                .labels()
+                .map(|label| (label.key().to_string(), label.value().to_string()))
+                .collect();
+            labels.sort();
+
+            label_names
+                .entry(key.name().to_string())
+                .or_default()
+                .insert(labels.iter().map(|(name, _)| name.clone()).collect());
+            values.insert((key.name().to_string(), labels), value);
+        }
+
+        let worker = |worker: usize| vec![("worker".to_string(), worker.to_string())];
+        let stream = |operator: &str, stream: &str, worker: usize| {
+            vec![
+                ("operator".to_string(), operator.to_string()),
+                ("stream".to_string(), stream.to_string()),
+                ("worker".to_string(), worker.to_string()),
+            ]
+        };
+        let counter = |name: &str, labels: Vec<(String, String)>| match values
+            .get(&(name.to_string(), labels.clone()))
+        {
+            Some(DebugValue::Counter(value)) => *value,
+            value => panic!("unexpected value of {name} {labels:?}: {value:?}"),
+        };
+
+        let worker_labels = BTreeSet::from([vec!["worker".to_string()]]);
+        let stream_labels = BTreeSet::from([vec![
+            "operator".to_string(),
+            "stream".to_string(),
+            "worker".to_string(),
+        ]]);
+
+        assert_eq!(label_names[STEPS], worker_labels);
+        assert_eq!(label_names[TRACE_MERGES], worker_labels);
+        assert_eq!(label_names[INPUT_TUPLES], stream_labels);
+        assert_eq!(label_names[OUTPUT_TUPLES], stream_labels);
+        assert_eq!(label_names[TRACE_ENTRIES], stream_labels);
+        assert_eq!(label_names[EXCHANGE_BYTES], stream_labels);
+
+        let mut input_tuples = 0;
+        let mut output_tuples = 0;
+
+        for w in 0..2 {
+            assert!(counter(STEPS, worker(w)) >= 5);
+            assert!(counter(EXCHANGE_BYTES, stream("Shard", &input_stream, w)) > 0);
+            assert!(matches!(
+                values.get(&(
+                    TRACE_ENTRIES.to_string(),
+                    stream("Z1 (trace)", &sharded_stream, w)
+                )),
+                Some(DebugValue::Gauge(_))
+            ));
+
+            input_tuples += counter(INPUT_TUPLES, stream("Input", &input_stream, w));
+            output_tuples += counter(OUTPUT_TUPLES, stream("Output", &sharded_stream, w));
+        }
+
+        assert!(input_tuples >= 500);
+        assert!(output_tuples >= 500);
+    }
+}
@@ -5,6 +5,11 @@
 //! the circuit is triggered, consuming a single value from each of its input
 //! streams and emitting a single value to the output stream.
 
+#[macro_use]
+pub mod metrics;
+#[macro_use]
+pub mod spans;
+
 mod activations;
 mod dbsp_handle;
 
@@ -20,10 +25,13 @@ pub mod trace;
 
 pub use activations::{Activations, Activator};
 pub use circuit_builder::{
-    ChildCircuit, Circuit, CircuitHandle, ExportId, ExportStream, FeedbackConnector, GlobalNodeId,
-    NodeId, OwnershipPreference, RootCircuit, Scope, Stream, WithClock,
+    ChildCircuit, Circuit, CircuitConfig, CircuitHandle, ExportId, ExportStream, FeedbackConnector,
+    GlobalNodeId, NodeId, OwnershipPreference, RootCircuit, Scope, Stream, WithClock,
 };
 pub use dbsp_handle::DBSPHandle;
-pub use runtime::{Error as RuntimeError, LocalStore, LocalStoreMarker, Runtime, RuntimeHandle};
+pub use runtime::{
+    Error as RuntimeError, LocalStore, LocalStoreMarker, Runtime, RuntimeConfig, RuntimeHandle,
+    WorkerPlacement,
+};
 
 pub use schedule::Error as SchedulerError;
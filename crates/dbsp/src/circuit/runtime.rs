@@ -1,37 +1,67 @@
 //! A multithreaded runtime for evaluating DBSP circuits in a data-parallel
 //! fashion.
 
+use crate::{CircuitConfig, ShardHasherKind};
 use crossbeam::channel::bounded;
 use crossbeam_utils::sync::{Parker, Unparker};
 use std::{
     cell::{Cell, RefCell},
+    error::Error as StdError,
     fmt,
     fmt::{Debug, Display, Error as FmtError, Formatter},
+    io,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread::{Builder, JoinHandle, LocalKey, Result as ThreadResult},
 };
 use typedmap::{TypedDashMap, TypedMapKey};
 
+/// Runtime errors.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
+    /// The operating system failed to spawn worker thread `worker`.
+    WorkerSpawn { worker: usize, error: String },
+    /// The constructor closure passed to [`Runtime::init_circuit`] panicked
+    /// in worker thread `worker`.
+    ConstructorPanic(usize),
+    /// Worker thread `worker` panicked.
     WorkerPanic(usize),
+    /// Worker thread `worker` disconnected from the client without
+    /// panicking.
+    WorkerDisconnected(usize),
+    /// The circuit has been killed, either by the user or after one of the
+    /// workers failed.
     Killed,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         match self {
+            Self::WorkerSpawn { worker, error } => {
+                write!(f, "failed to spawn worker thread '{worker}': {error}")
+            }
+            Self::ConstructorPanic(worker) => {
+                write!(
+                    f,
+                    "worker thread '{worker}' panicked while constructing the circuit"
+                )
+            }
             Self::WorkerPanic(worker) => {
                 write!(f, "worker thread '{worker}' panicked")
             }
-            Self::Killed => f.write_str("circuit killed by the user"),
+            Self::WorkerDisconnected(worker) => {
+                write!(f, "worker thread '{worker}' disconnected")
+            }
+            Self::Killed => f.write_str("circuit has been killed"),
         }
     }
 }
 
+impl StdError for Error {}
+
 // Thread-local variables used by the termination protocol.
 thread_local! {
     // Parker that must be used by all schedulers within the worker
@@ -54,6 +84,25 @@ thread_local! {
     // Returns `0` if the current thread in not running in a multithreaded
     // runtime.
     pub(crate) static WORKER_INDEX: Cell<usize> = Cell::new(0);
+
+    // Set to `true` while the current thread evaluates a bootstrap step (see
+    // `CircuitHandle::bootstrap`).
+    static BOOTSTRAP: Cell<bool> = Cell::new(false);
+}
+
+/// Marks the current thread as evaluating a bootstrap step until dropped.
+pub(crate) struct BootstrapGuard(bool);
+
+impl BootstrapGuard {
+    pub(crate) fn new() -> Self {
+        Self(BOOTSTRAP.with(|bootstrap| bootstrap.replace(true)))
+    }
+}
+
+impl Drop for BootstrapGuard {
+    fn drop(&mut self) {
+        BOOTSTRAP.with(|bootstrap| bootstrap.set(self.0));
+    }
 }
 
 pub struct LocalStoreMarker;
@@ -61,8 +110,192 @@ pub struct LocalStoreMarker;
 /// Local data store shared by all workers in a runtime.
 pub type LocalStore = TypedDashMap<LocalStoreMarker>;
 
+/// Configuration of a multithreaded [`Runtime`].
+///
+/// # Examples
+///
+/// ```
+/// use dbsp::{Runtime, RuntimeConfig};
+///
+/// let config = RuntimeConfig::new()
+///     .workers(4)
+///     .thread_name_prefix("my-circuit");
+///
+/// let (mut dbsp, ()) = Runtime::init(config, |_circuit| ()).unwrap();
+/// dbsp.step().unwrap();
+/// dbsp.kill().unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    workers: usize,
+    thread_name_prefix: String,
+    core_pinning: bool,
+    core_affinity: Vec<usize>,
+    storage_dir: Option<PathBuf>,
+    shard_hasher: ShardHasherKind,
+    circuit_config: CircuitConfig,
+}
+
+impl RuntimeConfig {
+    /// Creates a configuration for a runtime with a single worker thread.
+    pub fn new() -> Self {
+        Self {
+            workers: 1,
+            thread_name_prefix: "dbsp-worker".to_owned(),
+            core_pinning: false,
+            core_affinity: Vec::new(),
+            storage_dir: None,
+            shard_hasher: ShardHasherKind::Default,
+            circuit_config: CircuitConfig::new(),
+        }
+    }
+
+    /// Sets the number of worker threads, defaults to one.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Sets the prefix of worker thread names, worker `N` is named
+    /// `{prefix}-{N}`.  Defaults to `dbsp-worker`.
+    pub fn thread_name_prefix<S>(mut self, thread_name_prefix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.thread_name_prefix = thread_name_prefix.into();
+        self
+    }
+
+    /// Pins each worker thread to its own CPU core, wrapping around when
+    /// there are more workers than cores.  Disabled by default.
+    ///
+    /// Unless cores are chosen explicitly with
+    /// [`core_affinity`](Self::core_affinity), workers fill all the cores of
+    /// one socket before moving on to the next one, which keeps the data
+    /// exchanged between workers within a single NUMA node for as long as
+    /// possible.
+    ///
+    /// Pinning is only supported on Linux, on other platforms workers are
+    /// left unpinned.  Workers that fail to be pinned, e.g., because the
+    /// process is restricted to a subset of cores, log a warning and run
+    /// unpinned.  The placement workers actually ended up with is reported
+    /// by [`Runtime::worker_placement`].
+    pub fn core_pinning(mut self, core_pinning: bool) -> Self {
+        self.core_pinning = core_pinning;
+        self
+    }
+
+    /// Pins worker `N` to core `cores[N % cores.len()]`, enabling core
+    /// pinning.  An empty list of cores falls back to the default placement
+    /// described in [`core_pinning`](Self::core_pinning).
+    pub fn core_affinity(mut self, cores: Vec<usize>) -> Self {
+        self.core_pinning = true;
+        self.core_affinity = cores;
+        self
+    }
+
+    /// Sets the directory used by persistent traces to store their data.
+    /// Defaults to `/tmp`.
+    pub fn storage_dir<P>(mut self, storage_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.storage_dir = Some(storage_dir.into());
+        self
+    }
+
+    /// Selects the hash function used to route records to workers, defaults
+    /// to [`ShardHasherKind::Default`].
+    ///
+    /// All workers use the same hasher, and the assignment of keys to
+    /// workers is part of the state of the circuit, so a circuit must be
+    /// restored from a checkpoint with the same hasher it was created with.
+    /// See [`ShardHasher`](`crate::ShardHasher`) for the stability guarantees
+    /// provided by each hasher.
+    pub fn shard_hasher(mut self, shard_hasher: ShardHasherKind) -> Self {
+        self.shard_hasher = shard_hasher;
+        self
+    }
+
+    /// Sets the options used to prepare the circuit in each worker for
+    /// execution (see [`RootCircuit::build_with_config`]).
+    ///
+    /// [`RootCircuit::build_with_config`]: crate::RootCircuit::build_with_config
+    pub fn circuit_config(mut self, circuit_config: CircuitConfig) -> Self {
+        self.circuit_config = circuit_config;
+        self
+    }
+
+    /// Returns the number of worker threads.
+    pub fn num_workers(&self) -> usize {
+        self.workers
+    }
+
+    pub(crate) fn circuit_options(&self) -> &CircuitConfig {
+        &self.circuit_config
+    }
+
+    pub(crate) fn storage_dir_path(&self) -> Option<&Path> {
+        self.storage_dir.as_deref()
+    }
+
+    /// Returns the core each worker should be pinned to.
+    fn worker_cores(&self) -> Vec<Option<usize>> {
+        if !self.core_pinning {
+            return vec![None; self.workers];
+        }
+
+        let cores = if self.core_affinity.is_empty() {
+            numa_ordered_cores().unwrap_or_else(|error| {
+                tracing::warn!("failed to enumerate cores, workers are unpinned: {error}");
+                Vec::new()
+            })
+        } else {
+            self.core_affinity.clone()
+        };
+
+        if cores.is_empty() {
+            return vec![None; self.workers];
+        }
+
+        (0..self.workers)
+            .map(|worker| Some(cores[worker % cores.len()]))
+            .collect()
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The CPU placement of a worker thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkerPlacement {
+    requested_core: Option<usize>,
+    core: Option<usize>,
+}
+
+impl WorkerPlacement {
+    /// Returns the core the worker was configured to be pinned to, or `None`
+    /// if core pinning is disabled.
+    pub const fn requested_core(&self) -> Option<usize> {
+        self.requested_core
+    }
+
+    /// Returns the core the worker is pinned to, or `None` if it's unpinned,
+    /// either because core pinning is disabled or because pinning failed.
+    pub const fn core(&self) -> Option<usize> {
+        self.core
+    }
+}
+
 struct RuntimeInner {
     nworkers: usize,
+    storage_dir: Option<PathBuf>,
+    shard_hasher: ShardHasherKind,
+    placement: Mutex<Vec<WorkerPlacement>>,
     store: LocalStore,
 }
 
@@ -70,14 +303,20 @@ impl Debug for RuntimeInner {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("RuntimeInner")
             .field("nworkers", &self.nworkers)
+            .field("storage_dir", &self.storage_dir)
+            .field("shard_hasher", &self.shard_hasher)
+            .field("placement", &self.placement)
             .finish()
     }
 }
 
 impl RuntimeInner {
-    fn new(nworkers: usize) -> Self {
+    fn new(config: &RuntimeConfig) -> Self {
         Self {
-            nworkers,
+            nworkers: config.workers,
+            storage_dir: config.storage_dir.clone(),
+            shard_hasher: config.shard_hasher,
+            placement: Mutex::new(vec![WorkerPlacement::default(); config.workers]),
             store: TypedDashMap::new(),
         }
     }
@@ -96,7 +335,9 @@ impl Runtime {
     /// to the `Runtime` as an argument, so that workers can access shared
     /// services provided by the runtime.
     ///
-    /// Returns a handle through which the caller can interact with the runtime.
+    /// Returns a handle through which the caller can interact with the runtime,
+    /// or [`Error::WorkerSpawn`] if a worker thread could not be spawned, in
+    /// which case the workers spawned so far are signalled to exit.
     ///
     /// # Arguments
     ///
@@ -128,27 +369,58 @@ impl Runtime {
     ///     for _ in 0..100 {
     ///         root.step().unwrap();
     ///     }
-    /// });
+    /// })
+    /// .unwrap();
     ///
     /// // Wait for all worker threads to terminate.
     /// hruntime.join().unwrap();
     /// # }
     /// ```
-    pub fn run<F>(workers: usize, circuit: F) -> RuntimeHandle
+    pub fn run<F>(workers: usize, circuit: F) -> Result<RuntimeHandle, Error>
     where
         F: FnOnce() + Clone + Send + 'static,
     {
-        let runtime = Self(Arc::new(RuntimeInner::new(workers)));
+        Self::run_with_config(RuntimeConfig::new().workers(workers), circuit)
+    }
+
+    /// Like [`Runtime::run`], but creates the runtime described by `config`.
+    pub fn run_with_config<F>(config: RuntimeConfig, circuit: F) -> Result<RuntimeHandle, Error>
+    where
+        F: FnOnce() + Clone + Send + 'static,
+    {
+        let workers = config.workers;
+        let runtime = Self(Arc::new(RuntimeInner::new(&config)));
+        let cores = config.worker_cores();
 
         let mut handles = Vec::with_capacity(workers);
-        handles.extend((0..workers).map(|worker_index| {
+        for worker_index in 0..workers {
             let runtime = runtime.clone();
             let build_circuit = circuit.clone();
+            let requested_core = cores[worker_index];
 
             let (init_sender, init_receiver) = bounded(1);
             let join_handle = Builder::new()
-                .name(format!("dbsp-worker-{worker_index}"))
+                .name(format!("{}-{worker_index}", config.thread_name_prefix))
                 .spawn(move || {
+                    // Pin the worker before the main thread can observe its placement
+                    let core = requested_core.filter(|&core| match pin_to_core(core) {
+                        Ok(()) => {
+                            tracing::debug!("pinned worker {worker_index} to core {core}");
+                            true
+                        }
+                        Err(error) => {
+                            tracing::warn!(
+                                "failed to pin worker {worker_index} to core {core}, \
+                                 running unpinned: {error}",
+                            );
+                            false
+                        }
+                    });
+                    runtime.inner().placement.lock().unwrap()[worker_index] = WorkerPlacement {
+                        requested_core,
+                        core,
+                    };
+
                     // Set the worker's runtime handle and index
                     RUNTIME.with(|rt| *rt.borrow_mut() = Some(runtime));
                     WORKER_INDEX.with(|idx| idx.set(worker_index));
@@ -164,21 +436,61 @@ impl Runtime {
 
                     // Build the worker's circuit
                     build_circuit();
-                })
-                .unwrap_or_else(|error| {
-                    panic!("failed to spawn worker thread {worker_index}: {error}");
                 });
 
-            (join_handle, init_receiver)
-        }));
+            match join_handle {
+                Ok(join_handle) => handles.push((join_handle, init_receiver)),
+                Err(error) => {
+                    Self::abort_workers(
+                        handles
+                            .iter()
+                            .filter_map(|(_handle, recv)| recv.recv().ok()),
+                    );
+                    return Err(Error::WorkerSpawn {
+                        worker: worker_index,
+                        error: error.to_string(),
+                    });
+                }
+            }
+        }
+
+        let signals: Vec<_> = handles
+            .iter()
+            .map(|(_handle, recv)| recv.recv().ok())
+            .collect();
+
+        // A worker that panicked before sending us its parker.
+        if let Some(worker_index) = signals.iter().position(Option::is_none) {
+            Self::abort_workers(signals.into_iter().flatten());
+            return Err(Error::WorkerPanic(worker_index));
+        }
 
         let mut workers = Vec::with_capacity(workers);
-        workers.extend(handles.into_iter().map(|(handle, recv)| {
-            let (unparker, kill_signal) = recv.recv().unwrap();
-            WorkerHandle::new(handle, unparker, kill_signal)
-        }));
+        workers.extend(
+            handles
+                .into_iter()
+                .zip(signals)
+                .map(|((handle, _recv), signals)| {
+                    let (unparker, kill_signal) = signals.unwrap();
+                    WorkerHandle::new(handle, unparker, kill_signal)
+                }),
+        );
+
+        Ok(RuntimeHandle::new(runtime, workers))
+    }
 
-        RuntimeHandle::new(runtime, workers)
+    /// Signals workers to exit after a failure to start the runtime.
+    ///
+    /// Doesn't wait for the workers to terminate, since they may be blocked
+    /// waiting for the caller.
+    fn abort_workers<I>(signals: I)
+    where
+        I: IntoIterator<Item = (Unparker, Arc<AtomicBool>)>,
+    {
+        for (unparker, kill_signal) in signals {
+            kill_signal.store(true, Ordering::SeqCst);
+            unparker.unpark();
+        }
     }
 
     /// Returns a reference to the multithreaded runtime that
@@ -202,6 +514,15 @@ impl Runtime {
         WORKER_INDEX.with(|index| index.get())
     }
 
+    /// `true` if the current thread is evaluating a bootstrap step (see
+    /// [`CircuitHandle::bootstrap`](`crate::CircuitHandle::bootstrap`)).
+    ///
+    /// Sink operators use this method to avoid delivering the outputs of the
+    /// bootstrap step to the client.
+    pub fn bootstrap_in_progress() -> bool {
+        BOOTSTRAP.with(|bootstrap| bootstrap.get())
+    }
+
     fn inner(&self) -> &RuntimeInner {
         &self.0
     }
@@ -211,6 +532,23 @@ impl Runtime {
         self.inner().nworkers
     }
 
+    /// Returns the directory persistent traces store their data in, if one
+    /// was configured (see [`RuntimeConfig::storage_dir`]).
+    pub fn storage_dir(&self) -> Option<&Path> {
+        self.inner().storage_dir.as_deref()
+    }
+
+    /// Returns the hash function used to route records to workers (see
+    /// [`RuntimeConfig::shard_hasher`]).
+    pub fn shard_hasher(&self) -> ShardHasherKind {
+        self.inner().shard_hasher
+    }
+
+    /// Returns the CPU placement of each worker thread, indexed by worker.
+    pub fn worker_placement(&self) -> Vec<WorkerPlacement> {
+        self.inner().placement.lock().unwrap().clone()
+    }
+
     /// Returns reference to the data store shared by all workers within the
     /// runtime.
     ///
@@ -259,6 +597,83 @@ impl Runtime {
     }
 }
 
+/// Returns the cores the process is allowed to run on, ordered so that all
+/// cores of a socket come before the cores of the next socket.
+#[cfg(target_os = "linux")]
+fn numa_ordered_cores() -> io::Result<Vec<usize>> {
+    use std::{fs, mem};
+
+    // Safety: `cpu_set_t` is a plain bitmask that's valid when zeroed
+    let mut cores: Vec<usize> = unsafe {
+        let mut allowed: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut allowed) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&core| libc::CPU_ISSET(core, &allowed))
+            .collect()
+    };
+
+    // Cores whose socket can't be determined (e.g., because sysfs isn't
+    // mounted) are assumed to be on the first one
+    cores.sort_by_cached_key(|&core| {
+        let socket = fs::read_to_string(format!(
+            "/sys/devices/system/cpu/cpu{core}/topology/physical_package_id"
+        ))
+        .ok()
+        .and_then(|socket| socket.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+        (socket, core)
+    });
+
+    Ok(cores)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn numa_ordered_cores() -> io::Result<Vec<usize>> {
+    Err(unsupported_pinning())
+}
+
+/// Pins the current thread to the given core.
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> io::Result<()> {
+    use std::mem;
+
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("core {core} is out of range"),
+        ));
+    }
+
+    // Safety: `cpu_set_t` is a plain bitmask that's valid when zeroed and
+    // `core` is within its bounds
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> io::Result<()> {
+    Err(unsupported_pinning())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported_pinning() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "core pinning is only supported on linux",
+    )
+}
+
 /// Per-worker controls.
 #[derive(Debug)]
 struct WorkerHandle {
@@ -314,7 +729,10 @@ impl RuntimeHandle {
     /// evaluated to completion, after which the worker thread terminates
     /// even if the circuit has not been fully evaluated for the current
     /// clock cycle.
-    pub fn kill(self) -> ThreadResult<()> {
+    ///
+    /// Returns [`Error::WorkerPanic`] for the first worker that panicked, if
+    /// any.
+    pub fn kill(self) -> Result<(), Error> {
         for worker in self.workers.iter() {
             worker.kill_signal.store(true, Ordering::SeqCst);
             worker.unpark();
@@ -326,7 +744,9 @@ impl RuntimeHandle {
     /// Wait for all workers in the runtime to terminate.
     ///
     /// The calling thread blocks until all worker threads have terminated.
-    pub fn join(self) -> ThreadResult<()> {
+    /// Returns [`Error::WorkerPanic`] for the first worker that panicked, if
+    /// any.
+    pub fn join(self) -> Result<(), Error> {
         // Insist on joining all threads even if some of them fail.
         #[allow(clippy::needless_collect)]
         let results: Vec<ThreadResult<()>> = self
@@ -334,7 +754,11 @@ impl RuntimeHandle {
             .into_iter()
             .map(|h| h.join_handle.join())
             .collect();
-        results.into_iter().collect::<ThreadResult<()>>()
+
+        match results.iter().position(Result::is_err) {
+            Some(worker) => Err(Error::WorkerPanic(worker)),
+            None => Ok(()),
+        }
     }
 }
 
@@ -347,7 +771,7 @@ impl TypedMapKey<LocalStoreMarker> for WorkerId {
 
 #[cfg(test)]
 mod tests {
-    use super::Runtime;
+    use super::{Runtime, RuntimeConfig};
     use crate::{
         circuit::schedule::{DynamicScheduler, Scheduler, StaticScheduler},
         operator::Generator,
@@ -391,7 +815,40 @@ mod tests {
             }
 
             assert_eq!(&*data.borrow(), &(0..100).collect::<Vec<usize>>());
-        });
+        })
+        .unwrap();
+
+        hruntime.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_core_affinity() {
+        let config = RuntimeConfig::new().workers(3).core_affinity(vec![0, 1]);
+        let hruntime = Runtime::run_with_config(config, || {}).unwrap();
+
+        let placement = hruntime.runtime().worker_placement();
+        let requested: Vec<_> = placement.iter().map(|p| p.requested_core()).collect();
+        assert_eq!(requested, [Some(0), Some(1), Some(0)]);
+
+        // Pinning may fail in restricted environments, but workers must never
+        // end up on a core they didn't ask for
+        for placement in placement {
+            assert!(placement.core().is_none() || placement.core() == placement.requested_core());
+        }
+
+        hruntime.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_no_core_pinning() {
+        let hruntime = Runtime::run(2, || {}).unwrap();
+
+        for placement in hruntime.runtime().worker_placement() {
+            assert_eq!(placement.requested_core(), None);
+            assert_eq!(placement.core(), None);
+        }
 
         hruntime.join().unwrap();
     }
@@ -437,7 +894,8 @@ mod tests {
                     return;
                 }
             }
-        });
+        })
+        .unwrap();
 
         sleep(Duration::from_millis(100));
         hruntime.kill().unwrap();
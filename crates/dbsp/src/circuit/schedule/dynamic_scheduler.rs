@@ -25,8 +25,10 @@
 //! ## Run queue
 //!
 //! The run queue is organized as a priority queue, with the scheduler picking
-//! one of the highest-priority runnable tasks to run next.  Priority assignment
-//! is heuristic.
+//! one of the highest-priority runnable tasks to run next.  Tasks are ordered
+//! by user-assigned priorities (see
+//! [`Stream::set_priority`](`crate::circuit::Stream::set_priority`)) first.
+//! Ties are broken using a heuristic priority assignment.
 //!
 //! ## Notification processing
 //!
@@ -49,7 +51,7 @@ use std::{
 use crate::circuit::{
     runtime::Runtime,
     schedule::{
-        util::{circuit_graph, ownership_constraints},
+        util::{circuit_graph, node_priorities, ownership_constraints},
         Error, Scheduler,
     },
     trace::SchedulerEvent,
@@ -75,7 +77,7 @@ struct Task {
 
     /// Scheduling priority.  The scheduler picks the top priority node out
     /// of all runnable nodes in the current state.
-    priority: isize,
+    priority: Priority,
 
     /// `true` if this is an async node.  The node can only be evaluated in a
     /// ready state.
@@ -93,6 +95,10 @@ struct Task {
     /// Task has been scheduled (put on the run queue) in the current clock
     /// cycle.
     scheduled: bool,
+
+    /// `true` if the node has been absorbed by a fused operator.  Such tasks
+    /// have no predecessors or successors and are never scheduled.
+    is_absorbed: bool,
 }
 
 /// The set of async nodes for which the scheduler has received ready
@@ -121,8 +127,12 @@ impl Notifications {
     }
 }
 
+/// Task priority: a user-assigned priority followed by a heuristic priority
+/// used to break ties.
+type Priority = (u8, isize);
+
 /// Runnable tasks sorted by priority.
-struct RunQueue(PriorityQueue<NodeId, isize>);
+struct RunQueue(PriorityQueue<NodeId, Priority>);
 
 impl RunQueue {
     fn with_capacity(capacity: usize) -> Self {
@@ -143,7 +153,7 @@ impl RunQueue {
         task.scheduled = true;
     }
 
-    fn pop(&mut self) -> Option<(NodeId, isize)> {
+    fn pop(&mut self) -> Option<(NodeId, Priority)> {
         self.0.pop()
     }
 }
@@ -156,6 +166,10 @@ struct Inner {
     /// task index is equal to the node id.
     tasks: Vec<Task>,
 
+    /// The number of tasks evaluated at each clock cycle, not including
+    /// tasks for absorbed nodes.
+    num_active_tasks: usize,
+
     // Mutable fields.
     /// Ready notifications received while the scheduler was busy or sleeping.
     notifications: Notifications,
@@ -241,10 +255,12 @@ impl Inner {
         }
 
         // `toposort` fails if the graph contains cycles.
-        toposort(&g, None).map_err(|e| Error::CyclicCircuit {
+        let order = toposort(&g, None).map_err(|e| Error::CyclicCircuit {
             node_id: GlobalNodeId::child_of(circuit, e.node_id()),
         })?;
 
+        let priorities = node_priorities(circuit, &g, &order);
+
         let num_nodes = circuit.num_nodes();
         let mut successors: HashMap<NodeId, Vec<NodeId>> = HashMap::with_capacity(num_nodes);
         let mut predecessors: HashMap<NodeId, Vec<NodeId>> = HashMap::with_capacity(num_nodes);
@@ -269,6 +285,7 @@ impl Inner {
 
         let mut tasks = Vec::with_capacity(num_nodes);
         let mut num_async_nodes = 0;
+        let mut num_active_tasks = 0;
 
         for (i, node_id) in circuit.node_ids().into_iter().enumerate() {
             // We rely on node id to be equal to its index.
@@ -280,13 +297,21 @@ impl Inner {
             // streams during the evaluation of the circuit.
             let num_predecessors = predecessors.entry(node_id).or_default().len();
             let num_successors = successors.entry(node_id).or_default().len();
-            let priority = num_predecessors as isize - num_successors as isize;
+            let priority = (
+                priorities[i],
+                num_predecessors as isize - num_successors as isize,
+            );
 
             let is_async = circuit.is_async_node(node_id);
             if is_async {
                 num_async_nodes += 1;
             }
 
+            let is_absorbed = circuit.is_absorbed_node(node_id);
+            if !is_absorbed {
+                num_active_tasks += 1;
+            }
+
             tasks.push(Task {
                 node_id,
                 num_predecessors,
@@ -296,12 +321,14 @@ impl Inner {
                 unsatisfied_dependencies: num_predecessors,
                 is_ready: !is_async,
                 scheduled: false,
+                is_absorbed,
             });
         }
 
         let unparker = Runtime::parker().with(|parker| parker.unparker().clone());
         let scheduler = Self {
             tasks,
+            num_active_tasks,
             notifications: Notifications::new(num_async_nodes, unparker),
             runnable: RunQueue::with_capacity(num_nodes),
         };
@@ -339,12 +366,12 @@ impl Inner {
             task.unsatisfied_dependencies = task.num_predecessors;
             task.scheduled = false;
 
-            if task.unsatisfied_dependencies == 0 && task.is_ready {
+            if task.unsatisfied_dependencies == 0 && task.is_ready && !task.is_absorbed {
                 self.runnable.push(task);
             }
         }
 
-        while completed_tasks < self.tasks.len() {
+        while completed_tasks < self.num_active_tasks {
             if Runtime::kill_in_progress() {
                 return Err(Error::Killed);
             }
@@ -361,7 +388,15 @@ impl Inner {
                         circuit.log_scheduler_event(&SchedulerEvent::wait_start(
                             circuit.global_id().deref(),
                         ));
+                        #[cfg(feature = "tracing-spans")]
+                        let stall_start = std::time::Instant::now();
                         Runtime::parker().with(|parker| parker.park());
+                        span_event!(
+                            tracing::Level::DEBUG,
+                            circuit = %circuit.global_id(),
+                            wait_us = stall_start.elapsed().as_micros() as u64,
+                            "exchange stall"
+                        );
                         circuit.log_scheduler_event(&SchedulerEvent::wait_end(
                             circuit.global_id().deref(),
                         ));
@@ -1,8 +1,10 @@
 //! The scheduling framework controls the execution of a circuit at runtime.
 
-use super::{trace::SchedulerEvent, Circuit, GlobalNodeId};
+use super::{trace::SchedulerEvent, Circuit, GlobalNodeId, NodeId, Stream};
+use crate::circuit_cache_key;
 use itertools::Itertools;
 use std::{
+    error::Error as StdError,
     fmt::{Display, Error as FmtError, Formatter},
     string::ToString,
 };
@@ -13,6 +15,8 @@ pub use static_scheduler::StaticScheduler;
 mod dynamic_scheduler;
 pub use dynamic_scheduler::DynamicScheduler;
 
+circuit_cache_key!(PriorityId(NodeId => u8));
+
 /// Scheduler errors.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
@@ -45,6 +49,51 @@ impl Display for Error {
     }
 }
 
+impl StdError for Error {}
+
+impl<C, T> Stream<C, T>
+where
+    C: Circuit,
+    T: 'static,
+{
+    /// Set the scheduling priority of the operator that produces this
+    /// stream.
+    ///
+    /// Within a clock cycle, the scheduler evaluates runnable operators with
+    /// higher priorities first.  The priority of a stream applies to the
+    /// entire subgraph that computes it: an operator inherits the highest
+    /// priority of all operators that depend on it, so that the inputs of a
+    /// high-priority stream are computed ahead of other work.  In addition,
+    /// sink operators, such as those created by
+    /// [`output`](`crate::Stream::output`), inherit the priority assigned to
+    /// their inputs.  For example, in a circuit that maintains several
+    /// independent views, assigning a higher priority to cheap views causes
+    /// their output handles to be filled before expensive views are
+    /// evaluated (see
+    /// [`OutputHandle::wait_ready`](`crate::OutputHandle::wait_ready`)).
+    ///
+    /// Priorities never override data dependencies: an operator is only
+    /// evaluated after all of its inputs are available.  The default
+    /// priority is 0.
+    ///
+    /// Priorities are read by the scheduler once the circuit has been
+    /// constructed, so this method has no effect after that.
+    pub fn set_priority(&self, priority: u8) -> Self {
+        self.circuit()
+            .cache_insert(PriorityId::new(self.local_node_id()), priority);
+        self.clone()
+    }
+
+    /// Returns the scheduling priority assigned to this stream by
+    /// [`set_priority`](`Self::set_priority`), or 0 if no priority was
+    /// assigned.
+    pub fn priority(&self) -> u8 {
+        self.circuit()
+            .cache_get(&PriorityId::new(self.local_node_id()))
+            .unwrap_or(0)
+    }
+}
+
 /// A scheduler defines the order in which nodes in a circuit are evaluated at
 /// runtime.
 ///
@@ -172,8 +221,11 @@ where
 /// Some useful tools for developing schedulers.
 mod util {
 
-    use crate::circuit::{schedule::Error, Circuit, GlobalNodeId, NodeId, OwnershipPreference};
-    use petgraph::graphmap::DiGraphMap;
+    use crate::circuit::{
+        schedule::{Error, PriorityId},
+        Circuit, GlobalNodeId, NodeId, OwnershipPreference,
+    };
+    use petgraph::{graphmap::DiGraphMap, Direction};
     use std::{collections::HashMap, ops::Deref};
 
     /// Dump circuit topology as a graph.
@@ -194,6 +246,64 @@ mod util {
         g
     }
 
+    /// Compute the effective scheduling priority of each node in the circuit
+    /// (see [`Stream::set_priority`](`crate::circuit::Stream::set_priority`)),
+    /// indexed by node id.
+    ///
+    /// `graph` is the circuit graph, possibly extended with additional
+    /// scheduling constraints, and `order` is a topological order of its
+    /// nodes.  The effective priority of a node is the highest of its own
+    /// priority and the effective priorities of its successors.  Nodes without
+    /// successors additionally inherit the highest priority assigned to their
+    /// predecessors.
+    pub(crate) fn node_priorities<C>(
+        circuit: &C,
+        graph: &DiGraphMap<NodeId, ()>,
+        order: &[NodeId],
+    ) -> Vec<u8>
+    where
+        C: Circuit,
+    {
+        let assigned: Vec<u8> = circuit
+            .node_ids()
+            .into_iter()
+            .map(|node_id| circuit.cache_get(&PriorityId::new(node_id)).unwrap_or(0))
+            .collect();
+        let mut priorities = assigned.clone();
+
+        // Propagate priorities upstream, visiting successors before
+        // predecessors.
+        for node_id in order.iter().rev() {
+            if let Some(priority) = graph
+                .neighbors_directed(*node_id, Direction::Outgoing)
+                .map(|succ| priorities[succ.id()])
+                .max()
+            {
+                priorities[node_id.id()] = priorities[node_id.id()].max(priority);
+            }
+        }
+
+        // Sinks, e.g., output handles, inherit priorities assigned to their
+        // inputs.
+        for node_id in order.iter() {
+            if graph
+                .neighbors_directed(*node_id, Direction::Outgoing)
+                .next()
+                .is_none()
+            {
+                if let Some(priority) = graph
+                    .neighbors_directed(*node_id, Direction::Incoming)
+                    .map(|pred| assigned[pred.id()])
+                    .max()
+                {
+                    priorities[node_id.id()] = priorities[node_id.id()].max(priority);
+                }
+            }
+        }
+
+        priorities
+    }
+
     /// Helper function used by schedulers to enforce ownership preferences.
     ///
     /// Individual schedulers can implement their own algorithms to enforce (or
@@ -3,17 +3,21 @@
 use crate::circuit::{
     runtime::Runtime,
     schedule::{
-        util::{circuit_graph, ownership_constraints},
+        util::{circuit_graph, node_priorities, ownership_constraints},
         Error, Scheduler,
     },
     trace::SchedulerEvent,
     Circuit, GlobalNodeId, NodeId,
 };
-use petgraph::algo::toposort;
-use std::{ops::Deref, thread::yield_now};
+use petgraph::{algo::toposort, Direction};
+use std::{cmp::Reverse, collections::BinaryHeap, ops::Deref, thread::yield_now};
 
 /// Static scheduler evaluates nodes in the circuit in a fixed order computed
 /// based on its dependency graph.
+///
+/// Among nodes whose dependencies are satisfied, the schedule places nodes
+/// with higher priorities (see
+/// [`Stream::set_priority`](`crate::circuit::Stream::set_priority`)) first.
 pub struct StaticScheduler {
     schedule: Vec<(NodeId, bool)>,
 }
@@ -38,14 +42,50 @@ impl Scheduler for StaticScheduler {
 
         // `toposort` fails if the graph contains cycles.
         // The circuit_builder API makes it impossible to construct such graphs.
-        let schedule = toposort(&g, None)
-            .map_err(|e| Error::CyclicCircuit {
-                node_id: GlobalNodeId::child_of(circuit, e.node_id()),
-            })?
+        let order = toposort(&g, None).map_err(|e| Error::CyclicCircuit {
+            node_id: GlobalNodeId::child_of(circuit, e.node_id()),
+        })?;
+
+        let priorities = node_priorities(circuit, &g, &order);
+
+        // Position of each node in the topological order, used to break ties
+        // between nodes with equal priorities.  When all priorities are equal,
+        // the schedule is identical to the topological order.
+        let mut rank = vec![0; order.len()];
+        for (i, node_id) in order.iter().enumerate() {
+            rank[node_id.id()] = i;
+        }
+
+        let mut unsatisfied_dependencies: Vec<usize> = circuit
+            .node_ids()
             .into_iter()
-            .map(|node_id| (node_id, circuit.is_async_node(node_id)))
+            .map(|node_id| g.neighbors_directed(node_id, Direction::Incoming).count())
             .collect();
 
+        let mut runnable = BinaryHeap::with_capacity(order.len());
+        for node_id in order.iter() {
+            if unsatisfied_dependencies[node_id.id()] == 0 {
+                runnable.push((priorities[node_id.id()], Reverse(rank[node_id.id()])));
+            }
+        }
+
+        let mut schedule = Vec::with_capacity(order.len());
+        while let Some((_, Reverse(i))) = runnable.pop() {
+            let node_id = order[i];
+            // Nodes absorbed by fused operators are isolated and never
+            // evaluated.
+            if !circuit.is_absorbed_node(node_id) {
+                schedule.push((node_id, circuit.is_async_node(node_id)));
+            }
+
+            for succ in g.neighbors_directed(node_id, Direction::Outgoing) {
+                unsatisfied_dependencies[succ.id()] -= 1;
+                if unsatisfied_dependencies[succ.id()] == 0 {
+                    runnable.push((priorities[succ.id()], Reverse(rank[succ.id()])));
+                }
+            }
+        }
+
         Ok(Self { schedule })
     }
 
@@ -62,14 +102,29 @@ impl Scheduler for StaticScheduler {
                 }
                 circuit.eval_node(*node_id)?;
             } else {
+                // Start of the wait for the node to become ready.
+                #[cfg(feature = "tracing-spans")]
+                let mut stall_start = None;
+
                 loop {
                     if Runtime::kill_in_progress() {
                         return Err(Error::Killed);
                     }
                     if circuit.ready(*node_id) {
+                        #[cfg(feature = "tracing-spans")]
+                        if let Some(stall_start) = stall_start {
+                            span_event!(
+                                tracing::Level::DEBUG,
+                                circuit = %circuit.global_id(),
+                                wait_us = stall_start.elapsed().as_micros() as u64,
+                                "exchange stall"
+                            );
+                        }
                         circuit.eval_node(*node_id)?;
                         break;
                     }
+                    #[cfg(feature = "tracing-spans")]
+                    stall_start.get_or_insert_with(std::time::Instant::now);
                     circuit.log_scheduler_event(&SchedulerEvent::wait_start(
                         circuit.global_id().deref(),
                     ));
@@ -0,0 +1,167 @@
+//! Structured [`tracing`](https://docs.rs/tracing) spans and events emitted
+//! by the runtime.
+//!
+//! When the `tracing-spans` feature is enabled, DBSP reports the spans and
+//! events listed below to the `tracing` subscriber installed by the
+//! application.  When the feature is disabled, the instrumentation compiles
+//! to nothing.  Span names, event messages, and field names are stable.
+//!
+//! # Spans
+//!
+//! * `step` (`DEBUG`): a clock cycle of the root circuit evaluated by
+//!   [`CircuitHandle::step`](`crate::CircuitHandle::step`).
+//!   - `worker`: index of the worker thread.
+//!   - `step`: sequence number of the step, starting from 0.
+//!
+//! * `region` (`TRACE`): a region of the circuit created with
+//!   [`Circuit::region`](`crate::circuit::Circuit::region`).  Entered around
+//!   the evaluation of each operator in the region, so that nested regions
+//!   form nested spans.
+//!   - `name`: name of the region.
+//!
+//! * `operator` (`TRACE`): evaluation of an operator.  Nested inside the
+//!   `step` span and the `region` spans of the operator.  Operators in a
+//!   nested circuit are nested inside the span of the nested circuit's
+//!   node.
+//!   - `name`: name of the operator.
+//!   - `node_id`: global id of the node, e.g., `[0.3]`.
+//!
+//! # Events
+//!
+//! * `"trace truncated"` (`DEBUG`): a
+//!   [`Z1Trace`](`crate::operator::Z1Trace`) operator discarded trace
+//!   entries below the bounds set by the circuit.  Emitted inside the
+//!   operator's span.
+//!   - `bound`: `"keys"` or `"values"`.
+//!
+//! * `"merge started"` (`TRACE`): a trace started merging two batches.
+//!   - `len1`, `len2`: number of tuples in the batches.
+//!
+//! * `"exchange stall"` (`DEBUG`): the scheduler had no operators ready to
+//!   run and waited for an asynchronous operator, such as the receiving end
+//!   of an exchange, to receive data from other workers.
+//!   - `circuit`: global id of the circuit, e.g., `[]` for the root circuit.
+//!   - `wait_us`: duration of the wait in microseconds.
+
+/// Emit a `tracing` event.
+///
+/// Expands to [`tracing::event!`] when the `tracing-spans` feature is
+/// enabled and to nothing otherwise.
+macro_rules! span_event {
+    ($($args:tt)*) => {{
+        #[cfg(feature = "tracing-spans")]
+        ::tracing::event!($($args)*);
+    }};
+}
+
+/// A stack of entered spans, exited in reverse order when dropped.
+#[cfg(feature = "tracing-spans")]
+#[derive(Default)]
+pub(crate) struct EnteredSpans(Vec<tracing::span::EnteredSpan>);
+
+#[cfg(feature = "tracing-spans")]
+impl EnteredSpans {
+    pub(crate) fn push(&mut self, span: tracing::Span) {
+        self.0.push(span.entered());
+    }
+}
+
+#[cfg(feature = "tracing-spans")]
+impl Drop for EnteredSpans {
+    fn drop(&mut self) {
+        while let Some(span) = self.0.pop() {
+            drop(span);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing-spans"))]
+mod test {
+    use crate::{
+        circuit::{Circuit, RootCircuit},
+        operator::Generator,
+    };
+    use std::{
+        fmt::{Debug, Write},
+        sync::{Arc, Mutex},
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        registry::LookupSpan,
+        Layer,
+    };
+
+    // Records each new span as `parent/.../name{field=value, ...}`.
+    struct SpanRecorder(Arc<Mutex<Vec<String>>>);
+
+    struct FieldFormatter(String);
+
+    impl Visit for FieldFormatter {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if !self.0.is_empty() {
+                self.0.push_str(", ");
+            }
+            write!(self.0, "{}={value:?}", field.name()).unwrap();
+        }
+    }
+
+    impl<S> Layer<S> for SpanRecorder
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let path: Vec<_> = ctx
+                .span(id)
+                .unwrap()
+                .scope()
+                .from_root()
+                .map(|span| span.name())
+                .collect();
+            let mut fields = FieldFormatter(String::new());
+            attrs.record(&mut fields);
+
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}{{{}}}", path.join("/"), fields.0));
+        }
+    }
+
+    #[test]
+    fn span_hierarchy() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(SpanRecorder(spans.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let (circuit, ()) = RootCircuit::build(|circuit| {
+                let source = circuit.add_source(Generator::new(|| 1usize));
+                circuit.region("sink", || {
+                    source.inspect(|_| {});
+                });
+            })
+            .unwrap();
+
+            for _ in 0..2 {
+                circuit.step().unwrap();
+            }
+        });
+
+        let expected: Vec<String> = (0..2)
+            .flat_map(|step| {
+                [
+                    format!("step{{worker=0, step={step}}}"),
+                    "step/operator{name=Generator, node_id=[0]}".to_string(),
+                    "step/region{name=sink}".to_string(),
+                    "step/region/operator{name=Inspect, node_id=[1]}".to_string(),
+                ]
+            })
+            .collect();
+
+        assert_eq!(*spans.lock().unwrap(), expected);
+    }
+}
@@ -18,6 +18,7 @@
 
 use super::{circuit_builder::Node, GlobalNodeId, NodeId, OwnershipPreference};
 use crate::circuit::metadata::OperatorLocation;
+use itertools::Itertools;
 use std::{borrow::Cow, fmt, fmt::Display, hash::Hash};
 
 /// Type of edge in a circuit graph.
@@ -116,6 +117,23 @@ pub enum CircuitEvent {
         /// stream.
         to: GlobalNodeId,
     },
+
+    /// A linear chain of operators has been fused into a single operator
+    /// (see [`CircuitConfig::fuse_linear_chains`](`crate::CircuitConfig::fuse_linear_chains`)).
+    ///
+    /// The fused operator takes over the id of the last operator in the
+    /// chain.  All other operators in the chain are removed from the circuit
+    /// along with the edges between them, and edges that pointed to the
+    /// first operator in the chain now point to the fused operator.
+    FusedOperators {
+        /// Global id of the fused operator.
+        node_id: GlobalNodeId,
+        /// Name of the fused operator.
+        name: Cow<'static, str>,
+        /// Global ids of the operators absorbed by the fused operator, in
+        /// evaluation order, not including `node_id`.
+        absorbed: Vec<GlobalNodeId>,
+    },
 }
 
 impl CircuitEvent {
@@ -206,6 +224,19 @@ impl CircuitEvent {
         }
     }
 
+    /// Create a [`CircuitEvent::FusedOperators`] event instance.
+    pub fn fused_operators(
+        node_id: GlobalNodeId,
+        name: Cow<'static, str>,
+        absorbed: Vec<GlobalNodeId>,
+    ) -> Self {
+        Self::FusedOperators {
+            node_id,
+            name,
+            absorbed,
+        }
+    }
+
     /// `true` if `self` is a [`CircuitEvent::StrictOperatorInput`]
     pub fn is_strict_input_event(&self) -> bool {
         matches!(self, Self::StrictOperatorInput { .. })
@@ -432,6 +463,18 @@ impl Display for CircuitEvent {
             } => {
                 write!(f, "Dependency({from} -> {to})")
             }
+
+            Self::FusedOperators {
+                node_id,
+                name,
+                absorbed,
+            } => {
+                write!(
+                    f,
+                    "FusedOperators(\"{name}\", {node_id} <- [{}])",
+                    absorbed.iter().map(ToString::to_string).format(", ")
+                )
+            }
         }
     }
 }
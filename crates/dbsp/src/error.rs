@@ -1,5 +1,6 @@
 use crate::{RuntimeError, SchedulerError};
 use std::{
+    error::Error as StdError,
     fmt::{Display, Error as FmtError, Formatter},
     io::Error as IOError,
 };
@@ -29,6 +30,8 @@ impl Display for Error {
     }
 }
 
+impl StdError for Error {}
+
 impl From<IOError> for Error {
     fn from(error: IOError) -> Self {
         Self::IO(error)
@@ -1,13 +1,309 @@
 //! Hashing utilities.
 
-use std::hash::{Hash, Hasher};
+use bincode::{
+    config,
+    enc::{write::Writer, EncoderImpl},
+    error::EncodeError,
+    Encode,
+};
+use std::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+};
 use xxhash_rust::xxh3::Xxh3;
 
 const SEED: u64 = 0x7f95_ef85_be33_c337u64;
 
+/// Seed used by [`stable_hash`].
+///
+/// Changing this value changes the assignment of records to partitions by
+/// [`Stream::partition_by`](`crate::Stream::partition_by`) and breaks
+/// compatibility with existing checkpoints.
+pub const STABLE_HASH_SEED: u64 = 0x2d35_8dcc_aa6c_78a5u64;
+
 /// Default hashing function used to shard records across workers.
+///
+/// Equivalent to hashing `x` with [`DefaultShardHasher`].
 pub fn default_hash<T: Hash>(x: &T) -> u64 {
-    let mut hasher = Xxh3::with_seed(SEED);
-    x.hash(&mut hasher);
-    hasher.finish()
+    DefaultShardHasher.hash_key(x)
+}
+
+/// Hash function used to route records to workers.
+///
+/// Operators that need all records with the same key to be processed by the
+/// same worker, e.g., `join` and `aggregate`, assign key `k` to worker
+/// `hash(k) % workers` using the [`ShardHasher`] selected by
+/// [`RuntimeConfig::shard_hasher`](`crate::RuntimeConfig::shard_hasher`).
+/// [`Stream::shard_with`](`crate::Stream::shard_with`) shards a stream
+/// with an explicitly chosen hasher.
+///
+/// # Stability
+///
+/// Implementations must be deterministic: the hash of a key may only depend
+/// on the key and the value of the hasher, and not on, e.g., a random seed
+/// chosen at startup.  This guarantees that each key is routed to the same
+/// worker across runs of the program, which is required to restore a
+/// circuit from a checkpoint.  Changing the output of an existing hasher
+/// breaks compatibility with checkpoints created by earlier versions.
+pub trait ShardHasher: Clone + Eq + Hash + Debug + Send + Sync + 'static {
+    /// Computes the hash of `key`.
+    fn hash_key<K>(&self, key: &K) -> u64
+    where
+        K: Hash + ?Sized;
+
+    /// Returns the index of the shard out of `shards` that `key` belongs to.
+    fn shard_of<K>(&self, key: &K, shards: usize) -> usize
+    where
+        K: Hash + ?Sized,
+    {
+        self.hash_key(key) as usize % shards
+    }
+}
+
+/// The default [`ShardHasher`]: 64-bit xxh3 with a fixed seed.
+///
+/// This is the same function as [`default_hash`].  It works well for
+/// arbitrary keys, but it hashes the bytes fed to it by the `Hash`
+/// implementation of the key, which writes integers in native byte order.
+/// Routing is therefore stable across runs and across machines with the same
+/// endianness and pointer width, but not between, e.g., little-endian and
+/// big-endian machines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DefaultShardHasher;
+
+impl ShardHasher for DefaultShardHasher {
+    fn hash_key<K>(&self, key: &K) -> u64
+    where
+        K: Hash + ?Sized,
+    {
+        let mut hasher = Xxh3::with_seed(SEED);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Multiply-shift [`ShardHasher`] for integer keys.
+///
+/// Multiplies each integer written by the `Hash` implementation of the key
+/// by a fixed odd constant (Fibonacci hashing) and returns the high 32 bits
+/// of the result.  This is much cheaper than [`DefaultShardHasher`] and
+/// spreads sequential integer ids evenly across any number of workers.
+///
+/// The hash only depends on the values of the integers, not on their byte
+/// representation, so routing is stable across runs and architectures for
+/// keys built out of integers, e.g., `u64` or `(u32, i64)`.  Other keys are
+/// hashed 8 bytes at a time, which is stable, but may distribute poorly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MultiplyShiftHasher;
+
+impl ShardHasher for MultiplyShiftHasher {
+    fn hash_key<K>(&self, key: &K) -> u64
+    where
+        K: Hash + ?Sized,
+    {
+        let mut hasher = MultiplyShiftState(0);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// `Hasher` that implements [`MultiplyShiftHasher`].
+struct MultiplyShiftState(u64);
+
+impl MultiplyShiftState {
+    /// 2^64 divided by the golden ratio, rounded to an odd number.
+    const MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    fn mix(&mut self, x: u64) {
+        self.0 = (self.0.rotate_left(5) ^ x).wrapping_mul(Self::MULTIPLIER);
+    }
+}
+
+impl Hasher for MultiplyShiftState {
+    fn finish(&self) -> u64 {
+        self.0 >> 32
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.mix(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, x: u8) {
+        self.mix(x as u64);
+    }
+
+    fn write_u16(&mut self, x: u16) {
+        self.mix(x as u64);
+    }
+
+    fn write_u32(&mut self, x: u32) {
+        self.mix(x as u64);
+    }
+
+    fn write_u64(&mut self, x: u64) {
+        self.mix(x);
+    }
+
+    fn write_u128(&mut self, x: u128) {
+        self.mix(x as u64);
+        self.mix((x >> 64) as u64);
+    }
+
+    fn write_usize(&mut self, x: usize) {
+        self.mix(x as u64);
+    }
+
+    fn write_i8(&mut self, x: i8) {
+        self.mix(x as u64);
+    }
+
+    fn write_i16(&mut self, x: i16) {
+        self.mix(x as u64);
+    }
+
+    fn write_i32(&mut self, x: i32) {
+        self.mix(x as u64);
+    }
+
+    fn write_i64(&mut self, x: i64) {
+        self.mix(x as u64);
+    }
+
+    fn write_i128(&mut self, x: i128) {
+        self.write_u128(x as u128);
+    }
+
+    fn write_isize(&mut self, x: isize) {
+        self.mix(x as u64);
+    }
+}
+
+/// Shard hashers that can be selected with
+/// [`RuntimeConfig::shard_hasher`](`crate::RuntimeConfig::shard_hasher`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ShardHasherKind {
+    /// [`DefaultShardHasher`].
+    #[default]
+    Default,
+    /// [`MultiplyShiftHasher`].
+    MultiplyShift,
+}
+
+impl ShardHasher for ShardHasherKind {
+    fn hash_key<K>(&self, key: &K) -> u64
+    where
+        K: Hash + ?Sized,
+    {
+        match self {
+            Self::Default => DefaultShardHasher.hash_key(key),
+            Self::MultiplyShift => MultiplyShiftHasher.hash_key(key),
+        }
+    }
+}
+
+/// Hashing function whose output is stable across architectures and
+/// releases.
+///
+/// Unlike [`default_hash`], which relies on the `Hash` implementation of `T`,
+/// this function computes the 64-bit xxh3 hash with [`STABLE_HASH_SEED`] of
+/// the canonical bincode encoding of `x` (little endian, variable-length
+/// integers).  The result only depends on the encoded bytes, so it can be
+/// used to assign records to partitions that must survive restarts.
+pub fn stable_hash<T: Encode>(x: &T) -> u64 {
+    let config = config::standard()
+        .with_little_endian()
+        .with_variable_int_encoding();
+    let mut encoder = EncoderImpl::new(HashWriter(Xxh3::with_seed(STABLE_HASH_SEED)), config);
+    x.encode(&mut encoder)
+        .expect("stable_hash: failed to encode value");
+    encoder.into_writer().0.finish()
+}
+
+/// Feeds bytes produced by the bincode encoder to the hasher.
+struct HashWriter(Xxh3);
+
+impl Writer for HashWriter {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.0.update(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        default_hash, stable_hash, DefaultShardHasher, MultiplyShiftHasher, ShardHasher,
+        ShardHasherKind,
+    };
+
+    // The hash only depends on the encoded value, not on the in-memory
+    // representation of the type.
+    #[test]
+    fn stable_hash_test() {
+        assert_eq!(stable_hash(&0u64), stable_hash(&0usize));
+        assert_eq!(stable_hash(&5u32), stable_hash(&5u64));
+        assert_eq!(stable_hash(&"foo".to_string()), stable_hash(&"foo"));
+        assert_ne!(stable_hash(&(1u64, 2u64)), stable_hash(&(2u64, 1u64)));
+    }
+
+    // Shard hashers are deterministic, and the runtime-selectable hashers
+    // agree with the hashers they stand for.
+    #[test]
+    fn shard_hasher_deterministic() {
+        for key in [0u64, 1, 42, u64::max_value()] {
+            assert_eq!(DefaultShardHasher.hash_key(&key), default_hash(&key));
+            assert_eq!(
+                DefaultShardHasher.hash_key(&key),
+                DefaultShardHasher.hash_key(&key)
+            );
+            assert_eq!(
+                ShardHasherKind::Default.hash_key(&key),
+                DefaultShardHasher.hash_key(&key)
+            );
+            assert_eq!(
+                ShardHasherKind::MultiplyShift.hash_key(&key),
+                MultiplyShiftHasher.hash_key(&key)
+            );
+        }
+    }
+
+    // Pin the output of `MultiplyShiftHasher`, which must not change
+    // across releases or architectures.
+    #[test]
+    fn multiply_shift_hasher_stable() {
+        assert_eq!(MultiplyShiftHasher.hash_key(&0u64), 0);
+        assert_eq!(MultiplyShiftHasher.hash_key(&1u64), 2654435769);
+        assert_eq!(MultiplyShiftHasher.hash_key(&2u64), 1013904242);
+        assert_eq!(MultiplyShiftHasher.hash_key(&42u64), 4112119918);
+
+        // The hash of an integer only depends on its value.
+        assert_eq!(MultiplyShiftHasher.hash_key(&42u32), 4112119918);
+        assert_eq!(MultiplyShiftHasher.hash_key(&42usize), 4112119918);
+        assert_eq!(MultiplyShiftHasher.hash_key(&42i64), 4112119918);
+    }
+
+    // Sequential ids are spread evenly across workers.
+    #[test]
+    fn multiply_shift_hasher_balanced() {
+        for shards in [2, 3, 4, 5, 7, 8, 12, 16, 32] {
+            for (start, step) in [(0u64, 1u64), (1_000_000, 1), (0, 2), (0, 16)] {
+                let mut counts = vec![0usize; shards];
+                for i in 0..10_000u64 {
+                    counts[MultiplyShiftHasher.shard_of(&(start + i * step), shards)] += 1;
+                }
+
+                let expected = 10_000 / shards;
+                for count in counts {
+                    assert!(
+                        count.abs_diff(expected) * 20 <= expected,
+                        "shards: {shards}, start: {start}, step: {step}, count: {count}"
+                    );
+                }
+            }
+        }
+    }
 }
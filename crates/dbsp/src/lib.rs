@@ -9,25 +9,36 @@ mod ref_pair;
 #[macro_use]
 pub mod circuit;
 pub mod algebra;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "with-json")]
+pub mod cdc;
 pub mod mimalloc;
 pub mod monitor;
 pub mod operator;
+pub mod prelude;
 pub mod profile;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategies;
+pub mod testing;
 pub mod time;
 pub mod trace;
 pub mod utils;
 
 pub use crate::error::Error;
-pub use crate::hash::default_hash;
+pub use crate::hash::{
+    default_hash, stable_hash, DefaultShardHasher, MultiplyShiftHasher, ShardHasher,
+    ShardHasherKind,
+};
 pub use crate::num_entries::NumEntries;
 pub use crate::ref_pair::RefPair;
 pub use crate::time::Timestamp;
 
 pub use algebra::{IndexedZSet, ZSet};
 pub use circuit::{
-    ChildCircuit, Circuit, CircuitHandle, DBSPHandle, RootCircuit, Runtime, RuntimeError,
-    SchedulerError, Stream,
+    ChildCircuit, Circuit, CircuitConfig, CircuitHandle, DBSPHandle, RootCircuit, Runtime,
+    RuntimeConfig, RuntimeError, SchedulerError, Stream, WorkerPlacement,
 };
-pub use operator::{CollectionHandle, InputHandle, OutputHandle, UpsertHandle};
+pub use operator::{bridge, CollectionHandle, InputHandle, OutputHandle, UpsertHandle};
 pub use trace::ord::{OrdIndexedZSet, OrdZSet};
 pub use trace::{DBData, DBTimestamp, DBWeight};
@@ -1,7 +1,10 @@
 use crate::{
     circuit::{metadata::OperatorLocation, trace::EdgeKind, GlobalNodeId, NodeId},
-    monitor::visual_graph::{
-        ClusterNode, Edge as VisEdge, Graph as VisGraph, Node as VisNode, SimpleNode,
+    monitor::{
+        visual_graph::{
+            ClusterNode, Edge as VisEdge, Graph as VisGraph, Node as VisNode, SimpleNode,
+        },
+        TraceError,
     },
 };
 use std::{
@@ -157,6 +160,21 @@ impl Region {
         }
     }
 
+    /// Append names of all regions on the path from `self` to `region_id` to
+    /// `names`, outermost first, excluding `self`.
+    ///
+    /// * `self` - must be a root region.
+    /// * `region_id` - existing subregion id.
+    pub(super) fn region_names(&self, region_id: &RegionId, names: &mut Vec<String>) {
+        debug_assert_eq!(self.id, RegionId::root());
+
+        let mut region = self;
+        for child_id in region_id.0.iter() {
+            region = &region.children[*child_id];
+            names.push(region.name.to_string());
+        }
+    }
+
     /// Get a mutable reference to a subregion of `self`.
     ///
     /// * `self` - must be a root region.
@@ -188,7 +206,6 @@ pub(super) struct Node {
     id: GlobalNodeId,
     pub name: Cow<'static, str>,
     pub location: OperatorLocation,
-    #[allow(dead_code)]
     pub region_id: RegionId,
     pub kind: NodeKind,
 }
@@ -363,6 +380,40 @@ impl CircuitGraph {
         self.nodes.node_ref(id.path().iter())
     }
 
+    /// Names of the subcircuits and regions that contain operator `id`,
+    /// outermost first, followed by the name of the operator.
+    ///
+    /// Returns `None` if `id` does not exist or is a circuit node.
+    pub(super) fn operator_stack(&self, id: &GlobalNodeId) -> Option<Vec<String>> {
+        let mut stack = Vec::new();
+        let mut node = &self.nodes;
+
+        for local_id in id.path() {
+            let child = match &node.kind {
+                NodeKind::Circuit {
+                    children, region, ..
+                } => {
+                    let child = children.get(local_id)?;
+                    region.region_names(&child.region_id, &mut stack);
+                    child
+                }
+                _ => return None,
+            };
+
+            if child.is_circuit() {
+                stack.push(format!("subcircuit {}", child.id));
+            }
+            node = child;
+        }
+
+        if node.is_circuit() {
+            return None;
+        }
+
+        stack.push(node.name.to_string());
+        Some(stack)
+    }
+
     /// Locate node by its global id.
     pub(super) fn node_mut(&mut self, id: &GlobalNodeId) -> Option<&mut Node> {
         self.nodes.node_mut(id.path().iter())
@@ -379,13 +430,66 @@ impl CircuitGraph {
         }
     }
 
+    /// Replace a chain of operators with a fused operator with id `node_id`
+    /// (see [`CircuitEvent::FusedOperators`](`crate::circuit::trace::CircuitEvent::FusedOperators`)).
+    pub(super) fn fuse_operators(
+        &mut self,
+        node_id: &GlobalNodeId,
+        name: Cow<'static, str>,
+        absorbed: &[GlobalNodeId],
+    ) -> Result<(), TraceError> {
+        self.node_mut(node_id)
+            .ok_or_else(|| TraceError::UnknownNode(node_id.clone()))?
+            .name = name;
+
+        for absorbed_id in absorbed.iter() {
+            let local_id = absorbed_id.local_node_id().ok_or(TraceError::EmptyPath)?;
+            let parent_id = absorbed_id.parent_id().unwrap();
+
+            match &mut self
+                .node_mut(&parent_id)
+                .ok_or_else(|| TraceError::UnknownNode(parent_id.clone()))?
+                .kind
+            {
+                NodeKind::Circuit {
+                    children, region, ..
+                } => {
+                    let node = children
+                        .remove(&local_id)
+                        .ok_or_else(|| TraceError::UnknownNode(absorbed_id.clone()))?;
+                    region
+                        .get_region(&node.region_id)
+                        .nodes
+                        .retain(|id| *id != local_id);
+                }
+                _ => return Err(TraceError::NotACircuit(parent_id)),
+            }
+
+            // Edges between operators in the chain disappear, edges into the
+            // head of the chain now lead to the fused operator.
+            self.edges.remove(absorbed_id);
+            for (to, _) in self.edges.values_mut().flatten() {
+                if to == absorbed_id {
+                    *to = node_id.clone();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Output circuit graph as visual graph.
     pub(super) fn visualize(&self, annotate: &dyn Fn(&GlobalNodeId) -> String) -> VisGraph {
         let cluster = self.nodes.visualize(annotate).unwrap().cluster().unwrap();
 
         let mut edges = Vec::new();
 
-        for (from_id, to) in self.edges.iter() {
+        // Visit edges in order of their source so that visualizing the same
+        // circuit always produces the same graph
+        let mut sources: Vec<_> = self.edges.iter().collect();
+        sources.sort_unstable_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+        for (from_id, to) in sources {
             let from_node = self.node_ref(from_id).unwrap();
 
             for (to_id, kind) in to.iter() {
@@ -174,6 +174,15 @@ impl TraceMonitor {
     {
         self.0.lock().unwrap().circuit.visualize(&annotate)
     }
+
+    /// Returns the names of the subcircuits and regions that contain operator
+    /// `node_id`, outermost first, followed by the name of the operator.
+    ///
+    /// Returns `None` if the monitor has not observed the creation of the
+    /// operator or if `node_id` is a subcircuit.
+    pub fn operator_stack(&self, node_id: &GlobalNodeId) -> Option<Vec<String>> {
+        self.0.lock().unwrap().circuit.operator_stack(node_id)
+    }
 }
 
 pub struct TraceMonitorInternal {
@@ -389,6 +398,11 @@ impl TraceMonitorInternal {
                 }
 
                 CircuitEvent::PopRegion => self.pop_region(),
+                CircuitEvent::FusedOperators {
+                    node_id,
+                    name,
+                    absorbed,
+                } => self.circuit.fuse_operators(node_id, name.clone(), absorbed),
                 _ => panic!("unknown event"),
             }
         }
@@ -7,6 +7,7 @@ use std::{
         NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
     },
     rc::Rc,
+    sync::Arc,
 };
 
 /// Trait to report object size as the number of entries.
@@ -249,6 +250,23 @@ where
     }
 }
 
+impl<T> NumEntries for Arc<T>
+where
+    T: NumEntries,
+{
+    const CONST_NUM_ENTRIES: Option<usize> = T::CONST_NUM_ENTRIES;
+
+    #[inline]
+    fn num_entries_shallow(&self) -> usize {
+        self.as_ref().num_entries_shallow()
+    }
+
+    #[inline]
+    fn num_entries_deep(&self) -> usize {
+        self.as_ref().num_entries_deep()
+    }
+}
+
 impl<T> NumEntries for Option<T>
 where
     T: NumEntries,
@@ -0,0 +1,302 @@
+use crate::{
+    algebra::MonoidValue,
+    operator::aggregate::{Aggregator, MaxSemigroup, MinSemigroup},
+    trace::Cursor,
+    DBData, Timestamp,
+};
+use std::marker::PhantomData;
+
+/// An [aggregator](`crate::operator::Aggregator`) that returns the payload
+/// attached to the largest ordering key with non-zero weight.
+///
+/// The input of the aggregator is a Z-set of `(key, payload)` pairs.  The
+/// aggregator selects the pair with the largest `key` and returns its
+/// `payload`.  Ties between pairs with equal keys are broken
+/// deterministically by picking the largest payload, so that the result
+/// does not depend on how the input is partitioned across workers.
+///
+/// A typical application of this aggregator is to select the most recent
+/// record in each group by using record timestamp as the ordering key.
+///
+/// # Type arguments
+///
+/// * `K` - ordering key
+/// * `V` - payload
+#[derive(Clone)]
+pub struct ArgMax<K, V>(PhantomData<(K, V)>);
+
+impl<K, V> ArgMax<K, V> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<K, V> Default for ArgMax<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, T, R> Aggregator<(K, V), T, R> for ArgMax<K, V>
+where
+    K: DBData,
+    V: DBData,
+    T: Timestamp,
+    R: MonoidValue,
+{
+    type Accumulator = (K, V);
+    type Output = V;
+    type Semigroup = MaxSemigroup<(K, V)>;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<'s, (K, V), (), T, R>,
+    {
+        // Values are ordered by `(key, payload)`, which matches the
+        // order of `MaxSemigroup<(K, V)>`, so the last value with non-zero
+        // weight is the result.
+        let mut result = None;
+
+        while cursor.key_valid() {
+            let mut weight = R::zero();
+
+            cursor.map_times(|_t, w| weight.add_assign_by_ref(w));
+
+            if !weight.is_zero() {
+                result = Some(cursor.key().clone());
+            }
+
+            cursor.step_key();
+        }
+
+        result
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        accumulator.1
+    }
+}
+
+/// An [aggregator](`crate::operator::Aggregator`) that returns the payload
+/// attached to the smallest ordering key with non-zero weight.
+///
+/// This is the dual of [`ArgMax`].  Ties between pairs with equal keys are
+/// broken by picking the smallest payload.
+///
+/// # Type arguments
+///
+/// * `K` - ordering key
+/// * `V` - payload
+#[derive(Clone)]
+pub struct ArgMin<K, V>(PhantomData<(K, V)>);
+
+impl<K, V> ArgMin<K, V> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<K, V> Default for ArgMin<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, T, R> Aggregator<(K, V), T, R> for ArgMin<K, V>
+where
+    K: DBData,
+    V: DBData,
+    T: Timestamp,
+    R: MonoidValue,
+{
+    type Accumulator = (K, V);
+    type Output = V;
+    type Semigroup = MinSemigroup<(K, V)>;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<'s, (K, V), (), T, R>,
+    {
+        while cursor.key_valid() {
+            let mut weight = R::zero();
+
+            cursor.map_times(|_t, w| weight.add_assign_by_ref(w));
+
+            if !weight.is_zero() {
+                return Some(cursor.key().clone());
+            }
+
+            cursor.step_key();
+        }
+
+        None
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        accumulator.1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ArgMax, ArgMin};
+    use crate::{
+        indexed_zset,
+        operator::{
+            time_series::{RelOffset, RelRange},
+            Aggregator, FilterMap, Max, Min,
+        },
+        trace::{cursor::CursorGroup, BatchReader, Cursor},
+        OrdIndexedZSet, RootCircuit,
+    };
+
+    type Input = OrdIndexedZSet<u64, (u64, i64), isize>;
+
+    fn aggregate_batch<A>(aggregator: &A, batch: &Input) -> Vec<Option<i64>>
+    where
+        A: Aggregator<(u64, i64), (), isize, Output = i64>,
+    {
+        let mut cursor = batch.cursor();
+        let mut outputs = Vec::new();
+
+        while cursor.key_valid() {
+            outputs.push(aggregator.aggregate_and_finalize(&mut CursorGroup::new(&mut cursor, ())));
+            cursor.step_key();
+        }
+
+        outputs
+    }
+
+    #[test]
+    fn test_arg_max_ties() {
+        let batch: Input = indexed_zset! {
+            0 => { (5, 1) => 1, (5, 3) => 1, (5, 2) => 1, (3, 10) => 1 },
+            1 => { (1, 4) => 1, (1, -4) => 1, (2, 0) => 1 },
+            2 => { (7, 1) => 1, (7, 2) => 2 },
+        };
+
+        assert_eq!(
+            aggregate_batch(&ArgMax::new(), &batch),
+            vec![Some(3), Some(0), Some(2)]
+        );
+        assert_eq!(
+            aggregate_batch(&ArgMin::new(), &batch),
+            vec![Some(10), Some(-4), Some(1)]
+        );
+    }
+
+    #[test]
+    fn test_arg_max_aggregate() {
+        let (circuit, (input, output_max, output_min)) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let output_max = input_stream.aggregate(ArgMax::new()).integrate().output();
+            let output_min = input_stream.aggregate(ArgMin::new()).integrate().output();
+
+            (input_handle, output_max, output_min)
+        })
+        .unwrap();
+
+        input.push(0, ((10, 1), 1));
+        input.push(0, ((20, 2), 1));
+        input.push(0, ((20, 3), 1));
+        input.push(0, ((10, 4), 1));
+        input.push(1, ((5, 5), 1));
+        circuit.step().unwrap();
+
+        assert_eq!(
+            output_max.consolidate(),
+            indexed_zset! { 0 => { 3 => 1 }, 1 => { 5 => 1 } }
+        );
+        assert_eq!(
+            output_min.consolidate(),
+            indexed_zset! { 0 => { 1 => 1 }, 1 => { 5 => 1 } }
+        );
+
+        // Retract the current winners; group 1 becomes empty.
+        input.push(0, ((20, 3), -1));
+        input.push(0, ((10, 1), -1));
+        input.push(1, ((5, 5), -1));
+        circuit.step().unwrap();
+
+        assert_eq!(output_max.consolidate(), indexed_zset! { 0 => { 2 => 1 } });
+        assert_eq!(output_min.consolidate(), indexed_zset! { 0 => { 4 => 1 } });
+
+        // Retract the winner of `ArgMax`, leaving a single pair in group 0;
+        // add a new pair to the empty group 1.
+        input.push(0, ((20, 2), -1));
+        input.push(1, ((6, 6), 1));
+        circuit.step().unwrap();
+
+        assert_eq!(
+            output_max.consolidate(),
+            indexed_zset! { 0 => { 4 => 1 }, 1 => { 6 => 1 } }
+        );
+        assert_eq!(
+            output_min.consolidate(),
+            indexed_zset! { 0 => { 4 => 1 }, 1 => { 6 => 1 } }
+        );
+
+        // Empty all groups.
+        input.push(0, ((10, 4), -1));
+        input.push(1, ((6, 6), -1));
+        circuit.step().unwrap();
+
+        assert_eq!(output_max.consolidate(), indexed_zset! {});
+        assert_eq!(output_min.consolidate(), indexed_zset! {});
+    }
+
+    #[test]
+    fn test_arg_max_rolling_aggregate() {
+        let (circuit, input) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, (u64, i64)), isize>();
+
+            let range = RelRange::new(RelOffset::Before(10), RelOffset::Before(0));
+
+            // Reference implementations: `Max`/`Min` over `(key, payload)`
+            // pairs, projected to the payload.
+            let arg_max = input_stream
+                .partitioned_rolling_aggregate::<u64, (u64, i64), _>(ArgMax::new(), range)
+                .integrate();
+            let expected_max = input_stream
+                .partitioned_rolling_aggregate::<u64, (u64, i64), _>(Max, range)
+                .map_index(|(pk, (ts, max))| (*pk, (*ts, max.map(|(_, v)| v))))
+                .integrate();
+            arg_max.apply2(&expected_max, |actual, expected| {
+                assert_eq!(actual, expected)
+            });
+
+            let arg_min = input_stream
+                .partitioned_rolling_aggregate::<u64, (u64, i64), _>(ArgMin::new(), range)
+                .integrate();
+            let expected_min = input_stream
+                .partitioned_rolling_aggregate::<u64, (u64, i64), _>(Min, range)
+                .map_index(|(pk, (ts, min))| (*pk, (*ts, min.map(|(_, v)| v))))
+                .integrate();
+            arg_min.apply2(&expected_min, |actual, expected| {
+                assert_eq!(actual, expected)
+            });
+
+            input_handle
+        })
+        .unwrap();
+
+        input.push(0, ((1, (5, 1)), 1));
+        input.push(0, ((3, (5, 2)), 1));
+        input.push(0, ((7, (2, 3)), 1));
+        input.push(0, ((15, (1, 4)), 1));
+        input.push(1, ((100, (3, 5)), 1));
+        circuit.step().unwrap();
+
+        // Retract winners.
+        input.push(0, ((3, (5, 2)), -1));
+        input.push(1, ((100, (3, 5)), -1));
+        circuit.step().unwrap();
+
+        input.push(0, ((9, (5, 0)), 1));
+        input.push(0, ((1, (5, 1)), -1));
+        circuit.step().unwrap();
+    }
+}
@@ -4,7 +4,91 @@ use crate::{
     trace::Cursor,
     DBData, Timestamp,
 };
-use std::{convert::identity, marker::PhantomData};
+use std::{convert::identity, marker::PhantomData, ops::ControlFlow};
+
+/// A predicate applied to values before they are passed to the step
+/// function of a [`Fold`] or [`FoldUntil`] aggregator.
+///
+/// This trait is implemented for all closures of type `Fn(&V) -> bool`
+/// and for [`NoFilter`], which accepts all values.
+pub trait FoldFilter<V>: Clone + 'static {
+    /// Returns `true` if `value` should be passed to the step function.
+    fn accept(&self, value: &V) -> bool;
+}
+
+/// A [`FoldFilter`] that accepts all values.  This is the default filter
+/// of [`Fold`] and [`FoldUntil`] aggregators.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoFilter;
+
+impl<V> FoldFilter<V> for NoFilter {
+    #[inline]
+    fn accept(&self, _value: &V) -> bool {
+        true
+    }
+}
+
+impl<V, F> FoldFilter<V> for F
+where
+    F: Fn(&V) -> bool + Clone + 'static,
+{
+    #[inline]
+    fn accept(&self, value: &V) -> bool {
+        self(value)
+    }
+}
+
+/// Returns the weight of the current value of `cursor` if it is accepted by
+/// `filter` and its weight is non-zero.
+fn accepted_weight<'s, V, T, R, P, C>(cursor: &mut C, filter: &P) -> Option<R>
+where
+    R: MonoidValue,
+    P: FoldFilter<V>,
+    C: Cursor<'s, V, (), T, R>,
+{
+    if !filter.accept(cursor.key()) {
+        return None;
+    }
+
+    let mut weight = R::zero();
+    cursor.map_times(|_t, w| weight.add_assign_by_ref(w));
+
+    (!weight.is_zero()).then_some(weight)
+}
+
+/// Fold the contents of `cursor`, skipping values with zero weights and
+/// values rejected by `filter`, until `step` returns `ControlFlow::Break`.
+///
+/// Returns `None` if `step` was not invoked at least once.
+fn fold_cursor<'s, V, T, R, A, P, C, SF>(
+    cursor: &mut C,
+    init: A,
+    filter: &P,
+    mut step: SF,
+) -> Option<A>
+where
+    R: MonoidValue,
+    P: FoldFilter<V>,
+    C: Cursor<'s, V, (), T, R>,
+    SF: FnMut(A, &V, R) -> ControlFlow<A, A>,
+{
+    let mut acc = init;
+    let mut non_empty = false;
+
+    while cursor.key_valid() {
+        if let Some(weight) = accepted_weight(cursor, filter) {
+            non_empty = true;
+            match step(acc, cursor.key(), weight) {
+                ControlFlow::Continue(next) => acc = next,
+                ControlFlow::Break(last) => return Some(last),
+            }
+        }
+
+        cursor.step_key();
+    }
+
+    non_empty.then_some(acc)
+}
 
 /// An [aggregator](`crate::operator::Aggregator`) that can be expressed
 /// as a fold of the input Z-set.
@@ -21,11 +105,14 @@ use std::{convert::identity, marker::PhantomData};
 /// * `S` - semigroup structure used to compute aggregates piecewise
 /// * `SF` - step function
 /// * `OF` - output function
+/// * `P` - filter applied to values before they reach the step function
+///   (see [`with_filter`](`Self::with_filter`))
 #[derive(Clone)]
-pub struct Fold<A, S, SF, OF> {
+pub struct Fold<A, S, SF, OF, P = NoFilter> {
     init: A,
     step: SF,
     output: OF,
+    filter: P,
     phantom: PhantomData<S>,
 }
 
@@ -41,6 +128,7 @@ impl<A, S, SF> Fold<A, S, SF, fn(A) -> A> {
             init,
             step,
             output: identity,
+            filter: NoFilter,
             phantom: PhantomData,
         }
     }
@@ -54,12 +142,28 @@ impl<A, S, SF, OF> Fold<A, S, SF, OF> {
             init,
             step,
             output,
+            filter: NoFilter,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Only pass values that satisfy `filter` to the step function.
+    ///
+    /// Values rejected by the filter are skipped without computing their
+    /// weights.  If all values in the input Z-set are rejected, the
+    /// aggregate is empty.
+    pub fn with_filter<P>(self, filter: P) -> Fold<A, S, SF, OF, P> {
+        Fold {
+            init: self.init,
+            step: self.step,
+            output: self.output,
+            filter,
             phantom: PhantomData,
         }
     }
 }
 
-impl<V, T, R, A, S, O, SF, OF> Aggregator<V, T, R> for Fold<A, S, SF, OF>
+impl<V, T, R, A, S, O, SF, OF, P> Aggregator<V, T, R> for Fold<A, S, SF, OF, P>
 where
     T: Timestamp,
     R: MonoidValue,
@@ -68,6 +172,7 @@ where
     OF: Fn(A) -> O + Clone + 'static,
     S: Semigroup<A> + Clone + 'static,
     O: DBData,
+    P: FoldFilter<V>,
 {
     type Accumulator = A;
     type Output = O;
@@ -77,25 +182,327 @@ where
     where
         C: Cursor<'s, V, (), T, R>,
     {
-        let mut acc = self.init.clone();
-        let mut non_empty = false;
+        fold_cursor(cursor, self.init.clone(), &self.filter, |mut acc, v, w| {
+            (self.step)(&mut acc, v, w);
+            ControlFlow::Continue(acc)
+        })
+    }
+
+    // Continues the fold in place, so that folds whose semigroup is not
+    // implemented can be used in tuples of aggregators.
+    fn step<'s, C>(&self, accumulator: &mut Option<A>, cursor: &mut C) -> ControlFlow<()>
+    where
+        V: Ord,
+        C: Cursor<'s, V, (), T, R>,
+    {
+        if let Some(weight) = accepted_weight(cursor, &self.filter) {
+            let acc = accumulator.get_or_insert_with(|| self.init.clone());
+            (self.step)(acc, cursor.key(), weight);
+        }
 
-        while cursor.key_valid() {
-            let mut weight = R::zero();
+        ControlFlow::Continue(())
+    }
 
-            cursor.map_times(|_t, w| weight.add_assign_by_ref(w));
-            if !weight.is_zero() {
-                non_empty = true;
-                (self.step)(&mut acc, cursor.key(), weight);
-            }
+    fn finalize(&self, acc: Self::Accumulator) -> Self::Output {
+        (self.output)(acc)
+    }
+}
 
-            cursor.step_key();
+/// A [`Fold`] aggregator that can stop consuming its input early.
+///
+/// The `step` function of this aggregator returns
+/// `ControlFlow::Continue(acc)` to proceed to the next value or
+/// `ControlFlow::Break(acc)` to stop the fold, in which case `acc` is
+/// used as the final value of the accumulator, and remaining values are
+/// not scanned.  This is useful for aggregates that can short-circuit,
+/// e.g., "exists a value satisfying a predicate" or "count up to a limit".
+///
+/// The value passed to `step` when it returns `Break` is considered
+/// consumed, i.e., the returned accumulator must already account for this
+/// value and its weight.
+///
+/// Note that early exit only applies to a single invocation of
+/// [`Aggregator::aggregate`].  Accumulators computed over different
+/// partitions of the input Z-set are combined using semigroup `S`, which
+/// must produce the same result as scanning the union of the partitions.
+///
+/// # Type arguments
+///
+/// * `A` - accumulator
+/// * `S` - semigroup structure used to compute aggregates piecewise
+/// * `SF` - step function
+/// * `OF` - output function
+/// * `P` - filter applied to values before they reach the step function
+///   (see [`with_filter`](`Self::with_filter`))
+#[derive(Clone)]
+pub struct FoldUntil<A, S, SF, OF, P = NoFilter> {
+    init: A,
+    step: SF,
+    output: OF,
+    filter: P,
+    phantom: PhantomData<S>,
+}
+
+impl<A, S, SF> FoldUntil<A, S, SF, fn(A) -> A> {
+    /// Create a `FoldUntil` aggregator with initial accumulator value
+    /// `init`, step function `step`, and identity output function.
+    pub fn new(init: A, step: SF) -> Self {
+        Self {
+            init,
+            step,
+            output: identity,
+            filter: NoFilter,
+            phantom: PhantomData,
         }
+    }
+}
 
-        non_empty.then_some(acc)
+impl<A, S, SF, OF> FoldUntil<A, S, SF, OF> {
+    /// Create a `FoldUntil` aggregator with initial accumulator value
+    /// `init`, step function `step`, and output function `output`.
+    pub fn with_output(init: A, step: SF, output: OF) -> Self {
+        Self {
+            init,
+            step,
+            output,
+            filter: NoFilter,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Only pass values that satisfy `filter` to the step function.
+    ///
+    /// Values rejected by the filter are skipped without computing their
+    /// weights.  If all values in the input Z-set are rejected, the
+    /// aggregate is empty.
+    pub fn with_filter<P>(self, filter: P) -> FoldUntil<A, S, SF, OF, P> {
+        FoldUntil {
+            init: self.init,
+            step: self.step,
+            output: self.output,
+            filter,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<V, T, R, A, S, O, SF, OF, P> Aggregator<V, T, R> for FoldUntil<A, S, SF, OF, P>
+where
+    T: Timestamp,
+    R: MonoidValue,
+    A: DBData,
+    SF: Fn(A, &V, R) -> ControlFlow<A, A> + Clone + 'static,
+    OF: Fn(A) -> O + Clone + 'static,
+    S: Semigroup<A> + Clone + 'static,
+    O: DBData,
+    P: FoldFilter<V>,
+{
+    type Accumulator = A;
+    type Output = O;
+    type Semigroup = S;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<'s, V, (), T, R>,
+    {
+        fold_cursor(cursor, self.init.clone(), &self.filter, &self.step)
+    }
+
+    fn step<'s, C>(&self, accumulator: &mut Option<A>, cursor: &mut C) -> ControlFlow<()>
+    where
+        V: Ord,
+        C: Cursor<'s, V, (), T, R>,
+    {
+        if let Some(weight) = accepted_weight(cursor, &self.filter) {
+            let acc = accumulator.take().unwrap_or_else(|| self.init.clone());
+            match (self.step)(acc, cursor.key(), weight) {
+                ControlFlow::Continue(acc) => *accumulator = Some(acc),
+                ControlFlow::Break(acc) => {
+                    *accumulator = Some(acc);
+                    return ControlFlow::Break(());
+                }
+            }
+        }
+
+        ControlFlow::Continue(())
     }
 
     fn finalize(&self, acc: Self::Accumulator) -> Self::Output {
         (self.output)(acc)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::FoldUntil;
+    use crate::{
+        algebra::{DefaultSemigroup, Semigroup},
+        indexed_zset,
+        operator::{Aggregator, Fold},
+        trace::{cursor::CursorGroup, BatchReader, Cursor},
+        OrdIndexedZSet,
+    };
+    use std::{cell::Cell, ops::ControlFlow, rc::Rc};
+
+    /// Apply `aggregator` to each key in `batch`.
+    fn aggregate_batch<A>(
+        aggregator: &A,
+        batch: &OrdIndexedZSet<u64, i64, isize>,
+    ) -> Vec<Option<A::Output>>
+    where
+        A: Aggregator<i64, (), isize>,
+    {
+        let mut cursor = batch.cursor();
+        let mut outputs = Vec::new();
+
+        while cursor.key_valid() {
+            outputs.push(aggregator.aggregate_and_finalize(&mut CursorGroup::new(&mut cursor, ())));
+            cursor.step_key();
+        }
+
+        outputs
+    }
+
+    /// Semigroup for boolean "or".
+    #[derive(Clone)]
+    struct AnySemigroup;
+
+    impl Semigroup<bool> for AnySemigroup {
+        fn combine(left: &bool, right: &bool) -> bool {
+            *left || *right
+        }
+    }
+
+    #[test]
+    fn test_fold_until_exists() {
+        let batch: OrdIndexedZSet<u64, i64, isize> = indexed_zset! {
+            0 => { -5 => 1, -1 => 1, 3 => 1, 4 => 1, 7 => 1 },
+            1 => { -3 => 1, -2 => 2 },
+            2 => { 1 => 1, 2 => 0, 5 => 1 },
+        };
+
+        let steps = Rc::new(Cell::new(0));
+        let steps_clone = steps.clone();
+
+        let exists_positive = <FoldUntil<_, AnySemigroup, _, _>>::new(
+            false,
+            move |_acc: bool, v: &i64, _w: isize| {
+                steps_clone.set(steps_clone.get() + 1);
+                if *v > 0 {
+                    ControlFlow::Break(true)
+                } else {
+                    ControlFlow::Continue(false)
+                }
+            },
+        );
+
+        assert_eq!(
+            aggregate_batch(&exists_positive, &batch),
+            vec![Some(true), Some(false), Some(true)]
+        );
+
+        // Key 0: 3 steps, key 1: 2 steps, key 2: 1 step.
+        assert_eq!(steps.get(), 6);
+    }
+
+    #[test]
+    fn test_fold_until_limit() {
+        let batch: OrdIndexedZSet<u64, i64, isize> = indexed_zset! {
+            0 => { 1 => 3, 2 => 1, 3 => 1 },
+            1 => { 1 => 1, 2 => 1, 3 => 1, 4 => 1 },
+            2 => { 1 => 1 },
+        };
+
+        let steps = Rc::new(Cell::new(0));
+        let steps_clone = steps.clone();
+
+        // Count values up to a limit of 2.  The weight of the value that
+        // triggers early exit is included in the result.
+        let count_limit = <FoldUntil<_, DefaultSemigroup<_>, _, _>>::with_output(
+            0,
+            move |acc: isize, _v: &i64, w: isize| {
+                steps_clone.set(steps_clone.get() + 1);
+                let acc = acc + w;
+                if acc >= 2 {
+                    ControlFlow::Break(acc)
+                } else {
+                    ControlFlow::Continue(acc)
+                }
+            },
+            |acc: isize| acc.min(2),
+        );
+
+        assert_eq!(
+            aggregate_batch(&count_limit, &batch),
+            vec![Some(2), Some(2), Some(1)]
+        );
+
+        // Key 0: 1 step, key 1: 2 steps, key 2: 1 step.
+        assert_eq!(steps.get(), 4);
+    }
+
+    #[test]
+    fn test_fold_filter() {
+        let batch: OrdIndexedZSet<u64, i64, isize> = indexed_zset! {
+            0 => { -5 => 1, -1 => 2, 3 => 1, 4 => 2 },
+            1 => { -3 => 1, -2 => 2 },
+            2 => { 1 => 1, 5 => -1 },
+        };
+
+        let steps = Rc::new(Cell::new(0));
+        let steps_clone = steps.clone();
+
+        // Weighted sum of positive values.
+        let sum_positive = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+            0,
+            move |acc: &mut i64, v: &i64, w: isize| {
+                steps_clone.set(steps_clone.get() + 1);
+                *acc += *v * w as i64;
+            },
+        )
+        .with_filter(|v: &i64| *v > 0);
+
+        assert_eq!(
+            aggregate_batch(&sum_positive, &batch),
+            vec![Some(11), None, Some(-4)]
+        );
+
+        // The step function is only invoked for positive values.
+        assert_eq!(steps.get(), 4);
+    }
+
+    #[test]
+    fn test_fold_until_filter() {
+        let batch: OrdIndexedZSet<u64, i64, isize> = indexed_zset! {
+            0 => { 1 => 1, 2 => 1, 3 => 1, 4 => 1, 6 => 1, 8 => 1 },
+            1 => { 1 => 1, 3 => 1, 5 => 1 },
+            2 => { 2 => 2, 4 => 1 },
+        };
+
+        let steps = Rc::new(Cell::new(0));
+        let steps_clone = steps.clone();
+
+        // Count even values up to a limit of 2.
+        let count_evens = <FoldUntil<_, DefaultSemigroup<_>, _, _>>::new(
+            0,
+            move |acc: isize, _v: &i64, w: isize| {
+                steps_clone.set(steps_clone.get() + 1);
+                let acc = acc + w;
+                if acc >= 2 {
+                    ControlFlow::Break(acc)
+                } else {
+                    ControlFlow::Continue(acc)
+                }
+            },
+        )
+        .with_filter(|v: &i64| v % 2 == 0);
+
+        assert_eq!(
+            aggregate_batch(&count_evens, &batch),
+            vec![Some(2), None, Some(2)]
+        );
+
+        // Key 0: 2 steps, key 1: no steps, key 2: 1 step.
+        assert_eq!(steps.get(), 3);
+    }
+}
@@ -4,7 +4,7 @@ use crate::{
     trace::Cursor,
     DBData, Timestamp,
 };
-use std::{cmp::min, marker::PhantomData};
+use std::{cmp::min, marker::PhantomData, ops::ControlFlow};
 
 /// An [aggregator](`crate::operator::Aggregator`) that returns the
 /// smallest value with non-zero weight.
@@ -56,6 +56,24 @@ where
         None
     }
 
+    // Keys are scanned in ascending order, so the first key with non-zero
+    // weight is the minimum.
+    fn step<'s, C>(&self, accumulator: &mut Option<V>, cursor: &mut C) -> ControlFlow<()>
+    where
+        C: Cursor<'s, V, (), T, R>,
+    {
+        let weight = cursor.fold_times(R::zero(), |mut acc, _, weight| {
+            acc.add_assign_by_ref(weight);
+            acc
+        });
+        if weight.is_zero() {
+            ControlFlow::Continue(())
+        } else {
+            *accumulator = Some(cursor.key().clone());
+            ControlFlow::Break(())
+        }
+    }
+
     fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
         accumulator
     }
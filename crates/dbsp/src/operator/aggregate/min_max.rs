@@ -0,0 +1,375 @@
+//! Incremental `min` and `max` aggregates that handle retractions without
+//! rescanning groups.
+
+use crate::{
+    algebra::{AddAssignByRef, HasOne, HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope,
+    },
+    trace::{Batch, BatchReader, Cursor, Spine},
+    Circuit, OrdIndexedZSet, RootCircuit, Stream,
+};
+use std::{borrow::Cow, collections::BTreeMap, marker::PhantomData, ops::Neg};
+
+impl<Z> Stream<RootCircuit, Z>
+where
+    Z: IndexedZSet + Send,
+    Z::R: ZRingValue,
+{
+    /// Incrementally compute the smallest value associated with each key.
+    ///
+    /// Produces the same output as [`aggregate(Min)`](`Self::aggregate`):
+    /// a stream of changes to the collection that maps each key to the
+    /// smallest value with non-zero weight associated with it in the
+    /// integral of `self`.
+    ///
+    /// Unlike `aggregate`, which recomputes the aggregate from scratch for
+    /// every key modified by the input batch, this operator remembers the
+    /// current minimum of each key along with its weight.  Updates that
+    /// don't retract the minimum are processed in time proportional to the
+    /// size of the update.  The group is only scanned when all copies of its
+    /// minimum are retracted, in which case the next smallest value is located
+    /// using a cursor over the integral of the input stream.
+    #[allow(clippy::type_complexity)]
+    pub fn aggregate_min(&self) -> Stream<RootCircuit, OrdIndexedZSet<Z::Key, Z::Val, Z::R>> {
+        self.aggregate_extreme(Extreme::Min)
+    }
+
+    /// Incrementally compute the largest value associated with each key.
+    ///
+    /// Produces the same output as [`aggregate(Max)`](`Self::aggregate`).
+    /// See [`aggregate_min`](`Self::aggregate_min`) for details.
+    #[allow(clippy::type_complexity)]
+    pub fn aggregate_max(&self) -> Stream<RootCircuit, OrdIndexedZSet<Z::Key, Z::Val, Z::R>> {
+        self.aggregate_extreme(Extreme::Max)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn aggregate_extreme(
+        &self,
+        extreme: Extreme,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<Z::Key, Z::Val, Z::R>> {
+        let stream = self.shard();
+
+        self.circuit()
+            .add_binary_operator(
+                AggregateExtreme::new(extreme),
+                &stream,
+                &stream.integrate_trace(),
+            )
+            .mark_sharded()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Extreme {
+    Min,
+    Max,
+}
+
+impl Extreme {
+    /// `true` if `left` is strictly closer to the extreme than `right`.
+    fn better<V>(self, left: &V, right: &V) -> bool
+    where
+        V: Ord,
+    {
+        match self {
+            Self::Min => left < right,
+            Self::Max => left > right,
+        }
+    }
+}
+
+/// Implementation of [`Stream::aggregate_min`] and
+/// [`Stream::aggregate_max`].
+///
+/// The first input is the input batch, the second input is the integral of
+/// the input stream, including the current batch.
+struct AggregateExtreme<Z>
+where
+    Z: BatchReader,
+{
+    extreme: Extreme,
+    // The current extreme value of each key with a non-empty group, along
+    // with its weight in the integral.
+    extremes: BTreeMap<Z::Key, (Z::Val, Z::R)>,
+    _phantom: PhantomData<Z>,
+}
+
+impl<Z> AggregateExtreme<Z>
+where
+    Z: BatchReader<Time = ()>,
+{
+    fn new(extreme: Extreme) -> Self {
+        Self {
+            extreme,
+            extremes: BTreeMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Scan the group of `key` in the trace under `cursor` to find its
+    /// extreme value.
+    fn find_extreme<'s, C>(&self, cursor: &mut C, key: &Z::Key) -> Option<(Z::Val, Z::R)>
+    where
+        C: Cursor<'s, Z::Key, Z::Val, (), Z::R>,
+    {
+        let mut result = None;
+
+        cursor.seek_key(key);
+        if cursor.key_valid() && cursor.key() == key {
+            while cursor.val_valid() {
+                // Traces may contain values whose weights add up to zero.
+                let weight = cursor.weight();
+                if !weight.is_zero() {
+                    result = Some((cursor.val().clone(), weight));
+                    if self.extreme == Extreme::Min {
+                        break;
+                    }
+                }
+                cursor.step_val();
+            }
+        }
+
+        result
+    }
+}
+
+impl<Z> Operator for AggregateExtreme<Z>
+where
+    Z: BatchReader + 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        match self.extreme {
+            Extreme::Min => Cow::Borrowed("AggregateMin"),
+            Extreme::Max => Cow::Borrowed("AggregateMax"),
+        }
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<Z> BinaryOperator<Z, Spine<Z>, OrdIndexedZSet<Z::Key, Z::Val, Z::R>> for AggregateExtreme<Z>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+{
+    fn eval(&mut self, delta: &Z, trace: &Spine<Z>) -> OrdIndexedZSet<Z::Key, Z::Val, Z::R> {
+        let mut tuples = Vec::new();
+
+        let mut delta_cursor = delta.cursor();
+        let mut trace_cursor = trace.cursor();
+
+        while delta_cursor.key_valid() {
+            let key = delta_cursor.key();
+            let old = self.extremes.get(key);
+
+            // Values that are better than the old extreme were not present in
+            // the integral, so their new weights are equal to their weights in
+            // the delta.  Values that are worse than the old extreme cannot
+            // affect the result unless the extreme itself is retracted.
+            let mut best: Option<(Z::Val, Z::R)> = None;
+            let mut old_weight = old.map(|(_, weight)| weight.clone());
+
+            while delta_cursor.val_valid() {
+                let val = delta_cursor.val();
+                let weight = delta_cursor.weight();
+
+                if !weight.is_zero() {
+                    match old {
+                        Some((old_val, _)) if val == old_val => {
+                            old_weight.as_mut().unwrap().add_assign_by_ref(&weight);
+                        }
+                        Some((old_val, _)) if !self.extreme.better(val, old_val) => {}
+                        _ => {
+                            if best
+                                .as_ref()
+                                .map_or(true, |(best_val, _)| self.extreme.better(val, best_val))
+                            {
+                                best = Some((val.clone(), weight));
+                            }
+                        }
+                    }
+                }
+
+                delta_cursor.step_val();
+            }
+
+            let new = match (best, old, old_weight) {
+                (Some(best), _, _) => Some(best),
+                (None, Some((old_val, _)), Some(weight)) if !weight.is_zero() => {
+                    Some((old_val.clone(), weight))
+                }
+                // All copies of the old extreme have been retracted.
+                (None, Some(_), _) => self.find_extreme(&mut trace_cursor, key),
+                (None, None, _) => None,
+            };
+
+            let old_val = old.map(|(val, _)| val);
+            if old_val != new.as_ref().map(|(val, _)| val) {
+                if let Some(old_val) = old_val {
+                    tuples.push(((key.clone(), old_val.clone()), Z::R::one().neg()));
+                }
+                if let Some((new_val, _)) = &new {
+                    tuples.push(((key.clone(), new_val.clone()), Z::R::one()));
+                }
+            }
+
+            match new {
+                Some(new) => {
+                    self.extremes.insert(key.clone(), new);
+                }
+                None => {
+                    self.extremes.remove(key);
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        indexed_zset,
+        operator::{Max, Min},
+        trace::Batch,
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, OutputHandle, RootCircuit, Runtime,
+    };
+    use proptest::{collection, prelude::*};
+
+    type Output = OrdIndexedZSet<u64, i64, isize>;
+
+    #[test]
+    fn min_max_retractions() {
+        let (circuit, (mut input, min, max)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, i64, isize>();
+            (
+                input_handle,
+                input.aggregate_min().output(),
+                input.aggregate_max().output(),
+            )
+        })
+        .unwrap();
+
+        input.append(&mut vec![(1, (5, 1)), (1, (3, 2)), (1, (7, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(min.consolidate(), indexed_zset! { 1 => { 3 => 1 } });
+        assert_eq!(max.consolidate(), indexed_zset! { 1 => { 7 => 1 } });
+
+        // Inserting values that don't change the extremes produces no output.
+        input.append(&mut vec![(1, (4, 1)), (1, (6, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(min.consolidate(), Output::empty(()));
+        assert_eq!(max.consolidate(), Output::empty(()));
+
+        // Retract one of two copies of the minimum.
+        input.push(1, (3, -1));
+        circuit.step().unwrap();
+        assert_eq!(min.consolidate(), Output::empty(()));
+
+        // Retract the last copy of the minimum and the maximum.
+        input.append(&mut vec![(1, (3, -1)), (1, (7, -1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            min.consolidate(),
+            indexed_zset! { 1 => { 3 => -1, 4 => 1 } }
+        );
+        assert_eq!(
+            max.consolidate(),
+            indexed_zset! { 1 => { 7 => -1, 6 => 1 } }
+        );
+
+        // Insert and retract a new minimum in the same step.
+        input.append(&mut vec![(1, (1, 1)), (1, (1, -1))]);
+        circuit.step().unwrap();
+        assert_eq!(min.consolidate(), Output::empty(()));
+
+        // Replace the minimum.
+        input.append(&mut vec![(1, (4, -1)), (1, (2, 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            min.consolidate(),
+            indexed_zset! { 1 => { 4 => -1, 2 => 1 } }
+        );
+
+        // Empty the group.
+        input.append(&mut vec![(1, (2, -1)), (1, (5, -1)), (1, (6, -1))]);
+        circuit.step().unwrap();
+        assert_eq!(min.consolidate(), indexed_zset! { 1 => { 2 => -1 } });
+        assert_eq!(max.consolidate(), indexed_zset! { 1 => { 6 => -1 } });
+    }
+
+    const NUM_KEYS: u64 = 3;
+    const MAX_VAL: i64 = 4;
+    const MAX_TUPLES: usize = 10;
+    const MAX_STEPS: usize = 20;
+
+    // Small key and value ranges produce frequent inserts and retractions of
+    // the same extreme values.
+    fn test_input() -> impl Strategy<Value = Vec<Vec<(u64, (i64, isize))>>> {
+        collection::vec(
+            collection::vec((0..NUM_KEYS, (0..MAX_VAL, -2..=2isize)), 0..MAX_TUPLES),
+            0..MAX_STEPS,
+        )
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn min_max_circuit(
+        workers: usize,
+    ) -> (
+        DBSPHandle,
+        (
+            CollectionHandle<u64, (i64, isize)>,
+            [OutputHandle<Output>; 4],
+        ),
+    ) {
+        Runtime::init_circuit(workers, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, i64, isize>();
+
+            (
+                input_handle,
+                [
+                    input.aggregate_min().output(),
+                    input.aggregate(Min).output(),
+                    input.aggregate_max().output(),
+                    input.aggregate(Max).output(),
+                ],
+            )
+        })
+        .unwrap()
+    }
+
+    fn test_min_max(workers: usize, input: Vec<Vec<(u64, (i64, isize))>>) {
+        let (mut dbsp, (mut input_handle, [min, expected_min, max, expected_max])) =
+            min_max_circuit(workers);
+
+        for mut tuples in input {
+            input_handle.append(&mut tuples);
+            dbsp.step().unwrap();
+
+            assert_eq!(min.consolidate(), expected_min.consolidate());
+            assert_eq!(max.consolidate(), expected_max.consolidate());
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_min_max_st(input in test_input()) {
+            test_min_max(1, input);
+        }
+
+        #[test]
+        fn proptest_min_max_mt(input in test_input(), workers in 2..=4usize) {
+            test_min_max(workers, input);
+        }
+    }
+}
@@ -6,6 +6,7 @@ use std::{
     cmp::{min, Ordering},
     collections::{BTreeMap, BTreeSet},
     marker::PhantomData,
+    ops::ControlFlow,
 };
 
 use crate::{
@@ -14,9 +15,10 @@ use crate::{
         PartialOrder, Semigroup, ZRingValue,
     },
     circuit::{
-        operator_traits::{BinaryOperator, Operator, UnaryOperator},
+        operator_traits::{BinaryOperator, Operator, TernaryOperator, UnaryOperator},
         Circuit, Scope, Stream, WithClock,
     },
+    operator::trace::TraceBound,
     time::Timestamp,
     trace::{
         cursor::{Cursor, CursorGroup},
@@ -26,15 +28,24 @@ use crate::{
 };
 
 // Some standard aggregators.
+mod arg_max;
 mod average;
 mod fold;
 mod max;
 mod min;
+mod min_max;
+mod rollup;
+mod sketch;
+mod tuple;
 
+pub use arg_max::{ArgMax, ArgMin};
 pub use average::Avg;
-pub use fold::Fold;
+pub use fold::{Fold, FoldFilter, FoldUntil, NoFilter};
 pub use max::{Max, MaxSemigroup};
 pub use min::{Min, MinSemigroup};
+pub use rollup::RollupKey;
+pub use sketch::{QuantileSketch, SketchAggregator, SketchSemigroup};
+pub use tuple::{OptionSemigroup, TupleSemigroup};
 
 /// A trait for aggregator objects.  An aggregator summarizes the contents
 /// of a Z-set into a single value.
@@ -119,6 +130,136 @@ pub trait Aggregator<K, T, R>: Clone + 'static {
     {
         self.aggregate(cursor).map(|x| self.finalize(x))
     }
+
+    /// Adds the key under `cursor` to a partially computed aggregate without
+    /// moving the cursor.
+    ///
+    /// This method allows computing several aggregates in a single pass over
+    /// a cursor, as done by tuples of aggregators.  The caller invokes it for
+    /// each key in the cursor, starting with `accumulator` set to `None`, until
+    /// the method returns `ControlFlow::Break`, which indicates that
+    /// remaining keys cannot affect the aggregate.  At the end of the
+    /// traversal, `accumulator` must be equal to the result of
+    /// [`aggregate`](`Self::aggregate`) over the same cursor.
+    ///
+    /// The default implementation aggregates the current key on its own and
+    /// combines the result with `accumulator` using
+    /// [`Semigroup`](`Self::Semigroup`).
+    fn step<'s, C>(
+        &self,
+        accumulator: &mut Option<Self::Accumulator>,
+        cursor: &mut C,
+    ) -> ControlFlow<()>
+    where
+        K: Ord,
+        C: Cursor<'s, K, (), T, R>,
+    {
+        let key_accumulator = self.aggregate(&mut CurrentKeyCursor::new(cursor));
+        *accumulator = Self::Semigroup::combine_opt(accumulator, &key_accumulator);
+        ControlFlow::Continue(())
+    }
+}
+
+/// Cursor over the current key of another cursor.
+///
+/// Used by the default implementation of [`Aggregator::step`] to apply
+/// [`Aggregator::aggregate`] to a single key without moving the underlying
+/// cursor.
+struct CurrentKeyCursor<'c, C> {
+    cursor: &'c mut C,
+    key_valid: bool,
+    val_valid: bool,
+}
+
+impl<'c, C> CurrentKeyCursor<'c, C> {
+    fn new(cursor: &'c mut C) -> Self {
+        Self {
+            cursor,
+            key_valid: true,
+            val_valid: true,
+        }
+    }
+}
+
+impl<'s, 'c, K, T, R, C> Cursor<'s, K, (), T, R> for CurrentKeyCursor<'c, C>
+where
+    K: Ord,
+    C: Cursor<'s, K, (), T, R>,
+{
+    fn key_valid(&self) -> bool {
+        self.key_valid
+    }
+
+    fn val_valid(&self) -> bool {
+        self.key_valid && self.val_valid
+    }
+
+    fn key(&self) -> &K {
+        self.cursor.key()
+    }
+
+    fn val(&self) -> &() {
+        &()
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &T, &R) -> U,
+    {
+        self.cursor.fold_times(init, fold)
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &T, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &T, &R) -> U,
+    {
+        self.cursor.fold_times_through(upper, init, fold)
+    }
+
+    fn weight(&mut self) -> R
+    where
+        T: PartialEq<()>,
+    {
+        self.cursor.weight()
+    }
+
+    fn step_key(&mut self) {
+        self.key_valid = false;
+    }
+
+    fn seek_key(&mut self, key: &K) {
+        if self.key_valid && self.cursor.key() < key {
+            self.key_valid = false;
+        }
+    }
+
+    fn last_key(&mut self) -> Option<&K> {
+        Some(self.cursor.key())
+    }
+
+    fn step_val(&mut self) {
+        self.val_valid = false;
+    }
+
+    fn seek_val(&mut self, _val: &()) {}
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&()) -> bool + Clone,
+    {
+        if !predicate(&()) {
+            self.val_valid = false;
+        }
+    }
+
+    fn rewind_keys(&mut self) {
+        self.key_valid = true;
+        self.val_valid = true;
+    }
+
+    fn rewind_vals(&mut self) {
+        self.val_valid = true;
+    }
 }
 
 /// Aggregator used internally by [`Stream::aggregate_linear`].  Computes
@@ -155,6 +296,107 @@ where
     }
 }
 
+/// Aggregator used internally by [`Stream::aggregate_count_distinct`].
+/// Counts values with positive weights.
+#[derive(Clone)]
+struct CountDistinct;
+
+impl<V, T, R> Aggregator<V, T, R> for CountDistinct
+where
+    T: Timestamp,
+    R: DBWeight + ZRingValue,
+{
+    type Accumulator = i64;
+    type Output = i64;
+    type Semigroup = DefaultSemigroup<i64>;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Output>
+    where
+        C: Cursor<'s, V, (), T, R>,
+    {
+        let mut count = 0;
+
+        while cursor.key_valid() {
+            let mut weight = R::zero();
+
+            cursor.map_times(|_t, w| weight.add_assign_by_ref(w));
+
+            if !weight.le0() {
+                count += 1;
+            }
+
+            cursor.step_key();
+        }
+
+        (count > 0).then_some(count)
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        accumulator
+    }
+}
+
+/// Aggregator used internally by [`Stream::group_collect`].  Collects
+/// values with positive weights into a sorted vector.
+#[derive(Clone)]
+struct GroupCollect;
+
+/// Semigroup used by [`GroupCollect`]: merges two sorted vectors of distinct
+/// values.
+#[derive(Clone)]
+struct GroupCollectSemigroup<V>(PhantomData<V>);
+
+impl<V> Semigroup<Vec<V>> for GroupCollectSemigroup<V>
+where
+    V: Ord + Clone,
+{
+    fn combine(left: &Vec<V>, right: &Vec<V>) -> Vec<V> {
+        let mut result = Vec::with_capacity(left.len() + right.len());
+        result.extend_from_slice(left);
+        result.extend_from_slice(right);
+        result.sort();
+        result.dedup();
+        result
+    }
+}
+
+impl<V, T, R> Aggregator<V, T, R> for GroupCollect
+where
+    V: DBData,
+    T: Timestamp,
+    R: DBWeight + ZRingValue,
+{
+    type Accumulator = Vec<V>;
+    type Output = Vec<V>;
+    type Semigroup = GroupCollectSemigroup<V>;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Output>
+    where
+        C: Cursor<'s, V, (), T, R>,
+    {
+        let mut values = Vec::new();
+
+        // The cursor yields values in ascending order, so `values` is sorted.
+        while cursor.key_valid() {
+            let mut weight = R::zero();
+
+            cursor.map_times(|_t, w| weight.add_assign_by_ref(w));
+
+            if !weight.le0() {
+                values.push(cursor.key().clone());
+            }
+
+            cursor.step_key();
+        }
+
+        (!values.is_empty()).then_some(values)
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        accumulator
+    }
+}
+
 impl<C, Z> Stream<C, Z>
 where
     C: Circuit,
@@ -212,6 +454,25 @@ where
 
     /// Like [`Self::aggregate`], but can return any batch type.
     pub fn aggregate_generic<A, O>(&self, aggregator: A) -> Stream<C, O>
+    where
+        Z: IndexedZSet + Send,
+        A: Aggregator<Z::Val, <C as WithClock>::Time, Z::R>,
+        O: Batch<Key = Z::Key, Val = A::Output, Time = ()>,
+        O::R: ZRingValue,
+    {
+        self.aggregate_generic_with_bound(aggregator, TraceBound::new())
+    }
+
+    /// Like [`Self::aggregate_generic`], but truncates keys below
+    /// `lower_key_bound` in the input and output traces of the operator.
+    ///
+    /// The caller must guarantee that the input stream does not contain
+    /// updates to keys below the bound once the bound has been set.
+    pub(crate) fn aggregate_generic_with_bound<A, O>(
+        &self,
+        aggregator: A,
+        lower_key_bound: TraceBound<Z::Key>,
+    ) -> Stream<C, O>
     where
         Z: IndexedZSet + Send,
         A: Aggregator<Z::Val, <C as WithClock>::Time, Z::R>,
@@ -237,12 +498,164 @@ where
             .add_binary_operator(
                 AggregateIncremental::new(aggregator, circuit.clone()),
                 &stream,
-                &stream.trace::<Spine<<<C as WithClock>::Time as Timestamp>::OrdValBatch<Z::Key, Z::Val, Z::R>>>(),
+                &stream.trace_with_bound::<Spine<
+                    <<C as WithClock>::Time as Timestamp>::OrdValBatch<Z::Key, Z::Val, Z::R>,
+                >>(lower_key_bound.clone(), TraceBound::new()),
             )
-            .upsert::<O>()
+            .upsert_with_bound::<O>(lower_key_bound)
             .mark_sharded()
     }
 
+    /// Incrementally aggregate values associated with each key in an indexed
+    /// Z-set, grouped by an application-defined timestamp.
+    ///
+    /// Applies `extractor` to each `(key, value)` pair in the input to compute
+    /// its timestamp, and aggregates all values associated with each
+    /// `(key, timestamp)` pair.  The output indexed Z-set maps each such pair
+    /// to its aggregate.
+    ///
+    /// The result is the same as indexing the input by `(key, timestamp)` and
+    /// applying [`Self::aggregate`]; however, this operator does not use the
+    /// circuit clock.  Instead it maintains a trace of the input, where each
+    /// tuple is labeled with its extracted timestamp, and evaluates
+    /// `aggregator` over this trace at the timestamp of each group.  This
+    /// makes it possible to use aggregators parameterized by an application
+    /// time type `TS` inside nested scopes.  Like other operators based on
+    /// [`integrate_trace`](`Self::integrate_trace`), inside a nested scope the
+    /// operator aggregates the inputs received during the current clock epoch
+    /// of the parent scope.
+    #[allow(clippy::type_complexity)]
+    pub fn aggregate_by_time<TS, F, A>(
+        &self,
+        extractor: F,
+        aggregator: A,
+    ) -> Stream<C, OrdIndexedZSet<(Z::Key, TS), A::Output, Z::R>>
+    where
+        Z: IndexedZSet + Send,
+        Z::R: ZRingValue,
+        TS: DBTimestamp,
+        F: Fn(&Z::Key, &Z::Val) -> TS + 'static,
+        A: Aggregator<Z::Val, TS, Z::R>,
+    {
+        self.aggregate_by_time_generic(extractor, aggregator)
+    }
+
+    /// Like [`Self::aggregate_by_time`], but can return any batch type.
+    pub fn aggregate_by_time_generic<TS, F, A, O>(
+        &self,
+        extractor: F,
+        aggregator: A,
+    ) -> Stream<C, O>
+    where
+        Z: IndexedZSet + Send,
+        TS: DBTimestamp,
+        F: Fn(&Z::Key, &Z::Val) -> TS + 'static,
+        A: Aggregator<Z::Val, TS, Z::R>,
+        O: Batch<Key = (Z::Key, TS), Val = A::Output, Time = ()>,
+        O::R: ZRingValue,
+    {
+        let circuit = self.circuit();
+
+        // All values with the same key are assigned to the same worker, and so
+        // are all values with the same `(key, timestamp)` pair.
+        let stream = self.shard().apply_named("IndexByTime", move |batch: &Z| {
+            let mut tuples = Vec::with_capacity(batch.len());
+            let mut cursor = batch.cursor();
+            while cursor.key_valid() {
+                while cursor.val_valid() {
+                    let ts = extractor(cursor.key(), cursor.val());
+                    tuples.push((
+                        ((cursor.key().clone(), ts), cursor.val().clone()),
+                        cursor.weight(),
+                    ));
+                    cursor.step_val();
+                }
+                cursor.step_key();
+            }
+            OrdIndexedZSet::from_tuples((), tuples)
+        });
+
+        let trace = stream
+            .trace_with_time::<Spine<TS::OrdValBatch<(Z::Key, TS), Z::Val, Z::R>>, _>(
+                |(_key, ts), _val| ts.clone(),
+            );
+
+        circuit.add_ternary_operator(
+            AggregateByTime::new(aggregator),
+            &stream,
+            &trace.delay_trace(),
+            &trace,
+        )
+    }
+
+    /// Incrementally count distinct values associated with each key in an
+    /// indexed Z-set.
+    ///
+    /// A value is counted if its weight, consolidated across all updates
+    /// received so far, is positive.  Keys that have no such values are not
+    /// present in the output.  This is equivalent to, but more efficient
+    /// than, applying [`distinct`](`Self::distinct`) to the input stream
+    /// followed by a count, as it computes the count directly from the
+    /// input trace without materializing the set of distinct values.
+    ///
+    /// Like other incremental aggregates, this operator only recomputes
+    /// counts for keys modified by each input update.
+    #[allow(clippy::type_complexity)]
+    pub fn aggregate_count_distinct(&self) -> Stream<C, OrdIndexedZSet<Z::Key, i64, Z::R>>
+    where
+        Z: IndexedZSet + Send,
+        Z::R: ZRingValue,
+    {
+        self.aggregate_count_distinct_generic()
+    }
+
+    /// Like [`Self::aggregate_count_distinct`], but can return any batch
+    /// type.
+    pub fn aggregate_count_distinct_generic<O>(&self) -> Stream<C, O>
+    where
+        Z: IndexedZSet + Send,
+        Z::R: ZRingValue,
+        O: Batch<Key = Z::Key, Val = i64, Time = ()>,
+        O::R: ZRingValue,
+    {
+        self.aggregate_generic(CountDistinct)
+    }
+
+    /// Incrementally collect values associated with each key in an indexed
+    /// Z-set into a vector.
+    ///
+    /// For each key, the output contains a single `(key, values)` tuple
+    /// with weight 1, where `values` is a sorted vector of distinct values
+    /// whose weight, consolidated across all updates received so far, is
+    /// positive.  Keys that have no such values are not present in the
+    /// output.
+    ///
+    /// Like other incremental aggregates, this operator only recomputes
+    /// vectors for keys modified by each input update.  When the contents of
+    /// a key change, the output batch retracts the old vector and inserts the
+    /// new one, so a consumer can apply the output as a sequence of upserts.
+    /// Note that the entire vector is rebuilt on each update to the key,
+    /// which can be expensive for large groups.
+    #[allow(clippy::type_complexity)]
+    pub fn group_collect(&self) -> Stream<C, OrdIndexedZSet<Z::Key, Vec<Z::Val>, Z::R>>
+    where
+        Z: IndexedZSet + Send,
+        Z::R: ZRingValue,
+    {
+        self.group_collect_generic()
+    }
+
+    /// Like [`Self::group_collect`], but can return any batch type.
+    pub fn group_collect_generic<O>(&self) -> Stream<C, O>
+    where
+        Z: IndexedZSet + Send,
+        Z::R: ZRingValue,
+        O: Batch<Key = Z::Key, Val = Vec<Z::Val>, Time = ()>,
+        O::R: ZRingValue,
+    {
+        self.aggregate_generic(GroupCollect)
+    }
+
     /// A version of [`Self::aggregate`] optimized for linear
     /// aggregation functions.
     ///
@@ -684,6 +1097,118 @@ where
     }
 }
 
+/// Incremental aggregation operator used by
+/// [`aggregate_by_time`](`Stream::aggregate_by_time`).
+///
+/// * Input stream 1: updates to the input collection indexed by
+///   `(key, timestamp)`.
+/// * Input stream 2: delayed trace of the input collection, where each tuple
+///   is labeled with the timestamp in its key.
+/// * Input stream 3: the same trace, including the current update.
+///
+/// For each `(key, ts)` pair in the update, the operator computes the old and
+/// the new aggregate of values associated with the pair at time `ts` from the
+/// delayed and the current trace respectively, and outputs the difference.
+/// Since all values of the pair are labeled with the same timestamp, unlike
+/// `AggregateIncremental`, this operator never needs to revisit a pair at a
+/// later time.
+struct AggregateByTime<Z, IT, A, O> {
+    aggregator: A,
+    // The last input batch was empty - used in fixedpoint computation.
+    empty_input: bool,
+    _type: PhantomData<(Z, IT, O)>,
+}
+
+impl<Z, IT, A, O> AggregateByTime<Z, IT, A, O> {
+    pub fn new(aggregator: A) -> Self {
+        Self {
+            aggregator,
+            empty_input: false,
+            _type: PhantomData,
+        }
+    }
+
+    /// Computes the aggregate of values associated with `key` in the trace at
+    /// time `ts`.
+    fn eval_key<K, TS>(&self, key: &(K, TS), trace: &IT) -> Option<A::Output>
+    where
+        K: DBData,
+        TS: DBTimestamp,
+        IT: BatchReader<Key = (K, TS), Time = TS>,
+        A: Aggregator<IT::Val, TS, IT::R>,
+    {
+        let mut cursor = trace.cursor();
+
+        cursor.seek_key(key);
+        if cursor.key_valid() && cursor.key() == key {
+            self.aggregator
+                .aggregate_and_finalize(&mut CursorGroup::new(&mut cursor, key.1.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<Z, IT, A, O> Operator for AggregateByTime<Z, IT, A, O>
+where
+    Z: 'static,
+    IT: 'static,
+    A: 'static,
+    O: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("AggregateByTime")
+    }
+
+    fn clock_start(&mut self, scope: Scope) {
+        if scope == 0 {
+            self.empty_input = false;
+        }
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        self.empty_input
+    }
+}
+
+impl<K, TS, Z, IT, A, O> TernaryOperator<Z, IT, IT, O> for AggregateByTime<Z, IT, A, O>
+where
+    K: DBData,
+    TS: DBTimestamp,
+    Z: IndexedZSet<Key = (K, TS)>,
+    IT: BatchReader<Key = (K, TS), Val = Z::Val, Time = TS, R = Z::R> + Clone,
+    A: Aggregator<Z::Val, TS, Z::R>,
+    O: Batch<Key = (K, TS), Val = A::Output, Time = ()>,
+    O::R: ZRingValue,
+{
+    fn eval<'a>(&mut self, delta: Cow<'a, Z>, delayed_trace: Cow<'a, IT>, trace: Cow<'a, IT>) -> O {
+        self.empty_input = delta.is_empty();
+
+        let mut tuples = Vec::with_capacity(2 * delta.key_count());
+        let mut delta_cursor = delta.cursor();
+
+        while delta_cursor.key_valid() {
+            let key = delta_cursor.key();
+
+            let old = self.eval_key(key, &delayed_trace);
+            let new = self.eval_key(key, &trace);
+
+            if old != new {
+                if let Some(old) = old {
+                    tuples.push((O::item_from(key.clone(), old), -O::R::one()));
+                }
+                if let Some(new) = new {
+                    tuples.push((O::item_from(key.clone(), new), O::R::one()));
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        O::from_tuples((), tuples)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::{
@@ -696,7 +1221,7 @@ mod test {
         algebra::DefaultSemigroup,
         indexed_zset,
         operator::GeneratorNested,
-        operator::{Fold, Min},
+        operator::{FilterMap, Fold, Min},
         trace::{cursor::Cursor, Batch, BatchReader},
         zset, Circuit, OrdIndexedZSet, OrdZSet, RootCircuit, Runtime, Stream,
     };
@@ -1006,4 +1531,213 @@ mod test {
     fn count_test4() {
         count_test(4);
     }
+
+    fn count_distinct_test(workers: usize) {
+        let (mut dbsp, (mut input_handle, output_handle)) =
+            Runtime::init_circuit(workers, move |circuit| {
+                let (input_stream, input_handle) =
+                    circuit.add_input_indexed_zset::<usize, usize, isize>();
+                let output_handle = input_stream.aggregate_count_distinct().integrate().output();
+
+                (input_handle, output_handle)
+            })
+            .unwrap();
+
+        input_handle.append(&mut vec![(1, (1, 1)), (1, (2, 2)), (2, (5, 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {1 => {2 => 1}, 2 => {1 => 1}}
+        );
+
+        // Value 1 cancels out; value 6 has negative weight and is not counted.
+        input_handle.append(&mut vec![(1, (1, -1)), (2, (6, -1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {1 => {1 => 1}, 2 => {1 => 1}}
+        );
+
+        // Value 1 re-appears, value 2 cancels out, value 6 becomes positive.
+        input_handle.append(&mut vec![(1, (1, 1)), (1, (2, -2)), (2, (6, 2))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {1 => {1 => 1}, 2 => {2 => 1}}
+        );
+
+        // Key 1 no longer has any values.
+        input_handle.append(&mut vec![(1, (1, -1))]);
+        dbsp.step().unwrap();
+        assert_eq!(output_handle.consolidate(), indexed_zset! {2 => {2 => 1}});
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn count_distinct_test1() {
+        count_distinct_test(1);
+    }
+
+    #[test]
+    fn count_distinct_test4() {
+        count_distinct_test(4);
+    }
+
+    fn group_collect_test(workers: usize) {
+        let (mut dbsp, (mut input_handle, output_handle, integral_handle)) =
+            Runtime::init_circuit(workers, move |circuit| {
+                let (input_stream, input_handle) =
+                    circuit.add_input_indexed_zset::<usize, usize, isize>();
+                let output = input_stream.group_collect();
+
+                (input_handle, output.output(), output.integrate().output())
+            })
+            .unwrap();
+
+        input_handle.append(&mut vec![
+            (1, (3, 1)),
+            (1, (1, 2)),
+            (2, (5, 1)),
+            (2, (6, -1)),
+        ]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {1 => {vec![1, 3] => 1}, 2 => {vec![5] => 1}}
+        );
+
+        // Updates retract the old vector and insert the new one; untouched
+        // keys produce no output.
+        input_handle.append(&mut vec![(1, (2, 1)), (1, (3, -1)), (3, (7, 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {1 => {vec![1, 3] => -1, vec![1, 2] => 1}, 3 => {vec![7] => 1}}
+        );
+        assert_eq!(
+            integral_handle.consolidate(),
+            indexed_zset! {1 => {vec![1, 2] => 1}, 2 => {vec![5] => 1}, 3 => {vec![7] => 1}}
+        );
+
+        // Value 6 becomes positive; key 3 no longer has any values, so its
+        // vector is retracted without a replacement.
+        input_handle.append(&mut vec![(2, (6, 2)), (3, (7, -1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {2 => {vec![5] => -1, vec![5, 6] => 1}, 3 => {vec![7] => -1}}
+        );
+
+        // Large groups.
+        let all: Vec<usize> = (0..1000).collect();
+        let odd: Vec<usize> = (1..1000).step_by(2).collect();
+
+        input_handle.append(&mut (0..1000).rev().map(|v| (4, (v, 1))).collect());
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {4 => {all.clone() => 1}}
+        );
+
+        input_handle.append(&mut (0..1000).step_by(2).map(|v| (4, (v, -1))).collect());
+        dbsp.step().unwrap();
+        assert_eq!(
+            output_handle.consolidate(),
+            indexed_zset! {4 => {all => -1, odd.clone() => 1}}
+        );
+        assert_eq!(
+            integral_handle.consolidate(),
+            indexed_zset! {
+                1 => {vec![1, 2] => 1},
+                2 => {vec![5, 6] => 1},
+                4 => {odd => 1}
+            }
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn group_collect_test1() {
+        group_collect_test(1);
+    }
+
+    #[test]
+    fn group_collect_test4() {
+        group_collect_test(4);
+    }
+
+    type TimedTuple = (usize, ((u32, isize), isize));
+
+    // Compare `aggregate_by_time` against the ordinary `aggregate` of the
+    // input indexed by `(key, timestamp)`.
+    fn aggregate_by_time_test(workers: usize, inputs: Vec<Vec<TimedTuple>>) {
+        let (mut dbsp, mut input_handle) = Runtime::init_circuit(workers, |circuit| {
+            let (input, input_handle) =
+                circuit.add_input_indexed_zset::<usize, (u32, isize), isize>();
+            let indexed_by_time = input.map_index(|(k, (ts, v))| ((*k, *ts), (*ts, *v)));
+
+            let sum = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0,
+                |acc: &mut isize, (_ts, v): &(u32, isize), w: isize| *acc += *v * w,
+            );
+
+            let sum_expected = indexed_by_time.aggregate(sum.clone()).gather(0).integrate();
+            let sum_actual = input
+                .aggregate_by_time(|_k, (ts, _v)| *ts, sum)
+                .gather(0)
+                .integrate();
+            sum_expected.apply2(&sum_actual, |expected, actual| assert_eq!(expected, actual));
+
+            let min_expected = indexed_by_time.aggregate(Min).gather(0).integrate();
+            let min_actual = input
+                .aggregate_by_time(|_k, (ts, _v)| *ts, Min)
+                .gather(0)
+                .integrate();
+            min_expected.apply2(&min_actual, |expected, actual| assert_eq!(expected, actual));
+
+            input_handle
+        })
+        .unwrap();
+
+        for mut batch in inputs {
+            input_handle.append(&mut batch);
+            dbsp.step().unwrap();
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn aggregate_by_time_test1() {
+        aggregate_by_time_test(
+            1,
+            vec![
+                vec![(1, ((10, 1), 1)), (1, ((10, 2), 1)), (1, ((20, 5), 1))],
+                // Update both timestamps of key 1 and add key 2.
+                vec![(1, ((10, 1), -1)), (1, ((20, -3), 2)), (2, ((10, 4), 1))],
+                // Remove all values of key 1 with timestamp 10.
+                vec![(1, ((10, 2), -1))],
+            ],
+        );
+    }
+
+    fn timed_input() -> impl Strategy<Value = Vec<Vec<TimedTuple>>> {
+        collection::vec(
+            collection::vec(
+                (0..NUM_KEYS, ((0..5u32, -MAX_VAL..MAX_VAL), -1..=1isize)),
+                0..MAX_TUPLES,
+            ),
+            0..MAX_ROUNDS,
+        )
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_aggregate_by_time(inputs in timed_input(), workers in (1..=4usize)) {
+            aggregate_by_time_test(workers, inputs);
+        }
+    }
 }
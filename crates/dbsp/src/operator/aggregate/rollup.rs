@@ -0,0 +1,239 @@
+//! Aggregation over rolled-up grouping hierarchies (`GROUP BY ROLLUP`).
+
+use crate::{
+    algebra::{IndexedZSet, ZRingValue},
+    circuit::{Circuit, Stream, WithClock},
+    operator::{Aggregator, FlatMap},
+    trace::Batch,
+    DBData, DBTimestamp, OrdIndexedZSet,
+};
+
+/// A composite key that can be rolled up to its prefixes.
+///
+/// Implemented for tuples of up to six [`DBData`] components.  Rolling up
+/// a key `(a, b, c)` to level `1` yields `(Some(a), None, None)`, i.e.,
+/// components that are not part of the prefix are replaced with `None`.
+/// Level `0` is the grand total, level `Self::LEVELS` is the complete key.
+pub trait RollupKey: DBData {
+    /// Rolled-up key type: a tuple of `Option`s with one element per
+    /// component of the key.
+    type Rollup: DBData;
+
+    /// The number of components in the key.
+    const LEVELS: usize;
+
+    /// Roll up `self` to its prefix of length `level`.
+    fn rollup(&self, level: usize) -> Self::Rollup;
+}
+
+macro_rules! rollup_key {
+    ($(($component:ident, $index:tt)),+) => {
+        impl<$($component),+> RollupKey for ($($component,)+)
+        where
+            $($component: DBData,)+
+        {
+            type Rollup = ($(Option<$component>,)+);
+
+            const LEVELS: usize = [$($index),+].len();
+
+            fn rollup(&self, level: usize) -> Self::Rollup {
+                ($((level > $index).then(|| self.$index.clone()),)+)
+            }
+        }
+    };
+}
+
+rollup_key!((A, 0));
+rollup_key!((A, 0), (B, 1));
+rollup_key!((A, 0), (B, 1), (C, 2));
+rollup_key!((A, 0), (B, 1), (C, 2), (D, 3));
+rollup_key!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4));
+rollup_key!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5));
+
+impl<C, Z> Stream<C, Z>
+where
+    C: Circuit,
+    <C as WithClock>::Time: DBTimestamp,
+    Z: Clone + 'static,
+{
+    /// Incrementally aggregate an indexed Z-set at multiple levels of a
+    /// grouping hierarchy, like SQL `GROUP BY ROLLUP`.
+    ///
+    /// The input is keyed by a composite key that implements [`RollupKey`].
+    /// For each prefix length in `levels`, the operator aggregates the values
+    /// of all keys that share the same prefix.  The output maps each
+    /// rolled-up key to its aggregate, with components outside of the prefix
+    /// set to `None`.  For example, `GROUP BY ROLLUP(a, b)` over a stream
+    /// keyed by `(a, b)` corresponds to `levels = &[0, 1, 2]`, which
+    /// produces aggregates for keys `(None, None)`, `(Some(a), None)` and
+    /// `(Some(a), Some(b))`.
+    ///
+    /// The result is the same as the sum of independent aggregates computed
+    /// over the input rolled up to each level; however each input update is
+    /// read once and routed to all levels by a single operator, and all levels
+    /// share the same trace and aggregation operator.  Like
+    /// [`aggregate`](`Self::aggregate`), the operator only recomputes groups
+    /// modified by each input update and retracts the aggregates of groups
+    /// that become empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of `levels` exceeds the number of components of the
+    /// key.
+    #[allow(clippy::type_complexity)]
+    pub fn aggregate_rollup<A>(
+        &self,
+        levels: &[usize],
+        aggregator: A,
+    ) -> Stream<C, OrdIndexedZSet<<Z::Key as RollupKey>::Rollup, A::Output, Z::R>>
+    where
+        Z: IndexedZSet + Send,
+        Z::Key: RollupKey,
+        Z::R: ZRingValue,
+        A: Aggregator<Z::Val, <C as WithClock>::Time, Z::R>,
+    {
+        self.aggregate_rollup_generic(levels, aggregator)
+    }
+
+    /// Like [`Self::aggregate_rollup`], but can return any batch type.
+    pub fn aggregate_rollup_generic<A, O>(&self, levels: &[usize], aggregator: A) -> Stream<C, O>
+    where
+        Z: IndexedZSet + Send,
+        Z::Key: RollupKey,
+        Z::R: ZRingValue,
+        A: Aggregator<Z::Val, <C as WithClock>::Time, Z::R>,
+        O: Batch<Key = <Z::Key as RollupKey>::Rollup, Val = A::Output, Time = ()>,
+        O::R: ZRingValue,
+    {
+        let mut levels = levels.to_vec();
+        levels.sort_unstable();
+        levels.dedup();
+        if let Some(level) = levels.last() {
+            assert!(
+                *level <= <Z::Key as RollupKey>::LEVELS,
+                "aggregate_rollup: level {level} exceeds the number of key components ({})",
+                <Z::Key as RollupKey>::LEVELS
+            );
+        }
+
+        let rolled_up: Stream<C, OrdIndexedZSet<<Z::Key as RollupKey>::Rollup, Z::Val, Z::R>> =
+            self.circuit().add_unary_operator(
+                FlatMap::new(move |(key, val): (&Z::Key, &Z::Val)| {
+                    levels
+                        .iter()
+                        .map(|level| (key.rollup(*level), val.clone()))
+                        .collect::<Vec<_>>()
+                }),
+                self,
+            );
+
+        rolled_up.aggregate_generic::<A, O>(aggregator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        algebra::DefaultSemigroup,
+        indexed_zset,
+        operator::{FilterMap, Fold, Min, RollupKey},
+        OrdIndexedZSet, RootCircuit,
+    };
+
+    type Sum = Fold<i64, DefaultSemigroup<i64>, fn(&mut i64, &i64, isize), fn(i64) -> i64>;
+
+    fn sum_step(acc: &mut i64, v: &i64, w: isize) {
+        *acc += *v * w as i64;
+    }
+
+    fn sum() -> Sum {
+        Fold::new(0, sum_step as fn(&mut i64, &i64, isize))
+    }
+
+    #[test]
+    fn rollup_key() {
+        assert_eq!(<(u32, u32, u32)>::LEVELS, 3);
+        assert_eq!((1, 2, 3).rollup(0), (None, None, None));
+        assert_eq!((1, 2, 3).rollup(2), (Some(1), Some(2), None));
+        assert_eq!((1, 2, 3).rollup(3), (Some(1), Some(2), Some(3)));
+    }
+
+    #[test]
+    fn rollup_matches_per_level_aggregates() {
+        let (circuit, (mut input, output)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<(u32, u32), i64, isize>();
+
+            let rollup = input.aggregate_rollup(&[0, 1, 2], (sum(), Min));
+
+            // Aggregate each level independently.
+            let levels: Vec<_> = (0..=2)
+                .map(|level| {
+                    input
+                        .map_index(move |(key, val)| (key.rollup(level), *val))
+                        .aggregate((sum(), Min))
+                })
+                .collect();
+            rollup.apply2(&levels[0].sum(&levels[1..]), |rollup, expected| {
+                assert_eq!(rollup, expected)
+            });
+
+            (input_handle, rollup.output())
+        })
+        .unwrap();
+
+        input.append(&mut vec![
+            ((1, 1), (10, 1)),
+            ((1, 2), (5, 1)),
+            ((2, 1), (7, 1)),
+        ]);
+        circuit.step().unwrap();
+
+        // Group `2` empties out at the middle level.
+        input.append(&mut vec![((1, 1), (3, 1)), ((2, 1), (7, -1))]);
+        circuit.step().unwrap();
+        let expected: OrdIndexedZSet<
+            (Option<u32>, Option<u32>),
+            (Option<i64>, Option<i64>),
+            isize,
+        > = indexed_zset! {
+            (None, None) => { (Some(22), Some(5)) => -1, (Some(18), Some(3)) => 1 },
+            (Some(1), None) => { (Some(15), Some(5)) => -1, (Some(18), Some(3)) => 1 },
+            (Some(2), None) => { (Some(7), Some(7)) => -1 },
+            (Some(1), Some(1)) => { (Some(10), Some(10)) => -1, (Some(13), Some(3)) => 1 },
+            (Some(2), Some(1)) => { (Some(7), Some(7)) => -1 },
+        };
+        assert_eq!(output.consolidate(), expected);
+
+        // All groups empty out.
+        input.append(&mut vec![
+            ((1, 1), (10, -1)),
+            ((1, 1), (3, -1)),
+            ((1, 2), (5, -1)),
+        ]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![((2, 2), (4, 1)), ((2, 3), (4, 2))]);
+        circuit.step().unwrap();
+        let expected: OrdIndexedZSet<
+            (Option<u32>, Option<u32>),
+            (Option<i64>, Option<i64>),
+            isize,
+        > = indexed_zset! {
+            (None, None) => { (Some(12), Some(4)) => 1 },
+            (Some(2), None) => { (Some(12), Some(4)) => 1 },
+            (Some(2), Some(2)) => { (Some(4), Some(4)) => 1 },
+            (Some(2), Some(3)) => { (Some(8), Some(4)) => 1 },
+        };
+        assert_eq!(output.consolidate(), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the number of key components")]
+    fn rollup_invalid_level() {
+        RootCircuit::build(|circuit| {
+            let (input, _input_handle) = circuit.add_input_indexed_zset::<(u32, u32), i64, isize>();
+            input.aggregate_rollup(&[3], Min);
+        })
+        .unwrap();
+    }
+}
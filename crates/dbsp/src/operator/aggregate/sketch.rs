@@ -0,0 +1,525 @@
+use crate::{
+    algebra::{MonoidValue, Semigroup, F64},
+    operator::aggregate::Aggregator,
+    trace::Cursor,
+    DBData, Timestamp,
+};
+use num::ToPrimitive;
+use size_of::SizeOf;
+use std::cmp::Ordering;
+
+/// A mergeable sketch that summarizes a distribution of numeric values and
+/// can be used to compute approximate quantiles.
+///
+/// This is an implementation of
+/// [DDSketch](https://www.vldb.org/pvldb/vol12/p2195-masson.pdf).  Values are
+/// assigned to logarithmically sized buckets, such that any two values in the
+/// same bucket are within the relative accuracy configured in the
+/// [`SketchAggregator`] from each other.  The sketch only stores the number of
+/// values in each bucket and does not depend on the relative accuracy
+/// parameter, which is therefore stored in the aggregator and not in the
+/// sketch.
+///
+/// Sketches form a commutative group with bucket-wise addition of counts, so
+/// they can be merged (see [`SketchSemigroup`]) and values can be removed
+/// from a sketch by subtracting their counts.  Removing a value produces the
+/// same sketch as if the value was never added, hence quantiles computed
+/// after deletions retain the same accuracy guarantees.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    SizeOf,
+    bincode::Decode,
+    bincode::Encode,
+)]
+pub struct QuantileSketch {
+    /// Number of values too close to zero to be assigned to a bucket.
+    zero_count: i64,
+    /// `(bucket, count)` pairs for positive values, ordered by bucket index.
+    positive: Vec<(i32, i64)>,
+    /// `(bucket, count)` pairs for absolute values of negative values,
+    /// ordered by bucket index.
+    negative: Vec<(i32, i64)>,
+}
+
+impl QuantileSketch {
+    /// Returns the total number of values in the sketch.
+    pub fn count(&self) -> i64 {
+        self.zero_count
+            + self.positive.iter().map(|(_, count)| count).sum::<i64>()
+            + self.negative.iter().map(|(_, count)| count).sum::<i64>()
+    }
+
+    /// Returns `true` if the sketch doesn't contain any values.
+    pub fn is_empty(&self) -> bool {
+        self.zero_count == 0 && self.positive.is_empty() && self.negative.is_empty()
+    }
+
+    /// Merge two sketches by adding up the counts of matching buckets.
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            zero_count: self.zero_count + other.zero_count,
+            positive: Self::merge_buckets(&self.positive, &other.positive),
+            negative: Self::merge_buckets(&self.negative, &other.negative),
+        }
+    }
+
+    fn merge_buckets(left: &[(i32, i64)], right: &[(i32, i64)]) -> Vec<(i32, i64)> {
+        let mut result = Vec::with_capacity(left.len() + right.len());
+        let mut i = 0;
+        let mut j = 0;
+
+        while i < left.len() && j < right.len() {
+            match left[i].0.cmp(&right[j].0) {
+                Ordering::Less => {
+                    result.push(left[i]);
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    result.push(right[j]);
+                    j += 1;
+                }
+                Ordering::Equal => {
+                    let count = left[i].1 + right[j].1;
+                    if count != 0 {
+                        result.push((left[i].0, count));
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+
+        result.extend_from_slice(&left[i..]);
+        result.extend_from_slice(&right[j..]);
+        result
+    }
+
+    /// Add `count` to bucket `bucket` in `buckets`.
+    fn add_to_bucket(buckets: &mut Vec<(i32, i64)>, bucket: i32, count: i64) {
+        match buckets.binary_search_by_key(&bucket, |(bucket, _)| *bucket) {
+            Ok(idx) => {
+                buckets[idx].1 += count;
+                if buckets[idx].1 == 0 {
+                    buckets.remove(idx);
+                }
+            }
+            Err(idx) => buckets.insert(idx, (bucket, count)),
+        }
+    }
+}
+
+/// Semigroup over [`QuantileSketch`] that merges sketches.
+#[derive(Clone)]
+pub struct SketchSemigroup;
+
+impl Semigroup<QuantileSketch> for SketchSemigroup {
+    fn combine(left: &QuantileSketch, right: &QuantileSketch) -> QuantileSketch {
+        left.merge(right)
+    }
+}
+
+/// An [aggregator](`crate::operator::Aggregator`) that computes approximate
+/// quantiles of a collection of numeric values.
+///
+/// The accumulator of this aggregator is a [`QuantileSketch`], which can be
+/// efficiently merged, making the aggregator suitable for use with
+/// [`partitioned_rolling_aggregate`](`crate::Stream::partitioned_rolling_aggregate`)
+/// and other operators that compute aggregates piecewise.  The output of the
+/// aggregator is a vector of estimated values for each quantile configured
+/// with [`SketchAggregator::new`], in the same order.
+///
+/// For a quantile `q`, the aggregator returns a value within relative error
+/// `relative_accuracy` from the element with rank `floor(q * (n - 1))` in
+/// the sorted collection of `n` values, where each value occurs as many times
+/// as its weight.  The guarantee holds after deletions as well (see
+/// [`QuantileSketch`]).  The output is not defined for collections whose
+/// total weight is not positive.
+#[derive(Clone)]
+pub struct SketchAggregator {
+    relative_accuracy: f64,
+    gamma_ln: f64,
+    quantiles: Vec<f64>,
+}
+
+impl SketchAggregator {
+    /// Smallest absolute value that is assigned to a bucket.  Smaller values
+    /// are treated as zero.
+    const MIN_INDEXABLE_VALUE: f64 = 1e-9;
+
+    /// Create an aggregator that computes `quantiles` with the specified
+    /// relative accuracy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `relative_accuracy` is not in the range `(0, 1)` or any of
+    /// the `quantiles` is not in the range `[0, 1]`.
+    pub fn new(relative_accuracy: f64, quantiles: &[f64]) -> Self {
+        assert!(
+            relative_accuracy > 0.0 && relative_accuracy < 1.0,
+            "relative accuracy must be in the range (0, 1)"
+        );
+        assert!(
+            quantiles.iter().all(|q| (0.0..=1.0).contains(q)),
+            "quantiles must be in the range [0, 1]"
+        );
+
+        let gamma = (1.0 + relative_accuracy) / (1.0 - relative_accuracy);
+
+        Self {
+            relative_accuracy,
+            gamma_ln: gamma.ln(),
+            quantiles: quantiles.to_vec(),
+        }
+    }
+
+    /// Returns the relative accuracy of this aggregator.
+    pub fn relative_accuracy(&self) -> f64 {
+        self.relative_accuracy
+    }
+
+    /// Returns quantiles computed by this aggregator.
+    pub fn quantiles(&self) -> &[f64] {
+        &self.quantiles
+    }
+
+    fn bucket(&self, value: f64) -> i32 {
+        (value.ln() / self.gamma_ln).ceil() as i32
+    }
+
+    fn bucket_value(&self, bucket: i32) -> f64 {
+        // Midpoint of the bucket `(gamma^(bucket-1), gamma^bucket]` that
+        // minimizes relative error.
+        2.0 * (bucket as f64 * self.gamma_ln).exp() / (1.0 + self.gamma_ln.exp())
+    }
+
+    /// Add `count` occurrences of `value` to `sketch`.
+    pub fn insert(&self, sketch: &mut QuantileSketch, value: f64, count: i64) {
+        if value > Self::MIN_INDEXABLE_VALUE {
+            QuantileSketch::add_to_bucket(&mut sketch.positive, self.bucket(value), count);
+        } else if value < -Self::MIN_INDEXABLE_VALUE {
+            QuantileSketch::add_to_bucket(&mut sketch.negative, self.bucket(-value), count);
+        } else {
+            sketch.zero_count += count;
+        }
+    }
+
+    /// Estimate quantile `q` of the values in `sketch`.
+    ///
+    /// Returns `None` if the total count of values in the sketch is not
+    /// positive.
+    pub fn quantile(&self, sketch: &QuantileSketch, q: f64) -> Option<F64> {
+        let count = sketch.count();
+        if count <= 0 {
+            return None;
+        }
+
+        let rank = q * (count - 1) as f64;
+        let mut cumulative = 0;
+
+        // Negative values in ascending order.
+        for (bucket, bucket_count) in sketch.negative.iter().rev() {
+            cumulative += bucket_count;
+            if cumulative as f64 > rank {
+                return Some(F64::new(-self.bucket_value(*bucket)));
+            }
+        }
+
+        cumulative += sketch.zero_count;
+        if cumulative as f64 > rank {
+            return Some(F64::new(0.0));
+        }
+
+        for (bucket, bucket_count) in sketch.positive.iter() {
+            cumulative += bucket_count;
+            if cumulative as f64 > rank {
+                return Some(F64::new(self.bucket_value(*bucket)));
+            }
+        }
+
+        // Only reachable due to negative counts in individual buckets.
+        sketch
+            .positive
+            .last()
+            .map(|(bucket, _)| F64::new(self.bucket_value(*bucket)))
+            .or_else(|| Some(F64::new(0.0)))
+    }
+}
+
+impl<V, T, R> Aggregator<V, T, R> for SketchAggregator
+where
+    V: DBData + ToPrimitive,
+    T: Timestamp,
+    R: MonoidValue + ToPrimitive,
+{
+    type Accumulator = QuantileSketch;
+    type Output = Vec<F64>;
+    type Semigroup = SketchSemigroup;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<'s, V, (), T, R>,
+    {
+        let mut result: Option<QuantileSketch> = None;
+
+        while cursor.key_valid() {
+            let mut weight = R::zero();
+
+            cursor.map_times(|_t, w| weight.add_assign_by_ref(w));
+
+            if !weight.is_zero() {
+                let count = weight.to_i64().expect("weight out of range");
+                let value = cursor
+                    .key()
+                    .to_f64()
+                    .expect("value not representable as f64");
+
+                self.insert(result.get_or_insert_with(Default::default), value, count);
+            }
+
+            cursor.step_key();
+        }
+
+        result
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        self.quantiles
+            .iter()
+            .map(|q| {
+                self.quantile(&accumulator, *q)
+                    .unwrap_or(F64::new(f64::NAN))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{QuantileSketch, SketchAggregator, SketchSemigroup};
+    use crate::{
+        algebra::{Semigroup, F64},
+        operator::{
+            time_series::{RelOffset, RelRange},
+            Aggregator,
+        },
+        trace::{cursor::CursorGroup, Batch, BatchReader, Cursor},
+        OrdIndexedZSet, OrdZSet, RootCircuit,
+    };
+    use rand::{distributions::Uniform, Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    const QUANTILES: [f64; 5] = [0.0, 0.5, 0.9, 0.99, 1.0];
+
+    fn exact_quantile(sorted: &[i64], q: f64) -> i64 {
+        sorted[(q * (sorted.len() - 1) as f64).floor() as usize]
+    }
+
+    fn assert_within_bounds(aggregator: &SketchAggregator, estimates: &[F64], values: &[i64]) {
+        let mut sorted = values.to_vec();
+        sorted.sort();
+
+        for (q, estimate) in QUANTILES.iter().zip(estimates.iter()) {
+            let exact = exact_quantile(&sorted, *q) as f64;
+            let estimate = estimate.into_inner();
+            assert!(
+                (estimate - exact).abs() <= aggregator.relative_accuracy() * exact.abs() + 1e-6,
+                "quantile {q}: estimate {estimate}, exact value {exact}"
+            );
+        }
+    }
+
+    fn sketch_of(aggregator: &SketchAggregator, values: &[i64]) -> QuantileSketch {
+        let zset = OrdZSet::from_keys((), values.iter().map(|v| (*v, 1isize)).collect());
+        let mut cursor = zset.cursor();
+        <SketchAggregator as Aggregator<i64, (), isize>>::aggregate(aggregator, &mut cursor)
+            .unwrap()
+    }
+
+    fn distributions() -> Vec<Vec<i64>> {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(42);
+
+        vec![
+            // Uniform.
+            (0..10_000)
+                .map(|_| rng.sample(Uniform::new(0, 1_000_000)))
+                .collect(),
+            // Long tail.
+            (0..10_000)
+                .map(|_| {
+                    let x: f64 = rng.sample(Uniform::new(0.0, 1.0));
+                    (1.0 / (1.0 - x)).powi(3) as i64
+                })
+                .collect(),
+            // Values of both signs, with duplicates.
+            (0..10_000)
+                .map(|_| rng.sample(Uniform::new(-100, 100)))
+                .collect(),
+        ]
+    }
+
+    #[test]
+    fn test_sketch_error_bounds() {
+        let aggregator = SketchAggregator::new(0.01, &QUANTILES);
+
+        for values in distributions() {
+            let sketch = sketch_of(&aggregator, &values);
+            assert_eq!(sketch.count(), values.len() as i64);
+
+            let estimates =
+                <SketchAggregator as Aggregator<i64, (), isize>>::finalize(&aggregator, sketch);
+            assert_within_bounds(&aggregator, &estimates, &values);
+        }
+    }
+
+    #[test]
+    fn test_sketch_merge() {
+        let aggregator = SketchAggregator::new(0.02, &QUANTILES);
+
+        for values in distributions() {
+            let (left, right) = values.split_at(values.len() / 3);
+            let merged = SketchSemigroup::combine(
+                &sketch_of(&aggregator, left),
+                &sketch_of(&aggregator, right),
+            );
+
+            assert_eq!(merged, sketch_of(&aggregator, &values));
+        }
+    }
+
+    // Deleting values from a sketch produces the same sketch as if the values
+    // were never inserted.
+    #[test]
+    fn test_sketch_retractions() {
+        let aggregator = SketchAggregator::new(0.01, &QUANTILES);
+
+        for values in distributions() {
+            let (retained, deleted) = values.split_at(values.len() / 2);
+
+            let mut sketch = sketch_of(&aggregator, &values);
+            for value in deleted {
+                aggregator.insert(&mut sketch, *value as f64, -1);
+            }
+
+            assert_eq!(sketch, sketch_of(&aggregator, retained));
+
+            let estimates =
+                <SketchAggregator as Aggregator<i64, (), isize>>::finalize(&aggregator, sketch);
+            assert_within_bounds(&aggregator, &estimates, retained);
+        }
+    }
+
+    #[test]
+    fn test_sketch_cursor_group() {
+        let aggregator = SketchAggregator::new(0.01, &[0.5]);
+        let batch = OrdIndexedZSet::<u64, i64, isize>::from_tuples(
+            (),
+            vec![((0, 10), 1), ((0, 20), 3), ((1, 100), 1)],
+        );
+
+        let mut cursor = batch.cursor();
+        let mut group = CursorGroup::new(&mut cursor, ());
+        let median = <SketchAggregator as Aggregator<i64, (), isize>>::aggregate_and_finalize(
+            &aggregator,
+            &mut group,
+        )
+        .unwrap()[0]
+            .into_inner();
+        assert!((median - 20.0).abs() <= 0.2);
+    }
+
+    #[test]
+    fn test_sketch_rolling_aggregate() {
+        let aggregator = SketchAggregator::new(0.01, &QUANTILES);
+
+        let (circuit, mut input) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let expected_aggregator = aggregator.clone();
+            input_stream
+                .partitioned_rolling_aggregate::<u64, i64, _>(
+                    aggregator.clone(),
+                    RelRange::new(RelOffset::Before(99), RelOffset::Before(0)),
+                )
+                .integrate()
+                .gather(0)
+                .apply2(
+                    &input_stream.integrate().gather(0),
+                    move |output: &OrdIndexedZSet<u64, (u64, Option<Vec<F64>>), isize>,
+                          input: &OrdIndexedZSet<u64, (u64, i64), isize>| {
+                        let mut output_cursor = output.cursor();
+                        while output_cursor.key_valid() {
+                            let partition = *output_cursor.key();
+
+                            while output_cursor.val_valid() {
+                                let (ts, estimates) = output_cursor.val();
+
+                                let mut window = Vec::new();
+                                let mut input_cursor = input.cursor();
+                                input_cursor.seek_key(&partition);
+                                while input_cursor.val_valid() {
+                                    let weight = input_cursor.weight();
+                                    let (input_ts, value) = input_cursor.val();
+                                    if input_ts + 99 >= *ts && input_ts <= ts {
+                                        for _ in 0..weight {
+                                            window.push(*value);
+                                        }
+                                    }
+                                    input_cursor.step_val();
+                                }
+
+                                assert_within_bounds(
+                                    &expected_aggregator,
+                                    estimates.as_ref().unwrap(),
+                                    &window,
+                                );
+                                output_cursor.step_val();
+                            }
+                            output_cursor.step_key();
+                        }
+                    },
+                );
+
+            input_handle
+        })
+        .unwrap();
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let mut inserted = Vec::new();
+
+        for _ in 0..5 {
+            let mut batch: Vec<_> = (0..200)
+                .map(|_| {
+                    (
+                        rng.sample(Uniform::new(0u64, 3)),
+                        (
+                            (
+                                rng.sample(Uniform::new(0u64, 1000)),
+                                rng.sample(Uniform::new(-1000i64, 1000)),
+                            ),
+                            1isize,
+                        ),
+                    )
+                })
+                .collect();
+            inserted.extend(batch.iter().cloned());
+
+            // Retract some of the previously inserted values.
+            for _ in 0..50 {
+                let (partition, (val, w)) =
+                    inserted.swap_remove(rng.sample(Uniform::new(0, inserted.len())));
+                batch.push((partition, (val, -w)));
+            }
+
+            input.append(&mut batch);
+            circuit.step().unwrap();
+        }
+    }
+}
@@ -0,0 +1,452 @@
+//! Tuples of aggregators.
+//!
+//! A tuple of aggregators `(A1, A2, ...)` is itself an aggregator that
+//! computes all component aggregates in one invocation of an aggregation
+//! operator, e.g., `stream.aggregate((Min, Max))`.  All components are
+//! computed in a single pass over the values of each group using
+//! [`Aggregator::step`].
+//!
+//! Components are computed independently: a component that produces no
+//! aggregate for a group, e.g., a [`Fold`](`crate::operator::Fold`) whose
+//! filter rejects all values in the group, doesn't prevent other components
+//! from producing theirs.  Hence, the output of the tuple aggregator is a
+//! tuple of optional component outputs, `(Option<O1>, Option<O2>, ...)`,
+//! and the aggregator only skips groups for which all components return
+//! `None`.  The accumulator is a tuple of optional component accumulators
+//! combined componentwise by [`TupleSemigroup`] and [`OptionSemigroup`].
+
+use crate::{
+    algebra::Semigroup, operator::aggregate::Aggregator, trace::Cursor, DBData, Timestamp,
+};
+use std::marker::PhantomData;
+
+/// Semigroup over tuples that combines tuple elements componentwise.
+///
+/// `TupleSemigroup<(S1, S2, ...)>` implements `Semigroup<(A1, A2, ...)>`
+/// as long as `S1: Semigroup<A1>`, `S2: Semigroup<A2>`, etc.
+#[derive(Clone)]
+pub struct TupleSemigroup<S>(PhantomData<S>);
+
+/// Semigroup over `Option<A>` with `None` as the neutral element, which
+/// combines `Some` values using `S`.
+///
+/// Used as the semigroup of components of tuple aggregators, whose
+/// accumulators are optional.
+#[derive(Clone)]
+pub struct OptionSemigroup<S>(PhantomData<S>);
+
+impl<A, S> Semigroup<Option<A>> for OptionSemigroup<S>
+where
+    A: Clone,
+    S: Semigroup<A>,
+{
+    fn combine(left: &Option<A>, right: &Option<A>) -> Option<A> {
+        S::combine_opt(left, right)
+    }
+}
+
+macro_rules! tuple_aggregator {
+    ($(($agg:ident, $acc:ident, $semigroup:ident, $idx:tt)),+) => {
+        impl<$($acc, $semigroup),+> Semigroup<($($acc,)+)> for TupleSemigroup<($($semigroup,)+)>
+        where
+            $($semigroup: Semigroup<$acc>,)+
+        {
+            fn combine(left: &($($acc,)+), right: &($($acc,)+)) -> ($($acc,)+) {
+                ($($semigroup::combine(&left.$idx, &right.$idx),)+)
+            }
+        }
+
+        impl<V, T, R, $($agg),+> Aggregator<V, T, R> for ($($agg,)+)
+        where
+            V: DBData,
+            T: Timestamp,
+            R: Clone + 'static,
+            $($agg: Aggregator<V, T, R>,)+
+        {
+            type Accumulator = ($(Option<$agg::Accumulator>,)+);
+            type Semigroup = TupleSemigroup<($(OptionSemigroup<$agg::Semigroup>,)+)>;
+            type Output = ($(Option<$agg::Output>,)+);
+
+            fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+            where
+                C: Cursor<'s, V, (), T, R>,
+            {
+                let mut accumulator: Self::Accumulator = ($(None::<$agg::Accumulator>,)+);
+
+                // Bitmask of components that don't need to see any more values.
+                let mut done = 0u8;
+                let all_done = 0u8 $(| (1u8 << $idx))+;
+
+                while cursor.key_valid() && done != all_done {
+                    $(
+                        if done & (1u8 << $idx) == 0
+                            && self.$idx.step(&mut accumulator.$idx, cursor).is_break()
+                        {
+                            done |= 1u8 << $idx;
+                        }
+                    )+
+                    cursor.step_key();
+                }
+
+                if $(accumulator.$idx.is_none())&&+ {
+                    None
+                } else {
+                    Some(accumulator)
+                }
+            }
+
+            fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+                ($(accumulator.$idx.map(|acc| self.$idx.finalize(acc)),)+)
+            }
+        }
+    };
+}
+
+tuple_aggregator!((A0, Acc0, S0, 0), (A1, Acc1, S1, 1));
+tuple_aggregator!((A0, Acc0, S0, 0), (A1, Acc1, S1, 1), (A2, Acc2, S2, 2));
+tuple_aggregator!(
+    (A0, Acc0, S0, 0),
+    (A1, Acc1, S1, 1),
+    (A2, Acc2, S2, 2),
+    (A3, Acc3, S3, 3)
+);
+tuple_aggregator!(
+    (A0, Acc0, S0, 0),
+    (A1, Acc1, S1, 1),
+    (A2, Acc2, S2, 2),
+    (A3, Acc3, S3, 3),
+    (A4, Acc4, S4, 4)
+);
+tuple_aggregator!(
+    (A0, Acc0, S0, 0),
+    (A1, Acc1, S1, 1),
+    (A2, Acc2, S2, 2),
+    (A3, Acc3, S3, 3),
+    (A4, Acc4, S4, 4),
+    (A5, Acc5, S5, 5)
+);
+
+#[cfg(test)]
+mod test {
+    use super::TupleSemigroup;
+    use crate::{
+        algebra::{DefaultSemigroup, Semigroup, UnimplementedSemigroup},
+        indexed_zset,
+        operator::{
+            time_series::{RelOffset, RelRange},
+            Aggregator, Fold, FoldUntil, Max, MaxSemigroup, Min, MinSemigroup,
+        },
+        trace::{cursor::CursorGroup, Batch, BatchReader, Cursor},
+        OrdIndexedZSet, RootCircuit,
+    };
+    use std::ops::ControlFlow;
+
+    type Sum = Fold<i64, DefaultSemigroup<i64>, fn(&mut i64, &i64, isize), fn(i64) -> i64>;
+
+    fn sum_step(acc: &mut i64, v: &i64, w: isize) {
+        *acc += *v * w as i64;
+    }
+
+    fn sum() -> Sum {
+        Fold::new(0, sum_step as fn(&mut i64, &i64, isize))
+    }
+
+    #[test]
+    fn test_tuple_semigroup() {
+        type S = TupleSemigroup<(DefaultSemigroup<i64>, MaxSemigroup<i64>, MinSemigroup<i64>)>;
+
+        assert_eq!(S::combine(&(1, 5, 5), &(2, 3, 3)), (3, 5, 3));
+    }
+
+    #[test]
+    fn test_tuple_aggregator() {
+        let batch: OrdIndexedZSet<u64, i64, isize> = indexed_zset! {
+            0 => { 1 => 1, 5 => 2, 10 => -1 },
+            1 => { -3 => 1 },
+        };
+
+        let aggregator = (sum(), Max, Min);
+        let mut cursor = batch.cursor();
+
+        let mut outputs = Vec::new();
+        while cursor.key_valid() {
+            let output = <_ as Aggregator<i64, (), isize>>::aggregate_and_finalize(
+                &aggregator,
+                &mut CursorGroup::new(&mut cursor, ()),
+            );
+            outputs.push(output);
+            cursor.step_key();
+        }
+
+        assert_eq!(
+            outputs,
+            vec![
+                Some((Some(1), Some(10), Some(1))),
+                Some((Some(-3), Some(-3), Some(-3)))
+            ]
+        );
+    }
+
+    /// Apply `aggregator` to each key in `batch`.
+    fn aggregate_batch<A>(
+        aggregator: &A,
+        batch: &OrdIndexedZSet<u64, i64, isize>,
+    ) -> Vec<Option<A::Output>>
+    where
+        A: Aggregator<i64, (), isize>,
+    {
+        let mut cursor = batch.cursor();
+        let mut outputs = Vec::new();
+
+        while cursor.key_valid() {
+            outputs.push(aggregator.aggregate_and_finalize(&mut CursorGroup::new(&mut cursor, ())));
+            cursor.step_key();
+        }
+
+        outputs
+    }
+
+    // A component that doesn't produce an aggregate for a group doesn't
+    // suppress other components.
+    #[test]
+    fn test_tuple_aggregator_partial() {
+        let batch: OrdIndexedZSet<u64, i64, isize> = indexed_zset! {
+            0 => { 1 => 1, 5 => 2 },
+            1 => { -3 => 1, 4 => 1 },
+        };
+
+        let negative_sum = || sum().with_filter(|v: &i64| *v < 0);
+        let large_sum = || sum().with_filter(|v: &i64| *v > 100);
+
+        assert_eq!(
+            aggregate_batch(&(negative_sum(), Max), &batch),
+            vec![Some((None, Some(5))), Some((Some(-3), Some(4)))]
+        );
+
+        // Groups are only skipped if all components are empty.
+        assert_eq!(
+            aggregate_batch(&(negative_sum(), large_sum()), &batch),
+            vec![None, Some((Some(-3), None))]
+        );
+    }
+
+    // Counts how many times the cursor moves to the next key.
+    struct CountingCursor<C> {
+        cursor: C,
+        steps: usize,
+    }
+
+    impl<'s, K, T, R, C> Cursor<'s, K, (), T, R> for CountingCursor<C>
+    where
+        C: Cursor<'s, K, (), T, R>,
+    {
+        fn key_valid(&self) -> bool {
+            self.cursor.key_valid()
+        }
+
+        fn val_valid(&self) -> bool {
+            self.cursor.val_valid()
+        }
+
+        fn key(&self) -> &K {
+            self.cursor.key()
+        }
+
+        fn val(&self) -> &() {
+            self.cursor.val()
+        }
+
+        fn fold_times<F, U>(&mut self, init: U, fold: F) -> U
+        where
+            F: FnMut(U, &T, &R) -> U,
+        {
+            self.cursor.fold_times(init, fold)
+        }
+
+        fn fold_times_through<F, U>(&mut self, upper: &T, init: U, fold: F) -> U
+        where
+            F: FnMut(U, &T, &R) -> U,
+        {
+            self.cursor.fold_times_through(upper, init, fold)
+        }
+
+        fn weight(&mut self) -> R
+        where
+            T: PartialEq<()>,
+        {
+            self.cursor.weight()
+        }
+
+        fn step_key(&mut self) {
+            self.steps += 1;
+            self.cursor.step_key()
+        }
+
+        fn seek_key(&mut self, key: &K) {
+            self.cursor.seek_key(key)
+        }
+
+        fn last_key(&mut self) -> Option<&K> {
+            self.cursor.last_key()
+        }
+
+        fn step_val(&mut self) {
+            self.cursor.step_val()
+        }
+
+        fn seek_val(&mut self, val: &()) {
+            self.cursor.seek_val(val)
+        }
+
+        fn seek_val_with<P>(&mut self, predicate: P)
+        where
+            P: Fn(&()) -> bool + Clone,
+        {
+            self.cursor.seek_val_with(predicate)
+        }
+
+        fn rewind_keys(&mut self) {
+            self.cursor.rewind_keys()
+        }
+
+        fn rewind_vals(&mut self) {
+            self.cursor.rewind_vals()
+        }
+    }
+
+    // All components are computed in one pass over the group, which stops
+    // once none of the components needs more values.
+    #[test]
+    fn test_tuple_aggregator_single_pass() {
+        let batch: OrdIndexedZSet<u64, i64, isize> = indexed_zset! {
+            0 => { 1 => 1, 2 => 1, 3 => -1, 4 => 1 },
+        };
+
+        let mut batch_cursor = batch.cursor();
+        let mut cursor = CountingCursor {
+            cursor: CursorGroup::new(&mut batch_cursor, ()),
+            steps: 0,
+        };
+        let output = (sum(), Max, Min).aggregate_and_finalize(&mut cursor);
+        assert_eq!(output, Some((Some(4), Some(4), Some(1))));
+        assert_eq!(cursor.steps, 4);
+
+        // `Min` and `FoldUntil` stop at the first value.
+        let exists_positive = <FoldUntil<_, UnimplementedSemigroup<_>, _, _>>::new(
+            false,
+            |_acc: bool, v: &i64, _w: isize| {
+                if *v > 0 {
+                    ControlFlow::Break(true)
+                } else {
+                    ControlFlow::Continue(false)
+                }
+            },
+        );
+
+        let mut batch_cursor = batch.cursor();
+        let mut cursor = CountingCursor {
+            cursor: CursorGroup::new(&mut batch_cursor, ()),
+            steps: 0,
+        };
+        let output = (Min, exists_positive).aggregate_and_finalize(&mut cursor);
+        assert_eq!(output, Some((Some(1), Some(true))));
+        assert_eq!(cursor.steps, 1);
+    }
+
+    #[test]
+    fn test_tuple_aggregate_operator() {
+        let (circuit, input) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) = circuit.add_input_indexed_zset::<u64, i64, isize>();
+
+            let combined = input_stream.aggregate((sum(), Max));
+            let expected = input_stream
+                .aggregate(sum())
+                .join_index(&input_stream.aggregate(Max), |k, sum, max| {
+                    Some((*k, (Some(*sum), Some(*max))))
+                });
+
+            combined.apply2(&expected, |combined, expected| {
+                assert_eq!(combined, expected)
+            });
+
+            input_handle
+        })
+        .unwrap();
+
+        input.push(0, (1, 1));
+        input.push(0, (5, 1));
+        input.push(1, (-2, 3));
+        circuit.step().unwrap();
+
+        input.push(0, (5, -1));
+        input.push(0, (3, 1));
+        input.push(2, (7, 1));
+        circuit.step().unwrap();
+
+        input.push(1, (-2, -3));
+        circuit.step().unwrap();
+    }
+
+    #[test]
+    fn test_tuple_rolling_aggregate() {
+        let (circuit, input) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let range = RelRange::new(RelOffset::Before(10), RelOffset::Before(0));
+
+            let combined = input_stream
+                .partitioned_rolling_aggregate::<u64, i64, _>((sum(), Max), range)
+                .integrate();
+            let expected = input_stream
+                .partitioned_rolling_aggregate::<u64, i64, _>(sum(), range)
+                .integrate()
+                .apply2(
+                    &input_stream
+                        .partitioned_rolling_aggregate::<u64, i64, _>(Max, range)
+                        .integrate(),
+                    |sums, maxs| {
+                        let mut tuples = Vec::new();
+                        let mut sum_cursor = sums.cursor();
+                        let mut max_cursor = maxs.cursor();
+
+                        while sum_cursor.key_valid() {
+                            while sum_cursor.val_valid() {
+                                let weight = sum_cursor.weight();
+                                let (ts, sum) = sum_cursor.val();
+                                let (max_ts, max) = max_cursor.val();
+                                assert_eq!(ts, max_ts);
+
+                                let combined =
+                                    sum.zip(*max).map(|(sum, max)| (Some(sum), Some(max)));
+                                tuples.push(((*sum_cursor.key(), (*ts, combined)), weight));
+                                sum_cursor.step_val();
+                                max_cursor.step_val();
+                            }
+                            sum_cursor.step_key();
+                            max_cursor.step_key();
+                        }
+
+                        OrdIndexedZSet::from_tuples((), tuples)
+                    },
+                );
+
+            combined.apply2(&expected, |combined, expected| {
+                assert_eq!(combined, expected)
+            });
+
+            input_handle
+        })
+        .unwrap();
+
+        input.push(0, ((1, 5), 1));
+        input.push(0, ((5, -2), 1));
+        input.push(0, ((12, 3), 1));
+        input.push(1, ((100, 1), 2));
+        circuit.step().unwrap();
+
+        input.push(0, ((5, -2), -1));
+        input.push(0, ((8, 10), 1));
+        circuit.step().unwrap();
+    }
+}
@@ -0,0 +1,576 @@
+//! Connecting the output of one circuit to the input of another.
+
+use super::Mailbox;
+use crate::{
+    circuit::{
+        operator_traits::{Operator, SinkOperator, SourceOperator},
+        OwnershipPreference, RootCircuit, Scope,
+    },
+    trace::{Batch, Spine, Trace},
+    Circuit, Runtime, Stream,
+};
+use crossbeam::channel::{bounded, Receiver, Sender};
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Create a bridge that connects the output of one circuit to the input of
+/// another circuit, possibly running in a different runtime.
+///
+/// The sending circuit attaches the [`BridgeSender`] to one of its streams
+/// using [`Stream::output_to_bridge`].  The receiving circuit turns the
+/// [`BridgeReceiver`] into an input stream using
+/// [`RootCircuit::add_bridge_input`].  At each clock cycle, the sending
+/// circuit sends the batches computed by all of its workers over the bridge
+/// as a single message.  The receiving circuit processes one message per
+/// clock cycle; use [`BridgeReceiver::recv_and_step`] or
+/// [`BridgeReceiver::drive`] to step it once for each message.
+///
+/// The bridge buffers up to `capacity` messages.  When the buffer is full,
+/// the clock cycle of the sending circuit blocks until the receiver catches
+/// up.
+///
+/// Either side can shut the bridge down using [`BridgeSender::close`] or
+/// [`BridgeReceiver::close`].  The bridge is also closed when all clones
+/// of the sender are dropped, e.g., when the sending circuit is destroyed.
+///
+/// # Example
+///
+/// ```
+/// use dbsp::{bridge, zset, OrdZSet, Runtime};
+///
+/// let (tx, rx) = bridge::<OrdZSet<u64, isize>>(4);
+///
+/// let (mut sender, mut input) = Runtime::init_circuit(2, {
+///     let tx = tx.clone();
+///     move |circuit| {
+///         let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+///         stream.output_to_bridge(tx);
+///         handle
+///     }
+/// })
+/// .unwrap();
+///
+/// let (mut receiver, output) = Runtime::init_circuit(2, {
+///     let rx = rx.clone();
+///     move |circuit| circuit.add_bridge_input(rx).integrate().output()
+/// })
+/// .unwrap();
+///
+/// input.append(&mut vec![(1, 1), (2, 1)]);
+/// sender.step().unwrap();
+/// tx.close();
+///
+/// assert!(rx.recv_and_step(|| receiver.step()).unwrap());
+/// assert_eq!(output.consolidate(), zset! { 1 => 1, 2 => 1 });
+///
+/// // The sender has closed the bridge.
+/// assert!(!rx.recv_and_step(|| receiver.step()).unwrap());
+/// ```
+pub fn bridge<B>(capacity: usize) -> (BridgeSender<B>, BridgeReceiver<B>) {
+    let (sender, receiver) = bounded(capacity);
+    let receiver_closed = Arc::new(AtomicBool::new(false));
+
+    let tx = BridgeSender(Arc::new(BridgeSenderInternal {
+        sender,
+        closed: AtomicBool::new(false),
+        receiver_closed: receiver_closed.clone(),
+        pending: Mutex::new(Vec::new()),
+    }));
+    let rx = BridgeReceiver(Arc::new(BridgeReceiverInternal {
+        receiver: Mutex::new(Some(receiver)),
+        receiver_closed,
+        mailboxes: Mutex::new(Vec::new()),
+    }));
+
+    (tx, rx)
+}
+
+/// Message sent over a bridge.
+enum BridgeMessage<B> {
+    /// Batches computed by the workers of the sending circuit during one
+    /// clock cycle.
+    Batches(Vec<B>),
+    /// The sender has closed the bridge.
+    Close,
+}
+
+struct BridgeSenderInternal<B> {
+    sender: Sender<BridgeMessage<B>>,
+    closed: AtomicBool,
+    receiver_closed: Arc<AtomicBool>,
+    // Batches computed by each worker during the current clock cycle.
+    pending: Mutex<Vec<Option<B>>>,
+}
+
+/// The sending end of a bridge created by [`bridge`].
+///
+/// Clones of the sender share the same bridge.  The sender can be attached
+/// to a single stream, using [`Stream::output_to_bridge`].
+pub struct BridgeSender<B>(Arc<BridgeSenderInternal<B>>);
+
+impl<B> Clone for BridgeSender<B> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<B> BridgeSender<B> {
+    /// Returns `true` if either side has closed the bridge.
+    ///
+    /// Batches computed by the sending circuit after the bridge is closed
+    /// are discarded.
+    pub fn is_closed(&self) -> bool {
+        self.0.closed.load(Ordering::Acquire) || self.0.receiver_closed.load(Ordering::Acquire)
+    }
+
+    /// Close the bridge.
+    ///
+    /// The receiver gets all batches sent before the bridge was closed, after
+    /// which [`BridgeReceiver::recv`] returns `false`.  Blocks if the bridge
+    /// is full.
+    pub fn close(&self) {
+        if !self.0.closed.swap(true, Ordering::AcqRel) {
+            let _ = self.0.sender.send(BridgeMessage::Close);
+        }
+    }
+
+    fn connect(&self, num_workers: usize) {
+        let mut pending = self.0.pending.lock().unwrap();
+        if pending.is_empty() {
+            pending.resize_with(num_workers, || None);
+        }
+    }
+
+    /// Store the batch computed by `worker`.  The last worker to complete the
+    /// current clock cycle sends batches from all workers over the bridge.
+    fn publish(&self, worker: usize, batch: B) {
+        let batches = {
+            let mut pending = self.0.pending.lock().unwrap();
+            pending[worker] = Some(batch);
+            if pending.iter().any(Option::is_none) {
+                return;
+            }
+            pending
+                .iter_mut()
+                .map(|batch| batch.take().unwrap())
+                .collect()
+        };
+
+        if self.is_closed() {
+            return;
+        }
+
+        // Blocks while the bridge is full, throttling the sending circuit.
+        // Fails if the receiver has closed the bridge.
+        if self.0.sender.send(BridgeMessage::Batches(batches)).is_err() {
+            self.0.closed.store(true, Ordering::Release);
+        }
+    }
+}
+
+struct BridgeReceiverInternal<B> {
+    // `None` once the bridge is closed.
+    receiver: Mutex<Option<Receiver<BridgeMessage<B>>>>,
+    receiver_closed: Arc<AtomicBool>,
+    // Input mailboxes of the workers of the receiving circuit.
+    mailboxes: Mutex<Vec<Mailbox<Vec<B>>>>,
+}
+
+/// The receiving end of a bridge created by [`bridge`].
+///
+/// Clones of the receiver share the same bridge.  The receiver can be
+/// connected to a single circuit, using [`RootCircuit::add_bridge_input`].
+pub struct BridgeReceiver<B>(Arc<BridgeReceiverInternal<B>>);
+
+impl<B> Clone for BridgeReceiver<B> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<B> BridgeReceiver<B>
+where
+    B: Batch<Time = ()> + Send,
+{
+    /// Block until the sender sends the next message and buffer its batches
+    /// in the input stream of the receiving circuit, where they will be
+    /// consumed at the next clock cycle.
+    ///
+    /// Returns `false` if the bridge was closed by either side, in which case
+    /// no batches are buffered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the receiver is not connected to a circuit.
+    pub fn recv(&self) -> bool {
+        // Don't hold the lock while blocking, so that `close` can proceed.
+        let receiver = match &*self.0.receiver.lock().unwrap() {
+            None => return false,
+            Some(receiver) => receiver.clone(),
+        };
+
+        match receiver.recv() {
+            Ok(BridgeMessage::Batches(batches)) => {
+                let mailboxes = self.0.mailboxes.lock().unwrap();
+                assert!(
+                    !mailboxes.is_empty(),
+                    "bridge receiver is not connected to a circuit"
+                );
+
+                // Distribute batches across workers in round robin.
+                for (index, batch) in batches.into_iter().enumerate() {
+                    mailboxes[index % mailboxes.len()].update(|batches| batches.push(batch));
+                }
+                true
+            }
+            Ok(BridgeMessage::Close) | Err(_) => {
+                self.0.receiver.lock().unwrap().take();
+                false
+            }
+        }
+    }
+
+    /// Driver utility that steps the receiving circuit after the next
+    /// message arrives.
+    ///
+    /// Invokes [`recv`](`Self::recv`) followed by `step`, e.g.,
+    /// `|| dbsp.step()`.  Returns `Ok(false)` without calling `step` if the
+    /// bridge is closed.
+    pub fn recv_and_step<F, E>(&self, step: F) -> Result<bool, E>
+    where
+        F: FnOnce() -> Result<(), E>,
+    {
+        if !self.recv() {
+            return Ok(false);
+        }
+
+        step()?;
+        Ok(true)
+    }
+
+    /// Driver utility that steps the receiving circuit once for each message
+    /// sent over the bridge, until the bridge is closed.
+    ///
+    /// Returns the number of clock cycles performed.
+    pub fn drive<F, E>(&self, mut step: F) -> Result<usize, E>
+    where
+        F: FnMut() -> Result<(), E>,
+    {
+        let mut steps = 0;
+        while self.recv_and_step(&mut step)? {
+            steps += 1;
+        }
+
+        Ok(steps)
+    }
+
+    /// Close the bridge.
+    ///
+    /// Messages buffered in the bridge are discarded.  The sender discards
+    /// all subsequent batches without blocking (see
+    /// [`BridgeSender::is_closed`]).
+    pub fn close(&self) {
+        self.0.receiver_closed.store(true, Ordering::Release);
+        self.0.receiver.lock().unwrap().take();
+    }
+
+    /// Returns `true` if either side has closed the bridge.
+    pub fn is_closed(&self) -> bool {
+        self.0.receiver.lock().unwrap().is_none()
+    }
+
+    fn connect(&self, worker: usize, num_workers: usize) -> Mailbox<Vec<B>> {
+        let mut mailboxes = self.0.mailboxes.lock().unwrap();
+        if mailboxes.is_empty() {
+            mailboxes.resize_with(num_workers, Mailbox::new);
+        }
+
+        mailboxes[worker].clone()
+    }
+}
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: Batch<Time = ()> + Send,
+{
+    /// Send the contents of `self` to another circuit over a bridge.
+    ///
+    /// At each clock cycle, the batches computed by all workers are sent
+    /// to the receiving end of the bridge as a single message.  The clock
+    /// cycle blocks while the bridge is full.  See [`bridge`](`crate::bridge`).
+    pub fn output_to_bridge(&self, sender: BridgeSender<B>) {
+        sender.connect(num_workers());
+        self.circuit().add_sink(BridgeOutput::new(sender), self);
+    }
+}
+
+impl RootCircuit {
+    /// Create an input stream that carries batches received over a bridge.
+    ///
+    /// At each clock cycle, the stream yields the batches buffered by the
+    /// last call to [`BridgeReceiver::recv`] or an empty batch if no
+    /// message was received since the previous clock cycle.  When the
+    /// circuit runs in a multithreaded runtime, batches computed by
+    /// different workers of the sending circuit are distributed across
+    /// workers of the receiving circuit.  See [`bridge`](`crate::bridge`).
+    pub fn add_bridge_input<B>(&self, receiver: BridgeReceiver<B>) -> Stream<Self, B>
+    where
+        B: Batch<Time = ()> + Send,
+    {
+        let mailbox = receiver.connect(Runtime::worker_index(), num_workers());
+        self.add_source(BridgeInput { mailbox })
+    }
+}
+
+fn num_workers() -> usize {
+    Runtime::runtime()
+        .map(|runtime| runtime.num_workers())
+        .unwrap_or(1)
+}
+
+/// Sink operator that sends the contents of its input stream over a bridge.
+struct BridgeOutput<B> {
+    worker: usize,
+    sender: BridgeSender<B>,
+}
+
+impl<B> BridgeOutput<B> {
+    fn new(sender: BridgeSender<B>) -> Self {
+        Self {
+            worker: Runtime::worker_index(),
+            sender,
+        }
+    }
+}
+
+impl<B> Operator for BridgeOutput<B>
+where
+    B: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("BridgeOutput")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<B> SinkOperator<B> for BridgeOutput<B>
+where
+    B: Clone + 'static,
+{
+    fn eval(&mut self, batch: &B) {
+        self.sender.publish(self.worker, batch.clone());
+    }
+
+    fn eval_owned(&mut self, batch: B) {
+        self.sender.publish(self.worker, batch);
+    }
+
+    fn input_preference(&self) -> OwnershipPreference {
+        OwnershipPreference::PREFER_OWNED
+    }
+}
+
+/// Source operator that reads batches received over a bridge.
+struct BridgeInput<B> {
+    mailbox: Mailbox<Vec<B>>,
+}
+
+impl<B> Operator for BridgeInput<B>
+where
+    B: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("BridgeInput")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        false
+    }
+}
+
+impl<B> SourceOperator<B> for BridgeInput<B>
+where
+    B: Batch<Time = ()>,
+{
+    fn eval(&mut self) -> B {
+        let mut batches = self.mailbox.take();
+
+        if batches.len() <= 1 {
+            batches.pop().unwrap_or_else(|| B::empty(()))
+        } else {
+            let mut spine = Spine::new(None);
+            for batch in batches {
+                spine.insert(batch);
+            }
+            spine.consolidate().unwrap_or_else(|| B::empty(()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        bridge,
+        operator::FilterMap,
+        trace::{Batch, BatchReader},
+        zset, OrdZSet, RootCircuit, Runtime,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn bridge_end_to_end() {
+        let (tx, rx) = bridge::<OrdZSet<u64, isize>>(4);
+
+        let (mut sender, mut input) = Runtime::init_circuit(4, {
+            let tx = tx.clone();
+            move |circuit| {
+                let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+                stream.map(|x| x * 2).output_to_bridge(tx);
+                handle
+            }
+        })
+        .unwrap();
+
+        let (mut receiver, (output, integral)) = Runtime::init_circuit(3, {
+            let rx = rx.clone();
+            move |circuit| {
+                let stream = circuit.add_bridge_input(rx);
+                (stream.output(), stream.integrate().output())
+            }
+        })
+        .unwrap();
+
+        for step in 0..10 {
+            input.append(&mut (step * 10..step * 10 + 10).map(|x| (x, 1)).collect());
+            if step > 0 {
+                input.push(step * 10 - 1, -1);
+            }
+            sender.step().unwrap();
+
+            assert!(rx.recv_and_step(|| receiver.step()).unwrap());
+
+            let mut expected: OrdZSet<u64, isize> = OrdZSet::from_keys(
+                (),
+                (step * 10..step * 10 + 10).map(|x| (x * 2, 1)).collect(),
+            );
+            if step > 0 {
+                expected = expected.merge(&zset! { (step * 10 - 1) * 2 => -1 });
+            }
+            assert_eq!(output.consolidate(), expected);
+        }
+
+        assert_eq!(integral.consolidate().len(), 91);
+
+        // An empty clock cycle still produces a message.
+        sender.step().unwrap();
+        assert!(rx.recv_and_step(|| receiver.step()).unwrap());
+        assert_eq!(output.consolidate(), zset! {});
+
+        tx.close();
+        assert!(tx.is_closed());
+        assert!(!rx.recv_and_step(|| receiver.step()).unwrap());
+        assert!(rx.is_closed());
+
+        sender.kill().unwrap();
+        receiver.kill().unwrap();
+    }
+
+    // The sender blocks once the bridge is full, until the receiver catches up.
+    #[test]
+    fn bridge_backpressure() {
+        let (tx, rx) = bridge::<OrdZSet<u64, isize>>(2);
+        let sender_steps = Arc::new(AtomicUsize::new(0));
+
+        let sender = thread::spawn({
+            let sender_steps = sender_steps.clone();
+            move || {
+                let (circuit, input) = RootCircuit::build({
+                    let tx = tx.clone();
+                    move |circuit| {
+                        let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+                        stream.output_to_bridge(tx);
+                        handle
+                    }
+                })
+                .unwrap();
+
+                for x in 0..5 {
+                    input.push(x, 1);
+                    circuit.step().unwrap();
+                    sender_steps.fetch_add(1, Ordering::AcqRel);
+                }
+                tx.close();
+            }
+        });
+
+        let (receiver, output) = RootCircuit::build({
+            let rx = rx.clone();
+            move |circuit| circuit.add_bridge_input(rx).integrate().output()
+        })
+        .unwrap();
+
+        // The sender fills up the bridge and blocks at the third clock cycle.
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(sender_steps.load(Ordering::Acquire), 2);
+
+        assert_eq!(rx.drive(|| receiver.step()).unwrap(), 5);
+        sender.join().unwrap();
+        assert_eq!(sender_steps.load(Ordering::Acquire), 5);
+
+        assert_eq!(
+            output.consolidate(),
+            zset! { 0 => 1, 1 => 1, 2 => 1, 3 => 1, 4 => 1 }
+        );
+    }
+
+    // Closing the receiving end unblocks the sender.
+    #[test]
+    fn bridge_receiver_shutdown() {
+        let (tx, rx) = bridge::<OrdZSet<u64, isize>>(1);
+
+        let (sender, input) = RootCircuit::build({
+            let tx = tx.clone();
+            move |circuit| {
+                let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+                stream.output_to_bridge(tx);
+                handle
+            }
+        })
+        .unwrap();
+
+        let (_receiver, _output) = RootCircuit::build({
+            let rx = rx.clone();
+            move |circuit| circuit.add_bridge_input(rx).output()
+        })
+        .unwrap();
+
+        input.push(1, 1);
+        sender.step().unwrap();
+        assert!(!tx.is_closed());
+
+        rx.close();
+        assert!(rx.is_closed());
+        assert!(!rx.recv());
+
+        for x in 0..3 {
+            input.push(x, 1);
+            sender.step().unwrap();
+        }
+        assert!(tx.is_closed());
+    }
+}
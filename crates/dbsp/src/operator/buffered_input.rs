@@ -0,0 +1,382 @@
+//! Adaptive batching of inputs pushed to a circuit by multiple producers.
+
+use crate::{CollectionHandle, DBData, UpsertHandle};
+use crossbeam::queue::SegQueue;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// An input handle that accepts batches of `(key, value)` tuples, such as
+/// [`CollectionHandle`] and [`UpsertHandle`].
+pub trait AppendInput<K, V> {
+    /// Push `vals` to the input stream, leaving `vals` empty.
+    fn append(&mut self, vals: &mut Vec<(K, V)>);
+}
+
+impl<K, V> AppendInput<K, V> for CollectionHandle<K, V>
+where
+    K: DBData,
+    V: DBData,
+{
+    fn append(&mut self, vals: &mut Vec<(K, V)>) {
+        CollectionHandle::append(self, vals)
+    }
+}
+
+impl<K, V> AppendInput<K, V> for UpsertHandle<K, V>
+where
+    K: DBData,
+    V: DBData,
+{
+    fn append(&mut self, vals: &mut Vec<(K, V)>) {
+        UpsertHandle::append(self, vals)
+    }
+}
+
+/// Advice returned by [`BufferedInput::flush_for_step`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepHint {
+    /// Step the circuit now.
+    StepNow,
+    /// Keep accumulating inputs before stepping the circuit.
+    Accumulate,
+}
+
+/// An input adapter that sizes the batches fed to the circuit at each step.
+///
+/// Feeding the circuit one giant batch per step causes latency spikes, while
+/// stepping the circuit for every few tuples wastes throughput on per-step
+/// overheads.  `BufferedInput` sits between producers and an input handle
+/// and helps the driver loop pick a middle ground, aiming for steps that
+/// take about `target_step_duration`.
+///
+/// Producers push tuples to a lock-free queue, either directly via
+/// [`push`](`Self::push`) or through any number of
+/// [`BufferedInputProducer`]s running in other threads.  The driver loop
+/// calls [`flush_for_step`](`Self::flush_for_step`), which moves all queued
+/// tuples to the input handle and returns a [`StepHint`].  On
+/// [`StepHint::StepNow`], the driver is expected to step the circuit right
+/// away and call `flush_for_step` again once the step completes.  On
+/// [`StepHint::Accumulate`], the tuples stay buffered in the input handle
+/// and the driver should call `flush_for_step` again later.
+///
+/// The adapter measures the duration of each step as the time between a
+/// `StepNow` hint and the next call to `flush_for_step`, and maintains a
+/// moving average of the time the circuit spends per input tuple.  It
+/// advises to step once the buffered tuples are expected to take at least
+/// `target_step_duration` to process, or once `target_step_duration` has
+/// elapsed since the previous step started, whichever comes first.  Until the
+/// first step has been measured, it advises to step whenever there are
+/// buffered tuples.
+///
+/// # Example
+///
+/// ```
+/// use dbsp::{
+///     operator::{BufferedInput, StepHint},
+///     RootCircuit,
+/// };
+/// use std::{thread, time::Duration};
+///
+/// let (circuit, handle) = RootCircuit::build(|circuit| {
+///     let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+///     stream.inspect(|batch| println!("{batch:?}"));
+///     handle
+/// })
+/// .unwrap();
+///
+/// let mut input = BufferedInput::new(handle, Duration::from_millis(10));
+/// let producer = input.producer();
+///
+/// let producer_thread = thread::spawn(move || {
+///     for x in 0..1000 {
+///         producer.push(x, 1);
+///     }
+/// });
+///
+/// while !producer_thread.is_finished() {
+///     if input.flush_for_step() == StepHint::StepNow {
+///         circuit.step().unwrap();
+///     }
+/// }
+/// producer_thread.join().unwrap();
+///
+/// // Feed the remaining tuples to the circuit.
+/// input.flush_for_step();
+/// circuit.step().unwrap();
+/// ```
+pub struct BufferedInput<H, K, V> {
+    handle: H,
+    queue: Arc<SegQueue<(K, V)>>,
+    target_step_duration: Duration,
+    // Tuples moved from `queue` to `handle`.
+    buffer: Vec<(K, V)>,
+    // The number of tuples handed to `handle` since the last `StepNow` hint.
+    pending: usize,
+    // Start of the current accumulation period.
+    accumulating_since: Instant,
+    // The start time and the number of input tuples of the step performed by
+    // the driver after the last `StepNow` hint.
+    current_step: Option<(Instant, usize)>,
+    // Moving average of the time the circuit takes to process an input
+    // tuple, in seconds.
+    secs_per_tuple: Option<f64>,
+}
+
+impl<H, K, V> BufferedInput<H, K, V>
+where
+    H: AppendInput<K, V>,
+{
+    /// Create an adapter that feeds tuples to `handle`, aiming for steps
+    /// that take about `target_step_duration`.
+    pub fn new(handle: H, target_step_duration: Duration) -> Self {
+        Self {
+            handle,
+            queue: Arc::new(SegQueue::new()),
+            target_step_duration,
+            buffer: Vec::new(),
+            pending: 0,
+            accumulating_since: Instant::now(),
+            current_step: None,
+            secs_per_tuple: None,
+        }
+    }
+
+    /// Returns a handle that pushes tuples to this adapter and can be sent
+    /// to other threads.
+    pub fn producer(&self) -> BufferedInputProducer<K, V> {
+        BufferedInputProducer {
+            queue: self.queue.clone(),
+        }
+    }
+
+    /// Push a single `(key, value)` pair to the queue.
+    pub fn push(&self, k: K, v: V) {
+        self.queue.push((k, v));
+    }
+
+    /// The number of tuples pushed by producers and not yet flushed to the
+    /// input handle.
+    pub fn buffered_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The number of tuples flushed to the input handle since the circuit
+    /// was last advised to step.
+    pub fn pending_len(&self) -> usize {
+        self.pending
+    }
+
+    /// Move all queued tuples to the input handle and advise the driver
+    /// whether to step the circuit now or to keep accumulating inputs.
+    ///
+    /// Tuples pushed concurrently with this call are either flushed by it
+    /// or left in the queue for the next call, so that each tuple is handed
+    /// to the input handle exactly once.
+    pub fn flush_for_step(&mut self) -> StepHint {
+        let now = Instant::now();
+
+        if let Some((start, tuples)) = self.current_step.take() {
+            self.record_step(now.duration_since(start), tuples);
+        }
+
+        // Don't chase producers that push faster than we can drain the queue.
+        for _ in 0..self.queue.len() {
+            match self.queue.pop() {
+                Some(tuple) => self.buffer.push(tuple),
+                None => break,
+            }
+        }
+
+        if !self.buffer.is_empty() {
+            self.pending += self.buffer.len();
+            self.handle.append(&mut self.buffer);
+        }
+
+        let hint = self.hint(now);
+        if hint == StepHint::StepNow {
+            self.current_step = Some((now, self.pending));
+            self.pending = 0;
+            self.accumulating_since = now;
+        }
+
+        hint
+    }
+
+    fn hint(&self, now: Instant) -> StepHint {
+        if self.pending == 0 {
+            return StepHint::Accumulate;
+        }
+
+        let secs_per_tuple = match self.secs_per_tuple {
+            None => return StepHint::StepNow,
+            Some(secs_per_tuple) => secs_per_tuple,
+        };
+
+        let expected_duration = secs_per_tuple * self.pending as f64;
+        if expected_duration >= self.target_step_duration.as_secs_f64()
+            || now.duration_since(self.accumulating_since) >= self.target_step_duration
+        {
+            StepHint::StepNow
+        } else {
+            StepHint::Accumulate
+        }
+    }
+
+    fn record_step(&mut self, duration: Duration, tuples: usize) {
+        if tuples == 0 {
+            return;
+        }
+
+        let sample = duration.as_secs_f64() / tuples as f64;
+        self.secs_per_tuple = Some(match self.secs_per_tuple {
+            None => sample,
+            Some(average) => (average + sample) / 2.0,
+        });
+    }
+}
+
+/// A handle used to push tuples to a [`BufferedInput`] from any thread.
+///
+/// Created by [`BufferedInput::producer`].
+pub struct BufferedInputProducer<K, V> {
+    queue: Arc<SegQueue<(K, V)>>,
+}
+
+impl<K, V> Clone for BufferedInputProducer<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<K, V> BufferedInputProducer<K, V> {
+    /// Push a single `(key, value)` pair to the queue.
+    pub fn push(&self, k: K, v: V) {
+        self.queue.push((k, v));
+    }
+
+    /// Push multiple `(key, value)` pairs to the queue, leaving `vals`
+    /// empty.
+    pub fn append(&self, vals: &mut Vec<(K, V)>) {
+        for tuple in vals.drain(..) {
+            self.queue.push(tuple);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        operator::{BufferedInput, StepHint},
+        trace::Batch,
+        OrdZSet, RootCircuit,
+    };
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn step_hints() {
+        let (circuit, handle) = RootCircuit::build(|circuit| {
+            let (_stream, handle) = circuit.add_input_zset::<u64, isize>();
+            handle
+        })
+        .unwrap();
+
+        let mut input = BufferedInput::new(handle, Duration::from_secs(3600));
+
+        // Nothing to do.
+        assert_eq!(input.flush_for_step(), StepHint::Accumulate);
+
+        // Step as soon as there is input until the first step is measured.
+        input.push(1, 1);
+        assert_eq!(input.flush_for_step(), StepHint::StepNow);
+        assert_eq!(input.pending_len(), 0);
+        circuit.step().unwrap();
+
+        // A step takes much less than the target duration, keep accumulating.
+        input.push(2, 1);
+        input.push(3, 1);
+        assert_eq!(input.flush_for_step(), StepHint::Accumulate);
+        assert_eq!(input.buffered_len(), 0);
+        assert_eq!(input.pending_len(), 2);
+
+        input.push(4, 1);
+        assert_eq!(input.flush_for_step(), StepHint::Accumulate);
+        assert_eq!(input.pending_len(), 3);
+    }
+
+    #[test]
+    fn step_when_target_duration_expires() {
+        let (circuit, handle) = RootCircuit::build(|circuit| {
+            let (_stream, handle) = circuit.add_input_zset::<u64, isize>();
+            handle
+        })
+        .unwrap();
+
+        let mut input = BufferedInput::new(handle, Duration::from_millis(1));
+
+        input.push(1, 1);
+        assert_eq!(input.flush_for_step(), StepHint::StepNow);
+        circuit.step().unwrap();
+        assert_eq!(input.flush_for_step(), StepHint::Accumulate);
+
+        input.push(2, 1);
+        thread::sleep(Duration::from_millis(5));
+        assert_eq!(input.flush_for_step(), StepHint::StepNow);
+    }
+
+    // Producers push tuples from multiple threads while the driver steps the
+    // circuit.  Each tuple must be fed to the circuit exactly once.
+    #[test]
+    fn concurrent_producers() {
+        const PRODUCERS: u64 = 4;
+        const TUPLES: u64 = 10_000;
+
+        let (circuit, (handle, output)) = RootCircuit::build(|circuit| {
+            let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+            (handle, stream.integrate().output())
+        })
+        .unwrap();
+
+        let mut input = BufferedInput::new(handle, Duration::from_micros(100));
+
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|producer_index| {
+                let producer = input.producer();
+                thread::spawn(move || {
+                    let mut batch = Vec::new();
+                    for x in producer_index * TUPLES..(producer_index + 1) * TUPLES {
+                        if x % 2 == 0 {
+                            producer.push(x, 1);
+                        } else {
+                            batch.push((x, 1));
+                            if batch.len() == 100 {
+                                producer.append(&mut batch);
+                            }
+                        }
+                    }
+                    producer.append(&mut batch);
+                })
+            })
+            .collect();
+
+        while producers.iter().any(|producer| !producer.is_finished()) {
+            if input.flush_for_step() == StepHint::StepNow {
+                circuit.step().unwrap();
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        // Feed the remaining tuples to the circuit.
+        input.flush_for_step();
+        circuit.step().unwrap();
+        assert_eq!(input.buffered_len(), 0);
+
+        let expected = OrdZSet::from_keys((), (0..PRODUCERS * TUPLES).map(|x| (x, 1)).collect());
+        assert_eq!(output.consolidate(), expected);
+    }
+}
@@ -0,0 +1,307 @@
+//! Change capture output adapter.
+
+use crate::{
+    algebra::{HasOne, HasZero, IndexedZSet, ZRingValue},
+    trace::{Batch, BatchReader, Cursor},
+    DBData, NumEntries, OrdIndexedZSet, OutputHandle, RootCircuit, Stream,
+};
+use std::ops::Neg;
+
+/// A change to a keyed collection, in the form consumed by change data
+/// capture (CDC) systems.
+///
+/// Produced by [`Stream::as_change_stream`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeEvent<K, V> {
+    /// A new record with key `key`.
+    Insert { key: K, new: V },
+    /// Record `old` with key `key` was deleted.
+    Delete { key: K, old: V },
+    /// Record `old` with key `key` was replaced with `new`.
+    Update { key: K, old: V, new: V },
+}
+
+impl<K, V> NumEntries for ChangeEvent<K, V> {
+    const CONST_NUM_ENTRIES: Option<usize> = Some(1);
+
+    fn num_entries_shallow(&self) -> usize {
+        1
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        1
+    }
+}
+
+impl<K, V> ChangeEvent<K, V> {
+    /// Key of the record affected by the change.
+    pub fn key(&self) -> &K {
+        match self {
+            Self::Insert { key, .. } | Self::Delete { key, .. } | Self::Update { key, .. } => key,
+        }
+    }
+}
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet + Send,
+    B::R: ZRingValue,
+    B::Item: DBData,
+{
+    /// Convert a stream of changes to a collection into a stream of
+    /// [`ChangeEvent`]s.
+    ///
+    /// `key_extractor` computes the key of each record in the collection,
+    /// e.g., its primary key.  At each step, the operator groups updates in
+    /// the input batch by key, and converts each group into change events:
+    ///
+    /// * A record with weight `w > 0` is inserted `w` times, and a record
+    ///   with weight `w < 0` is deleted `-w` times.
+    /// * Deleted and inserted records with the same key are paired into
+    ///   `Update` events.  Deleted records and inserted records are each
+    ///   taken in ascending order, so the `i`-th smallest deleted record is
+    ///   replaced with the `i`-th smallest inserted record.
+    /// * Deleted records left without a matching insertion produce `Delete`
+    ///   events, and unmatched inserted records produce `Insert` events.
+    ///
+    /// Within each key, `Update` events come first, followed by `Delete` and
+    /// `Insert` events.  Since the input batch is consolidated, a record
+    /// inserted and deleted in the same step produces no events.
+    ///
+    /// All updates with the same key are processed by the same worker.  Use
+    /// [`OutputHandle::take_changes`] to read change events produced by all
+    /// workers ordered by key.
+    pub fn as_change_stream<K, F>(
+        &self,
+        key_extractor: F,
+    ) -> Stream<RootCircuit, Vec<ChangeEvent<K, B::Item>>>
+    where
+        K: DBData,
+        F: Fn(&B::Key, &B::Val) -> K + 'static,
+    {
+        self.circuit().region("as_change_stream", || {
+            self.apply_named("ChangeStreamIndex", move |batch: &B| {
+                let mut tuples = Vec::with_capacity(batch.len());
+                let mut cursor = batch.cursor();
+                while cursor.key_valid() {
+                    while cursor.val_valid() {
+                        let key = key_extractor(cursor.key(), cursor.val());
+                        let item = B::item_from(cursor.key().clone(), cursor.val().clone());
+                        tuples.push(((key, item), cursor.weight()));
+                        cursor.step_val();
+                    }
+                    cursor.step_key();
+                }
+                OrdIndexedZSet::from_tuples((), tuples)
+            })
+            .shard()
+            .apply_named(
+                "ChangeStream",
+                |batch: &OrdIndexedZSet<K, B::Item, B::R>| {
+                    let mut events = Vec::new();
+                    let mut deleted = Vec::new();
+                    let mut inserted = Vec::new();
+
+                    let mut cursor = batch.cursor();
+                    while cursor.key_valid() {
+                        deleted.clear();
+                        inserted.clear();
+
+                        while cursor.val_valid() {
+                            let weight = cursor.weight();
+                            if weight.ge0() {
+                                push_copies(cursor.val(), weight, &mut inserted);
+                            } else {
+                                push_copies(cursor.val(), weight.neg(), &mut deleted);
+                            }
+                            cursor.step_val();
+                        }
+
+                        let key = cursor.key();
+                        let updates = deleted.len().min(inserted.len());
+
+                        for (old, new) in deleted.drain(..updates).zip(inserted.drain(..updates)) {
+                            events.push(ChangeEvent::Update {
+                                key: key.clone(),
+                                old,
+                                new,
+                            });
+                        }
+                        for old in deleted.drain(..) {
+                            events.push(ChangeEvent::Delete {
+                                key: key.clone(),
+                                old,
+                            });
+                        }
+                        for new in inserted.drain(..) {
+                            events.push(ChangeEvent::Insert {
+                                key: key.clone(),
+                                new,
+                            });
+                        }
+
+                        cursor.step_key();
+                    }
+
+                    events
+                },
+            )
+        })
+    }
+}
+
+// Pushes `weight` copies of `item` to `items`; `weight` must be non-negative.
+fn push_copies<T, R>(item: &T, mut weight: R, items: &mut Vec<T>)
+where
+    T: Clone,
+    R: ZRingValue,
+{
+    let minus_one = R::one().neg();
+    while !weight.is_zero() {
+        items.push(item.clone());
+        weight += minus_one.clone();
+    }
+}
+
+impl<K, V> OutputHandle<Vec<ChangeEvent<K, V>>>
+where
+    K: Ord + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    /// Read change events produced by all worker threads during the last
+    /// clock cycle.
+    ///
+    /// Returns events ordered by key.  Events with the same key are
+    /// produced by the same worker and retain their relative order.  See
+    /// [`Stream::as_change_stream`] for details.
+    pub fn take_changes(&self) -> Vec<ChangeEvent<K, V>> {
+        let mut events: Vec<_> = self.take_from_all().into_iter().flatten().collect();
+        events.sort_by(|left, right| left.key().cmp(right.key()));
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChangeEvent;
+    use crate::{CollectionHandle, DBSPHandle, OutputHandle, Runtime};
+
+    type Record = (u64, String);
+    type Event = ChangeEvent<u64, Record>;
+
+    fn change_stream_circuit(
+        workers: usize,
+    ) -> (
+        DBSPHandle,
+        (
+            CollectionHandle<u64, (String, isize)>,
+            OutputHandle<Vec<Event>>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, String, isize>();
+            let output = input.as_change_stream(|key, _val| *key).output();
+
+            (input_handle, output)
+        })
+        .unwrap()
+    }
+
+    fn insert(key: u64, new: &str) -> Event {
+        ChangeEvent::Insert {
+            key,
+            new: (key, new.to_string()),
+        }
+    }
+
+    fn delete(key: u64, old: &str) -> Event {
+        ChangeEvent::Delete {
+            key,
+            old: (key, old.to_string()),
+        }
+    }
+
+    fn update(key: u64, old: &str, new: &str) -> Event {
+        ChangeEvent::Update {
+            key,
+            old: (key, old.to_string()),
+            new: (key, new.to_string()),
+        }
+    }
+
+    fn test_change_stream(workers: usize) {
+        let (mut dbsp, (mut input, output)) = change_stream_circuit(workers);
+
+        let steps: Vec<(Vec<(u64, (String, isize))>, Vec<Event>)> = vec![
+            // Pure inserts.
+            (
+                vec![(1, ("a".to_string(), 1)), (2, ("b".to_string(), 1))],
+                vec![insert(1, "a"), insert(2, "b")],
+            ),
+            // Updates.
+            (
+                vec![
+                    (1, ("a".to_string(), -1)),
+                    (1, ("c".to_string(), 1)),
+                    (2, ("b".to_string(), -1)),
+                    (2, ("a".to_string(), 1)),
+                ],
+                vec![update(1, "a", "c"), update(2, "b", "a")],
+            ),
+            // Pure deletes.
+            (
+                vec![(1, ("c".to_string(), -1)), (2, ("a".to_string(), -1))],
+                vec![delete(1, "c"), delete(2, "a")],
+            ),
+            // Weights beyond +1.
+            (
+                vec![(3, ("x".to_string(), 2)), (4, ("y".to_string(), 3))],
+                vec![
+                    insert(3, "x"),
+                    insert(3, "x"),
+                    insert(4, "y"),
+                    insert(4, "y"),
+                    insert(4, "y"),
+                ],
+            ),
+            // Multiple changes to the same key: the smallest deleted record
+            // is replaced with the smallest inserted record, remaining
+            // deletions and insertions are reported separately.
+            (
+                vec![
+                    (3, ("x".to_string(), -2)),
+                    (3, ("z".to_string(), 1)),
+                    (4, ("y".to_string(), -1)),
+                    (4, ("v".to_string(), 1)),
+                    (4, ("w".to_string(), 2)),
+                ],
+                vec![
+                    update(3, "x", "z"),
+                    delete(3, "x"),
+                    update(4, "y", "v"),
+                    insert(4, "w"),
+                    insert(4, "w"),
+                ],
+            ),
+            // Changes that cancel out within a step produce no events.
+            (
+                vec![(5, ("q".to_string(), 1)), (5, ("q".to_string(), -1))],
+                vec![],
+            ),
+        ];
+
+        for (mut changes, expected) in steps {
+            input.append(&mut changes);
+            dbsp.step().unwrap();
+            assert_eq!(output.take_changes(), expected);
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn change_stream_test() {
+        test_change_stream(1);
+        test_change_stream(4);
+    }
+}
@@ -368,7 +368,8 @@ where
 ///     for _ in 1..ROUNDS {
 ///         circuit.step();
 ///     }
-/// });
+/// })
+/// .unwrap();
 ///
 /// hruntime.join().unwrap();
 /// # }
@@ -654,7 +655,8 @@ mod tests {
 
                 assert_eq!(input_data, output_data);
             }
-        });
+        })
+        .unwrap();
 
         hruntime.join().unwrap();
     }
@@ -720,7 +722,8 @@ mod tests {
                 for _ in 1..ROUNDS {
                     circuit.step().unwrap();
                 }
-            });
+            })
+            .unwrap();
 
             hruntime.join().unwrap();
         }
@@ -37,6 +37,11 @@ where
     /// The output stream in `receiver_worker` will contain a union of all
     /// input batches across all workers. The output streams in all other
     /// workers will contain empty batches.
+    ///
+    /// The union is computed by merging input batches using the batch merger
+    /// (via [`Spine`]) rather than by concatenating them, so the output is a
+    /// canonical batch: its tuples are sorted and weights of identical tuples
+    /// received from different workers are consolidated.
     #[track_caller]
     pub fn gather(&self, receiver_worker: usize) -> Stream<C, B>
     where
@@ -386,3 +391,116 @@ where
         Default::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        operator::Generator,
+        trace::{Batch, BatchReader, Cursor},
+        Circuit, OrdIndexedZSet, RootCircuit, Runtime,
+    };
+
+    #[test]
+    fn test_gather() {
+        do_test_gather(2);
+        do_test_gather(4);
+        do_test_gather(16);
+    }
+
+    // All workers produce overlapping keys.  Tuple `(n, n)` is produced by
+    // every worker, tuple `(n, 1000 * n)` only by some workers, and tuple
+    // `(1000, 0)` is inserted by even workers and deleted by odd workers.
+    fn test_data(worker_index: usize) -> OrdIndexedZSet<usize, usize, isize> {
+        let tuples: Vec<_> = (1..100)
+            .flat_map(|n| {
+                let mut tuples = vec![((n, n), 1)];
+                if n % (worker_index + 1) == 0 {
+                    tuples.push(((n, 1000 * n), 1));
+                }
+                tuples
+            })
+            .chain([((1000, 0), if worker_index % 2 == 0 { 1 } else { -1 })])
+            .collect();
+        <OrdIndexedZSet<usize, usize, isize>>::from_tuples((), tuples)
+    }
+
+    fn do_test_gather(workers: usize) {
+        let hruntime = Runtime::run(workers, move || {
+            let circuit = RootCircuit::build(move |circuit| {
+                let input =
+                    circuit.add_source(Generator::new(|| test_data(Runtime::worker_index())));
+                input
+                    .gather(0)
+                    .inspect(move |batch: &OrdIndexedZSet<usize, usize, isize>| {
+                        if Runtime::worker_index() != 0 {
+                            assert_eq!(batch.len(), 0);
+                            return;
+                        }
+
+                        // Keys and values are sorted and there are no duplicate tuples.
+                        let mut tuples = Vec::new();
+                        let mut cursor = batch.cursor();
+                        while cursor.key_valid() {
+                            while cursor.val_valid() {
+                                tuples.push(((*cursor.key(), *cursor.val()), cursor.weight()));
+                                cursor.step_val();
+                            }
+                            cursor.step_key();
+                        }
+                        assert!(tuples.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+                        // Weights of identical tuples are consolidated, and tuples whose
+                        // weights add up to zero are removed.
+                        for ((key, val), weight) in tuples.iter() {
+                            let expected = if *key == 1000 {
+                                workers % 2
+                            } else if key == val {
+                                workers
+                            } else {
+                                (0..workers)
+                                    .filter(|worker| key % (worker + 1) == 0)
+                                    .count()
+                            };
+                            assert_eq!(*weight, expected as isize);
+                        }
+                        assert_eq!(
+                            tuples.iter().any(|((key, _), _)| *key == 1000),
+                            workers % 2 == 1
+                        );
+
+                        let expected = <OrdIndexedZSet<usize, usize, isize>>::from_tuples(
+                            (),
+                            (0..workers)
+                                .flat_map(|worker| {
+                                    let mut tuples = Vec::new();
+                                    let batch = test_data(worker);
+                                    let mut cursor = batch.cursor();
+                                    while cursor.key_valid() {
+                                        while cursor.val_valid() {
+                                            tuples.push((
+                                                (*cursor.key(), *cursor.val()),
+                                                cursor.weight(),
+                                            ));
+                                            cursor.step_val();
+                                        }
+                                        cursor.step_key();
+                                    }
+                                    tuples
+                                })
+                                .collect(),
+                        );
+                        assert_eq!(batch, &expected);
+                    });
+            })
+            .unwrap()
+            .0;
+
+            for _ in 0..3 {
+                circuit.step().unwrap();
+            }
+        })
+        .unwrap();
+
+        hruntime.join().unwrap();
+    }
+}
@@ -1,6 +1,8 @@
 mod exchange;
 mod gather;
+mod partition;
 mod shard;
 
 pub(crate) use exchange::Exchange;
 pub use exchange::{new_exchange_operators, ExchangeReceiver, ExchangeSender};
+pub use partition::Partitioning;
@@ -0,0 +1,396 @@
+//! Explicit repartitioning of streams by a stable hash of a key.
+
+use crate::{
+    circuit::GlobalNodeId,
+    circuit_cache_key,
+    operator::communication::exchange::new_exchange_operators,
+    stable_hash,
+    trace::{cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace},
+    Circuit, Runtime, Stream,
+};
+use bincode::Encode;
+use std::{any::type_name, fmt, panic::Location};
+
+circuit_cache_key!(PartitioningId(GlobalNodeId => Partitioning));
+
+/// Describes how the contents of a stream is partitioned across workers by
+/// [`Stream::partition_by`].
+///
+/// Two streams with compatible partitionings (see
+/// [`is_compatible`](`Self::is_compatible`)) are co-partitioned, i.e., records
+/// with equal partitioning keys are assigned to the same worker.  Since the
+/// partitioning key is computed by a user-defined closure, we can only
+/// compare the number of partitions and the type of keys, and rely on the
+/// user to partition both streams by the same function of the key.
+#[derive(Clone, Debug)]
+pub struct Partitioning {
+    partitions: usize,
+    key_type: &'static str,
+    location: &'static Location<'static>,
+}
+
+impl Partitioning {
+    /// The number of partitions.
+    pub fn partitions(&self) -> usize {
+        self.partitions
+    }
+
+    /// The name of the partitioning key type.
+    pub fn key_type(&self) -> &'static str {
+        self.key_type
+    }
+
+    /// Source location of the `partition_by` call that created the stream.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns `true` if `self` and `other` are co-partitioned.
+    pub fn is_compatible(&self, other: &Self) -> bool {
+        self.partitions == other.partitions && self.key_type == other.key_type
+    }
+}
+
+impl fmt::Display for Partitioning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "partitioned into {} partitions by a key of type `{}` at {}",
+            self.partitions, self.key_type, self.location
+        )
+    }
+}
+
+impl<C, B> Stream<C, B>
+where
+    C: Circuit,
+    B: Batch<Time = ()> + Send,
+{
+    /// Repartition the stream across workers by a stable hash of a key
+    /// extracted from each record.
+    ///
+    /// Each key in the stream is assigned to one of `partitions` partitions
+    /// by [`stable_hash`](`crate::stable_hash`) of the partitioning key
+    /// computed by `key_extractor`, modulo `partitions`.  Partition `i` is
+    /// processed by worker `i % num_workers`.  Unlike the hash function used
+    /// by [`shard`](`Self::shard`), the assignment of keys to partitions
+    /// does not depend on the architecture or the version of the library,
+    /// so it can be persisted in checkpoints.
+    ///
+    /// The output stream is marked as sharded, i.e., operators that shard
+    /// their inputs, such as `join` or `aggregate`, consume it without
+    /// exchanging its contents across workers again.  This is only correct
+    /// if all records with the same key have the same partitioning key and,
+    /// for operators with multiple inputs, if all inputs are partitioned
+    /// the same way.  Use
+    /// [`assert_co_partitioned`](`Self::assert_co_partitioned`) to check the
+    /// latter when constructing the circuit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `partitions` is 0.
+    #[track_caller]
+    pub fn partition_by<PK, F>(&self, key_extractor: F, partitions: usize) -> Stream<C, B>
+    where
+        PK: Encode,
+        F: Fn(&B::Key) -> PK + 'static,
+    {
+        assert!(
+            partitions > 0,
+            "partition_by: the number of partitions must be positive"
+        );
+
+        let location = Location::caller();
+        let partitioning = Partitioning {
+            partitions,
+            key_type: type_name::<PK>(),
+            location,
+        };
+
+        let output = match Runtime::runtime() {
+            Some(runtime) if runtime.num_workers() > 1 => {
+                let num_workers = runtime.num_workers();
+                let mut builders = Vec::with_capacity(num_workers);
+
+                let (sender, receiver) = new_exchange_operators(
+                    &runtime,
+                    Runtime::worker_index(),
+                    Some(location),
+                    move |batch: B, batches: &mut Vec<B>| {
+                        Self::partition_batch(
+                            &batch,
+                            &key_extractor,
+                            partitions,
+                            num_workers,
+                            &mut builders,
+                            batches,
+                        );
+                    },
+                    |trace: &mut Spine<B>, batch: B| trace.insert(batch),
+                );
+
+                self.circuit()
+                    .add_exchange(sender, receiver, self)
+                    .consolidate()
+            }
+            _ => self.clone(),
+        };
+
+        self.circuit().cache_insert(
+            PartitioningId::new(output.origin_node_id().clone()),
+            partitioning,
+        );
+
+        output.mark_sharded()
+    }
+
+    // Splits the batch into `num_workers` batches, one for each worker, based
+    // on the partition of each key.
+    fn partition_batch<PK, F>(
+        batch: &B,
+        key_extractor: &F,
+        partitions: usize,
+        num_workers: usize,
+        builders: &mut Vec<B::Builder>,
+        outputs: &mut Vec<B>,
+    ) where
+        PK: Encode,
+        F: Fn(&B::Key) -> PK,
+    {
+        builders.clear();
+
+        for _ in 0..num_workers {
+            builders.push(B::Builder::with_capacity((), batch.len() / num_workers));
+        }
+
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            let partition =
+                (stable_hash(&key_extractor(cursor.key())) % partitions as u64) as usize;
+            let worker = partition % num_workers;
+            while cursor.val_valid() {
+                builders[worker].push((
+                    B::item_from(cursor.key().clone(), cursor.val().clone()),
+                    cursor.weight(),
+                ));
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        for builder in builders.drain(..) {
+            outputs.push(builder.done());
+        }
+    }
+}
+
+impl<C, T> Stream<C, T>
+where
+    C: Circuit,
+    T: 'static,
+{
+    /// Returns the partitioning of the stream if it was produced by
+    /// [`partition_by`](`Self::partition_by`).
+    pub fn partitioning(&self) -> Option<Partitioning> {
+        self.circuit()
+            .cache_get(&PartitioningId::new(self.origin_node_id().clone()))
+    }
+
+    /// Asserts that `self` and `other` are co-partitioned.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless both streams were produced by
+    /// [`partition_by`](`Self::partition_by`) with the same number of
+    /// partitions and the same partitioning key type.  The panic message
+    /// identifies both streams and describes their partitioning.
+    #[track_caller]
+    pub fn assert_co_partitioned<T2>(&self, other: &Stream<C, T2>)
+    where
+        T2: 'static,
+    {
+        let describe =
+            |node_id: &GlobalNodeId, partitioning: &Option<Partitioning>| match partitioning {
+                Some(partitioning) => format!("stream {node_id} is {partitioning}"),
+                None => format!("stream {node_id} is not partitioned by `partition_by`"),
+            };
+
+        let partitioning = self.partitioning();
+        let other_partitioning = other.partitioning();
+
+        let compatible = match (&partitioning, &other_partitioning) {
+            (Some(partitioning), Some(other_partitioning)) => {
+                partitioning.is_compatible(other_partitioning)
+            }
+            _ => false,
+        };
+
+        if !compatible {
+            panic!(
+                "streams are not co-partitioned: {}; {}",
+                describe(self.origin_node_id(), &partitioning),
+                describe(other.origin_node_id(), &other_partitioning),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::trace::CircuitEvent,
+        operator::FilterMap,
+        stable_hash,
+        trace::{BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdZSet, OutputHandle, RootCircuit, Runtime,
+    };
+    use std::{cell::Cell, rc::Rc};
+
+    type InputHandle = CollectionHandle<u64, (u64, isize)>;
+    type JoinOutput = OrdZSet<(u64, u64, u64), isize>;
+
+    // Counts exchange operators added to the circuit.
+    fn count_exchanges(circuit: &RootCircuit) -> Rc<Cell<usize>> {
+        let exchanges = Rc::new(Cell::new(0));
+        let exchanges_clone = exchanges.clone();
+
+        circuit.register_circuit_event_handler("count_exchanges", move |event| {
+            if let CircuitEvent::Operator { name, .. } = event {
+                if name == "ExchangeSender" {
+                    exchanges_clone.set(exchanges_clone.get() + 1);
+                }
+            }
+        });
+
+        exchanges
+    }
+
+    fn join_circuit(
+        workers: usize,
+    ) -> (
+        DBSPHandle,
+        (
+            InputHandle,
+            InputHandle,
+            OutputHandle<JoinOutput>,
+            OutputHandle<JoinOutput>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, move |circuit| {
+            let exchanges = count_exchanges(circuit);
+
+            let (left, left_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (right, right_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+
+            let left_partitioned = left.partition_by(|k| *k, 16);
+            let right_partitioned = right.partition_by(|k| *k, 16);
+            left_partitioned.assert_co_partitioned(&right_partitioned);
+
+            // Joining co-partitioned streams does not exchange data across workers.
+            let before = exchanges.get();
+            let actual = left_partitioned
+                .join(&right_partitioned, |k, v1, v2| (*k, *v1, *v2))
+                .integrate();
+            assert_eq!(exchanges.get(), before);
+
+            let expected = left.join(&right, |k, v1, v2| (*k, *v1, *v2)).integrate();
+
+            circuit.unregister_circuit_event_handler("count_exchanges");
+
+            (
+                left_handle,
+                right_handle,
+                actual.output(),
+                expected.output(),
+            )
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_partition_by_join() {
+        for workers in [1, 2, 4] {
+            let (mut dbsp, (mut left, mut right, actual, expected)) = join_circuit(workers);
+
+            for step in 0..10u64 {
+                left.append(&mut (0..100).map(|k| (k, (step * 100 + k, 1))).collect());
+                right.append(
+                    &mut (0..100)
+                        .filter(|k| k % 3 == step % 3)
+                        .map(|k| (k, (step, 1)))
+                        .collect(),
+                );
+                dbsp.step().unwrap();
+
+                let actual = actual.consolidate();
+                assert_eq!(actual, expected.consolidate());
+                assert!(!actual.is_empty());
+            }
+
+            dbsp.kill().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_partition_by_placement() {
+        let (mut dbsp, (mut input, output)) = Runtime::init_circuit(4, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+
+            // Tag each record with the index of the worker that received it.
+            let output = input
+                .partition_by(|k| *k, 8)
+                .map(|(k, v)| (*k, *v, Runtime::worker_index()))
+                .output();
+
+            (input_handle, output)
+        })
+        .unwrap();
+
+        input.append(&mut (0..1000).map(|k| (k, (k, 1))).collect());
+        dbsp.step().unwrap();
+
+        let output = output.consolidate();
+        assert_eq!(output.len(), 1000);
+
+        let mut cursor = output.cursor();
+        while cursor.key_valid() {
+            let (k, _v, worker) = cursor.key();
+            assert_eq!(
+                *worker,
+                (stable_hash(k) as usize % 8) % 4,
+                "key {k} is assigned to the wrong worker"
+            );
+            cursor.step_key();
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "not co-partitioned")]
+    fn test_not_co_partitioned() {
+        RootCircuit::build(|circuit| {
+            let (left, _) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (right, _) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+
+            let left = left.partition_by(|k| *k, 16);
+            let right = right.partition_by(|k| *k, 8);
+            left.assert_co_partitioned(&right);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "is not partitioned by `partition_by`")]
+    fn test_not_partitioned() {
+        RootCircuit::build(|circuit| {
+            let (left, _) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (right, _) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+
+            left.partition_by(|k| *k, 16)
+                .assert_co_partitioned(&right.shard());
+        })
+        .unwrap();
+    }
+}
@@ -5,23 +5,52 @@
 // - different sharding modes.
 
 use crate::{
-    circuit::GlobalNodeId,
+    circuit::{
+        metrics::{MetricLabels, EXCHANGE_BYTES},
+        GlobalNodeId,
+    },
     circuit_cache_key, default_hash,
     operator::communication::exchange::new_exchange_operators,
     trace::{cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace},
-    Circuit, Runtime, Stream,
+    Circuit, Runtime, ShardHasher, ShardHasherKind, Stream,
 };
-use std::{hash::Hash, panic::Location};
+use std::{any::TypeId, hash::Hash, panic::Location};
 
 circuit_cache_key!(ShardId<C, D>((GlobalNodeId, ShardingPolicy) => Stream<C, D>));
 
-// An attempt to future-proof the design for when we support multiple sharding
-// disciplines.
+/// Identifies the hash function used to assign keys to workers.
+///
+/// A stream sharded with one hasher is not sharded from the point of view of
+/// operators that use a different hasher.
 #[derive(Hash, PartialEq, Eq)]
-pub struct ShardingPolicy;
+pub struct ShardingPolicy {
+    hasher_type: TypeId,
+    hasher_hash: u64,
+}
+
+impl ShardingPolicy {
+    fn new<H>(hasher: &H) -> Self
+    where
+        H: ShardHasher,
+    {
+        Self {
+            hasher_type: TypeId::of::<H>(),
+            hasher_hash: default_hash(hasher),
+        }
+    }
+}
 
+/// The sharding policy of the `shard` operator, used by all operators that
+/// shard their inputs.
 fn sharding_policy<C>(_circuit: &C) -> ShardingPolicy {
-    ShardingPolicy
+    ShardingPolicy::new(&runtime_shard_hasher())
+}
+
+/// Returns the shard hasher configured for the current runtime.
+fn runtime_shard_hasher() -> ShardHasherKind {
+    Runtime::runtime()
+        .map(|runtime| runtime.shard_hasher())
+        .unwrap_or_default()
 }
 
 impl<C, IB> Stream<C, IB>
@@ -51,6 +80,8 @@ where
     /// is true for all linear operators.
     ///
     /// The `shard` operator shards input batches based on the hash of the key,
+    /// computed by the [`ShardHasher`] selected by
+    /// [`RuntimeConfig::shard_hasher`](`crate::RuntimeConfig::shard_hasher`),
     /// making sure that tuples with the same key always end up at the same
     /// worker.  More precisely, the operator **re-shards** its input by
     /// partitioning batches in the input stream of each worker based on the
@@ -97,6 +128,22 @@ where
         self.shard_generic().unwrap_or_else(|| self.clone())
     }
 
+    /// Like [`Self::shard`], but routes keys to workers using `hasher`
+    /// instead of the hasher configured for the runtime.
+    ///
+    /// Operators that shard their inputs, e.g., `join`, only treat the
+    /// output of this operator as sharded if `hasher` is equal to the
+    /// hasher configured for the runtime; otherwise they re-shard it.
+    #[track_caller]
+    pub fn shard_with<H>(&self, hasher: H) -> Stream<C, IB>
+    where
+        IB: Batch + Send,
+        H: ShardHasher,
+    {
+        self.shard_generic_with(hasher)
+            .unwrap_or_else(|| self.clone())
+    }
+
     /// Like [`Self::shard`], but can assemble the results into any output batch
     /// type `OB`.
     ///
@@ -106,6 +153,17 @@ where
     pub fn shard_generic<OB>(&self) -> Option<Stream<C, OB>>
     where
         OB: Batch<Key = IB::Key, Val = IB::Val, Time = (), R = IB::R> + Send,
+    {
+        self.shard_generic_with(runtime_shard_hasher())
+    }
+
+    /// Like [`Self::shard_generic`], but routes keys to workers using
+    /// `hasher`.
+    #[track_caller]
+    pub fn shard_generic_with<OB, H>(&self, hasher: H) -> Option<Stream<C, OB>>
+    where
+        OB: Batch<Key = IB::Key, Val = IB::Val, Time = (), R = IB::R> + Send,
+        H: ShardHasher,
     {
         let location = Location::caller();
 
@@ -118,20 +176,36 @@ where
                 let output = self
                     .circuit()
                     .cache_get_or_insert_with(
-                        ShardId::new((
-                            self.origin_node_id().clone(),
-                            sharding_policy(self.circuit()),
-                        )),
+                        ShardId::new((self.origin_node_id().clone(), ShardingPolicy::new(&hasher))),
                         move || {
                             // As a minor optimization, we reuse this array across all invocations
                             // of the sharding operator.
                             let mut builders = Vec::with_capacity(runtime.num_workers());
+                            let worker = Runtime::worker_index();
+                            let labels = MetricLabels::stream("Shard", self.origin_node_id());
+                            let policy = ShardingPolicy::new(&hasher);
                             let (sender, receiver) = new_exchange_operators(
                                 &runtime,
-                                Runtime::worker_index(),
+                                worker,
                                 Some(location),
                                 move |batch: IB, batches: &mut Vec<OB>| {
-                                    Self::shard_batch(&batch, num_workers, &mut builders, batches);
+                                    Self::shard_batch(
+                                        &batch,
+                                        &hasher,
+                                        num_workers,
+                                        &mut builders,
+                                        batches,
+                                    );
+                                    metric_counter!(
+                                        EXCHANGE_BYTES,
+                                        batches
+                                            .iter()
+                                            .enumerate()
+                                            .filter(|(receiver, _)| *receiver != worker)
+                                            .map(|(_, batch)| batch.size_of().total_bytes())
+                                            .sum::<usize>(),
+                                        &labels
+                                    );
                                 },
                                 |trace: &mut Spine<OB>, batch: OB| trace.insert(batch),
                             );
@@ -144,10 +218,7 @@ where
                                 .consolidate();
 
                             self.circuit().cache_insert(
-                                ShardId::new((
-                                    output.origin_node_id().clone(),
-                                    sharding_policy(self.circuit()),
-                                )),
+                                ShardId::new((output.origin_node_id().clone(), policy)),
                                 output.clone(),
                             );
 
@@ -162,13 +233,15 @@ where
     }
 
     // Partitions the batch into `nshards` partitions based on the hash of the key.
-    fn shard_batch<OB>(
+    fn shard_batch<OB, H>(
         batch: &IB,
+        hasher: &H,
         shards: usize,
         builders: &mut Vec<OB::Builder>,
         outputs: &mut Vec<OB>,
     ) where
         OB: Batch<Key = IB::Key, Val = IB::Val, Time = (), R = IB::R>,
+        H: ShardHasher,
     {
         builders.clear();
 
@@ -182,7 +255,7 @@ where
         let mut cursor = batch.cursor();
 
         while cursor.key_valid() {
-            let batch_index = default_hash(cursor.key()) as usize % shards;
+            let batch_index = hasher.shard_of(cursor.key(), shards);
             while cursor.val_valid() {
                 builders[batch_index].push((
                     OB::item_from(cursor.key().clone(), cursor.val().clone()),
@@ -258,7 +331,8 @@ mod tests {
     use crate::{
         operator::Generator,
         trace::{Batch, BatchReader},
-        Circuit, OrdIndexedZSet, RootCircuit, Runtime,
+        Circuit, MultiplyShiftHasher, OrdIndexedZSet, OrdZSet, RootCircuit, Runtime, RuntimeConfig,
+        ShardHasherKind,
     };
 
     #[test]
@@ -301,7 +375,62 @@ mod tests {
             for _ in 0..3 {
                 circuit.step().unwrap();
             }
-        });
+        })
+        .unwrap();
+
+        hruntime.join().unwrap();
+    }
+
+    #[test]
+    fn test_shard_multiply_shift() {
+        for workers in [2, 3, 4, 7] {
+            do_test_shard_multiply_shift(workers);
+        }
+    }
+
+    // Sequential integer keys are spread evenly across workers by the
+    // multiply-shift hasher, and `shard_with` routes keys the same way as
+    // `shard` in a runtime configured with the same hasher.
+    fn do_test_shard_multiply_shift(workers: usize) {
+        let config = RuntimeConfig::new()
+            .workers(workers)
+            .shard_hasher(ShardHasherKind::MultiplyShift);
+
+        let hruntime = Runtime::run_with_config(config, move || {
+            let circuit = RootCircuit::build(move |circuit| {
+                let input = circuit.add_source(Generator::new(|| {
+                    let keys = if Runtime::worker_index() == 0 {
+                        (0..10_000u64).map(|n| (n, 1)).collect()
+                    } else {
+                        Vec::new()
+                    };
+                    <OrdZSet<u64, isize>>::from_keys((), keys)
+                }));
+
+                let sharded = input.shard();
+                sharded.inspect(move |batch: &OrdZSet<u64, isize>| {
+                    let expected = 10_000 / workers;
+                    assert!(
+                        batch.len().abs_diff(expected) * 20 <= expected,
+                        "worker {} received {} keys, expected {expected}",
+                        Runtime::worker_index(),
+                        batch.len()
+                    );
+                });
+
+                sharded.apply2(
+                    &input.shard_with(MultiplyShiftHasher),
+                    |sharded, sharded_with| assert_eq!(sharded, sharded_with),
+                );
+            })
+            .unwrap()
+            .0;
+
+            for _ in 0..3 {
+                circuit.step().unwrap();
+            }
+        })
+        .unwrap();
 
         hruntime.join().unwrap();
     }
@@ -4,7 +4,7 @@ use crate::{
     algebra::GroupValue,
     circuit::{Circuit, GlobalNodeId, Stream},
     circuit_cache_key,
-    operator::Minus,
+    operator::{Minus, Z1},
     NumEntries,
 };
 use size_of::SizeOf;
@@ -35,8 +35,59 @@ where
             .clone()
     }
 
+    /// Stream differentiation with a non-zero initial value.
+    ///
+    /// Like [`differentiate`](`Self::differentiate`), but assumes that the
+    /// value of `self` before the first clock cycle was `initial` rather than
+    /// zero: `differentiate_with_initial(a, i)[0] = a[0] - i`.  When `self` is
+    /// a stream of complete snapshots of a collection, e.g., the integral of a
+    /// stream of changes, and `initial` is the snapshot that the consumer of
+    /// the output stream already has, the first output only contains changes
+    /// relative to that snapshot.
+    ///
+    /// In a multithreaded runtime, `initial` must be the part of the initial
+    /// value that corresponds to the part of `self` computed by the current
+    /// worker.
+    pub fn differentiate_with_initial(&self, initial: D) -> Stream<C, D> {
+        let delayed = self.circuit().add_unary_operator(Z1::new(initial), self);
+        let differentiated = self
+            .circuit()
+            .add_binary_operator(Minus::new(), self, &delayed);
+        differentiated.mark_sharded_if(self);
+        differentiated
+    }
+
     /// Nested stream differentiation.
+    ///
+    /// Computes the difference between the current nested stream and the
+    /// nested stream at the previous parent timestamp, element-by-element:
+    /// `differentiate_nested(a)[i,j] = a[i,j] - a[i-1,j]`, where `stream[i,j]`
+    /// is the value of `stream` at time `[i,j]`, `i` is the parent timestamp,
+    /// and `j` is the child timestamp.  This is the inverse of
+    /// [`integrate_nested`](`Self::integrate_nested`).
+    ///
+    /// Inside an iterative subcircuit, this operator converts a value
+    /// computed from scratch at each parent timestamp into changes to that
+    /// value over the parent clock.  In particular, if `a` converges to a
+    /// fixed point, then the last value of `differentiate_nested(a)` is the
+    /// difference between the fixed points computed at the current and the
+    /// previous parent timestamps.  Like [`Z1Nested`](`crate::operator::Z1Nested`),
+    /// the operator assumes that the child circuit reached a fixed point at
+    /// the previous parent timestamp, i.e., `a[i-1,j]` beyond the last
+    /// iteration at parent timestamp `i-1` is equal to the value at the
+    /// last iteration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` belongs to the root circuit, which does not have a
+    /// parent clock.  Use [`differentiate`](`Self::differentiate`) instead.
+    #[track_caller]
     pub fn differentiate_nested(&self) -> Stream<C, D> {
+        assert!(
+            C::NESTING_DEPTH > 0,
+            "`differentiate_nested` cannot be used in the root circuit, which does not have a parent clock; use `differentiate` instead"
+        );
+
         self.circuit()
             .cache_get_or_insert_with(
                 NestedDifferentiateId::new(self.origin_node_id().clone()),
@@ -53,3 +104,33 @@ where
             .clone()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{zset, RootCircuit};
+
+    #[test]
+    fn differentiate_with_initial() {
+        let (circuit, (mut input, output)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+
+            // Snapshot already known to the consumer of the output stream.
+            let snapshot = zset! { 1 => 1, 2 => 1 };
+            let output = input
+                .integrate()
+                .differentiate_with_initial(snapshot)
+                .output();
+
+            (input_handle, output)
+        })
+        .unwrap();
+
+        input.append(&mut vec![(1, 1), (2, 1), (3, 1)]);
+        circuit.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 3 => 1 });
+
+        input.append(&mut vec![(1, -1)]);
+        circuit.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 1 => -1 });
+    }
+}
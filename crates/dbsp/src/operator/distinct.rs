@@ -5,11 +5,12 @@ use crate::{
     circuit::{
         metadata::{MetaItem, OperatorMeta},
         operator_traits::{BinaryOperator, Operator, UnaryOperator},
-        Circuit, GlobalNodeId, Scope, Stream, WithClock,
+        Circuit, GlobalNodeId, RootCircuit, Scope, Stream, WithClock,
     },
     circuit_cache_key,
+    operator::trace::TraceBound,
     trace::{ord::OrdValSpine, Batch, BatchReader, Builder, Cursor as TraceCursor, Trace},
-    DBTimestamp, OrdIndexedZSet, Timestamp,
+    DBData, DBTimestamp, OrdIndexedZSet, Timestamp,
 };
 use size_of::SizeOf;
 use std::{
@@ -106,6 +107,74 @@ where
     }
 }
 
+impl<Z> Stream<RootCircuit, Z> {
+    /// Incrementally deduplicate an append-only stream, using `watermark` to
+    /// bound the operator's state.
+    ///
+    /// Computes the same output as [`distinct`](`Self::distinct`), except
+    /// that the operator discards keys with timestamps below the current
+    /// watermark from its internal integral, where the timestamp of each key
+    /// is computed by `ts_func`.
+    ///
+    /// The operator expects that the input stream does not contain records
+    /// with timestamps below the watermark.  If such a record is a duplicate
+    /// of a previously observed record that has already been discarded, it
+    /// will appear in the output again.  In particular, duplicates separated
+    /// by more than the lateness bound used to compute the watermark may
+    /// reappear in the output.
+    ///
+    /// # Arguments
+    ///
+    /// * `watermark` - monotonically growing lower bound on timestamps in the
+    ///   input stream, e.g., computed by the
+    ///   [`watermark_monotonic`](`Stream::watermark_monotonic`) operator.
+    /// * `ts_func` - extracts timestamp from a key.  Must be monotonic with
+    ///   respect to key ordering, e.g., keys can be tuples whose first element
+    ///   is a timestamp.
+    pub fn distinct_with_watermark<TS, F>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        ts_func: F,
+    ) -> Stream<RootCircuit, Z>
+    where
+        Z: IndexedZSet + Send,
+        Z::R: ZRingValue,
+        TS: DBData,
+        F: Fn(&Z::Key) -> TS + 'static,
+    {
+        let circuit = self.circuit();
+
+        circuit.region("distinct_with_watermark", || {
+            let stream = self.shard();
+
+            // Keys below `bound` are truncated from the integral.
+            let bound = TraceBound::new();
+            let integral = stream.integrate_trace_with_bound(bound.clone(), TraceBound::new());
+
+            // Advance the bound to the smallest key whose timestamp is not
+            // below the watermark.
+            integral.apply2(watermark, move |integral, watermark| {
+                let mut cursor = integral.cursor();
+                while cursor.key_valid() && &ts_func(cursor.key()) < watermark {
+                    cursor.step_key();
+                }
+
+                if cursor.key_valid() && bound.get().as_ref() < Some(cursor.key()) {
+                    bound.set(cursor.key().clone());
+                }
+            });
+
+            circuit
+                .add_binary_operator(
+                    DistinctIncrementalTotal::new(),
+                    &stream,
+                    &integral.delay_trace(),
+                )
+                .mark_sharded()
+        })
+    }
+}
+
 /// `Distinct` operator changes all weights in the support of a Z-set to 1.
 pub struct Distinct<Z> {
     _type: PhantomData<Z>,
@@ -735,15 +804,18 @@ mod test {
 
     use crate::{
         indexed_zset,
-        operator::{Generator, GeneratorNested},
+        operator::{trace::TraceBound, Generator, GeneratorNested},
         trace::Batch,
-        zset, Circuit, OrdIndexedZSet, OrdZSet, OutputHandle, RootCircuit, Runtime,
+        zset, Circuit, CircuitHandle, CollectionHandle, OrdIndexedZSet, OrdZSet, OutputHandle,
+        RootCircuit, Runtime,
     };
+    use size_of::SizeOf;
 
     fn do_distinct_inc_test_mt(workers: usize) {
         let hruntime = Runtime::run(workers, || {
             distinct_inc_test();
-        });
+        })
+        .unwrap();
 
         hruntime.join().unwrap();
     }
@@ -1023,6 +1095,96 @@ mod test {
             .unwrap();
     }
 
+    #[test]
+    fn distinct_with_watermark_test() {
+        let (circuit, input_handle) = RootCircuit::build(move |circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<(u64, u64), isize>();
+
+            let mut expected_outputs = vec![
+                zset! { (5, 0) => 1, (20, 1) => 1 },
+                // In-bound duplicate.
+                zset! { (15, 0) => 1 },
+                zset! { (40, 0) => 1 },
+                // Duplicate separated by more than `lateness` reappears.
+                zset! { (5, 0) => 1 },
+            ]
+            .into_iter();
+
+            let watermark = input.watermark_monotonic(|(ts, _)| ts.saturating_sub(10));
+            input
+                .distinct_with_watermark(&watermark, |(ts, _)| *ts)
+                .inspect(move |batch| assert_eq!(batch, &expected_outputs.next().unwrap()));
+
+            input_handle
+        })
+        .unwrap();
+
+        input_handle.push((5, 0), 2);
+        input_handle.push((20, 1), 1);
+        circuit.step().unwrap();
+
+        input_handle.push((15, 0), 1);
+        input_handle.push((20, 1), 1);
+        circuit.step().unwrap();
+
+        input_handle.push((40, 0), 1);
+        circuit.step().unwrap();
+
+        input_handle.push((5, 0), 1);
+        circuit.step().unwrap();
+    }
+
+    type TimestampedZSet = OrdZSet<(u64, u64), isize>;
+
+    fn distinct_with_watermark_test_circuit(
+        lateness: u64,
+        size_bound: usize,
+    ) -> (CircuitHandle, CollectionHandle<(u64, u64), isize>) {
+        RootCircuit::build(move |circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<(u64, u64), isize>();
+
+            let watermark = input.watermark_monotonic(move |(ts, _)| ts.saturating_sub(lateness));
+            let expected = input.integrate().stream_distinct();
+            let actual = input
+                .distinct_with_watermark(&watermark, |(ts, _)| *ts)
+                .integrate();
+            expected.apply2(&actual, |expected: &TimestampedZSet, actual| {
+                assert_eq!(expected, actual)
+            });
+
+            // Check the size of the integral maintained by the operator.
+            let bound = TraceBound::new();
+            bound.set((u64::max_value(), u64::max_value()));
+            input
+                .integrate_trace_with_bound(bound, TraceBound::new())
+                .apply(move |trace| assert!(trace.size_of().total_bytes() <= size_bound));
+
+            input_handle
+        })
+        .unwrap()
+    }
+
+    // Generates batches with timestamps in a sliding window, so that the
+    // watermark computed with `lateness >= window_size` never exceeds the
+    // timestamps in the next batch.
+    fn timestamped_input(
+        window_step: u64,
+        window_size: u64,
+        max_batch_size: usize,
+        batches: usize,
+    ) -> impl Strategy<Value = Vec<Vec<((u64, u64), isize)>>> {
+        (0..batches)
+            .map(|i| {
+                let from = i as u64 * window_step;
+                collection::vec(
+                    ((from..from + window_size, 0..5u64), 1..3isize),
+                    0..max_batch_size,
+                )
+                .boxed()
+            })
+            .collect::<Vec<_>>()
+    }
+
     proptest! {
         #[test]
         fn proptest_distinct_test_st(inputs in test_input()) {
@@ -1079,4 +1241,19 @@ mod test {
             circuit.kill().unwrap();
         }
     }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(5))]
+
+        #[test]
+        fn proptest_distinct_with_watermark(trace in timestamped_input(100, 200, 50, 200)) {
+            // Without GC, this test needs >200KB.
+            let (circuit, mut input) = distinct_with_watermark_test_circuit(200, 50_000);
+
+            for mut batch in trace {
+                input.append(&mut batch);
+                circuit.step().unwrap();
+            }
+        }
+    }
 }
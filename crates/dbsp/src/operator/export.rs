@@ -0,0 +1,634 @@
+//! Consistent snapshot export of multiple collections.
+#![cfg(feature = "with-serde")]
+
+use crate::{
+    circuit::RootCircuit,
+    trace::{consolidation::consolidate, spine_fueled::Spine, Batch, BatchReader, Cursor},
+    Error as DBSPError, OutputHandle, Stream,
+};
+use serde::Serialize;
+use size_of::SizeOf;
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt::{self, Display},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Name of the manifest file written to the export directory.
+pub const MANIFEST_FILE: &str = "MANIFEST";
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: Batch<Time = ()> + Send + Sync,
+    B::Key: Serialize,
+    B::Val: Serialize,
+    B::R: Serialize,
+{
+    /// Integrates `self` and returns a target that can be passed to
+    /// [`DBSPHandle::export_snapshot`](`crate::DBSPHandle::export_snapshot`)
+    /// to dump the contents of the integral to a file named after `name`.
+    ///
+    /// The export reads the trace shared by all operators that call
+    /// [`Stream::integrate_trace`] on `self`, rather than maintaining a
+    /// separate integral.  At each clock cycle that changes its shard of the
+    /// trace, each worker publishes copies of the batches in the shard, so
+    /// taking a snapshot between clock cycles only requires cloning a few
+    /// `Arc`s.  `name` must be non-empty and consist of ASCII letters,
+    /// digits, `-` and `_` only.
+    pub fn export_target(&self, name: &str, format: ExportFormat) -> ExportTarget
+    where
+        Spine<B>: SizeOf,
+    {
+        let batches = RefCell::new(Vec::new());
+        let output = self
+            .apply2(
+                &self.integrate_trace(),
+                move |delta: &B, trace: &Spine<B>| {
+                    let mut batches = batches.borrow_mut();
+                    if !delta.is_empty() {
+                        batches.clear();
+                        trace.map_batches(|batch| {
+                            if !batch.is_empty() {
+                                batches.push(Arc::new(batch.clone()));
+                            }
+                        });
+                    }
+                    batches.clone()
+                },
+            )
+            .output();
+
+        ExportTarget {
+            name: name.to_string(),
+            format,
+            source: Arc::new(SnapshotHandle {
+                output,
+                snapshots: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+}
+
+/// Format of the files written by
+/// [`DBSPHandle::export_snapshot`](`crate::DBSPHandle::export_snapshot`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One `key,value,weight` record per line, written to `<name>.csv`.
+    #[cfg(feature = "with-csv")]
+    Csv,
+    /// A vector of `((key, value), weight)` tuples encoded with bincode's
+    /// standard configuration, written to `<name>.bin`.
+    Bincode,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "with-csv")]
+            Self::Csv => "csv",
+            Self::Bincode => "bin",
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "with-csv")]
+            Self::Csv => f.write_str("csv"),
+            Self::Bincode => f.write_str("bincode"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            #[cfg(feature = "with-csv")]
+            "csv" => Ok(Self::Csv),
+            "bincode" => Ok(Self::Bincode),
+            _ => Err(format!("unknown export format '{s}'")),
+        }
+    }
+}
+
+/// A collection that can be exported by
+/// [`DBSPHandle::export_snapshot`](`crate::DBSPHandle::export_snapshot`).
+///
+/// Created by [`Stream::export_target`].
+#[derive(Clone)]
+pub struct ExportTarget {
+    name: String,
+    format: ExportFormat,
+    source: Arc<dyn SnapshotSource>,
+}
+
+impl ExportTarget {
+    /// Name of the exported collection.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Format the collection is exported in.
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.{}", self.name, self.format.extension())
+    }
+}
+
+/// Type-erased source of snapshots of an exported collection.
+trait SnapshotSource: Send + Sync {
+    /// Clones references to the batches published by all workers during the
+    /// last clock cycle.
+    fn snapshot(&self) -> Box<dyn TraceSnapshot>;
+}
+
+/// A snapshot of an exported collection.
+trait TraceSnapshot: Send {
+    /// Writes the consolidated contents of the snapshot to `writer`, returns
+    /// the number of tuples written.
+    fn write(&self, format: ExportFormat, writer: &mut dyn Write) -> io::Result<usize>;
+}
+
+struct SnapshotHandle<B> {
+    output: OutputHandle<Vec<Arc<B>>>,
+    snapshots: Mutex<Vec<Vec<Arc<B>>>>,
+}
+
+impl<B> SnapshotHandle<B>
+where
+    B: Send + Sync + 'static,
+{
+    /// Pick up snapshots published during the last clock cycle, if any.
+    fn refresh(&self) -> MutexGuard<'_, Vec<Vec<Arc<B>>>> {
+        let mut snapshots = self.snapshots.lock().unwrap();
+
+        let new_snapshots = self.output.take_from_all();
+        if !new_snapshots.is_empty() {
+            *snapshots = new_snapshots;
+        }
+
+        snapshots
+    }
+}
+
+impl<B> SnapshotSource for SnapshotHandle<B>
+where
+    B: Batch<Time = ()> + Send + Sync,
+    B::Key: Serialize,
+    B::Val: Serialize,
+    B::R: Serialize,
+{
+    fn snapshot(&self) -> Box<dyn TraceSnapshot> {
+        Box::new(self.refresh().concat())
+    }
+}
+
+impl<B> TraceSnapshot for Vec<Arc<B>>
+where
+    B: Batch<Time = ()> + Send + Sync,
+    B::Key: Serialize,
+    B::Val: Serialize,
+    B::R: Serialize,
+{
+    fn write(&self, format: ExportFormat, writer: &mut dyn Write) -> io::Result<usize> {
+        let mut tuples = Vec::with_capacity(self.iter().map(|batch| batch.len()).sum());
+        for batch in self.iter() {
+            let mut cursor = batch.cursor();
+            while cursor.key_valid() {
+                while cursor.val_valid() {
+                    tuples.push((
+                        (cursor.key().clone(), cursor.val().clone()),
+                        cursor.weight(),
+                    ));
+                    cursor.step_val();
+                }
+                cursor.step_key();
+            }
+        }
+        consolidate(&mut tuples);
+
+        match format {
+            #[cfg(feature = "with-csv")]
+            ExportFormat::Csv => {
+                let mut csv_writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(writer);
+                for ((key, val), weight) in tuples.iter() {
+                    csv_writer
+                        .serialize((key, val, weight))
+                        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+                }
+                csv_writer.flush()?;
+            }
+            ExportFormat::Bincode => {
+                bincode::serde::encode_into_std_write(&tuples, writer, bincode::config::standard())
+                    .map_err(|error| io::Error::new(io::ErrorKind::Other, error.to_string()))?;
+            }
+        }
+
+        Ok(tuples.len())
+    }
+}
+
+/// A file written by an export, as recorded in its [`ExportManifest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedFile {
+    name: String,
+    file: String,
+    format: ExportFormat,
+    tuples: usize,
+    checksum: u64,
+}
+
+impl ExportedFile {
+    /// Parses a `file <name> <file> <format> <tuples> <checksum>` manifest
+    /// line.
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(' ').collect();
+        match fields.as_slice() {
+            ["file", name, file, format, tuples, checksum] => Some(Self {
+                name: name.to_string(),
+                file: file.to_string(),
+                format: format.parse().ok()?,
+                tuples: tuples.parse().ok()?,
+                checksum: u64::from_str_radix(checksum, 16).ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Name of the exported collection.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name of the file within the export directory.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Format of the file.
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    /// Number of `(key, value, weight)` tuples in the file.
+    pub fn tuples(&self) -> usize {
+        self.tuples
+    }
+
+    /// 64-bit xxh3 hash of the contents of the file.
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+}
+
+/// Description of a completed export, written to the [`MANIFEST_FILE`] in the
+/// export directory once all data files have been written.
+///
+/// The manifest is a text file whose first line is `step <n>`, followed by
+/// one `file <name> <file> <format> <tuples> <checksum>` line per exported
+/// collection, where the checksum is written in hexadecimal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportManifest {
+    step: u64,
+    files: Vec<ExportedFile>,
+}
+
+impl ExportManifest {
+    /// Number of clock cycles evaluated by the circuit when the snapshot was
+    /// taken.
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// Files written by the export, in the order of the export targets.
+    pub fn files(&self) -> &[ExportedFile] {
+        &self.files
+    }
+
+    /// Reads the manifest of an export from `dir`.
+    pub fn read<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid export manifest line '{line}'"),
+            )
+        };
+
+        let mut lines = BufReader::new(File::open(dir.as_ref().join(MANIFEST_FILE))?).lines();
+
+        let line = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+        let step = line
+            .strip_prefix("step ")
+            .and_then(|step| step.parse().ok())
+            .ok_or_else(|| invalid(&line))?;
+
+        let mut files = Vec::new();
+        for line in lines {
+            let line = line?;
+            files.push(ExportedFile::parse(&line).ok_or_else(|| invalid(&line))?);
+        }
+
+        Ok(Self { step, files })
+    }
+
+    fn write(&self, dir: &Path) -> io::Result<()> {
+        // Write the manifest under a temporary name and rename it, so that a
+        // manifest is only ever observed once the export is complete.
+        let tmp_path = dir.join(format!("{MANIFEST_FILE}.tmp"));
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            writeln!(writer, "step {}", self.step)?;
+            for file in self.files.iter() {
+                writeln!(
+                    writer,
+                    "file {} {} {} {} {:016x}",
+                    file.name, file.file, file.format, file.tuples, file.checksum
+                )?;
+            }
+            writer
+                .into_inner()
+                .map_err(|error| error.into_error())?
+                .sync_all()?;
+        }
+
+        fs::rename(tmp_path, dir.join(MANIFEST_FILE))
+    }
+}
+
+/// An export running in the background, started by
+/// [`DBSPHandle::export_snapshot`](`crate::DBSPHandle::export_snapshot`).
+pub struct SnapshotExport {
+    dir: PathBuf,
+    thread: JoinHandle<io::Result<ExportManifest>>,
+}
+
+impl SnapshotExport {
+    /// Directory the snapshot is exported to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns `true` if the export has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Blocks until the export completes and returns its manifest.
+    pub fn wait(self) -> Result<ExportManifest, DBSPError> {
+        match self.thread.join() {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(DBSPError::Custom(format!(
+                "snapshot export to '{}' panicked",
+                self.dir.display()
+            ))),
+        }
+    }
+}
+
+/// Snapshots `targets` and spawns a thread that writes them to `dir`.
+pub(crate) fn export_snapshot(
+    targets: &[ExportTarget],
+    dir: &Path,
+    step: u64,
+) -> Result<SnapshotExport, DBSPError> {
+    let mut names = BTreeSet::new();
+    for target in targets.iter() {
+        let valid = !target.name.is_empty()
+            && target
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(DBSPError::Custom(format!(
+                "invalid export target name '{}'",
+                target.name
+            )));
+        }
+        if !names.insert(target.name.as_str()) {
+            return Err(DBSPError::Custom(format!(
+                "duplicate export target name '{}'",
+                target.name
+            )));
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+
+    // This is the only part of the export that must happen between clock
+    // cycles; everything else runs in the background.
+    let snapshots: Vec<_> = targets
+        .iter()
+        .map(|target| {
+            (
+                target.name.clone(),
+                target.file_name(),
+                target.format,
+                target.source.snapshot(),
+            )
+        })
+        .collect();
+
+    let dir = dir.to_path_buf();
+    let thread_dir = dir.clone();
+    let thread = thread::Builder::new()
+        .name("dbsp-export".to_string())
+        .spawn(move || {
+            let mut files = Vec::with_capacity(snapshots.len());
+            for (name, file, format, snapshot) in snapshots {
+                let mut writer =
+                    ChecksumWriter::new(BufWriter::new(File::create(thread_dir.join(&file))?));
+                let tuples = snapshot.write(format, &mut writer)?;
+                let checksum = writer.finish()?;
+
+                files.push(ExportedFile {
+                    name,
+                    file,
+                    format,
+                    tuples,
+                    checksum,
+                });
+            }
+
+            let manifest = ExportManifest { step, files };
+            manifest.write(&thread_dir)?;
+            Ok(manifest)
+        })?;
+
+    Ok(SnapshotExport { dir, thread })
+}
+
+/// A writer that computes the checksum of the data written to a file.
+struct ChecksumWriter {
+    writer: BufWriter<File>,
+    hasher: Xxh3,
+}
+
+impl ChecksumWriter {
+    fn new(writer: BufWriter<File>) -> Self {
+        Self {
+            writer,
+            hasher: Xxh3::new(),
+        }
+    }
+
+    /// Flushes the file to disk and returns the checksum of its contents.
+    fn finish(self) -> io::Result<u64> {
+        self.writer
+            .into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_all()?;
+        Ok(self.hasher.digest())
+    }
+}
+
+impl Write for ChecksumWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ExportFormat, ExportManifest, MANIFEST_FILE};
+    use crate::{operator::FilterMap, trace::Cursor, OrdIndexedZSet, OrdZSet, Runtime};
+    use std::{env, fs};
+    use xxhash_rust::xxh3::xxh3_64;
+
+    /// Reads the tuples written to a bincode export file.
+    fn read_tuples<K, V>(path: &std::path::Path) -> Vec<((K, V), isize)>
+    where
+        K: serde::de::DeserializeOwned,
+        V: serde::de::DeserializeOwned,
+    {
+        let bytes = fs::read(path).unwrap();
+        bincode::serde::decode_from_slice(&bytes, bincode::config::standard())
+            .unwrap()
+            .0
+    }
+
+    fn zset_tuples<K: Clone + Ord>(zset: &OrdZSet<K, isize>) -> Vec<((K, ()), isize)> {
+        let mut tuples = Vec::new();
+        let mut cursor = zset.cursor();
+        while cursor.key_valid() {
+            tuples.push(((cursor.key().clone(), ()), cursor.weight()));
+            cursor.step_key();
+        }
+        tuples
+    }
+
+    fn indexed_zset_tuples<K, V>(zset: &OrdIndexedZSet<K, V, isize>) -> Vec<((K, V), isize)>
+    where
+        K: Clone + Ord,
+        V: Clone + Ord,
+    {
+        let mut tuples = Vec::new();
+        let mut cursor = zset.cursor();
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                tuples.push((
+                    (cursor.key().clone(), cursor.val().clone()),
+                    cursor.weight(),
+                ));
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+        tuples
+    }
+
+    #[test]
+    fn export_two_views() {
+        let dir = env::temp_dir().join(format!("dbsp-export-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let (mut dbsp, (mut input, targets, outputs)) = Runtime::init_circuit(4, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, String, isize>();
+            let keys = input.map(|(k, _v)| *k);
+
+            let targets = vec![
+                input.export_target("pairs", ExportFormat::Bincode),
+                keys.export_target("keys", ExportFormat::Bincode),
+            ];
+            let outputs = (input.integrate().output(), keys.integrate().output());
+
+            (input_handle, targets, outputs)
+        })
+        .unwrap();
+
+        for step in 0..3u64 {
+            let mut updates = (0..100)
+                .map(|i| ((i + step * 50) % 120, (format!("v{}", i % 7), 1)))
+                .collect();
+            input.append(&mut updates);
+            if step == 2 {
+                input.push(5, ("v5".to_string(), -1));
+            }
+            dbsp.step().unwrap();
+        }
+
+        let expected_pairs = indexed_zset_tuples(&outputs.0.consolidate());
+        let expected_keys = zset_tuples(&outputs.1.consolidate());
+
+        let export = dbsp.export_snapshot(&targets, &dir).unwrap();
+
+        // The export runs in the background and is not affected by subsequent
+        // steps.
+        let mut updates = vec![(1000, ("new".to_string(), 1))];
+        input.append(&mut updates);
+        dbsp.step().unwrap();
+
+        let manifest = export.wait().unwrap();
+        assert_eq!(manifest.step(), 3);
+        assert_eq!(manifest, ExportManifest::read(&dir).unwrap());
+        assert!(dir.join(MANIFEST_FILE).exists());
+
+        let files = manifest.files();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].name(), "pairs");
+        assert_eq!(files[0].file(), "pairs.bin");
+        assert_eq!(files[1].name(), "keys");
+        assert_eq!(files[1].format(), ExportFormat::Bincode);
+
+        for file in files {
+            let bytes = fs::read(dir.join(file.file())).unwrap();
+            assert_eq!(file.checksum(), xxh3_64(&bytes));
+        }
+
+        let pairs = read_tuples::<u64, String>(&dir.join("pairs.bin"));
+        assert_eq!(files[0].tuples(), pairs.len());
+        assert_eq!(pairs, expected_pairs);
+
+        let keys = read_tuples::<u64, ()>(&dir.join("keys.bin"));
+        assert_eq!(files[1].tuples(), keys.len());
+        assert_eq!(keys, expected_keys);
+
+        // Duplicate target names are rejected.
+        assert!(dbsp
+            .export_snapshot(&[targets[0].clone(), targets[0].clone()], &dir)
+            .is_err());
+
+        dbsp.kill().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
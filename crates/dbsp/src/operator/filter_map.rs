@@ -120,7 +120,11 @@ pub trait FilterMap<C> {
     /// records into `OrdZSet` batches.
     ///
     /// The output of `func` can be any type that implements `trait
-    /// IntoIterator`, e.g., `Option<>` or `Vec<>`.
+    /// IntoIterator`, e.g., `Option<>` or `Vec<>`.  Each output record
+    /// inherits the weight of the input record it was produced from.  Records
+    /// produced multiple times, from the same or different input records, are
+    /// consolidated by adding up their weights, so `func` can return records in
+    /// any order.
     fn flat_map<F, I>(&self, func: F) -> Stream<C, OrdZSet<I::Item, Self::R>>
     where
         F: FnMut(Self::ItemRef<'_>) -> I + 'static,
@@ -140,6 +144,10 @@ pub trait FilterMap<C> {
     /// Behaves as [`Self::flat_map`] followed by
     /// [`index`](`crate::Stream::index`), but is more efficient.  Assembles
     /// output records into `OrdIndexedZSet` batches.
+    ///
+    /// As with [`Self::flat_map`], each output `(key, value)` pair inherits
+    /// the weight of its input record, and `func` can return pairs in any
+    /// order.
     fn flat_map_index<F, K, V, I>(&self, func: F) -> Stream<C, OrdIndexedZSet<K, V, Self::R>>
     where
         F: Fn(Self::ItemRef<'_>) -> I + 'static,
@@ -729,8 +737,8 @@ mod test {
     use crate::{
         indexed_zset,
         operator::{FilterMap, Generator},
-        trace::ord::OrdZSet,
-        zset, Circuit, RootCircuit,
+        trace::{ord::OrdZSet, Batch},
+        zset, Circuit, OrdIndexedZSet, RootCircuit,
     };
     use std::vec;
 
@@ -887,4 +895,55 @@ mod test {
             circuit.step().unwrap();
         }
     }
+
+    #[test]
+    fn flat_map_weights_test() {
+        let circuit = RootCircuit::build(move |circuit| {
+            let mut input: vec::IntoIter<OrdZSet<usize, isize>> =
+                vec![zset! { 0 => 1, 3 => -2, 1000 => 1 }, zset! {}].into_iter();
+
+            // Empty iterator for `0`, large fan-out for `1000`.  Outputs of `3`
+            // and `1000` overlap and their weights add up.
+            let mut fan_out_output = vec![
+                OrdZSet::from_tuples(
+                    (),
+                    (0..1000).map(|n| (n, if n < 3 { -1 } else { 1 })).collect(),
+                ),
+                zset! {},
+            ]
+            .into_iter();
+            // The same record is produced multiple times by each input record.
+            let mut repeated_output = vec![zset! { 0 => 1000, 1 => -6 }, zset! {}].into_iter();
+            let mut i_fan_out_output = vec![
+                OrdIndexedZSet::from_tuples(
+                    (),
+                    (0..3)
+                        .map(|n| ((n, 1), -2))
+                        .chain((0..1000).map(|n| ((n, 0), 1)))
+                        .collect(),
+                ),
+                indexed_zset! {},
+            ]
+            .into_iter();
+
+            let input = circuit.add_source(Generator::new(move || input.next().unwrap()));
+            let input_indexed = input.map_index(|&n| (n % 2, n));
+
+            input
+                .flat_map(|&n| 0..n)
+                .inspect(move |n| assert_eq!(*n, fan_out_output.next().unwrap()));
+            input
+                .flat_map(|&n| vec![n % 2; n])
+                .inspect(move |n| assert_eq!(*n, repeated_output.next().unwrap()));
+            input_indexed
+                .flat_map_index(|(&k, &v)| (0..v).rev().map(move |n| (n, k)))
+                .inspect(move |n| assert_eq!(*n, i_fan_out_output.next().unwrap()));
+        })
+        .unwrap()
+        .0;
+
+        for _ in 0..2 {
+            circuit.step().unwrap();
+        }
+    }
 }
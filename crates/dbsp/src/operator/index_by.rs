@@ -0,0 +1,491 @@
+//! Secondary indexes over the integral of a stream.
+
+use crate::{
+    algebra::{HasZero, IndexedZSet, MulByRef, ZRingValue, ZSet},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope,
+    },
+    operator::Map,
+    trace::{Batch, BatchReader, Cursor, Spine},
+    Circuit, DBData, OrdIndexedZSet, Stream,
+};
+use size_of::SizeOf;
+use std::{borrow::Cow, marker::PhantomData, rc::Rc};
+
+/// Function that extracts the secondary key from a `(key, value)` pair.
+type Extractor<K, V, SK> = Rc<dyn Fn(&K, &V) -> SK>;
+
+/// A secondary index over the integral of a stream, created by
+/// [`Stream::index_by`].
+///
+/// The index consists of two traces: the base trace, which stores the
+/// integral of the indexed stream keyed by its primary key, and the index
+/// trace, which only stores `(secondary_key, primary_key)` pairs.  Values are
+/// resolved through the base trace at read time, so indexing a collection
+/// by an additional key does not store another copy of its values.
+pub struct SecondaryIndex<C, SK, B>
+where
+    B: BatchReader,
+{
+    extractor: Extractor<B::Key, B::Val, SK>,
+    base: Stream<C, Spine<B>>,
+    index: Stream<C, Spine<OrdIndexedZSet<SK, B::Key, B::R>>>,
+}
+
+impl<C, B> Stream<C, B>
+where
+    C: Circuit,
+    B: IndexedZSet + Send,
+    B::R: ZRingValue,
+{
+    /// Index the integral of `self` by a secondary key.
+    ///
+    /// `extractor` computes the secondary key of each `(key, value)` pair in
+    /// the stream.  The returned [`SecondaryIndex`] shares the integral of
+    /// `self` with other operators that integrate the same stream, such as
+    /// [`lookup`](`Self::lookup`), and maintains an additional trace of
+    /// `(secondary_key, primary_key)` pairs.  Both traces are updated from
+    /// the same input batch, so they are consistent with each other at
+    /// every clock cycle.
+    pub fn index_by<SK, F>(&self, extractor: F) -> SecondaryIndex<C, SK, B>
+    where
+        SK: DBData,
+        F: Fn(&B::Key, &B::Val) -> SK + 'static,
+        Spine<B>: SizeOf,
+    {
+        self.circuit().region("index_by", || {
+            let extractor: Extractor<B::Key, B::Val, SK> = Rc::new(extractor);
+            let stream = self.shard();
+            let base = stream.integrate_trace();
+
+            let pairs: Stream<C, OrdIndexedZSet<SK, B::Key, B::R>> =
+                self.circuit().add_unary_operator(
+                    Map::new({
+                        let extractor = extractor.clone();
+                        move |(key, val): (&B::Key, &B::Val)| (extractor(key, val), key.clone())
+                    }),
+                    &stream,
+                );
+            let index = pairs.shard().integrate_trace();
+
+            SecondaryIndex {
+                extractor,
+                base,
+                index,
+            }
+        })
+    }
+}
+
+impl<C, SK, B> SecondaryIndex<C, SK, B>
+where
+    C: Circuit,
+    SK: DBData,
+    B: IndexedZSet + Send,
+    B::R: ZRingValue,
+{
+    /// The integral of the indexed stream, keyed by primary key.
+    pub fn base(&self) -> &Stream<C, Spine<B>> {
+        &self.base
+    }
+
+    /// The integral of `(secondary_key, primary_key)` pairs.
+    pub fn index(&self) -> &Stream<C, Spine<OrdIndexedZSet<SK, B::Key, B::R>>> {
+        &self.index
+    }
+
+    /// Look up secondary keys in the index.
+    ///
+    /// `requests` is a stream of Z-sets of secondary keys to look up.  At each
+    /// clock cycle, the operator outputs all `(key, value)` pairs in the
+    /// integral of the indexed stream, including the current input batch,
+    /// whose secondary key is in `requests`, indexed by the secondary key.
+    /// The weight of each output tuple is the product of its weight in the
+    /// integral and the weight of the request.
+    ///
+    /// Like [`Stream::lookup`], the operator is not incremental with respect
+    /// to `requests`: the output at each clock cycle contains responses to
+    /// the requests received during this clock cycle only.
+    ///
+    /// The lookup proceeds in two stages: secondary keys are first resolved
+    /// to primary keys by seeking a cursor over the index trace, and primary
+    /// keys are then resolved to values by seeking a cursor over the base
+    /// trace.  The cost of the lookup is proportional to the size of the
+    /// output rather than the size of the integral.
+    #[allow(clippy::type_complexity)]
+    pub fn lookup<Z>(
+        &self,
+        requests: &Stream<C, Z>,
+    ) -> Stream<C, OrdIndexedZSet<SK, (B::Key, B::Val), B::R>>
+    where
+        Z: ZSet<Key = SK, R = B::R> + Send,
+    {
+        let circuit = self.base.circuit();
+
+        circuit.region("index_lookup", || {
+            let primary_requests: Stream<C, OrdIndexedZSet<B::Key, SK, B::R>> = circuit
+                .add_binary_operator(ResolveSecondaryKeys::new(), &requests.shard(), &self.index);
+
+            circuit.add_binary_operator(
+                ResolvePrimaryKeys::new(self.extractor.clone()),
+                &primary_requests.shard(),
+                &self.base,
+            )
+        })
+    }
+}
+
+/// Resolves secondary keys from the first input to primary keys using the
+/// index trace in the second input.
+struct ResolveSecondaryKeys<Z, T> {
+    _types: PhantomData<(Z, T)>,
+}
+
+impl<Z, T> ResolveSecondaryKeys<Z, T> {
+    fn new() -> Self {
+        Self {
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<Z, T> Operator for ResolveSecondaryKeys<Z, T>
+where
+    Z: 'static,
+    T: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("ResolveSecondaryKeys")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<Z, T> BinaryOperator<Z, T, OrdIndexedZSet<T::Val, Z::Key, Z::R>> for ResolveSecondaryKeys<Z, T>
+where
+    Z: ZSet,
+    T: BatchReader<Key = Z::Key, Time = (), R = Z::R> + 'static,
+    T::Val: DBData,
+{
+    fn eval(&mut self, requests: &Z, index: &T) -> OrdIndexedZSet<T::Val, Z::Key, Z::R> {
+        let mut tuples = Vec::with_capacity(requests.key_count());
+
+        let mut request_cursor = requests.cursor();
+        let mut index_cursor = index.cursor();
+
+        // Requests are sorted by key, so we only need to move the index cursor
+        // forward.
+        while request_cursor.key_valid() {
+            let weight = request_cursor.weight();
+            if !weight.is_zero() {
+                let key = request_cursor.key();
+
+                index_cursor.seek_key(key);
+                if index_cursor.key_valid() && index_cursor.key() == key {
+                    while index_cursor.val_valid() {
+                        // Traces may contain values whose weights add up to zero.
+                        if !index_cursor.weight().is_zero() {
+                            tuples
+                                .push(((index_cursor.val().clone(), key.clone()), weight.clone()));
+                        }
+                        index_cursor.step_val();
+                    }
+                }
+            }
+
+            request_cursor.step_key();
+        }
+
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+}
+
+/// Resolves `(primary_key, secondary_key)` pairs from the first input to
+/// values using the base trace in the second input.
+struct ResolvePrimaryKeys<K, V, SK, T> {
+    extractor: Extractor<K, V, SK>,
+    _types: PhantomData<T>,
+}
+
+impl<K, V, SK, T> ResolvePrimaryKeys<K, V, SK, T> {
+    fn new(extractor: Extractor<K, V, SK>) -> Self {
+        Self {
+            extractor,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<K, V, SK, T> Operator for ResolvePrimaryKeys<K, V, SK, T>
+where
+    K: 'static,
+    V: 'static,
+    SK: 'static,
+    T: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("ResolvePrimaryKeys")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<K, V, SK, R, T> BinaryOperator<OrdIndexedZSet<K, SK, R>, T, OrdIndexedZSet<SK, (K, V), R>>
+    for ResolvePrimaryKeys<K, V, SK, T>
+where
+    K: DBData,
+    V: DBData,
+    SK: DBData,
+    R: ZRingValue,
+    T: BatchReader<Key = K, Val = V, Time = (), R = R> + 'static,
+{
+    fn eval(
+        &mut self,
+        requests: &OrdIndexedZSet<K, SK, R>,
+        base: &T,
+    ) -> OrdIndexedZSet<SK, (K, V), R> {
+        let mut tuples = Vec::with_capacity(requests.len());
+
+        let mut request_cursor = requests.cursor();
+        let mut base_cursor = base.cursor();
+
+        while request_cursor.key_valid() {
+            let key = request_cursor.key();
+
+            base_cursor.seek_key(key);
+            if base_cursor.key_valid() && base_cursor.key() == key {
+                while base_cursor.val_valid() {
+                    let val_weight = base_cursor.weight();
+                    if !val_weight.is_zero() {
+                        let val = base_cursor.val();
+                        let secondary_key = (self.extractor)(key, val);
+
+                        // Only return values indexed by one of the requested
+                        // secondary keys.
+                        request_cursor.rewind_vals();
+                        request_cursor.seek_val(&secondary_key);
+                        if request_cursor.val_valid() && request_cursor.val() == &secondary_key {
+                            tuples.push((
+                                (secondary_key, (key.clone(), val.clone())),
+                                val_weight.mul_by_ref(&request_cursor.weight()),
+                            ));
+                        }
+                    }
+                    base_cursor.step_val();
+                }
+            }
+
+            request_cursor.step_key();
+        }
+
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        operator::FilterMap,
+        trace::{Batch, BatchReader, Cursor, Spine},
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, OrdZSet, OutputHandle, RootCircuit, Runtime,
+        Stream,
+    };
+    use size_of::SizeOf;
+    use std::{cell::Cell, rc::Rc};
+
+    // Records keyed by id, with a timestamp and a payload.
+    type Record = (u64, String);
+    type Output = OrdIndexedZSet<u64, (u64, Record), isize>;
+
+    fn timestamp(_id: &u64, record: &Record) -> u64 {
+        record.0
+    }
+
+    // Look up `requests` by scanning a trace that stores a copy of the data
+    // indexed by timestamp.
+    fn baseline_lookup(
+        requests: &OrdZSet<u64, isize>,
+        copy: &Spine<OrdIndexedZSet<u64, (u64, Record), isize>>,
+    ) -> Output {
+        let mut tuples = Vec::new();
+        let mut request_cursor = requests.cursor();
+        let mut cursor = copy.cursor();
+
+        while request_cursor.key_valid() {
+            let key = request_cursor.key();
+            cursor.seek_key(key);
+            if cursor.key_valid() && cursor.key() == key {
+                while cursor.val_valid() {
+                    if cursor.weight() != 0 {
+                        tuples.push((
+                            (*key, cursor.val().clone()),
+                            cursor.weight() * request_cursor.weight(),
+                        ));
+                    }
+                    cursor.step_val();
+                }
+            }
+            request_cursor.step_key();
+        }
+
+        Output::from_tuples((), tuples)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn index_by_circuit(
+        workers: usize,
+    ) -> (
+        DBSPHandle,
+        (
+            CollectionHandle<u64, (Record, isize)>,
+            CollectionHandle<u64, isize>,
+            OutputHandle<Output>,
+            OutputHandle<Output>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, Record, isize>();
+            let (requests, requests_handle) = circuit.add_input_zset::<u64, isize>();
+
+            let index = input.index_by(timestamp);
+            let output = index.lookup(&requests).output();
+
+            let copy = input
+                .map_index(|(id, record)| (timestamp(id, record), (*id, record.clone())))
+                .shard()
+                .integrate_trace();
+            let expected = requests.shard().apply2(&copy, baseline_lookup).output();
+
+            (input_handle, requests_handle, output, expected)
+        })
+        .unwrap()
+    }
+
+    fn record(ts: u64) -> Record {
+        (ts, format!("payload-{ts}"))
+    }
+
+    fn test_index_by(workers: usize) {
+        let (mut dbsp, (mut input, mut requests, output, expected)) = index_by_circuit(workers);
+
+        let steps: Vec<(Vec<(u64, (Record, isize))>, Vec<(u64, isize)>)> = vec![
+            // Lookups observe the current input batch.
+            (
+                vec![
+                    (1, (record(100), 1)),
+                    (2, (record(100), 1)),
+                    (3, (record(200), 1)),
+                    (4, (record(300), 2)),
+                ],
+                vec![(100, 1), (300, 1), (400, 1)],
+            ),
+            // Moving a record to a different secondary key updates both views.
+            (
+                vec![(1, (record(100), -1)), (1, (record(300), 1))],
+                vec![(100, 1), (300, 2)],
+            ),
+            // Deleted records disappear from the index.
+            (
+                vec![(4, (record(300), -2)), (5, (record(200), 1))],
+                vec![(200, 1), (300, 1)],
+            ),
+            // No requests, no output.
+            (vec![(6, (record(100), 1))], vec![]),
+            (vec![], vec![(100, 1), (200, -1)]),
+        ];
+
+        for (mut input_tuples, mut request_tuples) in steps {
+            input.append(&mut input_tuples);
+            requests.append(&mut request_tuples);
+            dbsp.step().unwrap();
+
+            let expected = expected.consolidate();
+            assert_eq!(output.consolidate(), expected);
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn index_by_test() {
+        test_index_by(1);
+        test_index_by(4);
+    }
+
+    #[test]
+    fn index_by_lookup_output() {
+        let (circuit, (input, requests, output)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, Record, isize>();
+            let (requests, requests_handle) = circuit.add_input_zset::<u64, isize>();
+
+            let output = input.index_by(timestamp).lookup(&requests).output();
+            (input_handle, requests_handle, output)
+        })
+        .unwrap();
+
+        input.push(1, (record(100), 1));
+        input.push(2, (record(100), 1));
+        input.push(3, (record(200), 1));
+        requests.push(100, 1);
+        circuit.step().unwrap();
+
+        let expected = Output::from_tuples(
+            (),
+            vec![((100, (1, record(100))), 1), ((100, (2, record(100))), 1)],
+        );
+        assert_eq!(output.consolidate(), expected);
+    }
+
+    // The index stores less data than a second copy of the collection.
+    #[test]
+    fn index_by_memory() {
+        fn trace_size<T>(stream: &Stream<RootCircuit, T>) -> Rc<Cell<usize>>
+        where
+            T: SizeOf + Clone + 'static,
+        {
+            let size = Rc::new(Cell::new(0));
+            let size_clone = size.clone();
+            stream.inspect(move |trace| size_clone.set(trace.size_of().total_bytes()));
+            size
+        }
+
+        let (circuit, (input, sizes)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, Record, isize>();
+
+            let index = input.index_by(timestamp);
+            let copy = input
+                .map_index(|(id, record)| (timestamp(id, record), (*id, record.clone())))
+                .integrate_trace();
+
+            let sizes = (
+                trace_size(index.base()),
+                trace_size(index.index()),
+                trace_size(&copy),
+            );
+            (input_handle, sizes)
+        })
+        .unwrap();
+
+        for step in 0..10 {
+            for id in step * 1000..(step + 1) * 1000 {
+                input.push(id, ((id % 100, "x".repeat(100)), 1));
+            }
+            circuit.step().unwrap();
+        }
+
+        let (base, index, copy) = (sizes.0.get(), sizes.1.get(), sizes.2.get());
+        assert!(base > 0 && index > 0);
+
+        // Compared to storing two copies of the data, the index only stores
+        // `(timestamp, id)` pairs instead of the second copy.
+        assert!(
+            2 * index < copy,
+            "base: {base}, index: {index}, copy: {copy}"
+        );
+    }
+}
@@ -1,12 +1,12 @@
 use crate::{
     algebra::ZRingValue,
     circuit::{
+        metrics::{MetricLabels, INPUT_TUPLES},
         operator_traits::{Operator, SourceOperator},
         LocalStoreMarker, RootCircuit, Scope,
     },
-    default_hash,
     trace::Batch,
-    Circuit, DBData, DBWeight, OrdIndexedZSet, OrdZSet, Runtime, Stream,
+    Circuit, DBData, DBWeight, OrdIndexedZSet, OrdZSet, Runtime, ShardHasher, Stream,
 };
 use std::{
     borrow::Cow,
@@ -80,7 +80,10 @@ impl RootCircuit {
         let (input, input_handle) = Input::new(|tuples| OrdZSet::from_keys((), tuples));
         let stream = self.add_source(input);
 
-        let zset_handle = <CollectionHandle<K, R>>::new(input_handle);
+        let zset_handle = <CollectionHandle<K, R>>::new(
+            input_handle,
+            MetricLabels::stream("Input", stream.origin_node_id()),
+        );
 
         (stream, zset_handle)
     }
@@ -120,7 +123,10 @@ impl RootCircuit {
         });
         let stream = self.add_source(input);
 
-        let zset_handle = <CollectionHandle<K, (V, R)>>::new(input_handle);
+        let zset_handle = <CollectionHandle<K, (V, R)>>::new(
+            input_handle,
+            MetricLabels::stream("Input", stream.origin_node_id()),
+        );
 
         (stream, zset_handle)
     }
@@ -235,7 +241,10 @@ impl RootCircuit {
         self.region("input_set", || {
             let (input, input_handle) = Input::new(|tuples: Vec<(K, bool)>| tuples);
             let input_stream = self.add_source(input);
-            let upsert_handle = <UpsertHandle<K, bool>>::new(input_handle);
+            let upsert_handle = <UpsertHandle<K, bool>>::new(
+                input_handle,
+                MetricLabels::stream("Input", input_stream.origin_node_id()),
+            );
 
             let upsert =
                 self.add_upsert(input_stream, |insert| if insert { Some(()) } else { None });
@@ -305,6 +314,11 @@ impl RootCircuit {
     /// Note that upsert commands cannot fail.  Duplicate inserts and deletes
     /// are simply ignored.
     ///
+    /// The operator keeps track of the current value associated with each key,
+    /// so the client does not need to remember previous values in order to
+    /// retract them.  When multiple commands for the same key are buffered
+    /// during the same clock cycle, the last one wins.
+    ///
     /// Internally, this operator maintains the contents of the map
     /// partitioned across all worker threads based on the hash of the
     /// key.  Upsert/delete commands are routed to the worker in charge of
@@ -319,7 +333,10 @@ impl RootCircuit {
         self.region("input_map", || {
             let (input, input_handle) = Input::new(|tuples: Vec<(K, Option<V>)>| tuples);
             let input_stream = self.add_source(input);
-            let zset_handle = <UpsertHandle<K, Option<V>>>::new(input_handle);
+            let zset_handle = <UpsertHandle<K, Option<V>>>::new(
+                input_handle,
+                MetricLabels::stream("Input", input_stream.origin_node_id()),
+            );
 
             let upsert = self.add_upsert(input_stream, |val| val);
 
@@ -446,7 +463,7 @@ where
         take(&mut *self.value.lock().unwrap())
     }
 
-    fn update<F>(&self, f: F)
+    pub(super) fn update<F>(&self, f: F)
     where
         F: FnOnce(&mut T),
     {
@@ -456,6 +473,13 @@ where
     pub(super) fn set(&self, v: T) {
         *self.value.lock().unwrap() = v;
     }
+
+    pub(super) fn map<F, O>(&self, f: F) -> O
+    where
+        F: FnOnce(&T) -> O,
+    {
+        f(&*self.value.lock().unwrap())
+    }
 }
 
 struct InputHandleInternal<T> {
@@ -596,6 +620,7 @@ pub struct CollectionHandle<K, V> {
     // of the key; however this is more efficient than doing it here, as
     // the work will be evenly split across workers.
     next_worker: AtomicUsize,
+    labels: MetricLabels,
 }
 
 impl<K, V> Clone for CollectionHandle<K, V>
@@ -605,7 +630,7 @@ where
 {
     fn clone(&self) -> Self {
         // Don't clone buffers.
-        Self::new(self.input_handle.clone())
+        Self::new(self.input_handle.clone(), self.labels.clone())
     }
 }
 
@@ -614,11 +639,12 @@ where
     K: DBData,
     V: DBData,
 {
-    fn new(input_handle: InputHandle<Vec<(K, V)>>) -> Self {
+    fn new(input_handle: InputHandle<Vec<(K, V)>>, labels: MetricLabels) -> Self {
         Self {
             buffers: vec![Vec::new(); input_handle.0.mailbox.len()],
             input_handle,
             next_worker: AtomicUsize::new(0),
+            labels,
         }
     }
 
@@ -632,10 +658,12 @@ where
         let num_partitions = self.num_partitions();
 
         if num_partitions > 1 {
-            let next_worker = self.next_worker.fetch_add(1, Ordering::AcqRel);
+            let next_worker = self.next_worker.fetch_add(1, Ordering::AcqRel) % num_partitions;
+            metric_counter!(INPUT_TUPLES, 1, self.labels.for_worker(next_worker));
             self.input_handle
-                .update_for_worker(next_worker % num_partitions, |tuples| tuples.push((k, v)));
+                .update_for_worker(next_worker, |tuples| tuples.push((k, v)));
         } else {
+            metric_counter!(INPUT_TUPLES, 1, self.labels.for_worker(0));
             self.input_handle
                 .update_for_worker(0, |tuples| tuples.push((k, v)));
         }
@@ -683,6 +711,11 @@ where
             self.next_worker.store(next_worker, Ordering::Release);
 
             for worker in 0..num_partitions {
+                metric_counter!(
+                    INPUT_TUPLES,
+                    self.buffers[worker].len(),
+                    self.labels.for_worker(worker)
+                );
                 self.input_handle.update_for_worker(worker, |tuples| {
                     if tuples.is_empty() {
                         *tuples = take(&mut self.buffers[worker]);
@@ -692,6 +725,7 @@ where
                 })
             }
         } else {
+            metric_counter!(INPUT_TUPLES, vals.len(), self.labels.for_worker(0));
             self.input_handle.update_for_worker(0, |tuples| {
                 if tuples.is_empty() {
                     *tuples = take(vals);
@@ -702,6 +736,18 @@ where
         }
     }
 
+    /// Push a snapshot of the input collection to the input stream.
+    ///
+    /// Buffers `vals` exactly like [`Self::append`].  The snapshot should be
+    /// consumed by a bootstrap step (see
+    /// [`CircuitHandle::bootstrap`](`crate::CircuitHandle::bootstrap`) and
+    /// [`DBSPHandle::bootstrap`](`crate::DBSPHandle::bootstrap`)), which loads
+    /// it into all integrals and traces in the circuit without delivering the
+    /// resulting outputs to output handles.
+    pub fn preload(&mut self, vals: &mut Vec<(K, V)>) {
+        self.append(vals)
+    }
+
     /// Clear all inputs buffered since the start of the last clock cycle.
     ///
     /// # Concurrency
@@ -723,9 +769,9 @@ where
     }
 }
 
-pub trait HashFunc<K>: Fn(&K) -> u32 + Send + Sync {}
+pub trait HashFunc<K>: Fn(&K) -> u64 + Send + Sync {}
 
-impl<K, F> HashFunc<K> for F where F: Fn(&K) -> u32 + Send + Sync {}
+impl<K, F> HashFunc<K> for F where F: Fn(&K) -> u64 + Send + Sync {}
 
 /// A handle used to write data to an input stream created by
 /// [`add_input_set`](`RootCircuit::add_input_set`) and
@@ -754,6 +800,7 @@ pub struct UpsertHandle<K, V> {
     // by the same worker thread and in the same order they were pushed
     // by the client.
     hash_func: Arc<dyn HashFunc<K>>,
+    labels: MetricLabels,
 }
 
 impl<K, V> Clone for UpsertHandle<K, V>
@@ -763,7 +810,11 @@ where
 {
     fn clone(&self) -> Self {
         // Don't clone buffers.
-        Self::with_hasher(self.input_handle.clone(), self.hash_func.clone())
+        Self::with_hasher(
+            self.input_handle.clone(),
+            self.hash_func.clone(),
+            self.labels.clone(),
+        )
     }
 }
 
@@ -772,24 +823,33 @@ where
     K: DBData,
     V: DBData,
 {
-    fn new(input_handle: InputHandle<Vec<(K, V)>>) -> Self
+    fn new(input_handle: InputHandle<Vec<(K, V)>>, labels: MetricLabels) -> Self
     where
         K: Hash,
     {
+        // Route keys the same way the `shard` operator does, since the output
+        // of the input operator is marked as sharded.
+        let hasher = Runtime::runtime()
+            .map(|runtime| runtime.shard_hasher())
+            .unwrap_or_default();
+
         Self::with_hasher(
             input_handle,
-            Arc::new(|k: &K| default_hash(k) as u32) as Arc<dyn HashFunc<K>>,
+            Arc::new(move |k: &K| hasher.hash_key(k)) as Arc<dyn HashFunc<K>>,
+            labels,
         )
     }
 
     fn with_hasher(
         input_handle: InputHandle<Vec<(K, V)>>,
         hash_func: Arc<dyn HashFunc<K>>,
+        labels: MetricLabels,
     ) -> Self {
         Self {
             buffers: vec![Vec::new(); input_handle.0.mailbox.len()],
             input_handle,
             hash_func,
+            labels,
         }
     }
 
@@ -803,11 +863,12 @@ where
         let num_partitions = self.num_partitions();
 
         if num_partitions > 1 {
+            let worker = ((self.hash_func)(&k) as usize) % num_partitions;
+            metric_counter!(INPUT_TUPLES, 1, self.labels.for_worker(worker));
             self.input_handle
-                .update_for_worker(((self.hash_func)(&k) as usize) % num_partitions, |tuples| {
-                    tuples.push((k, v))
-                });
+                .update_for_worker(worker, |tuples| tuples.push((k, v)));
         } else {
+            metric_counter!(INPUT_TUPLES, 1, self.labels.for_worker(0));
             self.input_handle
                 .update_for_worker(0, |tuples| tuples.push((k, v)));
         }
@@ -841,6 +902,11 @@ where
                 self.buffers[((self.hash_func)(&k) as usize) % num_partitions].push((k, v));
             }
             for worker in 0..num_partitions {
+                metric_counter!(
+                    INPUT_TUPLES,
+                    self.buffers[worker].len(),
+                    self.labels.for_worker(worker)
+                );
                 self.input_handle.update_for_worker(worker, |tuples| {
                     if tuples.is_empty() {
                         *tuples = take(&mut self.buffers[worker]);
@@ -850,6 +916,7 @@ where
                 })
             }
         } else {
+            metric_counter!(INPUT_TUPLES, vals.len(), self.labels.for_worker(0));
             self.input_handle.update_for_worker(0, |tuples| {
                 if tuples.is_empty() {
                     *tuples = take(vals);
@@ -948,11 +1015,12 @@ where
 mod test {
     use crate::{
         indexed_zset,
-        trace::{cursor::Cursor, BatchReader},
+        trace::{cursor::Cursor, Batch, BatchReader},
         zset, CollectionHandle, InputHandle, OrdIndexedZSet, OrdZSet, RootCircuit, Runtime,
         UpsertHandle,
     };
-    use std::iter::once;
+    use proptest::{collection, option, prelude::*};
+    use std::{collections::HashMap, iter::once};
 
     fn input_batches() -> Vec<OrdZSet<usize, isize>> {
         vec![
@@ -1406,4 +1474,61 @@ mod test {
     fn map_test_mt4() {
         map_test_mt(4);
     }
+
+    type MapUpdate = (u64, Option<u64>);
+
+    fn map_updates(
+        max_key: u64,
+        max_batch_size: usize,
+    ) -> impl Strategy<Value = Vec<Vec<MapUpdate>>> {
+        collection::vec(
+            collection::vec((0..max_key, option::of(0..5u64)), 0..max_batch_size),
+            0..20,
+        )
+    }
+
+    // Applies upserts to the map and compares the integral of the output
+    // of `add_input_map` with the contents of a reference `HashMap`.
+    fn map_test_reference(workers: usize, updates: Vec<Vec<MapUpdate>>) {
+        let (mut dbsp, (mut input_handle, output_handle)) =
+            Runtime::init_circuit(workers, |circuit| {
+                let (stream, handle) = circuit.add_input_map::<u64, u64, isize>();
+                (handle, stream.integrate().output())
+            })
+            .unwrap();
+
+        let mut reference = HashMap::new();
+
+        for mut batch in updates.into_iter() {
+            for (k, v) in batch.iter() {
+                match v {
+                    Some(v) => reference.insert(*k, *v),
+                    None => reference.remove(k),
+                };
+            }
+
+            input_handle.append(&mut batch);
+            dbsp.step().unwrap();
+
+            let expected = OrdIndexedZSet::from_tuples(
+                (),
+                reference.iter().map(|(k, v)| ((*k, *v), 1)).collect(),
+            );
+            assert_eq!(output_handle.consolidate(), expected);
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_map_reference_st(updates in map_updates(20, 30)) {
+            map_test_reference(1, updates);
+        }
+
+        #[test]
+        fn proptest_map_reference_mt(updates in map_updates(50, 50)) {
+            map_test_reference(4, updates);
+        }
+    }
 }
@@ -0,0 +1,324 @@
+//! Logging summaries of batches in a stream for debugging.
+
+use crate::{
+    circuit::{Circuit, Stream},
+    trace::{cursor::Cursor, Batch, BatchReader},
+};
+use std::fmt::{Debug, Write};
+use tracing::{level_filters::STATIC_MAX_LEVEL, Level};
+
+/// Options for [`Stream::inspect_batch`].
+#[derive(Clone, Debug)]
+pub struct InspectOptions {
+    /// Level at which batch summaries are logged.  Defaults to
+    /// [`Level::DEBUG`].
+    pub level: Level,
+
+    /// Log every `every_nth_step`'th batch, starting from the first one.
+    /// Must be positive.  Defaults to 1, i.e., every batch is logged.
+    pub every_nth_step: usize,
+
+    /// Maximal number of tuples included in the summary.  Defaults to 10.
+    pub max_tuples: usize,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        Self {
+            level: Level::DEBUG,
+            every_nth_step: 1,
+            max_tuples: 10,
+        }
+    }
+}
+
+// Invokes a `tracing` macro at a level only known at runtime, since `tracing`
+// macros require the level to be a constant.
+macro_rules! with_level {
+    ($level:expr, $macro:ident!()) => {
+        with_level!(@dispatch $level, $macro, ())
+    };
+    ($level:expr, $macro:ident!($($args:tt)+)) => {
+        with_level!(@dispatch $level, $macro, (, $($args)+))
+    };
+    (@dispatch $level:expr, $macro:ident, ($($args:tt)*)) => {
+        match $level {
+            Level::ERROR => tracing::$macro!(Level::ERROR $($args)*),
+            Level::WARN => tracing::$macro!(Level::WARN $($args)*),
+            Level::INFO => tracing::$macro!(Level::INFO $($args)*),
+            Level::DEBUG => tracing::$macro!(Level::DEBUG $($args)*),
+            _ => tracing::$macro!(Level::TRACE $($args)*),
+        }
+    };
+}
+
+impl<C, B> Stream<C, B>
+where
+    C: Circuit,
+    B: Batch<Time = ()>,
+    B::Item: Debug,
+{
+    /// Log a summary of each batch in the stream using [`tracing`].
+    ///
+    /// The summary of a batch includes `name`, the number of the step at
+    /// which the batch was produced (starting from 0), the number of tuples
+    /// in the batch, the range of its keys, and up to
+    /// [`max_tuples`](`InspectOptions::max_tuples`) tuples from the
+    /// batch, e.g.:
+    ///
+    /// ```text
+    /// edges: step 3: 1000 tuples, keys 0..=99, sample: [((0, 1), 1), ((0, 5), 1), ... 998 more]
+    /// ```
+    ///
+    /// Summaries are logged at the level specified in `options`.  When the
+    /// level is disabled by
+    /// [`STATIC_MAX_LEVEL`](`tracing::level_filters::STATIC_MAX_LEVEL`),
+    /// this method does not add any operators to the circuit.  When it is
+    /// disabled by the current subscriber, the operator does not compute
+    /// summaries.
+    ///
+    /// Returns a stream with the same contents as `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `options.every_nth_step` is 0.
+    pub fn inspect_batch(&self, name: &str, options: InspectOptions) -> Self {
+        assert!(
+            options.every_nth_step > 0,
+            "inspect_batch: `every_nth_step` must be positive"
+        );
+
+        if options.level > STATIC_MAX_LEVEL {
+            return self.clone();
+        }
+
+        let name = name.to_string();
+        let mut step = 0;
+
+        self.inspect(move |batch: &B| {
+            if step % options.every_nth_step == 0 && with_level!(options.level, enabled!()) {
+                let summary = batch_summary(&name, step, batch, options.max_tuples);
+                with_level!(options.level, event!("{summary}"));
+            }
+            step += 1;
+        })
+    }
+}
+
+/// Formats a summary of `batch` for [`Stream::inspect_batch`].
+fn batch_summary<B>(name: &str, step: usize, batch: &B, max_tuples: usize) -> String
+where
+    B: Batch<Time = ()>,
+    B::Item: Debug,
+{
+    let len = batch.len();
+    let mut summary = format!("{name}: step {step}: {len} tuples");
+
+    let mut cursor = batch.cursor();
+    if !cursor.key_valid() {
+        return summary;
+    }
+
+    let first_key = cursor.key().clone();
+    let last_key = cursor.last_key().cloned();
+    write!(summary, ", keys {first_key:?}..={:?}", last_key.unwrap()).unwrap();
+
+    if max_tuples == 0 {
+        return summary;
+    }
+
+    cursor.rewind_keys();
+    summary.push_str(", sample: [");
+
+    let mut count = 0;
+    'outer: while cursor.key_valid() {
+        while cursor.val_valid() {
+            if count == max_tuples {
+                break 'outer;
+            }
+            if count > 0 {
+                summary.push_str(", ");
+            }
+
+            let item = B::item_from(cursor.key().clone(), cursor.val().clone());
+            write!(summary, "{:?}", (item, cursor.weight())).unwrap();
+
+            count += 1;
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    if count < len {
+        write!(summary, ", ... {} more", len - count).unwrap();
+    }
+    summary.push(']');
+
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::InspectOptions;
+    use crate::{CollectionHandle, RootCircuit};
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+    use tracing::Level;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    // Collects log output in memory.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn take_lines(&self) -> Vec<String> {
+            let bytes = std::mem::take(&mut *self.0.lock().unwrap());
+            String::from_utf8(bytes)
+                .unwrap()
+                .lines()
+                .map(|line| line.trim().to_string())
+                .collect()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    // Runs `steps` through a circuit that inspects its input with
+    // `options`, returns lines logged at each step.
+    fn inspect_batch_test(
+        options: InspectOptions,
+        max_level: Level,
+        steps: Vec<Vec<(u64, (u64, isize))>>,
+    ) -> Vec<Vec<String>> {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_max_level(max_level)
+            .with_ansi(false)
+            .with_target(false)
+            .without_time()
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let (circuit, mut input): (_, CollectionHandle<u64, (u64, isize)>) =
+                RootCircuit::build(move |circuit| {
+                    let (stream, handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+                    stream.inspect_batch("edges", options);
+                    handle
+                })
+                .unwrap();
+
+            steps
+                .into_iter()
+                .map(|mut step| {
+                    input.append(&mut step);
+                    circuit.step().unwrap();
+                    buffer.take_lines()
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn summary_format() {
+        let output = inspect_batch_test(
+            InspectOptions {
+                max_tuples: 3,
+                ..Default::default()
+            },
+            Level::DEBUG,
+            vec![
+                vec![(1, (10, 1)), (1, (11, -1)), (5, (50, 2)), (3, (30, 1))],
+                vec![],
+                vec![(2, (20, 1)), (2, (21, 1))],
+                vec![(7, (70, 1)), (8, (80, 1)), (9, (90, 1))],
+            ],
+        );
+
+        assert_eq!(
+            output,
+            vec![
+                vec![
+                    "DEBUG edges: step 0: 4 tuples, keys 1..=5, sample: [((1, 10), 1), ((1, 11), -1), ((3, 30), 1), ... 1 more]"
+                ],
+                vec!["DEBUG edges: step 1: 0 tuples"],
+                vec!["DEBUG edges: step 2: 2 tuples, keys 2..=2, sample: [((2, 20), 1), ((2, 21), 1)]"],
+                vec!["DEBUG edges: step 3: 3 tuples, keys 7..=9, sample: [((7, 70), 1), ((8, 80), 1), ((9, 90), 1)]"],
+            ]
+        );
+    }
+
+    #[test]
+    fn sampling() {
+        let steps: Vec<_> = (0..7).map(|i| vec![(i, (i, 1))]).collect();
+
+        let output = inspect_batch_test(
+            InspectOptions {
+                level: Level::INFO,
+                every_nth_step: 3,
+                max_tuples: 0,
+            },
+            Level::INFO,
+            steps.clone(),
+        );
+
+        assert_eq!(
+            output,
+            vec![
+                vec!["INFO edges: step 0: 1 tuples, keys 0..=0"],
+                vec![],
+                vec![],
+                vec!["INFO edges: step 3: 1 tuples, keys 3..=3"],
+                vec![],
+                vec![],
+                vec!["INFO edges: step 6: 1 tuples, keys 6..=6"],
+            ]
+        );
+
+        // Levels disabled by the subscriber produce no output.
+        let output = inspect_batch_test(
+            InspectOptions {
+                level: Level::TRACE,
+                ..Default::default()
+            },
+            Level::DEBUG,
+            steps,
+        );
+        assert!(output.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    #[should_panic(expected = "`every_nth_step` must be positive")]
+    fn zero_every_nth_step() {
+        RootCircuit::build(|circuit| {
+            let (stream, _) = circuit.add_input_zset::<u64, isize>();
+            stream.inspect_batch(
+                "zset",
+                InspectOptions {
+                    every_nth_step: 0,
+                    ..Default::default()
+                },
+            );
+        })
+        .unwrap();
+    }
+}
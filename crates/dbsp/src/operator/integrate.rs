@@ -133,7 +133,27 @@ where
     /// 2 3 4 5 1
     /// 4 5 6 5 1
     /// ```
+    ///
+    /// Inside an iterative subcircuit, this operator converts changes over the
+    /// parent clock into their cumulative value, while keeping the child
+    /// clock intact.  For instance, given a stream of changes to a relation
+    /// imported into the subcircuit with
+    /// [`delta0`](`Self::delta0`), `integrate_nested` yields the complete
+    /// contents of the relation at the first iteration of each parent
+    /// timestamp.  The inverse operation is
+    /// [`differentiate_nested`](`Self::differentiate_nested`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` belongs to the root circuit, which does not have a
+    /// parent clock.  Use [`integrate`](`Self::integrate`) instead.
+    #[track_caller]
     pub fn integrate_nested(&self) -> Stream<C, D> {
+        assert!(
+            C::NESTING_DEPTH > 0,
+            "`integrate_nested` cannot be used in the root circuit, which does not have a parent clock; use `integrate` instead"
+        );
+
         self.circuit()
             .cache_get_or_insert_with(NestedIntegralId::new(self.origin_node_id().clone()), || {
                 self.circuit().region("integrate_nested", || {
@@ -159,10 +179,11 @@ mod test {
     use crate::{
         algebra::HasZero,
         monitor::TraceMonitor,
-        operator::{DelayedFeedback, Generator},
+        operator::{DelayedFeedback, DelayedNestedFeedback, FilterMap, Generator, Z1Nested},
         trace::{ord::OrdZSet, Batch},
-        zset, Circuit, RootCircuit,
+        zset, Circuit, OrdIndexedZSet, RootCircuit, Stream,
     };
+    use std::collections::BTreeSet;
 
     #[test]
     fn scalar_integrate() {
@@ -277,4 +298,149 @@ mod test {
             circuit.step().unwrap();
         }
     }
+
+    type Edges = OrdZSet<(usize, usize), isize>;
+
+    // Reference transitive closure of a set of edges.
+    fn transitive_closure(edges: &BTreeSet<(usize, usize)>) -> Edges {
+        let mut paths = edges.clone();
+        loop {
+            let new_paths: BTreeSet<_> = paths
+                .iter()
+                .flat_map(|&(from, via)| {
+                    edges
+                        .iter()
+                        .filter(move |&&(src, _)| src == via)
+                        .map(move |&(_, to)| (from, to))
+                })
+                .collect();
+            let len = paths.len();
+            paths.extend(new_paths);
+            if paths.len() == len {
+                break;
+            }
+        }
+
+        Edges::from_keys((), paths.into_iter().map(|path| (path, 1)).collect())
+    }
+
+    /// Computes the transitive closure of a changing graph by recomputing
+    /// it from scratch inside an iterative subcircuit at each parent
+    /// timestamp, and checks `integrate_nested` and `differentiate_nested`
+    /// against their manual implementations.
+    #[test]
+    fn transitive_closure_nested() {
+        let steps: Vec<Vec<((usize, usize), isize)>> = vec![
+            vec![((0, 1), 1), ((1, 2), 1), ((2, 3), 1)],
+            vec![((3, 4), 1), ((4, 0), 1)],
+            vec![((1, 2), -1), ((5, 6), 1)],
+            vec![((3, 4), -1), ((1, 2), 1), ((6, 5), 1)],
+            vec![],
+        ];
+
+        let mut expected_closures = Vec::new();
+        let mut edges = BTreeSet::new();
+        for step in steps.iter() {
+            for &(edge, weight) in step.iter() {
+                if weight > 0 {
+                    edges.insert(edge);
+                } else {
+                    edges.remove(&edge);
+                }
+            }
+            expected_closures.push(transitive_closure(&edges));
+        }
+
+        let mut inputs = steps.into_iter();
+        let mut expected_closures = expected_closures.into_iter();
+
+        let circuit = RootCircuit::build(move |circuit| {
+            TraceMonitor::new_panic_on_error().attach(circuit, "monitor");
+
+            // Changes to the set of edges.
+            let edges_delta = circuit.add_source(Generator::new(move || {
+                Edges::from_keys((), inputs.next().unwrap())
+            }));
+
+            let closure = circuit
+                .iterate_with_condition(|child| {
+                    let edges_delta = edges_delta.delta0(child);
+
+                    // The complete set of edges at the first iteration of each
+                    // parent timestamp.
+                    let edges = edges_delta.integrate_nested();
+
+                    // Manual implementation of `integrate_nested`.
+                    let feedback = <DelayedNestedFeedback<_, Edges>>::new(child);
+                    let edges_manual = edges_delta.plus(feedback.stream());
+                    feedback.connect(&edges_manual);
+                    edges.apply2(&edges_manual, |edges, edges_manual| {
+                        assert_eq!(edges, edges_manual)
+                    });
+
+                    // Non-incremental transitive closure.
+                    let edges_indexed: Stream<_, OrdIndexedZSet<usize, usize, isize>> =
+                        edges.integrate().index();
+                    let feedback = <DelayedFeedback<_, Edges>>::new(child);
+                    let paths_indexed: Stream<_, OrdIndexedZSet<usize, usize, isize>> =
+                        feedback.stream().map_index(|&(from, via)| (via, from));
+                    let paths = edges
+                        .integrate()
+                        .plus(
+                            &paths_indexed
+                                .stream_join(&edges_indexed, |_via, &from, &to| (from, to)),
+                        )
+                        .stream_distinct();
+                    feedback.connect(&paths);
+
+                    // Changes to paths relative to the same iteration at the
+                    // previous parent timestamp.
+                    let paths_delta = paths.differentiate_nested();
+
+                    // Manual implementation of `differentiate_nested`.
+                    let paths_delta_manual = paths
+                        .minus(&child.add_unary_operator(Z1Nested::new(Edges::zero()), &paths));
+                    paths_delta.apply2(&paths_delta_manual, |delta, delta_manual| {
+                        assert_eq!(delta, delta_manual)
+                    });
+
+                    // `integrate_nested` is the inverse of `differentiate_nested`.
+                    paths_delta
+                        .integrate_nested()
+                        .apply2(&paths, |paths1, paths2| assert_eq!(paths1, paths2));
+
+                    let condition = paths.differentiate().condition(|delta| delta.is_empty());
+                    Ok((condition, paths.export()))
+                })
+                .unwrap();
+
+            closure.inspect(move |closure| assert_eq!(closure, &expected_closures.next().unwrap()));
+        })
+        .unwrap()
+        .0;
+
+        for _ in 0..5 {
+            circuit.step().unwrap();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`integrate_nested` cannot be used in the root circuit")]
+    fn integrate_nested_in_root() {
+        RootCircuit::build(|circuit| {
+            circuit.add_source(Generator::new(|| 1)).integrate_nested();
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "`differentiate_nested` cannot be used in the root circuit")]
+    fn differentiate_nested_in_root() {
+        RootCircuit::build(|circuit| {
+            circuit
+                .add_source(Generator::new(|| 1))
+                .differentiate_nested();
+        })
+        .unwrap();
+    }
 }
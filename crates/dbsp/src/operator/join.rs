@@ -1,16 +1,19 @@
 //! Relational join operator.
 
 use crate::{
-    algebra::{IndexedZSet, Lattice, MulByRef, PartialOrder, ZRingValue, ZSet},
+    algebra::{HasZero, IndexedZSet, Lattice, MulByRef, PartialOrder, ZRingValue, ZSet},
     circuit::{
         metadata::{MetaItem, OperatorLocation, OperatorMeta},
-        operator_traits::{BinaryOperator, Operator},
+        operator_traits::{BinaryOperator, Operator, TernaryOperator},
         Circuit, GlobalNodeId, RootCircuit, Scope, Stream, WithClock,
     },
     circuit_cache_key,
     operator::FilterMap,
     time::Timestamp,
-    trace::{cursor::Cursor as TraceCursor, Batch, BatchReader, Batcher, Builder, Spine, Trace},
+    trace::{
+        cursor::Cursor as TraceCursor, layers::SearchHint, Batch, BatchReader, Batcher, Builder,
+        Spine, Trace,
+    },
     DBData, DBTimestamp, OrdIndexedZSet, OrdZSet,
 };
 use size_of::{Context, SizeOf};
@@ -26,6 +29,10 @@ use std::{
 
 circuit_cache_key!(AntijoinId<C, D>((GlobalNodeId, GlobalNodeId) => Stream<C, D>));
 
+/// Default limit on the size of the build side of
+/// [`join_with_hash_build`](`Stream::join_with_hash_build`).
+const DEFAULT_MAX_HASH_BUILD_SIZE: usize = 100_000;
+
 impl<C, I1> Stream<C, I1>
 where
     C: Circuit,
@@ -153,6 +160,81 @@ impl<I1> Stream<RootCircuit, I1> {
             .stream_join_inner(&right, join_func.clone(), Location::caller())
             .plus(&left.stream_join_inner(&right.integrate_trace(), join_func, Location::caller()))
     }
+
+    /// Incremental join optimized for joining a large stream with a small,
+    /// rarely changing collection, e.g., a dimension table.
+    ///
+    /// Computes the same result as [`join`](`Stream::join`).  However,
+    /// instead of merging the changes to `self` with the ordered trace of
+    /// `build`, the operator maintains the integral of `build` in a hash
+    /// index and probes it with each record in the changes to `self`.  The
+    /// hash index is only rebuilt at steps when `build` changes.
+    ///
+    /// When the integral of `build` grows beyond 100,000 records in a worker,
+    /// the operator falls back to the ordered join implementation until it
+    /// shrinks back below the limit.  Use
+    /// [`join_with_hash_build_limited`](`Self::join_with_hash_build_limited`)
+    /// to configure the limit.
+    #[track_caller]
+    pub fn join_with_hash_build<I2, F, V>(
+        &self,
+        build: &Stream<RootCircuit, I2>,
+        join_func: F,
+    ) -> Stream<RootCircuit, OrdZSet<V, I1::R>>
+    where
+        I1: IndexedZSet + Send,
+        I1::R: ZRingValue,
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> V + Clone + 'static,
+        V: DBData,
+    {
+        self.join_with_hash_build_limited(build, join_func, DEFAULT_MAX_HASH_BUILD_SIZE)
+    }
+
+    /// Like [`Self::join_with_hash_build`], but falls back to the ordered join
+    /// when the integral of `build` contains more than `max_build_size`
+    /// records in a worker.
+    #[track_caller]
+    pub fn join_with_hash_build_limited<I2, F, V>(
+        &self,
+        build: &Stream<RootCircuit, I2>,
+        join_func: F,
+        max_build_size: usize,
+    ) -> Stream<RootCircuit, OrdZSet<V, I1::R>>
+    where
+        I1: IndexedZSet + Send,
+        I1::R: ZRingValue,
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> V + Clone + 'static,
+        V: DBData,
+    {
+        let location = Location::caller();
+
+        self.circuit().region("join_with_hash_build", || {
+            let probe = self.shard();
+            let build = build.shard();
+
+            // Changes to the output caused by changes to the probe side
+            // (`probe <> build_trace`).
+            let probe_updates = self.circuit().add_ternary_operator(
+                HashJoinProbe::new(join_func.clone(), max_build_size, location),
+                &probe,
+                &build,
+                &build.integrate_trace(),
+            );
+
+            // Changes to the output caused by changes to the build side
+            // (`delayed_probe_trace <> build`).  The build side is small, so
+            // we use the ordered join.
+            let build_updates = build.stream_join_inner(
+                &probe.integrate_trace().delay_trace(),
+                move |k: &I1::Key, v2: &I2::Val, v1: &I1::Val| join_func(k, v1, v2),
+                location,
+            );
+
+            probe_updates.plus(&build_updates)
+        })
+    }
 }
 
 impl<C, I1> Stream<C, I1>
@@ -442,8 +524,8 @@ where
 
         while cursor1.key_valid() && cursor2.key_valid() {
             match cursor1.key().cmp(cursor2.key()) {
-                Ordering::Less => cursor1.seek_key(cursor2.key()),
-                Ordering::Greater => cursor2.seek_key(cursor1.key()),
+                Ordering::Less => cursor1.seek_key_with_hint(cursor2.key(), SearchHint::Near),
+                Ordering::Greater => cursor2.seek_key_with_hint(cursor1.key(), SearchHint::Near),
                 Ordering::Equal => {
                     while cursor1.val_valid() {
                         let w1 = cursor1.weight();
@@ -528,8 +610,8 @@ where
 
         while cursor1.key_valid() && cursor2.key_valid() {
             match cursor1.key().cmp(cursor2.key()) {
-                Ordering::Less => cursor1.seek_key(cursor2.key()),
-                Ordering::Greater => cursor2.seek_key(cursor1.key()),
+                Ordering::Less => cursor1.seek_key_with_hint(cursor2.key(), SearchHint::Near),
+                Ordering::Greater => cursor2.seek_key_with_hint(cursor1.key(), SearchHint::Near),
                 Ordering::Equal => {
                     while cursor1.val_valid() {
                         let w1 = cursor1.weight();
@@ -559,6 +641,191 @@ where
     }
 }
 
+/// Joins a batch of changes to the probe side of
+/// [`join_with_hash_build`](`Stream::join_with_hash_build`) with the trace of
+/// the build side.
+///
+/// * Input stream 1: changes to the probe side.
+/// * Input stream 2: changes to the build side.
+/// * Input stream 3: integrated trace of the build side.
+///
+/// The operator maintains a hash index of the build trace, rebuilt whenever
+/// the build side changes.  If the build trace contains more than
+/// `max_build_size` records, the index is dropped and the operator merges the
+/// probe batch with the build trace instead.
+struct HashJoinProbe<F, K, V, R> {
+    join_func: F,
+    max_build_size: usize,
+    location: &'static Location<'static>,
+    // `None` if the index hasn't been built yet or the build side is too large.
+    index: Option<HashMap<K, Vec<(V, R)>>>,
+}
+
+impl<F, K, V, R> HashJoinProbe<F, K, V, R> {
+    fn new(join_func: F, max_build_size: usize, location: &'static Location<'static>) -> Self {
+        Self {
+            join_func,
+            max_build_size,
+            location,
+            index: None,
+        }
+    }
+}
+
+impl<F, K, V, R> Operator for HashJoinProbe<F, K, V, R>
+where
+    F: 'static,
+    K: 'static,
+    V: 'static,
+    R: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("HashJoinProbe")
+    }
+
+    fn location(&self) -> OperatorLocation {
+        Some(self.location)
+    }
+
+    fn metadata(&self, meta: &mut OperatorMeta) {
+        let index_size = self
+            .index
+            .as_ref()
+            .map(|index| index.values().map(Vec::len).sum())
+            .unwrap_or(0);
+
+        meta.extend(metadata! {
+            "hash index" => self.index.is_some(),
+            "hash index size" => index_size,
+        });
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<F, K, V, R> HashJoinProbe<F, K, V, R>
+where
+    K: DBData,
+    V: DBData,
+    R: ZRingValue,
+{
+    fn build_index<T>(trace: &T) -> HashMap<K, Vec<(V, R)>>
+    where
+        T: BatchReader<Key = K, Val = V, Time = (), R = R>,
+    {
+        let mut index = HashMap::new();
+        let mut cursor = trace.cursor();
+
+        while cursor.key_valid() {
+            let mut vals = Vec::new();
+            while cursor.val_valid() {
+                let w = cursor.weight();
+                if !w.is_zero() {
+                    vals.push((cursor.val().clone(), w));
+                }
+                cursor.step_val();
+            }
+            if !vals.is_empty() {
+                index.insert(cursor.key().clone(), vals);
+            }
+            cursor.step_key();
+        }
+
+        index
+    }
+}
+
+impl<F, I, B, T, Z> TernaryOperator<I, B, T, Z> for HashJoinProbe<F, I::Key, B::Val, I::R>
+where
+    I: IndexedZSet,
+    I::R: ZRingValue,
+    B: IndexedZSet<Key = I::Key, R = I::R>,
+    T: BatchReader<Key = I::Key, Val = B::Val, Time = (), R = I::R> + Clone,
+    F: Fn(&I::Key, &I::Val, &B::Val) -> Z::Key + 'static,
+    Z: ZSet<R = I::R>,
+{
+    fn eval<'a>(
+        &mut self,
+        probe_delta: Cow<'a, I>,
+        build_delta: Cow<'a, B>,
+        build_trace: Cow<'a, T>,
+    ) -> Z {
+        // (Re)build the index when the build side changes.
+        if !build_delta.is_empty() || self.index.is_none() {
+            self.index = if build_trace.len() <= self.max_build_size {
+                Some(Self::build_index(build_trace.as_ref()))
+            } else {
+                None
+            };
+        }
+
+        let mut probe_cursor = probe_delta.cursor();
+        let mut batch = Vec::with_capacity(probe_delta.len());
+
+        match &self.index {
+            Some(index) => {
+                while probe_cursor.key_valid() {
+                    if let Some(vals) = index.get(probe_cursor.key()) {
+                        while probe_cursor.val_valid() {
+                            let w1 = probe_cursor.weight();
+                            let v1 = probe_cursor.val();
+                            for (v2, w2) in vals.iter() {
+                                batch.push((
+                                    (self.join_func)(probe_cursor.key(), v1, v2),
+                                    w1.mul_by_ref(w2),
+                                ));
+                            }
+                            probe_cursor.step_val();
+                        }
+                    }
+                    probe_cursor.step_key();
+                }
+            }
+            None => {
+                let mut build_cursor = build_trace.cursor();
+
+                while probe_cursor.key_valid() && build_cursor.key_valid() {
+                    match probe_cursor.key().cmp(build_cursor.key()) {
+                        Ordering::Less => {
+                            probe_cursor.seek_key_with_hint(build_cursor.key(), SearchHint::Near)
+                        }
+                        Ordering::Greater => {
+                            build_cursor.seek_key_with_hint(probe_cursor.key(), SearchHint::Near)
+                        }
+                        Ordering::Equal => {
+                            while probe_cursor.val_valid() {
+                                let w1 = probe_cursor.weight();
+                                let v1 = probe_cursor.val();
+                                while build_cursor.val_valid() {
+                                    batch.push((
+                                        (self.join_func)(
+                                            probe_cursor.key(),
+                                            v1,
+                                            build_cursor.val(),
+                                        ),
+                                        w1.mul_by_ref(&build_cursor.weight()),
+                                    ));
+                                    build_cursor.step_val();
+                                }
+
+                                build_cursor.rewind_vals();
+                                probe_cursor.step_val();
+                            }
+
+                            probe_cursor.step_key();
+                            build_cursor.step_key();
+                        }
+                    }
+                }
+            }
+        }
+
+        Z::from_keys((), batch)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default)]
 struct JoinStats {
     lhs_tuples: usize,
@@ -750,8 +1017,12 @@ where
 
         while index_cursor.key_valid() && trace_cursor.key_valid() {
             match index_cursor.key().cmp(trace_cursor.key()) {
-                Ordering::Less => index_cursor.seek_key(trace_cursor.key()),
-                Ordering::Greater => trace_cursor.seek_key(index_cursor.key()),
+                Ordering::Less => {
+                    index_cursor.seek_key_with_hint(trace_cursor.key(), SearchHint::Near)
+                }
+                Ordering::Greater => {
+                    trace_cursor.seek_key_with_hint(index_cursor.key(), SearchHint::Near)
+                }
                 Ordering::Equal => {
                     //println!("key: {}", index_cursor.key(index));
 
@@ -1019,7 +1290,8 @@ mod test {
     fn do_join_test_mt(workers: usize) {
         let hruntime = Runtime::run(workers, || {
             join_test();
-        });
+        })
+        .unwrap();
 
         hruntime.join().unwrap();
     }
@@ -1383,4 +1655,69 @@ mod test {
 
         circuit.kill().unwrap();
     }
+
+    fn hash_join_test_mt(workers: usize) {
+        let (mut circuit, (mut probe, mut build)) = Runtime::init_circuit(workers, |circuit| {
+            let (probe, probe_handle) = circuit.add_input_indexed_zset::<usize, usize, isize>();
+            let (build, build_handle) = circuit.add_input_indexed_zset::<usize, String, isize>();
+
+            let join_func = |k: &usize, v1: &usize, v2: &String| (*k, *v1, v2.clone());
+
+            let expected = probe.join(&build, join_func).gather(0);
+
+            // The second limit forces the operator to switch between the hash
+            // and the ordered join as the build side grows and shrinks.
+            for max_build_size in [usize::MAX, 4] {
+                let actual = probe
+                    .join_with_hash_build_limited(&build, join_func, max_build_size)
+                    .gather(0);
+                expected.apply2(&actual, |expected, actual| assert_eq!(expected, actual));
+            }
+
+            (probe_handle, build_handle)
+        })
+        .unwrap();
+
+        let build_updates: Vec<Vec<(usize, (String, isize))>> = vec![
+            vec![(1, ("a".to_string(), 1)), (2, ("b".to_string(), 1))],
+            vec![],
+            vec![],
+            // Update the value associated with a key.
+            vec![(1, ("a".to_string(), -1)), (1, ("c".to_string(), 1))],
+            vec![],
+            // Grow the build side beyond the size limit.
+            (3..10).map(|k| (k, (k.to_string(), 1))).collect(),
+            vec![],
+            vec![(2, ("b".to_string(), 2))],
+            // Shrink it back.
+            (3..10).map(|k| (k, (k.to_string(), -1))).collect(),
+            vec![],
+            vec![(1, ("c".to_string(), -1))],
+            vec![],
+        ];
+
+        for (step, mut build_batch) in build_updates.into_iter().enumerate() {
+            let mut probe_batch: Vec<_> = (0..100).map(|i| (i % 12, (step * 100 + i, 1))).collect();
+            // Retract some of the records inserted at the previous step.
+            if step > 0 {
+                probe_batch.extend(
+                    (0..100)
+                        .step_by(3)
+                        .map(|i| (i % 12, ((step - 1) * 100 + i, -1))),
+                );
+            }
+
+            probe.append(&mut probe_batch);
+            build.append(&mut build_batch);
+            circuit.step().unwrap();
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    #[test]
+    fn hash_join_test() {
+        hash_join_test_mt(1);
+        hash_join_test_mt(4);
+    }
 }
@@ -0,0 +1,557 @@
+//! Left outer join operator.
+//!
+//! The left outer join of indexed Z-sets `a` and `b` contains the inner join
+//! of `a` and `b`, plus a row for each `(k, v1)` in `a` such that `b` doesn't
+//! contain any values with positive weights under key `k`.
+
+use crate::{
+    algebra::{IndexedZSet, MulByRef, ZRingValue, ZSet},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator, QuaternaryOperator},
+        Scope,
+    },
+    trace::{cursor::Cursor, BatchReader},
+    Circuit, DBData, OrdZSet, RootCircuit, Stream,
+};
+use std::{borrow::Cow, marker::PhantomData, ops::Neg};
+
+impl<I1> Stream<RootCircuit, I1> {
+    /// Incremental left outer join.
+    ///
+    /// For each `(k, v1)` in `self`, outputs `join_func(k, v1, Some(v2))` for
+    /// every `(k, v2)` in `other`, or `join_func(k, v1, None)` if `other`
+    /// doesn't contain any values with positive weights for key `k`.
+    ///
+    /// This is equivalent to
+    /// [`outer_join`](`crate::circuit::Stream::outer_join`) without the
+    /// right-only rows, but only maintains the integrals of the two input
+    /// streams instead of computing a join and an anti-join.  When the first
+    /// value for a key arrives in `other`, the operator retracts the
+    /// previously emitted `None` rows for that key, and re-emits them when all
+    /// values for the key are retracted from `other`.
+    ///
+    /// This method only works in the top-level scope.
+    ///
+    /// # Type arguments
+    ///
+    /// * `I1` - batch type in the first input stream.
+    /// * `I2` - batch type in the second input stream.
+    /// * `F` - join function type: maps key, a value from the first input and
+    ///   an optional value from the second input to an output value.
+    /// * `O` - output value type.
+    pub fn outer_join_left<I2, F, O>(
+        &self,
+        other: &Stream<RootCircuit, I2>,
+        join_func: F,
+    ) -> Stream<RootCircuit, OrdZSet<O, I1::R>>
+    where
+        I1: IndexedZSet + Send,
+        I1::R: ZRingValue,
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        F: Fn(&I1::Key, &I1::Val, Option<&I2::Val>) -> O + Clone + 'static,
+        O: DBData,
+    {
+        self.circuit().region("outer_join_left", || {
+            let circuit = self.circuit();
+            let left = self.shard();
+            let right = other.shard();
+
+            let left_trace = left.integrate_trace();
+            let right_trace = right.integrate_trace();
+
+            // Changes to the output caused by changes to the left input
+            // (`delta_left <> right_trace`).
+            let left_updates = circuit
+                .add_binary_operator(OuterJoinLeft::new(join_func.clone()), &left, &right_trace)
+                .mark_sharded();
+
+            // Changes to the output caused by changes to the right input
+            // (`delayed_left_trace <> right_trace - delayed_left_trace <>
+            // delayed_right_trace`).
+            let right_updates = circuit
+                .add_quaternary_operator(
+                    OuterJoinRight::new(join_func),
+                    &right,
+                    &left_trace.delay_trace(),
+                    &right_trace.delay_trace(),
+                    &right_trace,
+                )
+                .mark_sharded();
+
+            left_updates.plus(&right_updates).mark_sharded()
+        })
+    }
+}
+
+/// Returns `true` if the current key of `cursor` has at least one value with
+/// positive weight.
+///
+/// Leaves the cursor at the first value of the key.
+fn has_positive_weight<'s, K, V, R, C>(cursor: &mut C) -> bool
+where
+    R: ZRingValue,
+    C: Cursor<'s, K, V, (), R>,
+{
+    let mut result = false;
+
+    while cursor.val_valid() {
+        if !cursor.weight().le0() {
+            result = true;
+            break;
+        }
+        cursor.step_val();
+    }
+    cursor.rewind_vals();
+
+    result
+}
+
+/// Binary operator that computes changes to the output of the left outer join
+/// caused by changes to the left input.
+///
+/// * Input stream 1: updates to the left input.
+/// * Input stream 2: trace containing the accumulated right input, including
+///   the current update.
+struct OuterJoinLeft<F, V2> {
+    join_func: F,
+    phantom: PhantomData<V2>,
+}
+
+impl<F, V2> OuterJoinLeft<F, V2> {
+    fn new(join_func: F) -> Self {
+        Self {
+            join_func,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<F, V2> Operator for OuterJoinLeft<F, V2>
+where
+    F: 'static,
+    V2: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("OuterJoinLeft")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<F, V2, B, T, Z> BinaryOperator<B, T, Z> for OuterJoinLeft<F, V2>
+where
+    V2: DBData,
+    B: BatchReader<Time = ()> + Clone,
+    B::R: ZRingValue,
+    T: BatchReader<Key = B::Key, Val = V2, Time = (), R = B::R> + Clone,
+    Z: ZSet<R = B::R>,
+    F: Fn(&B::Key, &B::Val, Option<&V2>) -> Z::Key + 'static,
+{
+    fn eval(&mut self, left_delta: &B, right_trace: &T) -> Z {
+        let mut delta_cursor = left_delta.cursor();
+        let mut right_cursor = right_trace.cursor();
+
+        let mut tuples = Vec::new();
+
+        while delta_cursor.key_valid() {
+            let key = delta_cursor.key().clone();
+
+            right_cursor.seek_key(&key);
+            let matched = right_cursor.key_valid()
+                && right_cursor.key() == &key
+                && has_positive_weight(&mut right_cursor);
+
+            while delta_cursor.val_valid() {
+                let w1 = delta_cursor.weight();
+                let v1 = delta_cursor.val();
+
+                if right_cursor.key_valid() && right_cursor.key() == &key {
+                    while right_cursor.val_valid() {
+                        let w2 = right_cursor.weight();
+                        let v2 = right_cursor.val();
+                        tuples.push((
+                            Z::item_from((self.join_func)(&key, v1, Some(v2)), ()),
+                            w1.mul_by_ref(&w2),
+                        ));
+                        right_cursor.step_val();
+                    }
+                    right_cursor.rewind_vals();
+                }
+
+                if !matched {
+                    tuples.push((Z::item_from((self.join_func)(&key, v1, None), ()), w1));
+                }
+
+                delta_cursor.step_val();
+            }
+
+            delta_cursor.step_key();
+        }
+
+        Z::from_tuples((), tuples)
+    }
+}
+
+/// Quaternary operator that computes changes to the output of the left outer
+/// join caused by changes to the right input.
+///
+/// * Input stream 1: updates to the right input.
+/// * Input stream 2: trace containing the accumulated left input, excluding
+///   the current update.
+/// * Input stream 3: trace containing the accumulated right input, excluding
+///   the current update.
+/// * Input stream 4: trace containing the accumulated right input, including
+///   the current update.
+///
+/// In addition to joining right updates with the left trace, the operator
+/// emits or retracts `None` rows for keys that gain their first value with
+/// positive weight or lose their last one.
+struct OuterJoinRight<F, V1> {
+    join_func: F,
+    phantom: PhantomData<V1>,
+}
+
+impl<F, V1> OuterJoinRight<F, V1> {
+    fn new(join_func: F) -> Self {
+        Self {
+            join_func,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<F, V1> Operator for OuterJoinRight<F, V1>
+where
+    F: 'static,
+    V1: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("OuterJoinRight")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<F, V1, B, LT, RT, Z> QuaternaryOperator<B, LT, RT, RT, Z> for OuterJoinRight<F, V1>
+where
+    V1: DBData,
+    B: BatchReader<Time = ()> + Clone,
+    B::R: ZRingValue,
+    LT: BatchReader<Key = B::Key, Val = V1, Time = (), R = B::R> + Clone,
+    RT: BatchReader<Key = B::Key, Val = B::Val, Time = (), R = B::R> + Clone,
+    Z: ZSet<R = B::R>,
+    F: Fn(&B::Key, &V1, Option<&B::Val>) -> Z::Key + 'static,
+{
+    fn eval<'a>(
+        &mut self,
+        right_delta: Cow<'a, B>,
+        left_trace: Cow<'a, LT>,
+        old_right_trace: Cow<'a, RT>,
+        new_right_trace: Cow<'a, RT>,
+    ) -> Z {
+        let mut delta_cursor = right_delta.cursor();
+        let mut left_cursor = left_trace.cursor();
+        let mut old_right_cursor = old_right_trace.cursor();
+        let mut new_right_cursor = new_right_trace.cursor();
+
+        let mut tuples = Vec::new();
+
+        while delta_cursor.key_valid() {
+            let key = delta_cursor.key().clone();
+
+            left_cursor.seek_key(&key);
+            if !left_cursor.key_valid() || left_cursor.key() != &key {
+                delta_cursor.step_key();
+                continue;
+            }
+
+            old_right_cursor.seek_key(&key);
+            let old_matched = old_right_cursor.key_valid()
+                && old_right_cursor.key() == &key
+                && has_positive_weight(&mut old_right_cursor);
+
+            new_right_cursor.seek_key(&key);
+            let new_matched = new_right_cursor.key_valid()
+                && new_right_cursor.key() == &key
+                && has_positive_weight(&mut new_right_cursor);
+
+            while left_cursor.val_valid() {
+                let w1 = left_cursor.weight();
+                let v1 = left_cursor.val();
+
+                while delta_cursor.val_valid() {
+                    let w2 = delta_cursor.weight();
+                    let v2 = delta_cursor.val();
+                    tuples.push((
+                        Z::item_from((self.join_func)(&key, v1, Some(v2)), ()),
+                        w1.mul_by_ref(&w2),
+                    ));
+                    delta_cursor.step_val();
+                }
+                delta_cursor.rewind_vals();
+
+                // The key gained its first value or lost its last value in
+                // `other`: retract or re-emit the corresponding `None` row.
+                if old_matched != new_matched {
+                    let weight = if new_matched { w1.neg() } else { w1 };
+                    tuples.push((Z::item_from((self.join_func)(&key, v1, None), ()), weight));
+                }
+
+                left_cursor.step_val();
+            }
+
+            delta_cursor.step_key();
+        }
+
+        Z::from_tuples((), tuples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, OrdZSet, RootCircuit, Runtime, Stream,
+    };
+
+    type LeftBatch = OrdIndexedZSet<u64, i64, isize>;
+    type RightBatch = OrdIndexedZSet<u64, String, isize>;
+    type OutputBatch = OrdZSet<(u64, i64, Option<String>), isize>;
+    type OutputStream = Stream<RootCircuit, OutputBatch>;
+
+    // Reference implementation of `outer_join_left`.
+    fn outer_join_left_slow(
+        left: &Stream<RootCircuit, LeftBatch>,
+        right: &Stream<RootCircuit, RightBatch>,
+    ) -> OutputStream {
+        left.gather(0).integrate().apply2(
+            &right.gather(0).integrate(),
+            |left: &LeftBatch, right: &RightBatch| {
+                let mut tuples = Vec::new();
+                let mut left_cursor = left.cursor();
+
+                while left_cursor.key_valid() {
+                    let key = *left_cursor.key();
+
+                    while left_cursor.val_valid() {
+                        let v1 = *left_cursor.val();
+                        let w1 = left_cursor.weight();
+
+                        let mut matched = false;
+                        let mut right_cursor = right.cursor();
+                        right_cursor.seek_key(&key);
+                        if right_cursor.key_valid() && right_cursor.key() == &key {
+                            while right_cursor.val_valid() {
+                                let w2 = right_cursor.weight();
+                                matched |= w2 > 0;
+                                tuples.push(((key, v1, Some(right_cursor.val().clone())), w1 * w2));
+                                right_cursor.step_val();
+                            }
+                        }
+                        if !matched {
+                            tuples.push(((key, v1, None), w1));
+                        }
+
+                        left_cursor.step_val();
+                    }
+
+                    left_cursor.step_key();
+                }
+
+                OutputBatch::from_tuples((), tuples)
+            },
+        )
+    }
+
+    type LeftHandle = CollectionHandle<u64, (i64, isize)>;
+    type RightHandle = CollectionHandle<u64, (String, isize)>;
+
+    fn outer_join_left_circuit() -> (DBSPHandle, (LeftHandle, RightHandle)) {
+        Runtime::init_circuit(4, |circuit| {
+            let (left, left_handle) = circuit.add_input_indexed_zset::<u64, i64, isize>();
+            let (right, right_handle) = circuit.add_input_indexed_zset::<u64, String, isize>();
+
+            let expected = outer_join_left_slow(&left, &right);
+            let actual = left
+                .outer_join_left(&right, |k, v1, v2| (*k, *v1, v2.cloned()))
+                .gather(0)
+                .integrate();
+            expected.apply2(&actual, |expected, actual| assert_eq!(expected, actual));
+
+            (left_handle, right_handle)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_outer_join_left() {
+        let (circuit, (left_handle, right_handle)) = RootCircuit::build(move |circuit| {
+            let (left, left_handle) = circuit.add_input_indexed_zset::<u64, i64, isize>();
+            let (right, right_handle) = circuit.add_input_indexed_zset::<u64, String, isize>();
+
+            let mut expected_outputs = vec![
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((1, 10, None), 1),
+                        ((1, 11, None), 2),
+                        ((2, 20, Some("a".to_string())), 1),
+                    ],
+                ),
+                // First right value for key 1 replaces `None` rows.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((1, 10, None), -1),
+                        ((1, 10, Some("b".to_string())), 1),
+                        ((1, 11, None), -2),
+                        ((1, 11, Some("b".to_string())), 2),
+                    ],
+                ),
+                // Second right value doesn't affect `None` rows.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((1, 10, Some("c".to_string())), 1),
+                        ((1, 11, Some("c".to_string())), 2),
+                    ],
+                ),
+                // Right weights for key 1 sum to zero: revert to `None` rows.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((1, 10, None), 1),
+                        ((1, 10, Some("b".to_string())), -1),
+                        ((1, 10, Some("c".to_string())), -1),
+                        ((1, 11, None), 2),
+                        ((1, 11, Some("b".to_string())), -2),
+                        ((1, 11, Some("c".to_string())), -2),
+                    ],
+                ),
+                // New left value for a key without right values.
+                OutputBatch::from_tuples((), vec![((1, 12, None), 1)]),
+            ]
+            .into_iter();
+
+            left.outer_join_left(&right, |k, v1, v2| (*k, *v1, v2.cloned()))
+                .inspect(move |batch| assert_eq!(batch, &expected_outputs.next().unwrap()));
+
+            (left_handle, right_handle)
+        })
+        .unwrap();
+
+        left_handle.push(1, (10, 1));
+        left_handle.push(1, (11, 2));
+        left_handle.push(2, (20, 1));
+        right_handle.push(2, ("a".to_string(), 1));
+        right_handle.push(3, ("x".to_string(), 1));
+        circuit.step().unwrap();
+
+        right_handle.push(1, ("b".to_string(), 1));
+        circuit.step().unwrap();
+
+        right_handle.push(1, ("c".to_string(), 1));
+        circuit.step().unwrap();
+
+        right_handle.push(1, ("b".to_string(), -1));
+        right_handle.push(1, ("c".to_string(), -1));
+        circuit.step().unwrap();
+
+        left_handle.push(1, (12, 1));
+        circuit.step().unwrap();
+    }
+
+    use proptest::{collection, prelude::*};
+
+    type LeftTuple = (u64, (i64, isize));
+    type RightTuple = (u64, (String, isize));
+
+    fn left_tuple(keys: u64) -> impl Strategy<Value = LeftTuple> {
+        ((0..keys), ((0..5i64), 1..3isize))
+    }
+
+    fn right_tuple(keys: u64) -> impl Strategy<Value = RightTuple> {
+        (
+            (0..keys),
+            (
+                "[a-c]".prop_map(|s| s.to_string()),
+                prop_oneof![Just(-1isize), 1..3isize],
+            ),
+        )
+    }
+
+    // Each step inserts batches of new values into both inputs and retracts
+    // some of the previously inserted values, identified by their indexes.
+    #[allow(clippy::type_complexity)]
+    fn input_trace(
+        keys: u64,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<(Vec<LeftTuple>, Vec<RightTuple>, Vec<usize>, Vec<usize>)>> {
+        collection::vec(
+            (
+                collection::vec(left_tuple(keys), 0..max_batch_size),
+                collection::vec(right_tuple(keys), 0..max_batch_size),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+            ),
+            0..max_batches,
+        )
+    }
+
+    fn retract<T: Clone>(
+        inserted: &mut Vec<(u64, (T, isize))>,
+        indexes: Vec<usize>,
+    ) -> Vec<(u64, (T, isize))> {
+        let mut retractions = Vec::new();
+
+        for index in indexes {
+            if index < inserted.len() {
+                let (key, (val, w)) = inserted.swap_remove(index);
+                retractions.push((key, (val, -w)));
+            }
+        }
+
+        retractions
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_trace(trace: Vec<(Vec<LeftTuple>, Vec<RightTuple>, Vec<usize>, Vec<usize>)>) {
+        let (mut circuit, (mut left, mut right)) = outer_join_left_circuit();
+
+        let mut inserted_left = Vec::new();
+        let mut inserted_right = Vec::new();
+
+        for (mut left_batch, mut right_batch, left_retractions, right_retractions) in trace {
+            inserted_left.extend(left_batch.iter().cloned());
+            inserted_right.extend(right_batch.iter().cloned());
+
+            left_batch.extend(retract(&mut inserted_left, left_retractions));
+            right_batch.extend(retract(&mut inserted_right, right_retractions));
+
+            left.append(&mut left_batch);
+            right.append(&mut right_batch);
+            circuit.step().unwrap();
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_outer_join_left_sparse(trace in input_trace(100, 20, 20)) {
+            run_trace(trace);
+        }
+
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_outer_join_left_dense(trace in input_trace(5, 30, 20)) {
+            run_trace(trace);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Point lookups in the integral of a stream.
+
+use crate::{
+    algebra::{HasZero, IndexedZSet, ZSet},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope,
+    },
+    trace::{Batch, BatchReader, Builder, Cursor, Spine},
+    Circuit, DBData, OrdZSet, Stream,
+};
+use size_of::SizeOf;
+use std::{borrow::Cow, marker::PhantomData};
+
+impl<C, B> Stream<C, B>
+where
+    C: Circuit,
+    B: IndexedZSet + Send,
+{
+    /// Look up keys in the integral of `self`.
+    ///
+    /// `requests` is a stream of Z-sets of keys to look up.  At each clock
+    /// cycle, the operator reads the contents of the integral of `self`,
+    /// including the current input batch, for each key in `requests`, and
+    /// outputs a `(key, values)` tuple, where `values` contains all values
+    /// associated with the key along with their weights in ascending order,
+    /// or `None` if the key is not present in the integral.  The weight of
+    /// the output tuple is equal to the weight of the request.
+    ///
+    /// Unlike most DBSP operators, this operator is not incremental with
+    /// respect to `requests`: the output at each clock cycle contains
+    /// responses to the requests received during this clock cycle only.
+    ///
+    /// The integral of `self` is stored in a trace, and each key is located
+    /// by seeking a cursor over the trace, so the cost of the lookup is
+    /// proportional to the number of requests rather than the size of the
+    /// integral.
+    pub fn lookup<Z>(
+        &self,
+        requests: &Stream<C, Z>,
+    ) -> Stream<C, OrdZSet<(B::Key, Option<Vec<(B::Val, B::R)>>), Z::R>>
+    where
+        Z: ZSet<Key = B::Key> + Send,
+        Spine<B>: SizeOf,
+    {
+        self.lookup_with(requests, |_key, values| values.to_vec())
+    }
+
+    /// Like [`lookup`](`Self::lookup`), but reduces the values associated
+    /// with each key using a user-supplied function.
+    ///
+    /// `reduce` is invoked for each requested key present in the integral of
+    /// `self` with a non-empty slice of values associated with the key along
+    /// with their weights in ascending order.  For instance, when values are
+    /// ordered by time, `|_key, values| values.last().unwrap().0.clone()`
+    /// returns the latest value.
+    pub fn lookup_with<Z, O, F>(
+        &self,
+        requests: &Stream<C, Z>,
+        reduce: F,
+    ) -> Stream<C, OrdZSet<(B::Key, Option<O>), Z::R>>
+    where
+        Z: ZSet<Key = B::Key> + Send,
+        O: DBData,
+        F: Fn(&B::Key, &[(B::Val, B::R)]) -> O + 'static,
+        Spine<B>: SizeOf,
+    {
+        self.circuit().region("lookup", || {
+            let trace = self.shard().integrate_trace();
+            self.circuit()
+                .add_binary_operator(Lookup::new(reduce), &requests.shard(), &trace)
+        })
+    }
+}
+
+/// Looks up keys from the first input in the trace in the second input.
+struct Lookup<Z, T, O, F>
+where
+    T: BatchReader,
+{
+    reduce: F,
+    // Buffer used to collect values associated with a key.
+    values: Vec<(T::Val, T::R)>,
+    _types: PhantomData<(Z, O)>,
+}
+
+impl<Z, T, O, F> Lookup<Z, T, O, F>
+where
+    T: BatchReader,
+{
+    fn new(reduce: F) -> Self {
+        Self {
+            reduce,
+            values: Vec::new(),
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<Z, T, O, F> Operator for Lookup<Z, T, O, F>
+where
+    Z: 'static,
+    T: BatchReader + 'static,
+    O: 'static,
+    F: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Lookup")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<Z, T, O, F> BinaryOperator<Z, T, OrdZSet<(Z::Key, Option<O>), Z::R>> for Lookup<Z, T, O, F>
+where
+    Z: ZSet,
+    T: BatchReader<Key = Z::Key, Time = ()> + 'static,
+    O: DBData,
+    F: Fn(&T::Key, &[(T::Val, T::R)]) -> O + 'static,
+{
+    fn eval(&mut self, requests: &Z, trace: &T) -> OrdZSet<(Z::Key, Option<O>), Z::R> {
+        let mut builder = <OrdZSet<(Z::Key, Option<O>), Z::R> as Batch>::Builder::with_capacity(
+            (),
+            requests.key_count(),
+        );
+
+        let mut request_cursor = requests.cursor();
+        let mut trace_cursor = trace.cursor();
+
+        // Requests are sorted by key, so we only need to move the trace cursor
+        // forward.
+        while request_cursor.key_valid() {
+            let weight = request_cursor.weight();
+            if !weight.is_zero() {
+                let key = request_cursor.key();
+
+                self.values.clear();
+                trace_cursor.seek_key(key);
+                if trace_cursor.key_valid() && trace_cursor.key() == key {
+                    while trace_cursor.val_valid() {
+                        // Traces may contain values whose weights add up to zero.
+                        let value_weight = trace_cursor.weight();
+                        if !value_weight.is_zero() {
+                            self.values.push((trace_cursor.val().clone(), value_weight));
+                        }
+                        trace_cursor.step_val();
+                    }
+                }
+
+                let result = if self.values.is_empty() {
+                    None
+                } else {
+                    Some((self.reduce)(key, &self.values))
+                };
+                builder.push(((key.clone(), result), weight));
+            }
+
+            request_cursor.step_key();
+        }
+
+        builder.done()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{trace::Batch, CollectionHandle, DBSPHandle, OrdZSet, OutputHandle, Runtime};
+
+    type Values = Option<Vec<(u64, isize)>>;
+
+    fn lookup_circuit(
+        workers: usize,
+    ) -> (
+        DBSPHandle,
+        (
+            CollectionHandle<u64, (u64, isize)>,
+            CollectionHandle<u64, isize>,
+            OutputHandle<OrdZSet<(u64, Values), isize>>,
+            OutputHandle<OrdZSet<(u64, Option<u64>), isize>>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (requests, requests_handle) = circuit.add_input_zset::<u64, isize>();
+
+            let values = input.lookup(&requests).output();
+            let latest = input
+                .lookup_with(&requests, |_key, values| values.last().unwrap().0)
+                .output();
+
+            (input_handle, requests_handle, values, latest)
+        })
+        .unwrap()
+    }
+
+    fn test_lookup(workers: usize) {
+        let (mut dbsp, (mut input, mut requests, values, latest)) = lookup_circuit(workers);
+
+        // Each step: updates to the input, requests, expected values, expected
+        // latest values.
+        let steps: Vec<(
+            Vec<(u64, (u64, isize))>,
+            Vec<(u64, isize)>,
+            Vec<((u64, Values), isize)>,
+            Vec<((u64, Option<u64>), isize)>,
+        )> = vec![
+            // Lookups observe the current input batch.
+            (
+                vec![(1, (10, 1)), (2, (20, 1)), (2, (21, 1)), (3, (30, 2))],
+                vec![(1, 1), (2, 1), (3, 1), (4, 1)],
+                vec![
+                    ((1, Some(vec![(10, 1)])), 1),
+                    ((2, Some(vec![(20, 1), (21, 1)])), 1),
+                    ((3, Some(vec![(30, 2)])), 1),
+                    ((4, None), 1),
+                ],
+                vec![
+                    ((1, Some(10)), 1),
+                    ((2, Some(21)), 1),
+                    ((3, Some(30)), 1),
+                    ((4, None), 1),
+                ],
+            ),
+            // Repeated requests observe state changes.
+            (
+                vec![(1, (10, -1)), (1, (11, 1)), (2, (21, -1)), (4, (40, 1))],
+                vec![(1, 1), (2, 2), (4, 1)],
+                vec![
+                    ((1, Some(vec![(11, 1)])), 1),
+                    ((2, Some(vec![(20, 1)])), 2),
+                    ((4, Some(vec![(40, 1)])), 1),
+                ],
+                vec![((1, Some(11)), 1), ((2, Some(20)), 2), ((4, Some(40)), 1)],
+            ),
+            // Deleted keys yield `None`.
+            (
+                vec![(3, (30, -2)), (2, (22, 1))],
+                vec![(2, 1), (3, 1), (5, 1)],
+                vec![
+                    ((2, Some(vec![(20, 1), (22, 1)])), 1),
+                    ((3, None), 1),
+                    ((5, None), 1),
+                ],
+                vec![((2, Some(22)), 1), ((3, None), 1), ((5, None), 1)],
+            ),
+            // No requests, no output.
+            (vec![(5, (50, 1))], vec![], vec![], vec![]),
+            (
+                vec![],
+                vec![(5, 1)],
+                vec![((5, Some(vec![(50, 1)])), 1)],
+                vec![((5, Some(50)), 1)],
+            ),
+        ];
+
+        for (mut input_tuples, mut request_tuples, expected_values, expected_latest) in steps {
+            input.append(&mut input_tuples);
+            requests.append(&mut request_tuples);
+            dbsp.step().unwrap();
+
+            assert_eq!(
+                values.consolidate(),
+                OrdZSet::from_tuples((), expected_values)
+            );
+            assert_eq!(
+                latest.consolidate(),
+                OrdZSet::from_tuples((), expected_latest)
+            );
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn lookup_test() {
+        test_lookup(1);
+        test_lookup(4);
+    }
+}
@@ -0,0 +1,119 @@
+//! Restoring collections checkpointed with an older schema.
+
+use crate::{
+    circuit::RootCircuit,
+    trace::{spine_fueled::Spine, Batch, Trace},
+    Stream,
+};
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: Batch<Time = ()>,
+{
+    /// Restores the contents of a collection from a trace `checkpoint` saved
+    /// with a different batch type, e.g., before a column with a default
+    /// value was added to the values of the collection.
+    ///
+    /// The checkpoint is migrated with
+    /// [`Spine::migrate_into`](`crate::trace::spine_fueled::Spine::migrate_into`),
+    /// applying `migrate` to each `(key, value, weight)` tuple in it.  The
+    /// migrated contents of the checkpoint are added to the first batch in
+    /// `self`.  All subsequent batches are passed through unmodified.
+    /// Hence the integral of the output stream is equal to the migrated
+    /// checkpoint plus the integral of `self`.
+    ///
+    /// In a multi-worker circuit, each worker restores its own checkpoint.
+    /// `checkpoint` must contain this worker's partition of the collection.
+    pub fn map_migrate<B1, F>(&self, checkpoint: Spine<B1>, migrate: F) -> Stream<RootCircuit, B>
+    where
+        B1: Batch<Time = ()>,
+        F: FnMut(B1::Key, B1::Val, B1::R) -> (B::Key, B::Val, B::R),
+    {
+        let mut restored = checkpoint.migrate_into::<B, _>(migrate).consolidate();
+
+        self.apply_named("MapMigrate", move |delta: &B| match restored.take() {
+            Some(restored) => restored.merge(delta),
+            None => delta.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        indexed_zset,
+        trace::{spine_fueled::Spine, Batch, Trace},
+        zset, OrdIndexedZSet, RootCircuit,
+    };
+
+    // Builds a trace in the old format, as it would be saved in a checkpoint.
+    fn old_checkpoint() -> Spine<OrdIndexedZSet<u64, i64, isize>> {
+        let mut checkpoint = Spine::new(None);
+        checkpoint.insert(OrdIndexedZSet::from_tuples(
+            (),
+            vec![((1, 10), 1), ((1, 11), 2), ((2, 20), -1)],
+        ));
+        checkpoint.insert(OrdIndexedZSet::from_tuples(
+            (),
+            vec![((1, 11), -1), ((3, 30), 1)],
+        ));
+        checkpoint
+    }
+
+    #[test]
+    fn map_migrate_restores_checkpoint() {
+        let (circuit, (mut input, output)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (i64, bool), isize>();
+
+            // Add a column with a default value to the restored collection.
+            let restored = input.map_migrate(old_checkpoint(), |k, v, w| (k, (v, false), w));
+
+            (input_handle, restored.integrate().output())
+        })
+        .unwrap();
+
+        input.append(&mut vec![(3, ((31, true), 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1 => { (10, false) => 1, (11, false) => 1 },
+                2 => { (20, false) => -1 },
+                3 => { (30, false) => 1, (31, true) => 1 }
+            }
+        );
+
+        // The checkpoint is only restored once.
+        input.append(&mut vec![(1, ((10, false), -1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! {
+                1 => { (11, false) => 1 },
+                2 => { (20, false) => -1 },
+                3 => { (30, false) => 1, (31, true) => 1 }
+            }
+        );
+    }
+
+    #[test]
+    fn map_migrate_consolidates() {
+        let (circuit, (mut input, output)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+
+            // Drop the value column and scale weights, consolidating tuples
+            // that only differ in their values.
+            let restored = input.map_migrate(old_checkpoint(), |k, _v, w| (k, (), w * 2));
+
+            (input_handle, restored.integrate().output())
+        })
+        .unwrap();
+
+        circuit.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 1 => 4, 2 => -2, 3 => 2 });
+
+        input.append(&mut vec![(2, 1)]);
+        circuit.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 1 => 4, 2 => -1, 3 => 2 });
+    }
+}
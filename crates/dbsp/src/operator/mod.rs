@@ -9,6 +9,9 @@ pub(crate) mod inspect;
 pub(crate) mod upsert;
 
 mod aggregate;
+mod bridge;
+mod buffered_input;
+mod change_stream;
 mod condition;
 mod consolidate;
 #[cfg(feature = "with-csv")]
@@ -16,40 +19,67 @@ mod csv;
 mod delta0;
 mod differentiate;
 mod distinct;
+pub(crate) mod export;
 mod filter_map;
 mod generator;
 mod index;
+mod index_by;
 mod input;
+mod inspect_batch;
 mod integrate;
 mod join;
 mod join_range;
+mod left_join;
+mod lookup;
+mod migrate;
+mod multiway_join;
 mod neg;
 mod output;
 mod plus;
+mod sample;
 mod semijoin;
+mod set_semantics;
 mod stream_fold;
 mod sum;
+mod suppress;
 pub mod time_series;
+mod topk;
 mod trace;
 mod z1;
 
 #[cfg(feature = "with-csv")]
 pub use self::csv::CsvSource;
-pub use aggregate::{Aggregator, Avg, Fold, Max, MaxSemigroup, Min, MinSemigroup};
+pub use aggregate::{
+    Aggregator, ArgMax, ArgMin, Avg, Fold, FoldFilter, FoldUntil, Max, MaxSemigroup, Min,
+    MinSemigroup, NoFilter, OptionSemigroup, QuantileSketch, RollupKey, SketchAggregator,
+    SketchSemigroup, TupleSemigroup,
+};
 pub use apply::Apply;
+pub use bridge::{bridge, BridgeReceiver, BridgeSender};
+pub use buffered_input::{AppendInput, BufferedInput, BufferedInputProducer, StepHint};
+pub use change_stream::ChangeEvent;
 pub use condition::Condition;
 pub use delta0::Delta0;
 pub use distinct::Distinct;
+#[cfg(feature = "with-serde")]
+pub use export::{
+    ExportFormat, ExportManifest, ExportTarget, ExportedFile, SnapshotExport, MANIFEST_FILE,
+};
 pub use filter_map::{FilterKeys, FilterMap, FilterVals, FlatMap, Map, MapKeys};
 pub use generator::{Generator, GeneratorNested};
 pub use index::Index;
+pub use index_by::SecondaryIndex;
 use input::Mailbox;
 pub use input::{CollectionHandle, InputHandle, UpsertHandle};
 pub use inspect::Inspect;
+pub use inspect_batch::InspectOptions;
 pub use join::Join;
 pub use join_range::StreamJoinRange;
+pub use multiway_join::{Join3Order, JoinStats};
 pub use neg::UnaryMinus;
 pub use output::OutputHandle;
 pub use plus::{Minus, Plus};
+pub use set_semantics::SetSemanticsViolation;
 pub use sum::Sum;
+pub use trace::CompactionBound;
 pub use z1::{DelayedFeedback, DelayedNestedFeedback, Z1Nested, Z1};
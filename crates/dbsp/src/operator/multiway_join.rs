@@ -0,0 +1,339 @@
+//! Three-way join with join order selected based on input cardinalities.
+
+use crate::{
+    algebra::{IndexedZSet, ZRingValue},
+    circuit::{Circuit, Stream, WithClock},
+    trace::{cursor::Cursor, BatchReader},
+    DBData, DBTimestamp, OrdZSet,
+};
+use std::{
+    iter::once,
+    sync::{
+        atomic::{AtomicIsize, Ordering},
+        Arc,
+    },
+};
+
+/// Order in which [`Stream::join3`] joins its inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Join3Order {
+    /// `(a ⋈ b) ⋈ c`.
+    AB,
+    /// `(a ⋈ c) ⋈ b`.
+    AC,
+    /// `(b ⋈ c) ⋈ a`.
+    BC,
+}
+
+impl Join3Order {
+    /// Choose the join order for inputs with estimated cardinalities
+    /// `hints`.
+    ///
+    /// The largest input is joined last, so that the intermediate result,
+    /// whose trace is maintained by the second join, is computed from the two
+    /// smallest inputs.  When several inputs have the same largest hint, the
+    /// last of them is joined last, e.g., equal hints yield `(a ⋈ b) ⋈ c`.
+    pub fn from_hints(hints: [usize; 3]) -> Self {
+        match (0..3).max_by_key(|&i| hints[i]) {
+            Some(0) => Self::BC,
+            Some(1) => Self::AC,
+            _ => Self::AB,
+        }
+    }
+
+    /// Name of the circuit region that contains the join.
+    pub fn region_name(&self) -> &'static str {
+        match self {
+            Self::AB => "join3: (a ⋈ b) ⋈ c",
+            Self::AC => "join3: (a ⋈ c) ⋈ b",
+            Self::BC => "join3: (b ⋈ c) ⋈ a",
+        }
+    }
+}
+
+/// Cardinalities of the inputs of [`Stream::join3_with_stats`] observed at
+/// runtime.
+///
+/// The cardinality of each input is estimated as the number of tuples with
+/// positive weights minus the number of tuples with negative weights added
+/// to the input by all workers so far, which is exact for inputs that are
+/// sets.  The estimates can be used as hints when constructing the circuit
+/// again, e.g., after a restart.
+#[derive(Clone, Debug, Default)]
+pub struct JoinStats(Arc<[AtomicIsize; 3]>);
+
+impl JoinStats {
+    /// Create an empty set of statistics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Estimated cardinalities of the inputs, in the format expected by
+    /// [`Stream::join3`].
+    pub fn hints(&self) -> [usize; 3] {
+        let mut hints = [0; 3];
+        for (hint, cardinality) in hints.iter_mut().zip(self.0.iter()) {
+            *hint = cardinality.load(Ordering::Acquire).max(0) as usize;
+        }
+        hints
+    }
+
+    /// Record changes to input `input` in `batch`.
+    fn record<B>(&self, input: usize, batch: &B)
+    where
+        B: BatchReader,
+        B::R: ZRingValue,
+    {
+        let mut delta = 0;
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                if cursor.weight().ge0() {
+                    delta += 1;
+                } else {
+                    delta -= 1;
+                }
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        self.0[input].fetch_add(delta, Ordering::AcqRel);
+    }
+}
+
+impl<C, I1> Stream<C, I1>
+where
+    C: Circuit,
+    <C as WithClock>::Time: DBTimestamp,
+    I1: IndexedZSet + Send,
+    I1::R: ZRingValue,
+{
+    /// Incrementally join three streams of batches indexed by the same key.
+    ///
+    /// Computes the join of `self` (`a`), `other2` (`b`) and `other3` (`c`),
+    /// applying `join_func` to each key and triple of values associated with
+    /// the key in all three inputs.  The join is evaluated as a pair of
+    /// binary joins, where the second join maintains the trace of the result
+    /// of the first join.  The order of the joins is chosen at circuit
+    /// construction time based on `hints`, which contain estimated
+    /// cardinalities of `a`, `b` and `c` (see [`Join3Order::from_hints`]).
+    /// The hints only affect performance: the result of the join is the same
+    /// regardless of the chosen order.
+    ///
+    /// The join is constructed inside a circuit region named after the chosen
+    /// join order (see [`Join3Order::region_name`]).
+    pub fn join3<I2, I3, F, V>(
+        &self,
+        other2: &Stream<C, I2>,
+        other3: &Stream<C, I3>,
+        hints: [usize; 3],
+        join_func: F,
+    ) -> Stream<C, OrdZSet<V, I1::R>>
+    where
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        I3: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val, &I3::Val) -> V + Clone + 'static,
+        V: DBData,
+    {
+        let order = Join3Order::from_hints(hints);
+
+        self.circuit().region(order.region_name(), || match order {
+            Join3Order::AB => self
+                .join_index(other2, |k, v1, v2| {
+                    once((k.clone(), (v1.clone(), v2.clone())))
+                })
+                .join(other3, move |k, (v1, v2), v3| join_func(k, v1, v2, v3)),
+            Join3Order::AC => self
+                .join_index(other3, |k, v1, v3| {
+                    once((k.clone(), (v1.clone(), v3.clone())))
+                })
+                .join(other2, move |k, (v1, v3), v2| join_func(k, v1, v2, v3)),
+            Join3Order::BC => {
+                let bc = other2.join_index(other3, |k, v2, v3| {
+                    once((k.clone(), (v2.clone(), v3.clone())))
+                });
+                self.join(&bc, move |k, v1, (v2, v3)| join_func(k, v1, v2, v3))
+            }
+        })
+    }
+
+    /// Like [`Self::join3`], but additionally records the cardinalities of
+    /// the inputs observed at runtime in `stats`.
+    pub fn join3_with_stats<I2, I3, F, V>(
+        &self,
+        other2: &Stream<C, I2>,
+        other3: &Stream<C, I3>,
+        hints: [usize; 3],
+        stats: &JoinStats,
+        join_func: F,
+    ) -> Stream<C, OrdZSet<V, I1::R>>
+    where
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        I3: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val, &I3::Val) -> V + Clone + 'static,
+        V: DBData,
+    {
+        let stats1 = stats.clone();
+        let stats2 = stats.clone();
+        let stats3 = stats.clone();
+
+        self.inspect(move |batch| stats1.record(0, batch)).join3(
+            &other2.inspect(move |batch| stats2.record(1, batch)),
+            &other3.inspect(move |batch| stats3.record(2, batch)),
+            hints,
+            join_func,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Join3Order, JoinStats};
+    use crate::{
+        circuit::trace::CircuitEvent, trace::Batch, CollectionHandle, DBSPHandle, OrdZSet,
+        OutputHandle, Runtime,
+    };
+    use std::sync::{Arc, Mutex};
+
+    type InputHandle = CollectionHandle<u64, (u64, isize)>;
+    type Output = OrdZSet<(u64, u64, u64, u64), isize>;
+
+    #[test]
+    fn join_order_from_hints() {
+        assert_eq!(Join3Order::from_hints([1, 1, 1]), Join3Order::AB);
+        assert_eq!(Join3Order::from_hints([1, 2, 3]), Join3Order::AB);
+        assert_eq!(Join3Order::from_hints([1, 3, 2]), Join3Order::AC);
+        assert_eq!(Join3Order::from_hints([3, 2, 1]), Join3Order::BC);
+        assert_eq!(Join3Order::from_hints([3, 3, 1]), Join3Order::AC);
+    }
+
+    fn join3_circuit(
+        workers: usize,
+        hints: [usize; 3],
+        stats: JoinStats,
+    ) -> (
+        DBSPHandle,
+        (
+            InputHandle,
+            InputHandle,
+            InputHandle,
+            OutputHandle<Output>,
+            Arc<Mutex<Vec<String>>>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, move |circuit| {
+            // Record the names of regions created by `join3`.
+            let regions = Arc::new(Mutex::new(Vec::new()));
+            let regions_clone = regions.clone();
+            circuit.register_circuit_event_handler("regions", move |event| {
+                if let CircuitEvent::PushRegion { name, .. } = event {
+                    if name.starts_with("join3") {
+                        regions_clone.lock().unwrap().push(name.to_string());
+                    }
+                }
+            });
+
+            let (a, a_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (b, b_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (c, c_handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+
+            let output = a
+                .join3_with_stats(&b, &c, hints, &stats, |k, va, vb, vc| (*k, *va, *vb, *vc))
+                .integrate()
+                .output();
+
+            circuit.unregister_circuit_event_handler("regions");
+
+            (a_handle, b_handle, c_handle, output, regions)
+        })
+        .unwrap()
+    }
+
+    // Computes the join of `a`, `b`, and `c` using nested loops.
+    fn reference_join(
+        a: &[(u64, (u64, isize))],
+        b: &[(u64, (u64, isize))],
+        c: &[(u64, (u64, isize))],
+    ) -> Output {
+        let mut tuples = Vec::new();
+
+        for (ka, (va, wa)) in a {
+            for (kb, (vb, wb)) in b {
+                for (kc, (vc, wc)) in c {
+                    if ka == kb && kb == kc {
+                        tuples.push(((*ka, *va, *vb, *vc), wa * wb * wc));
+                    }
+                }
+            }
+        }
+
+        Output::from_tuples((), tuples)
+    }
+
+    fn join3_test(workers: usize, hints: [usize; 3], expected_order: Join3Order) {
+        let stats = JoinStats::new();
+        let (mut dbsp, (mut a, mut b, mut c, output, regions)) =
+            join3_circuit(workers, hints, stats.clone());
+
+        assert_eq!(
+            *regions.lock().unwrap(),
+            vec![expected_order.region_name().to_string()]
+        );
+
+        let mut a_tuples = Vec::new();
+        let mut b_tuples = Vec::new();
+        let mut c_tuples = Vec::new();
+
+        for step in 0..5u64 {
+            let mut a_step: Vec<_> = (0..10).map(|k| (k, (step, 1))).collect();
+            let mut b_step: Vec<_> = (0..100).map(|i| (i % 10, (step * 100 + i, 1))).collect();
+            let mut c_step: Vec<_> = (0..5).map(|k| (k * 2, (step, 1))).collect();
+
+            // Retract some of the previous inputs.
+            if step > 0 {
+                a_step.push((1, (step - 1, -1)));
+                c_step.push((2, (step - 1, -1)));
+            }
+
+            a_tuples.extend(a_step.iter().cloned());
+            b_tuples.extend(b_step.iter().cloned());
+            c_tuples.extend(c_step.iter().cloned());
+
+            a.append(&mut a_step);
+            b.append(&mut b_step);
+            c.append(&mut c_step);
+            dbsp.step().unwrap();
+
+            let expected = reference_join(&a_tuples, &b_tuples, &c_tuples);
+            assert!(!expected.is_empty());
+            assert_eq!(output.consolidate(), expected);
+        }
+
+        dbsp.kill().unwrap();
+
+        // Statistics reflect the sizes of the inputs: `b` is the largest input,
+        // so it's joined last.
+        assert_eq!(stats.hints(), [46, 500, 21]);
+        assert_eq!(Join3Order::from_hints(stats.hints()), Join3Order::AC);
+    }
+
+    #[test]
+    fn join3_ab() {
+        join3_test(1, [1, 1, 1], Join3Order::AB);
+        join3_test(4, [10, 20, 30], Join3Order::AB);
+    }
+
+    #[test]
+    fn join3_ac() {
+        join3_test(1, [10, 1000, 20], Join3Order::AC);
+        join3_test(4, [10, 1000, 20], Join3Order::AC);
+    }
+
+    #[test]
+    fn join3_bc() {
+        join3_test(1, [1000, 10, 20], Join3Order::BC);
+        join3_test(4, [1000, 10, 20], Join3Order::BC);
+    }
+}
@@ -1,17 +1,23 @@
 use super::Mailbox;
 use crate::{
     circuit::{
+        metrics::{MetricLabels, OUTPUT_TUPLES},
         operator_traits::{Operator, SinkOperator},
         LocalStoreMarker, OwnershipPreference, RootCircuit, Scope,
     },
-    trace::{Batch, Spine, Trace},
-    Circuit, Runtime, Stream,
+    trace::{Batch, BatchReader, Builder, Cursor, Spine, Trace},
+    Circuit, NumEntries, Runtime, Stream,
 };
 use std::{
     borrow::Cow,
     hash::{Hash, Hasher},
     marker::PhantomData,
-    sync::Arc,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    time::{Duration, Instant},
 };
 use typedmap::TypedMapKey;
 
@@ -25,9 +31,15 @@ where
     /// This API makes the result of the computation performed by the circuit
     /// available to the outside world.  At each clock cycle, the contents
     /// of the stream is buffered inside the handle and can be read using
-    /// the [`OutputHandle`] API.
-    pub fn output(&self) -> OutputHandle<T> {
-        let (output, output_handle) = Output::new();
+    /// the [`OutputHandle`] API.  The number of entries in each value
+    /// written to the handle is counted in the
+    /// [`OUTPUT_TUPLES`](`crate::circuit::metrics::OUTPUT_TUPLES`) metric.
+    pub fn output(&self) -> OutputHandle<T>
+    where
+        T: NumEntries,
+    {
+        let (output, output_handle) =
+            Output::new(MetricLabels::stream("Output", self.origin_node_id()));
         self.circuit().add_sink(output, self);
         output_handle
     }
@@ -77,12 +89,24 @@ where
     type Value = OutputHandle<T>;
 }
 
+/// Callback that forwards a value produced by a worker to a subscription
+/// created by [`OutputHandle::subscribe_range`].  Returns `false` if the
+/// subscription has been dropped.
+type Subscriber<T> = Box<dyn Fn(usize, &T) -> bool + Send>;
+
 struct OutputHandleInternal<T> {
     mailbox: Vec<Mailbox<Option<T>>>,
+    subscribers: Mutex<Vec<Subscriber<T>>>,
+    // Number of elements in `subscribers`, used to skip locking
+    // `subscribers` when there are none.
+    num_subscribers: AtomicUsize,
+    // Signalled every time a worker writes to its mailbox.
+    published: (Mutex<()>, Condvar),
+    labels: MetricLabels,
 }
 
 impl<T> OutputHandleInternal<T> {
-    fn new(num_workers: usize) -> Self {
+    fn new(num_workers: usize, labels: MetricLabels) -> Self {
         assert_ne!(num_workers, 0);
 
         let mut mailbox = Vec::with_capacity(num_workers);
@@ -90,15 +114,65 @@ impl<T> OutputHandleInternal<T> {
             mailbox.push(Mailbox::new());
         }
 
-        Self { mailbox }
+        Self {
+            mailbox,
+            subscribers: Mutex::new(Vec::new()),
+            num_subscribers: AtomicUsize::new(0),
+            published: (Mutex::new(()), Condvar::new()),
+            labels,
+        }
+    }
+
+    /// Store the value produced by `worker` in its mailbox after passing
+    /// it to all subscribers.  Subscriptions that have been dropped are
+    /// removed.
+    fn publish(&self, worker: usize, val: T) {
+        if self.num_subscribers.load(Ordering::Acquire) != 0 {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|subscriber| subscriber(worker, &val));
+            self.num_subscribers
+                .store(subscribers.len(), Ordering::Release);
+        }
+        self.mailbox[worker].set(Some(val));
+
+        // Acquire the lock to avoid racing with `wait_ready`, which checks
+        // mailboxes while holding it.
+        let _guard = self.published.0.lock().unwrap();
+        self.published.1.notify_all();
     }
 
-    fn take_from_worker(&self, worker: usize) -> Option<T> {
-        self.mailbox[worker].take()
+    /// `true` if every worker's mailbox contains a value.
+    fn ready(&self) -> bool {
+        self.mailbox
+            .iter()
+            .all(|mailbox| mailbox.map(Option::is_some))
     }
 
-    fn mailbox(&self, worker: usize) -> &Mailbox<Option<T>> {
-        &self.mailbox[worker]
+    fn wait_ready(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut guard = self.published.0.lock().unwrap();
+
+        loop {
+            if self.ready() {
+                return true;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+
+            guard = self
+                .published
+                .1
+                .wait_timeout(guard, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    fn take_from_worker(&self, worker: usize) -> Option<T> {
+        self.mailbox[worker].take()
     }
 }
 
@@ -126,6 +200,12 @@ impl<T> OutputHandleInternal<T> {
 /// leaving the mailbox empty.  If the value is not read, it gets
 /// overwritten at the next clock cycle (i.e., during the next call to
 /// `step`).
+///
+/// Workers write to the mailbox as soon as the stream is computed, which
+/// can happen before the end of the clock cycle.  Use
+/// [`wait_ready`](`OutputHandle::wait_ready`) to read the output as soon
+/// as it is available, e.g., from a thread other than the one running the
+/// circuit.
 #[derive(Clone)]
 pub struct OutputHandle<T>(Arc<OutputHandleInternal<T>>);
 
@@ -133,9 +213,9 @@ impl<T> OutputHandle<T>
 where
     T: Send + Clone + 'static,
 {
-    fn new() -> Self {
+    fn new(labels: MetricLabels) -> Self {
         match Runtime::runtime() {
-            None => Self(Arc::new(OutputHandleInternal::new(1))),
+            None => Self(Arc::new(OutputHandleInternal::new(1, labels))),
             Some(runtime) => {
                 let output_id = runtime.sequence_next(Runtime::worker_index());
 
@@ -143,7 +223,10 @@ where
                     .local_store()
                     .entry(OutputId::new(output_id))
                     .or_insert_with(|| {
-                        Self(Arc::new(OutputHandleInternal::new(runtime.num_workers())))
+                        Self(Arc::new(OutputHandleInternal::new(
+                            runtime.num_workers(),
+                            labels,
+                        )))
                     })
                     .value()
                     .clone()
@@ -151,10 +234,6 @@ where
         }
     }
 
-    fn mailbox(&self, worker: usize) -> &Mailbox<Option<T>> {
-        self.0.mailbox(worker)
-    }
-
     /// Read the value produced by `worker` worker thread during the last
     /// clock cycle.
     ///
@@ -174,6 +253,26 @@ where
         self.0.take_from_worker(worker)
     }
 
+    /// Block until all worker threads have written a value to the handle.
+    ///
+    /// Returns `true` as soon as every worker's mailbox contains a value
+    /// that has not been read yet, or `false` if this does not happen
+    /// within `timeout`.  Workers write to the handle as soon as they finish
+    /// computing the stream, without waiting for the rest of the circuit, so
+    /// this method can be used to read the output of the current clock
+    /// cycle while [`DBSPHandle::step`](`crate::DBSPHandle::step`) is still
+    /// running, e.g., when the stream is computed ahead of other work in
+    /// the circuit (see
+    /// [`Stream::set_priority`](`crate::Stream::set_priority`)).
+    ///
+    /// Since values remain in the mailboxes until read, the caller must
+    /// read the output of each clock cycle (e.g., using
+    /// [`take_from_all`](`Self::take_from_all`)) before waiting for the next
+    /// one.
+    pub fn wait_ready(&self, timeout: Duration) -> bool {
+        self.0.wait_ready(timeout)
+    }
+
     /// Read values produced by all worker threads during the last
     /// clock cycle.
     ///
@@ -217,32 +316,100 @@ where
     /// to `take_from_worker` return `None`. `consolidate` skips `None` results
     /// when computing the consolidated batch.
     pub fn consolidate(&self) -> T {
-        let batches = self.take_from_all();
         let mut spine = Spine::new(None);
 
-        for batch in batches {
-            spine.insert(batch);
+        for worker in 0..self.0.mailbox.len() {
+            if let Some(batch) = self.take_from_worker(worker) {
+                spine.insert(batch);
+            }
         }
 
         spine.consolidate().unwrap_or_else(|| T::empty(()))
     }
+
+    /// Subscribe to updates to a range of keys.
+    ///
+    /// Returns a new output handle that receives the subset of updates
+    /// produced by each worker during each clock cycle whose keys fall in
+    /// `range`.  The subset is extracted by seeking a cursor to the start of
+    /// the range in the batch produced by the worker, so the cost of a
+    /// subscription is proportional to the number of updates in the range
+    /// rather than the size of the batch.  Subscriptions read directly from
+    /// the batch produced by the worker, so multiple, possibly overlapping,
+    /// subscriptions can be attached to the same handle without copying the
+    /// batch.
+    ///
+    /// The subscription starts receiving updates at the next clock cycle.
+    /// It is independent of `self`: reading from one of them does not
+    /// affect the contents of the other.  The subscription is cancelled
+    /// when all clones of the returned handle are dropped.
+    pub fn subscribe_range(&self, range: Range<T::Key>) -> OutputHandle<T> {
+        let subscription = Self(Arc::new(OutputHandleInternal::new(
+            self.0.mailbox.len(),
+            self.0.labels.clone(),
+        )));
+
+        // Don't keep the subscription alive after the user drops it.
+        let weak_subscription: Weak<OutputHandleInternal<T>> = Arc::downgrade(&subscription.0);
+
+        let mut subscribers = self.0.subscribers.lock().unwrap();
+        subscribers.push(Box::new(move |worker, batch: &T| {
+            if let Some(subscription) = weak_subscription.upgrade() {
+                subscription.publish(worker, batch_range(batch, &range));
+                true
+            } else {
+                false
+            }
+        }));
+        self.0
+            .num_subscribers
+            .store(subscribers.len(), Ordering::Release);
+
+        subscription
+    }
+}
+
+/// Extract updates with keys in `range` from `batch`.
+fn batch_range<B>(batch: &B, range: &Range<B::Key>) -> B
+where
+    B: Batch<Time = ()>,
+{
+    let mut builder = B::Builder::new_builder(());
+    let mut cursor = batch.cursor();
+
+    cursor.seek_key(&range.start);
+    while cursor.key_valid() && cursor.key() < &range.end {
+        while cursor.val_valid() {
+            builder.push((
+                B::item_from(cursor.key().clone(), cursor.val().clone()),
+                cursor.weight(),
+            ));
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    builder.done()
 }
 
 /// Sink operator that stores the contents of its input stream in
 /// an `OutputHandle`.
 struct Output<T> {
-    mailbox: Mailbox<Option<T>>,
+    worker: usize,
+    handle: OutputHandle<T>,
 }
 
 impl<T> Output<T>
 where
     T: Clone + Send + 'static,
 {
-    fn new() -> (Self, OutputHandle<T>) {
-        let handle = OutputHandle::new();
-        let mailbox = handle.mailbox(Runtime::worker_index()).clone();
+    fn new(labels: MetricLabels) -> (Self, OutputHandle<T>) {
+        let handle = OutputHandle::new(labels);
 
-        let output = Self { mailbox };
+        let output = Self {
+            worker: Runtime::worker_index(),
+            handle: handle.clone(),
+        };
 
         (output, handle)
     }
@@ -261,16 +428,35 @@ where
     }
 }
 
+impl<T> Output<T>
+where
+    T: NumEntries,
+{
+    fn count_tuples(&self, val: &T) {
+        metric_counter!(
+            OUTPUT_TUPLES,
+            val.num_entries_deep(),
+            self.handle.0.labels.for_worker(self.worker)
+        );
+    }
+}
+
 impl<T> SinkOperator<T> for Output<T>
 where
-    T: Clone + 'static,
+    T: Clone + NumEntries + 'static,
 {
     fn eval(&mut self, val: &T) {
-        self.mailbox.set(Some(val.clone()));
+        if !Runtime::bootstrap_in_progress() {
+            self.count_tuples(val);
+            self.handle.0.publish(self.worker, val.clone());
+        }
     }
 
     fn eval_owned(&mut self, val: T) {
-        self.mailbox.set(Some(val));
+        if !Runtime::bootstrap_in_progress() {
+            self.count_tuples(&val);
+            self.handle.0.publish(self.worker, val);
+        }
     }
 
     fn input_preference(&self) -> OwnershipPreference {
@@ -280,7 +466,16 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::{trace::Batch, OrdZSet, Runtime};
+    use crate::{
+        operator::FilterMap,
+        trace::{Batch, BatchReader, Cursor},
+        OrdIndexedZSet, OrdZSet, OutputHandle, Runtime,
+    };
+    use std::{
+        sync::{atomic::Ordering, Arc, Condvar, Mutex},
+        thread,
+        time::Duration,
+    };
 
     #[test]
     fn test_output_handle() {
@@ -308,4 +503,191 @@ mod test {
 
         dbsp.kill().unwrap();
     }
+
+    #[test]
+    fn test_wait_ready() {
+        // Set once the output of the cheap view has been observed outside the
+        // circuit.
+        let latch = Arc::new((Mutex::new(false), Condvar::new()));
+        let latch_clone = latch.clone();
+
+        let (mut dbsp, (mut input, cheap, expensive)) = Runtime::init_circuit(4, move |circuit| {
+            let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+
+            let cheap = stream.map(|x| x + 1).set_priority(1).output();
+
+            // Blocks until the latch is set and outputs `true`, or outputs
+            // `false` on timeout.
+            let latch = latch_clone.clone();
+            let expensive = stream
+                .apply(move |_| {
+                    let (lock, cvar) = &*latch;
+                    let (observed, _) = cvar
+                        .wait_timeout_while(
+                            lock.lock().unwrap(),
+                            Duration::from_secs(10),
+                            |observed| !*observed,
+                        )
+                        .unwrap();
+                    *observed
+                })
+                .output();
+
+            (handle, cheap, expensive)
+        })
+        .unwrap();
+
+        for step in 0..3u64 {
+            *latch.0.lock().unwrap() = false;
+
+            let observer = {
+                let cheap = cheap.clone();
+                let latch = latch.clone();
+                thread::spawn(move || {
+                    assert!(cheap.wait_ready(Duration::from_secs(10)));
+                    let output = cheap.consolidate();
+
+                    *latch.0.lock().unwrap() = true;
+                    latch.1.notify_all();
+
+                    output
+                })
+            };
+
+            input.append(&mut (0..10).map(|x| (step * 10 + x, 1)).collect());
+            dbsp.step().unwrap();
+
+            assert_eq!(
+                observer.join().unwrap(),
+                OrdZSet::from_tuples((), (0..10).map(|x| (step * 10 + x + 1, 1)).collect())
+            );
+            assert_eq!(expensive.take_from_all(), vec![true; 4]);
+
+            // All values have been read.
+            assert!(!cheap.wait_ready(Duration::from_millis(10)));
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    type TestBatch = OrdIndexedZSet<u64, u64, isize>;
+
+    fn keys(batch: &TestBatch) -> Vec<u64> {
+        let mut keys = Vec::new();
+        let mut cursor = batch.cursor();
+        while cursor.key_valid() {
+            keys.push(*cursor.key());
+            cursor.step_key();
+        }
+        keys
+    }
+
+    #[test]
+    fn test_subscribe_range() {
+        let (mut dbsp, (mut input, output)) = Runtime::init_circuit(4, |circuit| {
+            let (stream, handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let output = stream.output();
+
+            (handle, output)
+        })
+        .unwrap();
+
+        // Overlapping, adjacent and empty ranges.
+        let ranges = [10..20, 15..40, 40..41, 90..200, 200..300, 50..50];
+        let subscriptions: Vec<_> = ranges
+            .iter()
+            .map(|range| output.subscribe_range(range.clone()))
+            .collect();
+
+        for step in 0..5u64 {
+            // Updates to keys `0..100`, partitioned across workers, so the
+            // subscribed ranges span multiple batches.
+            let tuples: Vec<_> = (0..100)
+                .flat_map(|k| {
+                    (0..=k % 3).map(move |v| ((k, step * 10 + v), if k % 2 == 0 { 1 } else { -1 }))
+                })
+                .collect();
+
+            input.append(&mut tuples.iter().map(|&((k, v), w)| (k, (v, w))).collect());
+            dbsp.step().unwrap();
+
+            for (range, subscription) in ranges.iter().zip(subscriptions.iter()) {
+                let batches = subscription.take_from_all();
+                assert_eq!(batches.len(), 4);
+
+                // Updates outside of the range never reach the subscription.
+                for batch in batches.iter() {
+                    assert!(keys(batch).iter().all(|k| range.contains(k)));
+                }
+
+                let expected = TestBatch::from_tuples(
+                    (),
+                    tuples
+                        .iter()
+                        .filter(|((k, _), _)| range.contains(k))
+                        .cloned()
+                        .collect(),
+                );
+                let actual = batches
+                    .into_iter()
+                    .fold(TestBatch::empty(()), |acc, batch| acc.merge(&batch));
+                assert_eq!(actual, expected);
+            }
+
+            // Subscriptions don't affect the contents of the parent handle.
+            assert_eq!(output.consolidate(), TestBatch::from_tuples((), tuples));
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn test_drop_subscription() {
+        let (mut dbsp, (mut input, output)) = Runtime::init_circuit(2, |circuit| {
+            let (stream, handle) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let output = stream.output();
+
+            (handle, output)
+        })
+        .unwrap();
+
+        let num_subscribers = |output: &OutputHandle<TestBatch>| {
+            (
+                output.0.num_subscribers.load(Ordering::Acquire),
+                output.0.subscribers.lock().unwrap().len(),
+            )
+        };
+
+        let subscription1 = output.subscribe_range(0..10);
+        let subscription2 = output.subscribe_range(5..20);
+        assert_eq!(num_subscribers(&output), (2, 2));
+
+        // Clones keep the subscription alive.
+        let subscription1_clone = subscription1.clone();
+        drop(subscription1);
+
+        input.append(&mut vec![(1, (1, 1)), (7, (7, 1)), (15, (15, 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(num_subscribers(&output), (2, 2));
+        assert_eq!(
+            subscription1_clone.consolidate(),
+            TestBatch::from_tuples((), vec![((1, 1), 1), ((7, 7), 1)])
+        );
+
+        // Dropped subscriptions are removed at the next clock cycle.
+        drop(subscription1_clone);
+        input.append(&mut vec![(8, (8, 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(num_subscribers(&output), (1, 1));
+        assert_eq!(
+            subscription2.consolidate(),
+            TestBatch::from_tuples((), vec![((8, 8), 1)])
+        );
+
+        drop(subscription2);
+        dbsp.step().unwrap();
+        assert_eq!(num_subscribers(&output), (0, 0));
+
+        dbsp.kill().unwrap();
+    }
 }
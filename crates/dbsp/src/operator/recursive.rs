@@ -5,7 +5,7 @@ use crate::{
     circuit::{schedule::Error as SchedulerError, ChildCircuit, Circuit, Stream, WithClock},
     operator::DelayedFeedback,
     trace::Spine,
-    DBTimestamp,
+    DBData, DBTimestamp, OrdZSet, RootCircuit,
 };
 use impl_trait_for_tuples::impl_for_tuples;
 use size_of::SizeOf;
@@ -293,14 +293,85 @@ where
     }
 }
 
+impl<Z> Stream<RootCircuit, Z>
+where
+    Z: IndexedZSet + Send,
+    Z::R: ZRingValue,
+    Spine<Z>: SizeOf,
+{
+    /// Compute the least fixed point of the equation `x = self + f(x)`.
+    ///
+    /// This is a simpler form of [`ChildCircuit::recursive`] for the common
+    /// case of a single recursive relation seeded by `self`.  It creates a
+    /// nested circuit, imports `self` into it, and evaluates
+    /// `x = distinct(δ0(self) + f(x))` until a fixed point is reached.  The
+    /// closure `f` gets the recursive stream `x` inside the nested circuit
+    /// and returns the derived facts.  Other streams from the root circuit
+    /// can be imported into the nested circuit using
+    /// [`delta0`](`Stream::delta0`), e.g., `edges.delta0(x.circuit())`.
+    ///
+    /// Like `self`, the output stream contains changes: at each clock cycle
+    /// it outputs the difference between the fixed points computed for the
+    /// current and the previous value of the integral of `self` and the
+    /// imported streams, so both insertions and deletions are propagated
+    /// incrementally.
+    ///
+    /// See [`transitive_closure`](`Self::transitive_closure`) for an
+    /// example.
+    pub fn fixed_point<F>(&self, f: F) -> Result<Stream<RootCircuit, Z>, SchedulerError>
+    where
+        F: FnOnce(&Stream<ChildCircuit<RootCircuit>, Z>) -> Stream<ChildCircuit<RootCircuit>, Z>,
+    {
+        self.circuit().recursive(|child, x: Stream<_, Z>| {
+            let seed = self.delta0(child);
+            Ok(seed.plus(&f(&x)))
+        })
+    }
+}
+
+impl<N, R> Stream<RootCircuit, OrdZSet<(N, N), R>>
+where
+    N: DBData,
+    R: ZRingValue,
+    Spine<OrdZSet<(N, N), R>>: SizeOf,
+{
+    /// Compute the transitive closure of a graph.
+    ///
+    /// `self` is a stream of changes to the set of edges of a graph, where
+    /// each edge is a `(from, to)` pair.  Returns a stream of changes to the
+    /// set of pairs `(from, to)` such that `to` is reachable from `from` via
+    /// a path of one or more edges.  Edges with positive weights are
+    /// considered present in the graph.
+    ///
+    /// This is implemented on top of [`fixed_point`](`Self::fixed_point`) as
+    /// `paths = edges + paths ⋈ edges`.
+    pub fn transitive_closure(
+        &self,
+    ) -> Result<Stream<RootCircuit, OrdZSet<(N, N), R>>, SchedulerError> {
+        let edges = self.index::<N, N>();
+
+        self.fixed_point(|paths| {
+            let edges = edges.delta0(paths.circuit());
+
+            paths
+                .index_with(|(from, via)| (via.clone(), from.clone()))
+                .join(&edges, |_via, from, to| (from.clone(), to.clone()))
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
         operator::{FilterMap, Generator},
-        trace::ord::OrdZSet,
+        trace::{ord::OrdZSet, Batch},
         zset, Circuit, RootCircuit, Stream,
     };
-    use std::vec;
+    use proptest::{collection, prelude::*};
+    use std::{
+        collections::{BTreeSet, VecDeque},
+        vec,
+    };
 
     #[test]
     fn reachability() {
@@ -430,4 +501,83 @@ mod test {
             root.step().unwrap();
         }
     }
+
+    // Non-incremental reference implementation of transitive closure: BFS
+    // from every node.
+    fn closure_bfs(edges: &BTreeSet<(u32, u32)>) -> BTreeSet<(u32, u32)> {
+        let mut closure = BTreeSet::new();
+
+        for &(start, _) in edges {
+            let mut queue = VecDeque::from([start]);
+            let mut visited = BTreeSet::new();
+
+            while let Some(node) = queue.pop_front() {
+                for &(_, to) in edges.range((node, u32::MIN)..=(node, u32::MAX)) {
+                    if visited.insert(to) {
+                        queue.push_back(to);
+                    }
+                }
+            }
+
+            closure.extend(visited.into_iter().map(|to| (start, to)));
+        }
+
+        closure
+    }
+
+    // Each step toggles the presence of the listed edges in the graph.
+    fn test_transitive_closure(steps: Vec<Vec<(u32, u32)>>) {
+        let (circuit, (mut input, output)) = RootCircuit::build(|circuit| {
+            let (edges, input) = circuit.add_input_zset::<(u32, u32), isize>();
+            let paths = edges.transitive_closure().unwrap();
+
+            (input, paths.integrate().output())
+        })
+        .unwrap();
+
+        let mut edges = BTreeSet::new();
+
+        for step in steps {
+            let mut changes = Vec::new();
+            for edge in step.into_iter().collect::<BTreeSet<_>>() {
+                if edges.remove(&edge) {
+                    changes.push((edge, -1));
+                } else {
+                    edges.insert(edge);
+                    changes.push((edge, 1));
+                }
+            }
+
+            input.append(&mut changes);
+            circuit.step().unwrap();
+
+            let expected = closure_bfs(&edges)
+                .into_iter()
+                .map(|path| (path, 1))
+                .collect();
+            assert_eq!(output.consolidate(), OrdZSet::from_keys((), expected));
+        }
+    }
+
+    #[test]
+    fn transitive_closure() {
+        test_transitive_closure(vec![
+            vec![(1, 2), (2, 3)],
+            // Close a cycle.
+            vec![(3, 1)],
+            // Break the cycle and add an edge.
+            vec![(2, 3), (3, 4)],
+            // Restore the cycle and add a self-loop.
+            vec![(2, 3), (4, 4)],
+            vec![(1, 2), (3, 1), (3, 4)],
+            vec![(2, 3), (4, 4)],
+        ]);
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_transitive_closure(steps in collection::vec(collection::vec((0..6u32, 0..6u32), 0..6), 0..12)) {
+            test_transitive_closure(steps);
+        }
+    }
 }
@@ -0,0 +1,355 @@
+//! Operators that sample the contents of a stream.
+
+use crate::{
+    algebra::{IndexedZSet, ZRingValue},
+    circuit::Circuit,
+    trace::{Batch, BatchReader, Builder, Cursor},
+    OrdIndexedZSet, RootCircuit, Stream,
+};
+use std::hash::{Hash, Hasher};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Pseudo-random priority of a `(key, value)` pair under `seed`.
+///
+/// The priority only depends on `seed` and the contents of the pair, so an
+/// insertion and a later retraction of the same pair get the same priority
+/// regardless of the step or the worker they arrive at.
+fn sample_priority<K, V>(seed: u64, key: &K, val: &V) -> u64
+where
+    K: Hash,
+    V: Hash,
+{
+    let mut hasher = Xxh3::with_seed(seed);
+    key.hash(&mut hasher);
+    val.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Maps a priority to a number uniformly distributed in `[0, 1)`.
+fn priority_to_unit(priority: u64) -> f64 {
+    (priority >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl<C, B> Stream<C, B>
+where
+    C: Circuit,
+    B: Batch<Time = ()>,
+{
+    /// Bernoulli sample of the stream.
+    ///
+    /// Keeps each `(key, value)` pair in the input independently with
+    /// probability `p`, preserving its weight.
+    ///
+    /// The decision whether to keep a pair is made by hashing the pair with
+    /// `seed`, so the same pair is either always kept or always dropped.  In
+    /// particular, a retraction of a sampled pair is also sampled, which
+    /// makes this a linear operator: sampling a stream of changes yields the
+    /// changes to the sample of the accumulated stream.  For a fixed `seed`,
+    /// the output does not depend on the number of workers or on how the
+    /// input is split into batches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is not in the range `[0, 1]`.
+    pub fn sample_bernoulli(&self, p: f64, seed: u64) -> Stream<C, B> {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "sample_bernoulli: probability {p} is not in the range [0, 1]"
+        );
+
+        let output = self
+            .try_sharded_version()
+            .apply_named("SampleBernoulli", move |batch: &B| {
+                let mut builder = B::Builder::with_capacity((), batch.len());
+                let mut cursor = batch.cursor();
+                while cursor.key_valid() {
+                    while cursor.val_valid() {
+                        let priority = sample_priority(seed, cursor.key(), cursor.val());
+                        if priority_to_unit(priority) < p {
+                            builder.push((
+                                B::item_from(cursor.key().clone(), cursor.val().clone()),
+                                cursor.weight(),
+                            ));
+                        }
+                        cursor.step_val();
+                    }
+                    cursor.step_key();
+                }
+                builder.done()
+            });
+
+        output.mark_sharded_if(self);
+        output
+    }
+}
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet,
+    B::R: ZRingValue,
+{
+    /// Reservoir sample of the stream.
+    ///
+    /// Maintains a sample of up to `k` `(key, value)` pairs, chosen uniformly
+    /// at random among pairs with positive weights in the accumulated input
+    /// stream, and outputs changes to the sample.  Sampled pairs retain
+    /// their weights, but each pair counts as one row towards `k`
+    /// regardless of its weight.
+    ///
+    /// The operator implements bottom-`k` sampling: each pair is assigned a
+    /// pseudo-random priority by hashing it with `seed`, and the sample
+    /// consists of `k` pairs with the smallest priorities.  When a new pair
+    /// displaces a pair in the reservoir, the operator outputs a retraction
+    /// of the displaced pair.  When a sampled pair is deleted from the input,
+    /// it is removed from the reservoir, and the pair with the next smallest
+    /// priority, if any, takes its place.
+    ///
+    /// For a fixed `seed`, the output does not depend on the number of
+    /// workers or on how the input is split into batches.  Note that the
+    /// entire reservoir is maintained by a single worker.
+    pub fn sample_reservoir(&self, k: usize, seed: u64) -> Stream<RootCircuit, B> {
+        self.circuit().region("sample_reservoir", || {
+            // Index all pairs under the same key, ordered by priority.
+            let prioritized = self.apply_named("SamplePriority", move |batch: &B| {
+                let mut tuples = Vec::with_capacity(batch.len());
+                let mut cursor = batch.cursor();
+                while cursor.key_valid() {
+                    while cursor.val_valid() {
+                        let key = cursor.key().clone();
+                        let val = cursor.val().clone();
+                        let priority = sample_priority(seed, &key, &val);
+                        tuples.push((((), (priority, key, val)), cursor.weight()));
+                        cursor.step_val();
+                    }
+                    cursor.step_key();
+                }
+                OrdIndexedZSet::from_tuples((), tuples)
+            });
+
+            prioritized
+                .topk_custom(k, |left, right| left.cmp(right))
+                .apply_named(
+                    "SampleReservoir",
+                    |batch: &OrdIndexedZSet<(), (u64, B::Key, B::Val), B::R>| {
+                        let mut tuples = Vec::with_capacity(batch.len());
+                        let mut cursor = batch.cursor();
+                        while cursor.key_valid() {
+                            while cursor.val_valid() {
+                                let (_priority, key, val) = cursor.val();
+                                tuples.push((
+                                    B::item_from(key.clone(), val.clone()),
+                                    cursor.weight(),
+                                ));
+                                cursor.step_val();
+                            }
+                            cursor.step_key();
+                        }
+                        B::from_tuples((), tuples)
+                    },
+                )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::sample_priority;
+    use crate::{
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdZSet, OutputHandle, Runtime,
+    };
+
+    type TestZSet = OrdZSet<u64, isize>;
+
+    const SEED: u64 = 0x1234_5678;
+
+    fn sample_circuit(
+        workers: usize,
+        p: f64,
+        k: usize,
+        seed: u64,
+    ) -> (
+        DBSPHandle,
+        (
+            CollectionHandle<u64, isize>,
+            OutputHandle<TestZSet>,
+            OutputHandle<TestZSet>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, move |circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<u64, isize>();
+            let bernoulli = input.sample_bernoulli(p, seed).integrate().output();
+            let reservoir = input.sample_reservoir(k, seed).integrate().output();
+
+            (input_handle, bernoulli, reservoir)
+        })
+        .unwrap()
+    }
+
+    fn keys(batch: &TestZSet) -> Vec<(u64, isize)> {
+        let mut result = Vec::new();
+        let mut cursor = batch.cursor();
+        while cursor.key_valid() {
+            result.push((*cursor.key(), cursor.weight()));
+            cursor.step_key();
+        }
+        result
+    }
+
+    // Reference reservoir: `k` keys with positive weights and the smallest
+    // priorities.
+    fn expected_reservoir(input: &TestZSet, k: usize, seed: u64) -> TestZSet {
+        let mut tuples: Vec<_> = keys(input)
+            .into_iter()
+            .filter(|(_, w)| *w > 0)
+            .map(|(key, w)| (sample_priority(seed, &key, &()), key, w))
+            .collect();
+        tuples.sort();
+        tuples.truncate(k);
+
+        TestZSet::from_tuples((), tuples.into_iter().map(|(_, key, w)| (key, w)).collect())
+    }
+
+    /// Inserts keys `0..10_000` in batches of 1000, then deletes every other
+    /// key and bumps the weight of the remaining keys.  Returns samples
+    /// produced at each step, along with the accumulated input.
+    fn run_sample(
+        workers: usize,
+        p: f64,
+        k: usize,
+        seed: u64,
+    ) -> Vec<(TestZSet, TestZSet, TestZSet)> {
+        let (mut dbsp, (mut input_handle, bernoulli, reservoir)) =
+            sample_circuit(workers, p, k, seed);
+
+        let mut input = Vec::new();
+        let mut result = Vec::new();
+
+        let mut batches: Vec<Vec<(u64, isize)>> = (0..10)
+            .map(|i| (i * 1000..(i + 1) * 1000).map(|key| (key, 1)).collect())
+            .collect();
+        batches.push(
+            (0..10_000)
+                .map(|key| (key, if key % 2 == 0 { -1 } else { 1 }))
+                .collect(),
+        );
+
+        for mut batch in batches {
+            input.extend(batch.iter().cloned());
+            input_handle.append(&mut batch);
+            dbsp.step().unwrap();
+
+            result.push((
+                TestZSet::from_tuples((), input.clone()),
+                bernoulli.consolidate(),
+                reservoir.consolidate(),
+            ));
+        }
+
+        dbsp.kill().unwrap();
+
+        result
+    }
+
+    #[test]
+    fn sample_bernoulli_test() {
+        let steps = run_sample(4, 0.3, 100, SEED);
+
+        for (input, sample, _) in steps.iter() {
+            let input_keys = keys(input);
+            let sample_keys = keys(sample);
+
+            // Sampled keys retain their weights.
+            for (key, w) in sample_keys.iter() {
+                assert!(input_keys.contains(&(*key, *w)));
+            }
+
+            // The sample size is close to `p * input.len()`.
+            let expected = 0.3 * input_keys.len() as f64;
+            let actual = sample_keys.len() as f64;
+            assert!(
+                (actual - expected).abs() <= 0.2 * expected,
+                "expected about {expected} sampled keys, got {actual}"
+            );
+        }
+
+        // Deleted keys are removed from the sample.
+        let (_, sample, _) = steps.last().unwrap();
+        assert!(keys(sample).iter().all(|(key, w)| key % 2 == 1 && *w == 2));
+    }
+
+    #[test]
+    fn sample_bernoulli_extremes() {
+        let steps = run_sample(2, 0.0, 0, SEED);
+        assert!(steps.iter().all(|(_, sample, _)| sample.is_empty()));
+
+        let steps = run_sample(2, 1.0, 0, SEED);
+        assert!(steps.iter().all(|(input, sample, _)| input == sample));
+    }
+
+    #[test]
+    fn sample_reservoir_test() {
+        let k = 100;
+        let steps = run_sample(4, 0.3, k, SEED);
+
+        for (input, _, reservoir) in steps.iter() {
+            assert_eq!(reservoir, &expected_reservoir(input, k, SEED));
+            assert_eq!(reservoir.len(), k);
+        }
+
+        // The mean of a uniform sample of `0..10_000` is close to 5000.
+        let (_, _, reservoir) = &steps[9];
+        let mean = keys(reservoir)
+            .iter()
+            .map(|(key, _)| *key as f64)
+            .sum::<f64>()
+            / k as f64;
+        assert!(
+            (3500.0..=6500.0).contains(&mean),
+            "mean of sampled keys {mean} is too far from 5000"
+        );
+
+        // Deleted keys are replaced with the remaining ones.
+        let (_, _, reservoir) = steps.last().unwrap();
+        assert!(keys(reservoir)
+            .iter()
+            .all(|(key, w)| key % 2 == 1 && *w == 2));
+    }
+
+    #[test]
+    fn sample_reservoir_small_input() {
+        let (mut dbsp, (mut input_handle, _, reservoir)) = sample_circuit(2, 0.5, 10, SEED);
+
+        input_handle.append(&mut vec![(1, 1), (2, 3), (3, -1)]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            reservoir.consolidate(),
+            TestZSet::from_tuples((), vec![(1, 1), (2, 3)])
+        );
+
+        input_handle.append(&mut vec![(2, -3)]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            reservoir.consolidate(),
+            TestZSet::from_tuples((), vec![(1, 1)])
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn sample_determinism() {
+        let steps = run_sample(4, 0.3, 100, SEED);
+
+        // Same seed, same number of workers.
+        assert_eq!(steps, run_sample(4, 0.3, 100, SEED));
+
+        // Same seed, different number of workers.
+        assert_eq!(steps, run_sample(1, 0.3, 100, SEED));
+
+        // Different seed.
+        let other_steps = run_sample(4, 0.3, 100, SEED + 1);
+        assert_ne!(steps[9].1, other_steps[9].1);
+        assert_ne!(steps[9].2, other_steps[9].2);
+    }
+}
@@ -1,13 +1,13 @@
 use crate::{
-    algebra::{MulByRef, ZSet},
+    algebra::{HasZero, IndexedZSet, MulByRef, ZRingValue, ZSet},
     circuit::{
         operator_traits::{BinaryOperator, Operator},
         Scope,
     },
     circuit::{GlobalNodeId, OwnershipPreference},
     circuit_cache_key,
-    trace::{Batch, BatchReader, Builder, Consumer, Cursor, ValueConsumer},
-    Circuit, Stream,
+    trace::{layers::SearchHint, Batch, BatchReader, Builder, Consumer, Cursor, ValueConsumer},
+    Circuit, RootCircuit, Stream,
 };
 use std::{
     borrow::Cow,
@@ -16,6 +16,8 @@ use std::{
 };
 
 circuit_cache_key!(SemijoinId<C, D>((GlobalNodeId, GlobalNodeId) => Stream<C, D>));
+circuit_cache_key!(SemijoinKeysId<C, D>((GlobalNodeId, GlobalNodeId) => Stream<C, D>));
+circuit_cache_key!(AntijoinKeysId<C, D>((GlobalNodeId, GlobalNodeId) => Stream<C, D>));
 
 impl<C, Pairs> Stream<C, Pairs>
 where
@@ -62,6 +64,81 @@ where
     }
 }
 
+impl<I1> Stream<RootCircuit, I1>
+where
+    I1: IndexedZSet + Send,
+    I1::R: ZRingValue,
+{
+    /// Incremental semijoin with a set of keys.
+    ///
+    /// Returns the subset of `self` whose keys occur in `keys` with a positive
+    /// weight.  `keys` is a plain Z-set of keys, so there is no need to index
+    /// it or to drop its values before calling this operator.  Weights in
+    /// `keys` are ignored, i.e., the output contains each record of `self`
+    /// with its original weight.
+    ///
+    /// The operator maintains the integrals of both inputs.  The trace of
+    /// `keys` is created once per stream and is shared by all semijoins and
+    /// antijoins with the same `keys` stream, so semijoining multiple streams
+    /// with the same set of keys doesn't duplicate state.
+    pub fn semijoin_keys<Z2>(&self, keys: &Stream<RootCircuit, Z2>) -> Stream<RootCircuit, I1>
+    where
+        Z2: ZSet<Key = I1::Key, R = I1::R> + Send,
+    {
+        self.circuit()
+            .cache_get_or_insert_with(
+                SemijoinKeysId::new((self.origin_node_id().clone(), keys.origin_node_id().clone())),
+                move || {
+                    let circuit = self.circuit();
+
+                    circuit.region("semijoin_keys", || {
+                        let pairs = self.shard();
+                        // `distinct` is sharded and cached, so all semijoins
+                        // with `keys` share the same trace below.
+                        let keys = keys.distinct();
+
+                        let pairs_trace = pairs.integrate_trace();
+                        let keys_trace = keys.integrate_trace();
+
+                        // Changes to the output caused by changes to `self`.
+                        let pairs_updates: Stream<_, I1> =
+                            circuit.add_binary_operator(SemiJoinKeys::new(), &pairs, &keys_trace);
+
+                        // Changes to the output caused by changes to `keys`.
+                        let keys_updates: Stream<_, I1> = circuit.add_binary_operator(
+                            SemiJoinKeys::new(),
+                            &pairs_trace.delay_trace(),
+                            &keys,
+                        );
+
+                        pairs_updates.plus(&keys_updates).mark_sharded()
+                    })
+                },
+            )
+            .clone()
+    }
+
+    /// Incremental antijoin with a set of keys.
+    ///
+    /// Returns the subset of `self` whose keys do not occur in `keys` with a
+    /// positive weight.  This is the complement of
+    /// [`semijoin_keys`](`Self::semijoin_keys`) and shares its state.
+    pub fn antijoin_keys<Z2>(&self, keys: &Stream<RootCircuit, Z2>) -> Stream<RootCircuit, I1>
+    where
+        Z2: ZSet<Key = I1::Key, R = I1::R> + Send,
+    {
+        self.circuit()
+            .cache_get_or_insert_with(
+                AntijoinKeysId::new((self.origin_node_id().clone(), keys.origin_node_id().clone())),
+                move || {
+                    let pairs = self.shard();
+                    pairs.minus(&pairs.semijoin_keys(keys)).mark_sharded()
+                },
+            )
+            .clone()
+    }
+}
+
 /// Semijoin two streams of batches, see [`Stream::semijoin_stream`]
 pub struct SemiJoinStream<Pairs, Keys, Out> {
     _types: PhantomData<(Pairs, Keys, Out)>,
@@ -108,8 +185,12 @@ where
         while key_cursor.key_valid() && pair_cursor.key_valid() {
             match key_cursor.key().cmp(pair_cursor.key()) {
                 // Match up both the cursors
-                Ordering::Less => key_cursor.seek_key(pair_cursor.key()),
-                Ordering::Greater => pair_cursor.seek_key(key_cursor.key()),
+                Ordering::Less => {
+                    key_cursor.seek_key_with_hint(pair_cursor.key(), SearchHint::Near)
+                }
+                Ordering::Greater => {
+                    pair_cursor.seek_key_with_hint(key_cursor.key(), SearchHint::Near)
+                }
 
                 Ordering::Equal => {
                     // TODO: Can the value of `()` ever be invalid? Do we need an `if
@@ -230,3 +311,189 @@ where
         )
     }
 }
+
+/// Semijoin a batch or trace of key/value pairs with a batch or trace of keys,
+/// see [`Stream::semijoin_keys`].
+///
+/// Unlike [`SemiJoinStream`], preserves the structure of the `pairs` input.
+struct SemiJoinKeys<Pairs, Keys, Out> {
+    _types: PhantomData<(Pairs, Keys, Out)>,
+}
+
+impl<Pairs, Keys, Out> SemiJoinKeys<Pairs, Keys, Out> {
+    const fn new() -> Self {
+        Self {
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<Pairs, Keys, Out> Operator for SemiJoinKeys<Pairs, Keys, Out>
+where
+    Pairs: 'static,
+    Keys: 'static,
+    Out: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("SemiJoinKeys")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<Pairs, Keys, Out> BinaryOperator<Pairs, Keys, Out> for SemiJoinKeys<Pairs, Keys, Out>
+where
+    Pairs: BatchReader<Time = ()>,
+    Pairs::R: HasZero + MulByRef<Keys::R, Output = Pairs::R>,
+    Keys: BatchReader<Key = Pairs::Key, Val = (), Time = ()>,
+    Keys::R: HasZero,
+    Out: Batch<Key = Pairs::Key, Val = Pairs::Val, Time = (), R = Pairs::R>,
+{
+    fn eval(&mut self, pairs: &Pairs, keys: &Keys) -> Out {
+        let mut pair_cursor = pairs.cursor();
+        let mut key_cursor = keys.cursor();
+
+        // Choose capacity heuristically.
+        let mut builder = Out::Builder::with_capacity((), min(pairs.len(), keys.len()));
+
+        while key_cursor.key_valid() && pair_cursor.key_valid() {
+            match key_cursor.key().cmp(pair_cursor.key()) {
+                Ordering::Less => {
+                    key_cursor.seek_key_with_hint(pair_cursor.key(), SearchHint::Near)
+                }
+                Ordering::Greater => {
+                    pair_cursor.seek_key_with_hint(key_cursor.key(), SearchHint::Near)
+                }
+
+                Ordering::Equal => {
+                    // Traces may contain keys whose weights add up to zero.
+                    let key_weight = key_cursor.weight();
+                    if !key_weight.is_zero() {
+                        while pair_cursor.val_valid() {
+                            let pair_weight = pair_cursor.weight();
+                            if !pair_weight.is_zero() {
+                                builder.push((
+                                    Out::item_from(
+                                        pair_cursor.key().clone(),
+                                        pair_cursor.val().clone(),
+                                    ),
+                                    pair_weight.mul_by_ref(&key_weight),
+                                ));
+                            }
+                            pair_cursor.step_val();
+                        }
+                    }
+
+                    pair_cursor.step_key();
+                    key_cursor.step_key();
+                }
+            }
+        }
+
+        builder.done()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        circuit::Circuit, indexed_zset, operator::Generator, zset, OrdIndexedZSet, OrdZSet,
+        RootCircuit,
+    };
+    use std::vec;
+
+    type Pairs = OrdIndexedZSet<u64, u64, isize>;
+    type Keys = OrdZSet<u64, isize>;
+
+    #[test]
+    fn semijoin_keys_test() {
+        let circuit = RootCircuit::build(|circuit| {
+            let mut pairs: vec::IntoIter<Pairs> = vec![
+                indexed_zset! { 1 => {10 => 1, 11 => 2}, 2 => {12 => 1}, 3 => {13 => 1} },
+                indexed_zset! {},
+                indexed_zset! { 2 => {14 => 1}, 4 => {15 => 1} },
+                indexed_zset! { 1 => {10 => -1} },
+                indexed_zset! {},
+            ]
+            .into_iter();
+
+            let mut keys: vec::IntoIter<Keys> = vec![
+                zset! { 1 => 1, 5 => 1 },
+                // Keys with weights other than 1 count as a single occurrence.
+                zset! { 2 => 3 },
+                zset! {},
+                zset! { 1 => 1 },
+                // Removing one of two occurrences of a key doesn't change
+                // membership; removing the last one does.
+                zset! { 1 => -1, 2 => -3 },
+            ]
+            .into_iter();
+
+            let mut expected_semijoin: vec::IntoIter<Pairs> = vec![
+                indexed_zset! { 1 => {10 => 1, 11 => 2} },
+                indexed_zset! { 2 => {12 => 1} },
+                indexed_zset! { 2 => {14 => 1} },
+                indexed_zset! { 1 => {10 => -1} },
+                indexed_zset! { 2 => {12 => -1, 14 => -1} },
+            ]
+            .into_iter();
+
+            let mut expected_antijoin: vec::IntoIter<Pairs> = vec![
+                indexed_zset! { 2 => {12 => 1}, 3 => {13 => 1} },
+                indexed_zset! { 2 => {12 => -1} },
+                indexed_zset! { 4 => {15 => 1} },
+                indexed_zset! {},
+                indexed_zset! { 2 => {12 => 1, 14 => 1} },
+            ]
+            .into_iter();
+
+            let pairs = circuit.add_source(Generator::new(move || pairs.next().unwrap()));
+            let keys = circuit.add_source(Generator::new(move || keys.next().unwrap()));
+
+            pairs
+                .semijoin_keys(&keys)
+                .inspect(move |batch| assert_eq!(batch, &expected_semijoin.next().unwrap()));
+            pairs
+                .antijoin_keys(&keys)
+                .inspect(move |batch| assert_eq!(batch, &expected_antijoin.next().unwrap()));
+        })
+        .unwrap()
+        .0;
+
+        for _ in 0..5 {
+            circuit.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn semijoin_keys_shares_trace() {
+        RootCircuit::build(|circuit| {
+            let (pairs1, _) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (pairs2, _) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let (keys, _) = circuit.add_input_zset::<u64, isize>();
+
+            let nodes = circuit.num_nodes();
+            pairs1.semijoin_keys(&keys);
+            let first = circuit.num_nodes() - nodes;
+
+            // The second semijoin reuses the distinct keys and their trace.
+            let nodes = circuit.num_nodes();
+            pairs2.semijoin_keys(&keys);
+            let second = circuit.num_nodes() - nodes;
+            assert!(second < first);
+
+            let nodes = circuit.num_nodes();
+            keys.distinct().integrate_trace();
+            pairs1.semijoin_keys(&keys);
+            assert_eq!(circuit.num_nodes(), nodes);
+
+            // Antijoin is built on top of the existing semijoin.
+            let nodes = circuit.num_nodes();
+            pairs1.antijoin_keys(&keys);
+            assert_eq!(circuit.num_nodes(), nodes + 1);
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+//! Runtime checks that a stream of changes describes a set.
+
+use crate::{
+    algebra::{HasOne, HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope,
+    },
+    trace::{Batch, BatchReader, Cursor, Spine},
+    Circuit, NumEntries, RootCircuit, Stream,
+};
+use std::{
+    borrow::Cow,
+    fmt::{self, Debug, Display},
+    marker::PhantomData,
+};
+
+/// A tuple whose weight in the integral of a stream checked by
+/// [`Stream::set_semantics_violations`] is not 0 or 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetSemanticsViolation<K, V, R> {
+    /// Name of the stream passed to the checking operator.
+    pub stream: String,
+    /// Clock cycle at which the violation occurred, starting from 0.
+    pub step: usize,
+    pub key: K,
+    pub val: V,
+    /// Weight of the tuple in the integral of the stream after applying
+    /// the change at `step`.
+    pub weight: R,
+}
+
+impl<K, V, R> NumEntries for SetSemanticsViolation<K, V, R> {
+    const CONST_NUM_ENTRIES: Option<usize> = Some(1);
+
+    fn num_entries_shallow(&self) -> usize {
+        1
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        1
+    }
+}
+
+impl<K, V, R> Display for SetSemanticsViolation<K, V, R>
+where
+    K: Debug,
+    V: Debug,
+    R: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stream '{}' violates set semantics at step {}: key {:?}, value {:?} has weight {:?} (expected 0 or 1)",
+            self.stream, self.step, self.key, self.val, self.weight
+        )
+    }
+}
+
+impl<Z> Stream<RootCircuit, Z>
+where
+    Z: IndexedZSet + Send,
+    Z::R: ZRingValue,
+{
+    /// Check that the integral of `self` is a set, panicking otherwise.
+    ///
+    /// A stream satisfies set semantics if, after applying each change in
+    /// the stream, the weight of every tuple in the integral of the stream is
+    /// 0 or 1.  Negative weights or weights greater than 1 usually indicate a
+    /// bug upstream, e.g., a join that produces duplicates or a retraction of
+    /// a tuple that was never inserted.  Such weights propagate silently
+    /// through the circuit, so this operator can be used to catch them close
+    /// to their origin.
+    ///
+    /// The check panics with a message that includes `name`, the clock cycle,
+    /// and the offending key, value, and weight.  It is only performed in
+    /// debug builds; in release builds this method does not add any
+    /// operators to the circuit.  Use
+    /// [`set_semantics_violations`](`Self::set_semantics_violations`) to
+    /// check the stream in all builds without panicking.
+    ///
+    /// Returns a stream with the same contents as `self`.
+    pub fn assert_set_semantics(&self, name: &str) -> Self {
+        if cfg!(debug_assertions) {
+            self.set_semantics_violations(name).inspect(|violations| {
+                if let Some(violation) = violations.first() {
+                    panic!("{violation}");
+                }
+            });
+        }
+
+        self.clone()
+    }
+
+    /// Returns a stream of tuples whose weights in the integral of `self`
+    /// are not 0 or 1 after applying each change in `self`.
+    ///
+    /// See [`assert_set_semantics`](`Self::assert_set_semantics`).  Unlike
+    /// `assert_set_semantics`, this operator is enabled in all builds and
+    /// does not panic, so the output stream can be used to report violations
+    /// in production, e.g., by attaching an [`OutputHandle`](`crate::OutputHandle`)
+    /// to it.
+    ///
+    /// The operator uses the integral of `self` computed by
+    /// [`integrate_trace`](`Self::integrate_trace`), which is shared with
+    /// other operators, such as joins, that already maintain it.
+    #[allow(clippy::type_complexity)]
+    pub fn set_semantics_violations(
+        &self,
+        name: &str,
+    ) -> Stream<RootCircuit, Vec<SetSemanticsViolation<Z::Key, Z::Val, Z::R>>> {
+        let stream = self.shard();
+
+        self.circuit().add_binary_operator(
+            SetSemanticsCheck::new(name),
+            &stream,
+            &stream.integrate_trace(),
+        )
+    }
+}
+
+/// Implementation of [`Stream::set_semantics_violations`].
+///
+/// The first input is the input batch, the second input is the integral of
+/// the input stream, including the current batch.
+struct SetSemanticsCheck<Z> {
+    name: String,
+    step: usize,
+    _phantom: PhantomData<Z>,
+}
+
+impl<Z> SetSemanticsCheck<Z> {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            step: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Z> Operator for SetSemanticsCheck<Z>
+where
+    Z: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("SetSemanticsCheck")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<Z> BinaryOperator<Z, Spine<Z>, Vec<SetSemanticsViolation<Z::Key, Z::Val, Z::R>>>
+    for SetSemanticsCheck<Z>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+{
+    fn eval(
+        &mut self,
+        delta: &Z,
+        trace: &Spine<Z>,
+    ) -> Vec<SetSemanticsViolation<Z::Key, Z::Val, Z::R>> {
+        let mut violations = Vec::new();
+
+        let mut delta_cursor = delta.cursor();
+        let mut trace_cursor = trace.cursor();
+
+        // Only tuples modified by the current batch can change their weights.
+        while delta_cursor.key_valid() {
+            let key = delta_cursor.key();
+
+            trace_cursor.seek_key(key);
+            if trace_cursor.key_valid() && trace_cursor.key() == key {
+                while delta_cursor.val_valid() {
+                    let val = delta_cursor.val();
+
+                    trace_cursor.seek_val(val);
+                    if trace_cursor.val_valid() && trace_cursor.val() == val {
+                        let weight = trace_cursor.weight();
+                        if !weight.is_zero() && weight != Z::R::one() {
+                            violations.push(SetSemanticsViolation {
+                                stream: self.name.clone(),
+                                step: self.step,
+                                key: key.clone(),
+                                val: val.clone(),
+                                weight,
+                            });
+                        }
+                    }
+
+                    delta_cursor.step_val();
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        self.step += 1;
+        violations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SetSemanticsViolation;
+    use crate::{operator::Generator, zset, RootCircuit, Runtime};
+
+    type Violation = SetSemanticsViolation<(u64, u64), (), isize>;
+
+    #[test]
+    fn set_semantics_violations() {
+        let (circuit, (mut input, output)) = RootCircuit::build(|circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<(u64, u64), isize>();
+
+            (
+                input_handle,
+                input.set_semantics_violations("pairs").output(),
+            )
+        })
+        .unwrap();
+
+        input.append(&mut vec![((1, 2), 1), ((2, 3), 1)]);
+        circuit.step().unwrap();
+        assert_eq!(output.take_from_all(), vec![Vec::<Violation>::new()]);
+
+        // Retracting a tuple and re-inserting it is fine.
+        input.append(&mut vec![((1, 2), -1), ((3, 4), 1)]);
+        circuit.step().unwrap();
+        assert_eq!(output.take_from_all(), vec![Vec::<Violation>::new()]);
+
+        input.push((1, 2), 1);
+        circuit.step().unwrap();
+        assert_eq!(output.take_from_all(), vec![Vec::<Violation>::new()]);
+
+        // Duplicate and negative weights.
+        input.append(&mut vec![((2, 3), 1), ((5, 6), -1)]);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.take_from_all(),
+            vec![vec![
+                Violation {
+                    stream: "pairs".to_string(),
+                    step: 3,
+                    key: (2, 3),
+                    val: (),
+                    weight: 2,
+                },
+                Violation {
+                    stream: "pairs".to_string(),
+                    step: 3,
+                    key: (5, 6),
+                    val: (),
+                    weight: -1,
+                },
+            ]]
+        );
+
+        // Fixing the weights clears the violations.
+        input.append(&mut vec![((2, 3), -1), ((5, 6), 1)]);
+        circuit.step().unwrap();
+        assert_eq!(output.take_from_all(), vec![Vec::<Violation>::new()]);
+    }
+
+    // A join that forgets to include the value of the left input in its
+    // output produces duplicates when a key has multiple values.
+    #[test]
+    fn faulty_join_violations() {
+        let (mut dbsp, (mut input, output)) = Runtime::init_circuit(4, |circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<(u64, u64), isize>();
+            let indexed = input.index::<u64, u64>();
+            let joined = indexed.join(&indexed, |&k, _v1, &v2| (k, v2));
+
+            (
+                input_handle,
+                joined.set_semantics_violations("faulty_join").output(),
+            )
+        })
+        .unwrap();
+
+        input.append(&mut vec![((1, 10), 1), ((2, 20), 1)]);
+        dbsp.step().unwrap();
+        assert!(output.take_from_all().into_iter().all(|v| v.is_empty()));
+
+        input.push((1, 11), 1);
+        dbsp.step().unwrap();
+
+        let mut violations: Vec<_> = output.take_from_all().into_iter().flatten().collect();
+        violations.sort_by(|v1, v2| v1.key.cmp(&v2.key));
+        assert_eq!(
+            violations,
+            vec![
+                Violation {
+                    stream: "faulty_join".to_string(),
+                    step: 1,
+                    key: (1, 10),
+                    val: (),
+                    weight: 2,
+                },
+                Violation {
+                    stream: "faulty_join".to_string(),
+                    step: 1,
+                    key: (1, 11),
+                    val: (),
+                    weight: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "stream 'faulty_join' violates set semantics at step 1: key (1, 10), value () has weight 2 (expected 0 or 1)"
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(
+        expected = "stream 'faulty_join' violates set semantics at step 1: key (1, 0), value () has weight 2 (expected 0 or 1)"
+    )]
+    fn faulty_join_assert() {
+        let mut inputs = vec![zset! { (1u64, 0u64) => 1 }, zset! { (1, 1) => 1 }].into_iter();
+
+        let circuit = RootCircuit::build(move |circuit| {
+            let indexed = circuit
+                .add_source(Generator::new(move || inputs.next().unwrap()))
+                .index::<u64, u64>();
+
+            indexed
+                .join(&indexed, |&k, _v1, &v2| (k, v2))
+                .assert_set_semantics("faulty_join");
+        })
+        .unwrap()
+        .0;
+
+        for _ in 0..2 {
+            circuit.step().unwrap();
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Operator that holds back small changes to aggregate values.
+
+use crate::{
+    algebra::{HasOne, HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{Operator, TernaryOperator},
+        Circuit, OwnershipPreference, Scope, Stream,
+    },
+    operator::Generator,
+    trace::{cursor::Cursor, Batch, BatchReader, Spine},
+    OrdIndexedZSet,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    mem::take,
+    ops::{Neg, Sub},
+};
+
+impl<C, Z> Stream<C, Z>
+where
+    C: Circuit,
+    Z: IndexedZSet + Send,
+    Z::R: ZRingValue,
+    Z::Val: Sub<Output = Z::Val>,
+{
+    /// Hold back small changes to the values of an indexed Z-set.
+    ///
+    /// The input stream contains changes to a collection that maps each key
+    /// to at most one value, such as the output of
+    /// [`aggregate`](`Self::aggregate`).  For each key, the operator
+    /// remembers the value it emitted last and holds back updates to the key
+    /// until the difference between the current value of the key and the
+    /// emitted value exceeds `threshold_fn(key, emitted_value)`.  At that
+    /// point, it retracts the emitted value and inserts the current one.
+    /// Insertions and deletions of keys are emitted immediately.
+    ///
+    /// Updates are never lost: the current values of all keys are kept in the
+    /// integral of the input stream, so when the operator emits an update,
+    /// the new value is the exact current value of the key, which consolidates
+    /// all changes held back so far.  Use
+    /// [`suppress_small_changes_with_flush`](`Self::suppress_small_changes_with_flush`)
+    /// to emit held back changes on demand.
+    ///
+    /// The output stream contains changes to the emitted collection, which can
+    /// be integrated to obtain the latest emitted value of each key.
+    #[allow(clippy::type_complexity)]
+    pub fn suppress_small_changes<F>(
+        &self,
+        threshold_fn: F,
+    ) -> Stream<C, OrdIndexedZSet<Z::Key, Z::Val, Z::R>>
+    where
+        F: Fn(&Z::Key, &Z::Val) -> Z::Val + 'static,
+    {
+        let no_flush = self.circuit().add_source(Generator::new(|| false));
+        self.suppress_small_changes_with_flush(&no_flush, threshold_fn)
+    }
+
+    /// Like [`suppress_small_changes`](`Self::suppress_small_changes`), but
+    /// additionally emits all held back changes at every clock cycle when
+    /// `flush` is `true`.
+    ///
+    /// After a flush, the integral of the output stream is equal to the
+    /// integral of the input stream.
+    #[allow(clippy::type_complexity)]
+    pub fn suppress_small_changes_with_flush<F>(
+        &self,
+        flush: &Stream<C, bool>,
+        threshold_fn: F,
+    ) -> Stream<C, OrdIndexedZSet<Z::Key, Z::Val, Z::R>>
+    where
+        F: Fn(&Z::Key, &Z::Val) -> Z::Val + 'static,
+    {
+        self.suppress_small_changes_generic(flush, threshold_fn)
+    }
+
+    /// Like
+    /// [`suppress_small_changes_with_flush`](`Self::suppress_small_changes_with_flush`),
+    /// but can return any batch type.
+    pub fn suppress_small_changes_generic<F, O>(
+        &self,
+        flush: &Stream<C, bool>,
+        threshold_fn: F,
+    ) -> Stream<C, O>
+    where
+        F: Fn(&Z::Key, &Z::Val) -> Z::Val + 'static,
+        O: Batch<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+    {
+        let stream = self.shard();
+
+        self.circuit()
+            .add_ternary_operator(
+                SuppressSmallChanges::new(threshold_fn),
+                &stream,
+                &stream.integrate_trace(),
+                flush,
+            )
+            .mark_sharded()
+    }
+}
+
+/// Implementation of [`Stream::suppress_small_changes_generic`].
+struct SuppressSmallChanges<Z, O, F>
+where
+    Z: BatchReader,
+{
+    threshold_fn: F,
+    // The last value emitted for each key.
+    emitted: BTreeMap<Z::Key, Z::Val>,
+    // Keys whose current value differs from the emitted one.
+    pending: BTreeSet<Z::Key>,
+    _phantom: PhantomData<O>,
+}
+
+impl<Z, O, F> SuppressSmallChanges<Z, O, F>
+where
+    Z: IndexedZSet,
+    Z::Val: Sub<Output = Z::Val>,
+    F: Fn(&Z::Key, &Z::Val) -> Z::Val,
+{
+    fn new(threshold_fn: F) -> Self {
+        Self {
+            threshold_fn,
+            emitted: BTreeMap::new(),
+            pending: BTreeSet::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn exceeds_threshold(&self, key: &Z::Key, emitted: &Z::Val, current: &Z::Val) -> bool {
+        let change = if current >= emitted {
+            current.clone() - emitted.clone()
+        } else {
+            emitted.clone() - current.clone()
+        };
+
+        change > (self.threshold_fn)(key, emitted)
+    }
+}
+
+/// Returns the value of `key` in the trace under `cursor`, if any.
+fn current_value<'s, K, V, R, C>(cursor: &mut C, key: &K) -> Option<V>
+where
+    K: Eq,
+    V: Clone,
+    R: HasZero,
+    C: Cursor<'s, K, V, (), R>,
+{
+    cursor.seek_key(key);
+    if cursor.key_valid() && cursor.key() == key {
+        while cursor.val_valid() {
+            if !cursor.weight().is_zero() {
+                return Some(cursor.val().clone());
+            }
+            cursor.step_val();
+        }
+    }
+
+    None
+}
+
+impl<Z, O, F> Operator for SuppressSmallChanges<Z, O, F>
+where
+    Z: BatchReader,
+    O: 'static,
+    F: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("SuppressSmallChanges")
+    }
+
+    fn clock_start(&mut self, scope: Scope) {
+        // In a nested circuit, the integral of the input stream starts from
+        // scratch at each parent clock cycle, and so do the emitted values.
+        if scope == 0 {
+            self.emitted.clear();
+            self.pending.clear();
+        }
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        // Held back changes may still be emitted by a later flush.
+        self.pending.is_empty()
+    }
+}
+
+impl<Z, O, F> TernaryOperator<Z, Spine<Z>, bool, O> for SuppressSmallChanges<Z, O, F>
+where
+    Z: IndexedZSet,
+    Z::R: ZRingValue,
+    Z::Val: Sub<Output = Z::Val>,
+    O: Batch<Key = Z::Key, Val = Z::Val, Time = (), R = Z::R>,
+    F: Fn(&Z::Key, &Z::Val) -> Z::Val + 'static,
+{
+    fn eval(&mut self, delta: Cow<'_, Z>, trace: Cow<'_, Spine<Z>>, flush: Cow<'_, bool>) -> O {
+        let flush = *flush;
+
+        // Keys whose values may have to be emitted, in ascending order.
+        let mut keys: Vec<Z::Key> = Vec::with_capacity(delta.key_count());
+        let mut cursor = delta.cursor();
+        while cursor.key_valid() {
+            keys.push(cursor.key().clone());
+            cursor.step_key();
+        }
+
+        // A flush emits keys held back at earlier steps in addition to keys
+        // modified by the current delta.
+        if flush && !self.pending.is_empty() {
+            keys.extend(take(&mut self.pending));
+            keys.sort_unstable();
+            keys.dedup();
+        }
+
+        let mut tuples = Vec::new();
+        let mut cursor = trace.cursor();
+
+        for key in keys {
+            let current = current_value(&mut cursor, &key);
+            let emitted = self.emitted.get(&key).cloned();
+
+            match (emitted, current) {
+                (None, None) => {
+                    self.pending.remove(&key);
+                }
+                (Some(emitted), Some(current)) if emitted == current => {
+                    self.pending.remove(&key);
+                }
+                (Some(emitted), Some(current))
+                    if !flush && !self.exceeds_threshold(&key, &emitted, &current) =>
+                {
+                    self.pending.insert(key);
+                }
+                (emitted, current) => {
+                    if let Some(emitted) = emitted {
+                        tuples.push((O::item_from(key.clone(), emitted), Z::R::one().neg()));
+                    }
+                    if let Some(current) = current {
+                        tuples.push((O::item_from(key.clone(), current.clone()), Z::R::one()));
+                        self.emitted.insert(key.clone(), current);
+                    } else {
+                        self.emitted.remove(&key);
+                    }
+                    self.pending.remove(&key);
+                }
+            }
+        }
+
+        O::from_tuples((), tuples)
+    }
+
+    fn input_preference(
+        &self,
+    ) -> (
+        OwnershipPreference,
+        OwnershipPreference,
+        OwnershipPreference,
+    ) {
+        (
+            OwnershipPreference::INDIFFERENT,
+            OwnershipPreference::INDIFFERENT,
+            OwnershipPreference::INDIFFERENT,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{indexed_zset, trace::BatchReader, RootCircuit};
+
+    #[test]
+    fn suppress_small_changes() {
+        let (circuit, (mut input, flush, output, suppressed, expected)) =
+            RootCircuit::build(|circuit| {
+                let (input, input_handle) = circuit.add_input_indexed_zset::<u64, i64, isize>();
+                let (flush, flush_handle) = circuit.add_input_stream::<bool>();
+
+                let counters = input.aggregate_linear(|_key, val: &i64| *val);
+                let suppressed =
+                    counters.suppress_small_changes_with_flush(&flush, |_key, _val| 10);
+
+                (
+                    input_handle,
+                    flush_handle,
+                    suppressed.output(),
+                    suppressed.integrate().output(),
+                    counters.integrate().output(),
+                )
+            })
+            .unwrap();
+
+        // Counter `1` grows by one, counter `2` grows by 100 at each step.
+        let mut emissions = 0;
+        for _ in 0..25 {
+            input.append(&mut vec![(1, (1, 1)), (2, (100, 1))]);
+            circuit.step().unwrap();
+
+            let output = output.consolidate();
+            if output.key_count() == 2 {
+                emissions += 1;
+            } else {
+                assert_eq!(output.key_count(), 1);
+            }
+        }
+
+        // Counter `1` is emitted when it is created and when it changes by more
+        // than 10.
+        assert_eq!(emissions, 3);
+        assert_eq!(
+            suppressed.consolidate(),
+            indexed_zset! { 1 => { 23 => 1 }, 2 => { 2500 => 1 } }
+        );
+
+        // Deletions are emitted immediately.
+        input.append(&mut vec![(2, (100, -25))]);
+        circuit.step().unwrap();
+        assert_eq!(output.consolidate(), indexed_zset! { 2 => { 2500 => -1 } });
+
+        // Flushing emits exact values.
+        flush.set_for_all(true);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 1 => { 23 => -1, 25 => 1 } }
+        );
+        assert_eq!(suppressed.consolidate(), expected.consolidate());
+        assert_eq!(suppressed.consolidate(), indexed_zset! { 1 => { 25 => 1 } });
+
+        // Nothing to flush.
+        flush.set_for_all(true);
+        circuit.step().unwrap();
+        assert!(output.consolidate().is_empty());
+
+        // Changes that arrive during a flush are emitted along with held back
+        // changes.
+        input.append(&mut vec![(1, (1, 1)), (3, (5, 1))]);
+        circuit.step().unwrap();
+        input.append(&mut vec![(1, (1, 1)), (2, (100, 1)), (3, (1, 1))]);
+        flush.set_for_all(true);
+        circuit.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 1 => { 25 => -1, 27 => 1 }, 2 => { 100 => 1 }, 3 => { 5 => -1, 6 => 1 } }
+        );
+        assert_eq!(suppressed.consolidate(), expected.consolidate());
+    }
+}
@@ -0,0 +1,731 @@
+use crate::{
+    algebra::{HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator, QuaternaryOperator},
+        Scope,
+    },
+    operator::time_series::{
+        range::{Range, RangeCursor, Ranges},
+        OrdPartitionedIndexedZSet, PartitionCursor, PartitionedBatchReader, PartitionedIndexedZSet,
+    },
+    trace::{Builder, Cursor},
+    Circuit, DBData, RootCircuit, Stream,
+};
+use num::PrimInt;
+use std::{borrow::Cow, cmp::min, marker::PhantomData, ops::Neg};
+
+/// Stream produced by [`asof_join`](`Stream::asof_join`).
+///
+/// Each record has the form `(partition, (timestamp, (left_value,
+/// Option<(right_timestamp, right_value)>)))`.
+pub type OrdPartitionedAsofJoinStream<PK, TS, V1, V2, R> =
+    Stream<RootCircuit, OrdPartitionedIndexedZSet<PK, TS, (V1, Option<(TS, V2)>), R>>;
+
+impl<B> Stream<RootCircuit, B> {
+    /// As-of join of two partitioned time series.
+    ///
+    /// For each record `(partition, (ts, v1))` in `self`, finds the record
+    /// `(partition, (ts2, v2))` in `other` with the greatest `(ts2, v2)`,
+    /// such that `ts - tolerance <= ts2 <= ts`, and outputs `(partition, (ts,
+    /// (v1, Some((ts2, v2)))))`, or `(partition, (ts, (v1, None)))` if there
+    /// is no such record.  The weight of the output record is equal to the
+    /// weight of the left record.  Records in `other` with non-positive
+    /// weights are ignored.
+    ///
+    /// A typical use case is joining each trade with the latest quote for
+    /// the same symbol received before the trade.
+    ///
+    /// This operator is incremental: a new record in `other` only causes
+    /// recomputation of outputs for left records within `tolerance` from it
+    /// that do not have a later match.  When such a record displaces a
+    /// previous match, the old output is retracted.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - left time series, partitioned by partition key and indexed
+    ///   by time within each partition.
+    /// * `other` - right time series with the same partition key.
+    /// * `tolerance` - maximal distance between the timestamp of a left
+    ///   record and the timestamp of its match.  Use `TS::max_value()` to
+    ///   match records arbitrarily far in the past.
+    pub fn asof_join<TS, V1, V2, B2>(
+        &self,
+        other: &Stream<RootCircuit, B2>,
+        tolerance: TS,
+    ) -> OrdPartitionedAsofJoinStream<B::Key, TS, V1, V2, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V1>,
+        B::R: ZRingValue,
+        B2: PartitionedIndexedZSet<TS, V2, Key = B::Key, R = B::R>,
+        TS: DBData + PrimInt,
+        V1: DBData,
+        V2: DBData,
+    {
+        self.circuit().region("asof_join", || {
+            let circuit = self.circuit();
+            let left = self.shard();
+            let right = other.shard();
+
+            let left_trace = left.integrate_trace();
+            let right_trace = right.integrate_trace();
+
+            // Changes to the output caused by changes to the left input
+            // (`delta_left <> right_trace`).
+            let left_updates: OrdPartitionedAsofJoinStream<_, _, _, _, _> = circuit
+                .add_binary_operator(
+                    <AsofJoinLeft<TS, V1, V2>>::new(tolerance),
+                    &left,
+                    &right_trace,
+                )
+                .mark_sharded();
+
+            // Changes to the output caused by changes to the right input
+            // (`delayed_left_trace <> right_trace - delayed_left_trace <>
+            // delayed_right_trace`).
+            let right_updates: OrdPartitionedAsofJoinStream<_, _, _, _, _> = circuit
+                .add_quaternary_operator(
+                    <AsofJoinRight<TS, V1, V2>>::new(tolerance),
+                    &right,
+                    &left_trace.delay_trace(),
+                    &right_trace.delay_trace(),
+                    &right_trace,
+                )
+                .mark_sharded();
+
+            left_updates.plus(&right_updates).mark_sharded()
+        })
+    }
+}
+
+/// Finds the latest right record at or before each timestamp in a
+/// monotonically non-decreasing sequence of timestamps.
+struct AsofMatcher<TS, V> {
+    tolerance: TS,
+    last: Option<(TS, V)>,
+}
+
+impl<TS, V> AsofMatcher<TS, V>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+{
+    fn new(tolerance: TS) -> Self {
+        Self {
+            tolerance,
+            last: None,
+        }
+    }
+
+    /// Returns the greatest `(ts2, v2)` in `cursor` with positive weight such
+    /// that `ts - tolerance <= ts2 <= ts`.
+    ///
+    /// `cursor` must iterate over a single partition and must not be used
+    /// outside of this method.  Timestamps passed to consecutive invocations
+    /// must be non-decreasing.
+    fn find<'a, R, C>(&mut self, cursor: &mut C, ts: &TS) -> Option<(TS, V)>
+    where
+        R: ZRingValue,
+        C: Cursor<'a, TS, V, (), R>,
+    {
+        let lower = ts.saturating_sub(self.tolerance);
+
+        // Skip records that are too old to match.
+        if cursor.key_valid() && cursor.key() < &lower {
+            cursor.seek_key(&lower);
+        }
+
+        while cursor.key_valid() {
+            if !cursor.val_valid() {
+                cursor.step_key();
+                continue;
+            }
+            if cursor.key() > ts {
+                break;
+            }
+            if !cursor.weight().le0() {
+                self.last = Some((*cursor.key(), cursor.val().clone()));
+            }
+            cursor.step_val();
+        }
+
+        self.last.clone().filter(|(last_ts, _)| last_ts >= &lower)
+    }
+}
+
+/// Binary operator that computes changes to the output of the as-of join
+/// caused by changes to the left input.
+///
+/// * Input stream 1: updates to the left time series.
+/// * Input stream 2: trace containing the accumulated right time series,
+///   including the current update.
+struct AsofJoinLeft<TS, V1, V2> {
+    tolerance: TS,
+    phantom: PhantomData<(V1, V2)>,
+}
+
+impl<TS, V1, V2> AsofJoinLeft<TS, V1, V2> {
+    fn new(tolerance: TS) -> Self {
+        Self {
+            tolerance,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V1, V2> Operator for AsofJoinLeft<TS, V1, V2>
+where
+    TS: 'static,
+    V1: 'static,
+    V2: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("AsofJoinLeft")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<TS, V1, V2, B, T, O> BinaryOperator<B, T, O> for AsofJoinLeft<TS, V1, V2>
+where
+    TS: DBData + PrimInt,
+    V1: DBData,
+    V2: DBData,
+    B: PartitionedBatchReader<TS, V1> + Clone,
+    B::R: ZRingValue,
+    T: PartitionedBatchReader<TS, V2, Key = B::Key, R = B::R> + Clone,
+    O: IndexedZSet<Key = B::Key, Val = (TS, (V1, Option<(TS, V2)>)), R = B::R>,
+{
+    fn eval(&mut self, left_delta: &B, right_trace: &T) -> O {
+        let mut delta_cursor = left_delta.cursor();
+        let mut right_cursor = right_trace.cursor();
+
+        let mut builder = O::Builder::with_capacity((), left_delta.len());
+
+        while delta_cursor.key_valid() {
+            let partition = delta_cursor.key().clone();
+
+            right_cursor.seek_key(&partition);
+            let mut right_partition_cursor =
+                if right_cursor.key_valid() && right_cursor.key() == &partition {
+                    Some(PartitionCursor::new(&mut right_cursor))
+                } else {
+                    None
+                };
+
+            let mut matcher = AsofMatcher::new(self.tolerance);
+
+            while delta_cursor.val_valid() {
+                let weight = delta_cursor.weight();
+                let (ts, v1) = delta_cursor.val();
+
+                let asof = right_partition_cursor
+                    .as_mut()
+                    .and_then(|cursor| matcher.find(cursor, ts));
+
+                builder.push((
+                    O::item_from(partition.clone(), (*ts, (v1.clone(), asof))),
+                    weight,
+                ));
+
+                delta_cursor.step_val();
+            }
+
+            delta_cursor.step_key();
+        }
+
+        builder.done()
+    }
+}
+
+/// Quaternary operator that computes changes to the output of the as-of join
+/// caused by changes to the right input.
+///
+/// * Input stream 1: updates to the right time series.  Used to identify
+///   affected partitions and time ranges.
+/// * Input stream 2: trace containing the accumulated left time series,
+///   excluding the current update.
+/// * Input stream 3: trace containing the accumulated right time series,
+///   excluding the current update.
+/// * Input stream 4: trace containing the accumulated right time series,
+///   including the current update.
+///
+/// # Algorithm
+///
+/// A change to the right record with timestamp `ts` can only affect matches
+/// of left records with timestamps in `[ts, ts + tolerance]` that precede the
+/// next unmodified right record.  For all left records in the affected
+/// ranges, the operator computes matches before and after the update and
+/// outputs the difference.
+struct AsofJoinRight<TS, V1, V2> {
+    tolerance: TS,
+    phantom: PhantomData<(V1, V2)>,
+}
+
+impl<TS, V1, V2> AsofJoinRight<TS, V1, V2>
+where
+    TS: DBData + PrimInt,
+    V2: DBData,
+{
+    fn new(tolerance: TS) -> Self {
+        Self {
+            tolerance,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the smallest timestamp greater than `ts` that has a right
+    /// record with positive weight and isn't in `modified`.
+    ///
+    /// Timestamps passed to consecutive invocations with the same cursor must
+    /// be non-decreasing.
+    fn next_unmodified<'a, R, C>(cursor: &mut C, ts: &TS, modified: &[TS]) -> Option<TS>
+    where
+        R: ZRingValue,
+        C: Cursor<'a, TS, V2, (), R>,
+    {
+        if ts == &TS::max_value() {
+            return None;
+        }
+        cursor.seek_key(&(*ts + TS::one()));
+
+        while cursor.key_valid() {
+            if !cursor.val_valid() {
+                cursor.step_key();
+                continue;
+            }
+            if modified.binary_search(cursor.key()).is_ok() {
+                cursor.step_key();
+                continue;
+            }
+            if !cursor.weight().le0() {
+                return Some(*cursor.key());
+            }
+            cursor.step_val();
+        }
+
+        None
+    }
+}
+
+impl<TS, V1, V2> Operator for AsofJoinRight<TS, V1, V2>
+where
+    TS: 'static,
+    V1: 'static,
+    V2: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("AsofJoinRight")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<TS, V1, V2, B, LT, RT, O> QuaternaryOperator<B, LT, RT, RT, O> for AsofJoinRight<TS, V1, V2>
+where
+    TS: DBData + PrimInt,
+    V1: DBData,
+    V2: DBData,
+    B: PartitionedBatchReader<TS, V2> + Clone,
+    B::R: ZRingValue,
+    LT: PartitionedBatchReader<TS, V1, Key = B::Key, R = B::R> + Clone,
+    RT: PartitionedBatchReader<TS, V2, Key = B::Key, R = B::R> + Clone,
+    O: IndexedZSet<Key = B::Key, Val = (TS, (V1, Option<(TS, V2)>)), R = B::R>,
+{
+    fn eval<'a>(
+        &mut self,
+        right_delta: Cow<'a, B>,
+        left_trace: Cow<'a, LT>,
+        old_right_trace: Cow<'a, RT>,
+        new_right_trace: Cow<'a, RT>,
+    ) -> O {
+        let mut delta_cursor = right_delta.cursor();
+        let mut left_cursor = left_trace.cursor();
+        let mut old_right_cursor = old_right_trace.cursor();
+        let mut new_right_cursor = new_right_trace.cursor();
+        let mut bounds_cursor = new_right_trace.cursor();
+
+        let mut builder = O::Builder::new_builder(());
+
+        while delta_cursor.key_valid() {
+            let partition = delta_cursor.key().clone();
+
+            // Modified timestamps.
+            let mut modified: Vec<TS> = Vec::new();
+            while delta_cursor.val_valid() {
+                let ts = delta_cursor.val().0;
+                if modified.last() != Some(&ts) {
+                    modified.push(ts);
+                }
+                delta_cursor.step_val();
+            }
+
+            // Compute affected ranges.
+            bounds_cursor.seek_key(&partition);
+            let mut bounds_partition_cursor =
+                if bounds_cursor.key_valid() && bounds_cursor.key() == &partition {
+                    Some(PartitionCursor::new(&mut bounds_cursor))
+                } else {
+                    None
+                };
+
+            let mut ranges = Ranges::with_capacity(modified.len());
+            for ts in modified.iter() {
+                let next = bounds_partition_cursor
+                    .as_mut()
+                    .and_then(|cursor| Self::next_unmodified(cursor, ts, &modified));
+                let to = match next {
+                    Some(next) => min(ts.saturating_add(self.tolerance), next - TS::one()),
+                    None => ts.saturating_add(self.tolerance),
+                };
+                ranges.push_monotonic(Range::new(*ts, to));
+            }
+
+            // Recompute matches for left records in affected ranges.
+            left_cursor.seek_key(&partition);
+            if left_cursor.key_valid() && left_cursor.key() == &partition {
+                let mut left_range_cursor =
+                    RangeCursor::new(PartitionCursor::new(&mut left_cursor), ranges);
+
+                old_right_cursor.seek_key(&partition);
+                let mut old_right_partition_cursor =
+                    if old_right_cursor.key_valid() && old_right_cursor.key() == &partition {
+                        Some(PartitionCursor::new(&mut old_right_cursor))
+                    } else {
+                        None
+                    };
+
+                new_right_cursor.seek_key(&partition);
+                let mut new_right_partition_cursor =
+                    if new_right_cursor.key_valid() && new_right_cursor.key() == &partition {
+                        Some(PartitionCursor::new(&mut new_right_cursor))
+                    } else {
+                        None
+                    };
+
+                let mut old_matcher = AsofMatcher::new(self.tolerance);
+                let mut new_matcher = AsofMatcher::new(self.tolerance);
+
+                while left_range_cursor.key_valid() {
+                    let ts = *left_range_cursor.key();
+
+                    let old_asof = old_right_partition_cursor
+                        .as_mut()
+                        .and_then(|cursor| old_matcher.find(cursor, &ts));
+                    let new_asof = new_right_partition_cursor
+                        .as_mut()
+                        .and_then(|cursor| new_matcher.find(cursor, &ts));
+
+                    if old_asof != new_asof {
+                        while left_range_cursor.val_valid() {
+                            let weight = left_range_cursor.weight();
+                            if !weight.is_zero() {
+                                let v1 = left_range_cursor.val();
+
+                                // Push updates in the order expected by the builder.
+                                let mut updates = [
+                                    (old_asof.clone(), weight.clone().neg()),
+                                    (new_asof.clone(), weight),
+                                ];
+                                updates.sort_by(|(asof1, _), (asof2, _)| asof1.cmp(asof2));
+
+                                for (asof, weight) in updates {
+                                    builder.push((
+                                        O::item_from(partition.clone(), (ts, (v1.clone(), asof))),
+                                        weight,
+                                    ));
+                                }
+                            }
+                            left_range_cursor.step_val();
+                        }
+                    }
+
+                    left_range_cursor.step_key();
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        builder.done()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, RootCircuit, Runtime, Stream,
+    };
+
+    type TradeBatch = OrdIndexedZSet<u64, (u64, i64), isize>;
+    type QuoteBatch = OrdIndexedZSet<u64, (u64, String), isize>;
+    type OutputBatch = OrdIndexedZSet<u64, (u64, (i64, Option<(u64, String)>)), isize>;
+    type OutputStream = Stream<RootCircuit, OutputBatch>;
+
+    // Reference implementation of `asof_join`.
+    fn asof_join_slow(
+        left: &Stream<RootCircuit, TradeBatch>,
+        right: &Stream<RootCircuit, QuoteBatch>,
+        tolerance: u64,
+    ) -> OutputStream {
+        left.gather(0).integrate().apply2(
+            &right.gather(0).integrate(),
+            move |left: &TradeBatch, right: &QuoteBatch| {
+                let mut tuples = Vec::new();
+                let mut left_cursor = left.cursor();
+
+                while left_cursor.key_valid() {
+                    let partition = *left_cursor.key();
+
+                    while left_cursor.val_valid() {
+                        let (ts, v1) = left_cursor.val();
+
+                        let mut asof = None;
+                        let mut right_cursor = right.cursor();
+                        right_cursor.seek_key(&partition);
+                        if right_cursor.key_valid() && right_cursor.key() == &partition {
+                            while right_cursor.val_valid() {
+                                let (ts2, v2) = right_cursor.val();
+                                if ts2 <= ts && ts - ts2 <= tolerance && right_cursor.weight() > 0 {
+                                    asof = Some((*ts2, v2.clone()));
+                                }
+                                right_cursor.step_val();
+                            }
+                        }
+
+                        tuples.push(((partition, (*ts, (*v1, asof))), left_cursor.weight()));
+                        left_cursor.step_val();
+                    }
+
+                    left_cursor.step_key();
+                }
+
+                OutputBatch::from_tuples((), tuples)
+            },
+        )
+    }
+
+    type TradeHandle = CollectionHandle<u64, ((u64, i64), isize)>;
+    type QuoteHandle = CollectionHandle<u64, ((u64, String), isize)>;
+
+    fn asof_join_circuit(tolerances: &[u64]) -> (DBSPHandle, (TradeHandle, QuoteHandle)) {
+        let tolerances = tolerances.to_vec();
+
+        Runtime::init_circuit(4, move |circuit| {
+            let (trades, trades_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (quotes, quotes_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, String), isize>();
+
+            for tolerance in tolerances {
+                let expected = asof_join_slow(&trades, &quotes, tolerance);
+                let actual = trades
+                    .asof_join::<u64, i64, String, _>(&quotes, tolerance)
+                    .gather(0)
+                    .integrate();
+                expected.apply2(&actual, |expected, actual| assert_eq!(expected, actual));
+            }
+
+            (trades_handle, quotes_handle)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_asof_join() {
+        let (circuit, (trades_handle, quotes_handle)) = RootCircuit::build(move |circuit| {
+            let (trades, trades_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (quotes, quotes_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, String), isize>();
+
+            let mut expected_outputs = vec![
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (10, (1, Some((5, "a".to_string()))))), 1),
+                        ((0, (20, (2, Some((15, "b".to_string()))))), 1),
+                        ((0, (40, (3, None))), 1),
+                    ],
+                ),
+                // Late quote displaces previous match.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (20, (2, Some((15, "b".to_string()))))), -1),
+                        ((0, (20, (2, Some((18, "c".to_string()))))), 1),
+                    ],
+                ),
+                // Late trade.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![((0, (16, (4, Some((15, "b".to_string()))))), 1)],
+                ),
+                // Retract quote.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (16, (4, Some((15, "b".to_string()))))), -1),
+                        ((0, (16, (4, None))), 1),
+                    ],
+                ),
+            ]
+            .into_iter();
+
+            trades
+                .asof_join::<u64, i64, String, _>(&quotes, 10)
+                .inspect(move |batch| assert_eq!(batch, &expected_outputs.next().unwrap()));
+
+            (trades_handle, quotes_handle)
+        })
+        .unwrap();
+
+        quotes_handle.push(0, ((5, "a".to_string()), 1));
+        quotes_handle.push(0, ((15, "b".to_string()), 1));
+        quotes_handle.push(0, ((25, "x".to_string()), 1));
+        quotes_handle.push(0, ((25, "x".to_string()), -1));
+        trades_handle.push(0, ((10, 1), 1));
+        trades_handle.push(0, ((20, 2), 1));
+        trades_handle.push(0, ((40, 3), 1));
+        circuit.step().unwrap();
+
+        quotes_handle.push(0, ((18, "c".to_string()), 1));
+        circuit.step().unwrap();
+
+        trades_handle.push(0, ((16, 4), 1));
+        circuit.step().unwrap();
+
+        quotes_handle.push(0, ((15, "b".to_string()), -1));
+        circuit.step().unwrap();
+    }
+
+    #[test]
+    fn test_asof_join_multiple_partitions() {
+        let (mut circuit, (mut trades, mut quotes)) = asof_join_circuit(&[0, 10, u64::MAX]);
+
+        trades.append(&mut vec![
+            (0, ((10, 1), 1)),
+            (0, ((10, 2), 2)),
+            (0, ((30, 3), 1)),
+            (1, ((100, 4), 1)),
+        ]);
+        quotes.append(&mut vec![
+            (0, ((10, "a".to_string()), 1)),
+            (0, ((10, "b".to_string()), 1)),
+            (1, ((50, "c".to_string()), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        // Late quotes in both partitions.
+        quotes.append(&mut vec![
+            (0, ((25, "d".to_string()), 1)),
+            (1, ((95, "e".to_string()), 1)),
+            (1, ((100, "f".to_string()), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        // Retract quotes and trades.
+        quotes.append(&mut vec![
+            (0, ((10, "b".to_string()), -1)),
+            (1, ((100, "f".to_string()), -1)),
+        ]);
+        trades.append(&mut vec![(0, ((10, 2), -1))]);
+        circuit.step().unwrap();
+
+        circuit.kill().unwrap();
+    }
+
+    use proptest::{collection, prelude::*};
+
+    type TradeTuple = (u64, ((u64, i64), isize));
+    type QuoteTuple = (u64, ((u64, String), isize));
+
+    fn trade_tuple(partitions: u64, epoch: u64) -> impl Strategy<Value = TradeTuple> {
+        ((0..partitions), ((0..epoch, 0..5i64), 1..3isize))
+    }
+
+    fn quote_tuple(partitions: u64, epoch: u64) -> impl Strategy<Value = QuoteTuple> {
+        (
+            (0..partitions),
+            ((0..epoch, "[a-c]".prop_map(|s| s.to_string())), 1..3isize),
+        )
+    }
+
+    // Each step inserts batches of new values into both inputs in arbitrary
+    // time order and retracts some of the previously inserted values,
+    // identified by their indexes.
+    #[allow(clippy::type_complexity)]
+    fn input_trace(
+        partitions: u64,
+        epoch: u64,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<(Vec<TradeTuple>, Vec<QuoteTuple>, Vec<usize>, Vec<usize>)>>
+    {
+        collection::vec(
+            (
+                collection::vec(trade_tuple(partitions, epoch), 0..max_batch_size),
+                collection::vec(quote_tuple(partitions, epoch), 0..max_batch_size),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+            ),
+            0..max_batches,
+        )
+    }
+
+    fn retract<T: Clone>(
+        inserted: &mut Vec<(u64, (T, isize))>,
+        indexes: Vec<usize>,
+    ) -> Vec<(u64, (T, isize))> {
+        let mut retractions = Vec::new();
+
+        for index in indexes {
+            if index < inserted.len() {
+                let (partition, (val, w)) = inserted.swap_remove(index);
+                retractions.push((partition, (val, -w)));
+            }
+        }
+
+        retractions
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_trace(trace: Vec<(Vec<TradeTuple>, Vec<QuoteTuple>, Vec<usize>, Vec<usize>)>) {
+        let (mut circuit, (mut trades, mut quotes)) = asof_join_circuit(&[0, 5, 100, u64::MAX]);
+
+        let mut inserted_trades = Vec::new();
+        let mut inserted_quotes = Vec::new();
+
+        for (mut trade_batch, mut quote_batch, trade_retractions, quote_retractions) in trace {
+            inserted_trades.extend(trade_batch.iter().cloned());
+            inserted_quotes.extend(quote_batch.iter().cloned());
+
+            trade_batch.extend(retract(&mut inserted_trades, trade_retractions));
+            quote_batch.extend(retract(&mut inserted_quotes, quote_retractions));
+
+            trades.append(&mut trade_batch);
+            quotes.append(&mut quote_batch);
+            circuit.step().unwrap();
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_asof_join_sparse(trace in input_trace(5, 1_000_000, 20, 20)) {
+            run_trace(trace);
+        }
+
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_asof_join_dense(trace in input_trace(3, 200, 30, 20)) {
+            run_trace(trace);
+        }
+    }
+}
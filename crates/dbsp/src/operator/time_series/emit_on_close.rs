@@ -0,0 +1,380 @@
+use crate::{
+    algebra::{HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope,
+    },
+    trace::{Batch, BatchReader, Cursor, Spine, Trace},
+    Circuit, DBData, OrdIndexedZSet, RootCircuit, Stream,
+};
+use std::{borrow::Cow, marker::PhantomData};
+
+/// Updates buffered until their windows close, indexed by
+/// `(window_end, key)`, so that closed windows form a prefix of the trace.
+/// `(window_end, None)` is the smallest key in a window.
+type PendingUpdates<TS, K, V, R> = OrdIndexedZSet<(TS, Option<K>), V, R>;
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet + Send,
+    B::R: ZRingValue,
+{
+    /// Emit the final contents of each window once the watermark moves past
+    /// the end of the window (`EMIT ON CLOSE` semantics).
+    ///
+    /// `self` is a stream of changes to a collection whose keys identify
+    /// windows, e.g., the output of
+    /// [`tumbling_window_aggregate`](`Self::tumbling_window_aggregate`),
+    /// and `window_end` returns the end of the window of a key.  A window is
+    /// closed once its end falls below the watermark, i.e., when
+    /// `window_end(key) < watermark`.
+    ///
+    /// The operator buffers updates to keys in open windows.  When a window
+    /// closes, it outputs the consolidated updates to all keys in the window
+    /// exactly once and discards them.  Refinements received before the
+    /// window closes, e.g., a value that was inserted and then replaced, are
+    /// consolidated, so the output only contains the final contents of the
+    /// window.  Updates to windows that have already been closed are dropped;
+    /// use
+    /// [`emit_on_close_with_late_output`](`Self::emit_on_close_with_late_output`)
+    /// to route them to a side output instead.
+    ///
+    /// # State
+    ///
+    /// The operator stores updates in open windows only.  The state of each
+    /// window is discarded as soon as the window is emitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `watermark` - monotonically growing lower bound on the window ends
+    ///   of future updates, e.g., computed by the
+    ///   [`watermark_monotonic`](`Stream::watermark_monotonic`) operator.
+    /// * `window_end` - returns the end of the window that a key belongs to.
+    #[allow(clippy::type_complexity)]
+    pub fn emit_on_close<TS, F>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        window_end: F,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, B::Val, B::R>>
+    where
+        TS: DBData,
+        F: Fn(&B::Key) -> TS + 'static,
+    {
+        self.emit_on_close_inner(watermark, window_end)
+            .apply(|(emitted, _late)| emitted.clone())
+            .mark_sharded()
+    }
+
+    /// Like [`emit_on_close`](`Self::emit_on_close`), but also returns a
+    /// side output that contains updates to windows that were closed at
+    /// previous clock cycles.  Such updates do not affect the output of
+    /// `emit_on_close`.
+    ///
+    /// Returns a pair of streams `(emitted, late)`.
+    #[allow(clippy::type_complexity)]
+    pub fn emit_on_close_with_late_output<TS, F>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        window_end: F,
+    ) -> (
+        Stream<RootCircuit, OrdIndexedZSet<B::Key, B::Val, B::R>>,
+        Stream<RootCircuit, OrdIndexedZSet<B::Key, B::Val, B::R>>,
+    )
+    where
+        TS: DBData,
+        F: Fn(&B::Key) -> TS + 'static,
+    {
+        let output = self.emit_on_close_inner(watermark, window_end);
+
+        (
+            output
+                .apply(|(emitted, _late)| emitted.clone())
+                .mark_sharded(),
+            output.apply(|(_emitted, late)| late.clone()).mark_sharded(),
+        )
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn emit_on_close_inner<TS, F>(
+        &self,
+        watermark: &Stream<RootCircuit, TS>,
+        window_end: F,
+    ) -> Stream<
+        RootCircuit,
+        (
+            OrdIndexedZSet<B::Key, B::Val, B::R>,
+            OrdIndexedZSet<B::Key, B::Val, B::R>,
+        ),
+    >
+    where
+        TS: DBData,
+        F: Fn(&B::Key) -> TS + 'static,
+    {
+        self.circuit().region("emit_on_close", || {
+            self.circuit().add_binary_operator(
+                EmitOnClose::new(window_end),
+                &self.shard(),
+                watermark,
+            )
+        })
+    }
+}
+
+/// Binary operator that implements the internals of `emit_on_close`.
+///
+/// * Input stream 1: updates to the collection.
+/// * Input stream 2: watermark.
+///
+/// Outputs a pair of batches: updates to windows closed at this clock cycle
+/// and updates to windows closed at previous clock cycles.
+struct EmitOnClose<B, TS, F>
+where
+    B: BatchReader,
+    TS: DBData,
+{
+    window_end: F,
+    // Updates to open windows.
+    pending: Spine<PendingUpdates<TS, B::Key, B::Val, B::R>>,
+    // The watermark at the previous clock cycle.  Windows with ends below
+    // it have been emitted.
+    watermark: Option<TS>,
+    _phantom: PhantomData<B>,
+}
+
+impl<B, TS, F> EmitOnClose<B, TS, F>
+where
+    B: BatchReader<Time = ()>,
+    TS: DBData,
+{
+    fn new(window_end: F) -> Self {
+        Self {
+            window_end,
+            pending: Spine::new(None),
+            watermark: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<B, TS, F> Operator for EmitOnClose<B, TS, F>
+where
+    B: BatchReader + 'static,
+    TS: DBData,
+    F: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("EmitOnClose")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<B, TS, F>
+    BinaryOperator<
+        B,
+        TS,
+        (
+            OrdIndexedZSet<B::Key, B::Val, B::R>,
+            OrdIndexedZSet<B::Key, B::Val, B::R>,
+        ),
+    > for EmitOnClose<B, TS, F>
+where
+    B: IndexedZSet,
+    B::R: ZRingValue,
+    TS: DBData,
+    F: Fn(&B::Key) -> TS + 'static,
+{
+    fn eval(
+        &mut self,
+        delta: &B,
+        watermark: &TS,
+    ) -> (
+        OrdIndexedZSet<B::Key, B::Val, B::R>,
+        OrdIndexedZSet<B::Key, B::Val, B::R>,
+    ) {
+        let mut pending = Vec::with_capacity(delta.len());
+        let mut late = Vec::new();
+
+        let mut cursor = delta.cursor();
+        while cursor.key_valid() {
+            let end = (self.window_end)(cursor.key());
+            let closed = matches!(&self.watermark, Some(watermark) if &end < watermark);
+
+            while cursor.val_valid() {
+                if closed {
+                    late.push((
+                        (cursor.key().clone(), cursor.val().clone()),
+                        cursor.weight(),
+                    ));
+                } else {
+                    pending.push((
+                        (
+                            (end.clone(), Some(cursor.key().clone())),
+                            cursor.val().clone(),
+                        ),
+                        cursor.weight(),
+                    ));
+                }
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        if !pending.is_empty() {
+            self.pending
+                .insert(PendingUpdates::from_tuples((), pending));
+        }
+
+        // Emit windows that closed since the previous clock cycle.  Ignore
+        // watermarks that don't move forward.
+        let mut emitted = Vec::new();
+        if !matches!(&self.watermark, Some(previous) if watermark <= previous) {
+            let mut cursor = self.pending.cursor();
+            while cursor.key_valid() && &cursor.key().0 < watermark {
+                while cursor.val_valid() {
+                    // Refinements of the same value may cancel out.
+                    let weight = cursor.weight();
+                    if !weight.is_zero() {
+                        emitted.push((
+                            (cursor.key().1.clone().unwrap(), cursor.val().clone()),
+                            weight,
+                        ));
+                    }
+                    cursor.step_val();
+                }
+                cursor.step_key();
+            }
+
+            self.pending.truncate_keys_below(&(watermark.clone(), None));
+            self.watermark = Some(watermark.clone());
+        }
+
+        (
+            OrdIndexedZSet::from_tuples((), emitted),
+            OrdIndexedZSet::from_tuples((), late),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        indexed_zset, operator::FilterMap, trace::Batch, CollectionHandle, DBSPHandle, InputHandle,
+        OrdIndexedZSet, OutputHandle, Runtime,
+    };
+
+    type Windows = OrdIndexedZSet<(u64, u64), u64, isize>;
+
+    const WINDOW_SIZE: u64 = 10;
+
+    // Input: records `(partition, (timestamp, value))` and the watermark.
+    // Output: the sum of values in each `((partition, window_start), sum)`
+    // emitted once the window closes.
+    #[allow(clippy::type_complexity)]
+    fn emit_on_close_circuit(
+        workers: usize,
+    ) -> (
+        DBSPHandle,
+        (
+            CollectionHandle<u64, ((u64, u64), isize)>,
+            InputHandle<u64>,
+            OutputHandle<Windows>,
+            OutputHandle<Windows>,
+            OutputHandle<Windows>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, u64), isize>();
+
+            let (watermark, watermark_handle) = circuit.add_input_stream::<u64>();
+            let windows = input
+                .map_index(|(partition, (ts, val))| ((*partition, ts - ts % WINDOW_SIZE), *val))
+                .aggregate_linear(|_key, val| *val as isize)
+                .map_index(|(key, sum)| (*key, *sum as u64));
+
+            let (emitted, late) = windows
+                .emit_on_close_with_late_output(&watermark, |(_partition, start)| {
+                    start + WINDOW_SIZE
+                });
+
+            (
+                input_handle,
+                watermark_handle,
+                windows.output(),
+                emitted.output(),
+                late.output(),
+            )
+        })
+        .unwrap()
+    }
+
+    fn test_emit_on_close(workers: usize) {
+        let (mut dbsp, (mut input, watermark, refinements, emitted, late)) =
+            emit_on_close_circuit(workers);
+
+        // Refinements of open windows are not emitted.
+        input.append(&mut vec![
+            (1, ((1, 1), 1)),
+            (1, ((5, 2), 1)),
+            (2, ((3, 3), 1)),
+            (1, ((12, 4), 1)),
+        ]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            refinements.consolidate(),
+            indexed_zset! { (1, 0) => { 3 => 1 }, (1, 10) => { 4 => 1 }, (2, 0) => { 3 => 1 } }
+        );
+        assert_eq!(emitted.consolidate(), Windows::empty(()));
+
+        // Out-of-order record refines window 0, which is still open.
+        input.append(&mut vec![(2, ((9, 10), 1)), (1, ((15, 1), 1))]);
+        watermark.set_for_all(5);
+        dbsp.step().unwrap();
+        assert_eq!(emitted.consolidate(), Windows::empty(()));
+
+        // The watermark moves past the end of window 0.  The window is emitted
+        // exactly once, with consolidated contents.
+        input.append(&mut vec![(3, ((11, 1), 1))]);
+        watermark.set_for_all(11);
+        dbsp.step().unwrap();
+        assert_eq!(
+            emitted.consolidate(),
+            indexed_zset! { (1, 0) => { 3 => 1 }, (2, 0) => { 13 => 1 } }
+        );
+        assert_eq!(late.consolidate(), Windows::empty(()));
+
+        // Late update to window 0 goes to the side output.
+        input.append(&mut vec![(1, ((2, 5), 1)), (1, ((19, 1), 1))]);
+        watermark.set_for_all(12);
+        dbsp.step().unwrap();
+        assert_eq!(emitted.consolidate(), Windows::empty(()));
+        assert_eq!(
+            late.consolidate(),
+            indexed_zset! { (1, 0) => { 3 => -1, 8 => 1 } }
+        );
+
+        // Close window 10.
+        input.append(&mut vec![(1, ((25, 1), 1))]);
+        watermark.set_for_all(21);
+        dbsp.step().unwrap();
+        assert_eq!(
+            emitted.consolidate(),
+            indexed_zset! { (1, 10) => { 6 => 1 }, (3, 10) => { 1 => 1 } }
+        );
+        assert_eq!(late.consolidate(), Windows::empty(()));
+
+        // Nothing else to emit.  A watermark that moves backward is ignored.
+        dbsp.step().unwrap();
+        assert_eq!(emitted.consolidate(), Windows::empty(()));
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn emit_on_close_test() {
+        test_emit_on_close(1);
+        test_emit_on_close(4);
+    }
+}
@@ -0,0 +1,682 @@
+use crate::{
+    algebra::{HasOne, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{Operator, QuaternaryOperator, TernaryOperator},
+        OwnershipPreference, Scope,
+    },
+    operator::{
+        time_series::{OrdPartitionedIndexedZSet, PartitionedBatchReader, PartitionedIndexedZSet},
+        trace::{
+            DelayedTraceId, IntegrateTraceId, TraceBound, TraceBounds, UntimedTraceAppend, Z1Trace,
+        },
+    },
+    trace::{consolidation::consolidate, Batch, BatchReader, Builder, Cursor, Spine},
+    Circuit, DBData, OrdIndexedZSet, RootCircuit, Stream,
+};
+use num::PrimInt;
+use std::{borrow::Cow, marker::PhantomData, ops::Neg};
+
+/// Index of live partitions by their newest timestamp:
+/// `(newest_timestamp, partition)`.
+type OrdNewestIndex<TS, PK, R> = OrdIndexedZSet<TS, PK, R>;
+
+impl<B> Stream<RootCircuit, B> {
+    /// Expire partitions of a time series that haven't received new data
+    /// for `ttl` time units.
+    ///
+    /// Outputs the contents of the input stream, except that all records in
+    /// a partition are dropped once the newest timestamp in the partition
+    /// falls below `watermark - ttl`.  When this happens, the operator outputs
+    /// retractions of all records in the partition.  Retractions are output
+    /// exactly once, at the step when the watermark moves past the threshold.
+    ///
+    /// Records with timestamps at or above `watermark - ttl` are never
+    /// expired, even if they arrive out of order.  A new record arriving in
+    /// an expired partition starts a new partition, unless its timestamp is
+    /// already below `watermark - ttl`, in which case it is dropped
+    /// immediately.  Retractions of records that have been expired should
+    /// not be fed to the operator.
+    ///
+    /// # State
+    ///
+    /// The operator maintains the integral of its output, which only
+    /// contains live partitions, and an index of live partitions by their
+    /// newest timestamp, which is truncated below `watermark - ttl`.  Hence
+    /// the state of the operator is proportional to the size of live
+    /// partitions.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data partitioned by partition key and indexed by
+    ///   time within each partition.
+    /// * `ttl` - time to live of a partition after its newest timestamp.
+    /// * `watermark` - monotonically growing lower bound on timestamps in the
+    ///   input stream, e.g., computed by the
+    ///   [`watermark_monotonic`](`Stream::watermark_monotonic`) operator.
+    pub fn expire_after<TS, V>(
+        &self,
+        ttl: TS,
+        watermark: &Stream<RootCircuit, TS>,
+    ) -> Stream<RootCircuit, OrdPartitionedIndexedZSet<B::Key, TS, V, B::R>>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        // ```
+        //               ┌──────────────────────────────────────────────────────────────►
+        //               │                                                      output
+        // self   ┌──────┴────┐       ┌──────────────────┐  live_trace   ┌───────────┐
+        // ──────►│ExpireAfter├──────►│UntimedTraceAppend├──────────────►│ExpireIndex│
+        //        └───────────┘       └──────────────────┘               └─────┬─────┘
+        //         ▲    ▲    ▲                  ▲    │                         │ newest_delta
+        //         │    │    │                ┌─┴──┐ │                         ▼
+        //         │    │    └────────────────┤Z^-1│◄┘              ┌──────────────────┐
+        //         │    │  live_trace_delayed └────┘                │UntimedTraceAppend│
+        //         │    │                                           └─────────┬────────┘
+        //         │    │                     ┌────┐  newest_trace            │
+        //         │    └─────────────────────┤Z^-1│◄─────────────────────────┘
+        //         │     newest_trace_delayed └────┘
+        //     threshold
+        // ```
+        //
+        // `ExpireIndex` additionally reads `live_trace_delayed` to compute
+        // the previous newest timestamp of each modified partition.
+        self.circuit().region("expire_after", || {
+            let circuit = self.circuit();
+            let stream = self.shard();
+
+            // Partitions whose newest timestamp falls below the threshold are
+            // expired and removed from the newest-timestamp index by
+            // `ExpireAfter`, so the index can be truncated at the threshold.
+            let bound: TraceBound<TS> = TraceBound::new();
+            let bound_clone = bound.clone();
+            let threshold = watermark.apply(move |wm| {
+                let threshold = wm.saturating_sub(ttl);
+                bound_clone.set(threshold);
+                threshold
+            });
+
+            let live_bounds = <TraceBounds<B::Key, (TS, V)>>::unbounded();
+            let (live_trace_delayed, live_z1feedback) = circuit.add_feedback(
+                <Z1Trace<Spine<_>>>::new(false, circuit.root_scope(), live_bounds.clone()),
+            );
+            live_trace_delayed.mark_sharded();
+
+            let newest_bounds = <TraceBounds<TS, B::Key>>::new();
+            newest_bounds.add_key_bound(bound);
+            newest_bounds.add_val_bound(TraceBound::new());
+            let (newest_trace_delayed, newest_z1feedback) =
+                circuit.add_feedback(<Z1Trace<Spine<OrdNewestIndex<TS, B::Key, B::R>>>>::new(
+                    false,
+                    circuit.root_scope(),
+                    newest_bounds,
+                ));
+
+            let output: Stream<_, OrdPartitionedIndexedZSet<_, _, _, _>> = circuit
+                .add_quaternary_operator(
+                    <ExpireAfter<TS, V>>::new(),
+                    &stream,
+                    &live_trace_delayed,
+                    &newest_trace_delayed,
+                    &threshold,
+                )
+                .mark_sharded();
+
+            let live_trace = circuit
+                .add_binary_operator_with_preference(
+                    <UntimedTraceAppend<Spine<_>>>::new(),
+                    (
+                        &live_trace_delayed,
+                        OwnershipPreference::STRONGLY_PREFER_OWNED,
+                    ),
+                    (&output, OwnershipPreference::PREFER_OWNED),
+                )
+                .mark_sharded();
+            live_z1feedback
+                .connect_with_preference(&live_trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+
+            let newest_delta: Stream<_, OrdNewestIndex<_, _, _>> = circuit.add_ternary_operator(
+                <ExpireIndex<TS, V>>::new(),
+                &output,
+                &live_trace_delayed,
+                &live_trace,
+            );
+            let newest_trace = circuit.add_binary_operator_with_preference(
+                <UntimedTraceAppend<Spine<_>>>::new(),
+                (
+                    &newest_trace_delayed,
+                    OwnershipPreference::STRONGLY_PREFER_OWNED,
+                ),
+                (&newest_delta, OwnershipPreference::PREFER_OWNED),
+            );
+            newest_z1feedback
+                .connect_with_preference(&newest_trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+
+            circuit.cache_insert(
+                DelayedTraceId::new(live_trace.origin_node_id().clone()),
+                live_trace_delayed,
+            );
+            circuit.cache_insert(
+                IntegrateTraceId::new(output.origin_node_id().clone()),
+                (live_trace, live_bounds),
+            );
+
+            output
+        })
+    }
+}
+
+/// Returns the newest timestamp with a positive weight in the current
+/// partition of `cursor`.
+fn newest_timestamp<'s, PK, TS, V, R, C>(cursor: &mut C) -> Option<TS>
+where
+    C: Cursor<'s, PK, (TS, V), (), R>,
+    TS: Copy,
+    R: ZRingValue,
+{
+    let mut newest = None;
+
+    while cursor.val_valid() {
+        if !cursor.weight().le0() {
+            newest = Some(cursor.val().0);
+        }
+        cursor.step_val();
+    }
+
+    newest
+}
+
+/// Quaternary operator that implements the internals of `expire_after`.
+///
+/// * Input stream 1: updates to the time series.
+/// * Input stream 2: trace of previously produced outputs, i.e., live
+///   partitions.
+/// * Input stream 3: index of live partitions by their newest timestamp.
+/// * Input stream 4: expiration threshold, i.e., `watermark - ttl`.
+///
+/// Evaluates partitions modified by the update and partitions whose newest
+/// timestamp fell below the threshold.  If the newest timestamp in the
+/// partition after applying the update is below the threshold, outputs
+/// retractions of all live records in the partition.  Otherwise, outputs the
+/// update unmodified.
+struct ExpireAfter<TS, V> {
+    phantom: PhantomData<(TS, V)>,
+}
+
+impl<TS, V> ExpireAfter<TS, V> {
+    fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V> Operator for ExpireAfter<TS, V>
+where
+    TS: 'static,
+    V: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("ExpireAfter")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<TS, V, B, LT, NT, O> QuaternaryOperator<B, LT, NT, TS, O> for ExpireAfter<TS, V>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    B: PartitionedBatchReader<TS, V> + Clone,
+    B::R: ZRingValue,
+    LT: PartitionedBatchReader<TS, V, Key = B::Key, R = B::R> + Clone,
+    NT: BatchReader<Key = TS, Val = B::Key, Time = (), R = B::R> + Clone,
+    O: IndexedZSet<Key = B::Key, Val = (TS, V), R = B::R>,
+{
+    fn eval<'a>(
+        &mut self,
+        delta: Cow<'a, B>,
+        live_trace: Cow<'a, LT>,
+        newest_trace: Cow<'a, NT>,
+        threshold: Cow<'a, TS>,
+    ) -> O {
+        let threshold = *threshold;
+
+        // Partitions whose newest timestamp is below the threshold.
+        let mut expired = Vec::new();
+        let mut newest_cursor = newest_trace.cursor();
+        while newest_cursor.key_valid() && newest_cursor.key() < &threshold {
+            while newest_cursor.val_valid() {
+                if !newest_cursor.weight().le0() {
+                    expired.push(newest_cursor.val().clone());
+                }
+                newest_cursor.step_val();
+            }
+            newest_cursor.step_key();
+        }
+        expired.sort();
+        let mut expired = expired.into_iter().peekable();
+
+        let mut delta_cursor = delta.cursor();
+        let mut live_cursor = live_trace.cursor();
+        let mut builder = O::Builder::with_capacity((), delta.len());
+
+        // Iterate over the union of partitions in `delta` and `expired`.
+        loop {
+            let partition = match (delta_cursor.get_key(), expired.peek()) {
+                (Some(key1), Some(key2)) => key1.min(key2).clone(),
+                (Some(key), None) | (None, Some(key)) => key.clone(),
+                (None, None) => break,
+            };
+
+            let in_delta = delta_cursor.get_key() == Some(&partition);
+            if expired.peek() == Some(&partition) {
+                expired.next();
+            }
+
+            // Live records in the partition.
+            let mut old = Vec::new();
+            live_cursor.seek_key(&partition);
+            if live_cursor.key_valid() && live_cursor.key() == &partition {
+                while live_cursor.val_valid() {
+                    old.push((live_cursor.val().clone(), live_cursor.weight()));
+                    live_cursor.step_val();
+                }
+            }
+
+            // Contents of the partition after applying the update.
+            let mut new = old.clone();
+            if in_delta {
+                while delta_cursor.val_valid() {
+                    new.push((delta_cursor.val().clone(), delta_cursor.weight()));
+                    delta_cursor.step_val();
+                }
+                delta_cursor.rewind_vals();
+            }
+            consolidate(&mut new);
+
+            let newest = new
+                .iter()
+                .rev()
+                .find(|(_, weight)| !weight.le0())
+                .map(|((ts, _), _)| *ts);
+
+            match newest {
+                Some(newest) if newest < threshold => {
+                    for (val, weight) in old {
+                        builder.push((O::item_from(partition.clone(), val), weight.neg()));
+                    }
+                }
+                _ if in_delta => {
+                    while delta_cursor.val_valid() {
+                        builder.push((
+                            O::item_from(partition.clone(), delta_cursor.val().clone()),
+                            delta_cursor.weight(),
+                        ));
+                        delta_cursor.step_val();
+                    }
+                }
+                _ => {}
+            }
+
+            if in_delta {
+                delta_cursor.step_key();
+            }
+        }
+
+        builder.done()
+    }
+}
+
+/// Ternary operator that computes changes to the index of live partitions by
+/// their newest timestamp.
+///
+/// * Input stream 1: changes to the output of `expire_after`.
+/// * Input stream 2: trace of live partitions before the changes.
+/// * Input stream 3: trace of live partitions after the changes.
+struct ExpireIndex<TS, V> {
+    phantom: PhantomData<(TS, V)>,
+}
+
+impl<TS, V> ExpireIndex<TS, V> {
+    fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, V> Operator for ExpireIndex<TS, V>
+where
+    TS: 'static,
+    V: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("ExpireIndex")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<TS, V, B, T, O> TernaryOperator<B, T, T, O> for ExpireIndex<TS, V>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    B: PartitionedBatchReader<TS, V> + Clone,
+    B::R: ZRingValue,
+    T: PartitionedBatchReader<TS, V, Key = B::Key, R = B::R> + Clone,
+    O: IndexedZSet<Key = TS, Val = B::Key, R = B::R>,
+{
+    fn eval<'a>(&mut self, delta: Cow<'a, B>, delayed_trace: Cow<'a, T>, trace: Cow<'a, T>) -> O {
+        let mut tuples = Vec::new();
+
+        let mut delta_cursor = delta.cursor();
+        let mut delayed_cursor = delayed_trace.cursor();
+        let mut cursor = trace.cursor();
+
+        while delta_cursor.key_valid() {
+            let partition = delta_cursor.key();
+
+            delayed_cursor.seek_key(partition);
+            let old_newest = if delayed_cursor.key_valid() && delayed_cursor.key() == partition {
+                newest_timestamp(&mut delayed_cursor)
+            } else {
+                None
+            };
+
+            cursor.seek_key(partition);
+            let new_newest = if cursor.key_valid() && cursor.key() == partition {
+                newest_timestamp(&mut cursor)
+            } else {
+                None
+            };
+
+            if old_newest != new_newest {
+                if let Some(ts) = old_newest {
+                    tuples.push((O::item_from(ts, partition.clone()), B::R::one().neg()));
+                }
+                if let Some(ts) = new_newest {
+                    tuples.push((O::item_from(ts, partition.clone()), B::R::one()));
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        O::from_tuples((), tuples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, InputHandle, OrdIndexedZSet, OutputHandle, RootCircuit,
+        Runtime,
+    };
+    use std::{
+        cell::RefCell,
+        collections::{BTreeMap, BTreeSet},
+        rc::Rc,
+    };
+
+    type InputBatch = OrdIndexedZSet<u64, (u64, String), isize>;
+
+    #[test]
+    fn test_expire_after() {
+        let live_sizes = Rc::new(RefCell::new(Vec::new()));
+        let live_sizes_clone = live_sizes.clone();
+
+        let (circuit, (input_handle, watermark_handle)) = RootCircuit::build(move |circuit| {
+            let (input, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, String), isize>();
+            let (watermark, watermark_handle) = circuit.add_input_stream::<u64>();
+
+            let mut expected_outputs = vec![
+                InputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (5, "a".to_string())), 1),
+                        ((0, (8, "b".to_string())), 1),
+                        ((1, (20, "c".to_string())), 1),
+                    ],
+                ),
+                // Out-of-order record in a live partition.
+                InputBatch::from_tuples((), vec![((1, (3, "x".to_string())), 1)]),
+                // Partition 0 expires; partition 2 is expired on arrival.
+                InputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (5, "a".to_string())), -1),
+                        ((0, (8, "b".to_string())), -1),
+                    ],
+                ),
+                // New data keeps partition 1 alive.
+                InputBatch::from_tuples((), vec![((1, (30, "e".to_string())), 1)]),
+                // Partition 1 expires.
+                InputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((1, (3, "x".to_string())), -1),
+                        ((1, (20, "c".to_string())), -1),
+                        ((1, (30, "e".to_string())), -1),
+                    ],
+                ),
+                // Nothing left to expire.
+                InputBatch::from_tuples((), vec![]),
+            ]
+            .into_iter();
+
+            let output = input.expire_after(10, &watermark);
+            output.inspect(move |batch| assert_eq!(batch, &expected_outputs.next().unwrap()));
+
+            // The integral of the output is the internal trace of live records.
+            output
+                .integrate_trace()
+                .apply(|trace| {
+                    let mut count = 0;
+                    let mut cursor = trace.cursor();
+                    while cursor.key_valid() {
+                        while cursor.val_valid() {
+                            if cursor.weight() != 0 {
+                                count += 1;
+                            }
+                            cursor.step_val();
+                        }
+                        cursor.step_key();
+                    }
+                    count
+                })
+                .inspect(move |count| live_sizes_clone.borrow_mut().push(*count));
+
+            (input_handle, watermark_handle)
+        })
+        .unwrap();
+
+        watermark_handle.set_for_all(0);
+        input_handle.push(0, ((5, "a".to_string()), 1));
+        input_handle.push(0, ((8, "b".to_string()), 1));
+        input_handle.push(1, ((20, "c".to_string()), 1));
+        circuit.step().unwrap();
+
+        watermark_handle.set_for_all(15);
+        input_handle.push(1, ((3, "x".to_string()), 1));
+        circuit.step().unwrap();
+
+        watermark_handle.set_for_all(25);
+        input_handle.push(2, ((12, "d".to_string()), 1));
+        circuit.step().unwrap();
+
+        watermark_handle.set_for_all(32);
+        input_handle.push(1, ((30, "e".to_string()), 1));
+        circuit.step().unwrap();
+
+        watermark_handle.set_for_all(45);
+        circuit.step().unwrap();
+
+        watermark_handle.set_for_all(45);
+        circuit.step().unwrap();
+
+        assert_eq!(&*live_sizes.borrow(), &[3, 4, 2, 3, 0, 0]);
+    }
+
+    use proptest::{collection, prelude::*};
+
+    type InputBatchHandle = CollectionHandle<u64, ((u64, String), isize)>;
+
+    fn expire_after_circuit(
+        workers: usize,
+        ttl: u64,
+    ) -> (
+        DBSPHandle,
+        (InputBatchHandle, InputHandle<u64>, OutputHandle<InputBatch>),
+    ) {
+        Runtime::init_circuit(workers, move |circuit| {
+            let (input, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, String), isize>();
+            let (watermark, watermark_handle) = circuit.add_input_stream::<u64>();
+
+            let output_handle = input.expire_after(ttl, &watermark).integrate().output();
+
+            (input_handle, watermark_handle, output_handle)
+        })
+        .unwrap()
+    }
+
+    // Reference model of `expire_after`: live records in each partition.
+    #[derive(Default)]
+    struct Model {
+        live: BTreeMap<u64, BTreeMap<(u64, String), isize>>,
+    }
+
+    impl Model {
+        fn step(&mut self, delta: &[(u64, ((u64, String), isize))], threshold: u64) {
+            let mut partitions: BTreeSet<u64> = self.live.keys().cloned().collect();
+
+            for (partition, (val, weight)) in delta.iter() {
+                partitions.insert(*partition);
+                let records = self.live.entry(*partition).or_default();
+                *records.entry(val.clone()).or_default() += weight;
+                records.retain(|_, weight| *weight != 0);
+            }
+
+            for partition in partitions {
+                let newest = self.live[&partition]
+                    .iter()
+                    .rev()
+                    .find(|(_, weight)| **weight > 0)
+                    .map(|((ts, _), _)| *ts);
+
+                if matches!(newest, Some(newest) if newest < threshold)
+                    || self.live[&partition].is_empty()
+                {
+                    self.live.remove(&partition);
+                }
+            }
+        }
+
+        fn records(&self) -> Vec<(u64, ((u64, String), isize))> {
+            self.live
+                .iter()
+                .flat_map(|(partition, records)| {
+                    records
+                        .iter()
+                        .map(|(val, weight)| (*partition, (val.clone(), *weight)))
+                })
+                .collect()
+        }
+
+        fn to_batch(&self) -> InputBatch {
+            InputBatch::from_tuples(
+                (),
+                self.records()
+                    .into_iter()
+                    .map(|(partition, (val, weight))| ((partition, val), weight))
+                    .collect(),
+            )
+        }
+    }
+
+    type Step = (u64, Vec<(u64, (i64, String, isize))>, Vec<usize>);
+
+    // Each step advances the watermark, inserts new records around the
+    // watermark, and retracts some of the live records, identified by their
+    // indexes.
+    fn input_trace(
+        partitions: u64,
+        max_batch_size: usize,
+        max_steps: usize,
+    ) -> impl Strategy<Value = Vec<Step>> {
+        collection::vec(
+            (
+                0..10u64,
+                collection::vec(
+                    (
+                        0..partitions,
+                        (-20..30i64, "[a-c]".prop_map(|s| s.to_string()), 1..3isize),
+                    ),
+                    0..max_batch_size,
+                ),
+                collection::vec(0..100usize, 0..max_batch_size / 2),
+            ),
+            0..max_steps,
+        )
+    }
+
+    fn run_trace(workers: usize, ttl: u64, trace: Vec<Step>) {
+        let (mut circuit, (mut input_handle, watermark_handle, output_handle)) =
+            expire_after_circuit(workers, ttl);
+
+        let mut model = Model::default();
+        let mut watermark = 0;
+
+        for (advance, inserts, retractions) in trace {
+            watermark += advance;
+
+            let mut delta: Vec<_> = inserts
+                .into_iter()
+                .map(|(partition, (offset, val, weight))| {
+                    let ts = (watermark as i64 + offset).max(0) as u64;
+                    (partition, ((ts, val), weight))
+                })
+                .collect();
+
+            let live = model.records();
+            for index in retractions.into_iter().collect::<BTreeSet<_>>() {
+                if let Some((partition, (val, weight))) = live.get(index) {
+                    delta.push((*partition, (val.clone(), -weight)));
+                }
+            }
+
+            model.step(&delta, watermark.saturating_sub(ttl));
+
+            input_handle.append(&mut delta);
+            watermark_handle.set_for_all(watermark);
+            circuit.step().unwrap();
+
+            assert_eq!(output_handle.consolidate(), model.to_batch());
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_expire_after(trace in input_trace(5, 20, 50)) {
+            run_trace(4, 10, trace);
+        }
+
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_expire_after_zero_ttl(trace in input_trace(3, 10, 50)) {
+            run_trace(2, 0, trace);
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use crate::{
+    algebra::{HasZero, IndexedZSet, MulByRef, ZRingValue},
+    circuit::{
+        operator_traits::{BinaryOperator, Operator},
+        Scope,
+    },
+    operator::time_series::{
+        range::{Range, RangeCursor, Ranges},
+        PartitionCursor, PartitionedBatchReader, PartitionedIndexedZSet,
+    },
+    trace::{Batch, Cursor},
+    Circuit, DBData, OrdIndexedZSet, RootCircuit, Stream,
+};
+use num::PrimInt;
+use std::{borrow::Cow, collections::VecDeque, marker::PhantomData};
+
+/// Stream produced by [`join_range`](`Stream::join_range`).
+///
+/// Each record has the form `(partition, (left_timestamp, right_timestamp,
+/// left_value, right_value))`.
+pub type OrdPartitionedJoinRangeStream<PK, TS, V1, V2, R> =
+    Stream<RootCircuit, OrdIndexedZSet<PK, (TS, TS, V1, V2), R>>;
+
+impl<B> Stream<RootCircuit, B> {
+    /// Band join of two partitioned time series.
+    ///
+    /// For each pair of records `(partition, (ts1, v1))` in `self` and
+    /// `(partition, (ts2, v2))` in `other` from the same partition, such that
+    /// `|ts1 - ts2| <= distance`, outputs `(partition, (ts1, ts2, v1, v2))`
+    /// with the product of the weights of the two records.
+    ///
+    /// This is equivalent to an equi-join on the partition key followed by a
+    /// filter on timestamps, but only visits records within the band of each
+    /// timestamp instead of the entire partition.
+    ///
+    /// This operator is incremental: a new left record is joined with right
+    /// records within `distance` from it, and vice versa.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - left time series, partitioned by partition key and indexed
+    ///   by time within each partition.
+    /// * `other` - right time series with the same partition key.
+    /// * `distance` - maximal distance between the timestamps of matching
+    ///   records.
+    pub fn join_range<TS, V1, V2, B2>(
+        &self,
+        other: &Stream<RootCircuit, B2>,
+        distance: TS,
+    ) -> OrdPartitionedJoinRangeStream<B::Key, TS, V1, V2, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V1>,
+        B::R: ZRingValue,
+        B2: PartitionedIndexedZSet<TS, V2, Key = B::Key, R = B::R>,
+        TS: DBData + PrimInt,
+        V1: DBData,
+        V2: DBData,
+    {
+        self.circuit().region("join_range", || {
+            let circuit = self.circuit();
+            let left = self.shard();
+            let right = other.shard();
+
+            let left_trace = left.integrate_trace();
+            let right_trace = right.integrate_trace();
+
+            // Changes to the output caused by changes to the left input
+            // (`delta_left <> right_trace`).
+            let left_updates: OrdPartitionedJoinRangeStream<_, _, _, _, _> = circuit
+                .add_binary_operator(
+                    JoinBand::new(distance, |ts1: &TS, v1: &V1, ts2: &TS, v2: &V2| {
+                        (*ts1, *ts2, v1.clone(), v2.clone())
+                    }),
+                    &left,
+                    &right_trace,
+                )
+                .mark_sharded();
+
+            // Changes to the output caused by changes to the right input
+            // (`delayed_left_trace <> delta_right`).
+            let right_updates: OrdPartitionedJoinRangeStream<_, _, _, _, _> = circuit
+                .add_binary_operator(
+                    JoinBand::new(distance, |ts2: &TS, v2: &V2, ts1: &TS, v1: &V1| {
+                        (*ts1, *ts2, v1.clone(), v2.clone())
+                    }),
+                    &right,
+                    &left_trace.delay_trace(),
+                )
+                .mark_sharded();
+
+            left_updates.plus(&right_updates).mark_sharded()
+        })
+    }
+}
+
+/// Binary operator that joins each record in a batch of updates with records
+/// in a trace within `distance` of its timestamp.
+///
+/// * Input stream 1: updates to one of the time series.
+/// * Input stream 2: trace of the other time series.
+///
+/// `join_func` maps a pair of an update and a trace record to an output value,
+/// which allows the same operator to process updates to both inputs.
+///
+/// # Algorithm
+///
+/// Within each partition, the operator restricts the trace cursor to the union
+/// of the bands of all updated timestamps, so regions of the trace outside of
+/// these bands are skipped.  Since updates are ordered by timestamp, the bands
+/// of consecutive updates move monotonically through the trace.  The operator
+/// buffers trace records within the current band, so that records that belong
+/// to overlapping bands are only read from the trace once.
+struct JoinBand<TS, VD, VT, F> {
+    distance: TS,
+    join_func: F,
+    phantom: PhantomData<(VD, VT)>,
+}
+
+impl<TS, VD, VT, F> JoinBand<TS, VD, VT, F>
+where
+    TS: PrimInt,
+{
+    fn new<OV>(distance: TS, join_func: F) -> Self
+    where
+        F: Fn(&TS, &VD, &TS, &VT) -> OV,
+    {
+        Self {
+            distance,
+            join_func,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the range of timestamps within `self.distance` from `ts`.
+    fn band_of(&self, ts: &TS) -> Range<TS> {
+        Range::new(
+            ts.saturating_sub(self.distance),
+            ts.saturating_add(self.distance),
+        )
+    }
+}
+
+impl<TS, VD, VT, F> Operator for JoinBand<TS, VD, VT, F>
+where
+    TS: 'static,
+    VD: 'static,
+    VT: 'static,
+    F: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("JoinBand")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<TS, VD, VT, F, B, T, O> BinaryOperator<B, T, O> for JoinBand<TS, VD, VT, F>
+where
+    TS: DBData + PrimInt,
+    VD: DBData,
+    VT: DBData,
+    F: Fn(&TS, &VD, &TS, &VT) -> O::Val + 'static,
+    B: PartitionedBatchReader<TS, VD> + Clone,
+    B::R: ZRingValue,
+    T: PartitionedBatchReader<TS, VT, Key = B::Key, R = B::R> + Clone,
+    O: IndexedZSet<Key = B::Key, R = B::R>,
+{
+    fn eval(&mut self, delta: &B, trace: &T) -> O {
+        let mut delta_cursor = delta.cursor();
+        let mut trace_cursor = trace.cursor();
+
+        let mut tuples = Vec::new();
+
+        while delta_cursor.key_valid() {
+            let partition = delta_cursor.key().clone();
+
+            trace_cursor.seek_key(&partition);
+            if !trace_cursor.key_valid() || trace_cursor.key() != &partition {
+                delta_cursor.step_key();
+                continue;
+            }
+
+            // Bands of all updated timestamps.
+            let mut ranges = Ranges::new();
+            while delta_cursor.val_valid() {
+                ranges.push_monotonic(self.band_of(&delta_cursor.val().0));
+                delta_cursor.step_val();
+            }
+            delta_cursor.rewind_vals();
+
+            let mut trace_range_cursor =
+                RangeCursor::new(PartitionCursor::new(&mut trace_cursor), ranges);
+
+            // Trace records within the band of the current update.
+            let mut window: VecDeque<(TS, VT, B::R)> = VecDeque::new();
+
+            while delta_cursor.val_valid() {
+                let weight = delta_cursor.weight();
+                let (ts, v) = delta_cursor.val();
+                let band = self.band_of(ts);
+
+                while matches!(window.front(), Some((ts2, _, _)) if ts2 < &band.from) {
+                    window.pop_front();
+                }
+
+                while trace_range_cursor.key_valid() && trace_range_cursor.key() <= &band.to {
+                    let ts2 = *trace_range_cursor.key();
+
+                    // Records before the current band only belong to the
+                    // bands of earlier updates.
+                    if ts2 >= band.from {
+                        while trace_range_cursor.val_valid() {
+                            let weight2 = trace_range_cursor.weight();
+                            if !weight2.is_zero() {
+                                window.push_back((ts2, trace_range_cursor.val().clone(), weight2));
+                            }
+                            trace_range_cursor.step_val();
+                        }
+                    }
+
+                    trace_range_cursor.step_key();
+                }
+
+                for (ts2, v2, weight2) in window.iter() {
+                    tuples.push((
+                        O::item_from(partition.clone(), (self.join_func)(ts, v, ts2, v2)),
+                        weight.mul_by_ref(weight2),
+                    ));
+                }
+
+                delta_cursor.step_val();
+            }
+
+            delta_cursor.step_key();
+        }
+
+        O::from_tuples((), tuples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, RootCircuit, Runtime, Stream,
+    };
+
+    type LeftBatch = OrdIndexedZSet<u64, (u64, i64), isize>;
+    type RightBatch = OrdIndexedZSet<u64, (u64, String), isize>;
+    type OutputBatch = OrdIndexedZSet<u64, (u64, u64, i64, String), isize>;
+    type OutputStream = Stream<RootCircuit, OutputBatch>;
+
+    // Reference implementation of `join_range`.
+    fn join_range_slow(
+        left: &Stream<RootCircuit, LeftBatch>,
+        right: &Stream<RootCircuit, RightBatch>,
+        distance: u64,
+    ) -> OutputStream {
+        left.gather(0).integrate().apply2(
+            &right.gather(0).integrate(),
+            move |left: &LeftBatch, right: &RightBatch| {
+                let mut tuples = Vec::new();
+                let mut left_cursor = left.cursor();
+
+                while left_cursor.key_valid() {
+                    let partition = *left_cursor.key();
+
+                    while left_cursor.val_valid() {
+                        let (ts1, v1) = left_cursor.val();
+                        let w1 = left_cursor.weight();
+
+                        let mut right_cursor = right.cursor();
+                        right_cursor.seek_key(&partition);
+                        if right_cursor.key_valid() && right_cursor.key() == &partition {
+                            while right_cursor.val_valid() {
+                                let (ts2, v2) = right_cursor.val();
+                                if ts1.abs_diff(*ts2) <= distance {
+                                    tuples.push((
+                                        (partition, (*ts1, *ts2, *v1, v2.clone())),
+                                        w1 * right_cursor.weight(),
+                                    ));
+                                }
+                                right_cursor.step_val();
+                            }
+                        }
+
+                        left_cursor.step_val();
+                    }
+
+                    left_cursor.step_key();
+                }
+
+                OutputBatch::from_tuples((), tuples)
+            },
+        )
+    }
+
+    type LeftHandle = CollectionHandle<u64, ((u64, i64), isize)>;
+    type RightHandle = CollectionHandle<u64, ((u64, String), isize)>;
+
+    fn join_range_circuit(distances: &[u64]) -> (DBSPHandle, (LeftHandle, RightHandle)) {
+        let distances = distances.to_vec();
+
+        Runtime::init_circuit(4, move |circuit| {
+            let (left, left_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (right, right_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, String), isize>();
+
+            for distance in distances {
+                let expected = join_range_slow(&left, &right, distance);
+                let actual = left
+                    .join_range::<u64, i64, String, _>(&right, distance)
+                    .gather(0)
+                    .integrate();
+                expected.apply2(&actual, |expected, actual| assert_eq!(expected, actual));
+            }
+
+            (left_handle, right_handle)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_join_range() {
+        let (circuit, (left_handle, right_handle)) = RootCircuit::build(move |circuit| {
+            let (left, left_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+            let (right, right_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, String), isize>();
+
+            let mut expected_outputs = vec![
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (10, 5, 1, "a".to_string())), 1),
+                        ((0, (10, 15, 1, "b".to_string())), 1),
+                        ((0, (20, 15, 2, "b".to_string())), 2),
+                    ],
+                ),
+                // Late right record matches multiple left records.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (10, 12, 1, "c".to_string())), 1),
+                        ((0, (20, 12, 2, "c".to_string())), 2),
+                    ],
+                ),
+                // Left record in another partition.
+                OutputBatch::from_tuples((), vec![((1, (100, 100, 3, "d".to_string())), 1)]),
+                // Retract left record.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (20, 12, 2, "c".to_string())), -2),
+                        ((0, (20, 15, 2, "b".to_string())), -2),
+                    ],
+                ),
+                // Retract right record.
+                OutputBatch::from_tuples((), vec![((0, (10, 5, 1, "a".to_string())), -1)]),
+            ]
+            .into_iter();
+
+            left.join_range::<u64, i64, String, _>(&right, 8)
+                .inspect(move |batch| assert_eq!(batch, &expected_outputs.next().unwrap()));
+
+            (left_handle, right_handle)
+        })
+        .unwrap();
+
+        right_handle.push(0, ((5, "a".to_string()), 1));
+        right_handle.push(0, ((15, "b".to_string()), 1));
+        right_handle.push(0, ((30, "x".to_string()), 1));
+        left_handle.push(0, ((10, 1), 1));
+        left_handle.push(0, ((20, 2), 2));
+        circuit.step().unwrap();
+
+        right_handle.push(0, ((12, "c".to_string()), 1));
+        right_handle.push(1, ((100, "d".to_string()), 1));
+        circuit.step().unwrap();
+
+        left_handle.push(1, ((100, 3), 1));
+        circuit.step().unwrap();
+
+        left_handle.push(0, ((20, 2), -2));
+        circuit.step().unwrap();
+
+        right_handle.push(0, ((5, "a".to_string()), -1));
+        circuit.step().unwrap();
+    }
+
+    #[test]
+    fn test_join_range_multiple_partitions() {
+        let (mut circuit, (mut left, mut right)) = join_range_circuit(&[0, 10, u64::MAX]);
+
+        left.append(&mut vec![
+            (0, ((10, 1), 1)),
+            (0, ((10, 2), 2)),
+            (0, ((30, 3), 1)),
+            (1, ((100, 4), 1)),
+        ]);
+        right.append(&mut vec![
+            (0, ((10, "a".to_string()), 1)),
+            (0, ((10, "b".to_string()), 1)),
+            (1, ((50, "c".to_string()), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        // Updates to both sides in the same step.
+        left.append(&mut vec![(0, ((25, 5), 1)), (1, ((95, 6), -1))]);
+        right.append(&mut vec![
+            (0, ((25, "d".to_string()), 1)),
+            (1, ((95, "e".to_string()), 1)),
+            (1, ((100, "f".to_string()), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        // Retract records on both sides.
+        right.append(&mut vec![
+            (0, ((10, "b".to_string()), -1)),
+            (1, ((100, "f".to_string()), -1)),
+        ]);
+        left.append(&mut vec![(0, ((10, 2), -1))]);
+        circuit.step().unwrap();
+
+        circuit.kill().unwrap();
+    }
+
+    use proptest::{collection, prelude::*};
+
+    type LeftTuple = (u64, ((u64, i64), isize));
+    type RightTuple = (u64, ((u64, String), isize));
+
+    fn left_tuple(partitions: u64, epoch: u64) -> impl Strategy<Value = LeftTuple> {
+        ((0..partitions), ((0..epoch, 0..5i64), 1..3isize))
+    }
+
+    fn right_tuple(partitions: u64, epoch: u64) -> impl Strategy<Value = RightTuple> {
+        (
+            (0..partitions),
+            ((0..epoch, "[a-c]".prop_map(|s| s.to_string())), 1..3isize),
+        )
+    }
+
+    // Each step inserts batches of new values into both inputs in arbitrary
+    // time order and retracts some of the previously inserted values,
+    // identified by their indexes.
+    #[allow(clippy::type_complexity)]
+    fn input_trace(
+        partitions: u64,
+        epoch: u64,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<(Vec<LeftTuple>, Vec<RightTuple>, Vec<usize>, Vec<usize>)>> {
+        collection::vec(
+            (
+                collection::vec(left_tuple(partitions, epoch), 0..max_batch_size),
+                collection::vec(right_tuple(partitions, epoch), 0..max_batch_size),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+            ),
+            0..max_batches,
+        )
+    }
+
+    fn retract<T: Clone>(
+        inserted: &mut Vec<(u64, (T, isize))>,
+        indexes: Vec<usize>,
+    ) -> Vec<(u64, (T, isize))> {
+        let mut retractions = Vec::new();
+
+        for index in indexes {
+            if index < inserted.len() {
+                let (partition, (val, w)) = inserted.swap_remove(index);
+                retractions.push((partition, (val, -w)));
+            }
+        }
+
+        retractions
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_trace(trace: Vec<(Vec<LeftTuple>, Vec<RightTuple>, Vec<usize>, Vec<usize>)>) {
+        let (mut circuit, (mut left, mut right)) = join_range_circuit(&[0, 5, 100, u64::MAX]);
+
+        let mut inserted_left = Vec::new();
+        let mut inserted_right = Vec::new();
+
+        for (mut left_batch, mut right_batch, left_retractions, right_retractions) in trace {
+            inserted_left.extend(left_batch.iter().cloned());
+            inserted_right.extend(right_batch.iter().cloned());
+
+            left_batch.extend(retract(&mut inserted_left, left_retractions));
+            right_batch.extend(retract(&mut inserted_right, right_retractions));
+
+            left.append(&mut left_batch);
+            right.append(&mut right_batch);
+            circuit.step().unwrap();
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_join_range_sparse(trace in input_trace(5, 1_000_000, 20, 20)) {
+            run_trace(trace);
+        }
+
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_join_range_dense(trace in input_trace(3, 200, 30, 20)) {
+            run_trace(trace);
+        }
+    }
+}
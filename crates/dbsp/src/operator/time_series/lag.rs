@@ -0,0 +1,866 @@
+use crate::{
+    algebra::{HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{Operator, TernaryOperator},
+        OwnershipPreference, Scope,
+    },
+    operator::{
+        time_series::{
+            OrdPartitionedIndexedZSet, PartitionCursor, PartitionedBatchReader,
+            PartitionedIndexedZSet,
+        },
+        trace::{DelayedTraceId, IntegrateTraceId, TraceBounds, UntimedTraceAppend, Z1Trace},
+    },
+    trace::{Batch, Cursor, Spine},
+    Circuit, DBData, RootCircuit, Stream,
+};
+use num::{NumCast, PrimInt, ToPrimitive};
+use std::{borrow::Cow, collections::VecDeque, marker::PhantomData, ops::Neg};
+
+/// Stream produced by [`partitioned_lag`](`Stream::partitioned_lag`) and
+/// [`partitioned_lead`](`Stream::partitioned_lead`).
+///
+/// Each record has the form `(partition, (timestamp, (value,
+/// Option<neighbor_value>)))`.
+pub type OrdPartitionedLagStream<PK, TS, V, R> =
+    Stream<RootCircuit, OrdPartitionedIndexedZSet<PK, TS, (V, Option<V>), R>>;
+
+impl<B> Stream<RootCircuit, B> {
+    /// Lag operator over a partitioned time series.
+    ///
+    /// Orders records in each partition of the input stream by
+    /// `(timestamp, value)` and, for each record, outputs the value of the
+    /// record `n` positions earlier within the same partition, or `None` if
+    /// there is no such record.  This is the equivalent of SQL
+    /// `LAG(value, n) OVER (PARTITION BY partition ORDER BY timestamp)`.
+    ///
+    /// A record with weight `w > 1` represents `w` identical rows, and each
+    /// of them is assigned its own lagging value.  Copies that share the same
+    /// lagging value are output as a single record whose weight is the number
+    /// of such copies, so the size of the output does not depend on the
+    /// magnitude of input weights.  Records with non-positive weights are
+    /// ignored.
+    ///
+    /// This operator is incremental: inserting or deleting a record only
+    /// updates outputs for records within `n` positions from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data partitioned by partition key and indexed by
+    ///   time within each partition.
+    /// * `n` - offset, in rows, of the lagging record.
+    pub fn partitioned_lag<TS, V>(&self, n: usize) -> OrdPartitionedLagStream<B::Key, TS, V, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue + NumCast,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        self.circuit().region("partitioned_lag", || {
+            self.partitioned_lag_lead_inner(LagLead::Lag, n)
+        })
+    }
+
+    /// Lead operator over a partitioned time series.
+    ///
+    /// Like [`partitioned_lag`](`Self::partitioned_lag`), but outputs the
+    /// value of the record `n` positions later within the same partition.
+    /// This is the equivalent of SQL `LEAD(value, n) OVER (PARTITION BY
+    /// partition ORDER BY timestamp)`.
+    pub fn partitioned_lead<TS, V>(&self, n: usize) -> OrdPartitionedLagStream<B::Key, TS, V, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue + NumCast,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        self.circuit().region("partitioned_lead", || {
+            self.partitioned_lag_lead_inner(LagLead::Lead, n)
+        })
+    }
+
+    fn partitioned_lag_lead_inner<TS, V>(
+        &self,
+        direction: LagLead,
+        n: usize,
+    ) -> OrdPartitionedLagStream<B::Key, TS, V, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue + NumCast,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        // ```
+        //                  ┌───────────────┐   input_trace
+        //      ┌──────────►│integrate_trace├──────────────┐                       output
+        //      │           └───────────────┘              │                    ┌────────────────────────────────────►
+        //      │                                          ▼                    │
+        // self │                                  ┌───────────────────┐        │  ┌──────────────────┐ output_trace
+        // ─────┴─────────────────────────────────►│PartitionedLagLead ├────────┴──┤UntimedTraceAppend├────────┐
+        //                                         └───────────────────┘           └──────────────────┘        │
+        //                                                    ▲                       ▲                        │
+        //                                                    │                     ┌─┴──┐                     │
+        //                                                    └─────────────────────┤Z^-1│◄────────────────────┘
+        //                                                     output_trace_delayed └────┘
+        // ```
+        let circuit = self.circuit();
+        let stream = self.shard();
+
+        let input_trace = stream.integrate_trace();
+
+        let bounds = <TraceBounds<B::Key, (TS, (V, Option<V>))>>::unbounded();
+        let (output_trace_delayed, z1feedback) = circuit.add_feedback(<Z1Trace<Spine<_>>>::new(
+            false,
+            circuit.root_scope(),
+            bounds.clone(),
+        ));
+        output_trace_delayed.mark_sharded();
+
+        let output: OrdPartitionedLagStream<_, _, _, _> = circuit
+            .add_ternary_operator(
+                <PartitionedLagLead<TS, V>>::new(direction, n),
+                &stream,
+                &input_trace,
+                &output_trace_delayed,
+            )
+            .mark_sharded();
+
+        let output_trace = circuit
+            .add_binary_operator_with_preference(
+                <UntimedTraceAppend<Spine<_>>>::new(),
+                (
+                    &output_trace_delayed,
+                    OwnershipPreference::STRONGLY_PREFER_OWNED,
+                ),
+                (&output, OwnershipPreference::PREFER_OWNED),
+            )
+            .mark_sharded();
+
+        z1feedback
+            .connect_with_preference(&output_trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+
+        circuit.cache_insert(
+            DelayedTraceId::new(output_trace.origin_node_id().clone()),
+            output_trace_delayed,
+        );
+        circuit.cache_insert(
+            IntegrateTraceId::new(output.origin_node_id().clone()),
+            (output_trace, bounds),
+        );
+
+        output
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LagLead {
+    Lag,
+    Lead,
+}
+
+/// A group of identical rows: `(timestamp, value, multiplicity)`.
+type Row<TS, V> = (TS, V, usize);
+
+/// Ternary operator that implements the internals of `partitioned_lag` and
+/// `partitioned_lead`.
+///
+/// * Input stream 1: updates to the time series.  Used to identify affected
+///   partitions and rows.
+/// * Input stream 2: trace containing the accumulated time series data.
+/// * Input stream 3: trace of previously produced outputs.  Used to compute
+///   retractions.
+///
+/// # Algorithm
+///
+/// For each affected partition, the operator scans the input trace in
+/// segments that cover modified rows along with `n` rows before and after
+/// them, recomputes outputs for all rows in the segment whose neighbor may
+/// have changed, and retracts old outputs for the same rows.  Cursors can
+/// only move forward, so the start of a segment is located by probing
+/// exponentially growing time ranges preceding the first modified row.
+struct PartitionedLagLead<TS, V> {
+    direction: LagLead,
+    n: usize,
+    phantom: PhantomData<(TS, V)>,
+}
+
+impl<TS, V> PartitionedLagLead<TS, V>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+{
+    fn new(direction: LagLead, n: usize) -> Self {
+        Self {
+            direction,
+            n,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Read the next group of identical rows with positive weight from
+    /// `cursor`.
+    fn next_row<'a, R, C>(cursor: &mut C) -> Option<Row<TS, V>>
+    where
+        C: Cursor<'a, TS, V, (), R>,
+        R: ZRingValue + ToPrimitive,
+    {
+        loop {
+            if !cursor.key_valid() {
+                return None;
+            }
+            if !cursor.val_valid() {
+                cursor.step_key();
+                continue;
+            }
+
+            let weight = cursor.weight();
+            let ts = *cursor.key();
+            let val = cursor.val().clone();
+            cursor.step_val();
+
+            if !weight.le0() {
+                let count = weight
+                    .to_usize()
+                    .expect("partitioned_lag_lead: weight out of range");
+                return Some((ts, val, count));
+            }
+        }
+    }
+
+    /// Find rows preceding `key` in a partition of `input_trace`.
+    ///
+    /// Returns at least `self.n` rows immediately preceding `key`, or all
+    /// rows before `key` if there are fewer than `self.n` of them.  The first
+    /// returned row group is always complete.
+    ///
+    /// Returns `None` if the preceding rows overlap with rows with timestamps
+    /// `<= last_ts` that belong to the previous segment.
+    fn rows_before<'a, T>(
+        &self,
+        input_trace: &'a T,
+        partition: &T::Key,
+        key: &(TS, V),
+        last_ts: Option<TS>,
+    ) -> Option<VecDeque<Row<TS, V>>>
+    where
+        T: PartitionedBatchReader<TS, V>,
+        T::R: ZRingValue + ToPrimitive,
+    {
+        let two = TS::one() + TS::one();
+        let mut distance = TS::one();
+
+        loop {
+            let from = key.0.saturating_sub(distance);
+            if matches!(last_ts, Some(last_ts) if from <= last_ts) {
+                return None;
+            }
+
+            let mut rows = VecDeque::new();
+            let mut total = 0;
+
+            let mut cursor = input_trace.cursor();
+            cursor.seek_key(partition);
+            debug_assert!(cursor.key_valid() && cursor.key() == partition);
+
+            let mut partition_cursor = PartitionCursor::new(&mut cursor);
+            partition_cursor.seek_key(&from);
+
+            while let Some(row) = Self::next_row(&mut partition_cursor) {
+                if (&row.0, &row.1) >= (&key.0, &key.1) {
+                    break;
+                }
+                total += row.2;
+                rows.push_back(row);
+
+                while matches!(rows.front(), Some(row) if total - row.2 >= self.n) {
+                    total -= rows.pop_front().unwrap().2;
+                }
+            }
+
+            if total >= self.n || from == TS::min_value() {
+                return Some(rows);
+            }
+
+            distance = distance.checked_mul(&two).unwrap_or_else(TS::max_value);
+        }
+    }
+
+    /// Compute outputs for a segment of rows.
+    ///
+    /// * `segment` - a contiguous sequence of rows in a partition.
+    /// * `first_change` - index of the first row in the segment at or after
+    ///   the first modified row.
+    /// * `last_change` - index of the first row in the segment after the last
+    ///   modified row.
+    ///
+    /// Copies of a row group that have the same neighbor are output as a
+    /// single weighted record, so the cost of this function is proportional
+    /// to the number of row groups rather than the total number of rows.
+    fn segment_outputs<O>(
+        &self,
+        partition: &O::Key,
+        segment: &[Row<TS, V>],
+        first_change: usize,
+        last_change: usize,
+        outputs: &mut Vec<(O::Item, O::R)>,
+        emitted: &mut Vec<(TS, V)>,
+    ) where
+        O: Batch<Val = (TS, (V, Option<V>)), Time = ()>,
+        O::R: ZRingValue + NumCast,
+    {
+        // Position of the first row of each row group in the segment.
+        let mut starts = Vec::with_capacity(segment.len());
+        let mut total = 0;
+        for (_, _, count) in segment {
+            starts.push(total);
+            total += count;
+        }
+
+        let weight = |count: usize| -> O::R {
+            <O::R as NumCast>::from(count).expect("partitioned_lag_lead: weight out of range")
+        };
+
+        // Rows whose neighbors may have changed.
+        let (from, to) = match self.direction {
+            LagLead::Lag => (first_change, segment.len()),
+            LagLead::Lead => (0, last_change),
+        };
+
+        for (index, (ts, val, count)) in segment.iter().enumerate().take(to).skip(from) {
+            let start = starts[index];
+            let end = start + count;
+
+            // Range of neighbor positions of rows `start..end` that fall
+            // within the segment; the remaining rows don't have a neighbor.
+            let (neighbors_from, neighbors_to) = match self.direction {
+                LagLead::Lag => (start.max(self.n) - self.n, end.saturating_sub(self.n)),
+                LagLead::Lead => ((start + self.n).min(total), (end + self.n).min(total)),
+            };
+            let without_neighbor = count - (neighbors_to - neighbors_from);
+
+            if without_neighbor > 0 {
+                outputs.push((
+                    O::item_from(partition.clone(), (*ts, (val.clone(), None))),
+                    weight(without_neighbor),
+                ));
+            }
+
+            let mut position = neighbors_from;
+            let mut group = starts.partition_point(|start| *start <= position);
+            while position < neighbors_to {
+                let group_end = (starts[group - 1] + segment[group - 1].2).min(neighbors_to);
+                outputs.push((
+                    O::item_from(
+                        partition.clone(),
+                        (*ts, (val.clone(), Some(segment[group - 1].1.clone()))),
+                    ),
+                    weight(group_end - position),
+                ));
+                position = group_end;
+                group += 1;
+            }
+
+            emitted.push((*ts, val.clone()));
+        }
+    }
+}
+
+impl<TS, V> Operator for PartitionedLagLead<TS, V>
+where
+    TS: 'static,
+    V: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("PartitionedLagLead")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<TS, V, B, T, OT, O> TernaryOperator<B, T, OT, O> for PartitionedLagLead<TS, V>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    B: PartitionedBatchReader<TS, V> + Clone,
+    B::R: ZRingValue + NumCast,
+    T: PartitionedBatchReader<TS, V, Key = B::Key, R = B::R> + Clone,
+    OT: PartitionedBatchReader<TS, (V, Option<V>), Key = B::Key, R = B::R> + Clone,
+    O: IndexedZSet<Key = B::Key, Val = (TS, (V, Option<V>)), R = B::R>,
+{
+    fn eval<'a>(
+        &mut self,
+        input_delta: Cow<'a, B>,
+        input_trace: Cow<'a, T>,
+        output_trace: Cow<'a, OT>,
+    ) -> O {
+        let mut delta_cursor = input_delta.cursor();
+        let mut input_trace_cursor = input_trace.cursor();
+        let mut output_trace_cursor = output_trace.cursor();
+
+        let mut outputs = Vec::with_capacity(input_delta.len());
+
+        // Iterate over affected partitions.
+        while delta_cursor.key_valid() {
+            let partition = delta_cursor.key().clone();
+
+            // Modified rows.
+            let mut changes: Vec<(TS, V)> = Vec::new();
+            while delta_cursor.val_valid() {
+                let (ts, val) = delta_cursor.val();
+                if changes.last() != Some(&(*ts, val.clone())) {
+                    changes.push((*ts, val.clone()));
+                }
+                delta_cursor.step_val();
+            }
+
+            // Rows whose outputs we recompute.
+            let mut emitted: Vec<(TS, V)> = Vec::new();
+
+            input_trace_cursor.seek_key(&partition);
+            if input_trace_cursor.key_valid() && input_trace_cursor.key() == &partition {
+                let mut partition_cursor = PartitionCursor::new(&mut input_trace_cursor);
+
+                let mut segment: Vec<Row<TS, V>> = Vec::new();
+                let mut first_change = 0;
+                let mut last_change = 0;
+
+                let mut next_change = 0;
+                while next_change < changes.len() {
+                    let change = &changes[next_change];
+
+                    // Start a new segment, unless the rows preceding the change
+                    // overlap with the current segment, in which case we
+                    // continue scanning the current segment.
+                    let last_ts = segment.last().map(|row| row.0);
+                    if let Some(rows_before) =
+                        self.rows_before(input_trace.as_ref(), &partition, change, last_ts)
+                    {
+                        if !segment.is_empty() {
+                            self.segment_outputs::<O>(
+                                &partition,
+                                &segment,
+                                first_change,
+                                last_change,
+                                &mut outputs,
+                                &mut emitted,
+                            );
+                        }
+                        segment = rows_before.into();
+                        first_change = segment.len();
+
+                        partition_cursor.seek_key(&change.0);
+                        while partition_cursor.val_valid()
+                            && partition_cursor.key() == &change.0
+                            && partition_cursor.val() < &change.1
+                        {
+                            partition_cursor.step_val();
+                        }
+                    }
+
+                    // Scan rows until we've seen `n` rows after the last change.
+                    let target = next_change;
+                    let mut rows_since_change = 0;
+                    loop {
+                        if next_change > target && rows_since_change >= self.n {
+                            break;
+                        }
+
+                        let row = if let Some(row) = Self::next_row(&mut partition_cursor) {
+                            row
+                        } else {
+                            // Remaining changes are deletions past the end of the partition.
+                            next_change = changes.len();
+                            last_change = segment.len();
+                            break;
+                        };
+
+                        while next_change < changes.len()
+                            && (&changes[next_change].0, &changes[next_change].1) < (&row.0, &row.1)
+                        {
+                            next_change += 1;
+                            last_change = segment.len();
+                            rows_since_change = 0;
+                        }
+
+                        let count = row.2;
+                        let is_change = next_change < changes.len()
+                            && (&changes[next_change].0, &changes[next_change].1)
+                                == (&row.0, &row.1);
+                        segment.push(row);
+
+                        if is_change {
+                            next_change += 1;
+                            last_change = segment.len();
+                            rows_since_change = 0;
+                        } else {
+                            rows_since_change += count;
+                        }
+                    }
+                }
+
+                if !segment.is_empty() {
+                    self.segment_outputs::<O>(
+                        &partition,
+                        &segment,
+                        first_change,
+                        last_change,
+                        &mut outputs,
+                        &mut emitted,
+                    );
+                }
+            }
+
+            // Retract old outputs for modified and recomputed rows.
+            emitted.extend(changes);
+            emitted.sort();
+            emitted.dedup();
+
+            output_trace_cursor.seek_key(&partition);
+            if output_trace_cursor.key_valid() && output_trace_cursor.key() == &partition {
+                let mut partition_cursor = PartitionCursor::new(&mut output_trace_cursor);
+
+                for (ts, val) in emitted {
+                    partition_cursor.seek_key(&ts);
+                    if !partition_cursor.key_valid() {
+                        break;
+                    }
+                    if partition_cursor.key() != &ts {
+                        continue;
+                    }
+
+                    while partition_cursor.val_valid() && partition_cursor.val().0 < val {
+                        partition_cursor.step_val();
+                    }
+                    while partition_cursor.val_valid() && partition_cursor.val().0 == val {
+                        let weight = partition_cursor.weight();
+                        if !weight.is_zero() {
+                            outputs.push((
+                                O::item_from(
+                                    partition.clone(),
+                                    (ts, partition_cursor.val().clone()),
+                                ),
+                                weight.neg(),
+                            ));
+                        }
+                        partition_cursor.step_val();
+                    }
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        O::from_tuples((), outputs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LagLead;
+    use crate::{
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, RootCircuit, Runtime, Stream,
+    };
+
+    type DataBatch = OrdIndexedZSet<u64, (u64, i64), isize>;
+    type DataStream = Stream<RootCircuit, DataBatch>;
+    type OutputBatch = OrdIndexedZSet<u64, (u64, (i64, Option<i64>)), isize>;
+    type OutputStream = Stream<RootCircuit, OutputBatch>;
+
+    // Reference implementation of `partitioned_lag` and `partitioned_lead`.
+    fn partitioned_lag_lead_slow(
+        stream: &DataStream,
+        direction: LagLead,
+        n: usize,
+    ) -> OutputStream {
+        stream
+            .gather(0)
+            .integrate()
+            .apply(move |batch: &DataBatch| {
+                let mut tuples = Vec::new();
+
+                let mut cursor = batch.cursor();
+
+                while cursor.key_valid() {
+                    let partition = *cursor.key();
+
+                    let mut rows = Vec::new();
+                    while cursor.val_valid() {
+                        let w = cursor.weight();
+                        for _ in 0..w {
+                            rows.push(*cursor.val());
+                        }
+                        cursor.step_val();
+                    }
+
+                    for (i, (ts, val)) in rows.iter().enumerate() {
+                        let neighbor = match direction {
+                            LagLead::Lag => i.checked_sub(n),
+                            LagLead::Lead => Some(i + n),
+                        }
+                        .and_then(|neighbor| rows.get(neighbor))
+                        .map(|(_, val)| *val);
+
+                        tuples.push(((partition, (*ts, (*val, neighbor))), 1));
+                    }
+
+                    cursor.step_key();
+                }
+
+                OutputBatch::from_tuples((), tuples)
+            })
+    }
+
+    type LagHandle = CollectionHandle<u64, ((u64, i64), isize)>;
+
+    fn partitioned_lag_lead_circuit(offsets: &[usize]) -> (DBSPHandle, LagHandle) {
+        let offsets = offsets.to_vec();
+
+        Runtime::init_circuit(4, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            for n in offsets {
+                let expected_lag = partitioned_lag_lead_slow(&input_stream, LagLead::Lag, n);
+                let lag = input_stream
+                    .partitioned_lag::<u64, i64>(n)
+                    .gather(0)
+                    .integrate();
+                expected_lag.apply2(&lag, |expected, actual| assert_eq!(expected, actual));
+
+                let expected_lead = partitioned_lag_lead_slow(&input_stream, LagLead::Lead, n);
+                let lead = input_stream
+                    .partitioned_lead::<u64, i64>(n)
+                    .gather(0)
+                    .integrate();
+                expected_lead.apply2(&lead, |expected, actual| assert_eq!(expected, actual));
+            }
+
+            input_handle
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_partitioned_lag() {
+        let (circuit, mut input) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let mut expected_outputs = vec![
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (10, (1, None))), 1),
+                        ((0, (20, (2, Some(1)))), 1),
+                        ((0, (20, (3, Some(2)))), 1),
+                        ((0, (30, (4, Some(3)))), 1),
+                    ],
+                ),
+                // Insert a row in the middle of the partition.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (15, (5, Some(1)))), 1),
+                        ((0, (20, (2, Some(1)))), -1),
+                        ((0, (20, (2, Some(5)))), 1),
+                    ],
+                ),
+                // Retract a row in the middle of the partition.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (20, (2, Some(5)))), -1),
+                        ((0, (20, (3, Some(2)))), -1),
+                        ((0, (20, (3, Some(5)))), 1),
+                    ],
+                ),
+                // Duplicate row.
+                OutputBatch::from_tuples((), vec![((0, (20, (3, Some(3)))), 1)]),
+            ]
+            .into_iter();
+
+            input_stream
+                .partitioned_lag::<u64, i64>(1)
+                .inspect(move |batch| assert_eq!(batch, &expected_outputs.next().unwrap()));
+
+            input_handle
+        })
+        .unwrap();
+
+        input.append(&mut vec![
+            (0, ((10, 1), 1)),
+            (0, ((20, 2), 1)),
+            (0, ((20, 3), 1)),
+            (0, ((30, 4), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(0, ((15, 5), 1))]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(0, ((20, 2), -1))]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(0, ((20, 3), 1))]);
+        circuit.step().unwrap();
+    }
+
+    // Rows with large weights produce weighted outputs instead of one output
+    // per copy.
+    #[test]
+    fn test_partitioned_lag_lead_large_weights() {
+        let (circuit, (mut input, lag, lead)) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            (
+                input_handle,
+                input_stream.partitioned_lag::<u64, i64>(1).output(),
+                input_stream.partitioned_lead::<u64, i64>(1).output(),
+            )
+        })
+        .unwrap();
+
+        input.append(&mut vec![(0, ((10, 1), 1_000_000)), (0, ((20, 2), 1))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            lag.consolidate(),
+            OutputBatch::from_tuples(
+                (),
+                vec![
+                    ((0, (10, (1, None))), 1),
+                    ((0, (10, (1, Some(1)))), 999_999),
+                    ((0, (20, (2, Some(1)))), 1),
+                ],
+            )
+        );
+        assert_eq!(
+            lead.consolidate(),
+            OutputBatch::from_tuples(
+                (),
+                vec![
+                    ((0, (10, (1, Some(1)))), 999_999),
+                    ((0, (10, (1, Some(2)))), 1),
+                    ((0, (20, (2, None))), 1),
+                ],
+            )
+        );
+
+        input.append(&mut vec![(0, ((10, 1), -500_000))]);
+        circuit.step().unwrap();
+        assert_eq!(
+            lag.consolidate(),
+            OutputBatch::from_tuples((), vec![((0, (10, (1, Some(1)))), -500_000)])
+        );
+        assert_eq!(
+            lead.consolidate(),
+            OutputBatch::from_tuples((), vec![((0, (10, (1, Some(1)))), -500_000)])
+        );
+    }
+
+    #[test]
+    fn test_partitioned_lag_lead() {
+        let (mut circuit, mut input) = partitioned_lag_lead_circuit(&[0, 1, 2, 3]);
+
+        // Duplicate timestamps and duplicate rows.
+        input.append(&mut vec![
+            (0, ((10, 1), 1)),
+            (0, ((10, 2), 1)),
+            (0, ((10, 2), 1)),
+            (0, ((20, 3), 2)),
+            (0, ((30, 4), 1)),
+            (0, ((40, 5), 1)),
+            (0, ((50, 6), 1)),
+            (1, ((10, 1), 1)),
+            (1, ((1000, 2), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        // Retractions in the middle of a partition.
+        input.append(&mut vec![(0, ((20, 3), -1)), (0, ((40, 5), -1))]);
+        circuit.step().unwrap();
+
+        // Out-of-order inserts.
+        input.append(&mut vec![
+            (0, ((5, 7), 1)),
+            (0, ((25, 8), 1)),
+            (0, ((10, 2), 1)),
+            (1, ((500, 3), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        // Delete partition.
+        input.append(&mut vec![
+            (1, ((10, 1), -1)),
+            (1, ((500, 3), -1)),
+            (1, ((1000, 2), -1)),
+        ]);
+        circuit.step().unwrap();
+
+        circuit.kill().unwrap();
+    }
+
+    use proptest::{collection, prelude::*};
+
+    type InputTuple = (u64, ((u64, i64), isize));
+    type InputBatch = Vec<InputTuple>;
+
+    fn input_tuple(partitions: u64, epoch: u64) -> impl Strategy<Value = InputTuple> {
+        ((0..partitions), ((0..epoch, 0..5i64), 1..3isize))
+    }
+
+    // Each step inserts a batch of new values and retracts some of the
+    // previously inserted values, identified by their indexes.
+    fn input_trace(
+        partitions: u64,
+        epoch: u64,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<(InputBatch, Vec<usize>)>> {
+        collection::vec(
+            (
+                collection::vec(input_tuple(partitions, epoch), 0..max_batch_size),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+            ),
+            0..max_batches,
+        )
+    }
+
+    fn run_trace(trace: Vec<(InputBatch, Vec<usize>)>) {
+        let (mut circuit, mut input) = partitioned_lag_lead_circuit(&[0, 1, 3]);
+        let mut inserted: Vec<InputTuple> = Vec::new();
+
+        for (mut batch, retractions) in trace {
+            inserted.extend(batch.iter().cloned());
+
+            for index in retractions {
+                if index < inserted.len() {
+                    let (partition, (val, w)) = inserted.swap_remove(index);
+                    batch.push((partition, (val, -w)));
+                }
+            }
+
+            input.append(&mut batch);
+            circuit.step().unwrap();
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_partitioned_lag_lead_sparse(trace in input_trace(5, 1_000_000, 20, 20)) {
+            run_trace(trace);
+        }
+
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_partitioned_lag_lead_dense(trace in input_trace(3, 50, 30, 20)) {
+            run_trace(trace);
+        }
+    }
+}
@@ -1,12 +1,29 @@
+mod asof_join;
+mod emit_on_close;
+mod expire;
+mod join_range;
+mod lag;
 mod partitioned;
 mod radix_tree;
 mod range;
 mod rolling_aggregate;
+mod session_aggregate;
+mod tumbling_window;
 mod watermark;
 mod window;
 
+pub use asof_join::OrdPartitionedAsofJoinStream;
+pub use join_range::OrdPartitionedJoinRangeStream;
+pub use lag::OrdPartitionedLagStream;
 pub use partitioned::{
     OrdPartitionedIndexedZSet, PartitionCursor, PartitionedBatch, PartitionedBatchReader,
     PartitionedIndexedZSet,
 };
+pub use radix_tree::{
+    OrdPartitionedRadixTree, OrdPartitionedRadixTreeStream, PartitionedRadixTreeBatch,
+    PartitionedRadixTreeCursor, PartitionedRadixTreeQueryHandle, PartitionedRadixTreeReader,
+    Prefix, RadixTreeCursor, TreeNode,
+};
 pub use range::{Range, RelOffset, RelRange};
+pub use session_aggregate::OrdPartitionedSessionStream;
+pub use tumbling_window::OrdTumblingWindowStream;
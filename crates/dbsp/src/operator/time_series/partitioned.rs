@@ -58,7 +58,7 @@ impl<'a, 'b, C, PK, K, V, R> Cursor<'a, K, V, (), R> for PartitionCursor<'b, PK,
 where
     C: Cursor<'a, PK, (K, V), (), R>,
     K: Clone + Eq + Ord,
-    V: 'static,
+    V: Ord + 'static,
 {
     fn key_valid(&self) -> bool {
         self.cursor.val_valid()
@@ -120,8 +120,9 @@ where
         self.cursor.step_val();
     }
 
-    fn seek_val(&mut self, _val: &V) {
-        unimplemented!()
+    fn seek_val(&mut self, val: &V) {
+        let key = &self.key;
+        self.cursor.seek_val_with(|(k, v)| k != key || v >= val);
     }
 
     fn seek_val_with<P>(&mut self, _predicate: P)
@@ -139,6 +140,171 @@ where
     fn rewind_vals(&mut self) {
         unimplemented!()
     }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        // The current key can only be recovered from the underlying cursor
+        // while it points inside the partition.
+        if self.val_valid() {
+            self.cursor.mark_offsets()
+        } else {
+            None
+        }
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.cursor.rewind_to_offsets(offsets);
+        self.key = self.cursor.val().0.clone();
+    }
 }
 
 pub type OrdPartitionedIndexedZSet<PK, TS, V, R> = OrdIndexedZSet<PK, (TS, V), R>;
+
+#[cfg(test)]
+mod test {
+    use super::PartitionCursor;
+    use crate::{
+        trace::{Batch, BatchReader, Cursor, CursorMark},
+        OrdIndexedZSet,
+    };
+    use size_of::SizeOf;
+    use std::{cell::Cell, cmp::Ordering};
+
+    const PARTITIONS: u64 = 100;
+    const TIMESTAMPS: u64 = 20;
+
+    thread_local! {
+        static COMPARISONS: Cell<usize> = Cell::new(0);
+    }
+
+    fn comparisons() -> usize {
+        COMPARISONS.with(Cell::get)
+    }
+
+    /// Partition key that counts how many times it is compared.
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, SizeOf)]
+    #[cfg_attr(feature = "persistence", derive(bincode::Encode, bincode::Decode))]
+    struct Counted(u64);
+
+    impl Ord for Counted {
+        fn cmp(&self, other: &Self) -> Ordering {
+            COMPARISONS.with(|comparisons| comparisons.set(comparisons.get() + 1));
+            self.0.cmp(&other.0)
+        }
+    }
+
+    impl PartialOrd for Counted {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    fn partitioned_batch() -> OrdIndexedZSet<Counted, (u64, u64), isize> {
+        let tuples = (0..PARTITIONS)
+            .flat_map(|p| (0..TIMESTAMPS).map(move |ts| ((Counted(p), (ts * 10, p)), 1)))
+            .collect();
+
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+
+    /// Returns all `(timestamp, value)` pairs with timestamps in `[from, to]`.
+    fn scan<'s, C>(cursor: &mut C, from: u64, to: u64) -> Vec<(u64, u64)>
+    where
+        C: Cursor<'s, u64, u64, (), isize>,
+    {
+        let mut result = Vec::new();
+
+        cursor.seek_key(&from);
+        while cursor.key_valid() && *cursor.key() <= to {
+            while cursor.val_valid() {
+                result.push((*cursor.key(), *cursor.val()));
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        result
+    }
+
+    /// Returns all `(timestamp, value)` pairs from the current position on.
+    fn remaining<'s, C>(cursor: &mut C) -> Vec<(u64, u64)>
+    where
+        C: Cursor<'s, u64, u64, (), isize>,
+    {
+        let mut result = Vec::new();
+
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                result.push((*cursor.key(), *cursor.val()));
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        result
+    }
+
+    // Repeated range scans within a partition return the same results whether
+    // we search for the partition before every scan or rewind to a mark, but
+    // rewinding does not compare any partition keys.
+    #[test]
+    fn test_rewind_to_mark() {
+        let batch = partitioned_batch();
+        let ranges = [(0, 50), (30, 120), (100, 190), (0, 190), (75, 75)];
+
+        for partition in (0..PARTITIONS).step_by(7).map(Counted) {
+            let mut cursor = batch.cursor();
+
+            let start = comparisons();
+            let mut expected = Vec::new();
+            for (from, to) in ranges {
+                cursor.rewind_keys();
+                cursor.seek_key(&partition);
+                expected.push(scan(&mut PartitionCursor::new(&mut cursor), from, to));
+            }
+            assert!(comparisons() > start);
+
+            cursor.rewind_keys();
+            cursor.seek_key(&partition);
+            let mut partition_cursor = PartitionCursor::new(&mut cursor);
+            let mark = partition_cursor.mark();
+            assert!(matches!(mark, CursorMark::Offsets(_)));
+
+            let start = comparisons();
+            let mut actual = Vec::new();
+            for (from, to) in ranges {
+                partition_cursor.rewind_to_mark(&mark);
+                actual.push(scan(&mut partition_cursor, from, to));
+            }
+            assert_eq!(comparisons(), start);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    // Both kinds of marks restore the position they were taken at.
+    #[test]
+    fn test_mark_conformance() {
+        let batch = partitioned_batch();
+        let mut cursor = batch.cursor();
+        cursor.seek_key(&Counted(PARTITIONS / 2));
+        let mut partition_cursor = PartitionCursor::new(&mut cursor);
+
+        while partition_cursor.key_valid() {
+            let marks = [
+                partition_cursor.mark(),
+                CursorMark::Seek {
+                    key: partition_cursor.get_key().cloned(),
+                    val: partition_cursor.get_val().cloned(),
+                },
+            ];
+            let expected = remaining(&mut partition_cursor);
+            assert!(!expected.is_empty());
+
+            for mark in marks.iter() {
+                partition_cursor.rewind_to_mark(mark);
+                assert_eq!(remaining(&mut partition_cursor), expected);
+                partition_cursor.rewind_to_mark(mark);
+            }
+            partition_cursor.step_key();
+        }
+    }
+}
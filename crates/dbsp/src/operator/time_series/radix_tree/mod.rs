@@ -72,6 +72,12 @@
 //!   user id or tenant id, and by time.  The operator outputs a separate tree
 //!   per partition.
 //!
+//! The output of `partitioned_tree_aggregate` can be queried inside the circuit
+//! using
+//! [`PartitionedRadixTreeReader::aggregate_range_for_partition`](`crate::operator::time_series::PartitionedRadixTreeReader::aggregate_range_for_partition`)
+//! or outside the circuit via the handle returned by
+//! [`partitioned_tree_aggregate_query`](`crate::Stream::partitioned_tree_aggregate_query`).
+//!
 //! These are low-level operators that are used as building blocks by other time
 //! series operators like
 //! [`partitioned_rolling_aggregate`](`crate::Stream::partitioned_rolling_aggregate`).
@@ -95,7 +101,10 @@ mod partitioned_tree_aggregate;
 mod tree_aggregate;
 mod updater;
 
-pub use partitioned_tree_aggregate::{PartitionedRadixTreeCursor, PartitionedRadixTreeReader};
+pub use partitioned_tree_aggregate::{
+    OrdPartitionedRadixTree, OrdPartitionedRadixTreeStream, PartitionedRadixTreeBatch,
+    PartitionedRadixTreeCursor, PartitionedRadixTreeQueryHandle, PartitionedRadixTreeReader,
+};
 pub(self) use updater::radix_tree_update;
 
 // We use constant radix to reduce the need to dynamically allocate a vector of
@@ -281,6 +290,15 @@ where
                 let node_prefix = self.key().clone();
                 assert!(expected_prefixes.remove(&node_prefix));
                 let node = self.val().clone();
+
+                // Empty nodes are removed and nodes with a single child are
+                // merged into their parents, except for the root node.
+                if node_prefix == Prefix::full_range() {
+                    assert!(node.occupied_slots() >= 1);
+                } else {
+                    assert!(node.occupied_slots() >= 2);
+                }
+
                 for (child_idx, child_ptr) in node
                     .children
                     .iter()
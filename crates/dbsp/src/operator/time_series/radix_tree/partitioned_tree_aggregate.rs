@@ -2,6 +2,7 @@ use super::{radix_tree_update, Prefix, RadixTreeCursor, TreeNode};
 use crate::{
     algebra::{HasOne, HasZero, Semigroup, ZRingValue},
     circuit::{
+        metadata::{MetaItem, OperatorMeta},
         operator_traits::{Operator, TernaryOperator},
         GlobalNodeId, OwnershipPreference, Scope,
     },
@@ -9,12 +10,13 @@ use crate::{
     operator::{
         time_series::{
             PartitionCursor, PartitionedBatch, PartitionedBatchReader, PartitionedIndexedZSet,
+            Range,
         },
         trace::{DelayedTraceId, IntegrateTraceId, TraceBounds, UntimedTraceAppend, Z1Trace},
         Aggregator,
     },
-    trace::{Builder, Cursor, Spine},
-    Circuit, DBData, DBWeight, OrdIndexedZSet, RootCircuit, Stream,
+    trace::{BatchReader, Builder, Cursor, Spine},
+    Circuit, DBData, DBWeight, OrdIndexedZSet, OutputHandle, RootCircuit, Stream,
 };
 use num::PrimInt;
 use size_of::SizeOf;
@@ -26,6 +28,7 @@ use std::{
     fmt::{Debug, Write},
     marker::PhantomData,
     ops::Neg,
+    sync::{Arc, Mutex, MutexGuard},
 };
 
 circuit_cache_key!(PartitionedTreeAggregateId<C, D, Agg>(GlobalNodeId => Stream<C, D>));
@@ -40,9 +43,41 @@ impl<TS, A, B> PartitionedRadixTreeBatch<TS, A> for B where
 {
 }
 
+/// Read interface to a partitioned radix tree, e.g., the integrated output of
+/// [`partitioned_tree_aggregate`](`Stream::partitioned_tree_aggregate`).
 pub trait PartitionedRadixTreeReader<TS, A>:
     PartitionedBatchReader<Prefix<TS>, TreeNode<TS, A>>
 {
+    /// Computes aggregate over time range `range` within `partition`.
+    ///
+    /// Combines all aggregate values for timestamps in `range` using semigroup
+    /// `S`, which must be the semigroup of the aggregator used to build the
+    /// tree.  Returns `None` if the partition does not exist or does not
+    /// contain any timestamps in `range`.
+    ///
+    /// # Complexity
+    ///
+    /// This method performs a lookup of `partition` followed by a scan of
+    /// `O(log(range.to - range.from))` tree nodes.
+    fn aggregate_range_for_partition<S>(
+        &self,
+        partition: &Self::Key,
+        range: &Range<TS>,
+    ) -> Option<A>
+    where
+        TS: DBData + PrimInt,
+        A: DBData,
+        S: Semigroup<A>,
+    {
+        let mut cursor = self.cursor();
+
+        cursor.seek_key(partition);
+        if cursor.key_valid() && cursor.key() == partition {
+            PartitionCursor::new(&mut cursor).aggregate_range::<S>(range)
+        } else {
+            None
+        }
+    }
 }
 
 impl<TS, A, B> PartitionedRadixTreeReader<TS, A> for B where
@@ -50,8 +85,13 @@ impl<TS, A, B> PartitionedRadixTreeReader<TS, A> for B where
 {
 }
 
-type OrdPartitionedRadixTree<PK, TS, A, R> = OrdIndexedZSet<PK, (Prefix<TS>, TreeNode<TS, A>), R>;
-type OrdPartitionedRadixTreeStream<PK, TS, A, R> =
+/// Batch type produced by
+/// [`partitioned_tree_aggregate`](`Stream::partitioned_tree_aggregate`).
+pub type OrdPartitionedRadixTree<PK, TS, A, R> =
+    OrdIndexedZSet<PK, (Prefix<TS>, TreeNode<TS, A>), R>;
+
+/// Stream of updates to a partitioned radix tree.
+pub type OrdPartitionedRadixTreeStream<PK, TS, A, R> =
     Stream<RootCircuit, OrdPartitionedRadixTree<PK, TS, A, R>>;
 
 /// Cursor over partitioned radix tree.
@@ -112,6 +152,20 @@ where
 {
     /// Given a batch of updates to a partitioned time series stream, computes a
     /// stream of updates to its partitioned radix tree.
+    ///
+    /// The input stream is indexed by partition key; each value is a
+    /// `(timestamp, value)` pair.  The operator maintains a separate radix tree
+    /// per partition, where each tree node stores the value of `aggregator`
+    /// over the range of timestamps covered by the node.  The output stream
+    /// contains changes to the trees, encoded as `(partition, (prefix, node))`
+    /// tuples with weights `+1` and `-1`.
+    ///
+    /// Use [`integrate_trace`](`Stream::integrate_trace`) to assemble the
+    /// output stream into a trace that implements
+    /// [`PartitionedRadixTreeReader`], which can then be queried using
+    /// [`PartitionedRadixTreeReader::aggregate_range_for_partition`].
+    /// Alternatively, [`Self::partitioned_tree_aggregate_query`] makes the
+    /// tree available for querying outside of the circuit.
     pub fn partitioned_tree_aggregate<TS, V, Agg>(
         &self,
         aggregator: Agg,
@@ -225,6 +279,138 @@ where
             )
             .clone()
     }
+
+    /// Builds a partitioned radix tree over `self` using
+    /// [`partitioned_tree_aggregate`](`Self::partitioned_tree_aggregate`)
+    /// and returns a handle that can be used to compute range aggregates
+    /// over the tree from outside the circuit.
+    ///
+    /// At each clock cycle, the operator publishes a snapshot of the tree to
+    /// the handle.  The application can query the snapshot between clock
+    /// cycles using [`PartitionedRadixTreeQueryHandle::aggregate_range`].
+    ///
+    /// Note that each worker publishes a complete copy of its shard of the
+    /// tree at every clock cycle, so this operator is best suited for ad hoc
+    /// queries over trees of moderate size.
+    pub fn partitioned_tree_aggregate_query<TS, V, Agg>(
+        &self,
+        aggregator: Agg,
+    ) -> PartitionedRadixTreeQueryHandle<Z::Key, TS, Agg::Accumulator, Agg::Semigroup>
+    where
+        Z: PartitionedIndexedZSet<TS, V> + SizeOf,
+        TS: DBData + PrimInt,
+        V: DBData,
+        Agg: Aggregator<V, (), Z::R>,
+        Agg::Accumulator: Default,
+    {
+        let output = self
+            .partitioned_tree_aggregate::<TS, V, Agg>(aggregator)
+            .integrate()
+            .output();
+
+        PartitionedRadixTreeQueryHandle::new(output)
+    }
+}
+
+/// A handle used to query a partitioned radix tree from outside the circuit.
+///
+/// Created by
+/// [`partitioned_tree_aggregate_query`](`Stream::partitioned_tree_aggregate_query`).
+///
+/// The handle caches the most recent snapshot of the tree received from each
+/// worker, so it can be queried any number of times between two consecutive
+/// [`DBSPHandle::step`](`crate::DBSPHandle::step`) calls.
+///
+/// # Type arguments
+///
+/// * `PK` - partition key
+/// * `TS` - timestamp
+/// * `A` - accumulator type of the aggregator used to build the tree
+/// * `S` - semigroup used to combine accumulators
+pub struct PartitionedRadixTreeQueryHandle<PK, TS, A, S>
+where
+    PK: DBData,
+    TS: DBData + PrimInt,
+    A: DBData,
+{
+    output: OutputHandle<OrdPartitionedRadixTree<PK, TS, A, isize>>,
+    snapshots: Arc<Mutex<Vec<OrdPartitionedRadixTree<PK, TS, A, isize>>>>,
+    phantom: PhantomData<fn(&S)>,
+}
+
+impl<PK, TS, A, S> Clone for PartitionedRadixTreeQueryHandle<PK, TS, A, S>
+where
+    PK: DBData,
+    TS: DBData + PrimInt,
+    A: DBData,
+{
+    fn clone(&self) -> Self {
+        Self {
+            output: self.output.clone(),
+            snapshots: self.snapshots.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<PK, TS, A, S> PartitionedRadixTreeQueryHandle<PK, TS, A, S>
+where
+    PK: DBData,
+    TS: DBData + PrimInt,
+    A: DBData,
+    S: Semigroup<A>,
+{
+    fn new(output: OutputHandle<OrdPartitionedRadixTree<PK, TS, A, isize>>) -> Self {
+        Self {
+            output,
+            snapshots: Arc::new(Mutex::new(Vec::new())),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Pick up snapshots published during the last clock cycle, if any.
+    fn refresh(&self) -> MutexGuard<'_, Vec<OrdPartitionedRadixTree<PK, TS, A, isize>>> {
+        let mut snapshots = self.snapshots.lock().unwrap();
+
+        let new_snapshots = self.output.take_from_all();
+        if !new_snapshots.is_empty() {
+            *snapshots = new_snapshots;
+        }
+
+        snapshots
+    }
+
+    /// Computes aggregate over time range `range` within `partition`.
+    ///
+    /// Returns `None` if the partition does not exist or does not contain
+    /// any timestamps in `range`.  The result is computed over the snapshot
+    /// of the tree published during the most recent clock cycle.
+    pub fn aggregate_range(&self, partition: &PK, range: &Range<TS>) -> Option<A> {
+        // Partitions are sharded across workers, so at most one snapshot
+        // contains `partition`.
+        self.refresh().iter().fold(None, |acc, snapshot| {
+            S::combine_opt(
+                &acc,
+                &snapshot.aggregate_range_for_partition::<S>(partition, range),
+            )
+        })
+    }
+
+    /// Returns the list of partitions in the current snapshot of the tree.
+    pub fn partitions(&self) -> Vec<PK> {
+        let mut partitions = Vec::new();
+
+        for snapshot in self.refresh().iter() {
+            let mut cursor = snapshot.cursor();
+            while cursor.key_valid() {
+                partitions.push(cursor.key().clone());
+                cursor.step_key();
+            }
+        }
+
+        partitions.sort();
+        partitions
+    }
 }
 
 /// Cursor that contains no data.
@@ -315,25 +501,56 @@ where
 ///   data.
 /// * Input stream 3: trace containing the current contents of the partitioned
 ///   radix tree.
-struct PartitionedRadixTreeAggregate<TS, V, Z, IT, OT, Agg, O> {
+///
+/// The operator tracks the number of tree nodes in each partition.  Tree
+/// updates remove interior nodes whose children have all been deleted, so a
+/// partition whose timestamps have all been retracted, e.g., because they
+/// fell behind the watermark, ends up with no nodes and is forgotten.
+struct PartitionedRadixTreeAggregate<TS, V, Z, IT, OT, Agg, O>
+where
+    Z: BatchReader,
+{
     aggregator: Agg,
+    /// Number of nodes in the tree of each non-empty partition.
+    node_counts: BTreeMap<Z::Key, usize>,
     phantom: PhantomData<(TS, V, Z, IT, OT, O)>,
 }
 
-impl<TS, V, Z, IT, OT, Agg, O> PartitionedRadixTreeAggregate<TS, V, Z, IT, OT, Agg, O> {
+impl<TS, V, Z, IT, OT, Agg, O> PartitionedRadixTreeAggregate<TS, V, Z, IT, OT, Agg, O>
+where
+    Z: BatchReader,
+{
     pub fn new(aggregator: Agg) -> Self {
         Self {
             aggregator,
+            node_counts: BTreeMap::new(),
             phantom: PhantomData,
         }
     }
+
+    /// Adjusts the number of nodes in the tree of `partition` by `delta`,
+    /// dropping the partition once its tree becomes empty.
+    fn update_node_count(&mut self, partition: &Z::Key, delta: isize) {
+        if delta == 0 {
+            return;
+        }
+
+        let count = self.node_counts.get(partition).copied().unwrap_or(0) as isize + delta;
+        debug_assert!(count >= 0);
+
+        if count > 0 {
+            self.node_counts.insert(partition.clone(), count as usize);
+        } else {
+            self.node_counts.remove(partition);
+        }
+    }
 }
 
 impl<TS, V, Z, IT, OT, Agg, O> Operator for PartitionedRadixTreeAggregate<TS, V, Z, IT, OT, Agg, O>
 where
     TS: 'static,
     V: 'static,
-    Z: 'static,
+    Z: BatchReader + 'static,
     IT: 'static,
     OT: 'static,
     Agg: 'static,
@@ -343,6 +560,21 @@ where
         Cow::from("PartitionedRadixTreeAggregate")
     }
 
+    fn metadata(&self, meta: &mut OperatorMeta) {
+        let nodes: usize = self.node_counts.values().sum();
+        let nodes_per_partition: Vec<_> = self
+            .node_counts
+            .iter()
+            .map(|(partition, count)| (Cow::from(format!("{partition:?}")), MetaItem::Int(*count)))
+            .collect();
+
+        meta.extend(metadata! {
+            "partitions" => self.node_counts.len(),
+            "tree nodes" => nodes,
+            "nodes per partition" => MetaItem::Map(nodes_per_partition.into()),
+        });
+    }
+
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
@@ -431,7 +663,10 @@ where
 
             // `updates` are already ordered by prefix.  All that remains is to order
             // insertion and deletion within each update.
+            let mut node_delta = 0;
             for update in updates.drain(..) {
+                node_delta += update.new.is_some() as isize - update.old.is_some() as isize;
+
                 match update.new.cmp(&update.old) {
                     Ordering::Equal => {}
                     Ordering::Less => {
@@ -464,6 +699,7 @@ where
                     }
                 }
             }
+            self.update_node_count(&key, node_delta);
 
             delta_cursor.step_key();
         }
@@ -477,11 +713,12 @@ mod test {
     use super::{super::test::test_aggregate_range, PartitionCursor, PartitionedRadixTreeCursor};
     use crate::{
         algebra::{DefaultSemigroup, HasZero, Semigroup},
-        operator::Fold,
+        operator::{time_series::Range, Fold},
         trace::BatchReader,
-        CollectionHandle, DBData, RootCircuit,
+        CollectionHandle, DBData, RootCircuit, Runtime,
     };
     use num::PrimInt;
+    use proptest::{collection, prelude::*};
     use std::{
         collections::{btree_map::Entry, BTreeMap},
         sync::{Arc, Mutex},
@@ -862,4 +1099,108 @@ mod test {
         );
         circuit.step().unwrap();
     }
+
+    type InputTuple = (u64, ((u64, i64), isize));
+    type InputBatch = Vec<InputTuple>;
+
+    fn input_trace(
+        partitions: u64,
+        epoch: u64,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<(InputBatch, Vec<(u64, u64, u64)>)>> {
+        collection::vec(
+            (
+                collection::vec(
+                    ((0..partitions), ((0..epoch, -100..100i64), -1..2isize)),
+                    0..max_batch_size,
+                ),
+                // Range queries to issue after the batch has been processed.
+                collection::vec((0..partitions, 0..epoch, 0..epoch), 0..20),
+            ),
+            0..max_batches,
+        )
+    }
+
+    // Compute aggregate over a range by brute force.
+    fn aggregate_range_slow(
+        contents: &BTreeMap<u64, BTreeMap<(u64, i64), isize>>,
+        partition: u64,
+        range: &Range<u64>,
+    ) -> Option<i64> {
+        contents.get(&partition).and_then(|partition_contents| {
+            partition_contents
+                .range((range.from, i64::MIN)..=(range.to, i64::MAX))
+                .fold(None, |acc, ((_ts, val), w)| {
+                    Some(acc.unwrap_or(0) + *val * (*w as i64))
+                })
+        })
+    }
+
+    fn test_partitioned_tree_aggregate_query(
+        workers: usize,
+        trace: Vec<(InputBatch, Vec<(u64, u64, u64)>)>,
+    ) {
+        let (mut dbsp, (input, query)) = Runtime::init_circuit(workers, |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let aggregator = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0i64,
+                |agg: &mut i64, val: &i64, w: isize| *agg += val * (w as i64),
+            );
+
+            let query = input.partitioned_tree_aggregate_query::<u64, i64, _>(aggregator);
+
+            (input_handle, query)
+        })
+        .unwrap();
+
+        let mut contents: BTreeMap<u64, BTreeMap<(u64, i64), isize>> = BTreeMap::new();
+
+        for (batch, queries) in trace {
+            for (partition, (val, w)) in batch {
+                input.push(partition, (val, w));
+
+                let partition_contents = contents.entry(partition).or_default();
+                let weight = partition_contents.entry(val).or_default();
+                *weight += w;
+                if *weight == 0 {
+                    partition_contents.remove(&val);
+                }
+            }
+            dbsp.step().unwrap();
+
+            for (partition, from, to) in queries {
+                let range = Range::new(from.min(to), from.max(to));
+
+                assert_eq!(
+                    query.aggregate_range(&partition, &range),
+                    aggregate_range_slow(&contents, partition, &range)
+                );
+            }
+
+            let expected_partitions = contents
+                .iter()
+                .filter(|(_, partition_contents)| !partition_contents.is_empty())
+                .map(|(partition, _)| *partition)
+                .collect::<Vec<_>>();
+            assert_eq!(query.partitions(), expected_partitions);
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_partitioned_tree_aggregate_query_sparse(trace in input_trace(5, 1_000_000, 20, 20)) {
+            test_partitioned_tree_aggregate_query(1, trace);
+        }
+
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_partitioned_tree_aggregate_query_dense(trace in input_trace(5, 1_000, 50, 20)) {
+            test_partitioned_tree_aggregate_query(4, trace);
+        }
+    }
 }
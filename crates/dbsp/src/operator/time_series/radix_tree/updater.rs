@@ -136,6 +136,13 @@ where
             self.pop();
         }
 
+        // `pop` deletes nodes left without children, so no empty nodes
+        // survive the update.
+        debug_assert!(self.updates.iter().all(|update| update
+            .new
+            .as_ref()
+            .map_or(true, |node| node.occupied_slots() > 0)));
+
         // TODO: This can be expensive.  Can we keep `updates` ordered so no sorting is
         // required?  Currently out-of-order updates are added when creating
         // intermediate tree nodes (`push_new`).  I can think of two tricks to
@@ -309,8 +309,14 @@ where
         self.advance();
     }
 
-    fn seek_key(&mut self, _key: &TS) {
-        unimplemented!()
+    fn seek_key(&mut self, key: &TS) {
+        while self.current_range < self.ranges.len()
+            && &self.ranges.range(self.current_range).to < key
+        {
+            self.current_range += 1;
+        }
+        self.cursor.seek_key(key);
+        self.advance();
     }
 
     fn last_key(&mut self) -> Option<&TS> {
@@ -333,17 +339,39 @@ where
     }
 
     fn rewind_keys(&mut self) {
-        unimplemented!()
+        self.cursor.rewind_keys();
+        self.current_range = 0;
+        self.advance();
     }
 
     fn rewind_vals(&mut self) {
         self.cursor.rewind_vals()
     }
+
+    // The offsets of the underlying cursor followed by the current range.
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        let mut offsets = self.cursor.mark_offsets()?;
+        offsets.push(self.current_range);
+        Some(offsets)
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        let (current_range, offsets) = offsets.split_last().unwrap();
+        self.cursor.rewind_to_offsets(offsets);
+        self.current_range = *current_range;
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::operator::time_series::range::{Range, Ranges};
+    use crate::{
+        operator::time_series::{
+            partitioned::PartitionCursor,
+            range::{Range, RangeCursor, Ranges},
+        },
+        trace::{Batch, BatchReader, Cursor, CursorMark},
+        OrdIndexedZSet,
+    };
     use num::PrimInt;
 
     fn ranges_from_bounds<T: PrimInt>(bounds: &[(T, T)]) -> Ranges<T> {
@@ -373,4 +401,55 @@ mod test {
         let merged = ranges2.merge(&ranges1);
         assert_eq!(merged, expected);
     }
+
+    fn keys<'s, C>(cursor: &mut C) -> Vec<u64>
+    where
+        C: Cursor<'s, u64, u64, (), isize>,
+    {
+        let mut keys = Vec::new();
+
+        while cursor.key_valid() {
+            keys.push(*cursor.key());
+            cursor.step_key();
+        }
+
+        keys
+    }
+
+    #[test]
+    fn test_range_cursor() {
+        let tuples = (0..4)
+            .flat_map(|p| (0..50).map(move |ts| ((p, (ts, p)), 1)))
+            .collect();
+        let batch = OrdIndexedZSet::<u64, (u64, u64), isize>::from_tuples((), tuples);
+        let ranges = ranges_from_bounds(&[(3, 7), (10, 10), (20, 35), (48, 60)]);
+        let expected: Vec<u64> = (3..=7)
+            .chain(10..=10)
+            .chain(20..=35)
+            .chain(48..50)
+            .collect();
+
+        let mut cursor = batch.cursor();
+        cursor.seek_key(&2);
+        let mut range_cursor = RangeCursor::new(PartitionCursor::new(&mut cursor), ranges);
+        assert_eq!(keys(&mut range_cursor), expected);
+
+        range_cursor.rewind_keys();
+        assert_eq!(keys(&mut range_cursor), expected);
+
+        range_cursor.rewind_keys();
+        range_cursor.seek_key(&15);
+        assert_eq!(keys(&mut range_cursor), &expected[6..]);
+
+        range_cursor.rewind_keys();
+        for i in 0..expected.len() {
+            let mark = range_cursor.mark();
+            assert!(matches!(mark, CursorMark::Offsets(_)));
+            assert_eq!(keys(&mut range_cursor), &expected[i..]);
+
+            range_cursor.rewind_to_mark(&mark);
+            range_cursor.step_key();
+        }
+        assert!(!range_cursor.key_valid());
+    }
 }
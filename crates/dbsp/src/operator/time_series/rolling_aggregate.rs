@@ -1,5 +1,8 @@
 use crate::{
-    algebra::{DefaultSemigroup, GroupValue, HasOne, HasZero, IndexedZSet, MulByRef, ZRingValue},
+    algebra::{
+        DefaultSemigroup, GroupValue, HasOne, HasZero, IndexedZSet, MonoidValue, MulByRef,
+        Semigroup, ZRingValue,
+    },
     circuit::{
         operator_traits::{Operator, QuaternaryOperator},
         OwnershipPreference, Scope,
@@ -14,9 +17,9 @@ use crate::{
         trace::{
             DelayedTraceId, IntegrateTraceId, TraceBound, TraceBounds, UntimedTraceAppend, Z1Trace,
         },
-        Aggregator, FilterMap,
+        Aggregator, Avg, FilterMap,
     },
-    trace::{Builder, Cursor, Spine},
+    trace::{consolidation::consolidate, Builder, Cursor, Spine},
     Circuit, DBData, DBWeight, RootCircuit, Stream,
 };
 use num::{Bounded, PrimInt};
@@ -102,6 +105,163 @@ where
     }
 }
 
+/// `Aggregator` object that computes a linear aggregation function along with
+/// the total weight of the aggregated values and passes both to the output
+/// function.
+struct WeightedLinearAggregator<V, R, A, O, F, OF> {
+    f: F,
+    output_func: OF,
+    phantom: PhantomData<(V, R, A, O)>,
+}
+
+impl<V, R, A, O, F, OF> Clone for WeightedLinearAggregator<V, R, A, O, F, OF>
+where
+    F: Clone,
+    OF: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            output_func: self.output_func.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<V, R, A, O, F, OF> WeightedLinearAggregator<V, R, A, O, F, OF> {
+    fn new(f: F, output_func: OF) -> Self {
+        Self {
+            f,
+            output_func,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<V, R, A, O, F, OF> Aggregator<V, (), R> for WeightedLinearAggregator<V, R, A, O, F, OF>
+where
+    V: DBData,
+    R: DBWeight + ZRingValue,
+    A: DBData + MulByRef<R, Output = A> + GroupValue,
+    O: DBData,
+    F: Fn(&V) -> A + Clone + 'static,
+    OF: Fn(A, R) -> O + Clone + 'static,
+{
+    type Accumulator = Avg<A, R>;
+    type Output = O;
+
+    type Semigroup = DefaultSemigroup<Avg<A, R>>;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Avg<A, R>>
+    where
+        C: Cursor<'s, V, (), (), R>,
+    {
+        let mut res: Option<Avg<A, R>> = None;
+
+        while cursor.key_valid() {
+            let w = cursor.weight();
+            let new = Avg::new((self.f)(cursor.key()).mul_by_ref(&w), w);
+            res = match res {
+                None => Some(new),
+                Some(old) => Some(old + new),
+            };
+            cursor.step_key();
+        }
+        res
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        (self.output_func)(accumulator.sum(), accumulator.count())
+    }
+}
+
+/// `Aggregator` object that counts distinct values with positive weights.
+///
+/// Unlike [`Stream::aggregate_count_distinct`], which computes the count
+/// in one pass over all values of a key, the rolling aggregate combines
+/// partial aggregates computed over disjoint time ranges, which may contain
+/// the same values.  Hence the accumulator of this aggregator is a Z-set of
+/// values represented as a sorted vector of `(value, weight)` pairs.  Partial
+/// accumulators are combined by [`ZSetUnionSemigroup`] and the final count
+/// is computed by [`finalize`](`Aggregator::finalize`).
+struct CountDistinctAggregator<V, R> {
+    phantom: PhantomData<(V, R)>,
+}
+
+impl<V, R> Clone for CountDistinctAggregator<V, R> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<V, R> CountDistinctAggregator<V, R> {
+    fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Semigroup over Z-sets represented as sorted vectors of `(value, weight)`
+/// pairs.
+struct ZSetUnionSemigroup<V, R> {
+    phantom: PhantomData<(V, R)>,
+}
+
+impl<V, R> Clone for ZSetUnionSemigroup<V, R> {
+    fn clone(&self) -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<V, R> Semigroup<Vec<(V, R)>> for ZSetUnionSemigroup<V, R>
+where
+    V: Ord + Clone,
+    R: MonoidValue,
+{
+    fn combine(left: &Vec<(V, R)>, right: &Vec<(V, R)>) -> Vec<(V, R)> {
+        let mut result = Vec::with_capacity(left.len() + right.len());
+        result.extend_from_slice(left);
+        result.extend_from_slice(right);
+        consolidate(&mut result);
+        result
+    }
+}
+
+impl<V, R> Aggregator<V, (), R> for CountDistinctAggregator<V, R>
+where
+    V: DBData,
+    R: DBWeight + ZRingValue,
+{
+    type Accumulator = Vec<(V, R)>;
+    type Output = i64;
+
+    type Semigroup = ZSetUnionSemigroup<V, R>;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Vec<(V, R)>>
+    where
+        C: Cursor<'s, V, (), (), R>,
+    {
+        let mut res = Vec::new();
+
+        while cursor.key_valid() {
+            let w = cursor.weight();
+            if !w.is_zero() {
+                res.push((cursor.key().clone(), w));
+            }
+            cursor.step_key();
+        }
+
+        (!res.is_empty()).then_some(res)
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        accumulator.iter().filter(|(_, w)| !w.le0()).count() as i64
+    }
+}
+
 impl<B> Stream<RootCircuit, B>
 where
     B: IndexedZSet,
@@ -191,6 +351,8 @@ where
                         .range_of(wm)
                         .map(|range| range.from)
                         .unwrap_or_else(|| Bounded::min_value());
+                    // `(lower, None)` is the smallest value with timestamp
+                    // `lower`, so all outputs at `lower` are retained.
                     bound_clone.set((lower, None));
                     (lower, Bounded::max_value())
                 });
@@ -309,7 +471,8 @@ impl<B> Stream<RootCircuit, B> {
             .integrate_trace();
         let input_trace = stream_window.integrate_trace();
 
-        // Truncate timestamps `< bound` in the output trace.
+        // Truncate values below `bound`, i.e., timestamps `< bound.0`, in the
+        // output trace.
         let bounds = TraceBounds::new();
         bounds.add_key_bound(TraceBound::new());
         bounds.add_val_bound(bound);
@@ -406,6 +569,106 @@ impl<B> Stream<RootCircuit, B> {
         let aggregator = LinearAggregator::new(f, output_func);
         self.partitioned_rolling_aggregate_generic::<TS, V, _, _>(aggregator, range)
     }
+
+    /// Like [`Self::partitioned_rolling_aggregate_linear`], but additionally
+    /// passes the total weight of values in the window to `output_func`.
+    ///
+    /// The total weight is computed alongside the linear aggregate in the same
+    /// pass over the radix tree, which makes this method suitable for
+    /// computing weighted averages and other aggregates normalized by the
+    /// number of values in the window, e.g.:
+    ///
+    /// ```text
+    /// stream.partitioned_rolling_aggregate_linear_with_weight(
+    ///     |val| *val,
+    ///     |sum, count| sum / count,
+    ///     range,
+    /// )
+    /// ```
+    pub fn partitioned_rolling_aggregate_linear_with_weight<TS, V, A, O, F, OF>(
+        &self,
+        f: F,
+        output_func: OF,
+        range: RelRange<TS>,
+    ) -> OrdPartitionedOverStream<B::Key, TS, O, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue + Default,
+        A: DBData + MulByRef<B::R, Output = A> + GroupValue + Default,
+        F: Fn(&V) -> A + Clone + 'static,
+        OF: Fn(A, B::R) -> O + Clone + 'static,
+        TS: DBData + PrimInt,
+        V: DBData,
+        O: DBData,
+    {
+        let aggregator = WeightedLinearAggregator::new(f, output_func);
+        self.partitioned_rolling_aggregate_generic::<TS, V, _, _>(aggregator, range)
+    }
+
+    /// Like [`Self::partitioned_rolling_aggregate_linear_with_weight`], but can
+    /// return any batch type.
+    pub fn partitioned_rolling_aggregate_linear_with_weight_generic<TS, V, A, O, F, OF, Out>(
+        &self,
+        f: F,
+        output_func: OF,
+        range: RelRange<TS>,
+    ) -> Stream<RootCircuit, Out>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue + Default,
+        A: DBData + MulByRef<B::R, Output = A> + GroupValue + Default,
+        F: Fn(&V) -> A + Clone + 'static,
+        OF: Fn(A, B::R) -> O + Clone + 'static,
+        TS: DBData + PrimInt,
+        V: DBData,
+        O: DBData,
+        Out: PartitionedIndexedZSet<TS, Option<O>, Key = B::Key, R = B::R>,
+    {
+        let aggregator = WeightedLinearAggregator::new(f, output_func);
+        self.partitioned_rolling_aggregate_generic::<TS, V, _, _>(aggregator, range)
+    }
+
+    /// Rolling count of distinct values in a partitioned stream over time
+    /// range.
+    ///
+    /// For each record in the input stream, counts distinct values within
+    /// the relative time range `range` in the same partition.  A value is
+    /// counted if its total weight within the range is positive.  This is
+    /// the rolling counterpart of
+    /// [`aggregate_count_distinct`](`Stream::aggregate_count_distinct`).
+    ///
+    /// Note that the accumulator of this aggregate stores the set of
+    /// distinct values in each subrange of the partition, so its memory
+    /// footprint grows with the number of distinct values.
+    pub fn partitioned_rolling_count_distinct<TS, V>(
+        &self,
+        range: RelRange<TS>,
+    ) -> OrdPartitionedOverStream<B::Key, TS, i64, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        self.partitioned_rolling_count_distinct_generic::<TS, V, _>(range)
+    }
+
+    /// Like [`Self::partitioned_rolling_count_distinct`], but can return any
+    /// batch type.
+    pub fn partitioned_rolling_count_distinct_generic<TS, V, O>(
+        &self,
+        range: RelRange<TS>,
+    ) -> Stream<RootCircuit, O>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        TS: DBData + PrimInt,
+        V: DBData,
+        O: PartitionedIndexedZSet<TS, Option<i64>, Key = B::Key, R = B::R>,
+    {
+        let aggregator = CountDistinctAggregator::new();
+        self.partitioned_rolling_aggregate_generic::<TS, V, _, _>(aggregator, range)
+    }
 }
 
 /// Quaternary operator that implements the internals of
@@ -549,6 +812,7 @@ where
                 debug_assert_eq!(tree_cursor.key(), delta_cursor.key());
 
                 let mut tree_partition_cursor = PartitionCursor::new(&mut tree_cursor);
+                let partition_start = tree_partition_cursor.mark();
                 let mut input_range_cursor =
                     RangeCursor::new(PartitionCursor::new(&mut input_trace_cursor), ranges);
 
@@ -561,7 +825,7 @@ where
                         input_range_cursor.step_key();
                         continue;
                     };
-                    tree_partition_cursor.rewind_keys();
+                    tree_partition_cursor.rewind_to_mark(&partition_start);
 
                     // println!("aggregate_range({range:x?})");
                     // let mut treestr = String::new();
@@ -607,17 +871,12 @@ where
 #[cfg(test)]
 mod test {
     use crate::{
-        algebra::DefaultSemigroup,
         operator::{
-            time_series::{
-                range::{Range, RelOffset, RelRange},
-                PartitionCursor,
-            },
+            time_series::{range::Range, PartitionCursor},
             trace::TraceBound,
-            FilterMap, Fold,
         },
-        trace::{Batch, BatchReader, Cursor},
-        CollectionHandle, DBSPHandle, OrdIndexedZSet, RootCircuit, Runtime, Stream,
+        prelude::*,
+        testing::{CircuitTester, TestOutput},
     };
     use size_of::SizeOf;
 
@@ -694,8 +953,8 @@ mod test {
     fn partition_rolling_aggregate_circuit(
         lateness: u64,
         size_bound: Option<usize>,
-    ) -> (DBSPHandle, RangeHandle) {
-        Runtime::init_circuit(4, move |circuit| {
+    ) -> (CircuitTester, RangeHandle) {
+        CircuitTester::new(4, move |circuit| {
             let (input_stream, input_handle) =
                 circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
 
@@ -746,6 +1005,29 @@ mod test {
                 assert_eq!(expected, actual)
             });
 
+            // Rolling average computed using the total weight of the window
+            // must match the average computed with a `(sum, count)`
+            // accumulator.
+            let mean_1000_0 = input_stream
+                .partitioned_rolling_aggregate_linear_with_weight::<u64, i64, _, _, _, _>(
+                    |v| *v,
+                    |sum, count: isize| (count != 0).then(|| sum / count as i64),
+                    range_spec,
+                )
+                .gather(0)
+                .integrate();
+            let expected_mean_1000_0 = input_stream
+                .partitioned_rolling_aggregate_linear::<u64, i64, _, _, _, _>(
+                    |v| Avg::new(*v as isize, 1isize),
+                    |avg| avg.compute_avg().map(|avg| avg as i64),
+                    range_spec,
+                )
+                .gather(0)
+                .integrate();
+            expected_mean_1000_0.apply2(&mean_1000_0, |expected, actual| {
+                assert_eq!(expected, actual)
+            });
+
             let range_spec = RelRange::new(RelOffset::Before(500), RelOffset::After(500));
             let expected_500_500 = partitioned_rolling_aggregate_slow(&input_stream, range_spec);
             let aggregate_500_500 = input_stream
@@ -776,6 +1058,59 @@ mod test {
                     ()
                 });
 
+            if size_bound.is_some() {
+                // Build the radix tree over the same window as
+                // `partitioned_rolling_aggregate_with_watermark`.  Interior
+                // nodes have at least two children, except for the root, so
+                // a tree without empty nodes has no more nodes than there
+                // are distinct timestamps in the window.
+                let window_bounds =
+                    watermark.apply(|wm: &u64| (wm.saturating_sub(1000), u64::max_value()));
+                let partitioned_window = input_by_time
+                    .window(&window_bounds)
+                    .map_index(|(ts, (partition, val))| (*partition, (*ts, *val)))
+                    .shard();
+                let tree = partitioned_window
+                    .partitioned_tree_aggregate::<u64, i64, _>(aggregator.clone())
+                    .integrate_trace();
+
+                partitioned_window
+                    .integrate_trace()
+                    .apply2(&tree, |window, tree| {
+                        let mut timestamps = 0;
+                        let mut cursor = window.cursor();
+                        while cursor.key_valid() {
+                            let mut last_ts = None;
+                            while cursor.val_valid() {
+                                let ts = cursor.val().0;
+                                if cursor.weight() != 0 && last_ts != Some(ts) {
+                                    timestamps += 1;
+                                    last_ts = Some(ts);
+                                }
+                                cursor.step_val();
+                            }
+                            cursor.step_key();
+                        }
+
+                        let mut nodes = 0;
+                        let mut cursor = tree.cursor();
+                        while cursor.key_valid() {
+                            while cursor.val_valid() {
+                                if cursor.weight() != 0 {
+                                    nodes += 1;
+                                }
+                                cursor.step_val();
+                            }
+                            cursor.step_key();
+                        }
+
+                        assert!(
+                            nodes <= timestamps,
+                            "nodes: {nodes}, timestamps: {timestamps}"
+                        );
+                    });
+            }
+
             expected_500_500.apply2(&output_500_500_watermark, |expected, actual| {
                 assert_eq!(expected, actual)
             });
@@ -804,81 +1139,222 @@ mod test {
 
             input_handle
         })
-        .unwrap()
     }
 
     #[test]
     fn test_partitioned_over_range_2() {
-        let (mut circuit, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
+        let (mut tester, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
 
-        circuit.step().unwrap();
+        tester.step();
 
-        input.append(&mut vec![(2, ((110271, 100), 1))]);
-        circuit.step().unwrap();
+        tester.push(&mut input, vec![(2, ((110271, 100), 1))]);
+        tester.step();
 
-        input.append(&mut vec![(2, ((0, 100), 1))]);
-        circuit.step().unwrap();
+        tester.push(&mut input, vec![(2, ((0, 100), 1))]);
+        tester.step();
 
-        circuit.kill().unwrap();
+        tester.kill();
     }
 
     #[test]
     fn test_partitioned_over_range() {
-        let (mut circuit, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
-
-        circuit.step().unwrap();
-
-        input.append(&mut vec![
-            (0, ((1, 100), 1)),
-            (0, ((10, 100), 1)),
-            (0, ((20, 100), 1)),
-            (0, ((30, 100), 1)),
-        ]);
-        circuit.step().unwrap();
-
-        input.append(&mut vec![
-            (0, ((5, 100), 1)),
-            (0, ((15, 100), 1)),
-            (0, ((25, 100), 1)),
-            (0, ((35, 100), 1)),
-        ]);
-        circuit.step().unwrap();
-
-        input.append(&mut vec![
-            (0, ((1, 100), -1)),
-            (0, ((10, 100), -1)),
-            (0, ((20, 100), -1)),
-            (0, ((30, 100), -1)),
-        ]);
-        input.append(&mut vec![
-            (1, ((1, 100), 1)),
-            (1, ((1000, 100), 1)),
-            (1, ((2000, 100), 1)),
-            (1, ((3000, 100), 1)),
-        ]);
-        circuit.step().unwrap();
-
-        circuit.kill().unwrap();
-    }
-
-    use proptest::{collection, prelude::*};
-
-    type InputTuple = (u64, ((u64, i64), isize));
-    type InputBatch = Vec<InputTuple>;
-
-    fn input_tuple(partitions: u64, window: (u64, u64)) -> impl Strategy<Value = InputTuple> {
-        (
-            (0..partitions),
-            ((window.0..window.1, 100..101i64), 1..2isize),
-        )
+        let (mut tester, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
+
+        tester.step();
+
+        tester.push(
+            &mut input,
+            vec![
+                (0, ((1, 100), 1)),
+                (0, ((10, 100), 1)),
+                (0, ((20, 100), 1)),
+                (0, ((30, 100), 1)),
+            ],
+        );
+        tester.step();
+
+        tester.push(
+            &mut input,
+            vec![
+                (0, ((5, 100), 1)),
+                (0, ((15, 100), 1)),
+                (0, ((25, 100), 1)),
+                (0, ((35, 100), 1)),
+            ],
+        );
+        tester.step();
+
+        tester.push(
+            &mut input,
+            vec![
+                (0, ((1, 100), -1)),
+                (0, ((10, 100), -1)),
+                (0, ((20, 100), -1)),
+                (0, ((30, 100), -1)),
+            ],
+        );
+        tester.push(
+            &mut input,
+            vec![
+                (1, ((1, 100), 1)),
+                (1, ((1000, 100), 1)),
+                (1, ((2000, 100), 1)),
+                (1, ((3000, 100), 1)),
+            ],
+        );
+        tester.step();
+
+        tester.kill();
     }
 
+    #[test]
+    fn test_partitioned_rolling_count_distinct() {
+        let (mut tester, (mut input, output)) = CircuitTester::new(1, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let range = RelRange::new(RelOffset::Before(3), RelOffset::Before(0));
+            let output = TestOutput::new(&input_stream.partitioned_rolling_count_distinct(range));
+
+            (input_handle, output)
+        });
+
+        tester.push(
+            &mut input,
+            vec![
+                (0, ((1, 5), 1)),
+                (0, ((2, 5), 1)),
+                (0, ((3, 7), 1)),
+                (0, ((10, 5), 1)),
+                (1, ((3, 1), 1)),
+                (1, ((3, 2), 1)),
+            ],
+        );
+        tester.step();
+        tester.expect_integrated(
+            &output,
+            vec![
+                ((0, (1, Some(1))), 1),
+                ((0, (2, Some(1))), 1),
+                ((0, (3, Some(2))), 1),
+                ((0, (10, Some(1))), 1),
+                ((1, (3, Some(2))), 2),
+            ],
+        );
+
+        tester.push(&mut input, vec![(0, ((3, 7), -1)), (0, ((4, 8), 1))]);
+        tester.step();
+        tester.expect_integrated(
+            &output,
+            vec![
+                ((0, (1, Some(1))), 1),
+                ((0, (2, Some(1))), 1),
+                ((0, (4, Some(2))), 1),
+                ((0, (10, Some(1))), 1),
+                ((1, (3, Some(2))), 2),
+            ],
+        );
+
+        // Value 5 disappears from the window of timestamp 4.
+        tester.push(&mut input, vec![(0, ((1, 5), -1)), (0, ((2, 5), -1))]);
+        tester.step();
+        tester.expect_integrated(
+            &output,
+            vec![
+                ((0, (4, Some(1))), 1),
+                ((0, (10, Some(1))), 1),
+                ((1, (3, Some(2))), 2),
+            ],
+        );
+
+        // Value 5 re-appears in the window of timestamp 4.
+        tester.push(&mut input, vec![(0, ((2, 5), 1))]);
+        tester.step();
+        tester.expect_integrated(
+            &output,
+            vec![
+                ((0, (2, Some(1))), 1),
+                ((0, (4, Some(2))), 1),
+                ((0, (10, Some(1))), 1),
+                ((1, (3, Some(2))), 2),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_partitioned_rolling_sum_f64() {
+        let (mut tester, (mut input, output)) = CircuitTester::new(1, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, F64), isize>();
+
+            let range = RelRange::new(RelOffset::Before(1), RelOffset::Before(0));
+            let output = TestOutput::new(&input_stream.partitioned_rolling_aggregate_linear(
+                |v| *v,
+                |sum| sum,
+                range,
+            ));
+
+            (input_handle, output)
+        });
+
+        let f = F64::new;
+
+        tester.push(
+            &mut input,
+            vec![
+                (0, ((1, f(1.5)), 1)),
+                (0, ((2, f(-2.25)), 1)),
+                (0, ((3, f(0.75)), 2)),
+                (1, ((1, f(-0.5)), 1)),
+                (1, ((2, f(0.5)), 1)),
+            ],
+        );
+        tester.step();
+        tester.expect_integrated(
+            &output,
+            vec![
+                ((0, (1, Some(f(1.5)))), 1),
+                ((0, (2, Some(f(-0.75)))), 1),
+                ((0, (3, Some(f(-0.75)))), 2),
+                ((1, (1, Some(f(-0.5)))), 1),
+                ((1, (2, Some(f(0.0)))), 1),
+            ],
+        );
+
+        tester.push(
+            &mut input,
+            vec![(0, ((2, f(-2.25)), -1)), (1, ((2, f(-0.0)), 1))],
+        );
+        tester.step();
+        tester.expect_integrated(
+            &output,
+            vec![
+                ((0, (1, Some(f(1.5)))), 1),
+                ((0, (3, Some(f(1.5)))), 2),
+                ((1, (1, Some(f(-0.5)))), 1),
+                ((1, (2, Some(f(0.0)))), 2),
+            ],
+        );
+
+        tester.kill();
+    }
+
+    use crate::strategies::{indexed_updates, trace};
+    use proptest::prelude::*;
+
+    type InputBatch = Vec<(u64, ((u64, i64), isize))>;
+
     fn input_batch(
         partitions: u64,
         window: (u64, u64),
         max_batch_size: usize,
     ) -> impl Strategy<Value = InputBatch> {
-        collection::vec(input_tuple(partitions, window), 0..max_batch_size)
+        indexed_updates(
+            0..partitions,
+            (window.0..window.1, Just(100i64)),
+            1..=1,
+            0..max_batch_size,
+        )
     }
 
     fn input_trace(
@@ -887,7 +1363,7 @@ mod test {
         max_batch_size: usize,
         max_batches: usize,
     ) -> impl Strategy<Value = Vec<InputBatch>> {
-        collection::vec(
+        trace(
             input_batch(partitions, (0, epoch), max_batch_size),
             0..max_batches,
         )
@@ -919,14 +1395,14 @@ mod test {
         #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
         fn proptest_partitioned_rolling_aggregate_quasi_monotone(trace in input_trace_quasi_monotone(5, 10_000, 2_000, 20, 200)) {
             // 10_000 is an empirically established bound: without GC this test needs >10KB.
-            let (mut circuit, mut input) = partition_rolling_aggregate_circuit(10000, Some(10_000));
+            let (mut tester, mut input) = partition_rolling_aggregate_circuit(10000, Some(10_000));
 
-            for mut batch in trace {
-                input.append(&mut batch);
-                circuit.step().unwrap();
+            for batch in trace {
+                tester.push(&mut input, batch);
+                tester.step();
             }
 
-            circuit.kill().unwrap();
+            tester.kill();
         }
     }
 
@@ -934,27 +1410,27 @@ mod test {
         #[test]
         #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
         fn proptest_partitioned_over_range_sparse(trace in input_trace(5, 1_000_000, 20, 20)) {
-            let (mut circuit, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
+            let (mut tester, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
 
-            for mut batch in trace {
-                input.append(&mut batch);
-                circuit.step().unwrap();
+            for batch in trace {
+                tester.push(&mut input, batch);
+                tester.step();
             }
 
-            circuit.kill().unwrap();
+            tester.kill();
         }
 
         #[test]
         #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
         fn proptest_partitioned_over_range_dense(trace in input_trace(5, 1_000, 50, 20)) {
-            let (mut circuit, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
+            let (mut tester, mut input) = partition_rolling_aggregate_circuit(u64::max_value(), None);
 
-            for mut batch in trace {
-                input.append(&mut batch);
-                circuit.step().unwrap();
+            for batch in trace {
+                tester.push(&mut input, batch);
+                tester.step();
             }
 
-            circuit.kill().unwrap();
+            tester.kill();
         }
     }
 }
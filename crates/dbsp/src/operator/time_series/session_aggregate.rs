@@ -0,0 +1,584 @@
+use crate::{
+    algebra::{HasOne, HasZero, IndexedZSet, Semigroup, ZRingValue},
+    circuit::{
+        operator_traits::{Operator, TernaryOperator},
+        OwnershipPreference, Scope,
+    },
+    operator::{
+        time_series::{
+            range::{Range, RangeCursor, Ranges},
+            OrdPartitionedIndexedZSet, PartitionCursor, PartitionedBatchReader,
+            PartitionedIndexedZSet,
+        },
+        trace::{DelayedTraceId, IntegrateTraceId, TraceBounds, UntimedTraceAppend, Z1Trace},
+        Aggregator, FilterMap,
+    },
+    trace::{cursor::CursorGroup, Builder, Cursor, Spine},
+    Circuit, DBData, OrdIndexedZSet, RootCircuit, Stream,
+};
+use num::PrimInt;
+use std::{
+    borrow::Cow,
+    cmp::{max, min},
+    marker::PhantomData,
+    ops::Neg,
+};
+
+/// Stream of session aggregates produced by
+/// [`partitioned_session_aggregate`](`Stream::partitioned_session_aggregate`).
+///
+/// Each record has the form `(partition, (session_start, (session_end,
+/// aggregate)))`.
+pub type OrdPartitionedSessionStream<PK, TS, A, R> =
+    Stream<RootCircuit, OrdPartitionedIndexedZSet<PK, TS, (TS, A), R>>;
+
+// Sessions indexed by the last timestamp in the session:
+// `(partition, (session_end, (session_start, aggregate)))`.  This is the
+// layout used internally by the operator, as it allows finding all sessions
+// that overlap with a time range using `seek_key`.
+type OrdPartitionedSessionsByEnd<PK, TS, A, R> = OrdIndexedZSet<PK, (TS, (TS, A)), R>;
+
+impl<B> Stream<RootCircuit, B> {
+    /// Session window aggregate of a partitioned stream.
+    ///
+    /// Groups values within each partition of the input stream into
+    /// sessions, where two values belong to the same session if their
+    /// timestamps are separated by no more than `gap` from each other or
+    /// from other values in the session, i.e., a session ends when there
+    /// are no new values for more than `gap` time units.  Outputs one
+    /// record per session containing its first and last timestamp and the
+    /// value of `aggregator` over all values in the session:
+    /// `(partition, (session_start, (session_end, aggregate)))`.
+    ///
+    /// This operator is incremental: an out-of-order value that falls
+    /// between two existing sessions can merge them into one session, while
+    /// deleting a value can split a session in two.  In both cases the
+    /// operator retracts the superseded session records and outputs new
+    /// ones.
+    ///
+    /// # Aggregation
+    ///
+    /// The aggregate for a session is computed by aggregating values with
+    /// the same timestamp using `aggregator` and then combining per-timestamp
+    /// aggregates using `Agg::Semigroup`, the same way
+    /// [`partitioned_rolling_aggregate`](`Stream::partitioned_rolling_aggregate`)
+    /// does.  Recomputing an affected session requires scanning all of its
+    /// values, so this operator works best when sessions are short.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data partitioned by partition key and indexed by
+    ///   time within each partition.
+    /// * `gap` - maximal distance between consecutive timestamps in a session.
+    /// * `aggregator` - aggregator used to summarize values within a session.
+    pub fn partitioned_session_aggregate<TS, V, Agg>(
+        &self,
+        gap: TS,
+        aggregator: Agg,
+    ) -> OrdPartitionedSessionStream<B::Key, TS, Agg::Output, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        // ```
+        //                  ┌───────────────┐   input_trace
+        //      ┌──────────►│integrate_trace├──────────────┐                               sessions
+        //      │           └───────────────┘              │                            ┌──────────────────────────►
+        //      │                                          ▼                            │
+        // self │                                  ┌───────────────────────────┐        │  ┌──────────────────┐ output_trace
+        // ─────┴─────────────────────────────────►│PartitionedSessionAggregate├────────┴──┤UntimedTraceAppend├────────┐
+        //                                         └───────────────────────────┘           └──────────────────┘        │
+        //                                                         ▲                          ▲                        │
+        //                                                         │                        ┌─┴──┐                     │
+        //                                                         └────────────────────────┤Z^-1│◄────────────────────┘
+        //                                                            output_trace_delayed  └────┘
+        // ```
+        self.circuit().region("partitioned_session_aggregate", || {
+            let circuit = self.circuit();
+            let stream = self.shard();
+
+            let input_trace = stream.integrate_trace();
+
+            let bounds = <TraceBounds<B::Key, (TS, (TS, Agg::Output))>>::unbounded();
+            let (output_trace_delayed, z1feedback) = circuit.add_feedback(
+                <Z1Trace<Spine<_>>>::new(false, circuit.root_scope(), bounds.clone()),
+            );
+            output_trace_delayed.mark_sharded();
+
+            let sessions: Stream<_, OrdPartitionedSessionsByEnd<_, _, _, _>> = circuit
+                .add_ternary_operator(
+                    <PartitionedSessionAggregate<TS, V, Agg>>::new(gap, aggregator),
+                    &stream,
+                    &input_trace,
+                    &output_trace_delayed,
+                )
+                .mark_sharded();
+
+            let output_trace = circuit
+                .add_binary_operator_with_preference(
+                    <UntimedTraceAppend<Spine<_>>>::new(),
+                    (
+                        &output_trace_delayed,
+                        OwnershipPreference::STRONGLY_PREFER_OWNED,
+                    ),
+                    (&sessions, OwnershipPreference::PREFER_OWNED),
+                )
+                .mark_sharded();
+
+            z1feedback
+                .connect_with_preference(&output_trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+
+            circuit.cache_insert(
+                DelayedTraceId::new(output_trace.origin_node_id().clone()),
+                output_trace_delayed,
+            );
+            circuit.cache_insert(
+                IntegrateTraceId::new(sessions.origin_node_id().clone()),
+                (output_trace, bounds),
+            );
+
+            // Re-index sessions by start time.
+            sessions
+                .map_index(|(partition, (end, (start, agg)))| {
+                    (partition.clone(), (*start, (*end, agg.clone())))
+                })
+                .mark_sharded()
+        })
+    }
+}
+
+/// Ternary operator that implements the internals of
+/// `partitioned_session_aggregate`.
+///
+/// * Input stream 1: updates to the time series.  Used to identify affected
+///   partitions and times.
+/// * Input stream 2: trace containing the accumulated time series data.
+/// * Input stream 3: trace of previously produced sessions indexed by session
+///   end.  Used to find affected sessions and compute retractions.
+struct PartitionedSessionAggregate<TS, V, Agg> {
+    gap: TS,
+    aggregator: Agg,
+    phantom: PhantomData<V>,
+}
+
+impl<TS, V, Agg> PartitionedSessionAggregate<TS, V, Agg> {
+    fn new(gap: TS, aggregator: Agg) -> Self {
+        Self {
+            gap,
+            aggregator,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Groups timestamps in `delta_cursor` into clusters such that
+    /// neighborhoods of radius `gap` of timestamps in different clusters
+    /// don't overlap.
+    ///
+    /// Returns the smallest range that contains each cluster.
+    fn delta_clusters<'a, R, C>(&self, delta_cursor: &mut C) -> Vec<Range<TS>>
+    where
+        C: Cursor<'a, TS, V, (), R>,
+        TS: PrimInt,
+    {
+        let mut clusters: Vec<Range<TS>> = Vec::new();
+
+        while delta_cursor.key_valid() {
+            let ts = *delta_cursor.key();
+
+            match clusters.last_mut() {
+                Some(last) if ts.saturating_sub(self.gap) <= last.to.saturating_add(self.gap) => {
+                    last.to = ts;
+                }
+                _ => clusters.push(Range::new(ts, ts)),
+            }
+            delta_cursor.step_key();
+        }
+
+        clusters
+    }
+}
+
+impl<TS, V, Agg> Operator for PartitionedSessionAggregate<TS, V, Agg>
+where
+    TS: 'static,
+    V: 'static,
+    Agg: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("PartitionedSessionAggregate")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<TS, V, Agg, B, T, OT, O> TernaryOperator<B, T, OT, O>
+    for PartitionedSessionAggregate<TS, V, Agg>
+where
+    TS: DBData + PrimInt,
+    V: DBData,
+    Agg: Aggregator<V, (), B::R>,
+    B: PartitionedBatchReader<TS, V> + Clone,
+    B::R: ZRingValue,
+    T: PartitionedBatchReader<TS, V, Key = B::Key, R = B::R> + Clone,
+    OT: PartitionedBatchReader<TS, (TS, Agg::Output), Key = B::Key, R = B::R> + Clone,
+    O: IndexedZSet<Key = B::Key, Val = (TS, (TS, Agg::Output)), R = B::R>,
+{
+    fn eval<'a>(
+        &mut self,
+        input_delta: Cow<'a, B>,
+        input_trace: Cow<'a, T>,
+        output_trace: Cow<'a, OT>,
+    ) -> O {
+        let mut delta_cursor = input_delta.cursor();
+        let mut output_trace_cursor = output_trace.cursor();
+        let mut input_trace_cursor = input_trace.cursor();
+
+        let mut retraction_builder = O::Builder::new_builder(());
+        let mut insertion_builder = O::Builder::with_capacity((), input_delta.key_count());
+
+        // Iterate over affected partitions.
+        while delta_cursor.key_valid() {
+            let clusters = self.delta_clusters(&mut PartitionCursor::new(&mut delta_cursor));
+            let mut affected_ranges = Ranges::with_capacity(clusters.len());
+
+            // Find existing sessions within `gap` from each cluster of modified
+            // timestamps.  These sessions may be merged with new values or split
+            // by deleted values, so we retract them and recompute sessions
+            // across the range they cover.
+            output_trace_cursor.seek_key(delta_cursor.key());
+            if output_trace_cursor.key_valid() && output_trace_cursor.key() == delta_cursor.key() {
+                let mut sessions_cursor = PartitionCursor::new(&mut output_trace_cursor);
+
+                // A session can be within `gap` from multiple clusters; make
+                // sure we only retract it once.
+                let mut last_retracted: Option<TS> = None;
+
+                for cluster in clusters {
+                    let window = Range::new(
+                        cluster.from.saturating_sub(self.gap),
+                        cluster.to.saturating_add(self.gap),
+                    );
+                    let mut affected_range = cluster;
+
+                    // Sessions are indexed by their end time and don't overlap,
+                    // so sessions that end at or after `window.from` are ordered
+                    // by start time.
+                    sessions_cursor.seek_key(&window.from);
+
+                    'sessions: while sessions_cursor.key_valid() {
+                        let end = *sessions_cursor.key();
+                        let retract = last_retracted.map_or(true, |last| end > last);
+
+                        while sessions_cursor.val_valid() {
+                            let weight = sessions_cursor.weight();
+                            if !weight.is_zero() {
+                                let (start, agg) = sessions_cursor.val();
+                                if *start > window.to {
+                                    break 'sessions;
+                                }
+
+                                affected_range.from = min(affected_range.from, *start);
+                                affected_range.to = max(affected_range.to, end);
+
+                                if retract {
+                                    retraction_builder.push((
+                                        O::item_from(
+                                            delta_cursor.key().clone(),
+                                            (end, (*start, agg.clone())),
+                                        ),
+                                        weight.neg(),
+                                    ));
+                                    last_retracted = Some(end);
+                                }
+                            }
+                            sessions_cursor.step_val();
+                        }
+                        sessions_cursor.step_key();
+                    }
+
+                    affected_ranges.push_monotonic(affected_range);
+                }
+            } else {
+                for cluster in clusters {
+                    affected_ranges.push_monotonic(cluster);
+                }
+            }
+
+            // Recompute sessions in affected ranges.
+            input_trace_cursor.seek_key(delta_cursor.key());
+            if input_trace_cursor.key_valid() && input_trace_cursor.key() == delta_cursor.key() {
+                let mut input_range_cursor = RangeCursor::new(
+                    PartitionCursor::new(&mut input_trace_cursor),
+                    affected_ranges,
+                );
+
+                // Session being assembled: `(start, end, accumulator)`.
+                let mut session: Option<(TS, TS, Agg::Accumulator)> = None;
+
+                while input_range_cursor.key_valid() {
+                    let ts = *input_range_cursor.key();
+
+                    if let Some(acc) = self
+                        .aggregator
+                        .aggregate(&mut CursorGroup::new(&mut input_range_cursor, ()))
+                    {
+                        session = match session.take() {
+                            Some((start, end, session_acc)) if ts - end <= self.gap => {
+                                Some((start, ts, Agg::Semigroup::combine(&session_acc, &acc)))
+                            }
+                            Some((start, end, session_acc)) => {
+                                insertion_builder.push((
+                                    O::item_from(
+                                        delta_cursor.key().clone(),
+                                        (end, (start, self.aggregator.finalize(session_acc))),
+                                    ),
+                                    HasOne::one(),
+                                ));
+                                Some((ts, ts, acc))
+                            }
+                            None => Some((ts, ts, acc)),
+                        };
+                    }
+
+                    input_range_cursor.step_key();
+                }
+
+                if let Some((start, end, session_acc)) = session {
+                    insertion_builder.push((
+                        O::item_from(
+                            delta_cursor.key().clone(),
+                            (end, (start, self.aggregator.finalize(session_acc))),
+                        ),
+                        HasOne::one(),
+                    ));
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        let retractions = retraction_builder.done();
+        let insertions = insertion_builder.done();
+        retractions.add(insertions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        algebra::DefaultSemigroup,
+        operator::Fold,
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, RootCircuit, Runtime, Stream,
+    };
+
+    type DataBatch = OrdIndexedZSet<u64, (u64, i64), isize>;
+    type DataStream = Stream<RootCircuit, DataBatch>;
+    type OutputBatch = OrdIndexedZSet<u64, (u64, (u64, i64)), isize>;
+    type OutputStream = Stream<RootCircuit, OutputBatch>;
+
+    // Reference implementation of `partitioned_session_aggregate` for testing.
+    fn partitioned_session_aggregate_slow(stream: &DataStream, gap: u64) -> OutputStream {
+        stream
+            .gather(0)
+            .integrate()
+            .apply(move |batch: &DataBatch| {
+                let mut tuples = Vec::new();
+
+                let mut cursor = batch.cursor();
+
+                while cursor.key_valid() {
+                    let partition = *cursor.key();
+                    let mut session: Option<(u64, u64, i64)> = None;
+
+                    while cursor.val_valid() {
+                        let (ts, val) = *cursor.val();
+                        let w = cursor.weight() as i64;
+
+                        session = match session {
+                            Some((start, end, agg)) if ts - end <= gap => {
+                                Some((start, ts, agg + val * w))
+                            }
+                            Some((start, end, agg)) => {
+                                tuples.push(((partition, (start, (end, agg))), 1));
+                                Some((ts, ts, val * w))
+                            }
+                            None => Some((ts, ts, val * w)),
+                        };
+                        cursor.step_val();
+                    }
+
+                    if let Some((start, end, agg)) = session {
+                        tuples.push(((partition, (start, (end, agg))), 1));
+                    }
+                    cursor.step_key();
+                }
+
+                OutputBatch::from_tuples((), tuples)
+            })
+    }
+
+    type SessionHandle = CollectionHandle<u64, ((u64, i64), isize)>;
+
+    fn partitioned_session_aggregate_circuit(gaps: &[u64]) -> (DBSPHandle, SessionHandle) {
+        let gaps = gaps.to_vec();
+
+        Runtime::init_circuit(4, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let aggregator = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0i64,
+                |agg: &mut i64, val: &i64, w: isize| *agg += val * (w as i64),
+            );
+
+            for gap in gaps {
+                let expected = partitioned_session_aggregate_slow(&input_stream, gap);
+                let output = input_stream
+                    .partitioned_session_aggregate::<u64, i64, _>(gap, aggregator.clone())
+                    .gather(0)
+                    .integrate();
+                expected.apply2(&output, |expected, actual| assert_eq!(expected, actual));
+            }
+
+            input_handle
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_partitioned_session_aggregate() {
+        let (circuit, mut input) = RootCircuit::build(move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let aggregator = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0i64,
+                |agg: &mut i64, val: &i64, w: isize| *agg += val * (w as i64),
+            );
+
+            let mut expected_outputs = vec![
+                // Two sessions.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (10, (20, 3))), 1),
+                        ((0, (40, (40, 4))), 1),
+                        ((1, (0, (0, 5))), 1),
+                    ],
+                ),
+                // Out-of-order value bridges the gap between two sessions.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (10, (20, 3))), -1),
+                        ((0, (40, (40, 4))), -1),
+                        ((0, (10, (40, 17))), 1),
+                    ],
+                ),
+                // Deleting a value splits the session.
+                OutputBatch::from_tuples(
+                    (),
+                    vec![
+                        ((0, (10, (40, 17))), -1),
+                        ((0, (10, (20, 3))), 1),
+                        ((0, (40, (40, 4))), 1),
+                    ],
+                ),
+                // New value extends the session.
+                OutputBatch::from_tuples((), vec![((1, (0, (0, 5))), -1), ((1, (0, (5, 11))), 1)]),
+            ]
+            .into_iter();
+
+            input_stream
+                .partitioned_session_aggregate::<u64, i64, _>(10, aggregator)
+                .inspect(move |batch| assert_eq!(batch, &expected_outputs.next().unwrap()));
+
+            input_handle
+        })
+        .unwrap();
+
+        input.append(&mut vec![
+            (0, ((10, 1), 1)),
+            (0, ((20, 2), 1)),
+            (0, ((40, 4), 1)),
+            (1, ((0, 5), 1)),
+        ]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(0, ((30, 10), 1))]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(0, ((30, 10), -1))]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(1, ((5, 6), 1))]);
+        circuit.step().unwrap();
+    }
+
+    use proptest::{collection, prelude::*};
+
+    type InputTuple = (u64, ((u64, i64), isize));
+    type InputBatch = Vec<InputTuple>;
+
+    fn input_tuple(partitions: u64, epoch: u64) -> impl Strategy<Value = InputTuple> {
+        ((0..partitions), ((0..epoch, -100..100i64), 1..2isize))
+    }
+
+    // Each step inserts a batch of new values and retracts some of the
+    // previously inserted values, identified by their indexes.
+    fn input_trace(
+        partitions: u64,
+        epoch: u64,
+        max_batch_size: usize,
+        max_batches: usize,
+    ) -> impl Strategy<Value = Vec<(InputBatch, Vec<usize>)>> {
+        collection::vec(
+            (
+                collection::vec(input_tuple(partitions, epoch), 0..max_batch_size),
+                collection::vec(0..max_batch_size * max_batches, 0..max_batch_size / 2),
+            ),
+            0..max_batches,
+        )
+    }
+
+    fn run_trace(trace: Vec<(InputBatch, Vec<usize>)>) {
+        let (mut circuit, mut input) = partitioned_session_aggregate_circuit(&[0, 5, 50]);
+        let mut inserted: Vec<InputTuple> = Vec::new();
+
+        for (mut batch, retractions) in trace {
+            inserted.extend(batch.iter().cloned());
+
+            for index in retractions {
+                if index < inserted.len() {
+                    let (partition, (val, w)) = inserted.swap_remove(index);
+                    batch.push((partition, (val, -w)));
+                }
+            }
+
+            input.append(&mut batch);
+            circuit.step().unwrap();
+        }
+
+        circuit.kill().unwrap();
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_partitioned_session_aggregate_sparse(trace in input_trace(5, 10_000, 20, 20)) {
+            run_trace(trace);
+        }
+
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_partitioned_session_aggregate_dense(trace in input_trace(5, 500, 50, 20)) {
+            run_trace(trace);
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{
+    algebra::{IndexedZSet, ZRingValue},
+    operator::{time_series::PartitionedIndexedZSet, trace::TraceBound, Aggregator, FilterMap},
+    trace::{Batch, BatchReader, Cursor},
+    DBData, OrdIndexedZSet, RootCircuit, Stream,
+};
+use num::PrimInt;
+
+pub type OrdTumblingWindowStream<PK, TS, A, R> =
+    Stream<RootCircuit, OrdIndexedZSet<(PK, TS), A, R>>;
+
+/// Returns the start of the tumbling window of size `window_size` that
+/// contains `ts`.
+fn window_start<TS>(ts: TS, window_size: TS) -> TS
+where
+    TS: PrimInt,
+{
+    let offset = ts % window_size;
+
+    // Round negative timestamps down, not towards zero.
+    if offset < TS::zero() {
+        ts - offset - window_size
+    } else {
+        ts - offset
+    }
+}
+
+impl<B> Stream<RootCircuit, B> {
+    /// Aggregate a partitioned time series over tumbling windows.
+    ///
+    /// Splits the time axis into non-overlapping windows of size
+    /// `window_size`, where window `[start, start + window_size)` starts at a
+    /// multiple of `window_size`, assigns each record in the input stream to
+    /// the window that contains its timestamp, and applies `aggregator` to
+    /// all values in each `(partition, window)` pair.  The output stream
+    /// contains changes to the aggregates indexed by
+    /// `(partition, window_start)`.
+    ///
+    /// This operator is incremental: records that arrive out of order
+    /// update previously computed aggregates.  It uses `watermark` to discard
+    /// the state of closed windows: once the watermark reaches the end of a
+    /// window, no new records are expected to arrive for that window.  The
+    /// operator truncates its input and output traces below the start of the
+    /// window that contains the watermark and ignores records that belong
+    /// to windows before it.  Late records in windows that haven't been
+    /// closed yet are processed normally and produce corrections to the
+    /// aggregates of their windows.
+    ///
+    /// The watermark can be computed, e.g., by the
+    /// [`watermark_monotonic`](`Stream::watermark_monotonic`) operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - time series data partitioned by key of type `B::Key`.
+    /// * `window_size` - size of each window.
+    /// * `aggregator` - aggregator applied to the values in each window.
+    /// * `watermark` - monotonically growing lower bound on timestamps in the
+    ///   input stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_size` is not positive.
+    pub fn tumbling_window_aggregate<TS, V, Agg>(
+        &self,
+        window_size: TS,
+        aggregator: Agg,
+        watermark: &Stream<RootCircuit, TS>,
+    ) -> OrdTumblingWindowStream<B::Key, TS, Agg::Output, B::R>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        Agg: Aggregator<V, (), B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        self.tumbling_window_aggregate_generic::<TS, V, Agg, _>(window_size, aggregator, watermark)
+    }
+
+    /// Like [`Self::tumbling_window_aggregate`], but can return any batch
+    /// type.
+    pub fn tumbling_window_aggregate_generic<TS, V, Agg, O>(
+        &self,
+        window_size: TS,
+        aggregator: Agg,
+        watermark: &Stream<RootCircuit, TS>,
+    ) -> Stream<RootCircuit, O>
+    where
+        B: PartitionedIndexedZSet<TS, V>,
+        B::R: ZRingValue,
+        Agg: Aggregator<V, (), B::R>,
+        O: IndexedZSet<Key = (B::Key, TS), Val = Agg::Output, R = B::R>,
+        TS: DBData + PrimInt,
+        V: DBData,
+    {
+        assert!(
+            window_size > TS::zero(),
+            "tumbling_window_aggregate: window size must be positive"
+        );
+
+        self.circuit().region("tumbling_window_aggregate", || {
+            // Trace bound used to truncate the input and output traces of the
+            // aggregate.  Windows are indexed by `(window_start, partition)`,
+            // so that closed windows form a prefix of the trace.
+            // `(lower, None)` is the smallest key with window start `lower`.
+            let bound: TraceBound<(TS, Option<B::Key>)> = TraceBound::new();
+            let bound_clone = bound.clone();
+
+            // Start of the earliest window that is still open.
+            let lower = watermark.apply(move |wm| {
+                let lower = window_start(*wm, window_size);
+                bound_clone.set((lower, None));
+                lower
+            });
+
+            let windows = self.apply2(&lower, move |batch: &B, lower: &TS| {
+                let mut tuples = Vec::with_capacity(batch.len());
+                let mut cursor = batch.cursor();
+
+                while cursor.key_valid() {
+                    while cursor.val_valid() {
+                        let (ts, val) = cursor.val();
+                        let start = window_start(*ts, window_size);
+
+                        // Drop records that belong to closed windows.
+                        if start >= *lower {
+                            tuples.push((
+                                ((start, Some(cursor.key().clone())), val.clone()),
+                                cursor.weight(),
+                            ));
+                        }
+                        cursor.step_val();
+                    }
+                    cursor.step_key();
+                }
+
+                <OrdIndexedZSet<_, _, _>>::from_tuples((), tuples)
+            });
+
+            windows
+                .aggregate_generic_with_bound::<Agg, OrdIndexedZSet<_, _, B::R>>(aggregator, bound)
+                .map_index_generic(|((start, partition), aggregate)| {
+                    ((partition.clone().unwrap(), *start), aggregate.clone())
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::window_start;
+    use crate::{
+        prelude::*,
+        strategies::{indexed_updates, trace},
+        testing::{CircuitTester, TestOutput},
+    };
+    use proptest::prelude::*;
+
+    type InputHandle = CollectionHandle<u64, ((u64, i64), isize)>;
+    type Output = OrdIndexedZSet<(u64, u64), i64, isize>;
+
+    type Sum = Fold<i64, DefaultSemigroup<i64>, fn(&mut i64, &i64, isize), fn(i64) -> i64>;
+
+    fn sum() -> Sum {
+        Fold::new(0, |agg: &mut i64, val: &i64, w: isize| {
+            *agg += val * (w as i64)
+        })
+    }
+
+    fn tumbling_window_circuit(
+        workers: usize,
+        window_size: u64,
+        lateness: u64,
+    ) -> (CircuitTester, (InputHandle, TestOutput<Output>)) {
+        CircuitTester::new(workers, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+            let watermark = input_stream
+                .map_index(|(partition, (ts, val))| (*ts, (*partition, *val)))
+                .watermark_monotonic(move |ts| ts.saturating_sub(lateness));
+
+            let output = input_stream.tumbling_window_aggregate::<u64, i64, _>(
+                window_size,
+                sum(),
+                &watermark,
+            );
+
+            (input_handle, TestOutput::new(&output))
+        })
+    }
+
+    #[test]
+    fn test_window_start() {
+        assert_eq!(window_start(0u64, 10), 0);
+        assert_eq!(window_start(9u64, 10), 0);
+        assert_eq!(window_start(10u64, 10), 10);
+        assert_eq!(window_start(25i64, 10), 20);
+        assert_eq!(window_start(-1i64, 10), -10);
+        assert_eq!(window_start(-10i64, 10), -10);
+        assert_eq!(window_start(-11i64, 10), -20);
+    }
+
+    #[test]
+    fn test_window_boundaries() {
+        let (mut tester, (mut input, output)) = tumbling_window_circuit(4, 10, u64::max_value());
+
+        tester.push(
+            &mut input,
+            vec![
+                (0, ((0, 1), 1)),
+                (0, ((9, 2), 1)),
+                (0, ((10, 4), 1)),
+                (0, ((19, 8), 1)),
+                (0, ((20, 16), 1)),
+                (1, ((9, 32), 1)),
+                (1, ((10, 64), 1)),
+            ],
+        );
+        tester.step();
+        tester.expect_delta(
+            &output,
+            vec![
+                (((0, 0), 3), 1),
+                (((0, 10), 12), 1),
+                (((0, 20), 16), 1),
+                (((1, 0), 32), 1),
+                (((1, 10), 64), 1),
+            ],
+        );
+
+        tester.push(&mut input, vec![(0, ((9, 2), -1)), (1, ((29, 1), 2))]);
+        tester.step();
+        tester.expect_delta(
+            &output,
+            vec![(((0, 0), 3), -1), (((0, 0), 1), 1), (((1, 20), 2), 1)],
+        );
+
+        tester.push(&mut input, vec![(0, ((0, 1), -1))]);
+        tester.step();
+        tester.expect_delta(&output, vec![(((0, 0), 1), -1)]);
+        tester.expect_integrated(
+            &output,
+            vec![
+                (((0, 10), 12), 1),
+                (((0, 20), 16), 1),
+                (((1, 0), 32), 1),
+                (((1, 10), 64), 1),
+                (((1, 20), 2), 1),
+            ],
+        );
+
+        tester.kill();
+    }
+
+    #[test]
+    fn test_late_data() {
+        let (mut tester, (mut input, output)) = tumbling_window_circuit(4, 10, 10);
+
+        tester.push(&mut input, vec![(0, ((5, 1), 1))]);
+        tester.step();
+        tester.expect_delta(&output, vec![(((0, 0), 1), 1)]);
+
+        // Watermark moves to 15, closing window `[0, 10)`.
+        tester.push(&mut input, vec![(0, ((25, 1), 1))]);
+        tester.step();
+        tester.expect_delta(&output, vec![(((0, 20), 1), 1)]);
+
+        // Late records within the lateness bound.
+        tester.push(&mut input, vec![(0, ((12, 2), 1)), (0, ((15, 3), 1))]);
+        tester.step();
+        tester.expect_delta(&output, vec![(((0, 10), 5), 1)]);
+
+        // Records in the closed window are ignored.
+        tester.push(&mut input, vec![(0, ((7, 1), 1)), (0, ((5, 1), -1))]);
+        tester.step();
+        tester.expect_delta(&output, vec![]);
+
+        // Late retraction within the lateness bound produces a correction.
+        tester.push(&mut input, vec![(0, ((12, 2), -1))]);
+        tester.step();
+        tester.expect_delta(&output, vec![(((0, 10), 5), -1), (((0, 10), 3), 1)]);
+        tester.expect_integrated(
+            &output,
+            vec![(((0, 0), 1), 1), (((0, 10), 3), 1), (((0, 20), 1), 1)],
+        );
+
+        tester.kill();
+    }
+
+    type InputBatch = Vec<(u64, ((u64, i64), isize))>;
+
+    fn input_trace_quasi_monotone(
+        window_size: u64,
+        window_step: u64,
+        batch_size: usize,
+        batches: usize,
+    ) -> impl Strategy<Value = Vec<InputBatch>> {
+        (0..batches)
+            .map(|i| {
+                indexed_updates(
+                    0..5u64,
+                    (
+                        i as u64 * window_step..i as u64 * window_step + window_size,
+                        -100..100i64,
+                    ),
+                    -1..=1,
+                    batch_size,
+                )
+                .boxed()
+            })
+            .collect::<Vec<_>>()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(5))]
+
+        // No records are dropped, since `lateness` exceeds the range of
+        // timestamps in each batch, so the output must match aggregating
+        // the input by window without a watermark.
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_tumbling_window_aggregate(trace in trace(indexed_updates(0..5u64, (0..1_000u64, -100..100i64), -2..=2, 0..20), 0..20)) {
+            let (mut tester, mut input) = CircuitTester::new(4, |circuit| {
+                let (input_stream, input_handle) =
+                    circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+                let watermark = input_stream
+                    .map_index(|(partition, (ts, val))| (*ts, (*partition, *val)))
+                    .watermark_monotonic(|ts| ts.saturating_sub(1_000));
+
+                let expected = input_stream
+                    .map_index(|(partition, (ts, val))| ((*partition, ts - ts % 100), *val))
+                    .aggregate(sum())
+                    .gather(0)
+                    .integrate();
+                let actual = input_stream
+                    .tumbling_window_aggregate::<u64, i64, _>(100, sum(), &watermark)
+                    .gather(0)
+                    .integrate();
+                expected.apply2(&actual, |expected, actual| assert_eq!(expected, actual));
+
+                input_handle
+            });
+
+            for batch in trace {
+                tester.push(&mut input, batch);
+                tester.step();
+            }
+
+            tester.kill();
+        }
+
+        // The state of the operator must not grow with the number of
+        // batches, since the watermark closes old windows.
+        #[test]
+        #[cfg_attr(feature = "persistence", ignore = "takes a long time?")]
+        fn proptest_tumbling_window_aggregate_quasi_monotone(trace in input_trace_quasi_monotone(10_000, 2_000, 50, 200)) {
+            let (mut tester, mut input) = CircuitTester::new(4, |circuit| {
+                let (input_stream, input_handle) =
+                    circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+                let watermark = input_stream
+                    .map_index(|(partition, (ts, val))| (*ts, (*partition, *val)))
+                    .watermark_monotonic(|ts| ts.saturating_sub(10_000));
+
+                input_stream
+                    .tumbling_window_aggregate_generic::<u64, i64, _, Output>(1_000, sum(), &watermark)
+                    .output();
+
+                input_handle
+            });
+
+            let mut used_bytes = Vec::with_capacity(trace.len());
+            for batch in trace {
+                tester.push(&mut input, batch);
+                tester.step();
+                used_bytes.push(tester.dbsp().used_bytes().unwrap());
+            }
+
+            // Compare the largest state in the first and the last quarter of the
+            // trace, skipping the first few batches, while the set of open
+            // windows is still growing.
+            let early = used_bytes[10..50].iter().max().unwrap();
+            let late = used_bytes[150..].iter().max().unwrap();
+            prop_assert!(late <= &(early * 2), "early: {early}, late: {late}");
+
+            tester.kill();
+        }
+    }
+}
@@ -3,8 +3,9 @@ use crate::{
     trace::{cursor::Cursor, BatchReader},
     Circuit, NumEntries, RootCircuit, Runtime, Stream,
 };
+use num::PrimInt;
 use size_of::SizeOf;
-use std::{cmp::max, panic::Location};
+use std::{cmp::max, panic::Location, time::Duration};
 
 impl<B> Stream<RootCircuit, B>
 where
@@ -71,11 +72,154 @@ where
             local_watermark
         }
     }
+
+    /// Compute the watermark of a time series, advancing it based on
+    /// processing time when the stream goes idle.
+    ///
+    /// Like [`watermark_monotonic`](`Self::watermark_monotonic`), this
+    /// operator computes the watermark as the largest event time observed in
+    /// the stream, extracted from each record by the `extractor` function,
+    /// minus `lateness`.  Such a watermark stops advancing when the stream
+    /// stops receiving data, which in turn prevents operators that use it to
+    /// garbage collect old state.  This operator additionally tracks the time
+    /// when the last input batch arrived.  If no inputs arrive for the
+    /// `idle` time interval, it advances the watermark to `now - lateness`,
+    /// where `now` is the current processing time.  The resulting watermark
+    /// never regresses.
+    ///
+    /// Like `watermark_monotonic`, this method assumes that `extractor` is
+    /// monotonic in the key of the input batch and only applies it to the
+    /// last key in each batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `extractor` - extracts event time from a key.
+    /// * `lateness` - maximal lateness of input records relative to the
+    ///   largest event time or, when the stream is idle, to the current
+    ///   processing time.
+    /// * `idle` - time interval without inputs after which the stream is
+    ///   considered idle.
+    /// * `clock` - returns the current processing time in milliseconds.  Event
+    ///   time and `lateness` must be measured in the same units.  The clock is
+    ///   evaluated once at circuit construction time and once per clock cycle.
+    ///   It is injectable to make this operator testable.
+    ///
+    /// When running with multiple workers, the stream is considered idle when
+    /// none of the workers has received inputs for the `idle` interval.
+    #[track_caller]
+    pub fn watermark_with_idle_timeout<E, TS, C>(
+        &self,
+        extractor: E,
+        lateness: TS,
+        idle: Duration,
+        clock: C,
+    ) -> Stream<RootCircuit, TS>
+    where
+        E: Fn(&B::Key) -> TS + 'static,
+        TS: PrimInt + SizeOf + NumEntries + Send + 'static,
+        C: Fn() -> u64 + 'static,
+    {
+        let idle = u64::try_from(idle.as_millis()).unwrap_or(u64::MAX);
+        let start = clock();
+
+        // Each worker tracks a triple `(event_watermark, last_input, now)`,
+        // where `event_watermark` is the watermark derived from event time,
+        // `last_input` is the processing time when the worker last received
+        // input and `now` is the processing time at the current clock cycle.
+        let local_state = self.stream_fold(
+            (TS::min_value(), start, start),
+            move |(event_watermark, last_input, _now), batch| {
+                let now = clock();
+                let mut cursor = batch.cursor();
+                match cursor.last_key() {
+                    Some(key) => (
+                        max(event_watermark, extractor(key).saturating_sub(lateness)),
+                        now,
+                        now,
+                    ),
+                    None => (event_watermark, last_input, now),
+                }
+            },
+        );
+
+        let global_state = if let Some(runtime) = Runtime::runtime() {
+            let num_workers = runtime.num_workers();
+            if num_workers == 1 {
+                local_state
+            } else {
+                // Compute element-wise max of local states across all workers,
+                // so that all workers observe the same watermark.
+                //
+                // The receiver combines values starting from
+                // `Default::default()`.  We start from `None` rather than
+                // `TS::default()` so that negative watermarks don't get
+                // clamped to `0`.
+                let (sender, receiver) = new_exchange_operators(
+                    &runtime,
+                    Runtime::worker_index(),
+                    Some(Location::caller()),
+                    move |state: (TS, u64, u64), states: &mut Vec<(TS, u64, u64)>| {
+                        for _ in 0..num_workers {
+                            states.push(state);
+                        }
+                    },
+                    |result: &mut Option<(TS, u64, u64)>, state: (TS, u64, u64)| {
+                        *result = Some(match *result {
+                            None => state,
+                            Some(old) => (
+                                max(old.0, state.0),
+                                max(old.1, state.1),
+                                max(old.2, state.2),
+                            ),
+                        });
+                    },
+                );
+
+                self.circuit()
+                    .add_exchange(sender, receiver, &local_state)
+                    .apply(move |state: &Option<(TS, u64, u64)>| {
+                        state.unwrap_or((TS::min_value(), start, start))
+                    })
+            }
+        } else {
+            local_state
+        };
+
+        global_state.stream_fold(
+            TS::min_value(),
+            move |watermark, &(event_watermark, last_input, now)| {
+                let watermark = max(watermark, event_watermark);
+
+                if now.saturating_sub(last_input) >= idle {
+                    let now: TS = num::NumCast::from(now).unwrap_or_else(TS::max_value);
+                    max(watermark, now.saturating_sub(lateness))
+                } else {
+                    watermark
+                }
+            },
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Runtime;
+    use crate::{
+        algebra::DefaultSemigroup,
+        operator::{
+            time_series::{RelOffset, RelRange},
+            trace::TraceBound,
+            Fold,
+        },
+        trace::{BatchReader, Trace},
+        Runtime,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
 
     fn test_watermark_monotonic(workers: usize) {
         let mut expected_watermarks = vec![115, 115, 125, 145].into_iter();
@@ -117,4 +261,181 @@ mod tests {
     fn test_watermark_monotonic4() {
         test_watermark_monotonic(4);
     }
+
+    fn test_watermark_with_idle_timeout(workers: usize) {
+        let clock = Arc::new(AtomicU64::new(1000));
+        let clock_clone = clock.clone();
+
+        let mut expected_watermarks =
+            vec![100, 100, 1590, 1590, 1590, 2290, 2290, 2390].into_iter();
+
+        let (mut dbsp, mut input_handle) = Runtime::init_circuit(workers, move |circuit| {
+            let (stream, handle) = circuit.add_input_zset::<u64, isize>();
+            stream
+                .watermark_with_idle_timeout(
+                    |ts| *ts,
+                    10,
+                    Duration::from_millis(500),
+                    move || clock_clone.load(Ordering::Acquire),
+                )
+                .inspect(move |watermark| {
+                    if Runtime::worker_index() == 0 {
+                        assert_eq!(watermark, &expected_watermarks.next().unwrap());
+                    }
+                });
+            handle
+        })
+        .unwrap();
+
+        // Event time watermark.
+        input_handle.append(&mut vec![(100, 1), (110, 1), (50, 1)]);
+        dbsp.step().unwrap();
+
+        // No input, but the stream is not idle yet.
+        clock.store(1200, Ordering::Release);
+        dbsp.step().unwrap();
+
+        // Idle: watermark advances to `now - lateness`.
+        clock.store(1600, Ordering::Release);
+        dbsp.step().unwrap();
+
+        // New inputs don't cause the watermark to regress.
+        clock.store(1700, Ordering::Release);
+        input_handle.append(&mut vec![(120, 1)]);
+        dbsp.step().unwrap();
+
+        clock.store(1800, Ordering::Release);
+        dbsp.step().unwrap();
+
+        clock.store(2300, Ordering::Release);
+        dbsp.step().unwrap();
+
+        // Clock going backward doesn't cause the watermark to regress.
+        clock.store(2000, Ordering::Release);
+        dbsp.step().unwrap();
+
+        clock.store(2400, Ordering::Release);
+        dbsp.step().unwrap();
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn test_watermark_with_idle_timeout1() {
+        test_watermark_with_idle_timeout(1);
+    }
+
+    #[test]
+    fn test_watermark_with_idle_timeout4() {
+        test_watermark_with_idle_timeout(4);
+    }
+
+    // Check that the idle timeout releases state in a windowed rolling
+    // aggregate once the input stream goes idle.
+    fn test_idle_timeout_rolling_aggregate(workers: usize) {
+        let clock = Arc::new(AtomicU64::new(0));
+        let clock_clone = clock.clone();
+
+        let (mut dbsp, (input_handle, window_handle, aggregate_handle, bound_handle)) =
+            Runtime::init_circuit(workers, move |circuit| {
+                let (input_stream, input_handle) =
+                    circuit.add_input_indexed_zset::<u64, (u64, i64), isize>();
+
+                let input_by_time =
+                    input_stream.map_index(|(partition, (ts, val))| (*ts, (*partition, *val)));
+
+                let watermark = input_by_time.watermark_with_idle_timeout(
+                    |ts| *ts,
+                    1000,
+                    Duration::from_millis(1000),
+                    move || clock_clone.load(Ordering::Acquire),
+                );
+
+                let bounds = watermark.apply(|watermark| (*watermark, u64::MAX));
+                let window = input_by_time.window(&bounds).integrate();
+
+                let aggregator = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                    0i64,
+                    |agg: &mut i64, val: &i64, w: isize| *agg += val * (w as i64),
+                );
+                let aggregate = input_by_time.partitioned_rolling_aggregate_with_watermark(
+                    &watermark,
+                    |(partition, val)| (*partition, *val),
+                    aggregator,
+                    RelRange::new(RelOffset::Before(50), RelOffset::Before(0)),
+                );
+
+                // Observe the truncation bound of the output trace maintained
+                // by the aggregate operator, which `integrate_trace` returns
+                // from the cache.
+                let max_bound = TraceBound::new();
+                max_bound.set((u64::max_value(), None));
+                let trace_bound = aggregate
+                    .integrate_trace_with_bound(TraceBound::new(), max_bound)
+                    .apply(|trace| trace.lower_value_bound().clone());
+
+                (
+                    input_handle,
+                    window.output(),
+                    aggregate.integrate().output(),
+                    trace_bound.output(),
+                )
+            })
+            .unwrap();
+
+        for ts in 0..100 {
+            input_handle.push(ts % 5, ((ts * 10, 1), 1));
+        }
+        dbsp.step().unwrap();
+
+        let window_size = window_handle.consolidate().len();
+        assert_eq!(window_size, 100);
+        assert_eq!(aggregate_handle.consolidate().len(), 100);
+
+        // The stream is not idle yet: the window doesn't shrink.
+        clock.store(500, Ordering::Release);
+        dbsp.step().unwrap();
+        assert_eq!(window_handle.consolidate().len(), window_size);
+        let initial_bound = bound_handle.take_from_all();
+        assert!(initial_bound.iter().all(|bound| *bound < Some((450, None))));
+
+        // The stream is idle: the watermark advances to `1500 - 1000`,
+        // which drops inputs with timestamps below 500 from the window.
+        clock.store(1500, Ordering::Release);
+        dbsp.step().unwrap();
+        assert_eq!(window_handle.consolidate().len(), 50);
+
+        // Rolling aggregates computed before the idle gap are preserved.
+        assert_eq!(aggregate_handle.consolidate().len(), 100);
+
+        clock.store(3000, Ordering::Release);
+        dbsp.step().unwrap();
+        assert_eq!(window_handle.consolidate().len(), 0);
+
+        // The trace observes the bound set at the previous clock cycle:
+        // `watermark - 50 = 450`, i.e., the output trace of the aggregate
+        // gets truncated even though no new inputs arrived.
+        assert_eq!(
+            bound_handle.take_from_all(),
+            vec![Some((450, None)); workers]
+        );
+
+        dbsp.step().unwrap();
+        assert_eq!(
+            bound_handle.take_from_all(),
+            vec![Some((1950, None)); workers]
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn test_idle_timeout_rolling_aggregate1() {
+        test_idle_timeout_rolling_aggregate(1);
+    }
+
+    #[test]
+    fn test_idle_timeout_rolling_aggregate4() {
+        test_idle_timeout_rolling_aggregate(4);
+    }
 }
@@ -9,7 +9,12 @@ use crate::{
     operator::trace::TraceBound,
     trace::{cursor::Cursor, BatchReader, Spine},
 };
-use std::{borrow::Cow, cmp::max, marker::PhantomData};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
 
 impl<C, B> Stream<C, B>
 where
@@ -29,6 +34,10 @@ where
     /// complete contents of the window can be computed by integrating the
     /// output stream.
     ///
+    /// This is a shorthand for
+    /// [`window_with_bounds`](`Self::window_with_bounds`) with an inclusive
+    /// lower bound and an exclusive upper bound.
+    ///
     /// # Arguments
     ///
     /// * `self` - stream of indexed Z-sets (indexed by time).  The notion of
@@ -52,6 +61,10 @@ where
     /// earlier inputs that fall within the new range, but not the previous
     /// range.
     ///
+    /// # Panics
+    ///
+    /// Panics if `start_time` decreases.
+    ///
     /// # Circuit
     ///
     /// ```text
@@ -73,26 +86,156 @@ where
     ///                      └─────────────────────────────┘
     /// ```
     pub fn window(&self, bounds: &Stream<C, (B::Key, B::Key)>) -> Stream<C, B> {
+        self.window_with_bounds(
+            &bounds.apply(|(start, end)| {
+                (Bound::Included(start.clone()), Bound::Excluded(end.clone()))
+            }),
+        )
+    }
+
+    /// Like [`window`](`Self::window`), but with explicit bounds on both
+    /// ends of the window.
+    ///
+    /// At each clock cycle, `bounds` contains a `(lower, upper)` pair of
+    /// [`Bound`]s, which describe the range of keys that belong to the
+    /// window, e.g., `(Bound::Excluded(a), Bound::Included(b))` describes
+    /// the range `(a..=b]`.  The window is empty if the range doesn't contain
+    /// any keys.
+    ///
+    /// The lower bound must grow monotonically, where `Unbounded` is the
+    /// smallest lower bound and `Excluded(k)` is greater than `Included(k)`.
+    /// This allows the operator to discard input values that fall below the
+    /// lower bound.  The upper bound can move in both directions.  When
+    /// the lower bound advances, the operator retracts values that no longer
+    /// belong to the window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lower bound decreases.  Use
+    /// [`window_with_bounds_non_monotonic`](`Self::window_with_bounds_non_monotonic`)
+    /// to allow the window to move backward.
+    pub fn window_with_bounds(
+        &self,
+        bounds: &Stream<C, (Bound<B::Key>, Bound<B::Key>)>,
+    ) -> Stream<C, B> {
         let bound = TraceBound::new();
         let bound_clone = bound.clone();
-        bounds.apply(move |(lower, _upper)| {
-            bound_clone.set(lower.clone());
+        let mut lower_bound = Bound::Unbounded;
+
+        // Check that the lower bound grows monotonically, and use it to
+        // truncate the trace.
+        let bounds = bounds.apply(move |bounds: &(Bound<B::Key>, Bound<B::Key>)| {
+            let (lower, _upper) = bounds;
+            assert!(
+                cmp_lower_bounds(lower, &lower_bound) != Ordering::Less,
+                "window: lower bound moved backward from {lower_bound:?} to {lower:?}"
+            );
+
+            match lower {
+                Bound::Included(key) | Bound::Excluded(key) => bound_clone.set(key.clone()),
+                Bound::Unbounded => {}
+            }
+            lower_bound = lower.clone();
+
+            bounds.clone()
         });
+
         let trace = self
             .integrate_trace_with_bound(bound, TraceBound::new())
             .delay_trace();
+        self.circuit()
+            .add_ternary_operator(<Window<B>>::new(), &trace, self, &bounds)
+    }
+
+    /// Like [`window_with_bounds`](`Self::window_with_bounds`), but allows
+    /// both bounds of the window to move in either direction.
+    ///
+    /// Since a window that moves backward can include any value received
+    /// earlier, this operator keeps the entire input stream in memory.
+    pub fn window_with_bounds_non_monotonic(
+        &self,
+        bounds: &Stream<C, (Bound<B::Key>, Bound<B::Key>)>,
+    ) -> Stream<C, B> {
+        let trace = self.integrate_trace().delay_trace();
         self.circuit()
             .add_ternary_operator(<Window<B>>::new(), &trace, self, bounds)
     }
 }
 
+/// Compares lower bounds of two ranges.
+fn cmp_lower_bounds<K>(left: &Bound<K>, right: &Bound<K>) -> Ordering
+where
+    K: Ord,
+{
+    match (left, right) {
+        (Bound::Unbounded, Bound::Unbounded) => Ordering::Equal,
+        (Bound::Unbounded, _) => Ordering::Less,
+        (_, Bound::Unbounded) => Ordering::Greater,
+        (Bound::Included(left), Bound::Included(right))
+        | (Bound::Excluded(left), Bound::Excluded(right)) => left.cmp(right),
+        (Bound::Included(left), Bound::Excluded(right)) => left.cmp(right).then(Ordering::Less),
+        (Bound::Excluded(left), Bound::Included(right)) => left.cmp(right).then(Ordering::Greater),
+    }
+}
+
+/// Moves `cursor` to the first key that satisfies `lower`.
+fn seek_lower_bound<'s, K, V, R, C>(cursor: &mut C, lower: Bound<&K>)
+where
+    K: Ord,
+    C: Cursor<'s, K, V, (), R>,
+{
+    match lower {
+        Bound::Included(key) => cursor.seek_key(key),
+        Bound::Excluded(key) => {
+            cursor.seek_key(key);
+            if cursor.key_valid() && cursor.key() == key {
+                cursor.step_key();
+            }
+        }
+        Bound::Unbounded => {}
+    }
+}
+
+/// Applies `f` to all tuples in `cursor` whose keys belong to `range`, but
+/// not to `exclude`.
+fn for_each_in_range<'s, K, V, R, C, F>(
+    cursor: &mut C,
+    range: &(Bound<K>, Bound<K>),
+    exclude: Option<&(Bound<K>, Bound<K>)>,
+    mut f: F,
+) where
+    K: Ord + Clone,
+    C: Cursor<'s, K, V, (), R>,
+    F: FnMut(&K, &V, &R),
+{
+    seek_lower_bound(cursor, range.start_bound());
+
+    while cursor.key_valid() && range.contains(cursor.key()) {
+        if let Some(exclude) = exclude {
+            if exclude.contains(cursor.key()) {
+                // Skip keys in `exclude`.
+                match exclude.end_bound() {
+                    Bound::Included(key) => seek_lower_bound(cursor, Bound::Excluded(key)),
+                    Bound::Excluded(key) => seek_lower_bound(cursor, Bound::Included(key)),
+                    Bound::Unbounded => break,
+                }
+                continue;
+            }
+        }
+
+        let key = cursor.key().clone();
+        cursor.map_values(|val, weight| f(&key, val, weight));
+        cursor.step_key();
+    }
+}
+
 struct Window<B>
 where
     B: IndexedZSet,
 {
     // `None` means we're at the start of a clock epoch, no inputs
     // have been received yet, and window boundaries haven't been set.
-    window: Option<(B::Key, B::Key)>,
+    window: Option<(Bound<B::Key>, Bound<B::Key>)>,
     _phantom: PhantomData<B>,
 }
 
@@ -127,7 +270,7 @@ where
     }
 }
 
-impl<B> TernaryOperator<Spine<B>, B, (B::Key, B::Key), B> for Window<B>
+impl<B> TernaryOperator<Spine<B>, B, (Bound<B::Key>, Bound<B::Key>), B> for Window<B>
 where
     B: IndexedZSet,
     B::R: NegByRef,
@@ -136,29 +279,17 @@ where
     ///   by time.
     /// * `trace` - trace of the input stream up to, but not including current
     ///   clock cycle.
-    /// * `bounds` - window bounds.  The lower bound must grow monotonically.
+    /// * `bounds` - window bounds.
     // TODO: This can be optimized to add tuples in order, so we can use the
-    // builder API to construct the output batch.  This requires processing
-    // regions in order + extra care to iterate over `batch` and `trace` jointly
-    // in region3.
+    // builder API to construct the output batch.  This requires iterating over
+    // `batch` and `trace` jointly.
     fn eval(
         &mut self,
         trace: Cow<'_, Spine<B>>,
         batch: Cow<'_, B>,
-        bounds: Cow<'_, (B::Key, B::Key)>,
+        bounds: Cow<'_, (Bound<B::Key>, Bound<B::Key>)>,
     ) -> B {
-        //           ┌────────────────────────────────────────┐
-        //           │       previous window                  │
-        //           │                                        │             e1
-        // ──────────┴────────────┬───────────────────────────┴──────────────┬─────►
-        //          s0          s1│                          e0              │
-        //                        │         new window                       │
-        //                        └──────────────────────────────────────────┘
-        //  region 1: [s0 .. s1)
-        //  region 2: [s1 .. e0)
-        //  region 3: [e0 .. e1)
-
-        let (start1, end1) = bounds.into_owned();
+        let window = bounds.into_owned();
         let trace = trace.as_ref();
         let batch = batch.as_ref();
 
@@ -166,58 +297,35 @@ where
         // keys in each component below.  For this, we need to extend
         // `Cursor::seek` to return the number of keys skipped over by the search.
         let mut tuples = Vec::new();
-        let mut trace_cursor = trace.cursor();
-        let mut batch_cursor = batch.cursor();
-
-        if let Some((start0, end0)) = &self.window {
-            // Retract tuples in `trace` that slid out of the window (region 1).
-            trace_cursor.seek_key(start0);
-            while trace_cursor.key_valid()
-                && trace_cursor.key() < &start1
-                && trace_cursor.key() < end0
-            {
-                let key = trace_cursor.key().clone();
-                trace_cursor.map_values(|val, weight| {
-                    tuples.push((B::item_from(key.clone(), val.clone()), weight.neg_by_ref()))
-                });
-                trace_cursor.step_key();
-            }
-
-            // If the window shrunk, retract values that dropped off the right end of the
-            // window.
-            if &end1 < end0 {
-                trace_cursor.seek_key(&end1);
-                while trace_cursor.key_valid() && trace_cursor.key() < end0 {
-                    let key = trace_cursor.key().clone();
-                    trace_cursor.map_values(|val, weight| {
-                        tuples.push((B::item_from(key.clone(), val.clone()), weight.neg_by_ref()))
-                    });
-                    trace_cursor.step_key();
-                }
-            }
 
-            // Add tuples in `trace` that slid into the window (region 3).
-            trace_cursor.seek_key(max(end0, &start1));
-            while trace_cursor.key_valid() && trace_cursor.key() < &end1 {
-                let key = trace_cursor.key().clone();
-                trace_cursor.map_values(|val, weight| {
+        if let Some(old_window) = &self.window {
+            // Retract tuples in `trace` that dropped out of the window.
+            for_each_in_range(
+                &mut trace.cursor(),
+                old_window,
+                Some(&window),
+                |key, val, weight| {
+                    tuples.push((B::item_from(key.clone(), val.clone()), weight.neg_by_ref()))
+                },
+            );
+
+            // Add tuples in `trace` that entered the window.
+            for_each_in_range(
+                &mut trace.cursor(),
+                &window,
+                Some(old_window),
+                |key, val, weight| {
                     tuples.push((B::item_from(key.clone(), val.clone()), weight.clone()))
-                });
-                trace_cursor.step_key();
-            }
-        };
+                },
+            );
+        }
 
         // Insert tuples in `batch` that fall within the new window.
-        batch_cursor.seek_key(&start1);
-        while batch_cursor.key_valid() && batch_cursor.key() < &end1 {
-            let key = batch_cursor.key().clone();
-            batch_cursor.map_values(|val, weight| {
-                tuples.push((B::item_from(key.clone(), val.clone()), weight.clone()))
-            });
-            batch_cursor.step_key();
-        }
+        for_each_in_range(&mut batch.cursor(), &window, None, |key, val, weight| {
+            tuples.push((B::item_from(key.clone(), val.clone()), weight.clone()))
+        });
 
-        self.window = Some((start1, end1));
+        self.window = Some(window);
         B::from_tuples((), tuples)
     }
 
@@ -241,10 +349,14 @@ mod test {
     use crate::{
         indexed_zset,
         operator::{trace::TraceBound, Generator},
-        zset, Circuit, OrdIndexedZSet, RootCircuit, Runtime, Stream,
+        trace::{Batch, BatchReader, Cursor},
+        zset, Circuit, DBData, OrdIndexedZSet, RootCircuit, Runtime, Stream,
     };
     use size_of::SizeOf;
-    use std::vec;
+    use std::{
+        ops::{Bound, RangeBounds},
+        vec,
+    };
 
     #[test]
     fn sliding() {
@@ -481,4 +593,152 @@ mod test {
             dbsp.step().unwrap();
         }
     }
+
+    type Bounds<K> = (Bound<K>, Bound<K>);
+
+    /// Feeds `inputs` to the window operator with `windows` bounds, and
+    /// checks that the integral of its output contains all inputs received
+    /// so far that belong to the current window.
+    fn test_window_with_bounds<K>(
+        inputs: Vec<Vec<(K, (u64, isize))>>,
+        windows: Vec<Bounds<K>>,
+        monotonic: bool,
+    ) where
+        K: DBData,
+    {
+        let mut expected_windows = windows.clone().into_iter();
+        let mut windows = windows.into_iter();
+
+        let (circuit, mut input_handle) = RootCircuit::build(move |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<K, u64, isize>();
+            let bounds: Stream<_, Bounds<K>> =
+                circuit.add_source(Generator::new(move || windows.next().unwrap()));
+
+            let window = if monotonic {
+                input.window_with_bounds(&bounds)
+            } else {
+                input.window_with_bounds_non_monotonic(&bounds)
+            };
+
+            window
+                .integrate()
+                .apply2(&input.integrate(), move |window, input| {
+                    let bounds = expected_windows.next().unwrap();
+
+                    let mut tuples = Vec::new();
+                    let mut cursor = input.cursor();
+                    while cursor.key_valid() {
+                        if bounds.contains(cursor.key()) {
+                            while cursor.val_valid() {
+                                tuples
+                                    .push(((cursor.key().clone(), *cursor.val()), cursor.weight()));
+                                cursor.step_val();
+                            }
+                        }
+                        cursor.step_key();
+                    }
+
+                    assert_eq!(window, &OrdIndexedZSet::from_tuples((), tuples));
+                });
+
+            input_handle
+        })
+        .unwrap();
+
+        for mut input in inputs {
+            input_handle.append(&mut input);
+            circuit.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn string_keys() {
+        let s = |s: &str| s.to_string();
+
+        let inputs = vec![
+            vec![
+                (s("apple"), (1, 1)),
+                (s("banana"), (2, 1)),
+                (s("cherry"), (3, 1)),
+                (s("date"), (4, 1)),
+            ],
+            vec![(s("blueberry"), (5, 1)), (s("date"), (6, 1))],
+            // "apricot" arrives after the window moved past it.
+            vec![(s("apricot"), (7, 1)), (s("elderberry"), (8, 1))],
+            vec![(s("fig"), (9, 1)), (s("cherry"), (3, -1))],
+            vec![(s("date"), (4, -1))],
+            vec![],
+            vec![(s("grape"), (10, 1))],
+        ];
+
+        let windows = vec![
+            (Bound::Included(s("b")), Bound::Excluded(s("d"))),
+            (Bound::Included(s("banana")), Bound::Excluded(s("date"))),
+            (Bound::Excluded(s("banana")), Bound::Included(s("date"))),
+            (Bound::Excluded(s("banana")), Bound::Unbounded),
+            (Bound::Excluded(s("cherry")), Bound::Excluded(s("e"))),
+            // Empty window.
+            (Bound::Included(s("e")), Bound::Excluded(s("c"))),
+            (Bound::Included(s("e")), Bound::Unbounded),
+        ];
+
+        test_window_with_bounds(inputs, windows, true);
+    }
+
+    #[test]
+    fn exclusive_upper_bound() {
+        let inputs: Vec<Vec<(u64, (u64, isize))>> = vec![
+            (0..20).map(|k| (k, (k, 1))).collect(),
+            vec![(5, (100, 1)), (10, (100, 1)), (15, (100, 1))],
+            vec![(10, (10, -1))],
+            vec![],
+            vec![(25, (25, 1))],
+            vec![(30, (30, 1))],
+        ];
+
+        let windows = vec![
+            (Bound::Included(0), Bound::Excluded(10)),
+            (Bound::Included(5), Bound::Excluded(15)),
+            (Bound::Excluded(5), Bound::Excluded(15)),
+            (Bound::Excluded(5), Bound::Included(15)),
+            (Bound::Included(20), Bound::Excluded(20)),
+            (Bound::Included(20), Bound::Excluded(30)),
+        ];
+
+        test_window_with_bounds(inputs, windows, true);
+    }
+
+    #[test]
+    fn non_monotonic() {
+        let inputs: Vec<Vec<(u64, (u64, isize))>> = vec![
+            (0..20).map(|k| (k, (k, 1))).collect(),
+            vec![(3, (100, 1))],
+            vec![],
+            vec![(30, (30, 1))],
+            vec![],
+        ];
+
+        let windows = vec![
+            (Bound::Included(10), Bound::Excluded(20)),
+            // Move the window backward.
+            (Bound::Included(0), Bound::Excluded(5)),
+            (Bound::Unbounded, Bound::Unbounded),
+            (Bound::Excluded(15), Bound::Included(30)),
+            (Bound::Included(2), Bound::Included(3)),
+        ];
+
+        test_window_with_bounds(inputs, windows, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "lower bound moved backward")]
+    fn lower_bound_regression() {
+        let inputs: Vec<Vec<(u64, (u64, isize))>> = vec![vec![(1, (1, 1))], vec![]];
+        let windows = vec![
+            (Bound::Excluded(10), Bound::Excluded(20)),
+            (Bound::Included(10), Bound::Excluded(20)),
+        ];
+
+        test_window_with_bounds(inputs, windows, true);
+    }
 }
@@ -0,0 +1,487 @@
+//! Top-k operator over indexed Z-sets.
+
+use crate::{
+    algebra::{HasZero, IndexedZSet, ZRingValue},
+    circuit::{
+        operator_traits::{Operator, TernaryOperator},
+        OwnershipPreference, Scope,
+    },
+    operator::trace::{DelayedTraceId, IntegrateTraceId, TraceBounds, UntimedTraceAppend, Z1Trace},
+    trace::{Batch, BatchReader, Cursor, Spine},
+    Circuit, DBData, OrdIndexedZSet, RootCircuit, Stream,
+};
+use std::{
+    borrow::Cow, cmp::Ordering, collections::BinaryHeap, fmt::Debug, marker::PhantomData, ops::Neg,
+};
+
+impl<B> Stream<RootCircuit, B>
+where
+    B: IndexedZSet,
+{
+    /// Incrementally compute top `k` values for each key in an indexed Z-set
+    /// under a custom ordering.
+    ///
+    /// For each key, orders values with positive weights using the `cmp`
+    /// comparator and outputs the first `k` of them, i.e., the `k` values
+    /// that are smallest according to `cmp`, with their weights.  Values
+    /// with non-positive weights are ignored.  Keys with fewer than `k`
+    /// values output all of their values.
+    ///
+    /// This allows ordering values by a derived expression rather than by
+    /// their `Ord` implementation.  For example, the following comparator
+    /// selects records with the highest `score`, breaking ties by smallest
+    /// `id`:
+    ///
+    /// ```text
+    /// stream.topk_custom(10, |a: &Record, b: &Record| {
+    ///     b.score.cmp(&a.score).then(a.id.cmp(&b.id))
+    /// })
+    /// ```
+    ///
+    /// The operator is incremental: for each key modified by an input
+    /// update, it recomputes the top `k` values of the key and outputs the
+    /// difference between the new and the previous top `k`, retracting
+    /// values that are no longer in the top `k`.
+    ///
+    /// # Comparator requirements
+    ///
+    /// `cmp` must be a total order over values: it must be antisymmetric and
+    /// transitive, and it must only return `Ordering::Equal` for equal
+    /// values.  Otherwise the choice between values that compare equal
+    /// depends on the order in which they are scanned, and the output of
+    /// the operator is not deterministic.  In debug builds, the operator
+    /// checks the first and the last condition on every comparison.
+    pub fn topk_custom<F>(
+        &self,
+        k: usize,
+        cmp: F,
+    ) -> Stream<RootCircuit, OrdIndexedZSet<B::Key, B::Val, B::R>>
+    where
+        B::R: ZRingValue,
+        F: Fn(&B::Val, &B::Val) -> Ordering + Clone + 'static,
+    {
+        self.topk_custom_generic(k, cmp)
+    }
+
+    /// Like [`Self::topk_custom`], but can return any batch type.
+    pub fn topk_custom_generic<F, O>(&self, k: usize, cmp: F) -> Stream<RootCircuit, O>
+    where
+        B::R: ZRingValue,
+        F: Fn(&B::Val, &B::Val) -> Ordering + Clone + 'static,
+        O: IndexedZSet<Key = B::Key, Val = B::Val, R = B::R>,
+    {
+        self.circuit().region("topk_custom", || {
+            // ```
+            //                  ┌───────────────┐   input_trace
+            //      ┌──────────►│integrate_trace├──────────────┐                       output
+            //      │           └───────────────┘              │                    ┌────────────────────────────────────►
+            //      │                                          ▼                    │
+            // self │                                  ┌───────────────────┐        │  ┌──────────────────┐ output_trace
+            // ─────┴─────────────────────────────────►│    TopKCustom     ├────────┴──┤UntimedTraceAppend├────────┐
+            //                                         └───────────────────┘           └──────────────────┘        │
+            //                                                    ▲                       ▲                        │
+            //                                                    │                     ┌─┴──┐                     │
+            //                                                    └─────────────────────┤Z^-1│◄────────────────────┘
+            //                                                     output_trace_delayed └────┘
+            // ```
+            let circuit = self.circuit();
+            let stream = self.shard();
+
+            let input_trace = stream.integrate_trace();
+
+            let bounds = <TraceBounds<O::Key, O::Val>>::unbounded();
+            let (output_trace_delayed, z1feedback) = circuit.add_feedback(
+                <Z1Trace<Spine<O>>>::new(false, circuit.root_scope(), bounds.clone()),
+            );
+            output_trace_delayed.mark_sharded();
+
+            let output = circuit
+                .add_ternary_operator(
+                    TopKCustom::new(k, cmp),
+                    &stream,
+                    &input_trace,
+                    &output_trace_delayed,
+                )
+                .mark_sharded();
+
+            let output_trace = circuit
+                .add_binary_operator_with_preference(
+                    <UntimedTraceAppend<Spine<O>>>::new(),
+                    (
+                        &output_trace_delayed,
+                        OwnershipPreference::STRONGLY_PREFER_OWNED,
+                    ),
+                    (&output, OwnershipPreference::PREFER_OWNED),
+                )
+                .mark_sharded();
+
+            z1feedback
+                .connect_with_preference(&output_trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+
+            circuit.cache_insert(
+                DelayedTraceId::new(output_trace.origin_node_id().clone()),
+                output_trace_delayed,
+            );
+            circuit.cache_insert(
+                IntegrateTraceId::new(output.origin_node_id().clone()),
+                (output_trace, bounds),
+            );
+
+            output
+        })
+    }
+}
+
+/// Compare `left` and `right` using `cmp`, checking that `cmp` behaves like
+/// a total order in debug builds.
+fn compare<V, F>(cmp: &F, left: &V, right: &V) -> Ordering
+where
+    V: Eq + Debug,
+    F: Fn(&V, &V) -> Ordering,
+{
+    let ordering = cmp(left, right);
+
+    debug_assert_eq!(
+        ordering,
+        cmp(right, left).reverse(),
+        "topk_custom: comparator is not antisymmetric on {left:?} and {right:?}"
+    );
+    debug_assert!(
+        ordering != Ordering::Equal || left == right,
+        "topk_custom: comparator is not a total order: distinct values {left:?} and {right:?} compare equal"
+    );
+
+    ordering
+}
+
+/// An entry in the bounded heap used to compute top `k` values.
+///
+/// Entries are ordered by the comparator, so the root of the heap (a
+/// max-heap) is the worst value among the top `k` values seen so far.
+struct HeapEntry<'a, V, R, F> {
+    val: V,
+    weight: R,
+    cmp_func: &'a F,
+}
+
+impl<'a, V, R, F> PartialEq for HeapEntry<'a, V, R, F>
+where
+    V: Eq + Debug,
+    F: Fn(&V, &V) -> Ordering,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a, V, R, F> Eq for HeapEntry<'a, V, R, F>
+where
+    V: Eq + Debug,
+    F: Fn(&V, &V) -> Ordering,
+{
+}
+
+impl<'a, V, R, F> PartialOrd for HeapEntry<'a, V, R, F>
+where
+    V: Eq + Debug,
+    F: Fn(&V, &V) -> Ordering,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, V, R, F> Ord for HeapEntry<'a, V, R, F>
+where
+    V: Eq + Debug,
+    F: Fn(&V, &V) -> Ordering,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(self.cmp_func, &self.val, &other.val)
+    }
+}
+
+/// Ternary operator that implements the internals of `topk_custom`.
+///
+/// * Input stream 1: updates to the input indexed Z-set.  Used to identify
+///   affected keys.
+/// * Input stream 2: trace containing the accumulated input.
+/// * Input stream 3: trace of previously produced outputs.  Used to compute
+///   retractions.
+struct TopKCustom<V, F> {
+    k: usize,
+    cmp: F,
+    phantom: PhantomData<V>,
+}
+
+impl<V, F> TopKCustom<V, F>
+where
+    V: DBData,
+    F: Fn(&V, &V) -> Ordering,
+{
+    fn new(k: usize, cmp: F) -> Self {
+        Self {
+            k,
+            cmp,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Compute top `self.k` values with positive weights under the current
+    /// key of `cursor`.
+    fn topk<'s, K, R, C>(&self, cursor: &mut C) -> Vec<(V, R)>
+    where
+        C: Cursor<'s, K, V, (), R>,
+        R: ZRingValue,
+    {
+        let mut heap = BinaryHeap::with_capacity(self.k + 1);
+
+        while cursor.val_valid() {
+            let weight = cursor.weight();
+            if !weight.le0() {
+                let entry = HeapEntry {
+                    val: cursor.val().clone(),
+                    weight,
+                    cmp_func: &self.cmp,
+                };
+
+                // Skip values that are worse than all current top `k` values
+                // without touching the heap.
+                let admit =
+                    heap.len() < self.k || matches!(heap.peek(), Some(worst) if entry < *worst);
+                if admit {
+                    heap.push(entry);
+                    if heap.len() > self.k {
+                        heap.pop();
+                    }
+                }
+            }
+            cursor.step_val();
+        }
+
+        heap.into_iter()
+            .map(|entry| (entry.val, entry.weight))
+            .collect()
+    }
+}
+
+impl<V, F> Operator for TopKCustom<V, F>
+where
+    V: 'static,
+    F: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("TopKCustom")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<V, F, B, T, OT, O> TernaryOperator<B, T, OT, O> for TopKCustom<V, F>
+where
+    V: DBData,
+    F: Fn(&V, &V) -> Ordering + 'static,
+    B: BatchReader<Val = V, Time = ()> + Clone,
+    B::R: ZRingValue,
+    T: BatchReader<Key = B::Key, Val = V, Time = (), R = B::R> + Clone,
+    OT: BatchReader<Key = B::Key, Val = V, Time = (), R = B::R> + Clone,
+    O: IndexedZSet<Key = B::Key, Val = V, R = B::R>,
+{
+    fn eval<'a>(
+        &mut self,
+        input_delta: Cow<'a, B>,
+        input_trace: Cow<'a, T>,
+        output_trace: Cow<'a, OT>,
+    ) -> O {
+        let mut delta_cursor = input_delta.cursor();
+        let mut input_trace_cursor = input_trace.cursor();
+        let mut output_trace_cursor = output_trace.cursor();
+
+        let mut outputs = Vec::new();
+
+        // Iterate over affected keys.
+        while delta_cursor.key_valid() {
+            let key = delta_cursor.key();
+
+            // New top `k` values.
+            input_trace_cursor.seek_key(key);
+            if input_trace_cursor.key_valid() && input_trace_cursor.key() == key {
+                for (val, weight) in self.topk(&mut input_trace_cursor) {
+                    outputs.push((O::item_from(key.clone(), val), weight));
+                }
+            }
+
+            // Retract old top `k` values.  The batcher used to assemble the
+            // output batch cancels out values that remain in the top `k`.
+            output_trace_cursor.seek_key(key);
+            if output_trace_cursor.key_valid() && output_trace_cursor.key() == key {
+                while output_trace_cursor.val_valid() {
+                    let weight = output_trace_cursor.weight();
+                    if !weight.is_zero() {
+                        outputs.push((
+                            O::item_from(key.clone(), output_trace_cursor.val().clone()),
+                            weight.neg(),
+                        ));
+                    }
+                    output_trace_cursor.step_val();
+                }
+            }
+
+            delta_cursor.step_key();
+        }
+
+        O::from_tuples((), outputs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        indexed_zset,
+        trace::{Batch, BatchReader, Cursor},
+        CollectionHandle, DBSPHandle, OrdIndexedZSet, OutputHandle, RootCircuit, Runtime,
+    };
+    use proptest::{collection, prelude::*};
+    use std::cmp::Ordering;
+
+    /// `(score, id)` pair.
+    type Record = (i64, u64);
+    type TestBatch = OrdIndexedZSet<u64, Record, isize>;
+
+    /// Order by score descending, then by id ascending.
+    fn by_score(left: &Record, right: &Record) -> Ordering {
+        right.0.cmp(&left.0).then(left.1.cmp(&right.1))
+    }
+
+    fn topk_test_circuit(
+        workers: usize,
+        k: usize,
+    ) -> (
+        DBSPHandle,
+        (
+            CollectionHandle<u64, (Record, isize)>,
+            OutputHandle<TestBatch>,
+        ),
+    ) {
+        Runtime::init_circuit(workers, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<u64, Record, isize>();
+            let output_handle = input_stream.topk_custom(k, by_score).integrate().output();
+
+            (input_handle, output_handle)
+        })
+        .unwrap()
+    }
+
+    /// Reference implementation: sort values with positive weights and
+    /// truncate to `k` values.
+    fn topk_reference(tuples: &[(u64, (Record, isize))], k: usize) -> TestBatch {
+        let input = TestBatch::from_tuples(
+            (),
+            tuples
+                .iter()
+                .map(|(key, (val, weight))| ((*key, *val), *weight))
+                .collect(),
+        );
+
+        let mut output = Vec::new();
+        let mut cursor = input.cursor();
+        while cursor.key_valid() {
+            let mut vals = Vec::new();
+            while cursor.val_valid() {
+                if cursor.weight() > 0 {
+                    vals.push((*cursor.val(), cursor.weight()));
+                }
+                cursor.step_val();
+            }
+            vals.sort_by(|(left, _), (right, _)| by_score(left, right));
+            vals.truncate(k);
+            output.extend(
+                vals.into_iter()
+                    .map(|(val, weight)| ((*cursor.key(), val), weight)),
+            );
+            cursor.step_key();
+        }
+
+        TestBatch::from_tuples((), output)
+    }
+
+    #[test]
+    fn test_topk_custom() {
+        let (mut dbsp, (mut input, output)) = topk_test_circuit(1, 2);
+
+        input.append(&mut vec![
+            (0, ((10, 1), 1)),
+            (0, ((20, 2), 1)),
+            (0, ((20, 1), 1)),
+            (0, ((5, 3), 1)),
+            (1, ((1, 1), 2)),
+        ]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 0 => { (20, 1) => 1, (20, 2) => 1 }, 1 => { (1, 1) => 2 } }
+        );
+
+        // Retract a top value: the next best value takes its place.
+        input.append(&mut vec![(0, ((20, 1), -1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 0 => { (10, 1) => 1, (20, 2) => 1 }, 1 => { (1, 1) => 2 } }
+        );
+
+        // Insert a new best value and a value that doesn't make it to the
+        // top 2.
+        input.append(&mut vec![(0, ((30, 7), 1)), (0, ((0, 0), 1))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 0 => { (20, 2) => 1, (30, 7) => 1 }, 1 => { (1, 1) => 2 } }
+        );
+
+        // Values with negative weights are ignored.
+        input.append(&mut vec![(1, ((2, 2), -1)), (1, ((1, 1), -2))]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            indexed_zset! { 0 => { (20, 2) => 1, (30, 7) => 1 } }
+        );
+
+        dbsp.kill().unwrap();
+    }
+
+    const NUM_KEYS: u64 = 5;
+    const MAX_TUPLES: usize = 20;
+    const MAX_ROUNDS: usize = 20;
+
+    fn test_input() -> impl Strategy<Value = Vec<Vec<(u64, (Record, isize))>>> {
+        collection::vec(
+            collection::vec(
+                (0..NUM_KEYS, ((0..10i64, 0..5u64), -1..=2isize)),
+                0..MAX_TUPLES,
+            ),
+            0..MAX_ROUNDS,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_topk_custom(inputs in test_input(), k in 0..5usize, workers in 1..=4usize) {
+            let (mut dbsp, (mut input, output)) = topk_test_circuit(workers, k);
+            let mut all_tuples = Vec::new();
+
+            for mut batch in inputs {
+                all_tuples.extend(batch.iter().cloned());
+                input.append(&mut batch);
+                dbsp.step().unwrap();
+
+                assert_eq!(output.consolidate(), topk_reference(&all_tuples, k));
+            }
+
+            dbsp.kill().unwrap();
+        }
+    }
+}
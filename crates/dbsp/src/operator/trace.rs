@@ -1,6 +1,9 @@
+#[cfg(feature = "persistence")]
+use crate::trace::SpillingSpine;
 use crate::{
     circuit::{
         metadata::{MetaItem, OperatorMeta},
+        metrics::{MetricLabels, TRACE_ENTRIES},
         operator_traits::{BinaryOperator, Operator, StrictOperator, StrictUnaryOperator},
         Circuit, ExportId, ExportStream, GlobalNodeId, OwnershipPreference, Scope, Stream,
         WithClock,
@@ -10,14 +13,22 @@ use crate::{
     DBData, Timestamp,
 };
 use size_of::SizeOf;
-use std::{borrow::Cow, cell::RefCell, marker::PhantomData, ops::DerefMut, rc::Rc};
+#[cfg(feature = "persistence")]
+use std::path::PathBuf;
+use std::{
+    borrow::Cow, cell::RefCell, collections::BTreeMap, marker::PhantomData, ops::DerefMut, rc::Rc,
+};
 
 circuit_cache_key!(TraceId<B, D, K, V>(GlobalNodeId => (Stream<B, D>, TraceBounds<K, V>)));
 circuit_cache_key!(DelayedTraceId<B, D>(GlobalNodeId => Stream<B, D>));
 circuit_cache_key!(IntegrateTraceId<B, D, K, V>(GlobalNodeId => (Stream<B, D>, TraceBounds<K, V>)));
+circuit_cache_key!(TraceCompactionId<T>(GlobalNodeId => TraceCompaction<T>));
 
 /// Lower bound on keys or values in a trace.
 ///
+/// Keys or values strictly less than the bound get truncated, keys or values
+/// equal to the bound are retained.
+///
 /// Setting the bound to `None` is equivalent to setting it to
 /// `T::min_value()`, i.e., the contents of the trace will never
 /// get truncated.
@@ -121,6 +132,100 @@ struct TraceBoundsInner<K, V> {
     val_bounds: Vec<TraceBound<V>>,
 }
 
+/// Upper bound on the logical times that a consumer of a trace reads.
+///
+/// A consumer of a trace compacted with
+/// [`compact_trace_to`](`Stream::compact_trace_to`) uses this bound to
+/// declare that updates with timestamps less than or equal to the bound must
+/// keep their original timestamps.  In debug builds, compacting the trace to
+/// a frontier that is not greater than or equal to all bounds registered via
+/// [`add_compaction_bound`](`Stream::add_compaction_bound`) panics.
+///
+/// Setting the bound to `None` means that the consumer does not distinguish
+/// between any timestamps in the trace, e.g., because it only reads the sum
+/// of all updates.
+///
+/// The consumer can update the value of the bound at each clock cycle.  The
+/// bound can only increase monotonically.
+#[derive(Clone)]
+#[repr(transparent)]
+pub struct CompactionBound<T>(Rc<RefCell<Option<T>>>);
+
+impl<T> Default for CompactionBound<T> {
+    fn default() -> Self {
+        Self(Rc::new(RefCell::new(None)))
+    }
+}
+
+impl<T> CompactionBound<T>
+where
+    T: Timestamp,
+{
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the new value of the bound.
+    pub fn set(&self, bound: T) {
+        debug_assert!(self
+            .0
+            .borrow()
+            .as_ref()
+            .map_or(true, |old| old.less_equal(&bound)));
+        *self.0.borrow_mut() = Some(bound);
+    }
+
+    /// Get the current value of the bound.
+    pub fn get(&self) -> Option<T> {
+        self.0.borrow().clone()
+    }
+}
+
+/// Compaction frontier of a trace, along with the bounds supplied by all
+/// downstream consumers of the trace.
+#[derive(Clone)]
+pub(crate) struct TraceCompaction<T>(Rc<RefCell<TraceCompactionInner<T>>>);
+
+impl<T> TraceCompaction<T>
+where
+    T: Timestamp,
+{
+    pub(crate) fn new() -> Self {
+        Self(Rc::new(RefCell::new(TraceCompactionInner {
+            frontier: None,
+            bounds: Vec::new(),
+        })))
+    }
+
+    pub(crate) fn set_frontier(&self, frontier: T) {
+        self.0.borrow_mut().frontier = Some(frontier);
+    }
+
+    pub(crate) fn frontier(&self) -> Option<T> {
+        self.0.borrow().frontier.clone()
+    }
+
+    pub(crate) fn add_bound(&self, bound: CompactionBound<T>) {
+        self.0.borrow_mut().bounds.push(bound);
+    }
+
+    /// Returns a consumer bound that is not less than or equal to `frontier`,
+    /// if any.
+    pub(crate) fn bound_above(&self, frontier: &T) -> Option<T> {
+        self.0
+            .borrow()
+            .bounds
+            .iter()
+            .filter_map(|bound| bound.get())
+            .find(|bound| !bound.less_equal(frontier))
+    }
+}
+
+struct TraceCompactionInner<T> {
+    frontier: Option<T>,
+    bounds: Vec<CompactionBound<T>>,
+}
+
 // TODO: add infrastructure to compact the trace during slack time.
 
 /// Add `timestamp` to all tuples in the input batch.
@@ -159,6 +264,42 @@ where
     builder.done()
 }
 
+/// Add per-tuple timestamps computed by `time_func` to the input batch.
+///
+/// Unlike [`batch_add_time`], which labels all tuples in the batch with the
+/// same timestamp, this function computes the timestamp of each tuple from
+/// its key and value.  Since a batch builder only supports a single timestamp,
+/// the output is split into one batch per distinct timestamp.
+fn batch_add_times<BI, TS, BO, F>(batch: &BI, time_func: &F) -> Vec<BO>
+where
+    TS: Timestamp,
+    BI: BatchReader<Time = ()>,
+    BI::Key: Clone,
+    BI::Val: Clone,
+    BO: Batch<Key = BI::Key, Val = BI::Val, Time = TS, R = BI::R>,
+    F: Fn(&BI::Key, &BI::Val) -> TS,
+{
+    let mut builders: BTreeMap<TS, BO::Builder> = BTreeMap::new();
+    let mut cursor = batch.cursor();
+    while cursor.key_valid() {
+        while cursor.val_valid() {
+            let time = time_func(cursor.key(), cursor.val());
+            let val = cursor.val().clone();
+            let w = cursor.weight();
+            builders
+                .entry(time.clone())
+                .or_insert_with(|| BO::Builder::new_builder(time))
+                .push((BO::item_from(cursor.key().clone(), val), w.clone()));
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+    builders
+        .into_values()
+        .map(|builder| builder.done())
+        .collect()
+}
+
 impl<C, B> Stream<C, B>
 where
     C: Circuit,
@@ -192,14 +333,15 @@ where
             || {
                 let circuit = self.circuit();
                 let bounds = TraceBounds::new();
+                let compaction = TraceCompaction::new();
 
                 circuit.region("trace", || {
                     let (ExportStream { local, export }, z1feedback) = circuit
-                        .add_feedback_with_export(Z1Trace::new(
-                            false,
-                            circuit.root_scope(),
-                            bounds.clone(),
-                        ));
+                        .add_feedback_with_export(
+                            Z1Trace::new(false, circuit.root_scope(), bounds.clone())
+                                .with_compaction(compaction.clone())
+                                .with_stream(self.origin_node_id()),
+                        );
                     let trace = circuit.add_binary_operator_with_preference(
                         <TraceAppend<T, B, C>>::new(circuit.clone()),
                         (&local, OwnershipPreference::STRONGLY_PREFER_OWNED),
@@ -220,6 +362,10 @@ where
                     circuit
                         .cache_insert(DelayedTraceId::new(trace.origin_node_id().clone()), local);
                     circuit.cache_insert(ExportId::new(trace.origin_node_id().clone()), export);
+                    circuit.cache_insert(
+                        TraceCompactionId::new(trace.origin_node_id().clone()),
+                        compaction,
+                    );
                     (trace, bounds)
                 })
             },
@@ -233,6 +379,55 @@ where
         trace.clone()
     }
 
+    /// Record batches in `self` in a trace, using application-defined
+    /// timestamps.
+    ///
+    /// This operator is similar to [`trace`](`Self::trace`), but instead of
+    /// labeling each batch with the current clock time, it labels each tuple
+    /// with the timestamp computed by `time_func` from its key and value.
+    /// Like [`integrate_trace`](`Self::integrate_trace`), the trace is reset
+    /// at the start of each clock epoch of the parent scope.
+    ///
+    /// Unlike other trace operators, this operator does not share the trace
+    /// with other consumers, since `time_func` is not part of the cache key.
+    pub(crate) fn trace_with_time<T, F>(&self, time_func: F) -> Stream<C, T>
+    where
+        B: BatchReader<Time = ()>,
+        T: Trace<Key = B::Key, Val = B::Val, R = B::R> + Clone,
+        F: Fn(&B::Key, &B::Val) -> T::Time + 'static,
+    {
+        let circuit = self.circuit();
+        let compaction = TraceCompaction::new();
+
+        circuit.region("trace_with_time", || {
+            let (local, z1feedback) = circuit.add_feedback(
+                Z1Trace::new(true, circuit.root_scope(), TraceBounds::unbounded())
+                    .with_compaction(compaction.clone())
+                    .with_stream(self.origin_node_id()),
+            );
+            let trace = circuit.add_binary_operator_with_preference(
+                <TimedTraceAppend<T, B, F>>::new(time_func),
+                (&local, OwnershipPreference::STRONGLY_PREFER_OWNED),
+                (
+                    &self.try_sharded_version(),
+                    OwnershipPreference::PREFER_OWNED,
+                ),
+            );
+            if self.has_sharded_version() {
+                local.mark_sharded();
+                trace.mark_sharded();
+            }
+            z1feedback.connect_with_preference(&trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+
+            circuit.cache_insert(DelayedTraceId::new(trace.origin_node_id().clone()), local);
+            circuit.cache_insert(
+                TraceCompactionId::new(trace.origin_node_id().clone()),
+                compaction,
+            );
+            trace
+        })
+    }
+
     // TODO: this method should replace `Stream::integrate()`.
     #[track_caller]
     pub fn integrate_trace(&self) -> Stream<C, Spine<B>>
@@ -258,14 +453,15 @@ where
             || {
                 let circuit = self.circuit();
                 let bounds = TraceBounds::new();
+                let compaction = TraceCompaction::new();
 
                 circuit.region("integrate_trace", || {
                     let (ExportStream { local, export }, z1feedback) = circuit
-                        .add_feedback_with_export(Z1Trace::new(
-                            true,
-                            circuit.root_scope(),
-                            bounds.clone(),
-                        ));
+                        .add_feedback_with_export(
+                            Z1Trace::new(true, circuit.root_scope(), bounds.clone())
+                                .with_compaction(compaction.clone())
+                                .with_stream(self.origin_node_id()),
+                        );
 
                     let trace = circuit.add_binary_operator_with_preference(
                         UntimedTraceAppend::<Spine<B>>::new(),
@@ -289,6 +485,10 @@ where
                     circuit
                         .cache_insert(DelayedTraceId::new(trace.origin_node_id().clone()), local);
                     circuit.cache_insert(ExportId::new(trace.origin_node_id().clone()), export);
+                    circuit.cache_insert(
+                        TraceCompactionId::new(trace.origin_node_id().clone()),
+                        compaction,
+                    );
 
                     (trace, bounds)
                 })
@@ -302,6 +502,88 @@ where
 
         trace.clone()
     }
+
+    /// Like [`integrate_trace`](`Self::integrate_trace`), but keeps the
+    /// trace's in-memory footprint within `budget_bytes` by spilling batches
+    /// to files within `dir`.
+    ///
+    /// Unlike `integrate_trace`, this operator does not share the trace with
+    /// other consumers, since the budget is not part of the cache key.
+    #[cfg(feature = "persistence")]
+    #[track_caller]
+    pub fn integrate_trace_spilled<P>(
+        &self,
+        budget_bytes: usize,
+        dir: P,
+    ) -> Stream<C, SpillingSpine<B>>
+    where
+        B: Batch,
+        P: Into<PathBuf>,
+    {
+        self.integrate_trace_spilled_with_bound(
+            budget_bytes,
+            dir,
+            TraceBound::new(),
+            TraceBound::new(),
+        )
+    }
+
+    #[cfg(feature = "persistence")]
+    #[track_caller]
+    pub fn integrate_trace_spilled_with_bound<P>(
+        &self,
+        budget_bytes: usize,
+        dir: P,
+        lower_key_bound: TraceBound<B::Key>,
+        lower_val_bound: TraceBound<B::Val>,
+    ) -> Stream<C, SpillingSpine<B>>
+    where
+        B: Batch,
+        P: Into<PathBuf>,
+    {
+        let circuit = self.circuit();
+        let dir = dir.into();
+
+        let bounds = TraceBounds::new();
+        bounds.add_key_bound(lower_key_bound);
+        bounds.add_val_bound(lower_val_bound);
+        let compaction = TraceCompaction::new();
+
+        circuit.region("integrate_trace_spilled", || {
+            let (ExportStream { local, export }, z1feedback) = circuit.add_feedback_with_export(
+                Z1Trace::new(true, circuit.root_scope(), bounds)
+                    .with_trace_constructor(move || {
+                        SpillingSpine::with_budget(budget_bytes, dir.clone(), None)
+                    })
+                    .with_compaction(compaction.clone())
+                    .with_stream(self.origin_node_id()),
+            );
+
+            let trace = circuit.add_binary_operator_with_preference(
+                UntimedTraceAppend::<SpillingSpine<B>>::new(),
+                (&local, OwnershipPreference::STRONGLY_PREFER_OWNED),
+                (
+                    &self.try_sharded_version(),
+                    OwnershipPreference::PREFER_OWNED,
+                ),
+            );
+
+            if self.has_sharded_version() {
+                local.mark_sharded();
+                trace.mark_sharded();
+            }
+
+            z1feedback.connect_with_preference(&trace, OwnershipPreference::STRONGLY_PREFER_OWNED);
+
+            circuit.cache_insert(DelayedTraceId::new(trace.origin_node_id().clone()), local);
+            circuit.cache_insert(ExportId::new(trace.origin_node_id().clone()), export);
+            circuit.cache_insert(
+                TraceCompactionId::new(trace.origin_node_id().clone()),
+                compaction,
+            );
+            trace
+        })
+    }
 }
 
 impl<C, T> Stream<C, T>
@@ -319,6 +601,58 @@ where
             })
             .clone()
     }
+
+    /// Compact the trace in `self` to the frontier produced by `frontier`.
+    ///
+    /// At each clock cycle, the trace is compacted by pushing all timestamps
+    /// that are not less than or equal to the latest value of `frontier` back
+    /// to the frontier using [`Trace::recede_to`].  The trace then no longer
+    /// distinguishes between such timestamps, which reduces the number of
+    /// distinct timestamps it stores.  Operators that only read the sum of
+    /// updates across all timestamps, e.g., distinct, keep producing the same
+    /// outputs.
+    ///
+    /// Compaction happens between steps: a frontier produced during a clock
+    /// cycle takes effect by the end of the next clock cycle at the latest.
+    /// Updates added to the trace after compaction are compacted to the latest
+    /// frontier as well.  Each compaction takes time proportional to the size
+    /// of the trace.
+    ///
+    /// Consumers that distinguish between timestamps must declare the times
+    /// they read via [`add_compaction_bound`](`Self::add_compaction_bound`).
+    /// In debug builds, the circuit panics if the trace gets compacted to a
+    /// frontier that is not greater than or equal to all such bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is not a trace created by one of the `trace` or
+    /// `integrate_trace` operators.
+    pub fn compact_trace_to(&self, frontier: &Stream<C, T::Time>) {
+        let compaction = self.trace_compaction();
+        frontier.inspect(move |frontier| compaction.set_frontier(frontier.clone()));
+    }
+
+    /// Declare that a consumer of the trace in `self` reads updates with
+    /// timestamps up to `bound`.
+    ///
+    /// See [`compact_trace_to`](`Self::compact_trace_to`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is not a trace created by one of the `trace` or
+    /// `integrate_trace` operators.
+    pub fn add_compaction_bound(&self, bound: CompactionBound<T::Time>) {
+        self.trace_compaction().add_bound(bound);
+    }
+
+    fn trace_compaction(&self) -> TraceCompaction<T::Time> {
+        self.circuit()
+            .cache_get_or_insert_with(
+                TraceCompactionId::new(self.origin_node_id().clone()),
+                || panic!("called `.compact_trace_to()` on a stream without a previously created trace"),
+            )
+            .clone()
+    }
 }
 
 pub struct UntimedTraceAppend<T>
@@ -460,6 +794,73 @@ where
     }
 }
 
+/// Like [`TraceAppend`], but labels each tuple in the input batch with a
+/// timestamp computed by `time_func` rather than the current clock time.
+pub struct TimedTraceAppend<T, B, F> {
+    time_func: F,
+    _phantom: PhantomData<(T, B)>,
+}
+
+impl<T, B, F> TimedTraceAppend<T, B, F> {
+    pub fn new(time_func: F) -> Self {
+        Self {
+            time_func,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, B, F> Operator for TimedTraceAppend<T, B, F>
+where
+    T: 'static,
+    B: 'static,
+    F: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::from("TimedTraceAppend")
+    }
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+}
+
+impl<T, B, F> BinaryOperator<T, B, T> for TimedTraceAppend<T, B, F>
+where
+    B: BatchReader<Time = ()>,
+    T: Trace<Key = B::Key, Val = B::Val, R = B::R>,
+    F: Fn(&B::Key, &B::Val) -> T::Time + 'static,
+{
+    fn eval(&mut self, _trace: &T, _batch: &B) -> T {
+        // Refuse to accept trace by reference.  This should not happen in a correctly
+        // constructed circuit.
+        unimplemented!()
+    }
+
+    fn eval_owned_and_ref(&mut self, mut trace: T, batch: &B) -> T {
+        for batch in batch_add_times(batch, &self.time_func) {
+            trace.insert(batch);
+        }
+        trace
+    }
+
+    fn eval_ref_and_owned(&mut self, _trace: &T, _batch: B) -> T {
+        // Refuse to accept trace by reference.  This should not happen in a correctly
+        // constructed circuit.
+        unimplemented!()
+    }
+
+    fn eval_owned(&mut self, trace: T, batch: B) -> T {
+        self.eval_owned_and_ref(trace, &batch)
+    }
+
+    fn input_preference(&self) -> (OwnershipPreference, OwnershipPreference) {
+        (
+            OwnershipPreference::PREFER_OWNED,
+            OwnershipPreference::PREFER_OWNED,
+        )
+    }
+}
+
 pub struct Z1Trace<T: Trace> {
     time: T::Time,
     trace: Option<T>,
@@ -471,6 +872,13 @@ pub struct Z1Trace<T: Trace> {
     bounds: TraceBounds<T::Key, T::Val>,
     effective_key_bound: Option<T::Key>,
     effective_val_bound: Option<T::Val>,
+    compaction: TraceCompaction<T::Time>,
+    // The frontier the trace was last compacted to.
+    compaction_frontier: Option<T::Time>,
+    // The meet of all frontiers the trace was compacted to.
+    compacted_to: Option<T::Time>,
+    labels: MetricLabels,
+    new_trace: Box<dyn Fn() -> T>,
 }
 
 impl<T> Z1Trace<T>
@@ -491,8 +899,36 @@ where
             bounds,
             effective_key_bound: None,
             effective_val_bound: None,
+            compaction: TraceCompaction::new(),
+            compaction_frontier: None,
+            compacted_to: None,
+            labels: MetricLabels::default(),
+            new_trace: Box::new(|| T::new(None)),
         }
     }
+
+    /// Use `new_trace` instead of `T::new` to create the trace at the start
+    /// of each clock epoch.
+    pub(crate) fn with_trace_constructor<F>(mut self, new_trace: F) -> Self
+    where
+        F: Fn() -> T + 'static,
+    {
+        self.new_trace = Box::new(new_trace);
+        self
+    }
+
+    /// Compact the trace to the frontier stored in `compaction`.
+    pub(crate) fn with_compaction(mut self, compaction: TraceCompaction<T::Time>) -> Self {
+        self.compaction = compaction;
+        self
+    }
+
+    /// Label metrics reported by the operator with the global id of the
+    /// stream whose trace it maintains.
+    pub(crate) fn with_stream(mut self, stream: &GlobalNodeId) -> Self {
+        self.labels = MetricLabels::stream(&self.name(), stream);
+        self
+    }
 }
 
 impl<T> Operator for Z1Trace<T>
@@ -508,7 +944,7 @@ where
 
         if scope == 0 && self.trace.is_none() {
             // TODO: use T::with_effort with configurable effort?
-            self.trace = Some(T::new(None));
+            self.trace = Some((self.new_trace)());
         }
     }
 
@@ -562,7 +998,7 @@ where
         if self.reset_on_clock_start {
             self.get_output()
         } else {
-            T::new(None)
+            (self.new_trace)()
         }
     }
 }
@@ -584,6 +1020,7 @@ where
         if effective_key_bound != self.effective_key_bound {
             if let Some(bound) = &effective_key_bound {
                 i.truncate_keys_below(bound);
+                span_event!(tracing::Level::DEBUG, bound = "keys", "trace truncated");
             }
         }
         self.effective_key_bound = effective_key_bound;
@@ -592,10 +1029,37 @@ where
         if effective_val_bound != self.effective_val_bound {
             if let Some(bound) = &effective_val_bound {
                 i.truncate_values_below(bound);
+                span_event!(tracing::Level::DEBUG, bound = "values", "trace truncated");
             }
         }
         self.effective_val_bound = effective_val_bound;
 
+        if let Some(frontier) = self.compaction.frontier() {
+            // Compact new updates, as well as all existing updates if the
+            // frontier has changed.
+            if dirty || self.compaction_frontier.as_ref() != Some(&frontier) {
+                i.recede_to(&frontier);
+                span_event!(tracing::Level::DEBUG, "trace compacted");
+
+                self.compacted_to = Some(match self.compacted_to.take() {
+                    None => frontier.clone(),
+                    Some(compacted_to) => compacted_to.meet(&frontier),
+                });
+                self.compaction_frontier = Some(frontier);
+            }
+        }
+
+        if cfg!(debug_assertions) {
+            if let Some(compacted_to) = &self.compacted_to {
+                if let Some(bound) = self.compaction.bound_above(compacted_to) {
+                    panic!(
+                        "trace compacted to {compacted_to:?}, but one of its consumers reads updates up to {bound:?}"
+                    );
+                }
+            }
+        }
+
+        metric_gauge!(TRACE_ENTRIES, i.num_entries_deep(), &self.labels);
         self.trace = Some(i);
 
         self.dirty[0] = dirty;
@@ -608,3 +1072,125 @@ where
         OwnershipPreference::PREFER_OWNED
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        operator::{CompactionBound, Generator},
+        trace::{cursor::Cursor, ord::OrdValBatch, BatchReader, Spine},
+        CircuitHandle, CollectionHandle, OrdIndexedZSet, RootCircuit,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    type TimedTrace = Spine<OrdValBatch<u64, u32, u32, isize>>;
+
+    // Distinct over the sum of all updates in the trace.
+    fn distinct_trace(trace: &TimedTrace) -> OrdIndexedZSet<u64, u32, isize> {
+        let mut tuples = Vec::new();
+        let mut cursor = trace.cursor();
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                let mut weight = 0;
+                cursor.map_times(|_time, w| weight += w);
+                if weight > 0 {
+                    tuples.push(((*cursor.key(), *cursor.val()), 1));
+                }
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+        OrdIndexedZSet::from_tuples((), tuples)
+    }
+
+    fn trace_times(trace: &TimedTrace) -> Vec<u32> {
+        let mut times = Vec::new();
+        let mut cursor = trace.cursor();
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                cursor.map_times(|time, _w| times.push(*time));
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+        times
+    }
+
+    // Build a circuit that compacts a trace labeled with the values of the
+    // input indexed Z-set to `frontier`, with a consumer that reads times up
+    // to `bound`.
+    fn compaction_test_circuit(
+        frontier: u32,
+        bound: u32,
+    ) -> (
+        CircuitHandle,
+        CollectionHandle<u64, (u32, isize)>,
+        Rc<RefCell<Vec<u32>>>,
+    ) {
+        let times = Rc::new(RefCell::new(Vec::new()));
+        let times_clone = times.clone();
+
+        let (circuit, input_handle) = RootCircuit::build(move |circuit| {
+            let (input, input_handle) = circuit.add_input_indexed_zset::<u64, u32, isize>();
+
+            let trace = input.trace_with_time::<TimedTrace, _>(|_key, time| *time);
+            trace.compact_trace_to(&circuit.add_source(Generator::new(move || frontier)));
+
+            let compaction_bound = CompactionBound::new();
+            compaction_bound.set(bound);
+            trace.add_compaction_bound(compaction_bound);
+
+            // Distinct over the compacted trace must match distinct over the
+            // input stream.
+            trace
+                .apply(distinct_trace)
+                .differentiate()
+                .apply2(&input.distinct(), |actual, expected| {
+                    assert_eq!(actual, expected)
+                });
+
+            trace
+                .delay_trace()
+                .inspect(move |trace| *times_clone.borrow_mut() = trace_times(trace));
+
+            input_handle
+        })
+        .unwrap();
+
+        (circuit, input_handle, times)
+    }
+
+    #[test]
+    fn compact_trace() {
+        let (circuit, mut input, times) = compaction_test_circuit(5, 3);
+
+        input.append(&mut vec![(1, (2, 1)), (1, (7, 1)), (2, (9, 1))]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(1, (7, -1)), (2, (9, 1)), (3, (8, 1))]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(2, (9, -2)), (3, (4, 1))]);
+        circuit.step().unwrap();
+
+        // The trace has been compacted by the end of the second step.
+        let times = times.borrow().clone();
+        assert!(times.contains(&2));
+        assert!(times.iter().all(|time| *time <= 5));
+
+        input.append(&mut vec![(1, (2, -1)), (3, (8, 1)), (4, (6, 1))]);
+        circuit.step().unwrap();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "one of its consumers reads updates up to 7")]
+    fn compact_trace_below_bound() {
+        let (circuit, mut input, _times) = compaction_test_circuit(5, 7);
+
+        input.append(&mut vec![(1, (2, 1)), (1, (7, 1))]);
+        circuit.step().unwrap();
+
+        input.append(&mut vec![(2, (9, 1))]);
+        circuit.step().unwrap();
+    }
+}
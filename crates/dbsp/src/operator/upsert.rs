@@ -4,7 +4,7 @@ use crate::{
         operator_traits::{BinaryOperator, Operator},
         ExportId, ExportStream, OwnershipPreference, Scope, WithClock,
     },
-    operator::trace::{DelayedTraceId, TraceAppend, TraceBounds, TraceId, Z1Trace},
+    operator::trace::{DelayedTraceId, TraceAppend, TraceBound, TraceBounds, TraceId, Z1Trace},
     trace::{
         consolidation::consolidate, cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace,
     },
@@ -35,6 +35,22 @@ where
     /// This is a stateful operator that internaly maintains the trace of the
     /// collection.
     pub fn upsert<B>(&self) -> Stream<C, B>
+    where
+        K: DBData,
+        V: DBData,
+        B::R: DBData + ZRingValue,
+        B: Batch<Key = K, Val = V, Time = ()>,
+    {
+        self.upsert_with_bound(TraceBound::new())
+    }
+
+    /// Like [`Self::upsert`], but truncates keys below `lower_key_bound` in
+    /// the trace of the collection.
+    ///
+    /// Upserts to keys below the bound are evaluated against the truncated
+    /// trace, so the caller must guarantee that the input stream does not
+    /// contain such upserts once the bound has been set.
+    pub(crate) fn upsert_with_bound<B>(&self, lower_key_bound: TraceBound<K>) -> Stream<C, B>
     where
         K: DBData,
         V: DBData,
@@ -63,10 +79,13 @@ where
         //                    z1trace             └───────┘
         // ```
         circuit.region("upsert", || {
-            let bounds = <TraceBounds<K, V>>::unbounded();
+            let bounds = <TraceBounds<K, V>>::new();
+            bounds.add_key_bound(lower_key_bound);
+            bounds.add_val_bound(TraceBound::new());
 
             let (ExportStream { local, export }, z1feedback) = circuit.add_feedback_with_export(
-                Z1Trace::new(false, circuit.root_scope(), bounds.clone()),
+                Z1Trace::new(false, circuit.root_scope(), bounds.clone())
+                    .with_stream(self.origin_node_id()),
             );
             local.mark_sharded_if(self);
 
@@ -0,0 +1,35 @@
+//! Commonly used types and traits.
+//!
+//! Importing the prelude brings the types needed to build, run and feed data
+//! into most circuits into scope:
+//!
+//! ```
+//! use dbsp::prelude::*;
+//!
+//! let (mut dbsp, (input, output)) =
+//!     Runtime::init(RuntimeConfig::new().workers(2), |circuit| {
+//!         let (stream, input) = circuit.add_input_zset::<u64, isize>();
+//!         let output = stream.map(|x| x + 1).output();
+//!         (input, output)
+//!     })
+//!     .unwrap();
+//!
+//! input.push(1, 1);
+//! dbsp.step().unwrap();
+//! assert_eq!(output.consolidate(), OrdZSet::from_keys((), vec![(2, 1)]));
+//! ```
+//!
+//! The contents of the prelude are part of the crate's stable interface:
+//! items are only added to it, never removed or renamed.
+
+pub use crate::{
+    algebra::{DefaultSemigroup, IndexedZSet, ZSet, F32, F64},
+    circuit::{Circuit, DBSPHandle, RootCircuit, Runtime, RuntimeConfig, Stream},
+    operator::{
+        time_series::{RelOffset, RelRange},
+        Aggregator, Avg, CollectionHandle, FilterMap, Fold, InputHandle, Max, Min, OutputHandle,
+        UpsertHandle,
+    },
+    trace::{Batch, BatchReader, Cursor, DBData, DBTimestamp, DBWeight},
+    Error, OrdIndexedZSet, OrdZSet,
+};
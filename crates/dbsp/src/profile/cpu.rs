@@ -10,15 +10,37 @@ use crate::circuit::{trace::SchedulerEvent, GlobalNodeId, RootCircuit};
 use hashbrown::HashMap;
 use std::{
     cell::RefCell,
+    collections::BTreeMap,
+    fmt::Write,
     rc::Rc,
     time::{Duration, Instant},
 };
 
+/// Returns the CPU time consumed by the current thread or `None` if the
+/// platform does not support measuring it.
+fn thread_cpu_time() -> Option<Duration> {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+
+        // Safety: `time` is a valid pointer to a `timespec`.
+        if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } == 0 {
+            return Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32));
+        }
+    }
+
+    None
+}
+
 /// Per-operator CPU profile.
 #[derive(Clone, Default, Debug)]
 pub struct OperatorCPUProfile {
     invocations: usize,
     total_time: Duration,
+    cpu_time: Option<Duration>,
 }
 
 impl OperatorCPUProfile {
@@ -27,6 +49,11 @@ impl OperatorCPUProfile {
         self.total_time += duration;
     }
 
+    /// Add thread CPU time consumed by an invocation of the operator.
+    pub fn add_cpu_time(&mut self, cpu_time: Duration) {
+        *self.cpu_time.get_or_insert(Duration::ZERO) += cpu_time;
+    }
+
     /// Returns the number of times the operator has been invoked.
     /// This number is the same for all operators in a synchronous
     /// circuit.
@@ -38,6 +65,90 @@ impl OperatorCPUProfile {
     pub fn total_time(&self) -> Duration {
         self.total_time
     }
+
+    /// Total thread CPU time spent evaluating the operator across all
+    /// invocations or `None` if the platform does not support measuring
+    /// thread CPU time.
+    pub fn cpu_time(&self) -> Option<Duration> {
+        self.cpu_time
+    }
+}
+
+/// CPU profile aggregated by operator name and the hierarchy of subcircuits
+/// and regions that contain the operator.
+///
+/// The profile can be exported in the folded stacks format understood by
+/// flamegraph tools, such as `inferno-flamegraph` (see
+/// [`Self::to_folded`]).  Each stack consists of the names of subcircuits and
+/// regions containing an operator followed by the name of the operator.
+/// Operators with the same name in the same region are aggregated into a
+/// single stack.  Operator times are thread CPU times where supported by the
+/// platform and wall-clock times otherwise.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct FoldedCPUProfile {
+    stacks: BTreeMap<String, Duration>,
+}
+
+impl FoldedCPUProfile {
+    /// Create an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `time` to the stack consisting of `frames`, outermost first.
+    pub fn add(&mut self, frames: &[String], time: Duration) {
+        let mut stack = String::new();
+
+        for (i, frame) in frames.iter().enumerate() {
+            if i > 0 {
+                stack.push(';');
+            }
+            // Semicolons separate frames and newlines separate stacks.
+            stack.extend(frame.chars().map(|c| match c {
+                ';' => ':',
+                '\n' | '\r' => ' ',
+                c => c,
+            }));
+        }
+
+        *self.stacks.entry(stack).or_default() += time;
+    }
+
+    /// Add all stacks in `other` to `self`.
+    pub fn merge(&mut self, other: &Self) {
+        for (stack, time) in other.stacks.iter() {
+            *self.stacks.entry(stack.clone()).or_default() += *time;
+        }
+    }
+
+    /// Iterate over stacks in the profile, with frames separated by
+    /// semicolons, and their times.
+    pub fn stacks(&self) -> impl Iterator<Item = (&str, Duration)> {
+        self.stacks
+            .iter()
+            .map(|(stack, time)| (stack.as_str(), *time))
+    }
+
+    /// Total time across all stacks.
+    pub fn total_time(&self) -> Duration {
+        self.stacks.values().sum()
+    }
+
+    /// Format the profile in the folded stacks format, with one
+    /// `frame;frame;...;frame <microseconds>` line per stack.  Stacks whose
+    /// time rounds down to zero are omitted.
+    pub fn to_folded(&self) -> String {
+        let mut folded = String::new();
+
+        for (stack, time) in self.stacks() {
+            let micros = time.as_micros();
+            if micros > 0 {
+                writeln!(folded, "{stack} {micros}").unwrap();
+            }
+        }
+
+        folded
+    }
 }
 
 /// Circuit CPU profile.
@@ -54,7 +165,7 @@ pub struct CircuitCPUProfile {
 
 #[derive(Default, Debug)]
 struct CPUProfilerInner {
-    start_times: HashMap<GlobalNodeId, Instant>,
+    start_times: HashMap<GlobalNodeId, (Instant, Option<Duration>)>,
     operators: HashMap<GlobalNodeId, OperatorCPUProfile>,
     wait_start_times: HashMap<GlobalNodeId, Instant>,
     step_start_times: HashMap<GlobalNodeId, Instant>,
@@ -79,17 +190,26 @@ impl CPUProfilerInner {
                 };
             }
             SchedulerEvent::EvalStart { node } => {
-                self.start_times
-                    .insert(node.global_id().clone(), Instant::now());
+                self.start_times.insert(
+                    node.global_id().clone(),
+                    (Instant::now(), thread_cpu_time()),
+                );
             }
             SchedulerEvent::EvalEnd { node } => {
-                if let Some(start_time) = self.start_times.remove(node.global_id()) {
+                if let Some((start_time, start_cpu_time)) =
+                    self.start_times.remove(node.global_id())
+                {
                     let duration = Instant::now().duration_since(start_time);
                     let op_profile = self
                         .operators
                         .entry(node.global_id().clone())
                         .or_insert_with(Default::default);
                     op_profile.add_event(duration);
+                    if let (Some(start_cpu_time), Some(end_cpu_time)) =
+                        (start_cpu_time, thread_cpu_time())
+                    {
+                        op_profile.add_cpu_time(end_cpu_time.saturating_sub(start_cpu_time));
+                    }
                     // println!("{}:{}:{:?}", crate::Runtime::worker_index(),
                     // node.global_id(), duration);
                 };
@@ -148,6 +268,19 @@ impl CPUProfiler {
         }
     }
 
+    /// Returns CPU usage information of all circuit nodes (operators and
+    /// subcircuits) whose activations the profiler has observed.
+    pub fn operator_profiles(&self) -> Vec<(GlobalNodeId, OperatorCPUProfile)> {
+        if let Ok(this) = self.0.try_borrow() {
+            this.operators
+                .iter()
+                .map(|(node, profile)| (node.clone(), profile.clone()))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
     /// Returns the CPU profile of the circuit given its global node id.
     pub fn circuit_profile(&self, node: &GlobalNodeId) -> Option<CircuitCPUProfile> {
         if let Ok(this) = self.0.try_borrow() {
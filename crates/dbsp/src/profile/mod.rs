@@ -7,12 +7,12 @@ use crate::{
         GlobalNodeId,
     },
     monitor::TraceMonitor,
-    RootCircuit,
+    RootCircuit, Runtime,
 };
 use std::{borrow::Cow, collections::HashMap, fmt::Write};
 
 mod cpu;
-pub use cpu::CPUProfiler;
+pub use cpu::{CPUProfiler, FoldedCPUProfile};
 
 /// Rudimentary circuit profiler.
 ///
@@ -46,12 +46,64 @@ impl Profiler {
         self.cpu_profiler.attach(&self.circuit, "cpu_profiler");
     }
 
+    /// Total number of bytes used by the state of all operators in the
+    /// circuit (e.g., traces), as reported in operator metadata.
+    pub fn used_bytes(&self) -> usize {
+        let mut used_bytes = 0;
+
+        self.circuit.map_nodes_recursive(&mut |node: &dyn Node| {
+            let mut meta = OperatorMeta::new();
+            node.metadata(&mut meta);
+
+            for (label, item) in meta.iter() {
+                if let ("used bytes", MetaItem::Bytes(bytes)) = (label.as_ref(), item) {
+                    used_bytes += bytes.0 as usize;
+                }
+            }
+        });
+
+        used_bytes
+    }
+
+    /// CPU profile of the circuit aggregated by region hierarchy (see
+    /// [`FoldedCPUProfile`]).
+    ///
+    /// The profile is empty unless CPU profiling is enabled.  Subcircuits are
+    /// represented by the operators they contain, so the time a subcircuit
+    /// spends outside of its operators is not included in the profile.
+    pub fn cpu_profile(&self) -> FoldedCPUProfile {
+        let mut profile = FoldedCPUProfile::new();
+
+        for (node_id, op_profile) in self.cpu_profiler.operator_profiles() {
+            if let Some(stack) = self.monitor.operator_stack(&node_id) {
+                profile.add(
+                    &stack,
+                    op_profile.cpu_time().unwrap_or(op_profile.total_time()),
+                );
+            }
+        }
+
+        profile
+    }
+
     /// Dump profile in graphviz format.
     pub fn dump_profile(&self) -> String {
         let mut metadata = HashMap::<GlobalNodeId, OperatorMeta>::new();
 
-        // Make sure we add metadata for the root node.
-        metadata.insert(GlobalNodeId::root(), OperatorMeta::new());
+        // Make sure we add metadata for the root node, including the core
+        // the worker is running on.
+        let mut root_meta = OperatorMeta::new();
+        if let Some(runtime) = Runtime::runtime() {
+            let worker = Runtime::worker_index();
+            root_meta.push((Cow::Borrowed("worker"), MetaItem::Int(worker)));
+
+            let placement = runtime.worker_placement()[worker];
+            let core = placement
+                .core()
+                .map_or_else(|| "unpinned".to_owned(), |core| core.to_string());
+            root_meta.push((Cow::Borrowed("core"), MetaItem::String(core)));
+        }
+        metadata.insert(GlobalNodeId::root(), root_meta);
 
         // Collect node metadata.
         self.circuit.map_nodes_recursive(&mut |node: &dyn Node| {
@@ -63,6 +115,10 @@ impl Profiler {
         // Add CPU profiling info.
         for (node_id, meta) in metadata.iter_mut() {
             if let Some(profile) = self.cpu_profiler.operator_profile(node_id) {
+                if let Some(cpu_time) = profile.cpu_time() {
+                    meta.insert(0, (Cow::Borrowed("cpu_time"), MetaItem::Duration(cpu_time)));
+                }
+
                 let default_meta = [
                     (
                         Cow::Borrowed("invocations"),
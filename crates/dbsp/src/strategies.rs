@@ -0,0 +1,277 @@
+//! [`proptest`] strategies for generating batches and input traces.
+//!
+//! Batches are generated from vectors of tuples and traces from vectors of
+//! batches, so that when a property fails, `proptest` first removes whole
+//! batches from the trace, then individual tuples from each batch, and only
+//! then shrinks the keys, values, and weights of the remaining tuples.
+//!
+//! Strategies are tunable through their arguments: the key (and value)
+//! space, the range of weights, the number of tuples per batch, and the
+//! number of batches per trace.  [`OrdZSet`] and [`OrdIndexedZSet`] also
+//! implement [`Arbitrary`], parameterized by [`BatchParams`].
+//!
+//! This module is available in tests and, for downstream crates, when the
+//! `proptest` feature is enabled.
+
+use crate::{trace::Batch, DBData, OrdIndexedZSet, OrdZSet};
+use proptest::{
+    arbitrary::{any_with, Arbitrary},
+    collection::{vec, SizeRange},
+    strategy::{BoxedStrategy, Strategy},
+};
+use std::ops::RangeInclusive;
+
+/// Parameters of the [`Arbitrary`] implementations for [`OrdZSet`] and
+/// [`OrdIndexedZSet`].
+#[derive(Clone, Debug)]
+pub struct BatchParams<K, V = ()> {
+    /// Parameters used to generate keys.
+    pub keys: K,
+    /// Parameters used to generate values.
+    pub vals: V,
+    /// Range of weights of generated tuples.  Tuples with zero weight are
+    /// never generated.
+    pub weights: RangeInclusive<isize>,
+    /// Number of tuples to generate.  Batches may end up with fewer tuples
+    /// than generated, since tuples with equal keys and values are
+    /// consolidated.
+    pub tuples: SizeRange,
+}
+
+impl<K, V> Default for BatchParams<K, V>
+where
+    K: Default,
+    V: Default,
+{
+    fn default() -> Self {
+        Self {
+            keys: K::default(),
+            vals: V::default(),
+            weights: -2..=2,
+            tuples: (0..32).into(),
+        }
+    }
+}
+
+/// Generates non-zero weights in the range `weights`.
+pub fn weights(weights: RangeInclusive<isize>) -> impl Strategy<Value = isize> {
+    weights.prop_filter("weights must be non-zero", |&weight| weight != 0)
+}
+
+/// Generates vectors of `(key, weight)` updates, in the format accepted by
+/// [`CollectionHandle::append`](`crate::CollectionHandle::append`) for
+/// Z-set inputs.
+pub fn updates<K>(
+    keys: K,
+    weights: RangeInclusive<isize>,
+    tuples: impl Into<SizeRange>,
+) -> impl Strategy<Value = Vec<(K::Value, isize)>>
+where
+    K: Strategy,
+{
+    vec((keys, self::weights(weights)), tuples)
+}
+
+/// Generates vectors of `(key, (value, weight))` updates, in the format
+/// accepted by [`CollectionHandle::append`](`crate::CollectionHandle::append`)
+/// for indexed Z-set inputs.
+pub fn indexed_updates<K, V>(
+    keys: K,
+    vals: V,
+    weights: RangeInclusive<isize>,
+    tuples: impl Into<SizeRange>,
+) -> impl Strategy<Value = Vec<(K::Value, (V::Value, isize))>>
+where
+    K: Strategy,
+    V: Strategy,
+{
+    vec((keys, (vals, self::weights(weights))), tuples)
+}
+
+/// Generates Z-sets with keys drawn from `keys`.
+pub fn zset<K>(
+    keys: K,
+    weights: RangeInclusive<isize>,
+    tuples: impl Into<SizeRange>,
+) -> impl Strategy<Value = OrdZSet<K::Value, isize>>
+where
+    K: Strategy,
+    K::Value: DBData,
+{
+    updates(keys, weights, tuples).prop_map(|tuples| OrdZSet::from_keys((), tuples))
+}
+
+/// Generates indexed Z-sets with keys drawn from `keys` and values drawn
+/// from `vals`.
+pub fn indexed_zset<K, V>(
+    keys: K,
+    vals: V,
+    weights: RangeInclusive<isize>,
+    tuples: impl Into<SizeRange>,
+) -> impl Strategy<Value = OrdIndexedZSet<K::Value, V::Value, isize>>
+where
+    K: Strategy,
+    K::Value: DBData,
+    V: Strategy,
+    V::Value: DBData,
+{
+    indexed_updates(keys, vals, weights, tuples).prop_map(|tuples| {
+        OrdIndexedZSet::from_tuples(
+            (),
+            tuples
+                .into_iter()
+                .map(|(key, (val, weight))| ((key, val), weight))
+                .collect(),
+        )
+    })
+}
+
+/// Generates input traces consisting of `batches` batches drawn from
+/// `batch`.
+pub fn trace<B>(batch: B, batches: impl Into<SizeRange>) -> impl Strategy<Value = Vec<B::Value>>
+where
+    B: Strategy,
+{
+    vec(batch, batches)
+}
+
+/// Generates sorted vectors of elements drawn from `element`, which may
+/// contain duplicates.
+pub fn sorted<T>(element: T, length: impl Into<SizeRange>) -> impl Strategy<Value = Vec<T::Value>>
+where
+    T: Strategy,
+    T::Value: Ord,
+{
+    vec(element, length).prop_map(|mut vec| {
+        vec.sort();
+        vec
+    })
+}
+
+impl<K> Arbitrary for OrdZSet<K, isize>
+where
+    K: DBData + Arbitrary,
+{
+    type Parameters = BatchParams<K::Parameters>;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+        zset(any_with::<K>(params.keys), params.weights, params.tuples).boxed()
+    }
+}
+
+impl<K, V> Arbitrary for OrdIndexedZSet<K, V, isize>
+where
+    K: DBData + Arbitrary,
+    V: DBData + Arbitrary,
+{
+    type Parameters = BatchParams<K::Parameters, V::Parameters>;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+        indexed_zset(
+            any_with::<K>(params.keys),
+            any_with::<V>(params.vals),
+            params.weights,
+            params.tuples,
+        )
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{indexed_zset, trace, zset, BatchParams};
+    use crate::{
+        trace::{Batch, BatchReader, Cursor},
+        OrdIndexedZSet, OrdZSet,
+    };
+    use proptest::{
+        prelude::*,
+        strategy::ValueTree,
+        test_runner::{Config, TestError, TestRunner},
+    };
+
+    fn runner() -> TestRunner {
+        TestRunner::new(Config {
+            failure_persistence: None,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn shrink_zset_trace() {
+        let result = runner().run(&trace(zset(0..1_000u64, 1..=5, 0..50), 0..20), |trace| {
+            prop_assert!(trace.iter().all(|batch| batch.is_empty()));
+            Ok(())
+        });
+
+        match result {
+            Err(TestError::Fail(_, trace)) => {
+                assert_eq!(trace, vec![OrdZSet::from_keys((), vec![(0, 1)])]);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn shrink_indexed_zset_trace() {
+        let result = runner().run(
+            &trace(indexed_zset(10..1_000u64, 0..1_000i64, 1..=3, 0..50), 0..20),
+            |trace| {
+                prop_assert!(trace.iter().all(|batch| batch.len() < 1));
+                Ok(())
+            },
+        );
+
+        match result {
+            Err(TestError::Fail(_, trace)) => {
+                assert_eq!(
+                    trace,
+                    vec![OrdIndexedZSet::from_tuples((), vec![((10, 0), 1)])]
+                );
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn batch_params() {
+        let params = BatchParams {
+            weights: 1..=1,
+            tuples: (5..=5).into(),
+            ..BatchParams::default()
+        };
+
+        let mut runner = runner();
+        for _ in 0..100 {
+            let batch = any_with::<OrdZSet<bool, isize>>(params.clone())
+                .new_tree(&mut runner)
+                .unwrap()
+                .current();
+
+            assert!(batch.len() <= 2);
+            let mut cursor = batch.cursor();
+            while cursor.key_valid() {
+                assert!((1..=5).contains(&cursor.weight()));
+                cursor.step_key();
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_indexed_zset(batch in any::<OrdIndexedZSet<u8, u8, isize>>()) {
+            prop_assert!(batch.len() <= 32);
+
+            let mut cursor = batch.cursor();
+            while cursor.key_valid() {
+                while cursor.val_valid() {
+                    prop_assert_ne!(cursor.weight(), 0);
+                    cursor.step_val();
+                }
+                cursor.step_key();
+            }
+        }
+    }
+}
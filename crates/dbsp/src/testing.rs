@@ -0,0 +1,375 @@
+//! Utilities for testing circuits step by step.
+//!
+//! [`CircuitTester`] runs a circuit in a single- or multi-worker runtime
+//! and checks the contents of its output streams after each clock cycle.
+//! Outputs are created inside the circuit constructor using
+//! [`TestOutput::new`], which makes both the changes to the stream during
+//! the last clock cycle and the integral of the stream available to the
+//! tester.  When an output does not match the expected tuples, the tester
+//! panics with a diff of the two collections (see [`batch_diff`]).
+//!
+//! # Examples
+//!
+//! ```
+//! use dbsp::{
+//!     operator::FilterMap,
+//!     testing::{CircuitTester, TestOutput},
+//! };
+//!
+//! let (mut tester, (mut input, output)) = CircuitTester::new(4, |circuit| {
+//!     let (stream, input) = circuit.add_input_zset::<u64, isize>();
+//!     let output = TestOutput::new(&stream.map(|x| x % 2));
+//!     (input, output)
+//! });
+//!
+//! tester.push(&mut input, vec![(1, 1), (2, 1), (3, 1)]);
+//! tester.step();
+//! tester.expect_delta(&output, vec![(0, 1), (1, 2)]);
+//!
+//! tester.push(&mut input, vec![(1, -1)]);
+//! tester.step();
+//! tester.expect_delta(&output, vec![(1, -1)]);
+//! tester.expect_integrated(&output, vec![(0, 1), (1, 1)]);
+//!
+//! tester.kill();
+//! ```
+
+use crate::{
+    algebra::IndexedZSet,
+    trace::{Batch, BatchReader, Cursor},
+    CollectionHandle, DBData, DBSPHandle, OutputHandle, RootCircuit, Runtime, Stream,
+};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// Output of a circuit under test.
+///
+/// Tracks the changes to a stream, as well as its integral, so that they
+/// can be checked with [`CircuitTester::expect_delta`] and
+/// [`CircuitTester::expect_integrated`].
+#[derive(Clone)]
+pub struct TestOutput<B> {
+    delta: OutputHandle<B>,
+    integral: OutputHandle<B>,
+}
+
+impl<B> TestOutput<B>
+where
+    B: IndexedZSet + Send,
+{
+    /// Creates an output for `stream`.
+    ///
+    /// Must be called inside the circuit constructor passed to
+    /// [`CircuitTester::new`].
+    pub fn new(stream: &Stream<RootCircuit, B>) -> Self {
+        Self {
+            delta: stream.output(),
+            integral: stream.integrate().output(),
+        }
+    }
+}
+
+/// Runs a circuit one clock cycle at a time and checks its outputs.
+///
+/// See the [module documentation](self) for an example.
+pub struct CircuitTester {
+    dbsp: DBSPHandle,
+    steps: usize,
+}
+
+impl CircuitTester {
+    /// Builds a circuit in a runtime with `workers` worker threads.
+    ///
+    /// Returns the tester along with the value returned by `constructor`,
+    /// which typically contains input handles and [`TestOutput`]s.  Panics
+    /// if the circuit cannot be constructed.
+    pub fn new<F, T>(workers: usize, constructor: F) -> (Self, T)
+    where
+        F: FnOnce(&mut RootCircuit) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+    {
+        let (dbsp, handles) = Runtime::init_circuit(workers, constructor)
+            .unwrap_or_else(|error| panic!("failed to construct the circuit: {}", error));
+
+        (Self { dbsp, steps: 0 }, handles)
+    }
+
+    /// Returns the handle that controls the circuit.
+    pub fn dbsp(&mut self) -> &mut DBSPHandle {
+        &mut self.dbsp
+    }
+
+    /// Returns the number of clock cycles evaluated so far.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Pushes `tuples` to the input stream of `handle`.
+    ///
+    /// The tuples are processed during the next call to [`Self::step`].
+    pub fn push<K, V>(&self, handle: &mut CollectionHandle<K, V>, mut tuples: Vec<(K, V)>)
+    where
+        K: DBData,
+        V: DBData,
+    {
+        handle.append(&mut tuples);
+    }
+
+    /// Evaluates the circuit for one clock cycle.
+    #[track_caller]
+    pub fn step(&mut self) {
+        self.steps += 1;
+        if let Err(error) = self.dbsp.step() {
+            panic!("step {} failed: {}", self.steps, error);
+        }
+    }
+
+    /// Checks that the changes to `output` during the last clock cycle
+    /// consist of `tuples`.
+    ///
+    /// Reads the output of the last clock cycle, which can only be done
+    /// once per step.
+    #[track_caller]
+    pub fn expect_delta<B>(&self, output: &TestOutput<B>, tuples: Vec<(B::Item, B::R)>)
+    where
+        B: IndexedZSet + Send,
+    {
+        self.expect(&output.delta, tuples, "delta");
+    }
+
+    /// Checks that the integral of `output` after the last clock cycle
+    /// consists of `tuples`.
+    ///
+    /// Reads the output of the last clock cycle, which can only be done
+    /// once per step.
+    #[track_caller]
+    pub fn expect_integrated<B>(&self, output: &TestOutput<B>, tuples: Vec<(B::Item, B::R)>)
+    where
+        B: IndexedZSet + Send,
+    {
+        self.expect(&output.integral, tuples, "integral");
+    }
+
+    #[track_caller]
+    fn expect<B>(&self, output: &OutputHandle<B>, tuples: Vec<(B::Item, B::R)>, what: &str)
+    where
+        B: IndexedZSet + Send,
+    {
+        let expected = B::from_tuples((), tuples);
+        let actual = output.consolidate();
+
+        if let Some(diff) = batch_diff(&expected, &actual) {
+            panic!(
+                "unexpected {} after step {} (- expected, + actual):\n{}",
+                what, self.steps, diff
+            );
+        }
+    }
+
+    /// Terminates the circuit, panicking if any of the workers failed.
+    pub fn kill(self) {
+        if let Err(error) = self.dbsp.kill() {
+            panic!("failed to kill the circuit: {}", error);
+        }
+    }
+}
+
+/// Returns a human-readable diff between the contents of two batches, or
+/// `None` if they are equal.
+///
+/// The diff lists all tuples of both batches, sorted by key and value, one
+/// per line, with keys, values, and weights aligned in columns.  Tuples
+/// that only occur in `expected`, or occur with a different weight, are
+/// marked with `-`, and tuples that only occur in `actual`, or occur with a
+/// different weight, are marked with `+`.  The value column is omitted for
+/// batches whose values are all `()`.
+pub fn batch_diff<B>(expected: &B, actual: &B) -> Option<String>
+where
+    B: BatchReader<Time = ()>,
+{
+    let mut tuples: BTreeMap<_, (Option<B::R>, Option<B::R>)> = BTreeMap::new();
+    for (key, val, weight) in batch_tuples(expected) {
+        tuples.entry((key, val)).or_default().0 = Some(weight);
+    }
+    for (key, val, weight) in batch_tuples(actual) {
+        tuples.entry((key, val)).or_default().1 = Some(weight);
+    }
+
+    if tuples.values().all(|(expected, actual)| expected == actual) {
+        return None;
+    }
+
+    let mut rows = Vec::new();
+    for ((key, val), (expected, actual)) in tuples.iter() {
+        if expected == actual {
+            rows.push((' ', key, val, expected.as_ref().unwrap()));
+            continue;
+        }
+        if let Some(weight) = expected {
+            rows.push(('-', key, val, weight));
+        }
+        if let Some(weight) = actual {
+            rows.push(('+', key, val, weight));
+        }
+    }
+
+    let rows: Vec<_> = rows
+        .into_iter()
+        .map(|(marker, key, val, weight)| {
+            (
+                marker,
+                format!("{:?}", key),
+                format!("{:?}", val),
+                format!("{:?}", weight),
+            )
+        })
+        .collect();
+    let show_vals = rows.iter().any(|(_, _, val, _)| val != "()");
+    let key_width = rows.iter().map(|(_, key, _, _)| key.len()).max().unwrap();
+    let val_width = rows.iter().map(|(_, _, val, _)| val.len()).max().unwrap();
+    let weight_width = rows
+        .iter()
+        .map(|(_, _, _, weight)| weight.len())
+        .max()
+        .unwrap();
+
+    let mut diff = String::new();
+    for (marker, key, val, weight) in rows.iter() {
+        if show_vals {
+            writeln!(
+                diff,
+                "{} {:<key_width$}  {:<val_width$}  {:>weight_width$}",
+                marker,
+                key,
+                val,
+                weight,
+                key_width = key_width,
+                val_width = val_width,
+                weight_width = weight_width,
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                diff,
+                "{} {:<key_width$}  {:>weight_width$}",
+                marker,
+                key,
+                weight,
+                key_width = key_width,
+                weight_width = weight_width,
+            )
+            .unwrap();
+        }
+    }
+
+    Some(diff)
+}
+
+/// Returns the `(key, value, weight)` tuples of `batch`.
+fn batch_tuples<B>(batch: &B) -> Vec<(B::Key, B::Val, B::R)>
+where
+    B: BatchReader<Time = ()>,
+{
+    let mut tuples = Vec::with_capacity(batch.len());
+    let mut cursor = batch.cursor();
+
+    while cursor.key_valid() {
+        while cursor.val_valid() {
+            tuples.push((cursor.key().clone(), cursor.val().clone(), cursor.weight()));
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    tuples
+}
+
+#[cfg(test)]
+mod test {
+    use super::{batch_diff, CircuitTester, TestOutput};
+    use crate::{operator::FilterMap, trace::Batch, OrdIndexedZSet, OrdZSet};
+
+    #[test]
+    fn test_batch_diff() {
+        let expected = OrdIndexedZSet::<u64, String, isize>::from_tuples(
+            (),
+            vec![
+                ((1, "a".to_string()), 1),
+                ((1, "bb".to_string()), 2),
+                ((10, "c".to_string()), 1),
+            ],
+        );
+        let actual = OrdIndexedZSet::<u64, String, isize>::from_tuples(
+            (),
+            vec![
+                ((1, "a".to_string()), 1),
+                ((1, "bb".to_string()), -10),
+                ((2, "d".to_string()), 1),
+            ],
+        );
+
+        assert_eq!(batch_diff(&expected, &expected), None);
+        assert_eq!(
+            batch_diff(&expected, &actual).unwrap(),
+            concat!(
+                "  1   \"a\"     1\n",
+                "- 1   \"bb\"    2\n",
+                "+ 1   \"bb\"  -10\n",
+                "+ 2   \"d\"     1\n",
+                "- 10  \"c\"     1\n",
+            )
+        );
+
+        let expected = OrdZSet::<u64, isize>::from_keys((), vec![(1, 1), (2, 1)]);
+        let actual = OrdZSet::<u64, isize>::from_keys((), vec![(1, 1)]);
+        assert_eq!(batch_diff(&expected, &actual).unwrap(), "  1  1\n- 2  1\n");
+    }
+
+    fn test_tester(workers: usize) {
+        let (mut tester, (mut input, output)) = CircuitTester::new(workers, |circuit| {
+            let (stream, input) = circuit.add_input_indexed_zset::<u64, u64, isize>();
+            let output = TestOutput::new(&stream.map_index(|(k, v)| (*v, *k)));
+            (input, output)
+        });
+
+        tester.push(&mut input, (0..100).map(|i| (i, (i % 10, 1))).collect());
+        tester.step();
+        tester.expect_delta(&output, (0..100).map(|i| ((i % 10, i), 1)).collect());
+
+        tester.push(&mut input, (0..50).map(|i| (i, (i % 10, -1))).collect());
+        tester.step();
+        tester.expect_delta(&output, (0..50).map(|i| ((i % 10, i), -1)).collect());
+        tester.expect_integrated(&output, (50..100).map(|i| ((i % 10, i), 1)).collect());
+
+        tester.step();
+        tester.expect_delta(&output, Vec::new());
+        tester.expect_integrated(&output, (50..100).map(|i| ((i % 10, i), 1)).collect());
+        assert_eq!(tester.steps(), 3);
+
+        tester.kill();
+    }
+
+    #[test]
+    fn test_tester1() {
+        test_tester(1);
+    }
+
+    #[test]
+    fn test_tester4() {
+        test_tester(4);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "unexpected integral after step 1 (- expected, + actual):\n  1  1\n+ 2  1\n"
+    )]
+    fn test_tester_mismatch() {
+        let (mut tester, (mut input, output)) = CircuitTester::new(2, |circuit| {
+            let (stream, input) = circuit.add_input_zset::<u64, isize>();
+            (input, TestOutput::new(&stream))
+        });
+
+        tester.push(&mut input, vec![(1, 1), (2, 1)]);
+        tester.step();
+        tester.expect_integrated(&output, vec![(1, 1)]);
+    }
+}
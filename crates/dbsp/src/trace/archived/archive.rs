@@ -0,0 +1,1129 @@
+//! The archive file format and [`ArchivedBatch`], which reads an archive in
+//! place through a memory map.
+//!
+//! An archive consists of the following sections, all integers are 64-bit
+//! big endian:
+//!
+//! * The [`MAGIC`] header.
+//! * The data section: for each key, the key followed by each of its values,
+//!   each value followed by the number of its `(time, weight)` pairs and the
+//!   pairs themselves.  Keys, values and pairs are encoded with bincode.
+//! * The key table: the offset of each key and the index of its first value,
+//!   followed by a sentinel row holding the end of the data section and the
+//!   number of values.
+//! * The value table: the offset of each value.
+//! * The lower and upper bounds of the batch.
+//! * The footer: the offsets of the key table, value table and bounds, the
+//!   number of keys, values and updates, and [`MAGIC`] again.
+
+use crate::{
+    algebra::PartialOrder,
+    time::{Antichain, AntichainRef},
+    trace::{
+        consolidation::consolidate,
+        cursor::{Consumer, Cursor, ValueConsumer},
+        persistent::BINCODE_CONFIG,
+        BatchReader, DBData, DBTimestamp, DBWeight,
+    },
+    NumEntries,
+};
+use bincode::{error::DecodeError, Decode, Encode};
+use size_of::{Context, SizeOf};
+use std::{
+    cmp::max,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    marker::PhantomData,
+    mem::take,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use uuid::Uuid;
+
+/// The first and last bytes of every archive.
+const MAGIC: &[u8; 8] = b"DBSPARC1";
+
+/// The size of the footer, including the trailing [`MAGIC`].
+const FOOTER_BYTES: usize = 6 * 8 + MAGIC.len();
+
+/// The size of a row of the key table.
+const KEY_ROW_BYTES: usize = 16;
+
+/// The path of an archive.
+///
+/// Temporary archives are written by merges and rewrites of archived batches
+/// and are deleted once the last batch referring to them is dropped.  Archives
+/// opened with [`ArchivedBatch::open`] belong to the caller and are never
+/// deleted.
+struct ArchivePath {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl ArchivePath {
+    /// Returns a fresh path for a temporary archive within `dir`.
+    fn temporary(dir: &Path) -> Self {
+        Self {
+            path: dir.join(format!("{}.archive", Uuid::new_v4())),
+            temporary: true,
+        }
+    }
+}
+
+impl Drop for ArchivePath {
+    fn drop(&mut self) {
+        if self.temporary {
+            if let Err(error) = fs::remove_file(&self.path) {
+                tracing::warn!(
+                    "failed to remove batch archive {}: {error}",
+                    self.path.display(),
+                );
+            }
+        }
+    }
+}
+
+/// The contents of a file mapped into memory read-only.
+#[cfg(unix)]
+struct MappedFile {
+    ptr: *const u8,
+    len: usize,
+}
+
+// Safety: the mapping is read-only and owned by `MappedFile`
+#[cfg(unix)]
+unsafe impl Send for MappedFile {}
+#[cfg(unix)]
+unsafe impl Sync for MappedFile {}
+
+#[cfg(unix)]
+impl MappedFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        use std::{os::unix::io::AsRawFd, ptr};
+
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+
+        // Empty mappings aren't allowed, and are never valid archives anyway
+        if len < MAGIC.len() + FOOTER_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("batch archive {} is truncated", path.display()),
+            ));
+        }
+
+        // Safety: we map the whole file read-only and unmap it on drop, the
+        // mapping stays valid after `file` is closed
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // Safety: `ptr` points to a live mapping of `len` bytes
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // Safety: `ptr` and `len` describe a mapping created by `open`
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+/// Platforms without `mmap` read the whole file into memory instead.
+#[cfg(not(unix))]
+struct MappedFile(Vec<u8>);
+
+#[cfg(not(unix))]
+impl MappedFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        std::fs::read(path).map(Self)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> usize {
+    u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap()) as usize
+}
+
+fn decode<D>(bytes: &[u8]) -> Result<(D, usize), DecodeError>
+where
+    D: Decode,
+{
+    bincode::decode_from_slice(bytes, BINCODE_CONFIG)
+}
+
+struct ArchivedInner<K, V, T, R> {
+    // Declared before `path`, so that the file is unmapped before a temporary
+    // archive is deleted.
+    file: MappedFile,
+    path: ArchivePath,
+    key_table: usize,
+    key_count: usize,
+    val_table: usize,
+    val_count: usize,
+    last_key: Option<K>,
+    lower: Antichain<T>,
+    upper: Antichain<T>,
+    len: usize,
+    _phantom: PhantomData<(V, R)>,
+}
+
+impl<K, V, T, R> SizeOf for ArchivedInner<K, V, T, R>
+where
+    K: SizeOf,
+    T: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        // The updates are read from the mapped file, which is paged in and out
+        // by the OS and doesn't count against the in-memory footprint
+        self.path.path.size_of_children(context);
+        self.last_key.size_of_children(context);
+        self.lower.size_of_children(context);
+        self.upper.size_of_children(context);
+    }
+}
+
+impl<K, V, T, R> ArchivedInner<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn bytes(&self) -> &[u8] {
+        self.file.bytes()
+    }
+
+    /// The offset of key `index`, or of the end of the data section for
+    /// `index == key_count`.
+    fn key_offset(&self, index: usize) -> usize {
+        read_u64(self.bytes(), self.key_table + index * KEY_ROW_BYTES)
+    }
+
+    /// The index of the first value of key `index`, or the number of values
+    /// for `index == key_count`.
+    fn first_val(&self, index: usize) -> usize {
+        read_u64(self.bytes(), self.key_table + index * KEY_ROW_BYTES + 8)
+    }
+
+    fn val_offset(&self, index: usize) -> usize {
+        read_u64(self.bytes(), self.val_table + index * 8)
+    }
+
+    /// Checks that the archive's sections are consistent and that every key,
+    /// value and update decodes, so that cursors never have to.
+    fn validate(&mut self) -> Result<(), String> {
+        let bytes = self.file.bytes();
+        if bytes.len() < MAGIC.len() + FOOTER_BYTES
+            || !bytes.starts_with(MAGIC)
+            || !bytes.ends_with(MAGIC)
+        {
+            return Err("missing header or footer".to_string());
+        }
+
+        let footer = bytes.len() - FOOTER_BYTES;
+        self.key_table = read_u64(bytes, footer);
+        self.key_count = read_u64(bytes, footer + 8);
+        self.val_table = read_u64(bytes, footer + 16);
+        self.val_count = read_u64(bytes, footer + 24);
+        let bounds = read_u64(bytes, footer + 32);
+        self.len = read_u64(bytes, footer + 40);
+
+        // The sections must be laid out back to back
+        let key_table_end = self
+            .key_count
+            .checked_add(1)
+            .and_then(|rows| rows.checked_mul(KEY_ROW_BYTES))
+            .and_then(|table| table.checked_add(self.key_table));
+        let val_table_end = self
+            .val_count
+            .checked_mul(8)
+            .and_then(|table| table.checked_add(self.val_table));
+        if self.key_table < MAGIC.len()
+            || key_table_end != Some(self.val_table)
+            || val_table_end != Some(bounds)
+            || bounds > footer
+        {
+            return Err("section offsets out of bounds".to_string());
+        }
+
+        if self.key_offset(0) != MAGIC.len()
+            || self.key_offset(self.key_count) != self.key_table
+            || self.first_val(0) != 0
+            || self.first_val(self.key_count) != self.val_count
+        {
+            return Err("inconsistent key table".to_string());
+        }
+
+        let mut last_key: Option<K> = None;
+        let mut len = 0;
+        for index in 0..self.key_count {
+            let (start, end) = (self.key_offset(index), self.key_offset(index + 1));
+            let (first_val, last_val) = (self.first_val(index), self.first_val(index + 1));
+            if start >= end || end > self.key_table || first_val >= last_val {
+                return Err(format!("invalid entry for key {index}"));
+            }
+
+            let (key, bytes_read) = decode::<K>(&bytes[start..end])
+                .map_err(|error| format!("failed to decode key {index}: {error}"))?;
+            if last_key.as_ref().map_or(false, |last| last >= &key) {
+                return Err(format!("key {index} is out of order"));
+            }
+
+            let mut offset = start + bytes_read;
+            let mut last_val: Option<V> = None;
+            for val_index in first_val..last_val {
+                if val_index >= self.val_count || self.val_offset(val_index) != offset {
+                    return Err(format!("invalid offset for value {val_index}"));
+                }
+
+                let (val, bytes_read) = decode::<V>(&bytes[offset..end])
+                    .map_err(|error| format!("failed to decode value {val_index}: {error}"))?;
+                if last_val.as_ref().map_or(false, |last| last >= &val) {
+                    return Err(format!("value {val_index} is out of order"));
+                }
+                offset += bytes_read;
+
+                if offset + 8 > end {
+                    return Err(format!("missing updates for value {val_index}"));
+                }
+                let updates = read_u64(bytes, offset);
+                offset += 8;
+                if updates == 0 {
+                    return Err(format!("value {val_index} has no updates"));
+                }
+
+                for _ in 0..updates {
+                    let (_, bytes_read) =
+                        decode::<(T, R)>(&bytes[offset..end]).map_err(|error| {
+                            format!("failed to decode updates of value {val_index}: {error}")
+                        })?;
+                    offset += bytes_read;
+                }
+
+                len += updates;
+                last_val = Some(val);
+            }
+
+            if offset != end {
+                return Err(format!("trailing bytes after key {index}"));
+            }
+            last_key = Some(key);
+        }
+
+        if len != self.len {
+            return Err(format!("expected {} updates, found {len}", self.len));
+        }
+
+        let ((lower, upper), bytes_read) = decode::<(Vec<T>, Vec<T>)>(&bytes[bounds..footer])
+            .map_err(|error| format!("failed to decode batch bounds: {error}"))?;
+        if bounds + bytes_read != footer {
+            return Err("trailing bytes after batch bounds".to_string());
+        }
+
+        self.last_key = last_key;
+        self.lower = Antichain::from(lower);
+        self.upper = Antichain::from(upper);
+
+        Ok(())
+    }
+}
+
+/// A batch read in place from a file written by [`ArchivedBatch::write`].
+///
+/// The file is mapped into memory and validated once when it's opened, after
+/// which cursors decode keys, values and updates straight from the mapped
+/// bytes as they visit them, rather than deserializing the batch into owned
+/// structures.  Clones share the same mapping.  Archives opened with
+/// [`ArchivedBatch::open`] are owned by the caller and are never deleted by the
+/// batch; they must not be modified while open.
+pub struct ArchivedBatch<K, V, T, R> {
+    inner: Arc<ArchivedInner<K, V, T, R>>,
+    lower_key_bound: Option<K>,
+}
+
+impl<K, V, T, R> Clone for ArchivedBatch<K, V, T, R>
+where
+    K: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            lower_key_bound: self.lower_key_bound.clone(),
+        }
+    }
+}
+
+impl<K, V, T, R> SizeOf for ArchivedBatch<K, V, T, R>
+where
+    K: SizeOf,
+    T: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.inner.size_of_with_context(context);
+        self.lower_key_bound.size_of_children(context);
+    }
+}
+
+impl<K, V, T, R> ArchivedBatch<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    /// Writes the contents of `batch` to a new archive at `path`.
+    pub fn write<B>(path: &Path, batch: &B) -> io::Result<()>
+    where
+        B: BatchReader<Key = K, Val = V, Time = T, R = R>,
+    {
+        let path = ArchivePath {
+            path: path.to_path_buf(),
+            temporary: false,
+        };
+
+        let mut writer = ArchiveWriter::new(path)?;
+        writer.copy(&mut batch.cursor(), None, T::clone)?;
+        writer.finish(batch.lower().to_owned(), batch.upper().to_owned())?;
+        Ok(())
+    }
+
+    /// Maps the archive at `path` into memory and validates it.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut inner = ArchivedInner {
+            file: MappedFile::open(path)?,
+            path: ArchivePath {
+                path: path.to_path_buf(),
+                temporary: false,
+            },
+            key_table: 0,
+            key_count: 0,
+            val_table: 0,
+            val_count: 0,
+            last_key: None,
+            lower: Antichain::new(),
+            upper: Antichain::new(),
+            len: 0,
+            _phantom: PhantomData,
+        };
+
+        inner.validate().map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid batch archive {}: {error}", path.display()),
+            )
+        })?;
+
+        Ok(Self {
+            inner: Arc::new(inner),
+            lower_key_bound: None,
+        })
+    }
+
+    /// The path of the batch's archive.
+    pub fn path(&self) -> &Path {
+        &self.inner.path.path
+    }
+
+    /// The directory containing the batch's archive, where merges and
+    /// rewrites of the batch write their temporary archives.
+    pub(super) fn dir(&self) -> &Path {
+        self.path().parent().unwrap_or_else(|| Path::new("."))
+    }
+
+    /// Writes the contents of the batch to a temporary archive next to it,
+    /// skipping values below `lower_val_bound` and applying `map_time` to each
+    /// timestamp.
+    pub(super) fn rewrite<F>(&self, lower_val_bound: Option<&V>, map_time: F) -> io::Result<Self>
+    where
+        F: FnMut(&T) -> T,
+    {
+        let mut writer = ArchiveWriter::new(ArchivePath::temporary(self.dir()))?;
+        writer.copy(&mut self.cursor(), lower_val_bound, map_time)?;
+        writer.finish(self.inner.lower.clone(), self.inner.upper.clone())
+    }
+
+    /// Returns `true` if every timestamp in the batch is less than or equal to
+    /// `frontier`, i.e., receding the batch to `frontier` is a no-op.
+    pub(super) fn precedes(&self, frontier: &T) -> bool {
+        let mut cursor = self.cursor();
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                if !cursor.fold_times(true, |all, time, _| all && time.less_equal(frontier)) {
+                    return false;
+                }
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        true
+    }
+
+    /// Returns the last key of the batch, `None` if every key was truncated.
+    fn last_key(&self) -> Option<&K> {
+        self.inner.last_key.as_ref().filter(|&key| {
+            self.lower_key_bound
+                .as_ref()
+                .map_or(true, |bound| key >= bound)
+        })
+    }
+
+    /// Returns the index of the first key not less than `key`, searching from
+    /// key `from` onwards and decoding only the keys it probes.
+    fn find_key(&self, key: &K, from: usize) -> usize {
+        let (mut low, mut high) = (from, self.inner.key_count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if &self.decode_key(middle) < key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        low
+    }
+
+    /// The index of the first key that isn't truncated.
+    fn first_key(&self) -> usize {
+        match &self.lower_key_bound {
+            Some(bound) => self.find_key(bound, 0),
+            None => 0,
+        }
+    }
+
+    fn decode_key(&self, index: usize) -> K {
+        let bytes = &self.inner.bytes()[self.inner.key_offset(index)..];
+        decode(bytes)
+            .expect("archived batches are validated when opened")
+            .0
+    }
+
+    fn decode_val(&self, index: usize) -> V {
+        let bytes = &self.inner.bytes()[self.inner.val_offset(index)..];
+        decode(bytes)
+            .expect("archived batches are validated when opened")
+            .0
+    }
+
+    /// Returns the `(time, weight)` pairs of value `index`.
+    fn times(&self, index: usize) -> Times<'_, T, R> {
+        let bytes = &self.inner.bytes()[self.inner.val_offset(index)..];
+        let (_, val_bytes) =
+            decode::<V>(bytes).expect("archived batches are validated when opened");
+
+        Times {
+            remaining: read_u64(bytes, val_bytes),
+            bytes: &bytes[val_bytes + 8..],
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K, V, T, R> NumEntries for ArchivedBatch<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    const CONST_NUM_ENTRIES: Option<usize> = None;
+
+    fn num_entries_shallow(&self) -> usize {
+        self.len()
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V, T, R> BatchReader for ArchivedBatch<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    type Key = K;
+    type Val = V;
+    type Time = T;
+    type R = R;
+
+    type Cursor<'s> = ArchivedCursor<'s, K, V, T, R>;
+    type Consumer = ArchivedConsumer<K, V, T, R>;
+
+    fn cursor(&self) -> Self::Cursor<'_> {
+        ArchivedCursor::new(self)
+    }
+
+    fn consumer(self) -> Self::Consumer {
+        ArchivedConsumer::new(self)
+    }
+
+    fn key_count(&self) -> usize {
+        self.inner.key_count
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len
+    }
+
+    fn lower(&self) -> AntichainRef<'_, T> {
+        self.inner.lower.as_ref()
+    }
+
+    fn upper(&self) -> AntichainRef<'_, T> {
+        self.inner.upper.as_ref()
+    }
+
+    fn truncate_keys_below(&mut self, lower_bound: &K) {
+        let bound = match &self.lower_key_bound {
+            Some(bound) => max(bound, lower_bound).clone(),
+            None => lower_bound.clone(),
+        };
+        self.lower_key_bound = Some(bound);
+    }
+}
+
+/// Writes an archive one key at a time.
+pub(super) struct ArchiveWriter<K, V, T, R> {
+    // Declared before `path`, so that the file is closed before an abandoned
+    // temporary archive is deleted.
+    file: BufWriter<File>,
+    path: ArchivePath,
+    /// The number of bytes written so far.
+    offset: usize,
+    /// The rows of the key table written so far.
+    keys: Vec<(usize, usize)>,
+    /// The offsets of the values written so far.
+    vals: Vec<usize>,
+    last_key: Option<K>,
+    len: usize,
+    _phantom: PhantomData<(V, T, R)>,
+}
+
+impl<K, V, T, R> SizeOf for ArchiveWriter<K, V, T, R>
+where
+    K: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.path.path.size_of_children(context);
+        self.keys.size_of_children(context);
+        self.vals.size_of_children(context);
+        self.last_key.size_of_children(context);
+    }
+}
+
+impl<K, V, T, R> ArchiveWriter<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn new(path: ArchivePath) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(&path.path)?);
+        let mut writer = Self {
+            file,
+            path,
+            offset: 0,
+            keys: Vec::new(),
+            vals: Vec::new(),
+            last_key: None,
+            len: 0,
+            _phantom: PhantomData,
+        };
+
+        writer.file.write_all(MAGIC)?;
+        writer.offset += MAGIC.len();
+        Ok(writer)
+    }
+
+    /// Starts writing a temporary archive within `dir`.
+    pub(super) fn temporary(dir: &Path) -> io::Result<Self> {
+        Self::new(ArchivePath::temporary(dir))
+    }
+
+    /// Appends `key` with `values`, each with its consolidated `(time, weight)`
+    /// pairs.  Keys must be pushed in ascending order and values must be
+    /// sorted.  Values without updates are skipped, as is `key` if none of its
+    /// values have any.
+    pub(super) fn push(&mut self, key: &K, values: &[(V, Vec<(T, R)>)]) -> io::Result<()> {
+        let mut values = values
+            .iter()
+            .filter(|(_, times)| !times.is_empty())
+            .peekable();
+        if values.peek().is_none() {
+            return Ok(());
+        }
+
+        self.keys.push((self.offset, self.vals.len()));
+        self.encode(key)?;
+        for (val, times) in values {
+            self.vals.push(self.offset);
+            self.encode(val)?;
+            self.write_u64(times.len())?;
+            for update in times {
+                self.encode(update)?;
+            }
+            self.len += times.len();
+        }
+
+        self.last_key = Some(key.clone());
+        Ok(())
+    }
+
+    /// Appends the remaining contents of `cursor`, skipping values below
+    /// `lower_val_bound` and applying `map_time` to each timestamp.
+    fn copy<'s, C, F>(
+        &mut self,
+        cursor: &mut C,
+        lower_val_bound: Option<&V>,
+        mut map_time: F,
+    ) -> io::Result<()>
+    where
+        C: Cursor<'s, K, V, T, R>,
+        F: FnMut(&T) -> T,
+    {
+        let mut values = Vec::new();
+        while cursor.key_valid() {
+            if let Some(bound) = lower_val_bound {
+                cursor.seek_val(bound);
+            }
+
+            while cursor.val_valid() {
+                let mut times = Vec::new();
+                cursor.map_times(|time, weight| times.push((map_time(time), weight.clone())));
+                consolidate(&mut times);
+
+                values.push((cursor.val().clone(), times));
+                cursor.step_val();
+            }
+
+            self.push(cursor.key(), &values)?;
+            values.clear();
+            cursor.step_key();
+        }
+
+        Ok(())
+    }
+
+    /// Writes the key and value tables, the batch bounds and the footer, and
+    /// maps the finished archive into memory.
+    pub(super) fn finish(
+        mut self,
+        lower: Antichain<T>,
+        upper: Antichain<T>,
+    ) -> io::Result<ArchivedBatch<K, V, T, R>> {
+        let key_table = self.offset;
+        let key_count = self.keys.len();
+        let val_count = self.vals.len();
+        self.keys.push((key_table, val_count));
+        for (key_offset, first_val) in take(&mut self.keys) {
+            self.write_u64(key_offset)?;
+            self.write_u64(first_val)?;
+        }
+
+        let val_table = self.offset;
+        for val_offset in take(&mut self.vals) {
+            self.write_u64(val_offset)?;
+        }
+
+        let bounds = self.offset;
+        self.encode(&(lower.to_vec(), upper.to_vec()))?;
+
+        for field in [key_table, key_count, val_table, val_count, bounds, self.len] {
+            self.write_u64(field)?;
+        }
+        self.file.write_all(MAGIC)?;
+        self.file.flush()?;
+        drop(self.file);
+
+        // We wrote the archive ourselves, so there's no need to validate it.
+        let inner = ArchivedInner {
+            file: MappedFile::open(&self.path.path)?,
+            path: self.path,
+            key_table,
+            key_count,
+            val_table,
+            val_count,
+            last_key: self.last_key,
+            lower,
+            upper,
+            len: self.len,
+            _phantom: PhantomData,
+        };
+
+        Ok(ArchivedBatch {
+            inner: Arc::new(inner),
+            lower_key_bound: None,
+        })
+    }
+
+    fn write_u64(&mut self, value: usize) -> io::Result<()> {
+        self.file.write_all(&(value as u64).to_be_bytes())?;
+        self.offset += 8;
+        Ok(())
+    }
+
+    fn encode<E>(&mut self, value: &E) -> io::Result<()>
+    where
+        E: Encode,
+    {
+        self.offset += bincode::encode_into_std_write(value, &mut self.file, BINCODE_CONFIG)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+        Ok(())
+    }
+}
+
+/// An iterator decoding the `(time, weight)` pairs of an archived value.
+struct Times<'a, T, R> {
+    bytes: &'a [u8],
+    remaining: usize,
+    _phantom: PhantomData<(T, R)>,
+}
+
+impl<'a, T, R> Iterator for Times<'a, T, R>
+where
+    T: Decode,
+    R: Decode,
+{
+    type Item = (T, R);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let (update, bytes_read) =
+            decode(self.bytes).expect("archived batches are validated when opened");
+        self.bytes = &self.bytes[bytes_read..];
+        self.remaining -= 1;
+        Some(update)
+    }
+}
+
+/// A cursor over an [`ArchivedBatch`], decoding the current key and value from
+/// the mapped archive.
+pub struct ArchivedCursor<'s, K, V, T, R> {
+    batch: &'s ArchivedBatch<K, V, T, R>,
+    key_index: usize,
+    /// The index of the current value among all of the batch's values.
+    val_index: usize,
+    key: Option<K>,
+    val: Option<V>,
+}
+
+impl<'s, K, V, T, R> ArchivedCursor<'s, K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    pub(super) fn new(batch: &'s ArchivedBatch<K, V, T, R>) -> Self {
+        let mut cursor = Self {
+            batch,
+            key_index: 0,
+            val_index: 0,
+            key: None,
+            val: None,
+        };
+        cursor.rewind_keys();
+        cursor
+    }
+
+    /// Moves the cursor to the first value of key `index`.
+    fn move_to_key(&mut self, index: usize) {
+        self.key_index = index;
+        if index < self.batch.inner.key_count {
+            self.key = Some(self.batch.decode_key(index));
+            self.move_to_val(self.batch.inner.first_val(index));
+        } else {
+            self.key = None;
+            self.val = None;
+        }
+    }
+
+    fn move_to_val(&mut self, index: usize) {
+        self.val_index = index;
+        self.val = if self.val_valid() {
+            Some(self.batch.decode_val(index))
+        } else {
+            None
+        };
+    }
+
+    /// The index one past the last value of the current key.
+    fn vals_end(&self) -> usize {
+        self.batch.inner.first_val(self.key_index + 1)
+    }
+}
+
+impl<'s, K, V, T, R> Cursor<'s, K, V, T, R> for ArchivedCursor<'s, K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn key_valid(&self) -> bool {
+        self.key_index < self.batch.inner.key_count
+    }
+
+    fn val_valid(&self) -> bool {
+        self.key_valid() && self.val_index < self.vals_end()
+    }
+
+    fn key(&self) -> &K {
+        self.key.as_ref().unwrap()
+    }
+
+    fn val(&self) -> &V {
+        self.val.as_ref().unwrap()
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &T, &R) -> U,
+    {
+        self.batch
+            .times(self.val_index)
+            .fold(init, |acc, (time, weight)| fold(acc, &time, &weight))
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &T, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &T, &R) -> U,
+    {
+        self.batch
+            .times(self.val_index)
+            .filter(|(time, _)| time.less_equal(upper))
+            .fold(init, |acc, (time, weight)| fold(acc, &time, &weight))
+    }
+
+    fn weight(&mut self) -> R
+    where
+        T: PartialEq<()>,
+    {
+        debug_assert!(self.val_valid());
+        self.batch.times(self.val_index).next().unwrap().1
+    }
+
+    fn step_key(&mut self) {
+        if self.key_valid() {
+            self.move_to_key(self.key_index + 1);
+        }
+    }
+
+    fn seek_key(&mut self, key: &K) {
+        if !self.key_valid() || self.key() >= key {
+            return;
+        }
+
+        self.move_to_key(self.batch.find_key(key, self.key_index + 1));
+    }
+
+    fn last_key(&mut self) -> Option<&K> {
+        self.batch.last_key()
+    }
+
+    fn step_val(&mut self) {
+        if self.val_valid() {
+            self.move_to_val(self.val_index + 1);
+        }
+    }
+
+    fn seek_val(&mut self, val: &V) {
+        if !self.val_valid() || self.val() >= val {
+            return;
+        }
+
+        let (mut low, mut high) = (self.val_index + 1, self.vals_end());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if &self.batch.decode_val(middle) < val {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        self.move_to_val(low);
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&V) -> bool + Clone,
+    {
+        while self.val_valid() && !predicate(self.val()) {
+            self.step_val();
+        }
+    }
+
+    fn rewind_keys(&mut self) {
+        self.move_to_key(self.batch.first_key());
+    }
+
+    fn rewind_vals(&mut self) {
+        if self.key_valid() {
+            self.move_to_val(self.batch.inner.first_val(self.key_index));
+        }
+    }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        Some(vec![self.key_index, self.val_index])
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.move_to_key(offsets[0]);
+        if self.key_valid() {
+            self.move_to_val(offsets[1]);
+        }
+    }
+}
+
+/// A consumer of an [`ArchivedBatch`], decoding owned keys, values and updates
+/// from the mapped archive.
+pub struct ArchivedConsumer<K, V, T, R> {
+    batch: ArchivedBatch<K, V, T, R>,
+    key_index: usize,
+    key: Option<K>,
+}
+
+impl<K, V, T, R> ArchivedConsumer<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn new(batch: ArchivedBatch<K, V, T, R>) -> Self {
+        let mut consumer = Self {
+            key_index: batch.first_key(),
+            batch,
+            key: None,
+        };
+        consumer.move_to_key(consumer.key_index);
+        consumer
+    }
+
+    fn move_to_key(&mut self, index: usize) {
+        self.key_index = index;
+        self.key = (index < self.batch.inner.key_count).then(|| self.batch.decode_key(index));
+    }
+}
+
+impl<K, V, T, R> Consumer<K, V, R, T> for ArchivedConsumer<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    type ValueConsumer<'a> = ArchivedValueConsumer<'a, K, V, T, R>
+    where
+        Self: 'a;
+
+    fn key_valid(&self) -> bool {
+        self.key.is_some()
+    }
+
+    fn peek_key(&self) -> &K {
+        self.key.as_ref().unwrap()
+    }
+
+    fn next_key(&mut self) -> (K, Self::ValueConsumer<'_>) {
+        let index = self.key_index;
+        let key = self.key.take().unwrap();
+        self.move_to_key(index + 1);
+
+        let batch = &self.batch;
+        let vals = batch.inner.first_val(index)..batch.inner.first_val(index + 1);
+        let remaining = vals.clone().map(|val| batch.times(val).remaining).sum();
+
+        let values = ArchivedValueConsumer {
+            batch,
+            vals,
+            current: None,
+            remaining,
+        };
+        (key, values)
+    }
+
+    fn seek_key(&mut self, key: &K)
+    where
+        K: Ord,
+    {
+        if self.key.as_ref().map_or(false, |current| current < key) {
+            let index = self.batch.find_key(key, self.key_index + 1);
+            self.move_to_key(index);
+        }
+    }
+}
+
+/// A consumer of the values of a key of an [`ArchivedBatch`], yielding a
+/// `(value, weight, time)` triple for each update.
+pub struct ArchivedValueConsumer<'a, K, V, T, R> {
+    batch: &'a ArchivedBatch<K, V, T, R>,
+    /// The indices of the values that haven't been visited yet.
+    vals: Range<usize>,
+    /// The current value and its remaining updates.
+    current: Option<(V, Times<'a, T, R>)>,
+    remaining: usize,
+}
+
+impl<'a, K, V, T, R> ValueConsumer<'a, V, R, T> for ArchivedValueConsumer<'a, K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn value_valid(&self) -> bool {
+        self.remaining > 0
+    }
+
+    fn next_value(&mut self) -> (V, R, T) {
+        loop {
+            if let Some((val, times)) = &mut self.current {
+                if let Some((time, weight)) = times.next() {
+                    self.remaining -= 1;
+                    return (val.clone(), weight, time);
+                }
+            }
+
+            let index = self.vals.next().unwrap();
+            self.current = Some((self.batch.decode_val(index), self.batch.times(index)));
+        }
+    }
+
+    fn remaining_values(&self) -> usize {
+        self.remaining
+    }
+}
@@ -0,0 +1,156 @@
+//! Cursors over archivable batches.
+
+use super::ArchivedCursor;
+use crate::trace::{cursor::Cursor, layers::SearchHint, Batch};
+
+/// A cursor over an [`ArchivableBatch`](super::ArchivableBatch).
+pub enum ArchivableCursor<'s, B>
+where
+    B: Batch,
+{
+    Owned(B::Cursor<'s>),
+    Archived(ArchivedCursor<'s, B::Key, B::Val, B::Time, B::R>),
+}
+
+impl<'s, B> Cursor<'s, B::Key, B::Val, B::Time, B::R> for ArchivableCursor<'s, B>
+where
+    B: Batch,
+{
+    fn key_valid(&self) -> bool {
+        match self {
+            Self::Owned(cursor) => cursor.key_valid(),
+            Self::Archived(cursor) => cursor.key_valid(),
+        }
+    }
+
+    fn val_valid(&self) -> bool {
+        match self {
+            Self::Owned(cursor) => cursor.val_valid(),
+            Self::Archived(cursor) => cursor.val_valid(),
+        }
+    }
+
+    fn key(&self) -> &B::Key {
+        match self {
+            Self::Owned(cursor) => cursor.key(),
+            Self::Archived(cursor) => cursor.key(),
+        }
+    }
+
+    fn val(&self) -> &B::Val {
+        match self {
+            Self::Owned(cursor) => cursor.val(),
+            Self::Archived(cursor) => cursor.val(),
+        }
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        match self {
+            Self::Owned(cursor) => cursor.fold_times(init, fold),
+            Self::Archived(cursor) => cursor.fold_times(init, fold),
+        }
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &B::Time, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        match self {
+            Self::Owned(cursor) => cursor.fold_times_through(upper, init, fold),
+            Self::Archived(cursor) => cursor.fold_times_through(upper, init, fold),
+        }
+    }
+
+    fn weight(&mut self) -> B::R
+    where
+        B::Time: PartialEq<()>,
+    {
+        match self {
+            Self::Owned(cursor) => cursor.weight(),
+            Self::Archived(cursor) => cursor.weight(),
+        }
+    }
+
+    fn step_key(&mut self) {
+        match self {
+            Self::Owned(cursor) => cursor.step_key(),
+            Self::Archived(cursor) => cursor.step_key(),
+        }
+    }
+
+    fn seek_key(&mut self, key: &B::Key) {
+        match self {
+            Self::Owned(cursor) => cursor.seek_key(key),
+            Self::Archived(cursor) => cursor.seek_key(key),
+        }
+    }
+
+    fn seek_key_with_hint(&mut self, key: &B::Key, hint: SearchHint) {
+        match self {
+            Self::Owned(cursor) => cursor.seek_key_with_hint(key, hint),
+            Self::Archived(cursor) => cursor.seek_key(key),
+        }
+    }
+
+    fn last_key(&mut self) -> Option<&B::Key> {
+        match self {
+            Self::Owned(cursor) => cursor.last_key(),
+            Self::Archived(cursor) => cursor.last_key(),
+        }
+    }
+
+    fn step_val(&mut self) {
+        match self {
+            Self::Owned(cursor) => cursor.step_val(),
+            Self::Archived(cursor) => cursor.step_val(),
+        }
+    }
+
+    fn seek_val(&mut self, val: &B::Val) {
+        match self {
+            Self::Owned(cursor) => cursor.seek_val(val),
+            Self::Archived(cursor) => cursor.seek_val(val),
+        }
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        match self {
+            Self::Owned(cursor) => cursor.seek_val_with(predicate),
+            Self::Archived(cursor) => cursor.seek_val_with(predicate),
+        }
+    }
+
+    fn rewind_keys(&mut self) {
+        match self {
+            Self::Owned(cursor) => cursor.rewind_keys(),
+            Self::Archived(cursor) => cursor.rewind_keys(),
+        }
+    }
+
+    fn rewind_vals(&mut self) {
+        match self {
+            Self::Owned(cursor) => cursor.rewind_vals(),
+            Self::Archived(cursor) => cursor.rewind_vals(),
+        }
+    }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        match self {
+            Self::Owned(cursor) => cursor.mark_offsets(),
+            Self::Archived(cursor) => cursor.mark_offsets(),
+        }
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        match self {
+            Self::Owned(cursor) => cursor.rewind_to_offsets(offsets),
+            Self::Archived(cursor) => cursor.rewind_to_offsets(offsets),
+        }
+    }
+}
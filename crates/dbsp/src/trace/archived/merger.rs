@@ -0,0 +1,219 @@
+//! Merging of archivable batches.
+
+use super::{archive::ArchiveWriter, ArchivableBatch, ArchivedBatch};
+use crate::{
+    time::Antichain,
+    trace::{
+        consolidation::consolidate, cursor::Cursor, Batch, BatchReader, DBData, DBTimestamp,
+        DBWeight, Merger,
+    },
+};
+use size_of::{Context, SizeOf};
+use std::cmp::max;
+
+/// A merger of two [`ArchivableBatch`]es.
+///
+/// Owned batches are merged in memory by the underlying batch type's merger.
+/// Merges involving an archived batch are streamed into a new temporary
+/// archive next to it, one key at a time.
+pub enum ArchivableMerger<B>
+where
+    B: Batch,
+{
+    Owned(B::Merger),
+    Archiving(ArchivingMerger<B::Key, B::Val, B::Time, B::R>),
+}
+
+impl<B> SizeOf for ArchivableMerger<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        match self {
+            Self::Owned(merger) => merger.size_of_children(context),
+            Self::Archiving(merger) => merger.size_of_children(context),
+        }
+    }
+}
+
+impl<B> Merger<B::Key, B::Val, B::Time, B::R, ArchivableBatch<B>> for ArchivableMerger<B>
+where
+    B: Batch,
+{
+    fn new_merger(source1: &ArchivableBatch<B>, source2: &ArchivableBatch<B>) -> Self {
+        let dir = match (source1, source2) {
+            (ArchivableBatch::Owned(batch1), ArchivableBatch::Owned(batch2)) => {
+                return Self::Owned(B::Merger::new_merger(batch1, batch2));
+            }
+            (ArchivableBatch::Archived(batch), _) | (_, ArchivableBatch::Archived(batch)) => {
+                batch.dir()
+            }
+        };
+
+        let writer = ArchiveWriter::temporary(dir).unwrap_or_else(|error| {
+            panic!(
+                "failed to create a batch archive in {}: {error}",
+                dir.display(),
+            )
+        });
+
+        Self::Archiving(ArchivingMerger {
+            writer,
+            next_key: None,
+            done: false,
+            lower: source1.lower().meet(source2.lower()),
+            upper: source1.upper().join(source2.upper()),
+        })
+    }
+
+    fn work(
+        &mut self,
+        source1: &ArchivableBatch<B>,
+        source2: &ArchivableBatch<B>,
+        lower_val_bound: &Option<B::Val>,
+        fuel: &mut isize,
+    ) {
+        match self {
+            Self::Owned(merger) => match (source1, source2) {
+                (ArchivableBatch::Owned(batch1), ArchivableBatch::Owned(batch2)) => {
+                    merger.work(batch1, batch2, lower_val_bound, fuel)
+                }
+                _ => unreachable!("in-memory merge of an archived batch"),
+            },
+            Self::Archiving(merger) => merger.work(source1, source2, lower_val_bound, fuel),
+        }
+    }
+
+    fn done(self) -> ArchivableBatch<B> {
+        match self {
+            Self::Owned(merger) => ArchivableBatch::Owned(merger.done()),
+            Self::Archiving(merger) => ArchivableBatch::Archived(merger.done()),
+        }
+    }
+}
+
+/// A merge that writes its output to a temporary archive one key at a time.
+pub struct ArchivingMerger<K, V, T, R> {
+    writer: ArchiveWriter<K, V, T, R>,
+    /// The key to resume merging from, `None` before the first call to `work`.
+    next_key: Option<K>,
+    done: bool,
+    lower: Antichain<T>,
+    upper: Antichain<T>,
+}
+
+impl<K, V, T, R> SizeOf for ArchivingMerger<K, V, T, R>
+where
+    K: SizeOf,
+    T: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.writer.size_of_children(context);
+        self.next_key.size_of_children(context);
+        self.lower.size_of_children(context);
+        self.upper.size_of_children(context);
+    }
+}
+
+impl<K, V, T, R> ArchivingMerger<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn work<B>(&mut self, source1: &B, source2: &B, lower_val_bound: &Option<V>, fuel: &mut isize)
+    where
+        B: BatchReader<Key = K, Val = V, Time = T, R = R>,
+    {
+        if self.done {
+            *fuel = max(*fuel, 1);
+            return;
+        }
+
+        let mut cursor1 = source1.cursor();
+        let mut cursor2 = source2.cursor();
+        if let Some(key) = &self.next_key {
+            cursor1.seek_key(key);
+            cursor2.seek_key(key);
+        }
+
+        let mut updates = Vec::new();
+        let mut values: Vec<(V, Vec<(T, R)>)> = Vec::new();
+        while *fuel > 0 {
+            let key = match (cursor1.key_valid(), cursor2.key_valid()) {
+                (true, true) => cursor1.key().min(cursor2.key()).clone(),
+                (true, false) => cursor1.key().clone(),
+                (false, true) => cursor2.key().clone(),
+                (false, false) => break,
+            };
+
+            take_updates(&mut cursor1, &key, lower_val_bound, &mut updates);
+            take_updates(&mut cursor2, &key, lower_val_bound, &mut updates);
+            consolidate(&mut updates);
+
+            *fuel -= max(updates.len(), 1) as isize;
+
+            for ((val, time), weight) in updates.drain(..) {
+                match values.last_mut() {
+                    Some((last, times)) if last == &val => times.push((time, weight)),
+                    _ => values.push((val, vec![(time, weight)])),
+                }
+            }
+
+            self.writer
+                .push(&key, &values)
+                .unwrap_or_else(|error| panic!("failed to write a batch archive: {error}"));
+            values.clear();
+        }
+
+        match (cursor1.key_valid(), cursor2.key_valid()) {
+            (false, false) => {
+                self.done = true;
+                *fuel = max(*fuel, 1);
+            }
+            (true, true) => self.next_key = Some(cursor1.key().min(cursor2.key()).clone()),
+            (true, false) => self.next_key = Some(cursor1.key().clone()),
+            (false, true) => self.next_key = Some(cursor2.key().clone()),
+        }
+    }
+
+    fn done(self) -> ArchivedBatch<K, V, T, R> {
+        debug_assert!(self.done);
+        self.writer
+            .finish(self.lower, self.upper)
+            .unwrap_or_else(|error| panic!("failed to write a batch archive: {error}"))
+    }
+}
+
+/// If `cursor` points to `key`, appends its updates with values not below
+/// `lower_val_bound` to `updates` and steps to the next key.
+fn take_updates<'s, C, K, V, T, R>(
+    cursor: &mut C,
+    key: &K,
+    lower_val_bound: &Option<V>,
+    updates: &mut Vec<((V, T), R)>,
+) where
+    C: Cursor<'s, K, V, T, R>,
+    K: Eq,
+    V: Clone,
+    T: Clone,
+    R: Clone,
+{
+    if !cursor.key_valid() || cursor.key() != key {
+        return;
+    }
+
+    if let Some(bound) = lower_val_bound {
+        cursor.seek_val(bound);
+    }
+
+    while cursor.val_valid() {
+        let val = cursor.val().clone();
+        cursor
+            .map_times(|time, weight| updates.push(((val.clone(), time.clone()), weight.clone())));
+        cursor.step_val();
+    }
+
+    cursor.step_key();
+}
@@ -0,0 +1,319 @@
+//! Batches archived in files and read in place through a memory map.
+//!
+//! [`ArchivedBatch::write`] stores a batch in a self-describing file, which
+//! [`ArchivedBatch::open`] maps into memory and validates once.  Cursors over
+//! an [`ArchivedBatch`] decode only the keys, values and updates they visit,
+//! straight from the mapped bytes.
+//!
+//! [`ArchivableBatch`] wraps an owned batch type, so that a
+//! [`Spine`](crate::trace::spine_fueled::Spine) of `ArchivableBatch`es can
+//! hold archived batches alongside owned ones, e.g., to keep historical data
+//! on disk.  Owned batches are merged in memory by the underlying batch
+//! type's merger.  Merges that involve an archived batch stream their output
+//! into a new temporary archive in the same directory, which is deleted once
+//! it's no longer referenced.
+
+mod archive;
+mod cursor;
+mod merger;
+mod tests;
+
+pub use archive::{ArchivedBatch, ArchivedConsumer, ArchivedCursor, ArchivedValueConsumer};
+pub use cursor::ArchivableCursor;
+pub use merger::{ArchivableMerger, ArchivingMerger};
+
+use crate::{
+    algebra::Lattice,
+    time::{AntichainRef, Timestamp},
+    trace::{cursor::Cursor, Batch, BatchReader, Batcher, Builder},
+    NumEntries,
+};
+use size_of::{Context, SizeOf};
+use std::collections::BTreeMap;
+
+/// A batch that is either owned or archived.
+pub enum ArchivableBatch<B>
+where
+    B: Batch,
+{
+    Owned(B),
+    Archived(ArchivedBatch<B::Key, B::Val, B::Time, B::R>),
+}
+
+impl<B> From<ArchivedBatch<B::Key, B::Val, B::Time, B::R>> for ArchivableBatch<B>
+where
+    B: Batch,
+{
+    fn from(batch: ArchivedBatch<B::Key, B::Val, B::Time, B::R>) -> Self {
+        Self::Archived(batch)
+    }
+}
+
+impl<B> Clone for ArchivableBatch<B>
+where
+    B: Batch,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Owned(batch) => Self::Owned(batch.clone()),
+            Self::Archived(batch) => Self::Archived(batch.clone()),
+        }
+    }
+}
+
+impl<B> SizeOf for ArchivableBatch<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        match self {
+            Self::Owned(batch) => batch.size_of_children(context),
+            Self::Archived(batch) => batch.size_of_children(context),
+        }
+    }
+}
+
+impl<B> NumEntries for ArchivableBatch<B>
+where
+    B: Batch,
+{
+    const CONST_NUM_ENTRIES: Option<usize> = None;
+
+    fn num_entries_shallow(&self) -> usize {
+        self.len()
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<B> ArchivableBatch<B>
+where
+    B: Batch,
+{
+    /// Returns `true` if the batch is archived.
+    pub fn is_archived(&self) -> bool {
+        matches!(self, Self::Archived(_))
+    }
+
+    /// Returns the contents of the batch as an owned batch, decoding it from
+    /// its archive if it's archived.
+    pub fn into_owned(self) -> B {
+        let archived = match self {
+            Self::Owned(batch) => return batch,
+            Self::Archived(archived) => archived,
+        };
+
+        // Builders assign the same time to all of their updates, so we build a
+        // batch per distinct timestamp and merge them.
+        let mut builders: BTreeMap<B::Time, B::Builder> = BTreeMap::new();
+        let mut cursor = archived.cursor();
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                let mut times = Vec::new();
+                cursor.map_times(|time, weight| times.push((time.clone(), weight.clone())));
+
+                for (time, weight) in times {
+                    let item = B::item_from(cursor.key().clone(), cursor.val().clone());
+                    builders
+                        .entry(time.clone())
+                        .or_insert_with(|| B::Builder::new_builder(time))
+                        .push((item, weight));
+                }
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        builders
+            .into_values()
+            .map(|builder| builder.done())
+            .reduce(|merged, batch| merged.merge(&batch))
+            .unwrap_or_else(|| B::empty(B::Time::minimum()))
+    }
+}
+
+impl<B> BatchReader for ArchivableBatch<B>
+where
+    B: Batch,
+{
+    type Key = B::Key;
+    type Val = B::Val;
+    type Time = B::Time;
+    type R = B::R;
+
+    type Cursor<'s> = ArchivableCursor<'s, B>;
+    type Consumer = B::Consumer;
+
+    fn cursor(&self) -> Self::Cursor<'_> {
+        match self {
+            Self::Owned(batch) => ArchivableCursor::Owned(batch.cursor()),
+            Self::Archived(batch) => ArchivableCursor::Archived(batch.cursor()),
+        }
+    }
+
+    fn consumer(self) -> Self::Consumer {
+        self.into_owned().consumer()
+    }
+
+    fn key_count(&self) -> usize {
+        match self {
+            Self::Owned(batch) => batch.key_count(),
+            Self::Archived(batch) => batch.key_count(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Owned(batch) => batch.len(),
+            Self::Archived(batch) => batch.len(),
+        }
+    }
+
+    fn lower(&self) -> AntichainRef<'_, Self::Time> {
+        match self {
+            Self::Owned(batch) => batch.lower(),
+            Self::Archived(batch) => batch.lower(),
+        }
+    }
+
+    fn upper(&self) -> AntichainRef<'_, Self::Time> {
+        match self {
+            Self::Owned(batch) => batch.upper(),
+            Self::Archived(batch) => batch.upper(),
+        }
+    }
+
+    fn truncate_keys_below(&mut self, lower_bound: &Self::Key) {
+        match self {
+            Self::Owned(batch) => batch.truncate_keys_below(lower_bound),
+            Self::Archived(batch) => batch.truncate_keys_below(lower_bound),
+        }
+    }
+}
+
+impl<B> Batch for ArchivableBatch<B>
+where
+    B: Batch,
+{
+    type Item = B::Item;
+    type Batcher = ArchivableBatcher<B>;
+    type Builder = ArchivableBuilder<B>;
+    type Merger = ArchivableMerger<B>;
+
+    fn item_from(key: Self::Key, val: Self::Val) -> Self::Item {
+        B::item_from(key, val)
+    }
+
+    fn from_keys(time: Self::Time, keys: Vec<(Self::Key, Self::R)>) -> Self
+    where
+        Self::Val: From<()>,
+    {
+        Self::Owned(B::from_keys(time, keys))
+    }
+
+    fn recede_to(&mut self, frontier: &Self::Time) {
+        match self {
+            Self::Owned(batch) => batch.recede_to(frontier),
+            Self::Archived(batch) => {
+                if !batch.precedes(frontier) {
+                    *batch = batch
+                        .rewrite(None, |time| time.meet(frontier))
+                        .unwrap_or_else(|error| {
+                            panic!("failed to rewrite an archived batch: {error}")
+                        });
+                }
+            }
+        }
+    }
+
+    fn truncate_values_below(&mut self, lower_bound: &Self::Val) {
+        match self {
+            Self::Owned(batch) => batch.truncate_values_below(lower_bound),
+            Self::Archived(batch) => {
+                *batch = batch
+                    .rewrite(Some(lower_bound), B::Time::clone)
+                    .unwrap_or_else(|error| panic!("failed to rewrite an archived batch: {error}"));
+            }
+        }
+    }
+}
+
+/// A batcher for [`ArchivableBatch`]es, which produces owned batches.
+pub struct ArchivableBatcher<B>(B::Batcher)
+where
+    B: Batch;
+
+impl<B> SizeOf for ArchivableBatcher<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.0.size_of_children(context);
+    }
+}
+
+impl<B> Batcher<B::Item, B::Time, B::R, ArchivableBatch<B>> for ArchivableBatcher<B>
+where
+    B: Batch,
+{
+    fn new_batcher(time: B::Time) -> Self {
+        Self(B::Batcher::new_batcher(time))
+    }
+
+    fn push_batch(&mut self, batch: &mut Vec<(B::Item, B::R)>) {
+        self.0.push_batch(batch);
+    }
+
+    fn push_consolidated_batch(&mut self, batch: &mut Vec<(B::Item, B::R)>) {
+        self.0.push_consolidated_batch(batch);
+    }
+
+    fn tuples(&self) -> usize {
+        self.0.tuples()
+    }
+
+    fn seal(self) -> ArchivableBatch<B> {
+        ArchivableBatch::Owned(self.0.seal())
+    }
+}
+
+/// A builder for [`ArchivableBatch`]es, which produces owned batches.
+pub struct ArchivableBuilder<B>(B::Builder)
+where
+    B: Batch;
+
+impl<B> SizeOf for ArchivableBuilder<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.0.size_of_children(context);
+    }
+}
+
+impl<B> Builder<B::Item, B::Time, B::R, ArchivableBatch<B>> for ArchivableBuilder<B>
+where
+    B: Batch,
+{
+    fn new_builder(time: B::Time) -> Self {
+        Self(B::Builder::new_builder(time))
+    }
+
+    fn with_capacity(time: B::Time, capacity: usize) -> Self {
+        Self(B::Builder::with_capacity(time, capacity))
+    }
+
+    fn push(&mut self, element: (B::Item, B::R)) {
+        self.0.push(element);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional);
+    }
+
+    fn done(self) -> ArchivableBatch<B> {
+        ArchivableBatch::Owned(self.0.done())
+    }
+}
@@ -0,0 +1,246 @@
+//! Tests that check that archived batches behave like owned batches.
+#![cfg(test)]
+
+use super::{ArchivableBatch, ArchivedBatch};
+use crate::trace::{
+    cursor::Cursor,
+    ord::{OrdIndexedZSet, OrdValBatch},
+    spine_fueled::Spine,
+    test_batch::{assert_batch_eq, assert_trace_eq},
+    Batch, BatchReader, Trace,
+};
+use std::{cmp::Ordering, env, fs, io, path::PathBuf};
+use uuid::Uuid;
+
+type TestBatch = OrdValBatch<u64, u64, u32, i64>;
+
+/// A fresh directory to write archives to, removed when dropped.
+struct ArchiveDir(PathBuf);
+
+impl ArchiveDir {
+    fn new() -> Self {
+        let dir = env::temp_dir().join(format!("dbsp-archive-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    /// Returns the number of files in the directory.
+    fn files(&self) -> usize {
+        fs::read_dir(&self.0).unwrap().count()
+    }
+}
+
+impl Drop for ArchiveDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn test_batch(time: u32, step: u64) -> TestBatch {
+    let tuples = (0..500)
+        .map(|i| {
+            (
+                ((i * 7 + step) % 300, (i + step) % 11),
+                if i % 3 == 0 { -1 } else { 1 },
+            )
+        })
+        .collect();
+    TestBatch::from_tuples(time, tuples)
+}
+
+/// Writes `batch` to an archive within `dir` and opens it.
+fn archive<B>(
+    dir: &ArchiveDir,
+    name: &str,
+    batch: &B,
+) -> ArchivedBatch<B::Key, B::Val, B::Time, B::R>
+where
+    B: Batch,
+{
+    let path = dir.0.join(name);
+    ArchivedBatch::write(&path, batch).unwrap();
+    ArchivedBatch::open(&path).unwrap()
+}
+
+/// Joins two indexed Z-sets on their keys with a merge join over their
+/// cursors.
+fn join<B1, B2>(batch1: &B1, batch2: &B2) -> Vec<((u64, u64, u64), i64)>
+where
+    B1: BatchReader<Key = u64, Val = u64, Time = (), R = i64>,
+    B2: BatchReader<Key = u64, Val = u64, Time = (), R = i64>,
+{
+    let mut output = Vec::new();
+    let mut cursor1 = batch1.cursor();
+    let mut cursor2 = batch2.cursor();
+
+    while cursor1.key_valid() && cursor2.key_valid() {
+        match cursor1.key().cmp(cursor2.key()) {
+            Ordering::Less => cursor1.seek_key(cursor2.key()),
+            Ordering::Greater => cursor2.seek_key(cursor1.key()),
+            Ordering::Equal => {
+                while cursor1.val_valid() {
+                    let val1 = *cursor1.val();
+                    let weight1 = cursor1.weight();
+
+                    cursor2.rewind_vals();
+                    while cursor2.val_valid() {
+                        let weight = weight1 * cursor2.weight();
+                        output.push(((*cursor1.key(), val1, *cursor2.val()), weight));
+                        cursor2.step_val();
+                    }
+                    cursor1.step_val();
+                }
+
+                cursor1.step_key();
+                cursor2.step_key();
+            }
+        }
+    }
+
+    output
+}
+
+#[test]
+fn archived_batch_matches_batch() {
+    let dir = ArchiveDir::new();
+    let batch = test_batch(3, 5).merge(&test_batch(4, 6));
+    let archived = archive(&dir, "batch", &batch);
+
+    assert_eq!(archived.len(), batch.len());
+    assert_eq!(archived.key_count(), batch.key_count());
+    assert_eq!(archived.lower(), batch.lower());
+    assert_eq!(archived.upper(), batch.upper());
+    assert_batch_eq(&archived, &batch);
+
+    let mut archived_cursor = archived.cursor();
+    let mut cursor = batch.cursor();
+    for key in [0, 17, 17, 150, 299, 300] {
+        archived_cursor.seek_key(&key);
+        cursor.seek_key(&key);
+        assert_eq!(archived_cursor.key_valid(), cursor.key_valid());
+        if cursor.key_valid() {
+            assert_eq!(archived_cursor.key(), cursor.key());
+
+            archived_cursor.seek_val(&5);
+            cursor.seek_val(&5);
+            assert_eq!(archived_cursor.get_val(), cursor.get_val());
+        }
+    }
+    assert_eq!(archived_cursor.last_key(), cursor.last_key());
+
+    let mut archived = archived;
+    let mut batch = batch;
+    archived.truncate_keys_below(&100);
+    batch.truncate_keys_below(&100);
+    assert_batch_eq(&archived, &batch);
+
+    let mut archived = ArchivableBatch::<TestBatch>::from(archived);
+    archived.truncate_values_below(&5);
+    batch.truncate_values_below(&5);
+    assert!(archived.is_archived());
+    assert_batch_eq(&archived, &batch);
+    assert_batch_eq(&archived.into_owned(), &batch);
+}
+
+#[test]
+fn archived_batch_join() {
+    let dir = ArchiveDir::new();
+    let archived_batch = OrdIndexedZSet::<u64, u64, i64>::from_tuples(
+        (),
+        (0..5000)
+            .map(|i| (((i * 7) % 2000, i % 13), if i % 5 == 0 { -1 } else { 1 }))
+            .collect(),
+    );
+    let owned_batch = OrdIndexedZSet::<u64, u64, i64>::from_tuples(
+        (),
+        (0..300).map(|i| (((i * 31) % 2500, i % 3), 1)).collect(),
+    );
+    let archived = archive(&dir, "join", &archived_batch);
+
+    let expected = join(&archived_batch, &owned_batch);
+    assert!(!expected.is_empty());
+    assert_eq!(join(&archived, &owned_batch), expected);
+
+    let expected = join(&owned_batch, &archived_batch);
+    assert_eq!(join(&owned_batch, &archived), expected);
+}
+
+#[test]
+fn invalid_archive() {
+    let dir = ArchiveDir::new();
+    let path = dir.0.join("invalid");
+    ArchivedBatch::write(&path, &test_batch(0, 0)).unwrap();
+    let bytes = fs::read(&path).unwrap();
+
+    let open = |bytes: &[u8]| {
+        fs::write(&path, bytes).unwrap();
+        ArchivedBatch::<u64, u64, u32, i64>::open(&path)
+            .map(|_| ())
+            .unwrap_err()
+            .kind()
+    };
+
+    // Truncated file
+    assert_eq!(open(&bytes[..bytes.len() - 1]), io::ErrorKind::InvalidData);
+    assert_eq!(open(&bytes[..16]), io::ErrorKind::InvalidData);
+
+    // The first key is now larger than the second one
+    let mut corrupted = bytes.clone();
+    corrupted[8] = 0xff;
+    assert_eq!(open(&corrupted), io::ErrorKind::InvalidData);
+
+    fs::write(&path, &bytes).unwrap();
+    assert!(ArchivedBatch::<u64, u64, u32, i64>::open(&path).is_ok());
+}
+
+#[test]
+fn spine_with_archived_batches() {
+    let dir = ArchiveDir::new();
+    let mut spine = Spine::<ArchivableBatch<TestBatch>>::new(None);
+    let mut reference = Spine::<TestBatch>::new(None);
+
+    for step in 0..20 {
+        let batch = test_batch(step as u32, step);
+        if step % 3 == 0 {
+            spine.insert(archive(&dir, &format!("batch{step}"), &batch).into());
+        } else {
+            spine.insert(ArchivableBatch::Owned(batch.clone()));
+        }
+        reference.insert(batch);
+
+        let mut fuel = 1000;
+        spine.exert(&mut fuel);
+        let mut fuel = 1000;
+        reference.exert(&mut fuel);
+        assert_trace_eq(&spine, &reference);
+
+        if step == 10 {
+            spine.truncate_keys_below(&100);
+            reference.truncate_keys_below(&100);
+        }
+    }
+
+    spine.recede_to(&10);
+    reference.recede_to(&10);
+    assert_trace_eq(&spine, &reference);
+
+    let consolidated = spine.consolidate().unwrap();
+    assert!(consolidated.is_archived());
+    assert_batch_eq(&consolidated, &reference.consolidate().unwrap());
+
+    // Only the archives written by the test and the consolidated batch are
+    // left, temporary archives are deleted once they're merged.
+    assert_eq!(dir.files(), 7 + 1);
+    drop(consolidated);
+    assert_eq!(dir.files(), 7);
+}
+
+#[test]
+fn owned_batches_stay_owned() {
+    let mut spine = Spine::<ArchivableBatch<TestBatch>>::new(None);
+    for step in 0..10 {
+        spine.insert(ArchivableBatch::Owned(test_batch(step as u32, step)));
+    }
+
+    assert!(!spine.consolidate().unwrap().is_archived());
+}
@@ -11,12 +11,15 @@ mod tests;
 // Public for benchmarks
 // FIXME: Add a benchmarking feature
 #[doc(hidden)]
+pub mod pod;
+#[doc(hidden)]
 pub mod utils;
 
 use crate::{
     algebra::{AddAssignByRef, HasZero, MonoidValue},
     utils::assume,
 };
+use pod::try_compact_pod;
 use std::{
     mem::{replace, size_of},
     ops::AddAssign,
@@ -181,8 +184,8 @@ where
 /// discarded.
 pub fn consolidate_payload_from<K, R>(keys: &mut Vec<K>, diffs: &mut Vec<R>, offset: usize)
 where
-    K: Ord,
-    R: HasZero + AddAssign,
+    K: Ord + 'static,
+    R: HasZero + AddAssign + 'static,
 {
     // Ensure that the paired slices are the same length
     assert_eq!(keys.len(), diffs.len());
@@ -195,6 +198,13 @@ where
     // program. It makes up 90% of the work done while joining or merging anything
     quicksort::quicksort(&mut keys[offset..], &mut diffs[offset..]);
 
+    // Use the vectorized implementation for plain-old-data keys and weights
+    if let Some(len) = try_compact_pod(&mut keys[offset..], &mut diffs[offset..]) {
+        keys.truncate(offset + len);
+        diffs.truncate(offset + len);
+        return;
+    }
+
     // Deduplicate all difference values
     dedup_payload_starting_at(keys, &mut *diffs, offset, |key1, diff1, key2, diff2| {
         if key1 == key2 {
@@ -211,8 +221,8 @@ where
 
 pub fn consolidate_paired_slices<K, R>(keys: &mut [K], diffs: &mut [R]) -> usize
 where
-    K: Ord,
-    R: AddAssignByRef + HasZero,
+    K: Ord + 'static,
+    R: AddAssignByRef + HasZero + 'static,
 {
     // Ensure that the paired slices are the same length
     assert_eq!(keys.len(), diffs.len());
@@ -226,6 +236,56 @@ where
     // anything
     quicksort::quicksort(keys, diffs);
 
+    // Use the vectorized implementation for plain-old-data keys and weights
+    if let Some(len) = try_compact_pod(keys, diffs) {
+        return len;
+    }
+
+    // Safety: the keys & diffs slices are the same length and are non-empty
+    unsafe { compact_paired_slices(keys, diffs) }
+}
+
+/// Compacts already-sorted keys and their diffs, returning the compacted
+/// prefix length.
+///
+/// Like [`consolidate_paired_slices`], but expects `keys` to be sorted
+/// instead of sorting them.
+pub fn compact_sorted_paired_slices<K, R>(keys: &mut [K], diffs: &mut [R]) -> usize
+where
+    K: Eq + 'static,
+    R: AddAssignByRef + HasZero + 'static,
+{
+    // Ensure that the paired slices are the same length
+    assert_eq!(keys.len(), diffs.len());
+    if keys.is_empty() {
+        return 0;
+    }
+
+    // Use the vectorized implementation for plain-old-data keys and weights
+    if let Some(len) = try_compact_pod(keys, diffs) {
+        return len;
+    }
+
+    // Safety: the keys & diffs slices are the same length and are non-empty
+    unsafe { compact_paired_slices(keys, diffs) }
+}
+
+/// Compacts already-sorted values and their diffs using the scalar
+/// implementation, returning the compacted prefix length.
+///
+/// Unlike [`consolidate_paired_slices`], never uses the vectorized
+/// implementation in [`pod`].
+#[doc(hidden)]
+pub fn compact_paired_slices_scalar<K, R>(keys: &mut [K], diffs: &mut [R]) -> usize
+where
+    K: Eq,
+    R: AddAssignByRef + HasZero,
+{
+    assert_eq!(keys.len(), diffs.len());
+    if keys.is_empty() {
+        return 0;
+    }
+
     // Safety: the keys & diffs slices are the same length and are non-empty
     unsafe { compact_paired_slices(keys, diffs) }
 }
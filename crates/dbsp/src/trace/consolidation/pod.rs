@@ -0,0 +1,156 @@
+//! Fast consolidation of sorted runs of plain-old-data keys and weights.
+//!
+//! [`compact_pod`] processes keys in fixed-size chunks using non-branching
+//! comparisons and additions that the compiler can vectorize.  It is selected
+//! automatically by [`consolidate_paired_slices`](super::consolidate_paired_slices)
+//! and [`consolidate_payload_from`](super::consolidate_payload_from) for keys
+//! that implement [`PodConsolidate`] and weights that implement
+//! [`PodWeight`], and produces exactly the same output as the scalar path.
+
+use crate::algebra::HasZero;
+use std::{
+    any::TypeId,
+    ops::{Add, AddAssign},
+};
+
+/// Number of keys processed at once by [`compact_pod`].
+const CHUNK: usize = 8;
+
+/// Fixed-size plain-old-data keys that can be consolidated using
+/// [`compact_pod`].
+pub trait PodConsolidate: Copy + Eq + 'static {}
+
+/// Primitive weights that can be consolidated using [`compact_pod`].
+pub trait PodWeight: Copy + Eq + Add<Output = Self> + AddAssign + HasZero + 'static {}
+
+macro_rules! pod_types {
+    (keys: [$($key:ty),* $(,)?], weights: [$($weight:ty),* $(,)?] $(,)?) => {
+        $(impl PodConsolidate for $key {})*
+        $(impl PodWeight for $weight {})*
+
+        /// Compacts sorted `keys` and `diffs` using [`compact_pod`] if `K`
+        /// and `R` are plain-old-data types supported by it.  Returns `None`
+        /// otherwise.
+        pub(super) fn try_compact_pod<K, R>(keys: &mut [K], diffs: &mut [R]) -> Option<usize>
+        where
+            K: 'static,
+            R: 'static,
+        {
+            $(
+                if let Some(diffs) = cast_slice::<R, $weight>(diffs) {
+                    return try_compact_pod_keys(keys, diffs);
+                }
+            )*
+
+            None
+        }
+
+        fn try_compact_pod_keys<K, R>(keys: &mut [K], diffs: &mut [R]) -> Option<usize>
+        where
+            K: 'static,
+            R: PodWeight,
+        {
+            $(
+                if let Some(keys) = cast_slice::<K, $key>(keys) {
+                    return Some(compact_pod(keys, diffs));
+                }
+            )*
+
+            None
+        }
+    };
+}
+
+pod_types! {
+    keys: [u32, u64, i64, (u32, u32), (u64, u64), (i64, i64)],
+    weights: [i32, i64, isize],
+}
+
+/// Casts `slice` to `&mut [U]` if `T` and `U` are the same type.
+fn cast_slice<T, U>(slice: &mut [T]) -> Option<&mut [U]>
+where
+    T: 'static,
+    U: 'static,
+{
+    if TypeId::of::<T>() == TypeId::of::<U>() {
+        // Safety: `T` and `U` are the same type.
+        Some(unsafe { &mut *(slice as *mut [T] as *mut [U]) })
+    } else {
+        None
+    }
+}
+
+/// Compacts already-sorted keys and their diffs, returning the compacted
+/// prefix length.
+///
+/// Sums the diffs of equal keys and drops keys whose diffs sum up to zero.
+/// The compacted prefix is identical to the one produced by the scalar path;
+/// contents of `keys` and `diffs` past the prefix are unspecified.
+///
+/// # Panics
+///
+/// Panics if `keys` and `diffs` have different lengths.
+pub fn compact_pod<K, R>(keys: &mut [K], diffs: &mut [R]) -> usize
+where
+    K: PodConsolidate,
+    R: PodWeight,
+{
+    assert_eq!(keys.len(), diffs.len());
+
+    let len = keys.len();
+    let mut read = 0;
+    let mut write = 0;
+
+    while read < len {
+        // Fast path: the next `CHUNK` keys are distinct from their successors
+        // and have non-zero diffs, so they are already consolidated.  The
+        // comparisons deliberately don't short-circuit, which allows them to
+        // be vectorized.
+        if read + CHUNK < len {
+            let consolidated = keys[read..=read + CHUNK]
+                .windows(2)
+                .zip(&diffs[read..read + CHUNK])
+                .fold(true, |acc, (pair, diff)| {
+                    acc & (pair[0] != pair[1]) & !diff.is_zero()
+                });
+
+            if consolidated {
+                if write != read {
+                    keys.copy_within(read..read + CHUNK, write);
+                    diffs.copy_within(read..read + CHUNK, write);
+                }
+
+                read += CHUNK;
+                write += CHUNK;
+                continue;
+            }
+        }
+
+        // Slow path: sum up the diffs of the run of keys equal to `keys[read]`.
+        let key = keys[read];
+        let mut sum = diffs[read];
+        let mut end = read + 1;
+
+        // Since keys are sorted, a chunk belongs to the run iff its last key
+        // belongs to the run.
+        while end + CHUNK <= len && keys[end + CHUNK - 1] == key {
+            sum = diffs[end..end + CHUNK]
+                .iter()
+                .fold(sum, |acc, &diff| acc + diff);
+            end += CHUNK;
+        }
+        while end < len && keys[end] == key {
+            sum += diffs[end];
+            end += 1;
+        }
+
+        if !sum.is_zero() {
+            keys[write] = key;
+            diffs[write] = sum;
+            write += 1;
+        }
+        read = end;
+    }
+
+    write
+}
@@ -2,15 +2,16 @@
 
 use crate::{
     trace::consolidation::{
-        consolidate, consolidate_from, consolidate_paired_slices, consolidate_payload_from,
-        consolidate_slice,
+        compact_paired_slices_scalar, consolidate, consolidate_from, consolidate_paired_slices,
+        consolidate_payload_from, consolidate_slice,
+        pod::{compact_pod, PodConsolidate, PodWeight},
         quicksort::quicksort,
         utils::{dedup_payload_starting_at, retain_starting_at},
     },
     utils::VecExt,
 };
 use proptest::{collection::vec, prelude::*};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt::Debug};
 
 prop_compose! {
     /// Create a batch data tuple
@@ -59,6 +60,36 @@ prop_compose! {
     }
 }
 
+prop_compose! {
+    /// Generate sorted keys and their diffs, with long runs of equal keys
+    /// when `max_key` is small and diffs that frequently cancel out
+    fn sorted_pod_data()
+        (len in 0..=5000usize, max_key in 1..100_000u64)
+        (mut keys in vec(0..max_key, len), diffs in vec(-2..=2i64, len))
+    -> (Vec<u64>, Vec<i64>) {
+        keys.sort_unstable();
+        (keys, diffs)
+    }
+}
+
+/// Compacts `keys` and `diffs` using both the vectorized and the scalar
+/// implementations and ensures that their outputs are identical
+fn assert_pod_compaction_equivalent<K, R>(keys: Vec<K>, diffs: Vec<R>)
+where
+    K: PodConsolidate + Debug,
+    R: PodWeight + Debug,
+{
+    let (mut pod_keys, mut pod_diffs) = (keys.clone(), diffs.clone());
+    let pod_len = compact_pod(&mut pod_keys, &mut pod_diffs);
+
+    let (mut scalar_keys, mut scalar_diffs) = (keys, diffs);
+    let scalar_len = compact_paired_slices_scalar(&mut scalar_keys, &mut scalar_diffs);
+
+    assert_eq!(pod_len, scalar_len);
+    assert_eq!(pod_keys[..pod_len], scalar_keys[..scalar_len]);
+    assert_eq!(pod_diffs[..pod_len], scalar_diffs[..scalar_len]);
+}
+
 fn batch_data(batch: &[((usize, usize), isize)]) -> BTreeMap<(usize, usize), i64> {
     let mut values = BTreeMap::new();
     for &(tuple, diff) in batch {
@@ -168,6 +199,43 @@ proptest! {
         prop_assert_eq!(consolidated_diffs, diffs);
     }
 
+    #[test]
+    fn compact_pod_is_equivalent((keys, diffs) in sorted_pod_data()) {
+        assert_pod_compaction_equivalent(keys.clone(), diffs.clone());
+
+        let signed_keys = keys.iter().map(|&key| key as i64 - 50_000).collect();
+        assert_pod_compaction_equivalent(signed_keys, diffs.iter().map(|&diff| diff as isize).collect());
+
+        let narrow_keys = keys.iter().map(|&key| key as u32).collect();
+        assert_pod_compaction_equivalent(narrow_keys, diffs.iter().map(|&diff| diff as i32).collect());
+
+        let tuple_keys = keys.iter().map(|&key| (key as u32 / 10, key as u32 % 10)).collect();
+        assert_pod_compaction_equivalent(tuple_keys, diffs);
+    }
+
+    #[test]
+    fn consolidate_pod_is_equivalent(batch in vec(((0..100u64, 0..100u64), -2..=2isize), 0..=5000)) {
+        let (keys, diffs): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+
+        // Uses the vectorized implementation
+        let (mut pod_keys, mut pod_diffs) = (keys.clone(), diffs.clone());
+        let pod_len = consolidate_paired_slices(&mut pod_keys, &mut pod_diffs);
+
+        let (mut scalar_keys, mut scalar_diffs) = (keys.clone(), diffs.clone());
+        quicksort(&mut scalar_keys, &mut scalar_diffs);
+        let scalar_len = compact_paired_slices_scalar(&mut scalar_keys, &mut scalar_diffs);
+
+        prop_assert_eq!(&pod_keys[..pod_len], &scalar_keys[..scalar_len]);
+        prop_assert_eq!(&pod_diffs[..pod_len], &scalar_diffs[..scalar_len]);
+
+        // Uses the vectorized implementation past the offset
+        let (mut payload_keys, mut payload_diffs) = (keys, diffs);
+        consolidate_payload_from(&mut payload_keys, &mut payload_diffs, 0);
+
+        prop_assert_eq!(&payload_keys[..], &scalar_keys[..scalar_len]);
+        prop_assert_eq!(&payload_diffs[..], &scalar_diffs[..scalar_len]);
+    }
+
     #[test]
     fn dual_quicksort_smoke(mut data in vec(any::<(u32, u32)>(), 0..=5000)) {
         let (mut keys, mut values): (Vec<_>, Vec<_>) = data.clone().into_iter().unzip();
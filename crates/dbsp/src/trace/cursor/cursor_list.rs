@@ -2,7 +2,7 @@
 
 use crate::{
     algebra::{HasZero, MonoidValue},
-    trace::cursor::Cursor,
+    trace::{cursor::Cursor, layers::SearchHint},
 };
 use std::marker::PhantomData;
 
@@ -170,6 +170,13 @@ where
         self.minimize_keys();
     }
 
+    fn seek_key_with_hint(&mut self, key: &K, hint: SearchHint) {
+        for cursor in self.cursors.iter_mut() {
+            cursor.seek_key_with_hint(key, hint);
+        }
+        self.minimize_keys();
+    }
+
     fn last_key(&mut self) -> Option<&K> {
         self.cursors
             .iter_mut()
@@ -215,4 +222,30 @@ where
         }
         self.minimize_vals();
     }
+
+    // The offsets of each cursor, prefixed with their number.
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        let mut offsets = Vec::new();
+
+        for cursor in self.cursors.iter() {
+            let cursor_offsets = cursor.mark_offsets()?;
+            offsets.push(cursor_offsets.len());
+            offsets.extend_from_slice(&cursor_offsets);
+        }
+
+        Some(offsets)
+    }
+
+    fn rewind_to_offsets(&mut self, mut offsets: &[usize]) {
+        for cursor in self.cursors.iter_mut() {
+            let (len, rest) = offsets.split_first().unwrap();
+            let (cursor_offsets, rest) = rest.split_at(*len);
+            cursor.rewind_to_offsets(cursor_offsets);
+            offsets = rest;
+        }
+        debug_assert!(offsets.is_empty());
+
+        // `min_key` and `min_val` only depend on the positions of the cursors.
+        self.minimize_keys();
+    }
 }
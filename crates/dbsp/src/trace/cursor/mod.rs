@@ -19,6 +19,24 @@ pub mod cursor_pair;
 pub use cursor_group::CursorGroup;
 pub use cursor_list::CursorList;
 
+use crate::trace::layers::SearchHint;
+
+/// A position of a [`Cursor`], saved by [`Cursor::mark`] and restored by
+/// [`Cursor::rewind_to_mark`].
+///
+/// A mark can only be restored by the cursor that created it, or by another
+/// cursor over the same data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CursorMark<K, V> {
+    /// The current key and value, or `None` if the cursor is not valid.
+    /// Restoring the mark rewinds the cursor and seeks to the key and value.
+    Seek { key: Option<K>, val: Option<V> },
+    /// Cursor-specific offsets into the underlying storage (see
+    /// [`Cursor::mark_offsets`]).  Restoring the mark repositions the cursor
+    /// without comparing any keys or values.
+    Offsets(Vec<usize>),
+}
+
 /// A cursor for navigating ordered `(key, val, time, diff)` tuples.
 pub trait Cursor<'s, K, V, T, R> {
     /// Indicates if the current key is valid.
@@ -113,6 +131,16 @@ pub trait Cursor<'s, K, V, T, R> {
     /// Advances the cursor to the specified key.
     fn seek_key(&mut self, key: &K);
 
+    /// Advances the cursor to the specified key, using `hint` to guess how
+    /// far ahead of the current position the key is.
+    ///
+    /// The hint only affects performance.  Callers that walk the cursor in
+    /// lockstep with another cursor over similar keys, such as merge joins,
+    /// should pass [`SearchHint::Near`].
+    fn seek_key_with_hint(&mut self, key: &K, _hint: SearchHint) {
+        self.seek_key(key)
+    }
+
     /// Returns the last key in the cursor or `None` if the cursor is empty.
     fn last_key(&mut self) -> Option<&K>;
 
@@ -133,6 +161,69 @@ pub trait Cursor<'s, K, V, T, R> {
 
     /// Rewinds the cursor to the first value for current key.
     fn rewind_vals(&mut self);
+
+    /// Saves the current position of the cursor, so that it can be restored
+    /// later with [`Self::rewind_to_mark`].
+    ///
+    /// Returns [`CursorMark::Offsets`] for cursors that implement
+    /// [`Self::mark_offsets`].  Otherwise, records the current key and value,
+    /// which costs a rewind and two seeks to restore.
+    fn mark(&self) -> CursorMark<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        match self.mark_offsets() {
+            Some(offsets) => CursorMark::Offsets(offsets),
+            None => CursorMark::Seek {
+                key: self.get_key().cloned(),
+                val: self.get_val().cloned(),
+            },
+        }
+    }
+
+    /// Moves the cursor back (or forward) to a position saved by
+    /// [`Self::mark`].
+    fn rewind_to_mark(&mut self, mark: &CursorMark<K, V>) {
+        match mark {
+            CursorMark::Offsets(offsets) => self.rewind_to_offsets(offsets),
+            CursorMark::Seek {
+                key: Some(key),
+                val,
+            } => {
+                self.rewind_keys();
+                self.seek_key(key);
+                match val {
+                    Some(val) => self.seek_val(val),
+                    None => {
+                        while self.val_valid() {
+                            self.step_val();
+                        }
+                    }
+                }
+            }
+            CursorMark::Seek { key: None, .. } => {
+                while self.key_valid() {
+                    self.step_key();
+                }
+            }
+        }
+    }
+
+    /// Returns offsets that identify the current position of the cursor in
+    /// its underlying storage, or `None` if the cursor does not support them.
+    ///
+    /// Cursors that return `Some` must also implement
+    /// [`Self::rewind_to_offsets`].
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        None
+    }
+
+    /// Moves the cursor to the position identified by `offsets`, previously
+    /// returned by [`Self::mark_offsets`].
+    fn rewind_to_offsets(&mut self, _offsets: &[usize]) {
+        panic!("cursor does not support offset marks")
+    }
 }
 
 /// A cursor for traversing unordered values
@@ -2,6 +2,26 @@ use std::{cmp::min, mem::MaybeUninit};
 
 const DEFAULT_SMALL_LIMIT: usize = 8;
 
+/// A hint about the expected distance to the first element that doesn't
+/// satisfy the predicate of [`advance_with_hint`], used to choose between
+/// linear, exponential and binary search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchHint {
+    /// The answer is likely within the first few elements, e.g., when a merge
+    /// join seeks to the next key of the other input.  Scans a short prefix
+    /// of the slice linearly before switching to exponential search.
+    Near,
+    /// The answer is likely far from the start of the slice, e.g., when
+    /// truncating a batch below a watermark.  Performs a binary search over
+    /// the whole slice.
+    Far,
+    /// Nothing is known about the answer.  Probes a single element a short
+    /// distance into the slice to decide between a linear and an exponential
+    /// search.
+    #[default]
+    Unknown,
+}
+
 /// Reports the number of elements satisfying the predicate.
 ///
 /// This methods *relies strongly* on the assumption that the predicate
@@ -12,7 +32,22 @@ pub fn advance<T, F>(slice: &[T], function: F) -> usize
 where
     F: Fn(&T) -> bool,
 {
-    advance_raw::<T, F, DEFAULT_SMALL_LIMIT>(slice, function)
+    advance_with_hint(slice, SearchHint::Unknown, function)
+}
+
+/// Reports the number of elements satisfying the predicate, using `hint` to
+/// pick the search strategy.
+///
+/// Like [`advance`], this relies on the predicate staying false once it
+/// becomes false.  The hint only affects performance, not the result.
+pub fn advance_with_hint<T, F>(slice: &[T], hint: SearchHint, function: F) -> usize
+where
+    F: Fn(&T) -> bool,
+{
+    // Safety: `search` only probes indices below `slice.len()`
+    search(slice.len(), hint, DEFAULT_SMALL_LIMIT, |index| unsafe {
+        function(slice.get_unchecked(index))
+    })
 }
 
 /// Reports the number of elements satisfying the predicate with the additional
@@ -26,99 +61,118 @@ pub fn advance_raw<T, F, const SMALL_LIMIT: usize>(slice: &[T], function: F) ->
 where
     F: Fn(&T) -> bool,
 {
-    // Exponential search if the answer isn't within `SMALL_LIMIT`.
-    if slice.len() > SMALL_LIMIT && function(&slice[SMALL_LIMIT]) {
-        // Skip `slice[..SMALL_LIMIT]` outright, the above condition established
-        // that nothing within it satisfies the predicate
-        let mut index = SMALL_LIMIT + 1;
-
-        // FIXME: This is kinda weird
-        if index < slice.len() && function(&slice[index]) {
-            // Advance in exponentially growing steps
-            let mut step = 1;
-            while index + step < slice.len() && function(&slice[index + step]) {
-                index += step;
-                step <<= 1;
-            }
-
-            // Advance in exponentially shrinking steps
-            step >>= 1;
-            while step > 0 {
-                if index + step < slice.len() && function(&slice[index + step]) {
-                    index += step;
-                }
-                step >>= 1;
-            }
-
-            index += 1;
-        }
-
-        index
-
-    // If `slice[SMALL_LIMIT..]` doesn't satisfy the predicate, we can simply
-    // perform a linear search on `slice[..SMALL_LIMIT]`
-    } else {
-        // Clamp to the length of the slice, this branch will also be hit if the slice
-        // is smaller than SMALL_LIMIT
-        let limit = min(slice.len(), SMALL_LIMIT);
-
-        slice[..limit]
-            .iter()
-            .position(|x| !function(x))
-            // If nothing within `slice[..limit]` satisfies the predicate, we can advance
-            // past the searched prefix
-            .unwrap_or(limit)
-    }
+    // Safety: `search` only probes indices below `slice.len()`
+    search(
+        slice.len(),
+        SearchHint::Unknown,
+        SMALL_LIMIT,
+        |index| unsafe { function(slice.get_unchecked(index)) },
+    )
 }
 
 pub fn advance_erased<F>(slice: &[MaybeUninit<u8>], size: usize, function: F) -> usize
+where
+    F: Fn(*const u8) -> bool,
+{
+    advance_erased_with_hint(slice, size, SearchHint::Unknown, function)
+}
+
+/// Type-erased version of [`advance_with_hint`] over a slice of `size`-byte
+/// elements.
+pub fn advance_erased_with_hint<F>(
+    slice: &[MaybeUninit<u8>],
+    size: usize,
+    hint: SearchHint,
+    function: F,
+) -> usize
 where
     F: Fn(*const u8) -> bool,
 {
     let slice = SlicePtr::new(slice, size);
-    if slice.is_empty() {
-        return 0;
-    }
 
     // We have to use `.get_unchecked()` here since otherwise LLVM's not smart
     // enough to elide bounds checking (we still get checks in debug mode though)
-    unsafe {
-        // Exponential search if the answer isn't within `small_limit`.
-        if slice.len() > DEFAULT_SMALL_LIMIT && function(slice.get_unchecked(DEFAULT_SMALL_LIMIT)) {
-            // start with no advance
-            let mut index = DEFAULT_SMALL_LIMIT + 1;
-            if index < slice.len() && function(slice.get_unchecked(index)) {
-                // advance in exponentially growing steps.
-                let mut step = 1;
-                while index + step < slice.len() && function(slice.get_unchecked(index + step)) {
-                    index += step;
-                    step <<= 1;
-                }
-
-                // advance in exponentially shrinking steps.
-                step >>= 1;
-                while step > 0 {
-                    if index + step < slice.len() && function(slice.get_unchecked(index + step)) {
-                        index += step;
-                    }
-                    step >>= 1;
-                }
+    search(slice.len(), hint, DEFAULT_SMALL_LIMIT, |index| unsafe {
+        function(slice.get_unchecked(index))
+    })
+}
 
-                index += 1;
+/// Returns the index of the first element in `0..len` for which `predicate`
+/// returns `false`, or `len` if there is no such element.
+///
+/// `predicate` must be monotone, i.e., once it returns `false` for some index
+/// it must return `false` for all subsequent indices.  It's only ever called
+/// with indices in `0..len`.
+#[inline]
+fn search<F>(len: usize, hint: SearchHint, small_limit: usize, predicate: F) -> usize
+where
+    F: Fn(usize) -> bool,
+{
+    match hint {
+        SearchHint::Near => {
+            // Scan the first `small_limit` elements, then gallop from there
+            let limit = min(len, small_limit);
+            match (0..limit).find(|&index| !predicate(index)) {
+                Some(index) => index,
+                None => gallop(len, limit, predicate),
             }
+        }
 
-            index
-        } else {
-            let limit = min(slice.len(), DEFAULT_SMALL_LIMIT);
-            for offset in 0..limit {
-                if !function(slice.get_unchecked(offset)) {
-                    return offset;
-                }
+        SearchHint::Far => binary_search(0, len, predicate),
+
+        SearchHint::Unknown => {
+            // If `predicate` holds at `small_limit`, it holds for everything
+            // before it and we can gallop from the element after it.
+            // Otherwise, the answer is within `..small_limit` and a linear
+            // scan beats anything fancier.
+            if len > small_limit && predicate(small_limit) {
+                gallop(len, small_limit + 1, predicate)
+            } else {
+                let limit = min(len, small_limit);
+                (0..limit).find(|&index| !predicate(index)).unwrap_or(limit)
             }
+        }
+    }
+}
+
+/// Exponential search for the first index in `lower..len` for which
+/// `predicate` returns `false`, given that it returns `true` for all indices
+/// below `lower`.
+#[inline]
+fn gallop<F>(len: usize, mut lower: usize, predicate: F) -> usize
+where
+    F: Fn(usize) -> bool,
+{
+    // Advance in exponentially growing steps until we either run off the end
+    // of the slice or find an element that doesn't satisfy the predicate
+    let mut step = 1;
+    while lower + step <= len && predicate(lower + step - 1) {
+        lower += step;
+        step <<= 1;
+    }
 
-            limit
+    // The answer is within `lower..=lower + step - 1`, where the upper end is
+    // either out of bounds or known not to satisfy the predicate
+    binary_search(lower, min(lower + step - 1, len), predicate)
+}
+
+/// Binary search for the first index in `lower..upper` for which `predicate`
+/// returns `false`, or `upper` if there's no such index.
+#[inline]
+fn binary_search<F>(mut lower: usize, mut upper: usize, predicate: F) -> usize
+where
+    F: Fn(usize) -> bool,
+{
+    while lower < upper {
+        let middle = lower + (upper - lower) / 2;
+        if predicate(middle) {
+            lower = middle + 1;
+        } else {
+            upper = middle;
         }
     }
+
+    lower
 }
 
 struct SlicePtr {
@@ -144,11 +198,6 @@ impl SlicePtr {
         self.elements
     }
 
-    #[inline]
-    const fn is_empty(&self) -> bool {
-        self.elements == 0
-    }
-
     #[inline]
     unsafe fn get_unchecked(&self, idx: usize) -> *const u8 {
         debug_assert!(idx < self.elements);
@@ -159,14 +208,24 @@ impl SlicePtr {
 #[cfg(test)]
 mod tests {
     use crate::{
-        trace::layers::advance::{advance, advance_erased, DEFAULT_SMALL_LIMIT},
+        strategies::sorted,
+        trace::layers::advance::{
+            advance, advance_erased, advance_erased_with_hint, advance_with_hint, SearchHint,
+            DEFAULT_SMALL_LIMIT,
+        },
         utils::bytes_of,
     };
     use proptest::{
-        arbitrary::any, collection::vec, prop_assert_eq, proptest, sample::SizeRange,
-        strategy::Strategy, test_runner::TestCaseResult,
+        arbitrary::any,
+        prop_assert_eq, prop_oneof, proptest,
+        sample::SizeRange,
+        strategy::{Just, Strategy},
+        test_runner::TestCaseResult,
+    };
+    use std::{
+        cell::Cell,
+        mem::{align_of, size_of},
     };
-    use std::mem::{align_of, size_of};
 
     const HALF: usize = usize::MAX / 2;
 
@@ -264,14 +323,57 @@ mod tests {
         assert_eq!(count, 10);
     }
 
+    #[test]
+    fn advance_hints() {
+        // Exercise every hint right around `DEFAULT_SMALL_LIMIT`, where the
+        // linear and exponential searches meet
+        for len in 0..=4 * DEFAULT_SMALL_LIMIT {
+            let haystack: Vec<usize> = (0..len).collect();
+
+            for needle in 0..=len + 1 {
+                let expected = needle.min(len);
+
+                for hint in [SearchHint::Near, SearchHint::Far, SearchHint::Unknown] {
+                    assert_eq!(
+                        advance_with_hint(&haystack, hint, |&x| x < needle),
+                        expected,
+                        "len: {len}, needle: {needle}, hint: {hint:?}",
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn advance_past_small_limit() {
+        // Exactly `DEFAULT_SMALL_LIMIT + 1` elements satisfy the predicate
+        let haystack: Vec<usize> = (0..4 * DEFAULT_SMALL_LIMIT).collect();
+        let probes = Cell::new(0);
+
+        let count = advance(&haystack, |&x| {
+            probes.set(probes.get() + 1);
+            x <= DEFAULT_SMALL_LIMIT
+        });
+        assert_eq!(count, DEFAULT_SMALL_LIMIT + 1);
+
+        // One probe of `haystack[DEFAULT_SMALL_LIMIT]` and one of the element
+        // right after it
+        assert_eq!(probes.get(), 2);
+    }
+
+    fn search_hint() -> impl Strategy<Value = SearchHint> {
+        prop_oneof![
+            Just(SearchHint::Near),
+            Just(SearchHint::Far),
+            Just(SearchHint::Unknown),
+        ]
+    }
+
     fn haystack(
         length: impl Into<SizeRange>,
         value: impl Strategy<Value = usize>,
     ) -> impl Strategy<Value = Vec<usize>> {
-        vec(value, length.into()).prop_map(|mut vec| {
-            vec.sort();
-            vec
-        })
+        sorted(value, length)
     }
 
     fn advance_test(needle: usize, haystack: &[usize]) -> TestCaseResult {
@@ -285,14 +387,34 @@ mod tests {
         Ok(())
     }
 
+    fn advance_hint_test(needle: usize, haystack: &[usize], hint: SearchHint) -> TestCaseResult {
+        let count = advance_with_hint(haystack, hint, |&x| x < needle);
+        let expected = haystack
+            .iter()
+            .position(|&x| x >= needle)
+            .unwrap_or(haystack.len());
+
+        prop_assert_eq!(count, expected);
+        Ok(())
+    }
+
     fn advance_erased_test(needle: usize, haystack: &[usize]) -> TestCaseResult {
-        let count = advance_erased(bytes_of(haystack), size_of::<usize>(), |x| unsafe {
-            assert!(
-                x as usize & (align_of::<usize>() - 1) == 0,
-                "unaligned pointer",
-            );
-            *x.cast::<usize>() < needle
-        });
+        advance_erased_hint_test(needle, haystack, SearchHint::Unknown)
+    }
+
+    fn advance_erased_hint_test(
+        needle: usize,
+        haystack: &[usize],
+        hint: SearchHint,
+    ) -> TestCaseResult {
+        let count =
+            advance_erased_with_hint(bytes_of(haystack), size_of::<usize>(), hint, |x| unsafe {
+                assert!(
+                    x as usize & (align_of::<usize>() - 1) == 0,
+                    "unaligned pointer",
+                );
+                *x.cast::<usize>() < needle
+            });
         let expected = haystack
             .iter()
             .position(|&x| x >= needle)
@@ -348,5 +470,38 @@ mod tests {
         fn advance_erased_less_than_small_unsat(needle in ..HALF, haystack in haystack(0..=DEFAULT_SMALL_LIMIT, HALF..)) {
             advance_erased_test(needle, &haystack)?;
         }
+
+        #[test]
+        fn advance_hint_less_than(needle in any::<usize>(), haystack in haystack(0..100_000usize, any::<usize>()), hint in search_hint()) {
+            advance_hint_test(needle, &haystack, hint)?;
+        }
+
+        // Force `advance_with_hint()` to search the entire haystack
+        #[test]
+        fn advance_hint_less_than_unsat(needle in ..HALF, haystack in haystack(0..100_000usize, HALF..), hint in search_hint()) {
+            advance_hint_test(needle, &haystack, hint)?;
+        }
+
+        // Haystacks around `DEFAULT_SMALL_LIMIT`, where each hint switches strategies
+        #[test]
+        fn advance_hint_less_than_small(needle in any::<usize>(), haystack in haystack(0..=4 * DEFAULT_SMALL_LIMIT, any::<usize>()), hint in search_hint()) {
+            advance_hint_test(needle, &haystack, hint)?;
+        }
+
+        #[test]
+        fn advance_erased_hint_less_than(needle in any::<usize>(), haystack in haystack(0..100_000usize, any::<usize>()), hint in search_hint()) {
+            advance_erased_hint_test(needle, &haystack, hint)?;
+        }
+
+        // Force `advance_erased_with_hint()` to search the entire haystack
+        #[test]
+        fn advance_erased_hint_less_than_unsat(needle in ..HALF, haystack in haystack(0..100_000usize, HALF..), hint in search_hint()) {
+            advance_erased_hint_test(needle, &haystack, hint)?;
+        }
+
+        #[test]
+        fn advance_erased_hint_less_than_small(needle in any::<usize>(), haystack in haystack(0..=4 * DEFAULT_SMALL_LIMIT, any::<usize>()), hint in search_hint()) {
+            advance_erased_hint_test(needle, &haystack, hint)?;
+        }
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    trace::layers::{advance, column_layer::ColumnLayer, Cursor},
+    trace::layers::{advance, advance_with_hint, column_layer::ColumnLayer, Cursor, SearchHint},
     utils::cursor_position_oob,
     DBData, DBWeight,
 };
@@ -94,8 +94,14 @@ where
     }
 
     fn seek(&mut self, key: &Self::Key) {
+        self.seek_with_hint(key, SearchHint::Unknown);
+    }
+
+    fn seek_with_hint(&mut self, key: &Self::Key, hint: SearchHint) {
         unsafe { self.storage.assume_invariants() }
-        self.pos += advance(&self.storage.keys[self.pos..self.bounds.1], |k| k.lt(key));
+        self.pos += advance_with_hint(&self.storage.keys[self.pos..self.bounds.1], hint, |k| {
+            k.lt(key)
+        });
     }
 
     fn last_item(&mut self) -> Option<Self::Item<'s>> {
@@ -127,6 +133,11 @@ where
         self.pos = lower;
         self.bounds = (lower, upper);
     }
+
+    fn move_to(&mut self, position: usize) {
+        debug_assert!(self.bounds.0 <= position && position <= self.bounds.1);
+        self.pos = position;
+    }
 }
 
 impl<'a, K, R> Display for ColumnLayerCursor<'a, K, R>
@@ -12,7 +12,10 @@ pub use cursor::ColumnLayerCursor;
 
 use crate::{
     algebra::{AddAssignByRef, AddByRef, HasZero, NegByRef},
-    trace::layers::{advance, Trie},
+    trace::{
+        consolidation::compact_sorted_paired_slices,
+        layers::{advance_with_hint, SearchHint, Trie},
+    },
     utils::{assume, cast_uninit_vec},
     DBData, DBWeight, NumEntries,
 };
@@ -74,6 +77,35 @@ impl<K, R> ColumnLayer<K, R> {
         }
     }
 
+    /// Creates a new `ColumnLayer` from sorted `keys` and their `diffs`
+    ///
+    /// Runs of equal keys are consolidated by adding up their diffs and keys
+    /// whose diffs add up to zero are removed.  `keys` and `diffs` are
+    /// compacted in place and become the layer's storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` and `diffs` have different lengths.  When debug
+    /// assertions are enabled, also panics if `keys` are not sorted.
+    pub fn from_sorted_columns(mut keys: Vec<K>, mut diffs: Vec<R>) -> Self
+    where
+        K: Ord + 'static,
+        R: AddAssignByRef + HasZero + 'static,
+    {
+        assert_eq!(keys.len(), diffs.len());
+        debug_assert!(
+            keys.windows(2).all(|keys| keys[0] <= keys[1]),
+            "ColumnLayer::from_sorted_columns: keys are not sorted"
+        );
+
+        let len = compact_sorted_paired_slices(&mut keys, &mut diffs);
+        keys.truncate(len);
+        diffs.truncate(len);
+
+        // Safety: `keys` and `diffs` have the same length
+        unsafe { Self::from_parts(keys, diffs, 0) }
+    }
+
     /// Get the length of the current leaf
     pub fn len(&self) -> usize {
         unsafe { self.assume_invariants() }
@@ -309,7 +341,7 @@ where
 {
     /// Remove keys smaller than `lower_bound` from the batch.
     pub fn truncate_keys_below(&mut self, lower_bound: &K) {
-        let index = advance(&self.keys, |k| k < lower_bound);
+        let index = advance_with_hint(&self.keys, SearchHint::Far, |k| k < lower_bound);
         self.truncate_below(index);
     }
 }
@@ -1,8 +1,8 @@
 use crate::{
     trace::layers::{
-        advance_erased,
+        advance_erased, advance_erased_with_hint,
         erased::{ErasedLayer, TypedLayer},
-        Cursor,
+        Cursor, SearchHint,
     },
     utils::cursor_position_oob,
     DBData, DBWeight,
@@ -109,10 +109,15 @@ where
     }
 
     fn seek(&mut self, key: &Self::Key) {
+        self.seek_with_hint(key, SearchHint::Unknown);
+    }
+
+    fn seek_with_hint(&mut self, key: &Self::Key, hint: SearchHint) {
         let key = key as *const K as *const u8;
-        self.current += advance_erased(
+        self.current += advance_erased_with_hint(
             self.storage.keys.range(self.current..self.bounds.1),
             self.storage.key_size(),
+            hint,
             |x| unsafe { (self.storage.keys.vtable().common.lt)(x, key) },
         );
     }
@@ -145,6 +150,11 @@ where
         self.current = lower;
         self.bounds = (lower, upper);
     }
+
+    fn move_to(&mut self, position: usize) {
+        debug_assert!(self.bounds.0 <= position && position <= self.bounds.1);
+        self.current = position;
+    }
 }
 
 impl<'a, K, R> Display for TypedLayerCursor<'a, K, R>
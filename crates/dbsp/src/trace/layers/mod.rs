@@ -17,7 +17,9 @@ pub mod unordered;
 #[cfg(test)]
 mod test;
 
-pub use advance::{advance, advance_erased, advance_raw};
+pub use advance::{
+    advance, advance_erased, advance_erased_with_hint, advance_raw, advance_with_hint, SearchHint,
+};
 
 use crate::algebra::HasZero;
 use size_of::SizeOf;
@@ -208,6 +210,12 @@ pub trait Cursor<'s> {
     /// Advances the cursor until the location where `key` would be expected.
     fn seek(&mut self, key: &Self::Key);
 
+    /// Advances the cursor until the location where `key` would be expected,
+    /// using `hint` to guess how far away that location is.
+    fn seek_with_hint(&mut self, key: &Self::Key, _hint: SearchHint) {
+        self.seek(key)
+    }
+
     /// Returns the last item in the cursor or `None` if the cursor is empty.
     fn last_item(&mut self) -> Option<Self::Item<'s>>;
 
@@ -223,6 +231,10 @@ pub trait Cursor<'s> {
 
     /// Repositions the cursor to a different range of values.
     fn reposition(&mut self, lower: usize, upper: usize);
+
+    /// Moves the cursor to `position`, previously returned by
+    /// [`Self::position`], without changing its range of values.
+    fn move_to(&mut self, position: usize);
 }
 
 /// Trait for types used as offsets into an ordered layer.
@@ -369,4 +381,6 @@ impl<'s> Cursor<'s> for () {
     }
 
     fn reposition(&mut self, _lower: usize, _upper: usize) {}
+
+    fn move_to(&mut self, _position: usize) {}
 }
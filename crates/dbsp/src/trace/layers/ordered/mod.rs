@@ -6,10 +6,13 @@ mod tests;
 pub use consumer::{OrderedLayerConsumer, OrderedLayerValues};
 
 use crate::{
-    algebra::{AddAssignByRef, AddByRef, NegByRef},
-    trace::layers::{
-        advance, column_layer::ColumnLayer, Builder, Cursor, MergeBuilder, OrdOffset, Trie,
-        TupleBuilder,
+    algebra::{AddAssignByRef, AddByRef, HasZero, NegByRef},
+    trace::{
+        consolidation::compact_sorted_paired_slices,
+        layers::{
+            advance, advance_with_hint, column_layer::ColumnLayer, Builder, Cursor, MergeBuilder,
+            OrdOffset, SearchHint, Trie, TupleBuilder,
+        },
     },
     utils::{assume, cast_uninit_vec},
     DBData, NumEntries,
@@ -90,11 +93,142 @@ where
 {
     /// Truncate layer at the first key greater than or equal to `lower_bound`.
     pub fn truncate_keys_below(&mut self, lower_bound: &K) {
-        let index = advance(&self.keys, |k| k < lower_bound);
+        let index = advance_with_hint(&self.keys, SearchHint::Far, |k| k < lower_bound);
         self.truncate_below(index);
     }
 }
 
+impl<K, V, L, O> OrderedLayer<K, L, O>
+where
+    K: Ord + Clone,
+    L: Trie + 'static,
+    O: OrdOffset,
+    for<'a, 'b> <L as Trie>::Cursor<'a>: Cursor<'a, Key = V>,
+{
+    /// Remove values strictly less than `lower_bound` from the layer, along
+    /// with keys that are left without values.  Values equal to `lower_bound`
+    /// are retained.
+    ///
+    /// Unlike [`Self::truncate_keys_below`], this rebuilds the layer.
+    pub fn truncate_values_below(&mut self, lower_bound: &V) {
+        let (lower, upper) = (self.lower_bound, self.keys.len());
+
+        let mut builder =
+            <OrderedBuilder<K, L::MergeBuilder, O> as MergeBuilder>::with_key_capacity(
+                upper - lower,
+            );
+        if lower < upper {
+            builder.copy_range_truncate_values_fueled(self, lower, upper, lower_bound, usize::MAX);
+        }
+
+        *self = builder.done();
+    }
+}
+
+impl<K, V, R, O> OrderedLayer<K, ColumnLayer<V, R>, O>
+where
+    K: Ord,
+    V: Ord + 'static,
+    R: AddAssignByRef + HasZero + 'static,
+    O: OrdOffset,
+{
+    /// Creates a new `OrderedLayer` over a [`ColumnLayer`] from sorted
+    /// columns
+    ///
+    /// The values of `keys[i]` are `vals[offs[i]..offs[i + 1]]`, with diffs
+    /// `diffs[offs[i]..offs[i + 1]]`, so `offs` must be one element longer
+    /// than `keys`, start at `0` and end at `vals.len()`.  `(key, value)`
+    /// pairs must be sorted.
+    ///
+    /// Runs of equal keys are merged and runs of equal values within a key
+    /// are consolidated by adding up their diffs.  Values whose diffs add up
+    /// to zero are removed, along with keys left without values.  All
+    /// vectors are compacted in place and become the layer's storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lengths of the columns or the offsets are inconsistent.
+    /// When debug assertions are enabled, also panics if `(key, value)` pairs
+    /// are not sorted.
+    pub fn from_sorted_columns(
+        mut keys: Vec<K>,
+        mut offs: Vec<O>,
+        mut vals: Vec<V>,
+        mut diffs: Vec<R>,
+    ) -> Self {
+        assert_eq!(keys.len() + 1, offs.len());
+        assert_eq!(vals.len(), diffs.len());
+        assert_eq!(offs[0].into_usize(), 0);
+        assert_eq!(offs[keys.len()].into_usize(), vals.len());
+        debug_assert!(
+            sorted_columns(&keys, &offs, &vals),
+            "OrderedLayer::from_sorted_columns: (key, value) pairs are not sorted"
+        );
+
+        let (mut key_len, mut val_len) = (0, 0);
+        let mut start = 0;
+        let mut lower = 0;
+        while start < keys.len() {
+            let mut end = start + 1;
+            while end < keys.len() && keys[end] == keys[start] {
+                end += 1;
+            }
+
+            // Values of all keys in `start..end` form a single sorted run.
+            let upper = offs[end].into_usize();
+            let len =
+                compact_sorted_paired_slices(&mut vals[lower..upper], &mut diffs[lower..upper]);
+
+            // Move the consolidated values and their key to the end of the
+            // compacted prefix of each column.
+            if len != 0 {
+                for index in 0..len {
+                    vals.swap(val_len + index, lower + index);
+                    diffs.swap(val_len + index, lower + index);
+                }
+                keys.swap(key_len, start);
+
+                key_len += 1;
+                val_len += len;
+                offs[key_len] = O::from_usize(val_len);
+            }
+
+            start = end;
+            lower = upper;
+        }
+
+        keys.truncate(key_len);
+        offs.truncate(key_len + 1);
+        vals.truncate(val_len);
+        diffs.truncate(val_len);
+
+        // Safety: every key has a non-empty range of values and `offs`
+        // is one element longer than `keys`
+        unsafe { Self::from_parts(keys, offs, ColumnLayer::from_parts(vals, diffs, 0), 0) }
+    }
+}
+
+/// Returns `true` if the `(key, value)` pairs described by `keys`, `offs` and
+/// `vals` are sorted.
+fn sorted_columns<K, V, O>(keys: &[K], offs: &[O], vals: &[V]) -> bool
+where
+    K: Ord,
+    V: Ord,
+    O: OrdOffset,
+{
+    let mut previous: Option<(&K, &V)> = None;
+    for (index, key) in keys.iter().enumerate() {
+        for val in &vals[offs[index].into_usize()..offs[index + 1].into_usize()] {
+            if previous.map_or(false, |previous| previous > (key, val)) {
+                return false;
+            }
+            previous = Some((key, val));
+        }
+    }
+
+    true
+}
+
 impl<K, V, R, O> OrderedLayer<K, ColumnLayer<V, R>, O> {
     /// Turns the current `OrderedLayer<K, ColumnLayer<V, R>, O>` into a
     /// layer of [`MaybeUninit`] values
@@ -990,7 +1124,13 @@ where
     }
 
     fn seek(&mut self, key: &Self::Key) {
-        self.pos += advance(&self.storage.keys[self.pos..self.bounds.1], |k| k < key);
+        self.seek_with_hint(key, SearchHint::Unknown);
+    }
+
+    fn seek_with_hint(&mut self, key: &Self::Key, hint: SearchHint) {
+        self.pos += advance_with_hint(&self.storage.keys[self.pos..self.bounds.1], hint, |k| {
+            k < key
+        });
 
         if self.valid() {
             self.child.reposition(
@@ -1039,6 +1179,18 @@ where
             );
         }
     }
+
+    fn move_to(&mut self, position: usize) {
+        debug_assert!(self.bounds.0 <= position && position <= self.bounds.1);
+        self.pos = position;
+
+        if self.valid() {
+            self.child.reposition(
+                self.storage.offs[self.pos].into_usize(),
+                self.storage.offs[self.pos + 1].into_usize(),
+            );
+        }
+    }
 }
 
 impl<'a, K, L, O> Display for OrderedCursor<'a, K, O, L>
@@ -2,7 +2,9 @@
 
 use crate::{
     algebra::{AddAssignByRef, AddByRef, HasZero, NegByRef},
-    trace::layers::{advance, Builder, Cursor, MergeBuilder, Trie, TupleBuilder},
+    trace::layers::{
+        advance, advance_with_hint, Builder, Cursor, MergeBuilder, SearchHint, Trie, TupleBuilder,
+    },
     DBData, DBWeight, NumEntries,
 };
 use size_of::SizeOf;
@@ -35,7 +37,7 @@ where
 {
     /// Truncate layer at the first key greater than or equal to `lower_bound`.
     pub fn truncate_keys_below(&mut self, lower_bound: &K) {
-        let index = advance(&self.vals, |(k, _r)| k < lower_bound);
+        let index = advance_with_hint(&self.vals, SearchHint::Far, |(k, _r)| k < lower_bound);
         self.truncate_below(index);
     }
 }
@@ -409,9 +411,15 @@ where
     }
 
     fn seek(&mut self, key: &Self::Key) {
-        self.pos += advance(&self.storage.vals[self.pos..self.bounds.1], |(k, _)| {
-            k.lt(key)
-        });
+        self.seek_with_hint(key, SearchHint::Unknown);
+    }
+
+    fn seek_with_hint(&mut self, key: &Self::Key, hint: SearchHint) {
+        self.pos += advance_with_hint(
+            &self.storage.vals[self.pos..self.bounds.1],
+            hint,
+            |(k, _)| k.lt(key),
+        );
     }
 
     fn last_item(&mut self) -> Option<Self::Item<'s>> {
@@ -435,4 +443,8 @@ where
         self.pos = lower;
         self.bounds = (lower, upper);
     }
+    fn move_to(&mut self, position: usize) {
+        debug_assert!(self.bounds.0 <= position && position <= self.bounds.1);
+        self.pos = position;
+    }
 }
@@ -151,6 +151,10 @@ impl<'s, K, R> Cursor<'s> for UnorderedCursor<'s, K, R> {
     fn reposition(&mut self, _lower: usize, _upper: usize) {
         todo!()
     }
+
+    fn move_to(&mut self, _position: usize) {
+        todo!()
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, SizeOf)]
@@ -10,20 +10,28 @@
 //! and allows various data structures to be interpretable as multiple different
 //! types of trace.
 
+#[cfg(feature = "persistence")]
+pub mod archived;
 pub mod consolidation;
 pub mod cursor;
 pub mod layers;
 pub mod ord;
 #[cfg(feature = "persistence")]
 pub mod persistent;
+#[cfg(feature = "persistence")]
+pub mod spill;
 pub mod spine_fueled;
 
-pub use cursor::{Consumer, Cursor, UnorderedCursor, ValueConsumer};
+pub use cursor::{Consumer, Cursor, CursorMark, UnorderedCursor, ValueConsumer};
 #[cfg(feature = "persistence")]
 pub use persistent::PersistentTrace as Spine;
+#[cfg(feature = "persistence")]
+pub use spill::SpillingSpine;
 #[cfg(not(feature = "persistence"))]
 pub use spine_fueled::Spine;
 
+#[cfg(test)]
+mod test;
 #[cfg(test)]
 mod test_batch;
 
@@ -152,16 +160,17 @@ pub trait Trace: BatchReader {
     /// Informs the trace that values smaller than `lower_bound` are no longer
     /// used and can be removed from the trace.
     ///
-    /// The implementation is not required to remove truncated values instantly
-    /// or at all.  This method is just a hint that values below `lower_bound`
-    /// are no longer of interest to the consumer of the trace and can be
-    /// garbage collected.
+    /// Values strictly less than `lower_bound` may be removed, values equal to
+    /// `lower_bound` are retained.  The implementation is not required to
+    /// remove truncated values instantly or at all.  This method is just a
+    /// hint that values below `lower_bound` are no longer of interest to the
+    /// consumer of the trace and can be garbage collected.
     // This API is similar to `BatchReader::truncate_keys_below`, however we make
     // it a method of `trait Trace` rather than `trait BatchReader`.  The difference
     // is that a batch can truncate its keys instanly by simply moving an internal
     // pointer to the first remaining key.  However, there is no similar way to
-    // truncate values instantly, this can only be done as part of trace maintenance
-    // when either merging or compacting batches.
+    // truncate values instantly: `Batch::truncate_values_below` has to rebuild
+    // the batch, so traces postpone it until batches are merged or compacted.
     fn truncate_values_below(&mut self, lower_bound: &Self::Val);
 
     /// Current lower value bound.
@@ -228,8 +237,9 @@ where
 
     /// Remove keys smaller than `lower_bound` from the batch.
     ///
-    /// The removed tuples may not get deallocated instantly but they won't
-    /// appear when iterating over the batch.
+    /// Keys strictly less than `lower_bound` are removed, keys equal to
+    /// `lower_bound` are retained.  The removed tuples may not get deallocated
+    /// instantly but they won't appear when iterating over the batch.
     fn truncate_keys_below(&mut self, lower_bound: &Self::Key);
 }
 
@@ -297,6 +307,15 @@ where
     /// Modifies all timestamps `t` that are not less than or equal to
     /// `frontier` to `t.meet(frontier)`.  See [`Trace::recede_to`].
     fn recede_to(&mut self, frontier: &Self::Time);
+
+    /// Remove values smaller than `lower_bound` from the batch.
+    ///
+    /// Values strictly less than `lower_bound` are removed, values equal to
+    /// `lower_bound` are retained, following the same rule as
+    /// [`BatchReader::truncate_keys_below`].  Keys left without values are
+    /// removed as well.  Unlike key truncation, this rebuilds the batch.  See
+    /// [`Trace::truncate_values_below`].
+    fn truncate_values_below(&mut self, lower_bound: &Self::Val);
 }
 
 impl<B> HasZero for B
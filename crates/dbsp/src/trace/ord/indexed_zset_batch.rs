@@ -1,5 +1,5 @@
 use crate::{
-    algebra::{AddAssignByRef, AddByRef, MonoidValue, NegByRef},
+    algebra::{AddAssignByRef, AddByRef, HasZero, MonoidValue, NegByRef},
     time::AntichainRef,
     trace::{
         layers::{
@@ -8,8 +8,8 @@ use crate::{
                 OrderedBuilder, OrderedCursor, OrderedLayer, OrderedLayerConsumer,
                 OrderedLayerValues,
             },
-            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, OrdOffset, Trie,
-            TupleBuilder,
+            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, OrdOffset, SearchHint,
+            Trie, TupleBuilder,
         },
         ord::merge_batcher::MergeBatcher,
         Batch, BatchReader, Builder, Consumer, Cursor, Merger, ValueConsumer,
@@ -39,6 +39,44 @@ where
     pub(crate) layer: Layers<K, V, R, O>,
 }
 
+impl<K, V, R, O> OrdIndexedZSet<K, V, R, O>
+where
+    K: Ord,
+    V: Ord + 'static,
+    R: Clone + AddAssignByRef + HasZero + 'static,
+    O: OrdOffset,
+{
+    /// Create a batch from sorted columns of keys, values and weights.
+    ///
+    /// The values of `keys[i]` are `vals[offsets[i]..offsets[i + 1]]`, with
+    /// weights `weights[offsets[i]..offsets[i + 1]]`, so `offsets` must be one
+    /// element longer than `keys`, start at `0` and end at `vals.len()`.
+    /// `(key, value)` pairs must be sorted.
+    ///
+    /// Runs of equal keys are merged, runs of equal values within a key are
+    /// consolidated, and values whose weights add up to zero are removed,
+    /// along with keys left without values.  Unlike
+    /// [`from_tuples`](`Batch::from_tuples`), this does not sort the input and
+    /// reuses the columns as the storage of the batch instead of copying
+    /// them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lengths of the columns or the offsets are inconsistent.
+    /// When debug assertions are enabled, also panics if `(key, value)` pairs
+    /// are not sorted.
+    pub fn from_sorted_columns(
+        keys: Vec<K>,
+        offsets: Vec<O>,
+        vals: Vec<V>,
+        weights: Vec<R>,
+    ) -> Self {
+        Self {
+            layer: OrderedLayer::from_sorted_columns(keys, offsets, vals, weights),
+        }
+    }
+}
+
 impl<K, V, R, O> Display for OrdIndexedZSet<K, V, R, O>
 where
     K: DBData,
@@ -296,6 +334,10 @@ where
 
     fn recede_to(&mut self, _frontier: &()) {}
 
+    fn truncate_values_below(&mut self, lower_bound: &V) {
+        self.layer.truncate_values_below(lower_bound);
+    }
+
     fn empty(_time: Self::Time) -> Self {
         Self {
             layer: OrderedLayer::default(),
@@ -447,6 +489,10 @@ where
         self.cursor.seek(key);
     }
 
+    fn seek_key_with_hint(&mut self, key: &K, hint: SearchHint) {
+        self.cursor.seek_with_hint(key, hint);
+    }
+
     fn last_key(&mut self) -> Option<&K> {
         self.cursor.last_item()
     }
@@ -473,6 +519,17 @@ where
     fn rewind_vals(&mut self) {
         self.cursor.child.rewind();
     }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        Some(vec![self.cursor.position(), self.cursor.child.position()])
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.cursor.move_to(offsets[0]);
+        if self.cursor.valid() {
+            self.cursor.child.move_to(offsets[1]);
+        }
+    }
 }
 
 type IndexBuilder<K, V, R, O> = OrderedBuilder<K, ColumnLayerBuilder<V, R>, O>;
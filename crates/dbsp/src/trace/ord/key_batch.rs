@@ -8,8 +8,8 @@ use crate::{
                 OrderedBuilder, OrderedCursor, OrderedLayer, OrderedLayerConsumer,
                 OrderedLayerValues,
             },
-            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, OrdOffset, Trie,
-            TupleBuilder,
+            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, OrdOffset, SearchHint,
+            Trie, TupleBuilder,
         },
         ord::merge_batcher::MergeBatcher,
         Batch, BatchReader, Builder, Consumer, Cursor, Merger, ValueConsumer,
@@ -148,6 +148,9 @@ where
             self.do_recede_to(frontier);
         }
     }
+
+    // `()` is the only value, so there is nothing below `lower_bound`.
+    fn truncate_values_below(&mut self, _lower_bound: &()) {}
 }
 
 impl<K, T, R, O> OrdKeyBatch<K, T, R, O>
@@ -378,6 +381,11 @@ where
         self.valid = true;
     }
 
+    fn seek_key_with_hint(&mut self, key: &K, hint: SearchHint) {
+        self.cursor.seek_with_hint(key, hint);
+        self.valid = true;
+    }
+
     fn last_key(&mut self) -> Option<&K> {
         self.cursor.last_item()
     }
@@ -403,6 +411,15 @@ where
     fn rewind_vals(&mut self) {
         self.valid = true;
     }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        Some(vec![self.cursor.position(), self.valid as usize])
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.cursor.move_to(offsets[0]);
+        self.valid = offsets[1] != 0;
+    }
 }
 
 type RawOrdKeyBuilder<K, T, R, O> = OrderedBuilder<K, ColumnLayerBuilder<T, R>, O>;
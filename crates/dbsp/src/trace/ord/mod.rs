@@ -14,6 +14,9 @@
 //!   ordered and whose timestamp type is `()`.  Semantically, such collections
 //!   store `(key, weight)` tuples without timing information, and implement the
 //!   ZSet abstraction of DBSP.
+//! * `OrdZSetArena`: Like `OrdZSet`, but specialized for string keys, which
+//!   are stored in a single per-batch arena instead of separate heap
+//!   allocations.
 //!
 //! Although `OrdVal` is more general than `OrdKey`, the latter has a simpler
 //! representation and should consume fewer resources (computation and memory)
@@ -25,13 +28,16 @@
 pub mod indexed_zset_batch;
 pub mod key_batch;
 pub mod val_batch;
+pub mod zset_arena_batch;
 pub mod zset_batch;
 
 mod merge_batcher;
+mod tests;
 
 pub use indexed_zset_batch::OrdIndexedZSet;
 pub use key_batch::OrdKeyBatch;
 pub use val_batch::OrdValBatch;
+pub use zset_arena_batch::OrdZSetArena;
 pub use zset_batch::OrdZSet;
 
 use crate::trace::Spine;
@@ -0,0 +1,227 @@
+//! Conformance tests shared by all batch implementations in this module.
+//!
+//! Each batch type is checked against [`TestBatch`], which implements
+//! truncation by the book: tuples whose key (value) is strictly less than the
+//! bound are removed, tuples whose key (value) is equal to the bound are
+//! retained.
+#![cfg(test)]
+
+use crate::trace::{
+    ord::{OrdIndexedZSet, OrdKeyBatch, OrdValBatch, OrdZSet, OrdZSetArena},
+    test_batch::{assert_batch_eq, TestBatch},
+    Batch, BatchReader, Cursor,
+};
+use std::collections::BTreeSet;
+
+const TUPLES: u32 = 500;
+
+macro_rules! truncation_tests {
+    ($($name:ident: $batch:ty = ($time:expr, |$i:ident| $tuple:expr);)*) => {
+        $(
+            mod $name {
+                use super::*;
+
+                type B = $batch;
+                type Reference = TestBatch<
+                    <B as BatchReader>::Key,
+                    <B as BatchReader>::Val,
+                    <B as BatchReader>::Time,
+                    <B as BatchReader>::R,
+                >;
+
+                #[allow(clippy::type_complexity)]
+                fn tuples() -> Vec<(
+                    (<B as BatchReader>::Key, <B as BatchReader>::Val),
+                    <B as BatchReader>::R,
+                )> {
+                    (0..TUPLES).map(|$i| $tuple).collect()
+                }
+
+                fn batches() -> (B, Reference) {
+                    let tuples = tuples();
+                    let batch = B::from_tuples(
+                        $time,
+                        tuples
+                            .iter()
+                            .map(|((key, val), weight)| {
+                                (B::item_from(key.clone(), val.clone()), *weight)
+                            })
+                            .collect(),
+                    );
+                    let reference = Reference::from_tuples($time, tuples);
+                    assert_batch_eq(&batch, &reference);
+
+                    (batch, reference)
+                }
+
+                fn keys() -> BTreeSet<<B as BatchReader>::Key> {
+                    tuples().into_iter().map(|((key, _), _)| key).collect()
+                }
+
+                fn vals() -> BTreeSet<<B as BatchReader>::Val> {
+                    tuples().into_iter().map(|((_, val), _)| val).collect()
+                }
+
+                #[test]
+                fn truncate_keys_below() {
+                    for bound in keys() {
+                        let (mut batch, mut reference) = batches();
+                        batch.truncate_keys_below(&bound);
+                        reference.truncate_keys_below(&bound);
+                        assert_batch_eq(&batch, &reference);
+
+                        // A key equal to the bound is retained.
+                        let cursor = batch.cursor();
+                        assert!(cursor.key_valid());
+                        assert_eq!(cursor.key(), &bound);
+
+                        // Truncating to a smaller bound is a no-op.
+                        batch.truncate_keys_below(keys().first().unwrap());
+                        assert_batch_eq(&batch, &reference);
+                    }
+                }
+
+                #[test]
+                fn truncate_values_below() {
+                    for bound in vals() {
+                        let (mut batch, mut reference) = batches();
+                        Batch::truncate_values_below(&mut batch, &bound);
+                        Batch::truncate_values_below(&mut reference, &bound);
+                        assert_batch_eq(&batch, &reference);
+
+                        // A value equal to the bound is retained.
+                        let mut cursor = batch.cursor();
+                        let mut found = false;
+                        while cursor.key_valid() {
+                            assert!(cursor.val_valid());
+                            assert!(cursor.val() >= &bound);
+                            found |= cursor.val() == &bound;
+                            cursor.step_key();
+                        }
+                        assert!(found);
+                    }
+                }
+
+                #[test]
+                fn truncate_keys_and_values_below() {
+                    for (key_bound, val_bound) in keys().into_iter().zip(vals().into_iter().rev()) {
+                        let (mut batch, mut reference) = batches();
+                        batch.truncate_keys_below(&key_bound);
+                        reference.truncate_keys_below(&key_bound);
+                        Batch::truncate_values_below(&mut batch, &val_bound);
+                        Batch::truncate_values_below(&mut reference, &val_bound);
+                        assert_batch_eq(&batch, &reference);
+
+                        // Merging truncated batches preserves the result.
+                        let (other, other_reference) = batches();
+                        let merged = batch.merge(&other);
+                        let merged_reference = reference.merge(&other_reference);
+                        assert_batch_eq(&merged, &merged_reference);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+truncation_tests! {
+    zset: OrdZSet<u32, i64> = ((), |i| ((i % 37, ()), if i % 5 == 0 { -1 } else { 1 }));
+    indexed_zset: OrdIndexedZSet<u32, u32, i64> =
+        ((), |i| ((i % 37, i % 11), if i % 5 == 0 { -1 } else { 1 }));
+    key_batch: OrdKeyBatch<u32, u32, i64> =
+        (5, |i| ((i % 37, ()), if i % 5 == 0 { -1 } else { 1 }));
+    val_batch: OrdValBatch<u32, u32, u32, i64> =
+        (5, |i| ((i % 37, i % 11), if i % 5 == 0 { -1 } else { 1 }));
+    zset_arena: OrdZSetArena<i64> =
+        ((), |i| ((format!("{:03}", i % 37), ()), if i % 5 == 0 { -1 } else { 1 }));
+}
+
+#[test]
+fn zset_from_sorted_columns() {
+    let batch =
+        OrdZSet::from_sorted_columns(vec![1, 1, 2, 3, 3, 5, 5], vec![1, 1, 3, 1, -1, -2, 1]);
+    assert_eq!(
+        batch,
+        OrdZSet::<u32, i64>::from_keys((), vec![(1, 2), (2, 3), (5, -1)])
+    );
+
+    let tuples: Vec<(u32, i64)> = (0..TUPLES)
+        .map(|i| (i / 3, if i % 4 == 0 { -1 } else { 1 }))
+        .collect();
+    let (keys, weights) = tuples.iter().cloned().unzip();
+    assert_eq!(
+        OrdZSet::from_sorted_columns(keys, weights),
+        OrdZSet::from_keys((), tuples)
+    );
+
+    assert!(OrdZSet::<u32, i64>::from_sorted_columns(Vec::new(), Vec::new()).is_empty());
+}
+
+#[test]
+fn indexed_zset_from_sorted_columns() {
+    let batch = OrdIndexedZSet::<u32, u32, i64>::from_sorted_columns(
+        vec![1, 1, 2, 3],
+        vec![0, 2, 4, 6, 7],
+        vec![1, 2, 2, 3, 1, 1, 5],
+        vec![1, 1, 1, 1, 1, -1, 1],
+    );
+    assert_eq!(
+        batch,
+        OrdIndexedZSet::from_tuples((), vec![((1, 1), 1), ((1, 2), 2), ((1, 3), 1), ((3, 5), 1)])
+    );
+
+    let mut tuples: Vec<((u32, u32), i64)> = (0..TUPLES)
+        .map(|i| ((i % 37, i % 11), if i % 5 == 0 { -1 } else { 1 }))
+        .collect();
+    tuples.sort();
+    let expected = OrdIndexedZSet::<u32, u32, i64>::from_tuples((), tuples.clone());
+
+    // One key per tuple.
+    let keys = tuples.iter().map(|((key, _), _)| *key).collect();
+    let offsets = (0..=tuples.len()).collect();
+    let vals = tuples.iter().map(|((_, val), _)| *val).collect();
+    let weights = tuples.iter().map(|(_, weight)| *weight).collect();
+    assert_eq!(
+        OrdIndexedZSet::from_sorted_columns(keys, offsets, vals, weights),
+        expected
+    );
+
+    // One key per distinct key.
+    let mut keys = Vec::new();
+    let mut offsets = vec![0];
+    for (index, ((key, _), _)) in tuples.iter().enumerate() {
+        if keys.last() != Some(key) {
+            if index != 0 {
+                offsets.push(index);
+            }
+            keys.push(*key);
+        }
+    }
+    offsets.push(tuples.len());
+    let vals = tuples.iter().map(|((_, val), _)| *val).collect();
+    let weights = tuples.iter().map(|(_, weight)| *weight).collect();
+    assert_eq!(
+        OrdIndexedZSet::from_sorted_columns(keys, offsets, vals, weights),
+        expected
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "keys are not sorted")]
+fn zset_from_unsorted_columns() {
+    OrdZSet::<u32, i64>::from_sorted_columns(vec![1, 3, 2], vec![1, 1, 1]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "(key, value) pairs are not sorted")]
+fn indexed_zset_from_unsorted_columns() {
+    // Values of the two runs of key `1` are not sorted.
+    OrdIndexedZSet::<u32, u32, i64>::from_sorted_columns(
+        vec![1, 1],
+        vec![0, 2, 3],
+        vec![1, 3, 2],
+        vec![1, 1, 1],
+    );
+}
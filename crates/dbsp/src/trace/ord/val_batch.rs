@@ -5,8 +5,8 @@ use crate::{
         layers::{
             column_layer::{ColumnLayer, ColumnLayerBuilder},
             ordered::{OrderedBuilder, OrderedCursor, OrderedLayer},
-            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, OrdOffset, Trie,
-            TupleBuilder,
+            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, OrdOffset, SearchHint,
+            Trie, TupleBuilder,
         },
         ord::merge_batcher::MergeBatcher,
         Batch, BatchReader, Builder, Consumer, Cursor, Merger, ValueConsumer,
@@ -167,6 +167,10 @@ where
             self.do_recede_to(frontier);
         }
     }
+
+    fn truncate_values_below(&mut self, lower_bound: &V) {
+        self.layer.truncate_values_below(lower_bound);
+    }
 }
 
 impl<K, V, T, R, O> OrdValBatch<K, V, T, R, O>
@@ -438,6 +442,9 @@ where
     fn seek_key(&mut self, key: &K) {
         self.cursor.seek(key);
     }
+    fn seek_key_with_hint(&mut self, key: &K, hint: SearchHint) {
+        self.cursor.seek_with_hint(key, hint);
+    }
     fn last_key(&mut self) -> Option<&K> {
         self.cursor.last_item()
     }
@@ -459,6 +466,15 @@ where
     fn rewind_vals(&mut self) {
         self.cursor.child.rewind();
     }
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        Some(vec![self.cursor.position(), self.cursor.child.position()])
+    }
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.cursor.move_to(offsets[0]);
+        if self.cursor.valid() {
+            self.cursor.child.move_to(offsets[1]);
+        }
+    }
 }
 
 type RawOrdValBuilder<K, V, T, R, O> =
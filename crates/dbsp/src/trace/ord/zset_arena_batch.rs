@@ -0,0 +1,806 @@
+use crate::{
+    algebra::{AddAssignByRef, AddByRef, HasZero, NegByRef},
+    time::AntichainRef,
+    trace::{
+        layers::{advance, advance_with_hint, SearchHint},
+        ord::merge_batcher::MergeBatcher,
+        Batch, BatchReader, Builder, Consumer, Cursor, Merger, ValueConsumer,
+    },
+    DBWeight, NumEntries,
+};
+use size_of::SizeOf;
+use std::{
+    cmp::{max, Ordering},
+    marker::PhantomData,
+    ops::{Add, AddAssign, Neg},
+};
+
+/// An immutable collection of `(string, weight)` pairs without timing
+/// information, which stores all strings in a single arena.
+///
+/// [`OrdZSet<String, R>`](`super::OrdZSet`) stores each key in a separate
+/// heap allocation.  This batch instead copies all keys into a per-batch byte
+/// arena and stores each key as an `(offset, len)` range of the arena, which
+/// reduces the number of allocations and improves locality when scanning and
+/// merging batches.
+///
+/// Batch cursors expose keys as `&str` via
+/// [`OrdZSetArenaCursor::key_str`].  Since the batch does not store `String`
+/// objects, [`Cursor::key`] returns a copy of the current key stored in a
+/// buffer owned by the cursor, whose allocation is reused across keys.
+#[derive(Debug, Clone, SizeOf)]
+pub struct OrdZSetArena<R> {
+    arena: String,
+    keys: Vec<(usize, usize)>,
+    diffs: Vec<R>,
+}
+
+impl<R> OrdZSetArena<R> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns an iterator over `(key, weight)` pairs in the batch, ordered
+    /// by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &R)> {
+        (0..self.len()).map(|index| (self.key_at(index), &self.diffs[index]))
+    }
+
+    #[inline]
+    fn key_at(&self, index: usize) -> &str {
+        let (offset, len) = self.keys[index];
+        &self.arena[offset..offset + len]
+    }
+}
+
+impl<R> PartialEq for OrdZSetArena<R>
+where
+    R: PartialEq,
+{
+    // Arenas may contain unreachable bytes, so we compare contents rather than
+    // representations.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<R> Eq for OrdZSetArena<R> where R: Eq {}
+
+impl<R> NumEntries for OrdZSetArena<R>
+where
+    R: DBWeight,
+{
+    const CONST_NUM_ENTRIES: Option<usize> = None;
+
+    fn num_entries_shallow(&self) -> usize {
+        self.len()
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<R> Default for OrdZSetArena<R> {
+    fn default() -> Self {
+        Self {
+            arena: String::new(),
+            keys: Vec::new(),
+            diffs: Vec::new(),
+        }
+    }
+}
+
+impl<R> NegByRef for OrdZSetArena<R>
+where
+    R: NegByRef,
+{
+    fn neg_by_ref(&self) -> Self {
+        Self {
+            arena: self.arena.clone(),
+            keys: self.keys.clone(),
+            diffs: self.diffs.iter().map(NegByRef::neg_by_ref).collect(),
+        }
+    }
+}
+
+impl<R> Neg for OrdZSetArena<R>
+where
+    R: Neg<Output = R>,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            arena: self.arena,
+            keys: self.keys,
+            diffs: self.diffs.into_iter().map(Neg::neg).collect(),
+        }
+    }
+}
+
+impl<R> Add<Self> for OrdZSetArena<R>
+where
+    R: DBWeight,
+{
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.merge(&rhs)
+    }
+}
+
+impl<R> AddAssign<Self> for OrdZSetArena<R>
+where
+    R: DBWeight,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        *self = self.merge(&rhs);
+    }
+}
+
+impl<R> AddAssignByRef for OrdZSetArena<R>
+where
+    R: DBWeight,
+{
+    fn add_assign_by_ref(&mut self, rhs: &Self) {
+        *self = self.merge(rhs);
+    }
+}
+
+impl<R> AddByRef for OrdZSetArena<R>
+where
+    R: DBWeight,
+{
+    fn add_by_ref(&self, rhs: &Self) -> Self {
+        self.merge(rhs)
+    }
+}
+
+impl<R> BatchReader for OrdZSetArena<R>
+where
+    R: DBWeight,
+{
+    type Key = String;
+    type Val = ();
+    type Time = ();
+    type R = R;
+    type Cursor<'s> = OrdZSetArenaCursor<'s, R>;
+    type Consumer = OrdZSetArenaConsumer<R>;
+
+    #[inline]
+    fn cursor(&self) -> Self::Cursor<'_> {
+        OrdZSetArenaCursor::new(self)
+    }
+
+    #[inline]
+    fn consumer(self) -> Self::Consumer {
+        OrdZSetArenaConsumer::new(self)
+    }
+
+    #[inline]
+    fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    fn lower(&self) -> AntichainRef<'_, ()> {
+        AntichainRef::new(&[()])
+    }
+
+    #[inline]
+    fn upper(&self) -> AntichainRef<'_, ()> {
+        AntichainRef::empty()
+    }
+
+    fn truncate_keys_below(&mut self, lower_bound: &Self::Key) {
+        // Bytes of the truncated keys remain in the arena until the batch is
+        // merged.
+        let arena = &self.arena;
+        let index = advance_with_hint(&self.keys, SearchHint::Far, |&(offset, len)| {
+            &arena[offset..offset + len] < lower_bound.as_str()
+        });
+        self.keys.drain(..index);
+        self.diffs.drain(..index);
+    }
+}
+
+impl<R> Batch for OrdZSetArena<R>
+where
+    R: DBWeight,
+{
+    type Item = String;
+    type Batcher = MergeBatcher<String, (), R, Self>;
+    type Builder = OrdZSetArenaBuilder<R>;
+    type Merger = OrdZSetArenaMerger<R>;
+
+    fn item_from(key: String, _val: ()) -> Self::Item {
+        key
+    }
+
+    fn from_keys(time: Self::Time, keys: Vec<(Self::Key, Self::R)>) -> Self {
+        Self::from_tuples(time, keys)
+    }
+
+    fn begin_merge(&self, other: &Self) -> Self::Merger {
+        OrdZSetArenaMerger::new_merger(self, other)
+    }
+
+    fn recede_to(&mut self, _frontier: &()) {}
+
+    // `()` is the only value, so there is nothing below `lower_bound`.
+    fn truncate_values_below(&mut self, _lower_bound: &()) {}
+
+    fn empty(_time: Self::Time) -> Self {
+        Self::default()
+    }
+}
+
+/// State for an in-progress merge.
+///
+/// The arena of the merged batch is the concatenation of the arenas of both
+/// inputs, so keys are merged by rewriting their offsets without copying
+/// strings.  Keys whose weights cancel out and duplicate keys leave
+/// unreachable bytes in the arena, which is compacted when the merge
+/// completes if more than half of it is unreachable.
+#[derive(SizeOf)]
+pub struct OrdZSetArenaMerger<R> {
+    // result that we are currently assembling.
+    result: OrdZSetArena<R>,
+    // Offset of the arena of the second batch in the result arena.
+    offset2: usize,
+    // Positions of the next keys to merge in both batches.
+    pos1: usize,
+    pos2: usize,
+    // Total length of keys in the result.
+    live_bytes: usize,
+}
+
+impl<R> OrdZSetArenaMerger<R>
+where
+    R: DBWeight,
+{
+    fn push(&mut self, (offset, len): (usize, usize), diff: R) {
+        self.result.keys.push((offset, len));
+        self.result.diffs.push(diff);
+        self.live_bytes += len;
+    }
+}
+
+impl<R> Merger<String, (), (), R, OrdZSetArena<R>> for OrdZSetArenaMerger<R>
+where
+    R: DBWeight,
+{
+    fn new_merger(batch1: &OrdZSetArena<R>, batch2: &OrdZSetArena<R>) -> Self {
+        let mut arena = String::with_capacity(batch1.arena.len() + batch2.arena.len());
+        arena.push_str(&batch1.arena);
+        arena.push_str(&batch2.arena);
+
+        Self {
+            result: OrdZSetArena {
+                arena,
+                keys: Vec::with_capacity(batch1.len() + batch2.len()),
+                diffs: Vec::with_capacity(batch1.len() + batch2.len()),
+            },
+            offset2: batch1.arena.len(),
+            pos1: 0,
+            pos2: 0,
+            live_bytes: 0,
+        }
+    }
+
+    fn done(mut self) -> OrdZSetArena<R> {
+        if self.live_bytes * 2 < self.result.arena.len() {
+            let mut arena = String::with_capacity(self.live_bytes);
+            for key in self.result.keys.iter_mut() {
+                let (offset, len) = *key;
+                *key = (arena.len(), len);
+                arena.push_str(&self.result.arena[offset..offset + len]);
+            }
+            self.result.arena = arena;
+        }
+
+        self.result
+    }
+
+    fn work(
+        &mut self,
+        source1: &OrdZSetArena<R>,
+        source2: &OrdZSetArena<R>,
+        _lower_val_bound: &Option<()>,
+        fuel: &mut isize,
+    ) {
+        while *fuel > 0 && self.pos1 < source1.len() && self.pos2 < source2.len() {
+            let (offset2, len2) = source2.keys[self.pos2];
+            let key2 = (self.offset2 + offset2, len2);
+
+            match source1.key_at(self.pos1).cmp(source2.key_at(self.pos2)) {
+                Ordering::Less => {
+                    self.push(source1.keys[self.pos1], source1.diffs[self.pos1].clone());
+                    self.pos1 += 1;
+                }
+                Ordering::Greater => {
+                    self.push(key2, source2.diffs[self.pos2].clone());
+                    self.pos2 += 1;
+                }
+                Ordering::Equal => {
+                    let diff = source1.diffs[self.pos1].add_by_ref(&source2.diffs[self.pos2]);
+                    if !diff.is_zero() {
+                        self.push(source1.keys[self.pos1], diff);
+                    }
+                    self.pos1 += 1;
+                    self.pos2 += 1;
+                }
+            }
+            *fuel -= 1;
+        }
+
+        while *fuel > 0 && self.pos1 < source1.len() {
+            self.push(source1.keys[self.pos1], source1.diffs[self.pos1].clone());
+            self.pos1 += 1;
+            *fuel -= 1;
+        }
+
+        while *fuel > 0 && self.pos2 < source2.len() {
+            let (offset2, len2) = source2.keys[self.pos2];
+            self.push(
+                (self.offset2 + offset2, len2),
+                source2.diffs[self.pos2].clone(),
+            );
+            self.pos2 += 1;
+            *fuel -= 1;
+        }
+
+        if self.pos1 == source1.len() && self.pos2 == source2.len() {
+            *fuel = max(*fuel, 1);
+        }
+    }
+}
+
+/// A cursor for navigating an [`OrdZSetArena`].
+#[derive(Debug)]
+pub struct OrdZSetArenaCursor<'s, R> {
+    batch: &'s OrdZSetArena<R>,
+    pos: usize,
+    valid: bool,
+    // Copy of the current key returned by `Cursor::key`.
+    key: String,
+    // Copy of the last key returned by `Cursor::last_key`.
+    last_key: String,
+}
+
+impl<'s, R> OrdZSetArenaCursor<'s, R> {
+    fn new(batch: &'s OrdZSetArena<R>) -> Self {
+        let mut cursor = Self {
+            batch,
+            pos: 0,
+            valid: true,
+            key: String::new(),
+            last_key: String::new(),
+        };
+        cursor.update_key();
+        cursor
+    }
+
+    /// Returns the current key without copying it.  Panics if the cursor is
+    /// invalid.
+    pub fn key_str(&self) -> &'s str {
+        self.batch.key_at(self.pos)
+    }
+
+    fn update_key(&mut self) {
+        if self.pos < self.batch.len() {
+            self.key.clear();
+            self.key.push_str(self.batch.key_at(self.pos));
+        }
+    }
+}
+
+impl<'s, R> Cursor<'s, String, (), (), R> for OrdZSetArenaCursor<'s, R>
+where
+    R: DBWeight,
+{
+    fn key(&self) -> &String {
+        debug_assert!(self.key_valid());
+        &self.key
+    }
+
+    fn val(&self) -> &() {
+        &()
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &(), &R) -> U,
+    {
+        if self.key_valid() {
+            fold(init, &(), &self.batch.diffs[self.pos])
+        } else {
+            init
+        }
+    }
+
+    fn fold_times_through<F, U>(&mut self, _upper: &(), init: U, fold: F) -> U
+    where
+        F: FnMut(U, &(), &R) -> U,
+    {
+        self.fold_times(init, fold)
+    }
+
+    fn weight(&mut self) -> R {
+        debug_assert!(self.key_valid());
+        self.batch.diffs[self.pos].clone()
+    }
+
+    fn key_valid(&self) -> bool {
+        self.pos < self.batch.len()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.valid
+    }
+
+    fn step_key(&mut self) {
+        self.pos += 1;
+        self.valid = true;
+        self.update_key();
+    }
+
+    fn seek_key(&mut self, key: &String) {
+        let arena = &self.batch.arena;
+        self.pos += advance(&self.batch.keys[self.pos..], |&(offset, len)| {
+            &arena[offset..offset + len] < key.as_str()
+        });
+        self.valid = true;
+        self.update_key();
+    }
+
+    fn last_key(&mut self) -> Option<&String> {
+        if self.batch.is_empty() {
+            None
+        } else {
+            self.last_key.clear();
+            self.last_key
+                .push_str(self.batch.key_at(self.batch.len() - 1));
+            Some(&self.last_key)
+        }
+    }
+
+    fn step_val(&mut self) {
+        self.valid = false;
+    }
+
+    fn seek_val(&mut self, _val: &()) {}
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&()) -> bool + Clone,
+    {
+        if !predicate(&()) {
+            self.valid = false;
+        }
+    }
+
+    fn rewind_keys(&mut self) {
+        self.pos = 0;
+        self.valid = true;
+        self.update_key();
+    }
+
+    fn rewind_vals(&mut self) {
+        self.valid = true;
+    }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        Some(vec![self.pos, self.valid as usize])
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.pos = offsets[0];
+        self.valid = offsets[1] != 0;
+        self.update_key();
+    }
+}
+
+/// A builder for creating an [`OrdZSetArena`] from ordered and consolidated
+/// tuples.
+#[derive(SizeOf)]
+pub struct OrdZSetArenaBuilder<R> {
+    batch: OrdZSetArena<R>,
+}
+
+impl<R> Builder<String, (), R, OrdZSetArena<R>> for OrdZSetArenaBuilder<R>
+where
+    R: DBWeight,
+{
+    #[inline]
+    fn new_builder(_time: ()) -> Self {
+        Self {
+            batch: OrdZSetArena::default(),
+        }
+    }
+
+    #[inline]
+    fn with_capacity(_time: (), capacity: usize) -> Self {
+        Self {
+            batch: OrdZSetArena {
+                arena: String::new(),
+                keys: Vec::with_capacity(capacity),
+                diffs: Vec::with_capacity(capacity),
+            },
+        }
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.batch.keys.reserve(additional);
+        self.batch.diffs.reserve(additional);
+    }
+
+    #[inline]
+    fn push(&mut self, (key, diff): (String, R)) {
+        debug_assert!(
+            self.batch.is_empty() || self.batch.key_at(self.batch.len() - 1) < key.as_str()
+        );
+
+        self.batch.keys.push((self.batch.arena.len(), key.len()));
+        self.batch.arena.push_str(&key);
+        self.batch.diffs.push(diff);
+    }
+
+    #[inline(never)]
+    fn done(self) -> OrdZSetArena<R> {
+        self.batch
+    }
+}
+
+#[derive(Debug, SizeOf)]
+pub struct OrdZSetArenaConsumer<R> {
+    batch: OrdZSetArena<R>,
+    pos: usize,
+    // Copy of the current key returned by `Consumer::peek_key`.
+    key: String,
+}
+
+impl<R> OrdZSetArenaConsumer<R> {
+    fn new(batch: OrdZSetArena<R>) -> Self {
+        let mut consumer = Self {
+            batch,
+            pos: 0,
+            key: String::new(),
+        };
+        consumer.update_key();
+        consumer
+    }
+
+    fn update_key(&mut self) {
+        if self.pos < self.batch.len() {
+            self.key.clear();
+            self.key.push_str(self.batch.key_at(self.pos));
+        }
+    }
+}
+
+impl<R> Consumer<String, (), R, ()> for OrdZSetArenaConsumer<R>
+where
+    R: Clone,
+{
+    type ValueConsumer<'a>
+        = OrdZSetArenaValueConsumer<'a, R>
+    where
+        Self: 'a;
+
+    fn key_valid(&self) -> bool {
+        self.pos < self.batch.len()
+    }
+
+    fn peek_key(&self) -> &String {
+        debug_assert!(self.key_valid());
+        &self.key
+    }
+
+    fn next_key(&mut self) -> (String, Self::ValueConsumer<'_>) {
+        let key = self.batch.key_at(self.pos).to_string();
+        let diff = self.batch.diffs[self.pos].clone();
+
+        self.pos += 1;
+        self.update_key();
+
+        (
+            key,
+            OrdZSetArenaValueConsumer {
+                diff: Some(diff),
+                __type: PhantomData,
+            },
+        )
+    }
+
+    fn seek_key(&mut self, key: &String) {
+        let arena = &self.batch.arena;
+        self.pos += advance(&self.batch.keys[self.pos..], |&(offset, len)| {
+            &arena[offset..offset + len] < key.as_str()
+        });
+        self.update_key();
+    }
+}
+
+#[derive(Debug)]
+pub struct OrdZSetArenaValueConsumer<'a, R> {
+    diff: Option<R>,
+    __type: PhantomData<&'a ()>,
+}
+
+impl<'a, R> ValueConsumer<'a, (), R, ()> for OrdZSetArenaValueConsumer<'a, R> {
+    fn value_valid(&self) -> bool {
+        self.diff.is_some()
+    }
+
+    fn next_value(&mut self) -> ((), R, ()) {
+        ((), self.diff.take().unwrap(), ())
+    }
+
+    fn remaining_values(&self) -> usize {
+        self.diff.is_some() as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OrdZSetArena;
+    use crate::{
+        trace::{
+            test_batch::batch_to_tuples, Batch, BatchReader, Consumer, Cursor, Merger, Spine,
+            Trace, ValueConsumer,
+        },
+        OrdZSet,
+    };
+    use proptest::{collection::vec, prelude::*};
+    use size_of::SizeOf;
+
+    fn tuples() -> impl Strategy<Value = Vec<(String, isize)>> {
+        vec(("[a-e]{0,4}", -2..=2isize), 0..100)
+    }
+
+    #[test]
+    fn cursor() {
+        let batch = OrdZSetArena::from_keys(
+            (),
+            vec![
+                ("foo".to_string(), 1),
+                ("bar".to_string(), 2),
+                ("".to_string(), 1),
+                ("foo".to_string(), -1),
+                ("baz".to_string(), -1),
+                ("qux".to_string(), 3),
+            ],
+        );
+        assert_eq!(batch.len(), 4);
+
+        let mut cursor = batch.cursor();
+        let mut contents = Vec::new();
+        while cursor.key_valid() {
+            assert_eq!(cursor.key(), cursor.key_str());
+            while cursor.val_valid() {
+                contents.push((cursor.key_str(), cursor.weight()));
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+        assert_eq!(contents, vec![("", 1), ("bar", 2), ("baz", -1), ("qux", 3)]);
+
+        assert_eq!(cursor.last_key(), Some(&"qux".to_string()));
+
+        cursor.rewind_keys();
+        cursor.seek_key(&"bas".to_string());
+        assert_eq!(cursor.key_str(), "baz");
+        cursor.seek_key(&"qux".to_string());
+        assert_eq!(cursor.key(), "qux");
+        cursor.seek_key(&"zzz".to_string());
+        assert!(!cursor.key_valid());
+
+        let mut consumer = batch.clone().consumer();
+        let mut contents = Vec::new();
+        consumer.seek_key(&"bar".to_string());
+        while consumer.key_valid() {
+            let peeked = consumer.peek_key().clone();
+            let (key, mut values) = consumer.next_key();
+            assert_eq!(key, peeked);
+            while values.value_valid() {
+                let ((), weight, ()) = values.next_value();
+                contents.push((key.clone(), weight));
+            }
+        }
+        assert_eq!(
+            contents,
+            vec![
+                ("bar".to_string(), 2),
+                ("baz".to_string(), -1),
+                ("qux".to_string(), 3)
+            ]
+        );
+
+        let mut truncated = batch;
+        truncated.truncate_keys_below(&"baz".to_string());
+        assert_eq!(
+            truncated,
+            OrdZSetArena::from_keys((), vec![("baz".to_string(), -1), ("qux".to_string(), 3)])
+        );
+    }
+
+    #[test]
+    fn size_of() {
+        let keys: Vec<_> = (0..1000)
+            .map(|i| (format!("a moderately long string key #{i}"), 1))
+            .collect();
+
+        let arena_batch = OrdZSetArena::<isize>::from_keys((), keys.clone());
+        let string_batch = OrdZSet::<String, isize>::from_keys((), keys);
+
+        let arena_size = arena_batch.size_of();
+        let string_size = string_batch.size_of();
+
+        // An arena, an array of key ranges, and an array of weights.
+        assert_eq!(arena_size.distinct_allocations(), 3);
+        assert!(string_size.distinct_allocations() > 1000);
+        assert!(arena_size.total_bytes() < string_size.total_bytes());
+    }
+
+    proptest! {
+        #[test]
+        fn cursor_matches_ord_zset(tuples in tuples()) {
+            let batch = OrdZSetArena::from_keys((), tuples.clone());
+            let reference = OrdZSet::from_keys((), tuples);
+
+            prop_assert_eq!(batch_to_tuples(&batch), batch_to_tuples(&reference));
+        }
+
+        #[test]
+        fn merge_matches_ord_zset(tuples1 in tuples(), tuples2 in tuples(), fuel in 1..10isize) {
+            let batch1 = OrdZSetArena::from_keys((), tuples1.clone());
+            let batch2 = OrdZSetArena::from_keys((), tuples2.clone());
+            let reference = OrdZSet::from_keys((), tuples1).merge(&OrdZSet::from_keys((), tuples2));
+
+            let merged = batch1.merge(&batch2);
+            prop_assert_eq!(batch_to_tuples(&merged), batch_to_tuples(&reference));
+
+            // Perform the merge in small steps.
+            let mut merger = batch1.begin_merge(&batch2);
+            loop {
+                let mut step_fuel = fuel;
+                merger.work(&batch1, &batch2, &None, &mut step_fuel);
+                if step_fuel > 0 {
+                    break;
+                }
+            }
+            prop_assert_eq!(merger.done(), merged.clone());
+
+            // Merging doesn't let unreachable bytes accumulate in the arena.
+            let live_bytes: usize = merged.iter().map(|(key, _)| key.len()).sum();
+            prop_assert!(merged.arena.len() <= 2 * live_bytes);
+        }
+
+        #[test]
+        fn spine_matches_ord_zset(batches in vec(tuples(), 0..20)) {
+            let mut trace: Spine<OrdZSetArena<isize>> = Spine::new(None);
+            let mut reference: Spine<OrdZSet<String, isize>> = Spine::new(None);
+
+            for tuples in batches {
+                trace.insert(OrdZSetArena::from_keys((), tuples.clone()));
+                reference.insert(OrdZSet::from_keys((), tuples));
+
+                prop_assert_eq!(batch_to_tuples(&trace), batch_to_tuples(&reference));
+            }
+        }
+    }
+}
@@ -8,7 +8,8 @@ use crate::{
                 ColumnLayer, ColumnLayerBuilder, ColumnLayerConsumer, ColumnLayerCursor,
                 ColumnLayerValues,
             },
-            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, Trie, TupleBuilder,
+            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, SearchHint, Trie,
+            TupleBuilder,
         },
         ord::merge_batcher::MergeBatcher,
         Batch, BatchReader, Builder, Consumer, Cursor, Merger, ValueConsumer,
@@ -53,8 +54,8 @@ impl<K, R> OrdZSet<K, R> {
     #[inline]
     pub fn from_columns(mut keys: Vec<K>, mut diffs: Vec<R>) -> Self
     where
-        K: Ord,
-        R: HasZero + AddAssign,
+        K: Ord + 'static,
+        R: HasZero + AddAssign + 'static,
     {
         consolidate_payload_from(&mut keys, &mut diffs, 0);
 
@@ -64,6 +65,27 @@ impl<K, R> OrdZSet<K, R> {
             layer: unsafe { ColumnLayer::from_parts(keys, diffs, 0) },
         }
     }
+
+    /// Create a batch from sorted columns of keys and their weights.
+    ///
+    /// Runs of equal keys are consolidated and keys whose weights add up to
+    /// zero are removed.  Unlike [`from_keys`](`Batch::from_keys`), this
+    /// does not sort the input and reuses `keys` and `weights` as the
+    /// storage of the batch instead of copying them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` and `weights` have different lengths.  When debug
+    /// assertions are enabled, also panics if `keys` are not sorted.
+    pub fn from_sorted_columns(keys: Vec<K>, weights: Vec<R>) -> Self
+    where
+        K: Ord + 'static,
+        R: AddAssignByRef + HasZero + 'static,
+    {
+        Self {
+            layer: ColumnLayer::from_sorted_columns(keys, weights),
+        }
+    }
 }
 
 impl<K, R> Display for OrdZSet<K, R>
@@ -265,6 +287,9 @@ where
 
     fn recede_to(&mut self, _frontier: &()) {}
 
+    // `()` is the only value, so there is nothing below `lower_bound`.
+    fn truncate_values_below(&mut self, _lower_bound: &()) {}
+
     fn empty(_time: Self::Time) -> Self {
         Self {
             layer: ColumnLayer::empty(),
@@ -383,6 +408,11 @@ where
         self.valid = true;
     }
 
+    fn seek_key_with_hint(&mut self, key: &K, hint: SearchHint) {
+        self.cursor.seek_with_hint(key, hint);
+        self.valid = true;
+    }
+
     fn last_key(&mut self) -> Option<&K> {
         self.cursor.last_item().map(|(k, _)| k)
     }
@@ -410,6 +440,15 @@ where
     fn rewind_vals(&mut self) {
         self.valid = true;
     }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        Some(vec![self.cursor.position(), self.valid as usize])
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.cursor.move_to(offsets[0]);
+        self.valid = offsets[1] != 0;
+    }
 }
 
 /// A builder for creating layers from unsorted update tuples.
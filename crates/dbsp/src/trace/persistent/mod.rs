@@ -1,7 +1,10 @@
 //! This module implements logic and datastructures to provide a trace that is
 //! using on-disk storage with the help of RocksDB.
 
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+};
 
 use bincode::{
     config::{BigEndian, Fixint},
@@ -14,16 +17,18 @@ use once_cell::sync::Lazy;
 use rocksdb::{Cache, DBCompressionType, Options, DB};
 use uuid::Uuid;
 
+use crate::Runtime;
+
 mod cursor;
 mod tests;
 mod trace;
 
 /// A single value with many time and weight tuples.
-type ValueTimeWeights<V, T, R> = (V, Vec<(T, R)>);
+pub(crate) type ValueTimeWeights<V, T, R> = (V, Vec<(T, R)>);
 
 /// A collection of values with time and weight tuples, this is the type that we
 /// persist in RocksDB under values.
-type Values<V, T, R> = Vec<ValueTimeWeights<V, T, R>>;
+pub(crate) type Values<V, T, R> = Vec<ValueTimeWeights<V, T, R>>;
 
 /// The cursor for the persistent trace.
 pub use cursor::PersistentTraceCursor;
@@ -39,10 +44,18 @@ const DB_DRAM_CACHE_SIZE: usize = 1024 * 1024 * 1024;
 
 /// Path of the RocksDB database file on disk.
 ///
-/// # TODO
-/// Eventually should be supplied as a command-line argument or provided in a
-/// config file.
-static DB_PATH: Lazy<String> = Lazy::new(|| format!("/tmp/{}.db", Uuid::new_v4()));
+/// The database is created within the storage directory of the runtime that
+/// first opens it (see [`RuntimeConfig::storage_dir`]), or within `/tmp` if
+/// there is no runtime or it has no storage directory.
+///
+/// [`RuntimeConfig::storage_dir`]: crate::RuntimeConfig::storage_dir
+static DB_PATH: Lazy<PathBuf> = Lazy::new(|| {
+    let storage_dir = Runtime::runtime()
+        .and_then(|runtime| runtime.storage_dir().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+
+    storage_dir.join(format!("{}.db", Uuid::new_v4()))
+});
 
 /// Options for the RocksDB database
 static DB_OPTS: Lazy<Options> = Lazy::new(|| {
@@ -77,7 +90,7 @@ static ROCKS_DB_INSTANCE: Lazy<DB> = Lazy::new(|| {
 });
 
 /// Configuration we use for encodings/decodings to/from RocksDB data.
-static BINCODE_CONFIG: bincode::config::Configuration<BigEndian, Fixint> =
+pub(crate) static BINCODE_CONFIG: bincode::config::Configuration<BigEndian, Fixint> =
     bincode::config::standard()
         .with_fixed_int_encoding()
         .with_big_endian();
@@ -0,0 +1,416 @@
+//! Batches stored in files on disk.
+
+use crate::{
+    algebra::Lattice,
+    time::{Antichain, AntichainRef},
+    trace::{
+        consolidation::consolidate,
+        cursor::Cursor,
+        persistent::{Values, BINCODE_CONFIG},
+        DBData, DBTimestamp, DBWeight,
+    },
+};
+use size_of::{Context, SizeOf};
+use std::{
+    cmp::max,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+/// A block of a spilled batch: a run of consecutive keys, each with its
+/// values and their `(time, weight)` pairs.
+pub(super) type Block<K, V, T, R> = Vec<(K, Values<V, T, R>)>;
+
+/// The number of `(time, weight)` pairs buffered before a block is written to
+/// disk.
+const BLOCK_TUPLES: usize = 1024;
+
+/// A file holding the blocks of a spilled batch, deleted when dropped.
+struct SpillFile {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl SpillFile {
+    fn create(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(format!("{}.spill", Uuid::new_v4()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Write for SpillFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.get_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.get_mut().unwrap().flush()
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            tracing::warn!(
+                "failed to remove spill file {}: {error}",
+                self.path.display(),
+            );
+        }
+    }
+}
+
+/// The location of a block within a spill file.
+#[derive(SizeOf)]
+struct BlockInfo<K> {
+    first_key: K,
+    offset: u64,
+    bytes: usize,
+}
+
+struct SpilledInner<K, V, T, R> {
+    file: SpillFile,
+    blocks: Vec<BlockInfo<K>>,
+    last_key: Option<K>,
+    lower: Antichain<T>,
+    upper: Antichain<T>,
+    len: usize,
+    key_count: usize,
+    _phantom: PhantomData<(V, R)>,
+}
+
+impl<K, V, T, R> SizeOf for SpilledInner<K, V, T, R>
+where
+    K: SizeOf,
+    T: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        // The updates themselves live on disk, only the block index and the
+        // batch's bounds are kept in memory
+        self.blocks.size_of_children(context);
+        self.last_key.size_of_children(context);
+        self.lower.size_of_children(context);
+        self.upper.size_of_children(context);
+    }
+}
+
+/// A batch whose updates are stored in a file on disk.
+///
+/// Only the first key of each block of updates is kept in memory, blocks are
+/// read and decoded on demand by the batch's cursors.  Clones share the same
+/// file, which is deleted once the batch and all of its clones are dropped.
+pub struct SpilledBatch<K, V, T, R> {
+    inner: Arc<SpilledInner<K, V, T, R>>,
+    lower_key_bound: Option<K>,
+}
+
+impl<K, V, T, R> Clone for SpilledBatch<K, V, T, R>
+where
+    K: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            lower_key_bound: self.lower_key_bound.clone(),
+        }
+    }
+}
+
+impl<K, V, T, R> SizeOf for SpilledBatch<K, V, T, R>
+where
+    K: SizeOf,
+    T: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.inner.size_of_with_context(context);
+        self.lower_key_bound.size_of_children(context);
+    }
+}
+
+impl<K, V, T, R> SpilledBatch<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    /// Writes the contents of `cursor` to a new spill file within `dir`.
+    pub(super) fn spill<'s, C>(
+        dir: &Path,
+        cursor: &mut C,
+        lower: AntichainRef<'_, T>,
+        upper: AntichainRef<'_, T>,
+    ) -> io::Result<Self>
+    where
+        C: Cursor<'s, K, V, T, R>,
+    {
+        let mut writer = SpillWriter::new(dir)?;
+        writer.copy_from(cursor, None, T::clone)?;
+        writer.finish(lower.to_owned(), upper.to_owned())
+    }
+
+    /// The directory the batch's file is stored in.
+    pub(super) fn dir(&self) -> &Path {
+        self.inner
+            .file
+            .path
+            .parent()
+            .expect("spill files are always created within a directory")
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.inner.len
+    }
+
+    pub(super) fn key_count(&self) -> usize {
+        self.inner.key_count
+    }
+
+    pub(super) fn lower(&self) -> AntichainRef<'_, T> {
+        self.inner.lower.as_ref()
+    }
+
+    pub(super) fn upper(&self) -> AntichainRef<'_, T> {
+        self.inner.upper.as_ref()
+    }
+
+    pub(super) fn lower_key_bound(&self) -> Option<&K> {
+        self.lower_key_bound.as_ref()
+    }
+
+    pub(super) fn truncate_keys_below(&mut self, lower_bound: &K) {
+        let bound = match &self.lower_key_bound {
+            Some(bound) => max(bound, lower_bound).clone(),
+            None => lower_bound.clone(),
+        };
+        self.lower_key_bound = Some(bound);
+    }
+
+    /// Returns the last key of the batch, `None` if every key was truncated.
+    pub(super) fn last_key(&self) -> Option<&K> {
+        self.inner.last_key.as_ref().filter(|&key| {
+            self.lower_key_bound
+                .as_ref()
+                .map_or(true, |bound| key >= bound)
+        })
+    }
+
+    pub(super) fn num_blocks(&self) -> usize {
+        self.inner.blocks.len()
+    }
+
+    /// Returns the index of the block that `key` belongs in, searching from
+    /// block `from` onwards.
+    pub(super) fn find_block(&self, key: &K, from: usize) -> usize {
+        let blocks = &self.inner.blocks[from..];
+        from + blocks
+            .partition_point(|block| &block.first_key <= key)
+            .saturating_sub(1)
+    }
+
+    /// Reads and decodes the given block from disk.
+    pub(super) fn read_block(&self, index: usize) -> Block<K, V, T, R> {
+        let block = &self.inner.blocks[index];
+
+        let read = || -> io::Result<Block<K, V, T, R>> {
+            let mut bytes = vec![0; block.bytes];
+            {
+                let mut file = self.inner.file.file.lock().unwrap();
+                file.seek(SeekFrom::Start(block.offset))?;
+                file.read_exact(&mut bytes)?;
+            }
+
+            let (block, _) = bincode::decode_from_slice(&bytes, BINCODE_CONFIG)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+            Ok(block)
+        };
+
+        read().unwrap_or_else(|error| {
+            panic!(
+                "failed to read block {index} of spill file {}: {error}",
+                self.inner.file.path.display(),
+            )
+        })
+    }
+
+    /// Rewrites the batch with all timestamps pushed back to `frontier`, see
+    /// [`Batch::recede_to`](crate::trace::Batch::recede_to).
+    pub(super) fn recede_to(&self, frontier: &T) -> io::Result<Self> {
+        let mut cursor = super::cursor::SpilledCursor::new(self);
+        let mut writer = SpillWriter::new(self.dir())?;
+        writer.copy_from(&mut cursor, None, |time| time.meet(frontier))?;
+        writer.finish(self.inner.lower.clone(), self.inner.upper.clone())
+    }
+
+    /// Rewrites the batch without values less than `lower_bound`, see
+    /// [`Batch::truncate_values_below`](crate::trace::Batch::truncate_values_below).
+    pub(super) fn truncate_values_below(&self, lower_bound: &V) -> io::Result<Self> {
+        let mut cursor = super::cursor::SpilledCursor::new(self);
+        let mut writer = SpillWriter::new(self.dir())?;
+        writer.copy_from(&mut cursor, Some(lower_bound), T::clone)?;
+        writer.finish(self.inner.lower.clone(), self.inner.upper.clone())
+    }
+}
+
+/// Writes consecutive keys to a new spill file.
+pub(super) struct SpillWriter<K, V, T, R> {
+    file: BufWriter<SpillFile>,
+    offset: u64,
+    blocks: Vec<BlockInfo<K>>,
+    block: Block<K, V, T, R>,
+    block_tuples: usize,
+    last_key: Option<K>,
+    len: usize,
+    key_count: usize,
+}
+
+impl<K, V, T, R> SizeOf for SpillWriter<K, V, T, R>
+where
+    K: SizeOf,
+    V: SizeOf,
+    T: SizeOf,
+    R: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.blocks.size_of_children(context);
+        self.block.size_of_children(context);
+        self.last_key.size_of_children(context);
+    }
+}
+
+impl<K, V, T, R> SpillWriter<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    pub(super) fn new(dir: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(SpillFile::create(dir)?),
+            offset: 0,
+            blocks: Vec::new(),
+            block: Vec::new(),
+            block_tuples: 0,
+            last_key: None,
+            len: 0,
+            key_count: 0,
+        })
+    }
+
+    /// Appends `key` and its values, which must be non-empty, sorted and
+    /// consolidated.  Keys must be pushed in ascending order.
+    pub(super) fn push(&mut self, key: K, values: Values<V, T, R>) -> io::Result<()> {
+        debug_assert!(!values.is_empty());
+        debug_assert!(self.last_key.as_ref().map_or(true, |last| last < &key));
+
+        let tuples: usize = values.iter().map(|(_, times)| times.len()).sum();
+        self.len += tuples;
+        self.key_count += 1;
+        self.last_key = Some(key.clone());
+
+        self.block.push((key, values));
+        self.block_tuples += tuples;
+        if self.block_tuples >= BLOCK_TUPLES {
+            self.flush_block()?;
+        }
+
+        Ok(())
+    }
+
+    /// Appends the remaining contents of `cursor`, skipping values below
+    /// `lower_val_bound` and applying `map_time` to each timestamp.
+    fn copy_from<'s, C, F>(
+        &mut self,
+        cursor: &mut C,
+        lower_val_bound: Option<&V>,
+        map_time: F,
+    ) -> io::Result<()>
+    where
+        C: Cursor<'s, K, V, T, R>,
+        F: Fn(&T) -> T,
+    {
+        while cursor.key_valid() {
+            if let Some(bound) = lower_val_bound {
+                cursor.seek_val(bound);
+            }
+
+            let mut values = Vec::new();
+            while cursor.val_valid() {
+                let mut times = Vec::new();
+                cursor.map_times(|time, weight| times.push((map_time(time), weight.clone())));
+                consolidate(&mut times);
+
+                if !times.is_empty() {
+                    values.push((cursor.val().clone(), times));
+                }
+                cursor.step_val();
+            }
+
+            if !values.is_empty() {
+                self.push(cursor.key().clone(), values)?;
+            }
+            cursor.step_key();
+        }
+
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        let first_key = match self.block.first() {
+            Some((first_key, _)) => first_key.clone(),
+            None => return Ok(()),
+        };
+
+        let bytes = bincode::encode_to_vec(&self.block, BINCODE_CONFIG)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+        self.file.write_all(&bytes)?;
+
+        self.blocks.push(BlockInfo {
+            first_key,
+            offset: self.offset,
+            bytes: bytes.len(),
+        });
+        self.offset += bytes.len() as u64;
+        self.block.clear();
+        self.block_tuples = 0;
+
+        Ok(())
+    }
+
+    /// Writes out the remaining buffered keys and returns the finished batch.
+    pub(super) fn finish(
+        mut self,
+        lower: Antichain<T>,
+        upper: Antichain<T>,
+    ) -> io::Result<SpilledBatch<K, V, T, R>> {
+        self.flush_block()?;
+        let file = self.file.into_inner().map_err(|error| error.into_error())?;
+
+        Ok(SpilledBatch {
+            inner: Arc::new(SpilledInner {
+                file,
+                blocks: self.blocks,
+                last_key: self.last_key,
+                lower,
+                upper,
+                len: self.len,
+                key_count: self.key_count,
+                _phantom: PhantomData,
+            }),
+            lower_key_bound: None,
+        })
+    }
+}
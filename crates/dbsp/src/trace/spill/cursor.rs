@@ -0,0 +1,353 @@
+//! Cursors over spilled and spillable batches.
+
+use super::batch::{Block, SpilledBatch};
+use crate::{
+    algebra::PartialOrder,
+    trace::{
+        cursor::Cursor, layers::SearchHint, persistent::Values, Batch, DBData, DBTimestamp,
+        DBWeight,
+    },
+};
+use std::sync::Arc;
+
+/// The number of decoded blocks each cursor keeps around, so that rewinding
+/// and seeking within recently visited keys doesn't go back to disk.
+const CACHED_BLOCKS: usize = 4;
+
+/// A cursor over a [`SpilledBatch`], reading its blocks as it goes.
+pub struct SpilledCursor<'s, K, V, T, R> {
+    batch: &'s SpilledBatch<K, V, T, R>,
+    /// Recently read blocks, least recently used first.
+    cache: Vec<(usize, Arc<Block<K, V, T, R>>)>,
+    /// The current block, `None` once the cursor is exhausted.
+    block: Option<Arc<Block<K, V, T, R>>>,
+    block_index: usize,
+    key_index: usize,
+    val_index: usize,
+}
+
+impl<'s, K, V, T, R> SpilledCursor<'s, K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    pub(super) fn new(batch: &'s SpilledBatch<K, V, T, R>) -> Self {
+        let mut cursor = Self {
+            batch,
+            cache: Vec::with_capacity(CACHED_BLOCKS),
+            block: None,
+            block_index: 0,
+            key_index: 0,
+            val_index: 0,
+        };
+        cursor.rewind_keys();
+        cursor
+    }
+
+    /// Moves the cursor to the first key of the given block.
+    fn load(&mut self, index: usize) {
+        self.block_index = index;
+        self.key_index = 0;
+        self.val_index = 0;
+
+        if index >= self.batch.num_blocks() {
+            self.block = None;
+            return;
+        }
+
+        let block = match self.cache.iter().position(|&(cached, _)| cached == index) {
+            Some(position) => self.cache.remove(position).1,
+            None => {
+                if self.cache.len() == CACHED_BLOCKS {
+                    self.cache.remove(0);
+                }
+                Arc::new(self.batch.read_block(index))
+            }
+        };
+
+        self.cache.push((index, block.clone()));
+        self.block = Some(block);
+    }
+
+    fn values(&self) -> &Values<V, T, R> {
+        &self.block.as_ref().unwrap()[self.key_index].1
+    }
+
+    fn times(&self) -> &[(T, R)] {
+        &self.values()[self.val_index].1
+    }
+}
+
+impl<'s, K, V, T, R> Cursor<'s, K, V, T, R> for SpilledCursor<'s, K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn key_valid(&self) -> bool {
+        self.block.is_some()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.key_valid() && self.val_index < self.values().len()
+    }
+
+    fn key(&self) -> &K {
+        &self.block.as_ref().unwrap()[self.key_index].0
+    }
+
+    fn val(&self) -> &V {
+        &self.values()[self.val_index].0
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &T, &R) -> U,
+    {
+        self.times()
+            .iter()
+            .fold(init, |acc, (time, weight)| fold(acc, time, weight))
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &T, init: U, mut fold: F) -> U
+    where
+        F: FnMut(U, &T, &R) -> U,
+    {
+        self.times()
+            .iter()
+            .filter(|(time, _)| time.less_equal(upper))
+            .fold(init, |acc, (time, weight)| fold(acc, time, weight))
+    }
+
+    fn weight(&mut self) -> R
+    where
+        T: PartialEq<()>,
+    {
+        debug_assert!(self.val_valid());
+        self.times()[0].1.clone()
+    }
+
+    fn step_key(&mut self) {
+        if let Some(block) = &self.block {
+            self.key_index += 1;
+            self.val_index = 0;
+
+            if self.key_index == block.len() {
+                self.load(self.block_index + 1);
+            }
+        }
+    }
+
+    fn seek_key(&mut self, key: &K) {
+        if !self.key_valid() || self.key() >= key {
+            return;
+        }
+
+        let index = self.batch.find_block(key, self.block_index);
+        if index != self.block_index {
+            self.load(index);
+        }
+
+        if let Some(block) = &self.block {
+            self.key_index += block[self.key_index..].partition_point(|(k, _)| k < key);
+            self.val_index = 0;
+
+            // The key is past the end of its block, so it's at the start of the
+            // next one
+            if self.key_index == block.len() {
+                self.load(self.block_index + 1);
+            }
+        }
+    }
+
+    fn last_key(&mut self) -> Option<&K> {
+        self.batch.last_key()
+    }
+
+    fn step_val(&mut self) {
+        self.val_index += 1;
+    }
+
+    fn seek_val(&mut self, val: &V) {
+        if self.val_valid() {
+            self.val_index += self.values()[self.val_index..].partition_point(|(v, _)| v < val);
+        }
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&V) -> bool + Clone,
+    {
+        while self.val_valid() && !predicate(self.val()) {
+            self.val_index += 1;
+        }
+    }
+
+    fn rewind_keys(&mut self) {
+        self.load(0);
+
+        let batch = self.batch;
+        if let Some(bound) = batch.lower_key_bound() {
+            self.seek_key(bound);
+        }
+    }
+
+    fn rewind_vals(&mut self) {
+        self.val_index = 0;
+    }
+}
+
+/// A cursor over a [`SpillableBatch`](super::SpillableBatch).
+pub enum SpillableCursor<'s, B>
+where
+    B: Batch,
+{
+    Memory(B::Cursor<'s>),
+    Spilled(SpilledCursor<'s, B::Key, B::Val, B::Time, B::R>),
+}
+
+impl<'s, B> Cursor<'s, B::Key, B::Val, B::Time, B::R> for SpillableCursor<'s, B>
+where
+    B: Batch,
+{
+    fn key_valid(&self) -> bool {
+        match self {
+            Self::Memory(cursor) => cursor.key_valid(),
+            Self::Spilled(cursor) => cursor.key_valid(),
+        }
+    }
+
+    fn val_valid(&self) -> bool {
+        match self {
+            Self::Memory(cursor) => cursor.val_valid(),
+            Self::Spilled(cursor) => cursor.val_valid(),
+        }
+    }
+
+    fn key(&self) -> &B::Key {
+        match self {
+            Self::Memory(cursor) => cursor.key(),
+            Self::Spilled(cursor) => cursor.key(),
+        }
+    }
+
+    fn val(&self) -> &B::Val {
+        match self {
+            Self::Memory(cursor) => cursor.val(),
+            Self::Spilled(cursor) => cursor.val(),
+        }
+    }
+
+    fn fold_times<F, U>(&mut self, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        match self {
+            Self::Memory(cursor) => cursor.fold_times(init, fold),
+            Self::Spilled(cursor) => cursor.fold_times(init, fold),
+        }
+    }
+
+    fn fold_times_through<F, U>(&mut self, upper: &B::Time, init: U, fold: F) -> U
+    where
+        F: FnMut(U, &B::Time, &B::R) -> U,
+    {
+        match self {
+            Self::Memory(cursor) => cursor.fold_times_through(upper, init, fold),
+            Self::Spilled(cursor) => cursor.fold_times_through(upper, init, fold),
+        }
+    }
+
+    fn weight(&mut self) -> B::R
+    where
+        B::Time: PartialEq<()>,
+    {
+        match self {
+            Self::Memory(cursor) => cursor.weight(),
+            Self::Spilled(cursor) => cursor.weight(),
+        }
+    }
+
+    fn step_key(&mut self) {
+        match self {
+            Self::Memory(cursor) => cursor.step_key(),
+            Self::Spilled(cursor) => cursor.step_key(),
+        }
+    }
+
+    fn seek_key(&mut self, key: &B::Key) {
+        match self {
+            Self::Memory(cursor) => cursor.seek_key(key),
+            Self::Spilled(cursor) => cursor.seek_key(key),
+        }
+    }
+
+    fn seek_key_with_hint(&mut self, key: &B::Key, hint: SearchHint) {
+        match self {
+            Self::Memory(cursor) => cursor.seek_key_with_hint(key, hint),
+            Self::Spilled(cursor) => cursor.seek_key(key),
+        }
+    }
+
+    fn last_key(&mut self) -> Option<&B::Key> {
+        match self {
+            Self::Memory(cursor) => cursor.last_key(),
+            Self::Spilled(cursor) => cursor.last_key(),
+        }
+    }
+
+    fn step_val(&mut self) {
+        match self {
+            Self::Memory(cursor) => cursor.step_val(),
+            Self::Spilled(cursor) => cursor.step_val(),
+        }
+    }
+
+    fn seek_val(&mut self, val: &B::Val) {
+        match self {
+            Self::Memory(cursor) => cursor.seek_val(val),
+            Self::Spilled(cursor) => cursor.seek_val(val),
+        }
+    }
+
+    fn seek_val_with<P>(&mut self, predicate: P)
+    where
+        P: Fn(&B::Val) -> bool + Clone,
+    {
+        match self {
+            Self::Memory(cursor) => cursor.seek_val_with(predicate),
+            Self::Spilled(cursor) => cursor.seek_val_with(predicate),
+        }
+    }
+
+    fn rewind_keys(&mut self) {
+        match self {
+            Self::Memory(cursor) => cursor.rewind_keys(),
+            Self::Spilled(cursor) => cursor.rewind_keys(),
+        }
+    }
+
+    fn rewind_vals(&mut self) {
+        match self {
+            Self::Memory(cursor) => cursor.rewind_vals(),
+            Self::Spilled(cursor) => cursor.rewind_vals(),
+        }
+    }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        match self {
+            Self::Memory(cursor) => cursor.mark_offsets(),
+            Self::Spilled(cursor) => cursor.mark_offsets(),
+        }
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        match self {
+            Self::Memory(cursor) => cursor.rewind_to_offsets(offsets),
+            Self::Spilled(cursor) => cursor.rewind_to_offsets(offsets),
+        }
+    }
+}
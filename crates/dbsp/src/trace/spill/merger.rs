@@ -0,0 +1,227 @@
+//! Merging of spillable batches.
+
+use super::{
+    batch::{SpillWriter, SpilledBatch},
+    SpillableBatch,
+};
+use crate::{
+    algebra::Lattice,
+    time::Antichain,
+    trace::{
+        consolidation::consolidate, cursor::Cursor, Batch, BatchReader, DBData, DBTimestamp,
+        DBWeight, Merger,
+    },
+};
+use size_of::{Context, SizeOf};
+use std::cmp::max;
+
+/// A merger of two [`SpillableBatch`]es.
+///
+/// Batches that are both in memory are merged in memory by the underlying
+/// batch type's merger.  Merges involving a spilled batch are streamed
+/// straight into a new spill file, so that they never hold more than a block
+/// of updates in memory.
+pub enum SpillableMerger<B>
+where
+    B: Batch,
+{
+    Memory(B::Merger),
+    Streaming(StreamingMerger<B::Key, B::Val, B::Time, B::R>),
+}
+
+impl<B> SizeOf for SpillableMerger<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        match self {
+            Self::Memory(merger) => merger.size_of_children(context),
+            Self::Streaming(merger) => merger.size_of_children(context),
+        }
+    }
+}
+
+impl<B> Merger<B::Key, B::Val, B::Time, B::R, SpillableBatch<B>> for SpillableMerger<B>
+where
+    B: Batch,
+{
+    fn new_merger(source1: &SpillableBatch<B>, source2: &SpillableBatch<B>) -> Self {
+        let dir = match (source1, source2) {
+            (SpillableBatch::Memory(batch1), SpillableBatch::Memory(batch2)) => {
+                return Self::Memory(B::Merger::new_merger(batch1, batch2));
+            }
+            (SpillableBatch::Spilled(batch), _) | (_, SpillableBatch::Spilled(batch)) => {
+                batch.dir()
+            }
+        };
+
+        let writer = SpillWriter::new(dir).unwrap_or_else(|error| {
+            panic!(
+                "failed to create a spill file in {}: {error}",
+                dir.display(),
+            )
+        });
+
+        Self::Streaming(StreamingMerger {
+            writer,
+            next_key: None,
+            done: false,
+            lower: source1.lower().meet(source2.lower()),
+            upper: source1.upper().join(source2.upper()),
+        })
+    }
+
+    fn work(
+        &mut self,
+        source1: &SpillableBatch<B>,
+        source2: &SpillableBatch<B>,
+        lower_val_bound: &Option<B::Val>,
+        fuel: &mut isize,
+    ) {
+        match self {
+            Self::Memory(merger) => match (source1, source2) {
+                (SpillableBatch::Memory(batch1), SpillableBatch::Memory(batch2)) => {
+                    merger.work(batch1, batch2, lower_val_bound, fuel)
+                }
+                _ => unreachable!("in-memory merge of a spilled batch"),
+            },
+            Self::Streaming(merger) => merger.work(source1, source2, lower_val_bound, fuel),
+        }
+    }
+
+    fn done(self) -> SpillableBatch<B> {
+        match self {
+            Self::Memory(merger) => SpillableBatch::Memory(merger.done()),
+            Self::Streaming(merger) => SpillableBatch::Spilled(merger.done()),
+        }
+    }
+}
+
+/// A merge that writes its output to a spill file one key at a time.
+pub struct StreamingMerger<K, V, T, R> {
+    writer: SpillWriter<K, V, T, R>,
+    /// The key to resume merging from, `None` before the first call to `work`.
+    next_key: Option<K>,
+    done: bool,
+    lower: Antichain<T>,
+    upper: Antichain<T>,
+}
+
+impl<K, V, T, R> SizeOf for StreamingMerger<K, V, T, R>
+where
+    K: SizeOf,
+    V: SizeOf,
+    T: SizeOf,
+    R: SizeOf,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.writer.size_of_children(context);
+        self.next_key.size_of_children(context);
+        self.lower.size_of_children(context);
+        self.upper.size_of_children(context);
+    }
+}
+
+impl<K, V, T, R> StreamingMerger<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn work<B>(&mut self, source1: &B, source2: &B, lower_val_bound: &Option<V>, fuel: &mut isize)
+    where
+        B: BatchReader<Key = K, Val = V, Time = T, R = R>,
+    {
+        if self.done {
+            *fuel = max(*fuel, 1);
+            return;
+        }
+
+        let mut cursor1 = source1.cursor();
+        let mut cursor2 = source2.cursor();
+        if let Some(key) = &self.next_key {
+            cursor1.seek_key(key);
+            cursor2.seek_key(key);
+        }
+
+        let mut updates = Vec::new();
+        while *fuel > 0 {
+            let key = match (cursor1.key_valid(), cursor2.key_valid()) {
+                (true, true) => cursor1.key().min(cursor2.key()).clone(),
+                (true, false) => cursor1.key().clone(),
+                (false, true) => cursor2.key().clone(),
+                (false, false) => break,
+            };
+
+            take_updates(&mut cursor1, &key, lower_val_bound, &mut updates);
+            take_updates(&mut cursor2, &key, lower_val_bound, &mut updates);
+            consolidate(&mut updates);
+
+            *fuel -= max(updates.len(), 1) as isize;
+
+            let mut values: Vec<(V, Vec<(T, R)>)> = Vec::new();
+            for ((val, time), weight) in updates.drain(..) {
+                match values.last_mut() {
+                    Some((last, times)) if last == &val => times.push((time, weight)),
+                    _ => values.push((val, vec![(time, weight)])),
+                }
+            }
+
+            if !values.is_empty() {
+                self.writer
+                    .push(key, values)
+                    .unwrap_or_else(|error| panic!("failed to write to a spill file: {error}"));
+            }
+        }
+
+        match (cursor1.key_valid(), cursor2.key_valid()) {
+            (false, false) => {
+                self.done = true;
+                *fuel = max(*fuel, 1);
+            }
+            (true, true) => self.next_key = Some(cursor1.key().min(cursor2.key()).clone()),
+            (true, false) => self.next_key = Some(cursor1.key().clone()),
+            (false, true) => self.next_key = Some(cursor2.key().clone()),
+        }
+    }
+
+    fn done(self) -> SpilledBatch<K, V, T, R> {
+        debug_assert!(self.done);
+        self.writer
+            .finish(self.lower, self.upper)
+            .unwrap_or_else(|error| panic!("failed to write to a spill file: {error}"))
+    }
+}
+
+/// If `cursor` points to `key`, appends its updates with values not below
+/// `lower_val_bound` to `updates` and steps to the next key.
+fn take_updates<'s, C, K, V, T, R>(
+    cursor: &mut C,
+    key: &K,
+    lower_val_bound: &Option<V>,
+    updates: &mut Vec<((V, T), R)>,
+) where
+    C: Cursor<'s, K, V, T, R>,
+    K: Eq,
+    V: Clone,
+    T: Clone,
+    R: Clone,
+{
+    if !cursor.key_valid() || cursor.key() != key {
+        return;
+    }
+
+    if let Some(bound) = lower_val_bound {
+        cursor.seek_val(bound);
+    }
+
+    while cursor.val_valid() {
+        let val = cursor.val().clone();
+        cursor
+            .map_times(|time, weight| updates.push(((val.clone(), time.clone()), weight.clone())));
+        cursor.step_val();
+    }
+
+    cursor.step_key();
+}
@@ -0,0 +1,313 @@
+//! A trace that spills batches to disk once it exceeds a memory budget.
+//!
+//! [`SpillingSpine`] is a [`Spine`](crate::trace::spine_fueled::Spine) of
+//! [`SpillableBatch`]es.  Batches are inserted in memory; whenever the trace's
+//! in-memory footprint grows beyond its budget, the largest in-memory batches
+//! are written out to files and replaced with [`SpilledBatch`]es, which keep
+//! only a sparse index of their keys in memory.  Merges that involve a spilled
+//! batch stream their output directly to a new file.
+
+mod batch;
+mod cursor;
+mod merger;
+mod spine;
+mod tests;
+
+pub use batch::SpilledBatch;
+pub use cursor::{SpillableCursor, SpilledCursor};
+pub use merger::{SpillableMerger, StreamingMerger};
+pub use spine::SpillingSpine;
+
+use crate::{
+    time::{AntichainRef, Timestamp},
+    trace::{cursor::Cursor, Batch, BatchReader, Batcher, Builder},
+    NumEntries,
+};
+use size_of::{Context, SizeOf};
+use std::{collections::BTreeMap, io, path::Path};
+
+/// A batch that is either held in memory or spilled to disk.
+pub enum SpillableBatch<B>
+where
+    B: Batch,
+{
+    Memory(B),
+    Spilled(SpilledBatch<B::Key, B::Val, B::Time, B::R>),
+}
+
+impl<B> Clone for SpillableBatch<B>
+where
+    B: Batch,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Memory(batch) => Self::Memory(batch.clone()),
+            Self::Spilled(batch) => Self::Spilled(batch.clone()),
+        }
+    }
+}
+
+impl<B> SizeOf for SpillableBatch<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        match self {
+            Self::Memory(batch) => batch.size_of_children(context),
+            Self::Spilled(batch) => batch.size_of_children(context),
+        }
+    }
+}
+
+impl<B> NumEntries for SpillableBatch<B>
+where
+    B: Batch,
+{
+    const CONST_NUM_ENTRIES: Option<usize> = None;
+
+    fn num_entries_shallow(&self) -> usize {
+        self.len()
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<B> SpillableBatch<B>
+where
+    B: Batch,
+{
+    /// Returns `true` if the batch is held in memory.
+    pub fn is_memory(&self) -> bool {
+        matches!(self, Self::Memory(_))
+    }
+
+    /// Writes the batch to a new file within `dir`, unless it's already been
+    /// spilled.
+    pub fn spill(&mut self, dir: &Path) -> io::Result<()> {
+        if let Self::Memory(batch) = self {
+            let spilled =
+                SpilledBatch::spill(dir, &mut batch.cursor(), batch.lower(), batch.upper())?;
+            *self = Self::Spilled(spilled);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the contents of the batch as an in-memory batch, reading it
+    /// back from disk if it's been spilled.
+    pub fn into_memory(self) -> B {
+        let spilled = match self {
+            Self::Memory(batch) => return batch,
+            Self::Spilled(spilled) => spilled,
+        };
+
+        // Builders assign the same time to all of their updates, so we build a
+        // batch per distinct timestamp and merge them.
+        let mut builders: BTreeMap<B::Time, B::Builder> = BTreeMap::new();
+        let mut cursor = SpilledCursor::new(&spilled);
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                let mut times = Vec::new();
+                cursor.map_times(|time, weight| times.push((time.clone(), weight.clone())));
+
+                for (time, weight) in times {
+                    let item = B::item_from(cursor.key().clone(), cursor.val().clone());
+                    builders
+                        .entry(time.clone())
+                        .or_insert_with(|| B::Builder::new_builder(time))
+                        .push((item, weight));
+                }
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        builders
+            .into_values()
+            .map(|builder| builder.done())
+            .reduce(|merged, batch| merged.merge(&batch))
+            .unwrap_or_else(|| B::empty(B::Time::minimum()))
+    }
+}
+
+impl<B> BatchReader for SpillableBatch<B>
+where
+    B: Batch,
+{
+    type Key = B::Key;
+    type Val = B::Val;
+    type Time = B::Time;
+    type R = B::R;
+
+    type Cursor<'s> = SpillableCursor<'s, B>;
+    type Consumer = B::Consumer;
+
+    fn cursor(&self) -> Self::Cursor<'_> {
+        match self {
+            Self::Memory(batch) => SpillableCursor::Memory(batch.cursor()),
+            Self::Spilled(batch) => SpillableCursor::Spilled(SpilledCursor::new(batch)),
+        }
+    }
+
+    fn consumer(self) -> Self::Consumer {
+        self.into_memory().consumer()
+    }
+
+    fn key_count(&self) -> usize {
+        match self {
+            Self::Memory(batch) => batch.key_count(),
+            Self::Spilled(batch) => batch.key_count(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Memory(batch) => batch.len(),
+            Self::Spilled(batch) => batch.len(),
+        }
+    }
+
+    fn lower(&self) -> AntichainRef<'_, Self::Time> {
+        match self {
+            Self::Memory(batch) => batch.lower(),
+            Self::Spilled(batch) => batch.lower(),
+        }
+    }
+
+    fn upper(&self) -> AntichainRef<'_, Self::Time> {
+        match self {
+            Self::Memory(batch) => batch.upper(),
+            Self::Spilled(batch) => batch.upper(),
+        }
+    }
+
+    fn truncate_keys_below(&mut self, lower_bound: &Self::Key) {
+        match self {
+            Self::Memory(batch) => batch.truncate_keys_below(lower_bound),
+            Self::Spilled(batch) => batch.truncate_keys_below(lower_bound),
+        }
+    }
+}
+
+impl<B> Batch for SpillableBatch<B>
+where
+    B: Batch,
+{
+    type Item = B::Item;
+    type Batcher = SpillableBatcher<B>;
+    type Builder = SpillableBuilder<B>;
+    type Merger = SpillableMerger<B>;
+
+    fn item_from(key: Self::Key, val: Self::Val) -> Self::Item {
+        B::item_from(key, val)
+    }
+
+    fn from_keys(time: Self::Time, keys: Vec<(Self::Key, Self::R)>) -> Self
+    where
+        Self::Val: From<()>,
+    {
+        Self::Memory(B::from_keys(time, keys))
+    }
+
+    fn recede_to(&mut self, frontier: &Self::Time) {
+        match self {
+            Self::Memory(batch) => batch.recede_to(frontier),
+            Self::Spilled(batch) => {
+                *batch = batch
+                    .recede_to(frontier)
+                    .unwrap_or_else(|error| panic!("failed to rewrite a spilled batch: {error}"));
+            }
+        }
+    }
+
+    fn truncate_values_below(&mut self, lower_bound: &Self::Val) {
+        match self {
+            Self::Memory(batch) => batch.truncate_values_below(lower_bound),
+            Self::Spilled(batch) => {
+                *batch = batch
+                    .truncate_values_below(lower_bound)
+                    .unwrap_or_else(|error| panic!("failed to rewrite a spilled batch: {error}"));
+            }
+        }
+    }
+}
+
+/// A batcher for [`SpillableBatch`]es, which produces in-memory batches.
+pub struct SpillableBatcher<B>(B::Batcher)
+where
+    B: Batch;
+
+impl<B> SizeOf for SpillableBatcher<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.0.size_of_children(context);
+    }
+}
+
+impl<B> Batcher<B::Item, B::Time, B::R, SpillableBatch<B>> for SpillableBatcher<B>
+where
+    B: Batch,
+{
+    fn new_batcher(time: B::Time) -> Self {
+        Self(B::Batcher::new_batcher(time))
+    }
+
+    fn push_batch(&mut self, batch: &mut Vec<(B::Item, B::R)>) {
+        self.0.push_batch(batch);
+    }
+
+    fn push_consolidated_batch(&mut self, batch: &mut Vec<(B::Item, B::R)>) {
+        self.0.push_consolidated_batch(batch);
+    }
+
+    fn tuples(&self) -> usize {
+        self.0.tuples()
+    }
+
+    fn seal(self) -> SpillableBatch<B> {
+        SpillableBatch::Memory(self.0.seal())
+    }
+}
+
+/// A builder for [`SpillableBatch`]es, which produces in-memory batches.
+pub struct SpillableBuilder<B>(B::Builder)
+where
+    B: Batch;
+
+impl<B> SizeOf for SpillableBuilder<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.0.size_of_children(context);
+    }
+}
+
+impl<B> Builder<B::Item, B::Time, B::R, SpillableBatch<B>> for SpillableBuilder<B>
+where
+    B: Batch,
+{
+    fn new_builder(time: B::Time) -> Self {
+        Self(B::Builder::new_builder(time))
+    }
+
+    fn with_capacity(time: B::Time, capacity: usize) -> Self {
+        Self(B::Builder::with_capacity(time, capacity))
+    }
+
+    fn push(&mut self, element: (B::Item, B::R)) {
+        self.0.push(element);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional);
+    }
+
+    fn done(self) -> SpillableBatch<B> {
+        SpillableBatch::Memory(self.0.done())
+    }
+}
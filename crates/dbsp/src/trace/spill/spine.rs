@@ -0,0 +1,243 @@
+//! A spine that keeps its in-memory footprint within a budget.
+
+use super::SpillableBatch;
+use crate::{
+    circuit::Activator,
+    time::AntichainRef,
+    trace::{
+        spine_fueled::{MergeState, MergeVariant, Spine, SpineConsumer, SpineCursor},
+        Batch, BatchReader, Trace,
+    },
+    NumEntries,
+};
+use size_of::{Context, SizeOf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// A [`Spine`] that spills batches to disk once its in-memory footprint, as
+/// measured by [`SizeOf`], exceeds `budget_bytes`.
+///
+/// Inserted batches are kept in memory until the budget is exceeded, at which
+/// point the largest in-memory batches are written to files within the
+/// spine's directory.  Merges in progress are never spilled, so the budget
+/// may be temporarily exceeded while two in-memory batches are being merged.
+///
+/// Measuring the whole spine walks every in-memory batch, so the spine keeps
+/// a running count of its in-memory bytes instead, which grows by the size of
+/// each inserted batch, and only measures itself once the count exceeds the
+/// budget.  Completed merges never take more memory than their inputs (and
+/// merges with a spilled batch are written to disk), so the count only
+/// overestimates the footprint of the spine until it's measured again.
+pub struct SpillingSpine<B>
+where
+    B: Batch,
+{
+    spine: Spine<SpillableBatch<B>>,
+    budget_bytes: usize,
+    /// Running count of the bytes held in memory, see above.
+    memory_bytes: usize,
+    dir: PathBuf,
+}
+
+impl<B> SizeOf for SpillingSpine<B>
+where
+    B: Batch,
+{
+    fn size_of_children(&self, context: &mut Context) {
+        self.spine.size_of_children(context);
+    }
+}
+
+// TODO.
+impl<B> Clone for SpillingSpine<B>
+where
+    B: Batch,
+{
+    fn clone(&self) -> Self {
+        unimplemented!()
+    }
+}
+
+impl<B> NumEntries for SpillingSpine<B>
+where
+    B: Batch,
+{
+    const CONST_NUM_ENTRIES: Option<usize> = None;
+
+    fn num_entries_shallow(&self) -> usize {
+        self.spine.num_entries_shallow()
+    }
+
+    fn num_entries_deep(&self) -> usize {
+        self.spine.num_entries_deep()
+    }
+}
+
+impl<B> SpillingSpine<B>
+where
+    B: Batch,
+{
+    /// Creates an empty spine that spills batches to files within `dir` once
+    /// it holds more than `budget_bytes` bytes in memory.
+    pub fn with_budget<P>(budget_bytes: usize, dir: P, activator: Option<Activator>) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            spine: Spine::new(activator),
+            budget_bytes,
+            memory_bytes: 0,
+            dir: dir.into(),
+        }
+    }
+
+    /// The spine's memory budget in bytes.
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// The directory that batches are spilled to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Once the running count of in-memory bytes exceeds the budget, measures
+    /// the spine and spills the largest in-memory batches until it fits
+    /// within its budget or there are no more batches that can be spilled.
+    fn spill(&mut self) {
+        if self.memory_bytes <= self.budget_bytes {
+            return;
+        }
+
+        self.memory_bytes = self.spine.size_of().total_bytes();
+        while self.memory_bytes > self.budget_bytes {
+            let largest = self
+                .spine
+                .merging
+                .iter_mut()
+                .filter_map(spillable_batch)
+                .map(|batch| (batch.size_of().total_bytes(), batch))
+                .max_by_key(|(bytes, _)| *bytes);
+
+            let (bytes, batch) = match largest {
+                Some(largest) => largest,
+                None => break,
+            };
+
+            if let Err(error) = batch.spill(&self.dir) {
+                tracing::warn!("failed to spill a batch to {}: {error}", self.dir.display());
+                break;
+            }
+            self.memory_bytes =
+                self.memory_bytes.saturating_sub(bytes) + batch.size_of().total_bytes();
+        }
+    }
+}
+
+/// Returns the batch held in `state` if it's in memory and not being merged.
+fn spillable_batch<B>(state: &mut MergeState<SpillableBatch<B>>) -> Option<&mut SpillableBatch<B>>
+where
+    B: Batch,
+{
+    match state {
+        MergeState::Single(Some(batch))
+        | MergeState::Double(MergeVariant::Complete(Some(batch)))
+            if batch.is_memory() =>
+        {
+            Some(batch)
+        }
+        _ => None,
+    }
+}
+
+impl<B> BatchReader for SpillingSpine<B>
+where
+    B: Batch,
+{
+    type Key = B::Key;
+    type Val = B::Val;
+    type Time = B::Time;
+    type R = B::R;
+
+    type Cursor<'s> = SpineCursor<'s, SpillableBatch<B>>;
+    type Consumer = SpineConsumer<SpillableBatch<B>>;
+
+    fn cursor(&self) -> Self::Cursor<'_> {
+        self.spine.cursor()
+    }
+
+    fn consumer(self) -> Self::Consumer {
+        self.spine.consumer()
+    }
+
+    fn key_count(&self) -> usize {
+        self.spine.key_count()
+    }
+
+    fn len(&self) -> usize {
+        self.spine.len()
+    }
+
+    fn lower(&self) -> AntichainRef<'_, Self::Time> {
+        self.spine.lower()
+    }
+
+    fn upper(&self) -> AntichainRef<'_, Self::Time> {
+        self.spine.upper()
+    }
+
+    fn truncate_keys_below(&mut self, lower_bound: &Self::Key) {
+        self.spine.truncate_keys_below(lower_bound);
+    }
+}
+
+impl<B> Trace for SpillingSpine<B>
+where
+    B: Batch,
+{
+    type Batch = B;
+
+    fn new(activator: Option<Activator>) -> Self {
+        Self::with_budget(usize::MAX, env::temp_dir(), activator)
+    }
+
+    fn recede_to(&mut self, frontier: &B::Time) {
+        self.spine.recede_to(frontier);
+        self.spill();
+    }
+
+    fn exert(&mut self, effort: &mut isize) {
+        self.spine.exert(effort);
+        self.spill();
+    }
+
+    fn consolidate(self) -> Option<B> {
+        self.spine.consolidate().map(SpillableBatch::into_memory)
+    }
+
+    fn insert(&mut self, batch: B) {
+        self.memory_bytes = self
+            .memory_bytes
+            .saturating_add(batch.size_of().total_bytes());
+        self.spine.insert(SpillableBatch::Memory(batch));
+        self.spill();
+    }
+
+    fn clear_dirty_flag(&mut self) {
+        self.spine.clear_dirty_flag();
+    }
+
+    fn dirty(&self) -> bool {
+        self.spine.dirty()
+    }
+
+    fn truncate_values_below(&mut self, lower_bound: &Self::Val) {
+        self.spine.truncate_values_below(lower_bound);
+    }
+
+    fn lower_value_bound(&self) -> &Option<Self::Val> {
+        self.spine.lower_value_bound()
+    }
+}
@@ -0,0 +1,149 @@
+//! Tests that check that a spilling spine behaves like an in-memory spine.
+#![cfg(test)]
+
+use super::SpillingSpine;
+use crate::{
+    operator::Generator,
+    trace::{
+        ord::{OrdIndexedZSet, OrdValBatch},
+        spine_fueled::Spine,
+        test_batch::{assert_batch_eq, assert_trace_eq},
+        Batch, Trace,
+    },
+    RootCircuit,
+};
+use size_of::SizeOf;
+use std::{env, fs, path::PathBuf};
+use uuid::Uuid;
+
+type TestBatch = OrdValBatch<u64, u64, u32, i64>;
+
+/// A fresh directory to spill batches to, removed when dropped.
+struct SpillDir(PathBuf);
+
+impl SpillDir {
+    fn new() -> Self {
+        let dir = env::temp_dir().join(format!("dbsp-spill-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn test_batch(time: u32, step: u64) -> TestBatch {
+    let tuples = (0..500)
+        .map(|i| {
+            (
+                ((i * 7 + step) % 300, (i + step) % 11),
+                if i % 3 == 0 { -1 } else { 1 },
+            )
+        })
+        .collect();
+    TestBatch::from_tuples(time, tuples)
+}
+
+#[test]
+fn spilled_spine_matches_spine() {
+    let dir = SpillDir::new();
+    let mut spilled = SpillingSpine::<TestBatch>::with_budget(0, &dir.0, None);
+    let mut reference = Spine::<TestBatch>::new(None);
+
+    for step in 0..20 {
+        let batch = test_batch(step as u32, step);
+        spilled.insert(batch.clone());
+        reference.insert(batch);
+        assert_trace_eq(&spilled, &reference);
+
+        let mut fuel = 1000;
+        spilled.exert(&mut fuel);
+        let mut fuel = 1000;
+        reference.exert(&mut fuel);
+        assert_trace_eq(&spilled, &reference);
+    }
+
+    assert!(spilled.size_of().total_bytes() < reference.size_of().total_bytes());
+
+    spilled.recede_to(&10);
+    reference.recede_to(&10);
+    assert_trace_eq(&spilled, &reference);
+
+    let spilled = spilled.consolidate().unwrap();
+    let reference = reference.consolidate().unwrap();
+    assert_batch_eq(&spilled, &reference);
+}
+
+#[test]
+fn spilled_spine_truncation() {
+    let dir = SpillDir::new();
+    let mut spilled = SpillingSpine::<TestBatch>::with_budget(0, &dir.0, None);
+    let mut reference = Spine::<TestBatch>::new(None);
+
+    for step in 0..20 {
+        if step == 5 {
+            spilled.truncate_keys_below(&100);
+            reference.truncate_keys_below(&100);
+        }
+        if step == 10 {
+            spilled.truncate_values_below(&5);
+            reference.truncate_values_below(&5);
+        }
+
+        let batch = test_batch(step as u32, step);
+        spilled.insert(batch.clone());
+        reference.insert(batch);
+
+        let mut fuel = 1000;
+        spilled.exert(&mut fuel);
+        let mut fuel = 1000;
+        reference.exert(&mut fuel);
+        assert_trace_eq(&spilled, &reference);
+    }
+}
+
+#[test]
+fn unlimited_budget_stays_in_memory() {
+    let dir = SpillDir::new();
+    let mut spilled = SpillingSpine::<TestBatch>::with_budget(usize::MAX, &dir.0, None);
+
+    for step in 0..10 {
+        spilled.insert(test_batch(step as u32, step));
+    }
+
+    assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+}
+
+#[test]
+fn integrate_trace_spilled() {
+    let dir = SpillDir::new();
+    let spill_dir = dir.0.clone();
+
+    let circuit = RootCircuit::build(move |circuit| {
+        let mut step = 0u64;
+        let input = circuit.add_source(Generator::new(move || {
+            step += 1;
+            OrdIndexedZSet::<u64, u64, i64>::from_tuples(
+                (),
+                (0..200)
+                    .map(|i| (((i * 3 + step) % 50, (i + step) % 7), 1))
+                    .collect(),
+            )
+        }));
+
+        let spilled = input.integrate_trace_spilled(0, spill_dir);
+        let reference = input.integrate_trace();
+        spilled.apply2(&reference, |spilled, reference| {
+            assert_trace_eq(spilled, reference)
+        });
+    })
+    .unwrap()
+    .0;
+
+    for _ in 0..10 {
+        circuit.step().unwrap();
+    }
+}
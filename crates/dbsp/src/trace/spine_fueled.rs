@@ -80,20 +80,25 @@
 //! layers by continuing to provide fuel as updates arrive.
 
 use crate::{
-    circuit::Activator,
+    circuit::{
+        metrics::{MetricLabels, TRACE_MERGES},
+        Activator,
+    },
     time::{Antichain, AntichainRef, Timestamp},
     trace::{
         cursor::{Cursor, CursorList},
-        Batch, BatchReader, Consumer, Merger, Trace, ValueConsumer,
+        layers::SearchHint,
+        Batch, BatchReader, Batcher, Consumer, Merger, Trace, ValueConsumer,
     },
     NumEntries,
 };
 use size_of::SizeOf;
 use std::{
     cmp::max,
+    collections::BTreeMap,
     fmt::{self, Debug, Display, Write},
     marker::PhantomData,
-    mem::replace,
+    mem::{replace, take},
 };
 use textwrap::indent;
 
@@ -275,8 +280,9 @@ where
         s
     }
 
-    #[allow(dead_code)]
-    fn map_batches<F>(&self, mut map: F)
+    /// Applies `map` to each batch in the spine, from the oldest to the
+    /// newest, including both inputs of merges that are in progress.
+    pub fn map_batches<F>(&self, mut map: F)
     where
         F: FnMut(&B),
     {
@@ -412,6 +418,10 @@ where
         self.cursor.seek_key(key);
     }
 
+    fn seek_key_with_hint(&mut self, key: &B::Key, hint: SearchHint) {
+        self.cursor.seek_key_with_hint(key, hint);
+    }
+
     fn last_key(&mut self) -> Option<&B::Key> {
         self.cursor.last_key()
     }
@@ -438,6 +448,14 @@ where
     fn rewind_vals(&mut self) {
         self.cursor.rewind_vals();
     }
+
+    fn mark_offsets(&self) -> Option<Vec<usize>> {
+        self.cursor.mark_offsets()
+    }
+
+    fn rewind_to_offsets(&mut self, offsets: &[usize]) {
+        self.cursor.rewind_to_offsets(offsets);
+    }
 }
 
 pub struct SpineConsumer<B>
@@ -695,6 +713,61 @@ where
         }
     }
 
+    /// Rewrites the contents of the trace into a trace with a different batch
+    /// type, e.g., after adding a column with a default value to the values
+    /// stored in the trace.
+    ///
+    /// Applies `migrate` to each `(key, value, weight)` tuple in the trace.
+    /// The resulting tuple is inserted in the new trace with the same
+    /// timestamp as the original tuple.  Tuples mapped to the same key and
+    /// value are consolidated.
+    ///
+    /// The trace is migrated one batch at a time, so at most one batch worth
+    /// of migrated tuples is buffered in memory, in addition to the contents
+    /// of both traces.
+    ///
+    /// Key and value bounds set by `truncate_keys_below` and
+    /// `truncate_values_below` are not carried over to the new trace.
+    pub fn migrate_into<B2, F>(&self, mut migrate: F) -> Spine<B2>
+    where
+        B2: Batch<Time = B::Time>,
+        B2::Key: Ord,
+        B2::Val: Ord,
+        F: FnMut(B::Key, B::Val, B::R) -> (B2::Key, B2::Val, B2::R),
+    {
+        let mut result = Spine::new(None);
+        let mut updates: BTreeMap<B::Time, Vec<(B2::Item, B2::R)>> = BTreeMap::new();
+
+        self.map_batches(|batch| {
+            let mut cursor = batch.cursor();
+            while cursor.key_valid() {
+                while cursor.val_valid() {
+                    let key = cursor.key().clone();
+                    let val = cursor.val().clone();
+                    cursor.map_times(|time, weight| {
+                        let (key, val, weight) = migrate(key.clone(), val.clone(), weight.clone());
+                        updates
+                            .entry(time.clone())
+                            .or_default()
+                            .push((B2::item_from(key, val), weight));
+                    });
+                    cursor.step_val();
+                }
+                cursor.step_key();
+            }
+
+            // Batchers assign the same timestamp to all tuples, so we build a
+            // separate batch for each timestamp in the original batch.
+            for (time, mut tuples) in take(&mut updates) {
+                let mut batcher = B2::Batcher::new_batcher(time);
+                batcher.push_batch(&mut tuples);
+                result.insert(batcher.seal());
+            }
+        });
+
+        result
+    }
+
     /// Introduces a batch at an indicated level.
     ///
     /// The level indication is often related to the size of the batch, but
@@ -1086,6 +1159,13 @@ where
                 // Leonid: we do not require batch bounds to grow monotonically.
                 //assert!(batch1.upper() == batch2.lower());
 
+                metric_counter!(TRACE_MERGES, 1, MetricLabels::worker());
+                span_event!(
+                    tracing::Level::TRACE,
+                    len1 = batch1.len(),
+                    len2 = batch2.len(),
+                    "merge started"
+                );
                 let begin_merge = <B as Batch>::begin_merge(&batch1, &batch2);
                 MergeVariant::InProgress(batch1, batch2, begin_merge)
             }
@@ -1183,13 +1263,14 @@ mod test {
     use crate::{
         trace::{
             ord::{OrdKeyBatch, OrdValBatch},
-            test_batch::{assert_batch_eq, assert_trace_eq, TestBatch},
+            test_batch::{assert_batch_eq, assert_trace_eq, batch_to_tuples, TestBatch},
             Batch, BatchReader, Spine, Trace,
         },
         OrdIndexedZSet, OrdZSet,
     };
     use proptest::{collection::vec, prelude::*};
     use size_of::SizeOf;
+    use std::collections::BTreeMap;
 
     fn kr_batches(
         max_key: i32,
@@ -1310,7 +1391,7 @@ mod test {
                 ref_trace.truncate_keys_below(&key_bound);
 
                 trace.truncate_values_below(&val_bound);
-                ref_trace.truncate_values_below(&val_bound);
+                Trace::truncate_values_below(&mut ref_trace, &val_bound);
 
                 assert_trace_eq(&trace, &ref_trace);
             }
@@ -1359,10 +1440,42 @@ mod test {
                 ref_trace.truncate_keys_below(&key_bound);
 
                 trace.truncate_values_below(&val_bound);
-                ref_trace.truncate_values_below(&val_bound);
+                Trace::truncate_values_below(&mut ref_trace, &val_bound);
 
                 assert_trace_eq(&trace, &ref_trace);
             }
         }
+
+        #[test]
+        fn test_migrate_trace(batches in kvr_batches(100, 5, 2, 300, 20)) {
+            let mut trace: Spine<OrdValBatch<i32, i32, u32, i32>> = Spine::new(None);
+
+            for (time, (tuples, _key_bound, _val_bound)) in batches.into_iter().enumerate() {
+                let batch = OrdValBatch::from_tuples(time as u32, tuples);
+                trace.insert(batch);
+            }
+
+            // Add a column with a default value.
+            let migrated: Spine<OrdValBatch<i32, (i32, i32), u32, i32>> =
+                trace.migrate_into(|k, v, r| (k, (v, 0), r));
+
+            let expected: Vec<_> = batch_to_tuples(&trace)
+                .into_iter()
+                .map(|((k, v, t), r)| ((k, (v, 0), t), r))
+                .collect();
+            assert_eq!(batch_to_tuples(&migrated), expected);
+
+            // Drop the value column, consolidating weights of tuples that
+            // only differ in their values.
+            let migrated: Spine<OrdKeyBatch<i32, u32, i32>> =
+                trace.migrate_into(|k, _v, r| (k, (), r));
+
+            let mut expected = BTreeMap::new();
+            for ((k, _v, t), r) in batch_to_tuples(&trace) {
+                *expected.entry((k, (), t)).or_insert(0) += r;
+            }
+            expected.retain(|_, r| *r != 0);
+            assert_eq!(batch_to_tuples(&migrated), expected.into_iter().collect::<Vec<_>>());
+        }
     }
 }
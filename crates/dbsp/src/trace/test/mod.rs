@@ -0,0 +1,874 @@
+//! Model-based fuzzing of batch and trace implementations.
+//!
+//! A test [`Script`] is a sequence of [`Op`]s: inserting batches, truncating
+//! keys and values, receding timestamps, exerting effort, and moving a cursor
+//! around.  The harness runs the same script against the implementation under
+//! test and against [`TestBatch`], a `BTreeMap`-based reference model, and
+//! panics as soon as the two disagree.
+//!
+//! Keys, values, timestamps and weights are generated from small integer seeds
+//! (see [`FuzzData`] and [`FuzzTime`]), so that the same script runs against
+//! any batch type and has a compact textual form.  When `proptest` finds a
+//! failing script, it prints it in this form; adding the line to
+//! `regressions.txt` replays it in the `regressions` unit test of every
+//! registered type.
+//!
+//! New batch and trace types are registered with one line in the
+//! `fuzz_batches!` and `fuzz_traces!` invocations at the end of this file.
+
+use crate::{
+    algebra::HasZero,
+    time::Timestamp,
+    trace::{
+        ord::{OrdIndexedZSet, OrdKeyBatch, OrdValBatch, OrdZSet, OrdZSetArena},
+        spine_fueled::Spine,
+        test_batch::{assert_batch_eq, assert_trace_eq, batch_to_tuples, TestBatch},
+        Batch, BatchReader, Cursor, Merger, Trace,
+    },
+    DBData, DBTimestamp, DBWeight,
+};
+use proptest::{collection::vec, prelude::*};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Display},
+    str::FromStr,
+};
+
+#[cfg(feature = "persistence")]
+use crate::trace::{
+    persistent::PersistentTrace,
+    spill::{SpillableBatch, SpillingSpine},
+};
+#[cfg(feature = "persistence")]
+use std::env;
+
+/// Keys and values are generated from seeds in `0..DOMAIN`.
+///
+/// The domain is kept small so that batches collide on keys and values and
+/// weights of different batches cancel out.
+const DOMAIN: u8 = 16;
+
+/// Timestamps are generated from seeds in `0..TIMES`.
+const TIMES: u8 = 8;
+
+/// Maximal number of operations in a script.
+const MAX_OPS: usize = 32;
+
+/// Maximal number of tuples in an inserted batch.
+const MAX_TUPLES: usize = 24;
+
+/// Maximal number of steps in a single cursor walk.
+const MAX_CURSOR_OPS: usize = 16;
+
+/// Key and value types that can be generated from a seed.
+///
+/// `from_seed` must preserve the order of seeds (except for `()`, which only
+/// has one value), so that seeks and truncations in a script mean the same
+/// thing for every type.
+trait FuzzData: DBData {
+    fn from_seed(seed: u8) -> Self;
+}
+
+impl FuzzData for () {
+    fn from_seed(_seed: u8) -> Self {}
+}
+
+macro_rules! fuzz_data_from_int {
+    ($($type:ty),*) => {
+        $(
+            impl FuzzData for $type {
+                fn from_seed(seed: u8) -> Self {
+                    Self::from(seed)
+                }
+            }
+        )*
+    };
+}
+
+fuzz_data_from_int!(u32, u64, i32, i64);
+
+impl FuzzData for String {
+    fn from_seed(seed: u8) -> Self {
+        format!("{:03}", seed)
+    }
+}
+
+/// Timestamp types that can be generated from a seed.
+trait FuzzTime: DBTimestamp {
+    fn from_seed(seed: u8) -> Self;
+
+    /// Returns the weight of the current value for timestamp types that
+    /// support [`Cursor::weight`] and `None` otherwise.
+    fn weight<'s, K, V, R, C>(cursor: &mut C) -> Option<R>
+    where
+        C: Cursor<'s, K, V, Self, R>;
+}
+
+impl FuzzTime for () {
+    fn from_seed(_seed: u8) -> Self {}
+
+    fn weight<'s, K, V, R, C>(cursor: &mut C) -> Option<R>
+    where
+        C: Cursor<'s, K, V, Self, R>,
+    {
+        Some(cursor.weight())
+    }
+}
+
+impl FuzzTime for u32 {
+    fn from_seed(seed: u8) -> Self {
+        Self::from(seed)
+    }
+
+    fn weight<'s, K, V, R, C>(_cursor: &mut C) -> Option<R>
+    where
+        C: Cursor<'s, K, V, Self, R>,
+    {
+        None
+    }
+}
+
+/// An operation on a batch or trace, with keys, values and timestamps
+/// represented by their seeds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Op {
+    /// Inserts a batch of `(key, val, weight)` tuples with timestamp `time`.
+    ///
+    /// The batch harness merges the new batch into the batch under test, the
+    /// trace harness inserts it into the trace.
+    Insert {
+        time: u8,
+        tuples: Vec<(u8, u8, i8)>,
+    },
+    /// Exerts the given amount of effort on a trace.  In the batch harness,
+    /// sets the amount of fuel given to each step of subsequent merges.
+    Exert(u16),
+    TruncateKeys(u8),
+    TruncateValues(u8),
+    RecedeTo(u8),
+    /// Opens a cursor and walks it, comparing every position with the model.
+    Cursor(Vec<CursorOp>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum CursorOp {
+    StepKey,
+    StepVal,
+    SeekKey(u8),
+    SeekVal(u8),
+    RewindKeys,
+    RewindVals,
+    Mark,
+    RewindToMark,
+}
+
+/// A sequence of operations.
+///
+/// Both `Debug` and `Display` print the script as `;`-separated operations,
+/// which is also the format of the regression corpus, so that failing
+/// scripts reported by `proptest` can be copied to `regressions.txt` as is.
+#[derive(Clone, PartialEq, Eq)]
+struct Script(Vec<Op>);
+
+impl Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Insert { time, tuples } => {
+                write!(f, "insert {}", time)?;
+                for (key, val, weight) in tuples.iter() {
+                    write!(f, " {}:{}:{}", key, val, weight)?;
+                }
+                Ok(())
+            }
+            Self::Exert(effort) => write!(f, "exert {}", effort),
+            Self::TruncateKeys(bound) => write!(f, "truncate_keys {}", bound),
+            Self::TruncateValues(bound) => write!(f, "truncate_values {}", bound),
+            Self::RecedeTo(frontier) => write!(f, "recede_to {}", frontier),
+            Self::Cursor(ops) => {
+                write!(f, "cursor")?;
+                for op in ops.iter() {
+                    write!(f, " {}", op)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for CursorOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::StepKey => write!(f, "step_key"),
+            Self::StepVal => write!(f, "step_val"),
+            Self::SeekKey(key) => write!(f, "seek_key={}", key),
+            Self::SeekVal(val) => write!(f, "seek_val={}", val),
+            Self::RewindKeys => write!(f, "rewind_keys"),
+            Self::RewindVals => write!(f, "rewind_vals"),
+            Self::Mark => write!(f, "mark"),
+            Self::RewindToMark => write!(f, "rewind_to_mark"),
+        }
+    }
+}
+
+impl Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, op) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+fn parse_number<N>(s: &str) -> Result<N, String>
+where
+    N: FromStr,
+{
+    s.parse().map_err(|_| format!("invalid number '{}'", s))
+}
+
+/// Parses a `key:val:weight` tuple.
+fn parse_tuple(s: &str) -> Result<(u8, u8, i8), String> {
+    match s.split(':').collect::<Vec<_>>().as_slice() {
+        [key, val, weight] => Ok((
+            parse_number(key)?,
+            parse_number(val)?,
+            parse_number(weight)?,
+        )),
+        _ => Err(format!("invalid tuple '{}'", s)),
+    }
+}
+
+impl FromStr for Op {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().ok_or_else(|| "empty operation".to_string())?;
+        let args: Vec<&str> = words.collect();
+        let single_arg = || match args.as_slice() {
+            [arg] => Ok(*arg),
+            _ => Err(format!("'{}' expects exactly one argument: '{}'", name, s)),
+        };
+
+        match name {
+            "insert" => {
+                let (time, tuples) = args
+                    .split_first()
+                    .ok_or_else(|| format!("'insert' expects a timestamp: '{}'", s))?;
+                let tuples = tuples
+                    .iter()
+                    .map(|tuple| parse_tuple(tuple))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Self::Insert {
+                    time: parse_number(time)?,
+                    tuples,
+                })
+            }
+            "exert" => Ok(Self::Exert(parse_number(single_arg()?)?)),
+            "truncate_keys" => Ok(Self::TruncateKeys(parse_number(single_arg()?)?)),
+            "truncate_values" => Ok(Self::TruncateValues(parse_number(single_arg()?)?)),
+            "recede_to" => Ok(Self::RecedeTo(parse_number(single_arg()?)?)),
+            "cursor" => Ok(Self::Cursor(
+                args.iter().map(|op| op.parse()).collect::<Result<_, _>>()?,
+            )),
+            _ => Err(format!("unknown operation '{}'", name)),
+        }
+    }
+}
+
+impl FromStr for CursorOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("seek_key", key)) => Ok(Self::SeekKey(parse_number(key)?)),
+            Some(("seek_val", val)) => Ok(Self::SeekVal(parse_number(val)?)),
+            Some(_) => Err(format!("unknown cursor operation '{}'", s)),
+            None => match s {
+                "step_key" => Ok(Self::StepKey),
+                "step_val" => Ok(Self::StepVal),
+                "rewind_keys" => Ok(Self::RewindKeys),
+                "rewind_vals" => Ok(Self::RewindVals),
+                "mark" => Ok(Self::Mark),
+                "rewind_to_mark" => Ok(Self::RewindToMark),
+                _ => Err(format!("unknown cursor operation '{}'", s)),
+            },
+        }
+    }
+}
+
+impl FromStr for Script {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(';')
+            .map(str::trim)
+            .filter(|op| !op.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// Scripts from the regression corpus.
+fn corpus() -> impl Iterator<Item = Script> {
+    include_str!("regressions.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse()
+                .unwrap_or_else(|error| panic!("invalid regression '{}': {}", line, error))
+        })
+}
+
+fn cursor_op() -> impl Strategy<Value = CursorOp> {
+    prop_oneof![
+        Just(CursorOp::StepKey),
+        Just(CursorOp::StepVal),
+        (0..DOMAIN).prop_map(CursorOp::SeekKey),
+        (0..DOMAIN).prop_map(CursorOp::SeekVal),
+        Just(CursorOp::RewindKeys),
+        Just(CursorOp::RewindVals),
+        Just(CursorOp::Mark),
+        Just(CursorOp::RewindToMark),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (0..TIMES, vec((0..DOMAIN, 0..DOMAIN, -2i8..=2), 0..MAX_TUPLES))
+            .prop_map(|(time, tuples)| Op::Insert { time, tuples }),
+        1 => (1u16..1024).prop_map(Op::Exert),
+        1 => (0..=DOMAIN).prop_map(Op::TruncateKeys),
+        1 => (0..=DOMAIN).prop_map(Op::TruncateValues),
+        1 => (0..TIMES).prop_map(Op::RecedeTo),
+        2 => vec(cursor_op(), 0..MAX_CURSOR_OPS).prop_map(Op::Cursor),
+    ]
+}
+
+fn scripts() -> impl Strategy<Value = Script> {
+    vec(op(), 0..MAX_OPS).prop_map(Script)
+}
+
+type Model<B> = TestBatch<
+    <B as BatchReader>::Key,
+    <B as BatchReader>::Val,
+    <B as BatchReader>::Time,
+    <B as BatchReader>::R,
+>;
+
+/// Builds a batch under test and the equivalent model batch from seeds.
+fn batches<B>(time: u8, tuples: &[(u8, u8, i8)]) -> (B, Model<B>)
+where
+    B: Batch,
+    B::Key: FuzzData,
+    B::Val: FuzzData,
+    B::Time: FuzzTime,
+    B::R: From<i8>,
+{
+    let time = B::Time::from_seed(time);
+    let tuples: Vec<_> = tuples
+        .iter()
+        .map(|&(key, val, weight)| {
+            (
+                (B::Key::from_seed(key), B::Val::from_seed(val)),
+                B::R::from(weight),
+            )
+        })
+        .collect();
+
+    let batch = B::from_tuples(
+        time.clone(),
+        tuples
+            .iter()
+            .map(|((key, val), weight)| (B::item_from(key.clone(), val.clone()), weight.clone()))
+            .collect(),
+    );
+
+    (batch, Model::<B>::from_tuples(time, tuples))
+}
+
+/// Merges two batches, giving the merger `fuel` units of fuel at a time.
+fn merge_fueled<B>(batch1: &B, batch2: &B, fuel: isize) -> B
+where
+    B: Batch,
+{
+    let mut merger = B::Merger::new_merger(batch1, batch2);
+
+    loop {
+        let mut step = fuel;
+        merger.work(batch1, batch2, &None, &mut step);
+        if step > 0 {
+            return merger.done();
+        }
+    }
+}
+
+/// Runs `script` against batch type `B` and the model.
+fn check_batch<B>(script: &Script)
+where
+    B: Batch,
+    B::Key: FuzzData,
+    B::Val: FuzzData,
+    B::Time: FuzzTime,
+    B::R: From<i8>,
+{
+    let mut batch = B::empty(B::Time::minimum());
+    let mut model = <Model<B> as Trace>::new(None);
+    let mut fuel = isize::MAX;
+
+    for op in script.0.iter() {
+        match op {
+            Op::Insert { time, tuples } => {
+                let (new_batch, new_model) = batches::<B>(*time, tuples);
+                batch = merge_fueled(&batch, &new_batch, fuel);
+                model = model.merge(&new_model);
+            }
+            Op::Exert(effort) => fuel = (*effort as isize).max(1),
+            Op::TruncateKeys(bound) => {
+                let bound = B::Key::from_seed(*bound);
+                batch.truncate_keys_below(&bound);
+                model.truncate_keys_below(&bound);
+            }
+            Op::TruncateValues(bound) => {
+                let bound = B::Val::from_seed(*bound);
+                Batch::truncate_values_below(&mut batch, &bound);
+                Batch::truncate_values_below(&mut model, &bound);
+            }
+            Op::RecedeTo(frontier) => {
+                let frontier = B::Time::from_seed(*frontier);
+                Batch::recede_to(&mut batch, &frontier);
+                Batch::recede_to(&mut model, &frontier);
+            }
+            Op::Cursor(ops) => check_cursor(&batch, &model, None, None, ops),
+        }
+
+        assert_batch_eq(&batch, &model);
+    }
+}
+
+/// Runs `script` against `trace` and the model.
+fn check_trace<T>(mut trace: T, script: &Script)
+where
+    T: Trace,
+    T::Key: FuzzData,
+    T::Val: FuzzData,
+    T::Time: FuzzTime,
+    T::R: From<i8>,
+{
+    let mut model = <Model<T> as Trace>::new(None);
+    // Traces are allowed to drop keys below the bound lazily, so cursors
+    // may still visit them.
+    let mut key_bound: Option<T::Key> = None;
+
+    for op in script.0.iter() {
+        match op {
+            Op::Insert { time, tuples } => {
+                let (batch, model_batch) = batches::<T::Batch>(*time, tuples);
+                trace.insert(batch);
+                model.insert(model_batch);
+            }
+            Op::Exert(effort) => {
+                let mut effort = *effort as isize;
+                trace.exert(&mut effort);
+            }
+            Op::TruncateKeys(bound) => {
+                let bound = T::Key::from_seed(*bound);
+                trace.truncate_keys_below(&bound);
+                model.truncate_keys_below(&bound);
+                key_bound = key_bound.max(Some(bound));
+            }
+            Op::TruncateValues(bound) => {
+                let bound = T::Val::from_seed(*bound);
+                trace.truncate_values_below(&bound);
+                Trace::truncate_values_below(&mut model, &bound);
+            }
+            Op::RecedeTo(frontier) => {
+                let frontier = T::Time::from_seed(*frontier);
+                trace.recede_to(&frontier);
+                Trace::recede_to(&mut model, &frontier);
+            }
+            Op::Cursor(ops) => check_cursor(
+                &trace,
+                &model,
+                key_bound.as_ref(),
+                trace.lower_value_bound().as_ref(),
+                ops,
+            ),
+        }
+
+        assert_trace_eq(&trace, &model);
+    }
+
+    // Consolidation preserves the contents of the trace.
+    let val_bound = trace.lower_value_bound().clone();
+    let mut tuples = trace
+        .consolidate()
+        .map(|batch| batch_to_tuples(&batch))
+        .unwrap_or_default();
+    tuples.retain(|((key, val, _time), _weight)| {
+        Some(key) >= key_bound.as_ref() && Some(val) >= val_bound.as_ref()
+    });
+    assert_eq!(tuples, batch_to_tuples(&model));
+}
+
+/// What a cursor points to: the current key, if any, and the current value
+/// with its consolidated `(time, weight)` pairs, if any.
+type Observation<K, V, T, R> = Option<(K, Option<(V, Vec<(T, R)>)>)>;
+
+/// Consolidated `(time, weight)` pairs of the current value.
+fn times<'s, C, K, V, T, R>(cursor: &mut C) -> Vec<(T, R)>
+where
+    C: Cursor<'s, K, V, T, R>,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    let mut times: BTreeMap<T, R> = BTreeMap::new();
+    cursor.map_times(|time, weight| {
+        times
+            .entry(time.clone())
+            .or_insert_with(HasZero::zero)
+            .add_assign_by_ref(weight)
+    });
+    times.retain(|_, weight| !weight.is_zero());
+
+    times.into_iter().collect()
+}
+
+/// Skips values that the model does not contain: values below `val_bound`,
+/// which traces are allowed to drop lazily, and values whose updates cancel
+/// out, which a trace may still store in different batches.
+fn skip_vals<'s, C, K, V, T, R>(cursor: &mut C, val_bound: Option<&V>)
+where
+    C: Cursor<'s, K, V, T, R>,
+    V: Ord,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    while cursor.val_valid() && (Some(cursor.val()) < val_bound || times(cursor).is_empty()) {
+        cursor.step_val();
+    }
+}
+
+/// Skips keys that the model does not contain, including keys with no
+/// values left after [`skip_vals`].
+fn skip_keys<'s, C, K, V, T, R>(cursor: &mut C, key_bound: Option<&K>, val_bound: Option<&V>)
+where
+    C: Cursor<'s, K, V, T, R>,
+    K: Ord,
+    V: Ord,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    while cursor.key_valid() {
+        if Some(cursor.key()) >= key_bound {
+            skip_vals(cursor, val_bound);
+            if cursor.val_valid() {
+                break;
+            }
+        }
+        cursor.step_key();
+    }
+}
+
+fn observe<'s, C, K, V, T, R>(cursor: &mut C) -> Observation<K, V, T, R>
+where
+    C: Cursor<'s, K, V, T, R>,
+    K: Clone,
+    V: Clone,
+    T: FuzzTime,
+    R: DBWeight,
+{
+    if !cursor.key_valid() {
+        return None;
+    }
+
+    let val = if cursor.val_valid() {
+        let times = times(cursor);
+        if let Some(weight) = T::weight(cursor) {
+            let mut sum = R::zero();
+            for (_time, w) in times.iter() {
+                sum.add_assign_by_ref(w);
+            }
+            assert_eq!(weight, sum, "weight() disagrees with map_times()");
+        }
+        Some((cursor.val().clone(), times))
+    } else {
+        None
+    };
+
+    Some((cursor.key().clone(), val))
+}
+
+/// Cursor over the contents of the model.
+struct ModelCursor<K, V, T, R> {
+    keys: Vec<(K, Vec<(V, Vec<(T, R)>)>)>,
+    key: usize,
+    val: usize,
+}
+
+impl<K, V, T, R> ModelCursor<K, V, T, R>
+where
+    K: DBData,
+    V: DBData,
+    T: DBTimestamp,
+    R: DBWeight,
+{
+    fn new(model: &TestBatch<K, V, T, R>, val_bound: Option<&V>) -> Self {
+        let mut keys: Vec<(K, Vec<(V, Vec<(T, R)>)>)> = Vec::new();
+
+        for ((key, val, time), weight) in batch_to_tuples(model) {
+            if Some(&val) < val_bound {
+                continue;
+            }
+            if keys.last().map_or(true, |(k, _)| k != &key) {
+                keys.push((key, Vec::new()));
+            }
+            let vals = &mut keys.last_mut().unwrap().1;
+            if vals.last().map_or(true, |(v, _)| v != &val) {
+                vals.push((val, Vec::new()));
+            }
+            vals.last_mut().unwrap().1.push((time, weight));
+        }
+
+        Self {
+            keys,
+            key: 0,
+            val: 0,
+        }
+    }
+
+    fn key_valid(&self) -> bool {
+        self.key < self.keys.len()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.key_valid() && self.val < self.keys[self.key].1.len()
+    }
+
+    fn key(&self) -> &K {
+        &self.keys[self.key].0
+    }
+
+    fn val(&self) -> &V {
+        &self.keys[self.key].1[self.val].0
+    }
+
+    fn observe(&self) -> Observation<K, V, T, R> {
+        if !self.key_valid() {
+            return None;
+        }
+
+        let (key, vals) = &self.keys[self.key];
+        let val = vals.get(self.val).cloned();
+
+        Some((key.clone(), val))
+    }
+
+    fn step_key(&mut self) {
+        self.key += 1;
+        self.val = 0;
+    }
+
+    fn seek_key(&mut self, key: &K) {
+        while self.key_valid() && self.key() < key {
+            self.key += 1;
+        }
+        self.val = 0;
+    }
+
+    fn step_val(&mut self) {
+        self.val += 1;
+    }
+
+    fn seek_val(&mut self, val: &V) {
+        while self.val_valid() && self.val() < val {
+            self.val += 1;
+        }
+    }
+}
+
+/// Walks a cursor over `batch` according to `ops` and checks that it visits
+/// the same tuples as a cursor over `model`.
+///
+/// Seeks are only issued in the forward direction and steps only from valid
+/// positions, as required by the [`Cursor`] API.
+fn check_cursor<B>(
+    batch: &B,
+    model: &Model<B>,
+    key_bound: Option<&B::Key>,
+    val_bound: Option<&B::Val>,
+    ops: &[CursorOp],
+) where
+    B: BatchReader,
+    B::Key: FuzzData,
+    B::Val: FuzzData,
+    B::Time: FuzzTime,
+{
+    let mut model_cursor = ModelCursor::new(model, val_bound);
+    let mut cursor = batch.cursor();
+    let mut saved = None;
+
+    skip_keys(&mut cursor, key_bound, val_bound);
+    assert_eq!(observe(&mut cursor), model_cursor.observe(), "cursor start");
+
+    for (i, op) in ops.iter().enumerate() {
+        match op {
+            CursorOp::StepKey => {
+                if model_cursor.key_valid() {
+                    model_cursor.step_key();
+                    cursor.step_key();
+                    skip_keys(&mut cursor, key_bound, val_bound);
+                }
+            }
+            CursorOp::StepVal => {
+                if model_cursor.val_valid() {
+                    model_cursor.step_val();
+                    cursor.step_val();
+                    skip_vals(&mut cursor, val_bound);
+                }
+            }
+            CursorOp::SeekKey(key) => {
+                let key = B::Key::from_seed(*key);
+                if model_cursor.key_valid() && model_cursor.key() < &key {
+                    model_cursor.seek_key(&key);
+                    cursor.seek_key(&key);
+                    skip_keys(&mut cursor, key_bound, val_bound);
+                }
+            }
+            CursorOp::SeekVal(val) => {
+                let val = B::Val::from_seed(*val);
+                if model_cursor.val_valid() && model_cursor.val() < &val {
+                    model_cursor.seek_val(&val);
+                    cursor.seek_val(&val);
+                    skip_vals(&mut cursor, val_bound);
+                }
+            }
+            CursorOp::RewindKeys => {
+                model_cursor.key = 0;
+                model_cursor.val = 0;
+                cursor.rewind_keys();
+                skip_keys(&mut cursor, key_bound, val_bound);
+            }
+            CursorOp::RewindVals => {
+                if model_cursor.key_valid() {
+                    model_cursor.val = 0;
+                    cursor.rewind_vals();
+                    skip_vals(&mut cursor, val_bound);
+                }
+            }
+            CursorOp::Mark => {
+                saved = Some((cursor.mark(), model_cursor.key, model_cursor.val));
+            }
+            CursorOp::RewindToMark => {
+                if let Some((mark, key, val)) = &saved {
+                    model_cursor.key = *key;
+                    model_cursor.val = *val;
+                    cursor.rewind_to_mark(mark);
+                }
+            }
+        }
+
+        assert_eq!(
+            observe(&mut cursor),
+            model_cursor.observe(),
+            "cursor step {} ({})",
+            i,
+            op
+        );
+    }
+}
+
+/// Registers batch types with the batch harness.
+macro_rules! fuzz_batches {
+    ($($(#[$meta:meta])* $name:ident: $batch:ty;)*) => {
+        $(
+            $(#[$meta])*
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn regressions() {
+                    for script in corpus() {
+                        check_batch::<$batch>(&script);
+                    }
+                }
+
+                proptest! {
+                    #[test]
+                    fn fuzz(script in scripts()) {
+                        check_batch::<$batch>(&script);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+/// Registers trace types with the trace harness, along with an expression
+/// that creates an empty trace.
+macro_rules! fuzz_traces {
+    ($($(#[$meta:meta])* $name:ident: $trace:ty = $new:expr;)*) => {
+        $(
+            $(#[$meta])*
+            mod $name {
+                use super::*;
+
+                fn new_trace() -> $trace {
+                    $new
+                }
+
+                #[test]
+                fn regressions() {
+                    for script in corpus() {
+                        check_trace(new_trace(), &script);
+                    }
+                }
+
+                proptest! {
+                    #[test]
+                    fn fuzz(script in scripts()) {
+                        check_trace(new_trace(), &script);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+fuzz_batches! {
+    zset: OrdZSet<u32, i32>;
+    indexed_zset: OrdIndexedZSet<u32, u32, i32>;
+    key_batch: OrdKeyBatch<u32, u32, i32>;
+    val_batch: OrdValBatch<u32, u32, u32, i32>;
+    zset_arena: OrdZSetArena<i32>;
+    #[cfg(feature = "persistence")]
+    spillable_val_batch: SpillableBatch<OrdValBatch<u32, u32, u32, i32>>;
+}
+
+fuzz_traces! {
+    zset_spine: Spine<OrdZSet<u32, i32>> = Spine::new(None);
+    indexed_zset_spine: Spine<OrdIndexedZSet<u32, u32, i32>> = Spine::new(None);
+    key_batch_spine: Spine<OrdKeyBatch<u32, u32, i32>> = Spine::new(None);
+    val_batch_spine: Spine<OrdValBatch<u32, u32, u32, i32>> = Spine::new(None);
+    zset_arena_spine: Spine<OrdZSetArena<i32>> = Spine::new(None);
+    #[cfg(feature = "persistence")]
+    zset_persistent_trace: PersistentTrace<OrdZSet<u32, i32>> = PersistentTrace::new(None);
+    #[cfg(feature = "persistence")]
+    indexed_zset_persistent_trace: PersistentTrace<OrdIndexedZSet<u32, u32, i32>> =
+        PersistentTrace::new(None);
+    #[cfg(feature = "persistence")]
+    val_batch_spilling_spine: SpillingSpine<OrdValBatch<u32, u32, u32, i32>> =
+        SpillingSpine::with_budget(0, env::temp_dir(), None);
+}
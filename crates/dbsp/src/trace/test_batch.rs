@@ -536,6 +536,10 @@ where
 
         self.data = Self::from_data(&data).data;
     }
+
+    fn truncate_values_below(&mut self, lower_bound: &Self::Val) {
+        self.data.retain(|(_k, v, _t), _r| v >= lower_bound);
+    }
 }
 
 impl<K, V, T, R> Trace for TestBatch<K, V, T, R>
@@ -574,7 +578,7 @@ where
             batch.truncate_keys_below(bound);
         }
         if let Some(bound) = &self.lower_val_bound {
-            batch.truncate_values_below(bound);
+            Batch::truncate_values_below(&mut batch, bound);
         }
 
         self.data = self.merge(&batch).data;
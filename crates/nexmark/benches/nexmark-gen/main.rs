@@ -40,7 +40,7 @@ macro_rules! with_rng {
             let start = std::time::Instant::now();
             let mut prev_e = std::time::Instant::now();
             for _ in 0..count {
-                generator.next_event().unwrap();
+                generator.next_event()?;
                 let end_e = std::time::Instant::now();
                 hist.add_value((end_e - prev_e).as_nanos() as u64);
                 prev_e = end_e;
@@ -85,11 +85,36 @@ macro_rules! just_rng {
     }};
 }
 
+macro_rules! just_strings {
+    ($method:ident($($arg:expr),*)) => {{
+        let count = 10_000_000;
+        let mut generator = NexmarkGenerator::new(Config::default(), SmallRng::from_entropy(), 0);
+
+        println!("(just the strings) {}", stringify!($method($($arg),*)));
+
+        println!("throughput (hz) every {count} samples:");
+        for _ in 0..10 {
+            let start = std::time::Instant::now();
+            for _ in 0..count {
+                generator.$method($($arg),*);
+            }
+            let end = std::time::Instant::now();
+            print!("{:.2} ", count as f64 / (end - start).as_secs_f64());
+        }
+        println!();
+        println!();
+    }};
+}
+
 fn main() -> Result<()> {
     just_rng!("StepRng", StepRng::new(0, 1));
     just_rng!("SmallRng", SmallRng::from_entropy());
     just_rng!("ThreadRng", rand::thread_rng());
 
+    // Strings are the most expensive fields to generate.
+    just_strings!(next_string(100));
+    just_strings!(next_extra(32, 100));
+
     {
         let count = 1_000_000;
         let reps = 10;
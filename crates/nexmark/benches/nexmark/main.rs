@@ -15,6 +15,7 @@ use dbsp::{
 };
 use dbsp_nexmark::{
     config::{Config as NexmarkConfig, Query as NexmarkQuery},
+    generator::GeneratorError,
     model::Event,
     queries::{
         q0, q1, q12, q13, q13_side_input, q14, q15, q16, q17, q18, q19, q2, q20, q21, q22, q3, q4,
@@ -88,8 +89,27 @@ fn spawn_dbsp_consumer(
         .unwrap()
 }
 
-fn spawn_source_producer(
+// Creates the source of a benchmark run, explaining which setting to change
+// if the generators cannot generate the configured events.
+fn create_source(
     nexmark_config: NexmarkConfig,
+) -> Result<NexmarkSource<isize, OrdZSet<Event, isize>>> {
+    NexmarkSource::new(nexmark_config).map_err(|error| match error {
+        GeneratorError::NoEventTypes => {
+            anyhow!("{error}: at least one of the event proportions must be positive")
+        }
+        GeneratorError::NoPeople { .. } => {
+            anyhow!("{error}: increase --person-proportion to generate auctions and bids")
+        }
+        GeneratorError::NoAuctions { .. } => {
+            anyhow!("{error}: increase --auction-proportion to generate bids")
+        }
+    })
+}
+
+fn spawn_source_producer(
+    mut source: NexmarkSource<isize, OrdZSet<Event, isize>>,
+    batch_size: usize,
     mut input_handle: CollectionHandle<Event, isize>,
     step_do_rx: mpsc::Receiver<()>,
     step_done_tx: mpsc::SyncSender<StepCompleted>,
@@ -98,8 +118,6 @@ fn spawn_source_producer(
     thread::Builder::new()
         .name("benchmark producer".into())
         .spawn(move || {
-            let batch_size = nexmark_config.input_batch_size;
-            let mut source = NexmarkSource::<isize, OrdZSet<Event, isize>>::new(nexmark_config);
             let mut num_events: u64 = 0;
 
             // Start iterating by loading up the first batch of input ready for processing,
@@ -228,6 +246,7 @@ fn create_ascii_table() -> AsciiTable {
 
 fn main() -> Result<()> {
     let nexmark_config = NexmarkConfig::parse();
+    nexmark_config.validate()?;
     let max_events = nexmark_config.max_events;
     let queries_to_run = nexmark_config.query.clone();
     let cpu_cores = nexmark_config.cpu_cores;
@@ -23,7 +23,8 @@ macro_rules! run_queries {
             mpsc::sync_channel(1);
         let (source_exhausted_tx, source_exhausted_rx) = mpsc::sync_channel(1);
         spawn_source_producer(
-            $nexmark_config,
+            create_source($nexmark_config.clone())?,
+            $nexmark_config.input_batch_size,
             input_handle,
             source_step_rx,
             step_done_tx,
@@ -0,0 +1,297 @@
+//! Benchmark driver for Nexmark queries.
+//!
+//! Runs selected queries one after another against events from a
+//! [`NexmarkSource`], each in a fresh DBSP runtime, and reports per-query
+//! metrics in a machine-readable results file.
+
+use crate::{
+    config::{Config as NexmarkConfig, Query},
+    model::Event,
+    queries::{
+        q0, q1, q12, q13, q13_side_input, q14, q15, q16, q17, q18, q19, q2, q20, q21, q22, q3, q4,
+        q5, q6, q7, q8, q9,
+    },
+    NexmarkSource,
+};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use dbsp::{CollectionHandle, OrdZSet, RootCircuit, Runtime, Stream};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Format of the benchmark results file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum ResultsFormat {
+    /// One line per query, with a header line.
+    #[default]
+    Csv,
+    /// An array with one object per query.
+    Json,
+}
+
+/// Metrics collected for a single query.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    /// Name of the query, e.g., `q4`.
+    pub query: String,
+    /// Number of DBSP worker threads.
+    pub num_workers: usize,
+    /// Number of events fed to the query.
+    pub num_events: u64,
+    /// Number of circuit steps.
+    pub num_steps: usize,
+    /// Total time spent feeding events to the circuit and evaluating it.
+    pub elapsed_secs: f64,
+    /// Events processed per second.
+    pub throughput: f64,
+    /// Median latency of a circuit step, in microseconds.
+    pub step_latency_p50_us: u64,
+    /// 95th percentile latency of a circuit step, in microseconds.
+    pub step_latency_p95_us: u64,
+    /// 99th percentile latency of a circuit step, in microseconds.
+    pub step_latency_p99_us: u64,
+    /// Largest amount of memory used by traces and other operator state
+    /// after any step, in bytes.
+    pub peak_state_bytes: usize,
+}
+
+/// Runs the queries selected in `config` (all queries if none are selected)
+/// sequentially and returns their metrics.
+///
+/// Each query runs in its own runtime with `config.cpu_cores` workers, which
+/// is torn down before the next query starts.  Each query consumes
+/// `config.max_events` events in batches of `config.input_batch_size`.
+///
+/// If `config.results_file` is set, results are also written to that file in
+/// `config.results_format`.
+pub fn run_benchmark(config: &NexmarkConfig) -> Result<Vec<BenchmarkResult>> {
+    config.validate()?;
+
+    let queries = if config.query.is_empty() {
+        Query::value_variants().to_vec()
+    } else {
+        config.query.clone()
+    };
+
+    let results = queries
+        .into_iter()
+        .map(|query| run_query(query, config))
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(results_file) = &config.results_file {
+        write_results(results_file, config.results_format, &results)?;
+    }
+
+    Ok(results)
+}
+
+/// Write `results` to the file at `path`, replacing its contents.
+pub fn write_results<P: AsRef<Path>>(
+    path: P,
+    format: ResultsFormat,
+    results: &[BenchmarkResult],
+) -> Result<()> {
+    match format {
+        ResultsFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)?;
+            for result in results {
+                writer.serialize(result)?;
+            }
+            writer.flush()?;
+        }
+        ResultsFormat::Json => {
+            let writer = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(writer, results)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_query(query: Query, config: &NexmarkConfig) -> Result<BenchmarkResult> {
+    let num_workers = config.cpu_cores;
+    let (mut dbsp, mut input_handle) =
+        Runtime::init_circuit(num_workers, move |circuit| build_circuit(query, circuit))
+            .map_err(|error| anyhow!("failed to build circuit for {query:?}: {error}"))?;
+
+    let mut source = NexmarkSource::<isize, OrdZSet<Event, isize>>::new(config.clone())?;
+
+    let mut num_events = 0;
+    let mut step_latencies = Vec::new();
+    let mut peak_state_bytes = 0;
+    let mut elapsed = Duration::ZERO;
+
+    loop {
+        let start = Instant::now();
+
+        let mut batch: Vec<(Event, isize)> = (&mut source)
+            .take(config.input_batch_size)
+            .map(|event| (event, 1))
+            .collect();
+        if batch.is_empty() {
+            break;
+        }
+        num_events += batch.len() as u64;
+        input_handle.append(&mut batch);
+
+        let step_start = Instant::now();
+        dbsp.step()
+            .map_err(|error| anyhow!("failed to evaluate {query:?}: {error}"))?;
+        step_latencies.push(step_start.elapsed());
+        elapsed += start.elapsed();
+
+        // Measuring state size is expensive, so it is excluded from timings.
+        let used_bytes = dbsp
+            .used_bytes()
+            .map_err(|error| anyhow!("failed to measure state of {query:?}: {error}"))?;
+        peak_state_bytes = peak_state_bytes.max(used_bytes);
+    }
+
+    // Tear down the runtime together with all query state.
+    dbsp.kill()
+        .map_err(|error| anyhow!("failed to tear down the runtime of {query:?}: {error}"))?;
+
+    step_latencies.sort();
+
+    Ok(BenchmarkResult {
+        query: format!("{query:?}").to_lowercase(),
+        num_workers,
+        num_events,
+        num_steps: step_latencies.len(),
+        elapsed_secs: elapsed.as_secs_f64(),
+        throughput: num_events as f64 / elapsed.as_secs_f64(),
+        step_latency_p50_us: percentile(&step_latencies, 50),
+        step_latency_p95_us: percentile(&step_latencies, 95),
+        step_latency_p99_us: percentile(&step_latencies, 99),
+        peak_state_bytes,
+    })
+}
+
+/// Returns the `p`th percentile of `sorted_latencies` in microseconds, using
+/// the nearest-rank method.
+fn percentile(sorted_latencies: &[Duration], p: usize) -> u64 {
+    if sorted_latencies.is_empty() {
+        return 0;
+    }
+
+    let rank = (p * sorted_latencies.len() + 99) / 100;
+    sorted_latencies[rank.saturating_sub(1)].as_micros() as u64
+}
+
+// Builds the circuit for `query`, returning its input handle.
+fn build_circuit(query: Query, circuit: &mut RootCircuit) -> CollectionHandle<Event, isize> {
+    let (stream, input_handle) = circuit.add_input_zset::<Event, isize>();
+
+    match query {
+        Query::Q0 => sink(q0(stream)),
+        Query::Q1 => sink(q1(stream)),
+        Query::Q2 => sink(q2(stream)),
+        Query::Q3 => sink(q3(stream)),
+        Query::Q4 => sink(q4(stream)),
+        Query::Q5 => sink(q5(stream)),
+        Query::Q6 => sink(q6(stream)),
+        Query::Q7 => sink(q7(stream)),
+        Query::Q8 => sink(q8(stream)),
+        Query::Q9 => sink(q9(stream)),
+        Query::Q12 => sink(q12(stream)),
+        Query::Q13 => {
+            let (side_stream, mut side_input_handle) =
+                circuit.add_input_zset::<(usize, String, u64), isize>();
+            sink(q13(stream, side_stream));
+            side_input_handle.append(&mut q13_side_input());
+        }
+        Query::Q14 => sink(q14(stream)),
+        Query::Q15 => sink(q15(stream)),
+        Query::Q16 => sink(q16(stream)),
+        Query::Q17 => sink(q17(stream)),
+        Query::Q18 => sink(q18(stream)),
+        Query::Q19 => sink(q19(stream)),
+        Query::Q20 => sink(q20(stream)),
+        Query::Q21 => sink(q21(stream)),
+        Query::Q22 => sink(q22(stream)),
+    }
+
+    input_handle
+}
+
+// Consumes the output of a query, so that it is evaluated.
+fn sink<D>(output: Stream<RootCircuit, D>)
+where
+    D: Clone + 'static,
+{
+    output.inspect(|_| ());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::fs;
+
+    #[test]
+    fn test_percentile() {
+        let latencies: Vec<Duration> = (1..=200).map(Duration::from_micros).collect();
+
+        assert_eq!(percentile(&latencies, 50), 100);
+        assert_eq!(percentile(&latencies, 95), 190);
+        assert_eq!(percentile(&latencies, 99), 198);
+        assert_eq!(percentile(&latencies[..1], 99), 1);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[rstest]
+    #[case::csv(ResultsFormat::Csv)]
+    #[case::json(ResultsFormat::Json)]
+    fn test_run_benchmark(#[case] results_format: ResultsFormat) {
+        let results_file = std::env::temp_dir().join(format!(
+            "nexmark-results-{}-{results_format:?}",
+            std::process::id()
+        ));
+        let config = NexmarkConfig {
+            cpu_cores: 1,
+            max_events: 10_000,
+            input_batch_size: 1_000,
+            query: vec![Query::Q4],
+            seed: Some(1),
+            results_file: Some(results_file.to_str().unwrap().to_string()),
+            results_format,
+            ..NexmarkConfig::default()
+        };
+
+        let results = run_benchmark(&config).unwrap();
+
+        let parsed: Vec<BenchmarkResult> = match results_format {
+            ResultsFormat::Csv => csv::Reader::from_path(&results_file)
+                .unwrap()
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .unwrap(),
+            ResultsFormat::Json => {
+                serde_json::from_reader(File::open(&results_file).unwrap()).unwrap()
+            }
+        };
+        fs::remove_file(&results_file).unwrap();
+
+        assert_eq!(parsed.len(), 1);
+        let result = &parsed[0];
+        assert_eq!(result.query, "q4");
+        assert_eq!(result.num_workers, 1);
+        assert_eq!(result.num_events, 10_000);
+        assert_eq!(result.num_steps, 10);
+        assert!(result.elapsed_secs > 0.0);
+        assert!(result.throughput > 0.0);
+        assert!(result.step_latency_p50_us > 0);
+        assert!(result.step_latency_p50_us <= result.step_latency_p95_us);
+        assert!(result.step_latency_p95_us <= result.step_latency_p99_us);
+        assert!(result.peak_state_bytes > 0);
+
+        // Floats may not round-trip exactly through CSV.
+        assert_eq!(result.query, results[0].query);
+        assert_eq!(result.peak_state_bytes, results[0].peak_state_bytes);
+    }
+}
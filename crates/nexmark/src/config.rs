@@ -3,9 +3,13 @@
 //! API based on the equivalent [Nexmark Flink Configuration API](https://github.com/nexmark/nexmark/blob/v0.2.0/nexmark-flink/src/main/java/com/github/nexmark/flink/NexmarkConfiguration.java)
 //! and the specific [Nexmark Flink Generator config](https://github.com/nexmark/nexmark/blob/v0.2.0/nexmark-flink/src/main/java/com/github/nexmark/flink/generator/GeneratorConfig.java).
 
+use anyhow::{anyhow, bail, Context, Result};
+use arcstr::ArcStr;
 use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, str::FromStr};
 
-pub use crate::queries::Query;
+pub use crate::{benchmark::ResultsFormat, pacing::CatchUpPolicy, queries::Query};
 
 // Number of yet-to-be-created people and auction ids allowed.
 pub const PERSON_ID_LEAD: usize = 10;
@@ -13,7 +17,7 @@ pub const PERSON_ID_LEAD: usize = 10;
 /// A Nexmark streaming data source generator
 ///
 /// Based on the Java/Flink generator found in the [Nexmark repository](https://github.com/nexmark/nexmark).
-#[derive(Clone, Debug, Parser)]
+#[derive(Clone, Debug, Parser, Serialize, Deserialize)]
 #[clap(author, version, about)]
 pub struct Config {
     // Cargo passes any `--bench nexmark` (for example) through to our main
@@ -62,6 +66,38 @@ pub struct Config {
     #[clap(long, default_value = "4", env = "NEXMARK_HOT_SELLERS_RATIO")]
     pub hot_sellers_ratio: usize,
 
+    /// Choose the auctions and bidders of bids from a Zipf distribution with
+    /// the specified exponent, instead of using `hot_auction_ratio` and
+    /// `hot_bidders_ratio`.  Larger exponents concentrate more bids on fewer
+    /// auctions and bidders.
+    #[clap(long, env = "NEXMARK_SKEW_EXPONENT")]
+    pub skew_exponent: Option<f64>,
+
+    /// Lower bound of the log-uniform distribution of prices, in cents.
+    #[clap(long, default_value = "100", env = "NEXMARK_MIN_PRICE")]
+    pub min_price: usize,
+
+    /// Upper bound of the log-uniform distribution of prices, in cents.
+    #[clap(long, default_value = "100000000", env = "NEXMARK_MAX_PRICE")]
+    pub max_price: usize,
+
+    /// Draw prices from a histogram instead of a log-uniform distribution.
+    /// Buckets are specified as `<upper bound>:<weight>`, e.g.,
+    /// `1000:0.8,100000:0.2`.  Each bucket covers the prices from the upper
+    /// bound of the previous bucket (`min_price` for the first bucket) up to
+    /// its own upper bound, excluded, and prices are uniformly distributed
+    /// within a bucket.
+    #[clap(long, env = "NEXMARK_PRICE_HISTOGRAM", value_delimiter = ',')]
+    pub price_histogram: Vec<PriceBucket>,
+
+    /// Currencies of bids and their exchange rates from dollars, specified as
+    /// `<currency>:<rate>`, e.g., `EUR:0.908,GBP:0.79`.  Each bid is placed in
+    /// a currency picked uniformly at random, with its price converted from
+    /// dollars at the currency's rate.  Bids have no currency, i.e., are in
+    /// dollars, if no rates are specified.
+    #[clap(long, env = "NEXMARK_CURRENCY_RATES", value_delimiter = ',')]
+    pub currency_rates: Vec<CurrencyRate>,
+
     /// Max number of events to be generated. 0 is unlimited.
     #[clap(long, default_value = "100000000", env = "NEXMARK_MAX_EVENTS")]
     pub max_events: u64,
@@ -83,18 +119,48 @@ pub struct Config {
 
     /// Number of events in out-of-order groups. 1 implies no out-of-order
     /// events. 1000 implies every 1000 events per generator are emitted in
+    /// pseudo-random order, and the source delivers every 1000 events in
     /// pseudo-random order.
     #[clap(long, default_value = "1", env = "NEXMARK_OUT_OF_ORDER_GROUP_SIZE")]
     pub out_of_order_group_size: usize,
 
+    /// Distribution of events over time: `uniform` at `first_event_rate`,
+    /// `bursty:<burst_len>:<gap_len>:<ratio>` for bursts of `burst_len`
+    /// events at `ratio` times `first_event_rate`, separated by `gap_len`
+    /// milliseconds without events, or `replay:<path>` to replay the event
+    /// timestamps in a file.
+    #[clap(long, default_value = "uniform", env = "NEXMARK_TIMESTAMP_MODEL")]
+    pub timestamp_model: TimestampModel,
+
     /// Specify the proportion of events that will be new people.
     #[clap(long, default_value = "1", env = "NEXMARK_PERSON_PROPORTION")]
     pub person_proportion: usize,
 
+    /// Speed up (> 1) or slow down (< 1) the emission of events relative to
+    /// their wallclock timestamps.
+    #[clap(long, default_value = "1.0", env = "NEXMARK_RATE_MULTIPLIER")]
+    pub rate_multiplier: f64,
+
+    /// What to do when the source falls behind the event schedule.
+    #[clap(
+        long,
+        default_value = "burst",
+        env = "NEXMARK_CATCH_UP_POLICY",
+        value_enum
+    )]
+    pub catch_up_policy: CatchUpPolicy,
+
     /// Dump DBSP profiles for all executed queries to the specified directory.
     #[clap(long, env = "NEXMARK_PROFILE_PATH")]
     pub profile_path: Option<String>,
 
+    /// Seed for the random number generators used to generate events.  Runs
+    /// with the same seed and number of events generate identical event
+    /// sequences, whose event times start at a fixed time rather than the
+    /// current time.  Generators are seeded randomly if not specified.
+    #[clap(long, env = "NEXMARK_SEED")]
+    pub seed: Option<u64>,
+
     /// Queries to run, all by default.
     #[clap(long, env = "NEXMARK_QUERIES", value_enum)]
     pub query: Vec<Query>,
@@ -111,6 +177,176 @@ pub struct Config {
     /// Store results in a csv file in addition to printing on the command-line.
     #[clap(long = "csv", env = "DBSP_RESULTS_AS_CSV")]
     pub output_csv: Option<String>,
+
+    /// Write per-query benchmark metrics (step latency percentiles,
+    /// throughput and peak state size) to the specified file.
+    #[clap(long, env = "NEXMARK_RESULTS_FILE")]
+    pub results_file: Option<String>,
+
+    /// Format of the file specified by `--results-file`.
+    #[clap(
+        long,
+        default_value = "csv",
+        env = "NEXMARK_RESULTS_FORMAT",
+        value_enum
+    )]
+    pub results_format: ResultsFormat,
+}
+
+/// A bucket of the price histogram, see [`Config::price_histogram`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PriceBucket {
+    /// Exclusive upper bound of the prices in the bucket, in cents.
+    pub upper_bound: usize,
+    /// Relative frequency of prices in the bucket.
+    pub weight: f64,
+}
+
+impl FromStr for PriceBucket {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (upper_bound, weight) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected '<upper bound>:<weight>', got '{s}'"))?;
+
+        Ok(Self {
+            upper_bound: upper_bound.trim().parse()?,
+            weight: weight.trim().parse()?,
+        })
+    }
+}
+
+/// Exchange rate of a bid currency, see [`Config::currency_rates`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CurrencyRate {
+    /// Name of the currency, e.g., `EUR`.
+    pub currency: ArcStr,
+    /// Amount of the currency worth one dollar.
+    pub rate: f64,
+}
+
+impl CurrencyRate {
+    /// Convert a price in dollar cents to cents of this currency.
+    pub fn convert(&self, price: usize) -> usize {
+        (price as f64 * self.rate).round() as usize
+    }
+}
+
+impl FromStr for CurrencyRate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (currency, rate) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected '<currency>:<rate>', got '{s}'"))?;
+
+        Ok(Self {
+            currency: currency.trim().into(),
+            rate: rate.trim().parse()?,
+        })
+    }
+}
+
+/// Distribution of event timestamps, see [`Config::timestamp_model`].
+///
+/// Event timestamps never decrease with the event number under any model, so
+/// that the watermarks derived from them are valid.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TimestampModel {
+    /// Events arrive at a constant rate of `first_event_rate`.
+    #[default]
+    Uniform,
+
+    /// Events arrive in bursts of `burst_len` events at `ratio` times
+    /// `first_event_rate`, and bursts are separated by gaps of `gap_len`
+    /// milliseconds without events.
+    Bursty {
+        burst_len: u64,
+        gap_len: u64,
+        ratio: f64,
+    },
+
+    /// Event timestamps are replayed from a recorded sequence of
+    /// non-decreasing timestamps in milliseconds, relative to the first one.
+    /// The sequence repeats once it is exhausted, with each repetition
+    /// starting one average inter-arrival time after the end of the previous
+    /// one.
+    Replay { timestamps: Vec<u64> },
+}
+
+impl TimestampModel {
+    /// Read the timestamps to replay from `path`, which contains one timestamp
+    /// in milliseconds per line.
+    pub fn replay_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read '{}'", path.display()))?;
+        let timestamps = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.parse()
+                    .with_context(|| format!("invalid timestamp '{line}' in '{}'", path.display()))
+            })
+            .collect::<Result<Vec<u64>>>()?;
+
+        let model = Self::Replay { timestamps };
+        model.validate()?;
+        Ok(model)
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Self::Uniform => {}
+            Self::Bursty {
+                burst_len, ratio, ..
+            } => {
+                if *burst_len == 0 {
+                    bail!("burst_len of the bursty timestamp model must be positive");
+                }
+                if !(ratio.is_finite() && *ratio > 0.0) {
+                    bail!("ratio of the bursty timestamp model must be positive, got {ratio}");
+                }
+            }
+            Self::Replay { timestamps } => {
+                if timestamps.len() < 2 {
+                    bail!("replayed timestamps must contain at least two timestamps");
+                }
+                if timestamps.windows(2).any(|pair| pair[0] > pair[1]) {
+                    bail!("replayed timestamps must be non-decreasing");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for TimestampModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+
+        let model = match name {
+            "uniform" if args.is_empty() => Self::Uniform,
+            "bursty" => match args.split(':').collect::<Vec<_>>()[..] {
+                [burst_len, gap_len, ratio] => Self::Bursty {
+                    burst_len: burst_len.parse()?,
+                    gap_len: gap_len.parse()?,
+                    ratio: ratio.parse()?,
+                },
+                _ => bail!("expected 'bursty:<burst_len>:<gap_len>:<ratio>', got '{s}'"),
+            },
+            "replay" if !args.is_empty() => return Self::replay_file(args),
+            _ => bail!("expected 'uniform', 'bursty:...' or 'replay:<path>', got '{s}'"),
+        };
+
+        model.validate()?;
+        Ok(model)
+    }
 }
 
 /// Implementation of config methods based on the Java implementation at
@@ -119,6 +355,83 @@ impl Config {
     pub fn total_proportion(&self) -> usize {
         self.person_proportion + self.auction_proportion + self.bid_proportion
     }
+
+    /// Check that events can be generated with this config.
+    ///
+    /// Returns an error describing the first problem found, e.g., when the
+    /// proportions of event types make it impossible for bids to reference
+    /// auctions and people, or auctions to reference sellers.
+    pub fn validate(&self) -> Result<()> {
+        if self.person_proportion == 0 {
+            bail!("person_proportion must be positive, since auctions and bids reference people");
+        }
+        if self.auction_proportion == 0 {
+            bail!("auction_proportion must be positive, since bids reference auctions");
+        }
+
+        for (name, value) in [
+            ("num_active_people", self.num_active_people),
+            ("num_in_flight_auctions", self.num_in_flight_auctions),
+            ("hot_auction_ratio", self.hot_auction_ratio),
+            ("hot_bidders_ratio", self.hot_bidders_ratio),
+            ("hot_sellers_ratio", self.hot_sellers_ratio),
+            ("first_event_rate", self.first_event_rate),
+            ("num_event_generators", self.num_event_generators),
+            ("out_of_order_group_size", self.out_of_order_group_size),
+            ("min_price", self.min_price),
+        ] {
+            if value == 0 {
+                bail!("{name} must be positive");
+            }
+        }
+
+        for (name, value) in [
+            ("rate_multiplier", Some(self.rate_multiplier)),
+            ("skew_exponent", self.skew_exponent),
+        ] {
+            if let Some(value) = value {
+                if !(value.is_finite() && value > 0.0) {
+                    bail!("{name} must be a positive number, got {value}");
+                }
+            }
+        }
+
+        self.timestamp_model.validate()?;
+
+        if self.max_price < self.min_price {
+            bail!("max_price must not be less than min_price");
+        }
+
+        let mut lower_bound = self.min_price;
+        for bucket in &self.price_histogram {
+            if bucket.upper_bound <= lower_bound {
+                bail!("price_histogram upper bounds must be increasing and above min_price");
+            }
+            if !(bucket.weight.is_finite() && bucket.weight >= 0.0) {
+                bail!(
+                    "price_histogram weights must be non-negative numbers, got {}",
+                    bucket.weight
+                );
+            }
+            lower_bound = bucket.upper_bound;
+        }
+        if !self.price_histogram.is_empty()
+            && self
+                .price_histogram
+                .iter()
+                .all(|bucket| bucket.weight == 0.0)
+        {
+            bail!("price_histogram must have a bucket with positive weight");
+        }
+
+        for CurrencyRate { currency, rate } in &self.currency_rates {
+            if !(rate.is_finite() && *rate > 0.0) {
+                bail!("currency_rates must be positive numbers, got {rate} for {currency}");
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for Config {
@@ -135,17 +448,155 @@ impl Default for Config {
             hot_auction_ratio: 2,
             hot_bidders_ratio: 4,
             hot_sellers_ratio: 4,
+            skew_exponent: None,
+            min_price: 100,
+            max_price: 100_000_000,
+            price_histogram: Vec::new(),
+            currency_rates: Vec::new(),
             max_events: 100_000_000,
             num_active_people: 1000,
             num_event_generators: 2,
             num_in_flight_auctions: 100,
             out_of_order_group_size: 1,
+            timestamp_model: TimestampModel::Uniform,
             person_proportion: 1,
+            rate_multiplier: 1.0,
+            catch_up_policy: CatchUpPolicy::Burst,
             profile_path: None,
+            seed: None,
             query: Vec::new(),
             source_buffer_size: 10_000,
             input_batch_size: 40_000,
             output_csv: None,
+            results_file: None,
+            results_format: ResultsFormat::Csv,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn test_parse_price_and_currency_args() {
+        let config = Config::parse_from([
+            "nexmark",
+            "--price-histogram",
+            "1000:0.8,100000:0.2",
+            "--currency-rates",
+            "EUR:0.908,GBP:0.79",
+        ]);
+
+        assert_eq!(
+            config.price_histogram,
+            vec![
+                PriceBucket {
+                    upper_bound: 1000,
+                    weight: 0.8
+                },
+                PriceBucket {
+                    upper_bound: 100_000,
+                    weight: 0.2
+                },
+            ]
+        );
+        assert_eq!(
+            config.currency_rates,
+            vec![
+                CurrencyRate {
+                    currency: ArcStr::from("EUR"),
+                    rate: 0.908
+                },
+                CurrencyRate {
+                    currency: ArcStr::from("GBP"),
+                    rate: 0.79
+                },
+            ]
+        );
+        assert!("EUR".parse::<CurrencyRate>().is_err());
+        assert!("1000:x".parse::<PriceBucket>().is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp_model() {
+        assert_eq!(
+            "uniform".parse::<TimestampModel>().unwrap(),
+            TimestampModel::Uniform
+        );
+        assert_eq!(
+            "bursty:100:50:2.5".parse::<TimestampModel>().unwrap(),
+            TimestampModel::Bursty {
+                burst_len: 100,
+                gap_len: 50,
+                ratio: 2.5
+            }
+        );
+
+        let path = std::env::temp_dir().join(format!("nexmark-timestamps-{}", std::process::id()));
+        fs::write(&path, "1000\n1000\n1005\n\n1020\n").unwrap();
+        assert_eq!(
+            format!("replay:{}", path.display())
+                .parse::<TimestampModel>()
+                .unwrap(),
+            TimestampModel::Replay {
+                timestamps: vec![1000, 1000, 1005, 1020]
+            }
+        );
+        fs::write(&path, "1000\n999\n").unwrap();
+        assert!(format!("replay:{}", path.display())
+            .parse::<TimestampModel>()
+            .is_err());
+        fs::remove_file(&path).unwrap();
+
+        for invalid in [
+            "poisson",
+            "uniform:1",
+            "bursty:100:50",
+            "bursty:0:50:1",
+            "replay:",
+        ] {
+            assert!(
+                invalid.parse::<TimestampModel>().is_err(),
+                "parsed '{invalid}'"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::no_people(Config { person_proportion: 0, ..Config::default() }, "person_proportion")]
+    #[case::no_auctions(Config { auction_proportion: 0, ..Config::default() }, "auction_proportion")]
+    #[case::no_active_people(Config { num_active_people: 0, ..Config::default() }, "num_active_people")]
+    #[case::no_in_flight_auctions(
+        Config { num_in_flight_auctions: 0, ..Config::default() },
+        "num_in_flight_auctions"
+    )]
+    #[case::zero_hot_ratio(Config { hot_bidders_ratio: 0, ..Config::default() }, "hot_bidders_ratio")]
+    #[case::negative_skew(Config { skew_exponent: Some(-1.0), ..Config::default() }, "skew_exponent")]
+    #[case::inverted_price_bounds(Config { min_price: 1000, max_price: 100, ..Config::default() }, "max_price")]
+    #[case::unordered_price_histogram(
+        Config { price_histogram: vec!["1000:1".parse().unwrap(), "500:1".parse().unwrap()], ..Config::default() },
+        "price_histogram"
+    )]
+    #[case::zero_price_histogram(
+        Config { price_histogram: vec!["1000:0".parse().unwrap()], ..Config::default() },
+        "price_histogram"
+    )]
+    #[case::zero_currency_rate(
+        Config { currency_rates: vec!["EUR:0".parse().unwrap()], ..Config::default() },
+        "currency_rates"
+    )]
+    fn test_invalid_config(#[case] config: Config, #[case] expected_error: &str) {
+        let error = config.validate().unwrap_err().to_string();
+        assert!(
+            error.contains(expected_error),
+            "error '{error}' does not mention {expected_error}"
+        );
+    }
+}
@@ -5,9 +5,9 @@
 use super::{
     super::model::Auction,
     config::{FIRST_AUCTION_ID, FIRST_CATEGORY_ID, FIRST_PERSON_ID},
-    NexmarkGenerator,
+    zipf::Zipf,
+    GeneratorError, NexmarkGenerator,
 };
-use anyhow::Result;
 use rand::Rng;
 use std::{
     cmp,
@@ -24,12 +24,19 @@ const HOT_SELLER_RATIO: usize = 100;
 
 impl<R: Rng> NexmarkGenerator<R> {
     /// Generate and return a random auction with the next available id.
+    ///
+    /// Fails if the configuration does not generate any people that could
+    /// be the seller of the auction.
     pub fn next_auction(
         &mut self,
         events_count_so_far: u64,
         event_id: u64,
         timestamp: u64,
-    ) -> Result<Auction> {
+    ) -> Result<Auction, GeneratorError> {
+        if self.config.nexmark_config.person_proportion == 0 {
+            return Err(GeneratorError::NoPeople { event_id });
+        }
+
         let id = self.last_base0_auction_id(event_id) + FIRST_AUCTION_ID as u64;
 
         // Here P(auction will be for a hot seller) = 1 - 1/hot_sellers_ratio.
@@ -39,9 +46,13 @@ impl<R: Rng> NexmarkGenerator<R> {
         {
             0 => self.next_base0_person_id(event_id),
             _ => {
-                // Choose the first person in the batch of last hot_sellers_ratio people.
-                (self.last_base0_person_id(event_id) / HOT_SELLER_RATIO as u64)
-                    * HOT_SELLER_RATIO as u64
+                // Choose the first person in the batch of last HOT_SELLER_RATIO people, or of
+                // the active people if there are fewer.
+                let batch_size = cmp::min(
+                    HOT_SELLER_RATIO,
+                    self.config.nexmark_config.num_active_people,
+                ) as u64;
+                (self.last_base0_person_id(event_id) / batch_size) * batch_size
             }
         } + FIRST_PERSON_ID as u64;
 
@@ -120,6 +131,20 @@ impl<R: Rng> NexmarkGenerator<R> {
         min_auction + self.rng.gen_range(0..(max_auction - min_auction + 1))
     }
 
+    /// Return a random auction id (base 0) from the same auctions as
+    /// [`Self::next_base0_auction_id`], drawn from a Zipf distribution with
+    /// the specified exponent.  The most recent auction is the most popular
+    /// one.
+    pub fn next_skewed_base0_auction_id(&mut self, next_event_id: u64, skew_exponent: f64) -> u64 {
+        let max_auction = self.last_base0_auction_id(next_event_id);
+        let num_auctions = cmp::min(
+            max_auction,
+            self.config.nexmark_config.num_in_flight_auctions as u64,
+        ) + 1;
+        let rank = Zipf::new(num_auctions, skew_exponent).sample(&mut self.rng);
+        max_auction + 1 - rank
+    }
+
     /// Return a random time delay, in milliseconds, for length of auctions.
     fn next_auction_length_ms(&mut self, event_count_so_far: u64, timestamp: u64) -> u64 {
         // What's our current event number?
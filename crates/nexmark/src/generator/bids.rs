@@ -10,7 +10,7 @@ use super::{
 use arcstr::ArcStr;
 use cached::Cached;
 use rand::Rng;
-use std::mem::size_of;
+use std::{cmp, mem::size_of};
 
 /// Fraction of people/auctions which may be 'hot' sellers/bidders/auctions are
 /// 1 over these values.
@@ -58,33 +58,47 @@ impl<R: Rng> NexmarkGenerator<R> {
     }
 
     pub fn next_bid(&mut self, event_id: u64, timestamp: u64) -> Bid {
-        let auction = match self
-            .rng
-            .gen_range(0..self.config.nexmark_config.hot_auction_ratio)
-        {
-            0 => self.next_base0_auction_id(event_id),
-            _ => {
-                // Choose the first auction in the batch of last HOT_AUCTION_RATIO auctions.
-                (self.last_base0_auction_id(event_id) / HOT_AUCTON_RATIO as u64)
-                    * HOT_AUCTON_RATIO as u64
-            }
+        let auction = match self.config.nexmark_config.skew_exponent {
+            Some(skew_exponent) => self.next_skewed_base0_auction_id(event_id, skew_exponent),
+            None => match self
+                .rng
+                .gen_range(0..self.config.nexmark_config.hot_auction_ratio)
+            {
+                0 => self.next_base0_auction_id(event_id),
+                _ => {
+                    // Choose the first auction in the batch of last HOT_AUCTION_RATIO auctions,
+                    // or of the in-flight auctions if there are fewer.
+                    let batch_size = cmp::min(
+                        HOT_AUCTON_RATIO,
+                        self.config.nexmark_config.num_in_flight_auctions,
+                    ) as u64;
+                    (self.last_base0_auction_id(event_id) / batch_size) * batch_size
+                }
+            },
         } + FIRST_AUCTION_ID as u64;
 
-        let bidder = match self
-            .rng
-            .gen_range(0..self.config.nexmark_config.hot_bidders_ratio)
-        {
-            0 => self.next_base0_person_id(event_id),
-            _ => {
-                // Choose the second person (so hot bidders and hot sellers don't collide) in
-                // the batch of last HOT_BIDDER_RATIO people.
-                (self.last_base0_person_id(event_id) / HOT_BIDDER_RATIO as u64)
-                    * HOT_BIDDER_RATIO as u64
-                    + 1
-            }
+        let bidder = match self.config.nexmark_config.skew_exponent {
+            Some(skew_exponent) => self.next_skewed_base0_person_id(event_id, skew_exponent),
+            None => match self
+                .rng
+                .gen_range(0..self.config.nexmark_config.hot_bidders_ratio)
+            {
+                0 => self.next_base0_person_id(event_id),
+                _ => {
+                    // Choose the second person (so hot bidders and hot sellers don't collide) in
+                    // the batch of last HOT_BIDDER_RATIO people, or of the active people if there
+                    // are fewer.
+                    let batch_size = cmp::min(
+                        HOT_BIDDER_RATIO,
+                        self.config.nexmark_config.num_active_people,
+                    ) as u64;
+                    (self.last_base0_person_id(event_id) / batch_size) * batch_size
+                        + cmp::min(1, batch_size - 1)
+                }
+            },
         } + FIRST_PERSON_ID as u64;
 
-        let price = self.next_price();
+        let (price, currency) = self.next_bid_price();
 
         // NOTE: Shifted the start and finish of the range here simply so that when
         // testing with the StepRng, we can test the deterministic case where the first
@@ -107,6 +121,7 @@ impl<R: Rng> NexmarkGenerator<R> {
             auction,
             bidder,
             price,
+            currency,
             channel,
             url,
             date_time: timestamp,
@@ -124,10 +139,15 @@ fn get_base_url<R: Rng>(rng: &mut R) -> ArcStr {
 
 #[cfg(test)]
 pub mod tests {
-    use super::super::tests::make_test_generator;
+    use super::super::{config::Config, tests::make_test_generator};
     use super::*;
-    use rand::rngs::mock::StepRng;
+    use crate::config::Config as NexmarkConfig;
+    use rand::{
+        rngs::{mock::StepRng, SmallRng},
+        SeedableRng,
+    };
     use rstest::rstest;
+    use std::collections::HashMap;
 
     #[rstest]
     #[case(0, 1_000, 1_000)]
@@ -153,6 +173,7 @@ pub mod tests {
                 auction: expected_auction_id,
                 bidder: expected_bidder_id,
                 price: 100,
+                currency: None,
                 channel: arcstr::literal!("Google"),
                 url: arcstr::literal!("https://www.nexmark.com/googl/item.htm?query=1"),
                 date_time: 1_000_000_000_000,
@@ -162,6 +183,57 @@ pub mod tests {
         );
     }
 
+    // Frequency of the most popular auction among bids placed while there
+    // are 101 auctions in flight.
+    #[rstest]
+    #[case::hot_ratio(None, 4, 0.75 + 0.25 / 101.0)]
+    #[case::hot_ratio_disabled(None, 1, 1.0 / 101.0)]
+    #[case::zipf_0_5(Some(0.5), 4, zipf_top_frequency(101, 0.5))]
+    #[case::zipf_1(Some(1.0), 4, zipf_top_frequency(101, 1.0))]
+    #[case::zipf_1_5(Some(1.5), 4, zipf_top_frequency(101, 1.5))]
+    fn test_top_auction_frequency(
+        #[case] skew_exponent: Option<f64>,
+        #[case] hot_auction_ratio: usize,
+        #[case] expected_frequency: f64,
+    ) {
+        const NUM_BIDS: usize = 100_000;
+
+        let mut ng = NexmarkGenerator::new(
+            Config {
+                nexmark_config: NexmarkConfig {
+                    num_event_generators: 1,
+                    hot_auction_ratio,
+                    skew_exponent,
+                    ..NexmarkConfig::default()
+                },
+                ..Config::default()
+            },
+            SmallRng::seed_from_u64(1),
+            0,
+        );
+
+        // Event 10 of the epoch is a bid, with auctions 0..=4502 generated.
+        let event_id = 50 * 1500 + 10;
+        let mut counts = HashMap::new();
+        for _ in 0..NUM_BIDS {
+            *counts
+                .entry(ng.next_bid(event_id, 0).auction)
+                .or_insert(0usize) += 1;
+        }
+
+        let top_frequency = *counts.values().max().unwrap() as f64 / NUM_BIDS as f64;
+        assert!(
+            (top_frequency - expected_frequency).abs() < 0.01,
+            "top auction frequency {top_frequency}, expected {expected_frequency}"
+        );
+    }
+
+    fn zipf_top_frequency(n: u64, exponent: f64) -> f64 {
+        1.0 / (1..=n)
+            .map(|k| 1.0 / (k as f64).powf(exponent))
+            .sum::<f64>()
+    }
+
     #[test]
     fn test_get_base_url() {
         let mut rng = StepRng::new(0, 1);
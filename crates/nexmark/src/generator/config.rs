@@ -1,4 +1,5 @@
-use super::super::config::Config as NexmarkConfig;
+use super::super::config::{Config as NexmarkConfig, TimestampModel};
+use serde::{Deserialize, Serialize};
 
 // We start the ids at specific values to help ensure the queries find a match
 // even on small synthesized dataset sizes.
@@ -8,7 +9,7 @@ pub const FIRST_CATEGORY_ID: usize = 10;
 
 /// The generator config is a combination of the CLI configuration and the
 /// options specific to this generator instantiation.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     pub nexmark_config: NexmarkConfig,
 
@@ -88,6 +89,40 @@ impl Config {
         }
     }
 
+    /// Return a copy of this config restricted to event ids in the range
+    /// `[from_event_id, to_event_id)`.
+    ///
+    /// Requires a single generator (`num_event_generators == 1`), which
+    /// generates contiguous event numbers.
+    pub fn with_event_id_range(&self, from_event_id: u64, to_event_id: u64) -> Config {
+        assert_eq!(
+            self.nexmark_config.num_event_generators, 1,
+            "only configs for a single generator can be split"
+        );
+        assert!(from_event_id >= self.first_event_id && from_event_id <= to_event_id);
+
+        Config {
+            first_event_number: (from_event_id - self.first_event_id) as usize,
+            max_events: to_event_id,
+            ..self.clone()
+        }
+    }
+
+    /// Split this config at event id `event_id`, similar to `splitAtEventId`
+    /// in the Java implementation.
+    ///
+    /// After the split, this config generates events with ids smaller than
+    /// `event_id`, and the returned config generates the remaining events.
+    ///
+    /// With `out_of_order_group_size > 1`, `event_id` should be aligned to
+    /// the group size (relative to `first_event_id`), as events are only
+    /// reordered within a group.
+    pub fn split_at_event_id(&mut self, event_id: u64) -> Config {
+        let remaining = self.with_event_id_range(event_id, self.max_events);
+        self.max_events = event_id;
+        remaining
+    }
+
     /// Return the next event number for a generator which has so far emitted
     /// `num_events`.
     pub fn next_event_number(&self, num_events: u64) -> u64 {
@@ -115,8 +150,40 @@ impl Config {
 
     // What timestamp should the event with `eventNumber` have for this
     // generator?
+    //
+    // Timestamps must not decrease with the event number, since watermarks
+    // are derived from the timestamps of earlier event numbers (see
+    // `next_event_number_for_watermark`).
     pub fn timestamp_for_event(&self, event_number: u64) -> u64 {
-        self.base_time + (self.inter_event_delay_us[0] * event_number as f64) as u64 / 1000
+        match &self.nexmark_config.timestamp_model {
+            TimestampModel::Uniform => {
+                self.base_time + (self.inter_event_delay_us[0] * event_number as f64) as u64 / 1000
+            }
+            TimestampModel::Bursty {
+                burst_len,
+                gap_len,
+                ratio,
+            } => {
+                let inter_event_delay_us = self.inter_event_delay_us[0] / ratio;
+                let burst_period_us =
+                    *burst_len as f64 * inter_event_delay_us + *gap_len as f64 * 1000.0;
+                let burst = event_number / burst_len;
+                let offset = event_number % burst_len;
+                self.base_time
+                    + (burst as f64 * burst_period_us + offset as f64 * inter_event_delay_us) as u64
+                        / 1000
+            }
+            TimestampModel::Replay { timestamps } => {
+                let num_timestamps = timestamps.len() as u64;
+                let first = timestamps[0];
+                let span = timestamps[timestamps.len() - 1] - first;
+                // Repetitions are separated by the average inter-arrival time.
+                let period = span + span / (num_timestamps - 1);
+                let repetition = event_number / num_timestamps;
+                let offset = timestamps[(event_number % num_timestamps) as usize] - first;
+                self.base_time + repetition * period + offset
+            }
+        }
     }
 }
 
@@ -277,4 +344,81 @@ pub mod tests {
             expected,
         );
     }
+
+    fn config_with_timestamp_model(timestamp_model: TimestampModel) -> Config {
+        Config::new(
+            NexmarkConfig {
+                num_event_generators: 1,
+                first_event_rate: 10_000,
+                timestamp_model,
+                ..NexmarkConfig::default()
+            },
+            0,
+            0,
+            0,
+        )
+    }
+
+    // At 10_000 events per second, bursts of 100 events at 10 times the rate
+    // last 100 * 10µs = 1ms, followed by a 50ms gap.
+    #[rstest]
+    #[case(0, 0)]
+    #[case(99, 0)]
+    #[case(100, 51)]
+    #[case(199, 51)]
+    #[case(200, 102)]
+    #[case(1050, 510)]
+    fn test_timestamp_for_event_bursty(#[case] event_number: u64, #[case] expected: u64) {
+        let config = config_with_timestamp_model(TimestampModel::Bursty {
+            burst_len: 100,
+            gap_len: 50,
+            ratio: 10.0,
+        });
+
+        assert_eq!(config.timestamp_for_event(event_number), expected);
+    }
+
+    #[test]
+    fn test_bursty_inter_arrival_times() {
+        let config = config_with_timestamp_model(TimestampModel::Bursty {
+            burst_len: 100,
+            gap_len: 50,
+            ratio: 10.0,
+        });
+
+        let timestamps: Vec<u64> = (0..10_000)
+            .map(|event_number| config.timestamp_for_event(event_number))
+            .collect();
+        let gaps: Vec<u64> = timestamps
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .filter(|&inter_arrival_time| inter_arrival_time > 1)
+            .collect();
+
+        // One gap between each pair of consecutive bursts, and no gaps within
+        // bursts.
+        assert_eq!(gaps.len(), 99);
+        assert!(gaps.iter().all(|&gap| gap == 50 || gap == 51));
+        // The average rate over a period of a burst and a gap is 100 events
+        // per 51ms.
+        let mean_inter_arrival_time = timestamps[9_999] as f64 / 9_999.0;
+        assert!((mean_inter_arrival_time - 0.51).abs() < 0.01);
+    }
+
+    #[rstest]
+    #[case(0, 0)]
+    #[case(1, 0)]
+    #[case(2, 5)]
+    #[case(3, 20)]
+    // The replay repeats 20 + 20 / 3 = 26ms after it started.
+    #[case(4, 26)]
+    #[case(7, 46)]
+    #[case(8, 52)]
+    fn test_timestamp_for_event_replay(#[case] event_number: u64, #[case] expected: u64) {
+        let config = config_with_timestamp_model(TimestampModel::Replay {
+            timestamps: vec![1000, 1000, 1005, 1020],
+        });
+
+        assert_eq!(config.timestamp_for_event(event_number), expected);
+    }
 }
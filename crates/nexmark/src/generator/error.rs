@@ -0,0 +1,43 @@
+use std::{
+    error::Error,
+    fmt::{Display, Error as FmtError, Formatter},
+};
+
+/// Errors returned by [`NexmarkGenerator`](super::NexmarkGenerator).
+///
+/// All of these are caused by configurations that cannot generate consistent
+/// events, and are ruled out by
+/// [`Config::validate`](crate::config::Config::validate).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GeneratorError {
+    /// All event proportions are zero, so there is no event type to generate.
+    NoEventTypes,
+    /// The event with id `event_id` is an auction or a bid, which must
+    /// reference a person, but `person_proportion` is zero.
+    NoPeople { event_id: u64 },
+    /// The event with id `event_id` is a bid, which must reference an
+    /// auction, but `auction_proportion` is zero.
+    NoAuctions { event_id: u64 },
+}
+
+impl Display for GeneratorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self {
+            Self::NoEventTypes => f.write_str("all event proportions are zero"),
+            Self::NoPeople { event_id } => {
+                write!(
+                    f,
+                    "event {event_id} must reference a person, but person_proportion is zero"
+                )
+            }
+            Self::NoAuctions { event_id } => {
+                write!(
+                    f,
+                    "event {event_id} must reference an auction, but auction_proportion is zero"
+                )
+            }
+        }
+    }
+}
+
+impl Error for GeneratorError {}
@@ -0,0 +1,225 @@
+//! Interleaving of independent Nexmark streams, e.g., to benchmark
+//! multi-tenant pipelines.
+
+use super::{config::Config, generator_seed, GeneratorError, NexmarkGenerator, NextEvent};
+use crate::model::Event;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+/// Size of the range of person and auction ids reserved for each tenant.
+///
+/// The ids of tenant `t` are offset by `t * TENANT_ID_SPACE`, so that ids of
+/// different tenants never collide.
+pub const TENANT_ID_SPACE: u64 = 1 << 48;
+
+/// An event of one of the streams of an [`InterleavedGenerator`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TenantEvent {
+    /// Index of the tenant that generated the event.
+    pub tenant: usize,
+
+    /// The event, with person and auction ids offset to the id range of the
+    /// tenant.
+    pub next_event: NextEvent,
+}
+
+/// Generates a stream of events that interleaves the streams of multiple
+/// tenants, each with its own configuration.
+///
+/// Each tenant's events are generated by a separate [`NexmarkGenerator`],
+/// seeded deterministically from the seed of the interleaved generator and
+/// the index of the tenant.  Events are interleaved in order of wallclock
+/// timestamps (ties are broken by tenant index), so tenants contribute to the
+/// stream in proportion to their configured event rates.
+pub struct InterleavedGenerator {
+    generators: Vec<NexmarkGenerator<Xoshiro256PlusPlus>>,
+
+    /// Next event of each tenant, if it has been generated but not returned
+    /// yet.
+    pending: Vec<Option<NextEvent>>,
+}
+
+impl InterleavedGenerator {
+    pub fn new(configs: Vec<Config>, seed: u64) -> Self {
+        let generators: Vec<_> = configs
+            .into_iter()
+            .enumerate()
+            .map(|(tenant, config)| Self::tenant_generator(config, seed, tenant))
+            .collect();
+        let pending = vec![None; generators.len()];
+
+        Self {
+            generators,
+            pending,
+        }
+    }
+
+    /// Returns the generator used for tenant `tenant` by an interleaved
+    /// generator with the specified seed.
+    ///
+    /// The stream of the tenant, filtered out of the interleaved stream, is
+    /// equal to the events of this generator, with ids offset by
+    /// [`tenant_id_offset`].
+    pub fn tenant_generator(
+        config: Config,
+        seed: u64,
+        tenant: usize,
+    ) -> NexmarkGenerator<Xoshiro256PlusPlus> {
+        let wallclock_base_time = config.base_time;
+        NexmarkGenerator::new(
+            config,
+            Xoshiro256PlusPlus::seed_from_u64(generator_seed(seed, tenant as u64)),
+            wallclock_base_time,
+        )
+    }
+
+    pub fn num_tenants(&self) -> usize {
+        self.generators.len()
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.pending.iter().any(Option::is_some)
+            || self.generators.iter().any(NexmarkGenerator::has_next)
+    }
+
+    pub fn next_event(&mut self) -> Result<Option<TenantEvent>, GeneratorError> {
+        for (generator, pending) in self.generators.iter_mut().zip(self.pending.iter_mut()) {
+            if pending.is_none() && generator.has_next() {
+                *pending = generator.next_event()?;
+            }
+        }
+
+        let next_tenant = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter_map(|(tenant, pending)| {
+                pending
+                    .as_ref()
+                    .map(|next_event| (next_event.wallclock_timestamp, tenant))
+            })
+            .min()
+            .map(|(_, tenant)| tenant);
+
+        Ok(next_tenant.map(|tenant| {
+            let mut next_event = self.pending[tenant].take().unwrap();
+            next_event.event = offset_ids(next_event.event, tenant_id_offset(tenant));
+            TenantEvent { tenant, next_event }
+        }))
+    }
+}
+
+/// Returns the offset of the person and auction ids of tenant `tenant`.
+pub fn tenant_id_offset(tenant: usize) -> u64 {
+    tenant as u64 * TENANT_ID_SPACE
+}
+
+/// Offset all person and auction ids in `event` by `offset`.
+pub fn offset_ids(event: Event, offset: u64) -> Event {
+    match event {
+        Event::Person(mut person) => {
+            person.id += offset;
+            Event::Person(person)
+        }
+        Event::Auction(mut auction) => {
+            auction.id += offset;
+            auction.seller += offset;
+            Event::Auction(auction)
+        }
+        Event::Bid(mut bid) => {
+            bid.auction += offset;
+            bid.bidder += offset;
+            Event::Bid(bid)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config as NexmarkConfig;
+
+    fn tenant_configs() -> Vec<Config> {
+        [1_000_000, 2_000_000, 500_000]
+            .into_iter()
+            .map(|first_event_rate| Config {
+                nexmark_config: NexmarkConfig {
+                    num_event_generators: 1,
+                    first_event_rate,
+                    ..NexmarkConfig::default()
+                },
+                max_events: 1000,
+                ..Config::default()
+            })
+            .collect()
+    }
+
+    fn generate_interleaved_events(seed: u64) -> Vec<TenantEvent> {
+        let mut generator = InterleavedGenerator::new(tenant_configs(), seed);
+        let mut events = Vec::new();
+        while let Some(event) = generator.next_event().unwrap() {
+            events.push(event);
+        }
+        assert!(!generator.has_next());
+        events
+    }
+
+    #[test]
+    fn test_interleaved_generator_is_deterministic() {
+        assert_eq!(
+            generate_interleaved_events(42),
+            generate_interleaved_events(42)
+        );
+        assert_ne!(
+            generate_interleaved_events(42),
+            generate_interleaved_events(43)
+        );
+    }
+
+    #[test]
+    fn test_interleaved_events_are_ordered_by_wallclock_time() {
+        let events = generate_interleaved_events(42);
+
+        assert_eq!(events.len(), 3000);
+        assert!(events.windows(2).all(|events| {
+            events[0].next_event.wallclock_timestamp <= events[1].next_event.wallclock_timestamp
+        }));
+    }
+
+    #[test]
+    fn test_tenant_streams_match_standalone_generators() {
+        let events = generate_interleaved_events(42);
+
+        for (tenant, config) in tenant_configs().into_iter().enumerate() {
+            let mut generator = InterleavedGenerator::tenant_generator(config, 42, tenant);
+            let mut expected_events = Vec::new();
+            while let Some(mut next_event) = generator.next_event().unwrap() {
+                next_event.event = offset_ids(next_event.event, tenant_id_offset(tenant));
+                expected_events.push(next_event);
+            }
+
+            let tenant_events: Vec<NextEvent> = events
+                .iter()
+                .filter(|event| event.tenant == tenant)
+                .map(|event| event.next_event.clone())
+                .collect();
+            assert_eq!(tenant_events, expected_events);
+        }
+    }
+
+    #[test]
+    fn test_tenant_ids_do_not_collide() {
+        let events = generate_interleaved_events(42);
+
+        for event in events {
+            let ids = match event.next_event.event {
+                Event::Person(person) => vec![person.id],
+                Event::Auction(auction) => vec![auction.id, auction.seller],
+                Event::Bid(bid) => vec![bid.auction, bid.bidder],
+            };
+            for id in ids {
+                assert_eq!(id / TENANT_ID_SPACE, event.tenant as u64);
+            }
+        }
+    }
+}
@@ -3,29 +3,41 @@
 //! Based on the equivalent [Nexmark Flink generator API](https://github.com/nexmark/nexmark/blob/v0.2.0/nexmark-flink/src/main/java/com/github/nexmark/flink/generator).
 
 use self::config::Config;
-use super::model::Event;
-use anyhow::Result;
+use super::{config::Config as NexmarkConfig, model::Event};
 use arcstr::ArcStr;
 use bids::CHANNELS_NUMBER;
-use cached::SizedCache;
-use rand::Rng;
+use cached::{Cached, SizedCache};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
 
 mod auctions;
 mod bids;
 pub mod config;
+mod error;
+mod interleaved;
 mod people;
 mod price;
 mod strings;
+mod zipf;
+
+pub use error::GeneratorError;
+pub use interleaved::{
+    offset_ids, tenant_id_offset, InterleavedGenerator, TenantEvent, TENANT_ID_SPACE,
+};
 
 pub struct NexmarkGenerator<R: Rng> {
     /// Configuration to generate events against. Note that it may be replaced
-    /// by a call to `splitAtEventId`.
+    /// by a call to `split_at_event_id`.
     config: Config,
     rng: R,
 
     /// The memory cache used when creating bid channels.
     bid_channel_cache: SizedCache<u32, (ArcStr, ArcStr)>,
 
+    /// Buffer in which strings are built before they are copied into events.
+    scratch: String,
+
     /// Number of events generated by this generator.
     /// Note that when there are multiple generators working in parallel, the
     /// events count for each generator is less than the events generated by the
@@ -37,12 +49,221 @@ pub struct NexmarkGenerator<R: Rng> {
     wallclock_base_time: u64,
 }
 
+impl NexmarkGenerator<Xoshiro256PlusPlus> {
+    /// Creates a generator with a random number generator seeded according
+    /// to `config`.
+    ///
+    /// The random number generator is serializable, so that the state of the
+    /// generator can be persisted using [`Self::snapshot`].
+    ///
+    /// If `config.nexmark_config.seed` is set, the RNG of each generator is
+    /// derived deterministically from the seed and the index of the generator
+    /// (`config.first_event_number`), so that multiple generators running in
+    /// parallel produce independent but reproducible event streams.
+    /// Otherwise, the RNG is seeded from system entropy.
+    pub fn from_config(
+        config: Config,
+        wallclock_base_time: u64,
+    ) -> NexmarkGenerator<Xoshiro256PlusPlus> {
+        let rng = match config.nexmark_config.seed {
+            Some(seed) => Xoshiro256PlusPlus::seed_from_u64(generator_seed(
+                seed,
+                config.first_event_number as u64,
+            )),
+            None => Xoshiro256PlusPlus::from_entropy(),
+        };
+        NexmarkGenerator::new(config, rng, wallclock_base_time)
+    }
+}
+
+/// Derives the seed of the generator with index `generator_index` from the
+/// global `seed` using the SplitMix64 mixing function.
+fn generator_seed(seed: u64, generator_index: u64) -> u64 {
+    let mut z = seed.wrapping_add((generator_index + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Checks that generators configured with `config` can generate all of their
+/// events, returning the error [`NexmarkGenerator::next_event`] would
+/// eventually fail with otherwise.
+pub fn check_config(config: &NexmarkConfig) -> Result<(), GeneratorError> {
+    let (person_proportion, auction_proportion, total_proportion) = (
+        config.person_proportion as u64,
+        config.auction_proportion as u64,
+        config.total_proportion() as u64,
+    );
+
+    // Event `id` is a person if `id % total_proportion` is less than
+    // `person_proportion`, an auction if it is less than `person_proportion +
+    // auction_proportion` and a bid otherwise.
+    if total_proportion == 0 {
+        Err(GeneratorError::NoEventTypes)
+    } else if person_proportion == 0 {
+        Err(GeneratorError::NoPeople { event_id: 0 })
+    } else if auction_proportion == 0
+        && person_proportion < total_proportion
+        && (config.max_events == 0 || person_proportion < config.max_events)
+    {
+        Err(GeneratorError::NoAuctions {
+            event_id: person_proportion,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+impl<R: Rng + SeedableRng + Clone> NexmarkGenerator<R> {
+    /// Split this generator at event id `event_id`, similar to
+    /// `splitAtEventId` in the Java implementation.
+    ///
+    /// After the split, this generator generates events with ids smaller
+    /// than `event_id`, and the returned generator generates the remaining
+    /// events, so that the two generators together produce events with the
+    /// same ids and timestamps as this generator before the split.  The
+    /// returned generator's random number generator is seeded from the state
+    /// of this generator's and `event_id`, so that the two ranges get
+    /// independent, but reproducible, random contents.
+    ///
+    /// Must be called before generating any events, and requires
+    /// `num_event_generators == 1`.
+    pub fn split_at_event_id(&mut self, event_id: u64) -> NexmarkGenerator<R> {
+        assert_eq!(
+            self.events_count_so_far, 0,
+            "cannot split a generator that has already generated events"
+        );
+        let config = self.config.split_at_event_id(event_id);
+        NexmarkGenerator::new(config, self.range_rng(event_id), self.wallclock_base_time)
+    }
+
+    /// Return a generator for the events of worker `worker_index` out of
+    /// `total_workers`.
+    ///
+    /// Splits the events of this generator into `total_workers` disjoint,
+    /// contiguous ranges of event ids of (nearly) equal size, so that
+    /// concatenating the events generated for all workers in order of
+    /// worker index produces events with the same ids and timestamps as this
+    /// generator.  Range boundaries are aligned to `out_of_order_group_size`.
+    /// As with [`Self::split_at_event_id`], the random number generator of
+    /// each range is seeded from the state of this generator's and the first
+    /// event id of the range.
+    ///
+    /// Like [`Self::split_at_event_id`], must be called before generating
+    /// any events, and requires `num_event_generators == 1`.
+    pub fn for_worker(&self, worker_index: usize, total_workers: usize) -> NexmarkGenerator<R> {
+        assert!(worker_index < total_workers);
+        assert_eq!(
+            self.events_count_so_far, 0,
+            "cannot split a generator that has already generated events"
+        );
+
+        let group_size = self.config.nexmark_config.out_of_order_group_size as u64;
+        let first_event_number = self.config.first_event_number as u64;
+        let end_event_number = self
+            .config
+            .max_events
+            .saturating_sub(self.config.first_event_id)
+            .max(first_event_number);
+
+        // Event id at which the range of worker `index` starts.
+        let boundary = |index: usize| -> u64 {
+            let event_number = if index == total_workers {
+                end_event_number
+            } else {
+                let offset = ((end_event_number - first_event_number) as u128 * index as u128
+                    / total_workers as u128) as u64;
+                (((first_event_number + offset) / group_size) * group_size).max(first_event_number)
+            };
+            self.config.first_event_id + event_number
+        };
+
+        let first_event_id = boundary(worker_index);
+        NexmarkGenerator::new(
+            self.config
+                .with_event_id_range(first_event_id, boundary(worker_index + 1)),
+            self.range_rng(first_event_id),
+            self.wallclock_base_time,
+        )
+    }
+
+    /// Returns a random number generator for the range of events starting at
+    /// `first_event_id`, derived from (a copy of) this generator's.
+    fn range_rng(&self, first_event_id: u64) -> R {
+        let seed = self.rng.clone().next_u64();
+        R::seed_from_u64(generator_seed(seed, first_event_id))
+    }
+}
+
+impl<R: Rng + Clone> NexmarkGenerator<R> {
+    /// Return a checkpoint of the state of this generator, from which
+    /// [`Self::restore`] creates a generator that continues generating the
+    /// same events as this generator.
+    pub fn snapshot(&self) -> GeneratorCheckpoint<R> {
+        GeneratorCheckpoint {
+            config: self.config.clone(),
+            rng: self.rng.clone(),
+            bid_channels: self
+                .bid_channel_cache
+                .key_order()
+                .zip(self.bid_channel_cache.value_order())
+                .map(|(&channel_number, (channel, url))| {
+                    (channel_number, channel.to_string(), url.to_string())
+                })
+                .collect(),
+            events_count_so_far: self.events_count_so_far,
+            wallclock_base_time: self.wallclock_base_time,
+        }
+    }
+
+    /// Create a generator from a checkpoint returned by [`Self::snapshot`].
+    pub fn restore(checkpoint: GeneratorCheckpoint<R>) -> NexmarkGenerator<R> {
+        let mut generator = NexmarkGenerator::new(
+            checkpoint.config,
+            checkpoint.rng,
+            checkpoint.wallclock_base_time,
+        );
+        generator.events_count_so_far = checkpoint.events_count_so_far;
+
+        // Insert least recently used channels first to restore the LRU order.
+        for (channel_number, channel, url) in checkpoint.bid_channels.into_iter().rev() {
+            generator
+                .bid_channel_cache
+                .cache_set(channel_number, (channel.into(), url.into()));
+        }
+        generator
+    }
+}
+
+/// State of a [`NexmarkGenerator`], e.g., for resuming event generation
+/// after a process restart without regenerating all previous events.
+///
+/// The checkpoint is serializable if the random number generator `R` is,
+/// which is the case for generators created with
+/// [`NexmarkGenerator::from_config`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GeneratorCheckpoint<R> {
+    pub config: Config,
+    pub rng: R,
+
+    /// Contents of the bid channel cache, most recently used first.  Channel
+    /// names and URLs are only generated (consuming random numbers) the first
+    /// time a channel is used, so the cache must be restored for the restored
+    /// generator to generate the same events.
+    pub bid_channels: Vec<(u32, String, String)>,
+
+    pub events_count_so_far: u64,
+    pub wallclock_base_time: u64,
+}
+
 impl<R: Rng> NexmarkGenerator<R> {
     pub fn has_next(&self) -> bool {
         self.get_next_event_id() < self.config.max_events
     }
 
-    pub fn next_event(&mut self) -> Result<Option<NextEvent>> {
+    /// Generate the next event, or return `None` once `max_events` events
+    /// have been generated.
+    pub fn next_event(&mut self) -> Result<Option<NextEvent>, GeneratorError> {
         let new_event_id = self.get_next_event_id();
         if new_event_id >= self.config.max_events {
             return Ok(None);
@@ -74,6 +295,10 @@ impl<R: Rng> NexmarkGenerator<R> {
             self.config.nexmark_config.total_proportion() as u64,
         );
 
+        if total_proportion == 0 {
+            return Err(GeneratorError::NoEventTypes);
+        }
+
         let rem = new_event_id % total_proportion;
         let event = if rem < person_proportion {
             Event::Person(self.next_person(new_event_id, adjusted_event_timestamp))
@@ -83,6 +308,14 @@ impl<R: Rng> NexmarkGenerator<R> {
                 new_event_id,
                 adjusted_event_timestamp,
             )?)
+        } else if person_proportion == 0 {
+            return Err(GeneratorError::NoPeople {
+                event_id: new_event_id,
+            });
+        } else if auction_proportion == 0 {
+            return Err(GeneratorError::NoAuctions {
+                event_id: new_event_id,
+            });
         } else {
             Event::Bid(self.next_bid(new_event_id, adjusted_event_timestamp))
         };
@@ -101,6 +334,7 @@ impl<R: Rng> NexmarkGenerator<R> {
             config,
             rng,
             bid_channel_cache: SizedCache::with_size(CHANNELS_NUMBER as usize),
+            scratch: String::new(),
             events_count_so_far: 0,
             wallclock_base_time,
         }
@@ -119,7 +353,7 @@ impl<R: Rng> NexmarkGenerator<R> {
 
 /// The next event and its various timestamps. Ordered by increasing wallclock
 /// timestamp, then (arbitrary but stable) event hash order.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct NextEvent {
     /// When, in wallclock time, should this event be emitted?
     pub wallclock_timestamp: u64,
@@ -138,11 +372,15 @@ pub struct NextEvent {
 pub mod tests {
     use super::*;
     use crate::{
-        config::Config as NexmarkConfig,
+        config::{Config as NexmarkConfig, TimestampModel},
         model::{Auction, Bid, Person},
     };
-    use rand::{rngs::mock::StepRng, thread_rng};
+    use rand::{
+        rngs::{mock::StepRng, StdRng},
+        thread_rng,
+    };
     use rstest::rstest;
+    use std::mem::size_of;
 
     pub fn make_test_generator() -> NexmarkGenerator<StepRng> {
         NexmarkGenerator::new(
@@ -176,6 +414,7 @@ pub mod tests {
             auction: 1,
             bidder: 1,
             price: 99,
+            currency: None,
             channel: String::from("my-channel").into(),
             url: String::from("https://example.com").into(),
             date_time: 0,
@@ -219,6 +458,45 @@ pub mod tests {
         (0..num_events).map(|_| ng.next_event().unwrap()).collect()
     }
 
+    #[rstest]
+    #[case::no_event_types(0, 0, 0, GeneratorError::NoEventTypes)]
+    #[case::auction_without_people(0, 1, 1, GeneratorError::NoPeople { event_id: 0 })]
+    #[case::bid_without_people(0, 0, 1, GeneratorError::NoPeople { event_id: 0 })]
+    #[case::bid_without_auctions(1, 0, 1, GeneratorError::NoAuctions { event_id: 1 })]
+    fn test_invalid_proportions(
+        #[case] person_proportion: usize,
+        #[case] auction_proportion: usize,
+        #[case] bid_proportion: usize,
+        #[case] expected_error: GeneratorError,
+    ) {
+        let nexmark_config = NexmarkConfig {
+            person_proportion,
+            auction_proportion,
+            bid_proportion,
+            num_event_generators: 1,
+            ..NexmarkConfig::default()
+        };
+        assert_eq!(check_config(&nexmark_config), Err(expected_error.clone()));
+
+        let mut ng = NexmarkGenerator::new(
+            Config {
+                nexmark_config,
+                ..Config::default()
+            },
+            StepRng::new(0, 1),
+            0,
+        );
+
+        let error = loop {
+            match ng.next_event() {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("generator exhausted without an error"),
+                Err(error) => break error,
+            }
+        };
+        assert_eq!(error, expected_error);
+    }
+
     #[test]
     fn test_has_next() {
         let mut ng = make_test_generator();
@@ -328,6 +606,359 @@ pub mod tests {
         );
     }
 
+    fn generate_seeded_events(seed: u64, num_events: usize) -> Vec<u8> {
+        let config = Config {
+            nexmark_config: NexmarkConfig {
+                num_event_generators: 1,
+                seed: Some(seed),
+                ..NexmarkConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut ng = NexmarkGenerator::from_config(config, 0);
+
+        let events: Vec<Event> = (0..num_events)
+            .map(|_| ng.next_event().unwrap().unwrap().event)
+            .collect();
+        bincode::encode_to_vec(events, bincode::config::standard()).unwrap()
+    }
+
+    #[test]
+    fn test_seeded_generator_is_deterministic() {
+        assert_eq!(
+            generate_seeded_events(42, 1000),
+            generate_seeded_events(42, 1000)
+        );
+    }
+
+    #[test]
+    fn test_seeded_generators_diverge() {
+        assert_ne!(
+            generate_seeded_events(42, 1000),
+            generate_seeded_events(43, 1000)
+        );
+    }
+
+    #[test]
+    fn test_generator_seeds_are_distinct() {
+        let seeds: Vec<u64> = (0..4).map(|index| generator_seed(42, index)).collect();
+
+        for (i, seed) in seeds.iter().enumerate() {
+            assert!(!seeds[i + 1..].contains(seed));
+        }
+        assert_ne!(generator_seed(42, 0), generator_seed(43, 0));
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let config = Config {
+            nexmark_config: NexmarkConfig {
+                num_event_generators: 1,
+                seed: Some(42),
+                ..NexmarkConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut ng = NexmarkGenerator::from_config(config, 0);
+
+        for _ in 0..500 {
+            ng.next_event().unwrap().unwrap();
+        }
+        let checkpoint = serde_json::to_string(&ng.snapshot()).unwrap();
+        let expected_events: Vec<NextEvent> = (500..1000)
+            .map(|_| ng.next_event().unwrap().unwrap())
+            .collect();
+
+        let mut restored = NexmarkGenerator::restore(serde_json::from_str(&checkpoint).unwrap());
+        let restored_events: Vec<NextEvent> = (500..1000)
+            .map(|_| restored.next_event().unwrap().unwrap())
+            .collect();
+
+        assert_eq!(restored_events, expected_events);
+    }
+
+    // Bids should reference auctions that are still in flight, and auctions
+    // and bids should reference people that are active.  Some references to
+    // people are to 'leads', i.e., people that have not been created yet.
+    #[rstest]
+    #[case::small_windows(10, 100)]
+    #[case::default_windows(100, 1000)]
+    #[case::large_auction_window(1000, 200)]
+    fn test_referential_plausibility(
+        #[case] num_in_flight_auctions: usize,
+        #[case] num_active_people: usize,
+    ) {
+        let config = Config {
+            nexmark_config: NexmarkConfig {
+                num_event_generators: 1,
+                num_in_flight_auctions,
+                num_active_people,
+                seed: Some(1),
+                ..NexmarkConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut ng = NexmarkGenerator::from_config(config, 0);
+
+        let is_active = |person: u64, last_person: u64| {
+            person <= last_person && person + num_active_people as u64 > last_person
+        };
+
+        let (mut last_person, mut last_auction) = (0, 0);
+        let (mut num_bids, mut num_plausible_auctions, mut num_active_bidders) = (0, 0, 0);
+        let (mut num_auctions, mut num_active_sellers) = (0, 0);
+        for _ in 0..50_000 {
+            match ng.next_event().unwrap().unwrap().event {
+                Event::Person(person) => last_person = person.id,
+                Event::Auction(auction) => {
+                    num_auctions += 1;
+                    num_active_sellers += is_active(auction.seller, last_person) as usize;
+                    last_auction = auction.id;
+                }
+                Event::Bid(bid) => {
+                    num_bids += 1;
+                    num_plausible_auctions += (bid.auction <= last_auction
+                        && bid.auction + num_in_flight_auctions as u64 >= last_auction)
+                        as usize;
+                    num_active_bidders += is_active(bid.bidder, last_person) as usize;
+                }
+            }
+        }
+
+        for (name, count, total) in [
+            (
+                "bids on in-flight auctions",
+                num_plausible_auctions,
+                num_bids,
+            ),
+            ("bids by active people", num_active_bidders, num_bids),
+            (
+                "auctions by active people",
+                num_active_sellers,
+                num_auctions,
+            ),
+        ] {
+            let fraction = count as f64 / total as f64;
+            assert!(fraction > 0.95, "only {fraction} of {name}");
+        }
+    }
+
+    // Idealized size of an event in bytes, as used by the generators to size
+    // the `extra` field.
+    fn idealized_size(event: &Event) -> usize {
+        match event {
+            Event::Person(person) => {
+                size_of::<u64>()
+                    + person.name.len()
+                    + person.email_address.len()
+                    + person.credit_card.len()
+                    + person.city.len()
+                    + person.state.len()
+                    + person.extra.len()
+            }
+            Event::Auction(auction) => {
+                size_of::<u64>() * 3
+                    + size_of::<usize>() * 3
+                    + auction.item_name.len()
+                    + auction.description.len()
+                    + auction.extra.len()
+            }
+            Event::Bid(bid) => size_of::<u64>() * 3 + size_of::<usize>() + bid.extra.len(),
+        }
+    }
+
+    // The `extra` field pads events so that their average idealized size
+    // matches the configured average size for each event type.
+    #[rstest]
+    #[case::default_sizes(200, 500, 100)]
+    #[case::large_sizes(1000, 4000, 2000)]
+    fn test_average_event_sizes(
+        #[case] avg_person_byte_size: usize,
+        #[case] avg_auction_byte_size: usize,
+        #[case] avg_bid_byte_size: usize,
+    ) {
+        let config = Config {
+            nexmark_config: NexmarkConfig {
+                num_event_generators: 1,
+                avg_person_byte_size,
+                avg_auction_byte_size,
+                avg_bid_byte_size,
+                seed: Some(42),
+                ..NexmarkConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut ng = NexmarkGenerator::from_config(config, 0);
+
+        // (total size, count) for people, auctions and bids.
+        let mut sizes = [(0, 0); 3];
+        for _ in 0..10_000 {
+            let event = ng.next_event().unwrap().unwrap().event;
+            let index = match event {
+                Event::Person(_) => 0,
+                Event::Auction(_) => 1,
+                Event::Bid(_) => 2,
+            };
+            sizes[index].0 += idealized_size(&event);
+            sizes[index].1 += 1;
+        }
+
+        let expected_sizes = [
+            avg_person_byte_size,
+            avg_auction_byte_size,
+            avg_bid_byte_size,
+        ];
+        for ((total_size, count), expected_size) in sizes.into_iter().zip(expected_sizes) {
+            let average_size = total_size as f64 / count as f64;
+            assert!(
+                (average_size - expected_size as f64).abs() < expected_size as f64 * 0.05,
+                "average size {average_size} is not within 5% of {expected_size}"
+            );
+        }
+    }
+
+    // The parts of an event that don't depend on random numbers: its
+    // timestamps and the id of the person or auction it creates.
+    fn event_metadata(next_event: &NextEvent) -> (u64, u64, u64, Option<u64>) {
+        let id = match &next_event.event {
+            Event::Person(person) => Some(person.id),
+            Event::Auction(auction) => Some(auction.id),
+            Event::Bid(_) => None,
+        };
+        (
+            next_event.wallclock_timestamp,
+            next_event.event_timestamp,
+            next_event.watermark,
+            id,
+        )
+    }
+
+    // Splitting a generator partitions its events.  Each range gets its own
+    // random number generator, so only the ids and timestamps of events are
+    // preserved.
+    #[rstest]
+    #[case::in_order(1)]
+    #[case::out_of_order(7)]
+    fn test_for_worker(#[case] out_of_order_group_size: usize) {
+        let make_generator = || {
+            NexmarkGenerator::new(
+                Config::new(
+                    NexmarkConfig {
+                        num_event_generators: 1,
+                        max_events: 10_000,
+                        out_of_order_group_size,
+                        ..NexmarkConfig::default()
+                    },
+                    0,
+                    0,
+                    0,
+                ),
+                StdRng::seed_from_u64(1),
+                0,
+            )
+        };
+
+        let mut ng = make_generator();
+        let mut expected_events = Vec::new();
+        while let Some(event) = ng.next_event().unwrap() {
+            expected_events.push(event_metadata(&event));
+        }
+        assert_eq!(expected_events.len(), 10_000);
+
+        let ng = make_generator();
+        let mut worker_events = Vec::new();
+        for worker_index in 0..4 {
+            let mut worker_generator = ng.for_worker(worker_index, 4);
+            let mut num_events = 0;
+            while let Some(event) = worker_generator.next_event().unwrap() {
+                worker_events.push(event_metadata(&event));
+                num_events += 1;
+            }
+            // Workers get roughly equal shares of events.
+            assert!((2_000..=3_000).contains(&num_events));
+        }
+
+        assert_eq!(worker_events, expected_events);
+    }
+
+    // The ranges of a split generator get different random contents, while
+    // the concatenation of the ranges only depends on the seed.
+    #[test]
+    fn test_for_worker_reseeds_ranges() {
+        let worker_events = |seed: u64| -> Vec<Vec<Event>> {
+            let ng = NexmarkGenerator::new(
+                Config::new(
+                    NexmarkConfig {
+                        num_event_generators: 1,
+                        max_events: 1_000,
+                        ..NexmarkConfig::default()
+                    },
+                    0,
+                    0,
+                    0,
+                ),
+                StdRng::seed_from_u64(seed),
+                0,
+            );
+
+            (0..4)
+                .map(|worker_index| {
+                    let mut worker_generator = ng.for_worker(worker_index, 4);
+                    std::iter::from_fn(|| worker_generator.next_event().unwrap())
+                        .map(|next_event| next_event.event)
+                        .collect()
+                })
+                .collect()
+        };
+
+        let events = worker_events(42);
+        assert_eq!(events.concat(), worker_events(42).concat());
+        assert_ne!(events.concat(), worker_events(43).concat());
+
+        // Each range starts with a person, whose credit card number would be
+        // the same in every range if the ranges shared their random number
+        // generator.
+        let credit_cards: Vec<ArcStr> = events
+            .iter()
+            .map(|events| match &events[0] {
+                Event::Person(person) => person.credit_card.clone(),
+                event => panic!("expected a person, got {event:?}"),
+            })
+            .collect();
+        for (i, credit_card) in credit_cards.iter().enumerate() {
+            assert!(!credit_cards[i + 1..].contains(credit_card));
+        }
+    }
+
+    #[test]
+    fn test_split_at_event_id() {
+        let mut ng = NexmarkGenerator::new(
+            Config {
+                nexmark_config: NexmarkConfig {
+                    num_event_generators: 1,
+                    ..NexmarkConfig::default()
+                },
+                ..Config::default()
+            },
+            StdRng::seed_from_u64(1),
+            0,
+        );
+        ng.config.max_events = 100;
+        let mut remaining = ng.split_at_event_id(40);
+
+        let event_ids: Vec<u64> = std::iter::from_fn(|| {
+            let event_id = ng.get_next_event_id();
+            ng.next_event().unwrap().map(|_| event_id)
+        })
+        .chain(std::iter::from_fn(|| {
+            let event_id = remaining.get_next_event_id();
+            remaining.next_event().unwrap().map(|_| event_id)
+        }))
+        .collect();
+
+        assert_eq!(event_ids, (0..100).collect::<Vec<_>>());
+    }
+
     // Verifies that the `generate_expected_next_events()` test helper does
     // indeed output predictable results matching the order verified manually in
     // the above `test_next_events` (at least for the first 5 events).  Together
@@ -347,4 +978,49 @@ pub mod tests {
             expected_events
         );
     }
+
+    #[rstest]
+    #[case::uniform(TimestampModel::Uniform)]
+    #[case::bursty(TimestampModel::Bursty { burst_len: 100, gap_len: 50, ratio: 10.0 })]
+    #[case::replay(TimestampModel::Replay { timestamps: vec![0, 0, 3, 10, 10, 11, 40] })]
+    fn test_watermarks_are_lower_bounds(#[case] timestamp_model: TimestampModel) {
+        let mut ng = NexmarkGenerator::new(
+            Config::new(
+                NexmarkConfig {
+                    num_event_generators: 1,
+                    first_event_rate: 10_000,
+                    out_of_order_group_size: 10,
+                    timestamp_model,
+                    ..NexmarkConfig::default()
+                },
+                0,
+                0,
+                0,
+            ),
+            StepRng::new(0, 1),
+            0,
+        );
+
+        let events: Vec<NextEvent> = (0..10_000)
+            .map(|_| ng.next_event().unwrap().unwrap())
+            .collect();
+
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].watermark <= pair[1].watermark
+                && pair[0].event_timestamp <= pair[1].event_timestamp));
+
+        // The watermark of each event is a lower bound on the (out-of-order)
+        // timestamps of all later events.
+        let mut min_later_timestamp = u64::MAX;
+        for next_event in events.iter().rev() {
+            let date_time = match &next_event.event {
+                Event::Person(person) => person.date_time,
+                Event::Auction(auction) => auction.date_time,
+                Event::Bid(bid) => bid.date_time,
+            };
+            min_later_timestamp = min_later_timestamp.min(date_time);
+            assert!(next_event.watermark <= min_later_timestamp);
+        }
+    }
 }
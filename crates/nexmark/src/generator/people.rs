@@ -4,12 +4,16 @@
 
 use super::{
     super::{config as nexmark_config, model::Person},
-    config, NexmarkGenerator,
+    config,
+    strings::append_string,
+    zipf::Zipf,
+    NexmarkGenerator,
 };
 use arcstr::ArcStr;
 use rand::{seq::SliceRandom, Rng};
 use std::{
     cmp::min,
+    fmt::Write,
     mem::{size_of, size_of_val},
 };
 
@@ -102,6 +106,19 @@ impl<R: Rng> NexmarkGenerator<R> {
         num_people - active_people + n
     }
 
+    /// Return a random person id (base 0) from the 'active' people, drawn
+    /// from a Zipf distribution with the specified exponent.  The most
+    /// recent person is the most popular one.
+    pub fn next_skewed_base0_person_id(&mut self, event_id: u64, skew_exponent: f64) -> u64 {
+        let num_people = self.last_base0_person_id(event_id) + 1;
+        let active_people = min(
+            num_people,
+            self.config.nexmark_config.num_active_people as u64,
+        );
+        let rank = Zipf::new(active_people, skew_exponent).sample(&mut self.rng);
+        num_people - rank
+    }
+
     /// Return the last valid person id (ignoring FIRST_PERSON_ID). Will be the
     /// current person id if due to generate a person.
     pub fn last_base0_person_id(&self, event_id: u64) -> u64 {
@@ -129,29 +146,36 @@ impl<R: Rng> NexmarkGenerator<R> {
 
     // Return a random person name.
     fn next_person_name(&mut self) -> ArcStr {
-        format!(
-            "{} {}",
-            FIRST_NAMES.choose(&mut self.rng).unwrap(),
-            LAST_NAMES.choose(&mut self.rng).unwrap()
-        )
-        .into()
+        self.scratch.clear();
+        self.scratch
+            .push_str(FIRST_NAMES.choose(&mut self.rng).unwrap());
+        self.scratch.push(' ');
+        self.scratch
+            .push_str(LAST_NAMES.choose(&mut self.rng).unwrap());
+        self.take_scratch()
     }
 
     // Return a random email address.
     fn next_email(&mut self) -> ArcStr {
-        format!("{}@{}.com", self.next_string(7), self.next_string(5)).into()
+        self.scratch.clear();
+        append_string(&mut self.rng, &mut self.scratch, 7);
+        self.scratch.push('@');
+        append_string(&mut self.rng, &mut self.scratch, 5);
+        self.scratch.push_str(".com");
+        self.take_scratch()
     }
 
     // Return a random credit card number.
     fn next_credit_card(&mut self) -> ArcStr {
-        format!(
-            "{:04} {:04} {:04} {:04}",
-            &mut self.rng.gen_range(0..10_000),
-            &mut self.rng.gen_range(0..10_000),
-            &mut self.rng.gen_range(0..10_000),
-            &mut self.rng.gen_range(0..10_000)
-        )
-        .into()
+        self.scratch.clear();
+        for i in 0..4 {
+            if i > 0 {
+                self.scratch.push(' ');
+            }
+            // Writing to a `String` cannot fail.
+            write!(self.scratch, "{:04}", self.rng.gen_range(0..10_000)).unwrap();
+        }
+        self.take_scratch()
     }
 }
 
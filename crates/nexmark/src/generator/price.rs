@@ -3,17 +3,94 @@
 //! API based on the equivalent [Nexmark Flink PriceGenerator API](https://github.com/nexmark/nexmark/blob/v0.2.0/nexmark-flink/src/main/java/com/github/nexmark/flink/generator/model/PriceGenerator.java).
 
 use super::NexmarkGenerator;
+use arcstr::ArcStr;
 use rand::Rng;
 
 impl<R: Rng> NexmarkGenerator<R> {
+    /// Return a random price in cents, drawn from the configured price
+    /// histogram, or log-uniformly between the configured bounds if there is
+    /// no histogram.
     pub fn next_price(&mut self) -> usize {
-        (10.0_f32.powf(self.rng.gen_range(0.0..1.0) * 6.0) * 100.0).ceil() as usize
+        let config = &self.config.nexmark_config;
+        if !config.price_histogram.is_empty() {
+            return self.next_histogram_price();
+        }
+
+        // Computed in single precision like the Java implementation, which
+        // generates the same prices as the default bounds of 100 and 10^8.
+        let decades = (config.max_price as f64 / config.min_price as f64).log10() as f32;
+        (10.0_f32.powf(self.rng.gen_range(0.0..1.0) * decades) * config.min_price as f32).ceil()
+            as usize
+    }
+
+    fn next_histogram_price(&mut self) -> usize {
+        let histogram = &self.config.nexmark_config.price_histogram;
+        let total_weight: f64 = histogram.iter().map(|bucket| bucket.weight).sum();
+        let mut target = self.rng.gen_range(0.0..total_weight);
+
+        // Fall back to the last non-empty bucket, in case rounding errors make
+        // `target` exceed the sum of all weights.
+        let mut lower_bound = self.config.nexmark_config.min_price;
+        let mut bounds = (lower_bound, lower_bound + 1);
+        for bucket in histogram {
+            if bucket.weight > 0.0 {
+                bounds = (lower_bound, bucket.upper_bound);
+                if target < bucket.weight {
+                    break;
+                }
+            }
+            target -= bucket.weight;
+            lower_bound = bucket.upper_bound;
+        }
+
+        self.rng.gen_range(bounds.0..bounds.1)
+    }
+
+    /// Return a random bid price and its currency.
+    ///
+    /// Prices are converted from dollars to a currency picked from the
+    /// configured currency rates.  If there are no rates, bids have no
+    /// currency and prices are in dollars.
+    pub fn next_bid_price(&mut self) -> (usize, Option<ArcStr>) {
+        let price = self.next_price();
+
+        let currency_rates = &self.config.nexmark_config.currency_rates;
+        if currency_rates.is_empty() {
+            return (price, None);
+        }
+        let currency_rate = &currency_rates[self.rng.gen_range(0..currency_rates.len())];
+        (
+            currency_rate.convert(price),
+            Some(currency_rate.currency.clone()),
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::tests::make_test_generator;
+    use super::{
+        super::{config::Config, tests::make_test_generator},
+        *,
+    };
+    use crate::config::{Config as NexmarkConfig, CurrencyRate, PriceBucket};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    const NUM_PRICES: usize = 100_000;
+
+    fn make_generator(nexmark_config: NexmarkConfig) -> NexmarkGenerator<SmallRng> {
+        NexmarkGenerator::new(
+            Config {
+                nexmark_config,
+                ..Config::default()
+            },
+            SmallRng::seed_from_u64(1),
+            0,
+        )
+    }
+
+    fn fraction(prices: &[usize], predicate: impl Fn(usize) -> bool) -> f64 {
+        prices.iter().filter(|&&price| predicate(price)).count() as f64 / prices.len() as f64
+    }
 
     #[test]
     fn test_next_price() {
@@ -23,4 +100,134 @@ mod tests {
 
         assert_eq!(p, 10_usize.pow(0) * 100);
     }
+
+    #[test]
+    fn test_log_uniform_price_distribution() {
+        let mut ng = make_generator(NexmarkConfig {
+            min_price: 1_000,
+            max_price: 10_000_000,
+            ..NexmarkConfig::default()
+        });
+
+        let prices: Vec<usize> = (0..NUM_PRICES).map(|_| ng.next_price()).collect();
+
+        assert!(prices
+            .iter()
+            .all(|&price| (1_000..=10_000_000).contains(&price)));
+        // Each of the four decades between the bounds gets a quarter of the
+        // prices.
+        for decade in 3..7 {
+            let lower_bound = 10_usize.pow(decade);
+            let actual = fraction(&prices, |price| {
+                price > lower_bound && price <= lower_bound * 10
+            });
+            assert!(
+                (actual - 0.25).abs() < 0.01,
+                "fraction of prices in ({lower_bound}, {}]: {actual}",
+                lower_bound * 10
+            );
+        }
+    }
+
+    #[test]
+    fn test_histogram_price_distribution() {
+        let mut ng = make_generator(NexmarkConfig {
+            min_price: 100,
+            price_histogram: vec![
+                PriceBucket {
+                    upper_bound: 1_000,
+                    weight: 0.6,
+                },
+                PriceBucket {
+                    upper_bound: 2_000,
+                    weight: 0.0,
+                },
+                PriceBucket {
+                    upper_bound: 10_000,
+                    weight: 0.4,
+                },
+            ],
+            ..NexmarkConfig::default()
+        });
+
+        let prices: Vec<usize> = (0..NUM_PRICES).map(|_| ng.next_price()).collect();
+
+        assert!(prices.iter().all(|&price| (100..10_000).contains(&price)));
+        assert_eq!(
+            fraction(&prices, |price| (1_000..2_000).contains(&price)),
+            0.0
+        );
+        let first_bucket = fraction(&prices, |price| price < 1_000);
+        assert!((first_bucket - 0.6).abs() < 0.01, "{first_bucket}");
+        // Prices are uniform within a bucket.
+        let first_half = fraction(&prices, |price| (2_000..6_000).contains(&price));
+        assert!((first_half - 0.2).abs() < 0.01, "{first_half}");
+    }
+
+    #[test]
+    fn test_prices_are_deterministic() {
+        let nexmark_config = NexmarkConfig {
+            price_histogram: vec![
+                PriceBucket {
+                    upper_bound: 1_000,
+                    weight: 1.0,
+                },
+                PriceBucket {
+                    upper_bound: 1_000_000,
+                    weight: 1.0,
+                },
+            ],
+            currency_rates: vec![CurrencyRate {
+                currency: "EUR".into(),
+                rate: 0.908,
+            }],
+            ..NexmarkConfig::default()
+        };
+        let mut ng1 = make_generator(nexmark_config.clone());
+        let mut ng2 = make_generator(nexmark_config);
+
+        for _ in 0..1000 {
+            assert_eq!(ng1.next_bid_price(), ng2.next_bid_price());
+        }
+    }
+
+    #[test]
+    fn test_bid_currencies() {
+        let currency_rates = vec![
+            CurrencyRate {
+                currency: "EUR".into(),
+                rate: 0.5,
+            },
+            CurrencyRate {
+                currency: "JPY".into(),
+                rate: 100.0,
+            },
+        ];
+        let mut ng = make_generator(NexmarkConfig {
+            min_price: 100,
+            max_price: 200,
+            currency_rates,
+            ..NexmarkConfig::default()
+        });
+
+        let bid_prices: Vec<(usize, Option<ArcStr>)> =
+            (0..NUM_PRICES).map(|_| ng.next_bid_price()).collect();
+
+        let mut num_euro_prices = 0;
+        for (price, currency) in &bid_prices {
+            match currency.as_deref() {
+                Some("EUR") => {
+                    num_euro_prices += 1;
+                    assert!((50..=100).contains(price), "{price}");
+                }
+                Some("JPY") => assert!((10_000..=20_000).contains(price), "{price}"),
+                currency => panic!("unexpected currency {currency:?}"),
+            }
+        }
+        let euro_fraction = num_euro_prices as f64 / NUM_PRICES as f64;
+        assert!((euro_fraction - 0.5).abs() < 0.01, "{euro_fraction}");
+
+        // Without currency rates, bids are in dollars.
+        assert_eq!(make_test_generator().next_bid_price(), (100, None));
+    }
 }
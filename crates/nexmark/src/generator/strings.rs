@@ -10,26 +10,38 @@ const MIN_STRING_LENGTH: usize = 3;
 
 /// Return a random string of up to `max_length`.
 pub(super) fn next_string<R: Rng>(rng: &mut R, max_length: usize) -> ArcStr {
+    let mut string = String::new();
+    append_string(rng, &mut string, max_length);
+    ArcStr::from(string)
+}
+
+/// Append a random string of up to `max_length` to `string`.
+pub(super) fn append_string<R: Rng>(rng: &mut R, string: &mut String, max_length: usize) {
     let len = rng.gen_range(MIN_STRING_LENGTH..=max_length);
-    ArcStr::from(Alphanumeric.sample_string(rng, len))
+    Alphanumeric.append_string(rng, string, len);
 }
 
-/// Return a random string such that the current_size + string length is on
-/// average the desired average size.
-fn next_extra<R: Rng>(rng: &mut R, current_size: usize, desired_average_size: usize) -> ArcStr {
+/// Append a random string to `string` such that the current_size + string
+/// length is on average the desired average size.
+fn append_extra<R: Rng>(
+    rng: &mut R,
+    string: &mut String,
+    current_size: usize,
+    desired_average_size: usize,
+) {
     if current_size > desired_average_size {
-        return arcstr::literal!("");
+        return;
     }
 
     let avg_extra_size = desired_average_size - current_size;
     let delta = (avg_extra_size as f32 * 0.2).round() as usize;
     if delta == 0 {
-        return arcstr::literal!("");
+        return;
     }
 
     let desired_size =
         rng.gen_range((avg_extra_size.saturating_sub(delta))..=(avg_extra_size + delta));
-    ArcStr::from(Alphanumeric.sample_string(rng, desired_size))
+    Alphanumeric.append_string(rng, string, desired_size);
 }
 
 impl<R: Rng> NexmarkGenerator<R> {
@@ -40,13 +52,34 @@ impl<R: Rng> NexmarkGenerator<R> {
     /// If both are necessary, we can update to a less optimized version, but
     /// otherwise it's simpler to use the Alphanumeric distribution.
     pub fn next_string(&mut self, max_length: usize) -> ArcStr {
-        next_string(&mut self.rng, max_length)
+        self.scratch.clear();
+        append_string(&mut self.rng, &mut self.scratch, max_length);
+        self.take_scratch()
     }
 
     /// Return a random string such that the current_size + string length is on
     /// average the desired average size.
     pub fn next_extra(&mut self, current_size: usize, desired_average_size: usize) -> ArcStr {
-        next_extra(&mut self.rng, current_size, desired_average_size)
+        self.scratch.clear();
+        append_extra(
+            &mut self.rng,
+            &mut self.scratch,
+            current_size,
+            desired_average_size,
+        );
+        self.take_scratch()
+    }
+
+    /// Return a copy of the string built in the scratch buffer.
+    ///
+    /// Strings are built in the scratch buffer, which is reused across events,
+    /// so that only the final copy into an `ArcStr` allocates.
+    pub(super) fn take_scratch(&self) -> ArcStr {
+        if self.scratch.is_empty() {
+            arcstr::literal!("")
+        } else {
+            ArcStr::from(self.scratch.as_str())
+        }
     }
 }
 
@@ -0,0 +1,140 @@
+//! Sampling from a Zipf distribution, used to generate skewed keys.
+//!
+//! Uses the rejection-inversion method described in "Rejection-Inversion to
+//! Generate Variates from Monotone Discrete Distributions" by Hörmann and
+//! Derflinger, which samples in constant time without precomputing
+//! probabilities for all `n` elements.  This makes it cheap to create a new
+//! distribution whenever the number of elements changes.
+
+use rand::Rng;
+
+/// Zipf distribution over the ranks `1..=n`, where the probability of rank
+/// `k` is proportional to `1 / k^exponent`.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Zipf {
+    n: u64,
+    exponent: f64,
+    h_integral_x1: f64,
+    h_integral_n: f64,
+    s: f64,
+}
+
+impl Zipf {
+    pub(super) fn new(n: u64, exponent: f64) -> Self {
+        assert!(n > 0, "Zipf distribution needs at least one element");
+        assert!(
+            exponent.is_finite() && exponent > 0.0,
+            "Zipf exponent must be a positive number, got {exponent}"
+        );
+
+        let mut zipf = Self {
+            n,
+            exponent,
+            h_integral_x1: 0.0,
+            h_integral_n: 0.0,
+            s: 0.0,
+        };
+        zipf.h_integral_x1 = zipf.h_integral(1.5) - 1.0;
+        zipf.h_integral_n = zipf.h_integral(n as f64 + 0.5);
+        zipf.s = 2.0 - zipf.h_integral_inverse(zipf.h_integral(2.5) - zipf.h(2.0));
+        zipf
+    }
+
+    /// Return a random rank in `1..=n`.
+    pub(super) fn sample<R: Rng>(&self, rng: &mut R) -> u64 {
+        loop {
+            let u = self.h_integral_n + rng.gen::<f64>() * (self.h_integral_x1 - self.h_integral_n);
+            let x = self.h_integral_inverse(u);
+            let k = (x + 0.5).clamp(1.0, self.n as f64).floor();
+
+            if k - x <= self.s || u >= self.h_integral(k + 0.5) - self.h(k) {
+                return k as u64;
+            }
+        }
+    }
+
+    // `h(x) = 1 / x^exponent`
+    fn h(&self, x: f64) -> f64 {
+        (-self.exponent * x.ln()).exp()
+    }
+
+    // Integral of `h`, up to a constant.
+    fn h_integral(&self, x: f64) -> f64 {
+        let log_x = x.ln();
+        helper2((1.0 - self.exponent) * log_x) * log_x
+    }
+
+    fn h_integral_inverse(&self, x: f64) -> f64 {
+        let t = (x * (1.0 - self.exponent)).max(-1.0);
+        (helper1(t) * x).exp()
+    }
+}
+
+// `ln(1 + x) / x`, accurate for `x` close to 0.
+fn helper1(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.ln_1p() / x
+    } else {
+        1.0 - x * (0.5 - x * (1.0 / 3.0 - 0.25 * x))
+    }
+}
+
+// `(exp(x) - 1) / x`, accurate for `x` close to 0.
+fn helper2(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.exp_m1() / x
+    } else {
+        1.0 + x * 0.5 * (1.0 + x * (1.0 / 3.0) * (1.0 + 0.25 * x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+    use rstest::rstest;
+
+    const NUM_SAMPLES: usize = 100_000;
+
+    #[rstest]
+    #[case::uniform_like(10, 0.01)]
+    #[case::sqrt(100, 0.5)]
+    #[case::classic(100, 1.0)]
+    #[case::steep(1000, 2.0)]
+    fn test_zipf_frequencies(#[case] n: u64, #[case] exponent: f64) {
+        let zipf = Zipf::new(n, exponent);
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        let mut counts = vec![0; n as usize];
+        for _ in 0..NUM_SAMPLES {
+            let rank = zipf.sample(&mut rng);
+            assert!((1..=n).contains(&rank));
+            counts[rank as usize - 1] += 1;
+        }
+
+        let normalization: f64 = (1..=n).map(|k| 1.0 / (k as f64).powf(exponent)).sum();
+        for (rank, &count) in counts.iter().enumerate().take(3) {
+            let expected = 1.0 / ((rank + 1) as f64).powf(exponent) / normalization;
+            let actual = count as f64 / NUM_SAMPLES as f64;
+            assert!(
+                (actual - expected).abs() < 0.01,
+                "rank {}: frequency {actual}, expected {expected}",
+                rank + 1
+            );
+        }
+    }
+
+    #[test]
+    fn test_zipf_single_element() {
+        let zipf = Zipf::new(1, 1.0);
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        assert!((0..100).all(|_| zipf.sample(&mut rng) == 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Zipf exponent must be a positive number")]
+    fn test_invalid_exponent() {
+        Zipf::new(10, 0.0);
+    }
+}
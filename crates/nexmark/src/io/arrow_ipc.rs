@@ -0,0 +1,359 @@
+//! Apache Arrow IPC encoding of event files.
+//!
+//! Events are stored in a single flat table with one row per event.  Besides
+//! the timestamps of the [`NextEvent`], each row has an `event_type` column
+//! and one nullable column for every field of [`Person`], [`Auction`] and
+//! [`Bid`], prefixed by the name of the type.  Only the columns of the type of
+//! the event are set, except for optional fields such as `bid_currency`, which
+//! are null when unset.
+
+use crate::{
+    generator::NextEvent,
+    model::{Auction, Bid, Event, Person},
+};
+use anyhow::{anyhow, bail, Result};
+use arcstr::ArcStr;
+use arrow::{
+    array::{Array, ArrayRef, StringArray, UInt64Array, UInt8Array},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    ipc::{reader::FileReader, writer::FileWriter},
+    record_batch::RecordBatch,
+};
+use std::{fs::File, sync::Arc};
+
+/// Number of events per record batch.
+const BATCH_SIZE: usize = 8192;
+
+const PERSON: u8 = 0;
+const AUCTION: u8 = 1;
+const BID: u8 = 2;
+
+fn schema() -> SchemaRef {
+    let u64_field = |name: &str, nullable| Field::new(name, DataType::UInt64, nullable);
+    let string_field = |name: &str| Field::new(name, DataType::Utf8, true);
+
+    Arc::new(Schema::new(vec![
+        u64_field("wallclock_timestamp", false),
+        u64_field("event_timestamp", false),
+        u64_field("watermark", false),
+        Field::new("event_type", DataType::UInt8, false),
+        u64_field("person_id", true),
+        string_field("person_name"),
+        string_field("person_email_address"),
+        string_field("person_credit_card"),
+        string_field("person_city"),
+        string_field("person_state"),
+        u64_field("person_date_time", true),
+        string_field("person_extra"),
+        u64_field("auction_id", true),
+        string_field("auction_item_name"),
+        string_field("auction_description"),
+        u64_field("auction_initial_bid", true),
+        u64_field("auction_reserve", true),
+        u64_field("auction_date_time", true),
+        u64_field("auction_expires", true),
+        u64_field("auction_seller", true),
+        u64_field("auction_category", true),
+        string_field("auction_extra"),
+        u64_field("bid_auction", true),
+        u64_field("bid_bidder", true),
+        u64_field("bid_price", true),
+        string_field("bid_currency"),
+        string_field("bid_channel"),
+        string_field("bid_url"),
+        u64_field("bid_date_time", true),
+        string_field("bid_extra"),
+    ]))
+}
+
+/// Columns of a record batch under construction, in schema order.
+#[derive(Default)]
+struct Columns {
+    wallclock_timestamp: Vec<u64>,
+    event_timestamp: Vec<u64>,
+    watermark: Vec<u64>,
+    event_type: Vec<u8>,
+    person_id: Vec<Option<u64>>,
+    person_name: Vec<Option<String>>,
+    person_email_address: Vec<Option<String>>,
+    person_credit_card: Vec<Option<String>>,
+    person_city: Vec<Option<String>>,
+    person_state: Vec<Option<String>>,
+    person_date_time: Vec<Option<u64>>,
+    person_extra: Vec<Option<String>>,
+    auction_id: Vec<Option<u64>>,
+    auction_item_name: Vec<Option<String>>,
+    auction_description: Vec<Option<String>>,
+    auction_initial_bid: Vec<Option<u64>>,
+    auction_reserve: Vec<Option<u64>>,
+    auction_date_time: Vec<Option<u64>>,
+    auction_expires: Vec<Option<u64>>,
+    auction_seller: Vec<Option<u64>>,
+    auction_category: Vec<Option<u64>>,
+    auction_extra: Vec<Option<String>>,
+    bid_auction: Vec<Option<u64>>,
+    bid_bidder: Vec<Option<u64>>,
+    bid_price: Vec<Option<u64>>,
+    bid_currency: Vec<Option<String>>,
+    bid_channel: Vec<Option<String>>,
+    bid_url: Vec<Option<String>>,
+    bid_date_time: Vec<Option<u64>>,
+    bid_extra: Vec<Option<String>>,
+}
+
+impl Columns {
+    fn len(&self) -> usize {
+        self.event_type.len()
+    }
+
+    fn push(&mut self, next_event: NextEvent) {
+        self.wallclock_timestamp
+            .push(next_event.wallclock_timestamp);
+        self.event_timestamp.push(next_event.event_timestamp);
+        self.watermark.push(next_event.watermark);
+
+        let person = match &next_event.event {
+            Event::Person(person) => Some(person),
+            _ => None,
+        };
+        self.person_id.push(person.map(|p| p.id));
+        self.person_name.push(person.map(|p| p.name.to_string()));
+        self.person_email_address
+            .push(person.map(|p| p.email_address.to_string()));
+        self.person_credit_card
+            .push(person.map(|p| p.credit_card.to_string()));
+        self.person_city.push(person.map(|p| p.city.to_string()));
+        self.person_state.push(person.map(|p| p.state.to_string()));
+        self.person_date_time.push(person.map(|p| p.date_time));
+        self.person_extra.push(person.map(|p| p.extra.to_string()));
+
+        let auction = match &next_event.event {
+            Event::Auction(auction) => Some(auction),
+            _ => None,
+        };
+        self.auction_id.push(auction.map(|a| a.id));
+        self.auction_item_name
+            .push(auction.map(|a| a.item_name.to_string()));
+        self.auction_description
+            .push(auction.map(|a| a.description.to_string()));
+        self.auction_initial_bid
+            .push(auction.map(|a| a.initial_bid as u64));
+        self.auction_reserve.push(auction.map(|a| a.reserve as u64));
+        self.auction_date_time.push(auction.map(|a| a.date_time));
+        self.auction_expires.push(auction.map(|a| a.expires));
+        self.auction_seller.push(auction.map(|a| a.seller));
+        self.auction_category
+            .push(auction.map(|a| a.category as u64));
+        self.auction_extra
+            .push(auction.map(|a| a.extra.to_string()));
+
+        let bid = match &next_event.event {
+            Event::Bid(bid) => Some(bid),
+            _ => None,
+        };
+        self.bid_auction.push(bid.map(|b| b.auction));
+        self.bid_bidder.push(bid.map(|b| b.bidder));
+        self.bid_price.push(bid.map(|b| b.price as u64));
+        self.bid_currency
+            .push(bid.and_then(|b| b.currency.as_ref().map(ArcStr::to_string)));
+        self.bid_channel.push(bid.map(|b| b.channel.to_string()));
+        self.bid_url.push(bid.map(|b| b.url.to_string()));
+        self.bid_date_time.push(bid.map(|b| b.date_time));
+        self.bid_extra.push(bid.map(|b| b.extra.to_string()));
+
+        self.event_type.push(match next_event.event {
+            Event::Person(_) => PERSON,
+            Event::Auction(_) => AUCTION,
+            Event::Bid(_) => BID,
+        });
+    }
+
+    fn into_record_batch(self, schema: SchemaRef) -> Result<RecordBatch> {
+        let u64s = |values: Vec<Option<u64>>| Arc::new(UInt64Array::from(values)) as ArrayRef;
+        let strings = |values: Vec<Option<String>>| Arc::new(StringArray::from(values)) as ArrayRef;
+
+        let columns = vec![
+            Arc::new(UInt64Array::from(self.wallclock_timestamp)) as ArrayRef,
+            Arc::new(UInt64Array::from(self.event_timestamp)),
+            Arc::new(UInt64Array::from(self.watermark)),
+            Arc::new(UInt8Array::from(self.event_type)),
+            u64s(self.person_id),
+            strings(self.person_name),
+            strings(self.person_email_address),
+            strings(self.person_credit_card),
+            strings(self.person_city),
+            strings(self.person_state),
+            u64s(self.person_date_time),
+            strings(self.person_extra),
+            u64s(self.auction_id),
+            strings(self.auction_item_name),
+            strings(self.auction_description),
+            u64s(self.auction_initial_bid),
+            u64s(self.auction_reserve),
+            u64s(self.auction_date_time),
+            u64s(self.auction_expires),
+            u64s(self.auction_seller),
+            u64s(self.auction_category),
+            strings(self.auction_extra),
+            u64s(self.bid_auction),
+            u64s(self.bid_bidder),
+            u64s(self.bid_price),
+            strings(self.bid_currency),
+            strings(self.bid_channel),
+            strings(self.bid_url),
+            u64s(self.bid_date_time),
+            strings(self.bid_extra),
+        ];
+
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
+
+pub(super) fn write_events<I>(file: File, events: I) -> Result<u64>
+where
+    I: IntoIterator<Item = NextEvent>,
+{
+    let schema = schema();
+    let mut writer = FileWriter::try_new(file, &schema)?;
+
+    let mut num_events = 0;
+    let mut columns = Columns::default();
+    for next_event in events {
+        columns.push(next_event);
+        num_events += 1;
+
+        if columns.len() == BATCH_SIZE {
+            writer.write(&std::mem::take(&mut columns).into_record_batch(schema.clone())?)?;
+        }
+    }
+    if columns.len() > 0 {
+        writer.write(&columns.into_record_batch(schema)?)?;
+    }
+    writer.finish()?;
+
+    Ok(num_events)
+}
+
+pub(super) fn read_events(file: File) -> Result<impl Iterator<Item = Result<NextEvent>> + Send> {
+    let reader = FileReader::try_new(file, None)?;
+    if reader.schema() != schema() {
+        bail!(
+            "unexpected schema of Nexmark event file: {:?}",
+            reader.schema()
+        );
+    }
+
+    Ok(reader.flat_map(|batch| match batch {
+        Ok(batch) => match decode_record_batch(&batch) {
+            Ok(events) => events.into_iter().map(Ok).collect(),
+            Err(error) => vec![Err(error)],
+        },
+        Err(error) => vec![Err(error.into())],
+    }))
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<T>())
+        .ok_or_else(|| anyhow!("missing or invalid column '{name}'"))
+}
+
+fn decode_record_batch(batch: &RecordBatch) -> Result<Vec<NextEvent>> {
+    let u64_column = |name: &str| column::<UInt64Array>(batch, name);
+    let string_column = |name: &str| column::<StringArray>(batch, name);
+
+    let wallclock_timestamp = u64_column("wallclock_timestamp")?;
+    let event_timestamp = u64_column("event_timestamp")?;
+    let watermark = u64_column("watermark")?;
+    let event_type = column::<UInt8Array>(batch, "event_type")?;
+
+    let person_id = u64_column("person_id")?;
+    let person_name = string_column("person_name")?;
+    let person_email_address = string_column("person_email_address")?;
+    let person_credit_card = string_column("person_credit_card")?;
+    let person_city = string_column("person_city")?;
+    let person_state = string_column("person_state")?;
+    let person_date_time = u64_column("person_date_time")?;
+    let person_extra = string_column("person_extra")?;
+
+    let auction_id = u64_column("auction_id")?;
+    let auction_item_name = string_column("auction_item_name")?;
+    let auction_description = string_column("auction_description")?;
+    let auction_initial_bid = u64_column("auction_initial_bid")?;
+    let auction_reserve = u64_column("auction_reserve")?;
+    let auction_date_time = u64_column("auction_date_time")?;
+    let auction_expires = u64_column("auction_expires")?;
+    let auction_seller = u64_column("auction_seller")?;
+    let auction_category = u64_column("auction_category")?;
+    let auction_extra = string_column("auction_extra")?;
+
+    let bid_auction = u64_column("bid_auction")?;
+    let bid_bidder = u64_column("bid_bidder")?;
+    let bid_price = u64_column("bid_price")?;
+    let bid_currency = string_column("bid_currency")?;
+    let bid_channel = string_column("bid_channel")?;
+    let bid_url = string_column("bid_url")?;
+    let bid_date_time = u64_column("bid_date_time")?;
+    let bid_extra = string_column("bid_extra")?;
+
+    (0..batch.num_rows())
+        .map(|row| {
+            let u64_at = |column: &UInt64Array| -> Result<u64> {
+                if column.is_null(row) {
+                    bail!("unexpected null value in row {row}");
+                }
+                Ok(column.value(row))
+            };
+            let string_at = |column: &StringArray| -> Result<ArcStr> {
+                if column.is_null(row) {
+                    bail!("unexpected null value in row {row}");
+                }
+                Ok(column.value(row).into())
+            };
+
+            let event = match event_type.value(row) {
+                PERSON => Event::Person(Person {
+                    id: u64_at(person_id)?,
+                    name: string_at(person_name)?,
+                    email_address: string_at(person_email_address)?,
+                    credit_card: string_at(person_credit_card)?,
+                    city: string_at(person_city)?,
+                    state: string_at(person_state)?,
+                    date_time: u64_at(person_date_time)?,
+                    extra: string_at(person_extra)?,
+                }),
+                AUCTION => Event::Auction(Auction {
+                    id: u64_at(auction_id)?,
+                    item_name: string_at(auction_item_name)?,
+                    description: string_at(auction_description)?,
+                    initial_bid: u64_at(auction_initial_bid)? as usize,
+                    reserve: u64_at(auction_reserve)? as usize,
+                    date_time: u64_at(auction_date_time)?,
+                    expires: u64_at(auction_expires)?,
+                    seller: u64_at(auction_seller)?,
+                    category: u64_at(auction_category)? as usize,
+                    extra: string_at(auction_extra)?,
+                }),
+                BID => Event::Bid(Bid {
+                    auction: u64_at(bid_auction)?,
+                    bidder: u64_at(bid_bidder)?,
+                    price: u64_at(bid_price)? as usize,
+                    currency: (!bid_currency.is_null(row)).then(|| bid_currency.value(row).into()),
+                    channel: string_at(bid_channel)?,
+                    url: string_at(bid_url)?,
+                    date_time: u64_at(bid_date_time)?,
+                    extra: string_at(bid_extra)?,
+                }),
+                event_type => bail!("invalid event type {event_type} in row {row}"),
+            };
+
+            Ok(NextEvent {
+                wallclock_timestamp: wallclock_timestamp.value(row),
+                event_timestamp: event_timestamp.value(row),
+                event,
+                watermark: watermark.value(row),
+            })
+        })
+        .collect()
+}
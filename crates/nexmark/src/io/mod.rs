@@ -0,0 +1,213 @@
+//! Reading and writing Nexmark event streams from and to files.
+//!
+//! Dumping generated events to a file makes it possible to feed exactly the
+//! same events to DBSP and to other systems, and to replay a previously
+//! generated stream instead of generating events on the fly.
+//!
+//! Files store [`NextEvent`]s, i.e., events together with their timestamps and
+//! watermarks.
+
+use crate::{generator::NextEvent, model::Event};
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+#[cfg(feature = "arrow")]
+mod arrow_ipc;
+
+/// Format of an event file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventFormat {
+    /// One JSON object per line.
+    JsonLines,
+
+    /// Apache Arrow IPC file format, with one row per event.
+    #[cfg(feature = "arrow")]
+    Arrow,
+}
+
+/// Write `events` to the file at `path`, replacing its contents.
+///
+/// Returns the number of events written.
+pub fn write_events<P, I>(path: P, format: EventFormat, events: I) -> Result<u64>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = NextEvent>,
+{
+    let path = path.as_ref();
+    let file =
+        File::create(path).with_context(|| format!("failed to create '{}'", path.display()))?;
+
+    match format {
+        EventFormat::JsonLines => {
+            let mut writer = BufWriter::new(file);
+            let mut num_events = 0;
+            for event in events {
+                serde_json::to_writer(&mut writer, &event)?;
+                writer.write_all(b"\n")?;
+                num_events += 1;
+            }
+            writer.flush()?;
+            Ok(num_events)
+        }
+        #[cfg(feature = "arrow")]
+        EventFormat::Arrow => arrow_ipc::write_events(file, events),
+    }
+}
+
+/// A source of events read from a file written by [`write_events`].
+///
+/// Like [`NexmarkSource`](crate::NexmarkSource), this is an iterator over
+/// [`Event`]s that tracks the watermark of the events it has delivered, so the
+/// two can be used interchangeably to feed circuits.  Events are delivered in
+/// file order, as fast as they are consumed.
+pub struct EventFileSource {
+    events: Box<dyn Iterator<Item = Result<NextEvent>> + Send>,
+    watermark: u64,
+}
+
+impl EventFileSource {
+    /// Open the event file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P, format: EventFormat) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open '{}'", path.display()))?;
+
+        let events: Box<dyn Iterator<Item = Result<NextEvent>> + Send> = match format {
+            EventFormat::JsonLines => Box::new(
+                serde_json::Deserializer::from_reader(BufReader::new(file))
+                    .into_iter::<NextEvent>()
+                    .map(|event| Ok(event?)),
+            ),
+            #[cfg(feature = "arrow")]
+            EventFormat::Arrow => Box::new(arrow_ipc::read_events(file)?),
+        };
+
+        Ok(Self {
+            events,
+            watermark: 0,
+        })
+    }
+
+    /// Read the next event from the file, including its timestamps.
+    pub fn next_event(&mut self) -> Result<Option<NextEvent>> {
+        let next_event = self.events.next().transpose()?;
+        if let Some(next_event) = &next_event {
+            self.watermark = next_event.watermark;
+        }
+        Ok(next_event)
+    }
+
+    /// Returns the watermark of the last event read from the file, a lower
+    /// bound on the timestamps of all events that are yet to be read.
+    pub fn watermark(&self) -> u64 {
+        self.watermark
+    }
+}
+
+impl Iterator for EventFileSource {
+    type Item = Event;
+
+    /// Returns the next event.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file cannot be read or is malformed.  Use
+    /// [`EventFileSource::next_event`] to handle such errors.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event()
+            .expect("failed to read Nexmark event file")
+            .map(|next_event| next_event.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config as NexmarkConfig,
+        generator::{config::Config as GeneratorConfig, NexmarkGenerator},
+    };
+    use std::fs;
+
+    fn generate_next_events(num_events: usize) -> Vec<NextEvent> {
+        let mut generator = NexmarkGenerator::from_config(
+            GeneratorConfig {
+                nexmark_config: NexmarkConfig {
+                    num_event_generators: 1,
+                    out_of_order_group_size: 10,
+                    seed: Some(1),
+                    currency_rates: vec!["EUR:0.908".parse().unwrap()],
+                    ..NexmarkConfig::default()
+                },
+                ..GeneratorConfig::default()
+            },
+            1_000_000,
+        );
+
+        (0..num_events)
+            .map(|_| generator.next_event().unwrap().unwrap())
+            .collect()
+    }
+
+    fn check_round_trip(format: EventFormat) {
+        let path = std::env::temp_dir().join(format!(
+            "nexmark-events-{}-{format:?}",
+            std::process::id()
+        ));
+        let next_events = generate_next_events(3000);
+
+        assert_eq!(
+            write_events(&path, format, next_events.iter().cloned()).unwrap(),
+            3000
+        );
+
+        let mut source = EventFileSource::new(&path, format).unwrap();
+        let mut read_events = Vec::new();
+        while let Some(next_event) = source.next_event().unwrap() {
+            assert_eq!(source.watermark(), next_event.watermark);
+            read_events.push(next_event);
+        }
+        assert_eq!(read_events, next_events);
+
+        // The source can be consumed like a `NexmarkSource`.
+        let events: Vec<Event> = EventFileSource::new(&path, format).unwrap().collect();
+        assert_eq!(
+            events,
+            next_events
+                .into_iter()
+                .map(|next_event| next_event.event)
+                .collect::<Vec<_>>()
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        check_round_trip(EventFormat::JsonLines);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_arrow_round_trip() {
+        check_round_trip(EventFormat::Arrow);
+    }
+
+    #[test]
+    fn test_malformed_file() {
+        let path = std::env::temp_dir().join(format!(
+            "nexmark-events-{}-malformed",
+            std::process::id()
+        ));
+        fs::write(&path, "{\"wallclock_timestamp\": 1}\n").unwrap();
+
+        let mut source = EventFileSource::new(&path, EventFormat::JsonLines).unwrap();
+        assert!(source.next_event().is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
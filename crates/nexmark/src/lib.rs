@@ -12,28 +12,39 @@
 
 use self::{
     config::Config as NexmarkConfig,
-    generator::{config::Config as GeneratorConfig, NexmarkGenerator, NextEvent},
+    generator::{
+        check_config, config::Config as GeneratorConfig, GeneratorError, NexmarkGenerator,
+        NextEvent,
+    },
     model::Event,
+    pacing::{CatchUpPolicy, Clock, Pacer, SystemClock},
 };
 use dbsp::{
     algebra::{ZRingValue, ZSet},
-    circuit::operator_traits::Data,
-    OrdZSet,
+    circuit::{operator_traits::Data, LocalStoreMarker},
+    operator::{communication::new_exchange_operators, Generator},
+    trace::Batch,
+    Circuit, OrdZSet, RootCircuit, Runtime, Stream,
 };
-use rand::{rngs::ThreadRng, Rng};
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
 use std::{
+    cell::Cell,
     collections::VecDeque,
     marker::PhantomData,
-    ops::Range,
+    panic::Location,
+    rc::Rc,
     sync::mpsc,
-    thread::{self, sleep},
-    time::Duration,
+    thread,
     time::SystemTime,
 };
+use typedmap::TypedMapKey;
 
+pub mod benchmark;
 pub mod config;
 pub mod generator;
+pub mod io;
 pub mod model;
+pub mod pacing;
 pub mod queries;
 
 /// BatchedReceiver abstracts the Receiver interface for channels of VecDeque's.
@@ -112,37 +123,75 @@ pub struct NexmarkSource<W, C> {
     // Channel on which the source receives vectors of next events.
     next_events_rx: BatchedReceiver<NextEvent>,
 
-    /// An optional iterator that provides wallclock timestamps in tests.
-    /// This is set to None by default.
-    wallclock_iterator: Option<Range<u64>>,
+    /// Delays events until their wallclock timestamps.
+    pacer: Pacer<Box<dyn Clock + Send>>,
+
+    /// Number of consecutive events whose delivery order is shuffled.
+    out_of_order_group_size: usize,
+
+    /// Random number generator used to shuffle delivery groups.
+    delivery_rng: SmallRng,
+
+    /// Undelivered events of the current delivery group, delivered from the
+    /// back, each paired with the minimum watermark of the events in front
+    /// of it (i.e., the watermark after delivering the event).
+    delivery_group: Vec<(NextEvent, u64)>,
+
+    /// Watermark of the last event received from the generators.
+    received_watermark: u64,
+
+    /// Lower bound on the timestamps of all events that have not been
+    /// delivered yet.
+    watermark: u64,
 
     _t: PhantomData<(C, W)>,
 }
 
+// Returns the current wallclock time in ms since the epoch.
+fn wallclock_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 // Creates and spawns the generators according to the nexmark config, returning
 // the receiver to listen on for next events.
-fn create_generators_for_config<R: Rng + Default>(
+fn create_generators_for_config(nexmark_config: NexmarkConfig) -> BatchedReceiver<NextEvent> {
+    create_generators(nexmark_config, wallclock_now())
+}
+
+/// Event time at which seeded generators start generating events
+/// (2020-01-01T00:00:00Z in ms since the epoch).
+const SEEDED_BASE_TIME: u64 = 1_577_836_800_000;
+
+// Returns the event time at which the generators start generating events:
+// a fixed time if `nexmark_config.seed` is set, so that seeded runs generate
+// identical events, or `wallclock_base_time` otherwise.
+fn event_base_time(nexmark_config: &NexmarkConfig, wallclock_base_time: u64) -> u64 {
+    if nexmark_config.seed.is_some() {
+        SEEDED_BASE_TIME
+    } else {
+        wallclock_base_time
+    }
+}
+
+// Creates and spawns the generators with the specified wallclock base time.
+//
+// Generators are seeded according to `nexmark_config.seed`.  Since the
+// collector reads from generators round-robin, the resulting event sequence
+// is fully determined by the seed if it is set, only the wallclock timestamps
+// of the events depend on `wallclock_base_time`.
+fn create_generators(
     nexmark_config: NexmarkConfig,
-    // TODO: I originally planned for this function to be generic for Rng, so I could test
-    // it with a StepRng, but was unable to because although `R::default()` can be used to
-    // instantiate a ThreadRng, `Default` is not supported for `StepRng`. Not sure if it's
-    // worth writing a wrapper around `StepRng` with a `Default` implementation (or if there's
-    // a better way).
+    wallclock_base_time: u64,
 ) -> BatchedReceiver<NextEvent> {
-    let wallclock_base_time = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
     let buffer_size = nexmark_config.source_buffer_size;
+    let base_time = event_base_time(&nexmark_config, wallclock_base_time);
     let mut next_event_rxs: Vec<BatchedReceiver<NextEvent>> = (0..nexmark_config
         .num_event_generators)
         .map(|generator_num| {
-            GeneratorConfig::new(
-                nexmark_config.clone(),
-                wallclock_base_time,
-                0,
-                generator_num,
-            )
+            GeneratorConfig::new(nexmark_config.clone(), base_time, 0, generator_num)
         })
         .map(|generator_config| {
             let (mut tx, rx) = batched_channel(buffer_size);
@@ -150,9 +199,14 @@ fn create_generators_for_config<R: Rng + Default>(
                 .name(format!("generator-{}", generator_config.first_event_number))
                 .spawn(move || {
                     let mut generator =
-                        NexmarkGenerator::new(generator_config, R::default(), wallclock_base_time);
-                    while let Ok(Some(event)) = generator.next_event() {
-                        tx.send(event).unwrap();
+                        NexmarkGenerator::from_config(generator_config, wallclock_base_time);
+                    loop {
+                        match generator.next_event() {
+                            Ok(Some(event)) => tx.send(event).unwrap(),
+                            // Errors are ruled out by `check_config` in
+                            // `NexmarkSource::new`.
+                            Ok(None) | Err(_) => break,
+                        }
                     }
                     tx.flush().unwrap();
                 })
@@ -189,23 +243,235 @@ impl<W, C> NexmarkSource<W, C> {
     pub fn from_next_events(next_events_rx: BatchedReceiver<NextEvent>) -> Self {
         NexmarkSource {
             next_events_rx,
-            wallclock_iterator: None,
+            pacer: Pacer::new(
+                Box::new(SystemClock::new()) as Box<dyn Clock + Send>,
+                1.0,
+                CatchUpPolicy::Burst,
+            ),
+            out_of_order_group_size: 1,
+            delivery_rng: SmallRng::seed_from_u64(0),
+            delivery_group: Vec::new(),
+            received_watermark: 0,
+            watermark: 0,
             _t: PhantomData,
         }
     }
 
-    pub fn new(nexmark_config: NexmarkConfig) -> NexmarkSource<isize, OrdZSet<Event, isize>> {
-        NexmarkSource::from_next_events(create_generators_for_config::<ThreadRng>(nexmark_config))
+    /// Creates a source that generates events according to `nexmark_config`
+    /// in background threads.
+    ///
+    /// Fails if the generators cannot generate all of the configured events,
+    /// e.g., because bids are configured but auctions are not.
+    pub fn new(
+        nexmark_config: NexmarkConfig,
+    ) -> Result<NexmarkSource<isize, OrdZSet<Event, isize>>, GeneratorError> {
+        check_config(&nexmark_config)?;
+
+        let pacer = Pacer::new(
+            Box::new(SystemClock::new()) as Box<dyn Clock + Send>,
+            nexmark_config.rate_multiplier,
+            nexmark_config.catch_up_policy,
+        );
+        let out_of_order_group_size = nexmark_config.out_of_order_group_size;
+        let delivery_rng = match nexmark_config.seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_entropy(),
+        };
+        Ok(
+            NexmarkSource::from_next_events(create_generators_for_config(nexmark_config))
+                .with_pacer(pacer)
+                .with_out_of_order_delivery(out_of_order_group_size, delivery_rng),
+        )
     }
 
-    fn wallclock_time(&mut self) -> u64 {
-        match &mut self.wallclock_iterator {
-            Some(i) => i.next().unwrap(),
-            None => SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64,
+    /// Replace the pacer that delays events until their wallclock
+    /// timestamps.
+    pub fn with_pacer(mut self, pacer: Pacer<Box<dyn Clock + Send>>) -> Self {
+        self.pacer = pacer;
+        self
+    }
+
+    /// Deliver events out of order: events are delivered in consecutive
+    /// groups of `group_size` events, with the order of events within each
+    /// group shuffled using `rng`.
+    ///
+    /// Use [`Self::watermark`] to track the progress of event time.
+    pub fn with_out_of_order_delivery(mut self, group_size: usize, rng: SmallRng) -> Self {
+        assert!(group_size > 0, "out-of-order group size must be positive");
+        self.out_of_order_group_size = group_size;
+        self.delivery_rng = rng;
+        self
+    }
+
+    /// Returns the current watermark, a lower bound on the timestamps of all
+    /// events that are yet to be delivered by this source.  The watermark is
+    /// monotone and is updated with every delivered event.
+    pub fn watermark(&self) -> u64 {
+        self.watermark
+    }
+
+    // Receives the next group of events from the generators and shuffles
+    // them.
+    fn receive_delivery_group(&mut self) {
+        let mut events = Vec::with_capacity(self.out_of_order_group_size);
+        while events.len() < self.out_of_order_group_size {
+            match self.next_events_rx.recv() {
+                Ok(next_event) => events.push(next_event),
+                Err(_) => break,
+            }
         }
+        events.shuffle(&mut self.delivery_rng);
+
+        // Events are received in watermark order, so the last event received
+        // has the largest watermark, which bounds the timestamps of all future
+        // events.
+        let mut watermark = events
+            .iter()
+            .map(|next_event| next_event.watermark)
+            .max()
+            .unwrap_or(self.received_watermark);
+        self.received_watermark = watermark;
+
+        self.delivery_group = events
+            .into_iter()
+            .map(|next_event| {
+                let event_watermark = watermark;
+                watermark = watermark.min(next_event.watermark);
+                (next_event, event_watermark)
+            })
+            .collect();
+    }
+}
+
+/// `TypedMapKey` entry used to share the wallclock base time of sources
+/// created by [`NexmarkSource::build`] across the workers of a runtime. The
+/// first worker to build a source stores the current time in the map, so that
+/// the generators of all workers agree on it.
+#[derive(Hash, PartialEq, Eq)]
+struct SourceBaseTime;
+
+impl TypedMapKey<LocalStoreMarker> for SourceBaseTime {
+    type Value = u64;
+}
+
+impl NexmarkSource<isize, OrdZSet<Event, isize>> {
+    /// Add a Nexmark source to `circuit`, returning a stream of events and
+    /// the watermark of the stream.
+    ///
+    /// Unlike [`Self::new`], which generates events in background threads and
+    /// paces them in wallclock time, this source generates events on demand
+    /// inside the circuit: each step consumes the next `input_batch_size`
+    /// events without delay.  In a multi-worker runtime, the events are split
+    /// between workers by event id using [`NexmarkGenerator::for_worker`], and
+    /// every worker generates its share of each batch.  The source always uses
+    /// a single (split) generator, ignoring `num_event_generators`.  All
+    /// workers share the same base time, and the events of each worker are
+    /// generated with a distinct random number generator.  With
+    /// `nexmark_config.seed` set, the generated events only depend on the seed
+    /// and the number of workers.
+    ///
+    /// The watermark stream contains, at each step, a lower bound on the
+    /// timestamps of all events that have not been produced yet across all
+    /// workers.  It is monotone, and becomes `u64::MAX` once all `max_events`
+    /// events have been produced, which signals that the source is exhausted.
+    /// The source yields empty batches from then on.
+    ///
+    /// Fails if the generator cannot generate all of the configured events,
+    /// as in [`Self::new`].
+    #[track_caller]
+    pub fn build(
+        circuit: &mut RootCircuit,
+        nexmark_config: NexmarkConfig,
+    ) -> Result<
+        (
+            Stream<RootCircuit, OrdZSet<Event, isize>>,
+            Stream<RootCircuit, u64>,
+        ),
+        GeneratorError,
+    > {
+        check_config(&nexmark_config)?;
+
+        let runtime = Runtime::runtime();
+        let (worker_index, num_workers) = match &runtime {
+            Some(runtime) => (Runtime::worker_index(), runtime.num_workers()),
+            None => (0, 1),
+        };
+        let events_per_step = (nexmark_config.input_batch_size / num_workers
+            + (worker_index < nexmark_config.input_batch_size % num_workers) as usize)
+            .max(1);
+
+        let wallclock_base_time = match &runtime {
+            Some(runtime) => *runtime
+                .local_store()
+                .entry(SourceBaseTime)
+                .or_insert_with(wallclock_now)
+                .value(),
+            None => wallclock_now(),
+        };
+        let base_time = event_base_time(&nexmark_config, wallclock_base_time);
+        let generator_config = GeneratorConfig::new(
+            NexmarkConfig {
+                num_event_generators: 1,
+                ..nexmark_config
+            },
+            base_time,
+            0,
+            0,
+        );
+        let mut generator = NexmarkGenerator::from_config(generator_config, wallclock_base_time)
+            .for_worker(worker_index, num_workers);
+
+        let local_watermark = Rc::new(Cell::new(if generator.has_next() { 0 } else { u64::MAX }));
+        let events = circuit.add_source(Generator::new({
+            let local_watermark = local_watermark.clone();
+            move || {
+                let mut events = Vec::with_capacity(events_per_step);
+                while events.len() < events_per_step {
+                    match generator.next_event() {
+                        Ok(Some(next_event)) => {
+                            local_watermark.set(next_event.watermark);
+                            events.push((next_event.event, 1));
+                        }
+                        // Errors are ruled out by `check_config` above.
+                        Ok(None) | Err(_) => break,
+                    }
+                }
+                if !generator.has_next() {
+                    local_watermark.set(u64::MAX);
+                }
+
+                OrdZSet::from_keys((), events)
+            }
+        }));
+
+        // Evaluated after the source in every step, since it consumes its
+        // output.
+        let local_watermark = events.apply(move |_| local_watermark.get());
+
+        let watermark = match runtime {
+            Some(runtime) if num_workers > 1 => {
+                let (sender, receiver) = new_exchange_operators(
+                    &runtime,
+                    worker_index,
+                    Some(Location::caller()),
+                    move |watermark: u64, watermarks: &mut Vec<u64>| {
+                        for _ in 0..num_workers {
+                            watermarks.push(watermark);
+                        }
+                    },
+                    |result: &mut Option<u64>, watermark| {
+                        *result = Some(result.map_or(watermark, |result| result.min(watermark)));
+                    },
+                );
+
+                circuit
+                    .add_exchange(sender, receiver, &local_watermark)
+                    .apply(|watermark: &Option<u64>| watermark.unwrap_or_default())
+            }
+            _ => local_watermark,
+        };
+
+        Ok((events, watermark))
     }
 }
 
@@ -217,14 +483,15 @@ where
     type Item = Event;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next_event = self.next_events_rx.recv().ok()?;
+        if self.delivery_group.is_empty() {
+            self.receive_delivery_group();
+        }
+        let (next_event, watermark) = self.delivery_group.pop()?;
+        self.watermark = watermark;
+
         // If the next event is still in the future then we're getting ahead of
         // ourselves, so we sleep until we can emit it.
-        let wallclock_time_now = self.wallclock_time();
-        if next_event.wallclock_timestamp > wallclock_time_now {
-            let millis_to_sleep = next_event.wallclock_timestamp - wallclock_time_now;
-            sleep(Duration::from_millis(millis_to_sleep));
-        }
+        self.pacer.pace(next_event.wallclock_timestamp);
 
         Some(next_event.event)
     }
@@ -236,16 +503,18 @@ pub mod tests {
         config::Config as GeneratorConfig, tests::generate_expected_next_events,
     };
     use self::model::Event;
+    use self::pacing::tests::MockClock;
     use core::iter::zip;
 
     use super::*;
+    use crate::queries::q0;
     use core::ops::Range;
     use dbsp::{trace::Batch, OrdZSet, RootCircuit};
     use rand::rngs::mock::StepRng;
     use rstest::rstest;
 
-    /// Returns a source that generates the default events/s with the specified
-    /// range of wallclock time ticks.
+    /// Returns a source that generates the default events/s starting at
+    /// `times.start`, paced by a mock clock.
     pub fn make_source_with_wallclock_times(
         times: Range<u64>,
         max_events: u64,
@@ -270,9 +539,11 @@ pub mod tests {
         next_event_tx.send(v).unwrap();
 
         // Create a source using the pre-generated next events.
-        let mut source = NexmarkSource::from_next_events(BatchedReceiver::new(next_event_rx));
-        source.wallclock_iterator = Some(times);
-        source
+        NexmarkSource::from_next_events(BatchedReceiver::new(next_event_rx)).with_pacer(Pacer::new(
+            Box::new(MockClock::default()) as Box<dyn Clock + Send>,
+            1.0,
+            CatchUpPolicy::Burst,
+        ))
     }
 
     pub fn generate_expected_zset_tuples(
@@ -327,7 +598,7 @@ pub mod tests {
             max_events: 10,
             ..NexmarkConfig::default()
         };
-        let receiver = create_generators_for_config::<ThreadRng>(nexmark_config);
+        let receiver = create_generators_for_config(nexmark_config);
         let source = NexmarkSource::<isize, OrdZSet<Event, isize>>::from_next_events(receiver);
 
         let expected_zset_tuple = generate_expected_zset_tuples(0, 10);
@@ -355,6 +626,192 @@ pub mod tests {
         }
     }
 
+    // Reads all events generated by multiple seeded generators.
+    fn seeded_source_events(seed: u64, max_events: u64, wallclock_base_time: u64) -> Vec<u8> {
+        let nexmark_config = NexmarkConfig {
+            num_event_generators: 3,
+            max_events,
+            seed: Some(seed),
+            ..NexmarkConfig::default()
+        };
+        let mut receiver = create_generators(nexmark_config, wallclock_base_time);
+
+        let mut events = Vec::new();
+        while let Ok(next_event) = receiver.recv() {
+            events.push(next_event);
+        }
+        assert_eq!(events.len(), max_events as usize);
+
+        bincode::encode_to_vec(
+            events.into_iter().map(|e| e.event).collect::<Vec<_>>(),
+            bincode::config::standard(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_seeded_source_is_deterministic() {
+        assert_eq!(
+            seeded_source_events(1, 3000, 0),
+            seeded_source_events(1, 3000, 1_000_000)
+        );
+    }
+
+    #[test]
+    fn test_seeded_sources_diverge() {
+        assert_ne!(
+            seeded_source_events(1, 3000, 0),
+            seeded_source_events(2, 3000, 0)
+        );
+    }
+
+    // Pre-generates events at 1000 events/s using a single generator.
+    fn generate_next_events(
+        num_events: u64,
+        out_of_order_group_size: usize,
+    ) -> VecDeque<NextEvent> {
+        let mut generator = NexmarkGenerator::new(
+            GeneratorConfig::new(
+                NexmarkConfig {
+                    num_event_generators: 1,
+                    first_event_rate: 1000,
+                    max_events: num_events,
+                    out_of_order_group_size,
+                    ..NexmarkConfig::default()
+                },
+                1_000_000_000,
+                0,
+                0,
+            ),
+            StepRng::new(0, 1),
+            0,
+        );
+
+        let mut next_events = VecDeque::new();
+        while let Some(next_event) = generator.next_event().unwrap() {
+            next_events.push_back(next_event);
+        }
+        next_events
+    }
+
+    // Returns a source delivering `next_events` in groups of `group_size`
+    // events shuffled using `seed`.
+    fn make_out_of_order_source(
+        next_events: VecDeque<NextEvent>,
+        group_size: usize,
+        seed: u64,
+    ) -> NexmarkSource<isize, OrdZSet<Event, isize>> {
+        let (next_event_tx, next_event_rx) = mpsc::sync_channel(1);
+        next_event_tx.send(next_events).unwrap();
+
+        NexmarkSource::from_next_events(BatchedReceiver::new(next_event_rx))
+            .with_pacer(Pacer::new(
+                Box::new(MockClock::default()) as Box<dyn Clock + Send>,
+                1.0,
+                CatchUpPolicy::Burst,
+            ))
+            .with_out_of_order_delivery(group_size, SmallRng::seed_from_u64(seed))
+    }
+
+    fn event_time(event: &Event) -> u64 {
+        match event {
+            Event::Person(p) => p.date_time,
+            Event::Auction(a) => a.date_time,
+            Event::Bid(b) => b.date_time,
+        }
+    }
+
+    #[test]
+    fn test_out_of_order_delivery() {
+        let next_events = generate_next_events(3000, 10);
+        let in_order: Vec<Event> = make_out_of_order_source(next_events.clone(), 1, 0).collect();
+        let out_of_order: Vec<Event> =
+            make_out_of_order_source(next_events.clone(), 10, 0).collect();
+        let out_of_order2: Vec<Event> = make_out_of_order_source(next_events, 10, 0).collect();
+
+        // Delivery is shuffled deterministically within groups.
+        assert_ne!(in_order, out_of_order);
+        assert_eq!(out_of_order, out_of_order2);
+        for (in_order, out_of_order) in zip(in_order.chunks(10), out_of_order.chunks(10)) {
+            let mut out_of_order = out_of_order.to_vec();
+            out_of_order.sort();
+            let mut in_order = in_order.to_vec();
+            in_order.sort();
+            assert_eq!(in_order, out_of_order);
+        }
+    }
+
+    // The watermark is monotone and bounds the timestamps of all events
+    // delivered after it.
+    #[rstest]
+    #[case::in_order(1)]
+    #[case::group_of_7(7)]
+    #[case::group_of_100(100)]
+    fn test_out_of_order_delivery_watermark(#[case] group_size: usize) {
+        let mut source = make_out_of_order_source(generate_next_events(3000, 10), group_size, 1);
+
+        let mut watermarks = Vec::new();
+        let mut event_times = Vec::new();
+        while let Some(event) = source.next() {
+            event_times.push(event_time(&event));
+            watermarks.push(source.watermark());
+        }
+
+        assert_eq!(event_times.len(), 3000);
+        assert!(watermarks.windows(2).all(|w| w[0] <= w[1]));
+        for (i, watermark) in watermarks.iter().enumerate() {
+            assert!(event_times[i + 1..].iter().all(|t| t >= watermark));
+        }
+    }
+
+    // Window- and aggregate-based queries compute the same results regardless
+    // of the delivery order of events.
+    #[test]
+    fn test_queries_with_out_of_order_delivery() {
+        let (circuit, (mut in_order_handle, mut out_of_order_handle, outputs)) =
+            RootCircuit::build(move |circuit| {
+                let (in_order, in_order_handle) = circuit.add_input_zset::<Event, isize>();
+                let (out_of_order, out_of_order_handle) = circuit.add_input_zset::<Event, isize>();
+
+                let outputs = (
+                    queries::q4(in_order.clone()).integrate().output(),
+                    queries::q4(out_of_order.clone()).integrate().output(),
+                    queries::q5(in_order.clone()).integrate().output(),
+                    queries::q5(out_of_order.clone()).integrate().output(),
+                    queries::q7(in_order.clone()).integrate().output(),
+                    queries::q7(out_of_order.clone()).integrate().output(),
+                    queries::q8(in_order).integrate().output(),
+                    queries::q8(out_of_order).integrate().output(),
+                );
+
+                (in_order_handle, out_of_order_handle, outputs)
+            })
+            .unwrap();
+
+        let next_events = generate_next_events(5000, 10);
+        let mut in_order = make_out_of_order_source(next_events.clone(), 1, 0);
+        let mut out_of_order = make_out_of_order_source(next_events, 50, 0);
+
+        loop {
+            let mut in_order_batch: Vec<_> = (&mut in_order).take(100).map(|e| (e, 1)).collect();
+            let mut out_of_order_batch: Vec<_> =
+                (&mut out_of_order).take(100).map(|e| (e, 1)).collect();
+            assert_eq!(in_order_batch.len(), out_of_order_batch.len());
+            if in_order_batch.is_empty() {
+                break;
+            }
+
+            in_order_handle.append(&mut in_order_batch);
+            out_of_order_handle.append(&mut out_of_order_batch);
+            circuit.step().unwrap();
+        }
+
+        assert_eq!(outputs.0.consolidate(), outputs.1.consolidate());
+        assert_eq!(outputs.2.consolidate(), outputs.3.consolidate());
+        assert_eq!(outputs.4.consolidate(), outputs.5.consolidate());
+        assert_eq!(outputs.6.consolidate(), outputs.7.consolidate());
+    }
+
     #[rstest]
     #[case::two_batches_of_4(vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]])]
     #[case::four_batches_of_2(vec![vec![0, 1], vec![2, 3], vec![4, 5], vec![6, 7]])]
@@ -391,4 +848,90 @@ pub mod tests {
             .into_iter()
             .for_each(|v| assert_eq!(VecDeque::from(v), rx.recv().unwrap()));
     }
+
+    // Runs a passthrough query over the source built into the circuit,
+    // returning the number of events produced and the watermark after each
+    // step.
+    fn run_built_source(nexmark_config: NexmarkConfig, num_workers: usize) -> (isize, Vec<u64>) {
+        let (mut dbsp, (events_handle, watermark_handle)) =
+            Runtime::init_circuit(num_workers, move |circuit| {
+                let (events, watermark) = NexmarkSource::build(circuit, nexmark_config).unwrap();
+                (q0(events).output(), watermark.output())
+            })
+            .unwrap();
+
+        let mut num_events = 0;
+        let mut watermarks = Vec::new();
+        while watermarks.last() != Some(&u64::MAX) {
+            assert!(watermarks.len() < 100, "source did not terminate");
+            dbsp.step().unwrap();
+
+            num_events += events_handle.consolidate().weighted_count();
+            watermarks.push(watermark_handle.take_from_worker(0).unwrap());
+        }
+
+        // Once exhausted, the source produces no more events.
+        dbsp.step().unwrap();
+        assert_eq!(events_handle.consolidate().weighted_count(), 0);
+        assert_eq!(watermark_handle.take_from_worker(0), Some(u64::MAX));
+
+        dbsp.kill().unwrap();
+        (num_events, watermarks)
+    }
+
+    #[rstest]
+    #[case::single_worker(1, 1)]
+    #[case::multiple_workers(4, 1)]
+    #[case::out_of_order(4, 10)]
+    fn test_built_source(#[case] num_workers: usize, #[case] out_of_order_group_size: usize) {
+        let nexmark_config = NexmarkConfig {
+            max_events: 10_000,
+            input_batch_size: 1_000,
+            out_of_order_group_size,
+            seed: Some(1),
+            ..NexmarkConfig::default()
+        };
+
+        let (num_events, watermarks) = run_built_source(nexmark_config, num_workers);
+
+        assert_eq!(num_events, 10_000);
+        assert_eq!(watermarks.len(), 10);
+        assert!(watermarks.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    // Returns all events produced by a built source.
+    fn built_source_events(
+        nexmark_config: NexmarkConfig,
+        num_workers: usize,
+    ) -> OrdZSet<Event, isize> {
+        let (mut dbsp, (events_handle, watermark_handle)) =
+            Runtime::init_circuit(num_workers, move |circuit| {
+                let (events, watermark) = NexmarkSource::build(circuit, nexmark_config).unwrap();
+                (events.output(), watermark.output())
+            })
+            .unwrap();
+
+        let mut events = OrdZSet::empty(());
+        while watermark_handle.take_from_worker(0) != Some(u64::MAX) {
+            dbsp.step().unwrap();
+            events = events.merge(&events_handle.consolidate());
+        }
+
+        dbsp.kill().unwrap();
+        events
+    }
+
+    #[test]
+    fn test_seeded_built_source_is_deterministic() {
+        let nexmark_config = NexmarkConfig {
+            max_events: 2_000,
+            input_batch_size: 500,
+            seed: Some(1),
+            ..NexmarkConfig::default()
+        };
+
+        let events = built_source_events(nexmark_config.clone(), 4);
+        assert_eq!(events.weighted_count(), 2_000);
+        assert_eq!(events, built_source_events(nexmark_config, 4));
+    }
 }
@@ -4,13 +4,27 @@
 
 use arcstr::ArcStr;
 use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
 use size_of::SizeOf;
 
 /// The Nexmark Person model based on the [Nexmark Java Person class](https://github.com/nexmark/nexmark/blob/v0.2.0/nexmark-flink/src/main/java/com/github/nexmark/flink/model/Person.java).
 ///
 /// Note that Rust can simply derive the equivalent methods on the Java
 /// class.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, SizeOf, Encode, Decode)]
+#[derive(
+    Clone,
+    Debug,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    SizeOf,
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+)]
 pub struct Person {
     pub id: u64,
     pub name: ArcStr,
@@ -26,7 +40,21 @@ pub struct Person {
 ///
 /// Note that Rust can simply derive the equivalent methods on the Java
 /// class.
-#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, SizeOf, Encode, Decode)]
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    SizeOf,
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+)]
 pub struct Auction {
     pub id: u64,
     pub item_name: ArcStr,
@@ -44,7 +72,21 @@ pub struct Auction {
 ///
 /// Note that Rust can simply derive the equivalent methods on the Java
 /// class.
-#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, SizeOf, Encode, Decode)]
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    SizeOf,
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+)]
 pub struct Bid {
     /// Id of auction this bid is for.
     pub auction: u64,
@@ -52,6 +94,8 @@ pub struct Bid {
     pub bidder: u64,
     /// Price of bid, in cents.
     pub price: usize,
+    /// Currency of the price, or `None` for dollars.
+    pub currency: Option<ArcStr>,
     /// The channel that introduced this bidding.
     pub channel: ArcStr,
     /// The url of this channel.
@@ -65,9 +109,180 @@ pub struct Bid {
 
 /// An event in the auction system, either a (new) `Person`, a (new) `Auction`,
 /// or a `Bid`.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, SizeOf, Encode, Decode)]
+#[derive(
+    Clone,
+    Debug,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    SizeOf,
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+)]
 pub enum Event {
     Person(Person),
     Auction(Auction),
     Bid(Bid),
 }
+
+/// Parameters of the [`Arbitrary`](proptest::arbitrary::Arbitrary)
+/// implementations for the Nexmark model.
+#[cfg(feature = "proptest")]
+#[derive(Clone, Debug)]
+pub struct ModelParams {
+    /// Range of person and auction ids, which are also used by bids to
+    /// refer to auctions and bidders.
+    pub ids: std::ops::Range<u64>,
+    /// Range of event timestamps.
+    pub date_times: std::ops::Range<u64>,
+}
+
+#[cfg(feature = "proptest")]
+impl Default for ModelParams {
+    fn default() -> Self {
+        Self {
+            ids: 0..100,
+            date_times: 0..10_000,
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod arbitrary {
+    use super::{Auction, Bid, Event, ModelParams, Person};
+    use arcstr::ArcStr;
+    use proptest::{option, prelude::*};
+
+    fn string() -> impl Strategy<Value = ArcStr> {
+        "[a-z]{0,8}".prop_map(ArcStr::from)
+    }
+
+    impl Arbitrary for Person {
+        type Parameters = ModelParams;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+            (
+                params.ids,
+                string(),
+                string(),
+                string(),
+                string(),
+                string(),
+                params.date_times,
+                string(),
+            )
+                .prop_map(
+                    |(id, name, email_address, credit_card, city, state, date_time, extra)| {
+                        Person {
+                            id,
+                            name,
+                            email_address,
+                            credit_card,
+                            city,
+                            state,
+                            date_time,
+                            extra,
+                        }
+                    },
+                )
+                .boxed()
+        }
+    }
+
+    impl Arbitrary for Auction {
+        type Parameters = ModelParams;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+            (
+                params.ids.clone(),
+                string(),
+                string(),
+                0..10_000usize,
+                0..10_000usize,
+                params.date_times.clone(),
+                params.date_times,
+                params.ids,
+                0..10usize,
+                string(),
+            )
+                .prop_map(
+                    |(
+                        id,
+                        item_name,
+                        description,
+                        initial_bid,
+                        reserve,
+                        date_time,
+                        expires,
+                        seller,
+                        category,
+                        extra,
+                    )| Auction {
+                        id,
+                        item_name,
+                        description,
+                        initial_bid,
+                        reserve,
+                        date_time,
+                        expires,
+                        seller,
+                        category,
+                        extra,
+                    },
+                )
+                .boxed()
+        }
+    }
+
+    impl Arbitrary for Bid {
+        type Parameters = ModelParams;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+            (
+                params.ids.clone(),
+                params.ids,
+                0..10_000usize,
+                option::of(string()),
+                string(),
+                string(),
+                params.date_times,
+                string(),
+            )
+                .prop_map(
+                    |(auction, bidder, price, currency, channel, url, date_time, extra)| Bid {
+                        auction,
+                        bidder,
+                        price,
+                        currency,
+                        channel,
+                        url,
+                        date_time,
+                        extra,
+                    },
+                )
+                .boxed()
+        }
+    }
+
+    /// Shrinks towards persons, then auctions, then bids.
+    impl Arbitrary for Event {
+        type Parameters = ModelParams;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(params: Self::Parameters) -> Self::Strategy {
+            prop_oneof![
+                any_with::<Person>(params.clone()).prop_map(Event::Person),
+                any_with::<Auction>(params.clone()).prop_map(Event::Auction),
+                any_with::<Bid>(params).prop_map(Event::Bid),
+            ]
+            .boxed()
+        }
+    }
+}
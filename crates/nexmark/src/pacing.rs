@@ -0,0 +1,319 @@
+//! Pacing of Nexmark event emission.
+//!
+//! Each [`NextEvent`] carries the wallclock timestamp at which it should be
+//! emitted.  A [`Pacer`] delays the caller until that time (scaled by a rate
+//! multiplier), and [`PacedIterator`] applies a pacer to an iterator of
+//! events.
+
+use crate::generator::NextEvent;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Source of time for a [`Pacer`].
+///
+/// Abstracts over the system clock so that pacing can be tested without
+/// sleeping.
+pub trait Clock {
+    /// Time elapsed since an arbitrary, fixed origin.
+    fn now(&self) -> Duration;
+
+    /// Block for the specified duration.
+    fn sleep(&mut self, duration: Duration);
+}
+
+impl<K: Clock + ?Sized> Clock for Box<K> {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+/// [`Clock`] backed by the system's monotonic clock.
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// What to do when events are emitted later than scheduled, e.g., because
+/// the consumer cannot keep up with the configured rate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum CatchUpPolicy {
+    /// Emit late events without delay until emission is back on schedule.
+    /// The average rate over the whole run matches the configured rate.
+    #[default]
+    Burst,
+
+    /// Give up on the original schedule and pace subsequent events
+    /// relative to the first late event, so that the configured rate is
+    /// never exceeded.
+    Reset,
+}
+
+/// Delays event emission until the wallclock timestamps of events.
+///
+/// The first paced event is emitted immediately and fixes the schedule:
+/// an event with wallclock timestamp `ts` is due `(ts - first_ts) /
+/// rate_multiplier` milliseconds after the first event.
+///
+/// To avoid a syscall per event at high rates, the pacer only sleeps once
+/// emission gets ahead of schedule by at least `slack`, i.e., events may be
+/// emitted up to `slack` early.  Likewise, emission is only considered late
+/// by [`CatchUpPolicy::Reset`] when it falls behind by more than `slack`.
+pub struct Pacer<K> {
+    clock: K,
+    rate_multiplier: f64,
+    catch_up_policy: CatchUpPolicy,
+    slack: Duration,
+
+    /// Wallclock timestamp of the event that started the current schedule
+    /// and the time at which that event was emitted.
+    origin: Option<(u64, Duration)>,
+}
+
+impl<K: Clock> Pacer<K> {
+    /// Default value of `slack`.
+    pub const DEFAULT_SLACK: Duration = Duration::from_millis(1);
+
+    /// Create a pacer that speeds up (`rate_multiplier > 1`) or slows down
+    /// (`rate_multiplier < 1`) the schedule of events by `rate_multiplier`.
+    pub fn new(clock: K, rate_multiplier: f64, catch_up_policy: CatchUpPolicy) -> Self {
+        assert!(
+            rate_multiplier.is_finite() && rate_multiplier > 0.0,
+            "rate multiplier must be a positive number, got {rate_multiplier}"
+        );
+
+        Self {
+            clock,
+            rate_multiplier,
+            catch_up_policy,
+            slack: Self::DEFAULT_SLACK,
+            origin: None,
+        }
+    }
+
+    /// Set the maximum amount of time by which events may be emitted early
+    /// or late without the pacer intervening.
+    pub fn with_slack(mut self, slack: Duration) -> Self {
+        self.slack = slack;
+        self
+    }
+
+    pub fn clock(&self) -> &K {
+        &self.clock
+    }
+
+    /// Wait until an event with the specified wallclock timestamp (in
+    /// milliseconds) is due.
+    pub fn pace(&mut self, wallclock_timestamp: u64) {
+        let now = self.clock.now();
+        let (origin_timestamp, origin_time) =
+            *self.origin.get_or_insert((wallclock_timestamp, now));
+
+        // Events may arrive slightly out of wallclock order, such events are
+        // simply due immediately.
+        let offset = Duration::from_millis(wallclock_timestamp.saturating_sub(origin_timestamp))
+            .div_f64(self.rate_multiplier);
+        let due = origin_time + offset;
+
+        if due >= now {
+            let ahead = due - now;
+            if ahead >= self.slack {
+                self.clock.sleep(ahead);
+            }
+        } else if now - due > self.slack && self.catch_up_policy == CatchUpPolicy::Reset {
+            self.origin = Some((wallclock_timestamp, now));
+        }
+    }
+}
+
+/// Iterator adapter that paces the events of the underlying iterator using
+/// a [`Pacer`].
+pub struct PacedIterator<I, K> {
+    events: I,
+    pacer: Pacer<K>,
+}
+
+impl<I, K> PacedIterator<I, K> {
+    pub fn new(events: I, pacer: Pacer<K>) -> Self {
+        Self { events, pacer }
+    }
+
+    pub fn pacer(&self) -> &Pacer<K> {
+        &self.pacer
+    }
+}
+
+impl<I, K> Iterator for PacedIterator<I, K>
+where
+    I: Iterator<Item = NextEvent>,
+    K: Clock,
+{
+    type Item = NextEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next_event = self.events.next()?;
+        self.pacer.pace(next_event.wallclock_timestamp);
+        Some(next_event)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::generator::tests::make_next_event;
+    use rstest::rstest;
+
+    /// A [`Clock`] that only advances when sleeping or when advanced
+    /// explicitly.
+    #[derive(Debug, Default)]
+    pub struct MockClock {
+        pub now: Duration,
+        pub num_sleeps: usize,
+    }
+
+    impl MockClock {
+        pub fn advance(&mut self, duration: Duration) {
+            self.now += duration;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Duration {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.now += duration;
+            self.num_sleeps += 1;
+        }
+    }
+
+    fn events_at(wallclock_timestamps: &[u64]) -> Vec<NextEvent> {
+        wallclock_timestamps
+            .iter()
+            .map(|&wallclock_timestamp| NextEvent {
+                wallclock_timestamp,
+                ..make_next_event()
+            })
+            .collect()
+    }
+
+    // Returns the (mock) times in milliseconds at which events are emitted.
+    fn emission_times(
+        wallclock_timestamps: &[u64],
+        rate_multiplier: f64,
+        slack: Duration,
+    ) -> Vec<u128> {
+        let pacer = Pacer::new(MockClock::default(), rate_multiplier, CatchUpPolicy::Burst)
+            .with_slack(slack);
+        let mut events = PacedIterator::new(events_at(wallclock_timestamps).into_iter(), pacer);
+
+        let mut times = Vec::new();
+        while events.next().is_some() {
+            times.push(events.pacer().clock().now().as_millis());
+        }
+        times
+    }
+
+    #[rstest]
+    #[case::realtime(1.0, vec![0, 0, 5, 10, 30])]
+    #[case::double_speed(2.0, vec![0, 0, 2, 5, 15])]
+    #[case::half_speed(0.5, vec![0, 0, 10, 20, 60])]
+    fn test_rate_multiplier(#[case] rate_multiplier: f64, #[case] expected_times: Vec<u128>) {
+        assert_eq!(
+            emission_times(
+                &[1000, 1000, 1005, 1010, 1030],
+                rate_multiplier,
+                Duration::ZERO
+            ),
+            expected_times
+        );
+    }
+
+    #[test]
+    fn test_out_of_order_timestamps_are_due_immediately() {
+        assert_eq!(
+            emission_times(&[100, 110, 105, 120, 90], 1.0, Duration::ZERO),
+            vec![0, 10, 10, 20, 20]
+        );
+    }
+
+    // Events that are due within the slack are emitted early, so a single
+    // sleep covers multiple events.
+    #[test]
+    fn test_slack_batches_sleeps() {
+        let wallclock_timestamps: Vec<u64> = (0..100).collect();
+        let pacer = Pacer::new(MockClock::default(), 1.0, CatchUpPolicy::Burst)
+            .with_slack(Duration::from_millis(10));
+        let mut events = PacedIterator::new(events_at(&wallclock_timestamps).into_iter(), pacer);
+
+        for wallclock_timestamp in wallclock_timestamps {
+            events.next().unwrap();
+            let now = events.pacer().clock().now();
+            assert!(now + Duration::from_millis(10) > Duration::from_millis(wallclock_timestamp));
+            assert!(now <= Duration::from_millis(wallclock_timestamp));
+        }
+        assert!(events.next().is_none());
+        assert_eq!(events.pacer().clock().num_sleeps, 9);
+    }
+
+    // The consumer stalls for 50ms after the second event, then keeps up
+    // again.
+    #[rstest]
+    #[case::burst(CatchUpPolicy::Burst, vec![0, 10, 60, 60, 60, 60, 60, 70])]
+    #[case::reset(CatchUpPolicy::Reset, vec![0, 10, 60, 70, 80, 90, 100, 110])]
+    fn test_catch_up_policy(
+        #[case] catch_up_policy: CatchUpPolicy,
+        #[case] expected_times: Vec<u128>,
+    ) {
+        let mut pacer = Pacer::new(MockClock::default(), 1.0, catch_up_policy)
+            .with_slack(Duration::from_millis(1));
+
+        let mut times = Vec::new();
+        for wallclock_timestamp in [0, 10, 20, 30, 40, 50, 60, 70] {
+            pacer.pace(wallclock_timestamp);
+            times.push(pacer.clock.now().as_millis());
+            if wallclock_timestamp == 10 {
+                pacer.clock.advance(Duration::from_millis(50));
+            }
+        }
+
+        assert_eq!(times, expected_times);
+    }
+
+    #[test]
+    #[should_panic(expected = "rate multiplier must be a positive number")]
+    fn test_invalid_rate_multiplier() {
+        Pacer::new(MockClock::default(), 0.0, CatchUpPolicy::Burst);
+    }
+}
@@ -20,7 +20,16 @@ macro_rules! declare_queries {
 
         paste::paste! {
             /// All available nexmark queries
-            #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+            #[derive(
+                Debug,
+                Clone,
+                Copy,
+                PartialEq,
+                Eq,
+                clap::ValueEnum,
+                serde::Serialize,
+                serde::Deserialize,
+            )]
             pub enum Query {
                 $([<$query:upper>],)*
             }
@@ -60,3 +69,98 @@ fn process_time() -> u64 {
         .unwrap()
         .as_millis() as u64
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::NexmarkStream;
+    use crate::{
+        generator::tests::generate_expected_next_events,
+        model::{Auction, Bid, Event},
+    };
+    use dbsp::{operator::OutputHandle, trace::Batch, RootCircuit};
+    use std::collections::HashMap;
+
+    /// Lifetime of auctions returned by `generate_timed_events`.
+    pub const AUCTION_LIFETIME_MS: u64 = 15_000;
+
+    /// Returns `num_events` events produced by the test generator (see
+    /// `generate_expected_next_events`), re-timed so that consecutive events
+    /// are 10ms apart starting at `base_time`.
+    ///
+    /// The test generator produces events at 10M events/s, so all its events
+    /// share the same timestamp and auctions expire 1ms after they are
+    /// created.  Spreading events out makes them span multiple windows in
+    /// windowed queries, and auctions are given a lifetime of
+    /// `AUCTION_LIFETIME_MS` so that bids can win them.
+    pub fn generate_timed_events(base_time: u64, num_events: usize) -> Vec<Event> {
+        generate_expected_next_events(0, num_events)
+            .into_iter()
+            .enumerate()
+            .map(|(index, next_event)| {
+                let date_time = base_time + index as u64 * 10;
+                match next_event.unwrap().event {
+                    Event::Person(mut p) => {
+                        p.date_time = date_time;
+                        Event::Person(p)
+                    }
+                    Event::Auction(mut a) => {
+                        a.expires = date_time + AUCTION_LIFETIME_MS;
+                        a.date_time = date_time;
+                        Event::Auction(a)
+                    }
+                    Event::Bid(mut b) => {
+                        b.date_time = date_time;
+                        Event::Bid(b)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Returns all bids in `events` placed on an auction in `events` while the
+    /// auction was open, paired with the auction.
+    pub fn valid_bids(events: &[Event]) -> Vec<(&Auction, &Bid)> {
+        let auctions: HashMap<u64, &Auction> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Auction(a) => Some((a.id, a)),
+                _ => None,
+            })
+            .collect();
+
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Bid(b) => auctions
+                    .get(&b.auction)
+                    .filter(|a| b.date_time >= a.date_time && b.date_time <= a.expires)
+                    .map(|a| (*a, b)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Feeds `events` to the circuit built by `query` in batches of
+    /// `batch_size` events and returns the final contents of the output
+    /// handle returned by `query`.
+    pub fn run_query<O, F>(events: &[Event], batch_size: usize, query: F) -> O
+    where
+        O: Batch<Time = ()> + Send,
+        F: FnOnce(NexmarkStream) -> OutputHandle<O>,
+    {
+        let (circuit, (mut input_handle, output_handle)) = RootCircuit::build(move |circuit| {
+            let (stream, input_handle) = circuit.add_input_zset::<Event, isize>();
+            (input_handle, query(stream))
+        })
+        .unwrap();
+
+        let mut output = None;
+        for batch in events.chunks(batch_size) {
+            input_handle.append(&mut batch.iter().map(|event| (event.clone(), 1)).collect());
+            circuit.step().unwrap();
+            output = Some(output_handle.consolidate());
+        }
+
+        output.unwrap()
+    }
+}
@@ -88,8 +88,10 @@ mod tests {
     use crate::{
         generator::tests::{make_auction, make_bid},
         model::{Auction, Bid, Event},
+        queries::tests::{generate_timed_events, run_query, valid_bids},
     };
     use dbsp::{trace::Batch, RootCircuit, OrdZSet};
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_q4_average_final_bids_per_category() {
@@ -235,4 +237,39 @@ mod tests {
             circuit.step().unwrap();
         }
     }
+
+    #[test]
+    fn test_q4_generated_events() {
+        let events = generate_timed_events(1_000_000, 2000);
+
+        // Reference implementation: the winning bid of each auction is its
+        // highest valid bid.
+        let mut winning_bids: BTreeMap<(u64, usize), usize> = BTreeMap::new();
+        for (auction, bid) in valid_bids(&events) {
+            let winning_bid = winning_bids
+                .entry((auction.id, auction.category))
+                .or_insert(bid.price);
+            *winning_bid = (*winning_bid).max(bid.price);
+        }
+        assert!(!winning_bids.is_empty());
+
+        let mut totals_by_category: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+        for ((_auction, category), price) in winning_bids {
+            let (sum, count) = totals_by_category.entry(category).or_default();
+            *sum += price;
+            *count += 1;
+        }
+        let expected = OrdZSet::from_keys(
+            (),
+            totals_by_category
+                .into_iter()
+                .map(|(category, (sum, count))| ((category, sum / count), 1))
+                .collect(),
+        );
+
+        assert_eq!(
+            run_query(&events, 100, |input| q4(input).integrate().output()),
+            expected
+        );
+    }
 }
@@ -123,9 +123,11 @@ mod tests {
     use crate::{
         generator::tests::make_bid,
         model::{Bid, Event},
+        queries::tests::{generate_timed_events, run_query},
     };
-    use dbsp::{zset, RootCircuit};
+    use dbsp::{trace::Batch, zset, RootCircuit};
     use rstest::rstest;
+    use std::collections::BTreeMap;
 
     #[rstest]
     // Auction 2 has a single bid at t=20_000, so window is 6_000-16_000, which
@@ -210,4 +212,44 @@ mod tests {
             circuit.step().unwrap();
         }
     }
+
+    #[test]
+    fn test_q5_generated_events() {
+        let events = generate_timed_events(1_000_000, 2000);
+        let bids: Vec<(u64, u64)> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Bid(b) => Some((b.date_time, b.auction)),
+                _ => None,
+            })
+            .collect();
+
+        // Reference implementation: count bids per auction in the window
+        // preceding the final watermark.
+        let watermark = bids.iter().map(|(date_time, _)| *date_time).max().unwrap()
+            - WATERMARK_INTERVAL_SECONDS * 1000;
+        let window_end = watermark - watermark % (TUMBLE_SECONDS * 1000);
+        let window_start = window_end.saturating_sub(WINDOW_WIDTH_SECONDS * 1000);
+
+        let mut auction_counts: BTreeMap<u64, usize> = BTreeMap::new();
+        for (date_time, auction) in bids {
+            if (window_start..window_end).contains(&date_time) {
+                *auction_counts.entry(auction).or_default() += 1;
+            }
+        }
+        let max_count = auction_counts.values().copied().max().unwrap();
+        let expected = OrdZSet::from_keys(
+            (),
+            auction_counts
+                .into_iter()
+                .filter(|(_auction, count)| *count == max_count)
+                .map(|(auction, count)| ((auction, count), 1))
+                .collect(),
+        );
+
+        assert_eq!(
+            run_query(&events, 100, |input| q5(input).integrate().output()),
+            expected
+        );
+    }
 }
@@ -116,8 +116,10 @@ mod tests {
     use crate::{
         generator::tests::{make_auction, make_bid},
         model::{Auction, Bid, Event},
+        queries::tests::{generate_timed_events, run_query, valid_bids},
     };
-    use dbsp::{indexed_zset, RootCircuit};
+    use dbsp::{indexed_zset, trace::Batch, RootCircuit};
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_q6_single_seller_single_auction() {
@@ -637,4 +639,47 @@ mod tests {
             circuit.step().unwrap();
         }
     }
+
+    #[test]
+    fn test_q6_generated_events() {
+        let events = generate_timed_events(1_000_000, 2000);
+
+        // Reference implementation: the winning bid of each auction is its
+        // highest valid bid.
+        let mut winning_bids: BTreeMap<(u64, u64), usize> = BTreeMap::new();
+        for (auction, bid) in valid_bids(&events) {
+            let winning_bid = winning_bids
+                .entry((auction.seller, auction.id))
+                .or_insert(bid.price);
+            *winning_bid = (*winning_bid).max(bid.price);
+        }
+        assert!(!winning_bids.is_empty());
+
+        // Average the winning bids of the last `NUM_AUCTIONS_PER_SELLER`
+        // auctions (by id) of each seller.
+        let mut winning_bids_by_seller: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+        for ((seller, _auction), price) in winning_bids {
+            winning_bids_by_seller
+                .entry(seller)
+                .or_default()
+                .push(price);
+        }
+        let expected = OrdIndexedZSet::from_tuples(
+            (),
+            winning_bids_by_seller
+                .into_iter()
+                .map(|(seller, prices)| {
+                    let last_prices =
+                        &prices[prices.len().saturating_sub(NUM_AUCTIONS_PER_SELLER)..];
+                    let average = last_prices.iter().sum::<usize>() / last_prices.len();
+                    ((seller, average), 1)
+                })
+                .collect(),
+        );
+
+        assert_eq!(
+            run_query(&events, 100, |input| q6(input).integrate().output()),
+            expected
+        );
+    }
 }
@@ -99,8 +99,9 @@ mod tests {
     use crate::{
         generator::tests::make_bid,
         model::{Bid, Event},
+        queries::tests::{generate_timed_events, run_query},
     };
-    use dbsp::{zset, RootCircuit};
+    use dbsp::{trace::Batch, zset, RootCircuit};
     use rstest::rstest;
 
     type Q7Tuple = (u64, u64, usize, u64, ArcStr);
@@ -183,4 +184,47 @@ mod tests {
             circuit.step().unwrap();
         }
     }
+
+    #[test]
+    fn test_q7_generated_events() {
+        let events = generate_timed_events(1_000_000, 2000);
+        let bids: Vec<&Bid> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Bid(b) => Some(b),
+                _ => None,
+            })
+            .collect();
+
+        // Reference implementation: find the highest bids in the tumbling
+        // window preceding the final watermark.
+        let watermark =
+            bids.iter().map(|b| b.date_time).max().unwrap() - WATERMARK_INTERVAL_SECONDS * 1000;
+        let window_end = watermark - watermark % (TUMBLE_SECONDS * 1000);
+        let window_start = window_end.saturating_sub(TUMBLE_SECONDS * 1000);
+
+        let windowed_bids: Vec<&Bid> = bids
+            .into_iter()
+            .filter(|b| (window_start..window_end).contains(&b.date_time))
+            .collect();
+        let max_price = windowed_bids.iter().map(|b| b.price).max().unwrap();
+        let expected = OrdZSet::from_keys(
+            (),
+            windowed_bids
+                .into_iter()
+                .filter(|b| b.price == max_price)
+                .map(|b| {
+                    (
+                        (b.auction, b.bidder, b.price, b.date_time, b.extra.clone()),
+                        1,
+                    )
+                })
+                .collect(),
+        );
+
+        assert_eq!(
+            run_query(&events, 100, |input| q7(input).integrate().output()),
+            expected
+        );
+    }
 }
@@ -96,8 +96,9 @@ mod tests {
     use crate::{
         generator::tests::{make_auction, make_person},
         model::{Auction, Event, Person},
+        queries::tests::{generate_timed_events, run_query},
     };
-    use dbsp::{zset, RootCircuit};
+    use dbsp::{trace::Batch, zset, RootCircuit};
     use arcstr::ArcStr;
     use rstest::rstest;
 
@@ -213,4 +214,58 @@ mod tests {
             circuit.step().unwrap();
         }
     }
+
+    #[test]
+    fn test_q8_generated_events() {
+        // 30s of events, so that the window preceding the final watermark is
+        // the first 10s.
+        let events = generate_timed_events(1_000_000, 3000);
+
+        // Reference implementation: join people and auctions created in the
+        // tumbling window preceding the final watermark.
+        let watermark = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Auction(a) => Some(a.date_time),
+                _ => None,
+            })
+            .max()
+            .unwrap()
+            - TUMBLE_SECONDS * 1000;
+        let window_end = watermark - watermark % (TUMBLE_SECONDS * 1000);
+        let window = window_end.saturating_sub(TUMBLE_SECONDS * 1000)..window_end;
+
+        let windowed_sellers: Vec<u64> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Auction(a) if window.contains(&a.date_time) => Some(a.seller),
+                _ => None,
+            })
+            .collect();
+
+        // Each person is output once for every auction they created.
+        let mut expected_tuples = Vec::new();
+        for event in &events {
+            if let Event::Person(p) = event {
+                if window.contains(&p.date_time) {
+                    for _ in windowed_sellers.iter().filter(|seller| **seller == p.id) {
+                        expected_tuples.push((
+                            (
+                                p.id,
+                                p.name.clone(),
+                                p.date_time - p.date_time % (TUMBLE_SECONDS * 1000),
+                            ),
+                            1,
+                        ));
+                    }
+                }
+            }
+        }
+        assert!(!expected_tuples.is_empty());
+
+        assert_eq!(
+            run_query(&events, 100, |input| q8(input).integrate().output()),
+            OrdZSet::from_keys((), expected_tuples)
+        );
+    }
 }
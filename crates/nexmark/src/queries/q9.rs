@@ -271,8 +271,10 @@ mod tests {
     use crate::{
         generator::tests::{make_auction, make_bid},
         model::{Auction, Bid, Event},
+        queries::tests::{generate_timed_events, run_query, valid_bids},
     };
-    use dbsp::zset;
+    use dbsp::{trace::Batch, zset};
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_q9() {
@@ -396,4 +398,55 @@ mod tests {
             circuit.step().unwrap();
         }
     }
+
+    #[test]
+    fn test_q9_generated_events() {
+        let events = generate_timed_events(1_000_000, 2000);
+
+        // Reference implementation: the winning bid of each auction is its
+        // highest valid bid, with ties broken by bidder, time and payload.
+        let bid_key = |b: &Bid| (b.price, b.bidder, b.date_time, b.extra.clone());
+        let mut winning_bids: BTreeMap<u64, (&Auction, &Bid)> = BTreeMap::new();
+        for (auction, bid) in valid_bids(&events) {
+            let winning_bid = winning_bids.entry(auction.id).or_insert((auction, bid));
+            if bid_key(bid) > bid_key(winning_bid.1) {
+                winning_bid.1 = bid;
+            }
+        }
+        assert!(!winning_bids.is_empty());
+
+        let expected = OrdZSet::from_keys(
+            (),
+            winning_bids
+                .into_values()
+                .map(|(a, b)| {
+                    (
+                        Q9Output(
+                            a.id,
+                            a.item_name.clone(),
+                            a.description.clone(),
+                            a.initial_bid,
+                            a.reserve,
+                            a.date_time,
+                            a.expires,
+                            a.seller,
+                            a.category,
+                            a.extra.clone(),
+                            b.auction,
+                            b.bidder,
+                            b.price,
+                            b.date_time,
+                            b.extra.clone(),
+                        ),
+                        1,
+                    )
+                })
+                .collect(),
+        );
+
+        assert_eq!(
+            run_query(&events, 100, |input| q9(input).integrate().output()),
+            expected
+        );
+    }
 }
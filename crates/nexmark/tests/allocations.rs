@@ -0,0 +1,70 @@
+//! Counts the heap allocations made by the Nexmark generator.
+//!
+//! This lives in its own test binary, since it installs a global allocator.
+
+use dbsp_nexmark::{
+    config::Config as NexmarkConfig,
+    generator::{config::Config, NexmarkGenerator},
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// Allocator that counts the allocations made by each thread, so that
+/// tests running in parallel don't interfere with each other.
+struct CountingAllocator;
+
+thread_local! {
+    static NUM_ALLOCATIONS: Cell<usize> = Cell::new(0);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = NUM_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn num_allocations() -> usize {
+    NUM_ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn allocations_per_event() {
+    const NUM_EVENTS: usize = 100_000;
+
+    let mut ng = NexmarkGenerator::from_config(
+        Config {
+            nexmark_config: NexmarkConfig {
+                num_event_generators: 1,
+                seed: Some(1),
+                ..NexmarkConfig::default()
+            },
+            ..Config::default()
+        },
+        0,
+    );
+    let mut events = Vec::with_capacity(NUM_EVENTS);
+
+    let start = num_allocations();
+    for _ in 0..NUM_EVENTS {
+        events.push(ng.next_event().unwrap().unwrap());
+    }
+    let allocations_per_event = (num_allocations() - start) as f64 / NUM_EVENTS as f64;
+
+    // Strings are built in a reusable buffer, so each random string field
+    // takes a single allocation, i.e., one for the `extra` field of
+    // most bids.  Building strings directly takes at least twice as many.
+    assert!(
+        allocations_per_event < 1.5,
+        "{allocations_per_event} allocations per event"
+    );
+}